@@ -0,0 +1,42 @@
+//! Generates the driver's ioctl structs from its uapi header for the ABI
+//! check in `ioctl` (the `abi-check` feature); does nothing otherwise.
+
+fn main() {
+    #[cfg(feature = "abi-check")]
+    abi_check::generate();
+}
+
+#[cfg(feature = "abi-check")]
+mod abi_check {
+    use std::path::PathBuf;
+
+    const HEADER: &str = "../driver/include/uapi/sunpci_ioctl.h";
+
+    /// Write the header's structs to `$OUT_DIR/sunpci_ioctl.rs`, and their
+    /// names to `$OUT_DIR/sunpci_structs.rs` so the check can tell whether
+    /// it covers them all
+    pub fn generate() {
+        println!("cargo:rerun-if-changed={HEADER}");
+        let bindings = bindgen::Builder::default()
+            .header(HEADER)
+            .allowlist_type("sunpci_.*")
+            .layout_tests(false)
+            .generate()
+            .expect("Cannot generate bindings for the uapi header (is libclang installed?)")
+            .to_string();
+
+        let structs: Vec<&str> = bindings
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("pub struct "))
+            .filter_map(|rest| rest.split([' ', '{']).next())
+            .collect();
+
+        let out_dir = PathBuf::from(std::env::var("OUT_DIR").unwrap());
+        std::fs::write(out_dir.join("sunpci_ioctl.rs"), &bindings).unwrap();
+        std::fs::write(
+            out_dir.join("sunpci_structs.rs"),
+            format!("const C_STRUCTS: &[&str] = &{structs:?};\n"),
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,530 @@
+//! Appliances: a profile and its media bundled for sharing.
+//!
+//! An appliance is a directory laid out like an OVF package:
+//!
+//! ```text
+//! Win98 Games/
+//!   profile.toml    the configuration, media paths relative to the bundle
+//!   disks/          hard disk images and overlay children
+//!   templates/      templates the overlay children were made from
+//!   media/          CD-ROM and floppy images (a CUE sheet with its files)
+//!   manifest.mf     a "SHA256(<file>)= <hex>" line for each file above
+//! ```
+//!
+//! Settings that only make sense on the exporting host (window geometry,
+//! recent files, search paths, drive mappings, the network interface and
+//! MAC address) are left out of the profile. Importing copies the bundle's
+//! files, checks each copy against the manifest, puts templates in the
+//! templates directory (reusing one that is already there), points the
+//! overlay children at them and returns the profile with its paths
+//! rewritten. Nothing is left behind if any of that fails.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{Context, bail};
+
+use crate::config::{AppConfig, DiskConfig, RecentFiles};
+use crate::config_storage::parse_config;
+use crate::cuesheet;
+use crate::disk_library;
+use crate::image_ref;
+use crate::overlay;
+use crate::progress::ProgressReporter;
+use crate::sha256;
+
+/// Name of the profile in a bundle
+pub const PROFILE_FILE: &str = "profile.toml";
+/// Name of the manifest in a bundle
+pub const MANIFEST_FILE: &str = "manifest.mf";
+
+/// Default location of imported appliances
+pub fn appliances_dir() -> PathBuf {
+    AppConfig::data_dir().join("appliances")
+}
+
+/// Bundle the profile `config` and its media into the directory `path`,
+/// which must not exist
+pub fn export_appliance(config: &AppConfig, path: &Path, progress: &ProgressReporter) -> anyhow::Result<()> {
+    if path.exists() {
+        bail!("{} already exists", path.display());
+    }
+    fs::create_dir_all(path).with_context(|| format!("failed to create {}", path.display()))?;
+
+    let result = write_bundle(config, path, progress);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(path);
+    }
+    result
+}
+
+fn write_bundle(config: &AppConfig, path: &Path, progress: &ProgressReporter) -> anyhow::Result<()> {
+    let mut profile = shareable(config);
+    let mut bundle = Bundle {
+        root: path,
+        files: BTreeMap::new(),
+        sources: Vec::new(),
+    };
+
+    let storage = &mut profile.storage;
+    for disk in [&mut storage.primary_disk, &mut storage.secondary_disk].into_iter().flatten() {
+        let child = disk.overlay.take().filter(|child| child.is_file());
+        if child.is_some() || overlay::is_template(&disk.path) {
+            disk.path = bundle.add(&disk.path, "templates", progress)?;
+            disk.overlay = child.map(|child| bundle.add(&child, "disks", progress)).transpose()?;
+        } else {
+            disk.path = bundle.add(&disk.path, "disks", progress)?;
+        }
+    }
+
+    // Host CD-ROM drives and empty slots stay as they are
+    if let Some(iso) = storage.cdrom.mounted_iso.as_mut().filter(|iso| iso.is_file()) {
+        *iso = if is_cue_sheet(iso) {
+            bundle.add_cue(iso, progress)?
+        } else {
+            bundle.add(iso, "media", progress)?
+        };
+    }
+    for floppy in [&mut storage.floppy_a, &mut storage.floppy_b] {
+        if let Some(image) = floppy.mounted_image.as_mut().filter(|image| image.is_file()) {
+            *image = bundle.add(image, "media", progress)?;
+        }
+    }
+
+    progress.set_step("Writing profile");
+    progress.check_cancelled()?;
+    let profile_path = path.join(PROFILE_FILE);
+    fs::write(&profile_path, toml::to_string_pretty(&profile)?)?;
+    let digest = sha256::hash_file(&profile_path, progress)?;
+    bundle.files.insert(PROFILE_FILE.to_string(), digest);
+
+    let manifest: String = bundle
+        .files
+        .iter()
+        .map(|(name, digest)| format!("SHA256({})= {}\n", name, digest))
+        .collect();
+    fs::write(path.join(MANIFEST_FILE), manifest)?;
+    Ok(())
+}
+
+/// A copy of `config` without the settings that belong to this host
+fn shareable(config: &AppConfig) -> AppConfig {
+    let mut profile = config.clone();
+    let general = &mut profile.general;
+    general.window_x = None;
+    general.window_y = None;
+    general.window_width = None;
+    general.window_height = None;
+    general.window_screen = None;
+    profile.recent = RecentFiles::default();
+    profile.storage.search_paths.clear();
+    profile.drive_mappings.clear();
+    profile.network.host_interface.clear();
+    profile.network.mac_address.clear();
+    profile.remote.control_port = None;
+    profile.remote.control_address = None;
+    profile.remote.control_token.clear();
+    profile.remote.vnc_port = None;
+    profile.cards.clear();
+    profile
+}
+
+/// `profile` with the host settings an export leaves out taken from `host`
+pub fn with_host_settings(mut profile: AppConfig, host: &AppConfig) -> AppConfig {
+    let general = &mut profile.general;
+    general.window_x = host.general.window_x;
+    general.window_y = host.general.window_y;
+    general.window_width = host.general.window_width;
+    general.window_height = host.general.window_height;
+    general.window_screen = host.general.window_screen.clone();
+    profile.recent = host.recent.clone();
+    profile.storage.search_paths = host.storage.search_paths.clone();
+    profile.drive_mappings = host.drive_mappings.clone();
+    profile.network.host_interface = host.network.host_interface.clone();
+    profile.network.mac_address = host.network.mac_address.clone();
+    profile.remote.control_port = host.remote.control_port;
+    profile.remote.control_address = host.remote.control_address;
+    profile.remote.control_token = host.remote.control_token.clone();
+    profile.remote.vnc_port = host.remote.vnc_port;
+    profile.cards = host.cards.clone();
+    profile
+}
+
+fn is_cue_sheet(path: &Path) -> bool {
+    path.extension().is_some_and(|e| e.eq_ignore_ascii_case("cue"))
+}
+
+/// Files being written into a bundle
+struct Bundle<'a> {
+    root: &'a Path,
+    /// Checksum of each file, by name in the bundle
+    files: BTreeMap<String, String>,
+    /// Where each file came from, so media used twice is stored once
+    sources: Vec<(PathBuf, PathBuf)>,
+}
+
+impl Bundle<'_> {
+    /// Copy `src` into the bundle directory `dir`; returns its bundle path
+    fn add(&mut self, src: &Path, dir: &str, progress: &ProgressReporter) -> anyhow::Result<PathBuf> {
+        let src = fs::canonicalize(src).with_context(|| format!("cannot find {}", src.display()))?;
+        if let Some((_, stored)) = self.sources.iter().find(|(source, _)| *source == src) {
+            return Ok(stored.clone());
+        }
+        let name = src.file_name().with_context(|| format!("{} is not a file", src.display()))?;
+        let stored = self.unique(Path::new(dir), &name.to_string_lossy());
+        self.copy(&src, &stored, progress)?;
+        self.sources.push((src, stored.clone()));
+        Ok(stored)
+    }
+
+    /// Copy the CUE sheet at `src` and its files into a directory of its
+    /// own under media; returns the sheet's bundle path
+    fn add_cue(&mut self, src: &Path, progress: &ProgressReporter) -> anyhow::Result<PathBuf> {
+        let sheet = cuesheet::load(src)?;
+        let src = fs::canonicalize(src)?;
+        let sheet_dir = src.parent().unwrap_or(Path::new("/"));
+        let stem = src.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let dir = self.unique(Path::new("media"), &stem);
+
+        for file in &sheet.files {
+            let file = fs::canonicalize(file).with_context(|| format!("cannot find {}", file.display()))?;
+            let relative = file
+                .strip_prefix(sheet_dir)
+                .with_context(|| format!("{} is outside the CUE sheet's directory", file.display()))?;
+            self.copy(&file, &dir.join(relative), progress)?;
+        }
+        let stored = dir.join(src.file_name().unwrap_or_default());
+        self.copy(&src, &stored, progress)?;
+        Ok(stored)
+    }
+
+    /// `dir/name`, or `dir/name-N` with the stem numbered if that is taken
+    fn unique(&self, dir: &Path, name: &str) -> PathBuf {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name, String::new()),
+        };
+        let mut candidate = dir.join(name);
+        let mut n = 1;
+        while self.root.join(&candidate).exists() {
+            candidate = dir.join(format!("{}-{}{}", stem, n, extension));
+            n += 1;
+        }
+        candidate
+    }
+
+    /// Copy `src` to `stored` in the bundle and checksum the copy
+    fn copy(&mut self, src: &Path, stored: &Path, progress: &ProgressReporter) -> anyhow::Result<()> {
+        let target = self.root.join(stored);
+        progress.set_step(&format!("Copying {}", stored.display()));
+        disk_library::copy_image(src, &target, progress)?;
+
+        progress.set_step(&format!("Checksumming {}", stored.display()));
+        progress.set_progress(0, fs::metadata(&target)?.len());
+        let digest = sha256::hash_file(&target, progress)?;
+        self.files.insert(bundle_name(stored), digest);
+        Ok(())
+    }
+}
+
+/// Name of a file in the manifest: its bundle path with '/' separators
+fn bundle_name(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Read the manifest of the bundle at `path`: checksum by file name
+pub fn read_manifest(path: &Path) -> anyhow::Result<BTreeMap<String, String>> {
+    let manifest_path = path.join(MANIFEST_FILE);
+    let text = fs::read_to_string(&manifest_path)
+        .with_context(|| format!("{} is not an appliance (no {})", path.display(), MANIFEST_FILE))?;
+
+    let mut files = BTreeMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry = line
+            .strip_prefix("SHA256(")
+            .and_then(|rest| rest.split_once(")="))
+            .map(|(name, digest)| (name, digest.trim()));
+        let Some((name, digest)) = entry else {
+            bail!("{} line {}: not a SHA256 entry", MANIFEST_FILE, n + 1);
+        };
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            bail!("{} line {}: bad checksum for {}", MANIFEST_FILE, n + 1, name);
+        }
+        let inside = Path::new(name).components().all(|c| matches!(c, Component::Normal(_)));
+        if name.is_empty() || !inside {
+            bail!("{} line {}: {} is not a file in the bundle", MANIFEST_FILE, n + 1, name);
+        }
+        files.insert(name.to_string(), digest.to_ascii_lowercase());
+    }
+    if !files.contains_key(PROFILE_FILE) {
+        bail!("{} does not list {}", MANIFEST_FILE, PROFILE_FILE);
+    }
+    Ok(files)
+}
+
+/// Import the bundle at `path`: media into the directory `dest`, which
+/// must not exist, and templates into `templates`
+///
+/// Returns the bundle's profile with its paths pointing at the imported
+/// media.
+pub fn import_appliance(
+    path: &Path,
+    dest: &Path,
+    templates: &Path,
+    progress: &ProgressReporter,
+) -> anyhow::Result<AppConfig> {
+    let files = read_manifest(path)?;
+    if dest.exists() {
+        bail!("{} already exists", dest.display());
+    }
+
+    let mut import = Import {
+        bundle: path,
+        created: vec![dest.to_path_buf()],
+        imported: BTreeMap::new(),
+    };
+    let result = import.run(&files, dest, templates, progress);
+    if result.is_err() {
+        for created in import.created.iter().rev() {
+            let _ = fs::remove_dir_all(created).or_else(|_| fs::remove_file(created));
+        }
+    }
+    result
+}
+
+/// Files being copied out of a bundle
+struct Import<'a> {
+    bundle: &'a Path,
+    /// What to remove if the import fails
+    created: Vec<PathBuf>,
+    /// Where each file of the bundle went, by name in the bundle
+    imported: BTreeMap<String, PathBuf>,
+}
+
+impl Import<'_> {
+    fn run(
+        &mut self,
+        files: &BTreeMap<String, String>,
+        dest: &Path,
+        templates: &Path,
+        progress: &ProgressReporter,
+    ) -> anyhow::Result<AppConfig> {
+        progress.set_step("Verifying profile");
+        let profile_path = self.bundle.join(PROFILE_FILE);
+        self.verify(&profile_path, PROFILE_FILE, &files[PROFILE_FILE], progress)?;
+        let mut profile: AppConfig = parse_config(&fs::read_to_string(&profile_path)?)
+            .with_context(|| format!("{} is not a valid profile", PROFILE_FILE))?;
+
+        for (name, digest) in files {
+            if name == PROFILE_FILE {
+                continue;
+            }
+            let src = self.bundle.join(name);
+            if let Some(template) = name.strip_prefix("templates/") {
+                let target = self.import_template(&src, templates, template, digest, progress)?;
+                self.imported.insert(name.clone(), target);
+            } else {
+                let target = dest.join(name);
+                progress.set_step(&format!("Copying {}", name));
+                disk_library::copy_image(&src, &target, progress)?;
+                self.verify(&target, name, digest, progress)?;
+                self.imported.insert(name.clone(), target);
+            }
+        }
+
+        let storage = &mut profile.storage;
+        for disk in [&mut storage.primary_disk, &mut storage.secondary_disk].into_iter().flatten() {
+            self.import_disk(disk)?;
+        }
+        if let Some(iso) = storage.cdrom.mounted_iso.as_mut().filter(|iso| iso.is_relative()) {
+            *iso = self.resolve(iso)?;
+        }
+        for floppy in [&mut storage.floppy_a, &mut storage.floppy_b] {
+            if let Some(image) = floppy.mounted_image.as_mut().filter(|image| image.is_relative()) {
+                *image = self.resolve(image)?;
+            }
+        }
+        Ok(profile)
+    }
+
+    /// Point `disk` and its overlay child at the imported files
+    fn import_disk(&self, disk: &mut DiskConfig) -> anyhow::Result<()> {
+        disk.path = self.resolve(&disk.path)?;
+        if let Some(child) = disk.overlay.as_mut() {
+            *child = self.resolve(child)?;
+            overlay::set_base(child, &disk.path)
+                .with_context(|| format!("cannot attach {} to {}", child.display(), disk.path.display()))?;
+        }
+        disk.content_id = image_ref::content_id(&disk.path).ok();
+        Ok(())
+    }
+
+    /// Where the bundle file the profile calls `path` was imported to
+    fn resolve(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        self.imported
+            .get(&bundle_name(path))
+            .cloned()
+            .with_context(|| format!("{} is not in {}", path.display(), MANIFEST_FILE))
+    }
+
+    /// The template `name` in `templates`, copied from `src` unless an
+    /// identical one is already there
+    fn import_template(
+        &mut self,
+        src: &Path,
+        templates: &Path,
+        name: &str,
+        digest: &str,
+        progress: &ProgressReporter,
+    ) -> anyhow::Result<PathBuf> {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+            _ => (name, String::new()),
+        };
+        let mut target = templates.join(name);
+        let mut n = 1;
+        while target.exists() {
+            progress.set_step(&format!("Checking template {}", target.display()));
+            progress.set_progress(0, fs::metadata(&target)?.len());
+            if sha256::hash_file(&target, progress)? == digest {
+                return Ok(target);
+            }
+            target = templates.join(format!("{}-{}{}", stem, n, extension));
+            n += 1;
+        }
+
+        progress.set_step(&format!("Copying template {}", name));
+        disk_library::copy_image(src, &target, progress)?;
+        self.created.push(target.clone());
+        self.verify(&target, name, digest, progress)?;
+        let mut permissions = fs::metadata(&target)?.permissions();
+        permissions.set_mode(permissions.mode() & 0o444);
+        fs::set_permissions(&target, permissions)?;
+        Ok(target)
+    }
+
+    /// Check the file at `path` against the manifest's checksum for `name`
+    fn verify(&self, path: &Path, name: &str, digest: &str, progress: &ProgressReporter) -> anyhow::Result<()> {
+        progress.set_step(&format!("Verifying {}", name));
+        progress.set_progress(0, fs::metadata(path)?.len());
+        if sha256::hash_file(path, progress)? != digest {
+            bail!("{} does not match its checksum; the appliance is damaged", name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    use crate::config::DriveMapping;
+
+    fn image(path: &Path, fill: u8) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(&vec![fill; 64 * 1024]).unwrap();
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("win98.img");
+        image(&template, 0x98);
+        let child = dir.path().join("win98-1.img");
+        overlay::create_overlay(&template, &child).unwrap();
+        let floppy = dir.path().join("boot.img");
+        image(&floppy, 0xF0);
+
+        let mut config = AppConfig::default();
+        config.storage.primary_disk = Some(DiskConfig {
+            path: template.clone(),
+            bootable: true,
+            content_id: None,
+            overlay: Some(child),
+        });
+        config.storage.floppy_a.mounted_image = Some(floppy);
+        config.drive_mappings.push(DriveMapping::default());
+        config.general.window_x = Some(100);
+
+        let bundle = dir.path().join("bundle");
+        export_appliance(&config, &bundle, &ProgressReporter::new()).unwrap();
+        let files = read_manifest(&bundle).unwrap();
+        let names: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(names, ["disks/win98-1.img", "media/boot.img", "profile.toml", "templates/win98.img"]);
+
+        let templates = dir.path().join("templates");
+        let dest = dir.path().join("imported");
+        let imported = import_appliance(&bundle, &dest, &templates, &ProgressReporter::new()).unwrap();
+        assert!(imported.drive_mappings.is_empty());
+        assert_eq!(imported.general.window_x, None);
+        let adopted = with_host_settings(imported.clone(), &config);
+        assert_eq!(adopted.drive_mappings.len(), 1);
+        assert_eq!(adopted.general.window_x, Some(100));
+
+        let disk = imported.storage.primary_disk.unwrap();
+        assert_eq!(disk.path, templates.join("win98.img"));
+        assert!(fs::metadata(&disk.path).unwrap().permissions().readonly());
+        let child = disk.overlay.unwrap();
+        assert_eq!(child, dest.join("disks/win98-1.img"));
+        assert_eq!(overlay::read_overlay(&child).unwrap().unwrap().base, fs::canonicalize(&disk.path).unwrap());
+        assert_eq!(imported.storage.floppy_a.mounted_image, Some(dest.join("media/boot.img")));
+
+        // The same template is shared by a second import
+        let again = import_appliance(&bundle, &dir.path().join("again"), &templates, &ProgressReporter::new()).unwrap();
+        assert_eq!(again.storage.primary_disk.unwrap().path, templates.join("win98.img"));
+        assert_eq!(disk_library::list_templates(&templates).len(), 1);
+
+        // Never over an existing bundle
+        assert!(export_appliance(&config, &bundle, &ProgressReporter::new()).is_err());
+    }
+
+    #[test]
+    fn test_import_damaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("dos.img");
+        image(&disk, 0x62);
+        let mut config = AppConfig::default();
+        config.storage.primary_disk = Some(DiskConfig {
+            path: disk,
+            bootable: true,
+            content_id: None,
+            overlay: None,
+        });
+
+        let bundle = dir.path().join("bundle");
+        export_appliance(&config, &bundle, &ProgressReporter::new()).unwrap();
+        image(&bundle.join("disks/dos.img"), 0x00);
+
+        let dest = dir.path().join("imported");
+        let templates = dir.path().join("templates");
+        assert!(import_appliance(&bundle, &dest, &templates, &ProgressReporter::new()).is_err());
+        assert!(!dest.exists());
+    }
+
+    #[test]
+    fn test_read_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let digest = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        fs::write(dir.path().join(MANIFEST_FILE), format!("SHA256(profile.toml)= {}\n", digest)).unwrap();
+        assert_eq!(read_manifest(dir.path()).unwrap()["profile.toml"], digest);
+
+        // Files outside the bundle are refused
+        let escape = format!("SHA256(profile.toml)= {}\nSHA256(../etc/passwd)= {}\n", digest, digest);
+        fs::write(dir.path().join(MANIFEST_FILE), escape).unwrap();
+        assert!(read_manifest(dir.path()).is_err());
+
+        fs::write(dir.path().join(MANIFEST_FILE), "SHA256(profile.toml)= abc\n").unwrap();
+        assert!(read_manifest(dir.path()).is_err());
+    }
+}
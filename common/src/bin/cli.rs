@@ -0,0 +1,410 @@
+//! rising-sun-cli: command-line control of a SunPCi session.
+//!
+//! Without `--host` commands go straight to the driver, which only works
+//! while no frontend owns the card. With `--host` they are sent to the
+//! control API of a running frontend (see `rising_sun_common::control`),
+//! locally (`--host localhost`) or across the LAN, where the frontend
+//! wants its control token (`--token`). A host that is not a
+//! resolvable name is looked up among sessions advertised with mDNS.
+//!
+//! ```text
+//! rising-sun-cli --host lab1 mount-iso win98.iso
+//! rising-sun-cli discover
+//! rising-sun-cli soak --duration 8h --iso win98.iso --report soak.json
+//! rising-sun-cli convert dosbox.img c.diskimage
+//! rising-sun-cli guest-tools --source /mnt/SUNWspci2
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+
+use clap::{Arg, ArgMatches, Command, value_parser};
+
+use rising_sun_common::control::{ControlClient, ControlRequest, ControlResponse, command};
+use rising_sun_common::disk_convert::{self, DiskFormat};
+use rising_sun_common::guest_tools;
+use rising_sun_common::ioctl::{DriverVersion, SessionState};
+use rising_sun_common::progress::ProgressReporter;
+use rising_sun_common::soak::{self, DriverTarget, MockTarget, SoakOptions, SoakTarget};
+use rising_sun_common::{DriverHandle, driver, load_config, mdns};
+
+/// How long `discover` listens for sessions
+const DISCOVER_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn cli() -> Command {
+    Command::new("rising-sun-cli")
+        .about("Control a SunPCi session")
+        .version(env!("CARGO_PKG_VERSION"))
+        .subcommand_required(true)
+        .arg(
+            Arg::new("host")
+                .long("host")
+                .global(true)
+                .help("Frontend to control (host, address or advertised profile name)"),
+        )
+        .arg(
+            Arg::new("port")
+                .long("port")
+                .global(true)
+                .value_parser(value_parser!(u16))
+                .help("Control API port of the frontend"),
+        )
+        .arg(
+            Arg::new("token")
+                .long("token")
+                .global(true)
+                .help("Control token of the frontend (default: [remote] control_token)"),
+        )
+        .arg(
+            Arg::new("card")
+                .long("card")
+                .global(true)
+                .value_parser(value_parser!(u32))
+                .help("SunPCi card to use without --host (N in /dev/sunpciN)"),
+        )
+        .subcommand(Command::new(command::STATUS).about("Show the session state"))
+        .subcommand(Command::new(command::START).about("Start a session (needs --host)"))
+        .subcommand(Command::new(command::STOP).about("Stop the session"))
+        .subcommand(Command::new(command::RESET).about("Warm-reset the guest"))
+        .subcommand(
+            Command::new(command::MOUNT_ISO)
+                .about("Mount an ISO image or CUE sheet as the CD-ROM")
+                .arg(Arg::new("path").required(true).help("ISO image or CUE sheet")),
+        )
+        .subcommand(Command::new(command::EJECT).about("Eject the CD-ROM"))
+        .subcommand(Command::new("discover").about("List sessions advertised on the LAN"))
+        .subcommand(Command::new("cards").about("List the SunPCi cards the driver found"))
+        .subcommand(
+            Command::new("convert")
+                .about("Convert a disk image between raw, SunPCi and VHD")
+                .arg(Arg::new("source").required(true).help("Image to convert"))
+                .arg(Arg::new("destination").required(true).help("Image to write (must not exist)"))
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .value_parser(|v: &str| DiskFormat::parse(v).ok_or_else(|| format!("unknown format {}", v)))
+                        .help("raw, sunpci or vhd (default: from the destination's extension)"),
+                ),
+        )
+        .subcommand(
+            Command::new("guest-tools")
+                .about("Build the guest tools CD from the SunPCi software")
+                .arg(
+                    Arg::new("source")
+                        .long("source")
+                        .help("SunPCi software directory (default: [guest_tools] source or /opt/SUNWspci*)"),
+                )
+                .arg(Arg::new("output").long("output").help("ISO to write (default: in the data directory)"))
+                .arg(
+                    Arg::new("driver-version")
+                        .long("driver-version")
+                        .value_parser(parse_version)
+                        .help("Driver version to build for, as 1.2.3 (default: the loaded driver's)"),
+                ),
+        )
+        .subcommand(
+            Command::new("soak")
+                .about("Cycle sessions against the driver, reporting failures and leaks")
+                .arg(
+                    Arg::new("duration")
+                        .long("duration")
+                        .value_parser(parse_duration)
+                        .help("Stop after this long (e.g. 90s, 30m, 8h)"),
+                )
+                .arg(
+                    Arg::new("cycles")
+                        .long("cycles")
+                        .value_parser(value_parser!(u64))
+                        .help("Stop after this many cycles"),
+                )
+                .arg(
+                    Arg::new("sample-every")
+                        .long("sample-every")
+                        .value_parser(value_parser!(u64))
+                        .default_value("10")
+                        .help("Sample open files and memory every this many cycles"),
+                )
+                .arg(Arg::new("iso").long("iso").help("ISO image to mount and eject each cycle"))
+                .arg(Arg::new("report").long("report").help("Write the JSON report here"))
+                .arg(
+                    Arg::new("mock")
+                        .long("mock")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Run against an in-process mock instead of the driver"),
+                ),
+        )
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let (name, sub) = matches.subcommand().expect("subcommand is required");
+    if let Some(&card) = matches.get_one::<u32>("card") {
+        rising_sun_common::select_card(card);
+    }
+
+    let result = match name {
+        "discover" => discover(),
+        "cards" => cards(),
+        "convert" => convert(sub),
+        "guest-tools" => build_guest_tools(sub),
+        "soak" => soak_test(sub),
+        _ => match matches.get_one::<String>("host") {
+            Some(host) => {
+                let token = matches.get_one::<String>("token");
+                remote(host, matches.get_one::<u16>("port").copied(), token.map(String::as_str), name, sub)
+            }
+            None => local(name, sub),
+        },
+    };
+
+    match result {
+        Ok(message) => {
+            if !message.is_empty() {
+                println!("{}", message);
+            }
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("rising-sun-cli: {}", message);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Send the command to a frontend's control API
+///
+/// Without `--token` the one in this host's configuration is sent.
+fn remote(
+    host: &str,
+    port: Option<u16>,
+    token: Option<&str>,
+    name: &str,
+    sub: &ArgMatches,
+) -> Result<String, String> {
+    let mut args = Vec::new();
+    if let Some(path) = sub.try_get_one::<String>("path").ok().flatten() {
+        args.push(absolute_if_local(path));
+    }
+    let request = ControlRequest {
+        command: name.to_string(),
+        args,
+        token: None,
+    };
+
+    let config = load_config().unwrap_or_default();
+    let token = token.or_else(|| config.remote.token());
+    let mut client = ControlClient::connect(host, port)
+        .map_err(|e| format!("{}: {}", host, e))?
+        .with_token(token);
+    match client.call(&request).map_err(|e| format!("{}: {}", host, e))? {
+        ControlResponse { ok: true, message } => Ok(message),
+        ControlResponse { ok: false, message } => Err(message),
+    }
+}
+
+/// Paths that exist here are sent absolute, since the frontend resolves
+/// them from its own working directory; others are passed as given
+fn absolute_if_local(path: &str) -> String {
+    std::fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// Run the command on the driver directly
+fn local(name: &str, sub: &ArgMatches) -> Result<String, String> {
+    let driver = DriverHandle::open().map_err(|e| {
+        format!("{} (use --host to control a session owned by the frontend)", e)
+    })?;
+
+    match name {
+        command::STATUS => {
+            let status = driver.get_status().map_err(|e| e.to_string())?;
+            let state = match status.state {
+                s if s == SessionState::Running as u32 => "running",
+                s if s == SessionState::Starting as u32 => "starting",
+                s if s == SessionState::Stopping as u32 => "stopping",
+                s if s == SessionState::Error as u32 => "error",
+                s if s == SessionState::Suspended as u32 => "suspended",
+                _ => "stopped",
+            };
+            Ok(format!("Session {}, up {}s", state, status.uptime_ns() / 1_000_000_000))
+        }
+        command::START => Err("starting a session needs a frontend's configuration; use --host".to_string()),
+        command::STOP => driver.stop_session().map(|_| String::new()).map_err(|e| e.to_string()),
+        command::RESET => driver.reset_session().map(|_| String::new()).map_err(|e| e.to_string()),
+        command::MOUNT_ISO => {
+            let path = sub.get_one::<String>("path").expect("path is required");
+            if !Path::new(path).exists() {
+                return Err(format!("{} does not exist", path));
+            }
+            if path.to_ascii_lowercase().ends_with(".cue") {
+                return Err("CUE sheets are served by the frontend; use --host".to_string());
+            }
+            let path = absolute_if_local(path);
+            driver.mount_cdrom(&path).map(|_| format!("Mounted {}", path)).map_err(|e| e.to_string())
+        }
+        command::EJECT => driver.eject_cdrom().map(|_| String::new()).map_err(|e| e.to_string()),
+        other => Err(format!("unknown command {}", other)),
+    }
+}
+
+/// Run a soak test until its duration or cycle count (or forever)
+fn soak_test(sub: &ArgMatches) -> Result<String, String> {
+    let options = SoakOptions {
+        duration: sub.get_one::<Duration>("duration").copied(),
+        cycles: sub.get_one::<u64>("cycles").copied(),
+        sample_every: *sub.get_one::<u64>("sample-every").expect("has a default"),
+        iso: sub.get_one::<String>("iso").map(|p| PathBuf::from(absolute_if_local(p))),
+        report: sub.get_one::<String>("report").map(PathBuf::from),
+    };
+    let mut target: Box<dyn SoakTarget> = if sub.get_flag("mock") {
+        Box::new(MockTarget::default())
+    } else {
+        Box::new(DriverTarget::open().map_err(|e| format!("{:#}", e))?)
+    };
+
+    // A progress line per sample
+    let mut printed = 0;
+    let report = soak::run(target.as_mut(), &options, |report| {
+        if let Some(sample) = report.samples.get(printed..).and_then(|s| s.last()) {
+            printed = report.samples.len();
+            let failed: u64 = report.operations.values().map(|c| c.failed).sum();
+            eprintln!(
+                "cycle {}: {} failures, {} fds, {} KiB resident",
+                sample.cycle, failed, sample.open_fds, sample.rss_kb
+            );
+        }
+        true
+    })
+    .map_err(|e| format!("{:#}", e))?;
+
+    let mut lines = vec![format!(
+        "{} cycles in {}s against the {}",
+        report.cycles,
+        report.elapsed_ms / 1000,
+        report.target
+    )];
+    for (op, counts) in &report.operations {
+        if counts.failed > 0 {
+            lines.push(format!("{}: {} of {} failed", op, counts.failed, counts.ok + counts.failed));
+        }
+    }
+    lines.extend(report.leaks.iter().map(|leak| format!("Suspected leak: {}", leak)));
+    if report.passed {
+        Ok(lines.join("\n"))
+    } else {
+        Err(lines.join("\n"))
+    }
+}
+
+/// Convert a disk image
+fn convert(sub: &ArgMatches) -> Result<String, String> {
+    let source = Path::new(sub.get_one::<String>("source").expect("source is required"));
+    let destination = Path::new(sub.get_one::<String>("destination").expect("destination is required"));
+    let format = sub
+        .get_one::<DiskFormat>("format")
+        .copied()
+        .unwrap_or_else(|| DiskFormat::for_path(destination));
+    let converted = disk_convert::convert_disk(source, destination, format, &ProgressReporter::new())
+        .map_err(|e| format!("{:#}", e))?;
+    let mut message = format!(
+        "Converted {} ({}) to {} ({}), {} MB",
+        source.display(),
+        converted.from.name(),
+        destination.display(),
+        converted.to.name(),
+        converted.sectors / 2048
+    );
+    if let Some((cylinders, heads, sectors_per_track)) = converted.geometry {
+        message.push_str(&format!(", geometry {}/{}/{}", cylinders, heads, sectors_per_track));
+    }
+    Ok(message)
+}
+
+/// Build the guest tools CD
+fn build_guest_tools(sub: &ArgMatches) -> Result<String, String> {
+    let version = match sub.get_one::<DriverVersion>("driver-version") {
+        Some(&version) => version,
+        None => DriverHandle::open()
+            .and_then(|driver| driver.get_version())
+            .map_err(|e| format!("{} (use --driver-version without the driver)", e))?,
+    };
+    let configured = load_config().unwrap_or_default().guest_tools.source;
+    let source = match sub.get_one::<String>("source") {
+        Some(source) => PathBuf::from(source),
+        None => guest_tools::find_source(configured.as_deref())
+            .ok_or("no SunPCi software found; give its directory with --source")?,
+    };
+    let output = sub
+        .get_one::<String>("output")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| guest_tools::iso_path(&version));
+
+    let built = guest_tools::build_iso(&source, &version, &output).map_err(|e| format!("{:#}", e))?;
+    let mut lines = vec![format!("Wrote {} with {} tools", built.path.display(), built.files.len())];
+    lines.extend(built.missing.iter().map(|path| format!("Not in {}: {}", source.display(), path)));
+    Ok(lines.join("\n"))
+}
+
+/// Parse a driver version given as major.minor.patch
+fn parse_version(value: &str) -> Result<DriverVersion, String> {
+    let parts: Vec<u32> = value
+        .split('.')
+        .map(|part| part.parse().map_err(|_| format!("invalid version {}", value)))
+        .collect::<Result<_, _>>()?;
+    match parts[..] {
+        [major, minor, patch] => Ok(DriverVersion { major, minor, patch }),
+        _ => Err(format!("invalid version {} (expected major.minor.patch)", value)),
+    }
+}
+
+/// Parse a duration given in seconds, or with an s, m or h suffix
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let (number, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c),
+        _ => (value, 's'),
+    };
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration {}", value))?;
+    let seconds = match unit {
+        's' => number,
+        'm' => number * 60,
+        'h' => number * 3600,
+        _ => return Err(format!("invalid duration unit in {}", value)),
+    };
+    Ok(Duration::from_secs(seconds))
+}
+
+/// List advertised sessions
+/// List the cards in this machine
+fn cards() -> Result<String, String> {
+    let cards = driver::list_devices();
+    if cards.is_empty() {
+        return Err("no SunPCi cards found (is the driver loaded?)".to_string());
+    }
+    let config = load_config().unwrap_or_default();
+    let lines: Vec<String> = cards
+        .iter()
+        .map(|&card| {
+            let path = driver::device_path(card);
+            format!("{:<4} {:<16} {}", card, path.display(), config.card_name(card))
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
+
+fn discover() -> Result<String, String> {
+    let sessions = mdns::browse(DISCOVER_TIMEOUT).map_err(|e| e.to_string())?;
+    if sessions.is_empty() {
+        return Ok("No sessions found".to_string());
+    }
+    let lines: Vec<String> = sessions
+        .iter()
+        .map(|s| {
+            let addr = s.addr.map(|a| a.to_string()).unwrap_or_else(|| "?".to_string());
+            let port = s.control_port().map(|p| p.to_string()).unwrap_or_else(|| "-".to_string());
+            format!("{:<20} {:<16} {:<15} control {}", s.profile(), s.host, addr, port)
+        })
+        .collect();
+    Ok(lines.join("\n"))
+}
@@ -0,0 +1,203 @@
+//! BIOS images for the card.
+//!
+//! A session normally runs the BIOS in the card's flash. With `[bios] path`
+//! set, the driver loads that image instead (`IoctlSessionConfig.bios_path`).
+//! A bad image leaves the guest dead at power-on with nothing on screen, so
+//! the image is checked before the session starts: it has to be readable,
+//! have the size of a PC BIOS, end in a reset vector, and, when
+//! `verify_checksum` is set, still have the SHA-256 recorded when it was
+//! chosen. The BIOS date near the end of the image serves as its version.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::BiosConfig;
+use crate::sha256::{self, Sha256};
+
+/// Smallest image accepted (one 64 KiB segment)
+pub const MIN_SIZE: u64 = 64 * 1024;
+/// Largest image accepted
+pub const MAX_SIZE: u64 = 1024 * 1024;
+
+/// Why a BIOS image cannot be used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BiosProblem {
+    /// The file does not exist
+    Missing(PathBuf),
+    /// The file cannot be read
+    Unreadable(PathBuf, String),
+    /// Not a whole number of 64 KiB segments between 64 KiB and 1 MiB
+    BadSize(u64),
+    /// The last 16 bytes do not start with a jump
+    NoResetVector,
+    /// The file changed since it was chosen
+    ChecksumMismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for BiosProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(path) => write!(f, "BIOS image {} not found", path.display()),
+            Self::Unreadable(path, e) => write!(f, "Cannot read BIOS image {}: {}", path.display(), e),
+            Self::BadSize(size) => write!(
+                f,
+                "BIOS image is {} bytes; expected a multiple of 64 KiB up to 1 MiB",
+                size
+            ),
+            Self::NoResetVector => write!(f, "BIOS image has no reset vector; it is not a PC BIOS or is damaged"),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "BIOS image changed since it was chosen (SHA-256 {}, expected {}); choose it again if this is intended",
+                short(actual),
+                short(expected)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BiosProblem {}
+
+fn short(digest: &str) -> &str {
+    &digest[..digest.len().min(12)]
+}
+
+/// What a BIOS image is
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BiosImage {
+    pub path: PathBuf,
+    pub size: u64,
+    /// SHA-256 of the image (hex)
+    pub sha256: String,
+    /// BIOS date ("MM/DD/YY") if the image carries one
+    pub version: Option<String>,
+}
+
+/// Read and check a BIOS image
+pub fn inspect(path: &Path) -> Result<BiosImage, BiosProblem> {
+    if !path.exists() {
+        return Err(BiosProblem::Missing(path.to_path_buf()));
+    }
+    let data =
+        fs::read(path).map_err(|e| BiosProblem::Unreadable(path.to_path_buf(), e.to_string()))?;
+    let size = data.len() as u64;
+    if !(MIN_SIZE..=MAX_SIZE).contains(&size) || !size.is_multiple_of(MIN_SIZE) {
+        return Err(BiosProblem::BadSize(size));
+    }
+    // The CPU starts at F000:FFF0, 16 bytes from the end: a JMP FAR (EA),
+    // or a near (E9) or short (EB) jump on some BIOSes
+    let reset = &data[data.len() - 16..];
+    if !matches!(reset[0], 0xE9..=0xEB) {
+        return Err(BiosProblem::NoResetVector);
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(&data);
+    Ok(BiosImage {
+        path: path.to_path_buf(),
+        size,
+        sha256: sha256::to_hex(&hasher.finish()),
+        version: bios_date(reset),
+    })
+}
+
+/// The BIOS date at F000:FFF5, from the last 16 bytes of the image
+fn bios_date(reset: &[u8]) -> Option<String> {
+    let date = std::str::from_utf8(&reset[5..13]).ok()?;
+    let b = date.as_bytes();
+    let digits = [0, 1, 3, 4, 6, 7].iter().all(|&i| b[i].is_ascii_digit());
+    (digits && b[2] == b'/' && b[5] == b'/').then(|| date.to_string())
+}
+
+/// The image a session with `config` loads, checked; None for the card's
+/// own BIOS
+pub fn check(config: &BiosConfig) -> Result<Option<BiosImage>, BiosProblem> {
+    let Some(path) = config.path.as_deref() else {
+        return Ok(None);
+    };
+    let image = inspect(path)?;
+    let expected = config.sha256.as_deref().filter(|_| config.verify_checksum);
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(&image.sha256) => {
+            Err(BiosProblem::ChecksumMismatch {
+                expected: expected.to_string(),
+                actual: image.sha256,
+            })
+        }
+        _ => Ok(Some(image)),
+    }
+}
+
+/// Use the image at `path` from now on, recording its checksum
+pub fn choose(config: &mut BiosConfig, path: &Path) -> Result<BiosImage, BiosProblem> {
+    let image = inspect(path)?;
+    config.path = Some(path.to_path_buf());
+    config.sha256 = Some(image.sha256.clone());
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KnownBios;
+
+    /// A 64 KiB image with a reset vector and a BIOS date
+    fn write_image(dir: &Path, name: &str) -> PathBuf {
+        let mut data = vec![0xFFu8; MIN_SIZE as usize];
+        let end = data.len() - 16;
+        data[end..end + 5].copy_from_slice(&[0xEA, 0x5B, 0xE0, 0x00, 0xF0]);
+        data[end + 5..end + 13].copy_from_slice(b"06/15/99");
+        let path = dir.join(name);
+        fs::write(&path, &data).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_inspect() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_image(dir.path(), "sunpci.bin");
+        let image = inspect(&path).unwrap();
+        assert_eq!(image.size, MIN_SIZE);
+        assert_eq!(image.version.as_deref(), Some("06/15/99"));
+        assert_eq!(image.sha256.len(), 64);
+
+        let missing = dir.path().join("missing.bin");
+        assert_eq!(inspect(&missing), Err(BiosProblem::Missing(missing.clone())));
+
+        let short = dir.path().join("short.bin");
+        fs::write(&short, [0xEA; 1000]).unwrap();
+        assert_eq!(inspect(&short), Err(BiosProblem::BadSize(1000)));
+
+        let blank = dir.path().join("blank.bin");
+        fs::write(&blank, vec![0u8; MIN_SIZE as usize * 2]).unwrap();
+        assert_eq!(inspect(&blank), Err(BiosProblem::NoResetVector));
+    }
+
+    #[test]
+    fn test_check_and_choose() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_image(dir.path(), "sunpci.bin");
+
+        let mut config = BiosConfig::default();
+        assert_eq!(check(&config), Ok(None));
+
+        let image = choose(&mut config, &path).unwrap();
+        assert_eq!(config.sha256.as_deref(), Some(image.sha256.as_str()));
+        assert_eq!(check(&config).unwrap().unwrap().sha256, image.sha256);
+        assert!(config.known(&image.sha256).is_none());
+        config.known_good.push(KnownBios {
+            sha256: image.sha256.to_uppercase(),
+            version: "06/15/99".to_string(),
+            note: String::new(),
+        });
+        assert!(config.known(&image.sha256).is_some());
+
+        // Patched after it was chosen
+        let mut data = fs::read(&path).unwrap();
+        data[0] = 0x55;
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(check(&config), Err(BiosProblem::ChecksumMismatch { .. })));
+        config.verify_checksum = false;
+        assert!(check(&config).is_ok());
+    }
+}
@@ -0,0 +1,173 @@
+//! Conversion between RGBA images and Windows device-independent bitmaps.
+//!
+//! The guest exchanges clipboard images as CF_DIB: a BITMAPINFOHEADER
+//! followed by an optional color table and the pixel rows (bottom-up unless
+//! the height is negative, each row padded to 4 bytes, BGR byte order).
+//!
+//! Host images are sent as 24bpp BI_RGB, which every Windows version reads.
+//! Guest bitmaps are accepted at 1/4/8bpp (palettized), 24bpp and 32bpp.
+
+/// Size of BITMAPINFOHEADER
+const HEADER_SIZE: usize = 40;
+
+/// Uncompressed pixel data
+const BI_RGB: u32 = 0;
+
+/// Uncompressed pixel data with color masks after the header
+const BI_BITFIELDS: u32 = 3;
+
+/// Bytes per padded row for the given width and bit depth
+fn row_stride(width: usize, bits: usize) -> usize {
+    (width * bits).div_ceil(32) * 4
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+/// Encode an RGBA image as a 24bpp bottom-up DIB
+pub fn rgba_to_dib(width: u32, height: u32, rgba: &[u8]) -> Option<Vec<u8>> {
+    let (w, h) = (width as usize, height as usize);
+    if w == 0 || h == 0 || rgba.len() < w * h * 4 {
+        return None;
+    }
+
+    let stride = row_stride(w, 24);
+    let image_size = stride * h;
+    let mut dib = Vec::with_capacity(HEADER_SIZE + image_size);
+
+    dib.extend_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    dib.extend_from_slice(&(width as i32).to_le_bytes());
+    dib.extend_from_slice(&(height as i32).to_le_bytes());
+    dib.extend_from_slice(&1u16.to_le_bytes()); // planes
+    dib.extend_from_slice(&24u16.to_le_bytes()); // bit count
+    dib.extend_from_slice(&BI_RGB.to_le_bytes());
+    dib.extend_from_slice(&(image_size as u32).to_le_bytes());
+    dib.extend_from_slice(&2835i32.to_le_bytes()); // 72 DPI
+    dib.extend_from_slice(&2835i32.to_le_bytes());
+    dib.extend_from_slice(&0u32.to_le_bytes()); // colors used
+    dib.extend_from_slice(&0u32.to_le_bytes()); // colors important
+
+    for y in (0..h).rev() {
+        let row = &rgba[y * w * 4..(y + 1) * w * 4];
+        for px in row.chunks_exact(4) {
+            dib.extend_from_slice(&[px[2], px[1], px[0]]);
+        }
+        dib.resize(dib.len() + stride - w * 3, 0);
+    }
+
+    Some(dib)
+}
+
+/// Decode a DIB into (width, height, RGBA pixels)
+pub fn dib_to_rgba(dib: &[u8]) -> Option<(u32, u32, Vec<u8>)> {
+    if dib.len() < HEADER_SIZE {
+        return None;
+    }
+
+    let header_size = read_u32(dib, 0) as usize;
+    if header_size < HEADER_SIZE || header_size > dib.len() {
+        return None;
+    }
+    let width = read_u32(dib, 4) as i32;
+    let height = read_u32(dib, 8) as i32;
+    let bits = read_u16(dib, 14) as usize;
+    let compression = read_u32(dib, 16);
+    let colors_used = read_u32(dib, 32) as usize;
+
+    if width <= 0 || height == 0 {
+        return None;
+    }
+    let w = width as usize;
+    let h = height.unsigned_abs() as usize;
+    let bottom_up = height > 0;
+
+    let mut offset = header_size;
+    // BI_BITFIELDS masks follow a plain BITMAPINFOHEADER; only the standard
+    // 8-8-8 layout is supported, which matches BI_RGB at 32bpp
+    if compression == BI_BITFIELDS && bits == 32 {
+        if header_size == HEADER_SIZE {
+            offset += 12;
+        }
+    } else if compression != BI_RGB {
+        return None;
+    }
+
+    let palette: Vec<[u8; 3]> = if bits <= 8 {
+        let count = if colors_used == 0 { 1 << bits } else { colors_used.min(1 << bits) };
+        let table = dib.get(offset..offset + count * 4)?;
+        offset += count * 4;
+        table.chunks_exact(4).map(|c| [c[2], c[1], c[0]]).collect()
+    } else {
+        Vec::new()
+    };
+
+    let stride = row_stride(w, bits);
+    let pixels = dib.get(offset..offset + stride * h)?;
+    let mut rgba = Vec::with_capacity(w * h * 4);
+
+    for y in 0..h {
+        let src_y = if bottom_up { h - 1 - y } else { y };
+        let row = &pixels[src_y * stride..(src_y + 1) * stride];
+        for x in 0..w {
+            let px = match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits;
+                    let shift = 8 - bits - bit % 8;
+                    let index = (row[bit / 8] >> shift) as usize & ((1 << bits) - 1);
+                    let [r, g, b] = *palette.get(index)?;
+                    [r, g, b, 0xFF]
+                }
+                24 => [row[x * 3 + 2], row[x * 3 + 1], row[x * 3], 0xFF],
+                // The alpha byte is unreliable in guest bitmaps, so ignore it
+                32 => [row[x * 4 + 2], row[x * 4 + 1], row[x * 4], 0xFF],
+                _ => return None,
+            };
+            rgba.extend_from_slice(&px);
+        }
+    }
+
+    Some((w as u32, h as u32, rgba))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dib_round_trip() {
+        // 3x2 image: odd width exercises row padding
+        let rgba: Vec<u8> = (0..6u8).flat_map(|i| [i * 10, i * 10 + 1, i * 10 + 2, 0xFF]).collect();
+        let dib = rgba_to_dib(3, 2, &rgba).unwrap();
+        assert_eq!(dib.len(), HEADER_SIZE + 12 * 2);
+        assert_eq!(dib_to_rgba(&dib), Some((3, 2, rgba)));
+    }
+
+    #[test]
+    fn test_dib_palettized() {
+        // 2x1 1bpp top-down bitmap with a black/white palette
+        let mut dib = vec![0u8; HEADER_SIZE];
+        dib[0..4].copy_from_slice(&40u32.to_le_bytes());
+        dib[4..8].copy_from_slice(&2i32.to_le_bytes());
+        dib[8..12].copy_from_slice(&(-1i32).to_le_bytes());
+        dib[12..14].copy_from_slice(&1u16.to_le_bytes());
+        dib[14..16].copy_from_slice(&1u16.to_le_bytes());
+        dib.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0]);
+        dib.extend_from_slice(&[0b0100_0000, 0, 0, 0]);
+
+        let (w, h, rgba) = dib_to_rgba(&dib).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(rgba, vec![0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_dib_truncated() {
+        let dib = rgba_to_dib(4, 4, &[0u8; 64]).unwrap();
+        assert_eq!(dib_to_rgba(&dib[..dib.len() - 1]), None);
+        assert_eq!(dib_to_rgba(&dib[..10]), None);
+    }
+}
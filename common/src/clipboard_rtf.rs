@@ -0,0 +1,469 @@
+//! Conversion between host HTML and the guest's Rich Text Format.
+//!
+//! Windows applications such as Word and WordPad put formatted text on the
+//! clipboard as "Rich Text Format" (CF_RTF); most Linux applications offer
+//! text/html instead. Only the character formatting that survives both
+//! representations is carried across: bold, italic, underline, paragraph
+//! and line breaks. Fonts, colors and layout fall back to the receiving
+//! application's defaults.
+//!
+//! When the host clipboard already holds RTF (LibreOffice offers text/rtf)
+//! it is passed through unchanged.
+
+/// Character formatting applied to a run of text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Format {
+    bold: bool,
+    italic: bool,
+    underline: bool,
+}
+
+/// RTF destinations whose content is not document text
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl", "colortbl", "stylesheet", "info", "pict", "header", "footer",
+    "headerl", "headerr", "footerl", "footerr", "listtable", "listoverridetable",
+    "rsidtbl", "generator", "xmlnstbl", "themedata", "datastore", "latentstyles",
+];
+
+/// HTML elements whose content is not document text
+const SKIPPED_ELEMENTS: &[&str] = &["head", "style", "script", "title"];
+
+/// Windows-1252 characters in 0x80..=0x9F (undefined code points map to '?')
+const CP1252_HIGH: [char; 32] = [
+    '€', '?', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '?', 'Ž', '?',
+    '?', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '?', 'ž', 'Ÿ',
+];
+
+fn cp1252_char(byte: u8) -> char {
+    match byte {
+        0x80..=0x9F => CP1252_HIGH[(byte - 0x80) as usize],
+        _ => byte as char,
+    }
+}
+
+/// Convert an HTML fragment to an RTF document
+pub fn html_to_rtf(html: &str) -> String {
+    let mut rtf = String::from("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0 Arial;}}\\f0\\fs20 ");
+    let (mut bold, mut italic, mut underline) = (0u32, 0u32, 0u32);
+    let mut skip_depth = 0u32;
+    // Collapse whitespace as a browser would
+    let mut pending_space = false;
+    let mut at_line_start = true;
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('<') {
+            // A tag left open runs to the end of the text
+            let (tag, remainder) = after.split_once('>').unwrap_or((after, ""));
+            rest = remainder;
+
+            let closing = tag.starts_with('/');
+            let name: String = tag
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric())
+                .collect::<String>()
+                .to_ascii_lowercase();
+
+            if SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                skip_depth = if closing { skip_depth.saturating_sub(1) } else { skip_depth + 1 };
+                continue;
+            }
+            if skip_depth > 0 {
+                continue;
+            }
+
+            let (counter, on, off) = match name.as_str() {
+                "b" | "strong" => (&mut bold, "\\b ", "\\b0 "),
+                "i" | "em" => (&mut italic, "\\i ", "\\i0 "),
+                "u" | "ins" => (&mut underline, "\\ul ", "\\ulnone "),
+                "br" => {
+                    rtf.push_str("\\line ");
+                    pending_space = false;
+                    at_line_start = true;
+                    continue;
+                }
+                "p" | "div" | "li" | "tr" | "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                    // Block boundaries become paragraph breaks, but never
+                    // an empty leading paragraph
+                    if !at_line_start {
+                        rtf.push_str("\\par ");
+                        at_line_start = true;
+                    }
+                    pending_space = false;
+                    continue;
+                }
+                _ => continue,
+            };
+            // Keep a collapsed space outside the formatting change
+            if pending_space {
+                rtf.push(' ');
+                pending_space = false;
+            }
+            if closing {
+                *counter = counter.saturating_sub(1);
+                if *counter == 0 {
+                    rtf.push_str(off);
+                }
+            } else {
+                *counter += 1;
+                if *counter == 1 {
+                    rtf.push_str(on);
+                }
+            }
+            continue;
+        }
+
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = &rest[..end];
+        rest = &rest[end..];
+        if skip_depth > 0 {
+            continue;
+        }
+
+        for c in decode_entities(text).chars() {
+            if c.is_whitespace() && c != '\u{A0}' {
+                pending_space = !at_line_start;
+                continue;
+            }
+            if pending_space {
+                rtf.push(' ');
+                pending_space = false;
+            }
+            at_line_start = false;
+            push_rtf_char(&mut rtf, c);
+        }
+    }
+
+    rtf.push('}');
+    rtf
+}
+
+/// Append one character to RTF output, escaping as needed
+fn push_rtf_char(rtf: &mut String, c: char) {
+    match c {
+        '\\' | '{' | '}' => {
+            rtf.push('\\');
+            rtf.push(c);
+        }
+        '\u{A0}' => rtf.push_str("\\~"),
+        '\t' => rtf.push_str("\\tab "),
+        ' '..='~' => rtf.push(c),
+        _ => {
+            // \uN takes a signed 16-bit value; '?' is the fallback for
+            // readers without Unicode support
+            let mut units = [0u16; 2];
+            for unit in c.encode_utf16(&mut units) {
+                rtf.push_str(&format!("\\u{}?", *unit as i16));
+            }
+        }
+    }
+}
+
+/// Decode the HTML character references used in clipboard fragments
+fn decode_entities(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest[1..].find(';').filter(|&i| i <= 10).and_then(|i| {
+            let entity = &rest[1..=i];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{A0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, i + 2))
+        });
+
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Convert an RTF document to an HTML fragment
+pub fn rtf_to_html(rtf: &str) -> String {
+    let bytes = rtf.as_bytes();
+    let mut html = String::new();
+    let mut stack: Vec<(Format, bool, usize)> = Vec::new();
+    let mut format = Format::default();
+    let mut skip = false;
+    // Characters to skip after \uN (set by \ucN)
+    let mut uc = 1usize;
+    let mut skip_fallback = 0usize;
+    let mut open = Format::default();
+    let mut group_start = false;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let b = bytes[i];
+        match b {
+            b'{' => {
+                stack.push((format, skip, uc));
+                group_start = true;
+                i += 1;
+                continue;
+            }
+            b'}' => {
+                if let Some((f, s, u)) = stack.pop() {
+                    format = f;
+                    skip = s;
+                    uc = u;
+                }
+                i += 1;
+            }
+            b'\\' => {
+                let start = i + 1;
+                let Some(&next) = bytes.get(start) else { break };
+                if next.is_ascii_alphabetic() {
+                    let mut end = start;
+                    while end < bytes.len() && bytes[end].is_ascii_alphabetic() {
+                        end += 1;
+                    }
+                    let word = &rtf[start..end];
+                    let mut num_end = end;
+                    if num_end < bytes.len() && bytes[num_end] == b'-' {
+                        num_end += 1;
+                    }
+                    while num_end < bytes.len() && bytes[num_end].is_ascii_digit() {
+                        num_end += 1;
+                    }
+                    let param: Option<i32> = rtf[end..num_end].parse().ok();
+                    i = num_end;
+                    // A single space delimits the control word
+                    if i < bytes.len() && bytes[i] == b' ' {
+                        i += 1;
+                    }
+
+                    if group_start && SKIPPED_DESTINATIONS.contains(&word) {
+                        skip = true;
+                    }
+                    group_start = false;
+                    if skip {
+                        continue;
+                    }
+
+                    let enabled = param != Some(0);
+                    match word {
+                        "b" => format.bold = enabled,
+                        "i" => format.italic = enabled,
+                        "ul" => format.underline = enabled,
+                        "ulnone" => format.underline = false,
+                        "plain" => format = Format::default(),
+                        "uc" => uc = param.unwrap_or(1).max(0) as usize,
+                        "par" | "line" => {
+                            close_tags(&mut html, &mut open);
+                            html.push_str("<br>");
+                        }
+                        "tab" => push_html_char(&mut html, &mut open, format, '\t'),
+                        "emdash" => push_html_char(&mut html, &mut open, format, '—'),
+                        "endash" => push_html_char(&mut html, &mut open, format, '–'),
+                        "bullet" => push_html_char(&mut html, &mut open, format, '•'),
+                        "lquote" => push_html_char(&mut html, &mut open, format, '‘'),
+                        "rquote" => push_html_char(&mut html, &mut open, format, '’'),
+                        "ldblquote" => push_html_char(&mut html, &mut open, format, '“'),
+                        "rdblquote" => push_html_char(&mut html, &mut open, format, '”'),
+                        "u" => {
+                            if let Some(n) = param {
+                                let unit = n as i16 as u16;
+                                // Surrogate pairs arrive as two \u words
+                                let c = if (0xD800..0xDC00).contains(&unit) {
+                                    pending_low_surrogate(rtf, i, uc, unit).map(|(c, len)| {
+                                        i += len;
+                                        c
+                                    })
+                                } else {
+                                    char::from_u32(unit as u32)
+                                };
+                                push_html_char(&mut html, &mut open, format, c.unwrap_or('?'));
+                                skip_fallback = uc;
+                            }
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+
+                group_start = false;
+                // A control symbol is one character, which need not be ASCII
+                i = start + rtf[start..].chars().next().map_or(1, char::len_utf8);
+                match next {
+                    b'*' => skip = true,
+                    b'\'' => {
+                        let digits =
+                            bytes[i..].iter().take(2).take_while(|c| c.is_ascii_hexdigit()).count();
+                        let hex = (digits == 2).then(|| &rtf[i..i + 2]);
+                        i += digits;
+                        if skip_fallback > 0 {
+                            skip_fallback -= 1;
+                        } else if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok())
+                            && !skip
+                        {
+                            push_html_char(&mut html, &mut open, format, cp1252_char(byte));
+                        }
+                    }
+                    b'~' if !skip => push_html_char(&mut html, &mut open, format, '\u{A0}'),
+                    b'\\' | b'{' | b'}' if !skip => {
+                        push_html_char(&mut html, &mut open, format, next as char)
+                    }
+                    b'\n' | b'\r' if !skip => {
+                        close_tags(&mut html, &mut open);
+                        html.push_str("<br>");
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            b'\r' | b'\n' => i += 1,
+            _ => {
+                // Copy a run of plain text (RTF is 7-bit; anything else is
+                // passed through as UTF-8)
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&c| matches!(c, b'{' | b'}' | b'\\' | b'\r' | b'\n'))
+                    .map_or(bytes.len(), |p| i + p);
+                for c in rtf[i..end].chars() {
+                    if skip_fallback > 0 {
+                        skip_fallback -= 1;
+                    } else if !skip {
+                        push_html_char(&mut html, &mut open, format, c);
+                    }
+                }
+                i = end;
+            }
+        }
+        group_start = false;
+    }
+
+    close_tags(&mut html, &mut open);
+    html
+}
+
+/// Look for the low surrogate after a high surrogate \uN and its fallback
+///
+/// Returns the combined character and the number of bytes consumed.
+fn pending_low_surrogate(rtf: &str, at: usize, uc: usize, high: u16) -> Option<(char, usize)> {
+    // Skip the fallback characters of the high surrogate
+    let mut pos = at;
+    for _ in 0..uc {
+        pos += rtf[pos..].chars().next()?.len_utf8();
+    }
+    let rest = rtf[pos..].strip_prefix("\\u")?;
+    let digits = rest
+        .char_indices()
+        .take_while(|&(i, c)| c.is_ascii_digit() || (i == 0 && c == '-'))
+        .count();
+    let low = rest[..digits].parse::<i32>().ok()? as i16 as u16;
+    let c = char::decode_utf16([high, low]).next()?.ok()?;
+    let mut len = pos - at + 2 + digits;
+    if rtf.as_bytes().get(at + len) == Some(&b' ') {
+        len += 1;
+    }
+    Some((c, len))
+}
+
+/// Close the tags opened for the current run
+fn close_tags(html: &mut String, open: &mut Format) {
+    if open.underline {
+        html.push_str("</u>");
+    }
+    if open.italic {
+        html.push_str("</i>");
+    }
+    if open.bold {
+        html.push_str("</b>");
+    }
+    *open = Format::default();
+}
+
+/// Append one character to HTML output, switching tags if the format changed
+fn push_html_char(html: &mut String, open: &mut Format, format: Format, c: char) {
+    if *open != format {
+        close_tags(html, open);
+        if format.bold {
+            html.push_str("<b>");
+        }
+        if format.italic {
+            html.push_str("<i>");
+        }
+        if format.underline {
+            html.push_str("<u>");
+        }
+        *open = format;
+    }
+    match c {
+        '&' => html.push_str("&amp;"),
+        '<' => html.push_str("&lt;"),
+        '>' => html.push_str("&gt;"),
+        '"' => html.push_str("&quot;"),
+        '\u{A0}' => html.push_str("&nbsp;"),
+        _ => html.push(c),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_rtf() {
+        let rtf = html_to_rtf("<html><head><style>p {}</style></head><body>\
+            <p>Hello <b>bold</b> &amp; <i>it\u{e9}</i></p><p>{x}</p></body></html>");
+        assert!(rtf.starts_with("{\\rtf1"));
+        assert!(rtf.ends_with("\\par \\{x\\}\\par }"));
+        assert!(rtf.contains("Hello \\b bold\\b0  & \\i it\\u233?\\i0 "));
+        assert!(!rtf.contains("p {}"));
+    }
+
+    #[test]
+    fn test_rtf_to_html() {
+        // Trimmed-down WordPad output
+        let rtf = "{\\rtf1\\ansi\\ansicpg1252\\deff0{\\fonttbl{\\f0\\fnil Calibri;}}\
+            {\\*\\generator Riched20 10.0;}\\viewkind4\\uc1\\pard\\f0\\fs22 \
+            Plain \\b bold\\b0  \\i\\'e9\\i0\\par\n\\ul x<y\\ulnone  \\u8364?\\par\n}";
+        assert_eq!(
+            rtf_to_html(rtf),
+            "Plain <b>bold</b> <i>\u{e9}</i><br><u>x&lt;y</u> \u{20ac}<br>"
+        );
+    }
+
+    #[test]
+    fn test_malformed_input() {
+        // Clipboard content is untrusted: odd input must not panic
+        assert_eq!(html_to_rtf("a <\u{e9}"), html_to_rtf("a "));
+        assert_eq!(rtf_to_html("{\\rtf1 \\\u{e9}}"), "");
+        assert_eq!(rtf_to_html("{\\rtf1 \\'\u{e9}x}"), "\u{e9}x");
+        assert_eq!(rtf_to_html("{\\rtf1 \\'e"), "");
+    }
+
+    #[test]
+    fn test_rich_text_round_trip() {
+        let html = "<b>one</b> <i><u>two</u></i><br>\u{1F600}";
+        assert_eq!(
+            rtf_to_html(&html_to_rtf(html)),
+            "<b>one</b> <i><u>two</u></i><br>\u{1F600}"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Encoding of clipboard text exchanged with the guest.
+//!
+//! Windows guests offer CF_UNICODETEXT as null-terminated UTF-16LE and
+//! CF_TEXT as null-terminated 8-bit text in the guest's OEM code page (see
+//! `codepage`). The host side always sends CF_UNICODETEXT.
+//!
+//! Text is passed through `normalize` in each direction, so scripts copied
+//! in the guest don't arrive on the host with a carriage return on every
+//! line (see `ClipboardConfig`).
+
+use crate::codepage::{self, CodePage};
+use crate::config::{NewlineMode, TextNormalization};
+use crate::ioctl::clipboard_format;
+
+/// Encode text as null-terminated UTF-16LE
+pub fn encode_utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16()
+        .chain(std::iter::once(0))
+        .flat_map(|c| c.to_le_bytes())
+        .collect()
+}
+
+/// Decode UTF-16LE bytes to String
+pub fn decode_utf16le(bytes: &[u8]) -> String {
+    if bytes.len() < 2 {
+        return String::new();
+    }
+
+    // Convert bytes to u16 values
+    let u16_values: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+        .take_while(|&c| c != 0) // Stop at null terminator
+        .collect();
+
+    String::from_utf16_lossy(&u16_values)
+}
+
+/// Decode guest clipboard text in the given clipboard_format
+///
+/// 8-bit text is decoded with `code_page`, or a guessed one if None.
+pub fn decode_text(format: u32, data: &[u8], code_page: Option<CodePage>) -> String {
+    if format == clipboard_format::UNICODE {
+        // UTF-16LE from Windows
+        decode_utf16le(data)
+    } else {
+        let code_page = code_page.unwrap_or_else(|| codepage::detect(data, CodePage::Cp437));
+        code_page.decode(data)
+    }
+}
+
+/// Apply a direction's line ending and trailing NUL policy to text
+pub fn normalize(text: &str, policy: &TextNormalization) -> String {
+    let text = if policy.strip_trailing_nulls {
+        text.trim_end_matches('\0')
+    } else {
+        text
+    };
+    match policy.newlines {
+        NewlineMode::Keep => text.to_string(),
+        NewlineMode::Lf => text.replace("\r\n", "\n"),
+        NewlineMode::Crlf => {
+            let mut out = String::with_capacity(text.len());
+            let mut prev = None;
+            for c in text.chars() {
+                if c == '\n' && prev != Some('\r') {
+                    out.push('\r');
+                }
+                out.push(c);
+                prev = Some(c);
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_utf16le() {
+        // "Hello" in UTF-16LE
+        let bytes = [0x48, 0x00, 0x65, 0x00, 0x6C, 0x00, 0x6C, 0x00, 0x6F, 0x00, 0x00, 0x00];
+        assert_eq!(decode_utf16le(&bytes), "Hello");
+    }
+
+    #[test]
+    fn test_decode_utf16le_unicode() {
+        // "日本" in UTF-16LE
+        let bytes = [0xE5, 0x65, 0x2C, 0x67, 0x00, 0x00];
+        assert_eq!(decode_utf16le(&bytes), "日本");
+    }
+
+    #[test]
+    fn test_encode_utf16le() {
+        assert_eq!(encode_utf16le("Hi"), [0x48, 0x00, 0x69, 0x00, 0x00, 0x00]);
+        assert_eq!(decode_utf16le(&encode_utf16le("日本 🎌")), "日本 🎌");
+    }
+
+    #[test]
+    fn test_decode_text() {
+        let text = b"Gr\x81\xE1e\0";
+        assert_eq!(decode_text(clipboard_format::TEXT, text, Some(CodePage::Cp850)), "Grüße");
+        assert_eq!(decode_text(clipboard_format::TEXT, text, None), "Grüße");
+        assert_eq!(decode_text(clipboard_format::UNICODE, &encode_utf16le("Grüße"), None), "Grüße");
+    }
+
+    #[test]
+    fn test_normalize() {
+        let policy = |newlines, strip_trailing_nulls| TextNormalization { newlines, strip_trailing_nulls };
+
+        let script = "#!/bin/sh\r\necho hi\r\n\0\0";
+        assert_eq!(normalize(script, &policy(NewlineMode::Lf, true)), "#!/bin/sh\necho hi\n");
+        assert_eq!(normalize(script, &policy(NewlineMode::Keep, false)), script);
+
+        // Existing CRLF pairs are not doubled
+        assert_eq!(normalize("a\nb\r\nc\n", &policy(NewlineMode::Crlf, true)), "a\r\nb\r\nc\r\n");
+        assert_eq!(normalize("a\n\0", &policy(NewlineMode::Crlf, false)), "a\r\n\0");
+    }
+}
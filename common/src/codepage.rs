@@ -0,0 +1,302 @@
+//! OEM code pages for 8-bit text from DOS and Windows guests.
+//!
+//! CF_TEXT and DOS programs use the guest's OEM code page, so bytes above
+//! 0x7F mean different characters on a US, Central European or Russian
+//! system. `keyboard.code_page` names the guest's code page; when it is
+//! "auto" (or one without a table here) `detect` guesses it from the text.
+
+use crate::codepage_932::{CP932_LEADS, CP932_ROWS};
+
+/// Code pages with a conversion table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodePage {
+    Cp437,
+    Cp850,
+    Cp852,
+    Cp860,
+    Cp863,
+    Cp865,
+    Cp866,
+    /// Shift JIS
+    Cp932,
+}
+
+impl CodePage {
+    /// All supported code pages, in order of preference when guessing
+    pub const ALL: [CodePage; 8] = [
+        CodePage::Cp437,
+        CodePage::Cp850,
+        CodePage::Cp852,
+        CodePage::Cp866,
+        CodePage::Cp932,
+        CodePage::Cp860,
+        CodePage::Cp863,
+        CodePage::Cp865,
+    ];
+
+    /// Look up a code page by number ("850", "cp850" or "CP850")
+    ///
+    /// Returns None for "auto" and for code pages without a table.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.trim();
+        let number = name
+            .strip_prefix("cp")
+            .or_else(|| name.strip_prefix("CP"))
+            .unwrap_or(name);
+        Self::ALL.into_iter().find(|cp| cp.number().to_string() == number)
+    }
+
+    /// Code page number
+    pub fn number(&self) -> u16 {
+        match self {
+            Self::Cp437 => 437,
+            Self::Cp850 => 850,
+            Self::Cp852 => 852,
+            Self::Cp860 => 860,
+            Self::Cp863 => 863,
+            Self::Cp865 => 865,
+            Self::Cp866 => 866,
+            Self::Cp932 => 932,
+        }
+    }
+
+    /// Decode text, stopping at the first null byte
+    ///
+    /// Bytes without a character decode as U+FFFD.
+    pub fn decode(&self, data: &[u8]) -> String {
+        let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+        let data = &data[..end];
+        match self.high_table() {
+            Some(table) => data
+                .iter()
+                .map(|&b| if b < 0x80 { b as char } else { table[(b - 0x80) as usize] })
+                .collect(),
+            None => decode_cp932(data),
+        }
+    }
+
+    /// Characters for bytes 0x80-0xFF of a single-byte code page
+    fn high_table(&self) -> Option<&'static [char; 128]> {
+        match self {
+            Self::Cp437 => Some(&CP437),
+            Self::Cp850 => Some(&CP850),
+            Self::Cp852 => Some(&CP852),
+            Self::Cp860 => Some(&CP860),
+            Self::Cp863 => Some(&CP863),
+            Self::Cp865 => Some(&CP865),
+            Self::Cp866 => Some(&CP866),
+            Self::Cp932 => None,
+        }
+    }
+}
+
+/// Guess the code page of 8-bit text
+///
+/// Text in the right code page decodes to runs of letters from one script,
+/// where the wrong one gives box drawing, symbols or a jumble of scripts.
+/// Each code page is scored on that, and `preferred` wins ties (plain ASCII
+/// is a tie between all of them).
+pub fn detect(data: &[u8], preferred: CodePage) -> CodePage {
+    let mut best = (preferred, score(&preferred.decode(data)));
+    for cp in CodePage::ALL {
+        let score = score(&cp.decode(data));
+        if score > best.1 {
+            best = (cp, score);
+        }
+    }
+    best.0
+}
+
+/// How much decoded text looks like words
+fn score(text: &str) -> i64 {
+    let mut score = 0;
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if c == '\u{FFFD}' {
+            // Invalid sequences rule a code page out
+            score -= 100;
+        } else if !c.is_ascii() {
+            if c.is_alphabetic() {
+                score += 1;
+                // Letters continuing a word in the same script, and not
+                // capitals after small letters
+                let continues = prev.is_some_and(|p| {
+                    p.is_alphabetic() && same_script(p, c) && !(p.is_lowercase() && c.is_uppercase())
+                });
+                if continues {
+                    score += 2;
+                }
+            } else {
+                score -= 1;
+            }
+        }
+        prev = Some(c);
+    }
+    score
+}
+
+/// Whether two letters are from the same script, judging by Unicode block
+fn same_script(a: char, b: char) -> bool {
+    let block = |c: char| match c as u32 {
+        // ASCII and Latin-1/Extended letters mix in Latin words
+        0x0000..=0x024F => 0,
+        0x0370..=0x03FF => 1,
+        0x0400..=0x04FF => 2,
+        // Kana, CJK ideographs and their full- and half-width forms
+        0x3000..=0x9FFF | 0xFF00..=0xFFEF => 3,
+        other => other >> 8,
+    };
+    block(a) == block(b)
+}
+
+/// Decode Shift JIS text
+fn decode_cp932(data: &[u8]) -> String {
+    let mut text = String::with_capacity(data.len());
+    let mut bytes = data.iter().copied();
+    while let Some(b) = bytes.next() {
+        match b {
+            0x00..=0x7F => text.push(b as char),
+            // Half-width katakana
+            0xA1..=0xDF => text.push(char::from_u32(0xFF61 + (b - 0xA1) as u32).unwrap_or('\u{FFFD}')),
+            0x81..=0x9F | 0xE0..=0xFC => {
+                let c = bytes
+                    .next()
+                    .and_then(|trail| cp932_pair(b, trail))
+                    .unwrap_or('\u{FFFD}');
+                text.push(c);
+            }
+            _ => text.push('\u{FFFD}'),
+        }
+    }
+    text
+}
+
+/// Character for a Shift JIS lead and trail byte
+fn cp932_pair(lead: u8, trail: u8) -> Option<char> {
+    if !(0x40..=0xFC).contains(&trail) {
+        return None;
+    }
+    let row = CP932_LEADS.iter().position(|&l| l == lead)?;
+    let c = CP932_ROWS[row].chars().nth((trail - 0x40) as usize)?;
+    (c != '\u{FFFD}').then_some(c)
+}
+
+/// Code page 437 (US English), bytes 0x80-0xFF
+const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Code page 850 (Multilingual Latin-1), bytes 0x80-0xFF
+const CP850: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '×', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '®', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'À', '©', '╣', '║', '╗', '╝', '¢', '¥', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'ã', 'Ã', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'ð', 'Ð', 'Ê', 'Ë', 'È', 'ı', 'Í', 'Î', 'Ï', '┘', '┌', '█', '▄', '¦', 'Ì', '▀',
+    'Ó', 'ß', 'Ô', 'Ò', 'õ', 'Õ', 'µ', 'þ', 'Þ', 'Ú', 'Û', 'Ù', 'ý', 'Ý', '¯', '´',
+    '\u{AD}', '±', '‗', '¾', '¶', '§', '÷', '¸', '°', '¨', '·', '¹', '³', '²', '■', '\u{A0}',
+];
+
+/// Code page 852 (Central European), bytes 0x80-0xFF
+const CP852: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'ů', 'ć', 'ç', 'ł', 'ë', 'Ő', 'ő', 'î', 'Ź', 'Ä', 'Ć',
+    'É', 'Ĺ', 'ĺ', 'ô', 'ö', 'Ľ', 'ľ', 'Ś', 'ś', 'Ö', 'Ü', 'Ť', 'ť', 'Ł', '×', 'č',
+    'á', 'í', 'ó', 'ú', 'Ą', 'ą', 'Ž', 'ž', 'Ę', 'ę', '¬', 'ź', 'Č', 'ş', '«', '»',
+    '░', '▒', '▓', '│', '┤', 'Á', 'Â', 'Ě', 'Ş', '╣', '║', '╗', '╝', 'Ż', 'ż', '┐',
+    '└', '┴', '┬', '├', '─', '┼', 'Ă', 'ă', '╚', '╔', '╩', '╦', '╠', '═', '╬', '¤',
+    'đ', 'Đ', 'Ď', 'Ë', 'ď', 'Ň', 'Í', 'Î', 'ě', '┘', '┌', '█', '▄', 'Ţ', 'Ů', '▀',
+    'Ó', 'ß', 'Ô', 'Ń', 'ń', 'ň', 'Š', 'š', 'Ŕ', 'Ú', 'ŕ', 'Ű', 'ý', 'Ý', 'ţ', '´',
+    '\u{AD}', '˝', '˛', 'ˇ', '˘', '§', '÷', '¸', '°', '¨', '˙', 'ű', 'Ř', 'ř', '■', '\u{A0}',
+];
+
+/// Code page 860 (Portuguese), bytes 0x80-0xFF
+const CP860: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ã', 'à', 'Á', 'ç', 'ê', 'Ê', 'è', 'Í', 'Ô', 'ì', 'Ã', 'Â',
+    'É', 'À', 'È', 'ô', 'õ', 'ò', 'Ú', 'ù', 'Ì', 'Õ', 'Ü', '¢', '£', 'Ù', '₧', 'Ó',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', 'Ò', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Code page 863 (Canadian French), bytes 0x80-0xFF
+const CP863: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'Â', 'à', '¶', 'ç', 'ê', 'ë', 'è', 'ï', 'î', '‗', 'À', '§',
+    'É', 'È', 'Ê', 'ô', 'Ë', 'Ï', 'û', 'ù', '¤', 'Ô', 'Ü', '¢', '£', 'Ù', 'Û', 'ƒ',
+    '¦', '´', 'ó', 'ú', '¨', '¸', '³', '¯', 'Î', '⌐', '¬', '½', '¼', '¾', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Code page 865 (Nordic), bytes 0x80-0xFF
+const CP865: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', 'ø', '£', 'Ø', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '¤',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Code page 866 (Cyrillic), bytes 0x80-0xFF
+const CP866: [char; 128] = [
+    'А', 'Б', 'В', 'Г', 'Д', 'Е', 'Ж', 'З', 'И', 'Й', 'К', 'Л', 'М', 'Н', 'О', 'П',
+    'Р', 'С', 'Т', 'У', 'Ф', 'Х', 'Ц', 'Ч', 'Ш', 'Щ', 'Ъ', 'Ы', 'Ь', 'Э', 'Ю', 'Я',
+    'а', 'б', 'в', 'г', 'д', 'е', 'ж', 'з', 'и', 'й', 'к', 'л', 'м', 'н', 'о', 'п',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'р', 'с', 'т', 'у', 'ф', 'х', 'ц', 'ч', 'ш', 'щ', 'ъ', 'ы', 'ь', 'э', 'ю', 'я',
+    'Ё', 'ё', 'Є', 'є', 'Ї', 'ї', 'Ў', 'ў', '°', '∙', '·', '√', '№', '¤', '■', '\u{A0}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // "Café" and a box corner
+        assert_eq!(CodePage::Cp437.decode(b"Caf\x82 \xC9\0junk"), "Café ╔");
+        assert_eq!(CodePage::Cp850.decode(b"\x9D"), "Ø");
+        assert_eq!(CodePage::Cp852.decode(b"\xA5\xA4"), "ąĄ");
+        assert_eq!(CodePage::Cp866.decode(b"\x8F\xE0\xA8\xA2\xA5\xE2"), "Привет");
+        // "日本" and half-width "ｱ"
+        assert_eq!(CodePage::Cp932.decode(b"\x93\xFA\x96\x7B\xB1"), "日本ｱ");
+        assert_eq!(CodePage::Cp932.decode(b"\x93"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(CodePage::from_name("850"), Some(CodePage::Cp850));
+        assert_eq!(CodePage::from_name("cp866"), Some(CodePage::Cp866));
+        assert_eq!(CodePage::from_name("auto"), None);
+        assert_eq!(CodePage::from_name("1252"), None);
+    }
+
+    #[test]
+    fn test_detect() {
+        let russian = b"\x8F\xE0\xA8\xA2\xA5\xE2, \xAC\xA8\xE0";
+        assert_eq!(detect(russian, CodePage::Cp437), CodePage::Cp866);
+        let japanese = b"\x93\xFA\x96\x7B\x8C\xEA";
+        assert_eq!(detect(japanese, CodePage::Cp437), CodePage::Cp932);
+        // Shared by 437 and 850, so the preference decides
+        assert_eq!(detect(b"Caf\x82", CodePage::Cp850), CodePage::Cp850);
+        assert_eq!(detect(b"plain", CodePage::Cp437), CodePage::Cp437);
+    }
+}
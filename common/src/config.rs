@@ -4,30 +4,93 @@
 //! located at ~/.config/rising-sun/config.toml (or XDG_CONFIG_HOME).
 
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::image_ref;
+
+/// Layout of the configuration file this build writes; files of older
+/// versions are upgraded on load (see `config_storage::migrate`)
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Configuration file this process uses instead of the default one
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
 
 /// Main configuration structure containing all persistent settings
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct AppConfig {
+    /// Layout version of the file, `CONFIG_VERSION` when written
+    pub schema_version: u32,
     /// General application settings
     pub general: GeneralConfig,
     /// Display/presentation settings
     pub display: DisplayConfig,
     /// Keyboard settings
     pub keyboard: KeyboardConfig,
+    /// Recorded keystroke macros, by name
+    pub macros: Vec<KeyMacro>,
     /// Mouse settings
     pub mouse: MouseConfig,
+    /// Host hotkey bindings
+    pub hotkeys: HotkeyConfig,
     /// Clipboard settings
     pub clipboard: ClipboardConfig,
     /// Network adapter settings
     pub network: NetworkConfig,
+    /// Audio output settings
+    pub audio: AudioConfig,
+    /// MIDI output settings
+    pub midi: MidiConfig,
+    /// Game port (joystick) settings
+    pub gameport: GameportConfig,
     /// Storage devices (disks, CD-ROM, floppy)
     pub storage: StorageConfig,
     /// Host directory to guest drive letter mappings
     pub drive_mappings: Vec<DriveMapping>,
+    /// Guest tools CD
+    pub guest_tools: GuestToolsConfig,
     /// Recently used files
     pub recent: RecentFiles,
+    /// Remote access and LAN advertisement
+    pub remote: RemoteConfig,
+    /// Searchable screen history
+    pub history: HistoryConfig,
+    /// Log verbosity and log files
+    pub log: LogConfig,
+    /// BIOS image loaded into the card instead of its own
+    pub bios: BiosConfig,
+    /// Per-card settings for machines with more than one SunPCi card
+    pub cards: Vec<CardConfig>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            schema_version: CONFIG_VERSION,
+            general: GeneralConfig::default(),
+            display: DisplayConfig::default(),
+            keyboard: KeyboardConfig::default(),
+            macros: Vec::new(),
+            mouse: MouseConfig::default(),
+            hotkeys: HotkeyConfig::default(),
+            clipboard: ClipboardConfig::default(),
+            network: NetworkConfig::default(),
+            audio: AudioConfig::default(),
+            midi: MidiConfig::default(),
+            gameport: GameportConfig::default(),
+            storage: StorageConfig::default(),
+            drive_mappings: Vec::new(),
+            guest_tools: GuestToolsConfig::default(),
+            recent: RecentFiles::default(),
+            remote: RemoteConfig::default(),
+            history: HistoryConfig::default(),
+            log: LogConfig::default(),
+            bios: BiosConfig::default(),
+            cards: Vec::new(),
+        }
+    }
 }
 
 /// General application settings
@@ -40,6 +103,9 @@ pub struct GeneralConfig {
     pub save_state_on_exit: bool,
     /// Confirm before closing while session is running
     pub confirm_on_close: bool,
+    /// Seconds the guest is given to shut down when asked before the
+    /// session is stopped anyway
+    pub shutdown_timeout_secs: u32,
     /// Show status bar
     pub show_status_bar: bool,
     /// Remember window position and size
@@ -52,6 +118,28 @@ pub struct GeneralConfig {
     pub window_width: Option<u32>,
     /// Window height
     pub window_height: Option<u32>,
+    /// Name of the screen the window was on
+    pub window_screen: Option<String>,
+    /// Stop polling the driver for audio, network and clipboard updates
+    /// while no session is running
+    pub suspend_polling_when_stopped: bool,
+    /// Reset the session when the guest crashes or hangs; superseded by
+    /// `crash_action`
+    pub auto_restart_on_crash: bool,
+    /// What to do when the guest crashes or hangs
+    pub crash_action: CrashAction,
+    /// Shell command run by `CrashAction::RunCommand`, given the path of
+    /// the screenshot as its last argument
+    pub crash_command: String,
+    /// Seconds without a sign of life before the guest counts as hung
+    /// (0 = only crashes are detected)
+    pub hang_timeout_secs: u32,
+    /// Give a drive mapping to the driver again when its host directory
+    /// comes back, e.g. a USB stick that was plugged in again
+    pub reapply_drive_mappings: bool,
+    /// Map removable drives the host mounts to a free drive letter without
+    /// asking first
+    pub auto_map_removable_media: bool,
 }
 
 impl Default for GeneralConfig {
@@ -60,16 +148,50 @@ impl Default for GeneralConfig {
             auto_start: false,
             save_state_on_exit: true,
             confirm_on_close: true,
+            shutdown_timeout_secs: 30,
             show_status_bar: true,
             remember_window_geometry: true,
             window_x: None,
             window_y: None,
             window_width: None,
             window_height: None,
+            window_screen: None,
+            suspend_polling_when_stopped: true,
+            auto_restart_on_crash: false,
+            crash_action: CrashAction::Notify,
+            crash_command: String::new(),
+            hang_timeout_secs: 0,
+            reapply_drive_mappings: false,
+            auto_map_removable_media: false,
+        }
+    }
+}
+
+impl GeneralConfig {
+    /// Crash action, honoring `auto_restart_on_crash` from older configs
+    pub fn on_crash(&self) -> CrashAction {
+        if self.crash_action == CrashAction::Notify && self.auto_restart_on_crash {
+            CrashAction::Restart
+        } else {
+            self.crash_action
         }
     }
 }
 
+/// What happens when the guest crashes or hangs
+///
+/// A screenshot of the guest display is saved in every case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum CrashAction {
+    /// Tell the user
+    #[default]
+    Notify,
+    /// Reset the session
+    Restart,
+    /// Tell the user and run `crash_command`
+    RunCommand,
+}
+
 /// Display presentation settings (host-side only)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -80,14 +202,38 @@ pub struct DisplayConfig {
     pub maintain_aspect_ratio: bool,
     /// Use integer scaling only (pixel-perfect)
     pub integer_scaling: bool,
-    /// Apply CRT scanline effect
+    /// Apply CRT scanline effect (the kind chosen by crt_effect)
     pub scanline_effect: bool,
     /// Scanline intensity (0.0 - 1.0)
     pub scanline_intensity: f32,
+    /// CRT effect applied when scanline_effect is on
+    pub crt_effect: CrtEffect,
+    /// Strength of the aperture grille's phosphor stripes (0.0 - 1.0)
+    pub mask_intensity: f32,
+    /// Screen curvature of the curvature effect (0.0 - 0.5)
+    pub curvature: f32,
     /// Start in fullscreen mode
     pub start_fullscreen: bool,
     /// Hide menu bar in fullscreen
     pub fullscreen_hide_menu: bool,
+    /// Screen to go fullscreen on, counting from 0 (None = the window's screen)
+    pub fullscreen_screen: Option<u32>,
+    /// Apply palette changes only when a frame is presented, so palette
+    /// cycling effects don't tear (indexed 8-bit modes only)
+    pub palette_sync: bool,
+    /// When framebuffer snapshots are taken relative to the render loop
+    pub presentation_mode: PresentationMode,
+    /// Deinterlacing method for interlaced video modes
+    pub deinterlace: DeinterlaceMode,
+    /// Lower the refresh rate while the guest screen is unchanged; with
+    /// vsync, frames are skipped until the guest draws again
+    pub adaptive_refresh: bool,
+    /// Refresh rate while the guest is drawing, in Hz
+    pub active_refresh_hz: u32,
+    /// Refresh rate while the screen is unchanged, in Hz
+    pub idle_refresh_hz: u32,
+    /// How long the screen stays unchanged before the idle rate applies
+    pub idle_after_ms: u32,
 }
 
 impl Default for DisplayConfig {
@@ -98,8 +244,19 @@ impl Default for DisplayConfig {
             integer_scaling: false,
             scanline_effect: false,
             scanline_intensity: 0.3,
+            crt_effect: CrtEffect::Scanlines,
+            mask_intensity: 0.25,
+            curvature: 0.1,
             start_fullscreen: false,
             fullscreen_hide_menu: true,
+            fullscreen_screen: None,
+            palette_sync: true,
+            presentation_mode: PresentationMode::Vsync,
+            deinterlace: DeinterlaceMode::Bob,
+            adaptive_refresh: true,
+            active_refresh_hz: 60,
+            idle_refresh_hz: 5,
+            idle_after_ms: 500,
         }
     }
 }
@@ -118,13 +275,45 @@ pub enum ScalingMode {
     Fixed(u32),
 }
 
+/// Display presentation modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum PresentationMode {
+    /// Snapshot on a fixed ~60 Hz timer, independent of the display refresh
+    Immediate,
+    /// Snapshot once per presented frame, aligned with the host's vsync
+    #[default]
+    Vsync,
+}
+
+/// Deinterlacing methods
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum DeinterlaceMode {
+    /// Line-double each field (no combing, half vertical resolution)
+    #[default]
+    Bob,
+    /// Interleave the current field with the previous one (full resolution)
+    Weave,
+}
+
+/// CRT post-processing effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum CrtEffect {
+    /// Dark gaps between the guest's scanlines
+    #[default]
+    Scanlines,
+    /// Scanlines over vertical red, green and blue phosphor stripes
+    ApertureGrille,
+    /// Scanlines on a curved screen
+    Curvature,
+}
+
 /// Keyboard settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct KeyboardConfig {
     /// Keyboard layout (e.g., "us", "uk", "de")
     pub layout: String,
-    /// DOS code page (e.g., "437", "850")
+    /// DOS code page (e.g., "437", "850", or "auto" to detect it)
     pub code_page: String,
     /// Key used to release keyboard capture
     pub release_key: String,
@@ -136,6 +325,13 @@ pub struct KeyboardConfig {
     pub sync_num_lock: bool,
     /// Synchronize Scroll Lock state with host
     pub sync_scroll_lock: bool,
+    /// Characters per second when typing the clipboard into the guest
+    pub type_chars_per_sec: u32,
+    /// Keystroke macro typed into the guest after it starts, e.g.
+    /// "{F8}{Wait 1000}3{Enter}" (see scancode::macro_keystrokes)
+    pub boot_macro: String,
+    /// Time from the start of the session to typing the boot macro
+    pub boot_macro_delay_ms: u32,
 }
 
 impl Default for KeyboardConfig {
@@ -148,10 +344,36 @@ impl Default for KeyboardConfig {
             sync_caps_lock: true,
             sync_num_lock: true,
             sync_scroll_lock: true,
+            type_chars_per_sec: 20,
+            boot_macro: String::new(),
+            boot_macro_delay_ms: 2000,
         }
     }
 }
 
+/// A recorded keystroke macro (see keymacro)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyMacro {
+    /// Name it is played by
+    pub name: String,
+    /// Key presses and releases, in order
+    pub keys: Vec<MacroKey>,
+}
+
+/// A key press or release in a keystroke macro
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MacroKey {
+    /// XT scancode
+    pub scancode: u32,
+    /// Whether it is an extended (E0) key
+    #[serde(default)]
+    pub extended: bool,
+    pub pressed: bool,
+    /// Time since the key before, in ms
+    #[serde(default)]
+    pub delay_ms: u32,
+}
+
 /// Mouse settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -164,6 +386,8 @@ pub struct MouseConfig {
     pub swap_buttons: bool,
     /// Simulate middle button with left+right click
     pub simulate_middle_button: bool,
+    /// Maximum delay between left and right presses to count as a middle click (ms)
+    pub middle_button_window_ms: u32,
 }
 
 impl Default for MouseConfig {
@@ -173,6 +397,7 @@ impl Default for MouseConfig {
             capture_mode: MouseCaptureMode::ClickToCapture,
             swap_buttons: false,
             simulate_middle_button: false,
+            middle_button_window_ms: 50,
         }
     }
 }
@@ -198,6 +423,38 @@ pub enum MouseCaptureMode {
     Seamless,
 }
 
+/// Host hotkey bindings
+///
+/// Each binding is a key combination such as "Ctrl+Alt+F" or "RightCtrl".
+/// Hotkeys are intercepted before input is forwarded to the guest.
+/// An empty string disables the binding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HotkeyConfig {
+    /// Release keyboard and mouse capture
+    pub release_capture: String,
+    /// Toggle fullscreen mode
+    pub toggle_fullscreen: String,
+    /// Send Ctrl+Alt+Del to the guest
+    pub send_ctrl_alt_del: String,
+    /// Eject the CD-ROM
+    pub eject_cdrom: String,
+    /// Save a screenshot of the guest display
+    pub screenshot: String,
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            release_capture: "RightCtrl".to_string(),
+            toggle_fullscreen: "RightCtrl+F".to_string(),
+            send_ctrl_alt_del: "RightCtrl+Del".to_string(),
+            eject_cdrom: "RightCtrl+E".to_string(),
+            screenshot: "RightCtrl+S".to_string(),
+        }
+    }
+}
+
 /// Clipboard sharing settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
@@ -214,6 +471,10 @@ pub struct ClipboardConfig {
     pub share_images: bool,
     /// Share file references
     pub share_files: bool,
+    /// Cleanup of text copied in the guest before it reaches the host
+    pub guest_to_host_text: TextNormalization,
+    /// Cleanup of text copied on the host before it reaches the guest
+    pub host_to_guest_text: TextNormalization,
 }
 
 impl Default for ClipboardConfig {
@@ -225,10 +486,40 @@ impl Default for ClipboardConfig {
             share_rich_text: true,
             share_images: true,
             share_files: false,
+            guest_to_host_text: TextNormalization {
+                newlines: NewlineMode::Lf,
+                strip_trailing_nulls: true,
+            },
+            host_to_guest_text: TextNormalization {
+                newlines: NewlineMode::Crlf,
+                strip_trailing_nulls: true,
+            },
         }
     }
 }
 
+/// How clipboard text is cleaned up in one direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TextNormalization {
+    /// Line endings to convert to
+    pub newlines: NewlineMode,
+    /// Drop NUL characters at the end of the text
+    pub strip_trailing_nulls: bool,
+}
+
+/// Line endings for clipboard text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum NewlineMode {
+    /// Leave line endings as they are
+    #[default]
+    Keep,
+    /// Unix line endings (CRLF becomes LF)
+    Lf,
+    /// DOS/Windows line endings (a lone LF becomes CRLF)
+    Crlf,
+}
+
 /// Clipboard sharing direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 pub enum ClipboardDirection {
@@ -238,12 +529,159 @@ pub enum ClipboardDirection {
     GuestToHost,
 }
 
+/// Audio output settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioConfig {
+    /// Turn down other host applications while the guest plays audio
+    pub duck_host_audio: bool,
+    /// Volume ducked applications are turned down to, in percent of their own
+    pub duck_level: u32,
+    /// Pass the host's default microphone to the guest while it records
+    pub microphone: bool,
+    /// Guest audio buffered ahead of the output device, in milliseconds:
+    /// less answers sooner, more rides out a busy host without dropouts
+    pub latency_ms: u32,
+}
+
+impl AudioConfig {
+    /// Shortest buffer that can be asked for
+    pub const MIN_LATENCY_MS: u32 = 20;
+    /// Longest buffer that can be asked for
+    pub const MAX_LATENCY_MS: u32 = 1000;
+
+    /// `latency_ms`, within the range the playback supports
+    pub fn latency(&self) -> u32 {
+        self.latency_ms.clamp(Self::MIN_LATENCY_MS, Self::MAX_LATENCY_MS)
+    }
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        Self {
+            duck_host_audio: false,
+            duck_level: 30,
+            microphone: false,
+            latency_ms: 250,
+        }
+    }
+}
+
+/// MIDI output settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct MidiConfig {
+    /// Where the guest's MPU-401 output is played
+    pub output: MidiOutput,
+    /// Sequencer port for `MidiOutput::Sequencer`: "client:port", or a
+    /// client name (empty = the first synthesizer found)
+    pub port: String,
+}
+
+/// Where the guest's MIDI music goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum MidiOutput {
+    /// Dropped
+    Off,
+    /// The built-in synthesizer, through the audio output
+    #[default]
+    SoftSynth,
+    /// A port on the host's ALSA sequencer (a hardware synth, FluidSynth,
+    /// TiMidity...)
+    Sequencer,
+}
+
+/// Game port settings: which host gamepad drives the guest's joysticks,
+/// and how
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameportConfig {
+    /// Pass a host gamepad to the guest's game port
+    pub enabled: bool,
+    /// Gamepad to use: an evdev path (/dev/input/by-id/...) or part of its
+    /// name (empty = the first one found)
+    pub device: String,
+    /// Guest axes in port order: joystick A X and Y, joystick B X and Y
+    pub axes: Vec<AxisMapping>,
+    /// Guest buttons in port order: A1, A2, B1, B2
+    pub buttons: Vec<ButtonMapping>,
+}
+
+impl Default for GameportConfig {
+    fn default() -> Self {
+        // Left stick to joystick A, right stick to joystick B, face buttons
+        // (or a joystick's trigger and thumb buttons) to the four buttons
+        Self {
+            enabled: false,
+            device: String::new(),
+            axes: [0x00, 0x01, 0x03, 0x04].into_iter().map(AxisMapping::new).collect(),
+            buttons: [[0x130, 0x120], [0x131, 0x121], [0x133, 0x122], [0x134, 0x123]]
+                .into_iter()
+                .map(|keys| ButtonMapping { keys: keys.to_vec() })
+                .collect(),
+        }
+    }
+}
+
+/// Where a guest axis comes from, and its calibration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AxisMapping {
+    /// evdev absolute axis (ABS_X = 0, ABS_Y = 1, ABS_RX = 3, ABS_HAT0X =
+    /// 16...); none leaves the guest axis unplugged
+    pub source: Option<u16>,
+    /// Turn the axis around
+    pub invert: bool,
+    /// Raw value at the low end (none = the device's own)
+    pub min: Option<i32>,
+    /// Raw value at rest (none = halfway)
+    pub center: Option<i32>,
+    /// Raw value at the high end (none = the device's own)
+    pub max: Option<i32>,
+    /// Travel around the centre that reads as centred, in percent
+    pub dead_zone: u32,
+}
+
+impl AxisMapping {
+    /// An uncalibrated mapping of evdev axis `source`
+    pub fn new(source: u16) -> Self {
+        Self {
+            source: Some(source),
+            ..Self::default()
+        }
+    }
+}
+
+impl Default for AxisMapping {
+    fn default() -> Self {
+        Self {
+            source: None,
+            invert: false,
+            min: None,
+            center: None,
+            max: None,
+            dead_zone: 5,
+        }
+    }
+}
+
+/// Host buttons that press a guest button
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ButtonMapping {
+    /// evdev key codes (BTN_SOUTH = 0x130, BTN_TRIGGER = 0x120...); any of
+    /// them held holds the button
+    pub keys: Vec<u16>,
+}
+
 /// Network adapter settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct NetworkConfig {
     /// Enable network adapter
     pub enabled: bool,
+    /// How the adapter reaches the host network
+    pub mode: NetworkMode,
     /// Host network interface to bridge
     pub host_interface: String,
     /// MAC address (empty = auto-generate)
@@ -252,20 +690,191 @@ pub struct NetworkConfig {
     pub irq: u8,
     /// Enable promiscuous mode
     pub promiscuous: bool,
+    /// Create the TAP interface for the session instead of using an existing one
+    pub auto_tap: bool,
+    /// Name of the TAP interface created when auto_tap is set
+    pub tap_name: String,
+    /// Bridge to attach the created TAP to (empty = none)
+    pub bridge: String,
+    /// Create the bridge if it does not exist
+    pub create_bridge: bool,
 }
 
 impl Default for NetworkConfig {
     fn default() -> Self {
         Self {
             enabled: false,
+            mode: NetworkMode::Bridged,
             host_interface: String::new(),
             mac_address: String::new(),
             irq: 10,
             promiscuous: false,
+            auto_tap: false,
+            tap_name: "sunpci0".to_string(),
+            bridge: String::new(),
+            create_bridge: false,
         }
     }
 }
 
+/// Network attachment modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum NetworkMode {
+    /// Bridge a TAP interface to the host network (needs CAP_NET_ADMIN)
+    #[default]
+    Bridged,
+    /// User-space NAT: outbound connections through host sockets, no root
+    Nat,
+}
+
+/// Remote access settings
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct RemoteConfig {
+    /// Advertise the running session on the LAN with mDNS (_risingsun._tcp)
+    pub advertise: bool,
+    /// Profile name other instances see (empty = host name)
+    pub profile_name: String,
+    /// Port of the control API, if it is listening
+    pub control_port: Option<u16>,
+    /// Address the control API listens on (None = loopback only)
+    pub control_address: Option<IpAddr>,
+    /// Shared secret clients must send with each command; required when
+    /// the control API listens beyond loopback
+    pub control_token: String,
+    /// Port of the VNC server, if one is running
+    pub vnc_port: Option<u16>,
+}
+
+impl RemoteConfig {
+    /// Where the control API listens, if it is on
+    pub fn control_addr(&self) -> Option<SocketAddr> {
+        let ip = self.control_address.unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        self.control_port.map(|port| SocketAddr::new(ip, port))
+    }
+
+    /// Whether the control API can be reached from other machines
+    pub fn control_is_remote(&self) -> bool {
+        self.control_addr().is_some_and(|addr| !addr.ip().is_loopback())
+    }
+
+    /// The configured token, if any
+    pub fn token(&self) -> Option<&str> {
+        Some(self.control_token.trim()).filter(|t| !t.is_empty())
+    }
+}
+
+/// Screen history settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    /// Record screen text and thumbnails while a session runs
+    pub enabled: bool,
+    /// Seconds between screen text captures
+    pub text_interval_secs: u32,
+    /// Seconds between thumbnails
+    pub thumbnail_interval_secs: u32,
+    /// Number of sessions kept; older ones are deleted
+    pub max_sessions: u32,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text_interval_secs: 5,
+            thumbnail_interval_secs: 60,
+            max_sessions: 20,
+        }
+    }
+}
+
+/// Logging settings
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Most detailed messages logged: "error", "warn", "info", "debug" or
+    /// "trace" (--verbose raises it for one run)
+    pub level: String,
+    /// Also write the log to files in the log directory
+    pub file: bool,
+    /// Size in KiB at which the log file is rotated
+    pub max_file_kb: u32,
+    /// Rotated log files kept besides the current one
+    pub max_files: u32,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            file: false,
+            max_file_kb: 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// Guest tools CD settings
+///
+/// The tools themselves are the guest programs and drivers of the original
+/// SunPCi software, which cannot be shipped here; `source` is a copy of its
+/// install directory (see `guest_tools`).
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct GuestToolsConfig {
+    /// SunPCi software directory (None = look in /opt/SUNWspci*)
+    pub source: Option<PathBuf>,
+}
+
+/// BIOS image settings
+///
+/// Without a path the card runs the BIOS in its own flash. An image chosen
+/// here is checked at session start (see `bios`); its SHA-256 is recorded
+/// when it is chosen so a changed or damaged file is noticed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BiosConfig {
+    /// BIOS image to load (None = the card's own BIOS)
+    pub path: Option<PathBuf>,
+    /// Refuse to start when the image no longer matches `sha256`
+    pub verify_checksum: bool,
+    /// SHA-256 of the image when it was chosen (hex)
+    pub sha256: Option<String>,
+    /// Images known to work, by checksum
+    pub known_good: Vec<KnownBios>,
+}
+
+impl Default for BiosConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            verify_checksum: true,
+            sha256: None,
+            known_good: Vec::new(),
+        }
+    }
+}
+
+/// A BIOS image known to work with the card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct KnownBios {
+    /// SHA-256 of the image (hex)
+    pub sha256: String,
+    /// Version shown for it, e.g. the BIOS date
+    pub version: String,
+    /// Where it came from or what it is for
+    pub note: String,
+}
+
+impl BiosConfig {
+    /// The known-good entry of an image, by checksum
+    pub fn known(&self, sha256: &str) -> Option<&KnownBios> {
+        self.known_good.iter().find(|k| k.sha256.eq_ignore_ascii_case(sha256))
+    }
+}
+
 /// Storage device configuration
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 #[serde(default)]
@@ -280,6 +889,35 @@ pub struct StorageConfig {
     pub floppy_a: FloppyConfig,
     /// Floppy drive B:
     pub floppy_b: FloppyConfig,
+    /// Extra directories searched for disk images that moved
+    pub search_paths: Vec<PathBuf>,
+    /// Drive the guest boots from
+    pub boot_device: BootDevice,
+}
+
+impl StorageConfig {
+    /// Boot drive, honoring `boot_from_cd` from older configs
+    pub fn boot_order(&self) -> BootDevice {
+        if self.boot_device == BootDevice::Default && self.cdrom.boot_from_cd {
+            BootDevice::Cdrom
+        } else {
+            self.boot_device
+        }
+    }
+}
+
+/// Drive the guest BIOS boots from
+///
+/// A drive with nothing bootable in it falls back to the default order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum BootDevice {
+    /// Hard disk C:, then the CD-ROM
+    #[default]
+    Default,
+    HardDisk,
+    /// El Torito boot from the CD-ROM
+    Cdrom,
+    Floppy,
 }
 
 /// Hard disk configuration
@@ -289,6 +927,12 @@ pub struct DiskConfig {
     pub path: PathBuf,
     /// Whether this disk is bootable
     pub bootable: bool,
+    /// Content id used to find the image if it moves (see image_ref)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
+    /// Copy-on-write child mounted in place of a template (see overlay)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub overlay: Option<PathBuf>,
 }
 
 /// CD-ROM drive configuration
@@ -299,8 +943,11 @@ pub struct CdromConfig {
     pub mounted_iso: Option<PathBuf>,
     /// Auto-mount this ISO on session start
     pub auto_mount: bool,
-    /// Boot from CD-ROM (El Torito)
+    /// Boot from CD-ROM (El Torito); superseded by `StorageConfig::boot_device`
     pub boot_from_cd: bool,
+    /// Where the ISO was downloaded from, offered if it goes missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
 }
 
 impl Default for CdromConfig {
@@ -309,6 +956,7 @@ impl Default for CdromConfig {
             mounted_iso: None,
             auto_mount: true,
             boot_from_cd: false,
+            source_url: None,
         }
     }
 }
@@ -323,6 +971,9 @@ pub struct FloppyConfig {
     pub auto_mount: bool,
     /// Write protect the floppy
     pub write_protected: bool,
+    /// Where the image was downloaded from, offered if it goes missing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
 }
 
 impl Default for FloppyConfig {
@@ -331,6 +982,7 @@ impl Default for FloppyConfig {
             mounted_image: None,
             auto_mount: true,
             write_protected: false,
+            source_url: None,
         }
     }
 }
@@ -346,6 +998,61 @@ pub struct DriveMapping {
     pub description: String,
     /// Whether this mapping is enabled
     pub enabled: bool,
+    /// Whether the guest may only read the drive
+    #[serde(default)]
+    pub readonly: bool,
+    /// Time zone the guest sees file times in
+    #[serde(default)]
+    pub timestamps: TimestampPolicy,
+    /// How long host names are shortened to 8.3 names
+    #[serde(default)]
+    pub mangling: Mangling,
+    /// Case of the names the guest sees
+    #[serde(default)]
+    pub name_case: NameCase,
+    /// Leave out host files and directories whose names start with '.'
+    #[serde(default)]
+    pub hide_dotfiles: bool,
+    /// Most the guest may store under the host path, in MB (0 = no limit)
+    #[serde(default)]
+    pub max_size_mb: u32,
+}
+
+/// Time zone of the file times a mapped drive shows the guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum TimestampPolicy {
+    /// UTC, unconverted
+    Utc,
+    /// The host's local time, following its daylight saving changes
+    #[default]
+    Local,
+    /// A fixed offset east of UTC, in minutes
+    Offset(i32),
+}
+
+/// How a mapped drive shortens host names that are not valid 8.3 names
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Mangling {
+    /// Cut short with a "~1", "~2"... tail, as Windows does
+    #[default]
+    NumericTail,
+    /// Cut short with a tail from a hash of the whole name, so a file
+    /// keeps its short name whatever else is in the directory
+    Hash,
+    /// Cut short with no tail; names alike up to the cut clash
+    Truncate,
+}
+
+/// Case of the names a mapped drive shows the guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum NameCase {
+    /// Upper case, as DOS expects
+    #[default]
+    Upper,
+    /// Lower case, matching host names that are all lower case
+    Lower,
+    /// As the host name has it
+    Preserve,
 }
 
 impl Default for DriveMapping {
@@ -355,12 +1062,21 @@ impl Default for DriveMapping {
             host_path: PathBuf::new(),
             description: String::new(),
             enabled: true,
+            readonly: false,
+            timestamps: TimestampPolicy::Local,
+            mangling: Mangling::NumericTail,
+            name_case: NameCase::Upper,
+            hide_dotfiles: false,
+            max_size_mb: 0,
         }
     }
 }
 
 /// Recently used files for quick access
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+///
+/// Each category lists its newest files first, after the ones pinned as
+/// favourites; pinned files are kept however many others are added.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct RecentFiles {
     /// Recently used disk images
@@ -369,42 +1085,232 @@ pub struct RecentFiles {
     pub iso_files: Vec<PathBuf>,
     /// Recently used floppy images
     pub floppy_images: Vec<PathBuf>,
-    /// Maximum number of recent files to remember per category
+    /// Files of any category pinned to the top of their list
+    pub pinned: Vec<PathBuf>,
+    /// Maximum number of recent files to remember per category, not
+    /// counting pinned ones
     #[serde(default = "default_max_recent")]
     pub max_recent: usize,
 }
 
+impl Default for RecentFiles {
+    fn default() -> Self {
+        Self {
+            disk_images: Vec::new(),
+            iso_files: Vec::new(),
+            floppy_images: Vec::new(),
+            pinned: Vec::new(),
+            max_recent: default_max_recent(),
+        }
+    }
+}
+
 fn default_max_recent() -> usize {
     10
 }
 
+/// A list of recent files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecentCategory {
+    DiskImage,
+    Iso,
+    Floppy,
+}
+
+impl RecentCategory {
+    /// Parse a category name as used by the UI
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "disk" => Some(Self::DiskImage),
+            "iso" | "cdrom" => Some(Self::Iso),
+            "floppy" => Some(Self::Floppy),
+            _ => None,
+        }
+    }
+}
+
 impl RecentFiles {
+    /// Files of a category, pinned ones first
+    pub fn list(&self, category: RecentCategory) -> &[PathBuf] {
+        match category {
+            RecentCategory::DiskImage => &self.disk_images,
+            RecentCategory::Iso => &self.iso_files,
+            RecentCategory::Floppy => &self.floppy_images,
+        }
+    }
+
+    fn list_mut(&mut self, category: RecentCategory) -> &mut Vec<PathBuf> {
+        match category {
+            RecentCategory::DiskImage => &mut self.disk_images,
+            RecentCategory::Iso => &mut self.iso_files,
+            RecentCategory::Floppy => &mut self.floppy_images,
+        }
+    }
+
+    /// Whether a file is pinned
+    pub fn is_pinned(&self, path: &Path) -> bool {
+        self.pinned.iter().any(|p| p == path)
+    }
+
+    /// Add a file to the front of its category, after the pinned ones
+    pub fn add(&mut self, category: RecentCategory, path: PathBuf) {
+        let list = self.list_mut(category);
+        list.retain(|p| p != &path);
+        list.insert(0, path);
+        self.arrange(category);
+    }
+
     /// Add a disk image to recent files
     pub fn add_disk_image(&mut self, path: PathBuf) {
-        self.add_to_list(&mut self.disk_images.clone(), path);
+        self.add(RecentCategory::DiskImage, path);
     }
 
     /// Add an ISO to recent files
     pub fn add_iso(&mut self, path: PathBuf) {
-        self.add_to_list(&mut self.iso_files.clone(), path);
+        self.add(RecentCategory::Iso, path);
     }
 
     /// Add a floppy image to recent files
     pub fn add_floppy_image(&mut self, path: PathBuf) {
-        self.add_to_list(&mut self.floppy_images.clone(), path);
+        self.add(RecentCategory::Floppy, path);
     }
 
-    fn add_to_list(&mut self, list: &mut Vec<PathBuf>, path: PathBuf) {
-        // Remove if already present
-        list.retain(|p| p != &path);
-        // Add to front
-        list.insert(0, path);
-        // Trim to max size
-        list.truncate(self.max_recent);
+    /// Pin a file of a category to the top of its list, or unpin it
+    pub fn set_pinned(&mut self, category: RecentCategory, path: &Path, pinned: bool) {
+        if !self.list(category).iter().any(|p| p == path) {
+            return;
+        }
+        self.pinned.retain(|p| p != path);
+        if pinned {
+            self.pinned.push(path.to_path_buf());
+        }
+        self.arrange(category);
     }
+
+    /// Forget a file, pinned or not
+    pub fn remove(&mut self, category: RecentCategory, path: &Path) {
+        self.list_mut(category).retain(|p| p != path);
+        self.pinned.retain(|p| p != path);
+    }
+
+    /// Forget the files of a category that are not pinned
+    pub fn clear(&mut self, category: RecentCategory) {
+        let pinned = std::mem::take(&mut self.pinned);
+        self.list_mut(category).retain(|p| pinned.contains(p));
+        self.pinned = pinned;
+    }
+
+    /// Drop duplicates and files that no longer exist from every category;
+    /// true if anything was dropped
+    pub fn prune(&mut self) -> bool {
+        let mut changed = false;
+        for category in [RecentCategory::DiskImage, RecentCategory::Iso, RecentCategory::Floppy] {
+            let list = self.list_mut(category);
+            let before = list.len();
+            let mut seen = Vec::with_capacity(before);
+            list.retain(|p| {
+                let keep = !seen.contains(p) && p.exists();
+                seen.push(p.clone());
+                keep
+            });
+            changed |= list.len() != before;
+        }
+        let before = self.pinned.len();
+        let pinned: Vec<PathBuf> = std::mem::take(&mut self.pinned)
+            .into_iter()
+            .filter(|p| self.disk_images.contains(p) || self.iso_files.contains(p) || self.floppy_images.contains(p))
+            .collect();
+        self.pinned = pinned;
+        changed || self.pinned.len() != before
+    }
+
+    /// Move pinned files to the front, in the order pinned, and trim the
+    /// rest to `max_recent`
+    fn arrange(&mut self, category: RecentCategory) {
+        let max = self.max_recent;
+        let pinned = std::mem::take(&mut self.pinned);
+        let list = self.list_mut(category);
+        let (mut front, mut rest): (Vec<PathBuf>, Vec<PathBuf>) = list.drain(..).partition(|p| pinned.contains(p));
+        front.sort_by_key(|p| pinned.iter().position(|q| q == p));
+        rest.truncate(max);
+        front.append(&mut rest);
+        *list = front;
+        self.pinned = pinned;
+    }
+}
+
+/// Settings of one SunPCi card in a machine with several
+///
+/// Each card runs its own session. Sections a card leaves out fall back
+/// to the top-level ones, so card 0 usually needs no entry at all.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CardConfig {
+    /// Card index (N in /dev/sunpciN)
+    pub index: u32,
+    /// Name shown in the card selector (empty = "Card N")
+    pub name: String,
+    /// Storage devices of the card's session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageConfig>,
+    /// Network adapter of the card's session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkConfig>,
+    /// Drive mappings of the card's session
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drive_mappings: Option<Vec<DriveMapping>>,
+    /// BIOS image of the card (models differ in the BIOS they need)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bios: Option<BiosConfig>,
 }
 
 impl AppConfig {
+    /// The [[cards]] entry of card `index`, if there is one
+    pub fn card(&self, index: u32) -> Option<&CardConfig> {
+        self.cards.iter().find(|card| card.index == index)
+    }
+
+    /// Name of card `index` for display
+    pub fn card_name(&self, index: u32) -> String {
+        match self.card(index) {
+            Some(card) if !card.name.is_empty() => card.name.clone(),
+            _ => format!("Card {}", index),
+        }
+    }
+
+    /// Storage of card `index` as saved: its own section if it has one
+    pub fn card_storage_mut(&mut self, index: u32) -> &mut StorageConfig {
+        match self
+            .cards
+            .iter_mut()
+            .find(|card| card.index == index && card.storage.is_some())
+        {
+            Some(card) => card.storage.get_or_insert_with(StorageConfig::default),
+            None => &mut self.storage,
+        }
+    }
+
+    /// Settings a session on card `index` runs with: the card's sections
+    /// in place of the top-level ones
+    pub fn for_card(&self, index: u32) -> AppConfig {
+        let mut config = self.clone();
+        if let Some(card) = self.card(index) {
+            if let Some(ref storage) = card.storage {
+                config.storage = storage.clone();
+            }
+            if let Some(ref network) = card.network {
+                config.network = network.clone();
+            }
+            if let Some(ref mappings) = card.drive_mappings {
+                config.drive_mappings = mappings.clone();
+            }
+            if let Some(ref bios) = card.bios {
+                config.bios = bios.clone();
+            }
+        }
+        config
+    }
+
     /// Get the default configuration directory
     pub fn config_dir() -> PathBuf {
         if let Ok(xdg_config) = std::env::var("XDG_CONFIG_HOME") {
@@ -416,9 +1322,24 @@ impl AppConfig {
         }
     }
 
-    /// Get the default configuration file path
+    /// Get the configuration file path: the one given to
+    /// `use_config_file`, or the default one
     pub fn config_file() -> PathBuf {
-        Self::config_dir().join("config.toml")
+        match CONFIG_FILE.get() {
+            Some(path) => path.clone(),
+            None => Self::config_dir().join("config.toml"),
+        }
+    }
+
+    /// Read and write `path` instead of the default configuration file for
+    /// the rest of this process; only the first call counts
+    pub fn use_config_file(path: PathBuf) {
+        let _ = CONFIG_FILE.set(path);
+    }
+
+    /// Configuration file of the named profile, kept beside the default one
+    pub fn profile_file(name: &str) -> PathBuf {
+        Self::config_dir().join("profiles").join(format!("{}.toml", name))
     }
 
     /// Get the default data directory (for session state, etc.)
@@ -432,6 +1353,57 @@ impl AppConfig {
         }
     }
 
+    /// Directory for sockets and other files that only live while the
+    /// frontend runs: XDG_RUNTIME_DIR's, private to the user, or the data
+    /// directory's without one
+    pub fn runtime_dir() -> PathBuf {
+        match std::env::var("XDG_RUNTIME_DIR") {
+            Ok(runtime) if !runtime.is_empty() => PathBuf::from(runtime).join("rising-sun"),
+            _ => Self::data_dir().join("run"),
+        }
+    }
+
+    /// Directory the log files are written to
+    pub fn log_dir() -> PathBuf {
+        Self::data_dir().join("logs")
+    }
+
+    /// Directories searched for disk images that are no longer at their
+    /// stored path: the configured search paths, then the data directory
+    pub fn image_search_paths(&self) -> Vec<PathBuf> {
+        let data_dir = Self::data_dir();
+        let mut paths = self.storage.search_paths.clone();
+        paths.push(data_dir.join("disks"));
+        paths.push(data_dir);
+        paths
+    }
+
+    /// Re-locate hard disk images that moved and refresh their content ids
+    ///
+    /// Returns the (old, new) path of each image that was found elsewhere.
+    /// Images that cannot be found are left unchanged.
+    pub fn relocate_images(&mut self) -> Vec<(PathBuf, PathBuf)> {
+        let search_paths = self.image_search_paths();
+        let mut moved = Vec::new();
+
+        let disks = [&mut self.storage.primary_disk, &mut self.storage.secondary_disk];
+        for disk in disks.into_iter().flatten() {
+            let Some(found) =
+                image_ref::resolve_image(&disk.path, disk.content_id.as_deref(), &search_paths)
+            else {
+                continue;
+            };
+            if found != disk.path {
+                moved.push((std::mem::replace(&mut disk.path, found), disk.path.clone()));
+            }
+            if let Ok(id) = image_ref::content_id(&disk.path) {
+                disk.content_id = Some(id);
+            }
+        }
+
+        moved
+    }
+
     /// Create default drive mappings like original SunPCi
     /// Note: By default, no mappings are configured. This function
     /// provides suggested mappings that can be added by the user.
@@ -443,19 +1415,70 @@ impl AppConfig {
                 host_path: PathBuf::from("/opt/rising-sun"),
                 description: "Rising Sun Installation".to_string(),
                 enabled: true,
+                timestamps: TimestampPolicy::Local,
+                ..Default::default()
             },
             DriveMapping {
                 drive_letter: "H:".to_string(),
                 host_path: PathBuf::from(&home),
                 description: "Home Directory".to_string(),
                 enabled: true,
+                timestamps: TimestampPolicy::Local,
+                ..Default::default()
             },
             DriveMapping {
                 drive_letter: "R:".to_string(),
                 host_path: PathBuf::from("/"),
                 description: "Root Filesystem".to_string(),
                 enabled: false,
+                timestamps: TimestampPolicy::Local,
+                ..Default::default()
             },
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_files() {
+        let mut recent = RecentFiles {
+            max_recent: 2,
+            ..Default::default()
+        };
+        let (a, b, c) = (PathBuf::from("/a.iso"), PathBuf::from("/b.iso"), PathBuf::from("/c.iso"));
+        recent.add_iso(a.clone());
+        recent.add_iso(b.clone());
+        recent.set_pinned(RecentCategory::Iso, &a, true);
+        assert_eq!(recent.iso_files, [a.clone(), b.clone()]);
+
+        // Pinned files stay in front and do not count towards the limit
+        recent.add_iso(c.clone());
+        recent.add_iso(b.clone());
+        assert_eq!(recent.iso_files, [a.clone(), b.clone(), c.clone()]);
+        recent.clear(RecentCategory::Iso);
+        assert_eq!(recent.iso_files, std::slice::from_ref(&a));
+        recent.remove(RecentCategory::Iso, &a);
+        assert!(recent.iso_files.is_empty() && recent.pinned.is_empty());
+    }
+
+    #[test]
+    fn test_recent_files_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("c.img");
+        std::fs::write(&disk, b"").unwrap();
+        let gone = dir.path().join("gone.img");
+        let mut recent = RecentFiles {
+            disk_images: vec![disk.clone(), gone.clone(), disk.clone()],
+            pinned: vec![gone],
+            ..Default::default()
+        };
+        assert!(recent.prune());
+        assert_eq!(recent.disk_images, [disk]);
+        assert!(recent.pinned.is_empty());
+        assert!(!recent.prune());
+        assert_eq!(RecentCategory::parse("ISO"), Some(RecentCategory::Iso));
+    }
+}
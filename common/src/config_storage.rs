@@ -1,9 +1,26 @@
 //! Configuration file I/O operations.
+//!
+//! Files written by older builds are upgraded as they are loaded. Each
+//! file records the `schema_version` of its layout, and every change of
+//! layout since (a field renamed, moved or given a new meaning) has a
+//! migration in `MIGRATIONS` that rewrites the parsed TOML to the next
+//! version, so settings under old names are carried over rather than
+//! dropped as unknown keys. The original file is kept as
+//! `config.toml.v<N>.bak` before the upgraded one is written.
+//!
+//! Saving writes a temporary file and renames it over the old one, so a
+//! crash leaves either the old settings or the new. The settings replaced
+//! are kept as `config.toml.1` (the newest) to `config.toml.<CONFIG_BACKUPS>`,
+//! and can be put back with `restore_config_backup`.
 
-use crate::config::AppConfig;
-use std::fs;
-use std::io;
-use std::path::Path;
+use crate::config::{AppConfig, CONFIG_VERSION};
+use crate::config_watch;
+use crate::driver::selected_card;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use toml::{Table, Value};
 
 /// Error type for configuration operations
 #[derive(Debug, thiserror::Error)]
@@ -24,7 +41,19 @@ pub fn load_config() -> Result<AppConfig, ConfigError> {
     load_config_from(&config_file)
 }
 
+/// Load configuration as the selected card's session sees it
+///
+/// The card's [[cards]] sections replace the top-level ones; see
+/// `AppConfig::for_card`.
+pub fn load_card_config() -> Result<AppConfig, ConfigError> {
+    Ok(load_config()?.for_card(selected_card()))
+}
+
 /// Load configuration from a specific path
+///
+/// A file of an older layout is upgraded; the original is backed up and the
+/// upgraded file written in its place when possible, or upgraded again on
+/// the next load otherwise.
 pub fn load_config_from(path: &Path) -> Result<AppConfig, ConfigError> {
     if !path.exists() {
         // Return default config if file doesn't exist
@@ -32,17 +61,98 @@ pub fn load_config_from(path: &Path) -> Result<AppConfig, ConfigError> {
     }
 
     let contents = fs::read_to_string(path)?;
-    let config: AppConfig = toml::from_str(&contents)?;
+    let mut table: Table = toml::from_str(&contents)?;
+    let version = migrate(&mut table);
+    let config: AppConfig = Value::Table(table).try_into()?;
+
+    if version < CONFIG_VERSION {
+        // Never overwrite an earlier backup, which may be of an even older
+        // file
+        let backup = backup_path(path, version);
+        if backup.exists() || fs::copy(path, &backup).is_ok() {
+            let _ = save_config_to(&config, path);
+        }
+    }
     Ok(config)
 }
 
+/// Parse a configuration (e.g. an appliance profile), upgrading it from an
+/// older layout if need be
+pub fn parse_config(contents: &str) -> Result<AppConfig, ConfigError> {
+    let mut table: Table = toml::from_str(contents)?;
+    migrate(&mut table);
+    Ok(Value::Table(table).try_into()?)
+}
+
+/// Where the original of a file of layout `version` is kept when upgraded
+pub fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".v{}.bak", version));
+    path.with_file_name(name)
+}
+
+/// Upgrades of the configuration file: `MIGRATIONS[n]` takes a file of
+/// layout version n to version n + 1. When a field is renamed, moved or
+/// changes meaning, add one here and bump `CONFIG_VERSION`.
+const MIGRATIONS: [fn(&mut Table); CONFIG_VERSION as usize] = [migrate_boot_from_cd];
+
+/// Upgrade a parsed configuration file to `CONFIG_VERSION`, returning the
+/// version it had
+///
+/// Files without a `schema_version` predate it (version 0). A file of a
+/// newer version, from a later build, is left as it is.
+pub fn migrate(table: &mut Table) -> u32 {
+    let version = table
+        .get("schema_version")
+        .and_then(Value::as_integer)
+        .map_or(0, |v| v.clamp(0, u32::MAX as i64) as u32);
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(table);
+    }
+    if version < CONFIG_VERSION {
+        table.insert("schema_version".to_string(), Value::Integer(CONFIG_VERSION as i64));
+    }
+    version
+}
+
+/// Version 1: `storage.cdrom.boot_from_cd` became `storage.boot_device`,
+/// at the top level and in each card's storage section
+fn migrate_boot_from_cd(table: &mut Table) {
+    fn upgrade(storage: &mut Table) {
+        let boot_from_cd = storage
+            .get_mut("cdrom")
+            .and_then(Value::as_table_mut)
+            .and_then(|cdrom| cdrom.remove("boot_from_cd"))
+            .and_then(|v| v.as_bool());
+        if boot_from_cd == Some(true) && !storage.contains_key("boot_device") {
+            storage.insert("boot_device".to_string(), Value::String("Cdrom".to_string()));
+        }
+    }
+
+    if let Some(storage) = table.get_mut("storage").and_then(Value::as_table_mut) {
+        upgrade(storage);
+    }
+    let cards = table.get_mut("cards").and_then(Value::as_array_mut);
+    for card in cards.into_iter().flatten().filter_map(Value::as_table_mut) {
+        if let Some(storage) = card.get_mut("storage").and_then(Value::as_table_mut) {
+            upgrade(storage);
+        }
+    }
+}
+
 /// Save configuration to the default location
 pub fn save_config(config: &AppConfig) -> Result<(), ConfigError> {
     let config_file = AppConfig::config_file();
     save_config_to(config, &config_file)
 }
 
+/// Number of earlier versions of the configuration file kept
+pub const CONFIG_BACKUPS: usize = 5;
+
 /// Save configuration to a specific path
+///
+/// The file is replaced in one step, and the settings it held become
+/// backup 1 unless they are the same as the new ones.
 pub fn save_config_to(config: &AppConfig, path: &Path) -> Result<(), ConfigError> {
     // Ensure parent directory exists
     if let Some(parent) = path.parent() {
@@ -50,13 +160,86 @@ pub fn save_config_to(config: &AppConfig, path: &Path) -> Result<(), ConfigError
     }
 
     let contents = toml::to_string_pretty(config)?;
-    fs::write(path, contents)?;
+    if fs::read_to_string(path).is_ok_and(|old| old == contents) {
+        return Ok(());
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".tmp");
+    let temp = path.with_file_name(name);
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp)?;
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        if path.exists() {
+            rotate_backups(path)?;
+        }
+        fs::rename(&temp, path)?;
+        config_watch::note_written(path, &contents);
+        // Make the rename itself durable
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    Ok(result?)
+}
+
+/// Path of backup `n` of a configuration file, 1 being the newest
+pub fn config_backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", n));
+    path.with_file_name(name)
+}
+
+/// Backups of a configuration file that exist, newest first, with when
+/// each was replaced
+pub fn config_backups(path: &Path) -> Vec<(usize, SystemTime)> {
+    (1..=CONFIG_BACKUPS)
+        .filter_map(|n| {
+            let modified = fs::metadata(config_backup_path(path, n)).and_then(|m| m.modified()).ok()?;
+            Some((n, modified))
+        })
+        .collect()
+}
+
+/// Put backup `n` of a configuration file back, returning its settings
+///
+/// The settings replaced become backup 1 like on any save, so a restore can
+/// be undone.
+pub fn restore_config_backup(path: &Path, n: usize) -> Result<AppConfig, ConfigError> {
+    let config = parse_config(&fs::read_to_string(config_backup_path(path, n))?)?;
+    save_config_to(&config, path)?;
+    Ok(config)
+}
+
+/// Shift the backups along, dropping the oldest, and make the current file
+/// backup 1
+///
+/// The current file is hard linked rather than moved, so it stays in place
+/// until the new one is renamed over it.
+fn rotate_backups(path: &Path) -> io::Result<()> {
+    for n in (1..CONFIG_BACKUPS).rev() {
+        let from = config_backup_path(path, n);
+        if from.exists() {
+            fs::rename(&from, config_backup_path(path, n + 1))?;
+        }
+    }
+    let newest = config_backup_path(path, 1);
+    let _ = fs::remove_file(&newest);
+    if fs::hard_link(path, &newest).is_err() {
+        fs::copy(path, &newest)?;
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::BootDevice;
     use tempfile::tempdir;
 
     #[test]
@@ -77,4 +260,112 @@ mod tests {
         let config = load_config_from(Path::new("/nonexistent/path/config.toml")).unwrap();
         assert!(!config.general.auto_start);
     }
+
+    #[test]
+    fn test_card_sections() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+[network]
+enabled = true
+mac_address = "08:00:20:00:00:01"
+
+[[cards]]
+index = 1
+name = "Lab"
+
+[cards.network]
+enabled = true
+mac_address = "08:00:20:00:00:02"
+"#,
+        )
+        .unwrap();
+
+        let config = load_config_from(&config_path).unwrap();
+        assert_eq!(config.card_name(0), "Card 0");
+        assert_eq!(config.card_name(1), "Lab");
+        assert_eq!(config.for_card(0).network.mac_address, "08:00:20:00:00:01");
+        let card = config.for_card(1);
+        assert_eq!(card.network.mac_address, "08:00:20:00:00:02");
+        // Sections the card leaves out come from the top level
+        assert_eq!(card.keyboard.layout, config.keyboard.layout);
+
+        // Cards survive a save
+        save_config_to(&config, &config_path).unwrap();
+        let loaded = load_config_from(&config_path).unwrap();
+        assert_eq!(loaded.cards.len(), 1);
+        assert!(loaded.cards[0].storage.is_none());
+    }
+
+    #[test]
+    fn test_save_keeps_backups() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let mut config = AppConfig::default();
+        for i in 0..=CONFIG_BACKUPS + 1 {
+            config.keyboard.layout = format!("layout{}", i);
+            save_config_to(&config, &config_path).unwrap();
+            // Saving the same settings again leaves the backups alone
+            save_config_to(&config, &config_path).unwrap();
+        }
+        assert!(!dir.path().join("config.toml.tmp").exists());
+
+        let backups = config_backups(&config_path);
+        assert_eq!(backups.iter().map(|(n, _)| *n).collect::<Vec<_>>(), (1..=CONFIG_BACKUPS).collect::<Vec<_>>());
+        let newest = load_config_from(&config_backup_path(&config_path, 1)).unwrap();
+        assert_eq!(newest.keyboard.layout, format!("layout{}", CONFIG_BACKUPS));
+
+        let restored = restore_config_backup(&config_path, 2).unwrap();
+        let expected = format!("layout{}", CONFIG_BACKUPS - 1);
+        assert_eq!(restored.keyboard.layout, expected);
+        assert_eq!(load_config_from(&config_path).unwrap().keyboard.layout, expected);
+        // The settings replaced by the restore are the newest backup
+        let undo = load_config_from(&config_backup_path(&config_path, 1)).unwrap();
+        assert_eq!(undo.keyboard.layout, format!("layout{}", CONFIG_BACKUPS + 1));
+    }
+
+    #[test]
+    fn test_migrate_unversioned() {
+        let dir = tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let original = r#"
+[storage.cdrom]
+boot_from_cd = true
+
+[[cards]]
+index = 1
+
+[cards.storage.cdrom]
+boot_from_cd = true
+"#;
+        std::fs::write(&config_path, original).unwrap();
+
+        let config = load_config_from(&config_path).unwrap();
+        assert_eq!(config.schema_version, CONFIG_VERSION);
+        assert_eq!(config.storage.boot_device, BootDevice::Cdrom);
+        assert!(!config.storage.cdrom.boot_from_cd);
+        assert_eq!(config.for_card(1).storage.boot_device, BootDevice::Cdrom);
+
+        // The original is kept and the upgraded file written
+        let backup = dir.path().join("config.toml.v0.bak");
+        assert_eq!(std::fs::read_to_string(&backup).unwrap(), original);
+        let upgraded = std::fs::read_to_string(&config_path).unwrap();
+        assert!(upgraded.contains(&format!("schema_version = {}", CONFIG_VERSION)));
+        assert_eq!(load_config_from(&config_path).unwrap().storage.boot_device, BootDevice::Cdrom);
+    }
+
+    #[test]
+    fn test_migrate_current_untouched() {
+        let mut table: Table = toml::from_str("schema_version = 1\n[storage.cdrom]\nboot_from_cd = true\n").unwrap();
+        assert_eq!(migrate(&mut table), 1);
+        assert_eq!(table["storage"]["cdrom"]["boot_from_cd"].as_bool(), Some(true));
+
+        // A file from a later build keeps its version
+        let mut table: Table = toml::from_str("schema_version = 99").unwrap();
+        assert_eq!(migrate(&mut table), 99);
+        let config: AppConfig = Value::Table(table).try_into().unwrap();
+        assert_eq!(config.schema_version, 99);
+    }
 }
@@ -0,0 +1,164 @@
+//! Noticing edits made to the configuration file by hand.
+//!
+//! `ConfigWatcher` watches the directory of the configuration file with
+//! inotify for the file being written or replaced (editors do either), and
+//! reads it again when it is. Writes that change nothing, and the ones this
+//! process made itself through `save_config_to`, are not reported, so the
+//! frontend only reloads its settings for edits made elsewhere: in a text
+//! editor, by the CLI, or by another frontend.
+
+use std::collections::hash_map::DefaultHasher;
+use std::ffi::OsString;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+
+use crate::config::AppConfig;
+use crate::config_storage::{ConfigError, load_config_from};
+
+/// Events on the directory that can change the file
+const DIR_EVENTS: AddWatchFlags = AddWatchFlags::IN_CLOSE_WRITE
+    .union(AddWatchFlags::IN_MOVED_TO)
+    .union(AddWatchFlags::IN_CREATE)
+    .union(AddWatchFlags::IN_ONLYDIR);
+
+/// Digest of the contents this process last wrote to each configuration file
+static OWN_WRITES: Mutex<Vec<(PathBuf, u64)>> = Mutex::new(Vec::new());
+
+/// Record that this process wrote `contents` to `path`
+pub(crate) fn note_written(path: &Path, contents: &str) {
+    let digest = digest(contents);
+    let mut writes = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    match writes.iter_mut().find(|(p, _)| p == path) {
+        Some(entry) => entry.1 = digest,
+        None => writes.push((path.to_path_buf(), digest)),
+    }
+}
+
+/// Whether this process's last write to `path` was of these contents
+fn written_here(path: &Path, digest: u64) -> bool {
+    let writes = OWN_WRITES.lock().unwrap_or_else(|e| e.into_inner());
+    writes.iter().any(|(p, d)| p == path && *d == digest)
+}
+
+fn digest(contents: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Watches a configuration file for edits made outside this process
+pub struct ConfigWatcher {
+    inotify: Inotify,
+    path: PathBuf,
+    name: OsString,
+    /// Digest of the contents last read or written
+    seen: Option<u64>,
+}
+
+impl ConfigWatcher {
+    /// Start watching `path`, creating its directory if need be; its
+    /// current contents are known, not changed
+    pub fn new(path: &Path) -> io::Result<Self> {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        inotify.add_watch(dir, DIR_EVENTS)?;
+        Ok(Self {
+            inotify,
+            path: path.to_path_buf(),
+            name: path.file_name().unwrap_or_default().to_os_string(),
+            seen: fs::read_to_string(path).ok().map(|c| digest(&c)),
+        })
+    }
+
+    /// Read the events that have arrived, without blocking
+    ///
+    /// Returns the settings if the file was changed by someone else since
+    /// the last call. A file that cannot be parsed is reported once, as an
+    /// error, until it changes again.
+    pub fn poll(&mut self) -> Result<Option<AppConfig>, ConfigError> {
+        let mut touched = false;
+        loop {
+            match self.inotify.read_events() {
+                Ok(events) if !events.is_empty() => {
+                    touched |= events.iter().any(|e| e.name.as_deref() == Some(self.name.as_os_str()));
+                }
+                Ok(_) | Err(Errno::EAGAIN) => break,
+                Err(e) => return Err(io::Error::from(e).into()),
+            }
+        }
+        if !touched {
+            return Ok(None);
+        }
+
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            // Replaced in a way that leaves it missing for a moment; the
+            // new one raises events of its own
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let current = digest(&contents);
+        if self.seen == Some(current) {
+            return Ok(None);
+        }
+        self.seen = Some(current);
+        if written_here(&self.path, current) {
+            return Ok(None);
+        }
+
+        let config = load_config_from(&self.path)?;
+        // An upgrade of an older layout was written back; that is this
+        // process's own write
+        if let Ok(upgraded) = fs::read_to_string(&self.path) {
+            self.seen = Some(digest(&upgraded));
+        }
+        Ok(Some(config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config_storage::save_config_to;
+
+    #[test]
+    fn test_config_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut config = AppConfig::default();
+        save_config_to(&config, &path).unwrap();
+        let mut watcher = ConfigWatcher::new(&path).unwrap();
+        assert!(watcher.poll().unwrap().is_none());
+
+        // Saved by this process
+        config.keyboard.layout = "de".to_string();
+        save_config_to(&config, &path).unwrap();
+        assert!(watcher.poll().unwrap().is_none());
+
+        // Edited by hand
+        let edited = fs::read_to_string(&path).unwrap().replace("\"de\"", "\"fr\"");
+        fs::write(&path, edited).unwrap();
+        assert_eq!(watcher.poll().unwrap().unwrap().keyboard.layout, "fr");
+        assert!(watcher.poll().unwrap().is_none());
+
+        // Broken once, reported once
+        fs::write(&path, "[keyboard\n").unwrap();
+        assert!(watcher.poll().is_err());
+        fs::write(&path, "[keyboard\n").unwrap();
+        assert!(watcher.poll().unwrap().is_none());
+
+        // Other files in the directory are not the configuration
+        fs::write(dir.path().join("other.toml"), "x = 1").unwrap();
+        assert!(watcher.poll().unwrap().is_none());
+    }
+}
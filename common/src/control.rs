@@ -0,0 +1,573 @@
+//! Control API for driving a running frontend from the CLI.
+//!
+//! The hardware can only be owned by one process, so once the GUI has a
+//! session the CLI cannot use the driver itself. Instead the frontend
+//! listens on `[remote] control_port` and `rising-sun-cli --host` sends it
+//! commands. The protocol is one JSON object per line each way:
+//!
+//! ```text
+//! -> {"command":"mount-iso","args":["/srv/isos/win98.iso"]}
+//! <- {"ok":true,"message":"Mounted /srv/isos/win98.iso"}
+//! ```
+//!
+//! The port listens on loopback unless `[remote] control_address` says
+//! otherwise, and then only with a `control_token`, which every request
+//! must carry (`"token":"..."`); requests without it are answered with an
+//! error and never reach the session. The token is sent in the clear, so
+//! it keeps out strangers on the LAN, not anyone who can watch the traffic.
+//!
+//! `ControlServer` accepts connections on a background thread, reads each
+//! on a thread of its own (dropping it if a request line takes longer than
+//! `LINE_TIMEOUT`) and hands requests to the UI thread, which polls
+//! `try_next` and answers through `PendingRequest::reply`. The same protocol runs over a Unix socket
+//! (`ControlServer::start_local`), which a frontend started a second time
+//! uses to hand its command line to the first one (see `instance`).
+
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::mdns;
+
+/// Port the CLI tries when none is given
+pub const DEFAULT_CONTROL_PORT: u16 = 7070;
+
+/// Commands the frontend understands
+pub mod command {
+    /// Session state and display mode
+    pub const STATUS: &str = "status";
+    /// Start a session with the GUI's configuration
+    pub const START: &str = "start";
+    /// Stop the session
+    pub const STOP: &str = "stop";
+    /// Warm-reset the guest
+    pub const RESET: &str = "reset";
+    /// Mount an ISO image or CUE sheet (path on the frontend's host)
+    pub const MOUNT_ISO: &str = "mount-iso";
+    /// Eject the CD-ROM
+    pub const EJECT: &str = "eject";
+    /// Stop driving the session so another frontend can adopt it; the
+    /// answer is a `handoff::HandoffState` in JSON
+    pub const RELEASE: &str = "release";
+    /// Bring the window to the front, going fullscreen with the argument
+    /// `fullscreen`
+    pub const SHOW: &str = "show";
+}
+
+/// Longest request line accepted
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// How long a client has to send each request line before it is
+/// disconnected
+const LINE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most clients served at once
+const MAX_CLIENTS: usize = 16;
+
+/// How long a client waits for the UI thread to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to look for a session by name with mDNS
+const BROWSE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often the accept loop checks whether it should stop
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+
+/// A command sent to the frontend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlRequest {
+    /// Command name (see `command`)
+    pub command: String,
+    /// Command arguments
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Shared secret of a control API listening beyond loopback
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl ControlRequest {
+    /// Build a request
+    pub fn new(command: &str, args: &[&str]) -> Self {
+        Self {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            token: None,
+        }
+    }
+
+    /// First argument, or an empty string
+    pub fn arg(&self) -> &str {
+        self.args.first().map_or("", String::as_str)
+    }
+}
+
+/// The frontend's answer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    /// Whether the command succeeded
+    pub ok: bool,
+    /// Human-readable result or error
+    #[serde(default)]
+    pub message: String,
+}
+
+impl ControlResponse {
+    /// Successful response
+    pub fn ok(message: impl Into<String>) -> Self {
+        Self { ok: true, message: message.into() }
+    }
+
+    /// Failed response
+    pub fn error(message: impl Into<String>) -> Self {
+        Self { ok: false, message: message.into() }
+    }
+}
+
+/// A request waiting for the UI thread
+pub struct PendingRequest {
+    /// The command
+    pub request: ControlRequest,
+    reply: Sender<ControlResponse>,
+}
+
+impl PendingRequest {
+    /// Answer the client
+    pub fn reply(self, response: ControlResponse) {
+        // The client may have given up already
+        let _ = self.reply.send(response);
+    }
+}
+
+/// A stream the protocol runs over
+trait Connection: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+macro_rules! impl_connection {
+    ($stream:ty) => {
+        impl Connection for $stream {
+            fn try_clone(&self) -> io::Result<Self> {
+                <$stream>::try_clone(self)
+            }
+            fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+                <$stream>::set_nonblocking(self, nonblocking)
+            }
+            fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+                <$stream>::set_read_timeout(self, timeout)
+            }
+        }
+    };
+}
+
+impl_connection!(TcpStream);
+impl_connection!(UnixStream);
+
+/// Listens for control connections until dropped
+pub struct ControlServer {
+    /// TCP port, 0 on a Unix socket
+    port: u16,
+    /// Unix socket, removed on drop
+    socket: Option<PathBuf>,
+    requests: Receiver<PendingRequest>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl ControlServer {
+    /// Listen on `addr` (port 0 picks a free port), accepting only
+    /// requests that carry `token` if one is given
+    ///
+    /// Fails with `InvalidInput` for an address other than loopback
+    /// without a token.
+    pub fn start(addr: SocketAddr, token: Option<&str>) -> io::Result<Self> {
+        if !addr.ip().is_loopback() && token.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("a control token is needed to listen on {}", addr.ip()),
+            ));
+        }
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let port = listener.local_addr()?.port();
+        let token = token.map(str::to_string);
+        Self::spawn(port, None, token, move || listener.accept().map(|(stream, _)| stream))
+    }
+
+    /// Listen on the Unix socket at `path`; fails with `AddrInUse` if the
+    /// file exists
+    pub fn start_local(path: &Path) -> io::Result<Self> {
+        let listener = UnixListener::bind(path)?;
+        listener.set_nonblocking(true)?;
+        Self::spawn(0, Some(path.to_path_buf()), None, move || listener.accept().map(|(stream, _)| stream))
+    }
+
+    fn spawn<S: Connection>(
+        port: u16,
+        socket: Option<PathBuf>,
+        token: Option<String>,
+        accept: impl FnMut() -> io::Result<S> + Send + 'static,
+    ) -> io::Result<Self> {
+        let (sender, requests) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = std::thread::Builder::new()
+            .name("control".to_string())
+            .spawn(move || accept_thread(accept, sender, token, thread_running))?;
+        Ok(Self {
+            port,
+            socket,
+            requests,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    /// Port being listened on, 0 on a Unix socket
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Next request waiting for an answer, if any
+    pub fn try_next(&self) -> Option<PendingRequest> {
+        self.requests.try_recv().ok()
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        if let Some(socket) = self.socket.take() {
+            let _ = fs::remove_file(socket);
+        }
+    }
+}
+
+/// Serve each client on a thread of its own until stopped, so one that
+/// holds its connection open does not shut out the others
+fn accept_thread<S: Connection>(
+    mut accept: impl FnMut() -> io::Result<S>,
+    requests: Sender<PendingRequest>,
+    token: Option<String>,
+    running: Arc<AtomicBool>,
+) {
+    let clients = Arc::new(AtomicUsize::new(0));
+    while running.load(Ordering::SeqCst) {
+        match accept() {
+            // Dropping the stream turns away clients over the limit
+            Ok(_) if clients.load(Ordering::SeqCst) >= MAX_CLIENTS => {}
+            Ok(stream) => {
+                clients.fetch_add(1, Ordering::SeqCst);
+                let (requests, token, running) = (requests.clone(), token.clone(), running.clone());
+                let thread_clients = clients.clone();
+                let spawned = std::thread::Builder::new().name("control-client".to_string()).spawn(move || {
+                    let _ = serve_client(stream, &requests, token.as_deref(), &running);
+                    thread_clients.fetch_sub(1, Ordering::SeqCst);
+                });
+                if spawned.is_err() {
+                    clients.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => std::thread::sleep(ACCEPT_POLL),
+            Err(_) => std::thread::sleep(ACCEPT_POLL),
+        }
+    }
+}
+
+/// Answer each request line on a connection
+fn serve_client(
+    stream: impl Connection,
+    requests: &Sender<PendingRequest>,
+    token: Option<&str>,
+    running: &AtomicBool,
+) -> io::Result<()> {
+    // Short timeouts so a quiet client does not hold up shutdown
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(ACCEPT_POLL))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    let mut deadline = Instant::now() + LINE_TIMEOUT;
+    while running.load(Ordering::SeqCst) && Instant::now() < deadline {
+        match reader.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            // A partial line stays in `line` until the rest arrives
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e),
+        }
+        if !line.ends_with('\n') {
+            if line.len() > MAX_LINE_LEN {
+                break;
+            }
+            continue;
+        }
+
+        let response = match serde_json::from_str::<ControlRequest>(&line) {
+            Ok(request) if !token_matches(token, request.token.as_deref()) => {
+                // No second guess on the same connection
+                write_message(&mut writer, &ControlResponse::error("missing or wrong control token"))?;
+                break;
+            }
+            Ok(mut request) => {
+                request.token = None;
+                let (reply, answer) = mpsc::channel();
+                if requests.send(PendingRequest { request, reply }).is_err() {
+                    break;
+                }
+                wait_for_reply(&answer, running)
+            }
+            Err(_) if line.trim().is_empty() => {
+                line.clear();
+                continue;
+            }
+            Err(e) => ControlResponse::error(format!("bad request: {}", e)),
+        };
+        line.clear();
+        write_message(&mut writer, &response)?;
+        deadline = Instant::now() + LINE_TIMEOUT;
+    }
+    Ok(())
+}
+
+/// Whether a request's token is the one the server expects
+///
+/// Compares every byte so the time taken does not give away how much of
+/// a guess was right.
+fn token_matches(expected: Option<&str>, given: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+    let given = given.unwrap_or("");
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Wait for the UI thread's answer, giving up on timeout or shutdown
+fn wait_for_reply(answer: &Receiver<ControlResponse>, running: &AtomicBool) -> ControlResponse {
+    let deadline = Instant::now() + REPLY_TIMEOUT;
+    while running.load(Ordering::SeqCst) && Instant::now() < deadline {
+        match answer.recv_timeout(ACCEPT_POLL) {
+            Ok(response) => return response,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    ControlResponse::error("frontend did not answer")
+}
+
+fn write_message<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_string(message).map_err(io::Error::other)?;
+    line.push('\n');
+    writer.write_all(line.as_bytes())?;
+    writer.flush()
+}
+
+/// Connection to a frontend's control API
+pub struct ControlClient {
+    reader: BufReader<Box<dyn Read + Send>>,
+    writer: Box<dyn Write + Send>,
+    token: Option<String>,
+}
+
+impl ControlClient {
+    /// Connect to a frontend by host name or address
+    ///
+    /// A host that does not resolve is looked up among the sessions
+    /// advertised with mDNS, by host or profile name, which also supplies
+    /// the port when `port` is None.
+    pub fn connect(host: &str, port: Option<u16>) -> io::Result<Self> {
+        let addr = match (host, port.unwrap_or(DEFAULT_CONTROL_PORT)).to_socket_addrs() {
+            Ok(mut addrs) => addrs.next(),
+            Err(_) => None,
+        };
+        let addr = match addr {
+            Some(addr) => addr,
+            None => find_session(host, port)?,
+        };
+
+        let stream = TcpStream::connect_timeout(&addr, REPLY_TIMEOUT)?;
+        Self::over(stream)
+    }
+
+    /// Connect to a frontend listening on the Unix socket at `path`
+    pub fn connect_local(path: &Path) -> io::Result<Self> {
+        Self::over(UnixStream::connect(path)?)
+    }
+
+    fn over<S: Connection>(stream: S) -> io::Result<Self> {
+        stream.set_read_timeout(Some(REPLY_TIMEOUT + Duration::from_secs(5)))?;
+        Ok(Self {
+            reader: BufReader::new(Box::new(stream.try_clone()?)),
+            writer: Box::new(stream),
+            token: None,
+        })
+    }
+
+    /// Send `token` with every request, for a frontend that wants one
+    pub fn with_token(mut self, token: Option<&str>) -> Self {
+        self.token = token.map(str::to_string);
+        self
+    }
+
+    /// Send a command and wait for the answer
+    pub fn call(&mut self, request: &ControlRequest) -> io::Result<ControlResponse> {
+        match &self.token {
+            Some(token) if request.token.is_none() => {
+                let request = ControlRequest { token: Some(token.clone()), ..request.clone() };
+                write_message(&mut self.writer, &request)?;
+            }
+            _ => write_message(&mut self.writer, request)?,
+        }
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "frontend closed the connection"));
+        }
+        serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Address of an advertised session matching `name`
+fn find_session(name: &str, port: Option<u16>) -> io::Result<SocketAddr> {
+    let not_found = || io::Error::new(io::ErrorKind::NotFound, format!("no session found for {:?}", name));
+    let session = mdns::browse(BROWSE_TIMEOUT)?
+        .into_iter()
+        .find(|s| s.host.eq_ignore_ascii_case(name) || s.profile().eq_ignore_ascii_case(name))
+        .ok_or_else(not_found)?;
+    let addr = session.addr.ok_or_else(not_found)?;
+    let port = port
+        .or_else(|| session.control_port())
+        .ok_or_else(|| io::Error::other(format!("{} has no control port", session.profile())))?;
+    Ok(SocketAddr::from((addr, port)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn test_round_trip() {
+        let server = ControlServer::start(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), None).unwrap();
+        let port = server.port();
+
+        // Stand-in for the UI thread
+        let ui = std::thread::spawn(move || {
+            let mut answered = 0;
+            while answered < 2 {
+                match server.try_next() {
+                    Some(pending) => {
+                        let response = match pending.request.command.as_str() {
+                            command::MOUNT_ISO => ControlResponse::ok(format!("Mounted {}", pending.request.arg())),
+                            other => ControlResponse::error(format!("unknown command {}", other)),
+                        };
+                        pending.reply(response);
+                        answered += 1;
+                    }
+                    None => std::thread::sleep(Duration::from_millis(5)),
+                }
+            }
+        });
+
+        let mut client = ControlClient::connect("127.0.0.1", Some(port)).unwrap();
+        let response = client.call(&ControlRequest::new(command::MOUNT_ISO, &["win98.iso"])).unwrap();
+        assert_eq!(response, ControlResponse::ok("Mounted win98.iso"));
+        let response = client.call(&ControlRequest::new("format-c", &[])).unwrap();
+        assert!(!response.ok);
+        ui.join().unwrap();
+
+        // Malformed lines are answered without reaching the UI thread
+        let server = ControlServer::start(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), None).unwrap();
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, server.port())).unwrap();
+        stream.write_all(b"not json\n").unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        let response: ControlResponse = serde_json::from_str(&line).unwrap();
+        assert!(!response.ok);
+        assert!(server.try_next().is_none());
+    }
+
+    #[test]
+    fn test_token() {
+        // Nothing listens beyond loopback without a token
+        let err = ControlServer::start(SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)), None).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let server = ControlServer::start(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)), Some("s3cret")).unwrap();
+        let port = server.port();
+        let ui = std::thread::spawn(move || loop {
+            match server.try_next() {
+                Some(pending) => {
+                    // The token stays with the server
+                    assert_eq!(pending.request.token, None);
+                    let command = pending.request.command.clone();
+                    pending.reply(ControlResponse::ok(command));
+                    return server;
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        });
+
+        // A wrong token also ends the connection
+        let mut client = ControlClient::connect("127.0.0.1", Some(port)).unwrap();
+        assert!(!client.call(&ControlRequest::new(command::STATUS, &[])).unwrap().ok);
+        assert!(client.call(&ControlRequest::new(command::STATUS, &[])).is_err());
+        let mut client = ControlClient::connect("127.0.0.1", Some(port)).unwrap().with_token(Some("s3cre7"));
+        assert!(!client.call(&ControlRequest::new(command::STATUS, &[])).unwrap().ok);
+
+        // A client that sends nothing does not hold up the others
+        let _silent = TcpStream::connect((Ipv4Addr::LOCALHOST, port)).unwrap();
+        let mut client = ControlClient::connect("127.0.0.1", Some(port)).unwrap().with_token(Some("s3cret"));
+        let response = client.call(&ControlRequest::new(command::STATUS, &[])).unwrap();
+        assert_eq!(response, ControlResponse::ok(command::STATUS));
+        drop(ui.join().unwrap());
+
+        assert!(token_matches(None, None));
+        assert!(token_matches(None, Some("anything")));
+        assert!(!token_matches(Some("s3cret"), None));
+        assert!(!token_matches(Some("s3cret"), Some("s3cret!")));
+    }
+
+    #[test]
+    fn test_local_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sunpci0.sock");
+        let server = ControlServer::start_local(&path).unwrap();
+        let err = ControlServer::start_local(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+
+        let ui = std::thread::spawn(move || loop {
+            match server.try_next() {
+                Some(pending) => {
+                    let command = pending.request.command.clone();
+                    pending.reply(ControlResponse::ok(command));
+                    return server;
+                }
+                None => std::thread::sleep(Duration::from_millis(5)),
+            }
+        });
+        let mut client = ControlClient::connect_local(&path).unwrap();
+        let response = client.call(&ControlRequest::new(command::SHOW, &[])).unwrap();
+        assert_eq!(response, ControlResponse::ok(command::SHOW));
+
+        // The socket goes with the server
+        drop(client);
+        drop(ui.join().unwrap());
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,119 @@
+//! Parameters of the CRT post-processing shader.
+//!
+//! The frontend draws the display through one fragment shader that does
+//! all the effects; `CrtParams` turns the display settings into its
+//! uniforms. Scanlines need at least two host pixels per guest line to
+//! look like anything but moiré, so their strength fades out as the
+//! scale drops towards 1:1.
+
+use crate::config::{CrtEffect, DisplayConfig};
+
+/// Effect numbers as the shader knows them
+pub const EFFECT_NONE: i32 = 0;
+pub const EFFECT_SCANLINES: i32 = 1;
+pub const EFFECT_APERTURE_GRILLE: i32 = 2;
+pub const EFFECT_CURVATURE: i32 = 3;
+
+/// Uniform values for the CRT shader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrtParams {
+    /// One of the EFFECT_* numbers
+    pub effect: i32,
+    /// How dark the gaps between scanlines are (0.0 - 1.0)
+    pub scanline_intensity: f32,
+    /// Strength of the aperture grille stripes (0.0 - 1.0)
+    pub mask_intensity: f32,
+    /// Barrel distortion of the curvature effect (0.0 - 0.5)
+    pub curvature: f32,
+}
+
+impl CrtParams {
+    /// Shader parameters for the display settings, clamped to their ranges
+    pub fn from_config(display: &DisplayConfig) -> Self {
+        let effect = if !display.scanline_effect {
+            EFFECT_NONE
+        } else {
+            match display.crt_effect {
+                CrtEffect::Scanlines => EFFECT_SCANLINES,
+                CrtEffect::ApertureGrille => EFFECT_APERTURE_GRILLE,
+                CrtEffect::Curvature => EFFECT_CURVATURE,
+            }
+        };
+        Self {
+            effect,
+            scanline_intensity: display.scanline_intensity.clamp(0.0, 1.0),
+            mask_intensity: display.mask_intensity.clamp(0.0, 1.0),
+            curvature: display.curvature.clamp(0.0, 0.5),
+        }
+    }
+
+    /// Scanline strength with a `source_h` display drawn `target_h` high
+    pub fn scanline_strength(&self, source_h: u32, target_h: u32) -> f32 {
+        if self.effect == EFFECT_NONE || source_h == 0 {
+            return 0.0;
+        }
+        let scale = target_h as f32 / source_h as f32;
+        self.scanline_intensity * (scale - 1.0).clamp(0.0, 1.0)
+    }
+}
+
+/// Effect name used in the UI and the effect it stands for
+pub fn effect_from_name(name: &str) -> Option<CrtEffect> {
+    match name {
+        "scanlines" => Some(CrtEffect::Scanlines),
+        "aperture" => Some(CrtEffect::ApertureGrille),
+        "curvature" => Some(CrtEffect::Curvature),
+        _ => None,
+    }
+}
+
+/// UI name of an effect
+pub fn effect_name(effect: CrtEffect) -> &'static str {
+    match effect {
+        CrtEffect::Scanlines => "scanlines",
+        CrtEffect::ApertureGrille => "aperture",
+        CrtEffect::Curvature => "curvature",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_config() {
+        let mut display = DisplayConfig::default();
+        assert_eq!(CrtParams::from_config(&display).effect, EFFECT_NONE);
+
+        display.scanline_effect = true;
+        display.crt_effect = CrtEffect::ApertureGrille;
+        display.scanline_intensity = 1.5;
+        display.curvature = 0.8;
+        let params = CrtParams::from_config(&display);
+        assert_eq!(params.effect, EFFECT_APERTURE_GRILLE);
+        assert_eq!(params.scanline_intensity, 1.0);
+        assert_eq!(params.curvature, 0.5);
+    }
+
+    #[test]
+    fn test_scanline_strength() {
+        let display = DisplayConfig {
+            scanline_effect: true,
+            scanline_intensity: 0.4,
+            ..Default::default()
+        };
+        let params = CrtParams::from_config(&display);
+        assert_eq!(params.scanline_strength(400, 400), 0.0);
+        assert!((params.scanline_strength(400, 600) - 0.2).abs() < 1e-6);
+        assert_eq!(params.scanline_strength(400, 1200), 0.4);
+        assert_eq!(params.scanline_strength(0, 1200), 0.0);
+    }
+
+    #[test]
+    fn test_effect_names() {
+        for effect in [CrtEffect::Scanlines, CrtEffect::ApertureGrille, CrtEffect::Curvature] {
+            assert_eq!(effect_from_name(effect_name(effect)), Some(effect));
+        }
+        assert_eq!(effect_from_name("none"), None);
+    }
+}
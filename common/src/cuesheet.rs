@@ -0,0 +1,472 @@
+//! CUE sheet parsing for multi-track CD images.
+//!
+//! An ISO file holds one data track. Discs with audio tracks or several
+//! sessions of data (game discs with Red Book music, mixed-mode CDs) are
+//! imaged as one or more BIN files described by a CUE sheet:
+//!
+//! ```text
+//! FILE "game.bin" BINARY
+//!   TRACK 01 MODE1/2352
+//!     INDEX 01 00:00:00
+//!   TRACK 02 AUDIO
+//!     PREGAP 00:02:00
+//!     INDEX 01 58:41:36
+//! ```
+//!
+//! `parse` reads the sheet; `CueSheet::layout` places the tracks on the
+//! disc, which `virtual_cd::VirtualCd` uses to serve the guest. Only
+//! BINARY files are supported (not WAVE/MP3 or big-endian MOTOROLA audio).
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::scsi::{CD_FRAMES_PER_SECOND, Toc, TocTrack};
+
+/// Errors from reading a CUE sheet
+#[derive(Debug, Error)]
+pub enum CueError {
+    #[error("line {line}: {message}")]
+    Parse { line: usize, message: String },
+
+    #[error("no tracks in CUE sheet")]
+    NoTracks,
+
+    #[error("{path}: {source}")]
+    File {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("{0}: not a whole number of sectors")]
+    Truncated(PathBuf),
+}
+
+/// Sector format of a track
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackMode {
+    /// Red Book audio, 2352 bytes of samples per sector
+    Audio,
+    /// Mode 1 user data only
+    Mode1_2048,
+    /// Mode 1 raw sectors (sync, header, data, EDC/ECC)
+    Mode1_2352,
+    /// Mode 2 without sync and header
+    Mode2_2336,
+    /// Mode 2 raw sectors (CD-ROM XA)
+    Mode2_2352,
+}
+
+impl TrackMode {
+    /// Parse the mode field of a TRACK line
+    pub fn from_cue(mode: &str) -> Option<Self> {
+        match mode.to_ascii_uppercase().as_str() {
+            "AUDIO" => Some(Self::Audio),
+            "MODE1/2048" => Some(Self::Mode1_2048),
+            "MODE1/2352" => Some(Self::Mode1_2352),
+            "MODE2/2336" => Some(Self::Mode2_2336),
+            "MODE2/2352" => Some(Self::Mode2_2352),
+            _ => None,
+        }
+    }
+
+    /// Name as written in a CUE sheet
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Audio => "AUDIO",
+            Self::Mode1_2048 => "MODE1/2048",
+            Self::Mode1_2352 => "MODE1/2352",
+            Self::Mode2_2336 => "MODE2/2336",
+            Self::Mode2_2352 => "MODE2/2352",
+        }
+    }
+
+    /// Bytes per sector in the image file
+    pub fn sector_size(&self) -> u32 {
+        match self {
+            Self::Mode1_2048 => 2048,
+            Self::Mode2_2336 => 2336,
+            Self::Audio | Self::Mode1_2352 | Self::Mode2_2352 => 2352,
+        }
+    }
+
+    /// Offset of the 2048 bytes of user data within a stored sector
+    ///
+    /// Mode 2 tracks are assumed to be XA Form 1, whose data follows an
+    /// 8-byte subheader.
+    pub fn data_offset(&self) -> u32 {
+        match self {
+            Self::Audio | Self::Mode1_2048 => 0,
+            Self::Mode1_2352 => 16,
+            Self::Mode2_2336 => 8,
+            Self::Mode2_2352 => 24,
+        }
+    }
+
+    /// Whether this is an audio track
+    pub fn is_audio(&self) -> bool {
+        matches!(self, Self::Audio)
+    }
+}
+
+/// A TRACK entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueTrack {
+    /// Track number (1-99)
+    pub number: u8,
+    /// Sector format
+    pub mode: TrackMode,
+    /// Index into `CueSheet::files`
+    pub file: usize,
+    /// Silence before the track that is not stored in the file (frames)
+    pub pregap: u32,
+    /// Silence after the track that is not stored in the file (frames)
+    pub postgap: u32,
+    /// INDEX 00, frames from the start of the file
+    pub index0: Option<u32>,
+    /// INDEX 01 (the track start), frames from the start of the file
+    pub index1: u32,
+    /// TITLE of the track
+    pub title: Option<String>,
+}
+
+impl CueTrack {
+    /// Where the track's stored sectors begin in its file (frames)
+    fn file_start(&self) -> u32 {
+        self.index0.unwrap_or(self.index1)
+    }
+}
+
+/// A parsed CUE sheet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CueSheet {
+    /// Image files, resolved against the sheet's directory
+    pub files: Vec<PathBuf>,
+    /// Tracks in disc order
+    pub tracks: Vec<CueTrack>,
+    /// Disc TITLE
+    pub title: Option<String>,
+}
+
+/// Where a run of disc sectors comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// PREGAP/POSTGAP silence, not stored anywhere
+    Gap,
+    /// Stored in `CueSheet::files[file]` starting at `offset` bytes
+    File { file: usize, offset: u64 },
+}
+
+/// A run of consecutive disc sectors with one format and source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    /// First disc sector
+    pub start: u32,
+    /// Number of sectors
+    pub sectors: u32,
+    /// Sector format
+    pub mode: TrackMode,
+    /// Track the sectors belong to
+    pub track: u8,
+    /// Where the sectors are stored
+    pub source: Source,
+}
+
+/// Tracks placed on the disc
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscLayout {
+    /// Track starts (INDEX 01) and the lead-out
+    pub toc: Toc,
+    /// Sector runs in disc order, covering 0..lead-out
+    pub extents: Vec<Extent>,
+}
+
+impl DiscLayout {
+    /// Extent holding a disc sector
+    pub fn extent_at(&self, lba: u32) -> Option<&Extent> {
+        let i = self.extents.partition_point(|e| e.start + e.sectors <= lba);
+        self.extents.get(i).filter(|e| e.start <= lba)
+    }
+}
+
+impl CueSheet {
+    /// Place the tracks on the disc
+    ///
+    /// `file_sizes` holds the byte length of each file in `files`. Sectors
+    /// of a track run from its INDEX 00 (or 01) to the next track's in the
+    /// same file, or to the end of the file.
+    pub fn layout(&self, file_sizes: &[u64]) -> Result<DiscLayout, CueError> {
+        let mut extents = Vec::new();
+        let mut toc_tracks = Vec::new();
+        let mut lba = 0u32;
+        let mut file_offset = 0u64;
+
+        for (i, track) in self.tracks.iter().enumerate() {
+            let first_in_file = i == 0 || self.tracks[i - 1].file != track.file;
+            if first_in_file {
+                file_offset = 0;
+            }
+            let next_in_file = self.tracks.get(i + 1).filter(|t| t.file == track.file);
+            let region_start = if first_in_file { 0 } else { track.file_start() };
+            let sector_size = u64::from(track.mode.sector_size());
+            let file_size = file_sizes.get(track.file).copied().unwrap_or(0);
+            let sectors = match next_in_file {
+                Some(next) => next.file_start().saturating_sub(region_start),
+                None => {
+                    let remaining = file_size.saturating_sub(file_offset);
+                    if file_offset > file_size || remaining % sector_size != 0 {
+                        return Err(CueError::Truncated(self.files[track.file].clone()));
+                    }
+                    u32::try_from(remaining / sector_size).unwrap_or(u32::MAX)
+                }
+            };
+
+            if track.pregap > 0 {
+                extents.push(Extent {
+                    start: lba,
+                    sectors: track.pregap,
+                    mode: track.mode,
+                    track: track.number,
+                    source: Source::Gap,
+                });
+                lba += track.pregap;
+            }
+            toc_tracks.push(TocTrack {
+                number: track.number,
+                start_lba: lba + track.index1.saturating_sub(region_start),
+                audio: track.mode.is_audio(),
+            });
+            if sectors > 0 {
+                extents.push(Extent {
+                    start: lba,
+                    sectors,
+                    mode: track.mode,
+                    track: track.number,
+                    source: Source::File { file: track.file, offset: file_offset },
+                });
+            }
+            lba += sectors;
+            file_offset += u64::from(sectors) * sector_size;
+            if track.postgap > 0 {
+                extents.push(Extent {
+                    start: lba,
+                    sectors: track.postgap,
+                    mode: track.mode,
+                    track: track.number,
+                    source: Source::Gap,
+                });
+                lba += track.postgap;
+            }
+        }
+
+        Ok(DiscLayout {
+            toc: Toc { tracks: toc_tracks, lead_out: lba },
+            extents,
+        })
+    }
+}
+
+/// Read a CUE sheet from a file
+pub fn load(path: &Path) -> Result<CueSheet, CueError> {
+    let text = std::fs::read(path).map_err(|source| CueError::File {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    // Sheets are often written by Windows tools in a legacy code page
+    let text = String::from_utf8_lossy(&text);
+    parse(&text, path.parent().unwrap_or(Path::new(".")))
+}
+
+/// Parse CUE sheet text; FILE names are resolved against `base_dir`
+pub fn parse(text: &str, base_dir: &Path) -> Result<CueSheet, CueError> {
+    let mut sheet = CueSheet {
+        files: Vec::new(),
+        tracks: Vec::new(),
+        title: None,
+    };
+
+    for (n, raw) in text.lines().enumerate() {
+        let line = n + 1;
+        let error = |message: &str| CueError::Parse { line, message: message.to_string() };
+        let (keyword, rest) = split_keyword(raw.trim_start_matches('\u{feff}').trim());
+        match keyword.to_ascii_uppercase().as_str() {
+            "" | "REM" | "CATALOG" | "CDTEXTFILE" | "FLAGS" | "ISRC" | "PERFORMER" | "SONGWRITER" => {}
+            "FILE" => {
+                let (name, file_type) = split_quoted(rest);
+                if name.is_empty() {
+                    return Err(error("FILE without a name"));
+                }
+                if !file_type.eq_ignore_ascii_case("BINARY") {
+                    return Err(error(&format!("unsupported file type {:?}", file_type)));
+                }
+                sheet.files.push(base_dir.join(name));
+            }
+            "TRACK" => {
+                if sheet.files.is_empty() {
+                    return Err(error("TRACK before FILE"));
+                }
+                let mut fields = rest.split_whitespace();
+                let number = fields
+                    .next()
+                    .and_then(|n| n.parse::<u8>().ok())
+                    .filter(|n| (1..=99).contains(n))
+                    .ok_or_else(|| error("bad track number"))?;
+                let mode = fields
+                    .next()
+                    .and_then(TrackMode::from_cue)
+                    .ok_or_else(|| error("unsupported track mode"))?;
+                if sheet.tracks.last().is_some_and(|t| t.number >= number) {
+                    return Err(error("track numbers must increase"));
+                }
+                sheet.tracks.push(CueTrack {
+                    number,
+                    mode,
+                    file: sheet.files.len() - 1,
+                    pregap: 0,
+                    postgap: 0,
+                    index0: None,
+                    index1: u32::MAX,
+                    title: None,
+                });
+            }
+            "INDEX" => {
+                let track = sheet.tracks.last_mut().ok_or_else(|| error("INDEX before TRACK"))?;
+                let mut fields = rest.split_whitespace();
+                let index = fields.next().and_then(|i| i.parse::<u8>().ok());
+                let frames = fields.next().and_then(parse_msf).ok_or_else(|| error("bad INDEX time"))?;
+                match index {
+                    Some(0) => track.index0 = Some(frames),
+                    Some(1) => track.index1 = frames,
+                    // Sub-indexes only matter to CD players' displays
+                    Some(_) => {}
+                    None => return Err(error("bad INDEX number")),
+                }
+            }
+            "PREGAP" | "POSTGAP" => {
+                let track = sheet.tracks.last_mut().ok_or_else(|| error("gap before TRACK"))?;
+                let frames = parse_msf(rest.trim()).ok_or_else(|| error("bad gap length"))?;
+                if keyword.eq_ignore_ascii_case("PREGAP") {
+                    track.pregap = frames;
+                } else {
+                    track.postgap = frames;
+                }
+            }
+            "TITLE" => {
+                let (title, _) = split_quoted(rest);
+                match sheet.tracks.last_mut() {
+                    Some(track) => track.title = Some(title.to_string()),
+                    None => sheet.title = Some(title.to_string()),
+                }
+            }
+            other => return Err(error(&format!("unknown command {}", other))),
+        }
+    }
+
+    if sheet.tracks.is_empty() {
+        return Err(CueError::NoTracks);
+    }
+    if let Some(track) = sheet.tracks.iter().find(|t| t.index1 == u32::MAX) {
+        return Err(CueError::Parse {
+            line: 0,
+            message: format!("track {} has no INDEX 01", track.number),
+        });
+    }
+    Ok(sheet)
+}
+
+/// Split "KEYWORD rest of line"
+fn split_keyword(line: &str) -> (&str, &str) {
+    match line.split_once(char::is_whitespace) {
+        Some((keyword, rest)) => (keyword, rest.trim()),
+        None => (line, ""),
+    }
+}
+
+/// Split a possibly quoted first field from the rest
+fn split_quoted(text: &str) -> (&str, &str) {
+    let text = text.trim();
+    if let Some(quoted) = text.strip_prefix('"') {
+        match quoted.split_once('"') {
+            Some((value, rest)) => (value, rest.trim()),
+            None => (quoted, ""),
+        }
+    } else {
+        split_keyword(text)
+    }
+}
+
+/// Parse mm:ss:ff into frames
+fn parse_msf(text: &str) -> Option<u32> {
+    let mut parts = text.split(':').map(|p| p.parse::<u32>().ok());
+    let (m, s, f) = (parts.next()??, parts.next()??, parts.next()??);
+    if parts.next().is_some() || s >= 60 || f >= CD_FRAMES_PER_SECOND {
+        return None;
+    }
+    Some((m * 60 + s) * CD_FRAMES_PER_SECOND + f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIXED_MODE: &str = r#"REM GENRE Game
+TITLE "Example Disc"
+FILE "Example Disc (Track 1).bin" BINARY
+  TRACK 01 MODE1/2352
+    INDEX 01 00:00:00
+FILE "Example Disc (Track 2).bin" BINARY
+  TRACK 02 AUDIO
+    TITLE "Intro"
+    INDEX 00 00:00:00
+    INDEX 01 00:02:00
+  TRACK 03 AUDIO
+    PREGAP 00:02:00
+    INDEX 01 00:10:00
+"#;
+
+    #[test]
+    fn test_parse() {
+        let sheet = parse(MIXED_MODE, Path::new("/isos")).unwrap();
+        assert_eq!(sheet.title.as_deref(), Some("Example Disc"));
+        assert_eq!(sheet.files[0], Path::new("/isos/Example Disc (Track 1).bin"));
+        assert_eq!(sheet.tracks.len(), 3);
+        assert_eq!(sheet.tracks[1].mode, TrackMode::Audio);
+        assert_eq!(sheet.tracks[1].title.as_deref(), Some("Intro"));
+        assert_eq!((sheet.tracks[1].index0, sheet.tracks[1].index1), (Some(0), 150));
+        assert_eq!((sheet.tracks[2].file, sheet.tracks[2].pregap), (1, 150));
+
+        assert!(matches!(
+            parse("FILE \"a.wav\" WAVE\n", Path::new(".")),
+            Err(CueError::Parse { line: 1, .. })
+        ));
+        assert!(matches!(
+            parse("FILE a.bin BINARY\nTRACK 01 MODE1/2352\n", Path::new(".")),
+            Err(CueError::Parse { .. })
+        ));
+        assert!(matches!(parse("REM nothing\n", Path::new(".")), Err(CueError::NoTracks)));
+    }
+
+    #[test]
+    fn test_layout() {
+        let sheet = parse(MIXED_MODE, Path::new("/isos")).unwrap();
+        // 1000 data sectors; 2000 audio sectors in the second file
+        let layout = sheet.layout(&[1000 * 2352, 2000 * 2352]).unwrap();
+
+        let starts: Vec<u32> = layout.toc.tracks.iter().map(|t| t.start_lba).collect();
+        // Track 2 starts after its 150-frame INDEX 00 gap, track 3 after
+        // its 150-frame PREGAP that is not in the file
+        assert_eq!(starts, vec![0, 1150, 1000 + 750 + 150]);
+        assert_eq!(layout.toc.lead_out, 1000 + 2000 + 150);
+        assert!(!layout.toc.tracks[0].audio && layout.toc.tracks[2].audio);
+
+        // Sector 1900 is the first of track 3's stored data (file frame 750)
+        let extent = layout.extent_at(1900).unwrap();
+        assert_eq!(extent.track, 3);
+        assert_eq!(extent.source, Source::File { file: 1, offset: 750 * 2352 });
+        assert_eq!(layout.extent_at(1800).unwrap().source, Source::Gap);
+        assert!(layout.extent_at(layout.toc.lead_out).is_none());
+
+        assert!(matches!(sheet.layout(&[1000 * 2352, 100]), Err(CueError::Truncated(_))));
+    }
+}
@@ -0,0 +1,536 @@
+//! Integrity check and repair of SunPCi disk images.
+//!
+//! `check_disk` looks an image over the way CHKDSK would, without the
+//! guest: the MBR signature and SunPCi header, the header's geometry
+//! against its sector count and the file's size, the partition table, and
+//! on a FAT12/FAT16 first partition whether the copies of the FAT agree
+//! and whether every allocated cluster belongs to a file or directory.
+//! Clusters that do not are lost chains, left behind when the guest was
+//! stopped in the middle of writing.
+//!
+//! With `repair` set the image is locked (see `media_check::MediaLock`) and
+//! what can be fixed safely is: a short file is extended, a header whose
+//! cylinder count does not match is recalculated, FAT copies are made
+//! equal to the first, and lost chains are freed. Damaged chains and
+//! cross-linked files are only reported; they need the guest's own tools.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::disk_image::{SECTOR_SIZE, SUNPCI_MAGIC};
+use crate::fat::{self, Bpb, DIR_ENTRY_SIZE, FatType};
+use crate::media_check::MediaLock;
+
+/// Deepest directory nesting followed
+const MAX_DEPTH: usize = 32;
+
+/// Kind of problem found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+    /// Sector 0 does not end in 55 AA
+    MbrSignature,
+    /// No SunPCi header in the MBR
+    SunpciMagic,
+    /// The header's geometry does not add up to its sector count
+    Geometry,
+    /// The file is shorter or longer than the disk
+    FileSize,
+    /// A partition reaches past the end of the disk
+    Partition,
+    /// The first partition has no readable FAT12/FAT16 boot sector
+    BootSector,
+    /// A FAT copy differs from the first
+    FatMismatch,
+    /// A chain runs into a free, reserved or out-of-range cluster, or loops
+    BadChain,
+    /// Two files or directories share clusters
+    CrossLinked,
+    /// Allocated clusters no file or directory owns
+    LostClusters,
+}
+
+/// A problem found by `check_disk`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Issue {
+    pub kind: IssueKind,
+    pub message: String,
+    /// Whether `check_disk` can fix it
+    pub repairable: bool,
+    /// Whether it was fixed in this run
+    pub repaired: bool,
+}
+
+/// The FAT volume of the first partition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VolumeSummary {
+    pub fat_type: &'static str,
+    pub clusters: u32,
+    pub used_clusters: u32,
+    pub files: u32,
+    pub directories: u32,
+    pub lost_clusters: u32,
+    pub lost_chains: u32,
+}
+
+/// Result of `check_disk`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+    pub path: PathBuf,
+    /// Whether the image was being repaired
+    pub repair: bool,
+    pub issues: Vec<Issue>,
+    pub volume: Option<VolumeSummary>,
+}
+
+impl CheckReport {
+    /// No problems, or all of them repaired
+    pub fn is_clean(&self) -> bool {
+        self.issues.iter().all(|i| i.repaired)
+    }
+
+    /// Problems left
+    pub fn remaining(&self) -> usize {
+        self.issues.iter().filter(|i| !i.repaired).count()
+    }
+
+    fn add(&mut self, kind: IssueKind, message: String, repairable: bool) {
+        self.issues.push(Issue {
+            kind,
+            message,
+            repairable,
+            repaired: false,
+        });
+    }
+
+    /// Mark the last issue repaired
+    fn repaired(&mut self) {
+        if let Some(issue) = self.issues.last_mut() {
+            issue.repaired = true;
+        }
+    }
+}
+
+/// Check the image at `path`, repairing what can be repaired if `repair` is
+/// set
+///
+/// Fails only if the image cannot be read (or, when repairing, locked or
+/// written).
+pub fn check_disk(path: &Path, repair: bool) -> io::Result<CheckReport> {
+    let _lock = if repair { Some(MediaLock::acquire(path, true)?) } else { None };
+    let file = OpenOptions::new().read(true).write(repair).open(path)?;
+    let mut report = CheckReport {
+        path: path.to_path_buf(),
+        repair,
+        issues: Vec::new(),
+        volume: None,
+    };
+
+    let file_sectors = file.metadata()?.len() / SECTOR_SIZE as u64;
+    let mut mbr = [0u8; 512];
+    file.read_exact_at(&mut mbr, 0)?;
+    if mbr[510] != 0x55 || mbr[511] != 0xAA {
+        report.add(IssueKind::MbrSignature, "sector 0 has no MBR signature".to_string(), false);
+        return Ok(report);
+    }
+
+    let disk_sectors = check_header(&file, &mut mbr, file_sectors, repair, &mut report)?;
+    if check_partitions(&mbr, disk_sectors, &mut report) {
+        check_volume(&file, &mbr, repair, &mut report)?;
+    }
+    if repair {
+        file.sync_all()?;
+    }
+    Ok(report)
+}
+
+/// Check the SunPCi header against itself and the file; the disk's sectors
+fn check_header(file: &File, mbr: &mut [u8; 512], file_sectors: u64, repair: bool, report: &mut CheckReport) -> io::Result<u64> {
+    if read_le32(mbr, 12) != SUNPCI_MAGIC {
+        report.add(IssueKind::SunpciMagic, "the MBR has no SunPCi header".to_string(), false);
+        return Ok(file_sectors);
+    }
+
+    let cylinders = u16::from_le_bytes([mbr[18], mbr[19]]) as u64;
+    let (heads, sectors_per_track) = (mbr[20] as u64, mbr[21] as u64);
+    let sectors = match read_le32(mbr, 22) as u64 {
+        0 => file_sectors,
+        n => n,
+    };
+    if heads == 0 || sectors_per_track == 0 || sectors_per_track > 63 {
+        report.add(
+            IssueKind::Geometry,
+            format!("the header has an invalid geometry of {} heads, {} sectors per track", heads, sectors_per_track),
+            false,
+        );
+    } else {
+        // Images converted from another format may end part way through a
+        // cylinder; the header counts whole ones, at most 1024
+        let expected = (sectors / (heads * sectors_per_track)).min(1024);
+        if cylinders != expected {
+            report.add(
+                IssueKind::Geometry,
+                format!("the header says {} cylinders; {} sectors make {}", cylinders, sectors, expected),
+                true,
+            );
+            if repair {
+                mbr[18..20].copy_from_slice(&(expected as u16).to_le_bytes());
+                file.write_all_at(&mbr[..], 0)?;
+                report.repaired();
+            }
+        }
+    }
+
+    if file_sectors < sectors {
+        report.add(
+            IssueKind::FileSize,
+            format!("the file holds {} of the disk's {} sectors", file_sectors, sectors),
+            true,
+        );
+        if repair {
+            file.set_len(sectors * SECTOR_SIZE as u64)?;
+            report.repaired();
+        }
+    } else if file_sectors > sectors {
+        report.add(
+            IssueKind::FileSize,
+            format!("the file has {} sectors past the end of the disk", file_sectors - sectors),
+            false,
+        );
+    }
+    Ok(sectors)
+}
+
+/// Check every partition lies on the disk; whether the first can be read
+fn check_partitions(mbr: &[u8; 512], sectors: u64, report: &mut CheckReport) -> bool {
+    let mut first_ok = true;
+    for n in 0..4 {
+        let entry = &mbr[0x1BE + n * 16..0x1CE + n * 16];
+        if entry[4] == 0 {
+            first_ok &= n != 0;
+            continue;
+        }
+        let end = read_le32(entry, 8) as u64 + read_le32(entry, 12) as u64;
+        if end > sectors {
+            report.add(
+                IssueKind::Partition,
+                format!("partition {} ends at sector {}, past the end of the {} sector disk", n + 1, end, sectors),
+                false,
+            );
+            first_ok &= n != 0;
+        }
+    }
+    first_ok
+}
+
+/// Check the FAT volume of the first partition
+fn check_volume(file: &File, mbr: &[u8; 512], repair: bool, report: &mut CheckReport) -> io::Result<()> {
+    let entry = &mbr[0x1BE..0x1CE];
+    if !matches!(entry[4], 0x01 | 0x04 | 0x06 | 0x0E) {
+        return Ok(());
+    }
+    let start = read_le32(entry, 8) as u64;
+    let mut boot = [0u8; 512];
+    file.read_exact_at(&mut boot, start * SECTOR_SIZE as u64)?;
+    let Some((bpb, fat_type)) = Bpb::parse(&boot).and_then(|b| Some((b, b.fat_type()?))) else {
+        report.add(
+            IssueKind::BootSector,
+            "the first partition has no FAT12/FAT16 boot sector".to_string(),
+            false,
+        );
+        return Ok(());
+    };
+    let volume = Volume { file, bpb, fat_type, start };
+
+    // The copies of the FAT
+    let mut fat = volume.read_fat(0)?;
+    for n in 1..bpb.fats {
+        if volume.read_fat(n)? != fat {
+            report.add(
+                IssueKind::FatMismatch,
+                format!("FAT copy {} differs from the first", n + 1),
+                true,
+            );
+            if repair {
+                volume.write_fat(n, &fat)?;
+                report.repaired();
+            }
+        }
+    }
+
+    // Which clusters the files and directories own
+    let mut walk = Walk {
+        volume: &volume,
+        fat: &fat,
+        owned: vec![false; bpb.cluster_count() as usize + fat::FIRST_CLUSTER as usize],
+        files: 0,
+        directories: 0,
+        visited_dirs: HashSet::new(),
+    };
+    let root = volume.read(volume.bpb.root_dir_start() as u64, bpb.root_dir_sectors() as u64)?;
+    walk.directory(&root, "", 0, report)?;
+    let (files, directories, owned) = (walk.files, walk.directories, walk.owned);
+
+    // Allocated clusters nobody owns
+    let clusters = bpb.cluster_count();
+    let lost: Vec<u32> = (fat::FIRST_CLUSTER..clusters + fat::FIRST_CLUSTER)
+        .filter(|&c| !owned[c as usize] && is_allocated(fat::fat_entry(&fat, fat_type, c), fat_type))
+        .collect();
+    let pointed_to: HashSet<u32> = lost.iter().map(|&c| fat::fat_entry(&fat, fat_type, c)).collect();
+    let heads = lost.iter().filter(|c| !pointed_to.contains(c)).count() as u32;
+    // Lost clusters that only point at each other make at least one loop
+    let lost_chains = if heads == 0 && !lost.is_empty() { 1 } else { heads };
+    if !lost.is_empty() {
+        report.add(
+            IssueKind::LostClusters,
+            format!("{} lost clusters in {} chains", lost.len(), lost_chains),
+            true,
+        );
+        if repair {
+            for &cluster in &lost {
+                fat::set_fat_entry(&mut fat, fat_type, cluster, 0);
+            }
+            for n in 0..bpb.fats {
+                volume.write_fat(n, &fat)?;
+            }
+            report.repaired();
+        }
+    }
+
+    let used_clusters = (fat::FIRST_CLUSTER..clusters + fat::FIRST_CLUSTER)
+        .filter(|&c| is_allocated(fat::fat_entry(&fat, fat_type, c), fat_type))
+        .count() as u32;
+    report.volume = Some(VolumeSummary {
+        fat_type: fat_type.name(),
+        clusters,
+        used_clusters,
+        files,
+        directories,
+        lost_clusters: lost.len() as u32,
+        lost_chains,
+    });
+    Ok(())
+}
+
+/// Whether a FAT entry marks its cluster as in use
+fn is_allocated(entry: u32, fat_type: FatType) -> bool {
+    entry != 0 && entry != fat_type.bad_cluster()
+}
+
+/// A FAT volume in an image
+struct Volume<'a> {
+    file: &'a File,
+    bpb: Bpb,
+    fat_type: FatType,
+    /// First sector of the volume on the disk
+    start: u64,
+}
+
+impl Volume<'_> {
+    /// `count` sectors from `sector` of the volume
+    fn read(&self, sector: u64, count: u64) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; (count * SECTOR_SIZE as u64) as usize];
+        self.file.read_exact_at(&mut buf, (self.start + sector) * SECTOR_SIZE as u64)?;
+        Ok(buf)
+    }
+
+    fn read_fat(&self, n: u8) -> io::Result<Vec<u8>> {
+        self.read(self.bpb.fat_start(n) as u64, self.bpb.sectors_per_fat as u64)
+    }
+
+    fn write_fat(&self, n: u8, fat: &[u8]) -> io::Result<()> {
+        let at = (self.start + self.bpb.fat_start(n) as u64) * SECTOR_SIZE as u64;
+        self.file.write_all_at(fat, at)
+    }
+}
+
+/// Walk of the directory tree, marking the clusters it reaches
+struct Walk<'a> {
+    volume: &'a Volume<'a>,
+    fat: &'a [u8],
+    owned: Vec<bool>,
+    files: u32,
+    directories: u32,
+    visited_dirs: HashSet<u32>,
+}
+
+impl Walk<'_> {
+    /// Follow the entries of a directory, `name` being its path
+    fn directory(&mut self, entries: &[u8], name: &str, depth: usize, report: &mut CheckReport) -> io::Result<()> {
+        for entry in entries.chunks_exact(DIR_ENTRY_SIZE as usize) {
+            match entry[0] {
+                0x00 => break,
+                0xE5 | b'.' => continue,
+                _ => {}
+            }
+            let attributes = entry[11];
+            // Long file name pieces and the volume label
+            if attributes & 0x0F == 0x0F || attributes & 0x08 != 0 {
+                continue;
+            }
+            let path = format!("{}\\{}", name, short_name(entry));
+            let first = u16::from_le_bytes([entry[26], entry[27]]) as u32;
+            let is_dir = attributes & 0x10 != 0;
+            if first == 0 {
+                if is_dir {
+                    report.add(IssueKind::BadChain, format!("directory {} has no clusters", path), false);
+                } else {
+                    self.files += 1;
+                }
+                continue;
+            }
+
+            let chain = self.chain(first, &path, report);
+            if is_dir {
+                self.directories += 1;
+                if depth >= MAX_DEPTH || !self.visited_dirs.insert(first) {
+                    continue;
+                }
+                let spc = self.volume.bpb.sectors_per_cluster as u64;
+                let mut contents = Vec::new();
+                for &cluster in &chain {
+                    let sector = self.volume.bpb.cluster_start(cluster) as u64;
+                    contents.extend(self.volume.read(sector, spc)?);
+                }
+                self.directory(&contents, &path, depth + 1, report)?;
+            } else {
+                self.files += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// The clusters of the chain starting at `first`, up to where it breaks
+    fn chain(&mut self, first: u32, path: &str, report: &mut CheckReport) -> Vec<u32> {
+        let fat_type = self.volume.fat_type;
+        let last = self.volume.bpb.cluster_count() + fat::FIRST_CLUSTER - 1;
+        let mut chain = Vec::new();
+        let mut seen = HashSet::new();
+        let mut cluster = first;
+        loop {
+            if !(fat::FIRST_CLUSTER..=last).contains(&cluster) {
+                report.add(IssueKind::BadChain, format!("{} runs to invalid cluster {}", path, cluster), false);
+                break;
+            }
+            if !seen.insert(cluster) {
+                report.add(IssueKind::BadChain, format!("{} loops at cluster {}", path, cluster), false);
+                break;
+            }
+            if self.owned[cluster as usize] {
+                report.add(IssueKind::CrossLinked, format!("{} is cross-linked at cluster {}", path, cluster), false);
+                break;
+            }
+            self.owned[cluster as usize] = true;
+            chain.push(cluster);
+
+            let next = fat::fat_entry(self.fat, fat_type, cluster);
+            if next >= fat_type.end_of_chain() {
+                break;
+            }
+            if next == 0 || next == fat_type.bad_cluster() {
+                report.add(
+                    IssueKind::BadChain,
+                    format!("{} runs into a {} cluster after {}", path, if next == 0 { "free" } else { "bad" }, cluster),
+                    false,
+                );
+                break;
+            }
+            cluster = next;
+        }
+        chain
+    }
+}
+
+/// 8.3 name of a directory entry
+fn short_name(entry: &[u8]) -> String {
+    let base = String::from_utf8_lossy(&entry[..8]).trim_end().to_string();
+    let ext = String::from_utf8_lossy(&entry[8..11]).trim_end().to_string();
+    if ext.is_empty() { base } else { format!("{}.{}", base, ext) }
+}
+
+fn read_le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::{Allocation, create_disk_image};
+    use crate::progress::ProgressReporter;
+
+    /// Write `fat` to every copy
+    fn write_fats(file: &File, bpb: &Bpb, fat: &[u8]) {
+        for n in 0..bpb.fats {
+            file.write_all_at(fat, (63 + bpb.fat_start(n) as u64) * 512).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_clean_and_damaged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.diskimage");
+        create_disk_image(&path, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        let report = check_disk(&path, false).unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!(report.volume.as_ref().unwrap().fat_type, "FAT16");
+
+        // A file in clusters 2-3, a lost chain at 10-11, and a stale copy
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let mut boot = [0u8; 512];
+        file.read_exact_at(&mut boot, 63 * 512).unwrap();
+        let bpb = Bpb::parse(&boot).unwrap();
+        let mut fat = vec![0u8; bpb.sectors_per_fat as usize * 512];
+        file.read_exact_at(&mut fat, (63 + bpb.fat_start(0) as u64) * 512).unwrap();
+        for (cluster, next) in [(2, 3), (3, 0xFFFF), (10, 11), (11, 0xFFFF)] {
+            fat::set_fat_entry(&mut fat, FatType::Fat16, cluster, next);
+        }
+        write_fats(&file, &bpb, &fat);
+        file.write_all_at(&[1], (63 + bpb.fat_start(1) as u64) * 512 + 100).unwrap();
+        let mut entry = [0u8; 32];
+        entry[..11].copy_from_slice(b"AUTOEXECBAT");
+        entry[26] = 2;
+        file.write_all_at(&entry, (63 + bpb.root_dir_start() as u64) * 512).unwrap();
+        drop(file);
+
+        let report = check_disk(&path, false).unwrap();
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, [IssueKind::FatMismatch, IssueKind::LostClusters]);
+        let volume = report.volume.unwrap();
+        assert_eq!((volume.files, volume.lost_clusters, volume.lost_chains), (1, 2, 1));
+        assert!(serde_json::to_string(&report.issues).unwrap().contains("\"lost_clusters\""));
+
+        let repaired = check_disk(&path, true).unwrap();
+        assert!(repaired.is_clean());
+        let report = check_disk(&path, false).unwrap();
+        assert!(report.is_clean(), "{:?}", report.issues);
+        assert_eq!(report.volume.unwrap().used_clusters, 2);
+    }
+
+    #[test]
+    fn test_header_problems() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.diskimage");
+        create_disk_image(&path, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all_at(&7u16.to_le_bytes(), 18).unwrap();
+        let length = file.metadata().unwrap().len();
+        file.set_len(length - 4096).unwrap();
+        drop(file);
+
+        let report = check_disk(&path, true).unwrap();
+        let kinds: Vec<IssueKind> = report.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(kinds, [IssueKind::Geometry, IssueKind::FileSize]);
+        assert!(report.is_clean());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), length);
+        assert!(check_disk(&path, false).unwrap().is_clean());
+
+        std::fs::write(&path, [0u8; 1024]).unwrap();
+        let report = check_disk(&path, false).unwrap();
+        assert_eq!(report.issues[0].kind, IssueKind::MbrSignature);
+    }
+}
@@ -0,0 +1,200 @@
+//! Giving back the host space of free clusters in sparse disk images.
+//!
+//! A sparse image only grows: once the guest has written a cluster its
+//! blocks stay allocated on the host after the file in it is deleted.
+//! `compact_disk_image` finds the clusters of the first partition's FAT
+//! volume that the FAT marks free and that hold only zeros, and punches
+//! holes over them (`FALLOC_FL_PUNCH_HOLE`), so the host reclaims the space
+//! while the guest still reads zeros there. Free clusters that still hold
+//! old data are left alone; zeroing the free space in the guest first
+//! (e.g. with a zero-fill utility) lets all of it be reclaimed.
+//!
+//! Images created preallocated (see `disk_image::Allocation`) are left
+//! alone, since punching holes would undo what their owner asked for.
+//!
+//! The image is locked while it is compacted (see `media_check::MediaLock`).
+//! Only sectors that already read as zeros are touched, so cancelling part
+//! way leaves a consistent image.
+
+use std::fs::{File, OpenOptions};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::Path;
+
+use anyhow::{Context, bail};
+use nix::errno::Errno;
+use nix::fcntl::{self, FallocateFlags};
+use nix::libc;
+
+use crate::disk_image::{Allocation, SECTOR_SIZE, SUNPCI_MAGIC};
+use crate::fat::{self, Bpb};
+use crate::media_check::MediaLock;
+use crate::progress::ProgressReporter;
+
+/// Bytes of clusters read per step, between progress updates
+const CHUNK: u64 = 4 * 1024 * 1024;
+
+/// What a compaction did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compacted {
+    /// Free clusters of zeros holes were punched over
+    pub clusters: u32,
+    /// Host bytes allocated to the image before
+    pub allocated_before: u64,
+    /// Host bytes allocated to the image after
+    pub allocated_after: u64,
+}
+
+impl Compacted {
+    /// Host bytes given back
+    pub fn reclaimed(&self) -> u64 {
+        self.allocated_before.saturating_sub(self.allocated_after)
+    }
+}
+
+/// Punch holes over the free, zeroed clusters of the image at `path`
+///
+/// Reports progress in clusters and can be cancelled between steps.
+pub fn compact_disk_image(path: &Path, progress: &ProgressReporter) -> anyhow::Result<Compacted> {
+    let _lock = MediaLock::acquire(path, true).with_context(|| format!("{} is in use", path.display()))?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let allocated_before = allocated(&file)?;
+
+    let mut mbr = [0u8; 512];
+    file.read_exact_at(&mut mbr, 0)?;
+    let entry = &mbr[0x1BE..0x1CE];
+    if mbr[510] != 0x55 || mbr[511] != 0xAA || entry[4] == 0 {
+        bail!("{} has no partition", path.display());
+    }
+    let magic = u32::from_le_bytes(mbr[12..16].try_into().unwrap());
+    if magic == SUNPCI_MAGIC && !Allocation::from_header(&mbr).is_sparse() {
+        bail!("{} is preallocated; it is not compacted", path.display());
+    }
+    let start = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as u64 * SECTOR_SIZE as u64;
+    let mut boot = [0u8; 512];
+    file.read_exact_at(&mut boot, start)?;
+    let Some((bpb, fat_type)) = Bpb::parse(&boot).and_then(|b| Some((b, b.fat_type()?))) else {
+        bail!("the first partition of {} is not a FAT12 or FAT16 volume", path.display());
+    };
+    let mut table = vec![0u8; bpb.sectors_per_fat as usize * SECTOR_SIZE as usize];
+    file.read_exact_at(&mut table, start + bpb.fat_start(0) as u64 * SECTOR_SIZE as u64)?;
+
+    let clusters = bpb.cluster_count();
+    let cluster_bytes = bpb.sectors_per_cluster as u64 * SECTOR_SIZE as u64;
+    let per_step = (CHUNK / cluster_bytes).max(1) as u32;
+    let cluster_offset = |cluster: u32| start + bpb.cluster_start(cluster) as u64 * SECTOR_SIZE as u64;
+    progress.set_progress(0, clusters as u64);
+
+    let mut punched = 0;
+    // The run of punchable clusters being gathered: (offset, length)
+    let mut run: Option<(u64, u64)> = None;
+    let mut buf = vec![0u8; (per_step as u64 * cluster_bytes) as usize];
+    let last = clusters + fat::FIRST_CLUSTER;
+    let mut first = fat::FIRST_CLUSTER;
+    while first < last {
+        let count = per_step.min(last - first);
+        let window = &mut buf[..(count as u64 * cluster_bytes) as usize];
+        file.read_exact_at(window, cluster_offset(first))?;
+
+        for (i, data) in window.chunks(cluster_bytes as usize).enumerate() {
+            let cluster = first + i as u32;
+            let free = fat::fat_entry(&table, fat_type, cluster) == 0;
+            if free && data.iter().all(|&b| b == 0) {
+                punched += 1;
+                let offset = cluster_offset(cluster);
+                run = match run {
+                    Some((at, len)) if at + len == offset => Some((at, len + cluster_bytes)),
+                    other => {
+                        if let Some(other) = other {
+                            punch_hole(&file, other, path)?;
+                        }
+                        Some((offset, cluster_bytes))
+                    }
+                };
+            }
+        }
+
+        first += count;
+        progress.set_progress((first - fat::FIRST_CLUSTER) as u64, clusters as u64);
+        if progress.is_cancelled() {
+            break;
+        }
+    }
+    if let Some(run) = run {
+        punch_hole(&file, run, path)?;
+    }
+    file.sync_all()?;
+    progress.check_cancelled()?;
+
+    Ok(Compacted {
+        clusters: punched,
+        allocated_before,
+        allocated_after: allocated(&file)?,
+    })
+}
+
+/// Deallocate `(offset, length)` of the image, keeping its size
+fn punch_hole(file: &File, (offset, length): (u64, u64), path: &Path) -> anyhow::Result<()> {
+    let mode = FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE;
+    match fcntl::fallocate(file.as_raw_fd(), mode, offset as libc::off_t, length as libc::off_t) {
+        Ok(()) => Ok(()),
+        Err(Errno::EOPNOTSUPP) => bail!("the filesystem holding {} cannot punch holes", path.display()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Host bytes allocated to a file
+fn allocated(file: &File) -> std::io::Result<u64> {
+    Ok(file.metadata()?.blocks() * 512)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::{Allocation, create_disk_image};
+    use crate::fat::FatType;
+
+    #[test]
+    fn test_compact() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.diskimage");
+        create_disk_image(&path, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+
+        // Clusters 2-65 written with zeros (free), 66 with zeros but in use,
+        // 67 free but holding old data
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let mut boot = [0u8; 512];
+        file.read_exact_at(&mut boot, 63 * 512).unwrap();
+        let bpb = Bpb::parse(&boot).unwrap();
+        let at = |cluster: u32| (63 + bpb.cluster_start(cluster) as u64) * 512;
+        let cluster_bytes = bpb.sectors_per_cluster as usize * 512;
+        file.write_all_at(&vec![0u8; cluster_bytes * 65], at(2)).unwrap();
+        file.write_all_at(&vec![0x55; cluster_bytes], at(67)).unwrap();
+        let mut table = vec![0u8; bpb.sectors_per_fat as usize * 512];
+        file.read_exact_at(&mut table, (63 + bpb.fat_start(0) as u64) * 512).unwrap();
+        fat::set_fat_entry(&mut table, FatType::Fat16, 66, 0xFFFF);
+        for n in 0..bpb.fats {
+            file.write_all_at(&table, (63 + bpb.fat_start(n) as u64) * 512).unwrap();
+        }
+        file.sync_all().unwrap();
+        drop(file);
+
+        let compacted = match compact_disk_image(&path, &ProgressReporter::new()) {
+            Err(e) if e.to_string().contains("cannot punch holes") => return,
+            result => result.unwrap(),
+        };
+        assert_eq!(compacted.clusters, bpb.cluster_count() - 2);
+        assert!(compacted.reclaimed() > 0);
+
+        let file = File::open(&path).unwrap();
+        let mut data = vec![0u8; cluster_bytes];
+        file.read_exact_at(&mut data, at(67)).unwrap();
+        assert!(data.iter().all(|&b| b == 0x55));
+        file.read_exact_at(&mut data, at(2)).unwrap();
+        assert!(data.iter().all(|&b| b == 0));
+    }
+}
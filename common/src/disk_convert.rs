@@ -0,0 +1,583 @@
+//! Converting disk images between formats.
+//!
+//! Images made for DOSBox or QEMU can be used once converted, and SunPCi
+//! images can be taken to them:
+//!
+//! - raw: a flat disk starting with its MBR (DOSBox `.img`, QEMU raw)
+//! - SunPCi: a raw disk whose MBR carries the SunPCi header in bytes 12-25
+//!   (see `disk_image`), which take the place of those bytes of boot code
+//! - VHD: a fixed VHD (the raw disk and a footer) is written; fixed and
+//!   dynamic VHDs are read, differencing ones are not
+//!
+//! The sectors are copied unchanged apart from the SunPCi header, which is
+//! added or cleared. The partition table is checked against the size of the
+//! disk first, and a SunPCi header gets the geometry the first partition
+//! was laid out with, so DOS finds its sectors where it left them. Output
+//! is sparse wherever the source has zeros.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, bail};
+
+use crate::disk_image::{self, SECTOR_SIZE, SUNPCI_MAGIC};
+use crate::diskspace;
+use crate::fat::Bpb;
+use crate::overlay;
+use crate::progress::ProgressReporter;
+use crate::sha256::Sha256;
+
+/// Revision given to images converted to SunPCi
+pub const SUNPCI_REVISION: u8 = 2;
+
+/// Bytes copied per step, between progress updates and cancellation checks
+const CHUNK: usize = 4 * 1024 * 1024;
+
+/// VHD footer signature
+const VHD_COOKIE: &[u8; 8] = b"conectix";
+/// VHD dynamic disk header signature
+const VHD_SPARSE_COOKIE: &[u8; 8] = b"cxsparse";
+/// VHD timestamps count from 2000-01-01
+const VHD_EPOCH: u64 = 946_684_800;
+/// BAT entry of a block that holds no data
+const VHD_UNALLOCATED: u32 = 0xFFFF_FFFF;
+
+/// A disk image format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiskFormat {
+    Raw,
+    SunPci,
+    Vhd,
+}
+
+impl DiskFormat {
+    /// Format for a name given by the user ("raw", "sunpci" or "vhd")
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" | "img" => Some(Self::Raw),
+            "sunpci" | "diskimage" => Some(Self::SunPci),
+            "vhd" | "vpc" => Some(Self::Vhd),
+            _ => None,
+        }
+    }
+
+    /// Format a file named `path` is likely meant to have
+    pub fn for_path(path: &Path) -> Self {
+        let ext = path.extension().map(|e| e.to_string_lossy().to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("vhd") => Self::Vhd,
+            Some("img" | "ima" | "raw" | "bin") => Self::Raw,
+            _ => Self::SunPci,
+        }
+    }
+
+    /// Name as accepted by `parse`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Raw => "raw",
+            Self::SunPci => "sunpci",
+            Self::Vhd => "vhd",
+        }
+    }
+}
+
+/// What a conversion did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Converted {
+    pub from: DiskFormat,
+    pub to: DiskFormat,
+    /// Sectors of the disk
+    pub sectors: u64,
+    /// Geometry written to the SunPCi header or VHD footer, if any
+    pub geometry: Option<(u16, u8, u8)>,
+}
+
+/// Where the sectors of a source image are
+enum Sectors {
+    /// In one run starting at `offset`
+    Flat(u64),
+    /// In the blocks a dynamic VHD's block allocation table points at
+    Dynamic { bat: Vec<u32>, block_size: u64 },
+}
+
+/// An image being converted
+struct Source {
+    file: File,
+    format: DiskFormat,
+    sectors: u64,
+    layout: Sectors,
+    revision: u8,
+}
+
+impl Source {
+    /// Open `path`, working out its format
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        if overlay::read_overlay(path)?.is_some() {
+            bail!("{} is an overlay; convert its template instead", path.display());
+        }
+        let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+        let len = file.metadata()?.len();
+        if len < SECTOR_SIZE as u64 {
+            bail!("{} is too small to be a disk image", path.display());
+        }
+
+        let mut footer = [0u8; 512];
+        file.read_exact_at(&mut footer, len - 512)?;
+        if &footer[..8] == VHD_COOKIE {
+            return Self::open_vhd(file, &footer, len);
+        }
+
+        if !len.is_multiple_of(SECTOR_SIZE as u64) {
+            bail!("{} is not a whole number of sectors", path.display());
+        }
+        let mut mbr = [0u8; 512];
+        file.read_exact_at(&mut mbr, 0)?;
+        let sunpci = has_mbr(&mbr) && read_le32(&mbr, 12) == SUNPCI_MAGIC;
+        Ok(Self {
+            file,
+            format: if sunpci { DiskFormat::SunPci } else { DiskFormat::Raw },
+            sectors: len / SECTOR_SIZE as u64,
+            layout: Sectors::Flat(0),
+            revision: if sunpci { mbr[16] } else { SUNPCI_REVISION },
+        })
+    }
+
+    fn open_vhd(file: File, footer: &[u8; 512], len: u64) -> anyhow::Result<Self> {
+        if vhd_checksum(footer) != read_be32(footer, 64) {
+            bail!("the VHD footer is damaged (checksum mismatch)");
+        }
+        let size = read_be64(footer, 48);
+        if size == 0 || !size.is_multiple_of(SECTOR_SIZE as u64) {
+            bail!("the VHD has an invalid size of {} bytes", size);
+        }
+        let layout = match read_be32(footer, 60) {
+            2 => {
+                if len < size + 512 {
+                    bail!("the VHD is truncated: {} bytes of a {} byte disk", len - 512, size);
+                }
+                Sectors::Flat(0)
+            }
+            3 => {
+                let mut header = [0u8; 1024];
+                file.read_exact_at(&mut header, read_be64(footer, 16))?;
+                if &header[..8] != VHD_SPARSE_COOKIE {
+                    bail!("the VHD's dynamic disk header is missing");
+                }
+                let block_size = read_be32(&header, 32) as u64;
+                if block_size == 0 || !block_size.is_multiple_of(SECTOR_SIZE as u64) {
+                    bail!("the VHD has an invalid block size of {} bytes", block_size);
+                }
+                let entries = read_be32(&header, 28) as usize;
+                if (entries as u64) < size.div_ceil(block_size) {
+                    bail!("the VHD's block table is too short for its size");
+                }
+                let mut table = vec![0u8; entries * 4];
+                file.read_exact_at(&mut table, read_be64(&header, 16))?;
+                let bat = table.chunks_exact(4).map(|e| read_be32(e, 0)).collect();
+                Sectors::Dynamic { bat, block_size }
+            }
+            4 => bail!("differencing VHDs are not supported; merge it with its parent first"),
+            other => bail!("unknown VHD disk type {}", other),
+        };
+        Ok(Self {
+            file,
+            format: DiskFormat::Vhd,
+            sectors: size / SECTOR_SIZE as u64,
+            layout,
+            revision: SUNPCI_REVISION,
+        })
+    }
+
+    /// Fill `buf` with the disk's bytes from `at`
+    fn read(&self, at: u64, buf: &mut [u8]) -> io::Result<()> {
+        let (bat, block_size) = match self.layout {
+            Sectors::Flat(offset) => return self.file.read_exact_at(buf, offset + at),
+            Sectors::Dynamic { ref bat, block_size } => (bat, block_size),
+        };
+        // Each block is a sector bitmap followed by the sectors; sectors
+        // not marked in the bitmap read as zeros
+        let bitmap_len = (block_size / SECTOR_SIZE as u64).div_ceil(8).next_multiple_of(SECTOR_SIZE as u64);
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = at + done as u64;
+            let within = pos % block_size;
+            let n = (buf.len() - done).min((block_size - within) as usize);
+            let out = &mut buf[done..done + n];
+            match bat.get((pos / block_size) as usize) {
+                Some(&entry) if entry != VHD_UNALLOCATED => {
+                    let base = entry as u64 * SECTOR_SIZE as u64;
+                    let mut bitmap = vec![0u8; bitmap_len as usize];
+                    self.file.read_exact_at(&mut bitmap, base)?;
+                    self.file.read_exact_at(out, base + bitmap_len + within)?;
+                    let first = within / SECTOR_SIZE as u64;
+                    for (i, sector) in out.chunks_mut(SECTOR_SIZE as usize).enumerate() {
+                        let s = (first + i as u64) as usize;
+                        if bitmap[s / 8] & (0x80 >> (s % 8)) == 0 {
+                            sector.fill(0);
+                        }
+                    }
+                }
+                _ => out.fill(0),
+            }
+            done += n;
+        }
+        Ok(())
+    }
+}
+
+/// Format of the image at `path`
+pub fn detect_format(path: &Path) -> anyhow::Result<DiskFormat> {
+    Ok(Source::open(path)?.format)
+}
+
+/// Convert the image at `src` to `format`, writing it to `dst`, which must
+/// not exist
+///
+/// Reports progress in bytes and checks for cancellation between chunks; an
+/// unfinished image is removed.
+pub fn convert_disk(
+    src: &Path,
+    dst: &Path,
+    format: DiskFormat,
+    progress: &ProgressReporter,
+) -> anyhow::Result<Converted> {
+    let source = Source::open(src)?;
+    let mut mbr = [0u8; 512];
+    source.read(0, &mut mbr)?;
+    check_partitions(&mbr, source.sectors)?;
+
+    let geometry = match format {
+        DiskFormat::SunPci => {
+            if !has_mbr(&mbr) {
+                bail!("{} has no partition table to hold the SunPCi header", src.display());
+            }
+            let boot = first_boot_sector(&source, &mbr)?;
+            let geometry = sunpci_geometry(&mbr, boot.as_ref(), source.sectors)?;
+            write_sunpci_header(&mut mbr, source.revision, geometry, source.sectors);
+            Some(geometry)
+        }
+        DiskFormat::Raw | DiskFormat::Vhd => {
+            if source.format == DiskFormat::SunPci {
+                mbr[12..26].fill(0);
+            }
+            (format == DiskFormat::Vhd).then(|| vhd_geometry(source.sectors))
+        }
+    };
+
+    let length = source.sectors * SECTOR_SIZE as u64;
+    let allocated = source.file.metadata()?.blocks() * 512;
+    let required = diskspace::space_required(length, allocated + 512, true);
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    diskspace::check_free_space(dst, required)?;
+    progress.set_progress(0, length);
+    progress.check_cancelled()?;
+
+    let target = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dst)
+        .with_context(|| format!("failed to create {}", dst.display()))?;
+    let result = copy_sectors(&source, &target, &mbr, progress).and_then(|()| {
+        if let (DiskFormat::Vhd, Some(geometry)) = (format, geometry) {
+            target.write_all_at(&vhd_footer(length, geometry, dst), length)?;
+        }
+        target.sync_all()?;
+        Ok(())
+    });
+    if let Err(e) = result {
+        drop(target);
+        let _ = fs::remove_file(dst);
+        return Err(diskspace::map_disk_full(e, dst, required));
+    }
+
+    Ok(Converted {
+        from: source.format,
+        to: format,
+        sectors: source.sectors,
+        geometry,
+    })
+}
+
+/// Copy the source's sectors to `target`, with `mbr` in place of sector 0
+fn copy_sectors(source: &Source, target: &File, mbr: &[u8; 512], progress: &ProgressReporter) -> anyhow::Result<()> {
+    let length = source.sectors * SECTOR_SIZE as u64;
+    let mut buf = vec![0u8; CHUNK];
+    let mut at = 0;
+    while at < length {
+        let n = (length - at).min(CHUNK as u64) as usize;
+        let chunk = &mut buf[..n];
+        source.read(at, chunk)?;
+        if at == 0 {
+            chunk[..512].copy_from_slice(mbr);
+        }
+        // Zeros are left as holes
+        if chunk.iter().any(|&b| b != 0) {
+            target.write_all_at(chunk, at)?;
+        }
+        at += n as u64;
+        progress.set_progress(at, length);
+        progress.check_cancelled()?;
+    }
+    target.set_len(length)?;
+    Ok(())
+}
+
+/// Fail if a partition does not fit on a disk of `sectors` sectors
+fn check_partitions(mbr: &[u8; 512], sectors: u64) -> anyhow::Result<()> {
+    if !has_mbr(mbr) {
+        return Ok(());
+    }
+    for n in 0..4 {
+        let entry = &mbr[0x1BE + n * 16..0x1CE + n * 16];
+        if entry[4] == 0 {
+            continue;
+        }
+        let end = read_le32(entry, 8) as u64 + read_le32(entry, 12) as u64;
+        if end > sectors {
+            bail!(
+                "partition {} ends at sector {}, past the end of the {} sector disk",
+                n + 1,
+                end,
+                sectors
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Boot sector of the first partition, if it has a FAT one
+fn first_boot_sector(source: &Source, mbr: &[u8; 512]) -> io::Result<Option<Bpb>> {
+    let entry = &mbr[0x1BE..0x1CE];
+    if entry[4] == 0 {
+        return Ok(None);
+    }
+    let mut boot = [0u8; 512];
+    source.read(read_le32(entry, 8) as u64 * SECTOR_SIZE as u64, &mut boot)?;
+    Ok(Bpb::parse(&boot))
+}
+
+/// Geometry for a SunPCi header: the heads and sectors per track the first
+/// partition was laid out with (its end address, or its BPB), the usual
+/// ones for the size if there is none
+fn sunpci_geometry(mbr: &[u8; 512], bpb: Option<&Bpb>, sectors: u64) -> anyhow::Result<(u16, u8, u8)> {
+    let entry = &mbr[0x1BE..0x1CE];
+    let from_table = (entry[4] != 0 && entry[6] & 0x3F != 0).then(|| (entry[5] as u16 + 1, (entry[6] & 0x3F) as u16));
+    let from_bpb = bpb.map(|b| (b.heads, b.sectors_per_track));
+    let (heads, sectors_per_track) = match (from_table, from_bpb) {
+        (Some(table), Some(bpb)) if table != bpb => bail!(
+            "the partition table ({} heads, {} sectors per track) and the boot sector ({} heads, {} sectors per track) disagree on the geometry",
+            table.0,
+            table.1,
+            bpb.0,
+            bpb.1
+        ),
+        (Some(geometry), _) | (None, Some(geometry)) => geometry,
+        (None, None) => {
+            let (_, heads, sectors_per_track) =
+                disk_image::calculate_geometry((sectors * SECTOR_SIZE as u64 / (1024 * 1024)) as u32);
+            (heads as u16, sectors_per_track as u16)
+        }
+    };
+    if heads == 0 || heads > 255 || sectors_per_track == 0 || sectors_per_track > 63 {
+        bail!("invalid geometry: {} heads, {} sectors per track", heads, sectors_per_track);
+    }
+    let cylinders = (sectors / (heads as u64 * sectors_per_track as u64)).min(1024) as u16;
+    Ok((cylinders, heads as u8, sectors_per_track as u8))
+}
+
+/// Put the SunPCi header into an MBR
+fn write_sunpci_header(mbr: &mut [u8; 512], revision: u8, (cylinders, heads, sectors_per_track): (u16, u8, u8), sectors: u64) {
+    mbr[12..16].copy_from_slice(&SUNPCI_MAGIC.to_le_bytes());
+    mbr[16] = revision;
+    mbr[17] = 0;
+    mbr[18..20].copy_from_slice(&cylinders.to_le_bytes());
+    mbr[20] = heads;
+    mbr[21] = sectors_per_track;
+    mbr[22..26].copy_from_slice(&(sectors.min(u32::MAX as u64) as u32).to_le_bytes());
+}
+
+/// CHS geometry for a VHD of `sectors` sectors, as the VHD specification
+/// calculates it
+fn vhd_geometry(sectors: u64) -> (u16, u8, u8) {
+    let total = sectors.min(65535 * 16 * 255);
+    let (heads, sectors_per_track, cylinder_heads) = if total >= 65535 * 16 * 63 {
+        (16, 255, total / 255)
+    } else {
+        let mut spt = 17;
+        let mut cth = total / spt;
+        let mut heads = cth.div_ceil(1024).max(4);
+        if cth >= heads * 1024 || heads > 16 {
+            spt = 31;
+            heads = 16;
+            cth = total / spt;
+        }
+        if cth >= heads * 1024 {
+            spt = 63;
+            heads = 16;
+            cth = total / spt;
+        }
+        (heads, spt, cth)
+    };
+    ((cylinder_heads / heads) as u16, heads as u8, sectors_per_track as u8)
+}
+
+/// Footer of a fixed VHD holding `size` bytes
+fn vhd_footer(size: u64, (cylinders, heads, sectors_per_track): (u16, u8, u8), path: &Path) -> [u8; 512] {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut id = Sha256::new();
+    id.update(path.as_os_str().as_encoded_bytes());
+    id.update(&now.as_nanos().to_le_bytes());
+
+    let mut footer = [0u8; 512];
+    footer[..8].copy_from_slice(VHD_COOKIE);
+    footer[8..12].copy_from_slice(&2u32.to_be_bytes()); // features: reserved bit
+    footer[12..16].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // version 1.0
+    footer[16..24].copy_from_slice(&u64::MAX.to_be_bytes()); // no dynamic header
+    footer[24..28].copy_from_slice(&(now.as_secs().saturating_sub(VHD_EPOCH) as u32).to_be_bytes());
+    footer[28..32].copy_from_slice(b"rsun");
+    footer[32..36].copy_from_slice(&0x0001_0000u32.to_be_bytes());
+    footer[36..40].copy_from_slice(b"Wi2k");
+    footer[40..48].copy_from_slice(&size.to_be_bytes());
+    footer[48..56].copy_from_slice(&size.to_be_bytes());
+    footer[56..58].copy_from_slice(&cylinders.to_be_bytes());
+    footer[58] = heads;
+    footer[59] = sectors_per_track;
+    footer[60..64].copy_from_slice(&2u32.to_be_bytes()); // fixed disk
+    footer[68..84].copy_from_slice(&id.finish()[..16]);
+    let checksum = vhd_checksum(&footer);
+    footer[64..68].copy_from_slice(&checksum.to_be_bytes());
+    footer
+}
+
+/// One's complement of the sum of the footer's bytes, checksum field excluded
+fn vhd_checksum(footer: &[u8; 512]) -> u32 {
+    let sum = footer
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !(64..68).contains(i))
+        .fold(0u32, |sum, (_, &b)| sum.wrapping_add(b as u32));
+    !sum
+}
+
+fn has_mbr(mbr: &[u8]) -> bool {
+    mbr[510] == 0x55 && mbr[511] == 0xAA
+}
+
+fn read_le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_be32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_be64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_be_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::{Allocation, create_disk_image, read_disk_header};
+
+    fn convert(src: &Path, dst: &Path, format: DiskFormat) -> Converted {
+        convert_disk(src, dst, format, &ProgressReporter::new()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("c.diskimage");
+        create_disk_image(&original, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        File::options().write(true).open(&original).unwrap().write_all_at(b"DATA", 5_000_000).unwrap();
+        let header = read_disk_header(&original).unwrap();
+
+        let raw = dir.path().join("c.img");
+        let converted = convert(&original, &raw, DiskFormat::Raw);
+        assert_eq!(converted.from, DiskFormat::SunPci);
+        assert_eq!(detect_format(&raw).unwrap(), DiskFormat::Raw);
+
+        let vhd = dir.path().join("c.vhd");
+        convert(&raw, &vhd, DiskFormat::Vhd);
+        assert_eq!(detect_format(&vhd).unwrap(), DiskFormat::Vhd);
+        assert_eq!(fs::metadata(&vhd).unwrap().len(), fs::metadata(&raw).unwrap().len() + 512);
+
+        let back = dir.path().join("back.diskimage");
+        let converted = convert(&vhd, &back, DiskFormat::SunPci);
+        let (cylinders, heads, sectors_per_track) = converted.geometry.unwrap();
+        assert_eq!((cylinders, heads, sectors_per_track), (header.cylinders, header.heads, header.sectors_per_track));
+        assert_eq!(fs::read(&back).unwrap(), fs::read(&original).unwrap());
+
+        // Never overwrites
+        assert!(convert_disk(&raw, &back, DiskFormat::SunPci, &ProgressReporter::new()).is_err());
+    }
+
+    #[test]
+    fn test_dynamic_vhd() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dynamic.vhd");
+        let (size, block_size) = (64 * 1024u64, 16 * 1024u64);
+
+        // Footer copy, dynamic header at 512, BAT at 1536, one block at 2048
+        // with only its second sector marked
+        let mut footer = vhd_footer(size, vhd_geometry(size / 512), &path);
+        footer[16..24].copy_from_slice(&512u64.to_be_bytes());
+        footer[60..64].copy_from_slice(&3u32.to_be_bytes());
+        footer[64..68].fill(0);
+        let checksum = vhd_checksum(&footer);
+        footer[64..68].copy_from_slice(&checksum.to_be_bytes());
+        let mut image = footer.to_vec();
+        let mut header = [0u8; 1024];
+        header[..8].copy_from_slice(VHD_SPARSE_COOKIE);
+        header[16..24].copy_from_slice(&1536u64.to_be_bytes());
+        header[28..32].copy_from_slice(&4u32.to_be_bytes());
+        header[32..36].copy_from_slice(&(block_size as u32).to_be_bytes());
+        image.extend_from_slice(&header);
+        for entry in [VHD_UNALLOCATED, 4, VHD_UNALLOCATED, VHD_UNALLOCATED] {
+            image.extend_from_slice(&entry.to_be_bytes());
+        }
+        image.resize(2048, 0);
+        let mut bitmap = [0u8; 512];
+        bitmap[0] = 0x40;
+        image.extend_from_slice(&bitmap);
+        image.extend_from_slice(&[0x11; 512]);
+        image.extend_from_slice(&[0x22; 512]);
+        image.resize(2048 + 512 + block_size as usize, 0);
+        image.extend_from_slice(&footer);
+        fs::write(&path, &image).unwrap();
+
+        let raw = dir.path().join("disk.img");
+        let converted = convert(&path, &raw, DiskFormat::Raw);
+        assert_eq!((converted.from, converted.sectors), (DiskFormat::Vhd, size / 512));
+        let data = fs::read(&raw).unwrap();
+        assert_eq!(data.len() as u64, size);
+        assert_eq!(&data[block_size as usize..block_size as usize + 512], &[0u8; 512]);
+        assert_eq!(&data[block_size as usize + 512..block_size as usize + 1024], &[0x22; 512]);
+        assert!(data[..block_size as usize].iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_partition_past_end() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("short.img");
+        let mut mbr = [0u8; 512];
+        mbr[510] = 0x55;
+        mbr[511] = 0xAA;
+        mbr[0x1BE + 4] = 0x06;
+        mbr[0x1BE + 8..0x1BE + 12].copy_from_slice(&63u32.to_le_bytes());
+        mbr[0x1BE + 12..0x1BE + 16].copy_from_slice(&10_000u32.to_le_bytes());
+        let mut image = mbr.to_vec();
+        image.resize(1024 * 1024, 0);
+        fs::write(&path, &image).unwrap();
+
+        let err = convert_disk(&path, &dir.path().join("out.diskimage"), DiskFormat::SunPci, &ProgressReporter::new())
+            .unwrap_err();
+        assert!(err.to_string().contains("past the end"));
+        assert!(!dir.path().join("out.diskimage").exists());
+    }
+}
@@ -0,0 +1,579 @@
+//! SunPCi hard disk and floppy images.
+//!
+//! A SunPCi hard disk image is a flat disk with an MBR whose otherwise
+//! unused bytes 12-25 carry a "SPCI" signature, the format revision and
+//! the CHS geometry. New images get one active FAT16 (or FAT12) partition
+//! covering the disk after the first track. They are sparse unless created
+//! with another `Allocation`, which is recorded in byte 26 so that later
+//! operations on the image keep to it.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use nix::errno::Errno;
+use nix::fcntl::{self, FallocateFlags};
+use nix::libc;
+
+use crate::diskspace;
+use crate::progress::ProgressReporter;
+
+/// SunPCi disk magic number: "SPCI" = 0x53504349
+pub const SUNPCI_MAGIC: u32 = 0x53504349;
+
+/// Sector size in bytes
+pub const SECTOR_SIZE: u32 = 512;
+
+/// Largest floppy image create_floppy_image accepts (2.88 MB, rounded up)
+pub const MAX_FLOPPY_BYTES: u64 = 3 * 1024 * 1024;
+
+/// Offset in the MBR of the image's allocation mode
+const ALLOCATION_OFFSET: usize = 26;
+
+/// Bytes of zeros written per step when zero-filling
+const FILL_CHUNK: usize = 1024 * 1024;
+
+/// How the host space of a disk image is claimed
+///
+/// Sparse images are smallest but fragment badly on some filesystems as
+/// the guest fills them; preallocated ones take their whole size up front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Allocation {
+    /// Only blocks that have been written take space
+    #[default]
+    Sparse,
+    /// The whole image is reserved with fallocate
+    Preallocate,
+    /// The whole image is written with zeros
+    ZeroFill,
+}
+
+impl Allocation {
+    /// Parse a mode name as accepted by the UI
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "sparse" => Some(Self::Sparse),
+            "preallocate" | "full" => Some(Self::Preallocate),
+            "zero-fill" | "zerofill" | "zero" => Some(Self::ZeroFill),
+            _ => None,
+        }
+    }
+
+    /// Name as accepted by `parse`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sparse => "sparse",
+            Self::Preallocate => "preallocate",
+            Self::ZeroFill => "zero-fill",
+        }
+    }
+
+    /// Whether holes are left for the host to fill in later
+    pub fn is_sparse(self) -> bool {
+        self == Self::Sparse
+    }
+
+    /// The mode recorded in an MBR; images from before it was recorded are sparse
+    pub fn from_header(mbr: &[u8]) -> Self {
+        match mbr[ALLOCATION_OFFSET] {
+            1 => Self::Preallocate,
+            2 => Self::ZeroFill,
+            _ => Self::Sparse,
+        }
+    }
+
+    /// Record the mode in an MBR
+    pub fn write_header(self, mbr: &mut [u8]) {
+        mbr[ALLOCATION_OFFSET] = match self {
+            Self::Sparse => 0,
+            Self::Preallocate => 1,
+            Self::ZeroFill => 2,
+        };
+    }
+}
+
+/// Claim the host space of `length` bytes at `offset` as `allocation` asks
+///
+/// Preallocation falls back to writing zeros on filesystems without
+/// fallocate. Zero-filling reports progress in bytes and can be cancelled.
+pub fn allocate(
+    file: &File,
+    offset: u64,
+    length: u64,
+    allocation: Allocation,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    if allocation == Allocation::Preallocate {
+        let flags = FallocateFlags::empty();
+        match fcntl::fallocate(file.as_raw_fd(), flags, offset as libc::off_t, length as libc::off_t) {
+            Ok(()) => return Ok(()),
+            Err(Errno::EOPNOTSUPP) => {}
+            Err(e) => return Err(io::Error::from(e).into()),
+        }
+    } else if allocation.is_sparse() {
+        return Ok(());
+    }
+
+    let zeros = vec![0u8; FILL_CHUNK];
+    let mut done = 0;
+    while done < length {
+        let n = (length - done).min(FILL_CHUNK as u64) as usize;
+        file.write_all_at(&zeros[..n], offset + done)?;
+        done += n as u64;
+        progress.set_progress(done, length);
+        progress.check_cancelled()?;
+    }
+    Ok(())
+}
+
+/// Calculate disk geometry for a given size
+/// Returns (cylinders, heads, sectors_per_track)
+pub fn calculate_geometry(size_mb: u32) -> (u16, u8, u8) {
+    let total_sectors = (size_mb as u64 * 1024 * 1024) / SECTOR_SIZE as u64;
+    
+    // Standard sectors per track
+    let sectors_per_track: u8 = 63;
+    
+    // Choose heads based on disk size to stay within 1024 cylinder limit
+    let heads: u8 = if size_mb <= 504 {
+        16
+    } else if size_mb <= 1008 {
+        32
+    } else if size_mb <= 2016 {
+        64
+    } else if size_mb <= 4032 {
+        128
+    } else {
+        255
+    };
+    
+    let cylinders = (total_sectors / (heads as u64 * sectors_per_track as u64)) as u16;
+    let cylinders = cylinders.min(1024); // CHS limit
+    
+    (cylinders, heads, sectors_per_track)
+}
+
+/// Sectors per cluster used for a new image's FAT16 partition
+fn sectors_per_cluster(size_mb: u32) -> u8 {
+    if size_mb > 256 { 8 } else { 4 }
+}
+
+/// Size of one FAT (estimate: two bytes per cluster, rounded up)
+fn sectors_per_fat(partition_sectors: u32, sectors_per_cluster: u8) -> u16 {
+    ((partition_sectors / sectors_per_cluster as u32) * 2 / 512 + 1) as u16
+}
+
+/// Bytes create_disk_image actually allocates in a sparse image
+pub fn disk_image_allocated_bytes(size_mb: u32, total_sectors: u64, sectors_per_track: u8) -> u64 {
+    let partition_sectors = total_sectors as u32 - sectors_per_track as u32;
+    let fat = sectors_per_fat(partition_sectors, sectors_per_cluster(size_mb)) as u64;
+    // MBR, boot sector, two FATs, 32-sector root directory, plus a block
+    // each for the sector straddling the MBR gap and the final byte
+    (2 + 2 * fat + 32) * SECTOR_SIZE as u64 + 2 * 4096
+}
+
+/// Number of progress steps reported by create_disk_image
+const CREATE_DISK_STEPS: u64 = 6;
+
+/// Create a SunPCi-compatible disk image
+///
+/// Reports progress and checks for cancellation between steps; a partially
+/// written image is removed if creation fails or is cancelled.
+pub fn create_disk_image(
+    path: &Path,
+    size_mb: u32,
+    revision: u8,
+    allocation: Allocation,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    // Create parent directories if needed
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    
+    let (cylinders, heads, sectors_per_track) = calculate_geometry(size_mb);
+    let total_sectors = cylinders as u64 * heads as u64 * sectors_per_track as u64;
+    let total_bytes = total_sectors * SECTOR_SIZE as u64;
+    
+    // A sparse image only has the MBR, boot sector, FATs and root
+    // directory allocated up front
+    let required = diskspace::space_required(
+        total_bytes,
+        disk_image_allocated_bytes(size_mb, total_sectors, sectors_per_track),
+        allocation.is_sparse(),
+    );
+    diskspace::check_free_space(path, required)?;
+
+    progress.check_cancelled()?;
+    let mut file = File::create(path)?;
+
+    let result = write_disk_image(
+        &mut file,
+        size_mb,
+        revision,
+        allocation,
+        (cylinders, heads, sectors_per_track),
+        progress,
+    );
+    if let Err(e) = result {
+        drop(file);
+        let _ = std::fs::remove_file(path);
+        return Err(diskspace::map_disk_full(e, path, required));
+    }
+    Ok(())
+}
+
+/// Write the MBR, FAT structures and full length of a new disk image
+fn write_disk_image(
+    file: &mut File,
+    size_mb: u32,
+    revision: u8,
+    allocation: Allocation,
+    (cylinders, heads, sectors_per_track): (u16, u8, u8),
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    let total_sectors = cylinders as u64 * heads as u64 * sectors_per_track as u64;
+    let total_bytes = total_sectors * SECTOR_SIZE as u64;
+
+    // A preallocated image is claimed in full before anything is written
+    if !allocation.is_sparse() {
+        progress.set_step(match allocation {
+            Allocation::ZeroFill => "Zero-filling image",
+            _ => "Allocating image",
+        });
+        allocate(file, 0, total_bytes, allocation, progress)?;
+    }
+
+    progress.set_progress(0, CREATE_DISK_STEPS);
+    progress.set_step("Writing partition table");
+
+    // Create the MBR (sector 0)
+    let mut mbr = [0u8; 512];
+    
+    // Add SunPCi magic at offset 12
+    mbr[12..16].copy_from_slice(&SUNPCI_MAGIC.to_le_bytes());
+    
+    // Add revision info at offset 16
+    mbr[16] = revision;  // Major version
+    mbr[17] = 0;         // Minor version
+    
+    // Store geometry in header (offsets 18-23)
+    mbr[18..20].copy_from_slice(&cylinders.to_le_bytes());
+    mbr[20] = heads;
+    mbr[21] = sectors_per_track;
+    mbr[22..26].copy_from_slice(&(total_sectors as u32).to_le_bytes());
+    allocation.write_header(&mut mbr);
+    
+    // Create partition table entry at offset 0x1BE (446)
+    // Partition 1: Primary, active, FAT16
+    let partition_start: u32 = sectors_per_track as u32;  // Start after first track
+    let partition_sectors: u32 = total_sectors as u32 - partition_start;
+    
+    // Partition entry 1
+    let part_entry = &mut mbr[0x1BE..0x1CE];
+    part_entry[0] = 0x80;  // Active/bootable
+    
+    // CHS start (head 0, sector 1, cylinder 0) - after MBR
+    part_entry[1] = 1;     // Start head
+    part_entry[2] = 1;     // Start sector (bits 0-5) | cylinder high (bits 6-7)
+    part_entry[3] = 0;     // Start cylinder low
+    
+    // Partition type: FAT16 for larger disks, FAT12 for small
+    part_entry[4] = if size_mb > 32 { 0x06 } else { 0x01 };  // 0x06 = FAT16, 0x01 = FAT12
+    
+    // CHS end
+    let end_cyl = (cylinders - 1).min(1023);
+    let end_head = heads - 1;
+    let end_sector = sectors_per_track;
+    part_entry[5] = end_head;
+    part_entry[6] = (end_sector & 0x3F) | (((end_cyl >> 8) & 0x03) << 6) as u8;
+    part_entry[7] = (end_cyl & 0xFF) as u8;
+    
+    // LBA start and size
+    part_entry[8..12].copy_from_slice(&partition_start.to_le_bytes());
+    part_entry[12..16].copy_from_slice(&partition_sectors.to_le_bytes());
+    
+    // MBR signature
+    mbr[510] = 0x55;
+    mbr[511] = 0xAA;
+    
+    // Write MBR
+    file.write_all(&mbr)?;
+    progress.set_progress(1, CREATE_DISK_STEPS);
+    progress.set_step("Writing boot sector");
+    progress.check_cancelled()?;
+    
+    // Write FAT boot sector at partition start
+    let mut boot_sector = [0u8; 512];
+    
+    // Jump instruction
+    boot_sector[0] = 0xEB;
+    boot_sector[1] = 0x3C;
+    boot_sector[2] = 0x90;
+    
+    // OEM name
+    boot_sector[3..11].copy_from_slice(b"SUNPCI  ");
+    
+    // BIOS Parameter Block (BPB)
+    boot_sector[11..13].copy_from_slice(&512u16.to_le_bytes());  // Bytes per sector
+    boot_sector[13] = sectors_per_cluster(size_mb);              // Sectors per cluster
+    boot_sector[14..16].copy_from_slice(&1u16.to_le_bytes());    // Reserved sectors
+    boot_sector[16] = 2;                                          // Number of FATs
+    boot_sector[17..19].copy_from_slice(&512u16.to_le_bytes());  // Root entries
+    
+    // Total sectors (16-bit if <= 65535, else in 32-bit field)
+    if partition_sectors <= 65535 {
+        boot_sector[19..21].copy_from_slice(&(partition_sectors as u16).to_le_bytes());
+    } else {
+        boot_sector[19..21].copy_from_slice(&0u16.to_le_bytes());
+        boot_sector[32..36].copy_from_slice(&partition_sectors.to_le_bytes());
+    }
+    
+    boot_sector[21] = 0xF8;  // Media descriptor (fixed disk)
+    
+    // Sectors per FAT (estimate)
+    let sectors_per_fat = sectors_per_fat(partition_sectors, boot_sector[13]);
+    boot_sector[22..24].copy_from_slice(&sectors_per_fat.to_le_bytes());
+    
+    boot_sector[24..26].copy_from_slice(&(sectors_per_track as u16).to_le_bytes());
+    boot_sector[26..28].copy_from_slice(&(heads as u16).to_le_bytes());
+    boot_sector[28..32].copy_from_slice(&partition_start.to_le_bytes());  // Hidden sectors
+    
+    // Extended boot record
+    boot_sector[36] = 0x80;  // Drive number
+    boot_sector[38] = 0x29;  // Extended boot signature
+    boot_sector[39..43].copy_from_slice(&0x12345678u32.to_le_bytes());  // Volume serial
+    boot_sector[43..54].copy_from_slice(b"NO NAME    ");  // Volume label
+    boot_sector[54..62].copy_from_slice(b"FAT16   ");     // FS type
+    
+    // Boot signature
+    boot_sector[510] = 0x55;
+    boot_sector[511] = 0xAA;
+    
+    // Seek to partition start and write boot sector
+    file.seek(SeekFrom::Start(partition_start as u64 * SECTOR_SIZE as u64))?;
+    file.write_all(&boot_sector)?;
+    progress.set_progress(2, CREATE_DISK_STEPS);
+    progress.set_step("Writing FAT");
+    progress.check_cancelled()?;
+    
+    // Initialize first FAT
+    let mut fat = vec![0u8; sectors_per_fat as usize * SECTOR_SIZE as usize];
+    fat[0] = 0xF8;  // Media descriptor
+    fat[1] = 0xFF;
+    fat[2] = 0xFF;
+    fat[3] = 0xFF;
+    
+    // Write FAT1
+    file.write_all(&fat)?;
+    progress.set_progress(3, CREATE_DISK_STEPS);
+    progress.set_step("Writing FAT copy");
+    progress.check_cancelled()?;
+    
+    // Write FAT2
+    file.write_all(&fat)?;
+    progress.set_progress(4, CREATE_DISK_STEPS);
+    progress.set_step("Writing root directory");
+    progress.check_cancelled()?;
+    
+    // Write empty root directory (512 entries * 32 bytes = 16384 bytes = 32 sectors)
+    let root_dir = vec![0u8; 512 * 32];
+    file.write_all(&root_dir)?;
+    progress.set_progress(5, CREATE_DISK_STEPS);
+    progress.set_step("Allocating image");
+    progress.check_cancelled()?;
+    
+    // Extend file to full size
+    if allocation.is_sparse() {
+        file.seek(SeekFrom::Start(total_bytes - 1))?;
+        file.write_all(&[0])?;
+    }
+    file.sync_all()?;
+    progress.set_progress(CREATE_DISK_STEPS, CREATE_DISK_STEPS);
+    
+    Ok(())
+}
+
+/// Disk information parsed from header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskInfo {
+    /// Whether this appears to be a SunPCi disk image
+    pub is_sunpci: bool,
+    /// Size in megabytes
+    pub size_mb: u32,
+    /// SunPCi format revision
+    pub revision: u8,
+    /// CHS cylinders
+    pub cylinders: u16,
+    /// CHS heads
+    pub heads: u8,
+    /// CHS sectors per track
+    pub sectors_per_track: u8,
+    /// Total sectors
+    pub total_sectors: u64,
+    /// Whether partition is bootable
+    pub bootable: bool,
+    /// Partition type description
+    pub partition_type: String,
+    /// How the image's host space is claimed
+    pub allocation: Allocation,
+}
+
+/// Read and parse a disk image header
+pub fn read_disk_header(path: &Path) -> io::Result<DiskInfo> {
+    let mut file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+    
+    // Read MBR (first 512 bytes)
+    let mut mbr = [0u8; 512];
+    file.read_exact(&mut mbr)?;
+    
+    // Check for MBR signature
+    if mbr[510] != 0x55 || mbr[511] != 0xAA {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid MBR signature"
+        ));
+    }
+    
+    // Check for SunPCi magic at offset 12
+    let magic = u32::from_le_bytes([mbr[12], mbr[13], mbr[14], mbr[15]]);
+    let is_sunpci = magic == SUNPCI_MAGIC;
+    
+    // Read SunPCi-specific fields if present
+    let (revision, cylinders, heads, sectors_per_track, stored_sectors) = if is_sunpci {
+        let rev = mbr[16];
+        let cyls = u16::from_le_bytes([mbr[18], mbr[19]]);
+        let heads = mbr[20];
+        let spt = mbr[21];
+        let sectors = u32::from_le_bytes([mbr[22], mbr[23], mbr[24], mbr[25]]);
+        (rev, cyls, heads, spt, sectors as u64)
+    } else {
+        // Calculate geometry from file size
+        let size_mb = (file_size / (1024 * 1024)) as u32;
+        let (cyls, heads, spt) = calculate_geometry(size_mb);
+        let sectors = file_size / SECTOR_SIZE as u64;
+        (0, cyls, heads, spt, sectors)
+    };
+    
+    // Parse partition table entry 1 (offset 0x1BE)
+    let part_entry = &mbr[0x1BE..0x1CE];
+    let bootable = part_entry[0] == 0x80;
+    let partition_type_byte = part_entry[4];
+    
+    let partition_type = match partition_type_byte {
+        0x00 => "Empty",
+        0x01 => "FAT12",
+        0x04 => "FAT16 (<32MB)",
+        0x05 => "Extended",
+        0x06 => "FAT16",
+        0x07 => "NTFS/HPFS",
+        0x0B => "FAT32",
+        0x0C => "FAT32 (LBA)",
+        0x0E => "FAT16 (LBA)",
+        0x0F => "Extended (LBA)",
+        0x82 => "Linux Swap",
+        0x83 => "Linux",
+        _ => "Unknown",
+    }.to_string();
+    
+    let size_mb = (file_size / (1024 * 1024)) as u32;
+    let total_sectors = if stored_sectors > 0 { stored_sectors } else { file_size / SECTOR_SIZE as u64 };
+    
+    Ok(DiskInfo {
+        is_sunpci,
+        size_mb,
+        revision,
+        cylinders,
+        heads,
+        sectors_per_track,
+        total_sectors,
+        bootable,
+        partition_type,
+        allocation: if is_sunpci { Allocation::from_header(&mbr) } else { Allocation::Sparse },
+    })
+}
+
+/// Create a blank floppy image of `size_bytes`
+///
+/// The image is sparse and unformatted; the guest formats it. An existing
+/// file is never overwritten.
+pub fn create_floppy_image(path: &Path, size_bytes: u64) -> io::Result<()> {
+    if size_bytes == 0 || size_bytes > MAX_FLOPPY_BYTES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid floppy size: {} bytes", size_bytes),
+        ));
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let file = File::options().write(true).create_new(true).open(path)?;
+    if let Err(e) = file.set_len(size_bytes) {
+        drop(file);
+        let _ = std::fs::remove_file(path);
+        return Err(e);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_read_disk_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disks/c.diskimage");
+        create_disk_image(&path, 64, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+
+        let info = read_disk_header(&path).unwrap();
+        let (cylinders, heads, sectors_per_track) = calculate_geometry(64);
+        assert!(info.is_sunpci);
+        assert_eq!(info.revision, 2);
+        assert_eq!((info.cylinders, info.heads, info.sectors_per_track), (cylinders, heads, sectors_per_track));
+        assert_eq!(info.total_sectors, cylinders as u64 * heads as u64 * sectors_per_track as u64);
+        assert!(info.bootable);
+        assert_eq!(info.partition_type, "FAT16");
+        assert_eq!(info.allocation, Allocation::Sparse);
+
+        // A cancelled creation leaves nothing behind
+        let progress = ProgressReporter::new();
+        progress.cancel();
+        let cancelled = dir.path().join("d.diskimage");
+        assert!(create_disk_image(&cancelled, 64, 2, Allocation::Sparse, &progress).is_err());
+        assert!(!cancelled.exists());
+    }
+
+    #[test]
+    fn test_create_allocated_disk_image() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        for allocation in [Allocation::Preallocate, Allocation::ZeroFill] {
+            let path = dir.path().join(format!("{}.diskimage", allocation.name()));
+            create_disk_image(&path, 16, 2, allocation, &ProgressReporter::new()).unwrap();
+
+            let metadata = std::fs::metadata(&path).unwrap();
+            assert!(metadata.blocks() * 512 >= metadata.len());
+            assert_eq!(read_disk_header(&path).unwrap().allocation, allocation);
+        }
+        assert_eq!(Allocation::parse("Zero-Fill"), Some(Allocation::ZeroFill));
+        assert_eq!(Allocation::parse("thin"), None);
+    }
+
+    #[test]
+    fn test_create_floppy_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.img");
+        create_floppy_image(&path, 1_474_560).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 1_474_560);
+
+        // Never overwritten
+        assert_eq!(create_floppy_image(&path, 737_280).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+        assert!(create_floppy_image(&dir.path().join("b.img"), MAX_FLOPPY_BYTES + 1).is_err());
+    }
+}
@@ -0,0 +1,364 @@
+//! Copying, moving and renaming disk images without filling in their holes.
+//!
+//! New images are sparse (see `disk_image`), so an 8 GB disk may hold only
+//! a few megabytes. `copy_image` keeps it that way: it first asks the
+//! filesystem for a reflink (`FICLONE`), which shares the blocks and takes
+//! no time; failing that it copies only the data extents found with
+//! `SEEK_DATA`/`SEEK_HOLE`, in-kernel with `copy_file_range` where it
+//! can, and leaves the holes as holes. Copies report progress in bytes of
+//! the source and can be cancelled; an unfinished copy is removed.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, bail};
+use nix::errno::Errno;
+use nix::fcntl;
+use nix::libc;
+use nix::unistd::{self, Whence};
+
+use crate::config::AppConfig;
+use crate::diskspace;
+use crate::progress::ProgressReporter;
+
+/// Bytes copied per step, between progress updates and cancellation checks
+const CHUNK: usize = 8 * 1024 * 1024;
+
+/// How an image was copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// The copy shares the source's blocks
+    Reflink,
+    /// Data extents copied in the kernel, holes kept
+    CopyRange,
+    /// Data read and written, holes and zero blocks kept
+    ReadWrite,
+}
+
+/// Default location of disk templates
+pub fn templates_dir() -> PathBuf {
+    AppConfig::data_dir().join("templates")
+}
+
+/// Copy the image at `src` to `dst`, which must not exist
+pub fn copy_image(src: &Path, dst: &Path, progress: &ProgressReporter) -> anyhow::Result<CopyMethod> {
+    let source = File::open(src).with_context(|| format!("failed to open {}", src.display()))?;
+    let metadata = source.metadata()?;
+    if !metadata.is_file() {
+        bail!("{} is not a disk image file", src.display());
+    }
+    let length = metadata.len();
+    progress.set_progress(0, length);
+    progress.check_cancelled()?;
+
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut target = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(metadata.mode() & 0o777)
+        .open(dst)
+        .with_context(|| format!("failed to create {}", dst.display()))?;
+
+    let result = copy_contents(&source, &mut target, dst, length, metadata.blocks() * 512, progress);
+    if result.is_err() {
+        drop(target);
+        let _ = fs::remove_file(dst);
+    }
+    result
+}
+
+/// Copy between the open files, by reflink if possible
+fn copy_contents(
+    source: &File,
+    target: &mut File,
+    dst: &Path,
+    length: u64,
+    allocated: u64,
+    progress: &ProgressReporter,
+) -> anyhow::Result<CopyMethod> {
+    // Filesystems without reflinks (ext4, tmpfs) refuse this
+    if unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) } == 0 {
+        progress.set_current(length);
+        return Ok(CopyMethod::Reflink);
+    }
+
+    let required = diskspace::space_required(length, allocated, true);
+    diskspace::check_free_space(dst, required)?;
+
+    target.set_len(length)?;
+    let method = copy_extents(source, target, length, progress)
+        .map_err(|e| diskspace::map_disk_full(e, dst, required))?;
+    target.sync_all()?;
+    progress.set_current(length);
+    Ok(method)
+}
+
+/// Copy the data extents of `source` into the already sized `target`
+fn copy_extents(
+    source: &File,
+    target: &mut File,
+    length: u64,
+    progress: &ProgressReporter,
+) -> anyhow::Result<CopyMethod> {
+    let mut method = CopyMethod::CopyRange;
+    let mut offset = 0;
+    while offset < length {
+        progress.check_cancelled()?;
+        let Some((start, end)) = next_extent(source, offset, length)? else {
+            break;
+        };
+
+        let mut position = start;
+        while position < end {
+            progress.check_cancelled()?;
+            let len = (end - position).min(CHUNK as u64) as usize;
+            let copied = match method {
+                CopyMethod::CopyRange => match copy_range(source, target, position, len) {
+                    Ok(copied) => copied,
+                    // Not between these filesystems, or not on this kernel
+                    Err(Errno::EXDEV | Errno::ENOSYS | Errno::EINVAL | Errno::EOPNOTSUPP) => {
+                        method = CopyMethod::ReadWrite;
+                        continue;
+                    }
+                    Err(e) => return Err(io::Error::from(e).into()),
+                },
+                _ => copy_chunk(source, target, position, len)?,
+            };
+            if copied == 0 {
+                bail!("source image ended early at byte {}", position);
+            }
+            position += copied as u64;
+            progress.set_current(position);
+        }
+        offset = end;
+    }
+    Ok(method)
+}
+
+/// The next data extent at or after `offset`, or None if only a hole is left
+///
+/// Without SEEK_DATA support the rest of the file counts as data.
+fn next_extent(file: &File, offset: u64, length: u64) -> io::Result<Option<(u64, u64)>> {
+    let fd = file.as_raw_fd();
+    let start = match unistd::lseek(fd, offset as libc::off_t, Whence::SeekData) {
+        Ok(start) => start as u64,
+        Err(Errno::ENXIO) => return Ok(None),
+        Err(Errno::EINVAL) => return Ok(Some((offset, length))),
+        Err(e) => return Err(e.into()),
+    };
+    let end = match unistd::lseek(fd, start as libc::off_t, Whence::SeekHole) {
+        Ok(end) => end as u64,
+        Err(_) => length,
+    };
+    Ok((start < length).then_some((start, end.min(length))))
+}
+
+/// Copy up to `len` bytes at `position` in the kernel
+fn copy_range(source: &File, target: &File, position: u64, len: usize) -> Result<usize, Errno> {
+    let mut off_in = position as i64;
+    let mut off_out = position as i64;
+    fcntl::copy_file_range(source, Some(&mut off_in), target, Some(&mut off_out), len)
+}
+
+/// Copy up to `len` bytes at `position` through a buffer, leaving blocks
+/// of zeros unwritten
+fn copy_chunk(mut source: &File, target: &mut File, position: u64, len: usize) -> io::Result<usize> {
+    let mut buffer = vec![0u8; len];
+    source.seek(SeekFrom::Start(position))?;
+    let read = source.read(&mut buffer)?;
+
+    for (i, block) in buffer[..read].chunks(4096).enumerate() {
+        if block.iter().any(|&b| b != 0) {
+            target.seek(SeekFrom::Start(position + (i * 4096) as u64))?;
+            target.write_all(block)?;
+        }
+    }
+    Ok(read)
+}
+
+/// Move the image at `src` to `dst`, which must not exist
+///
+/// A rename when both are on one filesystem, otherwise a copy followed by
+/// removing the source.
+pub fn move_image(src: &Path, dst: &Path, progress: &ProgressReporter) -> anyhow::Result<()> {
+    if dst.exists() {
+        bail!("{} already exists", dst.display());
+    }
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::rename(src, dst) {
+        Ok(()) => {
+            let length = fs::metadata(dst).map(|m| m.len()).unwrap_or(0);
+            progress.set_progress(length, length);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_image(src, dst, progress)?;
+            fs::remove_file(src).with_context(|| format!("copied, but failed to remove {}", src.display()))
+        }
+        Err(e) => Err(e).with_context(|| format!("failed to move {}", src.display())),
+    }
+}
+
+/// Rename the image at `path` within its directory; returns the new path
+pub fn rename_image(path: &Path, new_name: &str) -> anyhow::Result<PathBuf> {
+    let new_name = new_name.trim();
+    if new_name.is_empty() || new_name.contains('/') || new_name == "." || new_name == ".." {
+        bail!("'{}' is not a valid file name", new_name);
+    }
+    let renamed = path.with_file_name(new_name);
+    if renamed.exists() {
+        bail!("{} already exists", renamed.display());
+    }
+    fs::rename(path, &renamed).with_context(|| format!("failed to rename {}", path.display()))?;
+    Ok(renamed)
+}
+
+/// Copy the image at `src` into `dir` as template `name`, read-only
+///
+/// The template keeps the source's extension; returns its path.
+pub fn duplicate_as_template(
+    src: &Path,
+    dir: &Path,
+    name: &str,
+    progress: &ProgressReporter,
+) -> anyhow::Result<PathBuf> {
+    let name = name.trim();
+    if name.is_empty() || name.contains('/') {
+        bail!("'{}' is not a valid template name", name);
+    }
+    // Appended rather than set, as names like "DOS 6.22" have a dot
+    let template = match src.extension() {
+        Some(extension) => dir.join(format!("{}.{}", name, extension.to_string_lossy())),
+        None => dir.join(name),
+    };
+
+    copy_image(src, &template, progress)?;
+    let mut permissions = fs::metadata(&template)?.permissions();
+    permissions.set_mode(permissions.mode() & 0o444);
+    fs::set_permissions(&template, permissions)?;
+    Ok(template)
+}
+
+/// Make the image at `src` a template: move it into `dir`, read-only
+///
+/// Profiles using a template get a copy-on-write child of it (see
+/// `overlay`); returns the template's path.
+pub fn mark_template(src: &Path, dir: &Path, progress: &ProgressReporter) -> anyhow::Result<PathBuf> {
+    let name = src.file_name().with_context(|| format!("{} is not a file", src.display()))?;
+    let template = dir.join(name);
+    move_image(src, &template, progress)?;
+    let mut permissions = fs::metadata(&template)?.permissions();
+    permissions.set_mode(permissions.mode() & 0o444);
+    fs::set_permissions(&template, permissions)?;
+    Ok(template)
+}
+
+/// Images in the templates directory `dir`, by name
+pub fn list_templates(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut templates: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    templates.sort();
+    templates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 64 MB file with data only at its start and end
+    fn sparse_file(path: &Path) {
+        let mut file = File::create(path).unwrap();
+        file.write_all(&[0x55; 4096]).unwrap();
+        file.seek(SeekFrom::Start(64 * 1024 * 1024 - 4096)).unwrap();
+        file.write_all(&[0xAA; 4096]).unwrap();
+    }
+
+    #[test]
+    fn test_copy_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("disk.img");
+        let dst = dir.path().join("copy.img");
+        sparse_file(&src);
+
+        let progress = ProgressReporter::new();
+        copy_image(&src, &dst, &progress).unwrap();
+        assert_eq!(fs::read(&src).unwrap(), fs::read(&dst).unwrap());
+        assert_eq!(progress.snapshot().percent(), 100);
+
+        // Holes stay holes (unless the filesystem can't have any)
+        let src_blocks = fs::metadata(&src).unwrap().blocks();
+        assert!(fs::metadata(&dst).unwrap().blocks() <= src_blocks.max(8) * 2);
+
+        // Never over an existing file
+        assert!(copy_image(&src, &dst, &progress).is_err());
+        assert!(dst.exists());
+    }
+
+    #[test]
+    fn test_copy_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("disk.img");
+        let dst = dir.path().join("copy.img");
+        sparse_file(&src);
+
+        let progress = ProgressReporter::new();
+        progress.cancel();
+        assert!(copy_image(&src, &dst, &progress).is_err());
+        assert!(!dst.exists());
+    }
+
+    #[test]
+    fn test_move_and_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("disk.img");
+        sparse_file(&src);
+
+        let moved = dir.path().join("sub").join("moved.img");
+        move_image(&src, &moved, &ProgressReporter::new()).unwrap();
+        assert!(!src.exists() && moved.exists());
+
+        let renamed = rename_image(&moved, "renamed.img").unwrap();
+        assert_eq!(renamed, dir.path().join("sub").join("renamed.img"));
+        assert!(rename_image(&renamed, "../escape.img").is_err());
+        assert!(rename_image(&renamed, "").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_as_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("disk.img");
+        sparse_file(&src);
+
+        let templates = dir.path().join("templates");
+        let template = duplicate_as_template(&src, &templates, "DOS 6.22", &ProgressReporter::new()).unwrap();
+        assert_eq!(template, templates.join("DOS 6.22.img"));
+        assert!(fs::metadata(&template).unwrap().permissions().readonly());
+    }
+
+    #[test]
+    fn test_mark_template() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("win98.img");
+        sparse_file(&src);
+
+        let templates = dir.path().join("templates");
+        let template = mark_template(&src, &templates, &ProgressReporter::new()).unwrap();
+        assert!(!src.exists());
+        assert!(fs::metadata(&template).unwrap().permissions().readonly());
+        assert_eq!(list_templates(&templates), vec![template]);
+        assert!(list_templates(&dir.path().join("missing")).is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+//! Growing SunPCi disk images.
+//!
+//! `resize_disk_image` makes an image bigger so a full C: does not have to
+//! be recreated. The file is extended, sparsely unless the image records
+//! another `Allocation`, the geometry in the
+//! SunPCi header is recalculated for the new size as `create_disk_image`
+//! would, and the partition table is rewritten for it. The new space is
+//! left unpartitioned unless the filesystem is grown too, in which case the
+//! first partition is extended to the end of the disk and its FAT12/FAT16
+//! volume takes the new space: the FATs are enlarged where the new clusters
+//! need it, which moves the root directory and data area up by the sectors
+//! added. A volume keeps its FAT type and cluster size, so it stops growing
+//! at the most clusters its type allows.
+//!
+//! Images only grow. Data is moved in place, so the image should not be in
+//! use, and an interrupted resize can leave it damaged; it can be
+//! cancelled until the first write.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use anyhow::{Context, bail};
+
+use crate::disk_image::{self, Allocation, SECTOR_SIZE, SUNPCI_MAGIC};
+use crate::diskspace;
+use crate::fat::{self, Bpb, FatType};
+use crate::progress::ProgressReporter;
+
+/// Bytes moved per step, between progress updates
+const CHUNK: u64 = 4 * 1024 * 1024;
+
+/// What a resize did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resized {
+    /// Sectors of the disk before
+    pub old_sectors: u64,
+    /// Sectors of the disk after
+    pub new_sectors: u64,
+    /// Data clusters of the volume before and after, if it was grown
+    pub clusters: Option<(u32, u32)>,
+}
+
+/// The layout of a grown FAT volume
+struct VolumePlan {
+    old: Bpb,
+    new: Bpb,
+    /// Sector of the disk the volume starts at
+    start: u64,
+    /// Sectors from the old root directory on that hold data
+    used_sectors: u64,
+    /// Length of the image before it was extended
+    old_end: u64,
+}
+
+impl VolumePlan {
+    /// Sectors the root directory and data area move up by
+    fn shift(&self) -> u64 {
+        (self.new.root_dir_start() - self.old.root_dir_start()) as u64
+    }
+}
+
+/// Grow the image at `path` to `new_size_mb`, and its first FAT volume with
+/// it if `grow_filesystem` is set
+pub fn resize_disk_image(
+    path: &Path,
+    new_size_mb: u32,
+    grow_filesystem: bool,
+    progress: &ProgressReporter,
+) -> anyhow::Result<Resized> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut mbr = [0u8; 512];
+    file.read_exact(&mut mbr)?;
+    if mbr[510] != 0x55 || mbr[511] != 0xAA || read_u32(&mbr, 12) != SUNPCI_MAGIC {
+        bail!("{} is not a SunPCi disk image", path.display());
+    }
+    let allocation = Allocation::from_header(&mbr);
+    let old_sectors = match read_u32(&mbr, 22) {
+        0 => file.metadata()?.len() / SECTOR_SIZE as u64,
+        n => n as u64,
+    };
+
+    let (cylinders, heads, sectors_per_track) = disk_image::calculate_geometry(new_size_mb);
+    let new_sectors = cylinders as u64 * heads as u64 * sectors_per_track as u64;
+    if new_sectors <= old_sectors {
+        bail!(
+            "a {} MB disk is not larger than the current {} MB; images can only grow",
+            new_size_mb,
+            old_sectors * SECTOR_SIZE as u64 / (1024 * 1024)
+        );
+    }
+
+    let entry = &mbr[0x1BE..0x1CE];
+    let partition_start = read_u32(entry, 8) as u64;
+    let partition_sectors = read_u32(entry, 12) as u64;
+    let has_partition = entry[4] != 0 && partition_sectors > 0;
+    let boot = if has_partition {
+        read_sector(&mut file, partition_start)?
+    } else {
+        [0u8; 512]
+    };
+    let bpb = Bpb::parse(&boot);
+
+    let plan = if grow_filesystem {
+        if !has_partition {
+            bail!("{} has no partition to grow", path.display());
+        }
+        let Some(old) = bpb.filter(|b| b.fat_type().is_some()) else {
+            bail!("the partition on {} is not FAT12 or FAT16; only the disk can be grown", path.display());
+        };
+        let mut plan = plan_volume(old, partition_start, new_sectors - partition_start, (heads, sectors_per_track));
+        plan.used_sectors = used_sectors(&mut file, &plan)?;
+        plan.old_end = file.metadata()?.len();
+        Some(plan)
+    } else {
+        None
+    };
+
+    // Moving data fills in the holes it lands on; a preallocated image
+    // claims all the new space
+    let moved = plan.as_ref().map_or(0, |p| if p.shift() > 0 { p.used_sectors } else { 0 });
+    let old_len = file.metadata()?.len();
+    let new_len = new_sectors * SECTOR_SIZE as u64;
+    let added = if allocation.is_sparse() { 0 } else { new_len.saturating_sub(old_len) };
+    let required = moved * SECTOR_SIZE as u64 + added;
+    diskspace::check_free_space(path, required)?;
+    progress.check_cancelled()?;
+
+    let result = (|| -> anyhow::Result<Resized> {
+        progress.set_step("Extending image");
+        file.set_len(new_len)?;
+        if new_len > old_len
+            && let Err(e) = disk_image::allocate(&file, old_len, new_len - old_len, allocation, progress)
+        {
+            // Nothing refers to the new space yet
+            let _ = file.set_len(old_len);
+            return Err(e);
+        }
+
+        let mut clusters = None;
+        if let Some(ref plan) = plan {
+            grow_volume(&mut file, plan, progress)?;
+            clusters = Some((plan.old.cluster_count(), plan.new.cluster_count()));
+        } else if let Some(mut bpb) = bpb {
+            // The boot code reads through the BIOS with the BPB's geometry
+            bpb.heads = heads as u16;
+            bpb.sectors_per_track = sectors_per_track as u16;
+            let mut boot = boot;
+            bpb.write(&mut boot);
+            write_sector(&mut file, partition_start, &boot)?;
+        }
+
+        progress.set_step("Writing partition table");
+        mbr[18..20].copy_from_slice(&cylinders.to_le_bytes());
+        mbr[20] = heads;
+        mbr[21] = sectors_per_track;
+        mbr[22..26].copy_from_slice(&(new_sectors as u32).to_le_bytes());
+        if has_partition {
+            let length = match plan {
+                Some(ref plan) => new_sectors - plan.start,
+                None => partition_sectors,
+            };
+            let entry = &mut mbr[0x1BE..0x1CE];
+            entry[1..4].copy_from_slice(&chs(partition_start, heads, sectors_per_track));
+            entry[5..8].copy_from_slice(&chs(partition_start + length - 1, heads, sectors_per_track));
+            entry[12..16].copy_from_slice(&(length as u32).to_le_bytes());
+            if let Some(ref plan) = plan {
+                entry[4] = match plan.new.fat_type() {
+                    Some(FatType::Fat12) => 0x01,
+                    _ if length <= 0xFFFF => 0x04,
+                    _ => 0x06,
+                };
+            }
+        }
+        write_sector(&mut file, 0, &mbr)?;
+        file.sync_all()?;
+
+        Ok(Resized {
+            old_sectors,
+            new_sectors,
+            clusters,
+        })
+    })();
+    result.map_err(|e| diskspace::map_disk_full(e, path, required))
+}
+
+/// Lay out `old` again over `sectors` sectors with the new geometry
+fn plan_volume(old: Bpb, start: u64, sectors: u64, (heads, sectors_per_track): (u8, u8)) -> VolumePlan {
+    let fat_type = old.fat_type().unwrap_or(FatType::Fat16);
+    let mut new = old;
+    new.heads = heads as u16;
+    new.sectors_per_track = sectors_per_track as u16;
+    new.total_sectors = sectors.min(u32::MAX as u64) as u32;
+
+    // A bigger FAT leaves fewer sectors for clusters, so settle on a size
+    // that covers the clusters that are left
+    loop {
+        let clusters = new.cluster_count().min(fat_type.max_clusters());
+        let needed = fat_type.fat_bytes(clusters).div_ceil(SECTOR_SIZE as u64) as u16;
+        if needed <= new.sectors_per_fat {
+            break;
+        }
+        new.sectors_per_fat = needed;
+    }
+    // The volume ends where its type runs out of clusters
+    let clusters = new.cluster_count().min(fat_type.max_clusters());
+    new.total_sectors = new.data_start() + clusters * new.sectors_per_cluster as u32;
+
+    VolumePlan {
+        old,
+        new,
+        start,
+        used_sectors: 0,
+        old_end: 0,
+    }
+}
+
+/// Sectors from the old root directory to the end of the last used cluster
+fn used_sectors(file: &mut File, plan: &VolumePlan) -> anyhow::Result<u64> {
+    let old = &plan.old;
+    let fat_type = old.fat_type().unwrap_or(FatType::Fat16);
+    let fat = read_sectors(file, plan.start + old.fat_start(0) as u64, old.sectors_per_fat as u64)?;
+    let end = match fat::last_used_cluster(&fat, fat_type, old.cluster_count()) {
+        Some(cluster) => old.cluster_start(cluster + 1),
+        None => old.data_start(),
+    };
+    Ok((end - old.root_dir_start()) as u64)
+}
+
+/// Move the root directory and data up, then write the bigger FATs and BPB
+fn grow_volume(file: &mut File, plan: &VolumePlan, progress: &ProgressReporter) -> anyhow::Result<()> {
+    let (old, new) = (&plan.old, &plan.new);
+    let mut fat = read_sectors(file, plan.start + old.fat_start(0) as u64, old.sectors_per_fat as u64)?;
+
+    let shift = plan.shift() * SECTOR_SIZE as u64;
+    if shift > 0 {
+        progress.set_step("Moving data");
+        let from = (plan.start + old.root_dir_start() as u64) * SECTOR_SIZE as u64;
+        let length = plan.used_sectors * SECTOR_SIZE as u64;
+        move_up(file, from, length, shift, plan.old_end, progress)?;
+    }
+
+    progress.set_step("Writing FAT");
+    // The new clusters start out free
+    fat.resize(new.sectors_per_fat as usize * SECTOR_SIZE as usize, 0);
+    for n in 0..new.fats {
+        let at = (plan.start + new.fat_start(n) as u64) * SECTOR_SIZE as u64;
+        file.seek(SeekFrom::Start(at))?;
+        file.write_all(&fat)?;
+    }
+
+    let mut boot = read_sector(file, plan.start)?;
+    new.write(&mut boot);
+    write_sector(file, plan.start, &boot)?;
+    Ok(())
+}
+
+/// Move `length` bytes at `from` up by `shift`, last chunk first so the
+/// source is read before it is overwritten; zero chunks landing past
+/// `old_end`, in the image's new holes, are skipped to keep it sparse
+fn move_up(
+    file: &mut File,
+    from: u64,
+    length: u64,
+    shift: u64,
+    old_end: u64,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    progress.set_progress(0, length);
+    let mut buf = vec![0u8; CHUNK as usize];
+    let mut remaining = length;
+    while remaining > 0 {
+        let size = remaining.min(CHUNK);
+        let at = from + remaining - size;
+        let chunk = &mut buf[..size as usize];
+        file.seek(SeekFrom::Start(at))?;
+        file.read_exact(chunk)?;
+        if at + shift < old_end || chunk.iter().any(|&b| b != 0) {
+            file.seek(SeekFrom::Start(at + shift))?;
+            file.write_all(chunk)?;
+        }
+        remaining -= size;
+        progress.set_progress(length - remaining, length);
+    }
+    Ok(())
+}
+
+/// CHS address of `lba` as stored in a partition entry, clamped at the
+/// 1024-cylinder limit
+fn chs(lba: u64, heads: u8, sectors_per_track: u8) -> [u8; 3] {
+    let per_cylinder = heads as u64 * sectors_per_track as u64;
+    let cylinder = lba / per_cylinder;
+    if cylinder > 1023 {
+        return [heads - 1, sectors_per_track | 0xC0, 0xFF];
+    }
+    let head = (lba / sectors_per_track as u64 % heads as u64) as u8;
+    let sector = (lba % sectors_per_track as u64 + 1) as u8;
+    [head, sector | ((cylinder >> 8) as u8 & 0x03) << 6, cylinder as u8]
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+fn read_sector(file: &mut File, sector: u64) -> std::io::Result<[u8; 512]> {
+    let mut buf = [0u8; 512];
+    file.seek(SeekFrom::Start(sector * SECTOR_SIZE as u64))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_sectors(file: &mut File, sector: u64, count: u64) -> std::io::Result<Vec<u8>> {
+    let mut buf = vec![0u8; (count * SECTOR_SIZE as u64) as usize];
+    file.seek(SeekFrom::Start(sector * SECTOR_SIZE as u64))?;
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn write_sector(file: &mut File, sector: u64, data: &[u8; 512]) -> std::io::Result<()> {
+    file.seek(SeekFrom::Start(sector * SECTOR_SIZE as u64))?;
+    file.write_all(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::{Allocation, create_disk_image, read_disk_header};
+
+    #[test]
+    fn test_grow_disk_and_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.diskimage");
+        create_disk_image(&path, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+
+        // A file in the first cluster and a root directory entry for it
+        let mut file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let boot = read_sector(&mut file, 63).unwrap();
+        let old = Bpb::parse(&boot).unwrap();
+        let mut fat = read_sectors(&mut file, 63 + old.fat_start(0) as u64, old.sectors_per_fat as u64).unwrap();
+        fat::set_fat_entry(&mut fat, FatType::Fat16, 2, 0xFFFF);
+        for n in 0..old.fats {
+            file.seek(SeekFrom::Start((63 + old.fat_start(n) as u64) * 512)).unwrap();
+            file.write_all(&fat).unwrap();
+        }
+        let mut root = [0u8; 512];
+        root[..11].copy_from_slice(b"README  TXT");
+        root[26] = 2;
+        write_sector(&mut file, 63 + old.root_dir_start() as u64, &root).unwrap();
+        write_sector(&mut file, 63 + old.data_start() as u64, &[0x42; 512]).unwrap();
+        drop(file);
+
+        let resized = resize_disk_image(&path, 64, true, &ProgressReporter::new()).unwrap();
+        let (cylinders, heads, sectors_per_track) = disk_image::calculate_geometry(64);
+        assert_eq!(resized.new_sectors, cylinders as u64 * heads as u64 * sectors_per_track as u64);
+        let (old_clusters, new_clusters) = resized.clusters.unwrap();
+        assert!(new_clusters > old_clusters * 3);
+
+        let info = read_disk_header(&path).unwrap();
+        assert_eq!(info.total_sectors, resized.new_sectors);
+        assert_eq!(info.cylinders, cylinders);
+
+        let mut file = File::open(&path).unwrap();
+        let new = Bpb::parse(&read_sector(&mut file, 63).unwrap()).unwrap();
+        assert!(new.sectors_per_fat > old.sectors_per_fat);
+        assert_eq!(new.cluster_count(), new_clusters);
+        assert!(63 + new.total_sectors as u64 <= resized.new_sectors);
+        // The file is still where its directory entry says
+        assert_eq!(&read_sector(&mut file, 63 + new.root_dir_start() as u64).unwrap()[..11], b"README  TXT");
+        assert_eq!(read_sector(&mut file, 63 + new.cluster_start(2) as u64).unwrap(), [0x42; 512]);
+        let fat = read_sectors(&mut file, 63 + new.fat_start(1) as u64, new.sectors_per_fat as u64).unwrap();
+        assert_eq!(fat::fat_entry(&fat, FatType::Fat16, 2), 0xFFFF);
+        assert_eq!(fat::last_used_cluster(&fat, FatType::Fat16, new_clusters), Some(2));
+
+        let mut mbr = [0u8; 512];
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.read_exact(&mut mbr).unwrap();
+        assert_eq!(read_u32(&mbr, 0x1BE + 12) as u64, resized.new_sectors - 63);
+    }
+
+    #[test]
+    fn test_grow_disk_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.diskimage");
+        create_disk_image(&path, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        let before = read_disk_header(&path).unwrap();
+
+        let resized = resize_disk_image(&path, 600, false, &ProgressReporter::new()).unwrap();
+        assert_eq!(resized.clusters, None);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), resized.new_sectors * 512);
+
+        // The partition keeps its size but takes the new geometry
+        let mut file = File::open(&path).unwrap();
+        let mbr = read_sector(&mut file, 0).unwrap();
+        assert_eq!(read_u32(&mbr, 0x1BE + 12) as u64, before.total_sectors - 63);
+        let bpb = Bpb::parse(&read_sector(&mut file, 63).unwrap()).unwrap();
+        assert_eq!(bpb.heads, 32);
+
+        assert!(resize_disk_image(&path, 100, false, &ProgressReporter::new()).is_err());
+    }
+}
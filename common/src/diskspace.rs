@@ -0,0 +1,160 @@
+//! Free-space checks for disk image operations.
+//!
+//! Image creation and expansion check the target filesystem before writing
+//! anything, so the common case of "not enough room" fails up front with a
+//! useful message instead of halfway through. Images are written sparse
+//! where possible, so only the bytes that are actually allocated count
+//! towards the requirement; the full logical size is only needed once the
+//! guest fills the disk.
+//!
+//! Free space can still run out mid-operation (other writers, quotas), so
+//! writers pass I/O errors through `map_disk_full` to turn ENOSPC/EDQUOT
+//! into the same `DiskSpaceError` and clean up their partial output.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::sys::statvfs::statvfs;
+use thiserror::Error;
+
+const MB: u64 = 1024 * 1024;
+
+/// Not enough space for a disk image operation
+#[derive(Debug, Error)]
+pub enum DiskSpaceError {
+    /// The pre-check found too little free space; nothing was written
+    #[error(
+        "not enough free space for {}: {} MB required, {} MB available",
+        .path.display(), .required.div_ceil(MB), .available / MB
+    )]
+    Insufficient {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+
+    /// The filesystem filled up during the operation
+    #[error(
+        "disk full while writing {}: {} MB required",
+        .path.display(), .required.div_ceil(MB)
+    )]
+    Full { path: PathBuf, required: u64 },
+}
+
+impl DiskSpaceError {
+    /// Image the operation was writing
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Insufficient { path, .. } | Self::Full { path, .. } => path,
+        }
+    }
+
+    /// Bytes of free space the operation needs
+    pub fn required(&self) -> u64 {
+        match self {
+            Self::Insufficient { required, .. } | Self::Full { required, .. } => *required,
+        }
+    }
+
+    /// Bytes of free space available now (re-queried for `Full`)
+    pub fn available(&self) -> u64 {
+        match self {
+            Self::Insufficient { available, .. } => *available,
+            Self::Full { path, .. } => available_space(path).unwrap_or(0),
+        }
+    }
+}
+
+/// Bytes available to unprivileged users on the filesystem holding `path`
+///
+/// `path` does not need to exist yet; its nearest existing ancestor is used.
+pub fn available_space(path: &Path) -> io::Result<u64> {
+    let dir = path
+        .ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or(Path::new("."));
+    let stat = statvfs(dir).map_err(io::Error::from)?;
+    Ok(stat.blocks_available() as u64 * stat.fragment_size() as u64)
+}
+
+/// Space an image of `logical_size` bytes needs when created
+///
+/// Sparse images only need the `allocated` bytes actually written (headers,
+/// FATs); fully allocated images need their whole size.
+pub fn space_required(logical_size: u64, allocated: u64, sparse: bool) -> u64 {
+    if sparse { allocated.min(logical_size) } else { logical_size }
+}
+
+/// Check that `required` bytes can be written at `path`
+///
+/// If free space cannot be determined the check passes; the write itself
+/// will then report ENOSPC through `map_disk_full`.
+pub fn check_free_space(path: &Path, required: u64) -> Result<(), DiskSpaceError> {
+    match available_space(path) {
+        Ok(available) if available < required => Err(DiskSpaceError::Insufficient {
+            path: path.to_path_buf(),
+            required,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Whether an I/O error means the filesystem (or the user's quota) is full
+pub fn is_disk_full(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error().map(Errno::from_raw),
+        Some(Errno::ENOSPC | Errno::EDQUOT)
+    )
+}
+
+/// Replace an ENOSPC/EDQUOT error with `DiskSpaceError::Full`
+///
+/// Other errors are returned unchanged.
+pub fn map_disk_full(err: anyhow::Error, path: &Path, required: u64) -> anyhow::Error {
+    let full = err
+        .chain()
+        .any(|cause| cause.downcast_ref::<io::Error>().is_some_and(is_disk_full));
+    if full {
+        DiskSpaceError::Full {
+            path: path.to_path_buf(),
+            required,
+        }
+        .into()
+    } else {
+        err
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_free_space_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("missing/subdir/disk.img");
+        assert!(available_space(&image).unwrap() > 0);
+        assert!(check_free_space(&image, 0).is_ok());
+
+        let err = check_free_space(&image, u64::MAX).unwrap_err();
+        assert_eq!(err.required(), u64::MAX);
+        assert!(err.to_string().starts_with("not enough free space"));
+
+        assert_eq!(space_required(100 * MB, MB, true), MB);
+        assert_eq!(space_required(100 * MB, MB, false), 100 * MB);
+    }
+
+    #[test]
+    fn test_map_disk_full() {
+        let path = Path::new("/tmp/disk.img");
+        let enospc = anyhow::Error::from(io::Error::from_raw_os_error(Errno::ENOSPC as i32));
+        let mapped = map_disk_full(enospc.context("writing FAT"), path, 5 * MB);
+        let err = mapped.downcast_ref::<DiskSpaceError>().unwrap();
+        assert!(matches!(err, DiskSpaceError::Full { required, .. } if *required == 5 * MB));
+
+        let other = anyhow::Error::from(io::Error::from_raw_os_error(Errno::EACCES as i32));
+        assert!(map_disk_full(other, path, 0).downcast_ref::<DiskSpaceError>().is_none());
+    }
+}
@@ -0,0 +1,78 @@
+//! Space used under the host directories of mapped drives.
+//!
+//! A mapping with a size limit (`DriveMapping::max_size_mb`) stops the guest
+//! from filling the host disk through it. The driver's FSD only counts the
+//! bytes the guest's writes add, so it is given a starting figure measured
+//! here, and the frontend measures again now and then to catch files
+//! deleted or changed on the host or by the guest.
+//!
+//! Sizes are file lengths, which is what the driver counts, not blocks
+//! allocated. The walk stays on the filesystem of the directory, does not
+//! follow symlinks, counts hard-linked files once and skips directories it
+//! cannot read.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use crate::progress::ProgressReporter;
+
+/// Bytes stored in files under `dir`
+///
+/// Reports the files counted as progress and can be cancelled between
+/// directories.
+pub fn measure(dir: &Path, progress: &ProgressReporter) -> io::Result<u64> {
+    let root = fs::metadata(dir)?;
+    if !root.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::NotADirectory, format!("{} is not a directory", dir.display())));
+    }
+
+    let mut total = 0;
+    let mut linked = HashSet::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        progress.check_cancelled().map_err(|_| io::Error::from(io::ErrorKind::Interrupted))?;
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            if meta.dev() != root.dev() {
+                continue;
+            }
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else if meta.is_file() && (meta.nlink() == 1 || linked.insert(meta.ino())) {
+                total += meta.len();
+                progress.advance(1);
+            }
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measure() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("one"), vec![0u8; 1000]).unwrap();
+        std::fs::write(dir.path().join("a/b/two"), vec![0u8; 234]).unwrap();
+        std::fs::hard_link(dir.path().join("one"), dir.path().join("a/same")).unwrap();
+        std::os::unix::fs::symlink("/usr", dir.path().join("a/usr")).unwrap();
+
+        let progress = ProgressReporter::new();
+        assert_eq!(measure(dir.path(), &progress).unwrap(), 1234);
+        assert!(measure(&dir.path().join("one"), &progress).is_err());
+
+        progress.cancel();
+        assert!(measure(dir.path(), &progress).is_err());
+    }
+}
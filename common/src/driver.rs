@@ -4,16 +4,26 @@
 //! The frontend uses this directly - no daemon required.
 
 use std::fs::{File, OpenOptions};
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use nix::errno::Errno;
+use nix::sys::epoll::{Epoll, EpollCreateFlags, EpollEvent, EpollFlags, EpollTimeout};
+use serde::Serialize;
 
 use crate::ioctl::{
-    AudioBuffer, AudioFormat, AudioStatus, AudioVolume,
-    Clipboard, DisplayConfig, DisplayInfo, DiskMount, DiskSlot, DriveLetter, DriveMapping,
-    FloppyMount, FloppySlot, FramebufferInfo, IoctlSessionConfig, KeyEvent, MouseEvent,
-    NetworkConfig, NetworkStatus, Path, SessionStatus, DriverVersion,
-    SUNPCI_MAX_PATH, clipboard_format, disk_flags, drive_flags,
+    AudioBuffer, AudioFormat, AudioStatus, AudioVolume, MidiBuffer,
+    Clipboard, ClipboardChunk, DisplayConfig, DisplayInfo, DiskMount, DiskSlot, DriveLetter, DriveMapping,
+    FloppyMount, FloppySlot, FramebufferInfo, IoctlSessionConfig, KeyEvent, KeyboardLeds,
+    GameportEvent, MouseAbsEvent, MouseEvent, TextCursor,
+    NetFrame, NetworkConfig, NetworkStatus, Path, SessionStatus, DriverVersion,
+    SUNPCI_MAX_CLIPBOARD, SUNPCI_MAX_CLIPBOARD_TRANSFER, SUNPCI_MAX_PATH, clipboard_format, disk_flags, drive_flags,
     sunpci_add_drive_map, sunpci_eject_cdrom, sunpci_eject_floppy, sunpci_get_clipboard,
     sunpci_get_display, sunpci_get_framebuffer, sunpci_get_network, sunpci_get_status,
     sunpci_get_version, sunpci_keyboard_event, sunpci_mount_cdrom, sunpci_mount_disk,
@@ -21,37 +31,155 @@ use crate::ioctl::{
     sunpci_set_clipboard, sunpci_set_display, sunpci_set_network, sunpci_start_session,
     sunpci_stop_session, sunpci_unmount_disk,
     sunpci_get_audio_format, sunpci_get_audio_status, sunpci_get_audio_volume,
-    sunpci_set_audio_volume, sunpci_read_audio,
+    sunpci_set_audio_volume, sunpci_read_audio, sunpci_write_audio, sunpci_read_midi,
+    sunpci_get_keyboard_leds, sunpci_set_keyboard_leds, sunpci_mouse_abs_event, sunpci_gameport_event,
+    sunpci_get_text_cursor, sunpci_get_clipboard_chunk, sunpci_set_clipboard_chunk,
+    sunpci_net_recv_frame, sunpci_net_send_frame,
+    CdromPassthrough, ScsiCommand, ScsiRequest, ScsiResponse,
+    sunpci_cdrom_passthrough, sunpci_scsi_command, sunpci_scsi_pt_complete, sunpci_scsi_pt_next,
+    SessionOwner, claim_flags, sunpci_claim_session, sunpci_get_owner, sunpci_release_session,
+    sunpci_request_shutdown,
+    StateChunk, StateInfo, SUNPCI_STATE_CHUNK, sunpci_read_state, sunpci_resume_session,
+    sunpci_suspend_session, sunpci_write_state,
+    LogBatch, sunpci_read_log,
+    DriverEvent, EventBatch, SUNPCI_MAX_EVENTS, event_type, sunpci_get_events,
 };
 use crate::SunPciError;
 
-const DEVICE_PATH: &str = "/dev/sunpci0";
+/// Directory the driver creates its device nodes in
+const DEVICE_DIR: &str = "/dev";
+/// Device nodes are named sunpci0, sunpci1, ... by card index
+const DEVICE_PREFIX: &str = "sunpci";
 
-/// Check if the SunPCi driver is loaded
+/// How often the event listener thread checks whether to stop
+const LISTEN_POLL: Duration = Duration::from_millis(100);
+
+/// Card `DriverHandle::open` and `is_driver_loaded` use
+static SELECTED_CARD: AtomicU32 = AtomicU32::new(0);
+
+/// Path of the device node of a card
+pub fn device_path(index: u32) -> PathBuf {
+    PathBuf::from(DEVICE_DIR).join(format!("{}{}", DEVICE_PREFIX, index))
+}
+
+/// Card index of a device node name ("sunpci1" -> 1)
+fn parse_device_name(name: &str) -> Option<u32> {
+    let index = name.strip_prefix(DEVICE_PREFIX)?;
+    if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    index.parse().ok()
+}
+
+/// Indices of the SunPCi cards the driver found, in order
+pub fn list_devices() -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(DEVICE_DIR) else {
+        return Vec::new();
+    };
+    let mut cards: Vec<u32> = entries
+        .flatten()
+        .filter_map(|entry| parse_device_name(&entry.file_name().to_string_lossy()))
+        .collect();
+    cards.sort_unstable();
+    cards
+}
+
+/// Use card `index` for the rest of the process
+///
+/// Each card runs its own session; a frontend drives one of them.
+pub fn select_card(index: u32) {
+    SELECTED_CARD.store(index, Ordering::Relaxed);
+}
+
+/// Card this process drives
+pub fn selected_card() -> u32 {
+    SELECTED_CARD.load(Ordering::Relaxed)
+}
+
+/// Check if the SunPCi driver is loaded and the selected card is present
 pub fn is_driver_loaded() -> bool {
-    std::path::Path::new(DEVICE_PATH).exists()
+    device_path(selected_card()).exists()
+}
+
+/// Oldest driver the frontend works with: the first with every ioctl it uses
+/// and their current payload sizes (0.1 lacks most of them)
+pub const MIN_DRIVER_VERSION: DriverVersion = DriverVersion { major: 0, minor: 2, patch: 0 };
+
+/// Newest driver release line the frontend knows. Patch releases keep the
+/// ioctl structures, so any patch of it is accepted; a later minor or
+/// major release may have changed them.
+pub const MAX_DRIVER_VERSION: DriverVersion = DriverVersion { major: 0, minor: 2, patch: 0 };
+
+/// Whether a driver version works with this frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compatibility {
+    Ok,
+    /// Older than the oldest supported release
+    TooOld { min: DriverVersion },
+    /// From a release line after the newest supported one
+    TooNew { max: DriverVersion },
+}
+
+impl Compatibility {
+    pub fn is_ok(&self) -> bool {
+        *self == Self::Ok
+    }
+
+    /// What to tell the user about driver `version`, None if it is fine
+    pub fn message(&self, version: &DriverVersion) -> Option<String> {
+        match self {
+            Self::Ok => None,
+            Self::TooOld { min } => Some(format!(
+                "SunPCi driver {} is too old; this version of Rising Sun needs {} or later. \
+                 Rebuild and reload the driver from this release (see driver/dkms.conf).",
+                version, min
+            )),
+            Self::TooNew { max } => Some(format!(
+                "SunPCi driver {} is newer than this version of Rising Sun supports (up to {}.{}.x). \
+                 Update Rising Sun, or load the driver that came with it.",
+                version, max.major, max.minor
+            )),
+        }
+    }
+}
+
+/// Check a driver version against the range this frontend supports
+pub fn check_compatibility(version: &DriverVersion) -> Compatibility {
+    if *version < MIN_DRIVER_VERSION {
+        Compatibility::TooOld { min: MIN_DRIVER_VERSION }
+    } else if (version.major, version.minor) > (MAX_DRIVER_VERSION.major, MAX_DRIVER_VERSION.minor) {
+        Compatibility::TooNew { max: MAX_DRIVER_VERSION }
+    } else {
+        Compatibility::Ok
+    }
 }
 
 /// Handle to the SunPCi device.
 /// 
 /// This provides direct access to the kernel driver via ioctl.
-/// Only one instance should be active at a time since the hardware
+/// Only one instance per card should be active at a time since each card
 /// is single-user (one display, one keyboard/mouse, one set of drives).
 pub struct DriverHandle {
     file: File,
 }
 
 impl DriverHandle {
-    /// Open the SunPCi device.
+    /// Open the selected SunPCi card (see `select_card`).
     /// 
-    /// Requires read/write access to /dev/sunpci0.
+    /// Requires read/write access to /dev/sunpciN.
     /// Use udev rules to grant access to a 'sunpci' group.
     pub fn open() -> Result<Self> {
+        Self::open_device(selected_card())
+    }
+
+    /// Open card `index` (/dev/sunpci<index>)
+    pub fn open_device(index: u32) -> Result<Self> {
+        let path = device_path(index);
         let file = OpenOptions::new()
             .read(true)
             .write(true)
-            .open(DEVICE_PATH)
-            .with_context(|| format!("Failed to open {}", DEVICE_PATH))?;
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
 
         Ok(Self { file })
     }
@@ -112,6 +240,117 @@ impl DriverHandle {
         Ok(())
     }
 
+    /// Ask the guest OS to shut down, as if its power button were pressed
+    ///
+    /// Returns once the request is sent; an `Event::PowerOff` follows when
+    /// the guest has shut down.
+    pub fn request_shutdown(&self) -> Result<()> {
+        unsafe {
+            sunpci_request_shutdown(self.file.as_raw_fd())
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Freeze the running guest and snapshot its state on the card
+    ///
+    /// The session is left suspended; read the snapshot with `read_state`,
+    /// then stop the session. A failed suspend leaves the guest running.
+    pub fn suspend_session(&self) -> Result<StateInfo> {
+        let mut info = StateInfo::default();
+        unsafe {
+            sunpci_suspend_session(self.file.as_raw_fd(), &mut info)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(info)
+    }
+
+    /// Read the suspended guest's snapshot from `offset` into `buf`
+    ///
+    /// Returns the bytes read, which is less than `buf.len()` only at the
+    /// end of the snapshot.
+    pub fn read_state(&self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        let mut chunk = Box::<StateChunk>::default();
+        let mut done = 0;
+        while done < buf.len() {
+            chunk.offset.set(offset + done as u64);
+            chunk.length = 0;
+            unsafe {
+                sunpci_read_state(self.file.as_raw_fd(), &mut *chunk)
+                    .map_err(SunPciError::from)?;
+            }
+            let length = (chunk.length as usize).min(SUNPCI_STATE_CHUNK);
+            let take = length.min(buf.len() - done);
+            buf[done..done + take].copy_from_slice(&chunk.data[..take]);
+            done += take;
+            if length < SUNPCI_STATE_CHUNK {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    /// Write part of a snapshot to a session started with `flags::RESUME`
+    pub fn write_state(&self, offset: u64, data: &[u8]) -> Result<()> {
+        let mut chunk = Box::<StateChunk>::default();
+        for (i, part) in data.chunks(SUNPCI_STATE_CHUNK).enumerate() {
+            chunk.offset.set(offset + (i * SUNPCI_STATE_CHUNK) as u64);
+            chunk.length = part.len() as u32;
+            chunk.data[..part.len()].copy_from_slice(part);
+            unsafe {
+                sunpci_write_state(self.file.as_raw_fd(), &*chunk)
+                    .map_err(SunPciError::from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Load the snapshot written with `write_state` and let the guest run
+    pub fn resume_session(&self, info: &StateInfo) -> Result<()> {
+        unsafe {
+            sunpci_resume_session(self.file.as_raw_fd(), info)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Become the owner of the running session
+    ///
+    /// `control_port` is published so another frontend can ask this one to
+    /// release the session (0 for none). Fails while another handle owns
+    /// the session unless `force` is set. Returns the previous owner.
+    pub fn claim_session(&self, control_port: u16, force: bool) -> Result<SessionOwner> {
+        let mut owner = SessionOwner {
+            control_port,
+            flags: if force { claim_flags::FORCE } else { 0 },
+            ..Default::default()
+        };
+        unsafe {
+            sunpci_claim_session(self.file.as_raw_fd(), &mut owner)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(owner)
+    }
+
+    /// Give up ownership of the session, leaving it running
+    pub fn release_session(&self) -> Result<()> {
+        unsafe {
+            sunpci_release_session(self.file.as_raw_fd())
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Current owner of the session
+    pub fn get_owner(&self) -> Result<SessionOwner> {
+        let mut owner = SessionOwner::default();
+        unsafe {
+            sunpci_get_owner(self.file.as_raw_fd(), &mut owner)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(owner)
+    }
+
     // ========================================================================
     // Display
     // ========================================================================
@@ -145,6 +384,16 @@ impl DriverHandle {
         Ok(info)
     }
 
+    /// Get the text mode cursor
+    pub fn get_text_cursor(&self) -> Result<TextCursor> {
+        let mut cursor = TextCursor::default();
+        unsafe {
+            sunpci_get_text_cursor(self.file.as_raw_fd(), &mut cursor)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(cursor)
+    }
+
     // ========================================================================
     // Storage
     // ========================================================================
@@ -192,6 +441,70 @@ impl DriverHandle {
         Ok(())
     }
 
+    /// Pass the guest CD-ROM through to a host drive, or back to images
+    ///
+    /// While enabled the caller must serve guest commands with
+    /// `next_scsi_request` / `complete_scsi_request`; `device` is only
+    /// recorded for status. Enabling ejects any mounted ISO.
+    pub fn set_cdrom_passthrough(&self, device: Option<&str>) -> Result<()> {
+        let mut pt = CdromPassthrough::default();
+        if let Some(device) = device {
+            pt.enable = 1;
+            set_path(&mut pt.device, device);
+        }
+        unsafe {
+            sunpci_cdrom_passthrough(self.file.as_raw_fd(), &pt)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Run a SCSI command on the guest's CD-ROM
+    ///
+    /// `data` is the transfer buffer in the direction of the request. In
+    /// pass-through mode the command is served like a guest command, so
+    /// this must not be called from the thread serving them.
+    pub fn scsi_command(&self, request: &ScsiRequest, data: &mut [u8]) -> Result<ScsiResponse> {
+        let mut cmd = ScsiCommand::with_buffer(data);
+        cmd.request = *request;
+        unsafe {
+            sunpci_scsi_command(self.file.as_raw_fd(), &mut cmd)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(cmd.response)
+    }
+
+    /// Take the next guest CD-ROM command in pass-through mode
+    ///
+    /// Write data is copied into `data`. Returns the command's tag and
+    /// request, or None when no command is waiting.
+    pub fn next_scsi_request(&self, data: &mut [u8]) -> Result<Option<(u32, ScsiRequest)>> {
+        let mut cmd = ScsiCommand::with_buffer(data);
+        match unsafe { sunpci_scsi_pt_next(self.file.as_raw_fd(), &mut cmd) } {
+            Ok(_) => Ok(Some((cmd.tag, cmd.request))),
+            Err(nix::errno::Errno::EAGAIN) => Ok(None),
+            Err(e) => Err(SunPciError::from(e).into()),
+        }
+    }
+
+    /// Answer a guest command taken with `next_scsi_request`
+    ///
+    /// `data` holds the read data (`response.data_len` bytes are used).
+    pub fn complete_scsi_request(&self, tag: u32, response: &ScsiResponse, data: &[u8]) -> Result<()> {
+        let cmd = ScsiCommand {
+            tag,
+            response: *response,
+            data_ptr: data.as_ptr() as u64,
+            data_buf_len: data.len().min(u32::MAX as usize) as u32,
+            ..Default::default()
+        };
+        unsafe {
+            sunpci_scsi_pt_complete(self.file.as_raw_fd(), &cmd)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
     /// Mount a floppy image (drive 0 = A:, drive 1 = B:)
     pub fn mount_floppy(&self, drive: u32, path: &str) -> Result<()> {
         let mut mount = FloppyMount::default();
@@ -236,6 +549,44 @@ impl DriverHandle {
         Ok(())
     }
 
+    /// Send an absolute mouse event to the guest (tablet mode)
+    pub fn send_mouse_abs_event(&self, event: &MouseAbsEvent) -> Result<()> {
+        unsafe {
+            sunpci_mouse_abs_event(self.file.as_raw_fd(), event)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Set the state of the guest's game port
+    pub fn send_gameport_event(&self, event: &GameportEvent) -> Result<()> {
+        unsafe {
+            sunpci_gameport_event(self.file.as_raw_fd(), event)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Get the guest's keyboard LED state (keyboard_leds::* bitmap)
+    pub fn get_keyboard_leds(&self) -> Result<u32> {
+        let mut leds = KeyboardLeds::default();
+        unsafe {
+            sunpci_get_keyboard_leds(self.file.as_raw_fd(), &mut leds)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(leds.leds)
+    }
+
+    /// Set the guest's keyboard LED state (keyboard_leds::* bitmap)
+    pub fn set_keyboard_leds(&self, leds: u32) -> Result<()> {
+        let leds = KeyboardLeds { leds };
+        unsafe {
+            sunpci_set_keyboard_leds(self.file.as_raw_fd(), &leds)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
     // ========================================================================
     // Clipboard
     // ========================================================================
@@ -244,7 +595,10 @@ impl DriverHandle {
     pub fn set_clipboard(&self, text: &str) -> Result<()> {
         let mut clipboard = Clipboard::default();
         let bytes = text.as_bytes();
-        let len = bytes.len().min(clipboard.data.len() - 1);
+        if bytes.len() >= clipboard.data.len() {
+            return self.set_clipboard_data(clipboard_format::TEXT, bytes);
+        }
+        let len = bytes.len();
         clipboard.data[..len].copy_from_slice(&bytes[..len]);
         clipboard.length = len as u32;
         clipboard.format = clipboard_format::TEXT;
@@ -262,22 +616,82 @@ impl DriverHandle {
             sunpci_get_clipboard(self.file.as_raw_fd(), &mut clipboard)
                 .map_err(SunPciError::from)?;
         }
-        let len = clipboard.length as usize;
-        let text = std::str::from_utf8(&clipboard.data[..len])
-            .context("Invalid UTF-8 in clipboard")?
-            .to_string();
+        let len = (clipboard.length as usize).min(SUNPCI_MAX_CLIPBOARD);
+        // A full buffer may be truncated; fetch the whole text in chunks
+        let data = if len == SUNPCI_MAX_CLIPBOARD {
+            self.get_clipboard_data(clipboard.format)?
+        } else {
+            clipboard.data[..len].to_vec()
+        };
+        let text = String::from_utf8(data)
+            .context("Invalid UTF-8 in clipboard")?;
         Ok(text)
     }
 
+    /// Send clipboard data of any size (up to SUNPCI_MAX_CLIPBOARD_TRANSFER)
+    /// as a chunked transfer
+    pub fn set_clipboard_data(&self, format: u32, data: &[u8]) -> Result<()> {
+        if data.len() > SUNPCI_MAX_CLIPBOARD_TRANSFER {
+            anyhow::bail!("Clipboard data too large: {} bytes", data.len());
+        }
+        let mut chunk = ClipboardChunk {
+            format,
+            total_length: data.len() as u32,
+            ..Default::default()
+        };
+        for (i, part) in data.chunks(SUNPCI_MAX_CLIPBOARD).enumerate() {
+            chunk.offset = (i * SUNPCI_MAX_CLIPBOARD) as u32;
+            chunk.length = part.len() as u32;
+            chunk.data[..part.len()].copy_from_slice(part);
+            unsafe {
+                sunpci_set_clipboard_chunk(self.file.as_raw_fd(), &chunk)
+                    .map_err(SunPciError::from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the whole guest clipboard in the given format as a chunked transfer
+    pub fn get_clipboard_data(&self, format: u32) -> Result<Vec<u8>> {
+        let mut chunk = ClipboardChunk {
+            format,
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+        loop {
+            chunk.offset = data.len() as u32;
+            unsafe {
+                sunpci_get_clipboard_chunk(self.file.as_raw_fd(), &mut chunk)
+                    .map_err(SunPciError::from)?;
+            }
+            let total = chunk.total_length as usize;
+            let len = chunk.length as usize;
+            if total > SUNPCI_MAX_CLIPBOARD_TRANSFER
+                || len > SUNPCI_MAX_CLIPBOARD
+                || data.len() + len > total
+                || (len == 0 && data.len() < total)
+            {
+                anyhow::bail!("Invalid clipboard chunk at offset {}", data.len());
+            }
+            data.extend_from_slice(&chunk.data[..len]);
+            if data.len() == total {
+                return Ok(data);
+            }
+        }
+    }
+
     // ========================================================================
     // Drive Mappings (filesystem redirection)
     // ========================================================================
 
     /// Add a drive mapping (E: through Z: mapped to host paths)
-    pub fn add_drive_mapping(&self, letter: char, path: &str, readonly: bool) -> Result<()> {
+    ///
+    /// The guest sees file times `tz_offset` minutes east of UTC.
+    pub fn add_drive_mapping(&self, letter: char, path: &str, readonly: bool, tz_offset: i16) -> Result<()> {
         let mut mapping = DriveMapping::default();
         mapping.letter = letter as u8;
-        mapping.flags = if readonly { drive_flags::READONLY } else { 0 };
+        mapping.flags = drive_flags::TZ_OFFSET | if readonly { drive_flags::READONLY } else { 0 };
+        mapping.tz_offset = tz_offset;
         set_path(&mut mapping.path, path);
         unsafe {
             sunpci_add_drive_map(self.file.as_raw_fd(), &mapping)
@@ -319,6 +733,28 @@ impl DriverHandle {
         Ok(status)
     }
 
+    /// Deliver an Ethernet frame to the guest (user-mode networking)
+    pub fn send_net_frame(&self, frame: &[u8]) -> Result<()> {
+        let frame = NetFrame::new(frame);
+        unsafe {
+            sunpci_net_send_frame(self.file.as_raw_fd(), &frame)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(())
+    }
+
+    /// Take the next Ethernet frame the guest sent (user-mode networking)
+    ///
+    /// Returns None when no frame is queued.
+    pub fn recv_net_frame(&self) -> Result<Option<Vec<u8>>> {
+        let mut frame = NetFrame::default();
+        match unsafe { sunpci_net_recv_frame(self.file.as_raw_fd(), &mut frame) } {
+            Ok(_) => Ok(Some(frame.as_bytes().to_vec())),
+            Err(nix::errno::Errno::EAGAIN) => Ok(None),
+            Err(e) => Err(SunPciError::from(e).into()),
+        }
+    }
+
     // ========================================================================
     // Audio
     // ========================================================================
@@ -383,12 +819,482 @@ impl DriverHandle {
         Ok(buffer.data[..bytes_read].to_vec())
     }
 
+    /// Feed recorded audio to the guest's input: 16-bit signed mono at
+    /// `SUNPCI_AUDIO_CAPTURE_RATE`. Returns the number of bytes taken, fewer
+    /// than given when the guest's buffer is full.
+    pub fn write_audio(&self, data: &[u8]) -> Result<usize> {
+        let mut buffer = AudioBuffer::default();
+        let len = data.len().min(buffer.data.len());
+        buffer.size = len as u32;
+        buffer.data[..len].copy_from_slice(&data[..len]);
+
+        unsafe {
+            sunpci_write_audio(self.file.as_raw_fd(), &mut buffer)
+                .map_err(SunPciError::from)?;
+        }
+
+        Ok(buffer.size as usize)
+    }
+
+    /// Read the bytes the guest wrote to the MPU-401 since the last call,
+    /// into `data`. Returns the number of bytes read.
+    pub fn read_midi(&self, data: &mut [u8]) -> Result<usize> {
+        let mut buffer = MidiBuffer::default();
+        buffer.size = data.len().min(buffer.data.len()) as u32;
+
+        unsafe {
+            sunpci_read_midi(self.file.as_raw_fd(), &mut buffer)
+                .map_err(SunPciError::from)?;
+        }
+
+        let len = (buffer.size as usize).min(data.len());
+        data[..len].copy_from_slice(&buffer.data[..len]);
+        Ok(len)
+    }
+
     /// Check if audio hardware is available
     pub fn is_audio_available(&self) -> bool {
         self.get_audio_status()
             .map(|s| s.is_available())
             .unwrap_or(false)
     }
+
+    // ========================================================================
+    // Events
+    // ========================================================================
+
+    /// Read the events posted since the last call on this handle
+    ///
+    /// Never blocks; the device fd polls readable while events are pending.
+    pub fn read_events(&self) -> Result<Vec<DriverEvent>> {
+        let mut events = Vec::new();
+        loop {
+            let mut batch = EventBatch::default();
+            unsafe {
+                sunpci_get_events(self.file.as_raw_fd(), &mut batch)
+                    .map_err(SunPciError::from)?;
+            }
+            events.extend_from_slice(batch.events());
+            // A short batch means the queue is drained
+            if batch.events().len() < SUNPCI_MAX_EVENTS {
+                return Ok(events);
+            }
+        }
+    }
+
+    // ========================================================================
+    // Driver Log
+    // ========================================================================
+
+    /// Read up to one batch of the driver's log messages from `since` on
+    /// (0 for the oldest held); pass the batch's `next` to continue
+    pub fn read_log(&self, since: u32) -> Result<LogBatch> {
+        let mut batch = LogBatch { since, ..Default::default() };
+        unsafe {
+            sunpci_read_log(self.file.as_raw_fd(), &mut batch)
+                .map_err(SunPciError::from)?;
+        }
+        Ok(batch)
+    }
+}
+
+/// An asynchronous driver event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Events were lost; whatever they would have reported must be re-read
+    Overflow,
+    /// The guest asked to eject the medium in a drive (BIOS numbering,
+    /// see ioctl::event_drive)
+    EjectRequest { drive: u32 },
+    /// A medium was mounted from `path`, or ejected if `path` is `None`
+    MediaChanged { drive: u32, path: Option<String> },
+    /// The guest set a display mode
+    DisplayChanged,
+    /// The session was started, stopped or reset (`state` is a SessionState)
+    SessionChanged { state: u32 },
+    /// The guest clipboard has new data (`format` is a clipboard_format)
+    Clipboard { format: u32 },
+    /// The guest changed the indexed 8-bit palette
+    PaletteChanged { generation: u32 },
+    /// The guest powered itself off
+    PowerOff,
+}
+
+impl Event {
+    /// Decode a raw event; `None` for types this build does not know
+    pub fn from_raw(raw: &DriverEvent) -> Option<Self> {
+        Some(match raw.kind {
+            event_type::OVERFLOW => Self::Overflow,
+            event_type::EJECT_REQUEST => Self::EjectRequest { drive: raw.drive },
+            event_type::MEDIA_CHANGED => Self::MediaChanged {
+                drive: raw.drive,
+                path: raw.media_present().then(|| raw.path()),
+            },
+            event_type::DISPLAY_CHANGED => Self::DisplayChanged,
+            event_type::SESSION_CHANGED => Self::SessionChanged { state: raw.flags },
+            event_type::CLIPBOARD => Self::Clipboard { format: raw.flags },
+            event_type::PALETTE_CHANGED => Self::PaletteChanged { generation: raw.flags },
+            event_type::POWER_OFF => Self::PowerOff,
+            _ => return None,
+        })
+    }
+}
+
+/// Waits for driver events with epoll
+///
+/// Holds its own driver handle, so it sees every event posted after it was
+/// opened, including the ones this process causes. The epoll fd can itself
+/// be added to another event loop.
+pub struct DriverEvents {
+    handle: DriverHandle,
+    epoll: Epoll,
+}
+
+impl DriverEvents {
+    /// Open the device and start collecting its events
+    pub fn open() -> Result<Self> {
+        let handle = DriverHandle::open()?;
+        let epoll = Epoll::new(EpollCreateFlags::EPOLL_CLOEXEC).map_err(SunPciError::from)?;
+        epoll
+            .add(&handle.file, EpollEvent::new(EpollFlags::EPOLLIN, 0))
+            .map_err(SunPciError::from)?;
+        Ok(Self { handle, epoll })
+    }
+
+    /// Wait up to `timeout` (forever if `None`) for events
+    ///
+    /// Returns every pending event, or none if the wait timed out or was
+    /// interrupted.
+    pub fn wait(&self, timeout: Option<Duration>) -> Result<Vec<Event>> {
+        let timeout = match timeout {
+            Some(timeout) => EpollTimeout::try_from(timeout).unwrap_or(EpollTimeout::MAX),
+            None => EpollTimeout::NONE,
+        };
+        let mut ready = [EpollEvent::empty()];
+        match self.epoll.wait(&mut ready, timeout) {
+            Ok(0) | Err(Errno::EINTR) => Ok(Vec::new()),
+            Ok(_) => self.read(),
+            Err(e) => Err(SunPciError::from(e).into()),
+        }
+    }
+
+    /// Pending events, without waiting
+    pub fn read(&self) -> Result<Vec<Event>> {
+        let raw = self.handle.read_events()?;
+        Ok(raw.iter().filter_map(Event::from_raw).collect())
+    }
+}
+
+impl AsRawFd for DriverEvents {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll.0.as_raw_fd()
+    }
+}
+
+/// Waits for driver events on a background thread until dropped
+///
+/// The UI thread takes them with `try_next`, so one timer serves every
+/// subsystem instead of each polling the driver for changes.
+pub struct EventListener {
+    events: Receiver<Event>,
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EventListener {
+    /// Open the device and start listening
+    pub fn start() -> Result<Self> {
+        let events = DriverEvents::open()?;
+        let (sender, receiver) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = std::thread::Builder::new()
+            .name("driver-events".to_string())
+            .spawn(move || {
+                while thread_running.load(Ordering::SeqCst) {
+                    let Ok(batch) = events.wait(Some(LISTEN_POLL)) else {
+                        // The device is gone; is_listening() reports it
+                        break;
+                    };
+                    for event in batch {
+                        if sender.send(event).is_err() {
+                            return;
+                        }
+                    }
+                }
+            })
+            .context("Failed to start the driver event thread")?;
+        Ok(Self {
+            events: receiver,
+            running,
+            handle: Some(handle),
+        })
+    }
+
+    /// Next event, if one is waiting
+    pub fn try_next(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Whether the thread is still waiting for events
+    pub fn is_listening(&self) -> bool {
+        self.handle.as_ref().is_some_and(|handle| !handle.is_finished())
+    }
+}
+
+impl Drop for EventListener {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// ============================================================================
+// Permission diagnosis
+// ============================================================================
+
+/// Group the udev rule gives the device nodes
+pub const DEVICE_GROUP: &str = "sunpci";
+
+/// Where the udev rule is installed
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/99-sunpci.rules";
+
+/// Logs searched for security module denials, if readable
+const DENIAL_LOGS: &[&str] = &["/var/log/audit/audit.log", "/var/log/kern.log", "/var/log/syslog"];
+
+/// Most of the end of a log read for denials
+const DENIAL_LOG_TAIL: u64 = 1024 * 1024;
+
+/// Outcome of one `diagnose` check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// Not in the way now, but may be
+    Warning,
+    /// Keeps the frontend from using the card
+    Failed,
+}
+
+/// One check of `diagnose`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiagnosticCheck {
+    /// Short name, e.g. "Device node"
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// What was found
+    pub detail: String,
+    /// Shell commands (or steps) that fix it
+    pub fix: Option<String>,
+}
+
+/// What `diagnose` found about access to the selected card
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnosis {
+    /// Device node of the card
+    pub device: String,
+    pub checks: Vec<DiagnosticCheck>,
+    /// udev rule granting `DEVICE_GROUP` access, for the fix
+    pub udev_rule: String,
+}
+
+impl Diagnosis {
+    /// Whether nothing failed
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Failed)
+    }
+
+    /// Fixes of the checks that did not pass, in order
+    pub fn fixes(&self) -> Vec<&str> {
+        self.checks.iter().filter_map(|c| c.fix.as_deref()).collect()
+    }
+}
+
+/// Text of the udev rule giving `group` read/write access to the cards
+pub fn udev_rule(group: &str) -> String {
+    format!(
+        "# SunPCi device permissions ({})\nKERNEL==\"{}[0-9]*\", GROUP=\"{}\", MODE=\"0660\"\n",
+        UDEV_RULE_PATH, DEVICE_PREFIX, group
+    )
+}
+
+/// Shell commands installing `rule` and applying it to existing nodes
+fn udev_rule_install(rule: &str) -> String {
+    format!(
+        "sudo tee {} <<'EOF'\n{}EOF\nsudo udevadm control --reload-rules\nsudo udevadm trigger",
+        UDEV_RULE_PATH, rule
+    )
+}
+
+/// Check why the selected card might not open: the module, the device
+/// node, its group and mode, the user's groups, and SELinux or AppArmor
+///
+/// Reads only what an unprivileged user can; a log that cannot be read is
+/// not searched for denials.
+pub fn diagnose() -> Diagnosis {
+    let path = device_path(selected_card());
+    let rule = udev_rule(DEVICE_GROUP);
+    let mut checks = Vec::new();
+    let mut check = |name, status, detail: String, fix: Option<String>| {
+        checks.push(DiagnosticCheck { name, status, detail, fix });
+    };
+
+    // The module and its device node
+    let module_loaded = std::path::Path::new("/sys/module/sunpci").exists();
+    if module_loaded {
+        check("Kernel module", CheckStatus::Ok, "sunpci is loaded".to_string(), None);
+    } else {
+        check(
+            "Kernel module",
+            CheckStatus::Failed,
+            "sunpci is not loaded".to_string(),
+            Some("sudo modprobe sunpci\n# or, from a source tree: sudo insmod driver/sunpci.ko".to_string()),
+        );
+    }
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => {
+            check("Device node", CheckStatus::Ok, format!("{} exists", path.display()), None);
+            Some(metadata)
+        }
+        Err(e) => {
+            let fix = module_loaded.then(|| "sudo dmesg | grep -i sunpci".to_string());
+            let detail = if module_loaded {
+                format!("{}: {} (the driver found no card there; see its kernel messages)", path.display(), e)
+            } else {
+                format!("{}: {}", path.display(), e)
+            };
+            check("Device node", CheckStatus::Failed, detail, fix);
+            None
+        }
+    };
+
+    // The node's group and mode, as the udev rule sets them
+    let device_group = nix::unistd::Group::from_name(DEVICE_GROUP).ok().flatten();
+    if let Some(metadata) = &metadata {
+        use std::os::unix::fs::MetadataExt;
+        let group = nix::unistd::Group::from_gid(nix::unistd::Gid::from_raw(metadata.gid()))
+            .ok()
+            .flatten()
+            .map_or_else(|| metadata.gid().to_string(), |g| g.name);
+        let mode = metadata.mode() & 0o777;
+        let detail = format!("owned by group {}, mode {:04o}", group, mode);
+        if group == DEVICE_GROUP && mode & 0o060 == 0o060 {
+            check("Permissions", CheckStatus::Ok, detail, None);
+        } else {
+            let status = if mode & 0o006 == 0o006 { CheckStatus::Warning } else { CheckStatus::Failed };
+            let detail = format!("{}; the udev rule is not applied", detail);
+            check("Permissions", status, detail, Some(udev_rule_install(&rule)));
+        }
+    }
+
+    // The user's membership, in this login and in /etc/group
+    let euid = nix::unistd::geteuid();
+    let user = nix::unistd::User::from_uid(euid).ok().flatten().map(|u| u.name).unwrap_or_default();
+    match &device_group {
+        _ if euid.is_root() => check("Group membership", CheckStatus::Ok, "running as root".to_string(), None),
+        None => check(
+            "Group membership",
+            CheckStatus::Failed,
+            format!("there is no {} group", DEVICE_GROUP),
+            Some(format!(
+                "sudo groupadd {}\nsudo usermod -aG {} $USER\n# then log out and back in",
+                DEVICE_GROUP, DEVICE_GROUP
+            )),
+        ),
+        Some(group) => {
+            let active = nix::unistd::getgroups().is_ok_and(|gids| gids.contains(&group.gid))
+                || nix::unistd::getegid() == group.gid;
+            if active {
+                check("Group membership", CheckStatus::Ok, format!("{} is in {}", user, DEVICE_GROUP), None);
+            } else if group.mem.contains(&user) {
+                check(
+                    "Group membership",
+                    CheckStatus::Failed,
+                    format!("{} was added to {} after logging in", user, DEVICE_GROUP),
+                    Some(format!("# Log out and back in (or run: newgrp {})", DEVICE_GROUP)),
+                );
+            } else {
+                check(
+                    "Group membership",
+                    CheckStatus::Failed,
+                    format!("{} is not in {}", user, DEVICE_GROUP),
+                    Some(format!("sudo usermod -aG {} $USER\n# then log out and back in", DEVICE_GROUP)),
+                );
+            }
+        }
+    }
+
+    // What the kernel says, which covers ACLs and everything above
+    if metadata.is_some() {
+        use nix::unistd::{AccessFlags, access};
+        match access(&path, AccessFlags::R_OK | AccessFlags::W_OK) {
+            Ok(()) => check("Access", CheckStatus::Ok, "read/write access granted".to_string(), None),
+            Err(e) => check("Access", CheckStatus::Failed, format!("cannot open for reading and writing: {}", e), None),
+        }
+    }
+
+    // Security modules
+    let read_trimmed =
+        |path: &str| std::fs::read_to_string(path).ok().map(|s| s.trim_matches(['\0', '\n', ' ']).to_string());
+    let selinux_enforcing = read_trimmed("/sys/fs/selinux/enforce").is_some_and(|s| s == "1");
+    let apparmor = read_trimmed("/sys/module/apparmor/parameters/enabled").is_some_and(|s| s == "Y");
+    let context = read_trimmed("/proc/self/attr/current").unwrap_or_default();
+    let denials: Vec<String> = DENIAL_LOGS
+        .iter()
+        .filter_map(|log| log_tail(log))
+        .flat_map(|text| security_denials(&text))
+        .collect();
+    if !denials.is_empty() {
+        check(
+            "Security modules",
+            CheckStatus::Failed,
+            format!("{} denials of {} logged, the last: {}", denials.len(), DEVICE_PREFIX, denials[denials.len() - 1]),
+            Some(if selinux_enforcing {
+                "sudo ausearch -m avc -c rising-sun | audit2allow -M rising-sun\nsudo semodule -i rising-sun.pp".to_string()
+            } else {
+                format!(
+                    "# Allow {}/{}* rw in the AppArmor profile {}, then:\nsudo systemctl reload apparmor",
+                    DEVICE_DIR, DEVICE_PREFIX, context
+                )
+            }),
+        );
+    } else if selinux_enforcing || (apparmor && !context.is_empty() && context != "unconfined") {
+        let which = if selinux_enforcing { "SELinux is enforcing" } else { "AppArmor confines this process" };
+        check(
+            "Security modules",
+            CheckStatus::Warning,
+            format!("{} (context {}); no denials found in readable logs", which, context),
+            None,
+        );
+    } else {
+        check("Security modules", CheckStatus::Ok, "not confined".to_string(), None);
+    }
+
+    Diagnosis { device: path.display().to_string(), checks, udev_rule: rule }
+}
+
+/// The end of a log, if it can be read
+fn log_tail(path: &str) -> Option<String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(DENIAL_LOG_TAIL))).ok()?;
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).ok()?;
+    Some(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// SELinux AVC and AppArmor denials of the SunPCi device nodes in a log
+fn security_denials(log: &str) -> Vec<String> {
+    log.lines()
+        .filter(|line| line.contains(DEVICE_PREFIX))
+        .filter(|line| {
+            line.contains("avc:  denied") || line.contains("avc: denied") || line.contains("apparmor=\"DENIED\"")
+        })
+        .map(|line| line.trim().to_string())
+        .collect()
 }
 
 /// Helper to set a path in a fixed-size buffer
@@ -398,3 +1304,95 @@ fn set_path(dest: &mut [u8; SUNPCI_MAX_PATH], src: &str) {
     dest[..len].copy_from_slice(&bytes[..len]);
     dest[len] = 0;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw(kind: u32, drive: u32, flags: u32, path: &str) -> DriverEvent {
+        let mut event = DriverEvent { kind, drive, flags, ..Default::default() };
+        set_path(&mut event.path, path);
+        event
+    }
+
+    #[test]
+    fn test_diagnosis_helpers() {
+        let rule = udev_rule(DEVICE_GROUP);
+        assert!(rule.contains("KERNEL==\"sunpci[0-9]*\", GROUP=\"sunpci\", MODE=\"0660\""), "{}", rule);
+        assert!(udev_rule_install(&rule).contains(UDEV_RULE_PATH));
+
+        let log = "\
+type=AVC msg=audit(1.2:3): avc:  denied  { read write } for pid=7 comm=\"rising-sun\" name=\"sunpci0\"
+type=AVC msg=audit(1.2:4): avc:  denied  { read } for pid=7 comm=\"cat\" name=\"shadow\"
+kernel: audit: apparmor=\"DENIED\" operation=\"open\" name=\"/dev/sunpci1\" requested_mask=\"wr\"
+kernel: sunpci: card 0 ready";
+        let denials = security_denials(log);
+        assert_eq!(denials.len(), 2);
+        assert!(denials[1].contains("/dev/sunpci1"));
+
+        let diagnosis = diagnose();
+        assert_eq!(diagnosis.is_ok(), diagnosis.checks.iter().all(|c| c.status != CheckStatus::Failed));
+        assert!(diagnosis.checks.iter().any(|c| c.name == "Device node"));
+    }
+
+    #[test]
+    fn test_check_compatibility() {
+        let v = |major, minor, patch| DriverVersion { major, minor, patch };
+        assert_eq!(check_compatibility(&MIN_DRIVER_VERSION), Compatibility::Ok);
+        assert_eq!(check_compatibility(&MAX_DRIVER_VERSION), Compatibility::Ok);
+        // Any patch of the newest line
+        let patched = v(MAX_DRIVER_VERSION.major, MAX_DRIVER_VERSION.minor, MAX_DRIVER_VERSION.patch + 7);
+        assert!(check_compatibility(&patched).is_ok());
+
+        let next_minor = v(MAX_DRIVER_VERSION.major, MAX_DRIVER_VERSION.minor + 1, 0);
+        assert_eq!(check_compatibility(&next_minor), Compatibility::TooNew { max: MAX_DRIVER_VERSION });
+        let next_major = v(MAX_DRIVER_VERSION.major + 1, 0, 0);
+        assert_eq!(check_compatibility(&next_major), Compatibility::TooNew { max: MAX_DRIVER_VERSION });
+        let message = check_compatibility(&next_minor).message(&next_minor).unwrap();
+        assert!(message.contains(&next_minor.to_string()), "{}", message);
+
+        // The first release, before most of the ioctls
+        let old = v(0, 1, 0);
+        assert!(old < MIN_DRIVER_VERSION);
+        assert_eq!(check_compatibility(&old), Compatibility::TooOld { min: MIN_DRIVER_VERSION });
+        assert_eq!(Compatibility::Ok.message(&old), None);
+    }
+
+    #[test]
+    fn test_event_from_raw() {
+        use crate::ioctl::{event_drive, event_flags};
+
+        assert_eq!(
+            Event::from_raw(&raw(event_type::MEDIA_CHANGED, event_drive::CDROM, event_flags::MEDIA_PRESENT, "/tmp/a.iso")),
+            Some(Event::MediaChanged { drive: event_drive::CDROM, path: Some("/tmp/a.iso".to_string()) })
+        );
+        // Ejected: no path even if the driver left one
+        assert_eq!(
+            Event::from_raw(&raw(event_type::MEDIA_CHANGED, event_drive::FLOPPY_B, 0, "/tmp/b.img")),
+            Some(Event::MediaChanged { drive: event_drive::FLOPPY_B, path: None })
+        );
+        assert_eq!(
+            Event::from_raw(&raw(event_type::SESSION_CHANGED, 0, 2, "")),
+            Some(Event::SessionChanged { state: 2 })
+        );
+        assert_eq!(Event::from_raw(&raw(event_type::DISPLAY_CHANGED, 0, 0, "")), Some(Event::DisplayChanged));
+        assert_eq!(
+            Event::from_raw(&raw(event_type::PALETTE_CHANGED, 0, 7, "")),
+            Some(Event::PaletteChanged { generation: 7 })
+        );
+        // Types from a newer driver are skipped
+        assert_eq!(Event::from_raw(&raw(event_type::POWER_OFF, 0, 0, "")), Some(Event::PowerOff));
+        assert_eq!(Event::from_raw(&raw(99, 0, 0, "")), None);
+    }
+
+    #[test]
+    fn test_device_names() {
+        assert_eq!(device_path(1), PathBuf::from("/dev/sunpci1"));
+        assert_eq!(parse_device_name("sunpci0"), Some(0));
+        assert_eq!(parse_device_name("sunpci12"), Some(12));
+        assert_eq!(parse_device_name("sunpci"), None);
+        assert_eq!(parse_device_name("sunpci+1"), None);
+        assert_eq!(parse_device_name("sunpci0p1"), None);
+        assert_eq!(parse_device_name("sda"), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Lowering other host applications' volume while the guest plays audio.
+//!
+//! Streams are found and adjusted with `pactl`, which speaks to PulseAudio
+//! and to PipeWire through pipewire-pulse alike. `HostDucker::duck` turns
+//! every playback stream but this process's own down to a fraction of its
+//! volume; `restore` (or dropping the ducker) puts back the volumes it
+//! changed. A stream whose volume was changed meanwhile, by the user or
+//! the application, is left as it is.
+
+use std::process::{self, Command};
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// Errors from adjusting host volumes
+#[derive(Debug, Error)]
+pub enum DuckError {
+    #[error("failed to run pactl: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    #[error("'pactl {args}' failed: {stderr}")]
+    Command { args: String, stderr: String },
+
+    #[error("unexpected pactl output: {0}")]
+    Parse(String),
+}
+
+/// A playback stream on the host's sound server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SinkInput {
+    /// Stream index
+    pub index: u32,
+    /// Process playing the stream, if it said
+    pub pid: Option<u32>,
+    /// Application name, if it said
+    pub application: Option<String>,
+    /// Volume of each channel, in channel map order
+    pub volumes: Vec<u32>,
+}
+
+/// A stream this ducker turned down
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Ducked {
+    index: u32,
+    original: Vec<u32>,
+    ducked: Vec<u32>,
+}
+
+/// Ducks other applications' playback streams
+#[derive(Debug)]
+pub struct HostDucker {
+    /// Volume ducked streams are turned down to, in percent of their own
+    level: u32,
+    ducked: Vec<Ducked>,
+}
+
+impl HostDucker {
+    /// Duck to `level` percent (clamped to 0-100) of each stream's volume
+    pub fn new(level: u32) -> Self {
+        Self {
+            level: level.min(100),
+            ducked: Vec::new(),
+        }
+    }
+
+    /// Whether streams are currently ducked
+    pub fn is_ducked(&self) -> bool {
+        !self.ducked.is_empty()
+    }
+
+    /// Turn down every other application's streams; returns how many
+    pub fn duck(&mut self) -> Result<usize, DuckError> {
+        if self.is_ducked() {
+            return Ok(self.ducked.len());
+        }
+        let own_pid = process::id();
+        for input in list_sink_inputs()? {
+            if input.pid == Some(own_pid) || input.volumes.is_empty() {
+                continue;
+            }
+            let ducked = duck_volumes(&input.volumes, self.level);
+            // The stream may have ended since it was listed
+            if set_volume(input.index, &ducked).is_ok() {
+                self.ducked.push(Ducked {
+                    index: input.index,
+                    original: input.volumes,
+                    ducked,
+                });
+            }
+        }
+        Ok(self.ducked.len())
+    }
+
+    /// Put back the volumes of the streams still at their ducked volume;
+    /// returns how many
+    pub fn restore(&mut self) -> Result<usize, DuckError> {
+        if !self.is_ducked() {
+            return Ok(0);
+        }
+        let current = list_sink_inputs()?;
+        let mut restored = 0;
+        for ducked in self.ducked.drain(..) {
+            let unchanged = current
+                .iter()
+                .any(|input| input.index == ducked.index && input.volumes == ducked.ducked);
+            if unchanged && set_volume(ducked.index, &ducked.original).is_ok() {
+                restored += 1;
+            }
+        }
+        Ok(restored)
+    }
+}
+
+impl Drop for HostDucker {
+    fn drop(&mut self) {
+        let _ = self.restore();
+    }
+}
+
+/// Volumes turned down to `level` percent
+fn duck_volumes(volumes: &[u32], level: u32) -> Vec<u32> {
+    volumes
+        .iter()
+        .map(|&v| (u64::from(v) * u64::from(level) / 100) as u32)
+        .collect()
+}
+
+/// List the host's playback streams
+pub fn list_sink_inputs() -> Result<Vec<SinkInput>, DuckError> {
+    let json = run_pactl(&["-f", "json", "list", "sink-inputs"])?;
+    parse_sink_inputs(&json)
+}
+
+/// Parse `pactl -f json list sink-inputs`
+fn parse_sink_inputs(json: &str) -> Result<Vec<SinkInput>, DuckError> {
+    let value: Value = serde_json::from_str(json).map_err(|e| DuckError::Parse(e.to_string()))?;
+    let inputs = value
+        .as_array()
+        .ok_or_else(|| DuckError::Parse("expected a list of sink inputs".to_string()))?;
+
+    inputs
+        .iter()
+        .map(|input| {
+            let index = input["index"]
+                .as_u64()
+                .ok_or_else(|| DuckError::Parse("sink input without an index".to_string()))?;
+            let properties = &input["properties"];
+            // Channel volumes are keyed by position; the channel map gives their order
+            let volumes = input["channel_map"]
+                .as_str()
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(|channel| input["volume"][channel.trim()]["value"].as_u64())
+                .map(|v| v.min(u64::from(u32::MAX)) as u32)
+                .collect();
+            Ok(SinkInput {
+                index: index as u32,
+                pid: properties["application.process.id"]
+                    .as_str()
+                    .and_then(|pid| pid.parse().ok()),
+                application: properties["application.name"].as_str().map(str::to_string),
+                volumes,
+            })
+        })
+        .collect()
+}
+
+/// Set a stream's channel volumes
+fn set_volume(index: u32, volumes: &[u32]) -> Result<(), DuckError> {
+    let mut args = vec!["set-sink-input-volume".to_string(), index.to_string()];
+    args.extend(volumes.iter().map(u32::to_string));
+    run_pactl(&args).map(|_| ())
+}
+
+/// Run `pactl` with the given arguments, returning its output
+fn run_pactl<S: AsRef<str>>(args: &[S]) -> Result<String, DuckError> {
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let output = Command::new("pactl").args(&args).output()?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(DuckError::Command {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_sink_inputs() {
+        let json = r#"[
+            {
+                "index": 42,
+                "channel_map": "front-left,front-right",
+                "volume": {
+                    "front-right": {"value": 32768, "value_percent": "50%"},
+                    "front-left": {"value": 65536, "value_percent": "100%"}
+                },
+                "properties": {"application.name": "Music", "application.process.id": "1234"}
+            },
+            {"index": 43, "channel_map": "mono", "volume": {"mono": {"value": 1000}}, "properties": {}}
+        ]"#;
+        let inputs = parse_sink_inputs(json).unwrap();
+        assert_eq!(
+            inputs[0],
+            SinkInput {
+                index: 42,
+                pid: Some(1234),
+                application: Some("Music".to_string()),
+                volumes: vec![65536, 32768],
+            }
+        );
+        assert_eq!(inputs[1].pid, None);
+        assert_eq!(inputs[1].volumes, [1000]);
+
+        assert!(parse_sink_inputs("{}").is_err());
+        assert!(parse_sink_inputs(r#"[{"channel_map": "mono"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_duck_volumes() {
+        assert_eq!(duck_volumes(&[65536, 32768], 30), [19660, 9830]);
+        assert_eq!(duck_volumes(&[65536], 0), [0]);
+        assert_eq!(duck_volumes(&[65536], 100), [65536]);
+    }
+}
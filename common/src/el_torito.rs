@@ -0,0 +1,287 @@
+//! El Torito boot catalog parsing.
+//!
+//! A bootable CD carries a boot record volume descriptor right after the
+//! ISO 9660 primary volume descriptor. It points at the boot catalog, whose
+//! validation entry names the platform and whose initial entry describes
+//! the image the BIOS loads. The SunPCi BIOS only boots x86 images.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Sector holding the boot record volume descriptor
+pub const BOOT_RECORD_SECTOR: u32 = 17;
+
+/// CD-ROM data sector size
+const SECTOR_SIZE: usize = 2048;
+
+/// Boot system identifier of an El Torito boot record
+const EL_TORITO_ID: &[u8] = b"EL TORITO SPECIFICATION";
+
+/// Boot indicator of a bootable catalog entry
+const BOOTABLE: u8 = 0x88;
+
+/// Platform a boot catalog is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    X86,
+    PowerPc,
+    Mac,
+    Efi,
+    Other(u8),
+}
+
+impl Platform {
+    fn from_id(id: u8) -> Self {
+        match id {
+            0x00 => Self::X86,
+            0x01 => Self::PowerPc,
+            0x02 => Self::Mac,
+            0xEF => Self::Efi,
+            other => Self::Other(other),
+        }
+    }
+
+    /// Human-readable platform name
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::X86 => "x86",
+            Self::PowerPc => "PowerPC",
+            Self::Mac => "Mac",
+            Self::Efi => "EFI",
+            Self::Other(_) => "unknown",
+        }
+    }
+}
+
+/// How the BIOS presents the boot image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMedia {
+    /// Loaded into memory and run directly
+    NoEmulation,
+    /// Emulates a 1.2 MB floppy as drive A:
+    Floppy1200,
+    /// Emulates a 1.44 MB floppy as drive A:
+    Floppy1440,
+    /// Emulates a 2.88 MB floppy as drive A:
+    Floppy2880,
+    /// Emulates a hard disk as drive C:
+    HardDisk,
+}
+
+impl BootMedia {
+    fn from_id(id: u8) -> Option<Self> {
+        match id & 0x0F {
+            0 => Some(Self::NoEmulation),
+            1 => Some(Self::Floppy1200),
+            2 => Some(Self::Floppy1440),
+            3 => Some(Self::Floppy2880),
+            4 => Some(Self::HardDisk),
+            _ => None,
+        }
+    }
+
+    /// Human-readable emulation mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NoEmulation => "no emulation",
+            Self::Floppy1200 => "1.2 MB floppy emulation",
+            Self::Floppy1440 => "1.44 MB floppy emulation",
+            Self::Floppy2880 => "2.88 MB floppy emulation",
+            Self::HardDisk => "hard disk emulation",
+        }
+    }
+}
+
+/// Initial (default) entry of a boot catalog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootEntry {
+    /// Whether the entry is marked bootable
+    pub bootable: bool,
+    pub media: BootMedia,
+    /// Real-mode segment the image is loaded at (0 means the 0x07C0 default)
+    pub load_segment: u16,
+    /// Partition type byte for hard disk emulation
+    pub system_type: u8,
+    /// Number of 512-byte sectors loaded
+    pub sector_count: u16,
+    /// CD sector the image starts at
+    pub load_rba: u32,
+}
+
+/// A parsed boot catalog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootCatalog {
+    /// CD sector the catalog is in
+    pub catalog_sector: u32,
+    pub platform: Platform,
+    /// Manufacturer/developer id string from the validation entry
+    pub id: String,
+    pub initial: BootEntry,
+}
+
+impl BootCatalog {
+    /// Whether the SunPCi BIOS can boot from the disc
+    pub fn is_bootable(&self) -> bool {
+        self.platform == Platform::X86 && self.initial.bootable
+    }
+}
+
+/// Read the boot catalog of a CD through `read_sector`
+///
+/// `read_sector(lba, buf)` fills `buf` with the 2048-byte user data of a
+/// sector. Returns `None` for a disc without an El Torito boot record.
+pub fn read_catalog(
+    mut read_sector: impl FnMut(u32, &mut [u8]) -> io::Result<()>,
+) -> io::Result<Option<BootCatalog>> {
+    let mut sector = vec![0u8; SECTOR_SIZE];
+    if read_sector(BOOT_RECORD_SECTOR, &mut sector).is_err() {
+        return Ok(None);
+    }
+    let Some(catalog_sector) = boot_record_catalog(&sector) else {
+        return Ok(None);
+    };
+
+    read_sector(catalog_sector, &mut sector)?;
+    parse_catalog(catalog_sector, &sector).map(Some)
+}
+
+/// Read the boot catalog of an ISO image
+pub fn read_iso(path: &Path) -> io::Result<Option<BootCatalog>> {
+    let mut file = File::open(path)?;
+    read_catalog(|lba, buf| {
+        file.seek(SeekFrom::Start(u64::from(lba) * SECTOR_SIZE as u64))?;
+        file.read_exact(buf)
+    })
+}
+
+/// Check that the SunPCi can boot an ISO image
+///
+/// The error says why not, for showing next to the boot order.
+pub fn check_bootable(path: &Path) -> Result<BootCatalog, String> {
+    let catalog = read_iso(path)
+        .map_err(|e| format!("cannot read boot catalog of {}: {}", path.display(), e))?
+        .ok_or_else(|| format!("{} has no El Torito boot record", path.display()))?;
+    if catalog.platform != Platform::X86 {
+        return Err(format!("{} boots {} systems, not x86", path.display(), catalog.platform.name()));
+    }
+    if !catalog.initial.bootable {
+        return Err(format!("the boot image of {} is not marked bootable", path.display()));
+    }
+    Ok(catalog)
+}
+
+/// Catalog sector from a boot record volume descriptor
+fn boot_record_catalog(descriptor: &[u8]) -> Option<u32> {
+    if descriptor.len() < 0x4B || &descriptor[..7] != b"\x00CD001\x01" {
+        return None;
+    }
+    let system_id = &descriptor[7..39];
+    if !system_id.starts_with(EL_TORITO_ID) || system_id[EL_TORITO_ID.len()..].iter().any(|&b| b != 0) {
+        return None;
+    }
+    Some(u32::from_le_bytes(descriptor[0x47..0x4B].try_into().unwrap()))
+}
+
+/// Parse the validation and initial entries at the start of a catalog sector
+fn parse_catalog(catalog_sector: u32, data: &[u8]) -> io::Result<BootCatalog> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    let validation = &data[..32];
+    if validation[0] != 0x01 || validation[30..32] != [0x55, 0xAA] {
+        return Err(invalid("boot catalog has no validation entry"));
+    }
+    // The 16-bit words of the validation entry sum to zero
+    let sum = validation
+        .chunks_exact(2)
+        .fold(0u16, |sum, word| sum.wrapping_add(u16::from_le_bytes([word[0], word[1]])));
+    if sum != 0 {
+        return Err(invalid("boot catalog checksum mismatch"));
+    }
+
+    let entry = &data[32..64];
+    let media = BootMedia::from_id(entry[1]).ok_or_else(|| invalid("unknown boot media type"))?;
+    let id = String::from_utf8_lossy(&validation[4..28])
+        .trim_end_matches(['\0', ' '])
+        .to_string();
+
+    Ok(BootCatalog {
+        catalog_sector,
+        platform: Platform::from_id(validation[1]),
+        id,
+        initial: BootEntry {
+            bootable: entry[0] == BOOTABLE,
+            media,
+            load_segment: u16::from_le_bytes([entry[2], entry[3]]),
+            system_type: entry[4],
+            sector_count: u16::from_le_bytes([entry[6], entry[7]]),
+            load_rba: u32::from_le_bytes(entry[8..12].try_into().unwrap()),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 32-sector image with a boot record and a catalog in sector 20
+    fn bootable_image(platform: u8, indicator: u8) -> Vec<u8> {
+        let mut image = vec![0u8; 32 * SECTOR_SIZE];
+
+        let record = &mut image[17 * SECTOR_SIZE..18 * SECTOR_SIZE];
+        record[..7].copy_from_slice(b"\x00CD001\x01");
+        record[7..7 + EL_TORITO_ID.len()].copy_from_slice(EL_TORITO_ID);
+        record[0x47..0x4B].copy_from_slice(&20u32.to_le_bytes());
+
+        let catalog = &mut image[20 * SECTOR_SIZE..21 * SECTOR_SIZE];
+        catalog[0] = 0x01;
+        catalog[1] = platform;
+        catalog[4..10].copy_from_slice(b"RISING");
+        catalog[30] = 0x55;
+        catalog[31] = 0xAA;
+        let sum = catalog[..32]
+            .chunks_exact(2)
+            .fold(0u16, |sum, word| sum.wrapping_add(u16::from_le_bytes([word[0], word[1]])));
+        catalog[28..30].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+
+        catalog[32] = indicator;
+        catalog[33] = 2;
+        catalog[38..40].copy_from_slice(&1u16.to_le_bytes());
+        catalog[40..44].copy_from_slice(&21u32.to_le_bytes());
+        image
+    }
+
+    fn read(image: &[u8]) -> io::Result<Option<BootCatalog>> {
+        read_catalog(|lba, buf| {
+            let start = lba as usize * SECTOR_SIZE;
+            let sector = image
+                .get(start..start + SECTOR_SIZE)
+                .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+            buf.copy_from_slice(sector);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_read_catalog() {
+        let catalog = read(&bootable_image(0x00, BOOTABLE)).unwrap().unwrap();
+        assert!(catalog.is_bootable());
+        assert_eq!(catalog.catalog_sector, 20);
+        assert_eq!(catalog.id, "RISING");
+        assert_eq!(catalog.initial.media, BootMedia::Floppy1440);
+        assert_eq!(catalog.initial.sector_count, 1);
+        assert_eq!(catalog.initial.load_rba, 21);
+
+        // Present but not for the SunPCi
+        assert!(!read(&bootable_image(0xEF, BOOTABLE)).unwrap().unwrap().is_bootable());
+        assert!(!read(&bootable_image(0x00, 0x00)).unwrap().unwrap().is_bootable());
+
+        // A plain data disc, and one too short to have a boot record
+        assert_eq!(read(&vec![0u8; 32 * SECTOR_SIZE]).unwrap(), None);
+        assert_eq!(read(&vec![0u8; 4 * SECTOR_SIZE]).unwrap(), None);
+
+        let mut corrupt = bootable_image(0x00, BOOTABLE);
+        corrupt[20 * SECTOR_SIZE + 4] ^= 0xFF;
+        assert!(read(&corrupt).is_err());
+    }
+}
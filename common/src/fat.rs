@@ -0,0 +1,274 @@
+//! FAT12 and FAT16 volume layout.
+//!
+//! Enough of the FAT filesystem to work on the volumes inside disk images
+//! without mounting them: the BIOS parameter block (BPB) in the boot
+//! sector, where the FATs, root directory and data area lie, and the
+//! entries of the FAT itself. FAT32 volumes, which have no fixed root
+//! directory and a 32-bit FAT, are not handled.
+
+use crate::disk_image::SECTOR_SIZE;
+
+/// First cluster of the data area; entries 0 and 1 of the FAT are reserved
+pub const FIRST_CLUSTER: u32 = 2;
+
+/// Bytes in a directory entry
+pub const DIR_ENTRY_SIZE: u32 = 32;
+
+/// Width of the FAT entries, which follows from the number of clusters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat12,
+    Fat16,
+}
+
+impl FatType {
+    /// The type a volume with `clusters` data clusters has, None for FAT32
+    pub fn for_clusters(clusters: u32) -> Option<Self> {
+        if clusters <= Self::Fat12.max_clusters() {
+            Some(Self::Fat12)
+        } else if clusters <= Self::Fat16.max_clusters() {
+            Some(Self::Fat16)
+        } else {
+            None
+        }
+    }
+
+    /// Most data clusters a volume of this type can have
+    pub fn max_clusters(self) -> u32 {
+        match self {
+            Self::Fat12 => 4084,
+            Self::Fat16 => 65524,
+        }
+    }
+
+    /// Name as in the boot sector's file system type field
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fat12 => "FAT12",
+            Self::Fat16 => "FAT16",
+        }
+    }
+
+    /// Entries at or above this end a cluster chain
+    pub fn end_of_chain(self) -> u32 {
+        match self {
+            Self::Fat12 => 0xFF8,
+            Self::Fat16 => 0xFFF8,
+        }
+    }
+
+    /// Entry marking a bad cluster
+    pub fn bad_cluster(self) -> u32 {
+        match self {
+            Self::Fat12 => 0xFF7,
+            Self::Fat16 => 0xFFF7,
+        }
+    }
+
+    /// Bytes of FAT needed for `clusters` data clusters
+    pub fn fat_bytes(self, clusters: u32) -> u64 {
+        let entries = (clusters + FIRST_CLUSTER) as u64;
+        match self {
+            Self::Fat12 => (entries * 3).div_ceil(2),
+            Self::Fat16 => entries * 2,
+        }
+    }
+}
+
+/// The BIOS parameter block of a FAT12/FAT16 boot sector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bpb {
+    pub bytes_per_sector: u16,
+    pub sectors_per_cluster: u8,
+    pub reserved_sectors: u16,
+    pub fats: u8,
+    pub root_entries: u16,
+    /// Sectors of the volume (from the 16- or 32-bit field)
+    pub total_sectors: u32,
+    pub media: u8,
+    pub sectors_per_fat: u16,
+    pub sectors_per_track: u16,
+    pub heads: u16,
+    /// Sectors before the volume on the disk
+    pub hidden_sectors: u32,
+}
+
+impl Bpb {
+    /// Read the BPB from a boot sector; None if it is not a FAT12/FAT16 one
+    pub fn parse(boot: &[u8]) -> Option<Self> {
+        if boot.len() < SECTOR_SIZE as usize || boot[510] != 0x55 || boot[511] != 0xAA {
+            return None;
+        }
+        let le16 = |at: usize| u16::from_le_bytes([boot[at], boot[at + 1]]);
+        let le32 = |at: usize| u32::from_le_bytes(boot[at..at + 4].try_into().unwrap());
+        let total_sectors = match le16(19) {
+            0 => le32(32),
+            n => n as u32,
+        };
+        let bpb = Self {
+            bytes_per_sector: le16(11),
+            sectors_per_cluster: boot[13],
+            reserved_sectors: le16(14),
+            fats: boot[16],
+            root_entries: le16(17),
+            total_sectors,
+            media: boot[21],
+            sectors_per_fat: le16(22),
+            sectors_per_track: le16(24),
+            heads: le16(26),
+            hidden_sectors: le32(28),
+        };
+        let valid = bpb.bytes_per_sector as u32 == SECTOR_SIZE
+            && bpb.sectors_per_cluster.is_power_of_two()
+            && bpb.reserved_sectors > 0
+            && bpb.fats > 0
+            && bpb.root_entries > 0
+            && bpb.sectors_per_fat > 0
+            && bpb.total_sectors > bpb.data_start();
+        valid.then_some(bpb)
+    }
+
+    /// Store the BPB in a boot sector, leaving the rest of it alone
+    pub fn write(&self, boot: &mut [u8]) {
+        boot[11..13].copy_from_slice(&self.bytes_per_sector.to_le_bytes());
+        boot[13] = self.sectors_per_cluster;
+        boot[14..16].copy_from_slice(&self.reserved_sectors.to_le_bytes());
+        boot[16] = self.fats;
+        boot[17..19].copy_from_slice(&self.root_entries.to_le_bytes());
+        let (short, long) = match u16::try_from(self.total_sectors) {
+            Ok(short) => (short, 0),
+            Err(_) => (0, self.total_sectors),
+        };
+        boot[19..21].copy_from_slice(&short.to_le_bytes());
+        boot[21] = self.media;
+        boot[22..24].copy_from_slice(&self.sectors_per_fat.to_le_bytes());
+        boot[24..26].copy_from_slice(&self.sectors_per_track.to_le_bytes());
+        boot[26..28].copy_from_slice(&self.heads.to_le_bytes());
+        boot[28..32].copy_from_slice(&self.hidden_sectors.to_le_bytes());
+        boot[32..36].copy_from_slice(&long.to_le_bytes());
+    }
+
+    /// Sectors taken by the root directory
+    pub fn root_dir_sectors(&self) -> u32 {
+        (self.root_entries as u32 * DIR_ENTRY_SIZE).div_ceil(SECTOR_SIZE)
+    }
+
+    /// First sector of FAT number `n`, from the start of the volume
+    pub fn fat_start(&self, n: u8) -> u32 {
+        self.reserved_sectors as u32 + n as u32 * self.sectors_per_fat as u32
+    }
+
+    /// First sector of the root directory
+    pub fn root_dir_start(&self) -> u32 {
+        self.fat_start(self.fats)
+    }
+
+    /// First sector of the data area (cluster 2)
+    pub fn data_start(&self) -> u32 {
+        self.root_dir_start() + self.root_dir_sectors()
+    }
+
+    /// Data clusters in the volume
+    pub fn cluster_count(&self) -> u32 {
+        self.total_sectors.saturating_sub(self.data_start()) / self.sectors_per_cluster as u32
+    }
+
+    /// FAT12 or FAT16, None if the volume is too big for either
+    pub fn fat_type(&self) -> Option<FatType> {
+        FatType::for_clusters(self.cluster_count())
+    }
+
+    /// First sector of `cluster`, from the start of the volume
+    pub fn cluster_start(&self, cluster: u32) -> u32 {
+        self.data_start() + (cluster - FIRST_CLUSTER) * self.sectors_per_cluster as u32
+    }
+}
+
+/// Entry `cluster` of a FAT
+pub fn fat_entry(fat: &[u8], fat_type: FatType, cluster: u32) -> u32 {
+    let n = cluster as usize;
+    match fat_type {
+        FatType::Fat12 => {
+            let at = n + n / 2;
+            let pair = u16::from_le_bytes([fat[at], fat[at + 1]]) as u32;
+            if n.is_multiple_of(2) { pair & 0xFFF } else { pair >> 4 }
+        }
+        FatType::Fat16 => u16::from_le_bytes([fat[n * 2], fat[n * 2 + 1]]) as u32,
+    }
+}
+
+/// Set entry `cluster` of a FAT to `value`
+pub fn set_fat_entry(fat: &mut [u8], fat_type: FatType, cluster: u32, value: u32) {
+    let n = cluster as usize;
+    match fat_type {
+        FatType::Fat12 => {
+            let at = n + n / 2;
+            let pair = u16::from_le_bytes([fat[at], fat[at + 1]]);
+            let value = (value & 0xFFF) as u16;
+            let pair = if n.is_multiple_of(2) {
+                (pair & 0xF000) | value
+            } else {
+                (pair & 0x000F) | (value << 4)
+            };
+            fat[at..at + 2].copy_from_slice(&pair.to_le_bytes());
+        }
+        FatType::Fat16 => fat[n * 2..n * 2 + 2].copy_from_slice(&(value as u16).to_le_bytes()),
+    }
+}
+
+/// Highest cluster in use in a FAT, None if the volume is empty
+pub fn last_used_cluster(fat: &[u8], fat_type: FatType, clusters: u32) -> Option<u32> {
+    (FIRST_CLUSTER..clusters + FIRST_CLUSTER)
+        .rev()
+        .find(|&cluster| fat_entry(fat, fat_type, cluster) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bpb_round_trip() {
+        let mut boot = [0u8; 512];
+        boot[510] = 0x55;
+        boot[511] = 0xAA;
+        let bpb = Bpb {
+            bytes_per_sector: 512,
+            sectors_per_cluster: 4,
+            reserved_sectors: 1,
+            fats: 2,
+            root_entries: 512,
+            total_sectors: 130_000,
+            media: 0xF8,
+            sectors_per_fat: 128,
+            sectors_per_track: 63,
+            heads: 16,
+            hidden_sectors: 63,
+        };
+        bpb.write(&mut boot);
+        assert_eq!(&boot[19..21], &[0, 0]);
+        assert_eq!(Bpb::parse(&boot), Some(bpb));
+        assert_eq!(bpb.root_dir_start(), 257);
+        assert_eq!(bpb.data_start(), 289);
+        assert_eq!(bpb.cluster_count(), (130_000 - 289) / 4);
+        assert_eq!(bpb.fat_type(), Some(FatType::Fat16));
+
+        boot[510] = 0;
+        assert_eq!(Bpb::parse(&boot), None);
+    }
+
+    #[test]
+    fn test_fat12_entries() {
+        let mut fat = vec![0u8; FatType::Fat12.fat_bytes(10) as usize];
+        assert_eq!(fat.len(), 18);
+        set_fat_entry(&mut fat, FatType::Fat12, 2, 3);
+        set_fat_entry(&mut fat, FatType::Fat12, 3, 0xFFF);
+        set_fat_entry(&mut fat, FatType::Fat12, 6, 0xABC);
+        assert_eq!(fat_entry(&fat, FatType::Fat12, 2), 3);
+        assert_eq!(fat_entry(&fat, FatType::Fat12, 3), 0xFFF);
+        assert_eq!(fat_entry(&fat, FatType::Fat12, 4), 0);
+        assert_eq!(fat_entry(&fat, FatType::Fat12, 6), 0xABC);
+        assert_eq!(last_used_cluster(&fat, FatType::Fat12, 10), Some(6));
+        assert_eq!(last_used_cluster(&[0u8; 18], FatType::Fat12, 10), None);
+    }
+}
@@ -0,0 +1,191 @@
+//! FAT timestamps on mapped drives.
+//!
+//! DOS and Windows 9x keep file times in local time with no zone, while
+//! the host keeps them in UTC. The driver's FSD converts between the two
+//! with a fixed offset per mapping, which the frontend works out from the
+//! mapping's `TimestampPolicy`. For `Local` that is the host's current UTC
+//! offset, so the frontend re-sends it when daylight saving time starts or
+//! ends; like Windows on FAT, files from the other half of the year are
+//! then shown an hour off.
+//!
+//! `to_dos` and `from_dos` do the same conversion as the driver.
+
+use nix::libc;
+
+use crate::config::TimestampPolicy;
+
+/// Westernmost UTC offset accepted, in minutes
+pub const MIN_OFFSET_MINUTES: i32 = -12 * 60;
+/// Easternmost UTC offset accepted, in minutes
+pub const MAX_OFFSET_MINUTES: i32 = 14 * 60;
+
+/// First and last second FAT can represent, as local times
+const DOS_EPOCH: i64 = 315_532_800; // 1980-01-01 00:00:00
+const DOS_END: i64 = 4_354_819_198; // 2107-12-31 23:59:58
+
+/// The host's UTC offset at `unix_secs`, in minutes
+pub fn local_offset_minutes(unix_secs: i64) -> i32 {
+    let time = unix_secs as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    // localtime_r fills tm_gmtoff with the offset in effect at that time
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return 0;
+    }
+    (tm.tm_gmtoff / 60) as i32
+}
+
+/// Offset in minutes the driver applies for `policy` at `unix_secs`,
+/// clamped to the accepted range
+pub fn offset_minutes(policy: TimestampPolicy, unix_secs: i64) -> i32 {
+    let offset = match policy {
+        TimestampPolicy::Utc => 0,
+        TimestampPolicy::Local => local_offset_minutes(unix_secs),
+        TimestampPolicy::Offset(minutes) => minutes,
+    };
+    offset.clamp(MIN_OFFSET_MINUTES, MAX_OFFSET_MINUTES)
+}
+
+/// Parse a policy as written in the UI: "utc", "local" or an offset
+/// such as "+05:30" or "-0800"
+pub fn parse_policy(text: &str) -> Option<TimestampPolicy> {
+    let text = text.trim();
+    match text.to_ascii_lowercase().as_str() {
+        "utc" => return Some(TimestampPolicy::Utc),
+        "local" => return Some(TimestampPolicy::Local),
+        _ => {}
+    }
+
+    let (sign, rest) = match text.as_bytes().first()? {
+        b'+' => (1, &text[1..]),
+        b'-' => (-1, &text[1..]),
+        _ => return None,
+    };
+    let digits: String = rest.chars().filter(|&c| c != ':').collect();
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    if minutes >= 60 {
+        return None;
+    }
+    let offset = sign * (hours * 60 + minutes);
+    (MIN_OFFSET_MINUTES..=MAX_OFFSET_MINUTES)
+        .contains(&offset)
+        .then_some(TimestampPolicy::Offset(offset))
+}
+
+/// A policy as `parse_policy` reads it
+pub fn policy_name(policy: TimestampPolicy) -> String {
+    match policy {
+        TimestampPolicy::Utc => "utc".to_string(),
+        TimestampPolicy::Local => "local".to_string(),
+        TimestampPolicy::Offset(minutes) => {
+            let sign = if minutes < 0 { '-' } else { '+' };
+            let minutes = minutes.unsigned_abs();
+            format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+        }
+    }
+}
+
+/// FAT date and time of a host timestamp, seen `offset` minutes east of
+/// UTC; times outside 1980-2107 are clamped to the ends of that range
+pub fn to_dos(unix_secs: i64, offset: i32) -> (u16, u16) {
+    let local = (unix_secs + i64::from(offset) * 60).clamp(DOS_EPOCH, DOS_END);
+    let (year, month, day) = civil_from_days(local.div_euclid(86_400));
+    let secs = local.rem_euclid(86_400);
+
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+    let time = ((secs / 3600) << 11) | ((secs / 60 % 60) << 5) | (secs % 60 / 2);
+    (date as u16, time as u16)
+}
+
+/// Host timestamp of a FAT date and time set `offset` minutes east of UTC
+pub fn from_dos(date: u16, time: u16, offset: i32) -> i64 {
+    let year = i64::from(date >> 9) + 1980;
+    let month = i64::from((date >> 5) & 0x0F).clamp(1, 12);
+    let day = i64::from(date & 0x1F).max(1);
+    let secs = i64::from(time >> 11) * 3600
+        + i64::from((time >> 5) & 0x3F) * 60
+        + i64::from(time & 0x1F) * 2;
+
+    days_from_civil(year, month, day) * 86_400 + secs - i64::from(offset) * 60
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Proleptic Gregorian date of a count of days since 1970-01-01
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dos_conversion() {
+        // 2000-01-01 00:00:00 UTC
+        let y2k = 946_684_800;
+        let midnight = (20 << 9) | (1 << 5) | 1;
+        assert_eq!(to_dos(y2k, 0), (midnight, 0));
+        assert_eq!(from_dos(midnight, 0, 0), y2k);
+
+        // An hour east it is 01:00; an hour west, the day before
+        assert_eq!(to_dos(y2k, 60), (midnight, 1 << 11));
+        let new_years_eve = (19 << 9) | (12 << 5) | 31;
+        assert_eq!(to_dos(y2k, -60), (new_years_eve, 23 << 11));
+
+        // Both directions agree, to FAT's two-second resolution
+        for (secs, offset) in [(1_700_000_001, 330), (1_234_567_890, -480), (951_782_400, 0)] {
+            let (date, time) = to_dos(secs, offset);
+            assert_eq!(from_dos(date, time, offset), secs & !1);
+        }
+
+        // Outside FAT's range: 1980-01-01, and 2107
+        assert_eq!(to_dos(0, 0), ((1 << 5) | 1, 0));
+        assert_eq!(to_dos(i64::from(u32::MAX) * 2, 0).0 >> 9, 127);
+    }
+
+    #[test]
+    fn test_policy_names() {
+        assert_eq!(parse_policy("UTC"), Some(TimestampPolicy::Utc));
+        assert_eq!(parse_policy("local"), Some(TimestampPolicy::Local));
+        assert_eq!(parse_policy("+05:30"), Some(TimestampPolicy::Offset(330)));
+        assert_eq!(parse_policy("-0800"), Some(TimestampPolicy::Offset(-480)));
+        assert_eq!(parse_policy("+15:00"), None);
+        assert_eq!(parse_policy("+01:75"), None);
+        assert_eq!(parse_policy("0100"), None);
+
+        for policy in [TimestampPolicy::Utc, TimestampPolicy::Local, TimestampPolicy::Offset(-570)] {
+            assert_eq!(parse_policy(&policy_name(policy)), Some(policy));
+        }
+    }
+
+    #[test]
+    fn test_offset_minutes() {
+        assert_eq!(offset_minutes(TimestampPolicy::Utc, 0), 0);
+        assert_eq!(offset_minutes(TimestampPolicy::Offset(120), 0), 120);
+        assert_eq!(offset_minutes(TimestampPolicy::Offset(-2000), 0), MIN_OFFSET_MINUTES);
+        let local = offset_minutes(TimestampPolicy::Local, 1_700_000_000);
+        assert!((MIN_OFFSET_MINUTES..=MAX_OFFSET_MINUTES).contains(&local));
+    }
+}
@@ -0,0 +1,138 @@
+//! Refresh pacing for the display.
+//!
+//! Redrawing an unchanged screen 60 times a second costs wakeups and
+//! uploads for nothing. The driver reports no dirty regions, so the
+//! frontend samples a checksum of the framebuffer each refresh and
+//! `FramePacer` picks the interval until the next one: the active rate
+//! while the guest draws, dropping to the idle rate once the screen has
+//! been still for a while. The first change seen while idle brings the
+//! active rate back.
+
+use std::time::{Duration, Instant};
+
+/// Chooses the display refresh interval from guest drawing activity
+#[derive(Debug, Clone)]
+pub struct FramePacer {
+    /// Interval while the guest is drawing
+    pub active_interval: Duration,
+    /// Interval once the screen has been still for `idle_after`
+    pub idle_interval: Duration,
+    /// How long the screen stays unchanged before the idle rate applies
+    pub idle_after: Duration,
+    /// Checksum of the last sampled frame
+    last_checksum: Option<u64>,
+    /// When the frame last changed
+    last_change: Option<Instant>,
+}
+
+impl FramePacer {
+    /// Pace between `active_hz` and `idle_hz` (each at least 1)
+    pub fn new(active_hz: u32, idle_hz: u32, idle_after: Duration) -> Self {
+        let mut pacer = Self {
+            active_interval: Duration::ZERO,
+            idle_interval: Duration::ZERO,
+            idle_after,
+            last_checksum: None,
+            last_change: None,
+        };
+        pacer.set_rates(active_hz, idle_hz);
+        pacer
+    }
+
+    /// Change the active and idle refresh rates
+    pub fn set_rates(&mut self, active_hz: u32, idle_hz: u32) {
+        self.active_interval = hz_interval(active_hz);
+        self.idle_interval = hz_interval(idle_hz).max(self.active_interval);
+    }
+
+    /// Record a sampled frame (None when there is nothing to show) and
+    /// return the interval until the next sample
+    pub fn sample(&mut self, now: Instant, checksum: Option<u64>) -> Duration {
+        if checksum.is_some() && checksum != self.last_checksum {
+            self.last_change = Some(now);
+        }
+        self.last_checksum = checksum;
+        if self.is_idle(now) {
+            self.idle_interval
+        } else {
+            self.active_interval
+        }
+    }
+
+    /// Whether the screen has been unchanged for `idle_after`
+    pub fn is_idle(&self, now: Instant) -> bool {
+        self.last_change
+            .is_none_or(|changed| now.saturating_duration_since(changed) >= self.idle_after)
+    }
+
+    /// Forget the sampled frames, e.g. after a mode change
+    pub fn reset(&mut self) {
+        self.last_checksum = None;
+        self.last_change = None;
+    }
+}
+
+/// Interval of a refresh rate, in whole milliseconds
+fn hz_interval(hz: u32) -> Duration {
+    Duration::from_millis(1000 / u64::from(hz.max(1)))
+}
+
+/// Checksum of framebuffer contents for spotting changes
+///
+/// Not cryptographic; fast enough to run over a full frame every refresh.
+pub fn checksum(data: &[u8]) -> u64 {
+    const K: u64 = 0x9E37_79B9_7F4A_7C15;
+    let mut words = data.chunks_exact(8);
+    let mut hash = data.len() as u64;
+    for word in &mut words {
+        let word = u64::from_le_bytes(word.try_into().expect("8-byte chunk"));
+        hash = (hash.rotate_left(5) ^ word).wrapping_mul(K);
+    }
+    for &byte in words.remainder() {
+        hash = (hash.rotate_left(5) ^ u64::from(byte)).wrapping_mul(K);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_pacer() {
+        let mut pacer = FramePacer::new(60, 5, Duration::from_millis(500));
+        assert_eq!(pacer.active_interval, Duration::from_millis(16));
+        assert_eq!(pacer.idle_interval, Duration::from_millis(200));
+
+        // Nothing drawn yet
+        let start = Instant::now();
+        assert_eq!(pacer.sample(start, None), pacer.idle_interval);
+
+        // Drawing switches to the active rate at once
+        assert_eq!(pacer.sample(start, Some(1)), pacer.active_interval);
+        let at = |ms| start + Duration::from_millis(ms);
+        assert_eq!(pacer.sample(at(100), Some(2)), pacer.active_interval);
+
+        // Still for less than idle_after, then longer
+        assert_eq!(pacer.sample(at(500), Some(2)), pacer.active_interval);
+        assert_eq!(pacer.sample(at(600), Some(2)), pacer.idle_interval);
+        assert!(pacer.is_idle(at(600)));
+
+        // The next change wakes it up
+        assert_eq!(pacer.sample(at(800), Some(3)), pacer.active_interval);
+
+        // An idle rate above the active rate is clamped
+        pacer.set_rates(10, 30);
+        assert_eq!(pacer.idle_interval, pacer.active_interval);
+    }
+
+    #[test]
+    fn test_checksum() {
+        let frame = vec![0x55u8; 1027];
+        let mut changed = frame.clone();
+        changed[1025] ^= 1;
+        assert_eq!(checksum(&frame), checksum(&frame.clone()));
+        assert_ne!(checksum(&frame), checksum(&changed));
+        assert_ne!(checksum(&frame), checksum(&frame[..1026]));
+    }
+}
@@ -0,0 +1,365 @@
+//! Host gamepads on the guest's game port.
+//!
+//! The SunPCi's game port takes two joysticks of two axes and two buttons
+//! each. A host gamepad or joystick is read through evdev
+//! (`/dev/input/event*`, which needs the user to be in the `input` group
+//! or a uaccess seat) and laid onto it by `[gameport]`: each guest axis
+//! comes from one host axis, calibrated to its own ends and centre with a
+//! dead zone, and each guest button from any of a list of host buttons.
+//! The frontend sends the result with `GAMEPORT_EVENT` whenever it changes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+
+use nix::libc;
+use nix::{ioctl_read_buf, request_code_read};
+
+use crate::config::{AxisMapping, GameportConfig};
+use crate::ioctl::{GameportEvent, SUNPCI_GAMEPORT_AXES};
+
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_DROPPED: u16 = 3;
+
+/// Highest absolute axis code, plus one
+const ABS_CNT: usize = 0x40;
+/// Highest key code, plus one
+const KEY_CNT: usize = 0x300;
+
+/// Joystick and gamepad buttons (BTN_JOYSTICK to the last BTN_GAMEPAD)
+const BTN_JOYSTICK: usize = 0x120;
+const BTN_GAMEPAD_END: usize = 0x13f;
+
+ioctl_read_buf!(eviocgname, b'E', 0x06, u8);
+ioctl_read_buf!(eviocgkey, b'E', 0x18, u8);
+ioctl_read_buf!(eviocgbit_key, b'E', 0x20 + EV_KEY as u8, u8);
+ioctl_read_buf!(eviocgbit_abs, b'E', 0x20 + EV_ABS as u8, u8);
+
+/// A gamepad found on the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GamepadInfo {
+    pub path: PathBuf,
+    pub name: String,
+}
+
+/// Gamepads and joysticks the user can read
+pub fn list_gamepads() -> Vec<GamepadInfo> {
+    let Ok(entries) = fs::read_dir("/dev/input") else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("event")))
+        .collect();
+    // event2 before event10
+    paths.sort_by_key(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n["event".len()..].parse::<u32>().ok())
+            .unwrap_or(u32::MAX)
+    });
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let gamepad = Gamepad::open(&path).ok()?;
+            Some(GamepadInfo { path, name: gamepad.name })
+        })
+        .collect()
+}
+
+/// The gamepad `spec` names (a path, or part of its name), or the first
+/// one when it is empty
+pub fn find_gamepad(spec: &str) -> Option<GamepadInfo> {
+    if spec.starts_with('/') {
+        let gamepad = Gamepad::open(Path::new(spec)).ok()?;
+        return Some(GamepadInfo {
+            path: PathBuf::from(spec),
+            name: gamepad.name,
+        });
+    }
+    let spec = spec.to_lowercase();
+    list_gamepads().into_iter().find(|g| g.name.to_lowercase().contains(&spec))
+}
+
+/// An axis as the device reports it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AbsAxis {
+    pub value: i32,
+    pub min: i32,
+    pub max: i32,
+}
+
+/// What a gamepad's axes and buttons are doing
+#[derive(Debug, Clone)]
+pub struct GamepadState {
+    axes: [Option<AbsAxis>; ABS_CNT],
+    keys: [u8; KEY_CNT / 8],
+}
+
+impl Default for GamepadState {
+    fn default() -> Self {
+        Self {
+            axes: [None; ABS_CNT],
+            keys: [0; KEY_CNT / 8],
+        }
+    }
+}
+
+impl GamepadState {
+    /// Axis `code`, if the device has it
+    pub fn axis(&self, code: u16) -> Option<AbsAxis> {
+        self.axes.get(code as usize).copied().flatten()
+    }
+
+    /// Give the device axis `code`
+    pub fn set_axis(&mut self, code: u16, axis: AbsAxis) {
+        if let Some(slot) = self.axes.get_mut(code as usize) {
+            *slot = Some(axis);
+        }
+    }
+
+    /// Whether button `code` is held
+    pub fn key(&self, code: u16) -> bool {
+        bit(&self.keys, code as usize)
+    }
+
+    /// Hold or release button `code`
+    pub fn set_key(&mut self, code: u16, held: bool) {
+        if let Some(byte) = self.keys.get_mut(code as usize / 8) {
+            let mask = 1 << (code % 8);
+            if held {
+                *byte |= mask;
+            } else {
+                *byte &= !mask;
+            }
+        }
+    }
+
+    /// The game port as `config` lays this state onto it
+    pub fn to_event(&self, config: &GameportConfig) -> GameportEvent {
+        let mut event = GameportEvent::default();
+        for (n, mapping) in config.axes.iter().take(SUNPCI_GAMEPORT_AXES).enumerate() {
+            if let Some(axis) = mapping.source.and_then(|code| self.axis(code)) {
+                event.axes[n] = map_axis(axis, mapping);
+                event.connected |= 1 << n;
+            }
+        }
+        for (n, mapping) in config.buttons.iter().take(4).enumerate() {
+            if mapping.keys.iter().any(|&code| self.key(code)) {
+                event.buttons |= 1 << n;
+            }
+        }
+        event
+    }
+
+    /// Take the mapped axes' current positions as their centres
+    pub fn calibrate_center(&self, config: &mut GameportConfig) {
+        for mapping in &mut config.axes {
+            if let Some(axis) = mapping.source.and_then(|code| self.axis(code)) {
+                mapping.center = Some(axis.value);
+            }
+        }
+    }
+}
+
+fn bit(bits: &[u8], n: usize) -> bool {
+    bits.get(n / 8).is_some_and(|byte| byte & (1 << (n % 8)) != 0)
+}
+
+/// Position of `axis` on the guest's scale, -32767 to 32767
+pub fn map_axis(axis: AbsAxis, mapping: &AxisMapping) -> i16 {
+    let min = mapping.min.unwrap_or(axis.min) as f64;
+    let max = mapping.max.unwrap_or(axis.max) as f64;
+    let center = mapping.center.map(|c| c as f64).unwrap_or((min + max) / 2.0);
+    let value = axis.value as f64;
+
+    let mut position = if value < center {
+        if center > min { -(center - value) / (center - min) } else { 0.0 }
+    } else if max > center {
+        (value - center) / (max - center)
+    } else {
+        0.0
+    };
+    position = position.clamp(-1.0, 1.0);
+
+    let dead_zone = (mapping.dead_zone.min(99) as f64) / 100.0;
+    position = if position.abs() <= dead_zone {
+        0.0
+    } else {
+        position.signum() * (position.abs() - dead_zone) / (1.0 - dead_zone)
+    };
+    if mapping.invert {
+        position = -position;
+    }
+    (position * i16::MAX as f64).round() as i16
+}
+
+/// An open evdev gamepad
+pub struct Gamepad {
+    file: File,
+    name: String,
+    /// Axes the device has
+    abs_present: [u8; ABS_CNT / 8],
+    state: GamepadState,
+}
+
+impl Gamepad {
+    /// Open the gamepad at `path`; fails for devices that are not one
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK | libc::O_CLOEXEC)
+            .open(path)?;
+        let fd = file.as_raw_fd();
+
+        let mut key_bits = [0u8; KEY_CNT / 8];
+        let mut abs_bits = [0u8; ABS_CNT / 8];
+        unsafe {
+            eviocgbit_key(fd, &mut key_bits).map_err(io::Error::from)?;
+            eviocgbit_abs(fd, &mut abs_bits).map_err(io::Error::from)?;
+        }
+        let has_buttons = (BTN_JOYSTICK..=BTN_GAMEPAD_END).any(|code| bit(&key_bits, code));
+        if !has_buttons || !bit(&abs_bits, 0) || !bit(&abs_bits, 1) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a gamepad", path.display())));
+        }
+
+        let mut name = [0u8; 256];
+        let len = unsafe { eviocgname(fd, &mut name) }.unwrap_or(0).max(0) as usize;
+        let name = String::from_utf8_lossy(&name[..len.min(name.len())]).trim_end_matches('\0').to_string();
+
+        let mut gamepad = Self {
+            file,
+            name,
+            abs_present: abs_bits,
+            state: GamepadState::default(),
+        };
+        gamepad.resync()?;
+        Ok(gamepad)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn state(&self) -> &GamepadState {
+        &self.state
+    }
+
+    /// Read what the device reported since the last call, without
+    /// blocking. Returns whether anything changed; fails once the device
+    /// is unplugged.
+    pub fn read_events(&mut self) -> io::Result<bool> {
+        const EVENT_SIZE: usize = mem::size_of::<libc::input_event>();
+        let mut buffer = [0u8; EVENT_SIZE * 64];
+        let mut changed = false;
+        loop {
+            let read = match self.file.read(&mut buffer) {
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(read) => read,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(changed),
+                Err(e) => return Err(e),
+            };
+            for chunk in buffer[..read].chunks_exact(EVENT_SIZE) {
+                let event: libc::input_event = unsafe { std::ptr::read_unaligned(chunk.as_ptr().cast()) };
+                match (event.type_, event.code) {
+                    // Events were lost; read the whole state again
+                    (EV_SYN, SYN_DROPPED) => {
+                        self.resync()?;
+                        changed = true;
+                    }
+                    (EV_KEY, code) => {
+                        self.state.set_key(code, event.value != 0);
+                        changed = true;
+                    }
+                    (EV_ABS, code) => {
+                        if let Some(Some(axis)) = self.state.axes.get_mut(code as usize) {
+                            axis.value = event.value;
+                            changed = true;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Read the whole state from the device
+    fn resync(&mut self) -> io::Result<()> {
+        let fd = self.file.as_raw_fd();
+        unsafe {
+            eviocgkey(fd, &mut self.state.keys).map_err(io::Error::from)?;
+        }
+        for code in 0..ABS_CNT {
+            if !bit(&self.abs_present, code) {
+                continue;
+            }
+            let mut info: libc::input_absinfo = unsafe { mem::zeroed() };
+            let request = request_code_read!(b'E', 0x40 + code, mem::size_of::<libc::input_absinfo>());
+            if unsafe { libc::ioctl(fd, request as _, &mut info) } < 0 {
+                continue;
+            }
+            self.state.axes[code] = Some(AbsAxis {
+                value: info.value,
+                min: info.minimum,
+                max: info.maximum,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl AsRawFd for Gamepad {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gameport_mapping() {
+        let stick = |value| AbsAxis { value, min: 0, max: 255 };
+        let mut mapping = AxisMapping::new(0);
+        mapping.dead_zone = 0;
+        assert_eq!(map_axis(stick(0), &mapping), -32767);
+        assert_eq!(map_axis(stick(255), &mapping), 32767);
+        assert_eq!(map_axis(AbsAxis { value: 0, min: -100, max: 100 }, &mapping), 0);
+
+        // Calibrated off-centre, with a dead zone, turned around
+        mapping.center = Some(100);
+        mapping.dead_zone = 10;
+        mapping.invert = true;
+        assert_eq!(map_axis(stick(105), &mapping), 0);
+        assert_eq!(map_axis(stick(0), &mapping), 32767);
+        assert_eq!(map_axis(stick(255), &mapping), -32767);
+        assert_eq!(map_axis(stick(300), &mapping), -32767);
+
+        // Left stick to joystick A, no right stick, south button to A1
+        let mut state = GamepadState::default();
+        state.set_axis(0, AbsAxis { value: -32768, min: -32768, max: 32767 });
+        state.set_axis(1, AbsAxis { value: 0, min: -32768, max: 32767 });
+        state.set_key(0x130, true);
+        let mut config = GameportConfig::default();
+        let event = state.to_event(&config);
+        assert_eq!(event.connected, 0b0011);
+        assert_eq!(event.axes[0], -32767);
+        assert_eq!(event.axes[1], 0);
+        assert_eq!(event.buttons, 1);
+
+        state.set_key(0x130, false);
+        state.set_key(0x121, true);
+        assert_eq!(state.to_event(&config).buttons, 0b10);
+
+        state.calibrate_center(&mut config);
+        assert_eq!(config.axes[0].center, Some(-32768));
+        assert_eq!(config.axes[2].center, None);
+    }
+}
@@ -0,0 +1,454 @@
+//! Guess which operating system a disk image holds.
+//!
+//! `detect` reads the FAT volume of the first partition (or of a floppy
+//! image with no partition table) without the guest, and looks for the
+//! files each system leaves in place: IO.SYS and COMMAND.COM for DOS,
+//! WIN.COM and KRNL386.EXE for Windows 3.x, a text MSDOS.SYS and
+//! VMM32.VXD for Windows 9x, and NTLDR and BOOT.INI for Windows NT. Each
+//! system has its own `GuestTweaks`: the mouse protocol its SunPCi drivers
+//! speak, whether it has a clipboard to share, and the drivers worth
+//! installing.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::{AppConfig, MouseProtocol};
+use crate::disk_image::SECTOR_SIZE;
+use crate::fat::{self, Bpb, DIR_ENTRY_SIZE, FatType};
+
+/// Most of a text file (MSDOS.SYS, BOOT.INI) read
+const MAX_TEXT: u32 = 16 * 1024;
+
+/// Operating system on a disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GuestOs {
+    Unknown,
+    Dos,
+    Windows31,
+    Windows9x,
+    WindowsNt,
+}
+
+impl GuestOs {
+    /// Name to show
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Dos => "DOS",
+            Self::Windows31 => "Windows 3.x",
+            Self::Windows9x => "Windows 9x",
+            Self::WindowsNt => "Windows NT",
+        }
+    }
+
+    /// Settings that suit the system, None if it is not known
+    pub fn tweaks(self) -> Option<GuestTweaks> {
+        let (mouse_protocol, clipboard, drivers): (_, _, &'static [&'static str]) = match self {
+            Self::Unknown => return None,
+            Self::Dos => (MouseProtocol::Ps2, false, &["redir.sys", "sunpcnet.exe"]),
+            Self::Windows31 => (MouseProtocol::Ps2, false, &["redir.sys", "sunpcnet.exe"]),
+            Self::Windows9x => (
+                MouseProtocol::Absolute,
+                true,
+                &["sunpci.vxd", "spcmouse.vxd", "spcdisp.drv", "sunfsd.vxd", "sunwndis.vxd", "sunclip.exe"],
+            ),
+            Self::WindowsNt => (
+                MouseProtocol::Serial,
+                true,
+                &["bridge.sys", "emdisk.sys", "sermouse.sys", "sunvmini.sys", "sunfsd.sys", "sunndis.sys", "sunclip.exe"],
+            ),
+        };
+        Some(GuestTweaks { mouse_protocol, clipboard, drivers })
+    }
+}
+
+/// Settings suited to a guest system
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GuestTweaks {
+    /// Protocol of the system's mouse driver
+    pub mouse_protocol: MouseProtocol,
+    /// Whether the system runs sunclip.exe, so sharing the clipboard works
+    pub clipboard: bool,
+    /// SunPCi drivers to install in the guest
+    pub drivers: &'static [&'static str],
+}
+
+impl GuestTweaks {
+    /// Use these settings in `config`
+    pub fn apply(&self, config: &mut AppConfig) {
+        config.mouse.protocol = self.mouse_protocol;
+        config.clipboard.enabled = self.clipboard;
+    }
+}
+
+/// What `detect` found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Detection {
+    pub os: GuestOs,
+    /// Release, such as "Windows 98", when the files tell
+    pub version: Option<String>,
+    /// Label of the volume, empty if it has none
+    pub volume_label: String,
+    /// Files and partitions the guess rests on
+    pub evidence: Vec<String>,
+}
+
+impl Detection {
+    fn unknown(evidence: &str) -> Self {
+        Self {
+            os: GuestOs::Unknown,
+            version: None,
+            volume_label: String::new(),
+            evidence: vec![evidence.to_string()],
+        }
+    }
+}
+
+/// Guess the operating system on the disk or floppy image at `path`
+pub fn detect(path: &Path) -> io::Result<Detection> {
+    let file = File::open(path)?;
+    let mut sector0 = [0u8; 512];
+    file.read_exact_at(&mut sector0, 0)?;
+
+    // A floppy, or a disk with its first partition
+    let start = if Bpb::parse(&sector0).is_some() {
+        0
+    } else {
+        let entry = &sector0[0x1BE..0x1CE];
+        match entry[4] {
+            0x01 | 0x04 | 0x06 | 0x0E => read_le32(entry, 8) as u64,
+            0x07 => {
+                let mut detection = Detection::unknown("NTFS partition");
+                detection.os = GuestOs::WindowsNt;
+                return Ok(detection);
+            }
+            0x0B | 0x0C => {
+                let mut detection = Detection::unknown("FAT32 partition");
+                detection.os = GuestOs::Windows9x;
+                return Ok(detection);
+            }
+            0x00 => return Ok(Detection::unknown("no partition")),
+            other => return Ok(Detection::unknown(&format!("partition type {:#04x}", other))),
+        }
+    };
+
+    let mut boot = [0u8; 512];
+    file.read_exact_at(&mut boot, start * SECTOR_SIZE as u64)?;
+    let Some((bpb, fat_type)) = Bpb::parse(&boot).and_then(|b| Some((b, b.fat_type()?))) else {
+        return Ok(Detection::unknown("no FAT12/FAT16 volume"));
+    };
+    let volume = Volume { file: &file, bpb, fat_type, start };
+    let fat = volume.read(bpb.fat_start(0) as u64, bpb.sectors_per_fat as u64)?;
+    let volume = FatVolume { volume, fat };
+
+    let root = volume.root()?;
+    let volume_label = root
+        .iter()
+        .find(|e| e.is_label())
+        .map(|e| e.name.clone())
+        .or_else(|| boot_label(&boot))
+        .unwrap_or_default();
+    let mut detection = Detection {
+        os: GuestOs::Unknown,
+        version: None,
+        volume_label,
+        evidence: Vec::new(),
+    };
+
+    // Windows NT boots through NTLDR whatever else is on the disk
+    let ntldr = note(&root, "NTLDR", &mut detection.evidence);
+    if let Some(boot_ini) = find(&root, "BOOT.INI") {
+        detection.evidence.push("BOOT.INI".to_string());
+        detection.os = GuestOs::WindowsNt;
+        detection.version = nt_version(&volume.read_text(boot_ini)?);
+        return Ok(detection);
+    }
+    if ntldr {
+        detection.os = GuestOs::WindowsNt;
+        return Ok(detection);
+    }
+
+    // Windows 9x replaced the binary MSDOS.SYS with a settings file
+    if let Some(msdos_sys) = find(&root, "MSDOS.SYS") {
+        let text = volume.read_text(msdos_sys)?;
+        if text.to_ascii_uppercase().contains("[PATHS]") {
+            detection.evidence.push("MSDOS.SYS with [Paths]".to_string());
+            detection.os = GuestOs::Windows9x;
+            detection.version = win9x_version(&text);
+            return Ok(detection);
+        }
+    }
+
+    let windows = match find(&root, "WINDOWS").filter(|e| e.is_dir()) {
+        Some(dir) => volume.directory(dir)?,
+        None => Vec::new(),
+    };
+    let system = match find(&windows, "SYSTEM").filter(|e| e.is_dir()) {
+        Some(dir) => volume.directory(dir)?,
+        None => Vec::new(),
+    };
+    if note(&system, "\\WINDOWS\\SYSTEM\\VMM32.VXD", &mut detection.evidence) {
+        detection.os = GuestOs::Windows9x;
+        return Ok(detection);
+    }
+    let win_com = note(&windows, "\\WINDOWS\\WIN.COM", &mut detection.evidence);
+    let krnl386 = note(&system, "\\WINDOWS\\SYSTEM\\KRNL386.EXE", &mut detection.evidence);
+    if win_com || krnl386 {
+        detection.os = GuestOs::Windows31;
+        return Ok(detection);
+    }
+
+    let io_sys = note(&root, "IO.SYS", &mut detection.evidence) || note(&root, "IBMBIO.COM", &mut detection.evidence);
+    let command_com = note(&root, "COMMAND.COM", &mut detection.evidence);
+    if io_sys || command_com {
+        detection.os = GuestOs::Dos;
+    }
+    Ok(detection)
+}
+
+/// Whether `path` is among `entries`, noting it as evidence if so
+fn note(entries: &[DirEntry], path: &str, evidence: &mut Vec<String>) -> bool {
+    let name = path.rsplit('\\').next().unwrap_or(path);
+    let found = find(entries, name).is_some();
+    if found {
+        evidence.push(path.to_string());
+    }
+    found
+}
+
+/// Release named by the first entry of BOOT.INI's [operating systems]
+fn nt_version(boot_ini: &str) -> Option<String> {
+    let mut in_systems = false;
+    for line in boot_ini.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_systems = line.eq_ignore_ascii_case("[operating systems]");
+        } else if in_systems {
+            let (_, rest) = line.split_once('"')?;
+            let (name, _) = rest.split_once('"')?;
+            return Some(name.to_string());
+        }
+    }
+    None
+}
+
+/// Release from the WinVer line of a Windows 9x MSDOS.SYS
+fn win9x_version(msdos_sys: &str) -> Option<String> {
+    let value = msdos_sys.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        key.trim().eq_ignore_ascii_case("WinVer").then(|| value.trim().to_string())
+    })?;
+    let name = match value.get(..4)? {
+        "4.00" => "Windows 95",
+        "4.10" => "Windows 98",
+        "4.90" => "Windows Me",
+        _ => return Some(format!("Windows {}", value)),
+    };
+    Some(name.to_string())
+}
+
+/// Label in the extended BPB of a boot sector
+fn boot_label(boot: &[u8]) -> Option<String> {
+    if boot[38] != 0x29 {
+        return None;
+    }
+    let label = String::from_utf8_lossy(&boot[43..54]).trim_end().to_string();
+    (!label.is_empty() && label != "NO NAME").then_some(label)
+}
+
+/// An entry of a directory
+#[derive(Debug, Clone)]
+struct DirEntry {
+    /// 8.3 name, or the volume label
+    name: String,
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+}
+
+impl DirEntry {
+    fn is_dir(&self) -> bool {
+        self.attributes & 0x10 != 0
+    }
+
+    fn is_label(&self) -> bool {
+        self.attributes & 0x08 != 0 && self.attributes & 0x0F != 0x0F
+    }
+}
+
+/// The entry called `name` (not the volume label), ignoring case
+fn find<'a>(entries: &'a [DirEntry], name: &str) -> Option<&'a DirEntry> {
+    entries.iter().find(|e| !e.is_label() && e.name.eq_ignore_ascii_case(name))
+}
+
+/// Entries of a directory, without deleted entries and long name pieces
+fn parse_entries(bytes: &[u8]) -> Vec<DirEntry> {
+    let mut entries = Vec::new();
+    for entry in bytes.chunks_exact(DIR_ENTRY_SIZE as usize) {
+        match entry[0] {
+            0x00 => break,
+            0xE5 => continue,
+            _ => {}
+        }
+        let attributes = entry[11];
+        if attributes & 0x0F == 0x0F {
+            continue;
+        }
+        let name = if attributes & 0x08 != 0 {
+            String::from_utf8_lossy(&entry[..11]).trim_end().to_string()
+        } else {
+            let base = String::from_utf8_lossy(&entry[..8]).trim_end().to_string();
+            let ext = String::from_utf8_lossy(&entry[8..11]).trim_end().to_string();
+            if ext.is_empty() { base } else { format!("{}.{}", base, ext) }
+        };
+        entries.push(DirEntry {
+            name,
+            attributes,
+            first_cluster: u16::from_le_bytes([entry[26], entry[27]]) as u32,
+            size: read_le32(entry, 28),
+        });
+    }
+    entries
+}
+
+/// A FAT volume in an image
+struct Volume<'a> {
+    file: &'a File,
+    bpb: Bpb,
+    fat_type: FatType,
+    /// First sector of the volume on the disk
+    start: u64,
+}
+
+impl Volume<'_> {
+    /// `count` sectors from `sector` of the volume
+    fn read(&self, sector: u64, count: u64) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; (count * SECTOR_SIZE as u64) as usize];
+        self.file.read_exact_at(&mut buf, (self.start + sector) * SECTOR_SIZE as u64)?;
+        Ok(buf)
+    }
+}
+
+/// A volume with its first FAT read
+struct FatVolume<'a> {
+    volume: Volume<'a>,
+    fat: Vec<u8>,
+}
+
+impl FatVolume<'_> {
+    fn root(&self) -> io::Result<Vec<DirEntry>> {
+        let bpb = &self.volume.bpb;
+        let bytes = self.volume.read(bpb.root_dir_start() as u64, bpb.root_dir_sectors() as u64)?;
+        Ok(parse_entries(&bytes))
+    }
+
+    fn directory(&self, dir: &DirEntry) -> io::Result<Vec<DirEntry>> {
+        Ok(parse_entries(&self.read_chain(dir.first_cluster, u32::MAX)?))
+    }
+
+    /// Start of a small file, as text
+    fn read_text(&self, file: &DirEntry) -> io::Result<String> {
+        let limit = file.size.min(MAX_TEXT);
+        let mut bytes = self.read_chain(file.first_cluster, limit)?;
+        bytes.truncate(limit as usize);
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    /// The clusters of the chain from `first`, stopping once `limit` bytes
+    /// are read or the chain breaks
+    fn read_chain(&self, first: u32, limit: u32) -> io::Result<Vec<u8>> {
+        let bpb = &self.volume.bpb;
+        let fat_type = self.volume.fat_type;
+        let last = bpb.cluster_count() + fat::FIRST_CLUSTER - 1;
+        let spc = bpb.sectors_per_cluster as u64;
+        let mut bytes = Vec::new();
+        let mut cluster = first;
+        // A chain can be no longer than the volume, which also ends loops
+        for _ in 0..bpb.cluster_count() {
+            if !(fat::FIRST_CLUSTER..=last).contains(&cluster) || bytes.len() as u64 >= limit as u64 {
+                break;
+            }
+            bytes.extend(self.volume.read(bpb.cluster_start(cluster) as u64, spc)?);
+            let next = fat::fat_entry(&self.fat, fat_type, cluster);
+            if next >= fat_type.end_of_chain() {
+                break;
+            }
+            cluster = next;
+        }
+        Ok(bytes)
+    }
+}
+
+fn read_le32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::{Allocation, create_disk_image};
+    use crate::progress::ProgressReporter;
+
+    /// Put root directory entries and one-cluster files on the volume
+    fn add_files(path: &Path, files: &[(&str, &[u8])]) {
+        let file = File::options().read(true).write(true).open(path).unwrap();
+        let mut boot = [0u8; 512];
+        file.read_exact_at(&mut boot, 63 * 512).unwrap();
+        let bpb = Bpb::parse(&boot).unwrap();
+        let mut fat = vec![0u8; bpb.sectors_per_fat as usize * 512];
+        file.read_exact_at(&mut fat, (63 + bpb.fat_start(0) as u64) * 512).unwrap();
+
+        for (n, (name, contents)) in files.iter().enumerate() {
+            let cluster = fat::FIRST_CLUSTER + n as u32;
+            let (base, ext) = name.split_once('.').unwrap_or((name, ""));
+            let mut entry = [0u8; 32];
+            entry[..11].copy_from_slice(format!("{:<8}{:<3}", base, ext).as_bytes());
+            entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+            entry[28..32].copy_from_slice(&(contents.len() as u32).to_le_bytes());
+            file.write_all_at(&entry, (63 + bpb.root_dir_start() as u64) * 512 + n as u64 * 32).unwrap();
+            file.write_all_at(contents, (63 + bpb.cluster_start(cluster) as u64) * 512).unwrap();
+            fat::set_fat_entry(&mut fat, FatType::Fat16, cluster, 0xFFFF);
+        }
+        file.write_all_at(&fat, (63 + bpb.fat_start(0) as u64) * 512).unwrap();
+    }
+
+    #[test]
+    fn test_detect() {
+        let dir = tempfile::tempdir().unwrap();
+        let blank = dir.path().join("blank.diskimage");
+        create_disk_image(&blank, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        assert_eq!(detect(&blank).unwrap().os, GuestOs::Unknown);
+
+        let dos = dir.path().join("dos.diskimage");
+        create_disk_image(&dos, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        add_files(&dos, &[("IO.SYS", b"\xE9"), ("MSDOS.SYS", b"\x00\x01"), ("COMMAND.COM", b"\xE9")]);
+        let detection = detect(&dos).unwrap();
+        assert_eq!(detection.os, GuestOs::Dos);
+        assert_eq!(detection.evidence, ["IO.SYS", "COMMAND.COM"]);
+
+        let win98 = dir.path().join("win98.diskimage");
+        create_disk_image(&win98, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        add_files(&win98, &[("IO.SYS", b"\xE9"), ("MSDOS.SYS", b"[Paths]\r\nWinDir=C:\\WINDOWS\r\nWinVer=4.10.2222\r\n")]);
+        let detection = detect(&win98).unwrap();
+        assert_eq!(detection.os, GuestOs::Windows9x);
+        assert_eq!(detection.version.as_deref(), Some("Windows 98"));
+
+        let nt = dir.path().join("nt.diskimage");
+        create_disk_image(&nt, 16, 2, Allocation::Sparse, &ProgressReporter::new()).unwrap();
+        let boot_ini = b"[boot loader]\r\ntimeout=30\r\n[operating systems]\r\n\
+            multi(0)disk(0)rdisk(0)partition(1)\\WINNT=\"Windows NT Workstation Version 4.00\"\r\n";
+        add_files(&nt, &[("NTLDR", b"\xE9"), ("BOOT.INI", boot_ini)]);
+        let detection = detect(&nt).unwrap();
+        assert_eq!(detection.os, GuestOs::WindowsNt);
+        assert_eq!(detection.version.as_deref(), Some("Windows NT Workstation Version 4.00"));
+
+        let mut config = AppConfig::default();
+        GuestOs::WindowsNt.tweaks().unwrap().apply(&mut config);
+        assert_eq!(config.mouse.protocol, MouseProtocol::Serial);
+        assert!(GuestOs::Unknown.tweaks().is_none());
+    }
+}
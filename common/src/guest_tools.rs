@@ -0,0 +1,228 @@
+//! The guest tools CD.
+//!
+//! The guest side of drive mapping, clipboard sharing and the display
+//! drivers are programs from the original SunPCi software, which cannot be
+//! shipped with Rising Sun. `build_iso` gathers them from a copy of its
+//! install directory (`[guest_tools] source`, or /opt/SUNWspci*) into a
+//! small ISO for the guest's CD-ROM, laid out by guest system:
+//!
+//! ```text
+//! DOS\REDIR.SYS, DOS\SUNPCNET.EXE     drive mapping client
+//! WIN9X\SUNCLIP.EXE, WIN9X\SPCDISP.*  clipboard agent, display driver
+//! WINNT\SUNCLIP.EXE, WINNT\SUNVIDEO.DLL, WINNT\SUNVMINI.SYS
+//! README.TXT                          what is there and for which driver
+//! ```
+//!
+//! A source directory may keep the tools of several releases in
+//! subdirectories named after the driver version ("1.2.3" or "1.2"); the
+//! ones matching the loaded driver are used before the top level's.
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result, bail};
+
+use crate::config::AppConfig;
+use crate::ioctl::DriverVersion;
+use crate::iso9660::{self, IsoFile};
+
+/// Volume label of the CD
+pub const VOLUME_LABEL: &str = "RISING_SUN_TOOLS";
+
+/// Where the SunPCi software is installed, newest release first
+const DEFAULT_SOURCES: &[&str] = &["/opt/SUNWspci3", "/opt/SUNWspci2", "/opt/SUNWspci"];
+
+/// A program or driver on the CD
+struct Tool {
+    /// Path on the CD
+    iso_path: &'static str,
+    /// Paths in the SunPCi software, preferred first
+    sources: &'static [&'static str],
+    /// What it is, for README.TXT
+    purpose: &'static str,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        iso_path: "DOS/REDIR.SYS",
+        sources: &["defaults/7.01/sunpc/redir.sys"],
+        purpose: "drive mapping redirector (DEVICE= in CONFIG.SYS)",
+    },
+    Tool {
+        iso_path: "DOS/SUNPCNET.EXE",
+        sources: &["defaults/7.01/sunpc/sunpcnet.exe"],
+        purpose: "drive mapping client (SUNPCNET USE E: \\\\HOST\\DIR)",
+    },
+    Tool {
+        iso_path: "WIN9X/SUNCLIP.EXE",
+        sources: &["drivers/win95/sunclip.exe"],
+        purpose: "clipboard agent",
+    },
+    Tool {
+        iso_path: "WIN9X/SPCDISP.DRV",
+        sources: &["drivers/win95/spcdisp.drv"],
+        purpose: "display driver",
+    },
+    Tool {
+        iso_path: "WIN9X/SPCDISP.VXD",
+        sources: &["drivers/win95/spcdisp.vxd"],
+        purpose: "display driver helper",
+    },
+    Tool {
+        iso_path: "WINNT/SUNCLIP.EXE",
+        sources: &["drivers/winnt/patch/sunclip.exe", "drivers/winnt/oem/$$/system32/sunclip.exe"],
+        purpose: "clipboard agent",
+    },
+    Tool {
+        iso_path: "WINNT/SUNVIDEO.DLL",
+        sources: &["drivers/winnt/oem/$$/system32/sunvideo.dll"],
+        purpose: "display driver",
+    },
+    Tool {
+        iso_path: "WINNT/SUNVMINI.SYS",
+        sources: &["drivers/winnt/oem/$$/system32/drivers/sunvmini.sys"],
+        purpose: "video miniport driver",
+    },
+];
+
+/// A guest tools CD written by `build_iso`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuestToolsIso {
+    pub path: PathBuf,
+    /// Paths on the CD of the tools found
+    pub files: Vec<String>,
+    /// Paths on the CD of the tools the source lacks
+    pub missing: Vec<String>,
+}
+
+/// The SunPCi software directory: `configured` if set, otherwise the first
+/// default install directory there is
+pub fn find_source(configured: Option<&Path>) -> Option<PathBuf> {
+    match configured {
+        Some(path) => Some(path.to_path_buf()),
+        None => DEFAULT_SOURCES.iter().map(PathBuf::from).find(|p| p.is_dir()),
+    }
+}
+
+/// Where the CD for a driver version is kept
+pub fn iso_path(version: &DriverVersion) -> PathBuf {
+    AppConfig::data_dir()
+        .join("guest-tools")
+        .join(format!("tools-{}.{}.{}.iso", version.major, version.minor, version.patch))
+}
+
+/// Directories of `source` to look in for `version`'s tools, best first
+fn version_roots(source: &Path, version: &DriverVersion) -> Vec<PathBuf> {
+    vec![
+        source.join(format!("{}.{}.{}", version.major, version.minor, version.patch)),
+        source.join(format!("{}.{}", version.major, version.minor)),
+        source.to_path_buf(),
+    ]
+}
+
+/// Write the guest tools CD for `version` from the SunPCi software in
+/// `source` to `dest`, replacing an older one
+///
+/// Tools the source lacks are left out and listed; a source with none of
+/// them at all is an error.
+pub fn build_iso(source: &Path, version: &DriverVersion, dest: &Path) -> Result<GuestToolsIso> {
+    if !source.is_dir() {
+        bail!("{} is not a directory", source.display());
+    }
+    let roots = version_roots(source, version);
+    let mut files = Vec::new();
+    let mut found = Vec::new();
+    let mut missing = Vec::new();
+    for tool in TOOLS {
+        let host = roots
+            .iter()
+            .flat_map(|root| tool.sources.iter().map(move |s| root.join(s)))
+            .find(|p| p.is_file());
+        match host {
+            Some(host) => {
+                let data = std::fs::read(&host).with_context(|| format!("Cannot read {}", host.display()))?;
+                files.push(IsoFile { path: tool.iso_path.to_string(), data });
+                found.push(tool);
+            }
+            None => missing.push(tool),
+        }
+    }
+    if found.is_empty() {
+        bail!("{} holds none of the SunPCi guest tools", source.display());
+    }
+    files.push(IsoFile {
+        path: "README.TXT".to_string(),
+        data: readme(version, &found, &missing).into_bytes(),
+    });
+
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).with_context(|| format!("Cannot create {}", parent.display()))?;
+    }
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs() as i64);
+    let partial = dest.with_extension("iso.partial");
+    let _ = std::fs::remove_file(&partial);
+    iso9660::write_iso(&partial, VOLUME_LABEL, &files, now)
+        .with_context(|| format!("Cannot write {}", partial.display()))?;
+    std::fs::rename(&partial, dest).with_context(|| format!("Cannot write {}", dest.display()))?;
+
+    Ok(GuestToolsIso {
+        path: dest.to_path_buf(),
+        files: found.iter().map(|t| t.iso_path.to_string()).collect(),
+        missing: missing.iter().map(|t| t.iso_path.to_string()).collect(),
+    })
+}
+
+/// README.TXT of the CD, with DOS line endings
+fn readme(version: &DriverVersion, found: &[&Tool], missing: &[&Tool]) -> String {
+    let mut lines = vec![
+        "Rising Sun guest tools".to_string(),
+        format!("For SunPCi driver {}.{}.{}", version.major, version.minor, version.patch),
+        String::new(),
+    ];
+    for tool in found {
+        lines.push(format!("{:<20} {}", tool.iso_path.replace('/', "\\"), tool.purpose));
+    }
+    if !missing.is_empty() {
+        lines.push(String::new());
+        lines.push("Not found in the SunPCi software:".to_string());
+        lines.extend(missing.iter().map(|t| t.iso_path.replace('/', "\\")));
+    }
+    lines.push(String::new());
+    lines.join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_iso() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("SUNWspci2");
+        let version = DriverVersion { major: 1, minor: 2, patch: 0 };
+        assert!(build_iso(&source, &version, &dir.path().join("none.iso")).is_err());
+
+        // An older clipboard agent at the top, the matching one under 1.2
+        for (path, data) in [
+            ("defaults/7.01/sunpc/redir.sys", "redir"),
+            ("drivers/win95/sunclip.exe", "old"),
+            ("1.2/drivers/win95/sunclip.exe", "new"),
+        ] {
+            let path = source.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, data).unwrap();
+        }
+
+        let dest = dir.path().join("tools/tools.iso");
+        let built = build_iso(&source, &version, &dest).unwrap();
+        assert_eq!(built.files, ["DOS/REDIR.SYS", "WIN9X/SUNCLIP.EXE"]);
+        assert_eq!(built.missing.len(), TOOLS.len() - 2);
+        let image = std::fs::read(&dest).unwrap();
+        assert!(image.windows(3).any(|w| w == b"new"));
+        assert!(!image.windows(3).any(|w| w == b"old"));
+
+        // Built again over the old one
+        std::fs::write(source.join("defaults/7.01/sunpc/sunpcnet.exe"), "net").unwrap();
+        assert_eq!(build_iso(&source, &version, &dest).unwrap().files.len(), 3);
+    }
+}
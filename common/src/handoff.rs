@@ -0,0 +1,201 @@
+//! Handing a running session from one frontend to another.
+//!
+//! The guest keeps running when a frontend exits, so another frontend (on
+//! the local console, or over SSH with X forwarding) can pick it up. The
+//! driver records which open handle owns the session
+//! (`DriverHandle::claim_session`) along with the owner's control port.
+//! Taking over goes:
+//!
+//! 1. The adopting frontend reads the owner with `DriverHandle::get_owner`.
+//! 2. If there is one, it sends `release` to the owner's control port. The
+//!    owner stops driving the guest (input, audio, pass-through CD, media
+//!    locks), releases ownership and answers with a `HandoffState`.
+//! 3. The adopter runs `check_adoption` against the state, claims the
+//!    session, locks the media and maps the framebuffer.
+//!
+//! A session whose owner exited has no owner and is adopted with the
+//! adopter's own configuration standing in for the state.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::{AppConfig, StorageConfig};
+use crate::ioctl::{DriverVersion, flags};
+use crate::media_check::{self, MediaSlot};
+
+/// Version of the `HandoffState` format
+pub const HANDOFF_VERSION: u32 = 1;
+
+/// What the releasing frontend knows about the session
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandoffState {
+    /// `HANDOFF_VERSION` of the releasing frontend
+    pub version: u32,
+    /// Releasing frontend's package version
+    pub frontend_version: String,
+    /// Driver version the releasing frontend saw
+    pub driver_version: [u32; 3],
+    /// Flags the session was started with, if known
+    pub session_flags: Option<u32>,
+    /// Media the session is using
+    pub storage: StorageConfig,
+    /// What is in the CD-ROM drive (image path or host device)
+    pub cdrom: Option<String>,
+    /// Whether the CD-ROM was served by the frontend (CUE image or host
+    /// drive) and must be served again by the adopter
+    pub cdrom_served: bool,
+    /// Profile name the session was advertised under
+    pub profile_name: String,
+}
+
+impl HandoffState {
+    /// State of a session being released
+    pub fn new(driver: &DriverVersion, session_flags: Option<u32>, storage: StorageConfig) -> Self {
+        Self {
+            version: HANDOFF_VERSION,
+            frontend_version: env!("CARGO_PKG_VERSION").to_string(),
+            driver_version: [driver.major, driver.minor, driver.patch],
+            session_flags,
+            storage,
+            cdrom: None,
+            cdrom_served: false,
+            profile_name: String::new(),
+        }
+    }
+
+    /// Stand-in state for a session with no owner to ask
+    pub fn orphaned(driver: &DriverVersion, config: &AppConfig) -> Self {
+        Self {
+            profile_name: config.remote.profile_name.clone(),
+            ..Self::new(driver, None, config.storage.clone())
+        }
+    }
+
+    /// Encode for the control API
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    /// Decode a `release` answer
+    pub fn from_json(json: &str) -> Result<Self, HandoffError> {
+        serde_json::from_str(json).map_err(|e| HandoffError::BadState(e.to_string()))
+    }
+}
+
+/// Why a session cannot be taken over
+#[derive(Debug, Error)]
+pub enum HandoffError {
+    #[error("no session is running")]
+    NotRunning,
+
+    #[error("driver: {0}")]
+    Driver(String),
+
+    #[error("the owning frontend (pid {0}) has no control port to ask it to release the session")]
+    NoControlPort(u32),
+
+    #[error("the owning frontend did not release the session: {0}")]
+    Refused(String),
+
+    #[error("bad handoff state: {0}")]
+    BadState(String),
+
+    #[error("handoff format {0} is newer than this frontend supports ({HANDOFF_VERSION})")]
+    Version(u32),
+
+    #[error("session was started under driver {0}, but the driver is now {1}")]
+    DriverChanged(String, String),
+
+    #[error("{0}")]
+    Media(String),
+}
+
+/// Check that this frontend can drive a session described by `state`
+///
+/// `driver` is the version this frontend sees and `config` its own
+/// configuration. Returns warnings about features the session uses that
+/// this frontend will not provide; hard incompatibilities are errors.
+pub fn check_adoption(
+    state: &HandoffState,
+    driver: &DriverVersion,
+    config: &AppConfig,
+) -> Result<Vec<String>, HandoffError> {
+    if state.version > HANDOFF_VERSION {
+        return Err(HandoffError::Version(state.version));
+    }
+
+    // A different driver means the module was reloaded under the session
+    let current = [driver.major, driver.minor, driver.patch];
+    if state.driver_version != current {
+        let version = |v: [u32; 3]| format!("{}.{}.{}", v[0], v[1], v[2]);
+        return Err(HandoffError::DriverChanged(version(state.driver_version), version(current)));
+    }
+
+    // The guest is using the hard disks, so they must be reachable from here
+    let report = media_check::check_media(&state.storage);
+    let mut warnings = Vec::new();
+    for issue in &report.issues {
+        match issue.slot {
+            MediaSlot::PrimaryDisk | MediaSlot::SecondaryDisk => {
+                return Err(HandoffError::Media(issue.to_string()));
+            }
+            _ => warnings.push(format!("{} will be ejected", issue)),
+        }
+    }
+
+    if let Some(session_flags) = state.session_flags {
+        if session_flags & flags::NETWORK_ENABLED != 0 && !config.network.enabled {
+            warnings.push("the guest has a network adapter but networking is disabled here".to_string());
+        }
+        if session_flags & flags::CLIPBOARD_ENABLED != 0 && !config.clipboard.enabled {
+            warnings.push("clipboard sharing is disabled here".to_string());
+        }
+    }
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DiskConfig;
+
+    fn version(patch: u32) -> DriverVersion {
+        DriverVersion { major: 0, minor: 1, patch }
+    }
+
+    #[test]
+    fn test_check_adoption() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("c.img");
+        std::fs::write(&disk, vec![0u8; 4096]).unwrap();
+
+        let mut config = AppConfig::default();
+        config.storage.primary_disk = Some(DiskConfig {
+            path: disk.clone(),
+            bootable: true,
+            content_id: None,
+            overlay: None,
+        });
+        let mut state = HandoffState::new(&version(0), Some(flags::NETWORK_ENABLED), config.storage.clone());
+        let decoded = HandoffState::from_json(&state.to_json()).unwrap();
+        assert_eq!(decoded.to_json(), state.to_json());
+        assert!(HandoffState::from_json("Cannot release").is_err());
+
+        // Networking is off in the default config
+        let warnings = check_adoption(&state, &version(0), &config).unwrap();
+        assert_eq!(warnings.len(), 1);
+        config.network.enabled = true;
+        assert!(check_adoption(&state, &version(0), &config).unwrap().is_empty());
+
+        assert!(matches!(
+            check_adoption(&state, &version(1), &config),
+            Err(HandoffError::DriverChanged(..))
+        ));
+
+        std::fs::remove_file(&disk).unwrap();
+        assert!(matches!(check_adoption(&state, &version(0), &config), Err(HandoffError::Media(_))));
+
+        state.version = HANDOFF_VERSION + 1;
+        assert!(matches!(check_adoption(&state, &version(0), &config), Err(HandoffError::Version(_))));
+    }
+}
@@ -0,0 +1,283 @@
+//! Searchable history of what the guest screen showed.
+//!
+//! While a session runs the frontend records snapshots: the screen text
+//! whenever the guest is in text mode (see `text_screen`), and now and then
+//! a thumbnail of the display. Each session gets a directory under
+//! `<data dir>/history`, named after the time it started:
+//!
+//! ```text
+//! history/1767225600000/
+//!     snapshots.jsonl        one Snapshot per line, oldest first
+//!     thumb-1767225630000.png
+//! ```
+//!
+//! `search` answers questions like "when did the installer show error 734?"
+//! with the time each matching line first appeared and the thumbnail taken
+//! closest before it.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+
+/// File holding a session's snapshots
+const SNAPSHOTS_FILE: &str = "snapshots.jsonl";
+
+/// One recorded moment of a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Milliseconds since the Unix epoch
+    pub time_ms: u64,
+    /// Screen text, if the guest was in text mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub text: Vec<String>,
+    /// Thumbnail file name within the session directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+/// A recorded session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistorySession {
+    /// Directory name under the history root
+    pub id: String,
+    /// When the session started, in milliseconds since the Unix epoch
+    pub started_ms: u64,
+    /// Number of snapshots recorded
+    pub snapshots: usize,
+}
+
+/// A line of screen text matching a search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryMatch {
+    /// Session the line was seen in
+    pub session: String,
+    /// When the line appeared
+    pub time_ms: u64,
+    /// The matching line
+    pub line: String,
+    /// Latest thumbnail taken at or before the line appeared
+    pub thumbnail: Option<PathBuf>,
+}
+
+/// Default location of the history
+pub fn history_dir() -> PathBuf {
+    AppConfig::data_dir().join("history")
+}
+
+/// Current time in milliseconds since the Unix epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Appends the snapshots of one running session
+#[derive(Debug)]
+pub struct SessionRecorder {
+    id: String,
+    dir: PathBuf,
+    file: File,
+    /// Text of the last snapshot, to skip ones where nothing changed
+    last_text: Vec<String>,
+}
+
+impl SessionRecorder {
+    /// Start recording a session that started at `started_ms`
+    pub fn start(root: &Path, started_ms: u64) -> io::Result<Self> {
+        let id = started_ms.to_string();
+        let dir = root.join(&id);
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(SNAPSHOTS_FILE))?;
+        Ok(Self {
+            id,
+            dir,
+            file,
+            last_text: Vec::new(),
+        })
+    }
+
+    /// Session id (directory name)
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Where to save a thumbnail taken at `time_ms`
+    pub fn thumbnail_path(&self, time_ms: u64) -> PathBuf {
+        self.dir.join(format!("thumb-{}.png", time_ms))
+    }
+
+    /// Record a snapshot
+    ///
+    /// A snapshot without a thumbnail whose text is the same as the last
+    /// one adds nothing and is skipped. Returns whether it was written.
+    pub fn record(&mut self, snapshot: &Snapshot) -> io::Result<bool> {
+        if snapshot.thumbnail.is_none() && snapshot.text == self.last_text {
+            return Ok(false);
+        }
+        let mut line = serde_json::to_string(snapshot).map_err(io::Error::other)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.last_text = snapshot.text.clone();
+        Ok(true)
+    }
+}
+
+/// Recorded sessions, newest first
+pub fn sessions(root: &Path) -> Vec<HistorySession> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut sessions: Vec<HistorySession> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let id = entry.file_name().into_string().ok()?;
+            let started_ms = id.parse().ok()?;
+            let snapshots = snapshots(root, &id).map(|s| s.len()).unwrap_or(0);
+            Some(HistorySession { id, started_ms, snapshots })
+        })
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.started_ms));
+    sessions
+}
+
+/// A session's snapshots, oldest first
+///
+/// A line that cannot be parsed (a write cut short by a crash) is skipped.
+pub fn snapshots(root: &Path, session: &str) -> io::Result<Vec<Snapshot>> {
+    let file = File::open(root.join(session).join(SNAPSHOTS_FILE))?;
+    Ok(BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// Lines of screen text containing `query` (ignoring case), newest first
+///
+/// A line is reported when it appears, not again for every snapshot it
+/// stays on screen. At most `limit` matches are returned.
+pub fn search(root: &Path, query: &str, limit: usize) -> Vec<HistoryMatch> {
+    let query = query.trim().to_lowercase();
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    let mut matches = Vec::new();
+    for session in sessions(root) {
+        let Ok(snapshots) = snapshots(root, &session.id) else {
+            continue;
+        };
+        let dir = root.join(&session.id);
+        let mut thumbnail = None;
+        let mut on_screen: HashSet<String> = HashSet::new();
+        let mut found = Vec::new();
+
+        for snapshot in snapshots {
+            if let Some(name) = &snapshot.thumbnail {
+                thumbnail = Some(dir.join(name));
+            }
+            // Graphics-mode snapshots carry no text and don't end a run
+            if snapshot.text.is_empty() {
+                continue;
+            }
+            let matching: HashSet<String> = snapshot
+                .text
+                .iter()
+                .filter(|line| line.to_lowercase().contains(&query))
+                .map(|line| line.trim().to_string())
+                .collect();
+            for line in matching.difference(&on_screen) {
+                found.push(HistoryMatch {
+                    session: session.id.clone(),
+                    time_ms: snapshot.time_ms,
+                    line: line.clone(),
+                    thumbnail: thumbnail.clone(),
+                });
+            }
+            on_screen = matching;
+        }
+
+        found.sort_by(|a, b| b.time_ms.cmp(&a.time_ms).then_with(|| a.line.cmp(&b.line)));
+        matches.extend(found);
+        if matches.len() >= limit {
+            break;
+        }
+    }
+    matches.truncate(limit);
+    matches
+}
+
+/// Delete all but the `keep` newest sessions
+///
+/// Returns how many sessions were deleted.
+pub fn prune(root: &Path, keep: usize) -> io::Result<usize> {
+    let old = sessions(root).into_iter().skip(keep);
+    let mut removed = 0;
+    for session in old {
+        fs::remove_dir_all(root.join(&session.id))?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn test_record_and_search() {
+        let root = tempfile::tempdir().unwrap();
+        let mut recorder = SessionRecorder::start(root.path(), 1000).unwrap();
+        let snapshot = |time_ms, lines: &[&str], thumbnail: Option<&str>| Snapshot {
+            time_ms,
+            text: text(lines),
+            thumbnail: thumbnail.map(str::to_string),
+        };
+
+        assert!(recorder.record(&snapshot(1000, &[], Some("thumb-1000.png"))).unwrap());
+        assert!(recorder.record(&snapshot(2000, &["Setup is copying files"], None)).unwrap());
+        assert!(recorder.record(&snapshot(3000, &["Error 734: disk full"], None)).unwrap());
+        // Unchanged screen
+        assert!(!recorder.record(&snapshot(4000, &["Error 734: disk full"], None)).unwrap());
+        assert!(recorder.record(&snapshot(5000, &["C:\\>"], None)).unwrap());
+        assert!(recorder.record(&snapshot(6000, &["ERROR 734: disk full"], None)).unwrap());
+
+        let sessions = sessions(root.path());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].started_ms, 1000);
+        assert_eq!(sessions[0].snapshots, 5);
+
+        let found = search(root.path(), "error 734", 10);
+        let times: Vec<u64> = found.iter().map(|m| m.time_ms).collect();
+        assert_eq!(times, [6000, 3000]);
+        assert_eq!(found[1].line, "Error 734: disk full");
+        assert_eq!(found[1].thumbnail, Some(root.path().join("1000").join("thumb-1000.png")));
+        assert_eq!(search(root.path(), "error 734", 1).len(), 1);
+        assert!(search(root.path(), "  ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        let root = tempfile::tempdir().unwrap();
+        for started in [1000, 2000, 3000] {
+            SessionRecorder::start(root.path(), started).unwrap();
+        }
+        assert_eq!(prune(root.path(), 2).unwrap(), 1);
+        let ids: Vec<String> = sessions(root.path()).into_iter().map(|s| s.id).collect();
+        assert_eq!(ids, ["3000", "2000"]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Host CD-ROM drives accessed through SG_IO.
+//!
+//! For discs an ISO image cannot represent (audio tracks, mixed-mode and
+//! copy-protected discs that rely on subchannel data or deliberate read
+//! errors), the guest CD-ROM is passed through to a real drive: the driver
+//! queues each guest SCSI command for userspace and `HostCdrom::execute`
+//! runs it unchanged on /dev/srN with the Linux SCSI generic SG_IO ioctl.
+//! The guest then sees the drive's own INQUIRY, TOC and sense data.
+//!
+//! Opening /dev/srN needs read access to the device (usually the `cdrom`
+//! or `optical` group).
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::OFlag;
+
+use crate::ioctl::{SCSI_SENSE_MAX_LEN, ScsiRequest, ScsiResponse, scsi_direction};
+
+/// Where the kernel lists block devices
+const SYSFS_BLOCK: &str = "/sys/class/block";
+
+/// SG_IO request code (<scsi/sg.h>)
+const SG_IO: u32 = 0x2285;
+
+/// sg_io_hdr.interface_id
+const SG_INTERFACE_ID: i32 = b'S' as i32;
+
+/// sg_io_hdr.dxfer_direction values
+const SG_DXFER_NONE: i32 = -1;
+const SG_DXFER_TO_DEV: i32 = -2;
+const SG_DXFER_FROM_DEV: i32 = -3;
+
+/// Driver status: sense data was returned (not an error by itself)
+const DRIVER_SENSE: u16 = 0x08;
+
+/// Per-command timeout; slow enough for a drive spinning up
+const COMMAND_TIMEOUT_MS: u32 = 30_000;
+
+/// Sense buffer size requested from the kernel
+const SENSE_BUF_LEN: usize = 32;
+
+/// struct sg_io_hdr from <scsi/sg.h>
+#[repr(C)]
+#[derive(Debug)]
+struct SgIoHdr {
+    interface_id: i32,
+    dxfer_direction: i32,
+    cmd_len: u8,
+    mx_sb_len: u8,
+    iovec_count: u16,
+    dxfer_len: u32,
+    dxferp: *mut u8,
+    cmdp: *const u8,
+    sbp: *mut u8,
+    timeout: u32,
+    flags: u32,
+    pack_id: i32,
+    usr_ptr: *mut u8,
+    status: u8,
+    masked_status: u8,
+    msg_status: u8,
+    sb_len_wr: u8,
+    host_status: u16,
+    driver_status: u16,
+    resid: i32,
+    duration: u32,
+    info: u32,
+}
+
+nix::ioctl_readwrite_bad!(sg_io, SG_IO, SgIoHdr);
+
+/// A host optical drive
+#[derive(Debug)]
+pub struct HostCdrom {
+    file: File,
+    path: PathBuf,
+}
+
+impl HostCdrom {
+    /// Open a drive such as /dev/sr0
+    ///
+    /// Opened non-blocking so an empty tray does not fail the open; the
+    /// guest finds out through TEST UNIT READY like on real hardware.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .custom_flags(OFlag::O_NONBLOCK.bits())
+            .open(path)?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Device path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Run a SCSI command on the drive
+    ///
+    /// `data` is the transfer buffer for the request's direction. SCSI
+    /// errors (CHECK CONDITION) are returned in the response; an `Err`
+    /// means the command could not be delivered at all.
+    pub fn execute(&self, request: &ScsiRequest, data: &mut [u8]) -> io::Result<ScsiResponse> {
+        let mut sense = [0u8; SENSE_BUF_LEN];
+        let mut hdr = sg_header(request, data, &mut sense);
+        unsafe { sg_io(self.file.as_raw_fd(), &mut hdr) }.map_err(io::Error::from)?;
+
+        if hdr.host_status != 0 || hdr.driver_status & !DRIVER_SENSE != 0 {
+            return Err(io::Error::other(format!(
+                "SCSI command 0x{:02x} failed on {}: host status {:#x}, driver status {:#x}",
+                request.cdb[0],
+                self.path.display(),
+                hdr.host_status,
+                hdr.driver_status
+            )));
+        }
+        Ok(sg_response(&hdr, &sense))
+    }
+}
+
+/// Build the SG_IO header for a request
+///
+/// The pointers in the header borrow `request`, `data` and `sense`.
+fn sg_header(request: &ScsiRequest, data: &mut [u8], sense: &mut [u8]) -> SgIoHdr {
+    let len = (request.data_len as usize).min(data.len());
+    let direction = match request.data_direction {
+        _ if len == 0 => SG_DXFER_NONE,
+        scsi_direction::READ => SG_DXFER_FROM_DEV,
+        scsi_direction::WRITE => SG_DXFER_TO_DEV,
+        _ => SG_DXFER_NONE,
+    };
+    let len = if direction == SG_DXFER_NONE { 0 } else { len };
+
+    SgIoHdr {
+        interface_id: SG_INTERFACE_ID,
+        dxfer_direction: direction,
+        cmd_len: request.cdb_len.min(request.cdb.len() as u32) as u8,
+        mx_sb_len: sense.len().min(u8::MAX as usize) as u8,
+        iovec_count: 0,
+        dxfer_len: len as u32,
+        dxferp: data.as_mut_ptr(),
+        cmdp: request.cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: COMMAND_TIMEOUT_MS,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    }
+}
+
+/// Turn a completed SG_IO header into the driver's SCSI response
+fn sg_response(hdr: &SgIoHdr, sense: &[u8]) -> ScsiResponse {
+    let mut response = ScsiResponse {
+        status: hdr.status,
+        data_len: hdr.dxfer_len.saturating_sub(hdr.resid.max(0) as u32),
+        ..Default::default()
+    };
+    let sense_len = (hdr.sb_len_wr as usize).min(SCSI_SENSE_MAX_LEN).min(sense.len());
+    response.sense[..sense_len].copy_from_slice(&sense[..sense_len]);
+    response.sense_len = sense_len as u8;
+    response
+}
+
+/// Optical drives on this host (/dev/srN), sorted
+pub fn list_host_cdroms() -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(SYSFS_BLOCK) else {
+        return Vec::new();
+    };
+    let mut drives: Vec<PathBuf> = entries
+        .flatten()
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|name| name.starts_with("sr"))
+        .map(|name| Path::new("/dev").join(name))
+        .collect();
+    drives.sort();
+    drives
+}
+
+/// Vendor and model of a drive as reported by the kernel (e.g. "TSSTcorp CDDVDW SH-224")
+pub fn drive_model(path: &Path) -> Option<String> {
+    let name = path.file_name()?;
+    let device = Path::new(SYSFS_BLOCK).join(name).join("device");
+    let read = |file: &str| std::fs::read_to_string(device.join(file)).ok();
+    let model = [read("vendor")?, read("model")?]
+        .iter()
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!model.is_empty()).then_some(model)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sg_header() {
+        assert_eq!(std::mem::size_of::<SgIoHdr>(), 88);
+
+        // READ CAPACITY into an 8-byte buffer
+        let request = ScsiRequest::new_cdb10([0x25, 0, 0, 0, 0, 0, 0, 0, 0, 0]).with_read(8);
+        let mut data = [0u8; 64];
+        let mut sense = [0u8; SENSE_BUF_LEN];
+        let mut hdr = sg_header(&request, &mut data, &mut sense);
+        assert_eq!(hdr.dxfer_direction, SG_DXFER_FROM_DEV);
+        assert_eq!((hdr.cmd_len, hdr.dxfer_len), (10, 8));
+
+        // Completed short with CHECK CONDITION and sense data
+        hdr.status = 0x02;
+        hdr.resid = 8;
+        hdr.sb_len_wr = 24;
+        sense[2] = 0x02;
+        sense[12] = 0x3A;
+        let response = sg_response(&hdr, &sense);
+        assert!(response.is_check_condition());
+        assert_eq!(response.data_len, 0);
+        assert_eq!(response.sense_len as usize, SCSI_SENSE_MAX_LEN);
+        assert_eq!((response.sense_key(), response.asc()), (Some(0x02), Some(0x3A)));
+
+        // TEST UNIT READY moves no data
+        let request = ScsiRequest::new_cdb6([0; 6]);
+        let hdr = sg_header(&request, &mut data, &mut sense);
+        assert_eq!((hdr.dxfer_direction, hdr.dxfer_len), (SG_DXFER_NONE, 0));
+    }
+}
@@ -0,0 +1,183 @@
+//! Locating disk images that moved.
+//!
+//! A profile stores each disk's absolute path plus a content id: a hash of
+//! the image's size and header sectors (MBR with the SunPCi geometry block,
+//! and the partition boot sector). Those don't change as the guest writes
+//! files, so the id survives normal use of the disk. When the stored
+//! path no longer exists - the profile was copied to another machine, or a
+//! directory was renamed - the configured search paths are scanned for a
+//! file with the same id, preferring one with the original file name.
+//!
+//! The stored path always wins while it exists, so re-partitioning or
+//! reformatting a disk in the guest (which changes its id) never detaches
+//! it; the id is refreshed whenever the image is found. Images created by
+//! the same version at the same size share identical headers, so an id
+//! alone can be ambiguous; then only a candidate with the original file
+//! name is accepted.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Bytes read from the start of the image (MBR and the first track)
+const HEADER_BYTES: u64 = 64 * 512;
+
+/// How deep below each search path to look for images
+const MAX_SEARCH_DEPTH: usize = 2;
+
+/// Image file extensions considered when searching
+const IMAGE_EXTENSIONS: &[&str] = &["img", "dsk", "hdd", "vhd", "raw"];
+
+/// 64-bit FNV-1a, stable across builds unlike `DefaultHasher`
+fn fnv1a(hash: u64, data: &[u8]) -> u64 {
+    data.iter()
+        .fold(hash, |h, &b| (h ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Compute the content id of an image
+pub fn content_id(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.seek(SeekFrom::End(0))?;
+    file.seek(SeekFrom::Start(0))?;
+
+    let mut header = Vec::with_capacity(HEADER_BYTES as usize);
+    file.take(HEADER_BYTES).read_to_end(&mut header)?;
+
+    let hash = fnv1a(0xcbf2_9ce4_8422_2325, &size.to_le_bytes());
+    let hash = fnv1a(hash, &header);
+    Ok(format!("fnv1a64:{:016x}", hash))
+}
+
+/// Find an image, following it to its new location if it moved
+///
+/// Returns `path` itself if it exists; otherwise the first match in
+/// `search_paths`, or None.
+pub fn resolve_image(path: &Path, id: Option<&str>, search_paths: &[PathBuf]) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    let matches_id = |p: &Path| id.is_none_or(|id| content_id(p).is_ok_and(|c| c == id));
+
+    let file_name = path.file_name()?;
+    let mut by_id = Vec::new();
+    for dir in search_paths {
+        for candidate in find_images(dir, MAX_SEARCH_DEPTH) {
+            if candidate.as_path() == path {
+                continue;
+            }
+            let same_name = candidate.file_name() == Some(file_name);
+            match id {
+                // Without an id, only the file name can identify the image
+                None if same_name => return Some(candidate),
+                None => {}
+                Some(_) if !matches_id(&candidate) => {}
+                Some(_) if same_name => return Some(candidate),
+                Some(_) => by_id.push(candidate),
+            }
+        }
+    }
+
+    // A renamed image is only trusted if its id is unique
+    by_id.sort();
+    by_id.dedup();
+    if by_id.len() == 1 { by_id.pop() } else { None }
+}
+
+/// List image files in `dir` and its subdirectories, up to `depth` levels
+fn find_images(dir: &Path, depth: usize) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut files = Vec::new();
+    let mut subdirs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => subdirs.push(path),
+            Ok(_) if is_image_file(&path) => files.push(path),
+            _ => {}
+        }
+    }
+
+    files.sort();
+    subdirs.sort();
+    if depth > 0 {
+        for subdir in subdirs {
+            files.extend(find_images(&subdir, depth - 1));
+        }
+    }
+    files
+}
+
+fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_image(path: &Path, marker: u8) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut data = vec![0u8; 4096];
+        data[0] = marker;
+        std::fs::write(path, data).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_moved_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let old = dir.path().join("old/c.img");
+        write_image(&old, 1);
+        let id = content_id(&old).unwrap();
+
+        // Moved to a new directory under the search path
+        let moved = dir.path().join("new/disks/c.img");
+        std::fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        std::fs::rename(&old, &moved).unwrap();
+        write_image(&dir.path().join("new/other.img"), 2);
+
+        let search = vec![dir.path().join("new")];
+        assert_eq!(resolve_image(&old, Some(&id), &search), Some(moved.clone()));
+
+        // Renamed: found by id alone while it is unique
+        let renamed = dir.path().join("new/disks/drive-c.img");
+        std::fs::rename(&moved, &renamed).unwrap();
+        assert_eq!(resolve_image(&old, Some(&id), &search), Some(renamed.clone()));
+        assert_eq!(resolve_image(&old, None, &search), None);
+
+        // A second copy makes the id ambiguous
+        std::fs::copy(&renamed, dir.path().join("new/copy.img")).unwrap();
+        assert_eq!(resolve_image(&old, Some(&id), &search), None);
+    }
+
+    #[test]
+    fn test_content_id_tracks_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("c.img");
+        write_image(&path, 1);
+        let id = content_id(&path).unwrap();
+        assert!(id.starts_with("fnv1a64:"));
+
+        // Writes past the header keep the id; header changes do not
+        let mut data = std::fs::read(&path).unwrap();
+        data.resize(HEADER_BYTES as usize * 2, 0);
+        let size_changed = {
+            std::fs::write(&path, &data).unwrap();
+            content_id(&path).unwrap()
+        };
+        *data.last_mut().unwrap() = 0xFF;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(content_id(&path).unwrap(), size_changed);
+        data[1] = 0xFF;
+        std::fs::write(&path, &data).unwrap();
+        assert_ne!(content_id(&path).unwrap(), size_changed);
+        assert_ne!(id, size_changed);
+
+        // The stored path wins even after the id changed
+        assert_eq!(resolve_image(&path, Some(&id), &[]), Some(path));
+    }
+}
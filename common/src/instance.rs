@@ -0,0 +1,63 @@
+//! One frontend per card.
+//!
+//! Two frontends driving the same card fight over its ioctls, so the first
+//! one started for a card listens for control requests (see `control`) on
+//! a Unix socket in the runtime directory, and a frontend started later for
+//! that card hands its command line to the first one there and exits. The
+//! socket is removed when the first frontend exits; one left behind by a
+//! crash is noticed by nobody answering on it, and replaced.
+
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::config::AppConfig;
+use crate::control::{ControlClient, ControlServer};
+
+/// Server of the frontend that claimed its card, until taken
+static SERVER: Mutex<Option<ControlServer>> = Mutex::new(None);
+
+/// Who has the card
+pub enum Instance {
+    /// This process; its requests are waiting in `take_server`
+    First,
+    /// Another frontend, reachable through the client
+    Running(ControlClient),
+}
+
+/// Socket the frontend of `card` listens on
+pub fn socket_path(card: u32) -> PathBuf {
+    AppConfig::runtime_dir().join(format!("sunpci{}.sock", card))
+}
+
+/// Make this process the frontend of `card`, or connect to the one that is
+pub fn claim(card: u32) -> io::Result<Instance> {
+    let path = socket_path(card);
+    if let Some(dir) = path.parent() {
+        DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    }
+    // A second try after removing a socket nobody answers on
+    for _ in 0..2 {
+        match ControlServer::start_local(&path) {
+            Ok(server) => {
+                *SERVER.lock().unwrap_or_else(|e| e.into_inner()) = Some(server);
+                return Ok(Instance::First);
+            }
+            Err(e) if e.kind() == io::ErrorKind::AddrInUse => {}
+            Err(e) => return Err(e),
+        }
+        match ControlClient::connect_local(&path) {
+            Ok(client) => return Ok(Instance::Running(client)),
+            Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => fs::remove_file(&path)?,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use", path.display())))
+}
+
+/// Server `claim` started, for the frontend to answer its requests
+pub fn take_server() -> Option<ControlServer> {
+    SERVER.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
@@ -1,12 +1,13 @@
 //! ioctl definitions that mirror the kernel driver interface.
 //!
 //! These definitions must stay in sync with driver/include/uapi/sunpci_ioctl.h
+//! (`cargo xtask abi-check` compares them field by field).
 //! See docs/api-contract.md for the full specification.
 
-use nix::ioctl_none;
-use nix::ioctl_read;
-use nix::ioctl_readwrite;
-use nix::ioctl_write_ptr;
+use std::mem;
+
+use nix::{ioctl_none, ioctl_read, ioctl_readwrite, ioctl_write_ptr};
+use nix::{request_code_none, request_code_read, request_code_readwrite, request_code_write};
 
 /// Magic number for SunPCi ioctls
 pub const SUNPCI_IOC_MAGIC: u8 = b'S';
@@ -17,53 +18,49 @@ pub const SUNPCI_MAX_PATH: usize = 256;
 /// Maximum clipboard size (must match kernel, must fit in ioctl ~8KB max)
 pub const SUNPCI_MAX_CLIPBOARD: usize = 4096;
 
-// ============================================================================
-// ioctl Command Numbers
-// ============================================================================
-
-pub mod cmd {
-    // Session management
-    pub const GET_VERSION: u8 = 0;
-    pub const GET_STATUS: u8 = 1;
-    pub const START_SESSION: u8 = 2;
-    pub const STOP_SESSION: u8 = 3;
-    pub const RESET_SESSION: u8 = 4;
-
-    // Display
-    pub const GET_DISPLAY: u8 = 10;
-    pub const SET_DISPLAY: u8 = 11;
-    pub const GET_FRAMEBUFFER: u8 = 12;
+/// Maximum total size of a chunked clipboard transfer (must match kernel)
+pub const SUNPCI_MAX_CLIPBOARD_TRANSFER: usize = 16 * 1024 * 1024;
 
-    // Storage
-    pub const MOUNT_DISK: u8 = 20;
-    pub const UNMOUNT_DISK: u8 = 21;
-    pub const MOUNT_CDROM: u8 = 22;
-    pub const EJECT_CDROM: u8 = 23;
-    pub const MOUNT_FLOPPY: u8 = 24;
-    pub const EJECT_FLOPPY: u8 = 25;
+/// A 64-bit value stored as two u32 halves, low half first
+///
+/// The kernel structs split 64-bit fields into `_lo`/`_hi` pairs so the
+/// layout is the same for 32-bit and 64-bit userspace; a plain `u64` would
+/// be 8-byte aligned on one and 4-byte aligned on the other. Use this for
+/// such pairs rather than shifting the halves by hand.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SplitU64 {
+    lo: u32,
+    hi: u32,
+}
 
-    // Input
-    pub const KEYBOARD_EVENT: u8 = 30;
-    pub const MOUSE_EVENT: u8 = 31;
+impl SplitU64 {
+    pub const fn new(value: u64) -> Self {
+        Self {
+            lo: value as u32,
+            hi: (value >> 32) as u32,
+        }
+    }
 
-    // Clipboard
-    pub const SET_CLIPBOARD: u8 = 40;
-    pub const GET_CLIPBOARD: u8 = 41;
+    pub const fn get(self) -> u64 {
+        ((self.hi as u64) << 32) | self.lo as u64
+    }
 
-    // Filesystem redirection
-    pub const ADD_DRIVE_MAP: u8 = 50;
-    pub const REMOVE_DRIVE_MAP: u8 = 51;
+    pub fn set(&mut self, value: u64) {
+        *self = Self::new(value);
+    }
+}
 
-    // Network
-    pub const SET_NETWORK: u8 = 60;
-    pub const GET_NETWORK: u8 = 61;
+impl From<u64> for SplitU64 {
+    fn from(value: u64) -> Self {
+        Self::new(value)
+    }
+}
 
-    // Audio
-    pub const GET_AUDIO_FORMAT: u8 = 70;
-    pub const SET_AUDIO_VOLUME: u8 = 71;
-    pub const GET_AUDIO_VOLUME: u8 = 72;
-    pub const GET_AUDIO_STATUS: u8 = 73;
-    pub const READ_AUDIO: u8 = 74;
+impl From<SplitU64> for u64 {
+    fn from(value: SplitU64) -> Self {
+        value.get()
+    }
 }
 
 // ============================================================================
@@ -71,14 +68,22 @@ pub mod cmd {
 // ============================================================================
 
 /// Driver version information
+///
+/// Orders by major, then minor, then patch.
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DriverVersion {
     pub major: u32,
     pub minor: u32,
     pub patch: u32,
 }
 
+impl std::fmt::Display for DriverVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
 /// Session state
 #[repr(u32)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -89,22 +94,22 @@ pub enum SessionState {
     Running = 2,
     Stopping = 3,
     Error = 4,
+    /// Guest frozen for a state save or restore
+    Suspended = 5,
 }
 
 /// Session status
 /// 
-/// Note: Uses explicit lo/hi u32 pairs for 64-bit values to ensure
-/// consistent struct layout between 32-bit and 64-bit architectures.
-/// Reserved fields maintain ABI compatibility with older versions.
+/// Note: 64-bit values are `SplitU64` to keep the layout the same on
+/// 32-bit and 64-bit architectures. Reserved fields maintain ABI
+/// compatibility with older versions.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct SessionStatus {
     pub state: u32,
-    _reserved1: u32,         // was cpu_usage - not meaningful for real hardware
-    _reserved2: u32,         // was memory_used_lo
-    _reserved3: u32,         // was memory_used_hi
-    pub uptime_ns_lo: u32,   // nanoseconds (low 32 bits)
-    pub uptime_ns_hi: u32,   // nanoseconds (high 32 bits)
+    pub guest_idle_ms: u32,  // was cpu_usage; ms since the guest last signalled
+    _reserved2: SplitU64,    // was memory_used
+    pub uptime_ns: SplitU64, // nanoseconds
     pub disk_activity: u32,  // bitmap of active drives
     pub network_rx_packets: u32,
     pub network_tx_packets: u32,
@@ -114,7 +119,36 @@ pub struct SessionStatus {
 impl SessionStatus {
     /// Get uptime_ns as u64
     pub fn uptime_ns(&self) -> u64 {
-        ((self.uptime_ns_hi as u64) << 32) | (self.uptime_ns_lo as u64)
+        self.uptime_ns.get()
+    }
+}
+
+/// Claim flags for `SessionOwner`
+pub mod claim_flags {
+    /// Take the session over from a live owner
+    pub const FORCE: u16 = 1 << 0;
+}
+
+/// Frontend driving the running session
+///
+/// The owner is the open driver file that last claimed the session; it is
+/// cleared when that file is closed, so a crashed frontend leaves the
+/// session unowned rather than stuck.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SessionOwner {
+    /// Owning process (0 if none)
+    pub pid: u32,
+    /// Owner's control API port (0 if none)
+    pub control_port: u16,
+    /// `claim_flags` on claim
+    pub flags: u16,
+}
+
+impl SessionOwner {
+    /// Whether some frontend owns the session
+    pub fn is_owned(&self) -> bool {
+        self.pid != 0
     }
 }
 
@@ -124,6 +158,18 @@ pub mod flags {
     pub const CLIPBOARD_ENABLED: u32 = 1 << 1;
     pub const CLIPBOARD_TO_HOST: u32 = 1 << 2;
     pub const CLIPBOARD_TO_GUEST: u32 = 1 << 3;
+    /// Start suspended; the saved state is written and then resumed
+    pub const RESUME: u32 = 1 << 4;
+}
+
+/// Drive the guest BIOS boots from
+pub mod boot_device {
+    /// First hard disk, then the CD-ROM
+    pub const DEFAULT: u32 = 0;
+    pub const HARD_DISK: u32 = 1;
+    /// El Torito boot from the CD-ROM
+    pub const CDROM: u32 = 2;
+    pub const FLOPPY: u32 = 3;
 }
 
 /// Session configuration for starting (ioctl version)
@@ -137,6 +183,8 @@ pub struct IoctlSessionConfig {
     pub primary_disk: [u8; SUNPCI_MAX_PATH],
     pub secondary_disk: [u8; SUNPCI_MAX_PATH],
     pub bios_path: [u8; SUNPCI_MAX_PATH],
+    /// Boot drive (`boot_device::*`)
+    pub boot_device: u32,
 }
 
 impl Default for IoctlSessionConfig {
@@ -147,6 +195,7 @@ impl Default for IoctlSessionConfig {
             primary_disk: [0; SUNPCI_MAX_PATH],
             secondary_disk: [0; SUNPCI_MAX_PATH],
             bios_path: [0; SUNPCI_MAX_PATH],
+            boot_device: boot_device::DEFAULT,
         }
     }
 }
@@ -168,11 +217,41 @@ pub struct DisplayInfo {
     pub width: u32,
     pub height: u32,
     pub color_depth: u32,    // 1, 2, 4, 8, 15, 16, 24, 32
-    pub mode: u32,           // 0=text, 1=graphics
+    pub mode: u32,           // 0=text, 1=graphics, plus display_mode flags
     pub text_cols: u32,      // for text mode
     pub text_rows: u32,      // for text mode
 }
 
+/// Display mode values and flags (DisplayInfo::mode)
+///
+/// The low byte holds the mode type; the upper bits are flags.
+pub mod display_mode {
+    pub const TEXT: u32 = 0;
+    pub const GRAPHICS: u32 = 1;
+    pub const TYPE_MASK: u32 = 0xFF;
+    /// Mode is interlaced: the framebuffer holds a single field of height/2 lines
+    pub const INTERLACED: u32 = 1 << 8;
+    /// The current field is the odd (bottom) field
+    pub const ODD_FIELD: u32 = 1 << 9;
+}
+
+impl DisplayInfo {
+    /// Whether the guest is in text mode
+    pub fn is_text(&self) -> bool {
+        self.mode & display_mode::TYPE_MASK == display_mode::TEXT
+    }
+
+    /// Whether the guest is delivering interlaced fields
+    pub fn is_interlaced(&self) -> bool {
+        self.mode & display_mode::INTERLACED != 0
+    }
+
+    /// Whether the current field is the odd (bottom) field
+    pub fn is_odd_field(&self) -> bool {
+        self.mode & display_mode::ODD_FIELD != 0
+    }
+}
+
 /// Display configuration flags
 pub mod display_flags {
     pub const MAINTAIN_ASPECT: u32 = 1 << 0;
@@ -201,15 +280,13 @@ pub enum PixelFormat {
 
 /// Framebuffer information
 /// 
-/// Note: Uses explicit lo/hi u32 pairs for 64-bit values to ensure
-/// consistent struct layout between 32-bit and 64-bit architectures.
+/// Note: 64-bit values are `SplitU64` to keep the layout the same on
+/// 32-bit and 64-bit architectures.
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct FramebufferInfo {
-    pub phys_addr_lo: u32,   // physical address (low 32 bits)
-    pub phys_addr_hi: u32,   // physical address (high 32 bits)
-    pub size_lo: u32,        // buffer size (low 32 bits)
-    pub size_hi: u32,        // buffer size (high 32 bits)
+    pub phys_addr: SplitU64, // physical address
+    pub size: SplitU64,      // buffer size
     pub stride: u32,         // bytes per row
     pub format: u32,         // PixelFormat
 }
@@ -217,15 +294,126 @@ pub struct FramebufferInfo {
 impl FramebufferInfo {
     /// Get physical address as u64
     pub fn phys_addr(&self) -> u64 {
-        ((self.phys_addr_hi as u64) << 32) | (self.phys_addr_lo as u64)
+        self.phys_addr.get()
     }
 
     /// Get size as u64
     pub fn size(&self) -> u64 {
-        ((self.size_hi as u64) << 32) | (self.size_lo as u64)
+        self.size.get()
     }
 }
 
+/// Maximum entries in the mmap region table (must match kernel)
+pub const SUNPCI_MAX_MMAP_REGIONS: usize = 8;
+
+/// mmap region identifiers
+pub mod region_id {
+    pub const FRAMEBUFFER: u32 = 0;
+    pub const TEXT: u32 = 1;
+    pub const AUDIO_RING: u32 = 2;
+    pub const EVENT_RING: u32 = 3;
+    pub const SHMEM: u32 = 4;
+}
+
+/// mmap region access flags
+pub mod region_flags {
+    pub const READ: u32 = 1 << 0;
+    pub const WRITE: u32 = 1 << 1;
+}
+
+/// One mappable region
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmapRegion {
+    pub id: u32,             // region_id::*
+    pub flags: u32,          // region_flags::*
+    pub offset: SplitU64,    // mmap offset in bytes
+    pub size: SplitU64,      // region size
+}
+
+impl MmapRegion {
+    /// Get mmap offset as u64
+    pub fn offset(&self) -> u64 {
+        self.offset.get()
+    }
+
+    /// Get size as u64
+    pub fn size(&self) -> u64 {
+        self.size.get()
+    }
+}
+
+/// Table of mappable regions
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MmapRegions {
+    pub count: u32,
+    pub reserved: u32,
+    pub regions: [MmapRegion; SUNPCI_MAX_MMAP_REGIONS],
+}
+
+impl MmapRegions {
+    /// Look up a region by id
+    pub fn find(&self, id: u32) -> Option<&MmapRegion> {
+        let count = (self.count as usize).min(SUNPCI_MAX_MMAP_REGIONS);
+        self.regions[..count].iter().find(|r| r.id == id)
+    }
+}
+
+/// Entries in the indexed 8-bit palette
+pub const SUNPCI_PALETTE_SIZE: usize = 256;
+
+/// Palette for PixelFormat::Indexed8
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    /// Incremented whenever the guest changes the palette
+    pub generation: u32,
+    pub count: u32,
+    /// Colors as 0x00RRGGBB
+    pub entries: [u32; SUNPCI_PALETTE_SIZE],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            generation: 0,
+            count: 0,
+            entries: [0; SUNPCI_PALETTE_SIZE],
+        }
+    }
+}
+
+impl Palette {
+    /// The valid entries
+    pub fn colors(&self) -> &[u32] {
+        &self.entries[..(self.count as usize).min(SUNPCI_PALETTE_SIZE)]
+    }
+
+    /// Color of an index as [r, g, b]; black past the valid entries
+    pub fn rgb(&self, index: u8) -> [u8; 3] {
+        if index as u32 >= self.count {
+            return [0, 0, 0];
+        }
+        let [_, r, g, b] = self.entries[index as usize].to_be_bytes();
+        [r, g, b]
+    }
+}
+
+/// Text mode cursor
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TextCursor {
+    pub col: u32,
+    pub row: u32,
+    /// First and last scanline of the cursor within the character cell
+    pub start: u8,
+    pub end: u8,
+    /// Non-zero if the guest shows the cursor
+    pub visible: u8,
+    pub reserved: u8,
+}
+
 /// Disk mount flags
 pub mod disk_flags {
     pub const READONLY: u32 = 1 << 0;
@@ -309,6 +497,9 @@ pub const SCSI_CDB_MAX_LEN: usize = 16;
 /// Maximum sense data length (fixed format)
 pub const SCSI_SENSE_MAX_LEN: usize = 18;
 
+/// Maximum data transfer per SCSI command
+pub const SCSI_DATA_MAX_LEN: usize = 65536;
+
 /// SCSI data direction
 pub mod scsi_direction {
     pub const NONE: u32 = 0;
@@ -402,6 +593,21 @@ impl Default for ScsiResponse {
 }
 
 impl ScsiResponse {
+    /// CHECK CONDITION with fixed-format sense data
+    pub fn check_condition(sense_key: u8, asc: u8, ascq: u8) -> Self {
+        let mut response = Self {
+            status: scsi_status::CHECK_CONDITION,
+            sense_len: SCSI_SENSE_MAX_LEN as u8,
+            ..Default::default()
+        };
+        response.sense[0] = 0x70; // current error, fixed format
+        response.sense[2] = sense_key;
+        response.sense[7] = 10; // additional sense length
+        response.sense[12] = asc;
+        response.sense[13] = ascq;
+        response
+    }
+
     /// Check if the command completed successfully
     pub fn is_good(&self) -> bool {
         self.status == scsi_status::GOOD
@@ -431,6 +637,59 @@ impl ScsiResponse {
     }
 }
 
+/// CD-ROM pass-through switch
+///
+/// While enabled, guest CD-ROM commands are queued for userspace (see
+/// `ScsiCommand`) instead of being emulated from an ISO image.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct CdromPassthrough {
+    pub enable: u32,
+    pub reserved: u32,
+    /// Host device being passed through (for status only)
+    pub device: [u8; SUNPCI_MAX_PATH],
+}
+
+impl Default for CdromPassthrough {
+    fn default() -> Self {
+        Self {
+            enable: 0,
+            reserved: 0,
+            device: [0; SUNPCI_MAX_PATH],
+        }
+    }
+}
+
+/// SCSI command exchanged with the driver
+///
+/// Used both to issue a command to the guest's CD-ROM (SCSI_COMMAND) and,
+/// in pass-through mode, to fetch guest commands (SCSI_PT_NEXT) and
+/// answer them (SCSI_PT_COMPLETE). Data moves through `data_ptr`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScsiCommand {
+    pub tag: u32,
+    pub flags: u32,
+    pub request: ScsiRequest,
+    pub response: ScsiResponse,
+    /// Userspace data buffer
+    pub data_ptr: u64,
+    /// Size of the buffer at `data_ptr`
+    pub data_buf_len: u32,
+    pub reserved: u32,
+}
+
+impl ScsiCommand {
+    /// Command with `data` as its transfer buffer
+    pub fn with_buffer(data: &mut [u8]) -> Self {
+        Self {
+            data_ptr: data.as_mut_ptr() as u64,
+            data_buf_len: data.len().min(u32::MAX as usize) as u32,
+            ..Default::default()
+        }
+    }
+}
+
 /// Key event flags
 pub mod key_flags {
     pub const PRESSED: u32 = 1 << 0;
@@ -462,10 +721,58 @@ pub struct MouseEvent {
     pub buttons: u32,        // button state bitmap
 }
 
+/// Absolute mouse event (tablet mode)
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MouseAbsEvent {
+    pub x: u32,              // X position in guest pixels
+    pub y: u32,              // Y position in guest pixels
+    pub dz: i32,             // wheel movement
+    pub buttons: u32,        // button state bitmap
+}
+
+/// Axes on the game port: joystick A X and Y, then joystick B X and Y
+pub const SUNPCI_GAMEPORT_AXES: usize = 4;
+
+/// Game port button flags
+pub mod gameport_buttons {
+    pub const A1: u32 = 1 << 0;
+    pub const A2: u32 = 1 << 1;
+    pub const B1: u32 = 1 << 2;
+    pub const B2: u32 = 1 << 3;
+}
+
+/// Game port state
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GameportEvent {
+    pub axes: [i16; SUNPCI_GAMEPORT_AXES], // -32768 (left, up) to 32767 (right, down)
+    pub buttons: u32,        // buttons held (gameport_buttons::*)
+    pub connected: u32,      // bit n set if axes[n] has a stick on it
+}
+
+/// Keyboard LED flags (same bit order as the PS/2 "set LEDs" command)
+pub mod keyboard_leds {
+    pub const SCROLL_LOCK: u32 = 1 << 0;
+    pub const NUM_LOCK: u32 = 1 << 1;
+    pub const CAPS_LOCK: u32 = 1 << 2;
+}
+
+/// Keyboard LED state
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyboardLeds {
+    pub leds: u32,           // LED bitmap (keyboard_leds::*)
+}
+
 /// Clipboard format
 pub mod clipboard_format {
     pub const TEXT: u32 = 0;
     pub const UNICODE: u32 = 1;
+    /// Device-independent bitmap (Windows CF_DIB layout)
+    pub const BITMAP: u32 = 2;
+    /// Rich Text Format (null-terminated 8-bit RTF document)
+    pub const RTF: u32 = 3;
 }
 
 /// Clipboard data (variable size, up to SUNPCI_MAX_CLIPBOARD)
@@ -487,10 +794,54 @@ impl Default for Clipboard {
     }
 }
 
+/// One part of a chunked clipboard transfer
+///
+/// Data larger than SUNPCI_MAX_CLIPBOARD is sent as consecutive chunks with
+/// increasing `offset`; the driver forwards it to the guest once the chunk
+/// ending at `total_length` arrives. When reading, the caller sets `format`
+/// and `offset` - offset 0 snapshots the guest clipboard, later offsets read
+/// from that snapshot.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ClipboardChunk {
+    pub format: u32,         // Clipboard format (clipboard_format::*)
+    pub total_length: u32,   // Length of the complete transfer
+    pub offset: u32,         // Offset of this chunk within the transfer
+    pub length: u32,         // Bytes of data in this chunk
+    pub data: [u8; SUNPCI_MAX_CLIPBOARD],
+}
+
+impl Default for ClipboardChunk {
+    fn default() -> Self {
+        Self {
+            format: 0,
+            total_length: 0,
+            offset: 0,
+            length: 0,
+            data: [0; SUNPCI_MAX_CLIPBOARD],
+        }
+    }
+}
+
 /// Drive mapping flags
 pub mod drive_flags {
     pub const READONLY: u8 = 1 << 0;
     pub const HIDDEN: u8 = 1 << 1;
+    pub const TZ_OFFSET: u8 = 1 << 2; // tz_offset is set; file times are UTC otherwise
+
+    // How long host file names are shortened to 8.3 names
+    pub const MANGLE_MASK: u8 = 3 << 3;
+    pub const MANGLE_TAIL: u8 = 0x00; // LONGNA~1.TXT, numbered per directory
+    pub const MANGLE_HASH: u8 = 1 << 3; // LONG~K3Z.TXT, from a hash of the name
+    pub const MANGLE_TRUNCATE: u8 = 2 << 3; // LONGNAME.TXT, cut short; names may clash
+
+    // Case of the names the guest sees
+    pub const CASE_MASK: u8 = 3 << 5;
+    pub const CASE_UPPER: u8 = 0x00;
+    pub const CASE_LOWER: u8 = 1 << 5; // guest names are looked up in lower case
+    pub const CASE_PRESERVE: u8 = 2 << 5;
+
+    pub const HIDE_DOTFILES: u8 = 1 << 7; // leave out names starting with '.'
 }
 
 /// Drive mapping
@@ -499,8 +850,10 @@ pub mod drive_flags {
 pub struct DriveMapping {
     pub letter: u8,          // 'E' through 'Z'
     pub flags: u8,
-    pub reserved: u16,
+    pub tz_offset: i16,      // Minutes east of UTC of the guest's file times
     pub path: [u8; SUNPCI_MAX_PATH],
+    pub max_size_mb: u32,    // Most the guest may store under path (0 = no limit)
+    pub used: SplitU64,      // Bytes stored under path already
 }
 
 impl Default for DriveMapping {
@@ -508,8 +861,10 @@ impl Default for DriveMapping {
         Self {
             letter: 0,
             flags: 0,
-            reserved: 0,
+            tz_offset: 0,
             path: [0; SUNPCI_MAX_PATH],
+            max_size_mb: 0,
+            used: SplitU64::default(),
         }
     }
 }
@@ -526,8 +881,12 @@ pub struct DriveLetter {
 pub mod net_flags {
     pub const ENABLED: u32 = 1 << 0;
     pub const PROMISCUOUS: u32 = 1 << 1;
+    pub const USER_MODE: u32 = 1 << 2; // frames exchanged with userspace, no TAP
 }
 
+/// Largest Ethernet frame passed through the frame ioctls
+pub const SUNPCI_NET_MAX_FRAME: usize = 1514;
+
 /// Network configuration
 #[repr(C)]
 #[derive(Debug, Clone, Copy, Default)]
@@ -549,13 +908,51 @@ pub struct NetworkStatus {
     pub tx_bytes: u64,
 }
 
+/// Ethernet frame exchanged with userspace in user mode
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct NetFrame {
+    pub length: u32,
+    pub reserved: u32,
+    pub data: [u8; SUNPCI_NET_MAX_FRAME],
+}
+
+impl Default for NetFrame {
+    fn default() -> Self {
+        Self {
+            length: 0,
+            reserved: 0,
+            data: [0; SUNPCI_NET_MAX_FRAME],
+        }
+    }
+}
+
+impl NetFrame {
+    /// Build a frame from raw bytes (truncated to the maximum frame size)
+    pub fn new(frame: &[u8]) -> Self {
+        let len = frame.len().min(SUNPCI_NET_MAX_FRAME);
+        let mut f = Self::default();
+        f.data[..len].copy_from_slice(&frame[..len]);
+        f.length = len as u32;
+        f
+    }
+
+    /// Frame contents
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data[..(self.length as usize).min(SUNPCI_NET_MAX_FRAME)]
+    }
+}
+
 // ============================================================================
 // Audio Structures
 // ============================================================================
 
-/// Maximum audio buffer size for single ioctl read
+/// Maximum audio buffer size for single ioctl read or write
 pub const SUNPCI_AUDIO_MAX_BUFFER: usize = 16384;
 
+/// Rate of the 16-bit signed mono audio written with WRITE_AUDIO
+pub const SUNPCI_AUDIO_CAPTURE_RATE: u32 = 22050;
+
 /// Audio format flags
 pub mod audio_format {
     pub const FMT_16BIT: u32 = 1 << 0;   // 16-bit samples (vs 8-bit)
@@ -568,6 +965,8 @@ pub mod audio_status_flags {
     pub const PLAYING: u32 = 1 << 0;     // Playback active
     pub const AVAILABLE: u32 = 1 << 1;   // Audio hardware present
     pub const MUTED: u32 = 1 << 2;       // Output muted
+    pub const RECORDING: u32 = 1 << 3;   // Guest is recording
+    pub const MIDI: u32 = 1 << 4;        // MPU-401 output waiting
 }
 
 /// Audio format information
@@ -590,6 +989,23 @@ impl AudioFormat {
     pub fn bytes_per_second(&self) -> u32 {
         self.sample_rate * self.bytes_per_sample()
     }
+
+    /// Whether the status reports another format than this one
+    pub fn differs_from(&self, status: &AudioStatus) -> bool {
+        status.sample_rate != 0 && (status.sample_rate, status.format) != (self.sample_rate, self.format)
+    }
+
+    /// Append PCM data in this format to `out` as signed 16-bit samples
+    pub fn decode(&self, data: &[u8], out: &mut Vec<i16>) {
+        let signed = self.format & audio_format::FMT_SIGNED != 0;
+        if self.bits_per_sample == 16 {
+            let flip = if signed { 0 } else { 0x8000 };
+            out.extend(data.chunks_exact(2).map(|b| (u16::from_le_bytes([b[0], b[1]]) ^ flip) as i16));
+        } else {
+            let flip = if signed { 0 } else { 0x80 };
+            out.extend(data.iter().map(|&b| (((b ^ flip) as i8) as i16) << 8));
+        }
+    }
 }
 
 /// Audio volume levels
@@ -632,8 +1048,7 @@ pub struct AudioStatus {
     pub sample_rate: u32,        // Current sample rate
     pub format: u32,             // Current format flags
     pub buffer_available: u32,   // Bytes of audio data available
-    pub samples_played_lo: u32,  // Total samples played (low 32 bits)
-    pub samples_played_hi: u32,  // Total samples played (high 32 bits)
+    pub samples_played: SplitU64, // Total samples played
     pub underruns: u32,          // Buffer underrun count
     pub reserved: u32,           // Reserved for alignment
 }
@@ -641,7 +1056,7 @@ pub struct AudioStatus {
 impl AudioStatus {
     /// Get samples_played as u64
     pub fn samples_played(&self) -> u64 {
-        ((self.samples_played_hi as u64) << 32) | (self.samples_played_lo as u64)
+        self.samples_played.get()
     }
 
     /// Check if audio is playing
@@ -658,13 +1073,18 @@ impl AudioStatus {
     pub fn is_muted(&self) -> bool {
         self.flags & audio_status_flags::MUTED != 0
     }
+
+    /// Check if the guest is recording
+    pub fn is_recording(&self) -> bool {
+        self.flags & audio_status_flags::RECORDING != 0
+    }
 }
 
-/// Audio buffer for reading samples
+/// Audio buffer for reading or writing samples
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct AudioBuffer {
-    pub size: u32,               // On input: max bytes. On output: bytes read.
+    pub size: u32,               // On input: max bytes, or bytes to write. On output: bytes read or taken.
     pub reserved: u32,
     pub data: [u8; SUNPCI_AUDIO_MAX_BUFFER],
 }
@@ -679,66 +1099,597 @@ impl Default for AudioBuffer {
     }
 }
 
+/// Maximum MIDI bytes for a single ioctl read
+pub const SUNPCI_MIDI_MAX_BUFFER: usize = 1024;
+
+/// Bytes the guest wrote to the MPU-401 data port (UART mode)
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MidiBuffer {
+    pub size: u32,               // On input: max bytes. On output: bytes read.
+    pub reserved: u32,
+    pub data: [u8; SUNPCI_MIDI_MAX_BUFFER],
+}
+
+impl Default for MidiBuffer {
+    fn default() -> Self {
+        Self {
+            size: SUNPCI_MIDI_MAX_BUFFER as u32,
+            reserved: 0,
+            data: [0; SUNPCI_MIDI_MAX_BUFFER],
+        }
+    }
+}
+
+// ============================================================================
+// Event Structures
+// ============================================================================
+
+/// Most events returned by one GET_EVENTS call
+pub const SUNPCI_MAX_EVENTS: usize = 8;
+
+/// Event types
+pub mod event_type {
+    /// Events were lost; re-read the state the events describe
+    pub const OVERFLOW: u32 = 0;
+    /// The guest asked to eject the medium in `drive`
+    pub const EJECT_REQUEST: u32 = 1;
+    /// The medium in `drive` was mounted or ejected
+    pub const MEDIA_CHANGED: u32 = 2;
+    /// The guest set a display mode; re-read GET_DISPLAY
+    pub const DISPLAY_CHANGED: u32 = 3;
+    /// The session was started, stopped or reset; `flags` is the new state
+    pub const SESSION_CHANGED: u32 = 4;
+    /// The guest clipboard has new data; `flags` is its clipboard_format
+    pub const CLIPBOARD: u32 = 5;
+    /// The guest changed the palette; `flags` is the new Palette::generation
+    pub const PALETTE_CHANGED: u32 = 6;
+    /// The guest powered itself off; the session should be stopped
+    pub const POWER_OFF: u32 = 7;
+}
+
+/// Event flags
+pub mod event_flags {
+    pub const MEDIA_PRESENT: u32 = 1 << 0;  // Drive holds a medium after the change
+}
+
+/// Drive numbers used in events (BIOS numbering)
+pub mod event_drive {
+    pub const FLOPPY_A: u32 = 0x00;
+    pub const FLOPPY_B: u32 = 0x01;
+    pub const CDROM: u32 = 0xE0;
+}
+
+/// One driver event
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DriverEvent {
+    pub kind: u32,
+    pub drive: u32,
+    pub flags: u32,
+    pub reserved: u32,
+    pub path: [u8; SUNPCI_MAX_PATH],  // Image path for MEDIA_CHANGED
+}
+
+impl Default for DriverEvent {
+    fn default() -> Self {
+        Self {
+            kind: 0,
+            drive: 0,
+            flags: 0,
+            reserved: 0,
+            path: [0; SUNPCI_MAX_PATH],
+        }
+    }
+}
+
+impl DriverEvent {
+    /// Check if the drive holds a medium after a MEDIA_CHANGED event
+    pub fn media_present(&self) -> bool {
+        self.flags & event_flags::MEDIA_PRESENT != 0
+    }
+
+    /// Image path, empty when none was given
+    pub fn path(&self) -> String {
+        let len = self.path.iter().position(|&b| b == 0).unwrap_or(SUNPCI_MAX_PATH);
+        String::from_utf8_lossy(&self.path[..len]).into_owned()
+    }
+}
+
+/// Events pending for the calling file
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct EventBatch {
+    pub count: u32,
+    pub reserved: u32,
+    pub events: [DriverEvent; SUNPCI_MAX_EVENTS],
+}
+
+impl Default for EventBatch {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            reserved: 0,
+            events: [DriverEvent::default(); SUNPCI_MAX_EVENTS],
+        }
+    }
+}
+
+impl EventBatch {
+    /// The valid events
+    pub fn events(&self) -> &[DriverEvent] {
+        &self.events[..(self.count as usize).min(SUNPCI_MAX_EVENTS)]
+    }
+}
+
+// ============================================================================
+// Saved State
+// ============================================================================
+
+/// Saved state is read and written in chunks of this size
+pub const SUNPCI_STATE_CHUNK: usize = 4096;
+
+/// Size and format of a guest state snapshot
+///
+/// SUSPEND_SESSION freezes a running guest and returns this; RESUME_SESSION
+/// takes it back once the snapshot has been written to a session started
+/// with `flags::RESUME`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateInfo {
+    pub size: SplitU64, // bytes
+    pub version: u32,   // snapshot format reported by the card
+    pub reserved: u32,
+}
+
+/// One part of a state snapshot
+///
+/// READ_STATE takes `offset` and sets `length` to the bytes read;
+/// WRITE_STATE takes both. Only valid while the session is suspended.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct StateChunk {
+    pub offset: SplitU64,
+    pub length: u32,
+    pub reserved: u32,
+    pub data: [u8; SUNPCI_STATE_CHUNK],
+}
+
+impl Default for StateChunk {
+    fn default() -> Self {
+        Self {
+            offset: SplitU64::default(),
+            length: 0,
+            reserved: 0,
+            data: [0; SUNPCI_STATE_CHUNK],
+        }
+    }
+}
+
+// ============================================================================
+// Driver Log
+// ============================================================================
+
+/// Most log messages returned by one READ_LOG call
+pub const SUNPCI_MAX_LOG_ENTRIES: usize = 16;
+
+/// Log message length, including the terminating NUL
+pub const SUNPCI_LOG_MESSAGE: usize = 112;
+
+/// Log levels (kernel log levels)
+pub mod log_level {
+    pub const ERR: u32 = 3;
+    pub const WARN: u32 = 4;
+    pub const INFO: u32 = 6;
+    pub const DEBUG: u32 = 7;
+}
+
+/// One message from the driver's log
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LogEntry {
+    pub seq: u32,
+    pub level: u32,         // log_level
+    pub time_ns: SplitU64,  // wall clock, since the epoch
+    pub message: [u8; SUNPCI_LOG_MESSAGE],
+}
+
+impl Default for LogEntry {
+    fn default() -> Self {
+        Self {
+            seq: 0,
+            level: 0,
+            time_ns: SplitU64::default(),
+            message: [0; SUNPCI_LOG_MESSAGE],
+        }
+    }
+}
+
+impl LogEntry {
+    /// Message text
+    pub fn message(&self) -> String {
+        let len = self.message.iter().position(|&b| b == 0).unwrap_or(SUNPCI_LOG_MESSAGE);
+        String::from_utf8_lossy(&self.message[..len]).into_owned()
+    }
+}
+
+/// Driver log messages from a sequence number on
+///
+/// READ_LOG takes `since` (0 for the oldest message held) and fills in
+/// the rest; `next` is `since` for the following call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct LogBatch {
+    pub since: u32,
+    pub count: u32,
+    pub next: u32,
+    pub dropped: u32,  // messages after `since` no longer held
+    pub entries: [LogEntry; SUNPCI_MAX_LOG_ENTRIES],
+}
+
+impl Default for LogBatch {
+    fn default() -> Self {
+        Self {
+            since: 0,
+            count: 0,
+            next: 0,
+            dropped: 0,
+            entries: [LogEntry::default(); SUNPCI_MAX_LOG_ENTRIES],
+        }
+    }
+}
+
+impl LogBatch {
+    /// The valid entries
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries[..(self.count as usize).min(SUNPCI_MAX_LOG_ENTRIES)]
+    }
+}
+
 // ============================================================================
-// ioctl Function Wrappers
+// ioctl Registry
 // ============================================================================
 
-// Session management
-ioctl_read!(sunpci_get_version, SUNPCI_IOC_MAGIC, cmd::GET_VERSION, DriverVersion);
-ioctl_read!(sunpci_get_status, SUNPCI_IOC_MAGIC, cmd::GET_STATUS, SessionStatus);
-ioctl_write_ptr!(sunpci_start_session, SUNPCI_IOC_MAGIC, cmd::START_SESSION, IoctlSessionConfig);
-ioctl_none!(sunpci_stop_session, SUNPCI_IOC_MAGIC, cmd::STOP_SESSION);
-ioctl_none!(sunpci_reset_session, SUNPCI_IOC_MAGIC, cmd::RESET_SESSION);
-
-// Display
-ioctl_read!(sunpci_get_display, SUNPCI_IOC_MAGIC, cmd::GET_DISPLAY, DisplayInfo);
-ioctl_write_ptr!(sunpci_set_display, SUNPCI_IOC_MAGIC, cmd::SET_DISPLAY, DisplayConfig);
-ioctl_read!(sunpci_get_framebuffer, SUNPCI_IOC_MAGIC, cmd::GET_FRAMEBUFFER, FramebufferInfo);
-
-// Storage
-ioctl_write_ptr!(sunpci_mount_disk, SUNPCI_IOC_MAGIC, cmd::MOUNT_DISK, DiskMount);
-ioctl_write_ptr!(sunpci_unmount_disk, SUNPCI_IOC_MAGIC, cmd::UNMOUNT_DISK, DiskSlot);
-ioctl_write_ptr!(sunpci_mount_cdrom, SUNPCI_IOC_MAGIC, cmd::MOUNT_CDROM, Path);
-ioctl_none!(sunpci_eject_cdrom, SUNPCI_IOC_MAGIC, cmd::EJECT_CDROM);
-ioctl_write_ptr!(sunpci_mount_floppy, SUNPCI_IOC_MAGIC, cmd::MOUNT_FLOPPY, FloppyMount);
-ioctl_write_ptr!(sunpci_eject_floppy, SUNPCI_IOC_MAGIC, cmd::EJECT_FLOPPY, FloppySlot);
-
-// Input
-ioctl_write_ptr!(sunpci_keyboard_event, SUNPCI_IOC_MAGIC, cmd::KEYBOARD_EVENT, KeyEvent);
-ioctl_write_ptr!(sunpci_mouse_event, SUNPCI_IOC_MAGIC, cmd::MOUSE_EVENT, MouseEvent);
-
-// Clipboard
-ioctl_write_ptr!(sunpci_set_clipboard, SUNPCI_IOC_MAGIC, cmd::SET_CLIPBOARD, Clipboard);
-ioctl_read!(sunpci_get_clipboard, SUNPCI_IOC_MAGIC, cmd::GET_CLIPBOARD, Clipboard);
-
-// Filesystem redirection
-ioctl_write_ptr!(sunpci_add_drive_map, SUNPCI_IOC_MAGIC, cmd::ADD_DRIVE_MAP, DriveMapping);
-ioctl_write_ptr!(sunpci_remove_drive_map, SUNPCI_IOC_MAGIC, cmd::REMOVE_DRIVE_MAP, DriveLetter);
-
-// Network
-ioctl_write_ptr!(sunpci_set_network, SUNPCI_IOC_MAGIC, cmd::SET_NETWORK, NetworkConfig);
-ioctl_read!(sunpci_get_network, SUNPCI_IOC_MAGIC, cmd::GET_NETWORK, NetworkStatus);
-
-// Audio
-ioctl_read!(sunpci_get_audio_format, SUNPCI_IOC_MAGIC, cmd::GET_AUDIO_FORMAT, AudioFormat);
-ioctl_write_ptr!(sunpci_set_audio_volume, SUNPCI_IOC_MAGIC, cmd::SET_AUDIO_VOLUME, AudioVolume);
-ioctl_read!(sunpci_get_audio_volume, SUNPCI_IOC_MAGIC, cmd::GET_AUDIO_VOLUME, AudioVolume);
-ioctl_read!(sunpci_get_audio_status, SUNPCI_IOC_MAGIC, cmd::GET_AUDIO_STATUS, AudioStatus);
-ioctl_readwrite!(sunpci_read_audio, SUNPCI_IOC_MAGIC, cmd::READ_AUDIO, AudioBuffer);
+/// Data direction of an ioctl, as seen from userspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoctlDir {
+    /// `_IO`: no payload
+    None,
+    /// `_IOR`: the driver fills the payload
+    Read,
+    /// `_IOW`: the driver reads the payload
+    Write,
+    /// `_IOWR`: both
+    ReadWrite,
+}
+
+/// One ioctl in the registry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoctlInfo {
+    /// Name without the `SUNPCI_IOC_` prefix, as in `cmd`
+    pub name: &'static str,
+    /// Command number
+    pub nr: u8,
+    pub dir: IoctlDir,
+    /// Payload size (0 for `IoctlDir::None`)
+    pub size: usize,
+    /// Request code passed to ioctl(2)
+    pub request: u64,
+}
+
+/// Registry entry for a request code, for a mock driver or tracing
+pub fn find_ioctl(request: u64) -> Option<&'static IoctlInfo> {
+    IOCTLS.iter().find(|info| info.request == request)
+}
+
+/// Fail compilation if two ioctls share a command number
+const fn check_unique(numbers: &[u8]) {
+    let mut i = 0;
+    while i < numbers.len() {
+        let mut j = i + 1;
+        while j < numbers.len() {
+            if numbers[i] == numbers[j] {
+                panic!("two ioctls share a command number");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+}
+
+/// nix wrapper for one registry entry
+macro_rules! ioctl_wrapper {
+    (None, $func:ident, $nr:expr) => {
+        ioctl_none!($func, SUNPCI_IOC_MAGIC, $nr);
+    };
+    (Read, $func:ident, $nr:expr, $ty:ty) => {
+        ioctl_read!($func, SUNPCI_IOC_MAGIC, $nr, $ty);
+    };
+    (Write, $func:ident, $nr:expr, $ty:ty) => {
+        ioctl_write_ptr!($func, SUNPCI_IOC_MAGIC, $nr, $ty);
+    };
+    (ReadWrite, $func:ident, $nr:expr, $ty:ty) => {
+        ioctl_readwrite!($func, SUNPCI_IOC_MAGIC, $nr, $ty);
+    };
+}
+
+/// Request code for one registry entry
+macro_rules! ioctl_request {
+    (None, $nr:expr) => {
+        request_code_none!(SUNPCI_IOC_MAGIC, $nr) as u64
+    };
+    (Read, $nr:expr, $ty:ty) => {
+        request_code_read!(SUNPCI_IOC_MAGIC, $nr, mem::size_of::<$ty>()) as u64
+    };
+    (Write, $nr:expr, $ty:ty) => {
+        request_code_write!(SUNPCI_IOC_MAGIC, $nr, mem::size_of::<$ty>()) as u64
+    };
+    (ReadWrite, $nr:expr, $ty:ty) => {
+        request_code_readwrite!(SUNPCI_IOC_MAGIC, $nr, mem::size_of::<$ty>()) as u64
+    };
+}
+
+/// Define every ioctl once: the `cmd` numbers, the nix wrappers and `IOCTLS`
+macro_rules! ioctl_registry {
+    ($($(#[$meta:meta])* $name:ident = $nr:literal, $dir:ident $(($ty:ty))? => $func:ident;)*) => {
+        /// ioctl command numbers (the `nr` of `SUNPCI_IOC_*`)
+        pub mod cmd {
+            $($(#[$meta])* pub const $name: u8 = $nr;)*
+        }
+
+        $(ioctl_wrapper!($dir, $func, cmd::$name $(, $ty)?);)*
+
+        /// Every ioctl the driver implements, in command number order
+        pub const IOCTLS: &[IoctlInfo] = &[$(
+            IoctlInfo {
+                name: stringify!($name),
+                nr: $nr,
+                dir: IoctlDir::$dir,
+                size: 0 $(+ mem::size_of::<$ty>())?,
+                request: ioctl_request!($dir, $nr $(, $ty)?),
+            },
+        )*];
+
+        const _: () = check_unique(&[$($nr),*]);
+    };
+}
+
+// A new ioctl is added here and in driver/include/uapi/sunpci_ioctl.h;
+// the tests check that the two agree.
+ioctl_registry! {
+    // Session management
+    GET_VERSION = 0, Read(DriverVersion) => sunpci_get_version;
+    GET_STATUS = 1, Read(SessionStatus) => sunpci_get_status;
+    START_SESSION = 2, Write(IoctlSessionConfig) => sunpci_start_session;
+    STOP_SESSION = 3, None => sunpci_stop_session;
+    RESET_SESSION = 4, None => sunpci_reset_session;
+    CLAIM_SESSION = 5, ReadWrite(SessionOwner) => sunpci_claim_session;
+    RELEASE_SESSION = 6, None => sunpci_release_session;
+    GET_OWNER = 7, Read(SessionOwner) => sunpci_get_owner;
+    REQUEST_SHUTDOWN = 8, None => sunpci_request_shutdown;
+
+    // Display
+    GET_DISPLAY = 10, Read(DisplayInfo) => sunpci_get_display;
+    SET_DISPLAY = 11, Write(DisplayConfig) => sunpci_set_display;
+    GET_FRAMEBUFFER = 12, Read(FramebufferInfo) => sunpci_get_framebuffer;
+    GET_MMAP_REGIONS = 13, Read(MmapRegions) => sunpci_get_mmap_regions;
+    GET_PALETTE = 14, Read(Palette) => sunpci_get_palette;
+    GET_TEXT_CURSOR = 15, Read(TextCursor) => sunpci_get_text_cursor;
+
+    // Storage
+    MOUNT_DISK = 20, Write(DiskMount) => sunpci_mount_disk;
+    UNMOUNT_DISK = 21, Write(DiskSlot) => sunpci_unmount_disk;
+    MOUNT_CDROM = 22, Write(Path) => sunpci_mount_cdrom;
+    EJECT_CDROM = 23, None => sunpci_eject_cdrom;
+    MOUNT_FLOPPY = 24, Write(FloppyMount) => sunpci_mount_floppy;
+    EJECT_FLOPPY = 25, Write(FloppySlot) => sunpci_eject_floppy;
+    CDROM_PASSTHROUGH = 26, Write(CdromPassthrough) => sunpci_cdrom_passthrough;
+    SCSI_COMMAND = 27, ReadWrite(ScsiCommand) => sunpci_scsi_command;
+    SCSI_PT_NEXT = 28, ReadWrite(ScsiCommand) => sunpci_scsi_pt_next;
+    SCSI_PT_COMPLETE = 29, Write(ScsiCommand) => sunpci_scsi_pt_complete;
+
+    // Input
+    KEYBOARD_EVENT = 30, Write(KeyEvent) => sunpci_keyboard_event;
+    MOUSE_EVENT = 31, Write(MouseEvent) => sunpci_mouse_event;
+    GET_KEYBOARD_LEDS = 32, Read(KeyboardLeds) => sunpci_get_keyboard_leds;
+    SET_KEYBOARD_LEDS = 33, Write(KeyboardLeds) => sunpci_set_keyboard_leds;
+    MOUSE_ABS_EVENT = 34, Write(MouseAbsEvent) => sunpci_mouse_abs_event;
+    GAMEPORT_EVENT = 35, Write(GameportEvent) => sunpci_gameport_event;
+
+    // Clipboard
+    SET_CLIPBOARD = 40, Write(Clipboard) => sunpci_set_clipboard;
+    GET_CLIPBOARD = 41, Read(Clipboard) => sunpci_get_clipboard;
+    SET_CLIPBOARD_CHUNK = 42, Write(ClipboardChunk) => sunpci_set_clipboard_chunk;
+    GET_CLIPBOARD_CHUNK = 43, ReadWrite(ClipboardChunk) => sunpci_get_clipboard_chunk;
+
+    // Filesystem redirection
+    ADD_DRIVE_MAP = 50, Write(DriveMapping) => sunpci_add_drive_map;
+    REMOVE_DRIVE_MAP = 51, Write(DriveLetter) => sunpci_remove_drive_map;
+
+    // Network
+    SET_NETWORK = 60, Write(NetworkConfig) => sunpci_set_network;
+    GET_NETWORK = 61, Read(NetworkStatus) => sunpci_get_network;
+    NET_SEND_FRAME = 62, Write(NetFrame) => sunpci_net_send_frame;
+    NET_RECV_FRAME = 63, Read(NetFrame) => sunpci_net_recv_frame;
+
+    // Audio
+    GET_AUDIO_FORMAT = 70, Read(AudioFormat) => sunpci_get_audio_format;
+    SET_AUDIO_VOLUME = 71, Write(AudioVolume) => sunpci_set_audio_volume;
+    GET_AUDIO_VOLUME = 72, Read(AudioVolume) => sunpci_get_audio_volume;
+    GET_AUDIO_STATUS = 73, Read(AudioStatus) => sunpci_get_audio_status;
+    READ_AUDIO = 74, ReadWrite(AudioBuffer) => sunpci_read_audio;
+    WRITE_AUDIO = 75, ReadWrite(AudioBuffer) => sunpci_write_audio;
+    READ_MIDI = 76, ReadWrite(MidiBuffer) => sunpci_read_midi;
+
+    // Events
+    GET_EVENTS = 80, Read(EventBatch) => sunpci_get_events;
+
+    // Saved state
+    SUSPEND_SESSION = 90, Read(StateInfo) => sunpci_suspend_session;
+    READ_STATE = 91, ReadWrite(StateChunk) => sunpci_read_state;
+    WRITE_STATE = 92, Write(StateChunk) => sunpci_write_state;
+    RESUME_SESSION = 93, Write(StateInfo) => sunpci_resume_session;
+
+    // Driver log
+    READ_LOG = 100, ReadWrite(LogBatch) => sunpci_read_log;
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::mem;
 
     #[test]
     fn test_struct_sizes() {
         // Ensure structs have predictable sizes for FFI
         assert_eq!(mem::size_of::<DriverVersion>(), 12);
         assert_eq!(mem::size_of::<SessionStatus>(), 40);  // 10 x u32
+        assert_eq!(mem::size_of::<SessionOwner>(), 8);
+        assert_eq!(mem::size_of::<IoctlSessionConfig>(), 12 + 3 * SUNPCI_MAX_PATH);
         assert_eq!(mem::size_of::<DisplayInfo>(), 24);
         assert_eq!(mem::size_of::<KeyEvent>(), 8);
         assert_eq!(mem::size_of::<MouseEvent>(), 16);
+        assert_eq!(mem::size_of::<MmapRegion>(), 24);
+        assert_eq!(mem::size_of::<MmapRegions>(), 8 + 24 * SUNPCI_MAX_MMAP_REGIONS);
+        assert_eq!(mem::size_of::<Palette>(), 8 + 4 * SUNPCI_PALETTE_SIZE);
+        assert_eq!(mem::size_of::<TextCursor>(), 12);
+        assert_eq!(mem::size_of::<MouseAbsEvent>(), 16);
+        assert_eq!(mem::size_of::<GameportEvent>(), 16);
+        assert_eq!(mem::size_of::<KeyboardLeds>(), 4);
+        assert_eq!(mem::size_of::<ClipboardChunk>(), 16 + SUNPCI_MAX_CLIPBOARD);
+        assert_eq!(mem::size_of::<NetFrame>(), 1524); // 8 + 1514, padded to 4
+        assert_eq!(mem::size_of::<ScsiRequest>(), 28);
+        assert_eq!(mem::size_of::<ScsiResponse>(), 28); // 26, padded to 4
+        assert_eq!(mem::size_of::<ScsiCommand>(), 80);
+        assert_eq!(mem::size_of::<CdromPassthrough>(), 8 + SUNPCI_MAX_PATH);
+        assert_eq!(mem::size_of::<DriveMapping>(), 16 + SUNPCI_MAX_PATH);
+        assert_eq!(mem::size_of::<FramebufferInfo>(), 24);
+        assert_eq!(mem::size_of::<AudioStatus>(), 32);
+        assert_eq!(mem::size_of::<MidiBuffer>(), 8 + SUNPCI_MIDI_MAX_BUFFER);
+        assert_eq!(mem::size_of::<DriverEvent>(), 16 + SUNPCI_MAX_PATH);
+        assert_eq!(mem::size_of::<EventBatch>(), 8 + (16 + SUNPCI_MAX_PATH) * SUNPCI_MAX_EVENTS);
+        assert_eq!(mem::size_of::<StateInfo>(), 16);
+        assert_eq!(mem::size_of::<StateChunk>(), 16 + SUNPCI_STATE_CHUNK);
+        assert_eq!(mem::size_of::<LogEntry>(), 16 + SUNPCI_LOG_MESSAGE);
+        assert_eq!(mem::size_of::<LogBatch>(), 16 + (16 + SUNPCI_LOG_MESSAGE) * SUNPCI_MAX_LOG_ENTRIES);
+    }
+
+    #[test]
+    fn test_palette_colors() {
+        let mut palette = Palette { count: 2, ..Palette::default() };
+        palette.entries[1] = 0x00FF8000;
+        assert_eq!(palette.colors(), [0, 0x00FF8000]);
+        assert_eq!(palette.rgb(1), [0xFF, 0x80, 0x00]);
+
+        // A bad count from the driver never reads past the table
+        palette.count = 1000;
+        assert_eq!(palette.colors().len(), SUNPCI_PALETTE_SIZE);
+    }
+
+    #[test]
+    fn test_audio_decode() {
+        let mut format = AudioFormat {
+            sample_rate: 22050,
+            format: 0,
+            channels: 1,
+            bits_per_sample: 8,
+        };
+        let mut out = Vec::new();
+        format.decode(&[0x00, 0x80, 0xff], &mut out);
+        assert_eq!(out, [-32768, 0, 32512]);
+
+        format.format = audio_format::FMT_16BIT | audio_format::FMT_STEREO | audio_format::FMT_SIGNED;
+        format.bits_per_sample = 16;
+        out.clear();
+        format.decode(&[0x00, 0x80, 0xff, 0x7f, 0x01], &mut out);
+        assert_eq!(out, [-32768, 32767]);
+
+        let mut status = AudioStatus::default();
+        assert!(!format.differs_from(&status));
+        status.sample_rate = 22050;
+        status.format = format.format;
+        assert!(!format.differs_from(&status));
+        status.sample_rate = 44100;
+        assert!(format.differs_from(&status));
+    }
+
+    #[test]
+    fn test_split_u64() {
+        // Same size and alignment as the kernel's __u32 pairs
+        assert_eq!(mem::size_of::<SplitU64>(), 8);
+        assert_eq!(mem::align_of::<SplitU64>(), 4);
+
+        for value in [0, 1, u32::MAX as u64, 1 << 32, 0x1234_5678_9ABC_DEF0, u64::MAX] {
+            let split = SplitU64::new(value);
+            assert_eq!(split.get(), value);
+            assert_eq!(u64::from(SplitU64::from(value)), value);
+        }
+
+        let mut split = SplitU64::default();
+        split.set(0x0000_0002_0000_0001);
+        assert_eq!((split.lo, split.hi), (1, 2));
+
+        // Low half first, as the driver writes it
+        let mut status = SessionStatus::default();
+        status.uptime_ns.set(0x0000_0005_0000_0007);
+        let words: [u32; 10] = unsafe { mem::transmute(status) };
+        assert_eq!(&words[4..6], &[7, 5]);
+        assert_eq!(status.uptime_ns(), 0x0000_0005_0000_0007);
+    }
+
+    #[test]
+    fn test_registry_matches_header() {
+        // SUNPCI_IOC_<NAME> _IO<dir>(SUNPCI_IOC_MAGIC, <nr>[, struct ...])
+        let header = include_str!("../../driver/include/uapi/sunpci_ioctl.h");
+        let mut defined = Vec::new();
+        for line in header.lines() {
+            let Some((name, value)) = line
+                .strip_prefix("#define SUNPCI_IOC_")
+                .and_then(|rest| rest.split_once(char::is_whitespace))
+            else {
+                continue;
+            };
+            let Some((dir, args)) = value.trim().split_once('(') else {
+                continue;
+            };
+            let dir = match dir {
+                "_IO" => IoctlDir::None,
+                "_IOR" => IoctlDir::Read,
+                "_IOW" => IoctlDir::Write,
+                "_IOWR" => IoctlDir::ReadWrite,
+                _ => continue,
+            };
+            let nr: u8 = args.split([',', ')']).nth(1).unwrap().trim().parse().unwrap();
+            defined.push((name.to_string(), nr, dir));
+        }
+        defined.sort_by_key(|&(_, nr, _)| nr);
+
+        let registered: Vec<_> = IOCTLS.iter().map(|info| (info.name.to_string(), info.nr, info.dir)).collect();
+        assert_eq!(registered, defined);
+    }
+
+    #[test]
+    #[allow(clippy::unnecessary_cast)] // the request code type depends on the libc
+    fn test_registry() {
+        assert!(IOCTLS.windows(2).all(|pair| pair[0].nr < pair[1].nr));
+
+        // Same codes as the nix wrappers use
+        let info = find_ioctl(request_code_read!(SUNPCI_IOC_MAGIC, cmd::GET_STATUS, 40) as u64).unwrap();
+        assert_eq!((info.name, info.dir, info.size), ("GET_STATUS", IoctlDir::Read, 40));
+        let info = find_ioctl(request_code_none!(SUNPCI_IOC_MAGIC, cmd::STOP_SESSION) as u64).unwrap();
+        assert_eq!((info.name, info.size), ("STOP_SESSION", 0));
+        assert!(find_ioctl(request_code_none!(SUNPCI_IOC_MAGIC, 99) as u64).is_none());
+    }
+
+    #[test]
+    fn test_display_mode_flags() {
+        let info = DisplayInfo {
+            mode: display_mode::GRAPHICS | display_mode::INTERLACED | display_mode::ODD_FIELD,
+            ..Default::default()
+        };
+        assert!(!info.is_text());
+        assert!(info.is_interlaced());
+        assert!(info.is_odd_field());
+        assert!(DisplayInfo::default().is_text());
     }
 
     #[test]
@@ -748,3 +1699,180 @@ mod tests {
         assert_eq!(&config.primary_disk[..18], b"/path/to/disk.img\0");
     }
 }
+
+/// Field-by-field comparison with the structs bindgen generates from the
+/// uapi header, so the two cannot drift apart unnoticed
+///
+/// Needs libclang; run it with `cargo xtask abi-check`, which passes any
+/// further arguments (such as `--target i686-unknown-linux-gnu`) to cargo.
+#[cfg(all(test, feature = "abi-check"))]
+mod abi_check {
+    use super::*;
+
+    #[allow(non_camel_case_types, non_upper_case_globals, dead_code)]
+    mod c {
+        include!(concat!(env!("OUT_DIR"), "/sunpci_ioctl.rs"));
+    }
+
+    include!(concat!(env!("OUT_DIR"), "/sunpci_structs.rs"));
+
+    /// Size of the field `_get` borrows
+    fn field_size<T, F>(_get: fn(&T) -> &F) -> usize {
+        mem::size_of::<F>()
+    }
+
+    /// Compare the size and alignment of C struct `$c` and Rust struct
+    /// `$rust`, and the offset and size of each listed field: `name` for a
+    /// field of the same name, `name = path` for one the Rust struct calls
+    /// something else (`uptime_ns_lo = uptime_ns.lo`)
+    macro_rules! compare {
+        ($report:ident, $c:ident => $rust:ty { $($fields:tt)* }) => {
+            $report.compared.push(stringify!($c));
+            if mem::size_of::<c::$c>() != mem::size_of::<$rust>() {
+                $report.errors.push(format!(
+                    "{}: size {} in C, {} in Rust",
+                    stringify!($c),
+                    mem::size_of::<c::$c>(),
+                    mem::size_of::<$rust>()
+                ));
+            }
+            if mem::align_of::<c::$c>() != mem::align_of::<$rust>() {
+                $report.errors.push(format!(
+                    "{}: alignment {} in C, {} in Rust",
+                    stringify!($c),
+                    mem::align_of::<c::$c>(),
+                    mem::align_of::<$rust>()
+                ));
+            }
+            compare!(@fields $report, $c, $rust; $($fields)*);
+        };
+        (@fields $report:ident, $c:ident, $rust:ty;) => {};
+        (@fields $report:ident, $c:ident, $rust:ty; $c_field:ident = $($rust_field:ident).+ $(, $($rest:tt)*)?) => {
+            $report.compare_field(
+                concat!(stringify!($c), ".", stringify!($c_field)),
+                (mem::offset_of!(c::$c, $c_field), field_size(|s: &c::$c| &s.$c_field)),
+                (mem::offset_of!($rust, $($rust_field).+), field_size(|s: &$rust| &s.$($rust_field).+)),
+            );
+            compare!(@fields $report, $c, $rust; $($($rest)*)?);
+        };
+        (@fields $report:ident, $c:ident, $rust:ty; $c_field:ident $(, $($rest:tt)*)?) => {
+            compare!(@fields $report, $c, $rust; $c_field = $c_field $(, $($rest)*)?);
+        };
+    }
+
+    /// Structs compared so far and the differences found
+    #[derive(Default)]
+    struct Report {
+        compared: Vec<&'static str>,
+        errors: Vec<String>,
+    }
+
+    impl Report {
+        /// Note a field whose (offset, size) differs between C and Rust
+        fn compare_field(&mut self, field: &str, c: (usize, usize), rust: (usize, usize)) {
+            if c.0 != rust.0 {
+                self.errors.push(format!("{field}: offset {} in C, {} in Rust", c.0, rust.0));
+            }
+            if c.1 != rust.1 {
+                self.errors.push(format!("{field}: size {} in C, {} in Rust", c.1, rust.1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_layouts_match_header() {
+        let mut report = Report::default();
+
+        compare!(report, sunpci_version => DriverVersion { major, minor, patch });
+        compare!(report, sunpci_status => SessionStatus {
+            state, guest_idle_ms, _reserved2 = _reserved2.lo, _reserved3 = _reserved2.hi,
+            uptime_ns_lo = uptime_ns.lo, uptime_ns_hi = uptime_ns.hi,
+            disk_activity, network_rx_packets, network_tx_packets, _pad,
+        });
+        compare!(report, sunpci_session_config => IoctlSessionConfig {
+            _reserved, flags, primary_disk, secondary_disk, bios_path, boot_device,
+        });
+        compare!(report, sunpci_session_owner => SessionOwner { pid, control_port, flags });
+
+        compare!(report, sunpci_display_info => DisplayInfo {
+            width, height, color_depth, mode, text_cols, text_rows,
+        });
+        compare!(report, sunpci_display_config => DisplayConfig { scale_mode, scale_factor, flags });
+        compare!(report, sunpci_framebuffer => FramebufferInfo {
+            phys_addr_lo = phys_addr.lo, phys_addr_hi = phys_addr.hi,
+            size_lo = size.lo, size_hi = size.hi, stride, format,
+        });
+        compare!(report, sunpci_mmap_region => MmapRegion {
+            id, flags, offset_lo = offset.lo, offset_hi = offset.hi, size_lo = size.lo, size_hi = size.hi,
+        });
+        compare!(report, sunpci_mmap_regions => MmapRegions { count, reserved, regions });
+        compare!(report, sunpci_palette => Palette { generation, count, entries });
+        compare!(report, sunpci_text_cursor => TextCursor { col, row, start, end, visible, reserved });
+
+        compare!(report, sunpci_disk_mount => DiskMount { slot, flags, path });
+        compare!(report, sunpci_disk_slot => DiskSlot { slot });
+        compare!(report, sunpci_path => Path { path });
+        compare!(report, sunpci_floppy_mount => FloppyMount { drive, flags, path });
+        compare!(report, sunpci_floppy_slot => FloppySlot { drive });
+        compare!(report, sunpci_cdrom_passthrough => CdromPassthrough { enable, reserved, device });
+        compare!(report, sunpci_scsi_cdb => ScsiRequest { cdb, cdb_len, data_direction, data_len });
+        compare!(report, sunpci_scsi_result => ScsiResponse { status, sense_len, reserved, data_len, sense });
+        compare!(report, sunpci_scsi_command => ScsiCommand {
+            tag, flags, cdb = request, result = response, data_ptr, data_buf_len, reserved,
+        });
+
+        compare!(report, sunpci_key_event => KeyEvent { scancode, flags });
+        compare!(report, sunpci_mouse_event => MouseEvent { dx, dy, dz, buttons });
+        compare!(report, sunpci_mouse_abs_event => MouseAbsEvent { x, y, dz, buttons });
+        compare!(report, sunpci_gameport_event => GameportEvent { axes, buttons, connected });
+        compare!(report, sunpci_keyboard_leds => KeyboardLeds { leds });
+
+        compare!(report, sunpci_clipboard => Clipboard { length, format, data });
+        compare!(report, sunpci_clipboard_chunk => ClipboardChunk {
+            format, total_length, offset, length, data,
+        });
+
+        compare!(report, sunpci_drive_mapping => DriveMapping {
+            letter, flags, tz_offset, path, max_size_mb, used_lo = used.lo, used_hi = used.hi,
+        });
+        compare!(report, sunpci_drive_letter => DriveLetter { letter, _pad });
+
+        compare!(report, sunpci_network_config => NetworkConfig { flags, interface, mac_address, reserved });
+        compare!(report, sunpci_network_status => NetworkStatus {
+            flags, rx_packets, tx_packets, rx_bytes, tx_bytes,
+        });
+        compare!(report, sunpci_net_frame => NetFrame { length, reserved, data });
+
+        compare!(report, sunpci_audio_format => AudioFormat { sample_rate, format, channels, bits_per_sample });
+        compare!(report, sunpci_audio_volume => AudioVolume { left, right, muted, reserved });
+        compare!(report, sunpci_audio_status => AudioStatus {
+            flags, sample_rate, format, buffer_available,
+            samples_played_lo = samples_played.lo, samples_played_hi = samples_played.hi,
+            underruns, reserved,
+        });
+        compare!(report, sunpci_audio_buffer => AudioBuffer { size, reserved, data });
+        compare!(report, sunpci_midi_buffer => MidiBuffer { size, reserved, data });
+
+        compare!(report, sunpci_event => DriverEvent { type_ = kind, drive, flags, reserved, path });
+        compare!(report, sunpci_event_batch => EventBatch { count, reserved, events });
+
+        compare!(report, sunpci_state_info => StateInfo {
+            size_lo = size.lo, size_hi = size.hi, version, reserved,
+        });
+        compare!(report, sunpci_state_chunk => StateChunk {
+            offset_lo = offset.lo, offset_hi = offset.hi, length, reserved, data,
+        });
+
+        compare!(report, sunpci_log_entry => LogEntry {
+            seq, level, time_lo = time_ns.lo, time_hi = time_ns.hi, message,
+        });
+        compare!(report, sunpci_log_batch => LogBatch { since, count, next, dropped, entries });
+
+        for c_struct in C_STRUCTS {
+            if !report.compared.contains(c_struct) {
+                report.errors.push(format!("{c_struct}: no Rust struct compared with it"));
+            }
+        }
+        assert!(report.errors.is_empty(), "ABI differences:\n{}", report.errors.join("\n"));
+    }
+}
@@ -0,0 +1,393 @@
+//! Writing small ISO 9660 images.
+//!
+//! Enough of the standard for the guest tools CD (see `guest_tools`): a
+//! primary volume descriptor, both path tables, and a tree of directories
+//! and files kept in memory. Names follow interchange level 1 (8.3,
+//! upper case, `;1` version), which DOS's MSCDEX and every Windows read
+//! without extensions.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::fat_time::civil_from_days;
+
+/// Logical block (sector) size
+pub const BLOCK_SIZE: usize = 2048;
+
+/// Sectors before the first volume descriptor
+const SYSTEM_AREA: usize = 16;
+
+/// A file to put on the image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IsoFile {
+    /// Path on the image, directories separated by '/'
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// A level 1 name for `name`: upper case, characters outside A-Z, 0-9 and
+/// '_' replaced by '_', and cut to 8.3
+pub fn level1_name(name: &str) -> String {
+    let clean = |part: &str, len: usize| -> String { d_characters(part).chars().take(len).collect() };
+    match name.rsplit_once('.') {
+        Some((base, ext)) if !base.is_empty() => format!("{}.{}", clean(base, 8), clean(ext, 3)),
+        _ => clean(name, 8),
+    }
+}
+
+/// A directory of the image being laid out
+#[derive(Debug, Default)]
+struct Dir {
+    name: String,
+    /// Index of the parent in the directory list (the root is its own)
+    parent: usize,
+    /// Subdirectories, by index
+    dirs: Vec<usize>,
+    /// Files, by index into the file list
+    files: Vec<usize>,
+    /// First sector and length of its records
+    extent: u32,
+    size: u32,
+}
+
+/// Write `files` to a new image at `path`, with volume label `label` and
+/// every timestamp at `unix_secs` (UTC)
+///
+/// Names are made level 1 with `level1_name`; two files that end up with
+/// the same name are an error.
+pub fn write_iso(path: &Path, label: &str, files: &[IsoFile], unix_secs: i64) -> io::Result<()> {
+    // The tree, directories breadth first as the path table wants them
+    let mut dirs = vec![Dir::default()];
+    for (index, file) in files.iter().enumerate() {
+        let mut parts: Vec<String> = file.path.split('/').filter(|p| !p.is_empty()).map(level1_name).collect();
+        let Some(name) = parts.pop() else {
+            return Err(invalid(format!("empty path for an ISO file: {:?}", file.path)));
+        };
+        let mut dir = 0;
+        for part in parts {
+            let part = part.replace('.', "_");
+            dir = match dirs[dir].dirs.iter().copied().find(|&d| dirs[d].name == part) {
+                Some(existing) => existing,
+                None => {
+                    dirs.push(Dir { name: part, parent: dir, ..Dir::default() });
+                    let new = dirs.len() - 1;
+                    dirs[dir].dirs.push(new);
+                    new
+                }
+            };
+        }
+        if dirs[dir].files.iter().any(|&f| level1_name(file_name(&files[f].path)) == name) {
+            return Err(invalid(format!("two files are called {} on the image", name)));
+        }
+        dirs[dir].files.push(index);
+    }
+    let order = breadth_first(&mut dirs);
+
+    // Sectors: descriptors, path tables, directories, then file data
+    let path_table_size = path_table(&dirs, &order, &vec![0; order.len()], false).len();
+    let table_sectors = path_table_size.div_ceil(BLOCK_SIZE).max(1);
+    let l_table = SYSTEM_AREA + 2;
+    let m_table = l_table + table_sectors;
+    let mut next = m_table + table_sectors;
+    for &d in &order {
+        let records = dir_records(&dirs, d, files, &[], unix_secs);
+        dirs[d].extent = next as u32;
+        dirs[d].size = (records.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE) as u32;
+        next += records.len().div_ceil(BLOCK_SIZE);
+    }
+    let mut file_extents = vec![0u32; files.len()];
+    for (index, file) in files.iter().enumerate() {
+        file_extents[index] = next as u32;
+        next += file.data.len().div_ceil(BLOCK_SIZE);
+    }
+    let total = next;
+
+    let extents: Vec<u32> = order.iter().map(|&d| dirs[d].extent).collect();
+    let mut image = vec![0u8; SYSTEM_AREA * BLOCK_SIZE];
+    image.extend(primary_descriptor(&dirs, label, total as u32, path_table_size as u32, l_table, m_table, unix_secs));
+    let mut terminator = vec![0u8; BLOCK_SIZE];
+    terminator[0] = 0xFF;
+    terminator[1..6].copy_from_slice(b"CD001");
+    terminator[6] = 1;
+    image.extend(terminator);
+    image.extend(padded(path_table(&dirs, &order, &extents, false), table_sectors));
+    image.extend(padded(path_table(&dirs, &order, &extents, true), table_sectors));
+    for &d in &order {
+        let records = dir_records(&dirs, d, files, &file_extents, unix_secs);
+        image.extend(padded(records, dirs[d].size as usize / BLOCK_SIZE));
+    }
+    for file in files {
+        let sectors = file.data.len().div_ceil(BLOCK_SIZE);
+        image.extend(padded(file.data.clone(), sectors));
+    }
+
+    let mut out = File::options().write(true).create_new(true).open(path)?;
+    out.write_all(&image)?;
+    out.sync_all()
+}
+
+/// Directory indexes breadth first, children by name
+fn breadth_first(dirs: &mut [Dir]) -> Vec<usize> {
+    let mut order = vec![0];
+    let mut at = 0;
+    while at < order.len() {
+        let mut children = dirs[order[at]].dirs.clone();
+        children.sort_by(|&a, &b| dirs[a].name.cmp(&dirs[b].name));
+        dirs[order[at]].dirs = children.clone();
+        order.extend(children);
+        at += 1;
+    }
+    order
+}
+
+/// The path table, little- or big-endian
+fn path_table(dirs: &[Dir], order: &[usize], extents: &[u32], big_endian: bool) -> Vec<u8> {
+    let mut table = Vec::new();
+    for (n, &d) in order.iter().enumerate() {
+        let name: &[u8] = if d == 0 { &[0] } else { dirs[d].name.as_bytes() };
+        // Directory numbers count from 1, in path table order
+        let parent = order.iter().position(|&p| p == dirs[d].parent).unwrap_or(0) as u16 + 1;
+        table.push(name.len() as u8);
+        table.push(0);
+        if big_endian {
+            table.extend(extents[n].to_be_bytes());
+            table.extend(parent.to_be_bytes());
+        } else {
+            table.extend(extents[n].to_le_bytes());
+            table.extend(parent.to_le_bytes());
+        }
+        table.extend(name);
+        if name.len() % 2 == 1 {
+            table.push(0);
+        }
+    }
+    table
+}
+
+/// The records of directory `d`: ".", "..", subdirectories and files by name
+///
+/// A record never crosses a sector; the rest of a sector is left empty.
+fn dir_records(dirs: &[Dir], d: usize, files: &[IsoFile], file_extents: &[u32], unix_secs: i64) -> Vec<u8> {
+    let dir = &dirs[d];
+    let parent = &dirs[dir.parent];
+    let mut entries: Vec<(Vec<u8>, u32, u32, bool)> = vec![
+        (vec![0], dir.extent, dir.size, true),
+        (vec![1], parent.extent, parent.size, true),
+    ];
+    let mut named: Vec<(Vec<u8>, u32, u32, bool)> = dir
+        .dirs
+        .iter()
+        .map(|&c| (dirs[c].name.as_bytes().to_vec(), dirs[c].extent, dirs[c].size, true))
+        .chain(dir.files.iter().map(|&f| {
+            let name = format!("{};1", level1_name(file_name(&files[f].path)));
+            let extent = file_extents.get(f).copied().unwrap_or(0);
+            (name.into_bytes(), extent, files[f].data.len() as u32, false)
+        }))
+        .collect();
+    named.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.extend(named);
+
+    let mut records = Vec::new();
+    for (name, extent, size, is_dir) in entries {
+        let record = dir_record(&name, extent, size, is_dir, unix_secs);
+        let used = records.len() % BLOCK_SIZE;
+        if used + record.len() > BLOCK_SIZE {
+            records.resize(records.len() + BLOCK_SIZE - used, 0);
+        }
+        records.extend(record);
+    }
+    records
+}
+
+/// One directory record
+fn dir_record(name: &[u8], extent: u32, size: u32, is_dir: bool, unix_secs: i64) -> Vec<u8> {
+    let len = 33 + name.len() + (name.len() + 1) % 2;
+    let mut record = vec![0u8; len];
+    record[0] = len as u8;
+    both_endian32(&mut record[2..10], extent);
+    both_endian32(&mut record[10..18], size);
+    record[18..25].copy_from_slice(&record_date(unix_secs));
+    record[25] = if is_dir { 0x02 } else { 0 };
+    both_endian16(&mut record[28..32], 1);
+    record[32] = name.len() as u8;
+    record[33..33 + name.len()].copy_from_slice(name);
+    record
+}
+
+/// The primary volume descriptor
+fn primary_descriptor(
+    dirs: &[Dir],
+    label: &str,
+    total: u32,
+    path_table_size: u32,
+    l_table: usize,
+    m_table: usize,
+    unix_secs: i64,
+) -> Vec<u8> {
+    let mut pvd = vec![0u8; BLOCK_SIZE];
+    pvd[0] = 1;
+    pvd[1..6].copy_from_slice(b"CD001");
+    pvd[6] = 1;
+    fill(&mut pvd[8..40], "");
+    fill(&mut pvd[40..72], &d_characters(label));
+    both_endian32(&mut pvd[80..88], total);
+    both_endian16(&mut pvd[120..124], 1);
+    both_endian16(&mut pvd[124..128], 1);
+    both_endian16(&mut pvd[128..132], BLOCK_SIZE as u16);
+    both_endian32(&mut pvd[132..140], path_table_size);
+    pvd[140..144].copy_from_slice(&(l_table as u32).to_le_bytes());
+    pvd[148..152].copy_from_slice(&(m_table as u32).to_be_bytes());
+    let root = dir_record(&[0], dirs[0].extent, dirs[0].size, true, unix_secs);
+    pvd[156..190].copy_from_slice(&root);
+    fill(&mut pvd[190..813], "");
+    fill(&mut pvd[574..702], "RISING SUN");
+    let date = descriptor_date(unix_secs);
+    pvd[813..830].copy_from_slice(&date);
+    pvd[830..847].copy_from_slice(&date);
+    pvd[847..863].fill(b'0');
+    pvd[864..880].fill(b'0');
+    pvd[881] = 1;
+    pvd
+}
+
+/// Seven-byte date of a directory record (UTC)
+fn record_date(unix_secs: i64) -> [u8; 7] {
+    let (year, month, day) = civil_from_days(unix_secs.div_euclid(86_400));
+    let secs = unix_secs.rem_euclid(86_400);
+    [
+        (year - 1900).clamp(0, 255) as u8,
+        month as u8,
+        day as u8,
+        (secs / 3600) as u8,
+        (secs / 60 % 60) as u8,
+        (secs % 60) as u8,
+        0,
+    ]
+}
+
+/// Seventeen-byte date of a volume descriptor (UTC)
+fn descriptor_date(unix_secs: i64) -> [u8; 17] {
+    let (year, month, day) = civil_from_days(unix_secs.div_euclid(86_400));
+    let secs = unix_secs.rem_euclid(86_400);
+    let text = format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}00",
+        year.clamp(0, 9999),
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    );
+    let mut date = [0u8; 17];
+    date[..16].copy_from_slice(text.as_bytes());
+    date
+}
+
+/// `text` in upper case, with characters outside A-Z, 0-9 and '_' as '_'
+fn d_characters(text: &str) -> String {
+    text.chars()
+        .map(|c| c.to_ascii_uppercase())
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
+
+/// `text` padded with spaces to fill `field`
+fn fill(field: &mut [u8], text: &str) {
+    field.fill(b' ');
+    let len = text.len().min(field.len());
+    field[..len].copy_from_slice(&text.as_bytes()[..len]);
+}
+
+fn both_endian16(field: &mut [u8], value: u16) {
+    field[..2].copy_from_slice(&value.to_le_bytes());
+    field[2..4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn both_endian32(field: &mut [u8], value: u32) {
+    field[..4].copy_from_slice(&value.to_le_bytes());
+    field[4..8].copy_from_slice(&value.to_be_bytes());
+}
+
+/// `bytes` zero-filled to `sectors` whole sectors
+fn padded(mut bytes: Vec<u8>, sectors: usize) -> Vec<u8> {
+    bytes.resize(sectors * BLOCK_SIZE, 0);
+    bytes
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::virtual_cd::iso9660_label;
+
+    /// (name, extent, size) of the records in a directory's sectors
+    fn records(image: &[u8], extent: u32, size: u32) -> Vec<(String, u32, u32)> {
+        let start = extent as usize * BLOCK_SIZE;
+        let bytes = &image[start..start + size as usize];
+        let mut found = Vec::new();
+        let mut at = 0;
+        while at < bytes.len() {
+            let len = bytes[at] as usize;
+            if len == 0 {
+                at = (at / BLOCK_SIZE + 1) * BLOCK_SIZE;
+                continue;
+            }
+            let record = &bytes[at..at + len];
+            let name = String::from_utf8_lossy(&record[33..33 + record[32] as usize]).into_owned();
+            let extent = u32::from_le_bytes(record[2..6].try_into().unwrap());
+            let size = u32::from_le_bytes(record[10..14].try_into().unwrap());
+            found.push((name, extent, size));
+            at += len;
+        }
+        found
+    }
+
+    #[test]
+    fn test_write_iso() {
+        assert_eq!(level1_name("sunpcnet.exe"), "SUNPCNET.EXE");
+        assert_eq!(level1_name("read me-first.text"), "READ_ME_.TEX");
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tools.iso");
+        let files = [
+            IsoFile { path: "README.TXT".to_string(), data: b"Guest tools\r\n".to_vec() },
+            IsoFile { path: "DOS/redir.sys".to_string(), data: vec![0xAB; 5000] },
+        ];
+        write_iso(&path, "Guest tools", &files, 1_000_000_000).unwrap();
+        let image = std::fs::read(&path).unwrap();
+        assert_eq!(image.len() % BLOCK_SIZE, 0);
+
+        let pvd = &image[16 * BLOCK_SIZE..17 * BLOCK_SIZE];
+        assert_eq!(iso9660_label(pvd).as_deref(), Some("GUEST_TOOLS"));
+        assert_eq!(u32::from_le_bytes(pvd[80..84].try_into().unwrap()) as usize, image.len() / BLOCK_SIZE);
+        assert_eq!(&pvd[813..821], b"20010909");
+
+        let root_extent = u32::from_le_bytes(pvd[158..162].try_into().unwrap());
+        let root = records(&image, root_extent, BLOCK_SIZE as u32);
+        let names: Vec<&str> = root.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["\0", "\u{1}", "DOS", "README.TXT;1"]);
+
+        let (_, dos_extent, dos_size) = root[2];
+        let dos = records(&image, dos_extent, dos_size);
+        assert_eq!(dos[1].1, root_extent);
+        let (name, extent, size) = &dos[2];
+        assert_eq!((name.as_str(), *size), ("REDIR.SYS;1", 5000));
+        let start = *extent as usize * BLOCK_SIZE;
+        assert!(image[start..start + 5000].iter().all(|&b| b == 0xAB));
+
+        // Names that clash once shortened
+        let clash = [
+            IsoFile { path: "longname1.txt".to_string(), data: Vec::new() },
+            IsoFile { path: "LONGNAME1.TXT".to_string(), data: Vec::new() },
+        ];
+        assert!(write_iso(&dir.path().join("clash.iso"), "X", &clash, 0).is_err());
+    }
+}
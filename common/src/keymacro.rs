@@ -0,0 +1,242 @@
+//! Keystroke macros recorded from the host keyboard.
+//!
+//! While the keyboard is captured, `MacroRecorder` notes every key the
+//! guest is sent with the time since the key before, so a macro plays back
+//! the way it was typed, pauses included. Macros are kept by name in the
+//! configuration (`[[macros]]`) and typed into the guest again with `play`,
+//! which is handy for long DOS commands or license keys. Text and keystroke
+//! macros typed into the guest (see `scancode`) are played the same way,
+//! after `from_keystrokes` or `from_macro_steps`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+
+use crate::config::MacroKey;
+use crate::scancode::{KeyStroke, MacroStep};
+
+/// Longest pause kept in a recording; longer ones (looking something up
+/// halfway through) are shortened to this
+pub const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How often a macro being played checks whether it was stopped
+const STOP_CHECK: Duration = Duration::from_millis(20);
+
+/// Records key presses and releases with their timing
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    keys: Vec<MacroKey>,
+    /// When the last key was recorded
+    last: Option<Instant>,
+    /// Keys pressed while recording and not yet released
+    held: Vec<(u32, bool)>,
+}
+
+impl MacroRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a key pressed or released at `at`
+    ///
+    /// A release of a key held down before recording started is left out,
+    /// as the macro never pressed it.
+    pub fn record(&mut self, scancode: u32, extended: bool, pressed: bool, at: Instant) {
+        let key = (scancode, extended);
+        if pressed {
+            if !self.held.contains(&key) {
+                self.held.push(key);
+            }
+        } else if let Some(index) = self.held.iter().position(|&k| k == key) {
+            self.held.remove(index);
+        } else {
+            return;
+        }
+
+        let delay = self.last.map_or(Duration::ZERO, |last| at.saturating_duration_since(last));
+        self.last = Some(at);
+        self.keys.push(MacroKey {
+            scancode,
+            extended,
+            pressed,
+            delay_ms: delay.min(MAX_DELAY).as_millis() as u32,
+        });
+    }
+
+    /// Number of key presses and releases recorded
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// The recording, with keys still held released at the end
+    pub fn finish(mut self) -> Vec<MacroKey> {
+        for (scancode, extended) in std::mem::take(&mut self.held) {
+            self.keys.push(MacroKey { scancode, extended, pressed: false, delay_ms: 0 });
+        }
+        self.keys
+    }
+}
+
+/// Keys that type groups of key strokes `step` apart, the keys within a
+/// group at once; an empty group is a pause of one step
+pub fn from_keystrokes(groups: &[Vec<KeyStroke>], step: Duration) -> Vec<MacroKey> {
+    let steps: Vec<MacroStep> = groups.iter().cloned().map(MacroStep::Keys).collect();
+    from_macro_steps(&steps, step)
+}
+
+/// Keys that type a keystroke macro, its groups of key strokes `step`
+/// apart and each pause added to the wait before the next group
+pub fn from_macro_steps(steps: &[MacroStep], step: Duration) -> Vec<MacroKey> {
+    let step_ms = u32::try_from(step.as_millis()).unwrap_or(u32::MAX);
+    let mut keys = Vec::new();
+    let mut delay_ms: u32 = 0;
+    for macro_step in steps {
+        let group = match macro_step {
+            MacroStep::Keys(group) => group,
+            MacroStep::Wait(ms) => {
+                delay_ms = delay_ms.saturating_add(*ms);
+                continue;
+            }
+        };
+        delay_ms = delay_ms.saturating_add(step_ms);
+        for (i, stroke) in group.iter().enumerate() {
+            keys.push(MacroKey {
+                scancode: stroke.scancode,
+                extended: false,
+                pressed: stroke.pressed,
+                delay_ms: if i == 0 { delay_ms } else { 0 },
+            });
+        }
+        if !group.is_empty() {
+            delay_ms = 0;
+        }
+    }
+    keys
+}
+
+/// How long `keys` take to play
+pub fn duration(keys: &[MacroKey]) -> Duration {
+    Duration::from_millis(keys.iter().map(|key| u64::from(key.delay_ms)).sum())
+}
+
+/// Play `keys` through `send`, waiting out their delays
+///
+/// Returns false if `stop` was set before the end; keys the macro left held
+/// are released then, so the guest does not see them stuck down.
+pub fn play(keys: &[MacroKey], stop: &AtomicBool, mut send: impl FnMut(&MacroKey) -> Result<()>) -> Result<bool> {
+    let mut held: Vec<(u32, bool)> = Vec::new();
+    for key in keys {
+        if !wait(Duration::from_millis(u64::from(key.delay_ms)), stop) {
+            for (scancode, extended) in held {
+                send(&MacroKey { scancode, extended, pressed: false, delay_ms: 0 })?;
+            }
+            return Ok(false);
+        }
+        send(key)?;
+        let id = (key.scancode, key.extended);
+        held.retain(|&k| k != id);
+        if key.pressed {
+            held.push(id);
+        }
+    }
+    Ok(true)
+}
+
+/// Sleep for `delay`, false if `stop` is set first
+fn wait(delay: Duration, stop: &AtomicBool) -> bool {
+    let deadline = Instant::now() + delay;
+    loop {
+        if stop.load(Ordering::SeqCst) {
+            return false;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        std::thread::sleep(left.min(STOP_CHECK));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_play() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+
+        let mut recorder = MacroRecorder::new();
+        // Released before it was pressed: held down when recording started
+        recorder.record(0x1D, false, false, at(0));
+        recorder.record(0x1E, false, true, at(100));
+        recorder.record(0x1E, false, false, at(180));
+        recorder.record(0x48, true, true, at(60_000));
+        let keys = recorder.finish();
+
+        assert_eq!(keys.len(), 4);
+        assert_eq!(keys[0], MacroKey { scancode: 0x1E, extended: false, pressed: true, delay_ms: 0 });
+        assert_eq!(keys[1].delay_ms, 80);
+        // The long pause is shortened, and the held key released at the end
+        assert_eq!(keys[2], MacroKey { scancode: 0x48, extended: true, pressed: true, delay_ms: 5000 });
+        assert_eq!(keys[3], MacroKey { scancode: 0x48, extended: true, pressed: false, delay_ms: 0 });
+        assert_eq!(duration(&keys), Duration::from_millis(5080));
+
+        let quick: Vec<MacroKey> = keys.iter().map(|&key| MacroKey { delay_ms: 0, ..key }).collect();
+        let mut sent = Vec::new();
+        let stop = AtomicBool::new(false);
+        assert!(play(&quick, &stop, |key| {
+            sent.push(*key);
+            Ok(())
+        })
+        .unwrap());
+        assert_eq!(sent, quick);
+
+        // Stopped part way: the held key is released
+        let mut sent = Vec::new();
+        let stopped = play(&quick, &stop, |key| {
+            sent.push(*key);
+            if key.extended {
+                stop.store(true, Ordering::SeqCst);
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert!(!stopped);
+        assert_eq!(sent.len(), 4);
+        assert_eq!(sent.last(), Some(&MacroKey { scancode: 0x48, extended: true, pressed: false, delay_ms: 0 }));
+    }
+
+    #[test]
+    fn test_from_keystrokes() {
+        let press = |scancode| KeyStroke { scancode, pressed: true };
+        let release = |scancode| KeyStroke { scancode, pressed: false };
+        let groups = [vec![press(0x1E), release(0x1E)], Vec::new(), Vec::new(), vec![press(0x1C), release(0x1C)]];
+
+        let keys = from_keystrokes(&groups, Duration::from_millis(50));
+        let delays: Vec<u32> = keys.iter().map(|key| key.delay_ms).collect();
+        // The two empty groups add to the wait before Enter
+        assert_eq!(delays, [50, 0, 150, 0]);
+        assert_eq!(keys[2], MacroKey { scancode: 0x1C, extended: false, pressed: true, delay_ms: 150 });
+        assert_eq!(duration(&keys), Duration::from_millis(200));
+
+        // A pause is added to the step before the next group, and many
+        // long ones do not overflow
+        let steps = [
+            MacroStep::Keys(vec![press(0x1E), release(0x1E)]),
+            MacroStep::Wait(120),
+            MacroStep::Keys(vec![press(0x1C), release(0x1C)]),
+        ];
+        let keys = from_macro_steps(&steps, Duration::from_millis(50));
+        let delays: Vec<u32> = keys.iter().map(|key| key.delay_ms).collect();
+        assert_eq!(delays, [50, 0, 170, 0]);
+        let mut steps = vec![MacroStep::Wait(u32::MAX); 3];
+        steps.push(MacroStep::Keys(vec![press(0x1C)]));
+        assert_eq!(from_macro_steps(&steps, Duration::from_millis(50))[0].delay_ms, u32::MAX);
+    }
+}
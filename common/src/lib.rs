@@ -1,15 +1,81 @@
 //! Common types and definitions shared between frontend and driver.
 
+pub mod appliance;
+pub mod bios;
+pub mod clipboard_bitmap;
+pub mod clipboard_rtf;
+pub mod clipboard_text;
+pub mod codepage;
+mod codepage_932;
 pub mod config;
 pub mod config_storage;
+pub mod config_watch;
+pub mod control;
+pub mod crt;
+pub mod cuesheet;
+pub mod disk_check;
+pub mod disk_compact;
+pub mod disk_convert;
+pub mod disk_image;
+pub mod disk_library;
+pub mod disk_resize;
+pub mod diskspace;
+pub mod drive_usage;
 pub mod driver;
+pub mod ducking;
+pub mod el_torito;
+pub mod fat;
+pub mod fat_time;
+pub mod frame_pacing;
+pub mod gameport;
+pub mod guest_os;
+pub mod guest_tools;
+pub mod handoff;
+pub mod history;
+pub mod host_cdrom;
+pub mod image_ref;
+pub mod instance;
 pub mod ioctl;
+pub mod iso9660;
+pub mod keymacro;
+pub mod log_file;
+pub mod mdns;
+pub mod media_check;
+pub mod midi;
+pub mod nat;
+pub mod netsetup;
+pub mod overlay;
+pub mod path_watch;
+pub mod placement;
+pub mod progress;
+pub mod removable;
+pub mod resample;
+pub mod saved_state;
+pub mod scaling;
+pub mod scancode;
 pub mod scsi;
+pub mod session_log;
+pub mod settings_bundle;
+pub mod sha256;
+pub mod short_name;
+pub mod soak;
+pub mod softsynth;
+pub mod startup;
+pub mod tasks;
+pub mod text_render;
+pub mod text_screen;
 pub mod types;
+pub mod ui_json;
+mod vga_font;
+pub mod virtual_cd;
+pub mod watchdog;
 
 pub use config::*;
 pub use config_storage::*;
-pub use driver::{is_driver_loaded, DriverHandle};
+pub use driver::{
+    check_compatibility, is_driver_loaded, list_devices, select_card, selected_card, Compatibility,
+    DriverEvents, DriverHandle, EventListener,
+};
 // Note: ioctl module is NOT re-exported via `pub use *` to avoid naming conflicts.
 // Use `rising_sun_common::ioctl::*` directly for kernel interface types.
 pub use types::*;
@@ -0,0 +1,140 @@
+//! Log file rotated by size.
+//!
+//! With `[log] file` set the frontend writes its log to `rising-sun.log` in
+//! the log directory as well. Once the file would grow past the configured
+//! size it is renamed to `rising-sun.log.1`, older files moving up to `.2`,
+//! `.3` and so on; the ones beyond `max_files` are deleted.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::config::LogConfig;
+
+/// Name of the current log file
+pub const LOG_FILE_NAME: &str = "rising-sun.log";
+
+/// Log file that starts over in a new file when it gets too big
+pub struct RotatingFile {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: u32,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFile {
+    /// Open (or create) the log file in `dir`, appending to it
+    pub fn open(dir: &Path, max_bytes: u64, max_files: u32) -> Result<Self> {
+        fs::create_dir_all(dir).with_context(|| format!("Cannot create {}", dir.display()))?;
+        let path = dir.join(LOG_FILE_NAME);
+        let file = open_append(&path).with_context(|| format!("Cannot open {}", path.display()))?;
+        let written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        Ok(Self {
+            path,
+            max_bytes: max_bytes.max(1),
+            max_files,
+            file,
+            written,
+        })
+    }
+
+    /// Open the log file the `[log]` settings describe, in `dir`
+    pub fn from_config(dir: &Path, config: &LogConfig) -> Result<Self> {
+        Self::open(dir, u64::from(config.max_file_kb) * 1024, config.max_files)
+    }
+
+    /// Path of the current log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the `n`th most recent rotated file
+    pub fn rotated_path(&self, n: u32) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    }
+
+    /// Move the current file aside and start a new one
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if self.max_files == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            let _ = fs::remove_file(self.rotated_path(self.max_files));
+            for n in (1..self.max_files).rev() {
+                let from = self.rotated_path(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(n + 1))?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A line longer than the limit still goes into a file of its own
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RotatingFile::open(dir.path(), 20, 2).unwrap();
+        for line in ["one 12345\n", "two 12345\n", "three 123\n", "four 1234\n", "five 1234\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+        log.flush().unwrap();
+
+        let read = |path: PathBuf| fs::read_to_string(path).unwrap();
+        assert_eq!(read(log.path().to_path_buf()), "five 1234\n");
+        assert_eq!(read(log.rotated_path(1)), "three 123\nfour 1234\n");
+        assert_eq!(read(log.rotated_path(2)), "one 12345\ntwo 12345\n");
+        assert!(!log.rotated_path(3).exists());
+
+        // Reopening appends and counts what is already there
+        drop(log);
+        let mut log = RotatingFile::open(dir.path(), 20, 2).unwrap();
+        log.write_all(b"six 12345\n").unwrap();
+        log.write_all(b"seven 123\n").unwrap();
+        assert_eq!(read(log.path().to_path_buf()), "seven 123\n");
+        assert_eq!(read(log.rotated_path(1)), "five 1234\nsix 12345\n");
+        assert_eq!(read(log.rotated_path(2)), "three 123\nfour 1234\n");
+    }
+
+    #[test]
+    fn test_no_rotated_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = RotatingFile::open(dir.path(), 8, 0).unwrap();
+        log.write_all(b"first\n").unwrap();
+        log.write_all(b"second\n").unwrap();
+        assert_eq!(fs::read_to_string(log.path()).unwrap(), "second\n");
+        assert!(!log.rotated_path(1).exists());
+    }
+}
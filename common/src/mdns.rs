@@ -0,0 +1,649 @@
+//! mDNS advertisement and discovery of running sessions.
+//!
+//! When `[remote] advertise` is set, a running session is announced on the
+//! LAN as a DNS-SD service of type `_risingsun._tcp` (RFC 6762/6763), so
+//! other instances and the CLI can find it without knowing the host:
+//!
+//! - PTR `_risingsun._tcp.local` -> `<profile>._risingsun._tcp.local`
+//! - SRV of the instance -> `<hostname>.local`, control port
+//! - TXT of the instance -> `profile=`, `control=`, `vnc=`, `version=`
+//! - A of the host -> the address used for multicast
+//!
+//! `Advertiser` answers queries on the shared mDNS port (alongside Avahi,
+//! both use SO_REUSEADDR) until dropped, when it sends a goodbye so caches
+//! forget the session at once. `browse` sends a one-shot query and collects
+//! the answers. Only IPv4 is handled.
+
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::config::RemoteConfig;
+
+/// Service type sessions are advertised under
+pub const SERVICE_TYPE: &str = "_risingsun._tcp.local";
+
+/// mDNS multicast group and port
+const MDNS_ADDR: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Cache-flush bit on records only we answer for (class field)
+const CLASS_FLUSH: u16 = 0x8000;
+/// Unicast-response bit in questions (class field)
+const CLASS_QU: u16 = 0x8000;
+
+/// Header flags of an authoritative response
+const FLAGS_RESPONSE: u16 = 0x8400;
+const FLAG_QR: u16 = 0x8000;
+
+/// Record lifetime in seconds (RFC 6762 recommends 120 for host records)
+const RECORD_TTL: u32 = 120;
+
+/// Longest DNS label
+const MAX_LABEL_LEN: usize = 63;
+
+/// How often the responder checks whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Gap between the two startup announcements
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A session advertised on the LAN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceInfo {
+    /// Instance name (the profile name)
+    pub instance: String,
+    /// Host name, without ".local"
+    pub host: String,
+    /// Host address
+    pub addr: Option<Ipv4Addr>,
+    /// Control API port (0 = none)
+    pub port: u16,
+    /// TXT record entries
+    pub txt: Vec<(String, String)>,
+}
+
+impl ServiceInfo {
+    /// Describe this host's session from the remote settings
+    pub fn for_session(remote: &RemoteConfig) -> Self {
+        let host = host_name();
+        let profile = if remote.profile_name.trim().is_empty() {
+            host.clone()
+        } else {
+            remote.profile_name.trim().to_string()
+        };
+
+        // A control API on loopback is of no use to anyone browsing
+        let control_port = remote.control_port.filter(|_| remote.control_is_remote());
+        let mut txt = vec![("profile".to_string(), profile.clone())];
+        if let Some(port) = control_port {
+            txt.push(("control".to_string(), port.to_string()));
+        }
+        if let Some(port) = remote.vnc_port {
+            txt.push(("vnc".to_string(), port.to_string()));
+        }
+        txt.push(("version".to_string(), env!("CARGO_PKG_VERSION").to_string()));
+
+        Self {
+            instance: truncate_label(&profile),
+            host: truncate_label(&host),
+            addr: None,
+            port: control_port.unwrap_or(0),
+            txt,
+        }
+    }
+
+    /// Value of a TXT entry
+    pub fn txt_value(&self, key: &str) -> Option<&str> {
+        self.txt.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Profile name (falls back to the instance name)
+    pub fn profile(&self) -> &str {
+        self.txt_value("profile").unwrap_or(&self.instance)
+    }
+
+    /// Control API port, if the session has one
+    pub fn control_port(&self) -> Option<u16> {
+        self.txt_value("control").and_then(|p| p.parse().ok())
+    }
+
+    /// VNC port, if the session has one
+    pub fn vnc_port(&self) -> Option<u16> {
+        self.txt_value("vnc").and_then(|p| p.parse().ok())
+    }
+
+    fn instance_name(&self) -> String {
+        format!("{}.{}", self.instance, SERVICE_TYPE)
+    }
+
+    fn host_name(&self) -> String {
+        format!("{}.local", self.host)
+    }
+}
+
+/// Advertises a session until dropped
+pub struct Advertiser {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Advertiser {
+    /// Join the mDNS group, announce the session and start answering queries
+    pub fn start(mut info: ServiceInfo) -> io::Result<Self> {
+        let socket = bind_mdns_socket()?;
+        info.addr = info.addr.or_else(local_address);
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = std::thread::Builder::new()
+            .name("mdns".to_string())
+            .spawn(move || responder_thread(socket, info, thread_running))?;
+        Ok(Self {
+            running,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Drop for Advertiser {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Answer queries for `info` until `running` is cleared, then say goodbye
+fn responder_thread(socket: UdpSocket, info: ServiceInfo, running: Arc<AtomicBool>) {
+    let group = SocketAddr::V4(SocketAddrV4::new(MDNS_ADDR, MDNS_PORT));
+    let announcement = encode_response(0, &info, RECORD_TTL);
+    let mut announcements = 0;
+    let mut next_announcement = Instant::now();
+    let mut buf = [0u8; 9000];
+
+    while running.load(Ordering::SeqCst) {
+        if announcements < 2 && Instant::now() >= next_announcement {
+            let _ = socket.send_to(&announcement, group);
+            announcements += 1;
+            next_announcement = Instant::now() + ANNOUNCE_INTERVAL;
+        }
+
+        let (len, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                continue;
+            }
+            Err(_) => break,
+        };
+        let Some(query) = parse_message(&buf[..len]) else {
+            continue;
+        };
+        if query.is_response || !query.questions.iter().any(|q| answers_question(&info, q)) {
+            continue;
+        }
+
+        // One-shot queriers (not from port 5353) get a unicast reply
+        // echoing their query id; everyone else shares the multicast one
+        let legacy = from.port() != MDNS_PORT;
+        let unicast = legacy || query.questions.iter().all(|q| q.class & CLASS_QU != 0);
+        let id = if legacy { query.id } else { 0 };
+        let target = if unicast { from } else { group };
+        let _ = socket.send_to(&encode_response(id, &info, RECORD_TTL), target);
+    }
+
+    // TTL 0 tells caches the session is gone
+    let _ = socket.send_to(&encode_response(0, &info, 0), group);
+}
+
+/// Whether a question asks for one of our records
+fn answers_question(info: &ServiceInfo, question: &Question) -> bool {
+    let name = &question.name;
+    match question.qtype {
+        TYPE_PTR => name.eq_ignore_ascii_case(SERVICE_TYPE),
+        TYPE_SRV | TYPE_TXT => name.eq_ignore_ascii_case(&info.instance_name()),
+        TYPE_A => name.eq_ignore_ascii_case(&info.host_name()),
+        TYPE_ANY => {
+            name.eq_ignore_ascii_case(SERVICE_TYPE)
+                || name.eq_ignore_ascii_case(&info.instance_name())
+                || name.eq_ignore_ascii_case(&info.host_name())
+        }
+        _ => false,
+    }
+}
+
+/// Look for advertised sessions on the LAN
+///
+/// Sends one query and collects answers for `timeout`. Sessions are
+/// returned in the order they answered, without duplicates.
+pub fn browse(timeout: Duration) -> io::Result<Vec<ServiceInfo>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_multicast_ttl_v4(255)?;
+    socket.send_to(&encode_query(SERVICE_TYPE, TYPE_PTR), (MDNS_ADDR, MDNS_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut found: Vec<ServiceInfo> = Vec::new();
+    let mut buf = [0u8; 9000];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv_from(&mut buf) {
+            Ok((len, _)) => len,
+            Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let Some(message) = parse_message(&buf[..len]) else {
+            continue;
+        };
+        for service in services_in(&message) {
+            if !found.iter().any(|s| s.instance == service.instance && s.host == service.host) {
+                found.push(service);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Bind the shared mDNS port and join the multicast group
+fn bind_mdns_socket() -> io::Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT)).into())?;
+
+    let socket: UdpSocket = socket.into();
+    socket.join_multicast_v4(&MDNS_ADDR, &Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_ttl_v4(255)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_read_timeout(Some(POLL_INTERVAL))?;
+    Ok(socket)
+}
+
+/// Address of the interface multicast leaves from
+///
+/// Connecting a UDP socket sends nothing; it only picks the route.
+fn local_address() -> Option<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((MDNS_ADDR, MDNS_PORT)).ok()?;
+    match socket.local_addr().ok()? {
+        SocketAddr::V4(addr) if !addr.ip().is_unspecified() => Some(*addr.ip()),
+        _ => None,
+    }
+}
+
+/// This machine's host name
+pub(crate) fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .map(|name| name.trim().split('.').next().unwrap_or_default().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "rising-sun".to_string())
+}
+
+/// Shorten a name to fit one DNS label without splitting a character
+fn truncate_label(name: &str) -> String {
+    let mut end = name.len().min(MAX_LABEL_LEN);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    name[..end].to_string()
+}
+
+/// A question from a parsed message
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Question {
+    name: String,
+    qtype: u16,
+    class: u16,
+}
+
+/// Record data this module understands
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum RecordData {
+    Ptr(String),
+    Srv { port: u16, target: String },
+    Txt(Vec<String>),
+    A(Ipv4Addr),
+    Other,
+}
+
+/// A resource record from a parsed message
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Record {
+    name: String,
+    ttl: u32,
+    data: RecordData,
+}
+
+/// A parsed DNS message
+#[derive(Debug, Clone, Default)]
+struct Message {
+    id: u16,
+    is_response: bool,
+    questions: Vec<Question>,
+    records: Vec<Record>,
+}
+
+/// Build a query for one name and type
+fn encode_query(name: &str, qtype: u16) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64);
+    put_header(&mut out, 0, 0, 1, 0, 0);
+    put_name(&mut out, name);
+    out.extend_from_slice(&qtype.to_be_bytes());
+    out.extend_from_slice(&CLASS_IN.to_be_bytes());
+    out
+}
+
+/// Build the full answer for a session: PTR, then SRV, TXT and A as extras
+fn encode_response(id: u16, info: &ServiceInfo, ttl: u32) -> Vec<u8> {
+    let instance = info.instance_name();
+    let host = info.host_name();
+    let additional = if info.addr.is_some() { 3 } else { 2 };
+
+    let mut out = Vec::with_capacity(512);
+    put_header(&mut out, id, FLAGS_RESPONSE, 0, 1, additional);
+
+    put_record(&mut out, SERVICE_TYPE, TYPE_PTR, CLASS_IN, ttl, |rdata| {
+        put_name(rdata, &instance)
+    });
+    put_record(&mut out, &instance, TYPE_SRV, CLASS_IN | CLASS_FLUSH, ttl, |rdata| {
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&0u16.to_be_bytes());
+        rdata.extend_from_slice(&info.port.to_be_bytes());
+        put_name(rdata, &host);
+    });
+    put_record(&mut out, &instance, TYPE_TXT, CLASS_IN | CLASS_FLUSH, ttl, |rdata| {
+        for (key, value) in &info.txt {
+            let entry = format!("{}={}", key, value);
+            let len = entry.len().min(u8::MAX as usize);
+            rdata.push(len as u8);
+            rdata.extend_from_slice(&entry.as_bytes()[..len]);
+        }
+    });
+    if let Some(addr) = info.addr {
+        put_record(&mut out, &host, TYPE_A, CLASS_IN | CLASS_FLUSH, ttl, |rdata| {
+            rdata.extend_from_slice(&addr.octets())
+        });
+    }
+    out
+}
+
+fn put_header(out: &mut Vec<u8>, id: u16, flags: u16, questions: u16, answers: u16, additional: u16) {
+    for field in [id, flags, questions, answers, 0, additional] {
+        out.extend_from_slice(&field.to_be_bytes());
+    }
+}
+
+/// Append a name as labels; the first label of an instance name may hold dots
+fn put_name(out: &mut Vec<u8>, name: &str) {
+    let labels: Vec<&str> = match name.strip_suffix(SERVICE_TYPE) {
+        Some(instance) if !instance.is_empty() => {
+            let mut labels = vec![instance.trim_end_matches('.')];
+            labels.extend(SERVICE_TYPE.split('.'));
+            labels
+        }
+        _ => name.split('.').collect(),
+    };
+    for label in labels.into_iter().filter(|l| !l.is_empty()) {
+        let len = label.len().min(MAX_LABEL_LEN);
+        out.push(len as u8);
+        out.extend_from_slice(&label.as_bytes()[..len]);
+    }
+    out.push(0);
+}
+
+fn put_record(out: &mut Vec<u8>, name: &str, rtype: u16, class: u16, ttl: u32, rdata: impl FnOnce(&mut Vec<u8>)) {
+    put_name(out, name);
+    out.extend_from_slice(&rtype.to_be_bytes());
+    out.extend_from_slice(&class.to_be_bytes());
+    out.extend_from_slice(&ttl.to_be_bytes());
+    let len_at = out.len();
+    out.extend_from_slice(&[0, 0]);
+    rdata(out);
+    let len = (out.len() - len_at - 2) as u16;
+    out[len_at..len_at + 2].copy_from_slice(&len.to_be_bytes());
+}
+
+/// Parse a DNS message; None if it is malformed
+fn parse_message(buf: &[u8]) -> Option<Message> {
+    let field = |i: usize| read_u16(buf, i * 2);
+    let flags = field(1)?;
+    let counts = [field(2)?, field(3)?, field(4)?, field(5)?];
+    let mut message = Message {
+        id: field(0)?,
+        is_response: flags & FLAG_QR != 0,
+        ..Default::default()
+    };
+
+    let mut pos = 12;
+    for _ in 0..counts[0] {
+        let name = read_name(buf, &mut pos)?;
+        message.questions.push(Question {
+            name,
+            qtype: read_u16(buf, pos)?,
+            class: read_u16(buf, pos + 2)?,
+        });
+        pos += 4;
+    }
+
+    let records = counts[1..].iter().map(|&c| c as usize).sum::<usize>();
+    for _ in 0..records {
+        let name = read_name(buf, &mut pos)?;
+        let rtype = read_u16(buf, pos)?;
+        let ttl = u32::from(read_u16(buf, pos + 4)?) << 16 | u32::from(read_u16(buf, pos + 6)?);
+        let rdlen = read_u16(buf, pos + 8)? as usize;
+        let start = pos + 10;
+        let end = start.checked_add(rdlen).filter(|&end| end <= buf.len())?;
+
+        let data = match rtype {
+            TYPE_PTR => RecordData::Ptr(read_name(buf, &mut start.clone())?),
+            TYPE_SRV => RecordData::Srv {
+                port: read_u16(buf, start + 4)?,
+                target: read_name(buf, &mut (start + 6))?,
+            },
+            TYPE_TXT => RecordData::Txt(parse_txt(&buf[start..end])),
+            TYPE_A if rdlen == 4 => RecordData::A(Ipv4Addr::new(
+                buf[start],
+                buf[start + 1],
+                buf[start + 2],
+                buf[start + 3],
+            )),
+            _ => RecordData::Other,
+        };
+        message.records.push(Record { name, ttl, data });
+        pos = end;
+    }
+    Some(message)
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*buf.get(pos)?, *buf.get(pos + 1)?]))
+}
+
+/// Read a possibly compressed name at `pos`, advancing past it
+fn read_name(buf: &[u8], pos: &mut usize) -> Option<String> {
+    let mut labels = Vec::new();
+    let mut at = *pos;
+    let mut jumped = false;
+
+    // Bounded so pointer loops cannot hang the parser
+    for _ in 0..128 {
+        let len = *buf.get(at)? as usize;
+        match len {
+            0 => {
+                if !jumped {
+                    *pos = at + 1;
+                }
+                return Some(labels.join("."));
+            }
+            l if l & 0xC0 == 0xC0 => {
+                if !jumped {
+                    *pos = at + 2;
+                }
+                at = (read_u16(buf, at)? & 0x3FFF) as usize;
+                jumped = true;
+            }
+            l if l <= MAX_LABEL_LEN => {
+                let label = buf.get(at + 1..at + 1 + l)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                at += 1 + l;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn parse_txt(rdata: &[u8]) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut at = 0;
+    while let Some(&len) = rdata.get(at) {
+        let Some(entry) = rdata.get(at + 1..at + 1 + len as usize) else {
+            break;
+        };
+        if !entry.is_empty() {
+            entries.push(String::from_utf8_lossy(entry).into_owned());
+        }
+        at += 1 + len as usize;
+    }
+    entries
+}
+
+/// Sessions described by the records of a response
+///
+/// Goodbyes (TTL 0) are skipped.
+fn services_in(message: &Message) -> Vec<ServiceInfo> {
+    let find = |name: &str, pick: &dyn Fn(&RecordData) -> bool| {
+        message
+            .records
+            .iter()
+            .find(|r| r.name.eq_ignore_ascii_case(name) && pick(&r.data))
+            .map(|r| &r.data)
+    };
+
+    let mut services = Vec::new();
+    for record in &message.records {
+        let RecordData::Ptr(instance_name) = &record.data else {
+            continue;
+        };
+        if record.ttl == 0 || !record.name.eq_ignore_ascii_case(SERVICE_TYPE) {
+            continue;
+        }
+        let Some(RecordData::Srv { port, target }) =
+            find(instance_name, &|d| matches!(d, RecordData::Srv { .. }))
+        else {
+            continue;
+        };
+
+        let instance = instance_name
+            .strip_suffix(SERVICE_TYPE)
+            .unwrap_or(instance_name)
+            .trim_end_matches('.')
+            .to_string();
+        let txt = match find(instance_name, &|d| matches!(d, RecordData::Txt(_))) {
+            Some(RecordData::Txt(entries)) => entries
+                .iter()
+                .map(|e| match e.split_once('=') {
+                    Some((k, v)) => (k.to_string(), v.to_string()),
+                    None => (e.clone(), String::new()),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let addr = match find(target, &|d| matches!(d, RecordData::A(_))) {
+            Some(RecordData::A(addr)) => Some(*addr),
+            _ => None,
+        };
+
+        services.push(ServiceInfo {
+            instance,
+            host: target.strip_suffix(".local").unwrap_or(target).to_string(),
+            addr,
+            port: *port,
+            txt,
+        });
+    }
+    services
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_round_trip() {
+        let remote = RemoteConfig {
+            advertise: true,
+            profile_name: "Win98 lab".to_string(),
+            control_port: Some(7070),
+            control_address: Some(Ipv4Addr::UNSPECIFIED.into()),
+            control_token: "secret".to_string(),
+            vnc_port: Some(5901),
+        };
+        let mut info = ServiceInfo::for_session(&remote);
+        info.host = "lab1".to_string();
+        info.addr = Some(Ipv4Addr::new(192, 168, 1, 20));
+
+        let message = parse_message(&encode_response(0, &info, RECORD_TTL)).unwrap();
+        assert!(message.is_response);
+        let services = services_in(&message);
+        assert_eq!(services, vec![info.clone()]);
+        assert_eq!(services[0].profile(), "Win98 lab");
+        assert_eq!(services[0].control_port(), Some(7070));
+        assert_eq!(services[0].vnc_port(), Some(5901));
+
+        // A control API that only listens on loopback is not advertised
+        let local = RemoteConfig { control_address: None, ..remote };
+        assert_eq!(ServiceInfo::for_session(&local).control_port(), None);
+
+        // Goodbyes do not count as sessions
+        let goodbye = parse_message(&encode_response(0, &info, 0)).unwrap();
+        assert!(services_in(&goodbye).is_empty());
+    }
+
+    #[test]
+    fn test_query_matching() {
+        let info = ServiceInfo {
+            instance: "dos".to_string(),
+            host: "lab1".to_string(),
+            addr: None,
+            port: 0,
+            txt: Vec::new(),
+        };
+        let query = parse_message(&encode_query("_RisingSun._tcp.local", TYPE_PTR)).unwrap();
+        assert!(!query.is_response);
+        assert!(answers_question(&info, &query.questions[0]));
+
+        let query = parse_message(&encode_query("_http._tcp.local", TYPE_PTR)).unwrap();
+        assert!(!answers_question(&info, &query.questions[0]));
+
+        // Compressed names (pointer back to offset 12)
+        let mut packet = encode_query(SERVICE_TYPE, TYPE_PTR);
+        packet[5] = 2;
+        packet.extend_from_slice(&[0xC0, 12, 0, TYPE_SRV as u8, 0, 1]);
+        let query = parse_message(&packet).unwrap();
+        assert_eq!(query.questions[1].name, SERVICE_TYPE);
+
+        // Pointer loops are rejected
+        let mut packet = encode_query(SERVICE_TYPE, TYPE_PTR);
+        packet.truncate(12);
+        packet.extend_from_slice(&[0xC0, 12, 0, 12, 0, 1]);
+        assert!(parse_message(&packet).is_none());
+    }
+}
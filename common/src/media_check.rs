@@ -0,0 +1,429 @@
+//! Startup check of the media a profile refers to.
+//!
+//! A profile names up to five images: two hard disks, a CD-ROM ISO and two
+//! floppies. Without a check, a missing or unusable image only shows up as
+//! a failed mount once the session is running. `check_media` looks at all of
+//! them when the profile is loaded and returns a `MediaReport` listing each
+//! problem with the fix-ups the UI can offer.
+//!
+//! Running sessions hold an advisory lock (`flock`) on their images through
+//! `MediaLock`: exclusive for writable images, shared for read-only ones. A
+//! lock that cannot be taken means another instance is using the image.
+//! Locks held by this process are remembered, so re-checking a profile while
+//! its session runs does not report the session's own images.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+
+use crate::config::StorageConfig;
+
+/// Images this process holds a `MediaLock` on
+static HELD_LOCKS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Drive an image is attached to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaSlot {
+    PrimaryDisk,
+    SecondaryDisk,
+    Cdrom,
+    FloppyA,
+    FloppyB,
+}
+
+impl MediaSlot {
+    pub const ALL: [MediaSlot; 5] = [
+        MediaSlot::PrimaryDisk,
+        MediaSlot::SecondaryDisk,
+        MediaSlot::Cdrom,
+        MediaSlot::FloppyA,
+        MediaSlot::FloppyB,
+    ];
+
+    /// Stable identifier for the UI ("primary", "secondary", "cdrom", "floppy_a", "floppy_b")
+    pub fn id(self) -> &'static str {
+        match self {
+            MediaSlot::PrimaryDisk => "primary",
+            MediaSlot::SecondaryDisk => "secondary",
+            MediaSlot::Cdrom => "cdrom",
+            MediaSlot::FloppyA => "floppy_a",
+            MediaSlot::FloppyB => "floppy_b",
+        }
+    }
+
+    /// Parse an identifier returned by `id`
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.id() == id)
+    }
+
+    /// Name shown to the user
+    pub fn label(self) -> &'static str {
+        match self {
+            MediaSlot::PrimaryDisk => "Hard disk C:",
+            MediaSlot::SecondaryDisk => "Hard disk D:",
+            MediaSlot::Cdrom => "CD-ROM",
+            MediaSlot::FloppyA => "Floppy A:",
+            MediaSlot::FloppyB => "Floppy B:",
+        }
+    }
+
+    /// Image configured for this slot
+    pub fn path(self, storage: &StorageConfig) -> Option<&Path> {
+        match self {
+            MediaSlot::PrimaryDisk => storage.primary_disk.as_ref().map(|d| d.path.as_path()),
+            MediaSlot::SecondaryDisk => storage.secondary_disk.as_ref().map(|d| d.path.as_path()),
+            MediaSlot::Cdrom => storage.cdrom.mounted_iso.as_deref(),
+            MediaSlot::FloppyA => storage.floppy_a.mounted_image.as_deref(),
+            MediaSlot::FloppyB => storage.floppy_b.mounted_image.as_deref(),
+        }
+    }
+
+    /// Whether the guest writes to the image
+    pub fn is_writable(self, storage: &StorageConfig) -> bool {
+        match self {
+            MediaSlot::PrimaryDisk | MediaSlot::SecondaryDisk => true,
+            MediaSlot::Cdrom => false,
+            MediaSlot::FloppyA => !storage.floppy_a.write_protected,
+            MediaSlot::FloppyB => !storage.floppy_b.write_protected,
+        }
+    }
+
+    /// Where the image can be downloaded again, if known
+    pub fn source_url(self, storage: &StorageConfig) -> Option<&str> {
+        match self {
+            MediaSlot::PrimaryDisk | MediaSlot::SecondaryDisk => None,
+            MediaSlot::Cdrom => storage.cdrom.source_url.as_deref(),
+            MediaSlot::FloppyA => storage.floppy_a.source_url.as_deref(),
+            MediaSlot::FloppyB => storage.floppy_b.source_url.as_deref(),
+        }
+    }
+
+    /// Detach the image from the profile
+    pub fn remove(self, storage: &mut StorageConfig) {
+        match self {
+            MediaSlot::PrimaryDisk => storage.primary_disk = None,
+            MediaSlot::SecondaryDisk => storage.secondary_disk = None,
+            MediaSlot::Cdrom => {
+                storage.cdrom.mounted_iso = None;
+                storage.cdrom.source_url = None;
+            }
+            MediaSlot::FloppyA => {
+                storage.floppy_a.mounted_image = None;
+                storage.floppy_a.source_url = None;
+            }
+            MediaSlot::FloppyB => {
+                storage.floppy_b.mounted_image = None;
+                storage.floppy_b.source_url = None;
+            }
+        }
+    }
+
+    /// Point the slot at another image (e.g. one the user browsed to)
+    ///
+    /// The disk's content id is cleared; it is recomputed the next time the
+    /// profile is loaded.
+    pub fn replace(self, storage: &mut StorageConfig, path: PathBuf) {
+        match self {
+            MediaSlot::PrimaryDisk => {
+                if let Some(disk) = storage.primary_disk.as_mut() {
+                    disk.path = path;
+                    disk.content_id = None;
+                }
+            }
+            MediaSlot::SecondaryDisk => {
+                if let Some(disk) = storage.secondary_disk.as_mut() {
+                    disk.path = path;
+                    disk.content_id = None;
+                }
+            }
+            MediaSlot::Cdrom => storage.cdrom.mounted_iso = Some(path),
+            MediaSlot::FloppyA => storage.floppy_a.mounted_image = Some(path),
+            MediaSlot::FloppyB => storage.floppy_b.mounted_image = Some(path),
+        }
+    }
+}
+
+/// What is wrong with an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaProblem {
+    /// The file does not exist
+    Missing,
+    /// The path exists but is not a regular file
+    NotAFile,
+    /// The file cannot be opened for reading
+    Unreadable(String),
+    /// The guest writes to the image but the file is read-only
+    ReadOnly,
+    /// Another instance holds a lock on the image
+    Locked,
+}
+
+impl fmt::Display for MediaProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MediaProblem::Missing => write!(f, "file not found"),
+            MediaProblem::NotAFile => write!(f, "not a regular file"),
+            MediaProblem::Unreadable(e) => write!(f, "cannot be read: {}", e),
+            MediaProblem::ReadOnly => write!(f, "file is read-only"),
+            MediaProblem::Locked => write!(f, "in use by another instance"),
+        }
+    }
+}
+
+/// Fix-up the UI can offer for a problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FixAction {
+    /// Choose another image for the slot
+    Browse,
+    /// Detach the image from the profile
+    Remove,
+    /// Fetch the image again from where it came from
+    Download(String),
+}
+
+impl FixAction {
+    /// Identifier for the UI ("browse", "remove", "download")
+    pub fn id(&self) -> &'static str {
+        match self {
+            FixAction::Browse => "browse",
+            FixAction::Remove => "remove",
+            FixAction::Download(_) => "download",
+        }
+    }
+}
+
+/// One image that cannot be used as configured
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaIssue {
+    pub slot: MediaSlot,
+    pub path: PathBuf,
+    pub problem: MediaProblem,
+    pub actions: Vec<FixAction>,
+}
+
+impl fmt::Display for MediaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}): {}", self.slot.label(), self.path.display(), self.problem)
+    }
+}
+
+/// Result of checking a profile's media
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaReport {
+    pub issues: Vec<MediaIssue>,
+}
+
+impl MediaReport {
+    /// Whether every referenced image is usable
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Issue for a slot, if any
+    pub fn issue(&self, slot: MediaSlot) -> Option<&MediaIssue> {
+        self.issues.iter().find(|i| i.slot == slot)
+    }
+}
+
+/// Check every image referenced by the storage configuration
+pub fn check_media(storage: &StorageConfig) -> MediaReport {
+    let issues = MediaSlot::ALL
+        .into_iter()
+        .filter_map(|slot| {
+            let path = slot.path(storage)?;
+            let problem = check_image(path, slot.is_writable(storage)).err()?;
+            let mut actions = vec![FixAction::Browse, FixAction::Remove];
+            if problem == MediaProblem::Missing
+                && let Some(url) = slot.source_url(storage)
+            {
+                actions.push(FixAction::Download(url.to_string()));
+            }
+            Some(MediaIssue {
+                slot,
+                path: path.to_path_buf(),
+                problem,
+                actions,
+            })
+        })
+        .collect();
+    MediaReport { issues }
+}
+
+/// Check one image; `writable` if the guest writes to it
+pub fn check_image(path: &Path, writable: bool) -> Result<(), MediaProblem> {
+    let meta = match std::fs::metadata(path) {
+        Ok(meta) => meta,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(MediaProblem::Missing),
+        Err(e) => return Err(MediaProblem::Unreadable(e.to_string())),
+    };
+    // Block devices (real floppy and CD drives) are fine too
+    if meta.is_dir() {
+        return Err(MediaProblem::NotAFile);
+    }
+
+    let file = File::open(path).map_err(|e| MediaProblem::Unreadable(e.to_string()))?;
+    let file = if writable {
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(MediaProblem::ReadOnly),
+            Err(e) => return Err(MediaProblem::Unreadable(e.to_string())),
+        }
+    } else {
+        file
+    };
+
+    if is_held(path) {
+        return Ok(());
+    }
+    match try_lock(file, writable) {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(MediaProblem::Locked),
+        // Filesystems without flock support cannot be checked
+        Err(_) => Ok(()),
+    }
+}
+
+/// Advisory lock on an image for the duration of a session
+///
+/// Released (and forgotten) on drop.
+#[derive(Debug)]
+pub struct MediaLock {
+    _lock: Flock<File>,
+    path: PathBuf,
+}
+
+impl MediaLock {
+    /// Lock an image: exclusively if the guest writes to it, shared otherwise
+    ///
+    /// Fails with `ErrorKind::WouldBlock` if another instance holds it.
+    pub fn acquire(path: &Path, writable: bool) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(writable).open(path)?;
+        let lock = try_lock(file, writable)?;
+        let path = lock_key(path);
+        if let Ok(mut held) = HELD_LOCKS.lock() {
+            held.push(path.clone());
+        }
+        Ok(Self { _lock: lock, path })
+    }
+
+    /// Image this lock is held on
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for MediaLock {
+    fn drop(&mut self) {
+        if let Ok(mut held) = HELD_LOCKS.lock()
+            && let Some(pos) = held.iter().position(|p| *p == self.path)
+        {
+            held.swap_remove(pos);
+        }
+    }
+}
+
+/// Lock every image of a profile for a session
+///
+/// Images that are missing are skipped (the check reports those). Fails
+/// with the first image another instance is using.
+pub fn lock_media(storage: &StorageConfig) -> Result<Vec<MediaLock>, MediaIssue> {
+    let mut locks = Vec::new();
+    for slot in MediaSlot::ALL {
+        let Some(path) = slot.path(storage) else {
+            continue;
+        };
+        if is_held(path) {
+            continue;
+        }
+        match MediaLock::acquire(path, slot.is_writable(storage)) {
+            Ok(lock) => locks.push(lock),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                return Err(MediaIssue {
+                    slot,
+                    path: path.to_path_buf(),
+                    problem: MediaProblem::Locked,
+                    actions: vec![FixAction::Browse, FixAction::Remove],
+                });
+            }
+            Err(_) => {}
+        }
+    }
+    Ok(locks)
+}
+
+fn try_lock(file: File, exclusive: bool) -> io::Result<Flock<File>> {
+    let arg = if exclusive {
+        FlockArg::LockExclusiveNonblock
+    } else {
+        FlockArg::LockSharedNonblock
+    };
+    Flock::lock(file, arg).map_err(|(_, errno)| match errno {
+        Errno::EWOULDBLOCK => io::ErrorKind::WouldBlock.into(),
+        e => io::Error::from(e),
+    })
+}
+
+fn lock_key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn is_held(path: &Path) -> bool {
+    let key = lock_key(path);
+    HELD_LOCKS.lock().map(|held| held.contains(&key)).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DiskConfig;
+
+    #[test]
+    fn test_check_media() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("c.img");
+        std::fs::write(&disk, [0u8; 512]).unwrap();
+
+        let mut storage = StorageConfig {
+            primary_disk: Some(DiskConfig {
+                path: disk.clone(),
+                bootable: true,
+                content_id: None,
+                overlay: None,
+            }),
+            ..Default::default()
+        };
+        storage.cdrom.mounted_iso = Some(dir.path().join("missing.iso"));
+        storage.cdrom.source_url = Some("https://example.com/tools.iso".to_string());
+        storage.floppy_a.mounted_image = Some(dir.path().to_path_buf());
+
+        let report = check_media(&storage);
+        assert_eq!(report.issues.len(), 2);
+        let cd = report.issue(MediaSlot::Cdrom).unwrap();
+        assert_eq!(cd.problem, MediaProblem::Missing);
+        assert_eq!(cd.actions.last().map(FixAction::id), Some("download"));
+        assert_eq!(report.issue(MediaSlot::FloppyA).unwrap().problem, MediaProblem::NotAFile);
+
+        MediaSlot::Cdrom.remove(&mut storage);
+        MediaSlot::FloppyA.remove(&mut storage);
+        assert!(check_media(&storage).is_ok());
+
+        // A lock from "another instance" (a separate open file description)
+        let other = Flock::lock(File::open(&disk).unwrap(), FlockArg::LockSharedNonblock).unwrap();
+        assert_eq!(check_image(&disk, true), Err(MediaProblem::Locked));
+        assert_eq!(check_image(&disk, false), Ok(()));
+        assert!(lock_media(&storage).is_err());
+        drop(other);
+
+        // Our own session's locks are not reported
+        let locks = lock_media(&storage).unwrap();
+        assert_eq!(locks.len(), 1);
+        assert!(check_media(&storage).is_ok());
+        drop(locks);
+        assert!(!is_held(&disk));
+    }
+}
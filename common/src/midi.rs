@@ -0,0 +1,203 @@
+//! MIDI messages from the guest's MPU-401.
+//!
+//! In UART mode the guest writes a plain MIDI byte stream to the MPU-401
+//! data port, and the driver hands it on as written (`READ_MIDI`), split
+//! wherever the reads happen to fall. `MidiParser` puts it back together
+//! into whole messages: it follows running status, lets real-time bytes
+//! through in the middle of other messages, and collects system exclusive
+//! messages up to their end byte. The frontend sends the messages to a
+//! host synthesizer, or plays them on the built-in one (see `softsynth`).
+
+/// Longest system exclusive message kept; longer ones are dropped
+const MAX_SYSEX: usize = 4096;
+
+/// A complete MIDI message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOff { channel: u8, key: u8, velocity: u8 },
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    KeyPressure { channel: u8, key: u8, pressure: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    ChannelPressure { channel: u8, pressure: u8 },
+    /// 14-bit bend, 0x2000 is the centre
+    PitchBend { channel: u8, value: u16 },
+    /// System exclusive, from 0xF0 to 0xF7 inclusive
+    SysEx(Vec<u8>),
+    /// Any other system message, status byte first
+    System(Vec<u8>),
+}
+
+impl MidiMessage {
+    /// The message as MIDI bytes, with its status byte
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match *self {
+            Self::NoteOff { channel, key, velocity } => vec![0x80 | channel, key, velocity],
+            Self::NoteOn { channel, key, velocity } => vec![0x90 | channel, key, velocity],
+            Self::KeyPressure { channel, key, pressure } => vec![0xA0 | channel, key, pressure],
+            Self::ControlChange { channel, controller, value } => vec![0xB0 | channel, controller, value],
+            Self::ProgramChange { channel, program } => vec![0xC0 | channel, program],
+            Self::ChannelPressure { channel, pressure } => vec![0xD0 | channel, pressure],
+            Self::PitchBend { channel, value } => vec![0xE0 | channel, (value & 0x7F) as u8, (value >> 7) as u8 & 0x7F],
+            Self::SysEx(ref bytes) | Self::System(ref bytes) => bytes.clone(),
+        }
+    }
+}
+
+/// Reassembles MIDI messages from a byte stream
+#[derive(Debug, Default)]
+pub struct MidiParser {
+    /// Running status: the last channel status byte
+    status: Option<u8>,
+    /// Data bytes of the message being read
+    data: Vec<u8>,
+    /// System exclusive message being read
+    sysex: Option<Vec<u8>>,
+}
+
+impl MidiParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read `bytes`, appending the messages they complete to `out`
+    pub fn feed(&mut self, bytes: &[u8], out: &mut Vec<MidiMessage>) {
+        for &byte in bytes {
+            if let Some(message) = self.push(byte) {
+                out.push(message);
+            }
+        }
+    }
+
+    /// Read one byte, returning the message it completes
+    pub fn push(&mut self, byte: u8) -> Option<MidiMessage> {
+        // Real-time messages may come between any two bytes
+        if byte >= 0xF8 {
+            return Some(MidiMessage::System(vec![byte]));
+        }
+        if let Some(sysex) = &mut self.sysex {
+            if byte < 0x80 {
+                if sysex.len() < MAX_SYSEX {
+                    sysex.push(byte);
+                }
+                return None;
+            }
+            let mut sysex = self.sysex.take().unwrap_or_default();
+            if byte == 0xF7 {
+                sysex.push(byte);
+                return (sysex.len() <= MAX_SYSEX).then_some(MidiMessage::SysEx(sysex));
+            }
+            // Any other status byte ends the message early; read it as usual
+        }
+
+        if byte >= 0x80 {
+            self.data.clear();
+            match byte {
+                0xF0 => {
+                    self.status = None;
+                    self.sysex = Some(vec![byte]);
+                    None
+                }
+                // Tune request
+                0xF6 => {
+                    self.status = None;
+                    Some(MidiMessage::System(vec![byte]))
+                }
+                0xF1..=0xF3 => {
+                    self.status = Some(byte);
+                    None
+                }
+                // Undefined, and a stray end of exclusive
+                0xF4 | 0xF5 | 0xF7 => {
+                    self.status = None;
+                    None
+                }
+                _ => {
+                    self.status = Some(byte);
+                    None
+                }
+            }
+        } else {
+            let status = self.status?;
+            self.data.push(byte);
+            if self.data.len() < data_len(status) {
+                return None;
+            }
+            let message = self.message(status);
+            self.data.clear();
+            // System common messages do not set running status
+            if status >= 0xF0 {
+                self.status = None;
+            }
+            Some(message)
+        }
+    }
+
+    fn message(&self, status: u8) -> MidiMessage {
+        let channel = status & 0x0F;
+        let d = &self.data;
+        match status & 0xF0 {
+            0x80 => MidiMessage::NoteOff { channel, key: d[0], velocity: d[1] },
+            // A note on with no velocity is a note off
+            0x90 if d[1] == 0 => MidiMessage::NoteOff { channel, key: d[0], velocity: 64 },
+            0x90 => MidiMessage::NoteOn { channel, key: d[0], velocity: d[1] },
+            0xA0 => MidiMessage::KeyPressure { channel, key: d[0], pressure: d[1] },
+            0xB0 => MidiMessage::ControlChange { channel, controller: d[0], value: d[1] },
+            0xC0 => MidiMessage::ProgramChange { channel, program: d[0] },
+            0xD0 => MidiMessage::ChannelPressure { channel, pressure: d[0] },
+            0xE0 => MidiMessage::PitchBend { channel, value: d[0] as u16 | (d[1] as u16) << 7 },
+            _ => {
+                let mut bytes = vec![status];
+                bytes.extend_from_slice(d);
+                MidiMessage::System(bytes)
+            }
+        }
+    }
+}
+
+/// Number of data bytes that follow `status`
+fn data_len(status: u8) -> usize {
+    match status {
+        0xC0..=0xDF | 0xF1 | 0xF3 => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_midi_parser() {
+        let mut parser = MidiParser::new();
+        let mut out = Vec::new();
+
+        // Running status across reads, a real-time clock in the middle, and
+        // a zero-velocity note on
+        parser.feed(&[0x91, 60, 100, 64], &mut out);
+        parser.feed(&[0xF8, 90, 60, 0], &mut out);
+        assert_eq!(
+            out,
+            [
+                MidiMessage::NoteOn { channel: 1, key: 60, velocity: 100 },
+                MidiMessage::System(vec![0xF8]),
+                MidiMessage::NoteOn { channel: 1, key: 64, velocity: 90 },
+                MidiMessage::NoteOff { channel: 1, key: 60, velocity: 64 },
+            ]
+        );
+
+        // A program change, a system exclusive message and a pitch bend
+        out.clear();
+        parser.feed(&[0xC9, 0, 0xF0, 0x41, 0x10, 0x42, 0x12, 0xF7, 0xE0, 0x00, 0x40], &mut out);
+        assert_eq!(out[0], MidiMessage::ProgramChange { channel: 9, program: 0 });
+        assert_eq!(out[1], MidiMessage::SysEx(vec![0xF0, 0x41, 0x10, 0x42, 0x12, 0xF7]));
+        assert_eq!(out[2], MidiMessage::PitchBend { channel: 0, value: 0x2000 });
+        assert_eq!(out[2].to_bytes(), [0xE0, 0x00, 0x40]);
+
+        // Data without a status is dropped
+        let mut parser = MidiParser::new();
+        out.clear();
+        parser.feed(&[60, 100, 0xB0, 7, 127], &mut out);
+        assert_eq!(out, [MidiMessage::ControlChange { channel: 0, controller: 7, value: 127 }]);
+    }
+}
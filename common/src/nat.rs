@@ -0,0 +1,1030 @@
+//! User-space NAT for guest networking without root or bridges.
+//!
+//! In NAT mode the driver does not attach the guest NIC to a TAP. Frames the
+//! guest sends are collected with `NET_RECV_FRAME` and fed to `NatStack`,
+//! which plays the rest of a small private network, in the style of QEMU's
+//! slirp:
+//!
+//! - 10.0.2.15 is handed to the guest by DHCP
+//! - 10.0.2.2 is the gateway; connections to it reach the host's loopback
+//! - 10.0.2.3 is the DNS server, forwarded to the host's nameserver
+//!
+//! UDP datagrams and TCP connections from the guest are re-originated from
+//! ordinary host sockets, so they carry the host's address and need no
+//! privileges. TCP is terminated on both sides: guest data is acknowledged
+//! once it is buffered for the host socket, and host data is segmented into
+//! the guest's window with go-back-N retransmission. ICMP, IP fragments and
+//! inbound connections are not supported.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, SocketAddr, SocketAddrV4, TcpStream, UdpSocket};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Address handed to the guest
+pub const GUEST_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 15);
+/// Gateway address; connections to it go to the host's 127.0.0.1
+pub const GATEWAY_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 2);
+/// DNS server address; queries are forwarded to the host's nameserver
+pub const DNS_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 2, 3);
+/// Netmask of the private network
+pub const NETMASK: Ipv4Addr = Ipv4Addr::new(255, 255, 255, 0);
+
+/// MAC address the gateway (and every other private address) answers with
+const GATEWAY_MAC: [u8; 6] = [0x52, 0x55, 0x0a, 0x00, 0x02, 0x02];
+const BROADCAST_MAC: [u8; 6] = [0xff; 6];
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+
+const ETH_HLEN: usize = 14;
+const IP_HLEN: usize = 20;
+const UDP_HLEN: usize = 8;
+const TCP_HLEN: usize = 20;
+/// Frames shorter than this are padded
+const ETH_FRAME_MIN: usize = 60;
+/// Largest TCP payload per segment (1500 byte MTU)
+const TCP_MSS: usize = 1460;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Bytes buffered per connection and direction
+const TCP_BUFFER: usize = 64 * 1024;
+/// Time before unacknowledged data is sent again
+const TCP_RTO: Duration = Duration::from_millis(500);
+/// Retransmissions before a connection is dropped
+const TCP_MAX_RETRIES: u32 = 8;
+/// Time allowed for the host side of a connection to be established
+const TCP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// UDP mappings are dropped after this much inactivity
+const UDP_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_MAGIC: [u8; 4] = [99, 130, 83, 99];
+const DHCP_LEASE_SECS: u32 = 86400;
+
+/// First IPv4 nameserver in /etc/resolv.conf, used for DNS forwarding
+pub fn host_nameserver() -> Option<SocketAddr> {
+    let conf = std::fs::read_to_string("/etc/resolv.conf").ok()?;
+    parse_nameserver(&conf)
+}
+
+fn parse_nameserver(conf: &str) -> Option<SocketAddr> {
+    conf.lines()
+        .filter_map(|line| line.trim().strip_prefix("nameserver"))
+        .filter_map(|addr| addr.trim().parse::<Ipv4Addr>().ok())
+        .map(|ip| SocketAddr::from((ip, 53)))
+        .next()
+}
+
+/// A UDP mapping: one guest port talking to one remote address
+struct UdpFlow {
+    socket: UdpSocket,
+    last_used: Instant,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TcpState {
+    /// Guest sent SYN; the host connection is being made
+    Connecting,
+    /// SYN-ACK sent to the guest, waiting for its ACK
+    SynReceived,
+    /// Data flowing; also covers the closing handshakes
+    Established,
+}
+
+/// A TCP connection from the guest, proxied through a host socket
+struct TcpFlow {
+    state: TcpState,
+    connect: Option<Receiver<io::Result<TcpStream>>>,
+    stream: Option<TcpStream>,
+
+    /// Next sequence number expected from the guest
+    rcv_nxt: u32,
+    /// Guest data acknowledged but not yet written to the host
+    to_host: Vec<u8>,
+    /// Guest sent FIN
+    guest_fin: bool,
+    /// Host socket shut down for writing after the guest's FIN
+    host_write_closed: bool,
+    /// Window advertised to the guest was too small for a full segment
+    window_closed: bool,
+
+    /// Oldest sequence number not acknowledged by the guest
+    snd_una: u32,
+    /// Next sequence number to send
+    snd_nxt: u32,
+    /// Host data from `snd_una` on, sent or not
+    to_guest: VecDeque<u8>,
+    /// Window the guest advertised
+    snd_wnd: u32,
+    /// Host closed its side
+    host_eof: bool,
+    /// FIN sent to the guest (occupies the sequence number after the data)
+    fin_sent: bool,
+    /// Our FIN was acknowledged
+    fin_acked: bool,
+
+    /// Last time the guest acknowledged something (or the timer restarted)
+    last_progress: Instant,
+    retries: u32,
+    created: Instant,
+}
+
+impl TcpFlow {
+    /// Bytes of `to_guest` already sent
+    fn sent(&self) -> usize {
+        let in_flight = self.snd_nxt.wrapping_sub(self.snd_una) as usize;
+        in_flight - usize::from(self.fin_sent && !self.fin_acked)
+    }
+
+    /// Receive window to advertise
+    fn window(&self) -> u16 {
+        TCP_BUFFER.saturating_sub(self.to_host.len()).min(u16::MAX as usize) as u16
+    }
+
+    fn is_closed(&self) -> bool {
+        self.guest_fin && self.host_write_closed && self.fin_acked
+    }
+}
+
+/// Guest-side identity of a connection: guest port and remote address
+type FlowKey = (u16, SocketAddrV4);
+
+/// A TCP or UDP header parsed from a guest frame
+struct Segment<'a> {
+    src_port: u16,
+    dst: SocketAddrV4,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    window: u16,
+    payload: &'a [u8],
+}
+
+/// User-mode network stack between the guest NIC and host sockets
+pub struct NatStack {
+    dns_server: Option<SocketAddr>,
+    guest_mac: Option<[u8; 6]>,
+    ip_id: u16,
+    udp: HashMap<FlowKey, UdpFlow>,
+    tcp: HashMap<FlowKey, TcpFlow>,
+    out: VecDeque<Vec<u8>>,
+}
+
+impl NatStack {
+    /// Create a stack forwarding DNS queries to `dns_server`
+    ///
+    /// Without a nameserver, queries to 10.0.2.3 are dropped.
+    pub fn new(dns_server: Option<SocketAddr>) -> Self {
+        Self {
+            dns_server,
+            guest_mac: None,
+            ip_id: 0,
+            udp: HashMap::new(),
+            tcp: HashMap::new(),
+            out: VecDeque::new(),
+        }
+    }
+
+    /// Process a frame the guest sent
+    pub fn handle_frame(&mut self, frame: &[u8]) {
+        if frame.len() < ETH_HLEN {
+            return;
+        }
+        let mut src_mac = [0u8; 6];
+        src_mac.copy_from_slice(&frame[6..12]);
+        if src_mac[0] & 1 == 0 {
+            self.guest_mac = Some(src_mac);
+        }
+
+        match be16(frame, 12) {
+            ETHERTYPE_ARP => self.handle_arp(&frame[ETH_HLEN..]),
+            ETHERTYPE_IPV4 => self.handle_ipv4(&frame[ETH_HLEN..]),
+            _ => {}
+        }
+    }
+
+    /// Move data between host sockets and the guest, and run timers
+    pub fn poll(&mut self) {
+        let now = Instant::now();
+        self.poll_udp(now);
+        self.poll_tcp(now);
+    }
+
+    /// Next frame for the guest, if any
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        self.out.pop_front()
+    }
+
+    /// Number of open UDP mappings and TCP connections
+    pub fn connection_count(&self) -> usize {
+        self.udp.len() + self.tcp.len()
+    }
+
+    // ------------------------------------------------------------------------
+    // ARP and IP
+    // ------------------------------------------------------------------------
+
+    fn handle_arp(&mut self, arp: &[u8]) {
+        // Ethernet/IPv4 requests only
+        if arp.len() < 28 || be16(arp, 0) != 1 || be16(arp, 2) != ETHERTYPE_IPV4 || be16(arp, 6) != 1 {
+            return;
+        }
+        let sender_mac = &arp[8..14];
+        let sender_ip = &arp[14..18];
+        let target_ip = ipv4(arp, 24);
+
+        // Every other address on the private network is us
+        if !in_subnet(target_ip) || target_ip == GUEST_IP {
+            return;
+        }
+
+        let mut reply = Vec::with_capacity(28);
+        reply.extend_from_slice(&[0, 1, 8, 0, 6, 4, 0, 2]);
+        reply.extend_from_slice(&GATEWAY_MAC);
+        reply.extend_from_slice(&target_ip.octets());
+        reply.extend_from_slice(sender_mac);
+        reply.extend_from_slice(sender_ip);
+
+        let mut dst = [0u8; 6];
+        dst.copy_from_slice(sender_mac);
+        self.push_frame(dst, ETHERTYPE_ARP, &reply);
+    }
+
+    fn handle_ipv4(&mut self, ip: &[u8]) {
+        if ip.len() < IP_HLEN || ip[0] >> 4 != 4 {
+            return;
+        }
+        let hlen = ((ip[0] & 0x0f) as usize) * 4;
+        let total = (be16(ip, 2) as usize).min(ip.len());
+        // Fragments are not reassembled
+        if hlen < IP_HLEN || total < hlen || be16(ip, 6) & 0x3fff != 0 {
+            return;
+        }
+        let src = ipv4(ip, 12);
+        let dst = ipv4(ip, 16);
+        let body = &ip[hlen..total];
+
+        match ip[9] {
+            PROTO_UDP => self.handle_udp(src, dst, body),
+            PROTO_TCP => self.handle_tcp(dst, body),
+            _ => {}
+        }
+    }
+
+    /// Host address a guest destination maps to, if it leaves the stack
+    fn host_target(&self, dst: SocketAddrV4) -> Option<SocketAddr> {
+        match *dst.ip() {
+            GATEWAY_IP => Some(SocketAddr::from((Ipv4Addr::LOCALHOST, dst.port()))),
+            DNS_IP if dst.port() == 53 => self.dns_server,
+            ip if in_subnet(ip) || ip.is_broadcast() || ip.is_multicast() || ip.is_unspecified() => None,
+            ip => Some(SocketAddr::from((ip, dst.port()))),
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    // UDP and DHCP
+    // ------------------------------------------------------------------------
+
+    fn handle_udp(&mut self, src: Ipv4Addr, dst: Ipv4Addr, udp: &[u8]) {
+        if udp.len() < UDP_HLEN {
+            return;
+        }
+        let src_port = be16(udp, 0);
+        let dst_port = be16(udp, 2);
+        let len = (be16(udp, 4) as usize).clamp(UDP_HLEN, udp.len());
+        let payload = &udp[UDP_HLEN..len];
+
+        if dst_port == DHCP_SERVER_PORT && src_port == DHCP_CLIENT_PORT {
+            self.handle_dhcp(payload);
+            return;
+        }
+        if src != GUEST_IP {
+            return;
+        }
+
+        let remote = SocketAddrV4::new(dst, dst_port);
+        let key = (src_port, remote);
+        if !self.udp.contains_key(&key) {
+            let Some(target) = self.host_target(remote) else {
+                return;
+            };
+            let Ok(socket) = open_udp(target) else {
+                return;
+            };
+            self.udp.insert(key, UdpFlow { socket, last_used: Instant::now() });
+        }
+        if let Some(flow) = self.udp.get_mut(&key) {
+            flow.last_used = Instant::now();
+            let _ = flow.socket.send(payload);
+        }
+    }
+
+    fn poll_udp(&mut self, now: Instant) {
+        let mut buf = [0u8; 2048];
+        let mut replies = Vec::new();
+        self.udp.retain(|&(guest_port, remote), flow| {
+            while let Ok(n) = flow.socket.recv(&mut buf) {
+                flow.last_used = now;
+                replies.push((remote, guest_port, buf[..n].to_vec()));
+            }
+            now.duration_since(flow.last_used) < UDP_IDLE_TIMEOUT
+        });
+        for (remote, guest_port, data) in replies {
+            self.send_udp(remote, SocketAddrV4::new(GUEST_IP, guest_port), &data, false);
+        }
+    }
+
+    fn handle_dhcp(&mut self, msg: &[u8]) {
+        // Fixed BOOTP header, then the magic cookie and options
+        if msg.len() < 240 || msg[0] != 1 || msg[236..240] != DHCP_MAGIC {
+            return;
+        }
+        let reply_type = match dhcp_option(&msg[240..], 53) {
+            Some([1]) => 2, // DISCOVER -> OFFER
+            Some([3]) => 5, // REQUEST -> ACK
+            _ => return,
+        };
+
+        let mut reply = vec![0u8; 240];
+        reply[0] = 2; // BOOTREPLY
+        reply[1] = 1; // Ethernet
+        reply[2] = 6;
+        reply[4..8].copy_from_slice(&msg[4..8]); // xid
+        reply[10..12].copy_from_slice(&msg[10..12]); // flags
+        reply[16..20].copy_from_slice(&GUEST_IP.octets());
+        reply[20..24].copy_from_slice(&GATEWAY_IP.octets());
+        reply[28..44].copy_from_slice(&msg[28..44]); // chaddr
+        reply[236..240].copy_from_slice(&DHCP_MAGIC);
+
+        reply.extend_from_slice(&[53, 1, reply_type]);
+        reply.extend_from_slice(&[54, 4]);
+        reply.extend_from_slice(&GATEWAY_IP.octets());
+        reply.extend_from_slice(&[51, 4]);
+        reply.extend_from_slice(&DHCP_LEASE_SECS.to_be_bytes());
+        reply.extend_from_slice(&[1, 4]);
+        reply.extend_from_slice(&NETMASK.octets());
+        reply.extend_from_slice(&[3, 4]);
+        reply.extend_from_slice(&GATEWAY_IP.octets());
+        reply.extend_from_slice(&[6, 4]);
+        reply.extend_from_slice(&DNS_IP.octets());
+        reply.push(255);
+
+        // The guest has no address yet, so the reply is broadcast
+        self.send_udp(
+            SocketAddrV4::new(GATEWAY_IP, DHCP_SERVER_PORT),
+            SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_CLIENT_PORT),
+            &reply,
+            true,
+        );
+    }
+
+    fn send_udp(&mut self, src: SocketAddrV4, dst: SocketAddrV4, data: &[u8], broadcast: bool) {
+        let len = UDP_HLEN + data.len();
+        let mut udp = Vec::with_capacity(len);
+        udp.extend_from_slice(&src.port().to_be_bytes());
+        udp.extend_from_slice(&dst.port().to_be_bytes());
+        udp.extend_from_slice(&(len as u16).to_be_bytes());
+        udp.extend_from_slice(&[0, 0]);
+        udp.extend_from_slice(data);
+
+        let sum = checksum(&udp, pseudo_header_sum(*src.ip(), *dst.ip(), PROTO_UDP, len));
+        let sum = if sum == 0 { 0xffff } else { sum };
+        udp[6..8].copy_from_slice(&sum.to_be_bytes());
+
+        self.push_ipv4(*src.ip(), *dst.ip(), PROTO_UDP, &udp, broadcast);
+    }
+
+    // ------------------------------------------------------------------------
+    // TCP
+    // ------------------------------------------------------------------------
+
+    fn handle_tcp(&mut self, dst: Ipv4Addr, tcp: &[u8]) {
+        if tcp.len() < TCP_HLEN {
+            return;
+        }
+        let data_offset = ((tcp[12] >> 4) as usize) * 4;
+        if data_offset < TCP_HLEN || data_offset > tcp.len() {
+            return;
+        }
+        let seg = Segment {
+            src_port: be16(tcp, 0),
+            dst: SocketAddrV4::new(dst, be16(tcp, 2)),
+            seq: be32(tcp, 4),
+            ack: be32(tcp, 8),
+            flags: tcp[13],
+            window: be16(tcp, 14),
+            payload: &tcp[data_offset..],
+        };
+        let key = (seg.src_port, seg.dst);
+
+        let Some(mut flow) = self.tcp.remove(&key) else {
+            self.open_tcp(key, &seg);
+            return;
+        };
+        if seg.flags & TCP_RST != 0 {
+            // Dropping the flow closes the host socket
+            return;
+        }
+        if self.segment_arrived(key, &mut flow, &seg) {
+            self.tcp.insert(key, flow);
+        }
+    }
+
+    /// Start a connection for a guest SYN, or reset a stray segment
+    fn open_tcp(&mut self, key: FlowKey, seg: &Segment) {
+        if seg.flags & TCP_RST != 0 {
+            return;
+        }
+        let target = match self.host_target(seg.dst) {
+            Some(target) if seg.flags & (TCP_SYN | TCP_ACK) == TCP_SYN => target,
+            _ => {
+                self.reset(key, seg);
+                return;
+            }
+        };
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let _ = tx.send(TcpStream::connect_timeout(&target, TCP_CONNECT_TIMEOUT));
+        });
+
+        let iss = initial_sequence();
+        let now = Instant::now();
+        self.tcp.insert(
+            key,
+            TcpFlow {
+                state: TcpState::Connecting,
+                connect: Some(rx),
+                stream: None,
+                rcv_nxt: seg.seq.wrapping_add(1),
+                to_host: Vec::new(),
+                guest_fin: false,
+                host_write_closed: false,
+                window_closed: false,
+                snd_una: iss,
+                snd_nxt: iss,
+                to_guest: VecDeque::new(),
+                snd_wnd: seg.window as u32,
+                host_eof: false,
+                fin_sent: false,
+                fin_acked: false,
+                last_progress: now,
+                retries: 0,
+                created: now,
+            },
+        );
+    }
+
+    /// Handle a segment for an existing connection; false drops it
+    fn segment_arrived(&mut self, key: FlowKey, flow: &mut TcpFlow, seg: &Segment) -> bool {
+        if seg.flags & TCP_SYN != 0 {
+            // Retransmitted SYN: answer again once connected
+            if flow.state == TcpState::SynReceived {
+                self.send_syn_ack(key, flow);
+            }
+            return true;
+        }
+        if flow.state == TcpState::Connecting || seg.flags & TCP_ACK == 0 {
+            return true;
+        }
+        flow.snd_wnd = seg.window as u32;
+
+        // Acknowledgement of our SYN, data and FIN
+        let acked = seg.ack.wrapping_sub(flow.snd_una);
+        let in_flight = flow.snd_nxt.wrapping_sub(flow.snd_una);
+        if acked > 0 && acked <= in_flight {
+            let mut data_acked = acked as usize;
+            if flow.state == TcpState::SynReceived {
+                flow.state = TcpState::Established;
+                data_acked -= 1;
+            }
+            if flow.fin_sent && seg.ack == flow.snd_nxt {
+                flow.fin_acked = true;
+                data_acked -= 1;
+            }
+            flow.to_guest.drain(..data_acked.min(flow.to_guest.len()));
+            flow.snd_una = seg.ack;
+            flow.last_progress = Instant::now();
+            flow.retries = 0;
+        }
+        if flow.state != TcpState::Established {
+            return true;
+        }
+
+        // Data and FIN, accepted in order only
+        let mut ack_needed = false;
+        if !seg.payload.is_empty() || seg.flags & TCP_FIN != 0 {
+            ack_needed = true;
+            if seg.seq == flow.rcv_nxt && !flow.guest_fin {
+                let room = TCP_BUFFER.saturating_sub(flow.to_host.len());
+                let accepted = seg.payload.len().min(room);
+                flow.to_host.extend_from_slice(&seg.payload[..accepted]);
+                flow.rcv_nxt = flow.rcv_nxt.wrapping_add(accepted as u32);
+                if accepted == seg.payload.len() && seg.flags & TCP_FIN != 0 {
+                    flow.rcv_nxt = flow.rcv_nxt.wrapping_add(1);
+                    flow.guest_fin = true;
+                }
+            }
+        }
+
+        if let Err(()) = flush_to_host(flow) {
+            self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, TCP_RST | TCP_ACK, 0, &[]);
+            return false;
+        }
+        if ack_needed {
+            flow.window_closed = (flow.window() as usize) < TCP_MSS;
+            self.send_ack(key, flow);
+        }
+        !flow.is_closed()
+    }
+
+    fn poll_tcp(&mut self, now: Instant) {
+        let keys: Vec<FlowKey> = self.tcp.keys().copied().collect();
+        for key in keys {
+            let Some(mut flow) = self.tcp.remove(&key) else {
+                continue;
+            };
+            if self.poll_flow(key, &mut flow, now) {
+                self.tcp.insert(key, flow);
+            }
+        }
+    }
+
+    /// Service one connection; false drops it
+    fn poll_flow(&mut self, key: FlowKey, flow: &mut TcpFlow, now: Instant) -> bool {
+        match flow.state {
+            TcpState::Connecting => {
+                let result = match flow.connect.as_ref().map(Receiver::try_recv) {
+                    Some(Ok(result)) => result,
+                    Some(Err(TryRecvError::Empty))
+                        if now.duration_since(flow.created) < TCP_CONNECT_TIMEOUT * 2 =>
+                    {
+                        return true;
+                    }
+                    _ => Err(io::ErrorKind::TimedOut.into()),
+                };
+                match result.and_then(|s| s.set_nonblocking(true).map(|()| s)) {
+                    Ok(stream) => {
+                        flow.stream = Some(stream);
+                        flow.connect = None;
+                        flow.state = TcpState::SynReceived;
+                        flow.snd_nxt = flow.snd_una.wrapping_add(1);
+                        flow.last_progress = now;
+                        self.send_syn_ack(key, flow);
+                        true
+                    }
+                    Err(_) => {
+                        // Connection refused or unreachable
+                        self.send_tcp(key, 0, flow.rcv_nxt, TCP_RST | TCP_ACK, 0, &[]);
+                        false
+                    }
+                }
+            }
+            TcpState::SynReceived => {
+                if now.duration_since(flow.last_progress) < TCP_RTO {
+                    return true;
+                }
+                flow.retries += 1;
+                flow.last_progress = now;
+                self.send_syn_ack(key, flow);
+                flow.retries <= TCP_MAX_RETRIES
+            }
+            TcpState::Established => self.poll_established(key, flow, now),
+        }
+    }
+
+    fn poll_established(&mut self, key: FlowKey, flow: &mut TcpFlow, now: Instant) -> bool {
+        if flush_to_host(flow).is_err() || read_from_host(flow).is_err() {
+            self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, TCP_RST | TCP_ACK, 0, &[]);
+            return false;
+        }
+
+        // Tell the guest once its window opens again
+        if flow.window_closed && flow.window() as usize >= TCP_MSS {
+            flow.window_closed = false;
+            self.send_ack(key, flow);
+        }
+
+        // Go back to the oldest unacknowledged byte after a timeout
+        let outstanding = flow.snd_nxt != flow.snd_una;
+        let stalled = !outstanding && flow.snd_wnd == 0 && !flow.to_guest.is_empty();
+        if (outstanding || stalled) && now.duration_since(flow.last_progress) >= TCP_RTO {
+            flow.retries += 1;
+            if flow.retries > TCP_MAX_RETRIES {
+                self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, TCP_RST | TCP_ACK, 0, &[]);
+                return false;
+            }
+            flow.snd_nxt = flow.snd_una;
+            flow.fin_sent = false;
+            flow.last_progress = now;
+            // Probe a closed window with a single byte
+            self.send_data(key, flow, stalled);
+        } else {
+            if !outstanding {
+                flow.last_progress = now;
+            }
+            self.send_data(key, flow, false);
+        }
+
+        !flow.is_closed()
+    }
+
+    /// Send as much buffered host data as the guest's window allows
+    fn send_data(&mut self, key: FlowKey, flow: &mut TcpFlow, probe: bool) {
+        let window = if probe { flow.snd_wnd.max(1) as usize } else { flow.snd_wnd as usize };
+        loop {
+            let sent = flow.sent();
+            let pending = flow.to_guest.len() - sent;
+            let room = window.saturating_sub(sent);
+            let len = pending.min(room).min(TCP_MSS);
+            if len == 0 {
+                break;
+            }
+            let data: Vec<u8> = flow.to_guest.range(sent..sent + len).copied().collect();
+            let flags = TCP_ACK | if len == pending { TCP_PSH } else { 0 };
+            self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, flags, flow.window(), &data);
+            flow.snd_nxt = flow.snd_nxt.wrapping_add(len as u32);
+        }
+
+        if flow.host_eof && !flow.fin_sent && flow.sent() == flow.to_guest.len() {
+            self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, TCP_FIN | TCP_ACK, flow.window(), &[]);
+            flow.snd_nxt = flow.snd_nxt.wrapping_add(1);
+            flow.fin_sent = true;
+        }
+    }
+
+    fn send_syn_ack(&mut self, key: FlowKey, flow: &TcpFlow) {
+        // MSS option: kind 2, length 4
+        let mss = [2, 4, (TCP_MSS >> 8) as u8, TCP_MSS as u8];
+        let flags = TCP_SYN | TCP_ACK;
+        let tcp = build_tcp(key, flow.snd_una, flow.rcv_nxt, flags, flow.window(), &mss, &[]);
+        self.push_ipv4(*key.1.ip(), GUEST_IP, PROTO_TCP, &tcp, false);
+    }
+
+    fn send_ack(&mut self, key: FlowKey, flow: &TcpFlow) {
+        self.send_tcp(key, flow.snd_nxt, flow.rcv_nxt, TCP_ACK, flow.window(), &[]);
+    }
+
+    /// Answer a segment that matches no connection with RST
+    fn reset(&mut self, key: FlowKey, seg: &Segment) {
+        if seg.flags & TCP_ACK != 0 {
+            self.send_tcp(key, seg.ack, 0, TCP_RST, 0, &[]);
+        } else {
+            let len = seg.payload.len() as u32 + u32::from(seg.flags & (TCP_SYN | TCP_FIN) != 0);
+            self.send_tcp(key, 0, seg.seq.wrapping_add(len), TCP_RST | TCP_ACK, 0, &[]);
+        }
+    }
+
+    fn send_tcp(&mut self, key: FlowKey, seq: u32, ack: u32, flags: u8, window: u16, data: &[u8]) {
+        let tcp = build_tcp(key, seq, ack, flags, window, &[], data);
+        self.push_ipv4(*key.1.ip(), GUEST_IP, PROTO_TCP, &tcp, false);
+    }
+
+    // ------------------------------------------------------------------------
+    // Framing
+    // ------------------------------------------------------------------------
+
+    fn push_ipv4(&mut self, src: Ipv4Addr, dst: Ipv4Addr, proto: u8, payload: &[u8], broadcast: bool) {
+        self.ip_id = self.ip_id.wrapping_add(1);
+        let total = IP_HLEN + payload.len();
+        let mut ip = Vec::with_capacity(total);
+        ip.extend_from_slice(&[0x45, 0]);
+        ip.extend_from_slice(&(total as u16).to_be_bytes());
+        ip.extend_from_slice(&self.ip_id.to_be_bytes());
+        ip.extend_from_slice(&[0x40, 0, 64, proto, 0, 0]); // DF, TTL 64
+        ip.extend_from_slice(&src.octets());
+        ip.extend_from_slice(&dst.octets());
+        let sum = checksum(&ip, 0);
+        ip[10..12].copy_from_slice(&sum.to_be_bytes());
+        ip.extend_from_slice(payload);
+
+        let dst_mac = match self.guest_mac {
+            Some(mac) if !broadcast => mac,
+            _ => BROADCAST_MAC,
+        };
+        self.push_frame(dst_mac, ETHERTYPE_IPV4, &ip);
+    }
+
+    fn push_frame(&mut self, dst: [u8; 6], ethertype: u16, payload: &[u8]) {
+        let mut frame = Vec::with_capacity((ETH_HLEN + payload.len()).max(ETH_FRAME_MIN));
+        frame.extend_from_slice(&dst);
+        frame.extend_from_slice(&GATEWAY_MAC);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        if frame.len() < ETH_FRAME_MIN {
+            frame.resize(ETH_FRAME_MIN, 0);
+        }
+        self.out.push_back(frame);
+    }
+}
+
+/// TCP segment from `remote` to the guest, with checksum
+fn build_tcp(
+    (guest_port, remote): FlowKey,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    window: u16,
+    options: &[u8],
+    data: &[u8],
+) -> Vec<u8> {
+    let hlen = TCP_HLEN + options.len();
+    let mut tcp = Vec::with_capacity(hlen + data.len());
+    tcp.extend_from_slice(&remote.port().to_be_bytes());
+    tcp.extend_from_slice(&guest_port.to_be_bytes());
+    tcp.extend_from_slice(&seq.to_be_bytes());
+    tcp.extend_from_slice(&ack.to_be_bytes());
+    tcp.push(((hlen / 4) as u8) << 4);
+    tcp.push(flags);
+    tcp.extend_from_slice(&window.to_be_bytes());
+    tcp.extend_from_slice(&[0, 0, 0, 0]); // checksum, urgent pointer
+    tcp.extend_from_slice(options);
+    tcp.extend_from_slice(data);
+
+    let sum = checksum(&tcp, pseudo_header_sum(*remote.ip(), GUEST_IP, PROTO_TCP, tcp.len()));
+    tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+    tcp
+}
+
+/// Write buffered guest data to the host; shut the host side down after FIN
+fn flush_to_host(flow: &mut TcpFlow) -> Result<(), ()> {
+    let Some(stream) = flow.stream.as_mut() else {
+        return Ok(());
+    };
+    while !flow.to_host.is_empty() {
+        match stream.write(&flow.to_host) {
+            Ok(0) => return Err(()),
+            Ok(n) => {
+                flow.to_host.drain(..n);
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return Err(()),
+        }
+    }
+    if flow.guest_fin && !flow.host_write_closed {
+        let _ = stream.shutdown(Shutdown::Write);
+        flow.host_write_closed = true;
+    }
+    Ok(())
+}
+
+/// Read host data into the send buffer, up to its capacity
+fn read_from_host(flow: &mut TcpFlow) -> Result<(), ()> {
+    let Some(stream) = flow.stream.as_mut() else {
+        return Ok(());
+    };
+    let mut buf = [0u8; 8192];
+    while !flow.host_eof && flow.to_guest.len() < TCP_BUFFER {
+        let max = buf.len().min(TCP_BUFFER - flow.to_guest.len());
+        match stream.read(&mut buf[..max]) {
+            Ok(0) => flow.host_eof = true,
+            Ok(n) => flow.to_guest.extend(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return Err(()),
+        }
+    }
+    Ok(())
+}
+
+fn open_udp(target: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(target)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+/// Initial sequence number for a new connection
+fn initial_sequence() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| (d.as_nanos() / 4000) as u32)
+        .unwrap_or(0)
+}
+
+fn in_subnet(ip: Ipv4Addr) -> bool {
+    u32::from(ip) & u32::from(NETMASK) == u32::from(GUEST_IP) & u32::from(NETMASK)
+}
+
+/// Value of a DHCP option
+fn dhcp_option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    while let [kind, rest @ ..] = options {
+        match *kind {
+            0 => options = rest,
+            255 => break,
+            _ => {
+                let (&len, rest) = rest.split_first()?;
+                let value = rest.get(..len as usize)?;
+                if *kind == code {
+                    return Some(value);
+                }
+                options = &rest[len as usize..];
+            }
+        }
+    }
+    None
+}
+
+fn be16(data: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([data[offset], data[offset + 1]])
+}
+
+fn be32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn ipv4(data: &[u8], offset: usize) -> Ipv4Addr {
+    Ipv4Addr::new(data[offset], data[offset + 1], data[offset + 2], data[offset + 3])
+}
+
+/// Sum of the TCP/UDP pseudo header, to seed `checksum`
+fn pseudo_header_sum(src: Ipv4Addr, dst: Ipv4Addr, proto: u8, len: usize) -> u32 {
+    let words = |ip: Ipv4Addr| {
+        let o = ip.octets();
+        u16::from_be_bytes([o[0], o[1]]) as u32 + u16::from_be_bytes([o[2], o[3]]) as u32
+    };
+    words(src) + words(dst) + proto as u32 + len as u32
+}
+
+/// Internet checksum (RFC 1071)
+fn checksum(data: &[u8], initial: u32) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]) as u32)
+        .fold(initial, |acc, w| acc + w);
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, UdpSocket};
+
+    const GUEST_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x15];
+
+    /// Wrap an IPv4 payload from the guest in Ethernet and IP headers
+    fn guest_frame(dst: Ipv4Addr, proto: u8, payload: &[u8]) -> Vec<u8> {
+        let mut stack = NatStack::new(None);
+        stack.push_ipv4(GUEST_IP, dst, proto, payload, false);
+        let mut frame = stack.next_frame().unwrap();
+        frame[6..12].copy_from_slice(&GUEST_MAC);
+        frame
+    }
+
+    fn guest_tcp(dst: SocketAddrV4, seq: u32, ack: u32, flags: u8, data: &[u8]) -> Vec<u8> {
+        let mut tcp = Vec::new();
+        tcp.extend_from_slice(&4000u16.to_be_bytes());
+        tcp.extend_from_slice(&dst.port().to_be_bytes());
+        tcp.extend_from_slice(&seq.to_be_bytes());
+        tcp.extend_from_slice(&ack.to_be_bytes());
+        tcp.extend_from_slice(&[0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        tcp.extend_from_slice(data);
+        guest_frame(*dst.ip(), PROTO_TCP, &tcp)
+    }
+
+    /// Poll until a frame for the guest appears
+    fn wait_frame(stack: &mut NatStack) -> Vec<u8> {
+        for _ in 0..500 {
+            stack.poll();
+            if let Some(frame) = stack.next_frame() {
+                return frame;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("no frame from the NAT stack");
+    }
+
+    /// (seq, ack, flags, payload) of a TCP frame for the guest
+    fn tcp_fields(frame: &[u8]) -> (u32, u32, u8, Vec<u8>) {
+        let ip = &frame[ETH_HLEN..];
+        let total = be16(ip, 2) as usize;
+        let tcp = &ip[IP_HLEN..total];
+        assert_eq!(checksum(tcp, pseudo_header_sum(ipv4(ip, 12), ipv4(ip, 16), PROTO_TCP, tcp.len())), 0);
+        let offset = ((tcp[12] >> 4) as usize) * 4;
+        (be32(tcp, 4), be32(tcp, 8), tcp[13], tcp[offset..].to_vec())
+    }
+
+    #[test]
+    fn test_arp_and_dhcp() {
+        let mut stack = NatStack::new(None);
+
+        let mut arp = vec![0, 1, 8, 0, 6, 4, 0, 1];
+        arp.extend_from_slice(&GUEST_MAC);
+        arp.extend_from_slice(&GUEST_IP.octets());
+        arp.extend_from_slice(&[0; 6]);
+        arp.extend_from_slice(&GATEWAY_IP.octets());
+        let mut frame = BROADCAST_MAC.to_vec();
+        frame.extend_from_slice(&GUEST_MAC);
+        frame.extend_from_slice(&ETHERTYPE_ARP.to_be_bytes());
+        frame.extend_from_slice(&arp);
+        stack.handle_frame(&frame);
+
+        let reply = stack.next_frame().unwrap();
+        assert_eq!(&reply[..6], &GUEST_MAC);
+        assert_eq!(be16(&reply, ETH_HLEN + 6), 2);
+        assert_eq!(&reply[ETH_HLEN + 8..ETH_HLEN + 14], &GATEWAY_MAC);
+
+        let mut dhcp = vec![0u8; 240];
+        dhcp[0] = 1;
+        dhcp[4..8].copy_from_slice(&[1, 2, 3, 4]);
+        dhcp[28..34].copy_from_slice(&GUEST_MAC);
+        dhcp[236..240].copy_from_slice(&DHCP_MAGIC);
+        dhcp.extend_from_slice(&[53, 1, 1, 255]);
+        let mut udp = vec![0, 68, 0, 67, 0, 0, 0, 0];
+        udp[4..6].copy_from_slice(&((UDP_HLEN + dhcp.len()) as u16).to_be_bytes());
+        udp.extend_from_slice(&dhcp);
+        stack.handle_frame(&guest_frame(Ipv4Addr::BROADCAST, PROTO_UDP, &udp));
+
+        let offer = stack.next_frame().unwrap();
+        let bootp = &offer[ETH_HLEN + IP_HLEN + UDP_HLEN..];
+        assert_eq!(&bootp[4..8], &[1, 2, 3, 4]);
+        assert_eq!(ipv4(bootp, 16), GUEST_IP);
+        assert_eq!(dhcp_option(&bootp[240..], 53), Some(&[2][..]));
+        assert_eq!(dhcp_option(&bootp[240..], 3), Some(&GATEWAY_IP.octets()[..]));
+    }
+
+    #[test]
+    fn test_udp_round_trip() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let mut stack = NatStack::new(None);
+
+        let mut udp = vec![0x13, 0x88, 0, 0, 0, 12, 0, 0];
+        udp[2..4].copy_from_slice(&port.to_be_bytes());
+        udp.extend_from_slice(b"ping");
+        stack.handle_frame(&guest_frame(GATEWAY_IP, PROTO_UDP, &udp));
+
+        let mut buf = [0u8; 16];
+        let (n, from) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        server.send_to(b"pong", from).unwrap();
+
+        let reply = wait_frame(&mut stack);
+        let ip = &reply[ETH_HLEN..];
+        assert_eq!((ipv4(ip, 12), ipv4(ip, 16)), (GATEWAY_IP, GUEST_IP));
+        assert_eq!(checksum(&ip[..IP_HLEN], 0), 0);
+        assert_eq!(be16(ip, IP_HLEN), port);
+        assert_eq!(be16(ip, IP_HLEN + 2), 5000);
+        assert_eq!(&ip[IP_HLEN + UDP_HLEN..IP_HLEN + UDP_HLEN + 4], b"pong");
+
+        // DNS goes to the first IPv4 nameserver
+        let conf = "# generated\nnameserver ::1\nnameserver 192.168.1.1\n";
+        assert_eq!(parse_nameserver(conf), Some(SocketAddr::from(([192, 168, 1, 1], 53))));
+    }
+
+    #[test]
+    fn test_tcp_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let remote = SocketAddrV4::new(GATEWAY_IP, listener.local_addr().unwrap().port());
+        let mut stack = NatStack::new(None);
+
+        // Handshake: SYN-ACK only once the host connection is up
+        stack.handle_frame(&guest_tcp(remote, 100, 0, TCP_SYN, &[]));
+        let (iss, ack, flags, _) = tcp_fields(&wait_frame(&mut stack));
+        assert_eq!((ack, flags), (101, TCP_SYN | TCP_ACK));
+        let (mut host, _) = listener.accept().unwrap();
+        stack.handle_frame(&guest_tcp(remote, 101, iss + 1, TCP_ACK, &[]));
+
+        // Guest to host
+        stack.handle_frame(&guest_tcp(remote, 101, iss + 1, TCP_ACK | TCP_PSH, b"hello"));
+        let (_, ack, _, _) = tcp_fields(&stack.next_frame().unwrap());
+        assert_eq!(ack, 106);
+        let mut buf = [0u8; 5];
+        host.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+
+        // Host to guest, then the host closes
+        host.write_all(b"world").unwrap();
+        drop(host);
+        let (seq, _, _, data) = tcp_fields(&wait_frame(&mut stack));
+        assert_eq!((seq, data.as_slice()), (iss + 1, &b"world"[..]));
+        let (seq, _, flags, _) = tcp_fields(&wait_frame(&mut stack));
+        assert_eq!((seq, flags), (iss + 6, TCP_FIN | TCP_ACK));
+
+        // Guest acknowledges and closes its side
+        stack.handle_frame(&guest_tcp(remote, 106, iss + 7, TCP_FIN | TCP_ACK, &[]));
+        let (_, ack, _, _) = tcp_fields(&stack.next_frame().unwrap());
+        assert_eq!(ack, 107);
+        assert_eq!(stack.connection_count(), 0);
+
+        // Unknown connections are reset
+        stack.handle_frame(&guest_tcp(remote, 500, 9, TCP_ACK, b"x"));
+        let (seq, _, flags, _) = tcp_fields(&stack.next_frame().unwrap());
+        assert_eq!((seq, flags), (9, TCP_RST));
+
+    }
+}
@@ -0,0 +1,465 @@
+//! Host-side TAP interface setup for guest networking.
+//!
+//! The driver attaches the guest NIC to a TAP interface by name. Without
+//! this helper that interface had to be created by hand (`ip tuntap add`,
+//! `ip link set ... master br0`, `ip link set ... up`). `TapDevice` does the
+//! same steps through iproute2, remembers what it created, and undoes only
+//! that on `teardown` or drop, so pre-existing interfaces and bridges are
+//! left alone.
+//!
+//! Creating interfaces needs CAP_NET_ADMIN. The TAP is made persistent and
+//! owned by the calling user so the driver can attach to it afterwards.
+//!
+//! `link_info` reads the carrier, speed and duplex of a host interface
+//! from sysfs, so the UI can tell an unplugged host cable apart from a
+//! guest that has not brought its adapter up. `host_interfaces` lists the
+//! interfaces the guest could be bridged to.
+
+use std::path::Path;
+use std::process::Command;
+
+use thiserror::Error;
+
+/// Maximum interface name length (IFNAMSIZ - 1)
+const MAX_IFNAME_LEN: usize = 15;
+
+/// Where the kernel lists network interfaces
+const SYSFS_NET: &str = "/sys/class/net";
+
+/// Errors from TAP/bridge setup
+#[derive(Debug, Error)]
+pub enum NetSetupError {
+    #[error("invalid interface name: {0:?}")]
+    InvalidName(String),
+
+    #[error("bridge {0} does not exist")]
+    NoSuchBridge(String),
+
+    #[error("failed to run ip: {0}")]
+    Spawn(#[from] std::io::Error),
+
+    #[error("'ip {args}' failed: {stderr}")]
+    Command { args: String, stderr: String },
+}
+
+/// What to create for a session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TapOptions {
+    /// TAP interface name (e.g. "sunpci0")
+    pub tap_name: String,
+    /// Bridge to attach the TAP to (empty = leave unattached)
+    pub bridge: String,
+    /// Create the bridge if it does not exist
+    pub create_bridge: bool,
+    /// User that may attach to the TAP (None = current user)
+    pub owner: Option<u32>,
+}
+
+/// Physical link state of a host interface
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkInfo {
+    /// Cable plugged in / link partner present
+    pub carrier: bool,
+    /// Negotiated speed in Mb/s (None for virtual interfaces or no link)
+    pub speed_mbps: Option<u32>,
+    /// Whether the link is full duplex (None if not reported)
+    pub full_duplex: Option<bool>,
+}
+
+/// Kind of host network interface, guessed from sysfs and its name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterfaceKind {
+    Ethernet,
+    Wireless,
+    Bridge,
+    Virtual,
+    Tap,
+    Loopback,
+    Unknown,
+}
+
+impl InterfaceKind {
+    /// Human-readable kind
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Ethernet => "Ethernet",
+            Self::Wireless => "Wireless",
+            Self::Bridge => "Bridge",
+            Self::Virtual => "Virtual",
+            Self::Tap => "TAP/TUN",
+            Self::Loopback => "Loopback",
+            Self::Unknown => "Unknown",
+        }
+    }
+
+    /// Whether the interface is (probably) backed by hardware
+    pub fn is_physical(&self) -> bool {
+        !matches!(self, Self::Bridge | Self::Virtual | Self::Tap)
+    }
+}
+
+/// A host network interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInterface {
+    pub name: String,
+    pub kind: InterfaceKind,
+    /// Operational state is "up"
+    pub up: bool,
+}
+
+/// A TAP interface set up for the guest
+#[derive(Debug)]
+pub struct TapDevice {
+    name: String,
+    bridge: Option<String>,
+    created_tap: bool,
+    created_bridge: bool,
+    up: bool,
+}
+
+impl TapDevice {
+    /// Create (or reuse) the TAP interface and attach it to the bridge
+    ///
+    /// On failure anything created so far is removed again.
+    pub fn create(options: &TapOptions) -> Result<Self, NetSetupError> {
+        validate_ifname(&options.tap_name)?;
+        let bridge = (!options.bridge.is_empty()).then(|| options.bridge.clone());
+        if let Some(bridge) = &bridge {
+            validate_ifname(bridge)?;
+        }
+
+        let mut device = Self {
+            name: options.tap_name.clone(),
+            bridge: None,
+            created_tap: false,
+            created_bridge: false,
+            up: false,
+        };
+
+        if !interface_exists(&device.name) {
+            let owner = options.owner.unwrap_or_else(|| nix::unistd::getuid().as_raw());
+            run_ip(&tap_add_args(&device.name, owner))?;
+            device.created_tap = true;
+        }
+
+        if let Some(bridge) = bridge {
+            if !interface_exists(&bridge) {
+                if !options.create_bridge {
+                    // Dropping `device` removes the TAP created above
+                    return Err(NetSetupError::NoSuchBridge(bridge));
+                }
+                run_ip(&["link", "add", "name", &bridge, "type", "bridge"])?;
+                device.created_bridge = true;
+                run_ip(&["link", "set", "dev", &bridge, "up"])?;
+            }
+            run_ip(&["link", "set", "dev", &device.name, "master", &bridge])?;
+            device.bridge = Some(bridge);
+        }
+
+        Ok(device)
+    }
+
+    /// Interface name to pass to the driver
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Bridge the TAP is attached to, if any
+    pub fn bridge(&self) -> Option<&str> {
+        self.bridge.as_deref()
+    }
+
+    /// Whether the link is up
+    pub fn is_up(&self) -> bool {
+        self.up
+    }
+
+    /// Bring the link up or down (follows the session state)
+    pub fn set_up(&mut self, up: bool) -> Result<(), NetSetupError> {
+        if self.up != up {
+            let state = if up { "up" } else { "down" };
+            run_ip(&["link", "set", "dev", &self.name, state])?;
+            self.up = up;
+        }
+        Ok(())
+    }
+
+    /// Undo the setup; equivalent to dropping, but reports the first error
+    pub fn teardown(mut self) -> Result<(), NetSetupError> {
+        self.cleanup()
+    }
+
+    /// Undo every step, continuing past failures
+    fn cleanup(&mut self) -> Result<(), NetSetupError> {
+        let mut result = Ok(());
+        let mut step = |r: Result<(), NetSetupError>| {
+            if let Err(e) = r
+                && result.is_ok()
+            {
+                result = Err(e);
+            }
+        };
+
+        if self.up {
+            step(run_ip(&["link", "set", "dev", &self.name, "down"]));
+            self.up = false;
+        }
+        if self.created_tap {
+            // Deleting the TAP also detaches it from the bridge
+            step(run_ip(&["tuntap", "del", "dev", &self.name, "mode", "tap"]));
+            self.created_tap = false;
+        } else if self.bridge.is_some() {
+            step(run_ip(&["link", "set", "dev", &self.name, "nomaster"]));
+        }
+        if let Some(bridge) = self.bridge.take()
+            && self.created_bridge
+        {
+            step(run_ip(&["link", "del", "dev", &bridge]));
+            self.created_bridge = false;
+        }
+
+        result
+    }
+}
+
+impl Drop for TapDevice {
+    fn drop(&mut self) {
+        let _ = self.cleanup();
+    }
+}
+
+/// Whether a network interface with this name exists
+pub fn interface_exists(name: &str) -> bool {
+    Path::new(SYSFS_NET).join(name).exists()
+}
+
+/// Carrier, speed and duplex of a host interface; None if it does not exist
+pub fn link_info(name: &str) -> Option<LinkInfo> {
+    link_info_at(Path::new(SYSFS_NET), name)
+}
+
+fn link_info_at(sysfs: &Path, name: &str) -> Option<LinkInfo> {
+    validate_ifname(name).ok()?;
+    let dir = sysfs.join(name);
+    if !dir.exists() {
+        return None;
+    }
+    // These files fail to read (EINVAL) while the interface is down
+    let read = |file: &str| {
+        std::fs::read_to_string(dir.join(file))
+            .map(|s| s.trim().to_string())
+            .ok()
+    };
+
+    let carrier = read("carrier").is_some_and(|c| c == "1");
+    // Speed is -1 or absent when unknown
+    let speed_mbps = read("speed")
+        .and_then(|s| s.parse::<i64>().ok())
+        .and_then(|s| u32::try_from(s).ok())
+        .filter(|&s| carrier && s > 0);
+    let full_duplex = read("duplex")
+        .filter(|_| carrier)
+        .and_then(|d| match d.as_str() {
+            "full" => Some(true),
+            "half" => Some(false),
+            _ => None,
+        });
+
+    Some(LinkInfo {
+        carrier,
+        speed_mbps,
+        full_duplex,
+    })
+}
+
+/// Host network interfaces other than loopback, physical ones first
+pub fn host_interfaces() -> Vec<HostInterface> {
+    host_interfaces_at(Path::new(SYSFS_NET))
+}
+
+fn host_interfaces_at(sysfs: &Path) -> Vec<HostInterface> {
+    let Ok(entries) = std::fs::read_dir(sysfs) else {
+        return Vec::new();
+    };
+    let mut interfaces: Vec<HostInterface> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name != "lo")
+        .map(|name| {
+            let up = std::fs::read_to_string(sysfs.join(&name).join("operstate"))
+                .is_ok_and(|state| state.trim() == "up");
+            HostInterface {
+                kind: interface_kind_at(sysfs, &name),
+                name,
+                up,
+            }
+        })
+        .collect();
+
+    interfaces.sort_by(|a, b| {
+        b.kind
+            .is_physical()
+            .cmp(&a.kind.is_physical())
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    interfaces
+}
+
+/// Guess an interface's kind from sysfs, falling back to its name
+fn interface_kind_at(sysfs: &Path, name: &str) -> InterfaceKind {
+    let dir = sysfs.join(name);
+    if dir.join("wireless").exists() {
+        return InterfaceKind::Wireless;
+    }
+
+    // ARPHRD_* device type
+    let device_type = std::fs::read_to_string(dir.join("type"))
+        .ok()
+        .and_then(|t| t.trim().parse::<u32>().ok());
+    match device_type {
+        Some(1) => {
+            // Ethernet framing; bridges and virtual devices use it too
+            return if name.starts_with("br") || name.starts_with("virbr") {
+                InterfaceKind::Bridge
+            } else if name.starts_with("veth") || name.starts_with("docker") {
+                InterfaceKind::Virtual
+            } else if name.starts_with("tap") || name.starts_with("tun") {
+                InterfaceKind::Tap
+            } else {
+                InterfaceKind::Ethernet
+            };
+        }
+        Some(772) => return InterfaceKind::Loopback,
+        _ => {}
+    }
+
+    // Naming convention
+    if name.starts_with("en") || name.starts_with("eth") {
+        InterfaceKind::Ethernet
+    } else if name.starts_with("wl") {
+        InterfaceKind::Wireless
+    } else if name.starts_with("br") {
+        InterfaceKind::Bridge
+    } else if name.starts_with("docker") || name.starts_with("veth") {
+        InterfaceKind::Virtual
+    } else {
+        InterfaceKind::Unknown
+    }
+}
+
+/// Check an interface name is usable with ip and the kernel
+pub fn validate_ifname(name: &str) -> Result<(), NetSetupError> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_IFNAME_LEN
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '/' && c != ':');
+    if valid {
+        Ok(())
+    } else {
+        Err(NetSetupError::InvalidName(name.to_string()))
+    }
+}
+
+/// Arguments for creating a persistent TAP owned by `owner`
+fn tap_add_args(name: &str, owner: u32) -> Vec<String> {
+    ["tuntap", "add", "dev", name, "mode", "tap", "user", &owner.to_string()]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Run `ip` with the given arguments
+fn run_ip<S: AsRef<str>>(args: &[S]) -> Result<(), NetSetupError> {
+    let args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let output = Command::new("ip").args(&args).output()?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(NetSetupError::Command {
+            args: args.join(" "),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_ifname() {
+        assert!(validate_ifname("sunpci0").is_ok());
+        assert!(validate_ifname("br-lan").is_ok());
+        assert!(validate_ifname("").is_err());
+        assert!(validate_ifname("a-very-long-ifname").is_err());
+        assert!(validate_ifname("tap 0").is_err());
+        assert!(validate_ifname("../eth0").is_err());
+
+        assert_eq!(
+            tap_add_args("sunpci0", 1000).join(" "),
+            "tuntap add dev sunpci0 mode tap user 1000"
+        );
+    }
+
+    #[test]
+    fn test_link_info() {
+        let sysfs = tempfile::tempdir().unwrap();
+        let eth = sysfs.path().join("eth0");
+        std::fs::create_dir(&eth).unwrap();
+        std::fs::write(eth.join("carrier"), "1\n").unwrap();
+        std::fs::write(eth.join("speed"), "1000\n").unwrap();
+        std::fs::write(eth.join("duplex"), "full\n").unwrap();
+        let info = link_info_at(sysfs.path(), "eth0").unwrap();
+        assert_eq!(info.speed_mbps, Some(1000));
+        assert_eq!(info.full_duplex, Some(true));
+
+        // Unplugged: the driver still reports the last speed, ignore it
+        std::fs::write(eth.join("carrier"), "0\n").unwrap();
+        assert_eq!(link_info_at(sysfs.path(), "eth0").unwrap(), LinkInfo::default());
+
+        // Virtual interfaces report speed -1
+        let br = sysfs.path().join("br0");
+        std::fs::create_dir(&br).unwrap();
+        std::fs::write(br.join("carrier"), "1\n").unwrap();
+        std::fs::write(br.join("speed"), "-1\n").unwrap();
+        let info = link_info_at(sysfs.path(), "br0").unwrap();
+        assert!(info.carrier);
+        assert_eq!(info.speed_mbps, None);
+
+        assert_eq!(link_info_at(sysfs.path(), "missing0"), None);
+    }
+
+    #[test]
+    fn test_host_interfaces() {
+        let sysfs = tempfile::tempdir().unwrap();
+        let add = |name: &str, device_type: &str, state: &str| {
+            let dir = sysfs.path().join(name);
+            std::fs::create_dir(&dir).unwrap();
+            std::fs::write(dir.join("type"), device_type).unwrap();
+            std::fs::write(dir.join("operstate"), state).unwrap();
+            dir
+        };
+        add("lo", "772\n", "unknown\n");
+        add("virbr0", "1\n", "down\n");
+        add("eth1", "1\n", "down\n");
+        add("enp3s0", "1\n", "up\n");
+        let wifi = add("wlp2s0", "1\n", "up\n");
+        std::fs::create_dir(wifi.join("wireless")).unwrap();
+
+        let interfaces = host_interfaces_at(sysfs.path());
+        let listed: Vec<_> = interfaces.iter().map(|i| (i.name.as_str(), i.kind, i.up)).collect();
+        assert_eq!(
+            listed,
+            [
+                ("enp3s0", InterfaceKind::Ethernet, true),
+                ("eth1", InterfaceKind::Ethernet, false),
+                ("wlp2s0", InterfaceKind::Wireless, true),
+                ("virbr0", InterfaceKind::Bridge, false),
+            ]
+        );
+    }
+}
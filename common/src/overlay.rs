@@ -0,0 +1,283 @@
+//! Copy-on-write overlays of template disks.
+//!
+//! A template is a read-only disk image kept in the library's templates
+//! directory. A profile whose disk is a template never writes to it: when
+//! the session starts it gets an overlay child instead, which the driver
+//! mounts in place of the template. The overlay holds only the sectors the
+//! guest has written, so any number of profiles can share one pristine
+//! install.
+//!
+//! `throwaway` does the same for a single run: the session writes to a
+//! temporary overlay that is deleted when it stops, leaving the image as it
+//! was. An image that is itself an overlay is copied instead (sparsely, so
+//! only the sectors it holds), as the driver does not stack overlays.
+//!
+//! Overlay layout (all integers little-endian):
+//!
+//! | Offset | Size | Contents                                   |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 8    | `"SPCIOVL\0"`                              |
+//! | 8      | 4    | version (1)                                |
+//! | 16     | 8    | sectors of the virtual disk                |
+//! | 24     | 8    | bitmap offset (4096)                       |
+//! | 32     | 8    | data offset                                |
+//! | 64     | ...  | template path, NUL-terminated              |
+//!
+//! The bitmap has one bit per sector, least significant bit first, set
+//! once the sector has been written. Sector `n` is stored at
+//! `data_offset + n * 512`; the data area is sparse, so an overlay starts
+//! out at a few kilobytes whatever the size of the disk.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::AppConfig;
+use crate::disk_image::SECTOR_SIZE;
+use crate::disk_library;
+use crate::progress::ProgressReporter;
+
+/// Overlay signature
+pub const OVERLAY_MAGIC: &[u8; 8] = b"SPCIOVL\0";
+/// Overlay format version
+pub const OVERLAY_VERSION: u32 = 1;
+/// Size of the header, which is also where the bitmap starts
+pub const HEADER_SIZE: u64 = 4096;
+/// Offset of the template path in the header
+const BASE_PATH_OFFSET: usize = 64;
+/// The data area starts on a boundary of this many bytes
+const DATA_ALIGN: u64 = 1024 * 1024;
+
+/// What an overlay header says
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverlayInfo {
+    /// Template the overlay is a child of
+    pub base: PathBuf,
+    /// Sectors of the virtual disk
+    pub disk_sectors: u64,
+    /// Where the bitmap starts
+    pub bitmap_offset: u64,
+    /// Where sector 0 is stored
+    pub data_offset: u64,
+}
+
+/// Default location of overlay children
+pub fn overlays_dir() -> PathBuf {
+    AppConfig::data_dir().join("overlays")
+}
+
+/// Default location of throwaway overlays
+pub fn throwaway_dir() -> PathBuf {
+    overlays_dir().join("throwaway")
+}
+
+/// Whether the image at `path` is a template
+pub fn is_template(path: &Path) -> bool {
+    let templates = disk_library::templates_dir();
+    match (path.parent().map(fs::canonicalize), fs::canonicalize(&templates)) {
+        (Some(Ok(parent)), Ok(templates)) => parent == templates,
+        _ => false,
+    }
+}
+
+/// Read the header of `path`; None if it is not an overlay
+pub fn read_overlay(path: &Path) -> io::Result<Option<OverlayInfo>> {
+    let mut header = vec![0u8; HEADER_SIZE as usize];
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < HEADER_SIZE {
+        return Ok(None);
+    }
+    file.read_exact(&mut header)?;
+    if &header[..8] != OVERLAY_MAGIC {
+        return Ok(None);
+    }
+
+    let le32 = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let le64 = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+    if le32(8) != OVERLAY_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported overlay version {}", le32(8)),
+        ));
+    }
+    let path_bytes = &header[BASE_PATH_OFFSET..];
+    let len = path_bytes.iter().position(|&b| b == 0).unwrap_or(path_bytes.len());
+    let base = String::from_utf8_lossy(&path_bytes[..len]).into_owned();
+
+    Ok(Some(OverlayInfo {
+        base: PathBuf::from(base),
+        disk_sectors: le64(16),
+        bitmap_offset: le64(24),
+        data_offset: le64(32),
+    }))
+}
+
+/// Create an empty overlay of `base` at `path`, which must not exist
+pub fn create_overlay(base: &Path, path: &Path) -> io::Result<OverlayInfo> {
+    let base = fs::canonicalize(base)?;
+    let base_bytes = base.as_os_str().as_encoded_bytes();
+    if base_bytes.len() >= HEADER_SIZE as usize - BASE_PATH_OFFSET {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "template path is too long"));
+    }
+
+    let disk_sectors = fs::metadata(&base)?.len() / SECTOR_SIZE as u64;
+    if disk_sectors == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "template is empty"));
+    }
+    let bitmap_offset = HEADER_SIZE;
+    let data_offset = (bitmap_offset + disk_sectors.div_ceil(8)).next_multiple_of(DATA_ALIGN);
+
+    let mut header = vec![0u8; HEADER_SIZE as usize];
+    header[..8].copy_from_slice(OVERLAY_MAGIC);
+    header[8..12].copy_from_slice(&OVERLAY_VERSION.to_le_bytes());
+    header[16..24].copy_from_slice(&disk_sectors.to_le_bytes());
+    header[24..32].copy_from_slice(&bitmap_offset.to_le_bytes());
+    header[32..40].copy_from_slice(&data_offset.to_le_bytes());
+    header[BASE_PATH_OFFSET..BASE_PATH_OFFSET + base_bytes.len()].copy_from_slice(base_bytes);
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o644)
+        .open(path)?;
+    file.write_all(&header)?;
+    // Bitmap and data stay holes until the guest writes
+    file.set_len(data_offset + disk_sectors * SECTOR_SIZE as u64)?;
+    file.sync_all()?;
+
+    Ok(OverlayInfo {
+        base,
+        disk_sectors,
+        bitmap_offset,
+        data_offset,
+    })
+}
+
+/// Point the overlay at `path` to the template at `base`, which must be
+/// the same size as the one it was made from
+pub fn set_base(path: &Path, base: &Path) -> io::Result<()> {
+    let info = read_overlay(path)?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an overlay"))?;
+    let base = fs::canonicalize(base)?;
+    if fs::metadata(&base)?.len() / SECTOR_SIZE as u64 != info.disk_sectors {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "template size does not match"));
+    }
+    let base_bytes = base.as_os_str().as_encoded_bytes();
+    if base_bytes.len() >= HEADER_SIZE as usize - BASE_PATH_OFFSET {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "template path is too long"));
+    }
+
+    let mut field = vec![0u8; HEADER_SIZE as usize - BASE_PATH_OFFSET];
+    field[..base_bytes.len()].copy_from_slice(base_bytes);
+    let file = OpenOptions::new().write(true).open(path)?;
+    file.write_all_at(&field, BASE_PATH_OFFSET as u64)?;
+    file.sync_all()
+}
+
+/// Overlay to mount for the template at `path`: the existing child
+/// `current` if it is still an overlay of this template, otherwise a new
+/// one in `dir`
+pub fn instantiate(path: &Path, current: Option<&Path>, dir: &Path) -> io::Result<PathBuf> {
+    let base = fs::canonicalize(path)?;
+    if let Some(child) = current
+        && let Ok(Some(info)) = read_overlay(child)
+        && info.base == base
+    {
+        return Ok(child.to_path_buf());
+    }
+
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned()).unwrap_or_else(|| "img".into());
+    let mut child = dir.join(format!("{}-{}.{}", stem, stamp, extension));
+    let mut n = 1;
+    while child.exists() {
+        child = dir.join(format!("{}-{}-{}.{}", stem, stamp, n, extension));
+        n += 1;
+    }
+    create_overlay(&base, &child)?;
+    Ok(child)
+}
+
+/// A temporary image in `dir` that the session can write to in place of
+/// the image at `path`, to be removed when the session stops
+pub fn throwaway(path: &Path, dir: &Path) -> anyhow::Result<PathBuf> {
+    let name = path.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut temporary = dir.join(format!("{}-{}", std::process::id(), name));
+    let mut n = 1;
+    while temporary.exists() {
+        temporary = dir.join(format!("{}-{}-{}", std::process::id(), n, name));
+        n += 1;
+    }
+
+    if read_overlay(path)?.is_some() {
+        disk_library::copy_image(path, &temporary, &ProgressReporter::new())?;
+    } else {
+        create_overlay(path, &temporary)?;
+    }
+    Ok(temporary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_and_read_overlay() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.img");
+        File::create(&base).unwrap().set_len(64 * 1024 * 1024).unwrap();
+
+        let child = dir.path().join("child.img");
+        let created = create_overlay(&base, &child).unwrap();
+        assert_eq!(created.disk_sectors, 131_072);
+        assert_eq!(created.bitmap_offset, HEADER_SIZE);
+        assert_eq!(created.data_offset, DATA_ALIGN);
+        assert_eq!(read_overlay(&child).unwrap(), Some(created));
+
+        assert_eq!(read_overlay(&base).unwrap(), None);
+        assert!(create_overlay(&base, &child).is_err());
+    }
+
+    #[test]
+    fn test_instantiate() {
+        let dir = tempfile::tempdir().unwrap();
+        let template = dir.path().join("Win98.img");
+        File::create(&template).unwrap().set_len(1024 * 1024).unwrap();
+        let children = dir.path().join("overlays");
+
+        let child = instantiate(&template, None, &children).unwrap();
+        assert!(child.starts_with(&children));
+        assert_eq!(child.extension().unwrap(), "img");
+
+        // The child is kept for as long as it belongs to the template
+        assert_eq!(instantiate(&template, Some(&child), &children).unwrap(), child);
+        let other = dir.path().join("Dos.img");
+        File::create(&other).unwrap().set_len(1024 * 1024).unwrap();
+        assert_ne!(instantiate(&other, Some(&child), &children).unwrap(), child);
+
+        assert!(!is_template(&template));
+    }
+
+    #[test]
+    fn test_throwaway() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("disk.img");
+        File::create(&disk).unwrap().set_len(1024 * 1024).unwrap();
+        let temporary_dir = dir.path().join("throwaway");
+
+        // A plain image gets an overlay
+        let first = throwaway(&disk, &temporary_dir).unwrap();
+        assert_eq!(read_overlay(&first).unwrap().unwrap().base, fs::canonicalize(&disk).unwrap());
+
+        // An overlay is copied, keeping its template
+        let second = throwaway(&first, &temporary_dir).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(read_overlay(&second).unwrap(), read_overlay(&first).unwrap());
+    }
+}
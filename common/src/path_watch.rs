@@ -0,0 +1,149 @@
+//! Watching host directories come and go.
+//!
+//! A drive mapping names a host directory that may not always be there:
+//! a USB stick that is unplugged, a network share that is unmounted, a
+//! directory that is created later. `PathWatcher` tracks whether each of a
+//! set of paths is an existing directory. Rather than polling them it uses
+//! inotify, watching the nearest existing ancestor of every path for
+//! entries created, deleted or moved, and every existing path for being
+//! deleted, moved or unmounted. After any event the watches are set up
+//! again, since the ancestor that matters may have changed.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nix::errno::Errno;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, WatchDescriptor};
+
+/// Events on an ancestor that can make a path appear or disappear
+const ANCESTOR_EVENTS: AddWatchFlags = AddWatchFlags::IN_CREATE
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_MOVED_FROM)
+    .union(AddWatchFlags::IN_MOVED_TO)
+    .union(AddWatchFlags::IN_ONLYDIR);
+
+/// Events on a path that make it disappear
+const SELF_EVENTS: AddWatchFlags = AddWatchFlags::IN_DELETE_SELF
+    .union(AddWatchFlags::IN_MOVE_SELF)
+    .union(AddWatchFlags::IN_UNMOUNT)
+    .union(AddWatchFlags::IN_ONLYDIR);
+
+/// Tracks whether a set of host paths are existing directories
+pub struct PathWatcher {
+    inotify: Inotify,
+    /// Whether each path was available when last checked
+    paths: HashMap<PathBuf, bool>,
+    watches: Vec<WatchDescriptor>,
+}
+
+impl PathWatcher {
+    /// Create a watcher with no paths
+    pub fn new() -> io::Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)?;
+        Ok(Self {
+            inotify,
+            paths: HashMap::new(),
+            watches: Vec::new(),
+        })
+    }
+
+    /// Watch `paths` instead of the paths watched so far
+    pub fn set_paths<I: IntoIterator<Item = PathBuf>>(&mut self, paths: I) -> io::Result<()> {
+        self.paths = paths
+            .into_iter()
+            .map(|path| {
+                let available = path.is_dir();
+                (path, available)
+            })
+            .collect();
+        self.rewatch()
+    }
+
+    /// Whether `path` was an existing directory when last checked, None if
+    /// it is not watched
+    pub fn is_available(&self, path: &Path) -> Option<bool> {
+        self.paths.get(path).copied()
+    }
+
+    /// Read the events that have arrived, without blocking
+    ///
+    /// Returns the paths whose availability changed, with their new state.
+    pub fn poll(&mut self) -> io::Result<Vec<(PathBuf, bool)>> {
+        let mut any = false;
+        loop {
+            match self.inotify.read_events() {
+                Ok(events) if !events.is_empty() => any = true,
+                Ok(_) | Err(Errno::EAGAIN) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if !any {
+            return Ok(Vec::new());
+        }
+
+        let mut changed = Vec::new();
+        for (path, available) in self.paths.iter_mut() {
+            let now = path.is_dir();
+            if now != *available {
+                *available = now;
+                changed.push((path.clone(), now));
+            }
+        }
+        self.rewatch()?;
+        Ok(changed)
+    }
+
+    /// Drop all watches and add those the current paths need
+    fn rewatch(&mut self) -> io::Result<()> {
+        for watch in self.watches.drain(..) {
+            // Gone already if its directory was deleted or unmounted
+            let _ = self.inotify.rm_watch(watch);
+        }
+
+        // A second watch on a directory would replace the first one's mask
+        let mut masks: HashMap<&Path, AddWatchFlags> = HashMap::new();
+        for (path, available) in &self.paths {
+            if *available {
+                *masks.entry(path).or_insert(AddWatchFlags::empty()) |= SELF_EVENTS;
+            }
+            if let Some(ancestor) = path.ancestors().skip(1).find(|a| a.is_dir()) {
+                *masks.entry(ancestor).or_insert(AddWatchFlags::empty()) |= ANCESTOR_EVENTS;
+            }
+        }
+        for (dir, mask) in masks {
+            match self.inotify.add_watch(dir, mask) {
+                Ok(watch) => self.watches.push(watch),
+                // Gone since it was checked, or unreadable: only the events are lost
+                Err(Errno::ENOENT | Errno::ENOTDIR | Errno::EACCES) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_watcher() {
+        let dir = tempfile::tempdir().unwrap();
+        let stick = dir.path().join("media/stick");
+        let mut watcher = PathWatcher::new().unwrap();
+        watcher.set_paths([stick.clone()]).unwrap();
+        assert_eq!(watcher.is_available(&stick), Some(false));
+        assert!(watcher.poll().unwrap().is_empty());
+
+        // Appears a level below the nearest existing ancestor
+        std::fs::create_dir(dir.path().join("media")).unwrap();
+        assert!(watcher.poll().unwrap().is_empty());
+        std::fs::create_dir(&stick).unwrap();
+        assert_eq!(watcher.poll().unwrap(), vec![(stick.clone(), true)]);
+
+        std::fs::remove_dir(&stick).unwrap();
+        assert_eq!(watcher.poll().unwrap(), vec![(stick.clone(), false)]);
+        assert_eq!(watcher.is_available(&stick), Some(false));
+    }
+}
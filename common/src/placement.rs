@@ -0,0 +1,192 @@
+//! Window placement across screens, and pointer motion under hi-DPI.
+//!
+//! The window's geometry is saved with the name of the screen it was on.
+//! `place_window` puts it back there if that screen is still connected,
+//! or on the primary screen otherwise, keeping it inside the screen's
+//! available area either way.
+//!
+//! Qt reports pointer positions in logical pixels, which on a screen
+//! scaled by 1.25 or 1.5 are fractions of the physical pixels the mouse
+//! actually moved. `MotionScaler` turns logical deltas back into whole
+//! device pixels and carries the remainder, so the guest pointer moves
+//! as far as the host one would at any scale.
+
+use serde::Deserialize;
+
+use crate::config::{DisplayConfig, GeneralConfig};
+
+/// A host screen, in virtual desktop coordinates (logical pixels)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ScreenInfo {
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// Physical pixels per logical pixel
+    pub device_pixel_ratio: f64,
+}
+
+/// Where the window goes: the index of its screen and its geometry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub screen: usize,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Place a `default_w` x `default_h` window using the saved geometry
+///
+/// `screens` lists the connected screens, primary first. Returns None
+/// when there are no screens.
+pub fn place_window(
+    general: &GeneralConfig,
+    screens: &[ScreenInfo],
+    default_w: i32,
+    default_h: i32,
+) -> Option<Placement> {
+    let saved_screen = general
+        .window_screen
+        .as_deref()
+        .and_then(|name| screens.iter().position(|s| s.name == name));
+    let index = saved_screen.unwrap_or(0);
+    let screen = screens.get(index)?;
+
+    let remember = general.remember_window_geometry;
+    let (width, height) = match (general.window_width, general.window_height) {
+        (Some(w), Some(h)) if remember => (w as i32, h as i32),
+        _ => (default_w, default_h),
+    };
+    let width = width.clamp(1, screen.width.max(1));
+    let height = height.clamp(1, screen.height.max(1));
+
+    // A position saved for another screen means nothing on this one
+    let (x, y) = match (general.window_x, general.window_y) {
+        (Some(x), Some(y)) if remember && saved_screen.is_some() => (x, y),
+        _ => (
+            screen.x + (screen.width - width) / 2,
+            screen.y + (screen.height - height) / 2,
+        ),
+    };
+    Some(Placement {
+        screen: index,
+        x: x.clamp(screen.x, screen.x + screen.width - width),
+        y: y.clamp(screen.y, screen.y + screen.height - height),
+        width,
+        height,
+    })
+}
+
+/// Index of the screen to go fullscreen on, given the window's screen
+pub fn fullscreen_screen(display: &DisplayConfig, screen_count: usize, current: usize) -> usize {
+    match display.fullscreen_screen {
+        Some(n) if (n as usize) < screen_count => n as usize,
+        _ => current,
+    }
+}
+
+/// Converts logical pointer deltas to device pixels
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MotionScaler {
+    remainder: (f64, f64),
+}
+
+impl MotionScaler {
+    /// Whole device pixels moved for a logical delta at `device_pixel_ratio`
+    pub fn scale(&mut self, dx: f64, dy: f64, device_pixel_ratio: f64) -> (i32, i32) {
+        let ratio = if device_pixel_ratio > 0.0 { device_pixel_ratio } else { 1.0 };
+        let x = dx * ratio + self.remainder.0;
+        let y = dy * ratio + self.remainder.1;
+        let (whole_x, whole_y) = (x.trunc(), y.trunc());
+        self.remainder = (x - whole_x, y - whole_y);
+        (whole_x as i32, whole_y as i32)
+    }
+
+    /// Drop any carried fraction, e.g. when the pointer is captured again
+    pub fn reset(&mut self) {
+        self.remainder = (0.0, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn screens() -> Vec<ScreenInfo> {
+        vec![
+            ScreenInfo {
+                name: "DP-1".into(),
+                x: 0,
+                y: 0,
+                width: 1920,
+                height: 1080,
+                device_pixel_ratio: 1.0,
+            },
+            ScreenInfo {
+                name: "HDMI-1".into(),
+                x: 1920,
+                y: 0,
+                width: 1280,
+                height: 720,
+                device_pixel_ratio: 1.5,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_place_on_saved_screen() {
+        let general = GeneralConfig {
+            window_screen: Some("HDMI-1".into()),
+            window_x: Some(3000),
+            window_y: Some(100),
+            window_width: Some(800),
+            window_height: Some(600),
+            ..Default::default()
+        };
+        let placement = place_window(&general, &screens(), 800, 600).unwrap();
+        assert_eq!(placement.screen, 1);
+        // Pulled back so the window fits on the screen
+        assert_eq!((placement.x, placement.y), (1920 + 1280 - 800, 100));
+    }
+
+    #[test]
+    fn test_place_when_screen_gone() {
+        let general = GeneralConfig {
+            window_screen: Some("VGA-1".into()),
+            window_x: Some(5000),
+            window_y: Some(100),
+            ..Default::default()
+        };
+        let placement = place_window(&general, &screens(), 800, 600).unwrap();
+        assert_eq!(placement.screen, 0);
+        assert_eq!((placement.x, placement.y), (560, 240));
+        assert_eq!(place_window(&general, &[], 800, 600), None);
+    }
+
+    #[test]
+    fn test_fullscreen_screen() {
+        let mut display = DisplayConfig::default();
+        assert_eq!(fullscreen_screen(&display, 2, 1), 1);
+        display.fullscreen_screen = Some(0);
+        assert_eq!(fullscreen_screen(&display, 2, 1), 0);
+        display.fullscreen_screen = Some(3);
+        assert_eq!(fullscreen_screen(&display, 2, 1), 1);
+    }
+
+    #[test]
+    fn test_motion_scaler() {
+        let mut scaler = MotionScaler::default();
+        // 1.25: four logical pixels are five device pixels
+        let moved: i32 = (0..4).map(|_| scaler.scale(1.0, 0.0, 1.25).0).sum();
+        assert_eq!(moved, 5);
+
+        // Fractional logical deltas add up instead of being dropped
+        let moved: i32 = (0..3).map(|_| scaler.scale(0.0, -0.5, 1.5).1).sum();
+        assert_eq!(moved, -2);
+
+        scaler.reset();
+        assert_eq!(scaler.scale(2.0, 2.0, 0.0), (2, 2));
+    }
+}
@@ -0,0 +1,143 @@
+//! Progress reporting shared by all long-running operations.
+//!
+//! Disk creation and conversion, backups and downloads all report through a
+//! `ProgressReporter`: a cheaply cloneable handle holding the current and
+//! total units of work, a short description of the current step, and a
+//! cancellation flag. The worker updates it; the UI polls `snapshot()` and
+//! may call `cancel()`. Cancellation is cooperative - workers call
+//! `check_cancelled()` between steps.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+/// Error an operation returns when it stops because cancellation was requested
+#[derive(Debug, Error)]
+#[error("operation cancelled")]
+pub struct Cancelled;
+
+#[derive(Default)]
+struct ProgressState {
+    current: AtomicU64,
+    total: AtomicU64,
+    cancelled: AtomicBool,
+    step: Mutex<String>,
+}
+
+/// Shared progress and cancellation state for one operation
+#[derive(Clone, Default)]
+pub struct ProgressReporter {
+    state: Arc<ProgressState>,
+}
+
+/// Point-in-time copy of a reporter's state
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProgressSnapshot {
+    /// Units of work done
+    pub current: u64,
+    /// Total units of work (0 = unknown)
+    pub total: u64,
+    /// Description of the current step
+    pub step: String,
+    /// Whether cancellation has been requested
+    pub cancelled: bool,
+}
+
+impl ProgressSnapshot {
+    /// Progress in percent (0 while the total is unknown)
+    pub fn percent(&self) -> u32 {
+        (self.current.min(self.total) * 100)
+            .checked_div(self.total)
+            .unwrap_or(0) as u32
+    }
+}
+
+impl ProgressReporter {
+    /// Create a reporter with no progress and no total
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the total units of work
+    pub fn set_total(&self, total: u64) {
+        self.state.total.store(total, Ordering::Relaxed);
+    }
+
+    /// Set the units of work done
+    pub fn set_current(&self, current: u64) {
+        self.state.current.store(current, Ordering::Relaxed);
+    }
+
+    /// Set both the units done and the total
+    pub fn set_progress(&self, current: u64, total: u64) {
+        self.set_total(total);
+        self.set_current(current);
+    }
+
+    /// Add to the units of work done
+    pub fn advance(&self, amount: u64) {
+        self.state.current.fetch_add(amount, Ordering::Relaxed);
+    }
+
+    /// Describe the current step (e.g. "Writing FAT")
+    pub fn set_step(&self, step: &str) {
+        if let Ok(mut s) = self.state.step.lock() {
+            s.clear();
+            s.push_str(step);
+        }
+    }
+
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Return `Err(Cancelled)` if cancellation has been requested
+    pub fn check_cancelled(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Copy the current state
+    pub fn snapshot(&self) -> ProgressSnapshot {
+        ProgressSnapshot {
+            current: self.state.current.load(Ordering::Relaxed),
+            total: self.state.total.load(Ordering::Relaxed),
+            step: self.state.step.lock().map(|s| s.clone()).unwrap_or_default(),
+            cancelled: self.is_cancelled(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_reporter() {
+        let reporter = ProgressReporter::new();
+        let ui = reporter.clone();
+        assert_eq!(ui.snapshot().percent(), 0);
+
+        reporter.set_total(200);
+        reporter.advance(50);
+        reporter.set_step("Writing FAT");
+        let snap = ui.snapshot();
+        assert_eq!((snap.current, snap.total, snap.percent()), (50, 200, 25));
+        assert_eq!(snap.step, "Writing FAT");
+
+        assert!(reporter.check_cancelled().is_ok());
+        ui.cancel();
+        assert!(reporter.check_cancelled().is_err());
+        assert!(ui.snapshot().cancelled);
+    }
+}
@@ -0,0 +1,282 @@
+//! Removable drives plugged into the host.
+//!
+//! `MediaMonitor` follows the volumes of USB sticks, card readers and other
+//! removable drives that the host has mounted, so they can be offered to
+//! the guest as mapped drives. It listens for the kernel's block device
+//! uevents (the events udev acts on) and for changes to the mount table:
+//! a drive is usually mounted by the desktop some time after its uevent,
+//! and a drive pulled out without unmounting it can leave its mount behind.
+//! Either way the mount table is read again and compared with the volumes
+//! known before.
+//!
+//! A mounted block device counts as removable if the kernel says so
+//! (`removable` in sysfs, for the device or the disk a partition is on) or
+//! if it hangs off a USB bus, since USB hard disks and many sticks report
+//! themselves as fixed.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+use nix::libc;
+use nix::poll::{PollFd, PollFlags, PollTimeout, poll};
+
+/// Where the kernel lists block devices
+const SYSFS_BLOCK: &str = "/sys/class/block";
+
+/// The mount table of this process's mount namespace
+const MOUNTS: &str = "/proc/self/mounts";
+
+/// Netlink multicast group of kernel uevents
+const UEVENT_GROUP: u32 = 1;
+
+/// A mounted volume of a removable drive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovableVolume {
+    /// Block device, e.g. /dev/sdb1
+    pub device: PathBuf,
+    /// Where the host mounted it
+    pub mount_point: PathBuf,
+    /// Name to show, from the mount point (usually the volume label)
+    pub label: String,
+}
+
+/// A removable volume appearing or disappearing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MediaEvent {
+    Inserted(RemovableVolume),
+    Removed(RemovableVolume),
+}
+
+/// Follows the removable volumes mounted on the host
+pub struct MediaMonitor {
+    uevents: OwnedFd,
+    mounts: File,
+    volumes: Vec<RemovableVolume>,
+}
+
+impl MediaMonitor {
+    /// Start listening; the volumes mounted already are known, not inserted
+    pub fn new() -> io::Result<Self> {
+        let mut monitor = Self {
+            uevents: open_uevent_socket()?,
+            mounts: File::open(MOUNTS)?,
+            volumes: Vec::new(),
+        };
+        monitor.volumes = monitor.scan()?;
+        Ok(monitor)
+    }
+
+    /// Removable volumes mounted now
+    pub fn volumes(&self) -> &[RemovableVolume] {
+        &self.volumes
+    }
+
+    /// Check for volumes inserted or removed since the last call, without
+    /// blocking
+    pub fn poll(&mut self) -> io::Result<Vec<MediaEvent>> {
+        let uevent = drain_uevents(&self.uevents)?;
+        let mut fds = [PollFd::new(self.mounts.as_fd(), PollFlags::POLLPRI)];
+        poll(&mut fds, PollTimeout::ZERO)?;
+        let remounted = fds[0]
+            .revents()
+            .is_some_and(|r| r.intersects(PollFlags::POLLPRI | PollFlags::POLLERR));
+        if !uevent && !remounted {
+            return Ok(Vec::new());
+        }
+
+        let volumes = self.scan()?;
+        let mut events: Vec<MediaEvent> = self
+            .volumes
+            .iter()
+            .filter(|v| !volumes.contains(v))
+            .map(|v| MediaEvent::Removed(v.clone()))
+            .collect();
+        events.extend(
+            volumes
+                .iter()
+                .filter(|v| !self.volumes.contains(v))
+                .map(|v| MediaEvent::Inserted(v.clone())),
+        );
+        self.volumes = volumes;
+        Ok(events)
+    }
+
+    /// Read the mount table again (which also clears its change flag)
+    fn scan(&mut self) -> io::Result<Vec<RemovableVolume>> {
+        let mut table = String::new();
+        self.mounts.seek(SeekFrom::Start(0))?;
+        self.mounts.read_to_string(&mut table)?;
+        Ok(removable_volumes(&table, Path::new(SYSFS_BLOCK)))
+    }
+}
+
+/// Open a socket receiving the kernel's uevents
+fn open_uevent_socket() -> io::Result<OwnedFd> {
+    // SAFETY: plain socket creation; the descriptor is owned from here on
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC,
+            libc::NETLINK_KOBJECT_UEVENT,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: fd was just created and nothing else owns it
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    // SAFETY: sockaddr_nl is plain data, valid when zeroed
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = UEVENT_GROUP;
+    // SAFETY: addr is a sockaddr_nl of the size given
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// Read all pending uevents; true if any concerned a block device
+fn drain_uevents(fd: &OwnedFd) -> io::Result<bool> {
+    let mut buf = [0u8; 8192];
+    let mut block = false;
+    loop {
+        // SAFETY: buf is valid for writes of its length
+        let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len(), 0) };
+        if n < 0 {
+            let err = io::Error::last_os_error();
+            return match err.kind() {
+                io::ErrorKind::WouldBlock => Ok(block),
+                io::ErrorKind::Interrupted => continue,
+                _ => Err(err),
+            };
+        }
+        block |= uevent_subsystem(&buf[..n as usize]) == Some("block");
+    }
+}
+
+/// Subsystem of a kernel uevent ("add@/devices/...\0ACTION=add\0SUBSYSTEM=block\0...")
+fn uevent_subsystem(message: &[u8]) -> Option<&str> {
+    message
+        .split(|&b| b == 0)
+        .filter_map(|field| std::str::from_utf8(field).ok())
+        .find_map(|field| field.strip_prefix("SUBSYSTEM="))
+}
+
+/// The mounts in `table` (as in /proc/mounts) of removable block devices
+fn removable_volumes(table: &str, sysfs: &Path) -> Vec<RemovableVolume> {
+    table
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = PathBuf::from(unescape(fields.next()?));
+            let mount_point = PathBuf::from(unescape(fields.next()?));
+            let name = device.strip_prefix("/dev").ok()?.to_str()?.to_string();
+            if name.contains('/') || !is_removable(sysfs, &name) {
+                return None;
+            }
+            let label = mount_point
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| name.clone());
+            Some(RemovableVolume {
+                device,
+                mount_point,
+                label,
+            })
+        })
+        .collect()
+}
+
+/// Whether block device `name` is on a removable drive
+fn is_removable(sysfs: &Path, name: &str) -> bool {
+    let Ok(device) = std::fs::canonicalize(sysfs.join(name)) else {
+        return false;
+    };
+    if device.components().any(|c| c.as_os_str().to_string_lossy().starts_with("usb")) {
+        return true;
+    }
+    // A partition's attributes are those of the disk it is on
+    let disk = if device.join("partition").exists() {
+        device.parent().unwrap_or(&device)
+    } else {
+        &device
+    };
+    std::fs::read_to_string(disk.join("removable")).is_ok_and(|r| r.trim() == "1")
+}
+
+/// Undo the octal escapes of spaces and such in /proc/mounts fields
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let code = bytes.get(i + 1..i + 4).and_then(|d| u8::from_str_radix(std::str::from_utf8(d).ok()?, 8).ok());
+        match (bytes[i], code) {
+            (b'\\', Some(code)) => {
+                out.push(code);
+                i += 4;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_removable_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let devices = dir.path().join("devices");
+        let block = dir.path().join("block");
+        std::fs::create_dir(&block).unwrap();
+        // A USB stick reporting itself as fixed, a card reader that says it
+        // is removable, and an internal disk
+        let disks = [
+            ("pci0000:00/usb1/1-1/host6/block/sdb", "sdb1", "0"),
+            ("platform/mmc0/block/mmcblk0", "mmcblk0p1", "1"),
+            ("pci0000:00/ata1/host0/block/sda", "sda1", "0"),
+        ];
+        for (disk, partition, removable) in disks {
+            let disk = devices.join(disk);
+            std::fs::create_dir_all(disk.join(partition)).unwrap();
+            std::fs::write(disk.join(partition).join("partition"), "1").unwrap();
+            std::fs::write(disk.join("removable"), removable).unwrap();
+            std::os::unix::fs::symlink(disk.join(partition), block.join(partition)).unwrap();
+        }
+
+        let table = "/dev/sda1 / ext4 rw 0 0\n\
+                     proc /proc proc rw 0 0\n\
+                     /dev/sdb1 /media/user/MY\\040STICK vfat rw 0 0\n\
+                     /dev/mmcblk0p1 /media/user/CARD exfat rw 0 0\n";
+        let volumes = removable_volumes(table, &block);
+        assert_eq!(volumes.len(), 2);
+        assert_eq!(volumes[0].device, Path::new("/dev/sdb1"));
+        assert_eq!(volumes[0].mount_point, Path::new("/media/user/MY STICK"));
+        assert_eq!(volumes[0].label, "MY STICK");
+        assert_eq!(volumes[1].label, "CARD");
+    }
+
+    #[test]
+    fn test_uevent_subsystem() {
+        let message = b"add@/devices/pci0000:00/usb1/1-1/block/sdb\0ACTION=add\0SUBSYSTEM=block\0DEVNAME=sdb\0";
+        assert_eq!(uevent_subsystem(message), Some("block"));
+        assert_eq!(uevent_subsystem(b"change@/devices/x\0ACTION=change\0"), None);
+    }
+}
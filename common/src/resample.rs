@@ -0,0 +1,114 @@
+//! Sample rate conversion for guest audio.
+//!
+//! The guest plays at whatever rate its sound driver picked (11025, 22050,
+//! 44100 Hz...), and not every host device takes every rate; many only
+//! run at 48 kHz. `Resampler` converts interleaved 16-bit samples from the
+//! guest's rate to the device's by linear interpolation, which is plenty
+//! for a Sound Blaster. It keeps its place between calls, so the driver's
+//! buffers can be fed in as they arrive.
+
+/// Converts interleaved 16-bit audio from one rate to another
+#[derive(Debug, Clone)]
+pub struct Resampler {
+    channels: usize,
+    from: u32,
+    to: u32,
+    /// Input frames per output frame
+    step: f64,
+    /// Position of the next output frame, in input frames after `last`
+    pos: f64,
+    /// Last input frame of the previous call
+    last: Vec<i16>,
+}
+
+impl Resampler {
+    /// Convert `channels`-channel audio from `from` Hz to `to` Hz
+    pub fn new(from: u32, to: u32, channels: u32) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            channels,
+            from,
+            to,
+            step: from.max(1) as f64 / to.max(1) as f64,
+            pos: 0.0,
+            last: vec![0; channels],
+        }
+    }
+
+    /// Whether samples come out as they go in
+    pub fn is_passthrough(&self) -> bool {
+        self.from == self.to
+    }
+
+    /// Convert `input`, appending the result to `output`; a partial frame
+    /// at the end of `input` is dropped
+    pub fn process(&mut self, input: &[i16], output: &mut Vec<i16>) {
+        if self.is_passthrough() {
+            output.extend_from_slice(input);
+            return;
+        }
+        let channels = self.channels;
+        let frames = input.len() / channels;
+        if frames == 0 {
+            return;
+        }
+        // Frame 0 is the last one of the previous call, frame k is
+        // input frame k - 1
+        let frame = |k: usize| -> &[i16] {
+            if k == 0 {
+                &self.last
+            } else {
+                &input[(k - 1) * channels..k * channels]
+            }
+        };
+        output.reserve(((frames as f64 / self.step) as usize + 1) * channels);
+        while self.pos < frames as f64 {
+            let k = self.pos as usize;
+            let frac = self.pos - k as f64;
+            let (a, b) = (frame(k), frame(k + 1));
+            for c in 0..channels {
+                let sample = a[c] as f64 + (b[c] as f64 - a[c] as f64) * frac;
+                output.push(sample.round() as i16);
+            }
+            self.pos += self.step;
+        }
+        self.pos -= frames as f64;
+        self.last.copy_from_slice(&input[(frames - 1) * channels..frames * channels]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resample_rates() {
+        let mut passthrough = Resampler::new(44100, 44100, 2);
+        let mut out = Vec::new();
+        passthrough.process(&[1, 2, 3, 4], &mut out);
+        assert_eq!(out, [1, 2, 3, 4]);
+
+        // Doubling interpolates between frames, fed in pieces or at once
+        let input: Vec<i16> = (1..=8).map(|n| n * 100).collect();
+        let mut whole = Vec::new();
+        Resampler::new(22050, 44100, 1).process(&input, &mut whole);
+        let mut pieces = Vec::new();
+        let mut up = Resampler::new(22050, 44100, 1);
+        for chunk in input.chunks(3) {
+            up.process(chunk, &mut pieces);
+        }
+        assert_eq!(whole, pieces);
+        assert_eq!(whole, [0, 50, 100, 150, 200, 250, 300, 350, 400, 450, 500, 550, 600, 650, 700, 750]);
+
+        // A second's worth comes out as a second's worth, channels kept
+        // apart once past the silence before the first frame
+        let stereo: Vec<i16> = (0..44100).flat_map(|_| [1000, -1000]).collect();
+        let mut out = Vec::new();
+        let mut up = Resampler::new(44100, 48000, 2);
+        for chunk in stereo.chunks(4096) {
+            up.process(chunk, &mut out);
+        }
+        assert!((out.len() as i64 / 2 - 48000).abs() <= 1);
+        assert!(out[4..].chunks(2).all(|f| f == [1000, -1000]));
+    }
+}
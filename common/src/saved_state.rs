@@ -0,0 +1,367 @@
+//! Saved guest state (suspend to disk).
+//!
+//! With `general.save_state_on_exit` set, closing the window suspends the
+//! guest instead of stopping it: the card freezes and snapshots the guest,
+//! the snapshot is copied to `state_file()` and the session is stopped.
+//! The next session start finds the file, starts the session with
+//! `flags::RESUME`, writes the snapshot back and lets the guest carry on
+//! where it left off instead of booting it.
+//!
+//! The guest's hard disks are part of its state: the file records the
+//! images that were mounted, with their size and modification time at the
+//! moment the guest was frozen. A file whose disks have changed since is
+//! not resumed, as the guest would find its disks different from what its
+//! caches remember.
+//!
+//! State file layout (all integers little-endian):
+//!
+//! | Offset | Size | Contents                                   |
+//! |--------|------|--------------------------------------------|
+//! | 0      | 8    | `"SPCISTA\0"`                              |
+//! | 8      | 4    | version (1)                                |
+//! | 12     | 4    | snapshot format reported by the card       |
+//! | 16     | 8    | snapshot size                              |
+//! | 24     | 4    | number of disks                            |
+//! | 28     | ...  | disks: path length (4), path, size (8),    |
+//! |        |      | modification time in ns since the epoch (8)|
+//! | 4096   | ...  | snapshot                                   |
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::config::{AppConfig, StorageConfig};
+use crate::driver::{selected_card, DriverHandle};
+use crate::ioctl::{SplitU64, StateInfo};
+use crate::progress::ProgressReporter;
+
+/// State file signature
+pub const STATE_MAGIC: &[u8; 8] = b"SPCISTA\0";
+/// State file format version
+pub const STATE_VERSION: u32 = 1;
+/// Size of the header, which is also where the snapshot starts
+pub const HEADER_SIZE: u64 = 4096;
+/// Bytes moved between the card and the file at a time
+const BLOCK_SIZE: usize = 1024 * 1024;
+
+/// Default location of the selected card's saved state
+pub fn state_file() -> PathBuf {
+    match selected_card() {
+        0 => AppConfig::data_dir().join("saved-state.bin"),
+        card => AppConfig::data_dir().join(format!("saved-state-card{}.bin", card)),
+    }
+}
+
+/// Hard disk images a session with this storage mounts
+pub fn session_disks(storage: &StorageConfig) -> Vec<PathBuf> {
+    [&storage.primary_disk, &storage.secondary_disk]
+        .into_iter()
+        .flatten()
+        .map(|disk| disk.path.clone())
+        .collect()
+}
+
+/// A disk image as it was when the guest was frozen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskStamp {
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// Modification time in nanoseconds since the epoch
+    pub modified_ns: u64,
+}
+
+impl DiskStamp {
+    /// Stamp the image at `path` as it is now
+    pub fn of(path: &Path) -> io::Result<Self> {
+        let meta = fs::metadata(path)?;
+        let modified_ns = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Ok(Self {
+            path: path.to_path_buf(),
+            size: meta.len(),
+            modified_ns,
+        })
+    }
+}
+
+/// What a state file header says
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedState {
+    /// Snapshot format reported by the card
+    pub card_version: u32,
+    /// Snapshot size in bytes
+    pub size: u64,
+    /// Hard disks mounted when the guest was frozen
+    pub disks: Vec<DiskStamp>,
+}
+
+impl SavedState {
+    /// Whether `disks` are the images the guest was frozen with, unchanged
+    pub fn matches(&self, disks: &[PathBuf]) -> bool {
+        self.disks.len() == disks.len()
+            && self
+                .disks
+                .iter()
+                .zip(disks)
+                .all(|(stamp, path)| DiskStamp::of(path).is_ok_and(|now| &now == stamp))
+    }
+
+    /// Size and format to hand back to the card
+    fn info(&self) -> StateInfo {
+        StateInfo {
+            size: SplitU64::new(self.size),
+            version: self.card_version,
+            reserved: 0,
+        }
+    }
+}
+
+/// Read the header of the state file at `path`
+pub fn read_header(path: &Path) -> io::Result<SavedState> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    let mut header = vec![0u8; HEADER_SIZE as usize];
+    File::open(path)?.read_exact(&mut header)?;
+    if &header[..8] != STATE_MAGIC {
+        return Err(invalid("not a saved state file".to_string()));
+    }
+
+    let le32 = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let le64 = |at: usize| u64::from_le_bytes(header[at..at + 8].try_into().unwrap());
+    if le32(8) != STATE_VERSION {
+        return Err(invalid(format!("unsupported saved state version {}", le32(8))));
+    }
+
+    let mut disks = Vec::new();
+    let mut at = 28;
+    for _ in 0..le32(24) {
+        if at + 4 > header.len() {
+            return Err(invalid("truncated saved state header".to_string()));
+        }
+        let len = le32(at) as usize;
+        if at + 4 + len + 16 > header.len() {
+            return Err(invalid("truncated saved state header".to_string()));
+        }
+        let path = String::from_utf8_lossy(&header[at + 4..at + 4 + len]).into_owned();
+        at += 4 + len;
+        disks.push(DiskStamp {
+            path: PathBuf::from(path),
+            size: le64(at),
+            modified_ns: le64(at + 8),
+        });
+        at += 16;
+    }
+
+    Ok(SavedState {
+        card_version: le32(12),
+        size: le64(16),
+        disks,
+    })
+}
+
+/// Write the header for `state` to the start of `file`
+fn write_header(file: &File, state: &SavedState) -> io::Result<()> {
+    let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+    header.extend_from_slice(STATE_MAGIC);
+    header.extend_from_slice(&STATE_VERSION.to_le_bytes());
+    header.extend_from_slice(&state.card_version.to_le_bytes());
+    header.extend_from_slice(&state.size.to_le_bytes());
+    header.extend_from_slice(&(state.disks.len() as u32).to_le_bytes());
+    for disk in &state.disks {
+        let path = disk.path.as_os_str().as_encoded_bytes();
+        header.extend_from_slice(&(path.len() as u32).to_le_bytes());
+        header.extend_from_slice(path);
+        header.extend_from_slice(&disk.size.to_le_bytes());
+        header.extend_from_slice(&disk.modified_ns.to_le_bytes());
+    }
+    if header.len() > HEADER_SIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "disk paths too long for the saved state header",
+        ));
+    }
+    header.resize(HEADER_SIZE as usize, 0);
+    file.write_all_at(&header, 0)
+}
+
+/// Suspend the running guest and save its state to `path`
+///
+/// `disks` are the hard disk images the session mounts. On success the
+/// guest is left suspended and the caller stops the session; on failure
+/// (or cancellation) the guest is let go again.
+pub fn save_state(
+    handle: &DriverHandle,
+    disks: &[PathBuf],
+    path: &Path,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    progress.set_step("Suspending guest");
+    let info = handle.suspend_session().context("Failed to suspend the guest")?;
+
+    write_state_file(handle, &info, disks, path, progress).map_err(|e| {
+        // The card still holds the snapshot it just took
+        match handle.resume_session(&info) {
+            Ok(()) => e,
+            Err(resume) => anyhow!("{:#}; the guest could not run again: {}", e, resume),
+        }
+    })
+}
+
+/// Copy the suspended guest's snapshot to `path`, through a temporary file
+fn write_state_file(
+    handle: &DriverHandle,
+    info: &StateInfo,
+    disks: &[PathBuf],
+    path: &Path,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    // The guest is frozen, so its disks no longer change
+    let state = SavedState {
+        card_version: info.version,
+        size: info.size.get(),
+        disks: disks
+            .iter()
+            .map(|disk| DiskStamp::of(disk).with_context(|| format!("Cannot read {:?}", disk)))
+            .collect::<anyhow::Result<_>>()?,
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp = path.with_extension("tmp");
+    let result = (|| -> anyhow::Result<()> {
+        let mut file = File::create(&temp)?;
+        write_header(&file, &state)?;
+        file.set_len(HEADER_SIZE)?;
+
+        progress.set_step("Saving guest state");
+        progress.set_progress(0, state.size);
+        let mut buf = vec![0u8; BLOCK_SIZE];
+        let mut done = 0u64;
+        while done < state.size {
+            progress.check_cancelled()?;
+            let want = (state.size - done).min(BLOCK_SIZE as u64) as usize;
+            let read = handle.read_state(done, &mut buf[..want])?;
+            if read == 0 {
+                bail!("Guest state ended after {} of {} bytes", done, state.size);
+            }
+            file.write_all_at(&buf[..read], HEADER_SIZE + done)?;
+            done += read as u64;
+            progress.set_current(done);
+        }
+        file.flush()?;
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// Restore the guest saved at `path` into a session started with
+/// `flags::RESUME`, and let it run
+///
+/// Check `SavedState::matches` before starting the session.
+pub fn restore_state(
+    handle: &DriverHandle,
+    path: &Path,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    let state = read_header(path)?;
+    let file = File::open(path)?;
+    if file.metadata()?.len() < HEADER_SIZE + state.size {
+        bail!("Saved state is truncated");
+    }
+
+    progress.set_step("Restoring guest state");
+    progress.set_progress(0, state.size);
+    let mut buf = vec![0u8; BLOCK_SIZE];
+    let mut done = 0u64;
+    while done < state.size {
+        progress.check_cancelled()?;
+        let len = (state.size - done).min(BLOCK_SIZE as u64) as usize;
+        file.read_exact_at(&mut buf[..len], HEADER_SIZE + done)?;
+        handle.write_state(done, &buf[..len])?;
+        done += len as u64;
+        progress.set_current(done);
+    }
+
+    progress.set_step("Resuming guest");
+    handle
+        .resume_session(&state.info())
+        .context("The card could not load the saved state")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    fn saved(disks: &[PathBuf]) -> SavedState {
+        SavedState {
+            card_version: 3,
+            size: 8192,
+            disks: disks.iter().map(|d| DiskStamp::of(d).unwrap()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_header_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let disk = dir.path().join("c.img");
+        fs::write(&disk, vec![0u8; 1024]).unwrap();
+        let state = saved(std::slice::from_ref(&disk));
+
+        let path = dir.path().join("state.bin");
+        write_header(&File::create(&path).unwrap(), &state).unwrap();
+        assert_eq!(read_header(&path).unwrap(), state);
+        assert_eq!(state.info().size.get(), 8192);
+        assert_eq!(state.info().version, 3);
+
+        fs::write(&path, vec![0u8; HEADER_SIZE as usize]).unwrap();
+        assert_eq!(read_header(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = dir.path().join("c.img");
+        let d = dir.path().join("d.img");
+        fs::write(&c, vec![0u8; 1024]).unwrap();
+        fs::write(&d, vec![0u8; 2048]).unwrap();
+        let state = saved(&[c.clone(), d.clone()]);
+
+        assert!(state.matches(&[c.clone(), d.clone()]));
+        // Different disks, or a disk missing
+        assert!(!state.matches(&[d.clone(), c.clone()]));
+        assert!(!state.matches(std::slice::from_ref(&c)));
+
+        // The guest's disk was written to since
+        let mut file = OpenOptions::new().append(true).open(&d).unwrap();
+        file.write_all(&[1]).unwrap();
+        assert!(!state.matches(&[c, d]));
+    }
+
+    #[test]
+    fn test_header_too_long() {
+        let dir = tempfile::tempdir().unwrap();
+        let long = PathBuf::from("/".repeat(HEADER_SIZE as usize));
+        let state = SavedState {
+            card_version: 1,
+            size: 0,
+            disks: vec![DiskStamp { path: long, size: 0, modified_ns: 0 }],
+        };
+        let file = File::create(dir.path().join("state.bin")).unwrap();
+        assert!(write_header(&file, &state).is_err());
+    }
+}
@@ -0,0 +1,127 @@
+//! Where the guest display goes in the host window.
+//!
+//! `target_rect` applies the display settings: the `ScalingMode`, and for
+//! `Fit` the aspect ratio and integer scaling options. The rect is centered
+//! in the window; at 1:1 or a fixed scale it may be larger than the window,
+//! in which case its origin is negative and the edges are cut off.
+//!
+//! Keeping the aspect ratio means the shape the mode had on a 4:3 monitor
+//! (`display_aspect`), so the frontend places the display again whenever
+//! the guest changes mode.
+
+use crate::config::{DisplayConfig, ScalingMode};
+
+/// Rectangle in window coordinates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TargetRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl TargetRect {
+    /// Rect of the given size centered in the window
+    fn centered(width: f64, height: f64, window_w: u32, window_h: u32) -> Self {
+        let width = width.round() as i32;
+        let height = height.round() as i32;
+        Self {
+            x: (window_w as i32 - width) / 2,
+            y: (window_h as i32 - height) / 2,
+            width,
+            height,
+        }
+    }
+}
+
+/// Modes drawn with non-square pixels, filling a 4:3 screen
+const NON_SQUARE_MODES: &[(u32, u32)] = &[
+    (320, 200),
+    (640, 200),
+    (640, 350),
+    (640, 400),
+    (720, 350),
+    (720, 400),
+];
+
+/// Width over height of a mode as it appeared on a monitor
+pub fn display_aspect(source_w: u32, source_h: u32) -> f64 {
+    if NON_SQUARE_MODES.contains(&(source_w, source_h)) {
+        4.0 / 3.0
+    } else {
+        source_w as f64 / source_h as f64
+    }
+}
+
+/// Place a `source_w` x `source_h` display in a `window_w` x `window_h` window
+pub fn target_rect(
+    display: &DisplayConfig,
+    source_w: u32,
+    source_h: u32,
+    window_w: u32,
+    window_h: u32,
+) -> TargetRect {
+    if source_w == 0 || source_h == 0 {
+        return TargetRect::default();
+    }
+    let scaled = |scale: f64| {
+        TargetRect::centered(source_w as f64 * scale, source_h as f64 * scale, window_w, window_h)
+    };
+    let x_ratio = window_w as f64 / source_w as f64;
+    let y_ratio = window_h as f64 / source_h as f64;
+
+    match display.scaling_mode {
+        ScalingMode::None => scaled(1.0),
+        ScalingMode::Fixed(factor) => scaled(factor.max(1) as f64),
+        ScalingMode::Stretch => TargetRect { x: 0, y: 0, width: window_w as i32, height: window_h as i32 },
+        ScalingMode::Fit if display.integer_scaling => {
+            // The largest whole multiple that fits, but never below 1:1
+            scaled(x_ratio.min(y_ratio).floor().max(1.0))
+        }
+        ScalingMode::Fit if display.maintain_aspect_ratio => {
+            let aspect = display_aspect(source_w, source_h);
+            let height = (window_w as f64 / aspect).min(window_h as f64);
+            TargetRect::centered(height * aspect, height, window_w, window_h)
+        }
+        ScalingMode::Fit => TargetRect { x: 0, y: 0, width: window_w as i32, height: window_h as i32 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_rect() {
+        let rect = |x, y, width, height| TargetRect { x, y, width, height };
+        let mut display = DisplayConfig::default();
+
+        // Fit with the aspect ratio kept: letterboxed left and right
+        display.scaling_mode = ScalingMode::Fit;
+        assert_eq!(target_rect(&display, 640, 480, 1000, 600), rect(100, 0, 800, 600));
+        // Text mode is shown 4:3 like on a CRT, not 9:5
+        assert_eq!(target_rect(&display, 720, 400, 1000, 600), rect(100, 0, 800, 600));
+        assert_eq!(target_rect(&display, 1280, 1024, 1000, 1000), rect(0, 100, 1000, 800));
+        // Integer scaling: 2x, not 2.5x
+        display.integer_scaling = true;
+        assert_eq!(target_rect(&display, 640, 480, 1600, 1300), rect(160, 170, 1280, 960));
+        // ...but never smaller than 1:1
+        assert_eq!(target_rect(&display, 640, 480, 600, 400), rect(-20, -40, 640, 480));
+        // Neither: fill the window
+        display.integer_scaling = false;
+        display.maintain_aspect_ratio = false;
+        assert_eq!(target_rect(&display, 640, 480, 1000, 600), rect(0, 0, 1000, 600));
+
+        display.scaling_mode = ScalingMode::None;
+        assert_eq!(target_rect(&display, 720, 400, 1000, 600), rect(140, 100, 720, 400));
+        display.scaling_mode = ScalingMode::Fixed(3);
+        assert_eq!(target_rect(&display, 320, 200, 1000, 600), rect(20, 0, 960, 600));
+        display.scaling_mode = ScalingMode::Fixed(0);
+        assert_eq!(target_rect(&display, 320, 200, 320, 200), rect(0, 0, 320, 200));
+        display.scaling_mode = ScalingMode::Stretch;
+        display.maintain_aspect_ratio = true;
+        assert_eq!(target_rect(&display, 640, 480, 1000, 600), rect(0, 0, 1000, 600));
+
+        assert_eq!(target_rect(&display, 0, 480, 1000, 600), TargetRect::default());
+    }
+}
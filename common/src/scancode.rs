@@ -0,0 +1,581 @@
+//! Translation of host key codes to guest XT (scan code set 1) scancodes.
+//!
+//! Key codes are Qt::Key values, so any frontend that reports keys the way
+//! Qt does can use this without linking Qt. Native X11/evdev key codes are
+//! preferred when the frontend has them.
+//!
+//! Text can also be turned into key strokes (`text_keystrokes`, or
+//! `layout_keystrokes` for a guest keyboard layout other than US), to type
+//! it into guests that have no clipboard integration, such as a DOS prompt.
+//! Keystroke macros (`macro_keystrokes`) add named keys and pauses, for
+//! answering boot menus.
+
+/// Convert a Qt key code to an XT scancode.
+/// Returns (scancode, is_extended).
+/// Returns (0, false) if the key is not mappable.
+pub fn qt_key_to_scancode(qt_key: i32, native_scancode: i32) -> (u32, bool) {
+    // If we have a valid native scancode from the system, prefer it
+    // Linux evdev scancodes are offset by 8 from XT scancodes
+    if native_scancode > 8 {
+        let xt = (native_scancode - 8) as u32;
+        // Extended keys have high bit set in certain ranges
+        let extended = xt > 0x7F || is_extended_key(qt_key);
+        return (xt & 0x7F, extended);
+    }
+
+    // Fall back to Qt key code mapping
+    qt_key_to_xt_scancode(qt_key)
+}
+
+/// Check if a Qt key is an extended key
+pub fn is_extended_key(qt_key: i32) -> bool {
+    matches!(
+        qt_key,
+        0x01000010  // Key_Home
+        | 0x01000011  // Key_End
+        | 0x01000012  // Key_Left
+        | 0x01000013  // Key_Up
+        | 0x01000014  // Key_Right
+        | 0x01000015  // Key_Down
+        | 0x01000016  // Key_PageUp
+        | 0x01000017  // Key_PageDown
+        | 0x01000006  // Key_Insert
+        | 0x01000007  // Key_Delete
+        | 0x01000025  // Key_Print
+        | 0x01000026  // Key_ScrollLock (sometimes)
+        | 0x01000027  // Key_Pause
+    )
+}
+
+/// Map Qt key constants to XT scancodes
+/// This is a fallback when native scancodes aren't available
+pub fn qt_key_to_xt_scancode(qt_key: i32) -> (u32, bool) {
+    // Qt key constants (from Qt::Key enum)
+    match qt_key {
+        // Escape
+        0x01000000 => (0x01, false), // Key_Escape
+        
+        // Function keys
+        0x01000030 => (0x3B, false), // Key_F1
+        0x01000031 => (0x3C, false), // Key_F2
+        0x01000032 => (0x3D, false), // Key_F3
+        0x01000033 => (0x3E, false), // Key_F4
+        0x01000034 => (0x3F, false), // Key_F5
+        0x01000035 => (0x40, false), // Key_F6
+        0x01000036 => (0x41, false), // Key_F7
+        0x01000037 => (0x42, false), // Key_F8
+        0x01000038 => (0x43, false), // Key_F9
+        0x01000039 => (0x44, false), // Key_F10
+        0x0100003a => (0x57, false), // Key_F11
+        0x0100003b => (0x58, false), // Key_F12
+
+        // Number row
+        0x31 => (0x02, false), // 1
+        0x32 => (0x03, false), // 2
+        0x33 => (0x04, false), // 3
+        0x34 => (0x05, false), // 4
+        0x35 => (0x06, false), // 5
+        0x36 => (0x07, false), // 6
+        0x37 => (0x08, false), // 7
+        0x38 => (0x09, false), // 8
+        0x39 => (0x0A, false), // 9
+        0x30 => (0x0B, false), // 0
+        0x2D => (0x0C, false), // -
+        0x3D => (0x0D, false), // =
+
+        // Backspace, Tab, Enter
+        0x01000003 => (0x0E, false), // Key_Backspace
+        0x01000001 => (0x0F, false), // Key_Tab
+        0x01000004 | 0x01000005 => (0x1C, false), // Key_Return / Key_Enter
+
+        // Letter keys (uppercase ASCII)
+        0x51 => (0x10, false), // Q
+        0x57 => (0x11, false), // W
+        0x45 => (0x12, false), // E
+        0x52 => (0x13, false), // R
+        0x54 => (0x14, false), // T
+        0x59 => (0x15, false), // Y
+        0x55 => (0x16, false), // U
+        0x49 => (0x17, false), // I
+        0x4F => (0x18, false), // O
+        0x50 => (0x19, false), // P
+        0x5B => (0x1A, false), // [
+        0x5D => (0x1B, false), // ]
+
+        0x41 => (0x1E, false), // A
+        0x53 => (0x1F, false), // S
+        0x44 => (0x20, false), // D
+        0x46 => (0x21, false), // F
+        0x47 => (0x22, false), // G
+        0x48 => (0x23, false), // H
+        0x4A => (0x24, false), // J
+        0x4B => (0x25, false), // K
+        0x4C => (0x26, false), // L
+        0x3B => (0x27, false), // ;
+        0x27 => (0x28, false), // '
+        0x60 => (0x29, false), // ` (backtick)
+        0x5C => (0x2B, false), // \
+
+        0x5A => (0x2C, false), // Z
+        0x58 => (0x2D, false), // X
+        0x43 => (0x2E, false), // C
+        0x56 => (0x2F, false), // V
+        0x42 => (0x30, false), // B
+        0x4E => (0x31, false), // N
+        0x4D => (0x32, false), // M
+        0x2C => (0x33, false), // ,
+        0x2E => (0x34, false), // .
+        0x2F => (0x35, false), // /
+
+        // Modifier keys
+        0x01000020 => (0x2A, false), // Key_Shift (left)
+        0x01000021 => (0x1D, false), // Key_Control (left)
+        0x01000023 => (0x38, false), // Key_Alt (left)
+        0x01000022 => (0x3A, false), // Key_CapsLock
+        
+        // Space
+        0x20 => (0x39, false), // Space
+
+        // Navigation keys (extended)
+        0x01000010 => (0x47, true), // Key_Home
+        0x01000011 => (0x4F, true), // Key_End
+        0x01000016 => (0x49, true), // Key_PageUp
+        0x01000017 => (0x51, true), // Key_PageDown
+        0x01000012 => (0x4B, true), // Key_Left
+        0x01000014 => (0x4D, true), // Key_Right
+        0x01000013 => (0x48, true), // Key_Up
+        0x01000015 => (0x50, true), // Key_Down
+        0x01000006 => (0x52, true), // Key_Insert
+        0x01000007 => (0x53, true), // Key_Delete
+
+        // Numpad
+        0x01000024 => (0x45, false), // Key_NumLock
+        
+        // Print Screen, Scroll Lock, Pause
+        0x01000025 => (0x37, true),  // Key_Print (SysRq)
+        0x01000026 => (0x46, false), // Key_ScrollLock
+        0x01000027 => (0x45, true),  // Key_Pause
+
+        _ => (0, false), // Unknown key
+    }
+}
+
+/// Scancode of the left Shift key
+const LEFT_SHIFT: u32 = 0x2A;
+
+/// Scancodes of the left Ctrl and Alt keys; AltGr is typed as Ctrl+Alt,
+/// which DOS KEYB and Windows both take for it, so no extended keys are
+/// needed
+const LEFT_CTRL: u32 = 0x1D;
+const LEFT_ALT: u32 = 0x38;
+
+/// Scancode of the 102nd key, left of Z on ISO keyboards
+const ISO_KEY: u32 = 0x56;
+
+/// A key press or release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyStroke {
+    pub scancode: u32,
+    pub pressed: bool,
+}
+
+/// Longest pause a keystroke macro may ask for, in milliseconds
+pub const MAX_MACRO_WAIT_MS: u32 = 10 * 60 * 1000;
+
+/// One step of a keystroke macro
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MacroStep {
+    /// Key strokes sent together
+    Keys(Vec<KeyStroke>),
+    /// A pause, in milliseconds
+    Wait(u32),
+}
+
+/// What a guest keyboard layout types with each key
+///
+/// `unshifted` and `shifted` hold the characters of scancodes 0x01 to 0x35
+/// in order ('\0' where a key types none). Characters typed with AltGr or
+/// with the 102nd key are listed apart, and `dead` are dead keys, which
+/// need a Space after them to type the accent itself.
+struct Layout {
+    unshifted: &'static str,
+    shifted: &'static str,
+    altgr: &'static [(char, u32)],
+    iso: [char; 2],
+    dead: &'static str,
+}
+
+/// How a character is typed on a layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct LayoutKey {
+    scancode: u32,
+    shift: bool,
+    altgr: bool,
+    dead: bool,
+    /// Whether Caps Lock changes it (a letter with its other case on Shift)
+    letter: bool,
+}
+
+const US: Layout = Layout {
+    unshifted: "\x001234567890-=\x08\tqwertyuiop[]\n\x00asdfghjkl;'`\x00\\zxcvbnm,./",
+    shifted: "\x00!@#$%^&*()_+\x00\x00QWERTYUIOP{}\x00\x00ASDFGHJKL:\"~\x00|ZXCVBNM<>?",
+    altgr: &[],
+    iso: ['\0', '\0'],
+    dead: "",
+};
+
+const UK: Layout = Layout {
+    unshifted: "\x001234567890-=\x08\tqwertyuiop[]\n\x00asdfghjkl;'`\x00#zxcvbnm,./",
+    shifted: "\x00!\"£$%^&*()_+\x00\x00QWERTYUIOP{}\x00\x00ASDFGHJKL:@¬\x00~ZXCVBNM<>?",
+    altgr: &[('€', 0x05)],
+    iso: ['\\', '|'],
+    dead: "",
+};
+
+const DE: Layout = Layout {
+    unshifted: "\x001234567890ß´\x08\tqwertzuiopü+\n\x00asdfghjklöä^\x00#yxcvbnm,.-",
+    shifted: "\x00!\"§$%&/()=?`\x00\x00QWERTZUIOPÜ*\x00\x00ASDFGHJKLÖÄ°\x00'YXCVBNM;:_",
+    altgr: &[
+        ('²', 0x03),
+        ('³', 0x04),
+        ('{', 0x08),
+        ('[', 0x09),
+        (']', 0x0A),
+        ('}', 0x0B),
+        ('\\', 0x0C),
+        ('@', 0x10),
+        ('€', 0x12),
+        ('~', 0x1B),
+        ('µ', 0x32),
+        ('|', ISO_KEY),
+    ],
+    iso: ['<', '>'],
+    dead: "´`^",
+};
+
+const FR: Layout = Layout {
+    unshifted: "\x00&é\"'(-è_çà)=\x08\tazertyuiop^$\n\x00qsdfghjklmù²\x00*wxcvbn,;:!",
+    shifted: "\x001234567890°+\x00\x00AZERTYUIOP¨£\x00\x00QSDFGHJKLM%\x00\x00µWXCVBN?./§",
+    altgr: &[
+        ('~', 0x03),
+        ('#', 0x04),
+        ('{', 0x05),
+        ('[', 0x06),
+        ('|', 0x07),
+        ('`', 0x08),
+        ('\\', 0x09),
+        ('@', 0x0B),
+        (']', 0x0C),
+        ('}', 0x0D),
+        ('€', 0x12),
+    ],
+    iso: ['<', '>'],
+    dead: "^¨~`",
+};
+
+/// Guest keyboard layout for a `[keyboard] layout` code
+fn layout(code: &str) -> Option<&'static Layout> {
+    match code.to_ascii_lowercase().as_str() {
+        "us" => Some(&US),
+        "uk" | "gb" => Some(&UK),
+        "de" | "gr" => Some(&DE),
+        "fr" => Some(&FR),
+        _ => None,
+    }
+}
+
+/// Whether text can be typed for a guest keyboard layout; others are typed
+/// as on a US keyboard
+pub fn has_layout(code: &str) -> bool {
+    layout(code).is_some()
+}
+
+impl Layout {
+    fn find(&self, c: char) -> Option<LayoutKey> {
+        if c == ' ' {
+            return Some(LayoutKey { scancode: 0x39, shift: false, altgr: false, dead: false, letter: false });
+        }
+        if c == '\0' {
+            return None;
+        }
+        let dead = self.dead.contains(c);
+        let key = |scancode, shift, altgr, letter| LayoutKey { scancode, shift, altgr, dead, letter };
+
+        // Index 0 stands for scancode 0x01 (Escape)
+        let position = |table: &str| table.chars().position(|t| t == c);
+        let other_case = |table: &str, index: usize| {
+            table.chars().nth(index).is_some_and(|o| o != c && o.to_lowercase().eq(c.to_lowercase()))
+        };
+        if let Some(index) = position(self.unshifted) {
+            return Some(key(index as u32 + 1, false, false, other_case(self.shifted, index)));
+        }
+        if let Some(index) = position(self.shifted) {
+            return Some(key(index as u32 + 1, true, false, other_case(self.unshifted, index)));
+        }
+        if let Some(&(_, scancode)) = self.altgr.iter().find(|&&(a, _)| a == c) {
+            return Some(key(scancode, false, true, false));
+        }
+        let iso = self.iso.iter().position(|&i| i == c && i != '\0')?;
+        Some(key(ISO_KEY, iso == 1, false, false))
+    }
+}
+
+/// Scancode and Shift state that type a character on a US keyboard
+pub fn char_to_scancode(c: char) -> Option<(u32, bool)> {
+    US.find(c).map(|key| (key.scancode, key.shift))
+}
+
+/// Key strokes that type `text` on a US keyboard, one group per character
+///
+/// With `caps_lock` on in the guest, Shift is inverted for letters. Line
+/// breaks (LF, CRLF or CR) become Enter. Returns the groups and the number
+/// of characters that have no key and were left out.
+pub fn text_keystrokes(text: &str, caps_lock: bool) -> (Vec<Vec<KeyStroke>>, usize) {
+    layout_keystrokes(text, "us", caps_lock)
+}
+
+/// Key strokes that type `text` with the guest keyboard layout `layout`
+/// (a `[keyboard] layout` code), as `text_keystrokes` does for US
+///
+/// Characters on AltGr are typed as Ctrl+Alt, and dead keys are followed
+/// by Space. A layout without a table is typed as US.
+pub fn layout_keystrokes(text: &str, layout_code: &str, caps_lock: bool) -> (Vec<Vec<KeyStroke>>, usize) {
+    let layout = layout(layout_code).unwrap_or(&US);
+    let mut groups = Vec::new();
+    let mut skipped = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(mut c) = chars.next() {
+        if c == '\r' {
+            chars.next_if_eq(&'\n');
+            c = '\n';
+        }
+        let Some(key) = layout.find(c) else {
+            skipped += 1;
+            continue;
+        };
+        let shift = key.shift != (caps_lock && key.letter);
+
+        let mut modifiers = Vec::with_capacity(2);
+        if shift {
+            modifiers.push(LEFT_SHIFT);
+        }
+        if key.altgr {
+            modifiers.extend([LEFT_CTRL, LEFT_ALT]);
+        }
+        let mut group = Vec::with_capacity(modifiers.len() * 2 + 4);
+        group.extend(modifiers.iter().map(|&scancode| KeyStroke { scancode, pressed: true }));
+        group.push(KeyStroke { scancode: key.scancode, pressed: true });
+        group.push(KeyStroke { scancode: key.scancode, pressed: false });
+        group.extend(modifiers.iter().rev().map(|&scancode| KeyStroke { scancode, pressed: false }));
+        if key.dead {
+            group.push(KeyStroke { scancode: 0x39, pressed: true });
+            group.push(KeyStroke { scancode: 0x39, pressed: false });
+        }
+        groups.push(group);
+    }
+    (groups, skipped)
+}
+
+/// Scancode of a key named in a keystroke macro (case is ignored)
+fn named_key_scancode(name: &str) -> Option<u32> {
+    let name = name.to_ascii_lowercase();
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<u32>().ok()) {
+        return match n {
+            1..=10 => Some(0x3A + n),
+            11 => Some(0x57),
+            12 => Some(0x58),
+            _ => None,
+        };
+    }
+    match name.as_str() {
+        "enter" => Some(0x1C),
+        "esc" | "escape" => Some(0x01),
+        "tab" => Some(0x0F),
+        "backspace" => Some(0x0E),
+        "space" => Some(0x39),
+        _ => None,
+    }
+}
+
+/// Key strokes of a keystroke macro for the guest keyboard layout
+/// `layout_code`, one group per key
+///
+/// A macro is text typed as with `layout_keystrokes`, with keys named in
+/// braces (`{Enter}`, `{Esc}`, `{Tab}`, `{Backspace}`, `{Space}`, `{F1}` to
+/// `{F12}`) and pauses in milliseconds (`{Wait 2000}`); `{{` types a brace.
+/// Pauses longer than `MAX_MACRO_WAIT_MS` and characters with no key are
+/// errors, as a macro typed partly would answer a menu wrongly.
+pub fn macro_keystrokes(spec: &str, layout_code: &str, caps_lock: bool) -> Result<Vec<MacroStep>, String> {
+    let mut groups = Vec::new();
+    let mut rest = spec;
+    while !rest.is_empty() {
+        let (text, token) = match rest.find('{') {
+            Some(start) if rest[start..].starts_with("{{") => {
+                let (text, _) = layout_keystrokes(&rest[..=start], layout_code, caps_lock);
+                groups.extend(text.into_iter().map(MacroStep::Keys));
+                rest = &rest[start + 2..];
+                continue;
+            }
+            Some(start) => {
+                let end = rest[start..]
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed '{{' in \"{}\"", spec))?;
+                let token = &rest[start + 1..start + end];
+                let text = &rest[..start];
+                rest = &rest[start + end + 1..];
+                (text, Some(token))
+            }
+            None => {
+                let text = rest;
+                rest = "";
+                (text, None)
+            }
+        };
+
+        let (text, skipped) = layout_keystrokes(text, layout_code, caps_lock);
+        if skipped > 0 {
+            return Err(format!("\"{}\" has characters with no key on the guest keyboard", spec));
+        }
+        groups.extend(text.into_iter().map(MacroStep::Keys));
+
+        let Some(token) = token.map(str::trim) else {
+            continue;
+        };
+        let mut words = token.split_whitespace();
+        if words.next().is_some_and(|word| word.eq_ignore_ascii_case("wait")) {
+            let ms: u64 = words
+                .next()
+                .and_then(|ms| ms.parse().ok())
+                .ok_or_else(|| format!("{{{}}} needs a time in milliseconds", token))?;
+            let ms = u32::try_from(ms)
+                .ok()
+                .filter(|&ms| ms <= MAX_MACRO_WAIT_MS)
+                .ok_or_else(|| format!("{{{}}} is longer than {} ms", token, MAX_MACRO_WAIT_MS))?;
+            groups.push(MacroStep::Wait(ms));
+        } else {
+            let scancode = named_key_scancode(token).ok_or_else(|| format!("unknown key {{{}}}", token))?;
+            groups.push(MacroStep::Keys(vec![
+                KeyStroke { scancode, pressed: true },
+                KeyStroke { scancode, pressed: false },
+            ]));
+        }
+    }
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qt_key_to_scancode() {
+        // Fallback table
+        assert_eq!(qt_key_to_scancode(0x41, 0), (0x1E, false)); // A
+        assert_eq!(qt_key_to_scancode(0x01000013, 0), (0x48, true)); // Up
+        assert_eq!(qt_key_to_scancode(0x01001234, 0), (0, false));
+
+        // Native X11 key code 38 is A (evdev 30)
+        assert_eq!(qt_key_to_scancode(0x41, 38), (0x1E, false));
+    }
+
+    #[test]
+    fn test_text_keystrokes() {
+        assert_eq!(char_to_scancode('a'), Some((0x1E, false)));
+        assert_eq!(char_to_scancode('A'), Some((0x1E, true)));
+        assert_eq!(char_to_scancode('1'), Some((0x02, false)));
+        assert_eq!(char_to_scancode('?'), Some((0x35, true)));
+        assert_eq!(char_to_scancode('\\'), Some((0x2B, false)));
+        assert_eq!(char_to_scancode('\n'), Some((0x1C, false)));
+        assert_eq!(char_to_scancode('é'), None);
+
+        let press = |scancode| KeyStroke { scancode, pressed: true };
+        let release = |scancode| KeyStroke { scancode, pressed: false };
+        let (groups, skipped) = text_keystrokes("Aé\r\n", false);
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            groups,
+            [
+                vec![press(LEFT_SHIFT), press(0x1E), release(0x1E), release(LEFT_SHIFT)],
+                vec![press(0x1C), release(0x1C)],
+            ]
+        );
+
+        // Caps Lock inverts Shift for letters only
+        let (groups, _) = text_keystrokes("A!", true);
+        assert_eq!(groups[0], [press(0x1E), release(0x1E)]);
+        assert_eq!(groups[1].len(), 4);
+    }
+
+    #[test]
+    fn test_layout_keystrokes() {
+        let press = |scancode| KeyStroke { scancode, pressed: true };
+        let release = |scancode| KeyStroke { scancode, pressed: false };
+
+        // German: Y and Z swap places, @ is AltGr+Q, ^ is a dead key
+        let (groups, skipped) = layout_keystrokes("zY@^", "de", false);
+        assert_eq!(skipped, 0);
+        assert_eq!(groups[0], [press(0x15), release(0x15)]);
+        assert_eq!(groups[1], [press(LEFT_SHIFT), press(0x2C), release(0x2C), release(LEFT_SHIFT)]);
+        assert_eq!(
+            groups[2],
+            [press(LEFT_CTRL), press(LEFT_ALT), press(0x10), release(0x10), release(LEFT_ALT), release(LEFT_CTRL)]
+        );
+        assert_eq!(groups[3], [press(0x29), release(0x29), press(0x39), release(0x39)]);
+
+        // Caps Lock changes umlauts as well, and not digits
+        let (groups, _) = layout_keystrokes("ü1", "de", true);
+        assert_eq!(groups[0][0], press(LEFT_SHIFT));
+        assert_eq!(groups[1], [press(0x02), release(0x02)]);
+
+        // French digits are on Shift; UK puts \\ on the 102nd key
+        let (groups, _) = layout_keystrokes("1a", "fr", false);
+        assert_eq!(groups[0], [press(LEFT_SHIFT), press(0x02), release(0x02), release(LEFT_SHIFT)]);
+        assert_eq!(groups[1], [press(0x10), release(0x10)]);
+        let (groups, _) = layout_keystrokes("\\\"", "uk", false);
+        assert_eq!(groups[0], [press(ISO_KEY), release(ISO_KEY)]);
+        assert_eq!(groups[1][1], press(0x03));
+
+        // Layouts without a table type as US
+        assert!(!has_layout("sv"));
+        assert_eq!(layout_keystrokes("@", "sv", false), text_keystrokes("@", false));
+    }
+
+    #[test]
+    fn test_macro_keystrokes() {
+        let press = |scancode| KeyStroke { scancode, pressed: true };
+        let release = |scancode| KeyStroke { scancode, pressed: false };
+        let keys = |strokes: &[KeyStroke]| MacroStep::Keys(strokes.to_vec());
+
+        let steps = macro_keystrokes("{F8}{wait 120}3{Enter}", "us", false).unwrap();
+        assert_eq!(
+            steps,
+            [
+                keys(&[press(0x42), release(0x42)]),
+                MacroStep::Wait(120),
+                keys(&[press(0x04), release(0x04)]),
+                keys(&[press(0x1C), release(0x1C)]),
+            ]
+        );
+
+        let steps = macro_keystrokes("a{{b", "us", false).unwrap();
+        assert_eq!(steps.len(), 3);
+        assert_eq!(steps[1], keys(&layout_keystrokes("{", "us", false).0[0]));
+
+        // Typed with the guest's layout: Z is where US has Y
+        let steps = macro_keystrokes("z{Enter}", "de", false).unwrap();
+        assert_eq!(steps[0], keys(&layout_keystrokes("z", "de", false).0[0]));
+        assert_eq!(steps[0], keys(&[press(0x15), release(0x15)]));
+        assert!(macro_keystrokes("é", "de", false).is_err());
+
+        assert!(macro_keystrokes("{F13}", "us", false).is_err());
+        assert!(macro_keystrokes("{Enter", "us", false).is_err());
+        assert!(macro_keystrokes("{Wait}", "us", false).is_err());
+        assert!(macro_keystrokes("é", "us", false).is_err());
+
+        // Pauses are one step however long, up to a limit
+        let longest = format!("{{Wait {}}}", MAX_MACRO_WAIT_MS);
+        assert_eq!(macro_keystrokes(&longest, "us", false).unwrap(), [MacroStep::Wait(MAX_MACRO_WAIT_MS)]);
+        assert!(macro_keystrokes("{Wait 600001}", "us", false).is_err());
+        assert!(macro_keystrokes("{Wait 99999999999999}", "us", false).is_err());
+    }
+}
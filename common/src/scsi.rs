@@ -2,6 +2,12 @@
 //!
 //! This module provides SCSI-2/MMC-2 command definitions for virtual CD-ROM
 //! emulation. The implementation focuses on ISO 9660 image file support.
+//!
+//! Discs with Red Book audio tracks (CUE/BIN images) are described by a
+//! multi-track `Toc`, and `CdAudio` implements the audio commands against
+//! it: PLAY AUDIO(10/12/MSF), PAUSE/RESUME, STOP PLAY/SCAN and READ
+//! SUB-CHANNEL. `CdAudio` only tracks the play position; the caller pulls
+//! the sectors to output with `CdAudio::advance` in real time.
 
 // ============================================================================
 // SCSI Command Opcodes (SPC-2 / MMC-2)
@@ -17,6 +23,8 @@ pub mod opcode {
     pub const INQUIRY: u8 = 0x12;
     /// Return mode parameters
     pub const MODE_SENSE_6: u8 = 0x1A;
+    /// Spin up/down, load or eject the medium
+    pub const START_STOP_UNIT: u8 = 0x1B;
     /// Prevent/allow medium removal
     pub const PREVENT_ALLOW_MEDIUM_REMOVAL: u8 = 0x1E;
     /// Return logical block address capacity
@@ -43,6 +51,18 @@ pub mod opcode {
     pub const MECHANISM_STATUS: u8 = 0xBD;
     /// Read CD (MMC)
     pub const READ_CD: u8 = 0xBE;
+    /// Read sub-channel (current audio position)
+    pub const READ_SUB_CHANNEL: u8 = 0x42;
+    /// Play audio, LBA start and 16-bit length
+    pub const PLAY_AUDIO_10: u8 = 0x45;
+    /// Play audio, MSF start and end
+    pub const PLAY_AUDIO_MSF: u8 = 0x47;
+    /// Pause or resume audio play
+    pub const PAUSE_RESUME: u8 = 0x4B;
+    /// Stop audio play or scan
+    pub const STOP_PLAY_SCAN: u8 = 0x4E;
+    /// Play audio, LBA start and 32-bit length
+    pub const PLAY_AUDIO_12: u8 = 0xA5;
 }
 
 // ============================================================================
@@ -107,6 +127,8 @@ pub mod asc {
     pub const NO_ADDITIONAL_SENSE: u8 = 0x00;
     /// Logical unit not ready, cause not reportable
     pub const LUN_NOT_READY: u8 = 0x04;
+    /// Unrecovered read error
+    pub const UNRECOVERED_READ_ERROR: u8 = 0x11;
     /// Medium not present
     pub const MEDIUM_NOT_PRESENT: u8 = 0x3A;
     /// Invalid command operation code
@@ -121,6 +143,10 @@ pub mod asc {
     pub const PARAMETERS_CHANGED: u8 = 0x2A;
     /// Not ready to ready transition (medium may have changed)
     pub const MEDIUM_MAY_HAVE_CHANGED: u8 = 0x28;
+    /// Command sequence error (e.g. RESUME without a paused play)
+    pub const COMMAND_SEQUENCE_ERROR: u8 = 0x2C;
+    /// Illegal mode for this track (e.g. PLAY AUDIO on a data track)
+    pub const ILLEGAL_MODE_FOR_TRACK: u8 = 0x64;
 }
 
 /// Additional Sense Code Qualifiers
@@ -177,6 +203,26 @@ pub mod mode_page {
     pub const ALL_PAGES: u8 = 0x3F;
 }
 
+// ============================================================================
+// Audio Status (READ SUB-CHANNEL)
+// ============================================================================
+
+/// Audio status byte in the READ SUB-CHANNEL header
+pub mod audio_status {
+    /// Audio status not supported or not valid
+    pub const INVALID: u8 = 0x00;
+    /// Play operation in progress
+    pub const PLAY_IN_PROGRESS: u8 = 0x11;
+    /// Play operation paused
+    pub const PAUSED: u8 = 0x12;
+    /// Play operation completed successfully
+    pub const COMPLETED: u8 = 0x13;
+    /// Play operation stopped due to error
+    pub const ERROR: u8 = 0x14;
+    /// No current audio status to return
+    pub const NO_STATUS: u8 = 0x15;
+}
+
 // ============================================================================
 // Data Structures
 // ============================================================================
@@ -273,6 +319,33 @@ impl SenseData {
         )
     }
 
+    /// Create "illegal request, illegal mode for this track" sense data
+    pub fn illegal_mode_for_track() -> Self {
+        Self::new(
+            sense_key::ILLEGAL_REQUEST,
+            asc::ILLEGAL_MODE_FOR_TRACK,
+            ascq::NONE,
+        )
+    }
+
+    /// Create "illegal request, command sequence error" sense data
+    pub fn command_sequence_error() -> Self {
+        Self::new(
+            sense_key::ILLEGAL_REQUEST,
+            asc::COMMAND_SEQUENCE_ERROR,
+            ascq::NONE,
+        )
+    }
+
+    /// Create "medium error, unrecovered read error" sense data
+    pub fn read_error() -> Self {
+        Self::new(
+            sense_key::MEDIUM_ERROR,
+            asc::UNRECOVERED_READ_ERROR,
+            ascq::NONE,
+        )
+    }
+
     /// Serialize sense data to a buffer
     pub fn to_bytes(&self) -> [u8; Self::SIZE] {
         let mut buf = [0u8; Self::SIZE];
@@ -444,6 +517,14 @@ impl TocEntry {
         }
     }
 
+    /// Create an audio track entry
+    pub fn audio_track(track_number: u8, start_lba: u32) -> Self {
+        Self {
+            adr_control: 0x10, // ADR=1 (Q sub-channel), Control=0 (2-channel audio)
+            ..Self::data_track(track_number, start_lba)
+        }
+    }
+
     /// Create a lead-out track entry (track AA)
     pub fn lead_out(total_sectors: u32) -> Self {
         Self {
@@ -521,6 +602,120 @@ impl SimpleToc {
     }
 }
 
+/// Lead-out track number in READ TOC
+pub const LEAD_OUT_TRACK: u8 = 0xAA;
+
+/// One track of a multi-track disc
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TocTrack {
+    /// Track number (1-99)
+    pub number: u8,
+    /// First sector of the track
+    pub start_lba: u32,
+    /// Red Book audio rather than data
+    pub audio: bool,
+}
+
+impl TocTrack {
+    /// TOC entry for this track
+    pub fn entry(&self) -> TocEntry {
+        if self.audio {
+            TocEntry::audio_track(self.number, self.start_lba)
+        } else {
+            TocEntry::data_track(self.number, self.start_lba)
+        }
+    }
+}
+
+/// Table of contents for a disc with any mix of data and audio tracks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toc {
+    /// Tracks in ascending order of number and start
+    pub tracks: Vec<TocTrack>,
+    /// First sector after the last track
+    pub lead_out: u32,
+}
+
+impl Toc {
+    /// TOC of a single-track data disc (the same layout as `SimpleToc`)
+    pub fn data_disc(total_sectors: u32) -> Self {
+        Self {
+            tracks: vec![TocTrack { number: 1, start_lba: 0, audio: false }],
+            lead_out: total_sectors,
+        }
+    }
+
+    /// First track number
+    pub fn first_track(&self) -> u8 {
+        self.tracks.first().map_or(1, |t| t.number)
+    }
+
+    /// Last track number
+    pub fn last_track(&self) -> u8 {
+        self.tracks.last().map_or(1, |t| t.number)
+    }
+
+    /// Track with the given number
+    pub fn track(&self, number: u8) -> Option<&TocTrack> {
+        self.tracks.iter().find(|t| t.number == number)
+    }
+
+    /// Track containing a sector
+    pub fn track_at(&self, lba: u32) -> Option<&TocTrack> {
+        if lba >= self.lead_out {
+            return None;
+        }
+        self.tracks.iter().rev().find(|t| t.start_lba <= lba)
+    }
+
+    /// First sector after a track
+    pub fn track_end(&self, track: &TocTrack) -> u32 {
+        self.tracks
+            .iter()
+            .find(|t| t.start_lba > track.start_lba)
+            .map_or(self.lead_out, |t| t.start_lba)
+    }
+
+    /// READ TOC format 0 response starting at `starting_track`
+    ///
+    /// Addresses are MSF when `msf` is set. Returns None when the
+    /// starting track is past the last track (and not the lead-out).
+    pub fn to_bytes(&self, msf: bool, starting_track: u8) -> Option<Vec<u8>> {
+        let lead_out = TocEntry::lead_out(self.lead_out);
+        let mut entries: Vec<TocEntry> = if starting_track == LEAD_OUT_TRACK {
+            Vec::new()
+        } else {
+            let entries: Vec<TocEntry> = self
+                .tracks
+                .iter()
+                .filter(|t| t.number >= starting_track)
+                .map(TocTrack::entry)
+                .collect();
+            if entries.is_empty() {
+                return None;
+            }
+            entries
+        };
+        entries.push(TocEntry {
+            adr_control: self.tracks.last().map_or(0x14, |t| t.entry().adr_control),
+            ..lead_out
+        });
+
+        // Length excludes the data_length field itself
+        let data_length = (2 + entries.len() * 8) as u16;
+        let mut buf = Vec::with_capacity(2 + data_length as usize);
+        buf.extend_from_slice(&data_length.to_be_bytes());
+        buf.push(self.first_track());
+        buf.push(self.last_track());
+        for entry in &entries {
+            buf.extend_from_slice(&[entry.reserved1, entry.adr_control, entry.track_number, entry.reserved2]);
+            let lba = u32::from_be_bytes(entry.start_address);
+            buf.extend_from_slice(&address_bytes(lba, msf));
+        }
+        Some(buf)
+    }
+}
+
 // ============================================================================
 // SCSI Command Result
 // ============================================================================
@@ -546,6 +741,225 @@ impl ScsiResult {
     }
 }
 
+// ============================================================================
+// CD-DA Playback
+// ============================================================================
+
+/// Audio play state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlayState {
+    /// Nothing playing
+    #[default]
+    Stopped,
+    /// Sectors are being played
+    Playing,
+    /// Play paused, position kept
+    Paused,
+    /// Play reached its end (reported once, then Stopped)
+    Completed,
+}
+
+/// CD-DA player state for the audio commands
+///
+/// Positions are absolute sector numbers; the play range is half open
+/// (`position..end`).
+#[derive(Debug, Clone, Default)]
+pub struct CdAudio {
+    state: PlayState,
+    position: u32,
+    end: u32,
+}
+
+impl CdAudio {
+    /// A stopped player
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current play state
+    pub fn state(&self) -> PlayState {
+        self.state
+    }
+
+    /// Sector currently playing (or paused at)
+    pub fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Handle an audio command against the disc's TOC
+    ///
+    /// Returns None for commands that are not audio commands, so the
+    /// caller can fall through to its data command handling.
+    pub fn handle_command(&mut self, cdb: &[u8], toc: &Toc) -> Option<ScsiResult> {
+        let result = match *cdb.first()? {
+            opcode::PLAY_AUDIO_10 => {
+                let start = cdb10_get_lba(cdb);
+                self.play_length(toc, start, u32::from(cdb10_get_length(cdb)))
+            }
+            opcode::PLAY_AUDIO_12 => {
+                let start = cdb10_get_lba(cdb);
+                self.play_length(toc, start, cdb12_get_length(cdb))
+            }
+            opcode::PLAY_AUDIO_MSF => {
+                if cdb.len() < 9 {
+                    return Some(ScsiResult::CheckCondition(SenseData::invalid_field()));
+                }
+                // Start of FF:FF:FF means the current position
+                let start = if cdb[3..6] == [0xFF; 3] {
+                    self.position
+                } else {
+                    msf_to_lba(cdb[3], cdb[4], cdb[5])
+                };
+                let end = msf_to_lba(cdb[6], cdb[7], cdb[8]);
+                if end < start {
+                    Err(SenseData::invalid_field())
+                } else {
+                    self.play(toc, start, end)
+                }
+            }
+            opcode::PAUSE_RESUME => {
+                let resume = cdb.get(8).is_some_and(|b| b & 0x01 != 0);
+                if resume { self.resume() } else { self.pause() }
+            }
+            opcode::STOP_PLAY_SCAN => {
+                self.stop();
+                Ok(())
+            }
+            opcode::READ_SUB_CHANNEL => return Some(self.read_sub_channel(cdb, toc)),
+            _ => return None,
+        };
+        Some(match result {
+            Ok(()) => ScsiResult::GoodNoData,
+            Err(sense) => ScsiResult::CheckCondition(sense),
+        })
+    }
+
+    /// PLAY AUDIO(10/12): `length` sectors from `start`
+    fn play_length(&mut self, toc: &Toc, start: u32, length: u32) -> Result<(), SenseData> {
+        // A start of FFFFFFFF means the current position
+        let start = if start == u32::MAX { self.position } else { start };
+        if length == 0 {
+            // Allowed; only checks that the address is valid
+            return toc.track_at(start).map(|_| ()).ok_or_else(SenseData::lba_out_of_range);
+        }
+        let end = start.checked_add(length).ok_or_else(SenseData::lba_out_of_range)?;
+        self.play(toc, start, end)
+    }
+
+    /// Start playing sectors `start..end`
+    ///
+    /// The range must lie on the disc and start in an audio track. Play
+    /// stops at the first data track after the start, as drives do.
+    pub fn play(&mut self, toc: &Toc, start: u32, end: u32) -> Result<(), SenseData> {
+        if end > toc.lead_out {
+            return Err(SenseData::lba_out_of_range());
+        }
+        let track = toc.track_at(start).ok_or_else(SenseData::lba_out_of_range)?;
+        if !track.audio {
+            return Err(SenseData::illegal_mode_for_track());
+        }
+        let data_start = toc
+            .tracks
+            .iter()
+            .find(|t| t.start_lba > start && !t.audio)
+            .map_or(toc.lead_out, |t| t.start_lba);
+
+        self.position = start;
+        self.end = end.min(data_start);
+        self.state = if start < self.end { PlayState::Playing } else { PlayState::Completed };
+        Ok(())
+    }
+
+    /// Pause play, keeping the position
+    pub fn pause(&mut self) -> Result<(), SenseData> {
+        match self.state {
+            PlayState::Playing | PlayState::Paused => {
+                self.state = PlayState::Paused;
+                Ok(())
+            }
+            _ => Err(SenseData::command_sequence_error()),
+        }
+    }
+
+    /// Resume a paused play
+    pub fn resume(&mut self) -> Result<(), SenseData> {
+        match self.state {
+            PlayState::Paused | PlayState::Playing => {
+                self.state = PlayState::Playing;
+                Ok(())
+            }
+            _ => Err(SenseData::command_sequence_error()),
+        }
+    }
+
+    /// Stop play
+    pub fn stop(&mut self) {
+        self.state = PlayState::Stopped;
+    }
+
+    /// Take up to `sectors` sectors to output while playing
+    ///
+    /// Called in real time (75 sectors per second) by whatever renders
+    /// the audio; returns the sectors to read and output next, which is
+    /// empty unless playing.
+    pub fn advance(&mut self, sectors: u32) -> std::ops::Range<u32> {
+        if self.state != PlayState::Playing {
+            return self.position..self.position;
+        }
+        let start = self.position;
+        self.position = start.saturating_add(sectors).min(self.end);
+        if self.position >= self.end {
+            self.state = PlayState::Completed;
+        }
+        start..self.position
+    }
+
+    /// Audio status for READ SUB-CHANNEL
+    ///
+    /// Completion is reported once; after that the status is NO_STATUS.
+    fn take_audio_status(&mut self) -> u8 {
+        match self.state {
+            PlayState::Playing => audio_status::PLAY_IN_PROGRESS,
+            PlayState::Paused => audio_status::PAUSED,
+            PlayState::Completed => {
+                self.state = PlayState::Stopped;
+                audio_status::COMPLETED
+            }
+            PlayState::Stopped => audio_status::NO_STATUS,
+        }
+    }
+
+    /// READ SUB-CHANNEL
+    ///
+    /// Only the CD-ROM current position format (01h) carries data; other
+    /// formats return the header alone.
+    fn read_sub_channel(&mut self, cdb: &[u8], toc: &Toc) -> ScsiResult {
+        if cdb.len() < 9 {
+            return ScsiResult::CheckCondition(SenseData::invalid_field());
+        }
+        let msf = cdb[1] & 0x02 != 0;
+        let subq = cdb[2] & 0x40 != 0;
+        let format = cdb[3];
+        let alloc_len = usize::from(u16::from_be_bytes([cdb[7], cdb[8]]));
+
+        let mut data = vec![0, self.take_audio_status(), 0, 0];
+        if subq && format == 0x01 {
+            let track = toc.track_at(self.position);
+            let (number, control, relative) = match track {
+                Some(t) => (t.number, t.entry().adr_control, self.position - t.start_lba),
+                None => (toc.last_track(), 0x10, 0),
+            };
+            data.extend_from_slice(&[0x01, control, number, 1]);
+            data.extend_from_slice(&address_bytes(self.position, msf));
+            data.extend_from_slice(&relative_address_bytes(relative, msf));
+        }
+        let sub_channel_len = (data.len() - 4) as u16;
+        data[2..4].copy_from_slice(&sub_channel_len.to_be_bytes());
+        data.truncate(alloc_len);
+        ScsiResult::Good(data)
+    }
+}
+
 // ============================================================================
 // CD-ROM Sector Size Constants
 // ============================================================================
@@ -556,6 +970,15 @@ pub const SECTOR_SIZE_CDROM: u32 = 2048;
 /// CD-ROM sector size with EDC/ECC (Mode 1 raw)
 pub const SECTOR_SIZE_CDROM_RAW: u32 = 2352;
 
+/// CD-DA sector size (588 stereo 16-bit samples)
+pub const SECTOR_SIZE_CDDA: u32 = 2352;
+
+/// Sectors (frames) per second of audio
+pub const CD_FRAMES_PER_SECOND: u32 = 75;
+
+/// MSF address of LBA 0 (the two-second pregap)
+pub const MSF_LBA_OFFSET: u32 = 150;
+
 // ============================================================================
 // Utility Functions
 // ============================================================================
@@ -576,6 +999,56 @@ pub fn cdb10_get_length(cdb: &[u8]) -> u16 {
     u16::from_be_bytes([cdb[7], cdb[8]])
 }
 
+/// Extract transfer length from a 12-byte CDB (bytes 6-9, big-endian)
+pub fn cdb12_get_length(cdb: &[u8]) -> u32 {
+    if cdb.len() < 10 {
+        return 0;
+    }
+    u32::from_be_bytes([cdb[6], cdb[7], cdb[8], cdb[9]])
+}
+
+/// Convert an LBA to minutes, seconds and frames
+pub fn lba_to_msf(lba: u32) -> (u8, u8, u8) {
+    frames_to_msf(lba + MSF_LBA_OFFSET)
+}
+
+/// Split a frame count into minutes, seconds and frames
+fn frames_to_msf(frames: u32) -> (u8, u8, u8) {
+    let per_minute = 60 * CD_FRAMES_PER_SECOND;
+    (
+        (frames / per_minute).min(u8::MAX as u32) as u8,
+        (frames / CD_FRAMES_PER_SECOND % 60) as u8,
+        (frames % CD_FRAMES_PER_SECOND) as u8,
+    )
+}
+
+/// Convert minutes, seconds and frames to an LBA
+pub fn msf_to_lba(minutes: u8, seconds: u8, frames: u8) -> u32 {
+    let total = (u32::from(minutes) * 60 + u32::from(seconds)) * CD_FRAMES_PER_SECOND
+        + u32::from(frames);
+    total.saturating_sub(MSF_LBA_OFFSET)
+}
+
+/// Absolute address field: big-endian LBA, or 0/M/S/F
+fn address_bytes(lba: u32, msf: bool) -> [u8; 4] {
+    if msf {
+        let (m, s, f) = lba_to_msf(lba);
+        [0, m, s, f]
+    } else {
+        lba.to_be_bytes()
+    }
+}
+
+/// Track-relative address field (no pregap offset in MSF form)
+fn relative_address_bytes(sectors: u32, msf: bool) -> [u8; 4] {
+    if msf {
+        let (m, s, f) = frames_to_msf(sectors);
+        [0, m, s, f]
+    } else {
+        sectors.to_be_bytes()
+    }
+}
+
 /// Extract allocation length from INQUIRY CDB (byte 4)
 pub fn inquiry_get_alloc_length(cdb: &[u8]) -> u8 {
     if cdb.len() < 5 {
@@ -651,5 +1124,109 @@ mod tests {
         assert_eq!(bytes[6], 1);
         // Lead-out track number
         assert_eq!(bytes[14], 0xAA);
+
+        // The multi-track TOC of a data disc is byte-identical
+        assert_eq!(Toc::data_disc(333000).to_bytes(false, 1).unwrap(), bytes.to_vec());
+    }
+
+    #[test]
+    fn test_msf_conversion() {
+        assert_eq!(lba_to_msf(0), (0, 2, 0));
+        assert_eq!(lba_to_msf(4350), (1, 0, 0));
+        assert_eq!(msf_to_lba(1, 0, 0), 4350);
+        assert_eq!(msf_to_lba(0, 2, 0), 0);
+        assert_eq!(msf_to_lba(0, 0, 0), 0);
+    }
+
+    /// Data track then two audio tracks (a typical game disc)
+    fn mixed_mode_toc() -> Toc {
+        Toc {
+            tracks: vec![
+                TocTrack { number: 1, start_lba: 0, audio: false },
+                TocTrack { number: 2, start_lba: 10_000, audio: true },
+                TocTrack { number: 3, start_lba: 20_000, audio: true },
+            ],
+            lead_out: 30_000,
+        }
+    }
+
+    #[test]
+    fn test_multi_track_toc() {
+        let toc = mixed_mode_toc();
+        let bytes = toc.to_bytes(false, 2).unwrap();
+        // Header + tracks 2, 3 + lead-out
+        assert_eq!(bytes.len(), 4 + 3 * 8);
+        assert_eq!(u16::from_be_bytes([bytes[0], bytes[1]]), 26);
+        assert_eq!((bytes[2], bytes[3]), (1, 3));
+        assert_eq!((bytes[5], bytes[6]), (0x10, 2));
+        assert_eq!(&bytes[8..12], &10_000u32.to_be_bytes());
+        assert_eq!((bytes[21], bytes[22]), (0x10, LEAD_OUT_TRACK));
+
+        // MSF addresses
+        let bytes = toc.to_bytes(true, 3).unwrap();
+        assert_eq!(&bytes[8..12], &[0, 4, 28, 50]);
+
+        assert!(toc.to_bytes(false, 4).is_none());
+        assert_eq!(toc.to_bytes(false, LEAD_OUT_TRACK).unwrap().len(), 12);
+        assert_eq!(toc.track_at(15_000).map(|t| t.number), Some(2));
+        assert_eq!(toc.track_end(toc.track(2).unwrap()), 20_000);
+    }
+
+    #[test]
+    fn test_cd_audio_playback() {
+        let toc = mixed_mode_toc();
+        let mut audio = CdAudio::new();
+
+        // PLAY AUDIO(10) on the data track is refused
+        let play_data = [opcode::PLAY_AUDIO_10, 0, 0, 0, 0, 0, 0, 0, 10, 0];
+        match audio.handle_command(&play_data, &toc) {
+            Some(ScsiResult::CheckCondition(sense)) => {
+                assert_eq!(sense.asc, asc::ILLEGAL_MODE_FOR_TRACK)
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // PLAY AUDIO MSF over track 3 to the lead-out
+        let (m, s, f) = lba_to_msf(20_000);
+        let play = [opcode::PLAY_AUDIO_MSF, 0, 0, m, s, f, 6, 42, 0];
+        assert!(matches!(audio.handle_command(&play, &toc), Some(ScsiResult::GoodNoData)));
+        assert_eq!(audio.advance(75), 20_000..20_075);
+
+        // Pause stops output; resume continues
+        let pause = [opcode::PAUSE_RESUME, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let resume = [opcode::PAUSE_RESUME, 0, 0, 0, 0, 0, 0, 0, 1, 0];
+        audio.handle_command(&pause, &toc);
+        assert_eq!(audio.state(), PlayState::Paused);
+        assert!(audio.advance(75).is_empty());
+        audio.handle_command(&resume, &toc);
+
+        // READ SUB-CHANNEL: current position in track 3, LBA form
+        let sub = [opcode::READ_SUB_CHANNEL, 0, 0x40, 0x01, 0, 0, 0, 0, 16, 0];
+        let Some(ScsiResult::Good(data)) = audio.handle_command(&sub, &toc) else {
+            panic!("READ SUB-CHANNEL failed");
+        };
+        assert_eq!(data.len(), 16);
+        assert_eq!(data[1], audio_status::PLAY_IN_PROGRESS);
+        assert_eq!((data[5], data[6]), (0x10, 3));
+        assert_eq!(&data[8..12], &20_075u32.to_be_bytes());
+        assert_eq!(&data[12..16], &75u32.to_be_bytes());
+
+        // Completion is reported once
+        assert_eq!(audio.advance(u32::MAX), 20_075..30_000);
+        let Some(ScsiResult::Good(data)) = audio.handle_command(&sub, &toc) else {
+            panic!("READ SUB-CHANNEL failed");
+        };
+        assert_eq!(data[1], audio_status::COMPLETED);
+        let Some(ScsiResult::Good(data)) = audio.handle_command(&sub, &toc) else {
+            panic!("READ SUB-CHANNEL failed");
+        };
+        assert_eq!(data[1], audio_status::NO_STATUS);
+
+        // RESUME without a paused play is a sequence error
+        assert!(matches!(
+            audio.handle_command(&resume, &toc),
+            Some(ScsiResult::CheckCondition(_))
+        ));
+        assert!(audio.handle_command(&[opcode::READ_10], &toc).is_none());
     }
 }
@@ -0,0 +1,406 @@
+//! Session log: driver messages and frontend tracing output in one place.
+//!
+//! The driver keeps its recent messages, debug ones included, in a ring
+//! per card that READ_LOG reads without consuming; `DriverLogTail` follows
+//! it. The frontend's own tracing events are captured by a tracing layer.
+//! Both end up as `LogLine`s in a `SessionLog`, which the log console
+//! filters by level, source and text and can export to a file.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::driver::DriverHandle;
+use crate::fat_time;
+use crate::ioctl::{log_level, LogBatch, SUNPCI_MAX_LOG_ENTRIES};
+
+/// Lines a session log keeps by default
+pub const DEFAULT_CAPACITY: usize = 5000;
+
+/// Severity of a log line, most severe first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    /// Level of a driver message (a kernel log level)
+    pub fn from_driver(level: u32) -> Self {
+        match level {
+            0..=log_level::ERR => Level::Error,
+            log_level::WARN => Level::Warn,
+            5 | log_level::INFO => Level::Info,
+            _ => Level::Debug,
+        }
+    }
+
+    /// Name as written in filters and exports
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    /// Parse a name written by `name`
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" | "warning" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// Where a log line came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The kernel driver of the card
+    Driver,
+    /// This frontend
+    Frontend,
+}
+
+impl Source {
+    /// Name as written in filters and exports
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Driver => "driver",
+            Source::Frontend => "frontend",
+        }
+    }
+}
+
+/// One line of the session log
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogLine {
+    /// Position in the session log (assigned by `SessionLog::push`)
+    pub id: u64,
+    /// Milliseconds since the epoch
+    pub time_ms: u64,
+    pub level: Level,
+    pub source: Source,
+    /// Module that logged the line (empty for driver messages)
+    pub target: String,
+    pub message: String,
+}
+
+impl LogLine {
+    /// A line not yet in a session log
+    pub fn new(time_ms: u64, level: Level, source: Source, target: &str, message: &str) -> Self {
+        Self {
+            id: 0,
+            time_ms,
+            level,
+            source,
+            target: target.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The line as exported, in local time
+    pub fn format(&self) -> String {
+        let secs = (self.time_ms / 1000) as i64;
+        let local = secs + i64::from(fat_time::local_offset_minutes(secs)) * 60;
+        let (year, month, day) = fat_time::civil_from_days(local.div_euclid(86_400));
+        let time = local.rem_euclid(86_400);
+        let mut line = format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03} {:<5} {:<8} ",
+            year,
+            month,
+            day,
+            time / 3600,
+            time / 60 % 60,
+            time % 60,
+            self.time_ms % 1000,
+            self.level.name().to_ascii_uppercase(),
+            self.source.name(),
+        );
+        if !self.target.is_empty() {
+            line.push_str(&self.target);
+            line.push_str(": ");
+        }
+        line.push_str(&self.message);
+        line
+    }
+}
+
+/// Which lines the console shows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    /// Least severe level shown
+    pub level: Level,
+    /// Only lines from this source (None for both)
+    pub source: Option<Source>,
+    /// Only lines containing this, ignoring case (empty for all)
+    pub text: String,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: Level::Info,
+            source: None,
+            text: String::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    /// Whether `line` is shown
+    pub fn matches(&self, line: &LogLine) -> bool {
+        if line.level > self.level {
+            return false;
+        }
+        if self.source.is_some_and(|source| source != line.source) {
+            return false;
+        }
+        if self.text.is_empty() {
+            return true;
+        }
+        let text = self.text.to_lowercase();
+        line.message.to_lowercase().contains(&text) || line.target.to_lowercase().contains(&text)
+    }
+}
+
+/// The most recent log lines of both sources, oldest first
+#[derive(Debug)]
+pub struct SessionLog {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+    next_id: u64,
+}
+
+impl Default for SessionLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl SessionLog {
+    /// An empty log keeping at most `capacity` lines
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::new(),
+            capacity: capacity.max(1),
+            next_id: 1,
+        }
+    }
+
+    /// Add a line, dropping the oldest if the log is full; returns its id
+    pub fn push(&mut self, mut line: LogLine) -> u64 {
+        line.id = self.next_id;
+        self.next_id += 1;
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+        self.next_id - 1
+    }
+
+    /// Number of lines held
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Whether no lines are held
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Id of the newest line (0 if none was ever added)
+    pub fn last_id(&self) -> u64 {
+        self.next_id - 1
+    }
+
+    /// Lines `filter` shows, oldest first
+    pub fn filtered<'a>(&'a self, filter: &'a LogFilter) -> impl Iterator<Item = &'a LogLine> {
+        self.lines.iter().filter(move |line| filter.matches(line))
+    }
+
+    /// Lines `filter` shows that were added after line `id`
+    pub fn filtered_after<'a>(
+        &'a self,
+        id: u64,
+        filter: &'a LogFilter,
+    ) -> impl Iterator<Item = &'a LogLine> {
+        self.filtered(filter).filter(move |line| line.id > id)
+    }
+
+    /// Drop every line
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Write the lines `filter` shows to `path`, one per line; returns how
+    /// many were written
+    pub fn export(&self, path: &Path, filter: &LogFilter) -> io::Result<usize> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let mut count = 0;
+        for line in self.filtered(filter) {
+            writeln!(out, "{}", line.format())?;
+            count += 1;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+}
+
+/// Follows the driver's log ring of one card
+#[derive(Debug, Default)]
+pub struct DriverLogTail {
+    /// Sequence number of the next message wanted (0 before the first read)
+    since: u32,
+}
+
+impl DriverLogTail {
+    /// Start at the oldest message the driver still holds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Messages logged since the last poll, oldest first
+    ///
+    /// A line reporting lost messages is added if the driver logged more
+    /// than its ring holds between polls.
+    pub fn poll(&mut self, handle: &DriverHandle) -> Result<Vec<LogLine>> {
+        let mut lines = Vec::new();
+        loop {
+            let batch = handle.read_log(self.since)?;
+            lines.extend(lines_from_batch(&batch, self.since));
+            self.since = batch.next;
+            // A short batch means the ring is drained
+            if (batch.count as usize) < SUNPCI_MAX_LOG_ENTRIES {
+                return Ok(lines);
+            }
+        }
+    }
+}
+
+/// Log lines for a READ_LOG batch asked for from `since` on
+fn lines_from_batch(batch: &LogBatch, since: u32) -> Vec<LogLine> {
+    let mut lines = Vec::new();
+    // Older messages are always missing on the first read; not worth a line
+    if batch.dropped > 0 && since != 0 {
+        let time_ms = batch.entries().first().map_or(0, |e| e.time_ns.get() / 1_000_000);
+        let message = format!("{} driver messages were lost", batch.dropped);
+        lines.push(LogLine::new(time_ms, Level::Warn, Source::Frontend, "session_log", &message));
+    }
+    lines.extend(batch.entries().iter().map(|entry| {
+        LogLine::new(
+            entry.time_ns.get() / 1_000_000,
+            Level::from_driver(entry.level),
+            Source::Driver,
+            "",
+            &entry.message(),
+        )
+    }));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ioctl::{LogEntry, SplitU64};
+    use tempfile::tempdir;
+
+    fn line(level: Level, source: Source, message: &str) -> LogLine {
+        LogLine::new(1_000, level, source, "", message)
+    }
+
+    #[test]
+    fn test_filter() {
+        let error = line(Level::Error, Source::Driver, "bad response magic");
+        let debug = line(Level::Debug, Source::Frontend, "Driver event: DisplayChanged");
+
+        let filter = LogFilter::default();
+        assert!(filter.matches(&error));
+        assert!(!filter.matches(&debug));
+
+        let filter = LogFilter { level: Level::Trace, ..Default::default() };
+        assert!(filter.matches(&debug));
+
+        let filter = LogFilter { level: Level::Trace, source: Some(Source::Driver), ..Default::default() };
+        assert!(filter.matches(&error));
+        assert!(!filter.matches(&debug));
+
+        let filter = LogFilter { level: Level::Trace, text: "MAGIC".to_string(), ..Default::default() };
+        assert!(filter.matches(&error));
+        assert!(!filter.matches(&debug));
+
+        assert_eq!(Level::parse("Warning"), Some(Level::Warn));
+        assert_eq!(Level::from_driver(log_level::ERR), Level::Error);
+        assert_eq!(Level::from_driver(log_level::DEBUG), Level::Debug);
+    }
+
+    #[test]
+    fn test_capacity_and_ids() {
+        let mut log = SessionLog::new(2);
+        for message in ["one", "two", "three"] {
+            log.push(line(Level::Info, Source::Frontend, message));
+        }
+        let filter = LogFilter::default();
+        let kept: Vec<_> = log.filtered(&filter).map(|l| (l.id, l.message.as_str())).collect();
+        assert_eq!(kept, [(2, "two"), (3, "three")]);
+        let after: Vec<_> = log.filtered_after(2, &filter).map(|l| l.id).collect();
+        assert_eq!(after, [3]);
+        assert_eq!(log.last_id(), 3);
+    }
+
+    #[test]
+    fn test_export() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("session.log");
+        let mut log = SessionLog::default();
+        log.push(line(Level::Info, Source::Driver, "session started (boot device 0)"));
+        log.push(LogLine::new(2_000, Level::Warn, Source::Frontend, "media_check", "disk missing"));
+        log.push(line(Level::Debug, Source::Driver, "VGA update"));
+
+        assert_eq!(log.export(&path, &LogFilter::default()).unwrap(), 2);
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with("INFO  driver   session started (boot device 0)"));
+        assert!(lines[1].ends_with("WARN  frontend media_check: disk missing"));
+    }
+
+    #[test]
+    fn test_lines_from_batch() {
+        let mut batch = LogBatch { count: 2, next: 13, dropped: 3, ..Default::default() };
+        for (i, text) in ["first", "second"].into_iter().enumerate() {
+            let mut entry = LogEntry {
+                seq: 11 + i as u32,
+                level: log_level::INFO,
+                time_ns: SplitU64::new(5_000_000_000),
+                ..Default::default()
+            };
+            entry.message[..text.len()].copy_from_slice(text.as_bytes());
+            batch.entries[i] = entry;
+        }
+
+        let lines = lines_from_batch(&batch, 8);
+        let messages: Vec<_> = lines.iter().map(|l| l.message.as_str()).collect();
+        assert_eq!(messages, ["3 driver messages were lost", "first", "second"]);
+        assert_eq!(lines[1].time_ms, 5_000);
+        assert_eq!(lines[1].source, Source::Driver);
+
+        // Nothing is reported lost on the first read
+        assert_eq!(lines_from_batch(&batch, 0).len(), 2);
+    }
+}
@@ -0,0 +1,175 @@
+//! Settings bundles: the whole configuration in one file.
+//!
+//! A bundle is a TOML document holding the configuration file as it is
+//! (drive mappings and recent files included) under `[config]`, and a
+//! `[bundle]` table saying where and when it was made. It moves a setup to
+//! another machine, or goes with a bug report; for the latter the host
+//! paths in it (media, mapped directories, recent files, search paths, the
+//! BIOS image) can be cut down to their file names, and the host name left
+//! out. Unlike an appliance (see `appliance`) it carries no media.
+//!
+//! A bundle made by an older build is upgraded on import like an older
+//! configuration file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::config::{AppConfig, BiosConfig, DriveMapping, StorageConfig};
+use crate::config_storage::migrate;
+use crate::mdns;
+
+/// Layout of the `[bundle]` table this build writes
+pub const BUNDLE_FORMAT: u32 = 1;
+
+/// What a bundle says about itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleInfo {
+    pub format: u32,
+    /// Version of the build that made it
+    pub app_version: String,
+    /// Seconds since the epoch
+    pub created: u64,
+    /// Machine it was made on; empty if left out
+    #[serde(default)]
+    pub host: String,
+    /// Whether host paths were cut down to file names
+    #[serde(default)]
+    pub paths_stripped: bool,
+}
+
+#[derive(Serialize)]
+struct BundleOut<'a> {
+    bundle: BundleInfo,
+    config: &'a AppConfig,
+}
+
+/// Write `config` to a bundle at `path`, with host paths cut down to file
+/// names and the host name left out if `strip_paths` is set
+pub fn export_bundle(config: &AppConfig, path: &Path, strip_paths: bool) -> anyhow::Result<BundleInfo> {
+    let stripped;
+    let config = if strip_paths {
+        stripped = strip_host_paths(config);
+        &stripped
+    } else {
+        config
+    };
+    let info = BundleInfo {
+        format: BUNDLE_FORMAT,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created: SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0),
+        host: if strip_paths { String::new() } else { mdns::host_name() },
+        paths_stripped: strip_paths,
+    };
+    let contents = toml::to_string_pretty(&BundleOut {
+        bundle: info.clone(),
+        config,
+    })?;
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(info)
+}
+
+/// Read the bundle at `path`, upgrading its configuration if it was made
+/// by an older build
+pub fn import_bundle(path: &Path) -> anyhow::Result<(BundleInfo, AppConfig)> {
+    let contents = fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    let mut table: Table = toml::from_str(&contents).with_context(|| format!("{} is not a settings bundle", path.display()))?;
+    let (Some(Value::Table(info)), Some(Value::Table(mut config))) = (table.remove("bundle"), table.remove("config")) else {
+        bail!("{} is not a settings bundle", path.display());
+    };
+    let info: BundleInfo = Value::Table(info).try_into().context("invalid [bundle] table")?;
+    if info.format > BUNDLE_FORMAT {
+        bail!("the bundle was made by a newer version ({})", info.app_version);
+    }
+    migrate(&mut config);
+    let config: AppConfig = Value::Table(config).try_into().context("invalid [config] table")?;
+    Ok((info, config))
+}
+
+/// A copy of `config` with every host path cut down to its file name
+fn strip_host_paths(config: &AppConfig) -> AppConfig {
+    let mut config = config.clone();
+    strip_storage(&mut config.storage);
+    strip_mappings(&mut config.drive_mappings);
+    strip_bios(&mut config.bios);
+    let recent = &mut config.recent;
+    for list in [&mut recent.disk_images, &mut recent.iso_files, &mut recent.floppy_images, &mut recent.pinned] {
+        list.iter_mut().for_each(strip);
+    }
+    config.remote.profile_name.clear();
+    config.remote.control_token.clear();
+    for card in &mut config.cards {
+        card.storage.iter_mut().for_each(strip_storage);
+        card.drive_mappings.iter_mut().for_each(|m| strip_mappings(m));
+        card.bios.iter_mut().for_each(strip_bios);
+    }
+    config
+}
+
+fn strip_storage(storage: &mut StorageConfig) {
+    for disk in [&mut storage.primary_disk, &mut storage.secondary_disk].into_iter().flatten() {
+        strip(&mut disk.path);
+        disk.overlay.iter_mut().for_each(strip);
+    }
+    storage.cdrom.mounted_iso.iter_mut().for_each(strip);
+    storage.floppy_a.mounted_image.iter_mut().for_each(strip);
+    storage.floppy_b.mounted_image.iter_mut().for_each(strip);
+    storage.search_paths.clear();
+}
+
+fn strip_mappings(mappings: &mut [DriveMapping]) {
+    mappings.iter_mut().for_each(|m| strip(&mut m.host_path));
+}
+
+fn strip_bios(bios: &mut BiosConfig) {
+    bios.path.iter_mut().for_each(strip);
+}
+
+/// Cut a path down to its file name
+fn strip(path: &mut PathBuf) {
+    *path = path.file_name().map(PathBuf::from).unwrap_or_default();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DiskConfig;
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let bundle = dir.path().join("settings.toml");
+        let mut config = AppConfig::default();
+        config.keyboard.layout = "de".to_string();
+        config.storage.primary_disk = Some(DiskConfig {
+            path: PathBuf::from("/home/someone/disks/win98.img"),
+            bootable: true,
+            content_id: None,
+            overlay: None,
+        });
+        config.drive_mappings = AppConfig::suggested_drive_mappings();
+        config.recent.add_iso(PathBuf::from("/home/someone/isos/dos622.iso"));
+
+        let info = export_bundle(&config, &bundle, false).unwrap();
+        let (read, imported) = import_bundle(&bundle).unwrap();
+        assert_eq!(read, info);
+        assert_eq!(imported.keyboard.layout, "de");
+        assert_eq!(imported.storage.primary_disk.unwrap().path, Path::new("/home/someone/disks/win98.img"));
+        assert_eq!(imported.drive_mappings.len(), config.drive_mappings.len());
+
+        export_bundle(&config, &bundle, true).unwrap();
+        let contents = fs::read_to_string(&bundle).unwrap();
+        assert!(!contents.contains("/home/someone"));
+        let (read, imported) = import_bundle(&bundle).unwrap();
+        assert!(read.paths_stripped && read.host.is_empty());
+        assert_eq!(imported.storage.primary_disk.unwrap().path, Path::new("win98.img"));
+        assert_eq!(imported.recent.iso_files, [PathBuf::from("dos622.iso")]);
+
+        fs::write(&bundle, "[keyboard]\nlayout = \"us\"\n").unwrap();
+        assert!(import_bundle(&bundle).is_err());
+    }
+}
@@ -0,0 +1,202 @@
+//! 8.3 names for the long host names on mapped drives.
+//!
+//! DOS sees at most eight characters of name and three of extension, in
+//! upper case, from a limited set. A mapped drive's `Mangling` and
+//! `NameCase` say how the driver's FSD shortens host names that do not fit,
+//! and are passed to it as flag bits of the mapping (`ioctl::drive_flags`).
+//! With `hide_dotfiles` set, names starting with '.' are left out
+//! altogether; otherwise they are shown with the hidden attribute.
+//!
+//! `short_name` does the same as the driver's `fsd_short_name`, so the
+//! frontend can preview what the guest will see.
+
+use crate::config::{Mangling, NameCase};
+use crate::ioctl::drive_flags;
+
+/// Punctuation DOS allows in names, besides letters and digits
+const ALLOWED: &str = "!#$%&'()-@^_`{}~";
+
+/// Largest numeric tail, "~999999"
+const MAX_TAIL: u32 = 999_999;
+
+/// Parse a mangling as written in the UI: "tail", "hash" or "truncate"
+pub fn parse_mangling(text: &str) -> Option<Mangling> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "tail" => Some(Mangling::NumericTail),
+        "hash" => Some(Mangling::Hash),
+        "truncate" => Some(Mangling::Truncate),
+        _ => None,
+    }
+}
+
+/// A mangling as written in the UI
+pub fn mangling_name(mangling: Mangling) -> &'static str {
+    match mangling {
+        Mangling::NumericTail => "tail",
+        Mangling::Hash => "hash",
+        Mangling::Truncate => "truncate",
+    }
+}
+
+/// Parse a case as written in the UI: "upper", "lower" or "preserve"
+pub fn parse_case(text: &str) -> Option<NameCase> {
+    match text.trim().to_ascii_lowercase().as_str() {
+        "upper" => Some(NameCase::Upper),
+        "lower" => Some(NameCase::Lower),
+        "preserve" => Some(NameCase::Preserve),
+        _ => None,
+    }
+}
+
+/// A case as written in the UI
+pub fn case_name(case: NameCase) -> &'static str {
+    match case {
+        NameCase::Upper => "upper",
+        NameCase::Lower => "lower",
+        NameCase::Preserve => "preserve",
+    }
+}
+
+/// Mapping flag bits for the name options
+pub fn flags(mangling: Mangling, case: NameCase, hide_dotfiles: bool) -> u8 {
+    let mangling = match mangling {
+        Mangling::NumericTail => drive_flags::MANGLE_TAIL,
+        Mangling::Hash => drive_flags::MANGLE_HASH,
+        Mangling::Truncate => drive_flags::MANGLE_TRUNCATE,
+    };
+    let case = match case {
+        NameCase::Upper => drive_flags::CASE_UPPER,
+        NameCase::Lower => drive_flags::CASE_LOWER,
+        NameCase::Preserve => drive_flags::CASE_PRESERVE,
+    };
+    let hide = if hide_dotfiles { drive_flags::HIDE_DOTFILES } else { 0 };
+    mangling | case | hide
+}
+
+/// The 8.3 name the guest sees for host name `name`
+///
+/// `tail` numbers the name among those in its directory that shorten alike,
+/// for `Mangling::NumericTail`; names that fit already are kept as they are
+/// (apart from their case).
+pub fn short_name(name: &str, mangling: Mangling, case: NameCase, tail: u32) -> String {
+    if name == "." || name == ".." {
+        return name.to_string();
+    }
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], Some(&name[dot + 1..])),
+        _ => (name, None),
+    };
+
+    let (mut base, mut lossy) = clean(base, case);
+    let (ext, ext_lossy) = ext.map(|e| clean(e, case)).unwrap_or_default();
+    lossy |= ext_lossy || name.starts_with('.') || base.is_empty() || base.len() > 8 || ext.len() > 3;
+    if base.is_empty() {
+        base.push('_');
+    }
+
+    if lossy {
+        match mangling {
+            Mangling::Truncate => base.truncate(8),
+            Mangling::Hash => {
+                let digits = if case == NameCase::Lower {
+                    b"0123456789abcdefghijklmnopqrstuvwxyz"
+                } else {
+                    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+                };
+                let mut hash = fnv1a(name.as_bytes()) % (36 * 36 * 36);
+                let mut suffix = [b'~'; 4];
+                for digit in suffix[1..].iter_mut().rev() {
+                    *digit = digits[(hash % 36) as usize];
+                    hash /= 36;
+                }
+                base.truncate(4);
+                base.push_str(std::str::from_utf8(&suffix).unwrap());
+            }
+            Mangling::NumericTail => {
+                let suffix = format!("~{}", tail.clamp(1, MAX_TAIL));
+                base.truncate(8 - suffix.len());
+                base.push_str(&suffix);
+            }
+        }
+    }
+
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, &ext[..ext.len().min(3)])
+    }
+}
+
+/// One part of a name with spaces and dots dropped, characters DOS does
+/// not allow replaced with '_', and the case applied; true if anything
+/// was lost
+fn clean(part: &str, case: NameCase) -> (String, bool) {
+    let mut lossy = false;
+    let mut out = String::with_capacity(part.len());
+    // Byte by byte, as the driver sees the name
+    for &b in part.as_bytes() {
+        let c = match b {
+            b' ' | b'.' => {
+                lossy = true;
+                continue;
+            }
+            _ if b.is_ascii_alphanumeric() || ALLOWED.as_bytes().contains(&b) => b as char,
+            _ => {
+                lossy = true;
+                '_'
+            }
+        };
+        out.push(match case {
+            NameCase::Upper => c.to_ascii_uppercase(),
+            NameCase::Lower => c.to_ascii_lowercase(),
+            NameCase::Preserve => c,
+        });
+    }
+    (out, lossy)
+}
+
+/// 32-bit FNV-1a hash
+fn fnv1a(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .fold(0x811c_9dc5, |hash, &b| (hash ^ b as u32).wrapping_mul(0x0100_0193))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_name() {
+        let upper = |name, mangling| short_name(name, mangling, NameCase::Upper, 1);
+        assert_eq!(upper("readme.txt", Mangling::NumericTail), "README.TXT");
+        assert_eq!(upper("Long File Name.html", Mangling::NumericTail), "LONGFI~1.HTM");
+        assert_eq!(upper("Long File Name.html", Mangling::Truncate), "LONGFILE.HTM");
+        assert_eq!(upper(".bashrc", Mangling::NumericTail), "BASHRC~1");
+        assert_eq!(upper("a+b.c", Mangling::Truncate), "A_B.C");
+        assert_eq!(upper("..", Mangling::NumericTail), "..");
+        assert_eq!(short_name("Makefile", Mangling::NumericTail, NameCase::Preserve, 1), "Makefile");
+        assert_eq!(short_name("archive.tar.gz", Mangling::NumericTail, NameCase::Lower, 12), "archi~12.gz");
+
+        let hashed = upper("Long File Name.html", Mangling::Hash);
+        assert_eq!(hashed.len(), 12);
+        assert!(hashed.starts_with("LONG~") && hashed.ends_with(".HTM"));
+        assert_eq!(hashed, upper("Long File Name.html", Mangling::Hash));
+        assert_ne!(hashed, upper("Long File Name 2.html", Mangling::Hash));
+    }
+
+    #[test]
+    fn test_flags() {
+        assert_eq!(flags(Mangling::NumericTail, NameCase::Upper, false), 0);
+        let all = flags(Mangling::Truncate, NameCase::Preserve, true);
+        assert_eq!(all & drive_flags::MANGLE_MASK, drive_flags::MANGLE_TRUNCATE);
+        assert_eq!(all & drive_flags::CASE_MASK, drive_flags::CASE_PRESERVE);
+        assert_ne!(all & drive_flags::HIDE_DOTFILES, 0);
+        for text in ["tail", "hash", "truncate"] {
+            assert_eq!(mangling_name(parse_mangling(text).unwrap()), text);
+        }
+        for text in ["upper", "lower", "preserve"] {
+            assert_eq!(case_name(parse_case(text).unwrap()), text);
+        }
+    }
+}
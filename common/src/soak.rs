@@ -0,0 +1,498 @@
+//! Soak testing: cycle sessions for hours and watch for leaks.
+//!
+//! Each cycle starts a session, mounts the CD-ROM (when an ISO is given),
+//! sends and reads the clipboard, ejects and stops. Every few cycles the
+//! process's open file descriptors and resident memory are sampled; once
+//! the run is over, growth beyond the warm-up sample is reported as a
+//! suspected leak. The report is JSON, rewritten after every sample so a
+//! run cut short still leaves one behind.
+//!
+//! `DriverTarget` runs against the card; `MockTarget` stands in for it,
+//! checking the same state rules, to exercise the frontend side alone.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::driver::DriverHandle;
+use crate::ioctl::{flags, IoctlSessionConfig, SessionState};
+
+/// Operations of one soak cycle, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SoakOp {
+    Start,
+    MountCdrom,
+    SetClipboard,
+    GetClipboard,
+    EjectCdrom,
+    Stop,
+}
+
+impl SoakOp {
+    /// Name used in the report
+    pub fn name(self) -> &'static str {
+        match self {
+            SoakOp::Start => "start",
+            SoakOp::MountCdrom => "mount-cdrom",
+            SoakOp::SetClipboard => "set-clipboard",
+            SoakOp::GetClipboard => "get-clipboard",
+            SoakOp::EjectCdrom => "eject-cdrom",
+            SoakOp::Stop => "stop",
+        }
+    }
+}
+
+/// What a soak test runs against
+pub trait SoakTarget {
+    /// Name recorded in the report
+    fn name(&self) -> &'static str;
+    fn start(&mut self) -> Result<()>;
+    fn stop(&mut self) -> Result<()>;
+    fn mount_cdrom(&mut self, path: &str) -> Result<()>;
+    fn eject_cdrom(&mut self) -> Result<()>;
+    fn set_clipboard(&mut self, text: &str) -> Result<()>;
+    fn get_clipboard(&mut self) -> Result<String>;
+}
+
+/// The SunPCi driver
+///
+/// Sessions start without disks, with the clipboard enabled both ways.
+pub struct DriverTarget {
+    handle: DriverHandle,
+}
+
+impl DriverTarget {
+    /// Open the driver, refusing to disturb a session already running
+    pub fn open() -> Result<Self> {
+        let handle = DriverHandle::open()?;
+        if handle.get_status()?.state != SessionState::Stopped as u32 {
+            bail!("a session is running; stop it before soak testing");
+        }
+        Ok(Self { handle })
+    }
+}
+
+impl SoakTarget for DriverTarget {
+    fn name(&self) -> &'static str {
+        "driver"
+    }
+
+    fn start(&mut self) -> Result<()> {
+        let mut config = IoctlSessionConfig::default();
+        config.flags = flags::CLIPBOARD_ENABLED | flags::CLIPBOARD_TO_GUEST | flags::CLIPBOARD_TO_HOST;
+        self.handle.start_session(&config)
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        self.handle.stop_session()
+    }
+
+    fn mount_cdrom(&mut self, path: &str) -> Result<()> {
+        self.handle.mount_cdrom(path)
+    }
+
+    fn eject_cdrom(&mut self) -> Result<()> {
+        self.handle.eject_cdrom()
+    }
+
+    fn set_clipboard(&mut self, text: &str) -> Result<()> {
+        self.handle.set_clipboard(text)
+    }
+
+    fn get_clipboard(&mut self) -> Result<String> {
+        self.handle.get_clipboard()
+    }
+}
+
+/// In-process stand-in for the driver
+#[derive(Debug, Default)]
+pub struct MockTarget {
+    running: bool,
+    cdrom: Option<String>,
+    clipboard: String,
+}
+
+impl SoakTarget for MockTarget {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn start(&mut self) -> Result<()> {
+        if self.running {
+            bail!("session already running");
+        }
+        self.running = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<()> {
+        if !self.running {
+            bail!("no session running");
+        }
+        self.running = false;
+        self.cdrom = None;
+        Ok(())
+    }
+
+    fn mount_cdrom(&mut self, path: &str) -> Result<()> {
+        if !Path::new(path).exists() {
+            bail!("{} does not exist", path);
+        }
+        self.cdrom = Some(path.to_string());
+        Ok(())
+    }
+
+    fn eject_cdrom(&mut self) -> Result<()> {
+        self.cdrom = None;
+        Ok(())
+    }
+
+    fn set_clipboard(&mut self, text: &str) -> Result<()> {
+        if !self.running {
+            bail!("no session running");
+        }
+        self.clipboard = text.to_string();
+        Ok(())
+    }
+
+    fn get_clipboard(&mut self) -> Result<String> {
+        if !self.running {
+            bail!("no session running");
+        }
+        Ok(self.clipboard.clone())
+    }
+}
+
+/// How long and how a soak test runs
+#[derive(Debug, Clone)]
+pub struct SoakOptions {
+    /// Stop after this long
+    pub duration: Option<Duration>,
+    /// Stop after this many cycles
+    pub cycles: Option<u64>,
+    /// Sample resources every this many cycles
+    pub sample_every: u64,
+    /// ISO mounted each cycle; the CD-ROM is left alone without one
+    pub iso: Option<PathBuf>,
+    /// Report file, rewritten after every sample
+    pub report: Option<PathBuf>,
+}
+
+impl Default for SoakOptions {
+    fn default() -> Self {
+        Self {
+            duration: None,
+            cycles: None,
+            sample_every: 10,
+            iso: None,
+            report: None,
+        }
+    }
+}
+
+/// Successes and failures of one operation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OpCounts {
+    pub ok: u64,
+    pub failed: u64,
+}
+
+/// A failed operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SoakFailure {
+    pub cycle: u64,
+    pub op: SoakOp,
+    pub elapsed_ms: u64,
+    pub error: String,
+}
+
+/// Process resources at one point of the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ResourceSample {
+    /// Cycles completed
+    pub cycle: u64,
+    pub elapsed_ms: u64,
+    pub open_fds: u64,
+    pub rss_kb: u64,
+}
+
+impl ResourceSample {
+    /// Sample this process
+    pub fn take(cycle: u64, elapsed: Duration) -> Result<Self> {
+        Ok(Self {
+            cycle,
+            elapsed_ms: elapsed.as_millis() as u64,
+            open_fds: open_fds()?,
+            rss_kb: rss_kb()?,
+        })
+    }
+}
+
+/// Machine-readable result of a soak test
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SoakReport {
+    /// "driver" or "mock"
+    pub target: String,
+    /// When the run started, in milliseconds since the Unix epoch
+    pub started_ms: u64,
+    pub elapsed_ms: u64,
+    /// Cycles completed
+    pub cycles: u64,
+    pub operations: BTreeMap<&'static str, OpCounts>,
+    /// First failures, up to MAX_FAILURES
+    pub failures: Vec<SoakFailure>,
+    /// Failures past MAX_FAILURES, counted only
+    pub failures_dropped: u64,
+    pub samples: Vec<ResourceSample>,
+    /// Suspected leaks, as sentences
+    pub leaks: Vec<String>,
+    /// No failures and no suspected leaks
+    pub passed: bool,
+}
+
+/// Failures kept in a report
+pub const MAX_FAILURES: usize = 1000;
+
+/// Resident memory growth tolerated past the warm-up sample
+const RSS_SLACK_KB: u64 = 4096;
+
+impl SoakReport {
+    fn record(&mut self, cycle: u64, op: SoakOp, elapsed: Duration, result: Result<()>) {
+        let counts = self.operations.entry(op.name()).or_default();
+        match result {
+            Ok(()) => counts.ok += 1,
+            Err(e) => {
+                counts.failed += 1;
+                if self.failures.len() < MAX_FAILURES {
+                    self.failures.push(SoakFailure {
+                        cycle,
+                        op,
+                        elapsed_ms: elapsed.as_millis() as u64,
+                        error: format!("{:#}", e),
+                    });
+                } else {
+                    self.failures_dropped += 1;
+                }
+            }
+        }
+    }
+
+    /// Fill in `leaks` and `passed` from the samples and failures
+    ///
+    /// The first sample is taken after a cycle has warmed up caches and
+    /// lazily opened files, so growth is measured from it.
+    pub fn finish(&mut self) {
+        self.leaks.clear();
+        if let (Some(first), Some(last)) = (self.samples.first(), self.samples.last()) {
+            if last.open_fds > first.open_fds {
+                self.leaks.push(format!(
+                    "open file descriptors grew from {} to {} over {} cycles",
+                    first.open_fds,
+                    last.open_fds,
+                    last.cycle - first.cycle
+                ));
+            }
+            if last.rss_kb > first.rss_kb + RSS_SLACK_KB.max(first.rss_kb / 10) {
+                self.leaks.push(format!(
+                    "resident memory grew from {} KiB to {} KiB over {} cycles",
+                    first.rss_kb,
+                    last.rss_kb,
+                    last.cycle - first.cycle
+                ));
+            }
+        }
+        self.passed = self.failures.is_empty() && self.failures_dropped == 0 && self.leaks.is_empty();
+    }
+
+    /// Write the report as JSON
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+/// Run cycles until the duration or cycle count is reached
+///
+/// Without either it runs until `keep_going` returns false, which is
+/// asked between cycles (e.g. to stop on Ctrl+C).
+pub fn run(
+    target: &mut dyn SoakTarget,
+    options: &SoakOptions,
+    mut keep_going: impl FnMut(&SoakReport) -> bool,
+) -> Result<SoakReport> {
+    let start = Instant::now();
+    let mut report = SoakReport {
+        target: target.name().to_string(),
+        started_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        ..Default::default()
+    };
+    let iso = options.iso.as_ref().map(|p| p.to_string_lossy().into_owned());
+    let sample_every = options.sample_every.max(1);
+
+    loop {
+        if options.cycles.is_some_and(|n| report.cycles >= n)
+            || options.duration.is_some_and(|d| start.elapsed() >= d)
+            || !keep_going(&report)
+        {
+            break;
+        }
+
+        let cycle = report.cycles + 1;
+        let started = target.start();
+        let running = started.is_ok();
+        report.record(cycle, SoakOp::Start, start.elapsed(), started);
+        if running {
+            if let Some(iso) = &iso {
+                let result = target.mount_cdrom(iso);
+                report.record(cycle, SoakOp::MountCdrom, start.elapsed(), result);
+            }
+            let text = format!("rising-sun soak cycle {}\r\n", cycle);
+            let result = target.set_clipboard(&text);
+            report.record(cycle, SoakOp::SetClipboard, start.elapsed(), result);
+            let result = target.get_clipboard().map(|_| ());
+            report.record(cycle, SoakOp::GetClipboard, start.elapsed(), result);
+            if iso.is_some() {
+                let result = target.eject_cdrom();
+                report.record(cycle, SoakOp::EjectCdrom, start.elapsed(), result);
+            }
+            let result = target.stop();
+            report.record(cycle, SoakOp::Stop, start.elapsed(), result);
+        }
+        report.cycles = cycle;
+
+        if cycle == 1 || cycle.is_multiple_of(sample_every) {
+            report.samples.push(ResourceSample::take(cycle, start.elapsed())?);
+            report.elapsed_ms = start.elapsed().as_millis() as u64;
+            report.finish();
+            if let Some(path) = &options.report {
+                report.save(path)?;
+            }
+        }
+    }
+
+    report.elapsed_ms = start.elapsed().as_millis() as u64;
+    if report.samples.last().is_none_or(|s| s.cycle != report.cycles) {
+        report.samples.push(ResourceSample::take(report.cycles, start.elapsed())?);
+    }
+    report.finish();
+    if let Some(path) = &options.report {
+        report.save(path)?;
+    }
+    Ok(report)
+}
+
+/// Number of file descriptors this process has open
+fn open_fds() -> Result<u64> {
+    Ok(fs::read_dir("/proc/self/fd")?.count() as u64)
+}
+
+/// Resident memory of this process in KiB
+fn rss_kb() -> Result<u64> {
+    let status = fs::read_to_string("/proc/self/status")?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("no VmRSS in /proc/self/status"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mock whose clipboard read fails every third cycle
+    struct Flaky {
+        mock: MockTarget,
+        reads: u64,
+    }
+
+    impl SoakTarget for Flaky {
+        fn name(&self) -> &'static str {
+            "flaky"
+        }
+        fn start(&mut self) -> Result<()> {
+            self.mock.start()
+        }
+        fn stop(&mut self) -> Result<()> {
+            self.mock.stop()
+        }
+        fn mount_cdrom(&mut self, path: &str) -> Result<()> {
+            self.mock.mount_cdrom(path)
+        }
+        fn eject_cdrom(&mut self) -> Result<()> {
+            self.mock.eject_cdrom()
+        }
+        fn set_clipboard(&mut self, text: &str) -> Result<()> {
+            self.mock.set_clipboard(text)
+        }
+        fn get_clipboard(&mut self) -> Result<String> {
+            self.reads += 1;
+            if self.reads.is_multiple_of(3) {
+                bail!("timed out");
+            }
+            self.mock.get_clipboard()
+        }
+    }
+
+    #[test]
+    fn test_soak_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let iso = dir.path().join("test.iso");
+        fs::write(&iso, [0u8; 2048]).unwrap();
+        let report_path = dir.path().join("soak.json");
+        let options = SoakOptions {
+            cycles: Some(6),
+            sample_every: 2,
+            iso: Some(iso),
+            report: Some(report_path.clone()),
+            ..Default::default()
+        };
+
+        let report = run(&mut MockTarget::default(), &options, |_| true).unwrap();
+        assert_eq!(report.target, "mock");
+        assert_eq!(report.cycles, 6);
+        assert_eq!(report.operations["mount-cdrom"], OpCounts { ok: 6, failed: 0 });
+        assert_eq!(report.operations.len(), 6);
+        assert!(report.failures.is_empty());
+        // After the first cycle, then every second one
+        let cycles: Vec<u64> = report.samples.iter().map(|s| s.cycle).collect();
+        assert_eq!(cycles, [1, 2, 4, 6]);
+        let saved: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report_path).unwrap()).unwrap();
+        assert_eq!(saved["cycles"], 6);
+
+        let options = SoakOptions { cycles: Some(6), ..Default::default() };
+        let mut flaky = Flaky { mock: MockTarget::default(), reads: 0 };
+        let report = run(&mut flaky, &options, |_| true).unwrap();
+        assert_eq!(report.operations["get-clipboard"], OpCounts { ok: 4, failed: 2 });
+        assert!(!report.operations.contains_key("mount-cdrom"));
+        assert_eq!(report.failures[0].cycle, 3);
+        assert_eq!(report.failures[0].op, SoakOp::GetClipboard);
+        assert!(!report.passed);
+    }
+
+    #[test]
+    fn test_soak_leaks() {
+        let sample = |cycle, open_fds, rss_kb| ResourceSample { cycle, elapsed_ms: 0, open_fds, rss_kb };
+        let mut report = SoakReport {
+            samples: vec![sample(1, 10, 20_000), sample(100, 10, 22_000)],
+            ..Default::default()
+        };
+        report.finish();
+        assert!(report.passed);
+
+        report.samples.push(sample(200, 12, 40_000));
+        report.finish();
+        assert_eq!(report.leaks.len(), 2);
+        assert!(report.leaks[0].starts_with("open file descriptors grew from 10 to 12"));
+        assert!(!report.passed);
+    }
+}
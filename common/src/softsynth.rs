@@ -0,0 +1,388 @@
+//! Built-in synthesizer for the guest's MIDI music.
+//!
+//! Not every host has a General MIDI synthesizer on its ALSA sequencer, so
+//! the frontend can play the guest's MPU-401 output here instead. Each
+//! General MIDI instrument family gets a simple oscillator (sine, triangle,
+//! square or sawtooth) and an envelope shaped like it: pianos and guitars
+//! die away, organs and strings hold. Channel 10 plays percussion from
+//! noise. It sounds more like an early sound card's FM than a wavetable,
+//! but it makes every note of a DOS game's score heard without anything
+//! installed on the host.
+
+use crate::midi::MidiMessage;
+
+/// Most notes sounding at once; the oldest is cut off for a new one
+const MAX_VOICES: usize = 32;
+
+/// Overall level, leaving room for chords before clipping
+const MASTER_GAIN: f32 = 0.2;
+
+/// Channel played as percussion (channel 10, counted from 1)
+const DRUM_CHANNEL: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Wave {
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+    Noise,
+}
+
+/// How an instrument sounds: its oscillator and envelope, in seconds
+#[derive(Debug, Clone, Copy)]
+struct Patch {
+    wave: Wave,
+    attack: f32,
+    decay: f32,
+    /// Level held after the decay; 0 lets the note die away
+    sustain: f32,
+    release: f32,
+}
+
+/// The patch for a General MIDI program, by instrument family
+fn patch(program: u8) -> Patch {
+    let (wave, attack, decay, sustain, release) = match program / 8 {
+        0 => (Wave::Triangle, 0.002, 1.5, 0.0, 0.2),     // Piano
+        1 => (Wave::Sine, 0.001, 0.8, 0.0, 0.3),         // Chromatic percussion
+        2 => (Wave::Square, 0.01, 0.1, 0.8, 0.05),       // Organ
+        3 => (Wave::Saw, 0.002, 1.0, 0.0, 0.15),         // Guitar
+        4 => (Wave::Triangle, 0.005, 0.6, 0.3, 0.1),     // Bass
+        5 | 6 => (Wave::Saw, 0.08, 0.2, 0.8, 0.3),       // Strings, ensemble
+        7 => (Wave::Saw, 0.03, 0.2, 0.7, 0.1),           // Brass
+        8 | 9 => (Wave::Square, 0.03, 0.2, 0.6, 0.1),    // Reed, pipe
+        10 => (Wave::Square, 0.005, 0.2, 0.7, 0.1),      // Synth lead
+        11 => (Wave::Triangle, 0.3, 0.5, 0.7, 0.6),      // Synth pad
+        12 => (Wave::Saw, 0.05, 0.5, 0.5, 0.5),          // Synth effects
+        13 => (Wave::Triangle, 0.005, 0.8, 0.2, 0.2),    // Ethnic
+        14 => (Wave::Sine, 0.001, 0.3, 0.0, 0.1),        // Percussive
+        _ => (Wave::Noise, 0.05, 0.5, 0.3, 0.4),         // Sound effects
+    };
+    Patch { wave, attack, decay, sustain, release }
+}
+
+/// The patch for a percussion key: bass drums are a low thud, the rest
+/// noise, the cymbals ringing longest
+fn drum_patch(key: u8) -> (Patch, f32) {
+    match key {
+        35 | 36 => (Patch { wave: Wave::Sine, attack: 0.001, decay: 0.25, sustain: 0.0, release: 0.05 }, 55.0),
+        41 | 43 | 45 | 47 | 48 | 50 => {
+            (Patch { wave: Wave::Sine, attack: 0.001, decay: 0.3, sustain: 0.0, release: 0.05 }, 80.0 + (key - 41) as f32 * 12.0)
+        }
+        49 | 51 | 52 | 55 | 57 | 59 => {
+            (Patch { wave: Wave::Noise, attack: 0.001, decay: 1.2, sustain: 0.0, release: 0.3 }, 0.0)
+        }
+        _ => (Patch { wave: Wave::Noise, attack: 0.001, decay: 0.12, sustain: 0.0, release: 0.05 }, 0.0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    Done,
+}
+
+#[derive(Debug, Clone)]
+struct Voice {
+    channel: u8,
+    key: u8,
+    wave: Wave,
+    /// Oscillator position, in cycles
+    phase: f32,
+    /// Cycles per sample before pitch bend
+    step: f32,
+    velocity: f32,
+    stage: Stage,
+    level: f32,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    /// Released while the sustain pedal was down
+    held: bool,
+}
+
+impl Voice {
+    /// Next envelope level
+    fn envelope(&mut self) -> f32 {
+        match self.stage {
+            Stage::Attack => {
+                self.level += self.attack;
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= self.decay;
+                if self.level <= self.sustain {
+                    self.level = self.sustain;
+                    self.stage = if self.sustain > 0.0 { Stage::Sustain } else { Stage::Done };
+                }
+            }
+            Stage::Sustain => {}
+            Stage::Release => {
+                self.level -= self.release;
+                if self.level <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Done;
+                }
+            }
+            Stage::Done => {}
+        }
+        self.level
+    }
+}
+
+/// Controller state of one MIDI channel
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    program: u8,
+    volume: f32,
+    expression: f32,
+    /// 0 is left, 1 right
+    pan: f32,
+    /// Pitch factor from the bend wheel
+    bend: f32,
+    sustain: bool,
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            program: 0,
+            volume: 100.0 / 127.0,
+            expression: 1.0,
+            pan: 0.5,
+            bend: 1.0,
+            sustain: false,
+        }
+    }
+}
+
+/// A small General MIDI synthesizer rendering 16-bit stereo
+#[derive(Debug, Clone)]
+pub struct SoftSynth {
+    sample_rate: f32,
+    channels: [Channel; 16],
+    voices: Vec<Voice>,
+    /// Noise generator state
+    noise: u32,
+}
+
+impl SoftSynth {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate: sample_rate.max(1) as f32,
+            channels: [Channel::default(); 16],
+            voices: Vec::with_capacity(MAX_VOICES),
+            noise: 0x1234_5678,
+        }
+    }
+
+    /// Number of notes sounding
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    /// Silence every note and put every controller back
+    pub fn reset(&mut self) {
+        self.voices.clear();
+        self.channels = [Channel::default(); 16];
+    }
+
+    /// Act on a message from the guest
+    pub fn handle(&mut self, message: &MidiMessage) {
+        match *message {
+            MidiMessage::NoteOn { channel, key, velocity } => self.note_on(channel, key, velocity),
+            MidiMessage::NoteOff { channel, key, .. } => self.note_off(channel, key),
+            MidiMessage::ControlChange { channel, controller, value } => self.control(channel, controller, value),
+            MidiMessage::ProgramChange { channel, program } => self.channels[channel as usize & 15].program = program,
+            MidiMessage::PitchBend { channel, value } => {
+                // Two semitones either way
+                let semitones = (value as f32 - 8192.0) / 8192.0 * 2.0;
+                self.channels[channel as usize & 15].bend = 2f32.powf(semitones / 12.0);
+            }
+            // GM System On and GS Reset
+            MidiMessage::SysEx(ref bytes) => {
+                let gm_on = bytes.starts_with(&[0xF0, 0x7E]) && bytes.get(3..5) == Some(&[0x09, 0x01]);
+                let gs_reset = bytes.starts_with(&[0xF0, 0x41]) && bytes.get(5..9) == Some(&[0x40, 0x00, 0x7F, 0x00]);
+                if gm_on || gs_reset {
+                    self.reset();
+                }
+            }
+            // System reset
+            MidiMessage::System(ref bytes) if bytes == &[0xFF] => self.reset(),
+            _ => {}
+        }
+    }
+
+    fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        let channel = channel & 15;
+        let (patch, freq) = if channel == DRUM_CHANNEL {
+            drum_patch(key)
+        } else {
+            let freq = 440.0 * 2f32.powf((key as f32 - 69.0) / 12.0);
+            (patch(self.channels[channel as usize].program), freq)
+        };
+        // A key struck again starts over
+        self.voices.retain(|v| !(v.channel == channel && v.key == key));
+        if self.voices.len() >= MAX_VOICES {
+            self.voices.remove(0);
+        }
+        let rate = self.sample_rate;
+        let per_sample = |seconds: f32| 1.0 / (seconds * rate).max(1.0);
+        self.voices.push(Voice {
+            channel,
+            key,
+            wave: patch.wave,
+            phase: 0.0,
+            step: freq / rate,
+            velocity: velocity as f32 / 127.0,
+            stage: Stage::Attack,
+            level: 0.0,
+            attack: per_sample(patch.attack),
+            decay: per_sample(patch.decay) * (1.0 - patch.sustain),
+            sustain: patch.sustain,
+            release: per_sample(patch.release),
+            held: false,
+        });
+    }
+
+    fn note_off(&mut self, channel: u8, key: u8) {
+        let sustain = self.channels[channel as usize & 15].sustain;
+        for voice in self.voices.iter_mut().filter(|v| v.channel == channel && v.key == key) {
+            if sustain {
+                voice.held = true;
+            } else if voice.stage != Stage::Done {
+                voice.stage = Stage::Release;
+            }
+        }
+    }
+
+    fn control(&mut self, channel: u8, controller: u8, value: u8) {
+        let channel = channel & 15;
+        let state = &mut self.channels[channel as usize];
+        let value_f = value as f32 / 127.0;
+        match controller {
+            7 => state.volume = value_f,
+            10 => state.pan = value_f,
+            11 => state.expression = value_f,
+            64 => {
+                state.sustain = value >= 64;
+                if !state.sustain {
+                    for voice in self.voices.iter_mut().filter(|v| v.channel == channel && v.held) {
+                        voice.held = false;
+                        if voice.stage != Stage::Done {
+                            voice.stage = Stage::Release;
+                        }
+                    }
+                }
+            }
+            // All sound off
+            120 => self.voices.retain(|v| v.channel != channel),
+            // Reset all controllers
+            121 => {
+                *state = Channel {
+                    program: state.program,
+                    ..Channel::default()
+                }
+            }
+            // All notes off
+            123..=127 => {
+                for voice in self.voices.iter_mut().filter(|v| v.channel == channel && v.stage != Stage::Done) {
+                    voice.stage = Stage::Release;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Render interleaved stereo into `out`, overwriting it
+    pub fn render(&mut self, out: &mut [i16]) {
+        for frame in out.chunks_exact_mut(2) {
+            let (mut left, mut right) = (0.0f32, 0.0f32);
+            for voice in &mut self.voices {
+                let channel = &self.channels[voice.channel as usize];
+                let level = voice.envelope();
+                if level <= 0.0 && voice.stage == Stage::Done {
+                    continue;
+                }
+                let sample = match voice.wave {
+                    Wave::Sine => (voice.phase * std::f32::consts::TAU).sin(),
+                    Wave::Triangle => 4.0 * (voice.phase - (voice.phase + 0.5).floor()).abs() - 1.0,
+                    Wave::Square => if voice.phase < 0.5 { 0.6 } else { -0.6 },
+                    Wave::Saw => (2.0 * voice.phase - 1.0) * 0.7,
+                    Wave::Noise => {
+                        // xorshift
+                        self.noise ^= self.noise << 13;
+                        self.noise ^= self.noise >> 17;
+                        self.noise ^= self.noise << 5;
+                        (self.noise as i32) as f32 / i32::MAX as f32 * 0.5
+                    }
+                };
+                voice.phase += voice.step * channel.bend;
+                voice.phase -= voice.phase.floor();
+
+                let sample = sample * level * voice.velocity * channel.volume * channel.expression;
+                left += sample * (1.0 - channel.pan).min(0.5) * 2.0;
+                right += sample * channel.pan.min(0.5) * 2.0;
+            }
+            frame[0] = ((left * MASTER_GAIN).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            frame[1] = ((right * MASTER_GAIN).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        }
+        self.voices.retain(|v| v.stage != Stage::Done);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loudness(synth: &mut SoftSynth, frames: usize) -> i32 {
+        let mut out = vec![0i16; frames * 2];
+        synth.render(&mut out);
+        out.iter().map(|s| (*s as i32).abs()).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn test_softsynth_notes() {
+        let mut synth = SoftSynth::new(22050);
+        assert_eq!(loudness(&mut synth, 1000), 0);
+
+        // An organ holds until released, then dies away
+        synth.handle(&MidiMessage::ProgramChange { channel: 0, program: 16 });
+        synth.handle(&MidiMessage::NoteOn { channel: 0, key: 60, velocity: 127 });
+        assert!(loudness(&mut synth, 2205) > 1000);
+        assert!(loudness(&mut synth, 22050) > 1000);
+        synth.handle(&MidiMessage::NoteOff { channel: 0, key: 60, velocity: 64 });
+        loudness(&mut synth, 2205);
+        assert_eq!(synth.active_voices(), 0);
+
+        // The sustain pedal holds a released note until it comes up
+        synth.handle(&MidiMessage::ControlChange { channel: 0, controller: 64, value: 127 });
+        synth.handle(&MidiMessage::NoteOn { channel: 0, key: 64, velocity: 100 });
+        synth.handle(&MidiMessage::NoteOff { channel: 0, key: 64, velocity: 64 });
+        assert!(loudness(&mut synth, 4410) > 1000);
+        synth.handle(&MidiMessage::ControlChange { channel: 0, controller: 64, value: 0 });
+        loudness(&mut synth, 2205);
+        assert_eq!(synth.active_voices(), 0);
+
+        // A snare on the drum channel dies away by itself, panned hard left
+        synth.handle(&MidiMessage::ControlChange { channel: 9, controller: 10, value: 0 });
+        synth.handle(&MidiMessage::NoteOn { channel: 9, key: 38, velocity: 127 });
+        let mut out = vec![0i16; 2000];
+        synth.render(&mut out);
+        assert!(out.chunks(2).any(|f| f[0] != 0) && out.chunks(2).all(|f| f[1] == 0));
+        loudness(&mut synth, 22050);
+        assert_eq!(synth.active_voices(), 0);
+
+        // A GM reset silences everything
+        synth.handle(&MidiMessage::NoteOn { channel: 0, key: 60, velocity: 127 });
+        synth.handle(&MidiMessage::SysEx(vec![0xF0, 0x7E, 0x7F, 0x09, 0x01, 0xF7]));
+        assert_eq!(synth.active_voices(), 0);
+    }
+}
@@ -0,0 +1,350 @@
+//! Startup phase timings and work deferred off the UI thread.
+//!
+//! The frontend marks the phases of its start (Qt setup, loading the QML,
+//! the first frame on screen) and times the controllers created during
+//! them, then logs where the time went. Slow probes such as opening the
+//! driver or listing the host's network interfaces run as `Deferred` work
+//! on a thread of their own, so they do not hold up the first frame; the UI
+//! collects their result when it is ready.
+
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+/// Time a cold start should stay under
+pub const STARTUP_BUDGET: Duration = Duration::from_secs(1);
+
+/// A timed stretch of the startup, relative to its beginning
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    pub name: String,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+impl Span {
+    /// When the span ended, relative to the beginning of the startup
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+}
+
+/// Timings of one startup
+///
+/// Phases follow each other: `mark` ends the current one. Spans record work
+/// done during a phase (creating a controller) or beside it (a deferred
+/// probe), and are reported under the phase they started in.
+#[derive(Debug, Clone)]
+pub struct StartupProfile {
+    origin: Instant,
+    last_mark: Duration,
+    phases: Vec<Span>,
+    spans: Vec<Span>,
+    finished: bool,
+}
+
+impl Default for StartupProfile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartupProfile {
+    /// Start timing now
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// Start timing at `origin`
+    pub fn starting_at(origin: Instant) -> Self {
+        Self {
+            origin,
+            last_mark: Duration::ZERO,
+            phases: Vec::new(),
+            spans: Vec::new(),
+            finished: false,
+        }
+    }
+
+    fn offset(&self, at: Instant) -> Duration {
+        at.saturating_duration_since(self.origin)
+    }
+
+    /// End the current phase now, naming it
+    pub fn mark(&mut self, name: &str) {
+        self.mark_at(name, Instant::now());
+    }
+
+    /// End the current phase at `now`, naming it
+    pub fn mark_at(&mut self, name: &str, now: Instant) {
+        if self.finished {
+            return;
+        }
+        let end = self.offset(now).max(self.last_mark);
+        self.phases.push(Span {
+            name: name.to_string(),
+            start: self.last_mark,
+            duration: end - self.last_mark,
+        });
+        self.last_mark = end;
+    }
+
+    /// Record work that started at `start` and took `duration`
+    pub fn record(&mut self, name: &str, start: Instant, duration: Duration) {
+        self.spans.push(Span {
+            name: name.to_string(),
+            start: self.offset(start),
+            duration,
+        });
+    }
+
+    /// Stop adding phases; spans may still be recorded
+    pub fn finish(&mut self) {
+        self.finished = true;
+    }
+
+    /// Whether `finish` was called
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Time until the end of the last phase
+    pub fn total(&self) -> Duration {
+        self.last_mark
+    }
+
+    /// Whether the startup took longer than `STARTUP_BUDGET`
+    pub fn over_budget(&self) -> bool {
+        self.total() > STARTUP_BUDGET
+    }
+
+    /// Phases in order
+    pub fn phases(&self) -> &[Span] {
+        &self.phases
+    }
+
+    /// Recorded spans in the order they were recorded
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The `count` longest spans, longest first
+    pub fn slowest(&self, count: usize) -> Vec<&Span> {
+        let mut spans: Vec<&Span> = self.spans.iter().collect();
+        spans.sort_by_key(|s| std::cmp::Reverse(s.duration));
+        spans.truncate(count);
+        spans
+    }
+
+    /// One line, e.g. "Started in 640 ms (qt 80 ms, qml 420 ms, first frame 140 ms)"
+    pub fn summary(&self) -> String {
+        let phases: Vec<String> = self
+            .phases
+            .iter()
+            .map(|p| format!("{} {}", p.name, format_ms(p.duration)))
+            .collect();
+        format!("Started in {} ({})", format_ms(self.total()), phases.join(", "))
+    }
+
+    /// Phases with the spans that started in each, one per line
+    ///
+    /// Spans that ran on past the end of the startup, like a slow driver
+    /// probe, are marked as such.
+    pub fn report(&self) -> String {
+        let mut lines = vec![format!(
+            "Startup took {} (budget {})",
+            format_ms(self.total()),
+            format_ms(STARTUP_BUDGET)
+        )];
+        let mut spans: Vec<&Span> = self.spans.iter().collect();
+        spans.sort_by_key(|s| s.start);
+        let mut spans = spans.into_iter().peekable();
+
+        for (i, phase) in self.phases.iter().enumerate() {
+            lines.push(format_line(phase, 0, false));
+            let last = i + 1 == self.phases.len();
+            while let Some(span) = spans.next_if(|s| last || s.start < phase.end()) {
+                lines.push(format_line(span, 2, span.end() > self.total()));
+            }
+        }
+        // Recorded before any phase ended
+        for span in spans {
+            lines.push(format_line(span, 2, span.end() > self.total()));
+        }
+        lines.join("\n")
+    }
+}
+
+fn format_line(span: &Span, indent: usize, past_end: bool) -> String {
+    format!(
+        "{:>8} {:>9}  {}{}{}",
+        format_ms(span.start),
+        format!("+{}", format_ms(span.duration)),
+        " ".repeat(indent),
+        span.name,
+        if past_end { " (after startup)" } else { "" }
+    )
+}
+
+/// Milliseconds, with a decimal below 10 ms
+pub fn format_ms(duration: Duration) -> String {
+    let ms = duration.as_secs_f64() * 1000.0;
+    if ms < 10.0 {
+        format!("{:.1} ms", ms)
+    } else {
+        format!("{:.0} ms", ms)
+    }
+}
+
+/// Work running on a thread of its own, collected when it is ready
+pub struct Deferred<T> {
+    name: String,
+    started: Instant,
+    receiver: Option<Receiver<(T, Duration)>>,
+    took: Option<Duration>,
+}
+
+impl<T: Send + 'static> Deferred<T> {
+    /// Run `work` on a new thread called `name`
+    pub fn spawn(name: &str, work: impl FnOnce() -> T + Send + 'static) -> Self {
+        let started = Instant::now();
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                let value = work();
+                let _ = sender.send((value, start.elapsed()));
+            })
+            .expect("failed to spawn deferred work");
+        Self {
+            name: name.to_string(),
+            started,
+            receiver: Some(receiver),
+            took: None,
+        }
+    }
+}
+
+impl<T> Deferred<T> {
+    /// Name the work was started under
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// When the work was started
+    pub fn started(&self) -> Instant {
+        self.started
+    }
+
+    /// How long the work took, once its result was collected
+    pub fn took(&self) -> Option<Duration> {
+        self.took
+    }
+
+    /// The result if the work has finished, without waiting
+    ///
+    /// None while it is still running (or once the result was taken); an
+    /// error if the work panicked.
+    pub fn try_take(&mut self) -> Option<Result<T>> {
+        let received = self.receiver.as_ref()?.try_recv();
+        match received {
+            Ok(done) => Some(Ok(self.collect(done))),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(self.failed())),
+        }
+    }
+
+    /// Wait for the result
+    pub fn wait(&mut self) -> Result<T> {
+        let Some(receiver) = self.receiver.as_ref() else {
+            return Err(anyhow!("{} was already collected", self.name));
+        };
+        match receiver.recv() {
+            Ok(done) => Ok(self.collect(done)),
+            Err(_) => Err(self.failed()),
+        }
+    }
+
+    fn collect(&mut self, (value, took): (T, Duration)) -> T {
+        self.receiver = None;
+        self.took = Some(took);
+        value
+    }
+
+    fn failed(&mut self) -> anyhow::Error {
+        self.receiver = None;
+        anyhow!("{} did not finish", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn test_phases_and_spans() {
+        let origin = Instant::now();
+        let mut profile = StartupProfile::starting_at(origin);
+        profile.mark_at("qt", origin + ms(80));
+        profile.record("SessionController", origin + ms(100), ms(12));
+        profile.record("driver probe", origin + ms(90), ms(900));
+        profile.mark_at("qml", origin + ms(500));
+        profile.mark_at("first frame", origin + ms(640));
+        profile.finish();
+        // Phases no longer move once finished
+        profile.mark_at("late", origin + ms(2000));
+
+        assert_eq!(profile.total(), ms(640));
+        assert!(!profile.over_budget());
+        assert_eq!(profile.phases().len(), 3);
+        assert_eq!(profile.phases()[1].start, ms(80));
+        assert_eq!(profile.phases()[1].duration, ms(420));
+        assert_eq!(profile.slowest(1)[0].name, "driver probe");
+        assert_eq!(
+            profile.summary(),
+            "Started in 640 ms (qt 80 ms, qml 420 ms, first frame 140 ms)"
+        );
+
+        let report = profile.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "Startup took 640 ms (budget 1000 ms)");
+        assert!(lines[1].ends_with("  qt"));
+        assert!(lines[2].ends_with("  qml"));
+        // Spans sit under the phase they started in, by start time
+        assert!(lines[3].ends_with("    driver probe (after startup)"));
+        assert!(lines[4].ends_with("    SessionController"));
+        assert!(lines[5].ends_with("  first frame"));
+    }
+
+    #[test]
+    fn test_format_ms() {
+        assert_eq!(format_ms(Duration::from_micros(1500)), "1.5 ms");
+        assert_eq!(format_ms(ms(12)), "12 ms");
+        assert_eq!(format_ms(ms(1250)), "1250 ms");
+    }
+
+    #[test]
+    fn test_deferred() {
+        let (go, wait) = mpsc::channel::<()>();
+        let mut deferred = Deferred::spawn("test-deferred", move || {
+            let _ = wait.recv();
+            42
+        });
+        assert!(deferred.try_take().is_none());
+        go.send(()).unwrap();
+        assert_eq!(deferred.wait().unwrap(), 42);
+        assert!(deferred.took().is_some());
+        // Collected once
+        assert!(deferred.try_take().is_none());
+        assert!(deferred.wait().is_err());
+
+        let mut panicked: Deferred<u32> = Deferred::spawn("test-panics", || panic!("probe failed"));
+        assert!(panicked.wait().is_err());
+    }
+}
@@ -0,0 +1,334 @@
+//! Background task pool for long-running operations.
+//!
+//! Disk creation, conversion and similar jobs run on a small pool of worker
+//! threads instead of the UI thread. Each submitted task gets a `TaskHandle`
+//! that can be polled for status and progress, or used to request
+//! cancellation. Jobs report through the task's `ProgressReporter`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::progress::{Cancelled, ProgressReporter, ProgressSnapshot};
+
+/// Identifier of a submitted task
+pub type TaskId = u64;
+
+/// Lifecycle state of a task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Waiting for a free worker
+    Queued,
+    /// Currently executing
+    Running,
+    /// Finished successfully
+    Completed,
+    /// Finished with an error
+    Failed(String),
+    /// Stopped at the user's request
+    Cancelled,
+}
+
+impl TaskStatus {
+    /// Whether the task has stopped (successfully or not)
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed(_) | Self::Cancelled)
+    }
+}
+
+/// State shared between a task's handle and the worker
+#[derive(Default)]
+struct TaskShared {
+    name: String,
+    progress: ProgressReporter,
+    status: Mutex<Option<TaskStatus>>,
+    error: Mutex<Option<Arc<anyhow::Error>>>,
+}
+
+impl TaskShared {
+    fn set_status(&self, status: TaskStatus) {
+        if let Ok(mut s) = self.status.lock() {
+            *s = Some(status);
+        }
+    }
+}
+
+/// Handle to a submitted task
+#[derive(Clone)]
+pub struct TaskHandle {
+    id: TaskId,
+    shared: Arc<TaskShared>,
+}
+
+impl TaskHandle {
+    /// Task identifier
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
+    /// Human-readable task name
+    pub fn name(&self) -> &str {
+        &self.shared.name
+    }
+
+    /// Request cancellation (the job stops at its next check)
+    pub fn cancel(&self) {
+        self.shared.progress.cancel();
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.shared.progress.is_cancelled()
+    }
+
+    /// Current progress of the job
+    pub fn progress(&self) -> ProgressSnapshot {
+        self.shared.progress.snapshot()
+    }
+
+    /// The error a failed job returned, for callers that need more than
+    /// the message in `TaskStatus::Failed`
+    pub fn error(&self) -> Option<Arc<anyhow::Error>> {
+        self.shared.error.lock().ok().and_then(|e| e.clone())
+    }
+
+    /// Current status
+    pub fn status(&self) -> TaskStatus {
+        self.shared
+            .status
+            .lock()
+            .ok()
+            .and_then(|s| s.clone())
+            .unwrap_or(TaskStatus::Queued)
+    }
+}
+
+type Job = Box<dyn FnOnce(&ProgressReporter) -> anyhow::Result<()> + Send>;
+
+/// Unit of work queued for the workers
+type QueuedJob = (Arc<TaskShared>, Job);
+
+/// Fixed-size pool of worker threads running submitted tasks in order
+///
+/// The workers start with the first submitted task, so a pool that is
+/// never used costs no threads.
+pub struct TaskManager {
+    sender: Option<Sender<QueuedJob>>,
+    receiver: Arc<Mutex<Receiver<QueuedJob>>>,
+    size: usize,
+    workers: Mutex<Vec<JoinHandle<()>>>,
+    tasks: Mutex<Vec<TaskHandle>>,
+    next_id: AtomicU64,
+}
+
+impl TaskManager {
+    /// Create a pool with the given number of worker threads (at least one)
+    pub fn new(workers: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<QueuedJob>();
+        Self {
+            sender: Some(sender),
+            receiver: Arc::new(Mutex::new(receiver)),
+            size: workers.max(1),
+            workers: Mutex::new(Vec::new()),
+            tasks: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Start the workers if they are not running yet
+    fn start_workers(&self) {
+        let Ok(mut workers) = self.workers.lock() else {
+            return;
+        };
+        if !workers.is_empty() {
+            return;
+        }
+        for i in 0..self.size {
+            let receiver = Arc::clone(&self.receiver);
+            workers.push(
+                std::thread::Builder::new()
+                    .name(format!("task-worker-{}", i))
+                    .spawn(move || worker_loop(receiver))
+                    .expect("failed to spawn task worker"),
+            );
+        }
+    }
+
+    /// Submit a job; it runs as soon as a worker is free
+    pub fn spawn<F>(&self, name: &str, job: F) -> TaskHandle
+    where
+        F: FnOnce(&ProgressReporter) -> anyhow::Result<()> + Send + 'static,
+    {
+        let shared = Arc::new(TaskShared {
+            name: name.to_string(),
+            ..Default::default()
+        });
+        shared.set_status(TaskStatus::Queued);
+
+        let handle = TaskHandle {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            shared: Arc::clone(&shared),
+        };
+
+        self.start_workers();
+        let queued = self
+            .sender
+            .as_ref()
+            .is_some_and(|s| s.send((shared, Box::new(job))).is_ok());
+        if !queued {
+            handle.shared.set_status(TaskStatus::Failed("task pool shut down".to_string()));
+        }
+
+        if let Ok(mut tasks) = self.tasks.lock() {
+            tasks.push(handle.clone());
+        }
+        handle
+    }
+
+    /// Look up a task by id
+    pub fn get(&self, id: TaskId) -> Option<TaskHandle> {
+        self.tasks.lock().ok()?.iter().find(|t| t.id == id).cloned()
+    }
+
+    /// All tracked tasks (finished ones stay until `take_finished`)
+    pub fn tasks(&self) -> Vec<TaskHandle> {
+        self.tasks.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    /// Number of tasks that are queued or running
+    pub fn active_count(&self) -> usize {
+        self.tasks()
+            .iter()
+            .filter(|t| !t.status().is_finished())
+            .count()
+    }
+
+    /// Request cancellation of a task; returns false if it is unknown
+    pub fn cancel(&self, id: TaskId) -> bool {
+        match self.get(id) {
+            Some(task) => {
+                task.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove and return all finished tasks
+    pub fn take_finished(&self) -> Vec<TaskHandle> {
+        let Ok(mut tasks) = self.tasks.lock() else {
+            return Vec::new();
+        };
+        let (finished, active): (Vec<_>, Vec<_>) =
+            tasks.drain(..).partition(|t| t.status().is_finished());
+        *tasks = active;
+        finished
+    }
+}
+
+impl Drop for TaskManager {
+    fn drop(&mut self) {
+        for task in self.tasks() {
+            task.cancel();
+        }
+        // Closing the channel stops the workers once the queue drains
+        self.sender = None;
+        let workers = match self.workers.get_mut() {
+            Ok(workers) => std::mem::take(workers),
+            Err(e) => std::mem::take(e.into_inner()),
+        };
+        for worker in workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Worker thread body: run jobs until the channel closes
+fn worker_loop(receiver: Arc<Mutex<Receiver<QueuedJob>>>) {
+    loop {
+        let next = match receiver.lock() {
+            Ok(rx) => rx.recv(),
+            Err(_) => return,
+        };
+        let Ok((shared, job)) = next else {
+            return;
+        };
+
+        let progress = &shared.progress;
+        if progress.is_cancelled() {
+            shared.set_status(TaskStatus::Cancelled);
+            continue;
+        }
+
+        shared.set_status(TaskStatus::Running);
+        let status = match job(progress) {
+            Ok(()) => TaskStatus::Completed,
+            Err(e) if e.is::<Cancelled>() || progress.is_cancelled() => TaskStatus::Cancelled,
+            Err(e) => {
+                let status = TaskStatus::Failed(format!("{:#}", e));
+                if let Ok(mut error) = shared.error.lock() {
+                    *error = Some(Arc::new(e));
+                }
+                status
+            }
+        };
+        shared.set_status(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_finished(task: &TaskHandle) -> TaskStatus {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !task.status().is_finished() {
+            assert!(Instant::now() < deadline, "task did not finish");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        task.status()
+    }
+
+    #[test]
+    fn test_task_completes_with_progress() {
+        let manager = TaskManager::new(2);
+        // Workers start with the first task
+        assert!(manager.workers.lock().unwrap().is_empty());
+        let task = manager.spawn("count", |progress| {
+            progress.set_step("Counting");
+            progress.set_progress(10, 10);
+            Ok(())
+        });
+        assert_eq!(manager.workers.lock().unwrap().len(), 2);
+        assert_eq!(wait_finished(&task), TaskStatus::Completed);
+        assert_eq!(task.progress().percent(), 100);
+        assert_eq!(task.progress().step, "Counting");
+
+        let failed = manager.spawn("fail", |_| anyhow::bail!("disk full"));
+        assert_eq!(wait_finished(&failed), TaskStatus::Failed("disk full".to_string()));
+        assert_eq!(failed.error().unwrap().to_string(), "disk full");
+        assert!(task.error().is_none());
+
+        assert_eq!(manager.take_finished().len(), 2);
+        assert!(manager.tasks().is_empty());
+    }
+
+    #[test]
+    fn test_task_cancellation() {
+        let manager = TaskManager::new(1);
+        let task = manager.spawn("spin", |progress| loop {
+            progress.check_cancelled()?;
+            std::thread::sleep(Duration::from_millis(1));
+        });
+        // Queued behind the spinning task, so cancelled before it starts
+        let queued = manager.spawn("queued", |_| Ok(()));
+        assert!(manager.cancel(queued.id()));
+        assert!(manager.cancel(task.id()));
+
+        assert_eq!(wait_finished(&task), TaskStatus::Cancelled);
+        assert_eq!(wait_finished(&queued), TaskStatus::Cancelled);
+        assert_eq!(manager.active_count(), 0);
+    }
+}
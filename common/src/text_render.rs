@@ -0,0 +1,125 @@
+//! Rendering of the guest's text screen to RGBA pixels.
+//!
+//! Cells are drawn the way VGA hardware draws its text modes: 9x16 pixels,
+//! an 8x16 glyph (see `vga_font`) plus a ninth column that is blank except
+//! for the line drawing characters 0xC0-0xDF, which extend into it so
+//! horizontal lines join up. An 80x25 screen is therefore 720x400, the
+//! size the driver reports for text mode.
+//!
+//! Attributes select the foreground color (low nibble) and background
+//! color (bits 4-6); bit 7 makes the character blink.
+
+use crate::ioctl::TextCursor;
+use crate::text_screen::TextScreen;
+use crate::vga_font::{GLYPH_HEIGHT, GLYPH_WIDTH, VGA_FONT};
+
+/// Cell width in pixels
+pub const CELL_WIDTH: usize = 9;
+/// Cell height in pixels
+pub const CELL_HEIGHT: usize = GLYPH_HEIGHT;
+
+/// Time the cursor stays on or off (16 frames at 70 Hz)
+pub const CURSOR_BLINK_MS: u64 = 229;
+/// Time blinking characters stay on or off (32 frames at 70 Hz)
+pub const TEXT_BLINK_MS: u64 = 457;
+
+/// The 16 text mode colors of the default VGA palette
+pub const VGA_COLORS: [[u8; 3]; 16] = [
+    [0x00, 0x00, 0x00], [0x00, 0x00, 0xAA], [0x00, 0xAA, 0x00], [0x00, 0xAA, 0xAA],
+    [0xAA, 0x00, 0x00], [0xAA, 0x00, 0xAA], [0xAA, 0x55, 0x00], [0xAA, 0xAA, 0xAA],
+    [0x55, 0x55, 0x55], [0x55, 0x55, 0xFF], [0x55, 0xFF, 0x55], [0x55, 0xFF, 0xFF],
+    [0xFF, 0x55, 0x55], [0xFF, 0x55, 0xFF], [0xFF, 0xFF, 0x55], [0xFF, 0xFF, 0xFF],
+];
+
+/// Render a text screen as RGBA pixels
+///
+/// `elapsed_ms` is a running clock driving the cursor and character blink.
+/// Returns (width, height, rgba).
+pub fn render_rgba(
+    screen: &TextScreen,
+    colors: &[[u8; 3]; 16],
+    cursor: Option<&TextCursor>,
+    elapsed_ms: u64,
+) -> (u32, u32, Vec<u8>) {
+    let width = screen.cols() * CELL_WIDTH;
+    let height = screen.rows() * CELL_HEIGHT;
+    let mut rgba = vec![0u8; width * height * 4];
+
+    let cursor_on = (elapsed_ms / CURSOR_BLINK_MS).is_multiple_of(2);
+    let text_on = (elapsed_ms / TEXT_BLINK_MS).is_multiple_of(2);
+    // The cursor covers scanlines start..=end; start past end hides it
+    let cursor = cursor.filter(|c| c.visible != 0 && cursor_on && c.start <= c.end);
+
+    for row in 0..screen.rows() {
+        for col in 0..screen.cols() {
+            let Some((ch, attr)) = screen.cell(col, row) else {
+                continue;
+            };
+            let fg = colors[(attr & 0x0F) as usize];
+            let bg = colors[((attr >> 4) & 0x07) as usize];
+            let hidden = attr & 0x80 != 0 && !text_on;
+            let glyph = &VGA_FONT[ch as usize];
+            let line_drawing = (0xC0..=0xDF).contains(&ch);
+            let cursor_lines = cursor
+                .filter(|c| c.col as usize == col && c.row as usize == row)
+                .map(|c| c.start as usize..=(c.end as usize).min(CELL_HEIGHT - 1));
+
+            for (y, &bits) in glyph.iter().enumerate() {
+                let on_cursor = cursor_lines.as_ref().is_some_and(|lines| lines.contains(&y));
+                let start = ((row * CELL_HEIGHT + y) * width + col * CELL_WIDTH) * 4;
+                let pixels = &mut rgba[start..start + CELL_WIDTH * 4];
+                for (x, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+                    let set = if x < GLYPH_WIDTH {
+                        bits & (0x80 >> x) != 0
+                    } else {
+                        line_drawing && bits & 0x01 != 0
+                    };
+                    let [r, g, b] = if on_cursor || (set && !hidden) { fg } else { bg };
+                    pixel.copy_from_slice(&[r, g, b, 255]);
+                }
+            }
+        }
+    }
+
+    (width as u32, height as u32, rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_screen::CELL_BYTES;
+
+    /// Color of a pixel in a render of `cols` cells per row
+    fn pixel(rgba: &[u8], cols: usize, x: usize, y: usize) -> [u8; 3] {
+        let offset = (y * cols * CELL_WIDTH + x) * 4;
+        [rgba[offset], rgba[offset + 1], rgba[offset + 2]]
+    }
+
+    #[test]
+    fn test_render_rgba() {
+        // A letter in white on blue, a blinking horizontal line, a space
+        let cells = [b'H', 0x17, 0xC4, 0x87, b' ', 0x07];
+        assert_eq!(cells.len(), 3 * CELL_BYTES);
+        let screen = TextScreen::from_cells(&cells, 3, 1).unwrap();
+        let cursor = TextCursor { col: 2, row: 0, start: 14, end: 15, visible: 1, reserved: 0 };
+
+        let (width, height, rgba) = render_rgba(&screen, &VGA_COLORS, Some(&cursor), 0);
+        assert_eq!((width, height), (27, 16));
+        let white = VGA_COLORS[7];
+        let blue = VGA_COLORS[1];
+        let black = VGA_COLORS[0];
+        assert_eq!(pixel(&rgba, 3, 0, 0), blue);
+        assert_eq!(pixel(&rgba, 3, 1, 7), white);
+        // The ninth column of a letter stays background
+        assert_eq!(pixel(&rgba, 3, 8, 7), blue);
+        // Line drawing extends into the ninth column
+        assert_eq!(pixel(&rgba, 3, 9 + 8, 7), white);
+        assert_eq!(pixel(&rgba, 3, 18, 14), white);
+        assert_eq!(pixel(&rgba, 3, 18, 13), black);
+
+        // Blink phase: blinking line hidden, cursor off
+        let (_, _, rgba) = render_rgba(&screen, &VGA_COLORS, Some(&cursor), TEXT_BLINK_MS);
+        assert_eq!(pixel(&rgba, 3, 9 + 8, 7), black);
+        assert_eq!(pixel(&rgba, 3, 18, 14), black);
+    }
+}
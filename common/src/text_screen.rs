@@ -0,0 +1,109 @@
+//! Guest text-mode screen contents.
+//!
+//! In text mode the framebuffer holds the VGA character buffer rather than
+//! pixels: `text_cols * text_rows` cells of two bytes each, the character
+//! code followed by its attribute (foreground in the low nibble, background
+//! and blink in the high nibble), row by row with no padding. Reading the
+//! cells gives the exact screen text, so text screens need no OCR.
+
+/// Bytes per character cell (character, attribute)
+pub const CELL_BYTES: usize = 2;
+
+/// A snapshot of the guest's text screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextScreen {
+    cols: usize,
+    rows: usize,
+    cells: Vec<u8>,
+}
+
+impl TextScreen {
+    /// Copy a screen out of raw cell data
+    ///
+    /// Returns None if `data` is too short for `cols` x `rows` cells.
+    pub fn from_cells(data: &[u8], cols: u32, rows: u32) -> Option<Self> {
+        let (cols, rows) = (cols as usize, rows as usize);
+        let len = cols * rows * CELL_BYTES;
+        if cols == 0 || rows == 0 || data.len() < len {
+            return None;
+        }
+        Some(Self {
+            cols,
+            rows,
+            cells: data[..len].to_vec(),
+        })
+    }
+
+    /// Screen width in characters
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Screen height in characters
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Character code and attribute at a position
+    pub fn cell(&self, col: usize, row: usize) -> Option<(u8, u8)> {
+        if col >= self.cols || row >= self.rows {
+            return None;
+        }
+        let offset = (row * self.cols + col) * CELL_BYTES;
+        Some((self.cells[offset], self.cells[offset + 1]))
+    }
+
+    /// One row as text, without trailing blanks
+    ///
+    /// Characters outside printable ASCII (box drawing, shading, symbols)
+    /// read as spaces, so frames and shadows don't get in the way of
+    /// searching the text they surround.
+    pub fn line(&self, row: usize) -> String {
+        let start = row * self.cols * CELL_BYTES;
+        let end = start + self.cols * CELL_BYTES;
+        let Some(row_cells) = self.cells.get(start..end) else {
+            return String::new();
+        };
+        let line: String = row_cells
+            .chunks_exact(CELL_BYTES)
+            .map(|cell| match cell[0] {
+                c @ 0x20..=0x7E => c as char,
+                _ => ' ',
+            })
+            .collect();
+        line.trim_end().to_string()
+    }
+
+    /// All rows as text
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.rows).map(|row| self.line(row)).collect()
+    }
+
+    /// Whether no row has any text
+    pub fn is_blank(&self) -> bool {
+        (0..self.rows).all(|row| self.line(row).is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_screen_lines() {
+        let mut data = vec![0u8; 4 * 2 * CELL_BYTES];
+        for (i, c) in b"C:\\>".iter().enumerate() {
+            data[i * CELL_BYTES] = *c;
+            data[i * CELL_BYTES + 1] = 0x07;
+        }
+        // Box drawing on the second row reads as blank
+        data[4 * CELL_BYTES] = 0xC9;
+
+        let screen = TextScreen::from_cells(&data, 4, 2).unwrap();
+        assert_eq!(screen.lines(), ["C:\\>", ""]);
+        assert_eq!(screen.cell(1, 0), Some((b':', 0x07)));
+        assert_eq!(screen.cell(4, 0), None);
+        assert!(!screen.is_blank());
+        assert!(TextScreen::from_cells(&data, 80, 25).is_none());
+    }
+}
@@ -0,0 +1,244 @@
+//! JSON exchanged between the frontend's controllers and QML.
+//!
+//! Controllers hand QML lists and records as JSON strings, and QML hands
+//! some back (e.g. the drive mappings edited in DriveMappingDialog). These
+//! are the serde models of those strings, so paths with quotes, backslashes
+//! or non-ASCII characters come through intact. Options QML gives as
+//! strings ("local", "tail"...) fall back to their defaults when they are
+//! not recognised, and keys QML adds for its own use are ignored.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::config::{DriveMapping, Mangling, NameCase, TimestampPolicy};
+use crate::disk_image::DiskInfo;
+use crate::{fat_time, short_name};
+
+/// A drive mapping as QML sees it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MappingJson {
+    /// "F:"
+    pub drive_letter: String,
+    pub host_path: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub readonly: bool,
+    #[serde(default = "enabled_default")]
+    pub enabled: bool,
+    /// "utc", "local" or an offset such as "+05:30"
+    #[serde(default, serialize_with = "write_policy", deserialize_with = "read_policy")]
+    pub timestamps: TimestampPolicy,
+    /// "tail", "hash" or "truncate"
+    #[serde(default, serialize_with = "write_mangling", deserialize_with = "read_mangling")]
+    pub mangling: Mangling,
+    /// "upper", "lower" or "preserve"
+    #[serde(default, serialize_with = "write_case", deserialize_with = "read_case")]
+    pub name_case: NameCase,
+    #[serde(default)]
+    pub hide_dotfiles: bool,
+    /// 0 for no limit
+    #[serde(default)]
+    pub max_size_mb: u32,
+    /// Whether the host path exists; reported, not read back
+    #[serde(default, skip_deserializing)]
+    pub available: bool,
+}
+
+impl From<&DriveMapping> for MappingJson {
+    fn from(m: &DriveMapping) -> Self {
+        Self {
+            drive_letter: m.drive_letter.clone(),
+            host_path: m.host_path.to_string_lossy().into_owned(),
+            description: m.description.clone(),
+            readonly: m.readonly,
+            enabled: m.enabled,
+            timestamps: m.timestamps,
+            mangling: m.mangling,
+            name_case: m.name_case,
+            hide_dotfiles: m.hide_dotfiles,
+            max_size_mb: m.max_size_mb,
+            available: false,
+        }
+    }
+}
+
+impl MappingJson {
+    /// The mapping as the configuration keeps it
+    pub fn to_config(&self) -> DriveMapping {
+        DriveMapping {
+            drive_letter: self.drive_letter.trim().to_uppercase(),
+            host_path: self.host_path.clone().into(),
+            description: self.description.clone(),
+            enabled: self.enabled,
+            readonly: self.readonly,
+            timestamps: self.timestamps,
+            mangling: self.mangling,
+            name_case: self.name_case,
+            hide_dotfiles: self.hide_dotfiles,
+            max_size_mb: self.max_size_mb,
+        }
+    }
+}
+
+/// Parse a JSON array of mappings
+pub fn parse_mappings(json: &str) -> serde_json::Result<Vec<MappingJson>> {
+    serde_json::from_str(json)
+}
+
+/// What `DiskManager::get_disk_info` reports about a disk image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskInfoJson {
+    pub valid: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub info: Option<DiskDetailsJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The header details of a valid disk image
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiskDetailsJson {
+    pub size_mb: u32,
+    pub revision: u8,
+    pub cylinders: u16,
+    pub heads: u8,
+    pub sectors: u8,
+    pub total_sectors: u64,
+    pub bootable: bool,
+    pub partition_type: String,
+    /// "sparse", "preallocate" or "zero-fill"
+    pub allocation: String,
+}
+
+impl DiskInfoJson {
+    /// A readable disk image
+    pub fn valid(info: &DiskInfo) -> Self {
+        Self {
+            valid: true,
+            info: Some(DiskDetailsJson {
+                size_mb: info.size_mb,
+                revision: info.revision,
+                cylinders: info.cylinders,
+                heads: info.heads,
+                sectors: info.sectors_per_track,
+                total_sectors: info.total_sectors,
+                bootable: info.bootable,
+                partition_type: info.partition_type.clone(),
+                allocation: info.allocation.name().to_string(),
+            }),
+            error: None,
+        }
+    }
+
+    /// A disk image that could not be read
+    pub fn invalid(error: impl Into<String>) -> Self {
+        Self {
+            valid: false,
+            info: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// Serialize a model; they hold only strings, numbers and flags, so this
+/// does not fail in practice
+pub fn to_string<T: Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+fn enabled_default() -> bool {
+    true
+}
+
+fn write_policy<S: Serializer>(policy: &TimestampPolicy, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&fat_time::policy_name(*policy))
+}
+
+fn read_policy<'de, D: Deserializer<'de>>(d: D) -> Result<TimestampPolicy, D::Error> {
+    Ok(fat_time::parse_policy(&String::deserialize(d)?).unwrap_or_default())
+}
+
+fn write_mangling<S: Serializer>(mangling: &Mangling, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(short_name::mangling_name(*mangling))
+}
+
+fn read_mangling<'de, D: Deserializer<'de>>(d: D) -> Result<Mangling, D::Error> {
+    Ok(short_name::parse_mangling(&String::deserialize(d)?).unwrap_or_default())
+}
+
+fn write_case<S: Serializer>(case: &NameCase, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(short_name::case_name(*case))
+}
+
+fn read_case<'de, D: Deserializer<'de>>(d: D) -> Result<NameCase, D::Error> {
+    Ok(short_name::parse_case(&String::deserialize(d)?).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk_image::Allocation;
+
+    #[test]
+    fn test_mapping_round_trip() {
+        let mapping = MappingJson {
+            drive_letter: "F:".to_string(),
+            host_path: r#"/home/user/"quoted" \ Müll/日本語"#.to_string(),
+            description: "Ünïcode \"stuff\"".to_string(),
+            readonly: true,
+            enabled: false,
+            timestamps: TimestampPolicy::Offset(-150),
+            mangling: Mangling::Hash,
+            name_case: NameCase::Preserve,
+            hide_dotfiles: true,
+            max_size_mb: 2048,
+            available: false,
+        };
+        let json = to_string(&vec![mapping.clone()]);
+        assert!(json.contains(r#""timestamps":"-02:30""#));
+        assert!(json.contains(r#""nameCase":"preserve""#));
+        assert_eq!(parse_mappings(&json).unwrap(), vec![mapping.clone()]);
+        assert_eq!(MappingJson::from(&mapping.to_config()), mapping);
+    }
+
+    #[test]
+    fn test_mapping_defaults() {
+        // As QML's ListModel sends them, with keys of its own and options
+        // missing or unknown
+        let json = r#"[{"driveLetter":"H:","hostPath":"~","description":"Home","timestamps":"bogus","available":true}]"#;
+        let mappings = parse_mappings(json).unwrap();
+        assert_eq!(mappings.len(), 1);
+        assert!(mappings[0].enabled);
+        assert!(!mappings[0].readonly);
+        assert!(!mappings[0].available);
+        assert_eq!(mappings[0].timestamps, TimestampPolicy::Local);
+        assert_eq!(mappings[0].mangling, Mangling::NumericTail);
+        assert_eq!(mappings[0].max_size_mb, 0);
+        assert!(parse_mappings(r#"[{"driveLetter":"H:"}]"#).is_err());
+    }
+
+    #[test]
+    fn test_disk_info_round_trip() {
+        let info = DiskInfo {
+            is_sunpci: true,
+            size_mb: 512,
+            revision: 2,
+            cylinders: 1040,
+            heads: 16,
+            sectors_per_track: 63,
+            total_sectors: 1_048_320,
+            bootable: true,
+            partition_type: "FAT16 \"big\"".to_string(),
+            allocation: Allocation::ZeroFill,
+        };
+        let json = to_string(&DiskInfoJson::valid(&info));
+        assert!(json.contains(r#""size_mb":512"#));
+        assert!(!json.contains("error"));
+        assert_eq!(serde_json::from_str::<DiskInfoJson>(&json).unwrap(), DiskInfoJson::valid(&info));
+
+        let json = to_string(&DiskInfoJson::invalid("Failed to read disk header"));
+        assert_eq!(json, r#"{"valid":false,"error":"Failed to read disk header"}"#);
+        assert_eq!(serde_json::from_str::<DiskInfoJson>(&json).unwrap().info, None);
+    }
+}
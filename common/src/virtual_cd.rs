@@ -0,0 +1,442 @@
+//! Virtual CD-ROM drive backed by a CUE/BIN image.
+//!
+//! The driver handles ISO images itself, but they can only hold one data
+//! track. Multi-track images go through the same pass-through interface as
+//! a host drive: the frontend takes the guest's SCSI commands with
+//! `DriverHandle::next_scsi_request` and answers them from `VirtualCd`.
+//!
+//! Data tracks are served as 2048-byte sectors whatever their stored
+//! format; READ CD returns whole 2352-byte sectors, synthesizing the sync
+//! pattern and header for tracks stored without them. Audio commands are
+//! handled by `CdAudio`, whose position the caller advances in real time.
+
+use std::fs::File;
+use std::ops::Range;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::cuesheet::{self, CueError, CueSheet, DiscLayout, Source, TrackMode};
+use crate::ioctl::{SCSI_CDB_MAX_LEN, ScsiRequest, ScsiResponse};
+use crate::scsi::{
+    CdAudio, InquiryData, ReadCapacityData, ScsiResult, SECTOR_SIZE_CDROM, SECTOR_SIZE_CDROM_RAW,
+    SenseData, Toc, cdb10_get_lba, cdb10_get_length, cdb12_get_length, lba_to_msf, opcode,
+};
+
+/// Sync pattern at the start of a raw data sector
+const SYNC_PATTERN: [u8; 12] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+
+/// Length of the sync pattern and header of a raw data sector
+const RAW_HEADER_LEN: usize = 16;
+
+/// READ CD field selection bits (CDB byte 9)
+const READ_CD_SYNC: u8 = 0x80;
+const READ_CD_HEADERS: u8 = 0x60;
+const READ_CD_USER_DATA: u8 = 0x10;
+
+/// READ CD expected sector types (CDB byte 1, bits 2-4)
+const SECTOR_TYPE_ANY: u8 = 0;
+const SECTOR_TYPE_CDDA: u8 = 1;
+
+/// A mounted CUE/BIN image
+pub struct VirtualCd {
+    sheet: CueSheet,
+    layout: DiscLayout,
+    files: Vec<File>,
+    audio: CdAudio,
+    /// Sense data for the next REQUEST SENSE
+    sense: SenseData,
+}
+
+impl VirtualCd {
+    /// Open the image described by a CUE sheet
+    pub fn open(cue: &Path) -> Result<Self, CueError> {
+        Self::from_sheet(cuesheet::load(cue)?)
+    }
+
+    /// Open the files of a parsed CUE sheet
+    pub fn from_sheet(sheet: CueSheet) -> Result<Self, CueError> {
+        let mut files = Vec::with_capacity(sheet.files.len());
+        let mut sizes = Vec::with_capacity(sheet.files.len());
+        for path in &sheet.files {
+            let file_error = |source| CueError::File { path: path.clone(), source };
+            let file = File::open(path).map_err(file_error)?;
+            sizes.push(file.metadata().map_err(file_error)?.len());
+            files.push(file);
+        }
+        let layout = sheet.layout(&sizes)?;
+
+        Ok(Self {
+            sheet,
+            layout,
+            files,
+            audio: CdAudio::new(),
+            sense: SenseData::no_sense(),
+        })
+    }
+
+    /// The CUE sheet the image was opened from
+    pub fn sheet(&self) -> &CueSheet {
+        &self.sheet
+    }
+
+    /// Table of contents
+    pub fn toc(&self) -> &Toc {
+        &self.layout.toc
+    }
+
+    /// Where each run of sectors is stored
+    pub fn layout(&self) -> &DiscLayout {
+        &self.layout
+    }
+
+    /// Audio player state
+    pub fn audio(&self) -> &CdAudio {
+        &self.audio
+    }
+
+    /// Volume label of the first data track, if it is ISO 9660
+    pub fn volume_label(&self) -> Option<String> {
+        let track = self.layout.toc.tracks.iter().find(|t| !t.audio)?;
+        let mut pvd = vec![0u8; SECTOR_SIZE_CDROM as usize];
+        self.read_user_data(track.start_lba + ISO9660_PVD_SECTOR, &mut pvd).ok()?;
+        iso9660_label(&pvd)
+    }
+
+    /// Advance audio play by `sectors`, returning the sectors to output
+    pub fn advance_audio(&mut self, sectors: u32) -> Range<u32> {
+        self.audio.advance(sectors)
+    }
+
+    /// Read the 2048 bytes of user data of a data sector into `buf`
+    pub fn read_user_data(&self, lba: u32, buf: &mut [u8]) -> Result<(), SenseData> {
+        let extent = self.layout.extent_at(lba).ok_or_else(SenseData::lba_out_of_range)?;
+        if extent.mode.is_audio() {
+            return Err(SenseData::illegal_mode_for_track());
+        }
+        let buf = &mut buf[..SECTOR_SIZE_CDROM as usize];
+        match extent.source {
+            Source::Gap => buf.fill(0),
+            Source::File { file, offset } => {
+                let sector_size = u64::from(extent.mode.sector_size());
+                let pos = offset
+                    + u64::from(lba - extent.start) * sector_size
+                    + u64::from(extent.mode.data_offset());
+                self.read_at(file, buf, pos)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a whole 2352-byte sector (audio samples or raw data) into `buf`
+    pub fn read_raw(&self, lba: u32, buf: &mut [u8]) -> Result<(), SenseData> {
+        let extent = self.layout.extent_at(lba).ok_or_else(SenseData::lba_out_of_range)?;
+        let buf = &mut buf[..SECTOR_SIZE_CDROM_RAW as usize];
+        let (file, offset) = match extent.source {
+            Source::Gap => {
+                buf.fill(0);
+                return Ok(());
+            }
+            Source::File { file, offset } => (file, offset),
+        };
+        let sector_size = extent.mode.sector_size() as usize;
+        let pos = offset + u64::from(lba - extent.start) * sector_size as u64;
+
+        match extent.mode {
+            TrackMode::Audio | TrackMode::Mode1_2352 | TrackMode::Mode2_2352 => self.read_at(file, buf, pos),
+            TrackMode::Mode1_2048 | TrackMode::Mode2_2336 => {
+                let mode = if extent.mode == TrackMode::Mode1_2048 { 1 } else { 2 };
+                write_raw_header(buf, lba, mode);
+                let (stored, ecc) = buf[RAW_HEADER_LEN..].split_at_mut(sector_size);
+                // EDC/ECC is not regenerated; nothing in a guest checks it
+                ecc.fill(0);
+                self.read_at(file, stored, pos)
+            }
+        }
+    }
+
+    fn read_at(&self, file: usize, buf: &mut [u8], pos: u64) -> Result<(), SenseData> {
+        self.files[file].read_exact_at(buf, pos).map_err(|_| SenseData::read_error())
+    }
+
+    /// Execute a guest SCSI command, filling `data` for reads
+    pub fn execute(&mut self, request: &ScsiRequest, data: &mut [u8]) -> ScsiResponse {
+        let cdb = &request.cdb[..(request.cdb_len as usize).min(SCSI_CDB_MAX_LEN)];
+        let limit = (request.data_len as usize).min(data.len());
+        match self.command(cdb, &mut data[..limit]) {
+            Ok(len) => ScsiResponse {
+                data_len: len as u32,
+                ..Default::default()
+            },
+            Err(sense) => {
+                self.sense = sense;
+                ScsiResponse::check_condition(sense.sense_key, sense.asc, sense.ascq)
+            }
+        }
+    }
+
+    /// Run a command, returning the number of bytes put in `out`
+    fn command(&mut self, cdb: &[u8], out: &mut [u8]) -> Result<usize, SenseData> {
+        let Some(&op) = cdb.first() else {
+            return Err(SenseData::invalid_command());
+        };
+        if let Some(result) = self.audio.handle_command(cdb, &self.layout.toc) {
+            return match result {
+                ScsiResult::Good(bytes) => Ok(copy_out(&bytes, out)),
+                ScsiResult::GoodNoData => Ok(0),
+                ScsiResult::CheckCondition(sense) => Err(sense),
+            };
+        }
+
+        match op {
+            opcode::TEST_UNIT_READY | opcode::PREVENT_ALLOW_MEDIUM_REMOVAL => Ok(0),
+            opcode::REQUEST_SENSE => {
+                let sense = std::mem::replace(&mut self.sense, SenseData::no_sense());
+                Ok(copy_out(&sense.to_bytes(), out))
+            }
+            opcode::INQUIRY => Ok(copy_out(&InquiryData::new().to_bytes(), out)),
+            opcode::START_STOP_UNIT => {
+                // Stopping the spindle ends audio play; eject is up to the host
+                if cdb.get(4).is_some_and(|b| b & 0x01 == 0) {
+                    self.audio.stop();
+                }
+                Ok(0)
+            }
+            opcode::SEEK_10 => {
+                self.audio.stop();
+                if cdb10_get_lba(cdb) >= self.layout.toc.lead_out {
+                    return Err(SenseData::lba_out_of_range());
+                }
+                Ok(0)
+            }
+            opcode::READ_CAPACITY => {
+                let capacity = ReadCapacityData::new(self.layout.toc.lead_out, SECTOR_SIZE_CDROM);
+                Ok(copy_out(&capacity.to_bytes(), out))
+            }
+            opcode::READ_10 => self.read_data(cdb10_get_lba(cdb), u32::from(cdb10_get_length(cdb)), out),
+            opcode::READ_12 => self.read_data(cdb10_get_lba(cdb), cdb12_get_length(cdb), out),
+            opcode::READ_TOC => {
+                let toc = self.read_toc(cdb)?;
+                Ok(copy_out(&toc, out))
+            }
+            opcode::READ_CD => self.read_cd(cdb, out),
+            // Header only: no block descriptors or pages
+            opcode::MODE_SENSE_6 => Ok(copy_out(&[3, 0, 0, 0], out)),
+            opcode::MODE_SENSE_10 => Ok(copy_out(&[0, 6, 0, 0, 0, 0, 0, 0], out)),
+            _ => Err(SenseData::invalid_command()),
+        }
+    }
+
+    /// READ(10/12): user data of `count` sectors from `lba`
+    fn read_data(&self, lba: u32, count: u32, out: &mut [u8]) -> Result<usize, SenseData> {
+        let end = lba.checked_add(count).ok_or_else(SenseData::lba_out_of_range)?;
+        if end > self.layout.toc.lead_out {
+            return Err(SenseData::lba_out_of_range());
+        }
+        let mut len = 0;
+        for (lba, chunk) in (lba..end).zip(out.chunks_exact_mut(SECTOR_SIZE_CDROM as usize)) {
+            self.read_user_data(lba, chunk)?;
+            len += chunk.len();
+        }
+        Ok(len)
+    }
+
+    /// READ CD: user data or whole sectors, by the field selection bits
+    fn read_cd(&self, cdb: &[u8], out: &mut [u8]) -> Result<usize, SenseData> {
+        if cdb.len() < 10 {
+            return Err(SenseData::invalid_field());
+        }
+        let expected_type = (cdb[1] >> 2) & 0x07;
+        let lba = cdb10_get_lba(cdb);
+        let count = u32::from_be_bytes([0, cdb[6], cdb[7], cdb[8]]);
+        let fields = cdb[9];
+        let end = lba.checked_add(count).ok_or_else(SenseData::lba_out_of_range)?;
+        if end > self.layout.toc.lead_out {
+            return Err(SenseData::lba_out_of_range());
+        }
+
+        let mut len = 0;
+        for lba in lba..end {
+            let extent = self.layout.extent_at(lba).ok_or_else(SenseData::lba_out_of_range)?;
+            let audio = extent.mode.is_audio();
+            match expected_type {
+                SECTOR_TYPE_ANY => {}
+                SECTOR_TYPE_CDDA if audio => {}
+                t if t != SECTOR_TYPE_CDDA && !audio => {}
+                _ => return Err(SenseData::illegal_mode_for_track()),
+            }
+
+            // Audio sectors are all user data; data sectors are raw when
+            // the sync or header fields are asked for
+            let raw = audio || fields & (READ_CD_SYNC | READ_CD_HEADERS) != 0;
+            let sector_len = match fields {
+                0 => 0,
+                _ if raw => SECTOR_SIZE_CDROM_RAW as usize,
+                _ if fields & READ_CD_USER_DATA != 0 => SECTOR_SIZE_CDROM as usize,
+                _ => 0,
+            };
+            let Some(chunk) = out.get_mut(len..len + sector_len) else {
+                break;
+            };
+            if sector_len == 0 {
+                continue;
+            }
+            if raw {
+                self.read_raw(lba, chunk)?;
+            } else {
+                self.read_user_data(lba, chunk)?;
+            }
+            len += sector_len;
+        }
+        Ok(len)
+    }
+
+    /// READ TOC formats 0 (tracks) and 1 (session information)
+    fn read_toc(&self, cdb: &[u8]) -> Result<Vec<u8>, SenseData> {
+        if cdb.len() < 10 {
+            return Err(SenseData::invalid_field());
+        }
+        let msf = cdb[1] & 0x02 != 0;
+        // Older drivers put the format in the top bits of the control byte
+        let format = match cdb[2] & 0x0F {
+            0 => cdb[9] >> 6,
+            format => format,
+        };
+        let toc = &self.layout.toc;
+        match format {
+            0 => toc.to_bytes(msf, cdb[6]).ok_or_else(SenseData::invalid_field),
+            1 => {
+                // A single session whose first track is the disc's first
+                let first = toc.tracks.first().ok_or_else(SenseData::invalid_field)?;
+                let entry = first.entry();
+                let mut buf = vec![0, 10, 1, 1, 0, entry.adr_control, first.number, 0];
+                if msf {
+                    let (m, s, f) = lba_to_msf(first.start_lba);
+                    buf.extend_from_slice(&[0, m, s, f]);
+                } else {
+                    buf.extend_from_slice(&first.start_lba.to_be_bytes());
+                }
+                Ok(buf)
+            }
+            _ => Err(SenseData::invalid_field()),
+        }
+    }
+}
+
+/// Sector holding the ISO 9660 primary volume descriptor
+pub const ISO9660_PVD_SECTOR: u32 = 16;
+
+/// Volume label from an ISO 9660 primary volume descriptor
+pub fn iso9660_label(pvd: &[u8]) -> Option<String> {
+    if pvd.len() < 72 || &pvd[..6] != b"\x01CD001" {
+        return None;
+    }
+    let label = String::from_utf8_lossy(&pvd[40..72]).trim_end().to_string();
+    Some(label)
+}
+
+/// Copy as much of `data` as fits in `out`
+fn copy_out(data: &[u8], out: &mut [u8]) -> usize {
+    let len = data.len().min(out.len());
+    out[..len].copy_from_slice(&data[..len]);
+    len
+}
+
+/// Write the sync pattern and header of a raw data sector
+fn write_raw_header(buf: &mut [u8], lba: u32, mode: u8) {
+    let bcd = |v: u8| ((v / 10) << 4) | (v % 10);
+    let (m, s, f) = lba_to_msf(lba);
+    buf[..12].copy_from_slice(&SYNC_PATTERN);
+    buf[12..RAW_HEADER_LEN].copy_from_slice(&[bcd(m), bcd(s), bcd(f), mode]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ioctl::ScsiResponse;
+    use crate::scsi::{PlayState, asc, status};
+
+    const DATA_SECTORS: u32 = 20;
+    const AUDIO_SECTORS: u32 = 10;
+
+    /// A MODE1/2048 data track followed by an audio track in its own file
+    fn mixed_mode_disc(dir: &Path) -> VirtualCd {
+        let mut data = Vec::new();
+        for lba in 0..DATA_SECTORS {
+            data.extend(std::iter::repeat_n(lba as u8, SECTOR_SIZE_CDROM as usize));
+        }
+        std::fs::write(dir.join("data.bin"), data).unwrap();
+        std::fs::write(dir.join("audio.bin"), vec![0x5A; (AUDIO_SECTORS * 2352) as usize]).unwrap();
+        let cue = "FILE \"data.bin\" BINARY\n  TRACK 01 MODE1/2048\n    INDEX 01 00:00:00\n\
+                   FILE \"audio.bin\" BINARY\n  TRACK 02 AUDIO\n    PREGAP 00:00:05\n    INDEX 01 00:00:00\n";
+        std::fs::write(dir.join("disc.cue"), cue).unwrap();
+        VirtualCd::open(&dir.join("disc.cue")).unwrap()
+    }
+
+    fn run(cd: &mut VirtualCd, cdb: &[u8], len: u32) -> (ScsiResponse, Vec<u8>) {
+        let mut request = ScsiRequest::default().with_read(len);
+        request.cdb[..cdb.len()].copy_from_slice(cdb);
+        request.cdb_len = cdb.len() as u32;
+        let mut data = vec![0u8; len as usize];
+        let response = cd.execute(&request, &mut data);
+        data.truncate(response.data_len as usize);
+        (response, data)
+    }
+
+    #[test]
+    fn test_data_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cd = mixed_mode_disc(dir.path());
+        let audio_start = DATA_SECTORS + 5;
+        assert_eq!(cd.toc().tracks[1].start_lba, audio_start);
+        assert_eq!(cd.toc().lead_out, audio_start + AUDIO_SECTORS);
+
+        // READ(10) of sectors 3-4
+        let (response, data) = run(&mut cd, &[opcode::READ_10, 0, 0, 0, 0, 3, 0, 0, 2, 0], 4096);
+        assert!(response.is_good());
+        assert_eq!((data[0], data[2048]), (3, 4));
+
+        // Audio sectors cannot be read as data, and the sense says why
+        let (response, _) = run(&mut cd, &[opcode::READ_10, 0, 0, 0, 0, audio_start as u8, 0, 0, 1, 0], 2048);
+        assert_eq!(response.status, status::CHECK_CONDITION);
+        let (_, sense) = run(&mut cd, &[opcode::REQUEST_SENSE, 0, 0, 0, 18, 0], 18);
+        assert_eq!(sense[12], asc::ILLEGAL_MODE_FOR_TRACK);
+
+        // READ CD with sync and headers synthesizes the raw sector
+        let (response, data) = run(&mut cd, &[opcode::READ_CD, 0, 0, 0, 0, 1, 0, 0, 1, 0xF8, 0, 0], 2352);
+        assert!(response.is_good());
+        assert_eq!(&data[..12], &SYNC_PATTERN);
+        assert_eq!(&data[12..16], &[0x00, 0x02, 0x01, 1]); // 00:02:01, mode 1
+        assert_eq!(data[16], 1);
+
+        // READ CD of audio returns the samples
+        let (_, data) = run(
+            &mut cd,
+            &[opcode::READ_CD, 0x04, 0, 0, 0, audio_start as u8, 0, 0, 1, 0x10, 0, 0],
+            2352,
+        );
+        assert_eq!(data, vec![0x5A; 2352]);
+
+        // Both tracks show in READ TOC
+        let (_, toc) = run(&mut cd, &[opcode::READ_TOC, 0, 0, 0, 0, 0, 1, 0, 100, 0], 100);
+        assert_eq!(&toc[2..4], &[1, 2]);
+        assert_eq!(toc.len(), 4 + 3 * 8);
+    }
+
+    #[test]
+    fn test_audio_play() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cd = mixed_mode_disc(dir.path());
+        let audio_start = DATA_SECTORS + 5;
+
+        // PLAY AUDIO(10) on the data track is refused
+        let (response, _) = run(&mut cd, &[opcode::PLAY_AUDIO_10, 0, 0, 0, 0, 0, 0, 0, 4, 0], 0);
+        assert_eq!(response.status, status::CHECK_CONDITION);
+
+        let (response, _) = run(&mut cd, &[opcode::PLAY_AUDIO_10, 0, 0, 0, 0, audio_start as u8, 0, 0, 4, 0], 0);
+        assert!(response.is_good());
+        assert_eq!(cd.audio().state(), PlayState::Playing);
+        assert_eq!(cd.advance_audio(3), audio_start..audio_start + 3);
+        assert_eq!(cd.advance_audio(3), audio_start + 3..audio_start + 4);
+        assert_eq!(cd.audio().state(), PlayState::Completed);
+    }
+}
@@ -0,0 +1,246 @@
+//! Session watchdog.
+//!
+//! The frontend feeds `Watchdog::check` the session status every few
+//! seconds. A session in `SessionState::Error` has crashed; a running one
+//! whose guest has not rung a doorbell for `hang_timeout_secs`
+//! (`SessionStatus::guest_idle_ms`) has hung. Each incident is reported
+//! once with the profile's `crash_action`: the frontend saves a screenshot
+//! and then tells the user, resets the session, or hands the screenshot to
+//! `crash_command` (see `run_hook`). Restarts are limited to
+//! `MAX_RESTARTS` within `RESTART_WINDOW`, so a guest that crashes while
+//! booting is not reset forever.
+
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::config::{CrashAction, GeneralConfig};
+use crate::ioctl::{SessionState, SessionStatus};
+
+/// Automatic restarts allowed within `RESTART_WINDOW`
+pub const MAX_RESTARTS: usize = 3;
+/// Window over which automatic restarts are counted
+pub const RESTART_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// What happened to the guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Incident {
+    /// The session went into the error state
+    Crashed,
+    /// The guest stopped signalling the host
+    Hung,
+}
+
+impl Incident {
+    /// Name used in signals to QML
+    pub fn name(self) -> &'static str {
+        match self {
+            Incident::Crashed => "crashed",
+            Incident::Hung => "hung",
+        }
+    }
+}
+
+/// What the frontend should do about an incident
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Tell the user only
+    Notify,
+    /// Reset the session
+    Restart,
+    /// Tell the user; restarting was given up after too many attempts
+    GiveUp,
+    /// Tell the user and run the crash command
+    RunCommand,
+}
+
+impl Action {
+    /// Name used in signals to QML
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Notify => "notify",
+            Action::Restart => "restart",
+            Action::GiveUp => "give_up",
+            Action::RunCommand => "run_command",
+        }
+    }
+}
+
+/// Watches session status for crashes and hangs
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    on_crash: CrashAction,
+    hang_timeout: Option<Duration>,
+    /// The incident already reported, until the guest recovers
+    reported: Option<Incident>,
+    /// When recent automatic restarts happened
+    restarts: Vec<Instant>,
+}
+
+impl Watchdog {
+    /// Watchdog with the `[general]` settings
+    pub fn new(general: &GeneralConfig) -> Self {
+        Self {
+            on_crash: general.on_crash(),
+            hang_timeout: (general.hang_timeout_secs > 0)
+                .then(|| Duration::from_secs(general.hang_timeout_secs as u64)),
+            reported: None,
+            restarts: Vec::new(),
+        }
+    }
+
+    /// Look at a status; returns a new incident and what to do about it
+    pub fn check(&mut self, status: &SessionStatus, now: Instant) -> Option<(Incident, Action)> {
+        let incident = if status.state == SessionState::Error as u32 {
+            Some(Incident::Crashed)
+        } else if status.state == SessionState::Running as u32
+            && self
+                .hang_timeout
+                .is_some_and(|timeout| Duration::from_millis(status.guest_idle_ms as u64) >= timeout)
+        {
+            Some(Incident::Hung)
+        } else {
+            None
+        };
+
+        let Some(incident) = incident else {
+            self.reported = None;
+            return None;
+        };
+        if self.reported == Some(incident) {
+            return None;
+        }
+        self.reported = Some(incident);
+
+        match self.on_crash {
+            CrashAction::Notify => return Some((incident, Action::Notify)),
+            CrashAction::RunCommand => return Some((incident, Action::RunCommand)),
+            CrashAction::Restart => {}
+        }
+        self.restarts.retain(|&at| now.duration_since(at) < RESTART_WINDOW);
+        if self.restarts.len() >= MAX_RESTARTS {
+            return Some((incident, Action::GiveUp));
+        }
+        self.restarts.push(now);
+        Some((incident, Action::Restart))
+    }
+
+    /// Forget the incident in progress, e.g. after the session restarted
+    pub fn clear(&mut self) {
+        self.reported = None;
+    }
+}
+
+/// Run the crash command `command` with the shell, passing it the
+/// screenshot at `screenshot` as its last argument
+///
+/// The incident name is in `RISING_SUN_INCIDENT`. Returns once the command
+/// has started; it is reaped in the background.
+pub fn run_hook(command: &str, incident: Incident, screenshot: &Path) -> io::Result<()> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", command))
+        .arg("sh")
+        .arg(screenshot)
+        .env("RISING_SUN_INCIDENT", incident.name())
+        .stdin(Stdio::null())
+        .spawn()?;
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(state: SessionState, guest_idle_ms: u32) -> SessionStatus {
+        let mut status = SessionStatus::default();
+        status.state = state as u32;
+        status.guest_idle_ms = guest_idle_ms;
+        status
+    }
+
+    #[test]
+    fn test_crash_reported_once() {
+        let mut watchdog = Watchdog::new(&GeneralConfig::default());
+        let now = Instant::now();
+        assert_eq!(watchdog.check(&status(SessionState::Running, 500_000), now), None);
+        assert_eq!(
+            watchdog.check(&status(SessionState::Error, 0), now),
+            Some((Incident::Crashed, Action::Notify))
+        );
+        assert_eq!(watchdog.check(&status(SessionState::Error, 0), now), None);
+        assert_eq!(watchdog.check(&status(SessionState::Running, 0), now), None);
+        assert!(watchdog.check(&status(SessionState::Error, 0), now).is_some());
+    }
+
+    #[test]
+    fn test_hang_and_restart_limit() {
+        let general = GeneralConfig {
+            crash_action: CrashAction::Restart,
+            hang_timeout_secs: 30,
+            ..Default::default()
+        };
+        let mut watchdog = Watchdog::new(&general);
+        let now = Instant::now();
+        assert_eq!(watchdog.check(&status(SessionState::Running, 29_000), now), None);
+
+        for _ in 0..MAX_RESTARTS {
+            assert_eq!(
+                watchdog.check(&status(SessionState::Running, 30_000), now),
+                Some((Incident::Hung, Action::Restart))
+            );
+            watchdog.clear();
+        }
+        assert_eq!(
+            watchdog.check(&status(SessionState::Running, 30_000), now),
+            Some((Incident::Hung, Action::GiveUp))
+        );
+
+        // Old restarts stop counting
+        watchdog.clear();
+        assert_eq!(
+            watchdog.check(&status(SessionState::Error, 0), now + RESTART_WINDOW),
+            Some((Incident::Crashed, Action::Restart))
+        );
+    }
+
+    #[test]
+    fn test_crash_action() {
+        // Configs from before crash_action
+        let general = GeneralConfig {
+            auto_restart_on_crash: true,
+            ..Default::default()
+        };
+        assert_eq!(general.on_crash(), CrashAction::Restart);
+
+        let general = GeneralConfig {
+            crash_action: CrashAction::RunCommand,
+            ..Default::default()
+        };
+        let mut watchdog = Watchdog::new(&general);
+        assert_eq!(
+            watchdog.check(&status(SessionState::Error, 0), Instant::now()),
+            Some((Incident::Crashed, Action::RunCommand))
+        );
+    }
+
+    #[test]
+    fn test_run_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let screenshot = dir.path().join("crash shot.png");
+        let out = dir.path().join("out");
+        let command = format!("printf '%s %s' \"$RISING_SUN_INCIDENT\" >'{}'", out.display());
+        run_hook(&command, Incident::Hung, &screenshot).unwrap();
+
+        let expected = format!("hung {}", screenshot.display());
+        for _ in 0..100 {
+            if std::fs::read_to_string(&out).is_ok_and(|s| s == expected) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        panic!("hook did not run");
+    }
+}
@@ -25,9 +25,27 @@
 /* Maximum clipboard size (must fit in ioctl, max ~8KB) */
 #define SUNPCI_MAX_CLIPBOARD 4096
 
+/* Maximum total size of a chunked clipboard transfer */
+#define SUNPCI_MAX_CLIPBOARD_TRANSFER (16 * 1024 * 1024)
+
+/* Maximum entries in the mmap region table */
+#define SUNPCI_MAX_MMAP_REGIONS 8
+
 /* Maximum drive mappings */
 #define SUNPCI_MAX_DRIVE_MAPS 24
 
+/* Maximum events returned by one GET_EVENTS */
+#define SUNPCI_MAX_EVENTS 8
+
+/* Saved state is read and written in chunks of this size */
+#define SUNPCI_STATE_CHUNK 4096
+
+/* Maximum log entries returned by one READ_LOG */
+#define SUNPCI_MAX_LOG_ENTRIES 16
+
+/* Log message length, including the terminating NUL */
+#define SUNPCI_LOG_MESSAGE 112
+
 /* ============================================================================
  * ioctl Commands
  * ============================================================================ */
@@ -38,11 +56,18 @@
 #define SUNPCI_IOC_START_SESSION    _IOW(SUNPCI_IOC_MAGIC, 2, struct sunpci_session_config)
 #define SUNPCI_IOC_STOP_SESSION     _IO(SUNPCI_IOC_MAGIC, 3)
 #define SUNPCI_IOC_RESET_SESSION    _IO(SUNPCI_IOC_MAGIC, 4)
+#define SUNPCI_IOC_CLAIM_SESSION    _IOWR(SUNPCI_IOC_MAGIC, 5, struct sunpci_session_owner)
+#define SUNPCI_IOC_RELEASE_SESSION  _IO(SUNPCI_IOC_MAGIC, 6)
+#define SUNPCI_IOC_GET_OWNER        _IOR(SUNPCI_IOC_MAGIC, 7, struct sunpci_session_owner)
+#define SUNPCI_IOC_REQUEST_SHUTDOWN _IO(SUNPCI_IOC_MAGIC, 8)
 
 /* Display */
 #define SUNPCI_IOC_GET_DISPLAY      _IOR(SUNPCI_IOC_MAGIC, 10, struct sunpci_display_info)
 #define SUNPCI_IOC_SET_DISPLAY      _IOW(SUNPCI_IOC_MAGIC, 11, struct sunpci_display_config)
 #define SUNPCI_IOC_GET_FRAMEBUFFER  _IOR(SUNPCI_IOC_MAGIC, 12, struct sunpci_framebuffer)
+#define SUNPCI_IOC_GET_MMAP_REGIONS _IOR(SUNPCI_IOC_MAGIC, 13, struct sunpci_mmap_regions)
+#define SUNPCI_IOC_GET_PALETTE      _IOR(SUNPCI_IOC_MAGIC, 14, struct sunpci_palette)
+#define SUNPCI_IOC_GET_TEXT_CURSOR  _IOR(SUNPCI_IOC_MAGIC, 15, struct sunpci_text_cursor)
 
 /* Storage */
 #define SUNPCI_IOC_MOUNT_DISK       _IOW(SUNPCI_IOC_MAGIC, 20, struct sunpci_disk_mount)
@@ -51,14 +76,24 @@
 #define SUNPCI_IOC_EJECT_CDROM      _IO(SUNPCI_IOC_MAGIC, 23)
 #define SUNPCI_IOC_MOUNT_FLOPPY     _IOW(SUNPCI_IOC_MAGIC, 24, struct sunpci_floppy_mount)
 #define SUNPCI_IOC_EJECT_FLOPPY     _IOW(SUNPCI_IOC_MAGIC, 25, struct sunpci_floppy_slot)
+#define SUNPCI_IOC_CDROM_PASSTHROUGH _IOW(SUNPCI_IOC_MAGIC, 26, struct sunpci_cdrom_passthrough)
+#define SUNPCI_IOC_SCSI_COMMAND     _IOWR(SUNPCI_IOC_MAGIC, 27, struct sunpci_scsi_command)
+#define SUNPCI_IOC_SCSI_PT_NEXT     _IOWR(SUNPCI_IOC_MAGIC, 28, struct sunpci_scsi_command)
+#define SUNPCI_IOC_SCSI_PT_COMPLETE _IOW(SUNPCI_IOC_MAGIC, 29, struct sunpci_scsi_command)
 
 /* Input */
 #define SUNPCI_IOC_KEYBOARD_EVENT   _IOW(SUNPCI_IOC_MAGIC, 30, struct sunpci_key_event)
 #define SUNPCI_IOC_MOUSE_EVENT      _IOW(SUNPCI_IOC_MAGIC, 31, struct sunpci_mouse_event)
+#define SUNPCI_IOC_GET_KEYBOARD_LEDS _IOR(SUNPCI_IOC_MAGIC, 32, struct sunpci_keyboard_leds)
+#define SUNPCI_IOC_SET_KEYBOARD_LEDS _IOW(SUNPCI_IOC_MAGIC, 33, struct sunpci_keyboard_leds)
+#define SUNPCI_IOC_MOUSE_ABS_EVENT  _IOW(SUNPCI_IOC_MAGIC, 34, struct sunpci_mouse_abs_event)
+#define SUNPCI_IOC_GAMEPORT_EVENT   _IOW(SUNPCI_IOC_MAGIC, 35, struct sunpci_gameport_event)
 
 /* Clipboard */
 #define SUNPCI_IOC_SET_CLIPBOARD    _IOW(SUNPCI_IOC_MAGIC, 40, struct sunpci_clipboard)
 #define SUNPCI_IOC_GET_CLIPBOARD    _IOR(SUNPCI_IOC_MAGIC, 41, struct sunpci_clipboard)
+#define SUNPCI_IOC_SET_CLIPBOARD_CHUNK _IOW(SUNPCI_IOC_MAGIC, 42, struct sunpci_clipboard_chunk)
+#define SUNPCI_IOC_GET_CLIPBOARD_CHUNK _IOWR(SUNPCI_IOC_MAGIC, 43, struct sunpci_clipboard_chunk)
 
 /* Filesystem redirection */
 #define SUNPCI_IOC_ADD_DRIVE_MAP    _IOW(SUNPCI_IOC_MAGIC, 50, struct sunpci_drive_mapping)
@@ -67,6 +102,8 @@
 /* Network */
 #define SUNPCI_IOC_SET_NETWORK      _IOW(SUNPCI_IOC_MAGIC, 60, struct sunpci_network_config)
 #define SUNPCI_IOC_GET_NETWORK      _IOR(SUNPCI_IOC_MAGIC, 61, struct sunpci_network_status)
+#define SUNPCI_IOC_NET_SEND_FRAME   _IOW(SUNPCI_IOC_MAGIC, 62, struct sunpci_net_frame)
+#define SUNPCI_IOC_NET_RECV_FRAME   _IOR(SUNPCI_IOC_MAGIC, 63, struct sunpci_net_frame)
 
 /* Audio */
 #define SUNPCI_IOC_GET_AUDIO_FORMAT _IOR(SUNPCI_IOC_MAGIC, 70, struct sunpci_audio_format)
@@ -74,6 +111,20 @@
 #define SUNPCI_IOC_GET_AUDIO_VOLUME _IOR(SUNPCI_IOC_MAGIC, 72, struct sunpci_audio_volume)
 #define SUNPCI_IOC_GET_AUDIO_STATUS _IOR(SUNPCI_IOC_MAGIC, 73, struct sunpci_audio_status)
 #define SUNPCI_IOC_READ_AUDIO       _IOWR(SUNPCI_IOC_MAGIC, 74, struct sunpci_audio_buffer)
+#define SUNPCI_IOC_WRITE_AUDIO      _IOWR(SUNPCI_IOC_MAGIC, 75, struct sunpci_audio_buffer)
+#define SUNPCI_IOC_READ_MIDI        _IOWR(SUNPCI_IOC_MAGIC, 76, struct sunpci_midi_buffer)
+
+/* Events */
+#define SUNPCI_IOC_GET_EVENTS       _IOR(SUNPCI_IOC_MAGIC, 80, struct sunpci_event_batch)
+
+/* Saved state */
+#define SUNPCI_IOC_SUSPEND_SESSION  _IOR(SUNPCI_IOC_MAGIC, 90, struct sunpci_state_info)
+#define SUNPCI_IOC_READ_STATE       _IOWR(SUNPCI_IOC_MAGIC, 91, struct sunpci_state_chunk)
+#define SUNPCI_IOC_WRITE_STATE      _IOW(SUNPCI_IOC_MAGIC, 92, struct sunpci_state_chunk)
+#define SUNPCI_IOC_RESUME_SESSION   _IOW(SUNPCI_IOC_MAGIC, 93, struct sunpci_state_info)
+
+/* Driver log */
+#define SUNPCI_IOC_READ_LOG         _IOWR(SUNPCI_IOC_MAGIC, 100, struct sunpci_log_batch)
 
 /* ============================================================================
  * Session Management Structures
@@ -100,11 +151,14 @@ enum sunpci_state {
     SUNPCI_STATE_RUNNING  = 2,
     SUNPCI_STATE_STOPPING = 3,
     SUNPCI_STATE_ERROR    = 4,
+    SUNPCI_STATE_SUSPENDED = 5,  /* Guest frozen for a state save or restore */
 };
 
 /**
  * struct sunpci_status - Session status
  * @state: Current session state (enum sunpci_state)
+ * @guest_idle_ms: Milliseconds since the guest last signalled the host
+ *                 (0 when no session is running)
  * @uptime_ns: Session uptime in nanoseconds
  * @disk_activity: Bitmap of active drives (bit 0=C, bit 1=D, etc.)
  * @network_rx_packets: Network packets received
@@ -116,7 +170,7 @@ enum sunpci_state {
  */
 struct sunpci_status {
     __u32 state;
-    __u32 _reserved1;        /* was cpu_usage - not meaningful for real hardware */
+    __u32 guest_idle_ms;     /* was cpu_usage - not meaningful for real hardware */
     __u32 _reserved2;        /* was memory_used_lo */
     __u32 _reserved3;        /* was memory_used_hi */
     __u32 uptime_ns_lo;
@@ -132,6 +186,13 @@ struct sunpci_status {
 #define SUNPCI_FLAG_CLIPBOARD_ENABLED  (1 << 1)
 #define SUNPCI_FLAG_CLIPBOARD_TO_HOST  (1 << 2)
 #define SUNPCI_FLAG_CLIPBOARD_TO_GUEST (1 << 3)
+#define SUNPCI_FLAG_RESUME             (1 << 4)  /* Start suspended; see RESUME_SESSION */
+
+/* Boot devices; a device with no medium falls back to the default order */
+#define SUNPCI_BOOT_DEFAULT            0         /* Hard disk, then CD-ROM */
+#define SUNPCI_BOOT_HARD_DISK          1
+#define SUNPCI_BOOT_CDROM              2         /* El Torito */
+#define SUNPCI_BOOT_FLOPPY             3
 
 /**
  * struct sunpci_session_config - Session configuration
@@ -139,6 +200,7 @@ struct sunpci_status {
  * @primary_disk: Path to primary disk image (C:)
  * @secondary_disk: Path to secondary disk image (D:)
  * @bios_path: Path to BIOS file (empty for default)
+ * @boot_device: Drive the BIOS boots from (SUNPCI_BOOT_*)
  *
  * Note: Memory is physically installed on SunPCi card, not configurable.
  */
@@ -148,6 +210,29 @@ struct sunpci_session_config {
     char primary_disk[SUNPCI_MAX_PATH];
     char secondary_disk[SUNPCI_MAX_PATH];
     char bios_path[SUNPCI_MAX_PATH];
+    __u32 boot_device;
+};
+
+/* Claim flags */
+#define SUNPCI_CLAIM_FORCE             (1 << 0)  /* Take over from a live owner */
+
+/**
+ * struct sunpci_session_owner - Frontend driving the running session
+ * @pid: Owning process (0 if the session has no owner)
+ * @control_port: Owner's control API TCP port (0 if it has none)
+ * @flags: SUNPCI_CLAIM_* on claim, 0 otherwise
+ *
+ * A session outlives the frontend that started it. The owner is the open
+ * file that last claimed it; closing that file, stopping the session or
+ * RELEASE_SESSION clears it. CLAIM_SESSION fails with -EBUSY while another
+ * file owns the session unless SUNPCI_CLAIM_FORCE is set, and returns the
+ * previous owner. A frontend taking over asks the owner to release through
+ * its control port, so the guest keeps running across the handoff.
+ */
+struct sunpci_session_owner {
+    __u32 pid;
+    __u16 control_port;
+    __u16 flags;
 };
 
 /* ============================================================================
@@ -159,9 +244,14 @@ struct sunpci_session_config {
  * @width: Display width in pixels
  * @height: Display height in pixels
  * @color_depth: Bits per pixel (1, 2, 4, 8, 15, 16, 24, 32)
- * @mode: Display mode (0=text, 1=graphics)
+ * @mode: Display mode (0=text, 1=graphics) in the low byte, plus
+ *        SUNPCI_DISPLAY_MODE_* flags
  * @text_cols: Text mode columns
  * @text_rows: Text mode rows
+ *
+ * In text mode the framebuffer starts with @text_cols * @text_rows
+ * character cells of two bytes, the character code followed by its
+ * attribute, row by row.
  */
 struct sunpci_display_info {
     __u32 width;
@@ -175,6 +265,11 @@ struct sunpci_display_info {
 /* Display mode values */
 #define SUNPCI_DISPLAY_MODE_TEXT     0
 #define SUNPCI_DISPLAY_MODE_GRAPHICS 1
+#define SUNPCI_DISPLAY_MODE_MASK     0xFF
+
+/* Display mode flags (upper bits of @mode) */
+#define SUNPCI_DISPLAY_MODE_INTERLACED (1 << 8)  /* framebuffer holds one field */
+#define SUNPCI_DISPLAY_MODE_ODD_FIELD  (1 << 9)  /* current field is odd */
 
 /* Display configuration flags */
 #define SUNPCI_DISPLAY_MAINTAIN_ASPECT (1 << 0)
@@ -221,6 +316,85 @@ struct sunpci_framebuffer {
     __u32 format;
 };
 
+/* mmap region identifiers */
+#define SUNPCI_REGION_FRAMEBUFFER 0  /* Guest framebuffer (BAR2) */
+#define SUNPCI_REGION_TEXT        1  /* Text mode character/attribute buffer */
+#define SUNPCI_REGION_AUDIO_RING  2  /* Audio sample ring */
+#define SUNPCI_REGION_EVENT_RING  3  /* Driver event ring */
+#define SUNPCI_REGION_SHMEM       4  /* Raw shared memory (BAR1) */
+
+/* mmap region flags */
+#define SUNPCI_REGION_READ   (1 << 0)
+#define SUNPCI_REGION_WRITE  (1 << 1)
+
+/**
+ * struct sunpci_mmap_region - One mappable region
+ * @id: Region identifier (SUNPCI_REGION_*)
+ * @flags: Access flags (SUNPCI_REGION_*)
+ * @offset_lo: mmap offset in bytes (low 32 bits, page aligned)
+ * @offset_hi: mmap offset in bytes (high 32 bits)
+ * @size_lo: Region size in bytes (low 32 bits)
+ * @size_hi: Region size in bytes (high 32 bits)
+ */
+struct sunpci_mmap_region {
+    __u32 id;
+    __u32 flags;
+    __u32 offset_lo;
+    __u32 offset_hi;
+    __u32 size_lo;
+    __u32 size_hi;
+};
+
+/**
+ * struct sunpci_mmap_regions - Table of mappable regions
+ * @count: Number of valid entries in @regions
+ * @reserved: Reserved for alignment
+ * @regions: Region entries
+ *
+ * Userspace must look regions up by id rather than assume fixed offsets.
+ */
+struct sunpci_mmap_regions {
+    __u32 count;
+    __u32 reserved;
+    struct sunpci_mmap_region regions[SUNPCI_MAX_MMAP_REGIONS];
+};
+
+/* Entries in the indexed 8-bit palette */
+#define SUNPCI_PALETTE_SIZE 256
+
+/**
+ * struct sunpci_palette - Palette for SUNPCI_FORMAT_INDEXED8
+ * @generation: Incremented whenever the guest changes the palette
+ * @count: Number of valid entries in @entries
+ * @entries: Colors as 0x00RRGGBB, 8 bits per component
+ *
+ * PALETTE_CHANGED events carry the new @generation, so a reader that
+ * already has it can skip the ioctl.
+ */
+struct sunpci_palette {
+    __u32 generation;
+    __u32 count;
+    __u32 entries[SUNPCI_PALETTE_SIZE];
+};
+
+/**
+ * struct sunpci_text_cursor - Text mode cursor
+ * @col: Column of the cursor
+ * @row: Row of the cursor
+ * @start: First scanline of the cursor within the character cell
+ * @end: Last scanline of the cursor
+ * @visible: Non-zero if the guest shows the cursor
+ * @reserved: Must be zero
+ */
+struct sunpci_text_cursor {
+    __u32 col;
+    __u32 row;
+    __u8 start;
+    __u8 end;
+    __u8 visible;
+    __u8 reserved;
+};
+
 /* ============================================================================
  * Storage Structures
  * ============================================================================ */
@@ -277,6 +451,89 @@ struct sunpci_floppy_slot {
     __u32 drive;
 };
 
+/* SCSI limits */
+#define SUNPCI_SCSI_CDB_MAX     16
+#define SUNPCI_SCSI_SENSE_MAX   18
+#define SUNPCI_SCSI_DATA_MAX    65536
+
+/**
+ * struct sunpci_cdrom_passthrough - Hand the CD-ROM over to userspace
+ * @enable: Nonzero to enable pass-through, zero to return to images
+ * @reserved: Reserved, must be zero
+ * @device: Host device being passed through (for status only)
+ *
+ * While enabled, the guest's CD-ROM commands are queued for userspace
+ * (SCSI_PT_NEXT) instead of being emulated from an ISO image, and the
+ * guest waits for the answer (SCSI_PT_COMPLETE). The frontend runs them
+ * on a real drive with SG_IO, so audio CDs and copy-protected discs work.
+ * Enabling ejects any mounted image.
+ */
+struct sunpci_cdrom_passthrough {
+    __u32 enable;
+    __u32 reserved;
+    char device[SUNPCI_MAX_PATH];
+};
+
+/**
+ * struct sunpci_scsi_cdb - SCSI command
+ * @cdb: Command Descriptor Block
+ * @cdb_len: CDB length (6, 10, 12 or 16)
+ * @data_direction: 0 = none, 1 = read (to host), 2 = write (to device)
+ * @data_len: Expected transfer length
+ */
+struct sunpci_scsi_cdb {
+    __u8 cdb[SUNPCI_SCSI_CDB_MAX];
+    __u32 cdb_len;
+    __u32 data_direction;
+    __u32 data_len;
+};
+
+/**
+ * struct sunpci_scsi_result - SCSI command result
+ * @status: SCSI status (0x00 GOOD, 0x02 CHECK CONDITION)
+ * @sense_len: Valid bytes in @sense
+ * @reserved: Reserved
+ * @data_len: Bytes actually transferred
+ * @sense: Fixed-format sense data
+ */
+struct sunpci_scsi_result {
+    __u8 status;
+    __u8 sense_len;
+    __u8 reserved[2];
+    __u32 data_len;
+    __u8 sense[SUNPCI_SCSI_SENSE_MAX];
+};
+
+/**
+ * struct sunpci_scsi_command - SCSI command exchanged with userspace
+ * @tag: Command tag (SCSI_PT_NEXT sets it, SCSI_PT_COMPLETE echoes it)
+ * @flags: Reserved, must be zero
+ * @cdb: The command
+ * @result: Its result
+ * @data_ptr: Userspace data buffer
+ * @data_buf_len: Size of the buffer at @data_ptr
+ * @reserved: Reserved
+ *
+ * SCSI_COMMAND runs @cdb on the guest's CD-ROM (emulated or passed
+ * through) and fills @result; data moves through @data_ptr in the
+ * direction of the command.
+ *
+ * SCSI_PT_NEXT returns the next guest command in pass-through mode, with
+ * its write data copied to @data_ptr, or fails with EAGAIN when none is
+ * waiting. SCSI_PT_COMPLETE answers it: @result plus any read data at
+ * @data_ptr. A command not answered within a few seconds is failed with
+ * NOT READY and its late completion gets ENOENT.
+ */
+struct sunpci_scsi_command {
+    __u32 tag;
+    __u32 flags;
+    struct sunpci_scsi_cdb cdb;
+    struct sunpci_scsi_result result;
+    __u64 data_ptr;
+    __u32 data_buf_len;
+    __u32 reserved;
+};
+
 /* ============================================================================
  * Input Structures
  * ============================================================================ */
@@ -314,6 +571,54 @@ struct sunpci_mouse_event {
     __u32 buttons;
 };
 
+/**
+ * struct sunpci_mouse_abs_event - Absolute mouse event (tablet mode)
+ * @x: X position in guest pixels
+ * @y: Y position in guest pixels
+ * @dz: Wheel movement
+ * @buttons: Button state bitmap (SUNPCI_MOUSE_*)
+ */
+struct sunpci_mouse_abs_event {
+    __u32 x;
+    __u32 y;
+    __s32 dz;
+    __u32 buttons;
+};
+
+/* Game port: two joysticks of two axes and two buttons each */
+#define SUNPCI_GAMEPORT_AXES      4
+#define SUNPCI_GAMEPORT_BUTTON_A1 (1 << 0)
+#define SUNPCI_GAMEPORT_BUTTON_A2 (1 << 1)
+#define SUNPCI_GAMEPORT_BUTTON_B1 (1 << 2)
+#define SUNPCI_GAMEPORT_BUTTON_B2 (1 << 3)
+
+/**
+ * struct sunpci_gameport_event - Game port state
+ * @axes: Stick positions, -32768 (left, up) to 32767 (right, down):
+ *        joystick A X and Y, then joystick B X and Y
+ * @buttons: Buttons held (SUNPCI_GAMEPORT_BUTTON_*)
+ * @connected: Bit n set if axes[n] has a stick on it; the others read as
+ *             unplugged, the way BIOS joystick detection expects
+ */
+struct sunpci_gameport_event {
+    __s16 axes[SUNPCI_GAMEPORT_AXES];
+    __u32 buttons;
+    __u32 connected;
+};
+
+/* Keyboard LED flags (PS/2 "set LEDs" bit order) */
+#define SUNPCI_LED_SCROLL_LOCK (1 << 0)
+#define SUNPCI_LED_NUM_LOCK    (1 << 1)
+#define SUNPCI_LED_CAPS_LOCK   (1 << 2)
+
+/**
+ * struct sunpci_keyboard_leds - Keyboard LED state
+ * @leds: LED bitmap (SUNPCI_LED_*)
+ */
+struct sunpci_keyboard_leds {
+    __u32 leds;
+};
+
 /* ============================================================================
  * Clipboard Structures
  * ============================================================================ */
@@ -321,6 +626,8 @@ struct sunpci_mouse_event {
 /* Clipboard formats */
 #define SUNPCI_CLIPBOARD_TEXT    0
 #define SUNPCI_CLIPBOARD_UNICODE 1
+#define SUNPCI_CLIPBOARD_BITMAP  2   /* Device-independent bitmap (CF_DIB) */
+#define SUNPCI_CLIPBOARD_RTF     3   /* Rich Text Format (CF_RTF) */
 
 /**
  * struct sunpci_clipboard - Clipboard data
@@ -334,6 +641,29 @@ struct sunpci_clipboard {
     char data[SUNPCI_MAX_CLIPBOARD];
 };
 
+/**
+ * struct sunpci_clipboard_chunk - One part of a chunked clipboard transfer
+ * @format: Clipboard format (SUNPCI_CLIPBOARD_*)
+ * @total_length: Length of the complete transfer
+ *                (up to SUNPCI_MAX_CLIPBOARD_TRANSFER)
+ * @offset: Offset of this chunk within the transfer
+ * @length: Bytes of data in this chunk
+ * @data: Chunk data (up to SUNPCI_MAX_CLIPBOARD bytes)
+ *
+ * Writers send chunks in order; the transfer is forwarded to the guest
+ * when the chunk ending at @total_length arrives. Readers set @format and
+ * @offset; offset 0 snapshots the guest clipboard and later offsets read
+ * from that snapshot. Transfers over SUNPCI_MAX_CLIPBOARD_TRANSFER fail
+ * with E2BIG.
+ */
+struct sunpci_clipboard_chunk {
+    __u32 format;
+    __u32 total_length;
+    __u32 offset;
+    __u32 length;
+    char data[SUNPCI_MAX_CLIPBOARD];
+};
+
 /* ============================================================================
  * Filesystem Redirection Structures
  * ============================================================================ */
@@ -341,19 +671,47 @@ struct sunpci_clipboard {
 /* Drive mapping flags */
 #define SUNPCI_DRIVE_READONLY  (1 << 0)
 #define SUNPCI_DRIVE_HIDDEN    (1 << 1)
+#define SUNPCI_DRIVE_TZ_OFFSET (1 << 2)  /* tz_offset is valid */
+
+/* How long host file names are shortened to 8.3 names */
+#define SUNPCI_DRIVE_MANGLE_MASK     (3 << 3)
+#define SUNPCI_DRIVE_MANGLE_TAIL     (0 << 3)  /* LONGNA~1.TXT, numbered per directory */
+#define SUNPCI_DRIVE_MANGLE_HASH     (1 << 3)  /* LONG~K3Z.TXT, from a hash of the name */
+#define SUNPCI_DRIVE_MANGLE_TRUNCATE (2 << 3)  /* LONGNAME.TXT, cut short; names may clash */
+
+/* Case of the names the guest sees */
+#define SUNPCI_DRIVE_CASE_MASK       (3 << 5)
+#define SUNPCI_DRIVE_CASE_UPPER      (0 << 5)
+#define SUNPCI_DRIVE_CASE_LOWER      (1 << 5)  /* guest names are looked up in lower case */
+#define SUNPCI_DRIVE_CASE_PRESERVE   (2 << 5)
+
+#define SUNPCI_DRIVE_HIDE_DOTFILES   (1 << 7)  /* Leave out names starting with '.' */
 
 /**
  * struct sunpci_drive_mapping - Drive mapping
  * @letter: Drive letter ('E' through 'Z')
  * @flags: Mapping flags (SUNPCI_DRIVE_*)
- * @reserved: Reserved for alignment
+ * @tz_offset: Minutes east of UTC the guest's file times are in, with
+ *             SUNPCI_DRIVE_TZ_OFFSET (-720 to 840); file times are UTC
+ *             without it
  * @path: Host filesystem path
+ * @max_size_mb: Most the guest may store under @path, in MB (0 = no limit)
+ * @used_lo: Low 32 bits of the bytes stored under @path already
+ * @used_hi: High 32 bits of the bytes stored under @path already
+ *
+ * With a limit, writes that would take the bytes under @path past it fail
+ * with ENOSPC and the drive reports the limit as its size. The driver only
+ * counts what the guest adds, so userspace measures the directory and
+ * re-adds the mapping now and then to pick up other changes.
  */
 struct sunpci_drive_mapping {
     __u8 letter;
     __u8 flags;
-    __u16 reserved;
+    __s16 tz_offset;
     char path[SUNPCI_MAX_PATH];
+    __u32 max_size_mb;
+    __u32 used_lo;
+    __u32 used_hi;
 };
 
 /**
@@ -372,6 +730,10 @@ struct sunpci_drive_letter {
 /* Network flags */
 #define SUNPCI_NET_ENABLED     (1 << 0)
 #define SUNPCI_NET_PROMISCUOUS (1 << 1)
+#define SUNPCI_NET_USER_MODE   (1 << 2)    /* Frames exchanged with userspace, no TAP */
+
+/* Largest Ethernet frame passed through SUNPCI_IOC_NET_*_FRAME */
+#define SUNPCI_NET_MAX_FRAME   1514
 
 /**
  * struct sunpci_network_config - Network configuration
@@ -403,6 +765,23 @@ struct sunpci_network_status {
     __u64 tx_bytes;
 };
 
+/**
+ * struct sunpci_net_frame - Ethernet frame exchanged with userspace
+ * @length: Frame length in bytes
+ * @reserved: Reserved for alignment
+ * @data: Frame data, starting with the Ethernet header
+ *
+ * Only used in SUNPCI_NET_USER_MODE, where a userspace network stack (the
+ * frontend's NAT) takes the place of the TAP device. SEND_FRAME delivers a
+ * frame to the guest; RECV_FRAME returns the next frame the guest sent, or
+ * fails with EAGAIN when none is queued.
+ */
+struct sunpci_net_frame {
+    __u32 length;
+    __u32 reserved;
+    __u8 data[SUNPCI_NET_MAX_FRAME];
+};
+
 /* ============================================================================
  * Audio Structures
  * ============================================================================ */
@@ -416,6 +795,8 @@ struct sunpci_network_status {
 #define SUNPCI_AUDIO_PLAYING     (1 << 0)    /* Playback active */
 #define SUNPCI_AUDIO_AVAILABLE   (1 << 1)    /* Audio hardware present */
 #define SUNPCI_AUDIO_MUTED       (1 << 2)    /* Output muted */
+#define SUNPCI_AUDIO_RECORDING   (1 << 3)    /* Guest is recording */
+#define SUNPCI_AUDIO_MIDI        (1 << 4)    /* MPU-401 output waiting */
 
 /**
  * struct sunpci_audio_format - Audio format information
@@ -466,12 +847,19 @@ struct sunpci_audio_status {
     __u32 reserved;
 };
 
-/* Maximum audio buffer size for single ioctl read */
+/* Maximum audio buffer size for single ioctl read or write */
 #define SUNPCI_AUDIO_MAX_BUFFER  16384
 
+/*
+ * Audio written with WRITE_AUDIO is 16-bit signed little-endian mono at
+ * this rate; it is converted to the format the guest records in.
+ */
+#define SUNPCI_AUDIO_CAPTURE_RATE 22050
+
 /**
- * struct sunpci_audio_buffer - Audio buffer for reading samples
- * @size: On input: max bytes to read. On output: bytes actually read.
+ * struct sunpci_audio_buffer - Audio buffer for reading or writing samples
+ * @size: On input: max bytes to read, or bytes to write. On output: bytes
+ *        actually read, or taken (less when the guest's input buffer is full).
  * @data: Audio sample data
  */
 struct sunpci_audio_buffer {
@@ -480,4 +868,162 @@ struct sunpci_audio_buffer {
     __u8 data[SUNPCI_AUDIO_MAX_BUFFER];
 };
 
+/* Maximum MIDI bytes for a single ioctl read */
+#define SUNPCI_MIDI_MAX_BUFFER   1024
+
+/**
+ * struct sunpci_midi_buffer - Bytes the guest wrote to the MPU-401
+ * @size: On input: max bytes to read. On output: bytes actually read.
+ * @data: MIDI bytes in the order written to the data port in UART mode,
+ *        running status and all
+ */
+struct sunpci_midi_buffer {
+    __u32 size;
+    __u32 reserved;
+    __u8 data[SUNPCI_MIDI_MAX_BUFFER];
+};
+
+/* ============================================================================
+ * Event Structures
+ * ============================================================================ */
+
+/* Event types */
+#define SUNPCI_EVENT_OVERFLOW        0  /* Reader fell behind; older events lost */
+#define SUNPCI_EVENT_EJECT_REQUEST   1  /* Guest asked to eject @drive */
+#define SUNPCI_EVENT_MEDIA_CHANGED   2  /* Medium in @drive inserted or removed */
+#define SUNPCI_EVENT_DISPLAY_CHANGED 3  /* Guest set a display mode; re-read GET_DISPLAY */
+#define SUNPCI_EVENT_SESSION_CHANGED 4  /* Session started, stopped or reset */
+#define SUNPCI_EVENT_CLIPBOARD       5  /* Guest clipboard has new data */
+#define SUNPCI_EVENT_PALETTE_CHANGED 6  /* Guest changed the indexed 8-bit palette */
+#define SUNPCI_EVENT_POWER_OFF       7  /* Guest powered itself off; stop the session */
+
+/* Event flags */
+#define SUNPCI_EVENT_MEDIA_PRESENT  (1 << 0)  /* MEDIA_CHANGED: a medium is in */
+
+/**
+ * struct sunpci_event - One asynchronous driver event
+ * @type: Event type (SUNPCI_EVENT_*)
+ * @drive: BIOS drive number (0x00/0x01 floppy, 0x80/0x81 disk, 0xE0 CD-ROM)
+ *         for media events, else zero
+ * @flags: SUNPCI_EVENT_* flags; the new SUNPCI_STATE_* for SESSION_CHANGED,
+ *         the SUNPCI_CLIPBOARD_* format for CLIPBOARD, the palette
+ *         generation for PALETTE_CHANGED
+ * @reserved: Must be zero
+ * @path: Image path for MEDIA_CHANGED with a medium present, else empty
+ */
+struct sunpci_event {
+    __u32 type;
+    __u32 drive;
+    __u32 flags;
+    __u32 reserved;
+    char path[SUNPCI_MAX_PATH];
+};
+
+/**
+ * struct sunpci_event_batch - Events returned by GET_EVENTS
+ * @count: Number of valid entries in @events
+ * @reserved: Must be zero
+ * @events: Oldest first
+ *
+ * Every open file reads every event posted after it was opened. GET_EVENTS
+ * never blocks; poll() reports POLLIN while events are pending. A full
+ * batch may mean more are waiting.
+ */
+struct sunpci_event_batch {
+    __u32 count;
+    __u32 reserved;
+    struct sunpci_event events[SUNPCI_MAX_EVENTS];
+};
+
+/* ============================================================================
+ * Saved State Structures
+ * ============================================================================ */
+
+/**
+ * struct sunpci_state_info - Size of a guest state snapshot
+ * @size_lo: Snapshot size in bytes (low 32 bits)
+ * @size_hi: Snapshot size in bytes (high 32 bits)
+ * @version: Snapshot format version reported by the card
+ * @reserved: Must be zero
+ *
+ * SUSPEND_SESSION freezes a running guest, snapshots it on the card and
+ * leaves the session SUSPENDED; the snapshot is then read with READ_STATE
+ * and the session stopped. To restore, START_SESSION with
+ * SUNPCI_FLAG_RESUME starts the session SUSPENDED, the snapshot is written
+ * with WRITE_STATE and RESUME_SESSION loads it and sets the session RUNNING.
+ */
+struct sunpci_state_info {
+    __u32 size_lo;
+    __u32 size_hi;
+    __u32 version;
+    __u32 reserved;
+};
+
+/**
+ * struct sunpci_state_chunk - One part of a state snapshot
+ * @offset_lo: Offset of this chunk within the snapshot (low 32 bits)
+ * @offset_hi: Offset of this chunk within the snapshot (high 32 bits)
+ * @length: Bytes of data in this chunk; READ_STATE sets it to the bytes read
+ * @reserved: Must be zero
+ * @data: Chunk data (up to SUNPCI_STATE_CHUNK bytes)
+ *
+ * Only valid while the session is SUSPENDED.
+ */
+struct sunpci_state_chunk {
+    __u32 offset_lo;
+    __u32 offset_hi;
+    __u32 length;
+    __u32 reserved;
+    __u8 data[SUNPCI_STATE_CHUNK];
+};
+
+/* ============================================================================
+ * Driver Log Structures
+ * ============================================================================ */
+
+/* Log levels (kernel log levels) */
+#define SUNPCI_LOG_ERR     3
+#define SUNPCI_LOG_WARN    4
+#define SUNPCI_LOG_INFO    6
+#define SUNPCI_LOG_DEBUG   7
+
+/**
+ * struct sunpci_log_entry - One message from the driver's log
+ * @seq: Sequence number; consecutive messages have consecutive numbers
+ * @level: SUNPCI_LOG_*
+ * @time_lo: Wall-clock time in nanoseconds since the epoch (low 32 bits)
+ * @time_hi: Wall-clock time (high 32 bits)
+ * @message: NUL-terminated text without the "sunpciN: " prefix or newline;
+ *           longer messages are cut short
+ */
+struct sunpci_log_entry {
+    __u32 seq;
+    __u32 level;
+    __u32 time_lo;
+    __u32 time_hi;
+    char message[SUNPCI_LOG_MESSAGE];
+};
+
+/**
+ * struct sunpci_log_batch - Messages returned by READ_LOG
+ * @since: In: sequence number of the first message wanted (0 for the
+ *         oldest still held)
+ * @count: Out: number of valid entries in @entries
+ * @next: Out: @since for the next call
+ * @dropped: Out: messages after @since that were no longer held
+ * @entries: Oldest first
+ *
+ * The driver keeps its recent messages, at every level including debug,
+ * in a ring per device; READ_LOG reads it without consuming anything, so
+ * any number of readers can follow it. A full batch may mean more are
+ * waiting.
+ */
+struct sunpci_log_batch {
+    __u32 since;
+    __u32 count;
+    __u32 next;
+    __u32 dropped;
+    struct sunpci_log_entry entries[SUNPCI_MAX_LOG_ENTRIES];
+};
+
 #endif /* _UAPI_SUNPCI_IOCTL_H */
@@ -1,7 +1,7 @@
 /*
  * SunPCi driver - Audio subsystem
  *
- * Handles ESS1869 sound card emulation. The guest x86 runs stock ESS
+ * Handles ESS1869 sound card and MPU-401 emulation. The guest x86 runs stock ESS
  * drivers which program the ISA DMA controller. The card's firmware
  * traps DMA transfers and writes PCM data to a fixed region in shared
  * memory. This driver reads from that region and can expose it to
@@ -9,6 +9,7 @@
  *
  * Audio data flows:
  *   Guest App → ESS Driver → ISA DMA → Firmware → Shared Memory → Host
+ *   Host → Shared Memory (capture ring) → Firmware → ISA DMA → ESS Driver
  *
  * The ESS1869 supports:
  *   - Sample rates: 5512, 11025, 22050, 44100 Hz
@@ -57,10 +58,30 @@
 #define AUDIO_HDR_VOLUME_L      0x14    /* Left volume (0-255) */
 #define AUDIO_HDR_VOLUME_R      0x18    /* Right volume (0-255) */
 #define AUDIO_HDR_STATUS        0x1C    /* Status flags */
+#define AUDIO_HDR_CAPTURE_WRITE 0x20    /* Host: next capture byte written */
+#define AUDIO_HDR_CAPTURE_READ  0x24    /* Guest: next capture byte read */
+#define AUDIO_HDR_MIDI_WRITE    0x28    /* Guest: next MIDI byte written */
+#define AUDIO_HDR_MIDI_READ     0x2C    /* Host: next MIDI byte read */
 
 /* Audio data starts after header */
 #define AUDIO_DATA_OFFSET       (AUDIO_BUFFER_OFFSET + AUDIO_HDR_SIZE)
 
+/*
+ * Capture ring, after the playback region: a byte ring of 16-bit signed
+ * mono at SUNPCI_AUDIO_CAPTURE_RATE, which the firmware converts to the
+ * format the guest records in. One byte is always left free so a full
+ * ring differs from an empty one.
+ */
+#define AUDIO_CAPTURE_OFFSET    (AUDIO_BUFFER_OFFSET + AUDIO_BUFFER_SIZE)
+#define AUDIO_CAPTURE_SIZE      0x8000      /* 32KB (~0.7s) */
+
+/*
+ * MIDI ring, after the capture ring: the bytes the guest writes to the
+ * MPU-401 data port in UART mode, in order, as the firmware traps them
+ */
+#define AUDIO_MIDI_OFFSET       (AUDIO_CAPTURE_OFFSET + AUDIO_CAPTURE_SIZE)
+#define AUDIO_MIDI_SIZE         0x1000      /* 4KB (~1.3s at 31250 baud) */
+
 /* Format flags */
 #define AUDIO_FMT_16BIT         (1 << 0)    /* 16-bit samples (vs 8-bit) */
 #define AUDIO_FMT_STEREO        (1 << 1)    /* Stereo (vs mono) */
@@ -68,7 +89,7 @@
 
 /* Status flags */
 #define AUDIO_STATUS_PLAYING    (1 << 0)    /* Playback active */
-#define AUDIO_STATUS_RECORDING  (1 << 1)    /* Recording active (future) */
+#define AUDIO_STATUS_RECORDING  (1 << 1)    /* Guest is recording */
 #define AUDIO_STATUS_MUTED      (1 << 2)    /* Output muted */
 
 /* Magic value */
@@ -93,6 +114,10 @@ struct sunpci_audio_state {
     
     /* Buffer access */
     void __iomem *buffer_base;      /* Audio region in shmem */
+    void __iomem *capture_base;     /* Capture ring, NULL if shmem lacks it */
+    u32 capture_write;              /* Our capture write offset */
+    void __iomem *midi_base;        /* MIDI ring, NULL if shmem lacks it */
+    u32 midi_read;                  /* Our MIDI read offset */
     
     /* Current format */
     u32 sample_rate;
@@ -165,15 +190,15 @@ int sunpci_audio_init(struct sunpci_device *dev)
     u32 magic;
     
     if (!dev->shmem_base) {
-        pr_warn("sunpci%d: no shared memory for audio\n", dev->minor);
+        sunpci_warn(dev, "no shared memory for audio\n");
         return -ENODEV;
     }
     
     /* Check if shared memory is large enough for audio region */
     if (dev->shmem_len < AUDIO_BUFFER_OFFSET + AUDIO_BUFFER_SIZE) {
-        pr_warn("sunpci%d: shmem too small for audio (%llu < %u)\n",
-                dev->minor, (unsigned long long)dev->shmem_len,
-                AUDIO_BUFFER_OFFSET + AUDIO_BUFFER_SIZE);
+        sunpci_warn(dev, "shmem too small for audio (%llu < %u)\n",
+                    (unsigned long long)dev->shmem_len,
+                    AUDIO_BUFFER_OFFSET + AUDIO_BUFFER_SIZE);
         return -ENOMEM;
     }
     
@@ -188,8 +213,7 @@ int sunpci_audio_init(struct sunpci_device *dev)
     /* Check for audio magic - indicates firmware supports audio */
     magic = audio_read_hdr(audio, AUDIO_HDR_MAGIC);
     if (magic != AUDIO_MAGIC) {
-        pr_info("sunpci%d: audio not available (magic=%08x)\n",
-                dev->minor, magic);
+        sunpci_info(dev, "audio not available (magic=%08x)\n", magic);
         /* Not an error - card may not have audio */
         kfree(audio);
         return 0;
@@ -202,6 +226,20 @@ int sunpci_audio_init(struct sunpci_device *dev)
     audio->volume_right = audio_read_hdr(audio, AUDIO_HDR_VOLUME_R) & 0xFF;
     audio->read_ptr = audio_read_hdr(audio, AUDIO_HDR_READ_PTR);
     
+    /* Recording needs the capture ring after the playback region */
+    if (dev->shmem_len >= AUDIO_CAPTURE_OFFSET + AUDIO_CAPTURE_SIZE) {
+        audio->capture_base = dev->shmem_base + AUDIO_CAPTURE_OFFSET;
+        audio->capture_write = audio_read_hdr(audio, AUDIO_HDR_CAPTURE_WRITE) %
+                               AUDIO_CAPTURE_SIZE;
+    }
+    
+    /* MIDI needs the ring after that */
+    if (dev->shmem_len >= AUDIO_MIDI_OFFSET + AUDIO_MIDI_SIZE) {
+        audio->midi_base = dev->shmem_base + AUDIO_MIDI_OFFSET;
+        audio->midi_read = audio_read_hdr(audio, AUDIO_HDR_MIDI_READ) %
+                           AUDIO_MIDI_SIZE;
+    }
+    
     /* Default to 44.1kHz stereo 16-bit if not set */
     if (audio->sample_rate == 0) {
         audio->sample_rate = 44100;
@@ -210,8 +248,7 @@ int sunpci_audio_init(struct sunpci_device *dev)
     
     dev->audio_state = audio;
     
-    pr_info("sunpci%d: audio initialized (%u Hz, %s, %s)\n",
-            dev->minor,
+    sunpci_info(dev, "audio initialized (%u Hz, %s, %s)\n",
             audio->sample_rate,
             (audio->format & AUDIO_FMT_16BIT) ? "16-bit" : "8-bit",
             (audio->format & AUDIO_FMT_STEREO) ? "stereo" : "mono");
@@ -274,6 +311,85 @@ int sunpci_audio_read(struct sunpci_device *dev, void *buffer, size_t size)
     return copied;
 }
 
+/*
+ * Write samples to the capture ring for the guest to record
+ * Returns number of bytes taken (whole samples, fewer than @size when the
+ * ring is nearly full), or negative error
+ */
+int sunpci_audio_write(struct sunpci_device *dev, const void *buffer, size_t size)
+{
+    struct sunpci_audio_state *audio = dev->audio_state;
+    unsigned long flags;
+    u32 read, space, first;
+    
+    if (!audio)
+        return -ENODEV;
+    if (!audio->capture_base)
+        return -EOPNOTSUPP;
+    
+    spin_lock_irqsave(&audio->lock, flags);
+    
+    read = audio_read_hdr(audio, AUDIO_HDR_CAPTURE_READ) % AUDIO_CAPTURE_SIZE;
+    space = (read + AUDIO_CAPTURE_SIZE - audio->capture_write - 1) % AUDIO_CAPTURE_SIZE;
+    size = min_t(size_t, size, space) & ~(size_t)1;
+    
+    /* In up to two pieces, around the end of the ring */
+    first = min_t(u32, size, AUDIO_CAPTURE_SIZE - audio->capture_write);
+    memcpy_toio(audio->capture_base + audio->capture_write, buffer, first);
+    if (size > first)
+        memcpy_toio(audio->capture_base, buffer + first, size - first);
+    
+    audio->capture_write = (audio->capture_write + size) % AUDIO_CAPTURE_SIZE;
+    audio_write_hdr(audio, AUDIO_HDR_CAPTURE_WRITE, audio->capture_write);
+    
+    spin_unlock_irqrestore(&audio->lock, flags);
+    
+    return size;
+}
+
+/*
+ * Bytes waiting in the MIDI ring (called with the lock held)
+ */
+static u32 audio_midi_pending(struct sunpci_audio_state *audio)
+{
+    u32 write = audio_read_hdr(audio, AUDIO_HDR_MIDI_WRITE) % AUDIO_MIDI_SIZE;
+
+    return (write + AUDIO_MIDI_SIZE - audio->midi_read) % AUDIO_MIDI_SIZE;
+}
+
+/*
+ * Read the bytes the guest wrote to the MPU-401
+ * Returns number of bytes copied, or negative error
+ */
+int sunpci_midi_read(struct sunpci_device *dev, void *buffer, size_t size)
+{
+    struct sunpci_audio_state *audio = dev->audio_state;
+    unsigned long flags;
+    u32 first;
+    
+    if (!audio)
+        return -ENODEV;
+    if (!audio->midi_base)
+        return -EOPNOTSUPP;
+    
+    spin_lock_irqsave(&audio->lock, flags);
+    
+    size = min_t(size_t, size, audio_midi_pending(audio));
+    
+    /* In up to two pieces, around the end of the ring */
+    first = min_t(u32, size, AUDIO_MIDI_SIZE - audio->midi_read);
+    memcpy_fromio(buffer, audio->midi_base + audio->midi_read, first);
+    if (size > first)
+        memcpy_fromio(buffer + first, audio->midi_base, size - first);
+    
+    audio->midi_read = (audio->midi_read + size) % AUDIO_MIDI_SIZE;
+    audio_write_hdr(audio, AUDIO_HDR_MIDI_READ, audio->midi_read);
+    
+    spin_unlock_irqrestore(&audio->lock, flags);
+    
+    return size;
+}
+
 /*
  * Get current audio format info
  */
@@ -310,8 +426,27 @@ int sunpci_audio_set_volume(struct sunpci_device *dev, u8 left, u8 right)
     audio->volume_left = left;
     audio->volume_right = right;
     
-    audio_write_hdr(audio, AUDIO_HDR_VOLUME_L, left);
-    audio_write_hdr(audio, AUDIO_HDR_VOLUME_R, right);
+    if (!audio->muted) {
+        audio_write_hdr(audio, AUDIO_HDR_VOLUME_L, left);
+        audio_write_hdr(audio, AUDIO_HDR_VOLUME_R, right);
+    }
+    
+    return 0;
+}
+
+/*
+ * Mute or unmute output, keeping the volume levels
+ */
+int sunpci_audio_set_muted(struct sunpci_device *dev, bool muted)
+{
+    struct sunpci_audio_state *audio = dev->audio_state;
+    
+    if (!audio)
+        return -ENODEV;
+    
+    audio->muted = muted;
+    audio_write_hdr(audio, AUDIO_HDR_VOLUME_L, muted ? 0 : audio->volume_left);
+    audio_write_hdr(audio, AUDIO_HDR_VOLUME_R, muted ? 0 : audio->volume_right);
     
     return 0;
 }
@@ -357,19 +492,19 @@ int sunpci_audio_handle_message(struct sunpci_device *dev,
     struct sunpci_audio_state *audio = dev->audio_state;
     
     if (!audio) {
-        pr_debug("sunpci%d: audio message but no audio state\n", dev->minor);
+        sunpci_dbg(dev, "audio message but no audio state\n");
         return -ENODEV;
     }
     
     switch (command) {
     case AUDIO_CMD_START:
         audio->playing = true;
-        pr_debug("sunpci%d: audio playback started\n", dev->minor);
+        sunpci_dbg(dev, "audio playback started\n");
         break;
         
     case AUDIO_CMD_STOP:
         audio->playing = false;
-        pr_debug("sunpci%d: audio playback stopped\n", dev->minor);
+        sunpci_dbg(dev, "audio playback stopped\n");
         break;
         
     case AUDIO_CMD_SET_FORMAT:
@@ -377,8 +512,8 @@ int sunpci_audio_handle_message(struct sunpci_device *dev,
             const __le32 *params = payload;
             audio->sample_rate = le32_to_cpu(params[0]);
             audio->format = le32_to_cpu(params[1]);
-            pr_debug("sunpci%d: audio format: %u Hz, flags=%08x\n",
-                     dev->minor, audio->sample_rate, audio->format);
+            sunpci_dbg(dev, "audio format: %u Hz, flags=%08x\n",
+                       audio->sample_rate, audio->format);
         }
         break;
         
@@ -392,12 +527,11 @@ int sunpci_audio_handle_message(struct sunpci_device *dev,
         
     case AUDIO_CMD_BUFFER_DONE:
         /* Guest has filled a buffer - wake up any waiters */
-        pr_debug("sunpci%d: audio buffer ready\n", dev->minor);
+        sunpci_trace(dev, "audio buffer ready\n");
         break;
         
     default:
-        pr_debug("sunpci%d: unknown audio command %04x\n",
-                 dev->minor, command);
+        sunpci_dbg(dev, "unknown audio command %04x\n", command);
         return -EINVAL;
     }
     
@@ -426,3 +560,42 @@ void sunpci_audio_get_stats(struct sunpci_device *dev,
     if (buffers)
         *buffers = audio->buffers_processed;
 }
+
+/*
+ * Get audio status for userspace
+ */
+int sunpci_audio_get_status(struct sunpci_device *dev,
+                            struct sunpci_audio_status *status)
+{
+    struct sunpci_audio_state *audio = dev->audio_state;
+    unsigned long flags;
+    u32 hw_status;
+    
+    memset(status, 0, sizeof(*status));
+    if (!audio)
+        return 0;   /* No audio hardware: AVAILABLE clear */
+    
+    spin_lock_irqsave(&audio->lock, flags);
+    
+    hw_status = audio_read_hdr(audio, AUDIO_HDR_STATUS);
+    status->flags = SUNPCI_AUDIO_AVAILABLE;
+    if (audio->playing || (hw_status & AUDIO_STATUS_PLAYING))
+        status->flags |= SUNPCI_AUDIO_PLAYING;
+    if (audio->muted)
+        status->flags |= SUNPCI_AUDIO_MUTED;
+    if (audio->capture_base && (hw_status & AUDIO_STATUS_RECORDING))
+        status->flags |= SUNPCI_AUDIO_RECORDING;
+    if (audio->midi_base && audio_midi_pending(audio))
+        status->flags |= SUNPCI_AUDIO_MIDI;
+    
+    status->sample_rate = audio->sample_rate;
+    status->format = audio->format;
+    status->buffer_available = audio_available_slots(audio) * AUDIO_SLOT_SIZE;
+    status->samples_played_lo = (u32)audio->samples_played;
+    status->samples_played_hi = (u32)(audio->samples_played >> 32);
+    status->underruns = min_t(u64, audio->underruns, U32_MAX);
+    
+    spin_unlock_irqrestore(&audio->lock, flags);
+    
+    return 0;
+}
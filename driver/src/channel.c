@@ -145,12 +145,12 @@ int sunpci_channel_create(struct sunpci_device *dev,
     utf16le_to_ascii(req->name, le32_to_cpu(req->name_len), 
                      name, sizeof(name));
     
-    dev_dbg(&dev->pdev->dev, "channel create: '%s'\n", name);
+    sunpci_dbg(dev, "channel create: '%s'\n", name);
     
     /* Look up dispatcher for this channel name */
     dispatcher = channel_name_to_dispatcher(name);
     if (dispatcher < 0) {
-        dev_warn(&dev->pdev->dev, "unknown channel: '%s'\n", name);
+        sunpci_warn(dev, "unknown channel: '%s'\n", name);
         rsp->status = cpu_to_le32(2);  /* Unknown channel */
         rsp->channel_id = 0;
         return 0;
@@ -202,7 +202,7 @@ int sunpci_channel_create(struct sunpci_device *dev,
     
     mutex_unlock(&reg->lock);
     
-    dev_info(&dev->pdev->dev, "channel '%s' created (id=%u, disp=%u)\n",
+    sunpci_info(dev, "channel '%s' created (id=%u, disp=%u)\n",
              name, ch->id, ch->dispatcher);
     
     rsp->status = 0;
@@ -226,7 +226,7 @@ int sunpci_channel_delete(struct sunpci_device *dev, u32 channel_id)
     
     for (i = 0; i < MAX_CHANNELS; i++) {
         if (reg->channels[i].active && reg->channels[i].id == channel_id) {
-            dev_info(&dev->pdev->dev, "channel '%s' deleted\n",
+            sunpci_info(dev, "channel '%s' deleted\n",
                      reg->channels[i].name);
             memset(&reg->channels[i], 0, sizeof(reg->channels[i]));
             mutex_unlock(&reg->lock);
@@ -400,7 +400,7 @@ int sunpci_channel_handle_nt_disk(struct sunpci_device *dev,
         
     default:
         kfree(data_buf);
-        dev_dbg(&dev->pdev->dev, "NT: unknown command 0x%02x\n", nt_req->command);
+        sunpci_dbg(dev, "NT: unknown command 0x%02x\n", nt_req->command);
         return -EINVAL;
     }
     
@@ -5,50 +5,109 @@
  * The guest runs Windows which uses UTF-16LE for Unicode text.
  */
 
+#include <linux/mm.h>
 #include <linux/slab.h>
 #include <linux/uaccess.h>
 
 #include "sunpci.h"
 #include "ipc.h"
 
-/**
- * sunpci_clip_set - Send clipboard data to guest
- * @dev: Device
- * @clip: Clipboard data from userspace
- *
- * Sends the host clipboard content to the Windows guest.
- * Text is expected to be in the format specified by clip->format.
- */
-int sunpci_clip_set(struct sunpci_device *dev,
-                    const struct sunpci_clipboard *clip)
+/* Map a SUNPCI_CLIPBOARD_* format to the Windows clipboard format */
+static u32 clip_format_to_ipc(u32 format)
+{
+    switch (format) {
+    case SUNPCI_CLIPBOARD_UNICODE:
+        return CLIP_FORMAT_UNICODE;
+    case SUNPCI_CLIPBOARD_BITMAP:
+        return CLIP_FORMAT_DIB;
+    case SUNPCI_CLIPBOARD_RTF:
+        return CLIP_FORMAT_RTF;
+    default:
+        return CLIP_FORMAT_TEXT;
+    }
+}
+
+/* Map a Windows clipboard format to SUNPCI_CLIPBOARD_* */
+static u32 clip_format_from_ipc(u32 format)
+{
+    switch (format) {
+    case CLIP_FORMAT_UNICODE:
+        return SUNPCI_CLIPBOARD_UNICODE;
+    case CLIP_FORMAT_DIB:
+        return SUNPCI_CLIPBOARD_BITMAP;
+    case CLIP_FORMAT_RTF:
+        return SUNPCI_CLIPBOARD_RTF;
+    default:
+        return SUNPCI_CLIPBOARD_TEXT;
+    }
+}
+
+/* Send data larger than one message as consecutive CLIP_CMD_SET_PART parts */
+static int clip_send_parts(struct sunpci_device *dev, u32 format,
+                           const u8 *data, u32 length)
+{
+    struct sunpci_clip_part *msg;
+    size_t rsp_len;
+    u32 offset, part;
+    int ret = 0;
+
+    msg = kmalloc(sizeof(*msg) + SUNPCI_CLIP_MAX_SIZE, GFP_KERNEL);
+    if (!msg)
+        return -ENOMEM;
+
+    msg->format = cpu_to_le32(clip_format_to_ipc(format));
+    msg->total_length = cpu_to_le32(length);
+
+    for (offset = 0; offset < length; offset += part) {
+        part = min_t(u32, length - offset, SUNPCI_CLIP_MAX_SIZE);
+        msg->offset = cpu_to_le32(offset);
+        msg->length = cpu_to_le32(part);
+        memcpy(msg + 1, data + offset, part);
+
+        /* Wait for each part so a large transfer can't overrun the ring */
+        ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CLIP, CLIP_CMD_SET_PART,
+                                  msg, sizeof(*msg) + part,
+                                  NULL, 0, &rsp_len, SUNPCI_CMD_TIMEOUT);
+        if (ret < 0)
+            break;
+    }
+
+    kfree(msg);
+    return ret;
+}
+
+/* Send clipboard data of any length (up to SUNPCI_MAX_CLIPBOARD_TRANSFER) */
+static int clip_send(struct sunpci_device *dev, u32 format,
+                     const void *data, u32 length)
 {
     struct sunpci_clip_data *msg;
     size_t msg_len;
     int ret;
 
-    if (!dev || !clip)
-        return -EINVAL;
-
     if (dev->state != SUNPCI_STATE_RUNNING)
         return -ENODEV;
 
-    if (clip->length == 0)
+    if (length == 0)
         return 0;  /* Nothing to send */
 
-    if (clip->length > SUNPCI_MAX_CLIPBOARD)
-        return -EINVAL;
+    if (length > SUNPCI_MAX_CLIPBOARD_TRANSFER)
+        return -E2BIG;
+
+    if (length > SUNPCI_CLIP_MAX_SIZE) {
+        ret = clip_send_parts(dev, format, data, length);
+        goto done;
+    }
 
     /* Allocate message with data appended */
-    msg_len = sizeof(*msg) + clip->length;
+    msg_len = sizeof(*msg) + length;
     msg = kmalloc(msg_len, GFP_KERNEL);
     if (!msg)
         return -ENOMEM;
 
     /* Build clipboard message */
-    msg->format = cpu_to_le32(clip->format == SUNPCI_CLIPBOARD_UNICODE ?
-                              CLIP_FORMAT_UNICODE : CLIP_FORMAT_TEXT);
-    msg->length = cpu_to_le32(clip->length);
-    memcpy(msg + 1, clip->data, clip->length);
+    msg->format = cpu_to_le32(clip_format_to_ipc(format));
+    msg->length = cpu_to_le32(length);
+    memcpy(msg + 1, data, length);
 
     /* Send to guest */
     ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_CLIP, CLIP_CMD_SET,
@@ -56,17 +115,38 @@ int sunpci_clip_set(struct sunpci_device *dev,
 
     kfree(msg);
 
+done:
     if (ret < 0) {
-        dev_dbg(&dev->pdev->dev, "clip_set failed: %d\n", ret);
+        sunpci_dbg(dev, "clip_set failed: %d\n", ret);
         return ret;
     }
 
-    dev_dbg(&dev->pdev->dev, "clipboard sent: %u bytes, format %u\n",
-            clip->length, clip->format);
+    sunpci_dbg(dev, "clipboard sent: %u bytes, format %u\n",
+            length, format);
 
     return 0;
 }
 
+/**
+ * sunpci_clip_set - Send clipboard data to guest
+ * @dev: Device
+ * @clip: Clipboard data from userspace
+ *
+ * Sends the host clipboard content to the Windows guest.
+ * Text is expected to be in the format specified by clip->format.
+ */
+int sunpci_clip_set(struct sunpci_device *dev,
+                    const struct sunpci_clipboard *clip)
+{
+    if (!dev || !clip)
+        return -EINVAL;
+
+    if (clip->length > SUNPCI_MAX_CLIPBOARD)
+        return -EINVAL;
+
+    return clip_send(dev, clip->format, clip->data, clip->length);
+}
+
 /**
  * sunpci_clip_get - Request clipboard data from guest
  * @dev: Device
@@ -103,14 +183,14 @@ int sunpci_clip_get(struct sunpci_device *dev,
                               SUNPCI_CMD_TIMEOUT);
 
     if (ret < 0) {
-        dev_dbg(&dev->pdev->dev, "clip_get failed: %d\n", ret);
+        sunpci_dbg(dev, "clip_get failed: %d\n", ret);
         kfree(rsp);
         return ret;
     }
 
     /* Parse response */
     if (actual_len < sizeof(*rsp)) {
-        dev_warn(&dev->pdev->dev, "clip_get: short response\n");
+        sunpci_warn(dev, "clip_get: short response\n");
         kfree(rsp);
         return -EIO;
     }
@@ -119,10 +199,7 @@ int sunpci_clip_get(struct sunpci_device *dev,
     clip->length = le32_to_cpu(rsp->length);
     
     /* Map Windows format to our format */
-    if (format == CLIP_FORMAT_UNICODE)
-        clip->format = SUNPCI_CLIPBOARD_UNICODE;
-    else
-        clip->format = SUNPCI_CLIPBOARD_TEXT;
+    clip->format = clip_format_from_ipc(format);
 
     /* Copy data, truncating if necessary */
     if (clip->length > SUNPCI_MAX_CLIPBOARD)
@@ -133,7 +210,7 @@ int sunpci_clip_get(struct sunpci_device *dev,
 
     kfree(rsp);
 
-    dev_dbg(&dev->pdev->dev, "clipboard received: %u bytes, format %u\n",
+    sunpci_dbg(dev, "clipboard received: %u bytes, format %u\n",
             clip->length, clip->format);
 
     return 0;
@@ -178,11 +255,227 @@ void sunpci_clip_handle_notify(struct sunpci_device *dev,
     if (length > 0)
         memcpy(dev->clipboard.data, clip_data + 1, length);
 
+    sunpci_event_post(dev, SUNPCI_EVENT_CLIPBOARD, 0,
+                      dev->clipboard.format, NULL);
     mutex_unlock(&dev->mutex);
 
-    dev_dbg(&dev->pdev->dev, "guest clipboard updated: %u bytes\n", length);
+    sunpci_dbg(dev, "guest clipboard updated: %u bytes\n", length);
 
     /* Signal userspace via poll/select that clipboard changed */
     dev->clipboard_changed = true;
     wake_up_interruptible(&dev->clipboard_wait);
 }
+
+/* Free a chunked transfer buffer and mark it idle */
+static void clip_xfer_reset(struct sunpci_clip_xfer *xfer)
+{
+    kvfree(xfer->data);
+    memset(xfer, 0, sizeof(*xfer));
+}
+
+/**
+ * sunpci_clip_set_chunk - Accept one chunk of a host -> guest transfer
+ * @dev: Device
+ * @chunk: Chunk from userspace
+ *
+ * Chunks must arrive in order starting at offset 0, which (re)starts the
+ * transfer. The assembled data is sent to the guest once the last chunk
+ * arrives, in as many IPC messages as it takes.
+ */
+int sunpci_clip_set_chunk(struct sunpci_device *dev,
+                          const struct sunpci_clipboard_chunk *chunk)
+{
+    struct sunpci_clip_xfer *tx = &dev->clip_tx;
+    struct sunpci_clip_xfer done = { 0 };
+    int ret = 0;
+
+    if (chunk->length > SUNPCI_MAX_CLIPBOARD || chunk->total_length == 0)
+        return -EINVAL;
+
+    if (chunk->total_length > SUNPCI_MAX_CLIPBOARD_TRANSFER)
+        return -E2BIG;
+
+    mutex_lock(&dev->mutex);
+
+    if (chunk->offset == 0) {
+        clip_xfer_reset(tx);
+        tx->data = kvmalloc(chunk->total_length, GFP_KERNEL);
+        if (!tx->data) {
+            ret = -ENOMEM;
+            goto out;
+        }
+        tx->format = chunk->format;
+        tx->length = chunk->total_length;
+    }
+
+    /* Out-of-order or mismatched chunk: abandon the transfer */
+    if (!tx->data || chunk->offset != tx->filled ||
+        chunk->format != tx->format || chunk->total_length != tx->length ||
+        chunk->length > tx->length - tx->filled) {
+        clip_xfer_reset(tx);
+        ret = -EINVAL;
+        goto out;
+    }
+
+    memcpy(tx->data + tx->filled, chunk->data, chunk->length);
+    tx->filled += chunk->length;
+
+    if (tx->filled == tx->length) {
+        /* Take ownership so the IPC happens without the device mutex */
+        done = *tx;
+        memset(tx, 0, sizeof(*tx));
+    }
+
+out:
+    mutex_unlock(&dev->mutex);
+
+    if (done.data) {
+        ret = clip_send(dev, done.format, done.data, done.length);
+        kvfree(done.data);
+    }
+
+    return ret;
+}
+
+/*
+ * Read the whole guest clipboard in one format with CLIP_CMD_GET_PART,
+ * into a kvmalloc'd buffer. Returns -ENODATA if the guest has nothing in
+ * that format.
+ */
+static int clip_fetch(struct sunpci_device *dev, u32 format,
+                      u8 **data_out, u32 *length_out)
+{
+    struct sunpci_clip_part_req req;
+    struct sunpci_clip_part *rsp;
+    size_t rsp_len, actual_len;
+    u32 total = 0, offset = 0, part;
+    u8 *data = NULL;
+    int ret;
+
+    if (dev->state != SUNPCI_STATE_RUNNING)
+        return -ENODEV;
+
+    rsp_len = sizeof(*rsp) + SUNPCI_CLIP_MAX_SIZE;
+    rsp = kmalloc(rsp_len, GFP_KERNEL);
+    if (!rsp)
+        return -ENOMEM;
+
+    /* Ask for the requested format; the guest replies with what it has */
+    req.format = cpu_to_le32(clip_format_to_ipc(format));
+
+    do {
+        req.offset = cpu_to_le32(offset);
+        ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CLIP, CLIP_CMD_GET_PART,
+                                  &req, sizeof(req),
+                                  rsp, rsp_len, &actual_len,
+                                  SUNPCI_CMD_TIMEOUT);
+        if (ret < 0)
+            goto fail;
+
+        part = le32_to_cpu(rsp->length);
+        if (actual_len < sizeof(*rsp) || part > SUNPCI_CLIP_MAX_SIZE ||
+            actual_len < sizeof(*rsp) + part ||
+            le32_to_cpu(rsp->offset) != offset) {
+            ret = -EIO;
+            goto fail;
+        }
+
+        if (offset == 0) {
+            total = le32_to_cpu(rsp->total_length);
+            if (total == 0 ||
+                clip_format_from_ipc(le32_to_cpu(rsp->format)) != format) {
+                ret = -ENODATA;
+                goto fail;
+            }
+            if (total > SUNPCI_MAX_CLIPBOARD_TRANSFER) {
+                ret = -E2BIG;
+                goto fail;
+            }
+            data = kvmalloc(total, GFP_KERNEL);
+            if (!data) {
+                ret = -ENOMEM;
+                goto fail;
+            }
+        }
+
+        /* The guest clipboard changed mid-transfer, or sent an empty part */
+        if (le32_to_cpu(rsp->total_length) != total || part == 0 ||
+            part > total - offset) {
+            ret = -EIO;
+            goto fail;
+        }
+
+        memcpy(data + offset, rsp + 1, part);
+        offset += part;
+    } while (offset < total);
+
+    kfree(rsp);
+    *data_out = data;
+    *length_out = total;
+    return 0;
+
+fail:
+    kvfree(data);
+    kfree(rsp);
+    return ret;
+}
+
+/**
+ * sunpci_clip_get_chunk - Read one chunk of the guest clipboard
+ * @dev: Device
+ * @chunk: In: format and offset. Out: total_length, length and data
+ *
+ * Offset 0 requests a fresh snapshot of the guest clipboard in the given
+ * format; later offsets read from that snapshot. Returns -ENODATA if the
+ * guest has nothing in that format.
+ */
+int sunpci_clip_get_chunk(struct sunpci_device *dev,
+                          struct sunpci_clipboard_chunk *chunk)
+{
+    struct sunpci_clip_xfer *rx = &dev->clip_rx;
+    u32 length;
+    int ret = 0;
+
+    if (chunk->offset == 0) {
+        u8 *data;
+
+        ret = clip_fetch(dev, chunk->format, &data, &length);
+        if (ret < 0)
+            return ret;
+
+        mutex_lock(&dev->mutex);
+        clip_xfer_reset(rx);
+        rx->format = chunk->format;
+        rx->length = length;
+        rx->data = data;
+        mutex_unlock(&dev->mutex);
+    }
+
+    mutex_lock(&dev->mutex);
+
+    if (!rx->data || rx->format != chunk->format) {
+        ret = -ENODATA;
+    } else if (chunk->offset > rx->length) {
+        ret = -EINVAL;
+    } else {
+        chunk->total_length = rx->length;
+        chunk->length = min_t(u32, rx->length - chunk->offset,
+                              SUNPCI_MAX_CLIPBOARD);
+        memcpy(chunk->data, rx->data + chunk->offset, chunk->length);
+    }
+
+    mutex_unlock(&dev->mutex);
+    return ret;
+}
+
+/**
+ * sunpci_clip_cleanup - Free chunked transfer buffers
+ * @dev: Device
+ */
+void sunpci_clip_cleanup(struct sunpci_device *dev)
+{
+    mutex_lock(&dev->mutex);
+    clip_xfer_reset(&dev->clip_tx);
+    clip_xfer_reset(&dev->clip_rx);
+    mutex_unlock(&dev->mutex);
+}
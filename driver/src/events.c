@@ -0,0 +1,104 @@
+/*
+ * SunPCi driver - Asynchronous events
+ *
+ * Events the frontend cannot learn from the ioctls it makes itself: the
+ * guest asking to eject a medium or setting a display mode, new guest
+ * clipboard data, and media or session state changed through another open
+ * file (the CLI, or a second frontend). Events go into a per-device ring;
+ * each open file reads from its own position, kept in f_pos since the
+ * device has no read(). A file that falls a full ring behind gets
+ * SUNPCI_EVENT_OVERFLOW and resumes at the oldest event still held.
+ */
+
+#include <linux/poll.h>
+#include <linux/slab.h>
+#include <linux/string.h>
+#include <linux/uaccess.h>
+
+#include "sunpci.h"
+
+void sunpci_events_init(struct sunpci_device *dev)
+{
+    spin_lock_init(&dev->event_lock);
+    init_waitqueue_head(&dev->event_wait);
+    dev->event_seq = 0;
+}
+
+/* Start a newly opened file at the next event */
+void sunpci_events_open(struct sunpci_device *dev, struct file *file)
+{
+    spin_lock(&dev->event_lock);
+    file->f_pos = dev->event_seq;
+    spin_unlock(&dev->event_lock);
+}
+
+void sunpci_event_post(struct sunpci_device *dev, u32 type, u32 drive,
+                       u32 flags, const char *path)
+{
+    struct sunpci_event *event;
+
+    spin_lock(&dev->event_lock);
+    event = &dev->events[dev->event_seq % SUNPCI_EVENT_RING];
+    memset(event, 0, sizeof(*event));
+    event->type = type;
+    event->drive = drive;
+    event->flags = flags;
+    if (path)
+        strscpy(event->path, path, SUNPCI_MAX_PATH);
+    dev->event_seq++;
+    spin_unlock(&dev->event_lock);
+
+    wake_up_interruptible(&dev->event_wait);
+}
+
+/* Copy this file's pending events into @batch */
+static void events_read(struct sunpci_device *dev, struct file *file,
+                        struct sunpci_event_batch *batch)
+{
+    u64 pos;
+
+    spin_lock(&dev->event_lock);
+    pos = file->f_pos;
+    if (dev->event_seq - pos > SUNPCI_EVENT_RING) {
+        batch->events[batch->count++].type = SUNPCI_EVENT_OVERFLOW;
+        pos = dev->event_seq - SUNPCI_EVENT_RING;
+    }
+    while (pos < dev->event_seq && batch->count < SUNPCI_MAX_EVENTS)
+        batch->events[batch->count++] = dev->events[pos++ % SUNPCI_EVENT_RING];
+    file->f_pos = pos;
+    spin_unlock(&dev->event_lock);
+}
+
+int sunpci_ioctl_get_events(struct sunpci_device *dev, struct file *file,
+                            unsigned long arg)
+{
+    struct sunpci_event_batch *batch;
+    int ret = 0;
+
+    /* Too big for the stack */
+    batch = kzalloc(sizeof(*batch), GFP_KERNEL);
+    if (!batch)
+        return -ENOMEM;
+
+    events_read(dev, file, batch);
+    if (copy_to_user((void __user *)arg, batch, sizeof(*batch)))
+        ret = -EFAULT;
+
+    kfree(batch);
+    return ret;
+}
+
+__poll_t sunpci_poll(struct file *file, poll_table *wait)
+{
+    struct sunpci_device *dev = file->private_data;
+    __poll_t mask = 0;
+
+    poll_wait(file, &dev->event_wait, wait);
+
+    spin_lock(&dev->event_lock);
+    if (file->f_pos != dev->event_seq)
+        mask |= EPOLLIN | EPOLLRDNORM;
+    spin_unlock(&dev->event_lock);
+
+    return mask;
+}
@@ -131,12 +131,28 @@ struct sunpci_fsd_state {
  * DOS time format conversion
  * DOS date: Bits 0-4=Day, 5-8=Month, 9-15=Year-1980
  * DOS time: Bits 0-4=Seconds/2, 5-10=Minutes, 11-15=Hours
+ *
+ * DOS keeps local time; tz_offset is the mapping's offset from UTC in
+ * minutes (see struct sunpci_drive_mapping). Times outside 1980-2107
+ * are clamped to the ends of the range DOS can represent.
  */
-static void unix_to_dos_time(time64_t unix_time, u16 *dos_date, u16 *dos_time)
+static void unix_to_dos_time(time64_t unix_time, s16 tz_offset,
+                             u16 *dos_date, u16 *dos_time)
 {
     struct tm tm;
     
-    time64_to_tm(unix_time, 0, &tm);
+    time64_to_tm(unix_time, tz_offset * 60, &tm);
+    
+    if (tm.tm_year < 80) {
+        *dos_date = (1 << 5) | 1;
+        *dos_time = 0;
+        return;
+    }
+    if (tm.tm_year > 207) {
+        *dos_date = (127 << 9) | (12 << 5) | 31;
+        *dos_time = (23 << 11) | (59 << 5) | 29;
+        return;
+    }
     
     *dos_date = ((tm.tm_year - 80) << 9) |
                 ((tm.tm_mon + 1) << 5) |
@@ -147,7 +163,8 @@ static void unix_to_dos_time(time64_t unix_time, u16 *dos_date, u16 *dos_time)
                 (tm.tm_sec / 2);
 }
 
-static __maybe_unused time64_t dos_to_unix_time(u16 dos_date, u16 dos_time)
+static __maybe_unused time64_t dos_to_unix_time(u16 dos_date, u16 dos_time,
+                                                s16 tz_offset)
 {
     struct tm tm = {0};
     
@@ -160,7 +177,179 @@ static __maybe_unused time64_t dos_to_unix_time(u16 dos_date, u16 dos_time)
     tm.tm_sec = (dos_time & 0x1F) * 2;
     
     return mktime64(tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday,
-                    tm.tm_hour, tm.tm_min, tm.tm_sec);
+                    tm.tm_hour, tm.tm_min, tm.tm_sec) -
+           (time64_t)tz_offset * 60;
+}
+
+/*
+ * Offset from UTC of the file times on the drive of a guest path
+ */
+static s16 fsd_drive_tz_offset(struct sunpci_device *dev, const char *guest_path)
+{
+    char drive_letter;
+    int i;
+    
+    if (!guest_path || !guest_path[0])
+        return 0;
+    
+    drive_letter = toupper(guest_path[0]);
+    for (i = 0; i < SUNPCI_MAX_DRIVE_MAPS; i++) {
+        if (dev->drive_maps[i].letter == drive_letter)
+            return dev->drive_maps[i].tz_offset;
+    }
+    return 0;
+}
+
+/*
+ * Mapping of a drive letter, NULL if the drive is not mapped
+ */
+static struct sunpci_drive_map *fsd_find_map(struct sunpci_device *dev, char letter)
+{
+    int i;
+    
+    letter = toupper(letter);
+    for (i = 0; i < SUNPCI_MAX_DRIVE_MAPS; i++) {
+        if (dev->drive_maps[i].letter == letter)
+            return &dev->drive_maps[i];
+    }
+    return NULL;
+}
+
+/*
+ * Copy one part of a long name into an 8.3 name, dropping spaces and dots
+ * and replacing characters DOS does not allow with '_'
+ * Returns true if anything was lost
+ */
+static bool fsd_clean_part(const char *part, size_t len, u8 flags,
+                           char *out, size_t *out_len)
+{
+    static const char allowed[] = "!#$%&'()-@^_`{}~";
+    bool lossy = false;
+    size_t i;
+    
+    *out_len = 0;
+    for (i = 0; i < len; i++) {
+        unsigned char c = part[i];
+        
+        if (c == ' ' || c == '.') {
+            lossy = true;
+            continue;
+        }
+        if (c >= 0x80 || !(isalnum(c) || strchr(allowed, c))) {
+            c = '_';
+            lossy = true;
+        }
+        switch (flags & SUNPCI_DRIVE_CASE_MASK) {
+        case SUNPCI_DRIVE_CASE_LOWER:
+            c = tolower(c);
+            break;
+        case SUNPCI_DRIVE_CASE_PRESERVE:
+            break;
+        default:
+            c = toupper(c);
+            break;
+        }
+        out[(*out_len)++] = c;
+    }
+    return lossy;
+}
+
+/*
+ * FNV-1a hash of a name, for SUNPCI_DRIVE_MANGLE_HASH
+ */
+static u32 fsd_name_hash(const char *name)
+{
+    u32 hash = 0x811c9dc5;
+    
+    while (*name) {
+        hash ^= (u8)*name++;
+        hash *= 0x01000193;
+    }
+    return hash;
+}
+
+/*
+ * Shorten a host name to the 8.3 name the guest sees, following the
+ * drive's SUNPCI_DRIVE_MANGLE_* and SUNPCI_DRIVE_CASE_* flags
+ * @tail: Number of the name among those shortening alike (numeric tails)
+ *
+ * The same rules as rising_sun_common::short_name, which previews them.
+ * Used by directory listing once the guest side asks for it.
+ */
+static __maybe_unused void fsd_short_name(const char *name, u8 flags,
+                                          unsigned int tail, char out[13])
+{
+    static const char digits[] = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    char base[256], ext[256];
+    size_t base_len, ext_len, name_len = strlen(name);
+    const char *dot;
+    bool lossy;
+    int n;
+    
+    if (!strcmp(name, ".") || !strcmp(name, "..")) {
+        strscpy(out, name, 13);
+        return;
+    }
+    if (name_len > 255)
+        name_len = 255;
+    
+    dot = strrchr(name, '.');
+    if (dot == name || dot >= name + name_len)
+        dot = NULL;
+    
+    lossy = fsd_clean_part(name, dot ? dot - name : name_len, flags,
+                           base, &base_len);
+    ext_len = 0;
+    if (dot)
+        lossy |= fsd_clean_part(dot + 1, name + name_len - dot - 1, flags,
+                                ext, &ext_len);
+    lossy |= name[0] == '.' || base_len == 0 || base_len > 8 || ext_len > 3;
+    
+    if (base_len == 0)
+        base[base_len++] = '_';
+    
+    if (lossy) {
+        switch (flags & SUNPCI_DRIVE_MANGLE_MASK) {
+        case SUNPCI_DRIVE_MANGLE_TRUNCATE:
+            base_len = min_t(size_t, base_len, 8);
+            break;
+        case SUNPCI_DRIVE_MANGLE_HASH: {
+            u32 hash = fsd_name_hash(name) % (36 * 36 * 36);
+            bool lower = (flags & SUNPCI_DRIVE_CASE_MASK) == SUNPCI_DRIVE_CASE_LOWER;
+            
+            base_len = min_t(size_t, base_len, 4);
+            base[base_len] = '~';
+            for (n = 3; n >= 1; n--) {
+                char c = digits[hash % 36];
+                
+                base[base_len + n] = lower ? tolower(c) : c;
+                hash /= 36;
+            }
+            base_len += 4;
+            break;
+        }
+        default: {
+            char suffix[8];
+            int suffix_len;
+            
+            suffix_len = snprintf(suffix, sizeof(suffix), "~%u",
+                                  clamp(tail, 1U, 999999U));
+            base_len = min_t(size_t, base_len, 8 - suffix_len);
+            memcpy(base + base_len, suffix, suffix_len);
+            base_len += suffix_len;
+            break;
+        }
+        }
+    }
+    
+    memcpy(out, base, base_len);
+    if (ext_len) {
+        out[base_len] = '.';
+        memcpy(out + base_len + 1, ext, min_t(size_t, ext_len, 3));
+        out[base_len + 1 + min_t(size_t, ext_len, 3)] = '\0';
+    } else {
+        out[base_len] = '\0';
+    }
 }
 
 /*
@@ -214,7 +403,7 @@ static int fsd_translate_path(struct sunpci_device *dev,
             /* Found mapping - concatenate paths */
             size_t base_len = strlen(dev->drive_maps[i].path);
             size_t rel_len = strlen(rel_path);
-            char *p;
+            char *p, *rel;
             
             if (base_len + rel_len + 2 > host_len)
                 return -ENAMETOOLONG;
@@ -234,6 +423,23 @@ static int fsd_translate_path(struct sunpci_device *dev,
                     *p = '/';
             }
             
+            /* Names the guest sees in lower case are lower case on the host */
+            rel = host_path + strlen(host_path) - rel_len;
+            if ((dev->drive_maps[i].flags & SUNPCI_DRIVE_CASE_MASK) ==
+                SUNPCI_DRIVE_CASE_LOWER) {
+                for (p = rel; *p; p++)
+                    *p = tolower(*p);
+            }
+            
+            /* Hidden dotfiles cannot be reached by name either */
+            if (dev->drive_maps[i].flags & SUNPCI_DRIVE_HIDE_DOTFILES) {
+                for (p = rel; *p; p++) {
+                    if ((p == rel || p[-1] == '/') && p[0] == '.' &&
+                        p[1] && p[1] != '/' && !(p[1] == '.' && (!p[2] || p[2] == '/')))
+                        return -ENOENT;
+                }
+            }
+            
             return 0;
         }
     }
@@ -319,7 +525,7 @@ int sunpci_fsd_init(struct sunpci_device *dev)
     
     dev->fsd_state = fsd;
     
-    pr_info("sunpci%d: filesystem redirection initialized\n", dev->minor);
+    sunpci_info(dev, "filesystem redirection initialized\n");
     return 0;
 }
 
@@ -419,6 +625,7 @@ static int fsd_handle_open(struct sunpci_fsd_state *fsd,
     }
     
     strscpy(h->path, host_path, sizeof(h->path));
+    h->drive_letter = toupper(req->path[0]);
     h->is_directory = S_ISDIR(file_inode(h->filp)->i_mode);
     
     fsd->files_opened++;
@@ -427,8 +634,7 @@ static int fsd_handle_open(struct sunpci_fsd_state *fsd,
     rsp->handle = cpu_to_le32(h->guest_handle);
     *rsp_len = sizeof(*rsp);
     
-    pr_debug("sunpci%d: fsd open %s -> handle %u\n",
-             fsd->dev->minor, host_path, h->guest_handle);
+    sunpci_trace(fsd->dev, "fsd open %s -> handle %u\n", host_path, h->guest_handle);
     
     return 0;
 }
@@ -462,7 +668,7 @@ static int fsd_handle_close(struct sunpci_fsd_state *fsd,
         return 0;
     }
     
-    pr_debug("sunpci%d: fsd close handle %u\n", fsd->dev->minor, handle);
+    sunpci_trace(fsd->dev, "fsd close handle %u\n", handle);
     
     fsd_free_handle(fsd, h);
     fsd->files_closed++;
@@ -548,8 +754,9 @@ static int fsd_handle_write(struct sunpci_fsd_state *fsd,
         __le32 bytes_written;
     } __packed *rsp = response;
     
+    struct sunpci_drive_map *map;
     struct fsd_handle *h;
-    loff_t pos;
+    loff_t pos, size;
     ssize_t bytes;
     u32 count;
     
@@ -569,6 +776,18 @@ static int fsd_handle_write(struct sunpci_fsd_state *fsd,
         count = len - 16;
     
     pos = le64_to_cpu(req->offset);
+    
+    /* Only writes that make the file longer count towards a size limit */
+    map = fsd_find_map(fsd->dev, h->drive_letter);
+    size = i_size_read(file_inode(h->filp));
+    if (map && map->max_bytes && pos + count > size &&
+        map->used + (pos + count - size) > map->max_bytes) {
+        rsp->status = cpu_to_le32(ENOSPC);
+        rsp->bytes_written = 0;
+        *rsp_len = 8;
+        return 0;
+    }
+    
     bytes = kernel_write(h->filp, req->data, count, &pos);
     
     if (bytes < 0) {
@@ -578,6 +797,8 @@ static int fsd_handle_write(struct sunpci_fsd_state *fsd,
         rsp->status = 0;
         rsp->bytes_written = cpu_to_le32(bytes);
         fsd->bytes_written += bytes;
+        if (map && pos > size)
+            map->used += pos - size;
     }
     
     *rsp_len = 8;
@@ -640,8 +861,11 @@ static int fsd_handle_stat(struct sunpci_fsd_state *fsd,
     rsp->status = 0;
     rsp->size_low = cpu_to_le32(stat.size & 0xFFFFFFFF);
     rsp->size_high = cpu_to_le32(stat.size >> 32);
-    unix_to_dos_time(stat.mtime.tv_sec, &rsp->date, &rsp->time);
+    unix_to_dos_time(stat.mtime.tv_sec, fsd_drive_tz_offset(fsd->dev, req->path),
+                     &rsp->date, &rsp->time);
     rsp->attr = mode_to_dos_attr(stat.mode);
+    if (kbasename(host_path)[0] == '.')
+        rsp->attr |= DOS_ATTR_HIDDEN;
     memset(rsp->reserved, 0, sizeof(rsp->reserved));
     
     *rsp_len = sizeof(*rsp);
@@ -685,8 +909,7 @@ static int fsd_handle_mkdir(struct sunpci_fsd_state *fsd,
      * For now, indicate this should be handled by userspace daemon.
      * Return ENOSYS to indicate unimplemented.
      */
-    pr_debug("sunpci%d: mkdir request for %s (delegated to userspace)\n",
-             fsd->dev->minor, host_path);
+    sunpci_dbg(fsd->dev, "mkdir request for %s (delegated to userspace)\n", host_path);
     
     rsp->status = cpu_to_le32(ENOSYS);
     *rsp_len = sizeof(*rsp);
@@ -728,8 +951,8 @@ static int fsd_handle_delete(struct sunpci_fsd_state *fsd,
      * File deletion from kernel space requires complex VFS locking.
      * Delegate to userspace daemon for proper handling.
      */
-    pr_debug("sunpci%d: delete request for %s (delegated to userspace)\n",
-             fsd->dev->minor, host_path);
+    sunpci_dbg(fsd->dev, "delete request for %s (delegated to userspace)\n",
+               host_path);
     
     rsp->status = cpu_to_le32(ENOSYS);
     *rsp_len = sizeof(*rsp);
@@ -782,6 +1005,16 @@ static int fsd_handle_statfs(struct sunpci_fsd_state *fsd,
                 return 0;
             }
             
+            /* A size limit is the size of the drive */
+            if (fsd->dev->drive_maps[i].max_bytes) {
+                u64 max = fsd->dev->drive_maps[i].max_bytes;
+                u64 used = min(fsd->dev->drive_maps[i].used, max);
+                u64 avail = statfs.f_bavail * statfs.f_bsize;
+                
+                statfs.f_blocks = div_u64(max, statfs.f_bsize);
+                statfs.f_bfree = div_u64(min(max - used, avail), statfs.f_bsize);
+            }
+            
             /* Convert to DOS-friendly format */
             rsp->status = 0;
             rsp->total_clusters = cpu_to_le32(statfs.f_blocks);
@@ -857,13 +1090,11 @@ int sunpci_fsd_handle_message(struct sunpci_device *dev,
     case FSD_CMD_LOCK:
     case FSD_CMD_UNLOCK:
         /* File region locking - for multi-user access */
-        pr_debug("sunpci%d: FSD command %04x not yet implemented\n",
-                 dev->minor, command);
+        sunpci_dbg(dev, "FSD command %04x not yet implemented\n", command);
         return -ENOSYS;
         
     default:
-        pr_debug("sunpci%d: unknown FSD command %04x\n",
-                 dev->minor, command);
+        sunpci_dbg(dev, "unknown FSD command %04x\n", command);
         return -EINVAL;
     }
 }
@@ -1,7 +1,8 @@
 /*
  * SunPCi driver - Input event injection
  *
- * Injects keyboard and mouse events into the guest.
+ * Injects keyboard, mouse (relative and absolute) and game port events
+ * into the guest, and tracks the guest's keyboard LEDs.
  */
 
 #include <linux/input.h>
@@ -44,7 +45,7 @@ int sunpci_inject_key(struct sunpci_device *dev,
     ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_INPUT, INPUT_CMD_KEYBOARD,
                               &msg, sizeof(msg), NULL);
     if (ret < 0) {
-        dev_dbg(&dev->pdev->dev, "inject_key failed: %d\n", ret);
+        sunpci_dbg(dev, "inject_key failed: %d\n", ret);
         return ret;
     }
 
@@ -87,9 +88,154 @@ int sunpci_inject_mouse(struct sunpci_device *dev,
     ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_INPUT, INPUT_CMD_MOUSE_MOVE,
                               &msg, sizeof(msg), NULL);
     if (ret < 0) {
-        dev_dbg(&dev->pdev->dev, "inject_mouse failed: %d\n", ret);
+        sunpci_dbg(dev, "inject_mouse failed: %d\n", ret);
         return ret;
     }
 
     return 0;
 }
+
+/**
+ * sunpci_inject_mouse_abs - Inject an absolute mouse event
+ * @dev: Device
+ * @event: Absolute mouse event from userspace
+ *
+ * Moves the guest pointer to a position in guest pixels (tablet mode),
+ * with the button state and any wheel movement.
+ */
+int sunpci_inject_mouse_abs(struct sunpci_device *dev,
+                            const struct sunpci_mouse_abs_event *event)
+{
+    struct sunpci_input_mouse_abs msg;
+    int ret;
+
+    if (!dev || !event)
+        return -EINVAL;
+
+    if (dev->state != SUNPCI_STATE_RUNNING)
+        return -ENODEV;
+
+    msg.x = cpu_to_le32(event->x);
+    msg.y = cpu_to_le32(event->y);
+    msg.wheel = cpu_to_le32(event->dz);
+
+    msg.buttons = 0;
+    if (event->buttons & SUNPCI_MOUSE_LEFT)
+        msg.buttons |= cpu_to_le32(INPUT_MOUSE_LEFT);
+    if (event->buttons & SUNPCI_MOUSE_RIGHT)
+        msg.buttons |= cpu_to_le32(INPUT_MOUSE_RIGHT);
+    if (event->buttons & SUNPCI_MOUSE_MIDDLE)
+        msg.buttons |= cpu_to_le32(INPUT_MOUSE_MIDDLE);
+
+    ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_INPUT, INPUT_CMD_MOUSE_ABS,
+                              &msg, sizeof(msg), NULL);
+    if (ret < 0) {
+        sunpci_dbg(dev, "inject_mouse_abs failed: %d\n", ret);
+        return ret;
+    }
+
+    return 0;
+}
+
+/**
+ * sunpci_inject_gameport - Set the game port state
+ * @dev: Device
+ * @event: Game port state from userspace
+ *
+ * Replaces the stick positions and buttons the guest reads from the game
+ * port until the next event.
+ */
+int sunpci_inject_gameport(struct sunpci_device *dev,
+                           const struct sunpci_gameport_event *event)
+{
+    struct sunpci_input_gameport msg;
+    int i, ret;
+
+    if (!dev || !event)
+        return -EINVAL;
+
+    if (dev->state != SUNPCI_STATE_RUNNING)
+        return -ENODEV;
+
+    for (i = 0; i < SUNPCI_GAMEPORT_AXES; i++)
+        msg.axes[i] = cpu_to_le16(event->axes[i]);
+    msg.buttons = cpu_to_le32(event->buttons & 0xF);
+    msg.connected = cpu_to_le32(event->connected & 0xF);
+
+    ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_INPUT, INPUT_CMD_GAMEPORT,
+                              &msg, sizeof(msg), NULL);
+    if (ret < 0) {
+        sunpci_dbg(dev, "inject_gameport failed: %d\n", ret);
+        return ret;
+    }
+
+    return 0;
+}
+
+/**
+ * sunpci_set_keyboard_leds - Set the guest's lock key LEDs
+ * @dev: Device
+ * @leds: LED bitmap (SUNPCI_LED_*)
+ *
+ * Sets the guest keyboard's lock state, as when the host's lock keys
+ * change while the guest does not have the keyboard.
+ */
+int sunpci_set_keyboard_leds(struct sunpci_device *dev, u32 leds)
+{
+    struct sunpci_input_leds msg;
+    int ret;
+
+    if (dev->state != SUNPCI_STATE_RUNNING)
+        return -ENODEV;
+
+    leds &= SUNPCI_LED_SCROLL_LOCK | SUNPCI_LED_NUM_LOCK | SUNPCI_LED_CAPS_LOCK;
+    msg.leds = cpu_to_le32(leds);
+
+    ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_INPUT, INPUT_CMD_SET_LEDS,
+                              &msg, sizeof(msg), NULL);
+    if (ret < 0) {
+        sunpci_dbg(dev, "set_keyboard_leds failed: %d\n", ret);
+        return ret;
+    }
+
+    WRITE_ONCE(dev->keyboard_leds, leds);
+    return 0;
+}
+
+/**
+ * sunpci_get_keyboard_leds - Guest's lock key LEDs
+ * @dev: Device
+ *
+ * Returns the LEDs the guest last set (SUNPCI_LED_*).
+ */
+u32 sunpci_get_keyboard_leds(struct sunpci_device *dev)
+{
+    return READ_ONCE(dev->keyboard_leds);
+}
+
+/**
+ * sunpci_input_handle_message - Handle an input message from the guest
+ * @dev: Device
+ * @command: INPUT_CMD_*
+ * @payload: Message payload
+ * @len: Payload length
+ *
+ * Returns 0, or -ENOSYS for a command the host does not take.
+ */
+int sunpci_input_handle_message(struct sunpci_device *dev, u16 command,
+                                const void *payload, size_t len)
+{
+    const struct sunpci_input_leds *leds;
+
+    switch (command) {
+    case INPUT_CMD_LEDS:
+        if (len < sizeof(*leds))
+            return -EINVAL;
+        leds = payload;
+        WRITE_ONCE(dev->keyboard_leds, le32_to_cpu(leds->leds));
+        return 0;
+
+    default:
+        return -ENOSYS;
+    }
+}
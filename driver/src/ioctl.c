@@ -7,8 +7,10 @@
 #include <linux/module.h>
 #include <linux/uaccess.h>
 #include <linux/slab.h>
+#include <linux/sched.h>
 
 #include "sunpci.h"
+#include "ipc.h"
 
 /* ============================================================================
  * Session Management
@@ -41,6 +43,8 @@ static int ioctl_get_status(struct sunpci_device *dev, unsigned long arg)
     if (dev->state == SUNPCI_STATE_RUNNING) {
         now = ktime_get();
         uptime_ns = ktime_to_ns(ktime_sub(now, dev->start_time));
+        status.guest_idle_ms = min_t(s64, U32_MAX,
+            ktime_ms_delta(now, READ_ONCE(dev->last_guest_activity)));
     } else {
         uptime_ns = 0;
     }
@@ -85,10 +89,15 @@ static int ioctl_start_session(struct sunpci_device *dev, unsigned long arg)
         strscpy(dev->storage.disk_path[1], cfg.secondary_disk, SUNPCI_MAX_PATH);
     }
 
-    dev->state = SUNPCI_STATE_RUNNING;
+    /* A resumed guest stays frozen until RESUME_SESSION loads its state */
+    dev->state = (cfg.flags & SUNPCI_FLAG_RESUME) ? SUNPCI_STATE_SUSPENDED
+                                                  : SUNPCI_STATE_RUNNING;
     dev->start_time = ktime_get();
+    WRITE_ONCE(dev->last_guest_activity, dev->start_time);
+    sunpci_event_post(dev, SUNPCI_EVENT_SESSION_CHANGED, 0, dev->state, NULL);
     
-    pr_info("sunpci%d: session started\n", dev->minor);
+    sunpci_info(dev, "session started (boot device %u)\n",
+            cfg.boot_device);
 
 out:
     mutex_unlock(&dev->mutex);
@@ -111,7 +120,11 @@ static int ioctl_stop_session(struct sunpci_device *dev)
     sunpci_storage_cleanup(dev);
 
     dev->state = SUNPCI_STATE_STOPPED;
-    pr_info("sunpci%d: session stopped\n", dev->minor);
+    dev->owner = NULL;
+    memset(&dev->owner_info, 0, sizeof(dev->owner_info));
+    sunpci_event_post(dev, SUNPCI_EVENT_SESSION_CHANGED, 0,
+                      SUNPCI_STATE_STOPPED, NULL);
+    sunpci_info(dev, "session stopped\n");
 
 out:
     mutex_unlock(&dev->mutex);
@@ -131,13 +144,219 @@ static int ioctl_reset_session(struct sunpci_device *dev)
 
     /* Reset is a soft reboot - just reset the start time for now */
     dev->start_time = ktime_get();
-    pr_info("sunpci%d: session reset (Ctrl+Alt+Del)\n", dev->minor);
+    WRITE_ONCE(dev->last_guest_activity, dev->start_time);
+    sunpci_event_post(dev, SUNPCI_EVENT_SESSION_CHANGED, 0,
+                      SUNPCI_STATE_RUNNING, NULL);
+    sunpci_info(dev, "session reset (Ctrl+Alt+Del)\n");
+
+out:
+    mutex_unlock(&dev->mutex);
+    return ret;
+}
+
+static int ioctl_request_shutdown(struct sunpci_device *dev)
+{
+    int ret = 0;
+
+    mutex_lock(&dev->mutex);
+
+    if (dev->state != SUNPCI_STATE_RUNNING) {
+        ret = -EINVAL;
+        goto out;
+    }
+
+    /* The guest reports back with CORE_CMD_POWER_OFF once it is off */
+    ret = sunpci_ipc_send_cmd(dev, SUNPCI_DISP_CORE, CORE_CMD_POWER_BUTTON,
+                              NULL, 0, NULL);
+    if (ret == 0)
+        sunpci_info(dev, "asked the guest to shut down\n");
 
 out:
     mutex_unlock(&dev->mutex);
     return ret;
 }
 
+/* ============================================================================
+ * Saved State
+ * ============================================================================ */
+
+/*
+ * The card does the snapshotting; the host only moves bytes. The device
+ * mutex is not held across the transactions so responses and guest
+ * requests keep flowing while the card works.
+ */
+
+static int ioctl_suspend_session(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_core_state_info rsp;
+    struct sunpci_state_info info = {0};
+    size_t rsp_len;
+    u64 size;
+    int ret;
+
+    mutex_lock(&dev->mutex);
+    if (dev->state != SUNPCI_STATE_RUNNING) {
+        mutex_unlock(&dev->mutex);
+        return -EINVAL;
+    }
+    dev->state = SUNPCI_STATE_SUSPENDED;
+    mutex_unlock(&dev->mutex);
+
+    ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CORE, CORE_CMD_STATE_SAVE,
+                              NULL, 0, &rsp, sizeof(rsp), &rsp_len,
+                              SUNPCI_STATE_TIMEOUT);
+    if (ret == 0 && rsp_len < sizeof(rsp))
+        ret = -EIO;
+
+    mutex_lock(&dev->mutex);
+    if (ret < 0) {
+        /* The guest was never frozen; let it carry on */
+        if (dev->state == SUNPCI_STATE_SUSPENDED)
+            dev->state = SUNPCI_STATE_RUNNING;
+    } else {
+        sunpci_event_post(dev, SUNPCI_EVENT_SESSION_CHANGED, 0,
+                          SUNPCI_STATE_SUSPENDED, NULL);
+    }
+    mutex_unlock(&dev->mutex);
+    if (ret < 0)
+        return ret;
+
+    size = le64_to_cpu(rsp.size);
+    info.size_lo = (u32)size;
+    info.size_hi = (u32)(size >> 32);
+    info.version = le32_to_cpu(rsp.version);
+    sunpci_info(dev, "guest suspended (%llu byte snapshot)\n", size);
+
+    if (copy_to_user((void __user *)arg, &info, sizeof(info)))
+        return -EFAULT;
+    return 0;
+}
+
+static bool state_is_suspended(struct sunpci_device *dev)
+{
+    bool suspended;
+
+    mutex_lock(&dev->mutex);
+    suspended = dev->state == SUNPCI_STATE_SUSPENDED;
+    mutex_unlock(&dev->mutex);
+    return suspended;
+}
+
+static int ioctl_read_state(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_state_chunk *chunk;
+    struct sunpci_core_state_chunk cmd = {0};
+    size_t rsp_len;
+    int ret;
+
+    if (!state_is_suspended(dev))
+        return -EINVAL;
+
+    chunk = kmalloc(sizeof(*chunk), GFP_KERNEL);
+    if (!chunk)
+        return -ENOMEM;
+
+    if (copy_from_user(chunk, (void __user *)arg, sizeof(*chunk))) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    cmd.offset = cpu_to_le64(((u64)chunk->offset_hi << 32) | chunk->offset_lo);
+    cmd.length = cpu_to_le32(SUNPCI_STATE_CHUNK);
+
+    ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CORE, CORE_CMD_STATE_READ,
+                              &cmd, sizeof(cmd), chunk->data,
+                              SUNPCI_STATE_CHUNK, &rsp_len,
+                              SUNPCI_CMD_TIMEOUT);
+    if (ret < 0)
+        goto out;
+
+    chunk->length = min_t(size_t, rsp_len, SUNPCI_STATE_CHUNK);
+    if (copy_to_user((void __user *)arg, chunk, sizeof(*chunk)))
+        ret = -EFAULT;
+
+out:
+    kfree(chunk);
+    return ret;
+}
+
+static int ioctl_write_state(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_state_chunk *chunk;
+    struct sunpci_core_state_chunk *cmd;
+    size_t rsp_len;
+    int ret;
+
+    if (!state_is_suspended(dev))
+        return -EINVAL;
+
+    chunk = kmalloc(sizeof(*chunk), GFP_KERNEL);
+    cmd = kmalloc(sizeof(*cmd) + SUNPCI_STATE_CHUNK, GFP_KERNEL);
+    if (!chunk || !cmd) {
+        ret = -ENOMEM;
+        goto out;
+    }
+
+    if (copy_from_user(chunk, (void __user *)arg, sizeof(*chunk))) {
+        ret = -EFAULT;
+        goto out;
+    }
+    if (chunk->length > SUNPCI_STATE_CHUNK) {
+        ret = -EINVAL;
+        goto out;
+    }
+
+    cmd->offset = cpu_to_le64(((u64)chunk->offset_hi << 32) | chunk->offset_lo);
+    cmd->length = cpu_to_le32(chunk->length);
+    cmd->reserved = 0;
+    memcpy(cmd + 1, chunk->data, chunk->length);
+
+    ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CORE, CORE_CMD_STATE_WRITE,
+                              cmd, sizeof(*cmd) + chunk->length,
+                              NULL, 0, &rsp_len, SUNPCI_CMD_TIMEOUT);
+
+out:
+    kfree(cmd);
+    kfree(chunk);
+    return ret;
+}
+
+static int ioctl_resume_session(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_state_info info;
+    struct sunpci_core_state_info cmd = {0};
+    size_t rsp_len;
+    int ret;
+
+    if (copy_from_user(&info, (void __user *)arg, sizeof(info)))
+        return -EFAULT;
+
+    if (!state_is_suspended(dev))
+        return -EINVAL;
+
+    cmd.size = cpu_to_le64(((u64)info.size_hi << 32) | info.size_lo);
+    cmd.version = cpu_to_le32(info.version);
+
+    ret = sunpci_ipc_transact(dev, SUNPCI_DISP_CORE, CORE_CMD_STATE_LOAD,
+                              &cmd, sizeof(cmd), NULL, 0, &rsp_len,
+                              SUNPCI_STATE_TIMEOUT);
+    if (ret < 0)
+        return ret;
+
+    mutex_lock(&dev->mutex);
+    if (dev->state == SUNPCI_STATE_SUSPENDED) {
+        dev->state = SUNPCI_STATE_RUNNING;
+        WRITE_ONCE(dev->last_guest_activity, ktime_get());
+        sunpci_event_post(dev, SUNPCI_EVENT_SESSION_CHANGED, 0,
+                          SUNPCI_STATE_RUNNING, NULL);
+        sunpci_info(dev, "guest resumed\n");
+    } else {
+        ret = -EINVAL;
+    }
+    mutex_unlock(&dev->mutex);
+    return ret;
+}
+
 /* ============================================================================
  * Display
  * ============================================================================ */
@@ -167,8 +386,7 @@ static int ioctl_set_display(struct sunpci_device *dev, unsigned long arg)
     dev->display.config = cfg;
     mutex_unlock(&dev->mutex);
 
-    pr_debug("sunpci%d: display config updated (scale=%u)\n", 
-             dev->minor, cfg.scale_mode);
+    sunpci_dbg(dev, "display config updated (scale=%u)\n", cfg.scale_mode);
 
     return 0;
 }
@@ -187,6 +405,52 @@ static int ioctl_get_framebuffer(struct sunpci_device *dev, unsigned long arg)
     return 0;
 }
 
+static int ioctl_get_mmap_regions(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_mmap_regions regions;
+
+    mutex_lock(&dev->mutex);
+    sunpci_get_mmap_regions(dev, &regions);
+    mutex_unlock(&dev->mutex);
+
+    if (copy_to_user((void __user *)arg, &regions, sizeof(regions)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_get_palette(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_palette *palette;
+    int ret;
+
+    palette = kzalloc(sizeof(*palette), GFP_KERNEL);
+    if (!palette)
+        return -ENOMEM;
+
+    ret = sunpci_vga_get_palette(dev, palette);
+    if (!ret && copy_to_user((void __user *)arg, palette, sizeof(*palette)))
+        ret = -EFAULT;
+
+    kfree(palette);
+    return ret;
+}
+
+static int ioctl_get_text_cursor(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_text_cursor cursor;
+    int ret;
+
+    ret = sunpci_vga_get_text_cursor(dev, &cursor);
+    if (ret)
+        return ret;
+
+    if (copy_to_user((void __user *)arg, &cursor, sizeof(cursor)))
+        return -EFAULT;
+
+    return 0;
+}
+
 /* ============================================================================
  * Storage
  * ============================================================================ */
@@ -210,8 +474,7 @@ static int ioctl_mount_disk(struct sunpci_device *dev, unsigned long arg)
     /* Notify storage subsystem */
     ret = sunpci_storage_mount_disk(dev, mount.slot, mount.path, mount.flags);
 
-    pr_info("sunpci%d: mounted disk %u: %s\n", 
-            dev->minor, mount.slot, mount.path);
+    sunpci_info(dev, "mounted disk %u: %s\n", mount.slot, mount.path);
 
     return ret;
 }
@@ -235,7 +498,7 @@ static int ioctl_unmount_disk(struct sunpci_device *dev, unsigned long arg)
     dev->storage.disk_flags[slot.slot] = 0;
     mutex_unlock(&dev->mutex);
 
-    pr_info("sunpci%d: unmounted disk %u\n", dev->minor, slot.slot);
+    sunpci_info(dev, "unmounted disk %u\n", slot.slot);
 
     return ret;
 }
@@ -255,7 +518,7 @@ static int ioctl_mount_cdrom(struct sunpci_device *dev, unsigned long arg)
     /* Notify storage subsystem */
     ret = sunpci_storage_mount_cdrom(dev, path.path);
 
-    pr_info("sunpci%d: mounted CD-ROM: %s\n", dev->minor, path.path);
+    sunpci_info(dev, "mounted CD-ROM: %s\n", path.path);
 
     return ret;
 }
@@ -271,7 +534,7 @@ static int ioctl_eject_cdrom(struct sunpci_device *dev)
     dev->storage.cdrom_path[0] = '\0';
     mutex_unlock(&dev->mutex);
 
-    pr_info("sunpci%d: ejected CD-ROM\n", dev->minor);
+    sunpci_info(dev, "ejected CD-ROM\n");
 
     return ret;
 }
@@ -294,8 +557,7 @@ static int ioctl_mount_floppy(struct sunpci_device *dev, unsigned long arg)
     /* Notify storage subsystem */
     ret = sunpci_storage_mount_floppy(dev, mount.drive, mount.path);
 
-    pr_info("sunpci%d: mounted floppy %c: %s\n", 
-            dev->minor, 'A' + mount.drive, mount.path);
+    sunpci_info(dev, "mounted floppy %c: %s\n", 'A' + mount.drive, mount.path);
 
     return ret;
 }
@@ -318,11 +580,126 @@ static int ioctl_eject_floppy(struct sunpci_device *dev, unsigned long arg)
     dev->storage.floppy_path[slot.drive][0] = '\0';
     mutex_unlock(&dev->mutex);
 
-    pr_info("sunpci%d: ejected floppy %c\n", dev->minor, 'A' + slot.drive);
+    sunpci_info(dev, "ejected floppy %c\n", 'A' + slot.drive);
 
     return ret;
 }
 
+static int ioctl_cdrom_passthrough(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_cdrom_passthrough pt;
+    int ret;
+
+    if (copy_from_user(&pt, (void __user *)arg, sizeof(pt)))
+        return -EFAULT;
+
+    ret = sunpci_storage_set_passthrough(dev, pt.enable != 0);
+    if (ret < 0)
+        return ret;
+
+    mutex_lock(&dev->mutex);
+    if (pt.enable)
+        strscpy(dev->storage.cdrom_path, pt.device, SUNPCI_MAX_PATH);
+    else
+        dev->storage.cdrom_path[0] = '\0';
+    mutex_unlock(&dev->mutex);
+
+    if (pt.enable)
+        sunpci_info(dev, "CD-ROM passed through to %s\n", pt.device);
+    else
+        sunpci_info(dev, "CD-ROM pass-through disabled\n");
+
+    return 0;
+}
+
+static int ioctl_scsi_command(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_scsi_command cmd;
+    struct sunpci_scsi_req req;
+    struct sunpci_scsi_rsp rsp;
+    void __user *ubuf;
+    size_t len;
+    u8 *data;
+    int ret;
+
+    if (copy_from_user(&cmd, (void __user *)arg, sizeof(cmd)))
+        return -EFAULT;
+
+    if (cmd.cdb.cdb_len > SUNPCI_SCSI_CDB_MAX ||
+        cmd.cdb.data_direction > SCSI_DIR_WRITE)
+        return -EINVAL;
+
+    ubuf = u64_to_user_ptr(cmd.data_ptr);
+    len = min_t(size_t, cmd.cdb.data_len, SUNPCI_SCSI_DATA_MAX);
+    if (cmd.cdb.data_direction != SCSI_DIR_NONE && cmd.data_buf_len < len)
+        return -EINVAL;
+
+    data = kzalloc(SUNPCI_SCSI_DATA_MAX, GFP_KERNEL);
+    if (!data)
+        return -ENOMEM;
+
+    if (cmd.cdb.data_direction == SCSI_DIR_WRITE &&
+        copy_from_user(data, ubuf, len)) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    memcpy(req.cdb, cmd.cdb.cdb, SUNPCI_SCSI_CDB_MAX);
+    req.cdb_len = cpu_to_le32(cmd.cdb.cdb_len);
+    req.data_direction = cpu_to_le32(cmd.cdb.data_direction);
+    req.data_len = cpu_to_le32(len);
+
+    ret = sunpci_storage_scsi_command(dev, &req, &rsp, data, len);
+    if (ret < 0)
+        goto out;
+
+    memset(&cmd.result, 0, sizeof(cmd.result));
+    cmd.result.status = rsp.status;
+    cmd.result.sense_len = rsp.sense_len;
+    cmd.result.data_len = min_t(u32, le32_to_cpu(rsp.data_len), len);
+    memcpy(cmd.result.sense, rsp.sense, SUNPCI_SCSI_SENSE_MAX);
+
+    if (cmd.cdb.data_direction == SCSI_DIR_READ &&
+        copy_to_user(ubuf, data, cmd.result.data_len)) {
+        ret = -EFAULT;
+        goto out;
+    }
+    if (copy_to_user((void __user *)arg, &cmd, sizeof(cmd)))
+        ret = -EFAULT;
+
+out:
+    kfree(data);
+    return ret;
+}
+
+static int ioctl_scsi_pt_next(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_scsi_command cmd;
+    int ret;
+
+    if (copy_from_user(&cmd, (void __user *)arg, sizeof(cmd)))
+        return -EFAULT;
+
+    ret = sunpci_storage_pt_next(dev, &cmd);
+    if (ret < 0)
+        return ret;
+
+    if (copy_to_user((void __user *)arg, &cmd, sizeof(cmd)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_scsi_pt_complete(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_scsi_command cmd;
+
+    if (copy_from_user(&cmd, (void __user *)arg, sizeof(cmd)))
+        return -EFAULT;
+
+    return sunpci_storage_pt_complete(dev, &cmd);
+}
+
 /* ============================================================================
  * Input
  * ============================================================================ */
@@ -334,8 +711,8 @@ static int ioctl_keyboard_event(struct sunpci_device *dev, unsigned long arg)
     if (copy_from_user(&event, (void __user *)arg, sizeof(event)))
         return -EFAULT;
 
-    pr_debug("sunpci%d: key event scancode=0x%x flags=0x%x\n",
-             dev->minor, event.scancode, event.flags);
+    sunpci_trace(dev, "key event scancode=0x%x flags=0x%x\n",
+                 event.scancode, event.flags);
 
     return sunpci_inject_key(dev, &event);
 }
@@ -347,12 +724,54 @@ static int ioctl_mouse_event(struct sunpci_device *dev, unsigned long arg)
     if (copy_from_user(&event, (void __user *)arg, sizeof(event)))
         return -EFAULT;
 
-    pr_debug("sunpci%d: mouse event dx=%d dy=%d buttons=0x%x\n",
-             dev->minor, event.dx, event.dy, event.buttons);
+    sunpci_trace(dev, "mouse event dx=%d dy=%d buttons=0x%x\n",
+                 event.dx, event.dy, event.buttons);
 
     return sunpci_inject_mouse(dev, &event);
 }
 
+static int ioctl_mouse_abs_event(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_mouse_abs_event event;
+
+    if (copy_from_user(&event, (void __user *)arg, sizeof(event)))
+        return -EFAULT;
+
+    return sunpci_inject_mouse_abs(dev, &event);
+}
+
+static int ioctl_gameport_event(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_gameport_event event;
+
+    if (copy_from_user(&event, (void __user *)arg, sizeof(event)))
+        return -EFAULT;
+
+    return sunpci_inject_gameport(dev, &event);
+}
+
+static int ioctl_get_keyboard_leds(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_keyboard_leds leds = {
+        .leds = sunpci_get_keyboard_leds(dev),
+    };
+
+    if (copy_to_user((void __user *)arg, &leds, sizeof(leds)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_set_keyboard_leds(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_keyboard_leds leds;
+
+    if (copy_from_user(&leds, (void __user *)arg, sizeof(leds)))
+        return -EFAULT;
+
+    return sunpci_set_keyboard_leds(dev, leds.leds);
+}
+
 /* ============================================================================
  * Clipboard
  * ============================================================================ */
@@ -384,11 +803,11 @@ static int ioctl_set_clipboard(struct sunpci_device *dev, unsigned long arg)
     ret = sunpci_clip_set(dev, clip);
     if (ret < 0 && ret != -ENODEV) {
         /* IPC failed but local copy succeeded - log and continue */
-        pr_debug("sunpci%d: clipboard IPC failed: %d\n", dev->minor, ret);
+        sunpci_dbg(dev, "clipboard IPC failed: %d\n", ret);
         ret = 0;
     }
 
-    pr_debug("sunpci%d: clipboard set (%u bytes)\n", dev->minor, clip->length);
+    sunpci_dbg(dev, "clipboard set (%u bytes)\n", clip->length);
 
 out:
     kfree(clip);
@@ -421,6 +840,52 @@ static int ioctl_get_clipboard(struct sunpci_device *dev, unsigned long arg)
     return ret;
 }
 
+static int ioctl_set_clipboard_chunk(struct sunpci_device *dev,
+                                     unsigned long arg)
+{
+    struct sunpci_clipboard_chunk *chunk;
+    int ret;
+
+    chunk = kmalloc(sizeof(*chunk), GFP_KERNEL);
+    if (!chunk)
+        return -ENOMEM;
+
+    if (copy_from_user(chunk, (void __user *)arg, sizeof(*chunk))) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    ret = sunpci_clip_set_chunk(dev, chunk);
+
+out:
+    kfree(chunk);
+    return ret;
+}
+
+static int ioctl_get_clipboard_chunk(struct sunpci_device *dev,
+                                     unsigned long arg)
+{
+    struct sunpci_clipboard_chunk *chunk;
+    int ret;
+
+    chunk = kmalloc(sizeof(*chunk), GFP_KERNEL);
+    if (!chunk)
+        return -ENOMEM;
+
+    if (copy_from_user(chunk, (void __user *)arg, sizeof(*chunk))) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    ret = sunpci_clip_get_chunk(dev, chunk);
+    if (ret == 0 && copy_to_user((void __user *)arg, chunk, sizeof(*chunk)))
+        ret = -EFAULT;
+
+out:
+    kfree(chunk);
+    return ret;
+}
+
 /* ============================================================================
  * Drive Mappings
  * ============================================================================ */
@@ -437,6 +902,16 @@ static int ioctl_add_drive_map(struct sunpci_device *dev, unsigned long arg)
     if (map.letter < 'E' || map.letter > 'Z')
         return -EINVAL;
 
+    /* The fourth value of each two-bit option is reserved */
+    if ((map.flags & SUNPCI_DRIVE_MANGLE_MASK) == SUNPCI_DRIVE_MANGLE_MASK ||
+        (map.flags & SUNPCI_DRIVE_CASE_MASK) == SUNPCI_DRIVE_CASE_MASK)
+        return -EINVAL;
+
+    if (!(map.flags & SUNPCI_DRIVE_TZ_OFFSET))
+        map.tz_offset = 0;
+    else if (map.tz_offset < -12 * 60 || map.tz_offset > 14 * 60)
+        return -EINVAL;
+
     mutex_lock(&dev->mutex);
     
     /* Check if already mapped, or find empty slot */
@@ -457,12 +932,14 @@ static int ioctl_add_drive_map(struct sunpci_device *dev, unsigned long arg)
 
     dev->drive_maps[slot].letter = map.letter;
     dev->drive_maps[slot].flags = map.flags;
+    dev->drive_maps[slot].tz_offset = map.tz_offset;
+    dev->drive_maps[slot].max_bytes = (u64)map.max_size_mb << 20;
+    dev->drive_maps[slot].used = ((u64)map.used_hi << 32) | map.used_lo;
     strscpy(dev->drive_maps[slot].path, map.path, SUNPCI_MAX_PATH);
     
     mutex_unlock(&dev->mutex);
 
-    pr_info("sunpci%d: mapped drive %c: -> %s\n", 
-            dev->minor, map.letter, map.path);
+    sunpci_info(dev, "mapped drive %c: -> %s\n", map.letter, map.path);
 
     return 0;
 }
@@ -483,7 +960,7 @@ static int ioctl_remove_drive_map(struct sunpci_device *dev, unsigned long arg)
             dev->drive_maps[i].path[0] = '\0';
             mutex_unlock(&dev->mutex);
             
-            pr_info("sunpci%d: unmapped drive %c:\n", dev->minor, letter.letter);
+            sunpci_info(dev, "unmapped drive %c:\n", letter.letter);
             return 0;
         }
     }
@@ -524,8 +1001,8 @@ static int ioctl_set_network(struct sunpci_device *dev, unsigned long arg)
     mutex_unlock(&dev->mutex);
 
     if (ret == 0) {
-        pr_info("sunpci%d: network configured (interface=%s, MAC=%pM)\n", 
-                dev->minor, cfg.interface, cfg.mac_address);
+        sunpci_info(dev, "network configured (interface=%s, MAC=%pM)\n",
+                    cfg.interface, cfg.mac_address);
     }
 
     return ret;
@@ -545,6 +1022,262 @@ static int ioctl_get_network(struct sunpci_device *dev, unsigned long arg)
     return 0;
 }
 
+static int ioctl_net_send_frame(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_net_frame *frame;
+    int ret;
+
+    frame = kmalloc(sizeof(*frame), GFP_KERNEL);
+    if (!frame)
+        return -ENOMEM;
+
+    if (copy_from_user(frame, (void __user *)arg, sizeof(*frame))) {
+        kfree(frame);
+        return -EFAULT;
+    }
+
+    if (frame->length > SUNPCI_NET_MAX_FRAME) {
+        kfree(frame);
+        return -EINVAL;
+    }
+
+    mutex_lock(&dev->mutex);
+    ret = sunpci_net_user_send(dev, frame->data, frame->length);
+    mutex_unlock(&dev->mutex);
+
+    kfree(frame);
+    return ret;
+}
+
+static int ioctl_net_recv_frame(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_net_frame *frame;
+    size_t len = 0;
+    int ret;
+
+    frame = kzalloc(sizeof(*frame), GFP_KERNEL);
+    if (!frame)
+        return -ENOMEM;
+
+    mutex_lock(&dev->mutex);
+    ret = sunpci_net_user_recv(dev, frame->data, &len);
+    mutex_unlock(&dev->mutex);
+
+    if (ret == 0) {
+        frame->length = len;
+        if (copy_to_user((void __user *)arg, frame, sizeof(*frame)))
+            ret = -EFAULT;
+    }
+
+    kfree(frame);
+    return ret;
+}
+
+/* ============================================================================
+ * Audio
+ * ============================================================================ */
+
+static int ioctl_get_audio_format(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_format fmt = {0};
+    int ret;
+
+    ret = sunpci_audio_get_format(dev, &fmt.sample_rate, &fmt.format);
+    if (ret)
+        return ret;
+
+    fmt.channels = (fmt.format & SUNPCI_AUDIO_FMT_STEREO) ? 2 : 1;
+    fmt.bits_per_sample = (fmt.format & SUNPCI_AUDIO_FMT_16BIT) ? 16 : 8;
+
+    if (copy_to_user((void __user *)arg, &fmt, sizeof(fmt)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_set_audio_volume(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_volume vol;
+    int ret;
+
+    if (copy_from_user(&vol, (void __user *)arg, sizeof(vol)))
+        return -EFAULT;
+
+    ret = sunpci_audio_set_volume(dev, vol.left, vol.right);
+    if (ret)
+        return ret;
+
+    return sunpci_audio_set_muted(dev, vol.muted);
+}
+
+static int ioctl_get_audio_volume(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_volume vol = {0};
+    struct sunpci_audio_status status;
+    int ret;
+
+    ret = sunpci_audio_get_volume(dev, &vol.left, &vol.right);
+    if (ret)
+        return ret;
+
+    sunpci_audio_get_status(dev, &status);
+    vol.muted = !!(status.flags & SUNPCI_AUDIO_MUTED);
+
+    if (copy_to_user((void __user *)arg, &vol, sizeof(vol)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_get_audio_status(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_status status;
+
+    sunpci_audio_get_status(dev, &status);
+
+    if (copy_to_user((void __user *)arg, &status, sizeof(status)))
+        return -EFAULT;
+
+    return 0;
+}
+
+static int ioctl_read_audio(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_buffer __user *ubuf = (void __user *)arg;
+    struct sunpci_audio_buffer *buf;
+    u32 size;
+    int ret;
+
+    if (get_user(size, &ubuf->size))
+        return -EFAULT;
+    size = min_t(u32, size, SUNPCI_AUDIO_MAX_BUFFER);
+
+    buf = kzalloc(sizeof(*buf), GFP_KERNEL);
+    if (!buf)
+        return -ENOMEM;
+
+    ret = sunpci_audio_read(dev, buf->data, size);
+    if (ret >= 0) {
+        buf->size = ret;
+        ret = copy_to_user(ubuf, buf, sizeof(*buf)) ? -EFAULT : 0;
+    }
+
+    kfree(buf);
+    return ret;
+}
+
+static int ioctl_write_audio(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_audio_buffer __user *ubuf = (void __user *)arg;
+    struct sunpci_audio_buffer *buf;
+    int ret;
+
+    buf = kmalloc(sizeof(*buf), GFP_KERNEL);
+    if (!buf)
+        return -ENOMEM;
+
+    if (copy_from_user(buf, ubuf, sizeof(*buf))) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    ret = sunpci_audio_write(dev, buf->data,
+                             min_t(u32, buf->size, SUNPCI_AUDIO_MAX_BUFFER));
+    if (ret >= 0)
+        ret = put_user((u32)ret, &ubuf->size) ? -EFAULT : 0;
+
+out:
+    kfree(buf);
+    return ret;
+}
+
+static int ioctl_read_midi(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_midi_buffer __user *ubuf = (void __user *)arg;
+    struct sunpci_midi_buffer *buf;
+    u32 size;
+    int ret;
+
+    if (get_user(size, &ubuf->size))
+        return -EFAULT;
+    size = min_t(u32, size, SUNPCI_MIDI_MAX_BUFFER);
+
+    buf = kzalloc(sizeof(*buf), GFP_KERNEL);
+    if (!buf)
+        return -ENOMEM;
+
+    ret = sunpci_midi_read(dev, buf->data, size);
+    if (ret >= 0) {
+        buf->size = ret;
+        ret = copy_to_user(ubuf, buf, sizeof(*buf)) ? -EFAULT : 0;
+    }
+
+    kfree(buf);
+    return ret;
+}
+
+static int ioctl_claim_session(struct sunpci_device *dev, struct file *file,
+                               unsigned long arg)
+{
+    struct sunpci_session_owner req, prev;
+    int ret = 0;
+
+    if (copy_from_user(&req, (void __user *)arg, sizeof(req)))
+        return -EFAULT;
+
+    mutex_lock(&dev->mutex);
+
+    if (dev->state != SUNPCI_STATE_RUNNING) {
+        ret = -EINVAL;
+        goto out;
+    }
+    if (dev->owner && dev->owner != file && !(req.flags & SUNPCI_CLAIM_FORCE)) {
+        ret = -EBUSY;
+        goto out;
+    }
+
+    prev = dev->owner_info;
+    dev->owner = file;
+    dev->owner_info.pid = task_tgid_vnr(current);
+    dev->owner_info.control_port = req.control_port;
+    dev->owner_info.flags = 0;
+
+    if (prev.pid && prev.pid != dev->owner_info.pid)
+        sunpci_info(dev, "session handed from pid %u to pid %u\n",
+                    prev.pid, dev->owner_info.pid);
+
+out:
+    mutex_unlock(&dev->mutex);
+
+    if (ret == 0 && copy_to_user((void __user *)arg, &prev, sizeof(prev)))
+        return -EFAULT;
+    return ret;
+}
+
+/* Clear the owner if it is @file (RELEASE_SESSION and close) */
+void sunpci_release_owner(struct sunpci_device *dev, struct file *file)
+{
+    mutex_lock(&dev->mutex);
+    if (dev->owner == file) {
+        dev->owner = NULL;
+        memset(&dev->owner_info, 0, sizeof(dev->owner_info));
+    }
+    mutex_unlock(&dev->mutex);
+}
+
+static int ioctl_get_owner(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_session_owner owner;
+
+    mutex_lock(&dev->mutex);
+    owner = dev->owner_info;
+    mutex_unlock(&dev->mutex);
+
+    if (copy_to_user((void __user *)arg, &owner, sizeof(owner)))
+        return -EFAULT;
+    return 0;
+}
+
 /* ============================================================================
  * Main ioctl Handler
  * ============================================================================ */
@@ -568,6 +1301,15 @@ long sunpci_ioctl(struct file *file, unsigned int cmd, unsigned long arg)
         return ioctl_stop_session(dev);
     case SUNPCI_IOC_RESET_SESSION:
         return ioctl_reset_session(dev);
+    case SUNPCI_IOC_CLAIM_SESSION:
+        return ioctl_claim_session(dev, file, arg);
+    case SUNPCI_IOC_RELEASE_SESSION:
+        sunpci_release_owner(dev, file);
+        return 0;
+    case SUNPCI_IOC_GET_OWNER:
+        return ioctl_get_owner(dev, arg);
+    case SUNPCI_IOC_REQUEST_SHUTDOWN:
+        return ioctl_request_shutdown(dev);
 
     /* Display */
     case SUNPCI_IOC_GET_DISPLAY:
@@ -576,6 +1318,12 @@ long sunpci_ioctl(struct file *file, unsigned int cmd, unsigned long arg)
         return ioctl_set_display(dev, arg);
     case SUNPCI_IOC_GET_FRAMEBUFFER:
         return ioctl_get_framebuffer(dev, arg);
+    case SUNPCI_IOC_GET_MMAP_REGIONS:
+        return ioctl_get_mmap_regions(dev, arg);
+    case SUNPCI_IOC_GET_PALETTE:
+        return ioctl_get_palette(dev, arg);
+    case SUNPCI_IOC_GET_TEXT_CURSOR:
+        return ioctl_get_text_cursor(dev, arg);
 
     /* Storage */
     case SUNPCI_IOC_MOUNT_DISK:
@@ -590,18 +1338,38 @@ long sunpci_ioctl(struct file *file, unsigned int cmd, unsigned long arg)
         return ioctl_mount_floppy(dev, arg);
     case SUNPCI_IOC_EJECT_FLOPPY:
         return ioctl_eject_floppy(dev, arg);
+    case SUNPCI_IOC_CDROM_PASSTHROUGH:
+        return ioctl_cdrom_passthrough(dev, arg);
+    case SUNPCI_IOC_SCSI_COMMAND:
+        return ioctl_scsi_command(dev, arg);
+    case SUNPCI_IOC_SCSI_PT_NEXT:
+        return ioctl_scsi_pt_next(dev, arg);
+    case SUNPCI_IOC_SCSI_PT_COMPLETE:
+        return ioctl_scsi_pt_complete(dev, arg);
 
     /* Input */
     case SUNPCI_IOC_KEYBOARD_EVENT:
         return ioctl_keyboard_event(dev, arg);
     case SUNPCI_IOC_MOUSE_EVENT:
         return ioctl_mouse_event(dev, arg);
+    case SUNPCI_IOC_MOUSE_ABS_EVENT:
+        return ioctl_mouse_abs_event(dev, arg);
+    case SUNPCI_IOC_GAMEPORT_EVENT:
+        return ioctl_gameport_event(dev, arg);
+    case SUNPCI_IOC_GET_KEYBOARD_LEDS:
+        return ioctl_get_keyboard_leds(dev, arg);
+    case SUNPCI_IOC_SET_KEYBOARD_LEDS:
+        return ioctl_set_keyboard_leds(dev, arg);
 
     /* Clipboard */
     case SUNPCI_IOC_SET_CLIPBOARD:
         return ioctl_set_clipboard(dev, arg);
     case SUNPCI_IOC_GET_CLIPBOARD:
         return ioctl_get_clipboard(dev, arg);
+    case SUNPCI_IOC_SET_CLIPBOARD_CHUNK:
+        return ioctl_set_clipboard_chunk(dev, arg);
+    case SUNPCI_IOC_GET_CLIPBOARD_CHUNK:
+        return ioctl_get_clipboard_chunk(dev, arg);
 
     /* Drive mappings */
     case SUNPCI_IOC_ADD_DRIVE_MAP:
@@ -614,6 +1382,44 @@ long sunpci_ioctl(struct file *file, unsigned int cmd, unsigned long arg)
         return ioctl_set_network(dev, arg);
     case SUNPCI_IOC_GET_NETWORK:
         return ioctl_get_network(dev, arg);
+    case SUNPCI_IOC_NET_SEND_FRAME:
+        return ioctl_net_send_frame(dev, arg);
+    case SUNPCI_IOC_NET_RECV_FRAME:
+        return ioctl_net_recv_frame(dev, arg);
+
+    /* Audio */
+    case SUNPCI_IOC_GET_AUDIO_FORMAT:
+        return ioctl_get_audio_format(dev, arg);
+    case SUNPCI_IOC_SET_AUDIO_VOLUME:
+        return ioctl_set_audio_volume(dev, arg);
+    case SUNPCI_IOC_GET_AUDIO_VOLUME:
+        return ioctl_get_audio_volume(dev, arg);
+    case SUNPCI_IOC_GET_AUDIO_STATUS:
+        return ioctl_get_audio_status(dev, arg);
+    case SUNPCI_IOC_READ_AUDIO:
+        return ioctl_read_audio(dev, arg);
+    case SUNPCI_IOC_WRITE_AUDIO:
+        return ioctl_write_audio(dev, arg);
+    case SUNPCI_IOC_READ_MIDI:
+        return ioctl_read_midi(dev, arg);
+
+    /* Events */
+    case SUNPCI_IOC_GET_EVENTS:
+        return sunpci_ioctl_get_events(dev, file, arg);
+
+    /* Saved state */
+    case SUNPCI_IOC_SUSPEND_SESSION:
+        return ioctl_suspend_session(dev, arg);
+    case SUNPCI_IOC_READ_STATE:
+        return ioctl_read_state(dev, arg);
+    case SUNPCI_IOC_WRITE_STATE:
+        return ioctl_write_state(dev, arg);
+    case SUNPCI_IOC_RESUME_SESSION:
+        return ioctl_resume_session(dev, arg);
+
+    /* Driver log */
+    case SUNPCI_IOC_READ_LOG:
+        return sunpci_ioctl_read_log(dev, arg);
 
     default:
         return -ENOTTY;
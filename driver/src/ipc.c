@@ -27,6 +27,12 @@ static void sunpci_dispatch_fsd(struct sunpci_device *dev,
 static void sunpci_dispatch_clipboard(struct sunpci_device *dev,
                                       u16 command, u32 sequence,
                                       void *payload, size_t payload_len);
+static void sunpci_dispatch_input(struct sunpci_device *dev,
+                                  u16 command, u32 sequence,
+                                  void *payload, size_t payload_len);
+static void sunpci_dispatch_audio(struct sunpci_device *dev,
+                                  u16 command, u32 sequence,
+                                  void *payload, size_t payload_len);
 
 /* Sequence number for message tracking */
 static atomic_t ipc_sequence = ATOMIC_INIT(0);
@@ -91,7 +97,7 @@ int sunpci_ipc_send_cmd(struct sunpci_device *dev,
 
     /* Check ring has space */
     if (sunpci_ring_space(&dev->cmd_ring) < total_len) {
-        dev_warn(&dev->pdev->dev, "cmd ring full\n");
+        sunpci_warn(dev, "cmd ring full\n");
         return -ENOSPC;
     }
 
@@ -162,7 +168,7 @@ int sunpci_ipc_recv_rsp(struct sunpci_device *dev,
 
             /* Validate magic */
             if (le32_to_cpu(hdr.magic) != SUNPCI_MSG_MAGIC) {
-                dev_err(&dev->pdev->dev, "bad response magic: 0x%08x\n",
+                sunpci_err(dev, "bad response magic: 0x%08x\n",
                         le32_to_cpu(hdr.magic));
                 /* Try to recover by skipping a byte */
                 sunpci_ring_skip(&dev->rsp_ring, 1);
@@ -264,7 +270,7 @@ int sunpci_ipc_transact(struct sunpci_device *dev,
 
     /* Check status */
     if (status != SUNPCI_RSP_SUCCESS) {
-        dev_dbg(&dev->pdev->dev, "command failed: dispatcher=%d cmd=%d status=%d\n",
+        sunpci_dbg(dev, "command failed: dispatcher=%d cmd=%d status=%d\n",
                 dispatcher, command, status);
         return -EIO;
     }
@@ -290,11 +296,11 @@ int sunpci_ipc_init(struct sunpci_device *dev)
                               &rsp, sizeof(rsp), &rsp_len,
                               SUNPCI_INIT_TIMEOUT);
     if (ret < 0) {
-        dev_warn(&dev->pdev->dev, "guest init failed: %d\n", ret);
+        sunpci_warn(dev, "guest init failed: %d\n", ret);
         return ret;
     }
 
-    dev_info(&dev->pdev->dev, "guest version 0x%08x, features 0x%08x\n",
+    sunpci_info(dev, "guest version 0x%08x, features 0x%08x\n",
              le32_to_cpu(rsp.guest_version),
              le32_to_cpu(rsp.features_enabled));
 
@@ -362,7 +368,7 @@ int sunpci_ipc_send_response(struct sunpci_device *dev,
 
     /* Check ring has space */
     if (sunpci_ring_space(&dev->rsp_ring) < total_len) {
-        dev_warn(&dev->pdev->dev, "rsp ring full\n");
+        sunpci_warn(dev, "rsp ring full\n");
         return -ENOSPC;
     }
 
@@ -408,7 +414,7 @@ void sunpci_ipc_process_requests(struct work_struct *work)
     /* Allocate payload buffer */
     payload_buf = kmalloc(SUNPCI_MAX_PAYLOAD, GFP_KERNEL);
     if (!payload_buf) {
-        dev_err(&dev->pdev->dev, "failed to allocate request buffer\n");
+        sunpci_err(dev, "failed to allocate request buffer\n");
         return;
     }
 
@@ -428,7 +434,7 @@ void sunpci_ipc_process_requests(struct work_struct *work)
 
         /* Validate magic */
         if (le32_to_cpu(hdr.magic) != SUNPCI_MSG_MAGIC) {
-            dev_err(&dev->pdev->dev, "bad request magic: 0x%08x\n",
+            sunpci_err(dev, "bad request magic: 0x%08x\n",
                     le32_to_cpu(hdr.magic));
             /* Try to recover by skipping a byte */
             sunpci_ring_skip(&dev->rsp_ring, 1);
@@ -451,7 +457,7 @@ void sunpci_ipc_process_requests(struct work_struct *work)
         /* Read payload */
         if (payload_len > 0) {
             if (payload_len > SUNPCI_MAX_PAYLOAD) {
-                dev_err(&dev->pdev->dev, "payload too large: %zu\n",
+                sunpci_err(dev, "payload too large: %zu\n",
                         payload_len);
                 sunpci_ring_skip(&dev->rsp_ring, payload_len);
                 sunpci_ipc_send_response(dev, sequence,
@@ -460,7 +466,7 @@ void sunpci_ipc_process_requests(struct work_struct *work)
             }
             ret = sunpci_ring_read(&dev->rsp_ring, payload_buf, payload_len);
             if (ret < 0) {
-                dev_err(&dev->pdev->dev, "failed to read payload\n");
+                sunpci_err(dev, "failed to read payload\n");
                 continue;
             }
         }
@@ -473,6 +479,11 @@ void sunpci_ipc_process_requests(struct work_struct *work)
                 command <= CORE_CMD_CHANNEL_UNBIND) {
                 sunpci_dispatch_channel(dev, command, sequence,
                                        payload_buf, payload_len);
+            } else if (command == CORE_CMD_POWER_OFF) {
+                /* Userspace stops the session, as after a hard stop */
+                sunpci_event_post(dev, SUNPCI_EVENT_POWER_OFF, 0, 0, NULL);
+                sunpci_ipc_send_response(dev, sequence,
+                                        SUNPCI_RSP_SUCCESS, NULL, 0);
             } else {
                 sunpci_ipc_send_response(dev, sequence,
                                         SUNPCI_RSP_INVALID_CMD, NULL, 0);
@@ -499,8 +510,18 @@ void sunpci_ipc_process_requests(struct work_struct *work)
                                      payload_buf, payload_len);
             break;
 
+        case SUNPCI_DISP_INPUT:
+            sunpci_dispatch_input(dev, command, sequence,
+                                  payload_buf, payload_len);
+            break;
+
+        case SUNPCI_DISP_AUDIO:
+            sunpci_dispatch_audio(dev, command, sequence,
+                                  payload_buf, payload_len);
+            break;
+
         default:
-            dev_dbg(&dev->pdev->dev, "unknown dispatcher: %d\n", dispatcher);
+            sunpci_dbg(dev, "unknown dispatcher: %d\n", dispatcher);
             sunpci_ipc_send_response(dev, sequence,
                                     SUNPCI_RSP_INVALID_DISP, NULL, 0);
             break;
@@ -702,6 +723,42 @@ static void sunpci_dispatch_fsd(struct sunpci_device *dev,
     kfree(rsp_buf);
 }
 
+/*
+ * Dispatch input notification (keyboard LEDs)
+ */
+static void sunpci_dispatch_input(struct sunpci_device *dev,
+                                  u16 command, u32 sequence,
+                                  void *payload, size_t payload_len)
+{
+    int ret;
+
+    ret = sunpci_input_handle_message(dev, command, payload, payload_len);
+    if (ret == -ENOSYS)
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_INVALID_CMD, NULL, 0);
+    else if (ret < 0)
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_ERROR, NULL, 0);
+    else
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_SUCCESS, NULL, 0);
+}
+
+/*
+ * Dispatch audio notification (format, start and stop)
+ */
+static void sunpci_dispatch_audio(struct sunpci_device *dev,
+                                  u16 command, u32 sequence,
+                                  void *payload, size_t payload_len)
+{
+    int ret;
+
+    ret = sunpci_audio_handle_message(dev, command, payload, payload_len);
+    if (ret == -EINVAL)
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_INVALID_CMD, NULL, 0);
+    else if (ret < 0)
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_ERROR, NULL, 0);
+    else
+        sunpci_ipc_send_response(dev, sequence, SUNPCI_RSP_SUCCESS, NULL, 0);
+}
+
 /*
  * Dispatch clipboard request
  */
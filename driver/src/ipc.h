@@ -33,6 +33,12 @@
 #define CORE_CMD_GET_VERSION    0x0004
 #define CORE_CMD_SET_FEATURES   0x0005
 #define CORE_CMD_GET_FEATURES   0x0006
+#define CORE_CMD_POWER_BUTTON   0x0007  /* Host -> Guest: APM/ACPI power button press */
+#define CORE_CMD_POWER_OFF      0x0008  /* Guest -> Host: guest entered the off state */
+#define CORE_CMD_STATE_SAVE     0x0009  /* Freeze the guest and snapshot its state */
+#define CORE_CMD_STATE_READ     0x000A  /* Read part of the snapshot */
+#define CORE_CMD_STATE_WRITE    0x000B  /* Write part of a snapshot to restore */
+#define CORE_CMD_STATE_LOAD     0x000C  /* Restore the written snapshot and thaw */
 
 /*
  * VGA dispatcher commands (SUNPCI_DISP_VGA)
@@ -64,6 +70,10 @@
 #define INPUT_CMD_MOUSE_MOVE    0x0002
 #define INPUT_CMD_MOUSE_BUTTON  0x0003
 #define INPUT_CMD_MOUSE_WHEEL   0x0004
+#define INPUT_CMD_SET_LEDS      0x0005  /* Host -> Guest: set the lock key LEDs */
+#define INPUT_CMD_LEDS          0x0006  /* Guest -> Host: guest set the LEDs */
+#define INPUT_CMD_MOUSE_ABS     0x0007  /* Host -> Guest: pointer position */
+#define INPUT_CMD_GAMEPORT      0x0008  /* Host -> Guest: game port state */
 
 /*
  * Clipboard dispatcher commands (SUNPCI_DISP_CLIP)
@@ -72,6 +82,8 @@
 #define CLIP_CMD_GET            0x0002  /* Host -> Guest: request clipboard */
 #define CLIP_CMD_NOTIFY         0x0003  /* Guest -> Host: clipboard changed */
 #define CLIP_CMD_DATA           0x0004  /* Guest -> Host: clipboard data */
+#define CLIP_CMD_SET_PART       0x0005  /* Host -> Guest: part of a large set */
+#define CLIP_CMD_GET_PART       0x0006  /* Host -> Guest: read part of clipboard */
 
 /*
  * Network dispatcher commands (SUNPCI_DISP_NETWORK)
@@ -106,6 +118,7 @@
 #define STORAGE_CMD_MOUNT       0x000B  /* Mount media notification */
 #define STORAGE_CMD_UNMOUNT     0x000C  /* Unmount media notification */
 #define STORAGE_CMD_SCSI        0x000D  /* SCSI CDB pass-through (CD-ROM) */
+#define STORAGE_CMD_GET_BOOT    0x000E  /* Get BIOS boot drive (in count) */
 
 /*
  * Message header - prepended to all IPC messages
@@ -159,6 +172,25 @@ struct sunpci_core_init_rsp {
     __le32 framebuffer_size;
 } __packed;
 
+/*
+ * Saved state (CORE_CMD_STATE_*)
+ *
+ * STATE_SAVE answers with the snapshot size and format; STATE_LOAD takes
+ * the same for the snapshot written with STATE_WRITE.
+ */
+struct sunpci_core_state_info {
+    __le64 size;
+    __le32 version;
+    __le32 reserved;
+} __packed;
+
+struct sunpci_core_state_chunk {
+    __le64 offset;
+    __le32 length;      /* Bytes of data following (READ: bytes wanted) */
+    __le32 reserved;
+    /* Data follows for STATE_WRITE and in the STATE_READ response */
+} __packed;
+
 /*
  * VGA mode info
  */
@@ -204,6 +236,25 @@ struct sunpci_input_mouse {
 #define INPUT_MOUSE_RIGHT   0x0002
 #define INPUT_MOUSE_MIDDLE  0x0004
 
+/* Absolute pointer: x and y in guest pixels */
+struct sunpci_input_mouse_abs {
+    __le32 x;
+    __le32 y;
+    __le32 buttons;
+    __le32 wheel;
+} __packed;
+
+/* Game port: what the guest reads from port 201h is timed from this */
+struct sunpci_input_gameport {
+    __le16 axes[4];     /* -32768 to 32767, joystick A X/Y then B X/Y */
+    __le32 buttons;     /* SUNPCI_GAMEPORT_BUTTON_* */
+    __le32 connected;   /* Bit n set if axes[n] has a stick on it */
+} __packed;
+
+struct sunpci_input_leds {
+    __le32 leds;        /* SUNPCI_LED_* (PS/2 "set LEDs" bit order) */
+} __packed;
+
 /*
  * Video surface descriptor (for DirectDraw emulation)
  */
@@ -251,10 +302,16 @@ struct sunpci_video_blt {
  *
  * Format values match Windows clipboard formats:
  *   1 = CF_TEXT (ANSI text)
+ *   8 = CF_DIB (device-independent bitmap)
  *   13 = CF_UNICODETEXT (UTF-16LE)
+ *
+ * CF_RTF is a registered format with no fixed number; the guest service
+ * registers "Rich Text Format" and maps it to CLIP_FORMAT_RTF.
  */
 #define CLIP_FORMAT_TEXT        1   /* ANSI/ASCII text */
+#define CLIP_FORMAT_DIB         8   /* BITMAPINFOHEADER + pixels */
 #define CLIP_FORMAT_UNICODE     13  /* UTF-16LE text */
+#define CLIP_FORMAT_RTF         0x0200  /* Rich Text Format (CF_PRIVATEFIRST) */
 
 struct sunpci_clip_data {
     __le32 format;      /* Clipboard format */
@@ -262,7 +319,29 @@ struct sunpci_clip_data {
     /* Variable length data follows */
 } __packed;
 
-#define SUNPCI_CLIP_MAX_SIZE    (32 * 1024)  /* Max clipboard transfer */
+#define SUNPCI_CLIP_MAX_SIZE    (32 * 1024)  /* Max clipboard data per message */
+
+/*
+ * Clipboard part - for transfers larger than one message
+ *
+ * CLIP_CMD_SET_PART carries consecutive parts of one host -> guest
+ * transfer; the guest sets its clipboard once offset + length reaches
+ * total_length. CLIP_CMD_GET_PART sends a struct sunpci_clip_part_req and
+ * the guest replies with a struct sunpci_clip_part; offset 0 takes a fresh
+ * snapshot of the guest clipboard, later offsets read from it.
+ */
+struct sunpci_clip_part {
+    __le32 format;      /* Clipboard format */
+    __le32 total_length;/* Length of the whole transfer */
+    __le32 offset;      /* Offset of this part */
+    __le32 length;      /* Length of this part (up to SUNPCI_CLIP_MAX_SIZE) */
+    /* Variable length data follows */
+} __packed;
+
+struct sunpci_clip_part_req {
+    __le32 format;      /* Clipboard format wanted */
+    __le32 offset;      /* Offset to read from */
+} __packed;
 
 /*
  * Storage request/response structures
@@ -396,6 +475,7 @@ struct sunpci_net_stats {
  */
 #define SUNPCI_CMD_TIMEOUT      (HZ * 5)    /* 5 seconds */
 #define SUNPCI_INIT_TIMEOUT     (HZ * 10)   /* 10 seconds */
+#define SUNPCI_STATE_TIMEOUT    (HZ * 30)   /* Snapshotting guest RAM */
 
 /*
  * NT Named Channel Support
@@ -0,0 +1,107 @@
+/*
+ * SunPCi driver - Log ring
+ *
+ * Messages logged with sunpci_err/warn/info/dbg go to the kernel log as
+ * "sunpciN: ..." (from the macros, see sunpci.h) and into a ring per
+ * device here, so the frontend can show them next to its own without
+ * reading the kernel log (which needs privileges, and drops debug messages
+ * unless dynamic debug is on). Each message gets a sequence number;
+ * READ_LOG returns the messages from a given number on and never consumes
+ * them. Messages longer than SUNPCI_LOG_MESSAGE are cut short in the ring
+ * only.
+ */
+
+#include <linux/kernel.h>
+#include <linux/slab.h>
+#include <linux/string.h>
+#include <linux/timekeeping.h>
+#include <linux/uaccess.h>
+
+#include "sunpci.h"
+
+void sunpci_log_init(struct sunpci_device *dev)
+{
+    spin_lock_init(&dev->log_lock);
+    dev->log_seq = 0;
+}
+
+/* Record a message in the log ring */
+void sunpci_log(struct sunpci_device *dev, u32 level, const char *fmt, ...)
+{
+    struct sunpci_log_entry *entry;
+    char message[SUNPCI_LOG_MESSAGE];
+    unsigned long flags;
+    va_list args;
+    size_t len;
+    u64 now;
+
+    va_start(args, fmt);
+    vsnprintf(message, sizeof(message), fmt, args);
+    va_end(args);
+
+    /* Callers end their messages with a newline, as for printk */
+    len = strlen(message);
+    if (len && message[len - 1] == '\n')
+        message[len - 1] = '\0';
+
+    now = ktime_get_real_ns();
+
+    /* Called from interrupt context too (doorbell handlers) */
+    spin_lock_irqsave(&dev->log_lock, flags);
+    dev->log_seq++;
+    entry = &dev->log[dev->log_seq % SUNPCI_LOG_RING];
+    entry->seq = dev->log_seq;
+    entry->level = level;
+    entry->time_lo = lower_32_bits(now);
+    entry->time_hi = upper_32_bits(now);
+    strscpy(entry->message, message, SUNPCI_LOG_MESSAGE);
+    spin_unlock_irqrestore(&dev->log_lock, flags);
+}
+
+/* Copy the messages from batch->since on into @batch */
+static void log_read(struct sunpci_device *dev, struct sunpci_log_batch *batch)
+{
+    unsigned long flags;
+    u32 oldest;
+    u32 seq;
+
+    spin_lock_irqsave(&dev->log_lock, flags);
+    oldest = dev->log_seq > SUNPCI_LOG_RING ?
+             dev->log_seq - SUNPCI_LOG_RING + 1 : 1;
+    seq = batch->since;
+    /* Nothing yet, or numbers from before the module was reloaded */
+    if (seq == 0 || seq > dev->log_seq + 1) {
+        seq = oldest;
+    } else if (seq < oldest) {
+        batch->dropped = oldest - seq;
+        seq = oldest;
+    }
+    while (seq <= dev->log_seq && batch->count < SUNPCI_MAX_LOG_ENTRIES)
+        batch->entries[batch->count++] = dev->log[seq++ % SUNPCI_LOG_RING];
+    batch->next = seq;
+    spin_unlock_irqrestore(&dev->log_lock, flags);
+}
+
+int sunpci_ioctl_read_log(struct sunpci_device *dev, unsigned long arg)
+{
+    struct sunpci_log_batch *batch;
+    int ret = 0;
+
+    /* Too big for the stack */
+    batch = kzalloc(sizeof(*batch), GFP_KERNEL);
+    if (!batch)
+        return -ENOMEM;
+
+    if (copy_from_user(&batch->since, (void __user *)arg, sizeof(batch->since))) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    log_read(dev, batch);
+    if (copy_to_user((void __user *)arg, batch, sizeof(*batch)))
+        ret = -EFAULT;
+
+out:
+    kfree(batch);
+    return ret;
+}
@@ -18,7 +18,7 @@
 MODULE_AUTHOR("Rising Sun Project");
 MODULE_DESCRIPTION("SunPCi driver");
 MODULE_LICENSE("GPL");
-MODULE_VERSION("0.1.0");
+MODULE_VERSION("0.2.0");
 
 /* Global variables */
 struct class *sunpci_class;
@@ -40,6 +40,7 @@ static int sunpci_open(struct inode *inode, struct file *file)
         return -ENODEV;
 
     file->private_data = dev;
+    sunpci_events_open(dev, file);
 
     pr_debug("sunpci: device %d opened\n", minor);
     return 0;
@@ -49,6 +50,9 @@ static int sunpci_release(struct inode *inode, struct file *file)
 {
     struct sunpci_device *dev = file->private_data;
 
+    /* The session keeps running; it just has no owner until claimed */
+    sunpci_release_owner(dev, file);
+
     pr_debug("sunpci: device %d closed\n", dev->minor);
     return 0;
 }
@@ -60,6 +64,7 @@ const struct file_operations sunpci_fops = {
     .unlocked_ioctl = sunpci_ioctl,
     .compat_ioctl = sunpci_ioctl,
     .mmap = sunpci_mmap,
+    .poll = sunpci_poll,
 };
 
 /* Initialize default display state */
@@ -102,8 +107,11 @@ struct sunpci_device *sunpci_create_device(int minor, struct pci_dev *pdev)
     dev->minor = minor;
     dev->pdev = pdev;
     mutex_init(&dev->mutex);
+    sunpci_storage_init(dev);
     init_waitqueue_head(&dev->rsp_wait);
     init_waitqueue_head(&dev->clipboard_wait);
+    sunpci_events_init(dev);
+    sunpci_log_init(dev);
     dev->state = SUNPCI_STATE_STOPPED;
     
     /* Default configuration - memory is physical on card, not configurable */
@@ -155,6 +163,7 @@ void sunpci_destroy_device(struct sunpci_device *dev)
     /* Cleanup subsystems */
     sunpci_net_shutdown(dev);
     sunpci_storage_cleanup(dev);
+    sunpci_clip_cleanup(dev);
 
     sunpci_devices[dev->minor] = NULL;
     device_destroy(sunpci_class, MKDEV(sunpci_major, dev->minor));
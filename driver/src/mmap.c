@@ -17,6 +17,9 @@
  * We use the vm_pgoff to select which region to map:
  *   pgoff == 0: Framebuffer (BAR2)
  *   pgoff == 1: Shared memory (BAR1) - for advanced users
+ *
+ * Userspace discovers these offsets through SUNPCI_IOC_GET_MMAP_REGIONS,
+ * so new regions can be added here without breaking existing clients.
  */
 #define SUNPCI_MMAP_FRAMEBUFFER  0
 #define SUNPCI_MMAP_SHMEM        1
@@ -72,20 +75,20 @@ int sunpci_mmap(struct file *file, struct vm_area_struct *vma)
         break;
 
     default:
-        dev_warn(&dev->pdev->dev, "mmap: invalid region %lu\n", vma->vm_pgoff);
+        sunpci_warn(dev, "mmap: invalid region %lu\n", vma->vm_pgoff);
         return -EINVAL;
     }
 
     /* Validate region exists */
     if (phys_start == 0 || region_size == 0) {
-        dev_warn(&dev->pdev->dev, "mmap: region %lu not available\n",
+        sunpci_warn(dev, "mmap: region %lu not available\n",
                  vma->vm_pgoff);
         return -ENODEV;
     }
 
     /* Validate size fits in region */
     if (size > region_size) {
-        dev_warn(&dev->pdev->dev, "mmap: requested size %lu > region size %llu\n",
+        sunpci_warn(dev, "mmap: requested size %lu > region size %llu\n",
                  size, (unsigned long long)region_size);
         return -EINVAL;
     }
@@ -104,11 +107,11 @@ int sunpci_mmap(struct file *file, struct vm_area_struct *vma)
     pfn = phys_start >> PAGE_SHIFT;
     ret = remap_pfn_range(vma, vma->vm_start, pfn, size, vma->vm_page_prot);
     if (ret) {
-        dev_err(&dev->pdev->dev, "mmap: remap_pfn_range failed: %d\n", ret);
+        sunpci_err(dev, "mmap: remap_pfn_range failed: %d\n", ret);
         return ret;
     }
 
-    dev_dbg(&dev->pdev->dev, "mmap: mapped region %lu, phys 0x%llx, size %lu\n",
+    sunpci_dbg(dev, "mmap: mapped region %lu, phys 0x%llx, size %lu\n",
             vma->vm_pgoff, (unsigned long long)phys_start, size);
 
     return 0;
@@ -167,3 +170,45 @@ int sunpci_get_fb_info(struct sunpci_device *dev, struct sunpci_framebuffer *inf
 
     return 0;
 }
+
+/* Append a region to the table if it is backed by a PCI resource */
+static void add_region(struct sunpci_mmap_regions *regions, u32 id, u32 flags,
+                       unsigned long pgoff, resource_size_t len)
+{
+    struct sunpci_mmap_region *r;
+    u64 offset = (u64)pgoff << PAGE_SHIFT;
+
+    if (len == 0 || regions->count >= SUNPCI_MAX_MMAP_REGIONS)
+        return;
+
+    r = &regions->regions[regions->count++];
+    r->id = id;
+    r->flags = flags;
+    r->offset_lo = (u32)offset;
+    r->offset_hi = (u32)(offset >> 32);
+    r->size_lo = (u32)len;
+    r->size_hi = (u32)((u64)len >> 32);
+}
+
+/**
+ * sunpci_get_mmap_regions - Build the table of mappable regions
+ * @dev: Device
+ * @regions: Output table
+ *
+ * Called by ioctl so userspace can look up mmap offsets by region id.
+ */
+void sunpci_get_mmap_regions(struct sunpci_device *dev,
+                             struct sunpci_mmap_regions *regions)
+{
+    memset(regions, 0, sizeof(*regions));
+
+    if (!dev || !dev->pdev)
+        return;
+
+    add_region(regions, SUNPCI_REGION_FRAMEBUFFER,
+               SUNPCI_REGION_READ | SUNPCI_REGION_WRITE,
+               SUNPCI_MMAP_FRAMEBUFFER, pci_resource_len(dev->pdev, 2));
+    add_region(regions, SUNPCI_REGION_SHMEM,
+               SUNPCI_REGION_READ | SUNPCI_REGION_WRITE,
+               SUNPCI_MMAP_SHMEM, pci_resource_len(dev->pdev, 1));
+}
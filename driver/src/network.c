@@ -5,8 +5,14 @@
  * The guest sees a virtual NIC that communicates through the host's
  * network stack using a TAP interface.
  *
+ * In user mode (SUNPCI_NET_USER_MODE) no TAP is opened: frames the guest
+ * sends are queued for userspace to collect with SUNPCI_IOC_NET_RECV_FRAME,
+ * and userspace injects frames with SUNPCI_IOC_NET_SEND_FRAME. The
+ * frontend runs its NAT stack this way, which needs no privileges.
+ *
  * Supports:
  *   - TAP device creation and management
+ *   - User-mode frame exchange
  *   - Packet send/receive via ring buffers
  *   - Multicast filtering
  *   - Link state notifications
@@ -32,8 +38,8 @@
 #define ETH_FRAME_MAX       1514
 #define ETH_FRAME_MIN       60
 
-/* Receive queue depth */
-#define NET_RX_QUEUE_SIZE   64
+/* Frame queue depth */
+#define NET_QUEUE_SIZE      64
 
 /* TAP device path */
 #define TUN_DEV_PATH        "/dev/net/tun"
@@ -44,6 +50,20 @@
 #define NET_IRQ_11          11
 #define NET_IRQ_15          15
 
+/*
+ * Queue of Ethernet frames
+ */
+struct net_queue {
+    struct {
+        u8 data[ETH_FRAME_MAX];
+        size_t len;
+    } frames[NET_QUEUE_SIZE];
+    size_t head;
+    size_t tail;
+    size_t count;
+    spinlock_t lock;
+};
+
 /*
  * Network device state
  */
@@ -57,6 +77,7 @@ struct sunpci_net_dev {
     u8 irq_line;
     bool promiscuous;
     bool enabled;
+    bool user_mode;     /* Frames go to userspace instead of a TAP */
     
     /* Multicast filter */
     u8 mcast_list[32][ETH_ALEN];  /* Up to 32 multicast addresses */
@@ -71,15 +92,11 @@ struct sunpci_net_dev {
     u64 rx_dropped;
     u64 tx_dropped;
     
-    /* Receive queue */
-    struct {
-        u8 data[ETH_FRAME_MAX];
-        size_t len;
-    } rx_queue[NET_RX_QUEUE_SIZE];
-    size_t rx_head;
-    size_t rx_tail;
-    size_t rx_count;
-    spinlock_t rx_lock;
+    /* Receive queue (frames for the guest) */
+    struct net_queue rx_queue;
+    
+    /* Transmit queue (frames from the guest, user mode only) */
+    struct net_queue tx_queue;
     
     /* Receive thread */
     struct task_struct *rx_thread;
@@ -199,55 +216,86 @@ static bool net_mac_filter(struct sunpci_net_dev *ndev, const u8 *dest)
 }
 
 /*
- * Enqueue received packet
+ * Append a frame to a queue
  */
-static int net_rx_enqueue(struct sunpci_net_dev *ndev, const u8 *data, size_t len)
+static int net_queue_push(struct net_queue *q, const u8 *data, size_t len)
 {
     unsigned long flags;
     
     if (len > ETH_FRAME_MAX)
         len = ETH_FRAME_MAX;
     
-    spin_lock_irqsave(&ndev->rx_lock, flags);
+    spin_lock_irqsave(&q->lock, flags);
     
-    if (ndev->rx_count >= NET_RX_QUEUE_SIZE) {
-        ndev->rx_dropped++;
-        spin_unlock_irqrestore(&ndev->rx_lock, flags);
+    if (q->count >= NET_QUEUE_SIZE) {
+        spin_unlock_irqrestore(&q->lock, flags);
         return -ENOBUFS;
     }
     
-    memcpy(ndev->rx_queue[ndev->rx_tail].data, data, len);
-    ndev->rx_queue[ndev->rx_tail].len = len;
-    ndev->rx_tail = (ndev->rx_tail + 1) % NET_RX_QUEUE_SIZE;
-    ndev->rx_count++;
+    memcpy(q->frames[q->tail].data, data, len);
+    q->frames[q->tail].len = len;
+    q->tail = (q->tail + 1) % NET_QUEUE_SIZE;
+    q->count++;
     
-    spin_unlock_irqrestore(&ndev->rx_lock, flags);
+    spin_unlock_irqrestore(&q->lock, flags);
     return 0;
 }
 
 /*
- * Dequeue received packet
+ * Take the oldest frame from a queue
  */
-static int net_rx_dequeue(struct sunpci_net_dev *ndev, u8 *data, size_t *len)
+static int net_queue_pop(struct net_queue *q, u8 *data, size_t *len)
 {
     unsigned long flags;
     
-    spin_lock_irqsave(&ndev->rx_lock, flags);
+    spin_lock_irqsave(&q->lock, flags);
     
-    if (ndev->rx_count == 0) {
-        spin_unlock_irqrestore(&ndev->rx_lock, flags);
+    if (q->count == 0) {
+        spin_unlock_irqrestore(&q->lock, flags);
         return -ENODATA;
     }
     
-    *len = ndev->rx_queue[ndev->rx_head].len;
-    memcpy(data, ndev->rx_queue[ndev->rx_head].data, *len);
-    ndev->rx_head = (ndev->rx_head + 1) % NET_RX_QUEUE_SIZE;
-    ndev->rx_count--;
+    *len = q->frames[q->head].len;
+    memcpy(data, q->frames[q->head].data, *len);
+    q->head = (q->head + 1) % NET_QUEUE_SIZE;
+    q->count--;
     
-    spin_unlock_irqrestore(&ndev->rx_lock, flags);
+    spin_unlock_irqrestore(&q->lock, flags);
     return 0;
 }
 
+/*
+ * Discard all queued frames
+ */
+static void net_queue_clear(struct net_queue *q)
+{
+    unsigned long flags;
+    
+    spin_lock_irqsave(&q->lock, flags);
+    q->head = q->tail = q->count = 0;
+    spin_unlock_irqrestore(&q->lock, flags);
+}
+
+/*
+ * Enqueue received packet
+ */
+static int net_rx_enqueue(struct sunpci_net_dev *ndev, const u8 *data, size_t len)
+{
+    int ret = net_queue_push(&ndev->rx_queue, data, len);
+    
+    if (ret < 0)
+        ndev->rx_dropped++;
+    return ret;
+}
+
+/*
+ * Dequeue received packet
+ */
+static int net_rx_dequeue(struct sunpci_net_dev *ndev, u8 *data, size_t *len)
+{
+    return net_queue_pop(&ndev->rx_queue, data, len);
+}
+
 /*
  * Receive thread - reads packets from TAP and queues them
  */
@@ -289,19 +337,30 @@ static int net_rx_thread(void *data)
 }
 
 /*
- * Send packet to TAP device
+ * Send packet to TAP device (or queue it for userspace in user mode)
  */
 static int net_send_packet(struct sunpci_net_dev *ndev, const u8 *data, size_t len)
 {
     loff_t pos = 0;
     ssize_t ret;
     
-    if (!ndev->tap_file || !ndev->enabled)
+    if (!ndev->enabled || (!ndev->tap_file && !ndev->user_mode))
         return -ENODEV;
     
     if (len < ETH_FRAME_MIN || len > ETH_FRAME_MAX)
         return -EINVAL;
     
+    if (ndev->user_mode) {
+        ret = net_queue_push(&ndev->tx_queue, data, len);
+        if (ret < 0) {
+            ndev->tx_dropped++;
+            return ret;
+        }
+        ndev->tx_packets++;
+        ndev->tx_bytes += len;
+        return 0;
+    }
+    
     ret = kernel_write(ndev->tap_file, data, len, &pos);
     if (ret < 0) {
         ndev->tx_dropped++;
@@ -495,7 +554,8 @@ int sunpci_net_init(struct sunpci_device *dev)
         return -ENOMEM;
     
     ndev->dev = dev;
-    spin_lock_init(&ndev->rx_lock);
+    spin_lock_init(&ndev->rx_queue.lock);
+    spin_lock_init(&ndev->tx_queue.lock);
     
     /* Generate MAC address */
     if (is_zero_ether_addr(dev->network.mac_address)) {
@@ -505,7 +565,8 @@ int sunpci_net_init(struct sunpci_device *dev)
     }
     
     /* Open TAP device if networking is enabled */
-    if (dev->network.flags & SUNPCI_NET_ENABLED) {
+    ndev->user_mode = (dev->network.flags & SUNPCI_NET_USER_MODE) != 0;
+    if ((dev->network.flags & SUNPCI_NET_ENABLED) && !ndev->user_mode) {
         ret = net_open_tap(ndev, dev->network.interface);
         if (ret < 0) {
             kfree(ndev);
@@ -529,6 +590,7 @@ int sunpci_net_configure(struct sunpci_device *dev,
                          const struct sunpci_network_config *config)
 {
     struct sunpci_net_dev *ndev = dev->net_dev;
+    bool user_mode;
     int ret = 0;
     
     if (!ndev)
@@ -538,8 +600,29 @@ int sunpci_net_configure(struct sunpci_device *dev,
     if (!is_zero_ether_addr(config->mac_address))
         memcpy(ndev->mac_addr, config->mac_address, ETH_ALEN);
     
+    /* Changing mode releases the TAP and drops frames queued for userspace */
+    user_mode = (config->flags & SUNPCI_NET_USER_MODE) != 0;
+    if (user_mode != ndev->user_mode) {
+        if (ndev->rx_running) {
+            ndev->rx_running = false;
+            if (ndev->rx_thread) {
+                kthread_stop(ndev->rx_thread);
+                ndev->rx_thread = NULL;
+            }
+        }
+        net_close_tap(ndev);
+        net_queue_clear(&ndev->tx_queue);
+        ndev->user_mode = user_mode;
+    }
+    
     /* Handle enable/disable */
-    if ((config->flags & SUNPCI_NET_ENABLED) && !ndev->tap_file) {
+    if (user_mode) {
+        /* Nothing to open; the guest's NET_CMD_OPEN enables the adapter */
+        if (!(config->flags & SUNPCI_NET_ENABLED)) {
+            ndev->enabled = false;
+            net_queue_clear(&ndev->tx_queue);
+        }
+    } else if ((config->flags & SUNPCI_NET_ENABLED) && !ndev->tap_file) {
         /* Enable networking - open TAP */
         ret = net_open_tap(ndev, config->interface);
     } else if (!(config->flags & SUNPCI_NET_ENABLED) && ndev->tap_file) {
@@ -581,6 +664,8 @@ int sunpci_net_get_status(struct sunpci_device *dev,
     status->flags = ndev->enabled ? SUNPCI_NET_ENABLED : 0;
     if (ndev->promiscuous)
         status->flags |= SUNPCI_NET_PROMISCUOUS;
+    if (ndev->user_mode)
+        status->flags |= SUNPCI_NET_USER_MODE;
     
     status->rx_packets = ndev->rx_packets;
     status->tx_packets = ndev->tx_packets;
@@ -590,6 +675,49 @@ int sunpci_net_get_status(struct sunpci_device *dev,
     return 0;
 }
 
+/*
+ * Deliver a frame from userspace to the guest (user mode)
+ */
+int sunpci_net_user_send(struct sunpci_device *dev, const u8 *data, size_t len)
+{
+    struct sunpci_net_dev *ndev = dev->net_dev;
+    int ret;
+    
+    if (!ndev || !ndev->user_mode)
+        return -ENODEV;
+    
+    if (len < ETH_HLEN || len > ETH_FRAME_MAX)
+        return -EINVAL;
+    
+    /* Frames arriving before the guest opened the adapter are dropped */
+    if (!ndev->enabled || !net_mac_filter(ndev, data))
+        return 0;
+    
+    ret = net_rx_enqueue(ndev, data, len);
+    if (ret < 0)
+        return ret;
+    
+    ndev->rx_packets++;
+    ndev->rx_bytes += len;
+    sunpci_net_notify_rx(dev);
+    return 0;
+}
+
+/*
+ * Collect the next frame the guest sent (user mode)
+ */
+int sunpci_net_user_recv(struct sunpci_device *dev, u8 *data, size_t *len)
+{
+    struct sunpci_net_dev *ndev = dev->net_dev;
+    
+    if (!ndev || !ndev->user_mode)
+        return -ENODEV;
+    
+    if (net_queue_pop(&ndev->tx_queue, data, len) < 0)
+        return -EAGAIN;
+    return 0;
+}
+
 /*
  * Shutdown network subsystem
  */
@@ -55,6 +55,9 @@ static irqreturn_t sunpci_irq_handler(int irq, void *dev_id)
     /* Clear the bits we're handling */
     sunpci_write32(dev, I21554_SEC_DOORBELL_CLR, doorbell);
 
+    /* Any doorbell shows the guest is alive (see the session watchdog) */
+    WRITE_ONCE(dev->last_guest_activity, ktime_get());
+
     /* Handle doorbell events */
     if (doorbell & SUNPCI_DOORBELL_CMD_READY) {
         /* Guest has sent a request - queue work to process it */
@@ -68,12 +71,12 @@ static irqreturn_t sunpci_irq_handler(int irq, void *dev_id)
 
     if (doorbell & SUNPCI_DOORBELL_VGA_UPDATE) {
         /* VGA framebuffer was updated */
-        pr_debug("sunpci%d: VGA update\n", dev->minor);
+        sunpci_trace(dev, "VGA update\n");
     }
 
     if (doorbell & SUNPCI_DOORBELL_RESET) {
         /* Guest initiated reset */
-        pr_info("sunpci%d: guest reset\n", dev->minor);
+        sunpci_info(dev, "guest reset\n");
     }
 
     return IRQ_HANDLED;
@@ -90,9 +93,9 @@ static int sunpci_setup_rings(struct sunpci_device *dev)
     int ret;
 
     if (!dev->shmem_base || dev->shmem_len < SUNPCI_SHMEM_MIN_SIZE) {
-        pr_warn("sunpci%d: shared memory too small for rings (%llu < %d)\n",
-                dev->minor, (unsigned long long)dev->shmem_len,
-                SUNPCI_SHMEM_MIN_SIZE);
+        sunpci_warn(dev, "shared memory too small for rings (%llu < %d)\n",
+                    (unsigned long long)dev->shmem_len,
+                    SUNPCI_SHMEM_MIN_SIZE);
         return -ENOMEM;
     }
 
@@ -102,7 +105,7 @@ static int sunpci_setup_rings(struct sunpci_device *dev)
                            0, /* Physical addr not needed for MMIO */
                            SUNPCI_SHMEM_CMD_SIZE);
     if (ret) {
-        pr_err("sunpci%d: failed to init command ring: %d\n", dev->minor, ret);
+        sunpci_err(dev, "failed to init command ring: %d\n", ret);
         return ret;
     }
 
@@ -117,7 +120,7 @@ static int sunpci_setup_rings(struct sunpci_device *dev)
                            0,
                            SUNPCI_SHMEM_RSP_SIZE);
     if (ret) {
-        pr_err("sunpci%d: failed to init response ring: %d\n", dev->minor, ret);
+        sunpci_err(dev, "failed to init response ring: %d\n", ret);
         return ret;
     }
 
@@ -125,8 +128,8 @@ static int sunpci_setup_rings(struct sunpci_device *dev)
     dev->rsp_ring.head_reg = SUNPCI_SCRATCH_RSP_HEAD;
     dev->rsp_ring.tail_reg = SUNPCI_SCRATCH_RSP_TAIL;
 
-    pr_info("sunpci%d: ring buffers initialized (cmd=%uB, rsp=%uB)\n",
-            dev->minor, SUNPCI_SHMEM_CMD_SIZE, SUNPCI_SHMEM_RSP_SIZE);
+    sunpci_info(dev, "ring buffers initialized (cmd=%uB, rsp=%uB)\n",
+                SUNPCI_SHMEM_CMD_SIZE, SUNPCI_SHMEM_RSP_SIZE);
 
     return 0;
 }
@@ -219,6 +222,13 @@ static int sunpci_pci_probe(struct pci_dev *pdev, const struct pci_device_id *id
             pr_warn("sunpci: ring buffer setup failed, continuing without IPC\n");
     }
 
+    /* Audio lives in a fixed region of shared memory */
+    if (dev->shmem_base) {
+        ret = sunpci_audio_init(dev);
+        if (ret)
+            pr_warn("sunpci: audio init failed: %d\n", ret);
+    }
+
     /* Initialize NT named channel support */
     ret = sunpci_channel_init(dev);
     if (ret)
@@ -286,6 +296,8 @@ static void sunpci_pci_remove(struct pci_dev *pdev)
         if (dev->irq)
             free_irq(dev->irq, dev);
 
+        sunpci_audio_shutdown(dev);
+
         /* Unmap BARs */
         if (dev->shmem_base)
             pci_iounmap(pdev, dev->shmem_base);
@@ -315,7 +327,7 @@ static int sunpci_pci_suspend(struct device *device)
     struct pci_dev *pdev = to_pci_dev(device);
     struct sunpci_device *dev = pci_get_drvdata(pdev);
 
-    pr_info("sunpci%d: suspending\n", dev->minor);
+    sunpci_info(dev, "suspending\n");
 
     /* Mark device as suspended */
     dev->suspended = true;
@@ -332,7 +344,7 @@ static int sunpci_pci_suspend(struct device *device)
     if (dev->mmio_base)
         sunpci_write32(dev, I21554_PRI_DOORBELL, SUNPCI_DOORBELL_RESET);
 
-    pr_debug("sunpci%d: suspended\n", dev->minor);
+    sunpci_dbg(dev, "suspended\n");
     return 0;
 }
 
@@ -350,7 +362,7 @@ static int sunpci_pci_resume(struct device *device)
     struct pci_dev *pdev = to_pci_dev(device);
     struct sunpci_device *dev = pci_get_drvdata(pdev);
 
-    pr_info("sunpci%d: resuming\n", dev->minor);
+    sunpci_info(dev, "resuming\n");
 
     /* Re-enable doorbell interrupts */
     if (dev->mmio_base)
@@ -366,7 +378,7 @@ static int sunpci_pci_resume(struct device *device)
     /* Mark device as active */
     dev->suspended = false;
 
-    pr_debug("sunpci%d: resumed\n", dev->minor);
+    sunpci_dbg(dev, "resumed\n");
     return 0;
 }
 
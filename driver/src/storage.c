@@ -4,12 +4,18 @@
  * Handles INT 13h BIOS disk service requests from the guest.
  * Provides access to disk images, ISO files, and floppy images.
  * Includes SCSI-2/MMC-2 command emulation for CD-ROM devices.
+ *
+ * A hard disk or floppy image may be a copy-on-write overlay of a
+ * read-only template (see common/src/overlay.rs for the format). Sectors the guest
+ * has written are kept in the overlay, marked in its bitmap; the rest are
+ * read from the template, which is never written.
  */
 
 #include <linux/fs.h>
 #include <linux/file.h>
 #include <linux/slab.h>
 #include <linux/uio.h>
+#include <linux/uaccess.h>
 
 #include "sunpci.h"
 #include "ipc.h"
@@ -22,10 +28,29 @@
 /* Maximum sectors per transfer */
 #define MAX_SECTORS_PER_IO  128
 
+/* How long a guest CD-ROM command waits for userspace in pass-through */
+#define SCSI_PT_TIMEOUT     (10 * HZ)
+
 /* SunPCi disk image magic */
 #define SUNPCI_DISK_MAGIC       0x53504349  /* "SPCI" at offset 12 */
 #define SUNPCI_DISK_MAGIC_OFF   12
 
+/* Copy-on-write overlay header */
+#define OVERLAY_MAGIC           "SPCIOVL"   /* 8 bytes with the NUL */
+#define OVERLAY_MAGIC_LEN       8
+#define OVERLAY_VERSION         1
+#define OVERLAY_BASE_PATH_OFF   64
+#define OVERLAY_HEADER_SIZE     4096
+
+struct overlay_header {
+    char magic[OVERLAY_MAGIC_LEN];
+    __le32 version;
+    __le32 reserved;
+    __le64 disk_sectors;        /* Sectors of the virtual disk */
+    __le64 bitmap_offset;       /* One bit per sector, LSB first */
+    __le64 data_offset;         /* Sector n at data_offset + n * 512 */
+} __packed;
+
 /* ISO 9660 signature */
 #define ISO9660_MAGIC           "CD001"
 #define ISO9660_MAGIC_OFF       (16 * 2048 + 1)  /* Sector 16, offset 1 */
@@ -36,6 +61,7 @@
 #define SCSI_REQUEST_SENSE              0x03
 #define SCSI_INQUIRY                    0x12
 #define SCSI_MODE_SENSE_6               0x1A
+#define SCSI_START_STOP_UNIT            0x1B
 #define SCSI_PREVENT_ALLOW_REMOVAL      0x1E
 #define SCSI_READ_CAPACITY              0x25
 #define SCSI_READ_10                    0x28
@@ -98,6 +124,10 @@ struct sunpci_storage_dev {
     u64 total_sectors;          /* Total sectors */
     bool readonly;              /* Write protected */
     bool mounted;               /* Currently mounted */
+    struct file *base;          /* Template of an overlay, or NULL */
+    u8 *bitmap;                 /* Overlay sectors written, one bit each */
+    loff_t bitmap_offset;       /* Bitmap position in the overlay file */
+    loff_t data_offset;         /* Sector 0 position in the overlay file */
 };
 
 /*
@@ -121,6 +151,44 @@ static struct sunpci_storage_dev *get_storage_dev(struct sunpci_device *dev,
     return NULL;
 }
 
+/*
+ * Whether a drive number has media in it
+ */
+static bool storage_has_media(struct sunpci_device *dev, u32 drive)
+{
+    struct sunpci_storage_dev *sdev = get_storage_dev(dev, drive);
+
+    return sdev && sdev->mounted;
+}
+
+/*
+ * BIOS drive number to boot from
+ *
+ * A device with no medium (or a disc that is only passed through, which the
+ * BIOS cannot read) falls back to the default order: C:, then the CD-ROM.
+ */
+static u32 storage_boot_drive(struct sunpci_device *dev)
+{
+    switch (dev->config.boot_device) {
+    case SUNPCI_BOOT_HARD_DISK:
+        if (storage_has_media(dev, 0x80))
+            return 0x80;
+        break;
+    case SUNPCI_BOOT_CDROM:
+        if (storage_has_media(dev, 0xE0))
+            return 0xE0;
+        break;
+    case SUNPCI_BOOT_FLOPPY:
+        if (storage_has_media(dev, 0x00))
+            return 0x00;
+        break;
+    }
+
+    if (!storage_has_media(dev, 0x80) && storage_has_media(dev, 0xE0))
+        return 0xE0;
+    return 0x80;
+}
+
 /*
  * Calculate CHS geometry for a disk size
  * Uses the same algorithm as the original SunPCi
@@ -275,6 +343,104 @@ static int validate_hdd(struct file *file, loff_t size)
     return 0;
 }
 
+/*
+ * Release an overlay's template and bitmap
+ */
+static void storage_close_overlay(struct sunpci_storage_dev *sdev)
+{
+    if (sdev->base) {
+        filp_close(sdev->base, NULL);
+        sdev->base = NULL;
+    }
+    kvfree(sdev->bitmap);
+    sdev->bitmap = NULL;
+}
+
+/*
+ * Set up an overlay if the file is one; returns 0 for a plain image
+ * (sdev->base stays NULL) or an overlay, negative on error. Sets *size
+ * to the size of the virtual disk.
+ */
+static int storage_open_overlay(struct sunpci_storage_dev *sdev,
+                                struct file *file, loff_t *size)
+{
+    struct overlay_header hdr;
+    char *base_path;
+    struct file *base;
+    loff_t pos, base_size;
+    u64 sectors, bitmap_len;
+    ssize_t ret;
+    int err;
+
+    if (*size < OVERLAY_HEADER_SIZE)
+        return 0;
+
+    pos = 0;
+    ret = kernel_read(file, &hdr, sizeof(hdr), &pos);
+    if (ret != sizeof(hdr) || memcmp(hdr.magic, OVERLAY_MAGIC, OVERLAY_MAGIC_LEN))
+        return 0;
+
+    if (le32_to_cpu(hdr.version) != OVERLAY_VERSION)
+        return -EINVAL;
+
+    sectors = le64_to_cpu(hdr.disk_sectors);
+    bitmap_len = DIV_ROUND_UP(sectors, 8);
+    sdev->bitmap_offset = le64_to_cpu(hdr.bitmap_offset);
+    sdev->data_offset = le64_to_cpu(hdr.data_offset);
+    if (sectors == 0 || sdev->bitmap_offset < OVERLAY_HEADER_SIZE ||
+        sdev->data_offset < sdev->bitmap_offset + bitmap_len)
+        return -EINVAL;
+
+    base_path = kzalloc(OVERLAY_HEADER_SIZE - OVERLAY_BASE_PATH_OFF, GFP_KERNEL);
+    if (!base_path)
+        return -ENOMEM;
+    pos = OVERLAY_BASE_PATH_OFF;
+    ret = kernel_read(file, base_path,
+                      OVERLAY_HEADER_SIZE - OVERLAY_BASE_PATH_OFF - 1, &pos);
+    if (ret <= 0) {
+        kfree(base_path);
+        return -EIO;
+    }
+
+    /* The template is only ever read */
+    base = filp_open(base_path, O_RDONLY | O_LARGEFILE, 0);
+    if (IS_ERR(base)) {
+        pr_err("sunpci: cannot open overlay template %s\n", base_path);
+        kfree(base_path);
+        return PTR_ERR(base);
+    }
+    kfree(base_path);
+
+    base_size = i_size_read(file_inode(base));
+    if (base_size < sectors * SECTOR_SIZE_HD) {
+        err = -EINVAL;
+        goto err_base;
+    }
+
+    sdev->bitmap = kvzalloc(bitmap_len, GFP_KERNEL);
+    if (!sdev->bitmap) {
+        err = -ENOMEM;
+        goto err_base;
+    }
+    pos = sdev->bitmap_offset;
+    ret = kernel_read(file, sdev->bitmap, bitmap_len, &pos);
+    if (ret < 0) {
+        err = ret;
+        goto err_bitmap;
+    }
+
+    sdev->base = base;
+    *size = sectors * SECTOR_SIZE_HD;
+    return 0;
+
+err_bitmap:
+    kvfree(sdev->bitmap);
+    sdev->bitmap = NULL;
+err_base:
+    filp_close(base, NULL);
+    return err;
+}
+
 /*
  * Open and validate a disk image file
  */
@@ -309,19 +475,25 @@ static int storage_open_image(struct sunpci_storage_dev *sdev,
         break;
         
     case STORAGE_TYPE_FLOPPY:
-        ret = validate_floppy(size);
+        ret = storage_open_overlay(sdev, file, &size);
+        if (ret == 0)
+            ret = validate_floppy(size);
         if (ret < 0) {
             pr_err("sunpci: invalid floppy image size (%lld bytes): %s\n",
                    size, path);
+            storage_close_overlay(sdev);
             filp_close(file, NULL);
             return ret;
         }
         break;
         
     case STORAGE_TYPE_HDD:
-        ret = validate_hdd(file, size);
+        ret = storage_open_overlay(sdev, file, &size);
+        if (ret == 0)
+            ret = validate_hdd(sdev->base ? sdev->base : file, size);
         if (ret < 0) {
             pr_err("sunpci: invalid disk image: %s\n", path);
+            storage_close_overlay(sdev);
             filp_close(file, NULL);
             return ret;
         }
@@ -356,9 +528,84 @@ static void storage_close_image(struct sunpci_storage_dev *sdev)
         filp_close(sdev->file, NULL);
         sdev->file = NULL;
     }
+    storage_close_overlay(sdev);
     sdev->mounted = false;
 }
 
+static bool overlay_has_sector(struct sunpci_storage_dev *sdev, u64 lba)
+{
+    return sdev->bitmap[lba / 8] & (1 << (lba % 8));
+}
+
+/*
+ * Read sectors from an overlay: runs the guest wrote come from the
+ * overlay file, the rest from the template
+ */
+static int overlay_read_sectors(struct sunpci_storage_dev *sdev,
+                                u64 lba, u32 count, u8 *buffer)
+{
+    while (count) {
+        bool written = overlay_has_sector(sdev, lba);
+        struct file *file = written ? sdev->file : sdev->base;
+        u32 run = 1;
+        loff_t offset;
+        size_t len;
+        ssize_t ret;
+
+        while (run < count && overlay_has_sector(sdev, lba + run) == written)
+            run++;
+
+        offset = lba * SECTOR_SIZE_HD;
+        if (written)
+            offset += sdev->data_offset;
+        len = run * SECTOR_SIZE_HD;
+
+        ret = kernel_read(file, buffer, len, &offset);
+        if (ret < 0)
+            return ret;
+        if (ret != len)
+            return -EIO;
+
+        lba += run;
+        count -= run;
+        buffer += len;
+    }
+    return 0;
+}
+
+/*
+ * Write sectors to an overlay and mark them in its bitmap
+ */
+static int overlay_write_sectors(struct sunpci_storage_dev *sdev,
+                                 u64 lba, u32 count, const void *buffer)
+{
+    loff_t offset = sdev->data_offset + lba * SECTOR_SIZE_HD;
+    size_t len = count * SECTOR_SIZE_HD;
+    u64 first = lba / 8, last = (lba + count - 1) / 8;
+    u64 i;
+    ssize_t ret;
+
+    ret = kernel_write(sdev->file, buffer, len, &offset);
+    if (ret < 0)
+        return ret;
+    if (ret != len)
+        return -EIO;
+
+    /* Data first, so a crash in between loses the write, not the disk */
+    for (i = lba; i < lba + count; i++)
+        sdev->bitmap[i / 8] |= 1 << (i % 8);
+
+    offset = sdev->bitmap_offset + first;
+    len = last - first + 1;
+    ret = kernel_write(sdev->file, sdev->bitmap + first, len, &offset);
+    if (ret < 0)
+        return ret;
+    if (ret != len)
+        return -EIO;
+
+    return 0;
+}
+
 /*
  * Read sectors from disk image
  */
@@ -375,6 +622,9 @@ static int storage_read_sectors(struct sunpci_storage_dev *sdev,
     if (lba + count > sdev->total_sectors)
         return -EINVAL;
     
+    if (sdev->base)
+        return overlay_read_sectors(sdev, lba, count, buffer);
+    
     offset = lba * sdev->sector_size;
     len = count * sdev->sector_size;
     
@@ -406,6 +656,9 @@ static int storage_write_sectors(struct sunpci_storage_dev *sdev,
     if (lba + count > sdev->total_sectors)
         return -EINVAL;
     
+    if (sdev->base)
+        return count ? overlay_write_sectors(sdev, lba, count, buffer) : 0;
+    
     offset = lba * sdev->sector_size;
     len = count * sdev->sector_size;
     
@@ -657,6 +910,67 @@ static int scsi_mode_sense(struct sunpci_storage_dev *sdev,
  *
  * Returns 0 on success, negative error code on failure
  */
+/*
+ * Hand a guest CD-ROM command to userspace and wait for its result
+ *
+ * Userspace picks the command up with SCSI_PT_NEXT and answers with
+ * SCSI_PT_COMPLETE. If it does not answer in time (frontend gone or the
+ * drive hung) the guest gets NOT READY rather than blocking forever.
+ */
+static int scsi_pt_execute(struct sunpci_scsi_pt *pt,
+                           const struct sunpci_scsi_req *req,
+                           struct sunpci_scsi_rsp *rsp,
+                           void *data_buf, size_t data_len)
+{
+    size_t transfer_len;
+    bool answered;
+
+    memset(rsp, 0, sizeof(*rsp));
+
+    mutex_lock(&pt->cmd_lock);
+
+    mutex_lock(&pt->lock);
+    pt->tag++;
+    memcpy(pt->req.cdb, req->cdb, SUNPCI_SCSI_CDB_MAX);
+    pt->req.cdb_len = le32_to_cpu(req->cdb_len);
+    pt->req.data_direction = le32_to_cpu(req->data_direction);
+    pt->req.data_len = min_t(u32, le32_to_cpu(req->data_len), data_len);
+    memset(&pt->rsp, 0, sizeof(pt->rsp));
+    pt->data = data_buf;
+    pt->data_len = data_len;
+    pt->pending = true;
+    pt->active = false;
+    reinit_completion(&pt->done);
+    mutex_unlock(&pt->lock);
+
+    answered = wait_for_completion_timeout(&pt->done, SCSI_PT_TIMEOUT) > 0;
+
+    mutex_lock(&pt->lock);
+    answered = answered && pt->enabled;
+    if (answered) {
+        rsp->status = pt->rsp.status;
+        rsp->sense_len = min_t(u8, pt->rsp.sense_len, SCSI_SENSE_MAX_LEN);
+        memcpy(rsp->sense, pt->rsp.sense, SCSI_SENSE_MAX_LEN);
+        transfer_len = min_t(size_t, pt->rsp.data_len, data_len);
+        rsp->data_len = cpu_to_le32(transfer_len);
+    }
+    pt->pending = false;
+    pt->active = false;
+    pt->data = NULL;
+    mutex_unlock(&pt->lock);
+
+    mutex_unlock(&pt->cmd_lock);
+
+    if (!answered) {
+        pr_debug("sunpci: pass-through command 0x%02x not answered\n",
+                 req->cdb[0]);
+        rsp->status = SCSI_STATUS_CHECK_CONDITION;
+        build_sense(rsp->sense, SENSE_NOT_READY, ASC_LUN_NOT_READY, 0);
+        rsp->sense_len = SCSI_SENSE_MAX_LEN;
+    }
+    return 0;
+}
+
 int sunpci_storage_scsi_command(struct sunpci_device *dev,
                                 const struct sunpci_scsi_req *req,
                                 struct sunpci_scsi_rsp *rsp,
@@ -668,6 +982,10 @@ int sunpci_storage_scsi_command(struct sunpci_device *dev,
     u32 lba, count;
     int ret = 0;
     
+    if (READ_ONCE(dev->storage.cdrom_pt.enabled))
+        return scsi_pt_execute(&dev->storage.cdrom_pt, req, rsp,
+                               data_buf, data_len);
+    
     /* Initialize response */
     memset(rsp, 0, sizeof(*rsp));
     rsp->status = SCSI_STATUS_GOOD;
@@ -823,6 +1141,12 @@ int sunpci_storage_scsi_command(struct sunpci_device *dev,
         /* We don't actually prevent/allow anything, just succeed */
         break;
         
+    case SCSI_START_STOP_UNIT:
+        /* LoEj with Start clear: the guest pressed eject; the host decides */
+        if ((req->cdb[4] & 0x03) == 0x02 && sdev && sdev->mounted)
+            sunpci_event_post(dev, SUNPCI_EVENT_EJECT_REQUEST, 0xE0, 0, NULL);
+        break;
+        
     case SCSI_GET_CONFIGURATION:
     case SCSI_GET_EVENT_STATUS:
     case SCSI_READ_DISC_INFORMATION:
@@ -872,6 +1196,13 @@ int sunpci_storage_handle_request(struct sunpci_device *dev,
     drive = le32_to_cpu(req->drive);
     count = le32_to_cpu(req->count);
     
+    /* Asked by the BIOS before any drive is chosen */
+    if (le32_to_cpu(req->command) == STORAGE_CMD_GET_BOOT) {
+        rsp->status = cpu_to_le32(STORAGE_STATUS_OK);
+        rsp->count = cpu_to_le32(storage_boot_drive(dev));
+        return 0;
+    }
+    
     sdev = get_storage_dev(dev, drive);
     if (!sdev || !sdev->mounted) {
         rsp->status = cpu_to_le32(STORAGE_STATUS_NO_MEDIA);
@@ -937,6 +1268,13 @@ int sunpci_storage_handle_request(struct sunpci_device *dev,
         }
         break;
         
+    case STORAGE_CMD_EJECT:
+        /* INT 13h eject: left to the frontend, which owns the media */
+        sunpci_event_post(dev, SUNPCI_EVENT_EJECT_REQUEST, drive, 0, NULL);
+        rsp->status = cpu_to_le32(STORAGE_STATUS_OK);
+        rsp->count = 0;
+        break;
+        
     case STORAGE_CMD_RESET:
     case STORAGE_CMD_RECAL:
         /* No-op for image files */
@@ -1103,6 +1441,9 @@ int sunpci_storage_mount_cdrom(struct sunpci_device *dev, const char *path)
                             &msg, sizeof(msg), NULL);
     }
     
+    sunpci_event_post(dev, SUNPCI_EVENT_MEDIA_CHANGED, 0xE0,
+                      SUNPCI_EVENT_MEDIA_PRESENT, path);
+
     return 0;
 }
 
@@ -1129,6 +1470,8 @@ int sunpci_storage_eject_cdrom(struct sunpci_device *dev)
                             &msg, sizeof(msg), NULL);
     }
     
+    sunpci_event_post(dev, SUNPCI_EVENT_MEDIA_CHANGED, 0xE0, 0, NULL);
+
     return 0;
 }
 
@@ -1177,6 +1520,9 @@ int sunpci_storage_mount_floppy(struct sunpci_device *dev,
                             &msg, sizeof(msg), NULL);
     }
     
+    sunpci_event_post(dev, SUNPCI_EVENT_MEDIA_CHANGED, drive,
+                      SUNPCI_EVENT_MEDIA_PRESENT, path);
+
     return 0;
 }
 
@@ -1206,9 +1552,135 @@ int sunpci_storage_eject_floppy(struct sunpci_device *dev, u32 drive)
                             &msg, sizeof(msg), NULL);
     }
     
+    sunpci_event_post(dev, SUNPCI_EVENT_MEDIA_CHANGED, drive, 0, NULL);
+
     return 0;
 }
 
+/*
+ * Initialize storage state - called when the device is created
+ */
+void sunpci_storage_init(struct sunpci_device *dev)
+{
+    struct sunpci_scsi_pt *pt = &dev->storage.cdrom_pt;
+
+    mutex_init(&pt->cmd_lock);
+    mutex_init(&pt->lock);
+    init_completion(&pt->done);
+}
+
+/*
+ * Switch the CD-ROM between image emulation and userspace pass-through
+ */
+int sunpci_storage_set_passthrough(struct sunpci_device *dev, bool enable)
+{
+    struct sunpci_scsi_pt *pt = &dev->storage.cdrom_pt;
+
+    if (enable) {
+        /* The drive replaces any mounted image */
+        sunpci_storage_eject_cdrom(dev);
+    }
+
+    mutex_lock(&pt->lock);
+    WRITE_ONCE(pt->enabled, enable);
+    if (!enable && (pt->pending || pt->active)) {
+        /* Fail the command in flight; the waiter sees !enabled */
+        complete(&pt->done);
+    }
+    mutex_unlock(&pt->lock);
+
+    /* A (new) drive behaves like a disc change to the guest */
+    if (enable && dev->state == SUNPCI_STATE_RUNNING) {
+        struct {
+            __le32 drive;
+            __le32 flags;
+        } msg;
+
+        msg.drive = cpu_to_le32(0xE0);
+        msg.flags = cpu_to_le32(1);
+
+        sunpci_ipc_send_cmd(dev, SUNPCI_DISP_STORAGE, STORAGE_CMD_MOUNT,
+                            &msg, sizeof(msg), NULL);
+    }
+
+    return 0;
+}
+
+/*
+ * Fetch the guest command waiting for userspace, if any
+ */
+int sunpci_storage_pt_next(struct sunpci_device *dev,
+                           struct sunpci_scsi_command *cmd)
+{
+    struct sunpci_scsi_pt *pt = &dev->storage.cdrom_pt;
+    void __user *ubuf = u64_to_user_ptr(cmd->data_ptr);
+    int ret = 0;
+
+    mutex_lock(&pt->lock);
+    if (!pt->enabled) {
+        ret = -ENODEV;
+        goto out;
+    }
+    if (!pt->pending) {
+        ret = -EAGAIN;
+        goto out;
+    }
+
+    if (pt->req.data_direction == SCSI_DIR_WRITE && pt->req.data_len > 0) {
+        if (cmd->data_buf_len < pt->req.data_len) {
+            ret = -ENOSPC;
+            goto out;
+        }
+        if (copy_to_user(ubuf, pt->data, pt->req.data_len)) {
+            ret = -EFAULT;
+            goto out;
+        }
+    }
+
+    cmd->tag = pt->tag;
+    cmd->cdb = pt->req;
+    memset(&cmd->result, 0, sizeof(cmd->result));
+    pt->pending = false;
+    pt->active = true;
+out:
+    mutex_unlock(&pt->lock);
+    return ret;
+}
+
+/*
+ * Answer the command userspace fetched with sunpci_storage_pt_next()
+ */
+int sunpci_storage_pt_complete(struct sunpci_device *dev,
+                               const struct sunpci_scsi_command *cmd)
+{
+    struct sunpci_scsi_pt *pt = &dev->storage.cdrom_pt;
+    void __user *ubuf = u64_to_user_ptr(cmd->data_ptr);
+    u32 len;
+    int ret = 0;
+
+    mutex_lock(&pt->lock);
+    if (!pt->active || cmd->tag != pt->tag) {
+        /* Timed out (or never fetched) */
+        ret = -ENOENT;
+        goto out;
+    }
+
+    len = min_t(u32, cmd->result.data_len, pt->req.data_len);
+    if (pt->req.data_direction == SCSI_DIR_READ && len > 0 &&
+        copy_from_user(pt->data, ubuf, len)) {
+        ret = -EFAULT;
+        goto out;
+    }
+
+    pt->rsp = cmd->result;
+    pt->rsp.data_len = pt->req.data_direction == SCSI_DIR_READ ? len : 0;
+    pt->active = false;
+    complete(&pt->done);
+out:
+    mutex_unlock(&pt->lock);
+    return ret;
+}
+
 /*
  * Cleanup all storage devices - called on device removal
  */
@@ -1216,6 +1688,8 @@ void sunpci_storage_cleanup(struct sunpci_device *dev)
 {
     int i;
     
+    sunpci_storage_set_passthrough(dev, false);
+    
     /* Close hard disks */
     for (i = 0; i < 2; i++) {
         if (dev->storage.disks[i]) {
@@ -7,10 +7,14 @@
 
 #include <linux/types.h>
 #include <linux/cdev.h>
+#include <linux/completion.h>
 #include <linux/device.h>
 #include <linux/mutex.h>
 #include <linux/ktime.h>
 #include <linux/pci.h>
+#include <linux/poll.h>
+#include <linux/printk.h>
+#include <linux/spinlock.h>
 #include <linux/workqueue.h>
 #include <linux/wait.h>
 
@@ -20,13 +24,23 @@
 #define SUNPCI_DRIVER_NAME "sunpci"
 #define SUNPCI_MAX_DEVICES 4
 
+/* Events held for readers that fall behind */
+#define SUNPCI_EVENT_RING 32
+
+/* Log messages held for READ_LOG */
+#define SUNPCI_LOG_RING 128
+
 /* PCI Vendor/Device IDs for SunPCi card */
 #define SUNPCI_VENDOR_ID    0x108e  /* Sun Microsystems */
 #define SUNPCI_DEVICE_ID    0x5043  /* SunPCi ("PC" in ASCII) */
 
-/* Driver version */
+/*
+ * Driver version. Bump the minor version with any change to the uapi
+ * ioctls (new ones, or payload sizes, which are part of the request code),
+ * and MIN_DRIVER_VERSION/MAX_DRIVER_VERSION in common/src/driver.rs with it.
+ */
 #define SUNPCI_VERSION_MAJOR 0
-#define SUNPCI_VERSION_MINOR 1
+#define SUNPCI_VERSION_MINOR 2
 #define SUNPCI_VERSION_PATCH 0
 
 /* Forward declarations */
@@ -48,6 +62,34 @@ struct sunpci_audio_state;
 /* Forward declaration for FSD state */
 struct sunpci_fsd_state;
 
+/**
+ * struct sunpci_scsi_pt - CD-ROM SCSI pass-through to userspace
+ * @enabled: Guest CD-ROM commands go to userspace instead of an image
+ * @cmd_lock: Serializes guest commands (one in flight at a time)
+ * @lock: Protects the fields below
+ * @done: Completed by SCSI_PT_COMPLETE (or on disable)
+ * @tag: Tag of the current command
+ * @pending: Command waiting for userspace to fetch it
+ * @active: Command fetched, waiting for its completion
+ * @req: The command
+ * @rsp: Its result, filled in by userspace
+ * @data: Guest command's data buffer
+ * @data_len: Size of @data
+ */
+struct sunpci_scsi_pt {
+    bool enabled;
+    struct mutex cmd_lock;
+    struct mutex lock;
+    struct completion done;
+    u32 tag;
+    bool pending;
+    bool active;
+    struct sunpci_scsi_cdb req;
+    struct sunpci_scsi_result rsp;
+    void *data;
+    size_t data_len;
+};
+
 /**
  * struct sunpci_storage - Storage state
  * @disk_path: Paths to mounted disk images
@@ -57,6 +99,7 @@ struct sunpci_fsd_state;
  * @disks: Hard disk device contexts
  * @cdrom: CD-ROM device context
  * @floppies: Floppy device contexts
+ * @cdrom_pt: CD-ROM pass-through state
  */
 struct sunpci_storage {
     char disk_path[2][SUNPCI_MAX_PATH];
@@ -68,6 +111,8 @@ struct sunpci_storage {
     struct sunpci_storage_dev *disks[2];
     struct sunpci_storage_dev *cdrom;
     struct sunpci_storage_dev *floppies[2];
+
+    struct sunpci_scsi_pt cdrom_pt;
 };
 
 /**
@@ -86,12 +131,32 @@ struct sunpci_display_state {
  * struct sunpci_drive_map - Drive mapping entry
  * @letter: Drive letter (0 if unused)
  * @flags: Mapping flags
+ * @tz_offset: Minutes east of UTC of the guest's file times
  * @path: Host path
+ * @max_bytes: Most the guest may store under @path (0 = no limit)
+ * @used: Bytes stored under @path, as measured plus what the guest added
  */
 struct sunpci_drive_map {
     u8 letter;
     u8 flags;
+    s16 tz_offset;
     char path[SUNPCI_MAX_PATH];
+    u64 max_bytes;
+    u64 used;
+};
+
+/**
+ * struct sunpci_clip_xfer - Buffer for a chunked clipboard transfer
+ * @format: Clipboard format (SUNPCI_CLIPBOARD_*)
+ * @length: Total transfer length
+ * @filled: Bytes received so far (host -> guest only)
+ * @data: Transfer buffer (kvmalloc'd, NULL when idle)
+ */
+struct sunpci_clip_xfer {
+    u32 format;
+    u32 length;
+    u32 filled;
+    u8 *data;
 };
 
 /**
@@ -102,6 +167,7 @@ struct sunpci_drive_map {
  * @mutex: Device mutex
  * @state: Current session state
  * @start_time: Session start time
+ * @last_guest_activity: When the guest last rang a doorbell
  * @config: Session configuration
  * @storage: Storage state
  * @display: Display state
@@ -127,7 +193,10 @@ struct sunpci_device {
     /* Session state */
     enum sunpci_state state;
     ktime_t start_time;
+    ktime_t last_guest_activity;
     struct sunpci_session_config config;
+    struct file *owner;                  /* File driving the session, if any */
+    struct sunpci_session_owner owner_info;
     
     /* Subsystems */
     struct sunpci_storage storage;
@@ -140,6 +209,7 @@ struct sunpci_device {
     struct sunpci_video_state *video_state; /* Video/GDI state */
     struct sunpci_audio_state *audio_state; /* Audio state */
     struct sunpci_fsd_state *fsd_state;      /* Filesystem redirection */
+    u32 keyboard_leds;                  /* Guest LED state (SUNPCI_LED_*) */
     
     /* PCI device and resources */
     struct pci_dev *pdev;
@@ -162,6 +232,19 @@ struct sunpci_device {
     wait_queue_head_t rsp_wait;         /* Wait for IPC responses */
     wait_queue_head_t clipboard_wait;   /* Wait for clipboard changes */
     bool clipboard_changed;             /* Clipboard data updated */
+    struct sunpci_clip_xfer clip_tx;    /* Chunked host -> guest staging */
+    struct sunpci_clip_xfer clip_rx;    /* Chunked guest snapshot */
+    
+    /* Asynchronous events (see events.c) */
+    spinlock_t event_lock;
+    wait_queue_head_t event_wait;
+    struct sunpci_event events[SUNPCI_EVENT_RING];
+    u64 event_seq;                      /* Events posted so far */
+    
+    /* Recent log messages (see log.c) */
+    spinlock_t log_lock;
+    struct sunpci_log_entry log[SUNPCI_LOG_RING];
+    u32 log_seq;                        /* Messages logged so far */
     
     /* Interrupt handling */
     int irq;
@@ -185,6 +268,52 @@ void sunpci_pci_exit(void);
 
 /* ioctl.c */
 long sunpci_ioctl(struct file *file, unsigned int cmd, unsigned long arg);
+void sunpci_release_owner(struct sunpci_device *dev, struct file *file);
+
+/* events.c */
+void sunpci_events_init(struct sunpci_device *dev);
+void sunpci_events_open(struct sunpci_device *dev, struct file *file);
+void sunpci_event_post(struct sunpci_device *dev, u32 type, u32 drive,
+                       u32 flags, const char *path);
+int sunpci_ioctl_get_events(struct sunpci_device *dev, struct file *file,
+                            unsigned long arg);
+__poll_t sunpci_poll(struct file *file, poll_table *wait);
+
+/* log.c */
+void sunpci_log_init(struct sunpci_device *dev);
+__printf(3, 4)
+void sunpci_log(struct sunpci_device *dev, u32 level, const char *fmt, ...);
+int sunpci_ioctl_read_log(struct sunpci_device *dev, unsigned long arg);
+
+/*
+ * Log to the kernel log and the device's log ring. The pr_* call is made
+ * here at the call site so dynamic debug can turn sunpci_dbg on per file
+ * and line, and kernel log lines aren't cut to the ring's message size.
+ */
+#define sunpci_err(dev, fmt, ...) do { \
+    pr_err("sunpci%d: " fmt, (dev)->minor, ##__VA_ARGS__); \
+    sunpci_log(dev, SUNPCI_LOG_ERR, fmt, ##__VA_ARGS__); \
+} while (0)
+#define sunpci_warn(dev, fmt, ...) do { \
+    pr_warn("sunpci%d: " fmt, (dev)->minor, ##__VA_ARGS__); \
+    sunpci_log(dev, SUNPCI_LOG_WARN, fmt, ##__VA_ARGS__); \
+} while (0)
+#define sunpci_info(dev, fmt, ...) do { \
+    pr_info("sunpci%d: " fmt, (dev)->minor, ##__VA_ARGS__); \
+    sunpci_log(dev, SUNPCI_LOG_INFO, fmt, ##__VA_ARGS__); \
+} while (0)
+#define sunpci_dbg(dev, fmt, ...) do { \
+    pr_debug("sunpci%d: " fmt, (dev)->minor, ##__VA_ARGS__); \
+    sunpci_log(dev, SUNPCI_LOG_DEBUG, fmt, ##__VA_ARGS__); \
+} while (0)
+
+/*
+ * Debug messages that can come with every interrupt, input event or file
+ * operation: kernel log only, so they don't push real errors out of the
+ * ring.
+ */
+#define sunpci_trace(dev, fmt, ...) \
+    pr_debug("sunpci%d: " fmt, (dev)->minor, ##__VA_ARGS__)
 
 /* ipc.c */
 int sunpci_ipc_send_cmd(struct sunpci_device *dev,
@@ -214,12 +343,22 @@ int sunpci_ipc_send_response(struct sunpci_device *dev,
 /* mmap.c */
 int sunpci_mmap(struct file *file, struct vm_area_struct *vma);
 int sunpci_get_fb_info(struct sunpci_device *dev, struct sunpci_framebuffer *info);
+void sunpci_get_mmap_regions(struct sunpci_device *dev,
+                             struct sunpci_mmap_regions *regions);
 
 /* input.c */
 int sunpci_inject_key(struct sunpci_device *dev,
                       const struct sunpci_key_event *event);
 int sunpci_inject_mouse(struct sunpci_device *dev,
                         const struct sunpci_mouse_event *event);
+int sunpci_inject_mouse_abs(struct sunpci_device *dev,
+                            const struct sunpci_mouse_abs_event *event);
+int sunpci_inject_gameport(struct sunpci_device *dev,
+                           const struct sunpci_gameport_event *event);
+int sunpci_set_keyboard_leds(struct sunpci_device *dev, u32 leds);
+u32 sunpci_get_keyboard_leds(struct sunpci_device *dev);
+int sunpci_input_handle_message(struct sunpci_device *dev, u16 command,
+                                const void *payload, size_t len);
 
 /* clipboard.c */
 int sunpci_clip_set(struct sunpci_device *dev,
@@ -228,6 +367,11 @@ int sunpci_clip_get(struct sunpci_device *dev,
                     struct sunpci_clipboard *clip);
 void sunpci_clip_handle_notify(struct sunpci_device *dev,
                                const void *data, size_t len);
+int sunpci_clip_set_chunk(struct sunpci_device *dev,
+                          const struct sunpci_clipboard_chunk *chunk);
+int sunpci_clip_get_chunk(struct sunpci_device *dev,
+                          struct sunpci_clipboard_chunk *chunk);
+void sunpci_clip_cleanup(struct sunpci_device *dev);
 
 /* storage.c */
 struct sunpci_storage_req;
@@ -250,6 +394,12 @@ int sunpci_storage_scsi_command(struct sunpci_device *dev,
                                 const struct sunpci_scsi_req *req,
                                 struct sunpci_scsi_rsp *rsp,
                                 void *data_buf, size_t data_len);
+void sunpci_storage_init(struct sunpci_device *dev);
+int sunpci_storage_set_passthrough(struct sunpci_device *dev, bool enable);
+int sunpci_storage_pt_next(struct sunpci_device *dev,
+                           struct sunpci_scsi_command *cmd);
+int sunpci_storage_pt_complete(struct sunpci_device *dev,
+                               const struct sunpci_scsi_command *cmd);
 void sunpci_storage_cleanup(struct sunpci_device *dev);
 
 /* network.c */
@@ -265,6 +415,8 @@ int sunpci_net_handle_request(struct sunpci_device *dev,
                               struct sunpci_net_rsp *rsp,
                               void *data_buf, size_t data_len);
 void sunpci_net_notify_rx(struct sunpci_device *dev);
+int sunpci_net_user_send(struct sunpci_device *dev, const u8 *data, size_t len);
+int sunpci_net_user_recv(struct sunpci_device *dev, u8 *data, size_t *len);
 void sunpci_net_shutdown(struct sunpci_device *dev);
 
 /* vga.c */
@@ -276,7 +428,10 @@ int sunpci_vga_handle_message(struct sunpci_device *dev,
                               void *response, size_t *rsp_len);
 int sunpci_vga_get_info(struct sunpci_device *dev,
                         struct sunpci_display_info *info);
-int sunpci_vga_get_palette(struct sunpci_device *dev, u32 *palette, size_t count);
+int sunpci_vga_get_palette(struct sunpci_device *dev,
+                           struct sunpci_palette *palette);
+int sunpci_vga_get_text_cursor(struct sunpci_device *dev,
+                               struct sunpci_text_cursor *cursor);
 bool sunpci_vga_get_dirty(struct sunpci_device *dev,
                           u16 *x, u16 *y, u16 *w, u16 *h);
 void sunpci_vga_mark_dirty_region(struct sunpci_device *dev,
@@ -297,9 +452,14 @@ int sunpci_audio_handle_message(struct sunpci_device *dev,
                                 u16 command,
                                 const void *payload, size_t len);
 int sunpci_audio_read(struct sunpci_device *dev, void *buffer, size_t size);
+int sunpci_audio_write(struct sunpci_device *dev, const void *buffer, size_t size);
+int sunpci_midi_read(struct sunpci_device *dev, void *buffer, size_t size);
 int sunpci_audio_get_format(struct sunpci_device *dev, u32 *sample_rate, u32 *format);
 int sunpci_audio_set_volume(struct sunpci_device *dev, u8 left, u8 right);
+int sunpci_audio_set_muted(struct sunpci_device *dev, bool muted);
 int sunpci_audio_get_volume(struct sunpci_device *dev, u8 *left, u8 *right);
+int sunpci_audio_get_status(struct sunpci_device *dev,
+                            struct sunpci_audio_status *status);
 bool sunpci_audio_data_available(struct sunpci_device *dev);
 void sunpci_audio_get_stats(struct sunpci_device *dev, u64 *samples, u64 *underruns, u64 *buffers);
 
@@ -54,8 +54,10 @@ struct sunpci_vga_state {
     u8 bpp;
     u32 pitch;
     
-    /* Palette */
+    /* Palette, guarded by palette_lock */
     u32 palette[256];
+    u32 palette_generation;
+    spinlock_t palette_lock;
     
     /* Dirty tracking */
     bool dirty;
@@ -83,6 +85,7 @@ int sunpci_vga_init(struct sunpci_device *dev)
         return -ENOMEM;
     
     spin_lock_init(&vga->dirty_lock);
+    spin_lock_init(&vga->palette_lock);
     
     /* Default to 80x25 text mode */
     vga->mode = 0x03;
@@ -235,6 +238,7 @@ static int vga_handle_set_mode(struct sunpci_device *dev,
     
     /* Mark entire screen dirty */
     vga_mark_dirty(vga, 0, 0, vga->width, vga->height);
+    sunpci_event_post(dev, SUNPCI_EVENT_DISPLAY_CHANGED, 0, 0, NULL);
     
     pr_info("sunpci: VGA mode set: %dx%d %dbpp %s\n",
             vga->width, vga->height, vga->bpp,
@@ -251,8 +255,10 @@ static int vga_handle_set_palette(struct sunpci_device *dev,
 {
     struct sunpci_vga_state *vga = dev->vga_state;
     const u8 *data = payload;
+    unsigned long flags;
     size_t count, i;
     u8 start_index;
+    u32 generation;
     
     if (len < 1)
         return -EINVAL;
@@ -266,6 +272,7 @@ static int vga_handle_set_palette(struct sunpci_device *dev,
     if (start_index + count > 256)
         count = 256 - start_index;
     
+    spin_lock_irqsave(&vga->palette_lock, flags);
     for (i = 0; i < count; i++) {
         u8 r = data[i * 3 + 0];
         u8 g = data[i * 3 + 1];
@@ -274,9 +281,12 @@ static int vga_handle_set_palette(struct sunpci_device *dev,
         vga->palette[start_index + i] = 
             ((r << 2) << 16) | ((g << 2) << 8) | (b << 2);
     }
+    generation = ++vga->palette_generation;
+    spin_unlock_irqrestore(&vga->palette_lock, flags);
     
     /* Palette change means screen needs redraw */
     vga_mark_dirty(vga, 0, 0, vga->width, vga->height);
+    sunpci_event_post(dev, SUNPCI_EVENT_PALETTE_CHANGED, 0, generation, NULL);
     
     return 0;
 }
@@ -454,17 +464,42 @@ int sunpci_vga_get_info(struct sunpci_device *dev,
 /*
  * Get palette for userspace
  */
-int sunpci_vga_get_palette(struct sunpci_device *dev, u32 *palette, size_t count)
+int sunpci_vga_get_palette(struct sunpci_device *dev,
+                           struct sunpci_palette *palette)
 {
     struct sunpci_vga_state *vga = dev->vga_state;
+    unsigned long flags;
     
     if (!vga)
         return -ENODEV;
     
-    if (count > 256)
-        count = 256;
+    spin_lock_irqsave(&vga->palette_lock, flags);
+    palette->generation = vga->palette_generation;
+    palette->count = SUNPCI_PALETTE_SIZE;
+    memcpy(palette->entries, vga->palette, sizeof(palette->entries));
+    spin_unlock_irqrestore(&vga->palette_lock, flags);
+    return 0;
+}
+
+/*
+ * Get text mode cursor for userspace
+ */
+int sunpci_vga_get_text_cursor(struct sunpci_device *dev,
+                               struct sunpci_text_cursor *cursor)
+{
+    struct sunpci_vga_state *vga = dev->vga_state;
+    u16 pos;
+    
+    if (!vga)
+        return -ENODEV;
     
-    memcpy(palette, vga->palette, count * sizeof(u32));
+    pos = READ_ONCE(vga->cursor_pos);
+    memset(cursor, 0, sizeof(*cursor));
+    cursor->col = pos % vga->text_cols;
+    cursor->row = pos / vga->text_cols;
+    cursor->start = vga->cursor_start;
+    cursor->end = vga->cursor_end;
+    cursor->visible = vga->cursor_visible && !vga->graphics_mode;
     return 0;
 }
 
@@ -1,9 +1,56 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
 use cxx_qt_build::{CxxQtBuilder, QmlModule};
+use qt_build_utils::QtBuild;
+
+/// Put the CRT shader in a resource file for the display's ShaderEffect
+///
+/// Qt 6 only loads shaders precompiled by qsb; Qt 5 takes GLSL source.
+/// The shader's URL reaches the code as RISING_SUN_CRT_SHADER.
+fn build_shaders(out_dir: &Path) -> PathBuf {
+    let qt = QtBuild::new(vec![]).expect("failed to find Qt");
+    let shader = if qt.version().major >= 6 {
+        let qsb = ["QT_HOST_BINS", "QT_HOST_LIBEXECS"]
+            .iter()
+            .map(|var| Path::new(&qt.qmake_query(var)).join("qsb"))
+            .find(|path| path.exists())
+            .unwrap_or_else(|| PathBuf::from("qsb"));
+        let status = Command::new(&qsb)
+            .args(["--glsl", "100 es,120,150", "--hlsl", "50", "--msl", "12", "-o"])
+            .arg(out_dir.join("crt.frag.qsb"))
+            .arg("shaders/crt.frag")
+            .status()
+            .unwrap_or_else(|e| panic!("failed to run {}: {e}", qsb.display()));
+        assert!(status.success(), "qsb failed to compile shaders/crt.frag");
+        "crt.frag.qsb"
+    } else {
+        fs::copy("shaders/crt_gl2.frag", out_dir.join("crt_gl2.frag"))
+            .expect("failed to copy shaders/crt_gl2.frag");
+        "crt_gl2.frag"
+    };
+
+    let qrc = out_dir.join("shaders.qrc");
+    fs::write(
+        &qrc,
+        format!("<RCC>\n  <qresource prefix=\"/shaders\">\n    <file>{shader}</file>\n  </qresource>\n</RCC>\n"),
+    )
+    .expect("failed to write shaders.qrc");
+    println!("cargo::rustc-env=RISING_SUN_CRT_SHADER=qrc:/shaders/{shader}");
+    qrc
+}
 
 fn main() {
     // Rebuild if Qt version preference changes
     println!("cargo::rerun-if-env-changed=QT_VERSION_MAJOR");
-    
+    println!("cargo::rerun-if-changed=cpp");
+    println!("cargo::rerun-if-changed=shaders");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR not set"));
+    let shaders_qrc = build_shaders(&out_dir);
+
     CxxQtBuilder::new()
         .qml_module(QmlModule {
             uri: "com.risingsun",
@@ -13,12 +60,25 @@ fn main() {
                 "src/ui/settings_controller.rs",
                 "src/ui/disk_manager.rs",
                 "src/ui/drive_mapping_controller.rs",
+                "src/ui/drive_mapping_model.rs",
                 "src/ui/session_controller.rs",
                 "src/ui/display_view.rs",
                 "src/ui/network_controller.rs",
                 "src/ui/input_controller.rs",
                 "src/ui/audio_controller.rs",
+                "src/ui/midi_controller.rs",
                 "src/ui/clipboard_controller.rs",
+                "src/ui/gameport_controller.rs",
+                "src/ui/stats_controller.rs",
+                "src/ui/hotkey_controller.rs",
+                "src/ui/macro_controller.rs",
+                "src/ui/event_controller.rs",
+                "src/ui/history_controller.rs",
+                "src/ui/action_controller.rs",
+                "src/ui/log_controller.rs",
+                "src/ui/removable_media_controller.rs",
+                "src/ui/recent_files_model.rs",
+                "src/ui/framebuffer_item.rs",
             ],
             qml_files: &[
                 "qml/main.qml",
@@ -33,8 +93,23 @@ fn main() {
                 "qml/dialogs/NetworkSettingsDialog.qml",
                 "qml/dialogs/MountIsoDialog.qml",
                 "qml/dialogs/MountFloppyDialog.qml",
+                "qml/dialogs/MissingMediaDialog.qml",
+                "qml/dialogs/RecentFilesList.qml",
+                "qml/dialogs/HistoryDialog.qml",
+                "qml/dialogs/CommandPalette.qml",
+                "qml/dialogs/LogConsoleDialog.qml",
+                "qml/dialogs/LazyDialog.qml",
+                "qml/dialogs/BiosDialog.qml",
+                "qml/dialogs/DriverDiagnosticsDialog.qml",
             ],
             ..Default::default()
         })
+        // The display item's scene graph node
+        .qt_module("Quick")
+        .qrc(&shaders_qrc)
+        .cc_builder(|cc| {
+            cc.include("cpp");
+            cc.file("cpp/framebuffer_node.cpp");
+        })
         .build();
 }
@@ -0,0 +1,202 @@
+#include "framebuffer_node.h"
+
+#include <QtGui/QImage>
+#include <QtGui/QOpenGLContext>
+#include <QtGui/QOpenGLFunctions>
+#include <QtQuick/QQuickWindow>
+#include <QtQuick/QSGRendererInterface>
+#include <QtQuick/QSGSimpleTextureNode>
+#include <QtQuick/QSGTexture>
+
+#include <memory>
+
+#if QT_VERSION >= QT_VERSION_CHECK(6, 0, 0)
+#include <QtQuick/qsgtexture_platform.h>
+#endif
+
+#ifndef GL_UNPACK_ROW_LENGTH
+#define GL_UNPACK_ROW_LENGTH 0x0CF2
+#endif
+#ifndef GL_BGR
+#define GL_BGR 0x80E0
+#endif
+#ifndef GL_BGRA
+#define GL_BGRA 0x80E1
+#endif
+
+namespace rising_sun {
+namespace {
+
+int bytesPerPixel(std::int32_t layout)
+{
+    switch (layout) {
+    case FramebufferRgb565:
+        return 2;
+    case FramebufferBgr888:
+        return 3;
+    default:
+        return 4;
+    }
+}
+
+QImage::Format imageFormat(std::int32_t layout)
+{
+    switch (layout) {
+    case FramebufferRgb565:
+        return QImage::Format_RGB16;
+    case FramebufferBgr888:
+        return QImage::Format_BGR888;
+    case FramebufferXrgb8888:
+        return QImage::Format_RGB32;
+    default:
+        return QImage::Format_RGBA8888;
+    }
+}
+
+// GL format and type reading a layout as it is, if the context can
+bool glFormat(const QOpenGLContext &context, std::int32_t layout, GLenum &format, GLenum &type)
+{
+    switch (layout) {
+    case FramebufferRgba8888:
+        format = GL_RGBA;
+        type = GL_UNSIGNED_BYTE;
+        return true;
+    case FramebufferRgb565:
+        format = GL_RGB;
+        type = GL_UNSIGNED_SHORT_5_6_5;
+        return true;
+    case FramebufferBgr888:
+        // OpenGL ES has no BGR formats
+        format = GL_BGR;
+        type = GL_UNSIGNED_BYTE;
+        return !context.isOpenGLES();
+    case FramebufferXrgb8888:
+        format = GL_BGRA;
+        type = GL_UNSIGNED_BYTE;
+        return !context.isOpenGLES();
+    default:
+        return false;
+    }
+}
+
+QSGTexture *wrapTexture(QQuickWindow *window, GLuint id, const QSize &size)
+{
+#if QT_VERSION >= QT_VERSION_CHECK(6, 0, 0)
+    return QNativeInterface::QSGOpenGLTexture::fromNative(id, window, size);
+#else
+    return window->createTextureFromId(id, size);
+#endif
+}
+
+// Texture node that frames are uploaded into
+class FramebufferNode : public QSGSimpleTextureNode
+{
+public:
+    ~FramebufferNode() override
+    {
+        setTexture(nullptr);
+        m_texture.reset();
+        if (m_textureId != 0) {
+            if (QOpenGLContext *context = QOpenGLContext::currentContext())
+                context->functions()->glDeleteTextures(1, &m_textureId);
+        }
+    }
+
+    void upload(QQuickWindow *window, const std::uint8_t *pixels, int width, int height,
+                int stride, std::int32_t layout)
+    {
+        if (!uploadDirect(window, pixels, width, height, stride, layout))
+            uploadImage(window, pixels, width, height, stride, layout);
+    }
+
+private:
+    // Copy the frame into our own GL texture, skipping any CPU conversion
+    bool uploadDirect(QQuickWindow *window, const std::uint8_t *pixels, int width, int height,
+                      int stride, std::int32_t layout)
+    {
+        if (window->rendererInterface()->graphicsApi() != QSGRendererInterface::OpenGL)
+            return false;
+        QOpenGLContext *context = QOpenGLContext::currentContext();
+        GLenum format;
+        GLenum type;
+        if (!context || !glFormat(*context, layout, format, type))
+            return false;
+        // Rows are addressed in whole pixels; OpenGL ES 2 can't skip padding at all
+        const int bpp = bytesPerPixel(layout);
+        const bool padded = stride != width * bpp;
+        if (stride % bpp != 0 || (padded && context->isOpenGLES() && context->format().majorVersion() < 3))
+            return false;
+
+        QOpenGLFunctions *gl = context->functions();
+        const QSize size(width, height);
+        if (m_textureId == 0)
+            gl->glGenTextures(1, &m_textureId);
+        gl->glBindTexture(GL_TEXTURE_2D, m_textureId);
+        if (m_size != size || m_format != format) {
+            // OpenGL ES wants the internal format to match the data
+            const GLint internal = context->isOpenGLES() ? GLint(format) : GL_RGBA;
+            gl->glTexImage2D(GL_TEXTURE_2D, 0, internal, width, height, 0, format, type, nullptr);
+            std::unique_ptr<QSGTexture> texture(wrapTexture(window, m_textureId, size));
+            setTexture(texture.get());
+            m_texture = std::move(texture);
+            m_size = size;
+            m_format = format;
+        }
+
+        gl->glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+        if (padded)
+            gl->glPixelStorei(GL_UNPACK_ROW_LENGTH, stride / bpp);
+        gl->glTexSubImage2D(GL_TEXTURE_2D, 0, 0, 0, width, height, format, type, pixels);
+        if (padded)
+            gl->glPixelStorei(GL_UNPACK_ROW_LENGTH, 0);
+        gl->glPixelStorei(GL_UNPACK_ALIGNMENT, 4);
+        gl->glBindTexture(GL_TEXTURE_2D, 0);
+        markDirty(DirtyMaterial);
+        return true;
+    }
+
+    // Hand the frame to the scene graph as an image
+    void uploadImage(QQuickWindow *window, const std::uint8_t *pixels, int width, int height,
+                     int stride, std::int32_t layout)
+    {
+        // The texture uploads later, after the frame is released, so it
+        // needs its own copy
+        const QImage frame(pixels, width, height, stride, imageFormat(layout));
+        std::unique_ptr<QSGTexture> texture(window->createTextureFromImage(frame.copy()));
+        setTexture(texture.get());
+        m_texture = std::move(texture);
+        m_size = QSize(width, height);
+        m_format = 0;
+    }
+
+    std::unique_ptr<QSGTexture> m_texture;
+    GLuint m_textureId = 0;
+    QSize m_size;
+    GLenum m_format = 0;
+};
+
+} // namespace
+
+namespace detail {
+
+QSGNode *updateFramebufferNode(QQuickItem &item, QSGNode *old, const std::uint8_t *pixels,
+                               std::int32_t width, std::int32_t height, std::int32_t stride,
+                               std::int32_t layout)
+{
+    QQuickWindow *window = item.window();
+    if (!pixels || !window || width <= 0 || height <= 0) {
+        delete old;
+        return nullptr;
+    }
+
+    auto *node = static_cast<FramebufferNode *>(old);
+    if (!node)
+        node = new FramebufferNode;
+    node->upload(window, pixels, width, height, stride, layout);
+    node->setRect(item.boundingRect());
+    node->setFiltering(item.smooth() ? QSGTexture::Linear : QSGTexture::Nearest);
+    return node;
+}
+
+} // namespace detail
+} // namespace rising_sun
@@ -0,0 +1,53 @@
+// Scene graph node showing the SunPCi framebuffer.
+//
+// FramebufferItem (src/ui/framebuffer_item.rs) hands each frame to
+// updateFramebufferNode from its updatePaintNode override. With an OpenGL
+// scene graph the pixels are uploaded straight into a texture; other
+// backends fall back to a QImage.
+
+#pragma once
+
+#include <QtQuick/QQuickItem>
+#include <QtQuick/QSGNode>
+
+#include <cstdint>
+
+namespace rising_sun {
+
+using UpdatePaintNodeData = QQuickItem::UpdatePaintNodeData;
+
+// Pixel layout of a frame; must match FrameLayout in framebuffer_provider.rs
+enum FramebufferLayout : std::int32_t {
+    FramebufferRgba8888 = 0,
+    FramebufferRgb565 = 1,
+    FramebufferBgr888 = 2,
+    FramebufferXrgb8888 = 3,
+};
+
+namespace detail {
+
+QSGNode *updateFramebufferNode(QQuickItem &item, QSGNode *old, const std::uint8_t *pixels,
+                               std::int32_t width, std::int32_t height, std::int32_t stride,
+                               std::int32_t layout);
+
+} // namespace detail
+
+// Mark the item as drawing its own content
+//
+// Templates so the generated QObject subclass binds without a cast.
+template <typename T>
+void initFramebufferItem(T &item)
+{
+    item.setFlag(QQuickItem::ItemHasContents, true);
+}
+
+// Update (or create) the item's node with a frame; null pixels drop the node
+template <typename T>
+QSGNode *updateFramebufferNode(T &item, QSGNode *old, const std::uint8_t *pixels,
+                               std::int32_t width, std::int32_t height, std::int32_t stride,
+                               std::int32_t layout)
+{
+    return detail::updateFramebufferNode(item, old, pixels, width, height, stride, layout);
+}
+
+} // namespace rising_sun
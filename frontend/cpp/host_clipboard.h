@@ -0,0 +1,67 @@
+// Host clipboard access for ClipboardController.
+//
+// QML has no clipboard API, so the controller (src/ui/clipboard_controller.rs)
+// is told about host clipboard changes by watchHostClipboard and puts guest
+// clipboard content on the host with the setHostClipboard* functions.
+// Formatted text travels as both HTML and RTF, since host applications
+// offer and accept one or the other.
+
+#pragma once
+
+#include <QtCore/QMimeData>
+#include <QtCore/QObject>
+#include <QtGui/QClipboard>
+#include <QtGui/QGuiApplication>
+#include <QtGui/QImage>
+
+namespace rising_sun {
+
+// MIME type for RTF on the host clipboard
+inline const QString &rtfMimeType()
+{
+    static const QString type = QStringLiteral("text/rtf");
+    return type;
+}
+
+// Pass each host clipboard change to the controller's on_host_* handlers
+//
+// A template so the generated QObject subclass binds without a cast.
+template <typename T>
+void watchHostClipboard(T &controller)
+{
+    QClipboard *clipboard = QGuiApplication::clipboard();
+    QObject::connect(clipboard, &QClipboard::dataChanged, &controller, [&controller, clipboard]() {
+        const QMimeData *mime = clipboard->mimeData();
+        if (!mime)
+            return;
+        if (mime->hasImage())
+            controller.on_host_image_changed(qvariant_cast<QImage>(mime->imageData()));
+        if (mime->hasHtml() || mime->hasFormat(rtfMimeType()))
+            controller.on_host_rich_text_changed(
+                mime->html(), QString::fromLatin1(mime->data(rtfMimeType())));
+        if (mime->hasText())
+            controller.on_host_clipboard_changed(mime->text());
+    });
+}
+
+inline void setHostClipboardText(const QString &text)
+{
+    QGuiApplication::clipboard()->setText(text);
+}
+
+inline void setHostClipboardImage(const QImage &image)
+{
+    QGuiApplication::clipboard()->setImage(image);
+}
+
+inline void setHostClipboardRichText(const QString &html, const QString &rtf, const QString &text)
+{
+    auto *mime = new QMimeData;
+    mime->setHtml(html);
+    mime->setData(rtfMimeType(), rtf.toLatin1());
+    if (!text.isEmpty())
+        mime->setText(text);
+    QGuiApplication::clipboard()->setMimeData(mime);
+}
+
+} // namespace rising_sun
@@ -0,0 +1,172 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+import QtQuick.Dialogs 1.1 as Dialogs
+
+// Dialog for choosing the BIOS image a session loads instead of the card's
+// own. The image is checked when chosen and again at every start.
+Dialog {
+    id: biosDialog
+    title: "BIOS Image"
+    modal: true
+    standardButtons: Dialog.Apply | Dialog.Cancel
+    width: 460
+    height: Math.min(380, Screen.height - 100)
+
+    // Reference to config manager
+    required property var config
+
+    // What is wrong with the chosen image, empty if nothing
+    property string problem: ""
+    property string status: "default"
+
+    function refresh() {
+        biosPathField.text = config.get_bios_path()
+        problem = config.check_bios()
+        status = config.get_bios_status()
+        versionLabel.text = config.get_bios_version()
+    }
+
+    function choose(path) {
+        problem = config.choose_bios(path)
+        if (problem === "") {
+            biosPathField.text = path
+            status = config.get_bios_status()
+            versionLabel.text = config.get_bios_version()
+        }
+    }
+
+    function loadSettings() {
+        verifyCheck.checked = config.get_bios_verify()
+        noteField.text = ""
+        refresh()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: biosDialog.config
+        function onConfig_changed() {
+            if (biosDialog.visible)
+                biosDialog.loadSettings()
+        }
+    }
+
+    onApplied: {
+        config.set_bios_verify_value(verifyCheck.checked)
+        config.save()
+        close()
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        spacing: 12
+
+        Text {
+            Layout.fillWidth: true
+            text: "The card runs the BIOS in its flash unless an image is chosen here.\n" +
+                  "Changes take effect at the next start."
+            font.pixelSize: 12
+            color: palette.text
+            wrapMode: Text.WordWrap
+        }
+
+        GroupBox {
+            title: "Image"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                RowLayout {
+                    Layout.fillWidth: true
+                    TextField {
+                        id: biosPathField
+                        Layout.fillWidth: true
+                        readOnly: true
+                        placeholderText: "Card's own BIOS"
+                    }
+                    Button {
+                        text: "Browse..."
+                        onClicked: biosFileDialog.open()
+                    }
+                }
+
+                Button {
+                    text: "Use the Card's Own BIOS"
+                    enabled: biosPathField.text !== ""
+                    onClicked: biosDialog.choose("")
+                }
+
+                GridLayout {
+                    columns: 2
+                    visible: biosPathField.text !== "" && problem === ""
+
+                    Label { text: "BIOS date:" }
+                    Label {
+                        id: versionLabel
+                        text: ""
+                    }
+
+                    Label { text: "Status:" }
+                    Label {
+                        text: status === "known_good" ? "Known good" : "Not in the known-good list"
+                    }
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    visible: problem !== ""
+                    text: problem
+                    color: "red"
+                    wrapMode: Text.WordWrap
+                }
+            }
+        }
+
+        GroupBox {
+            title: "Checks"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                CheckBox {
+                    id: verifyCheck
+                    text: "Refuse the image if it changes after it is chosen"
+                }
+
+                RowLayout {
+                    Layout.fillWidth: true
+                    visible: status === "unknown"
+                    TextField {
+                        id: noteField
+                        Layout.fillWidth: true
+                        placeholderText: "Note (e.g. where the image came from)"
+                    }
+                    Button {
+                        text: "Trust This Image"
+                        onClicked: {
+                            if (config.mark_bios_known_good(noteField.text))
+                                status = config.get_bios_status()
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Dialogs.FileDialog {
+        id: biosFileDialog
+        title: "Select BIOS Image"
+        selectExisting: true
+        nameFilters: ["BIOS Images (*.bin *.BIN *.rom *.ROM)", "All Files (*)"]
+        folder: shortcuts.home
+
+        onAccepted: biosDialog.choose(fileUrl.toString().replace("file://", ""))
+    }
+}
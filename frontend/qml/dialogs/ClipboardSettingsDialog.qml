@@ -18,9 +18,22 @@ Dialog {
 
     signal settingsApplied(bool enabled, string direction)
 
-    // Load current values when dialog opens
-    onOpened: {
+    // Load current values, when the dialog opens or the file is edited
+    function loadSettings() {
         enableClipboardCheck.checked = config.get_clipboard_enabled()
+        bitmapFormatCheck.checked = config.get_clipboard_share_images()
+        rtfFormatCheck.checked = config.get_clipboard_share_rich_text()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: clipboardSettingsDialog.config
+        function onConfig_changed() {
+            if (clipboardSettingsDialog.visible)
+                clipboardSettingsDialog.loadSettings()
+        }
     }
 
     // Get current direction as string
@@ -34,6 +47,8 @@ Dialog {
     // Apply settings
     function applySettings() {
         config.set_clipboard_enabled_value(enableClipboardCheck.checked)
+        config.set_clipboard_share_images_value(bitmapFormatCheck.checked)
+        config.set_clipboard_share_rich_text_value(rtfFormatCheck.checked)
         config.save()
         settingsApplied(enableClipboardCheck.checked, getDirection())
     }
@@ -131,21 +146,16 @@ Dialog {
                         checked: true
                     }
 
+                    CheckBox {
+                        id: rtfFormatCheck
+                        text: "Rich text (CF_RTF)"
+                        checked: true
+                    }
+
                     CheckBox {
                         id: bitmapFormatCheck
-                        text: "Bitmap images (CF_BITMAP)"
-                        checked: false
-                        enabled: false  // Not yet implemented
-                        
-                        Text {
-                            anchors.left: parent.right
-                            anchors.leftMargin: 8
-                            anchors.verticalCenter: parent.verticalCenter
-                            text: "(not yet supported)"
-                            font.pixelSize: 10
-                            color: palette.text
-                            opacity: 0.5
-                        }
+                        text: "Bitmap images (CF_DIB)"
+                        checked: true
                     }
                 }
             }
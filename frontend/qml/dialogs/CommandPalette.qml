@@ -0,0 +1,110 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+
+// Searchable list of every registered action (Ctrl+K); Enter runs the
+// highlighted one
+Popup {
+    id: commandPalette
+    width: 480
+    height: Math.min(400, contentColumn.implicitHeight + 24)
+    modal: true
+    focus: true
+    padding: 12
+
+    // Reference to the action controller
+    required property var actions
+
+    ListModel {
+        id: actionsModel
+    }
+
+    function refresh() {
+        actionsModel.clear()
+        let found = JSON.parse(actions.search(queryField.text))
+        for (let i = 0; i < found.length; i++) {
+            actionsModel.append(found[i])
+        }
+        actionList.currentIndex = 0
+    }
+
+    function run(index) {
+        if (index < 0 || index >= actionsModel.count)
+            return
+        let action = actionsModel.get(index)
+        if (!action.enabled)
+            return
+        commandPalette.close()
+        actions.trigger(action.name)
+    }
+
+    onOpened: {
+        queryField.text = ""
+        refresh()
+        queryField.forceActiveFocus()
+    }
+
+    ColumnLayout {
+        id: contentColumn
+        anchors.fill: parent
+        spacing: 8
+
+        TextField {
+            id: queryField
+            Layout.fillWidth: true
+            placeholderText: "Type a command, e.g. mount iso"
+            onTextChanged: commandPalette.refresh()
+            onAccepted: commandPalette.run(actionList.currentIndex)
+            Keys.onUpPressed: actionList.decrementCurrentIndex()
+            Keys.onDownPressed: actionList.incrementCurrentIndex()
+            Keys.onEscapePressed: commandPalette.close()
+        }
+
+        ListView {
+            id: actionList
+            Layout.fillWidth: true
+            Layout.preferredHeight: Math.min(contentHeight, 320)
+            clip: true
+            model: actionsModel
+            highlightMoveDuration: 0
+
+            delegate: ItemDelegate {
+                width: ListView.view.width
+                highlighted: ListView.isCurrentItem
+                enabled: model.enabled
+                onClicked: commandPalette.run(index)
+
+                contentItem: RowLayout {
+                    spacing: 8
+
+                    Text {
+                        text: model.category
+                        font.pixelSize: 11
+                        color: palette.placeholderText
+                        Layout.preferredWidth: 64
+                    }
+                    Text {
+                        text: model.title
+                        font.pixelSize: 13
+                        color: palette.text
+                        opacity: model.enabled ? 1.0 : 0.5
+                        Layout.fillWidth: true
+                        elide: Text.ElideRight
+                    }
+                    Text {
+                        text: model.shortcut
+                        font.pixelSize: 11
+                        color: palette.placeholderText
+                    }
+                }
+            }
+        }
+
+        Text {
+            visible: actionsModel.count === 0
+            text: "No matching commands"
+            font.pixelSize: 12
+            color: palette.text
+        }
+    }
+}
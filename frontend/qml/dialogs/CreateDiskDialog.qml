@@ -16,7 +16,7 @@ Dialog {
     
     property string selectedPath: ""
     
-    signal diskCreated(string path, int sizeMb, int revision)
+    signal diskCreated(string path, int sizeMb, int revision, string allocation)
 
     ScrollView {
         anchors.fill: parent
@@ -165,6 +165,40 @@ Dialog {
             }
         }
 
+        // Host space allocation
+        GroupBox {
+            title: "Allocation"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                ComboBox {
+                    id: allocationCombo
+                    Layout.fillWidth: true
+                    model: [
+                        { text: "Sparse (grows as it is used)", value: "sparse" },
+                        { text: "Preallocated (reserve the full size)", value: "preallocate" },
+                        { text: "Zero-filled (write the full size)", value: "zero-fill" }
+                    ]
+                    textRole: "text"
+                    valueRole: "value"
+                    currentIndex: 0
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    text: "Preallocate on filesystems where sparse images fragment badly. " +
+                          "Zero-filling works everywhere but takes longer."
+                    font.pixelSize: 11
+                    color: palette.text
+                    opacity: 0.6
+                    wrapMode: Text.WordWrap
+                }
+            }
+        }
+
         // Options
         GroupBox {
             title: "Options"
@@ -205,7 +239,8 @@ Dialog {
 
     onAccepted: {
         if (selectedPath !== "") {
-            diskCreated(selectedPath, diskSizeSpinBox.value, revisionCombo.currentValue)
+            diskCreated(selectedPath, diskSizeSpinBox.value, revisionCombo.currentValue,
+                        allocationCombo.currentValue)
         }
     }
 }
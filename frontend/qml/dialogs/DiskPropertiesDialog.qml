@@ -22,6 +22,31 @@ Dialog {
     property int sectorsPerTrack: 0
     property bool isBootable: false
 
+    // Reference to disk manager
+    required property var disks
+
+    // Task of a resize in progress, -1 if none
+    property int resizeTask: -1
+    // Task of a compaction in progress, -1 if none
+    property int compactTask: -1
+    property int compactPercent: 0
+    property string compactResult: ""
+
+    // Last check_disk report, null before a check
+    property var checkReport: null
+
+    function runCheck(repair) {
+        checkReport = JSON.parse(disks.check_disk(diskPath, repair))
+    }
+
+    // Last detect_guest_os result, null before a detection
+    property var guestOs: null
+
+    onDiskPathChanged: {
+        checkReport = null
+        guestOs = null
+    }
+
     ScrollView {
         anchors.fill: parent
         contentWidth: availableWidth
@@ -102,6 +127,247 @@ Dialog {
             }
         }
 
+        // Resize
+        GroupBox {
+            title: "Resize"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                RowLayout {
+                    spacing: 8
+
+                    Label { text: "New size:" }
+
+                    SpinBox {
+                        id: newSizeSpin
+                        from: diskPropertiesDialog.diskSizeMb + 1
+                        to: 8064
+                        stepSize: 64
+                        editable: true
+                        value: diskPropertiesDialog.diskSizeMb * 2
+                    }
+
+                    Label { text: "MB" }
+                }
+
+                CheckBox {
+                    id: growFilesystemCheck
+                    text: "Grow the FAT partition to fill the disk"
+                    checked: true
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    text: growFilesystemCheck.checked
+                          ? "C: gets the new space. Keep a copy of the image; an interrupted resize can damage it."
+                          : "The new space is left unpartitioned for FDISK in the guest."
+                    font.pixelSize: 11
+                    color: palette.text
+                    opacity: 0.6
+                    wrapMode: Text.WordWrap
+                }
+
+                Button {
+                    text: diskPropertiesDialog.resizeTask >= 0 ? "Resizing..." : "Resize"
+                    enabled: diskPropertiesDialog.diskPath !== "" && diskPropertiesDialog.resizeTask < 0
+                    onClicked: diskPropertiesDialog.resizeTask = disks.resize_disk_async(
+                        diskPropertiesDialog.diskPath, newSizeSpin.value, growFilesystemCheck.checked)
+                }
+            }
+        }
+
+        // Integrity
+        GroupBox {
+            title: "Integrity"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                RowLayout {
+                    spacing: 8
+
+                    Button {
+                        text: "Check"
+                        enabled: diskPropertiesDialog.diskPath !== ""
+                        onClicked: diskPropertiesDialog.runCheck(false)
+                    }
+
+                    Button {
+                        text: "Repair"
+                        enabled: checkReport !== null && checkReport.issues !== undefined
+                                 && checkReport.issues.some(issue => issue.repairable && !issue.repaired)
+                        onClicked: diskPropertiesDialog.runCheck(true)
+                    }
+                }
+
+                Label {
+                    visible: checkReport !== null
+                    text: checkReport === null ? ""
+                          : checkReport.error !== undefined ? checkReport.error
+                          : checkReport.clean ? (checkReport.repair ? "Repaired; no problems left" : "No problems found")
+                          : checkReport.issues.filter(issue => !issue.repaired).length + " problems found"
+                    font.bold: true
+                }
+
+                Repeater {
+                    model: checkReport !== null && checkReport.issues !== undefined ? checkReport.issues : []
+                    delegate: Text {
+                        Layout.fillWidth: true
+                        text: (modelData.repaired ? "Fixed: " : modelData.repairable ? "" : "Needs the guest: ")
+                              + modelData.message
+                        font.pixelSize: 11
+                        color: palette.text
+                        opacity: modelData.repaired ? 0.6 : 1.0
+                        wrapMode: Text.WordWrap
+                    }
+                }
+
+                Label {
+                    visible: checkReport !== null && checkReport.volume
+                    text: !visible ? "" : checkReport.volume.fat_type + ": "
+                          + checkReport.volume.used_clusters + " of " + checkReport.volume.clusters
+                          + " clusters used, " + checkReport.volume.files + " files, "
+                          + checkReport.volume.directories + " directories"
+                    opacity: 0.6
+                }
+            }
+        }
+
+        // Guest operating system
+        GroupBox {
+            title: "Guest System"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                RowLayout {
+                    spacing: 8
+
+                    Button {
+                        text: "Detect"
+                        enabled: diskPropertiesDialog.diskPath !== ""
+                        onClicked: guestOs = JSON.parse(disks.detect_guest_os(diskPropertiesDialog.diskPath))
+                    }
+
+                    Button {
+                        text: "Use Suggested Settings"
+                        enabled: guestOs !== null && guestOs.tweaks !== undefined && guestOs.tweaks !== null
+                        onClicked: disks.apply_guest_os_tweaks(diskPropertiesDialog.diskPath)
+                    }
+                }
+
+                Label {
+                    visible: guestOs !== null
+                    text: guestOs === null ? ""
+                          : guestOs.error !== undefined ? guestOs.error
+                          : (guestOs.version || guestOs.name)
+                            + (guestOs.volume_label !== "" ? " (" + guestOs.volume_label + ")" : "")
+                    font.bold: true
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    visible: guestOs !== null && guestOs.tweaks !== undefined && guestOs.tweaks !== null
+                    text: !visible ? ""
+                          : "Mouse: " + guestOs.tweaks.mouse_protocol
+                            + ", clipboard " + (guestOs.tweaks.clipboard ? "shared" : "not available")
+                            + "\nDrivers: " + guestOs.tweaks.drivers.join(", ")
+                    font.pixelSize: 11
+                    color: palette.text
+                    wrapMode: Text.WordWrap
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    visible: guestOs !== null && guestOs.evidence !== undefined && guestOs.evidence.length > 0
+                    text: !visible ? "" : "Found: " + guestOs.evidence.join(", ")
+                    font.pixelSize: 11
+                    color: palette.text
+                    opacity: 0.6
+                    wrapMode: Text.WordWrap
+                }
+            }
+        }
+
+        // Host space
+        GroupBox {
+            title: "Host Space"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                Text {
+                    Layout.fillWidth: true
+                    text: "Gives back the host space of free clusters that hold only zeros. " +
+                          "Zero the free space in the guest first to reclaim all of it."
+                    font.pixelSize: 11
+                    color: palette.text
+                    opacity: 0.6
+                    wrapMode: Text.WordWrap
+                }
+
+                RowLayout {
+                    spacing: 8
+
+                    Button {
+                        text: diskPropertiesDialog.compactTask >= 0 ? "Cancel" : "Compact"
+                        enabled: diskPropertiesDialog.diskPath !== ""
+                        onClicked: {
+                            if (diskPropertiesDialog.compactTask >= 0) {
+                                disks.cancel_task(diskPropertiesDialog.compactTask)
+                            } else {
+                                diskPropertiesDialog.compactResult = ""
+                                diskPropertiesDialog.compactPercent = 0
+                                diskPropertiesDialog.compactTask = disks.compact_disk_async(diskPropertiesDialog.diskPath)
+                            }
+                        }
+                    }
+
+                    ProgressBar {
+                        Layout.fillWidth: true
+                        visible: diskPropertiesDialog.compactTask >= 0
+                        from: 0
+                        to: 100
+                        value: diskPropertiesDialog.compactPercent
+                    }
+
+                    Label {
+                        visible: diskPropertiesDialog.compactTask < 0
+                        text: diskPropertiesDialog.compactResult
+                    }
+                }
+            }
+        }
+
+        Connections {
+            target: disks
+            function onTask_progress(taskId, name, step, percent) {
+                if (taskId === diskPropertiesDialog.compactTask)
+                    diskPropertiesDialog.compactPercent = percent
+            }
+            function onTask_finished(taskId, success, errorCode, message) {
+                if (taskId === diskPropertiesDialog.compactTask) {
+                    diskPropertiesDialog.compactTask = -1
+                    diskPropertiesDialog.compactResult = success ? "Done" : message
+                    return
+                }
+                if (taskId !== diskPropertiesDialog.resizeTask)
+                    return
+                diskPropertiesDialog.resizeTask = -1
+                if (success)
+                    diskPropertiesDialog.diskSizeMb = disks.get_disk_size_mb(diskPropertiesDialog.diskPath)
+            }
+        }
+
         // Drive assignment
         GroupBox {
             title: "Drive Assignment"
@@ -21,21 +21,67 @@ Dialog {
 
     signal settingsApplied()
 
-    // Load current values when dialog opens
-    onOpened: {
+    // Load current values, when the dialog opens or the file is edited
+    function loadSettings() {
         aspectRatioCheck.checked = config.get_maintain_aspect_ratio()
         scanlineCheck.checked = config.get_scanline_effect()
-        integerScaleRadio.checked = config.get_integer_scaling()
-        if (!integerScaleRadio.checked) {
+        fullscreenCheck.checked = config.get_start_fullscreen()
+        hideMenuFullscreenCheck.checked = config.get_fullscreen_hide_menu()
+        fullscreenScreenCombo.currentIndex = config.get_fullscreen_screen() + 1
+        crtEffectCombo.currentIndex = Math.max(0, crtEffectCombo.effects.indexOf(config.get_crt_effect()))
+        scanlineIntensitySlider.value = config.get_scanline_intensity()
+        maskIntensitySlider.value = config.get_mask_intensity()
+        curvatureSlider.value = config.get_curvature()
+        paletteSyncCheck.checked = config.get_palette_sync()
+        vsyncCheck.checked = config.get_presentation_mode() === "vsync"
+        deinterlaceCombo.currentIndex = config.get_deinterlace_mode() === "weave" ? 1 : 0
+        scaleFactorSpinBox.value = config.get_scale_factor()
+        var mode = config.get_scaling_mode()
+        if (mode === "none") {
+            noScaleRadio.checked = true
+        } else if (mode === "stretch") {
+            stretchRadio.checked = true
+        } else if (mode === "fixed") {
+            fixedScaleRadio.checked = true
+        } else if (config.get_integer_scaling()) {
+            integerScaleRadio.checked = true
+        } else {
             fitWindowRadio.checked = true
         }
     }
 
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: displaySettingsDialog.config
+        function onConfig_changed() {
+            if (displaySettingsDialog.visible)
+                displaySettingsDialog.loadSettings()
+        }
+    }
+
     // Apply settings
     function applySettings() {
         config.set_maintain_aspect_ratio_value(aspectRatioCheck.checked)
         config.set_scanline_effect_value(scanlineCheck.checked)
+        config.set_start_fullscreen_value(fullscreenCheck.checked)
+        config.set_fullscreen_hide_menu_value(hideMenuFullscreenCheck.checked)
+        config.set_fullscreen_screen_value(fullscreenScreenCombo.currentIndex - 1)
+        config.set_crt_effect_value(crtEffectCombo.effects[crtEffectCombo.currentIndex])
+        config.set_scanline_intensity_value(scanlineIntensitySlider.value)
+        config.set_mask_intensity_value(maskIntensitySlider.value)
+        config.set_curvature_value(curvatureSlider.value)
+        config.set_palette_sync_value(paletteSyncCheck.checked)
+        config.set_presentation_mode_value(vsyncCheck.checked ? "vsync" : "immediate")
+        config.set_deinterlace_mode_value(deinterlaceCombo.currentIndex === 1 ? "weave" : "bob")
         config.set_integer_scaling_value(integerScaleRadio.checked)
+        if (fixedScaleRadio.checked) {
+            config.set_scale_factor_value(scaleFactorSpinBox.value)
+        } else {
+            config.set_scaling_mode_value(noScaleRadio.checked ? "none"
+                                          : stretchRadio.checked ? "stretch" : "fit")
+        }
         config.save()
         settingsApplied()
     }
@@ -114,13 +160,23 @@ Dialog {
 
                     RadioButton {
                         id: integerScaleRadio
-                        text: "Integer scaling (2×, 3×, etc.)"
+                        text: "Integer scaling (largest whole multiple that fits)"
+                    }
+
+                    RadioButton {
+                        id: stretchRadio
+                        text: "Stretch to fill window"
+                    }
+
+                    RadioButton {
+                        id: fixedScaleRadio
+                        text: "Fixed scale"
                     }
 
                     RowLayout {
                         spacing: 16
                         Layout.leftMargin: 24
-                        enabled: integerScaleRadio.checked
+                        enabled: fixedScaleRadio.checked
 
                         Label { text: "Scale factor:" }
                         SpinBox {
@@ -168,6 +224,72 @@ Dialog {
                         id: scanlineCheck
                         text: "CRT scanline effect"
                     }
+
+                    GridLayout {
+                        columns: 2
+                        columnSpacing: 8
+                        Layout.leftMargin: 24
+                        enabled: scanlineCheck.checked
+
+                        Label { text: "Effect:" }
+                        ComboBox {
+                            id: crtEffectCombo
+                            // Matches the effect names of ConfigManager.get_crt_effect
+                            property var effects: ["scanlines", "aperture", "curvature"]
+                            model: ["Scanlines", "Aperture grille", "Curved screen"]
+                        }
+
+                        Label { text: "Scanlines:" }
+                        Slider {
+                            id: scanlineIntensitySlider
+                            from: 0.0
+                            to: 1.0
+                        }
+
+                        Label { text: "Phosphor stripes:" }
+                        Slider {
+                            id: maskIntensitySlider
+                            from: 0.0
+                            to: 1.0
+                            enabled: crtEffectCombo.currentIndex === 1
+                        }
+
+                        Label { text: "Curvature:" }
+                        Slider {
+                            id: curvatureSlider
+                            from: 0.0
+                            to: 0.5
+                            enabled: crtEffectCombo.currentIndex === 2
+                        }
+                    }
+
+                    CheckBox {
+                        id: vsyncCheck
+                        text: "Sync to vertical refresh (tear-free)"
+                        ToolTip.text: "Update the display once per host frame instead of on a fixed timer"
+                        ToolTip.visible: hovered
+                    }
+
+                    RowLayout {
+                        spacing: 8
+
+                        Label { text: "Deinterlacing:" }
+
+                        ComboBox {
+                            id: deinterlaceCombo
+                            model: ["Bob (line doubling)", "Weave (combine fields)"]
+                            currentIndex: 0
+                            ToolTip.text: "Used for interlaced modes such as 1024x768i"
+                            ToolTip.visible: hovered
+                        }
+                    }
+
+                    CheckBox {
+                        id: paletteSyncCheck
+                        text: "Synchronize palette changes with frames"
+                        ToolTip.text: "Reduces flicker in 256-color palette cycling effects"
+                        ToolTip.visible: hovered
+                    }
                 }
             }
 
@@ -188,7 +310,23 @@ Dialog {
                     CheckBox {
                         id: hideMenuFullscreenCheck
                         text: "Hide menu bar in fullscreen"
-                        enabled: fullscreenCheck.checked
+                    }
+
+                    RowLayout {
+                        Label { text: "Fullscreen on:" }
+                        ComboBox {
+                            id: fullscreenScreenCombo
+                            Layout.fillWidth: true
+                            // Entry 0 is the window's own screen, then display 1..N
+                            model: {
+                                var names = ["Current display"]
+                                for (var i = 0; i < Qt.application.screens.length; i++) {
+                                    var screen = Qt.application.screens[i]
+                                    names.push("Display " + (i + 1) + " (" + screen.name + ")")
+                                }
+                                return names
+                            }
+                        }
                     }
 
                     CheckBox {
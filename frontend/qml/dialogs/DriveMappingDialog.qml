@@ -15,16 +15,51 @@ Dialog {
     width: 550
     height: Math.min(500, Screen.height - 100)
 
-    // Model for drive mappings
-    ListModel {
-        id: driveMappingsModel
-        // Default mappings like original SunPCi
-        ListElement { driveLetter: "F:"; hostPath: "/opt/SUNWspci"; description: "SunPCi Installation"; enabled: true }
-        ListElement { driveLetter: "H:"; hostPath: "~"; description: "Home Directory"; enabled: true }
-        ListElement { driveLetter: "R:"; hostPath: "/"; description: "Root Filesystem"; enabled: false }
+    // DriveMappingModel holding the configured mappings; edits change it
+    // in place, and are saved on OK or read back from the file on Cancel
+    property var mappingModel: null
+
+    // DriveMappingController, for previewing short names and space used
+    property var controller: null
+
+    // Bytes used under each mapped path by drive letter, as last measured
+    property var usage: ({})
+
+    function refreshUsage() {
+        let report = JSON.parse(controller.get_usage_json())
+        let byLetter = {}
+        for (let i = 0; i < report.length; i++)
+            byLetter[report[i].driveLetter] = report[i].usedBytes
+        usage = byLetter
     }
 
-    signal mappingsApplied(var mappings)
+    function formatSize(bytes) {
+        if (bytes >= 1024 * 1024 * 1024)
+            return (bytes / (1024 * 1024 * 1024)).toFixed(1) + " GB"
+        if (bytes >= 1024 * 1024)
+            return (bytes / (1024 * 1024)).toFixed(1) + " MB"
+        return Math.ceil(bytes / 1024) + " KB"
+    }
+
+    onOpened: {
+        if (controller) {
+            refreshUsage()
+            controller.refresh_usage(false)
+        }
+    }
+
+    Connections {
+        target: driveMappingDialog.controller
+        ignoreUnknownSignals: true
+        function onUsage_measured(driveLetter) { driveMappingDialog.refreshUsage() }
+    }
+
+    // Re-apply mappings whose host directory comes back
+    property bool autoReapply: false
+    // Map removable drives the host mounts without asking
+    property bool autoMapRemovable: false
+
+    signal mappingsApplied(var mappings, bool autoReapply, bool autoMapRemovable)
 
     ColumnLayout {
         anchors.fill: parent
@@ -67,6 +102,12 @@ Dialog {
                         Layout.fillWidth: true
                         color: palette.text
                     }
+                    Text {
+                        text: "Used"
+                        font.bold: true
+                        Layout.preferredWidth: 110
+                        color: palette.text
+                    }
                     Text {
                         text: "Enabled"
                         font.bold: true
@@ -87,7 +128,7 @@ Dialog {
                     id: mappingsListView
                     Layout.fillWidth: true
                     Layout.fillHeight: true
-                    model: driveMappingsModel
+                    model: driveMappingDialog.mappingModel
                     clip: true
                     spacing: 4
 
@@ -118,20 +159,29 @@ Dialog {
                             }
 
                             Text {
-                                text: model.hostPath
+                                text: model.hostPath + (model.readonly ? " (read-only)" : "")
                                 elide: Text.ElideMiddle
                                 Layout.fillWidth: true
                                 color: parent.parent.ListView.isCurrentItem ? palette.highlightedText : palette.text
                                 opacity: model.enabled ? 1.0 : 0.5
                             }
 
+                            Text {
+                                property var used: driveMappingDialog.usage[model.driveLetter]
+                                text: (used === undefined || used === null ? "—" : driveMappingDialog.formatSize(used)) +
+                                      (model.maxSizeMb > 0 ? " of " + driveMappingDialog.formatSize(model.maxSizeMb * 1024 * 1024) : "")
+                                Layout.preferredWidth: 110
+                                color: model.maxSizeMb > 0 && used > model.maxSizeMb * 1024 * 1024
+                                       ? "red"
+                                       : (parent.parent.ListView.isCurrentItem ? palette.highlightedText : palette.text)
+                                opacity: 0.8
+                            }
+
                             CheckBox {
                                 checked: model.enabled
                                 Layout.preferredWidth: 60
                                 Layout.alignment: Qt.AlignHCenter
-                                onCheckedChanged: {
-                                    driveMappingsModel.setProperty(index, "enabled", checked)
-                                }
+                                onToggled: driveMappingDialog.mappingModel.set_enabled(index, checked)
                             }
                         }
                     }
@@ -161,21 +211,14 @@ Dialog {
                 text: "Remove"
                 icon.name: "list-remove"
                 enabled: mappingsListView.currentIndex >= 0
-                onClicked: {
-                    driveMappingsModel.remove(mappingsListView.currentIndex)
-                }
+                onClicked: mappingModel.remove_mapping(mappingsListView.currentIndex)
             }
 
             Item { Layout.fillWidth: true }
 
             Button {
                 text: "Restore Defaults"
-                onClicked: {
-                    driveMappingsModel.clear()
-                    driveMappingsModel.append({ driveLetter: "F:", hostPath: "/opt/SUNWspci", description: "SunPCi Installation", enabled: true })
-                    driveMappingsModel.append({ driveLetter: "H:", hostPath: "~", description: "Home Directory", enabled: true })
-                    driveMappingsModel.append({ driveLetter: "R:", hostPath: "/", description: "Root Filesystem", enabled: false })
-                }
+                onClicked: mappingModel.restore_defaults()
             }
         }
 
@@ -202,6 +245,18 @@ Dialog {
                 }
             }
         }
+
+        CheckBox {
+            id: autoReapplyCheck
+            text: "Reconnect drives when their host directory comes back (e.g. USB sticks)"
+            checked: driveMappingDialog.autoReapply
+        }
+
+        CheckBox {
+            id: autoMapRemovableCheck
+            text: "Map removable drives to a free letter without asking"
+            checked: driveMappingDialog.autoMapRemovable
+        }
     }
 
     // Dialog for editing a mapping
@@ -220,15 +275,27 @@ Dialog {
             driveLetterField.text = "G:"
             hostPathField.text = ""
             descriptionField.text = ""
+            readonlyCheck.checked = false
+            timestampsField.editText = "local"
+            manglingField.currentIndex = 0
+            nameCaseField.currentIndex = 0
+            hideDotfilesCheck.checked = false
+            maxSizeField.value = 0
             open()
         }
 
         function openForEdit(index) {
             editIndex = index
-            let item = driveMappingsModel.get(index)
+            let item = JSON.parse(driveMappingDialog.mappingModel.get(index))
             driveLetterField.text = item.driveLetter
             hostPathField.text = item.hostPath
             descriptionField.text = item.description
+            readonlyCheck.checked = item.readonly
+            timestampsField.editText = item.timestamps || "local"
+            manglingField.currentIndex = Math.max(0, manglingField.indexOfValue(item.mangling || "tail"))
+            nameCaseField.currentIndex = Math.max(0, nameCaseField.indexOfValue(item.nameCase || "upper"))
+            hideDotfilesCheck.checked = item.hideDotfiles || false
+            maxSizeField.value = item.maxSizeMb || 0
             open()
         }
 
@@ -273,6 +340,86 @@ Dialog {
                     Layout.fillWidth: true
                     placeholderText: "Optional description"
                 }
+
+                Item { width: 1; height: 1 }
+                CheckBox {
+                    id: readonlyCheck
+                    text: "Read-only"
+                }
+
+                Label { text: "File Times:" }
+                ComboBox {
+                    id: timestampsField
+                    Layout.fillWidth: true
+                    // "local", "utc" or an offset from UTC such as +05:30
+                    model: ["local", "utc"]
+                    editable: true
+                    ToolTip.text: "Time zone the guest sees file dates in: the host's local time, UTC, or an offset such as +05:30"
+                    ToolTip.visible: hovered
+                }
+
+                Label { text: "Short Names:" }
+                ComboBox {
+                    id: manglingField
+                    Layout.fillWidth: true
+                    textRole: "text"
+                    valueRole: "value"
+                    model: [
+                        { text: "Numbered (LONGFI~1.TXT)", value: "tail" },
+                        { text: "Hashed (LONG~K3Z.TXT)", value: "hash" },
+                        { text: "Truncated (LONGFILE.TXT)", value: "truncate" }
+                    ]
+                    ToolTip.text: "How host names that do not fit 8.3 are shortened. Hashed names stay the same whatever else is in the folder; truncated names can clash."
+                    ToolTip.visible: hovered
+                }
+
+                Label { text: "Case:" }
+                ComboBox {
+                    id: nameCaseField
+                    Layout.fillWidth: true
+                    textRole: "text"
+                    valueRole: "value"
+                    model: [
+                        { text: "Upper case", value: "upper" },
+                        { text: "Lower case", value: "lower" },
+                        { text: "As on the host", value: "preserve" }
+                    ]
+                }
+
+                Item { width: 1; height: 1 }
+                CheckBox {
+                    id: hideDotfilesCheck
+                    text: "Hide files starting with '.'"
+                }
+
+                Label { text: "Size Limit (MB):" }
+                SpinBox {
+                    id: maxSizeField
+                    Layout.fillWidth: true
+                    from: 0
+                    to: 1048576
+                    stepSize: 100
+                    editable: true
+                    textFromValue: (value) => value === 0 ? "None" : value.toString()
+                    valueFromText: (text) => text === "None" ? 0 : parseInt(text) || 0
+                    ToolTip.text: "Most the guest may store in this directory; the drive shows this as its size. 0 for no limit."
+                    ToolTip.visible: hovered
+                }
+
+                Label {
+                    text: "Example:"
+                    visible: driveMappingDialog.controller !== null
+                }
+                Label {
+                    visible: driveMappingDialog.controller !== null
+                    text: visible
+                          ? "Long File Name.html → " +
+                            driveMappingDialog.controller.preview_short_name("Long File Name.html",
+                                                                             manglingField.currentValue || "tail",
+                                                                             nameCaseField.currentValue || "upper")
+                          : ""
+                    opacity: 0.6
+                }
             }
 
             Text {
@@ -295,30 +442,26 @@ Dialog {
         }
 
         onAccepted: {
-            let driveLetter = driveLetterField.editText || driveLetterField.currentText
-            if (editIndex >= 0) {
-                driveMappingsModel.set(editIndex, {
-                    driveLetter: driveLetter,
-                    hostPath: hostPathField.text,
-                    description: descriptionField.text,
-                    enabled: true
-                })
-            } else {
-                driveMappingsModel.append({
-                    driveLetter: driveLetter,
-                    hostPath: hostPathField.text,
-                    description: descriptionField.text,
-                    enabled: true
-                })
-            }
+            let enabled = editIndex >= 0 ? JSON.parse(driveMappingDialog.mappingModel.get(editIndex)).enabled : true
+            driveMappingDialog.mappingModel.set_mapping(editIndex, JSON.stringify({
+                driveLetter: driveLetterField.editText || driveLetterField.currentText,
+                hostPath: hostPathField.text,
+                description: descriptionField.text,
+                readonly: readonlyCheck.checked,
+                enabled: enabled,
+                timestamps: timestampsField.editText,
+                mangling: manglingField.currentValue,
+                nameCase: nameCaseField.currentValue,
+                hideDotfiles: hideDotfilesCheck.checked,
+                maxSizeMb: maxSizeField.value
+            }))
         }
     }
 
     onAccepted: {
-        let mappings = []
-        for (let i = 0; i < driveMappingsModel.count; i++) {
-            mappings.push(driveMappingsModel.get(i))
-        }
-        mappingsApplied(mappings)
+        mappingModel.save()
+        mappingsApplied(JSON.parse(mappingModel.to_json()), autoReapplyCheck.checked, autoMapRemovableCheck.checked)
     }
+
+    onRejected: mappingModel.load()
 }
@@ -0,0 +1,134 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+
+// Dialog explaining why the card cannot be opened: each check of the
+// session controller's diagnose_driver, with the commands that fix it
+Dialog {
+    id: driverDiagnosticsDialog
+    title: "Driver Access"
+    modal: true
+    standardButtons: Dialog.Close
+    width: 600
+    height: Math.min(520, Screen.height - 100)
+
+    // Reference to the session controller
+    required property var session
+
+    // Last diagnose_driver report
+    property var report: null
+
+    function refresh() {
+        report = JSON.parse(session.diagnose_driver())
+    }
+
+    // Every fix, in order, for copying in one go
+    function allFixes() {
+        if (report === null)
+            return ""
+        return report.checks.filter(check => check.fix !== null).map(check => check.fix).join("\n")
+    }
+
+    onOpened: refresh()
+
+    // Scratch area for copying text to the clipboard
+    TextEdit {
+        id: clipboardHelper
+        visible: false
+    }
+
+    function copy(text) {
+        clipboardHelper.text = text
+        clipboardHelper.selectAll()
+        clipboardHelper.copy()
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        spacing: 12
+
+        Label {
+            Layout.fillWidth: true
+            text: report === null ? ""
+                  : report.ok ? report.device + " can be used. If a session still fails, check again after reloading the driver."
+                  : report.device + " cannot be used. Run the commands below, then check again."
+            font.bold: true
+            wrapMode: Text.WordWrap
+        }
+
+        ListView {
+            Layout.fillWidth: true
+            Layout.fillHeight: true
+            clip: true
+            spacing: 8
+            model: report !== null ? report.checks : []
+
+            delegate: ColumnLayout {
+                width: ListView.view.width
+                spacing: 4
+
+                RowLayout {
+                    spacing: 8
+
+                    Rectangle {
+                        width: 12
+                        height: 12
+                        radius: 6
+                        color: modelData.status === "ok" ? "#88cc88"
+                               : modelData.status === "warning" ? "#ccaa44" : "#cc6666"
+                    }
+                    Label {
+                        text: modelData.name
+                        font.bold: true
+                    }
+                }
+
+                Text {
+                    Layout.fillWidth: true
+                    Layout.leftMargin: 20
+                    text: modelData.detail
+                    font.pixelSize: 12
+                    color: palette.text
+                    wrapMode: Text.WordWrap
+                }
+
+                TextArea {
+                    Layout.fillWidth: true
+                    Layout.leftMargin: 20
+                    visible: modelData.fix !== null
+                    text: modelData.fix !== null ? modelData.fix : ""
+                    readOnly: true
+                    selectByMouse: true
+                    font.family: "monospace"
+                    font.pixelSize: 11
+                    wrapMode: TextEdit.NoWrap
+                }
+            }
+        }
+
+        RowLayout {
+            Layout.fillWidth: true
+            spacing: 8
+
+            Button {
+                text: "Check Again"
+                onClicked: driverDiagnosticsDialog.refresh()
+            }
+
+            Button {
+                text: "Copy Commands"
+                enabled: driverDiagnosticsDialog.allFixes() !== ""
+                onClicked: driverDiagnosticsDialog.copy(driverDiagnosticsDialog.allFixes())
+            }
+
+            Button {
+                text: "Copy udev Rule"
+                enabled: report !== null
+                onClicked: driverDiagnosticsDialog.copy(report.udev_rule)
+            }
+
+            Item { Layout.fillWidth: true }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+
+// Dialog searching the screen text recorded during past and current
+// sessions, listing when each matching line appeared with the thumbnail
+// taken closest before it
+Dialog {
+    id: historyDialog
+    title: "Session History"
+    modal: true
+    standardButtons: Dialog.Close
+    width: 640
+    height: Math.min(520, Screen.height - 100)
+
+    // Reference to history controller
+    required property var history
+
+    // Number of recorded sessions, refreshed when opened
+    property int sessionCount: 0
+
+    ListModel {
+        id: resultsModel
+    }
+
+    function runSearch() {
+        resultsModel.clear()
+        let matches = JSON.parse(history.search(queryField.text, 200))
+        for (let i = 0; i < matches.length; i++) {
+            resultsModel.append({
+                when: new Date(matches[i].time_ms).toLocaleString(Qt.locale(), Locale.ShortFormat),
+                line: matches[i].line,
+                thumbnail: matches[i].thumbnail ? "file://" + matches[i].thumbnail : ""
+            })
+        }
+    }
+
+    onOpened: {
+        sessionCount = JSON.parse(history.get_sessions_json()).length
+        queryField.forceActiveFocus()
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        spacing: 12
+
+        RowLayout {
+            Layout.fillWidth: true
+            spacing: 8
+
+            TextField {
+                id: queryField
+                Layout.fillWidth: true
+                placeholderText: "Text shown on the guest screen, e.g. error 734"
+                onAccepted: historyDialog.runSearch()
+            }
+            Button {
+                text: "Search"
+                onClicked: historyDialog.runSearch()
+            }
+        }
+
+        Text {
+            Layout.fillWidth: true
+            text: historyDialog.sessionCount === 0
+                  ? "No sessions recorded. Set enabled = true in the [history] " +
+                    "section of the configuration to record the screen."
+                  : historyDialog.sessionCount + " sessions recorded, " +
+                    resultsModel.count + " matches"
+            font.pixelSize: 12
+            color: palette.text
+            wrapMode: Text.WordWrap
+        }
+
+        ListView {
+            Layout.fillWidth: true
+            Layout.fillHeight: true
+            clip: true
+            spacing: 8
+            model: resultsModel
+
+            delegate: Frame {
+                width: ListView.view.width
+
+                RowLayout {
+                    anchors.fill: parent
+                    spacing: 8
+
+                    Image {
+                        Layout.preferredWidth: 120
+                        Layout.preferredHeight: 90
+                        source: model.thumbnail
+                        fillMode: Image.PreserveAspectFit
+                        asynchronous: true
+                        visible: model.thumbnail !== ""
+                    }
+
+                    ColumnLayout {
+                        Layout.fillWidth: true
+                        spacing: 4
+
+                        Text {
+                            text: model.when
+                            font.bold: true
+                            color: palette.text
+                        }
+                        Text {
+                            Layout.fillWidth: true
+                            text: model.line
+                            elide: Text.ElideRight
+                            font.family: "monospace"
+                            color: palette.text
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
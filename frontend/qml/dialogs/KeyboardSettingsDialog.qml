@@ -18,8 +18,8 @@ Dialog {
 
     signal settingsApplied()
 
-    // Load current values when dialog opens
-    onOpened: {
+    // Load current values, when the dialog opens or the file is edited
+    function loadSettings() {
         // Find index for current layout
         let layout = config.get_keyboard_layout()
         for (let i = 0; i < layoutCombo.model.count; i++) {
@@ -37,6 +37,21 @@ Dialog {
                 break
             }
         }
+
+        syncCapsLockCheck.checked = config.get_sync_caps_lock()
+        syncNumLockCheck.checked = config.get_sync_num_lock()
+        syncScrollLockCheck.checked = config.get_sync_scroll_lock()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: keyboardSettingsDialog.config
+        function onConfig_changed() {
+            if (keyboardSettingsDialog.visible)
+                keyboardSettingsDialog.loadSettings()
+        }
     }
 
     // Apply settings
@@ -45,6 +60,9 @@ Dialog {
         let codePage = codePageCombo.model.get(codePageCombo.currentIndex).code
         config.set_keyboard_layout_value(layout)
         config.set_code_page_value(codePage)
+        config.set_sync_caps_lock_value(syncCapsLockCheck.checked)
+        config.set_sync_num_lock_value(syncNumLockCheck.checked)
+        config.set_sync_scroll_lock_value(syncScrollLockCheck.checked)
         config.save()
         settingsApplied()
     }
@@ -119,9 +137,13 @@ Dialog {
                         model: ListModel {
                             ListElement { text: "437 - US English (default)"; code: "437" }
                             ListElement { text: "850 - Multilingual Latin-1"; code: "850" }
+                            ListElement { text: "852 - Central European"; code: "852" }
                             ListElement { text: "860 - Portuguese"; code: "860" }
                             ListElement { text: "863 - Canadian French"; code: "863" }
                             ListElement { text: "865 - Nordic"; code: "865" }
+                            ListElement { text: "866 - Cyrillic"; code: "866" }
+                            ListElement { text: "932 - Japanese (Shift JIS)"; code: "932" }
+                            ListElement { text: "Detect automatically"; code: "auto" }
                         }
                         textRole: "text"
                         currentIndex: 0
@@ -129,7 +151,7 @@ Dialog {
 
                     Text {
                         text: "Code page affects character encoding for non-ASCII characters.\n" +
-                              "Used for Compose key sequences."
+                              "Used for Compose key sequences and 8-bit clipboard text."
                         font.pixelSize: 11
                         color: palette.text
                         opacity: 0.6
@@ -0,0 +1,22 @@
+import QtQuick 2.15
+
+// Holds a dialog that is only created the first time it is opened, so the
+// dialogs a session never uses do not slow down startup. The dialog goes
+// in sourceComponent; open() and close() are passed on to it.
+Loader {
+    id: lazyDialog
+    active: false
+
+    // Whether the dialog has been created and is showing
+    readonly property bool opened: item !== null && item.visible
+
+    function open() {
+        active = true
+        item.open()
+    }
+
+    function close() {
+        if (item !== null)
+            item.close()
+    }
+}
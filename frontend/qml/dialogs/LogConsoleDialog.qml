@@ -0,0 +1,240 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+import QtQuick.Dialogs 1.1 as Dialogs
+
+// Console showing the driver's messages and the frontend's own log as
+// they arrive, filtered by severity, source and text
+Dialog {
+    id: logConsoleDialog
+    title: "Log Console"
+    modal: false
+    standardButtons: Dialog.Close
+    width: 760
+    height: Math.min(560, Screen.height - 100)
+
+    // Reference to log controller
+    required property var log
+
+    // Reference to config manager (log level and log files)
+    required property var config
+
+    readonly property var levels: [
+        { text: "Errors", value: "error" },
+        { text: "Warnings", value: "warn" },
+        { text: "Info", value: "info" },
+        { text: "Debug", value: "debug" },
+        { text: "Trace", value: "trace" }
+    ]
+
+    // Lines kept in the view; older ones scroll off
+    readonly property int maxLines: 5000
+
+    ListModel {
+        id: linesModel
+    }
+
+    function levelColor(level) {
+        switch (level) {
+        case "error": return "#d32f2f"
+        case "warn": return "#f57c00"
+        case "info": return palette.text
+        default: return palette.placeholderText
+        }
+    }
+
+    function append(lines) {
+        let atEnd = lineList.atYEnd
+        for (let i = 0; i < lines.length; i++) {
+            let line = lines[i]
+            linesModel.append({
+                when: new Date(line.time_ms).toLocaleTimeString(Qt.locale(), "hh:mm:ss.zzz"),
+                level: line.level,
+                source: line.source,
+                text: line.target !== "" ? line.target + ": " + line.message : line.message
+            })
+        }
+        if (linesModel.count > maxLines)
+            linesModel.remove(0, linesModel.count - maxLines)
+        // Keep following the newest lines unless scrolled up
+        if (atEnd || followCheck.checked)
+            lineList.positionViewAtEnd()
+    }
+
+    function refresh() {
+        linesModel.clear()
+        append(JSON.parse(log.apply_filter(levelCombo.currentValue,
+                                           sourceCombo.currentValue,
+                                           searchField.text)))
+    }
+
+    function loadSettings() {
+        recordCombo.currentIndex = Math.max(0, recordCombo.indexOfValue(config.get_log_level()))
+        logFilesCheck.checked = config.get_log_file_enabled()
+        refresh()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: logConsoleDialog.config
+        function onConfig_changed() {
+            if (logConsoleDialog.visible)
+                logConsoleDialog.loadSettings()
+        }
+    }
+
+    Connections {
+        target: log
+        function onLines_added(lines) {
+            if (logConsoleDialog.visible)
+                logConsoleDialog.append(JSON.parse(lines))
+        }
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        spacing: 8
+
+        RowLayout {
+            Layout.fillWidth: true
+            spacing: 8
+
+            ComboBox {
+                id: levelCombo
+                textRole: "text"
+                valueRole: "value"
+                currentIndex: 2
+                model: logConsoleDialog.levels
+                onActivated: logConsoleDialog.refresh()
+            }
+            ComboBox {
+                id: sourceCombo
+                textRole: "text"
+                valueRole: "value"
+                model: [
+                    { text: "All sources", value: "all" },
+                    { text: "Driver", value: "driver" },
+                    { text: "Frontend", value: "frontend" }
+                ]
+                onActivated: logConsoleDialog.refresh()
+            }
+            TextField {
+                id: searchField
+                Layout.fillWidth: true
+                placeholderText: "Filter, e.g. cdrom"
+                onTextChanged: logConsoleDialog.refresh()
+            }
+        }
+
+        Frame {
+            Layout.fillWidth: true
+            Layout.fillHeight: true
+            padding: 4
+
+            ListView {
+                id: lineList
+                anchors.fill: parent
+                clip: true
+                model: linesModel
+                ScrollBar.vertical: ScrollBar {}
+
+                delegate: RowLayout {
+                    width: ListView.view.width
+                    spacing: 8
+
+                    Text {
+                        text: model.when
+                        font.family: "monospace"
+                        font.pixelSize: 11
+                        color: palette.placeholderText
+                    }
+                    Text {
+                        text: model.source === "driver" ? "drv" : "ui"
+                        font.family: "monospace"
+                        font.pixelSize: 11
+                        color: palette.placeholderText
+                        Layout.preferredWidth: 24
+                    }
+                    Text {
+                        Layout.fillWidth: true
+                        text: model.text
+                        font.family: "monospace"
+                        font.pixelSize: 11
+                        color: logConsoleDialog.levelColor(model.level)
+                        wrapMode: Text.WrapAnywhere
+                    }
+                }
+            }
+        }
+
+        RowLayout {
+            Layout.fillWidth: true
+            spacing: 8
+
+            CheckBox {
+                id: followCheck
+                text: "Follow"
+                checked: true
+            }
+            Text {
+                Layout.fillWidth: true
+                text: log.driver_available
+                      ? linesModel.count + " lines"
+                      : linesModel.count + " lines (driver log not available)"
+                font.pixelSize: 12
+                color: palette.text
+            }
+            // What the frontend logs to stderr and its log files, as opposed
+            // to what this console shows
+            Label {
+                text: "Record:"
+            }
+            ComboBox {
+                id: recordCombo
+                textRole: "text"
+                valueRole: "value"
+                model: logConsoleDialog.levels
+                onActivated: {
+                    if (config.set_log_level(currentValue))
+                        config.save()
+                }
+            }
+            CheckBox {
+                id: logFilesCheck
+                text: "Log files"
+                ToolTip.visible: hovered
+                ToolTip.text: "Write the log to " + config.get_log_dir() + " from the next start"
+                onToggled: {
+                    config.set_log_file_enabled_value(checked)
+                    config.save()
+                }
+            }
+            Button {
+                text: "Clear"
+                onClicked: {
+                    log.clear()
+                    linesModel.clear()
+                }
+            }
+            Button {
+                text: "Export..."
+                onClicked: exportDialog.open()
+            }
+        }
+    }
+
+    Dialogs.FileDialog {
+        id: exportDialog
+        title: "Export Log"
+        selectExisting: false
+        nameFilters: ["Log Files (*.log *.txt)", "All Files (*)"]
+        folder: shortcuts.home
+
+        onAccepted: {
+            log.export_log(fileUrl.toString().replace("file://", ""))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+import QtQuick.Window 2.15
+import QtQuick.Dialogs 1.1 as Dialogs
+
+// Dialog listing disks, ISOs and floppies the profile references but that
+// cannot be used (missing, unreadable, read-only or in use elsewhere),
+// with a fix-up for each: pick another file, detach it, or fetch it again
+Dialog {
+    id: missingMediaDialog
+    title: "Missing Media"
+    modal: true
+    standardButtons: Dialog.Close
+    width: 560
+    height: Math.min(420, Screen.height - 100)
+
+    // Reference to config manager
+    required property var config
+
+    // Emitted after a fix-up changed the profile
+    signal mediaChanged()
+
+    ListModel {
+        id: issuesModel
+    }
+
+    // Rebuild the list from the config manager's last check
+    function refresh() {
+        issuesModel.clear()
+        for (let i = 0; i < config.media_issue_count(); i++) {
+            issuesModel.append({
+                slot: config.get_media_issue_slot(i),
+                label: config.get_media_issue_label(i),
+                path: config.get_media_issue_path(i),
+                problem: config.get_media_issue_problem(i),
+                actions: config.get_media_issue_actions(i),
+                downloadUrl: config.get_media_issue_download_url(i)
+            })
+        }
+    }
+
+    function fixed(remaining) {
+        config.save()
+        mediaChanged()
+        refresh()
+        if (remaining === 0) {
+            close()
+        }
+    }
+
+    onOpened: refresh()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: missingMediaDialog.config
+        function onConfig_changed() {
+            if (missingMediaDialog.visible)
+                missingMediaDialog.refresh()
+        }
+    }
+
+    ColumnLayout {
+        anchors.fill: parent
+        spacing: 12
+
+        Text {
+            Layout.fillWidth: true
+            text: "Some media used by this profile cannot be opened. " +
+                  "Fix or remove them before starting the session."
+            font.pixelSize: 12
+            color: palette.text
+            wrapMode: Text.WordWrap
+        }
+
+        ListView {
+            Layout.fillWidth: true
+            Layout.fillHeight: true
+            clip: true
+            spacing: 8
+            model: issuesModel
+
+            delegate: Frame {
+                width: ListView.view.width
+
+                ColumnLayout {
+                    anchors.fill: parent
+                    spacing: 4
+
+                    Text {
+                        text: model.label + ": " + model.problem
+                        font.bold: true
+                        color: palette.text
+                    }
+                    Text {
+                        Layout.fillWidth: true
+                        text: model.path
+                        elide: Text.ElideMiddle
+                        font.family: "monospace"
+                        color: palette.text
+                    }
+
+                    RowLayout {
+                        spacing: 8
+
+                        Button {
+                            text: "Browse..."
+                            visible: model.actions.split(";").indexOf("browse") >= 0
+                            onClicked: {
+                                replaceFileDialog.slot = model.slot
+                                replaceFileDialog.open()
+                            }
+                        }
+                        Button {
+                            text: "Remove"
+                            visible: model.actions.split(";").indexOf("remove") >= 0
+                            onClicked: fixed(config.fix_media_remove(model.slot))
+                        }
+                        Button {
+                            text: "Download"
+                            visible: model.actions.split(";").indexOf("download") >= 0
+                            onClicked: Qt.openUrlExternally(model.downloadUrl)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Dialogs.FileDialog {
+        id: replaceFileDialog
+        title: "Locate Image"
+        selectExisting: true
+        nameFilters: ["Disk Images (*.img *.dsk *.hdd *.iso *.ISO)", "All Files (*)"]
+        folder: shortcuts.home
+
+        // Slot being fixed
+        property string slot: ""
+
+        onAccepted: {
+            let path = fileUrl.toString().replace("file://", "")
+            missingMediaDialog.fixed(config.fix_media_browse(slot, path))
+        }
+    }
+}
@@ -17,10 +17,14 @@ Dialog {
     property string selectedFloppyPath: ""
     property bool isMounted: false
     property int driveNumber: 0  // 0 = A:, 1 = B:
+    // RecentFilesModel of floppy images
+    required property var recentFiles
 
     signal floppyMounted(string path, int drive)
     signal floppyEjected(int drive)
 
+    onOpened: recentFiles.load()
+
     ScrollView {
         anchors.fill: parent
         contentWidth: availableWidth
@@ -109,6 +113,15 @@ Dialog {
                     }
                 }
 
+                RecentFilesList {
+                    Layout.fillWidth: true
+                    recentModel: mountFloppyDialog.recentFiles
+                    onFileSelected: (path) => {
+                        floppyPathField.text = path
+                        mountFloppyDialog.selectedFloppyPath = path
+                    }
+                }
+
                 // Floppy format info
                 Text {
                     text: "Supported formats: Raw sector images (.img, .ima, .flp, .vfd)"
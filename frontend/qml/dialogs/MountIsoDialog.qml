@@ -14,11 +14,28 @@ Dialog {
     width: 500
     height: Math.min(500, Screen.height - 100)
 
+    // Reference to disk manager (for the host drive list)
+    required property var disks
+    // RecentFilesModel of CD images
+    required property var recentFiles
+
     property string selectedIsoPath: ""
     property bool isMounted: false
+    // Whether the user asked to boot the guest from the selected image
+    property alias bootFromCd: bootFromCdCheck.checked
+
+    // get_disc_info() for the selected image
+    property var discInfo: selectedIsoPath !== "" && !selectedIsoPath.startsWith("/dev/")
+                           ? JSON.parse(disks.get_disc_info(selectedIsoPath)) : null
 
     signal isoMounted(string path)
     signal isoEjected()
+    signal hostDriveSelected(string device)
+
+    onOpened: {
+        hostDriveCombo.model = disks.get_host_cdroms().split(";").filter(d => d !== "")
+        recentFiles.load()
+    }
 
     ScrollView {
         anchors.fill: parent
@@ -68,7 +85,7 @@ Dialog {
 
         // ISO file selection
         GroupBox {
-            title: "Select ISO Image"
+            title: "Select CD Image"
             Layout.fillWidth: true
 
             ColumnLayout {
@@ -82,7 +99,7 @@ Dialog {
                     TextField {
                         id: isoPathField
                         Layout.fillWidth: true
-                        placeholderText: "Path to ISO file..."
+                        placeholderText: "Path to ISO or CUE file..."
                         text: mountIsoDialog.selectedIsoPath
                         readOnly: true
                     }
@@ -93,58 +110,122 @@ Dialog {
                     }
                 }
 
-                // Recent ISOs list (placeholder)
-                Text {
-                    text: "Recent images:"
-                    font.bold: true
-                    color: palette.text
+                RecentFilesList {
+                    Layout.fillWidth: true
+                    recentModel: mountIsoDialog.recentFiles
+                    onFileSelected: (path) => {
+                        isoPathField.text = path
+                        mountIsoDialog.selectedIsoPath = path
+                    }
                 }
+            }
+        }
 
-                ListView {
-                    id: recentIsosList
+        // Host drive pass-through
+        GroupBox {
+            title: "Use Host Drive"
+            Layout.fillWidth: true
+
+            ColumnLayout {
+                anchors.fill: parent
+                spacing: 8
+
+                RowLayout {
                     Layout.fillWidth: true
-                    Layout.preferredHeight: 80
-                    clip: true
-                    
-                    model: ListModel {
-                        // Would be populated from settings
-                        ListElement { path: "~/isos/win98se.iso" }
-                        ListElement { path: "~/isos/dos622.iso" }
+                    spacing: 8
+
+                    ComboBox {
+                        id: hostDriveCombo
+                        Layout.fillWidth: true
+                        enabled: count > 0
+                        displayText: count > 0 ? currentText : "No optical drives found"
                     }
 
-                    delegate: ItemDelegate {
-                        width: recentIsosList.width
-                        height: 24
-                        text: model.path
-                        font.pixelSize: 11
+                    Button {
+                        text: "Use Drive"
+                        enabled: hostDriveCombo.count > 0
                         onClicked: {
-                            isoPathField.text = model.path
-                            mountIsoDialog.selectedIsoPath = model.path
+                            let device = hostDriveCombo.currentText.split(" - ")[0]
+                            mountIsoDialog.selectedIsoPath = device
+                            mountIsoDialog.isMounted = true
+                            hostDriveSelected(device)
+                            mountIsoDialog.close()
                         }
                     }
                 }
+
+                Text {
+                    Layout.fillWidth: true
+                    text: "Passes the drive through to the guest, for audio CDs and " +
+                          "discs that cannot be imaged."
+                    font.pixelSize: 11
+                    color: palette.text
+                    opacity: 0.6
+                    wrapMode: Text.WordWrap
+                }
             }
         }
 
-        // CD-ROM info (shown when mounted)
+        // Disc info for the selected image
         GroupBox {
             title: "Disc Information"
             Layout.fillWidth: true
-            visible: mountIsoDialog.isMounted
+            visible: mountIsoDialog.discInfo !== null
 
             GridLayout {
+                anchors.fill: parent
                 columns: 2
                 rowSpacing: 8
                 columnSpacing: 16
 
-                Label { text: "Format:"; font.bold: true }
-                Label { text: "ISO 9660 + Joliet" }
+                Label {
+                    Layout.columnSpan: 2
+                    visible: mountIsoDialog.discInfo !== null && !mountIsoDialog.discInfo.valid
+                    text: mountIsoDialog.discInfo && mountIsoDialog.discInfo.error
+                          ? "Cannot use image: " + mountIsoDialog.discInfo.error : ""
+                    color: "#aa3333"
+                    wrapMode: Text.WordWrap
+                    Layout.fillWidth: true
+                }
+
+                Label { text: "Format:"; font.bold: true; visible: tracksLabel.visible }
+                Label {
+                    text: mountIsoDialog.discInfo && mountIsoDialog.discInfo.valid
+                          ? mountIsoDialog.discInfo.format : ""
+                    visible: tracksLabel.visible
+                }
+
+                Label { text: "Volume Label:"; font.bold: true; visible: tracksLabel.visible }
+                Label {
+                    text: mountIsoDialog.discInfo && mountIsoDialog.discInfo.volume_label
+                          ? mountIsoDialog.discInfo.volume_label : "(none)"
+                    visible: tracksLabel.visible
+                }
 
-                Label { text: "Sector Size:"; font.bold: true }
-                Label { text: "2048 bytes" }
+                Label { text: "Bootable:"; font.bold: true; visible: tracksLabel.visible }
+                Label {
+                    text: {
+                        let info = mountIsoDialog.discInfo
+                        if (!info || !info.boot) return "No"
+                        return (info.bootable ? "Yes (" : "No (") + info.boot + ")"
+                    }
+                    visible: tracksLabel.visible
+                }
 
-                Label { text: "Volume Label:"; font.bold: true }
-                Label { text: "(detected from ISO)" }
+                Label { text: "Tracks:"; font.bold: true; visible: tracksLabel.visible }
+                Label {
+                    id: tracksLabel
+                    visible: mountIsoDialog.discInfo !== null && mountIsoDialog.discInfo.valid
+                    text: {
+                        if (!visible) return ""
+                        let info = mountIsoDialog.discInfo
+                        let audio = info.tracks.filter(t => t.type === "audio").length
+                        let minutes = Math.floor(info.total_sectors / 75 / 60)
+                        let seconds = Math.floor(info.total_sectors / 75) % 60
+                        return info.tracks.length + " (" + (info.tracks.length - audio) + " data, " +
+                               audio + " audio), " + minutes + ":" + String(seconds).padStart(2, "0")
+                    }
+                }
             }
         }
 
@@ -167,10 +248,11 @@ Dialog {
                     id: bootFromCdCheck
                     text: "Boot from CD-ROM (El Torito)"
                     checked: false
+                    enabled: mountIsoDialog.discInfo !== null && mountIsoDialog.discInfo.bootable === true
                 }
 
                 Text {
-                    text: "Note: CD audio playback is not supported."
+                    text: "Note: CUE/BIN audio tracks play silently; use a host drive to hear CD audio."
                     font.pixelSize: 11
                     color: palette.text
                     opacity: 0.6
@@ -182,9 +264,10 @@ Dialog {
 
     Dialogs.FileDialog {
         id: isoFileDialog
-        title: "Select ISO Image"
+        title: "Select CD Image"
         selectExisting: true
-        nameFilters: ["ISO Images (*.iso *.ISO)", "All Files (*)"]
+        nameFilters: ["CD Images (*.iso *.ISO *.cue *.CUE)", "ISO Images (*.iso *.ISO)",
+                      "CUE Sheets (*.cue *.CUE)", "All Files (*)"]
         folder: shortcuts.home
 
         onAccepted: {
@@ -195,7 +278,7 @@ Dialog {
     }
 
     onAccepted: {
-        if (selectedIsoPath !== "") {
+        if (selectedIsoPath !== "" && (discInfo === null || discInfo.valid)) {
             isMounted = true
             isoMounted(selectedIsoPath)
         }
@@ -18,10 +18,35 @@ Dialog {
 
     signal settingsApplied()
 
+    // Load current values, when the dialog opens or the file is edited
+    function loadSettings() {
+        let protocol = config.get_mouse_protocol()
+        for (let i = 0; i < protocolCombo.model.count; i++) {
+            if (protocolCombo.model.get(i).value === protocol) {
+                protocolCombo.currentIndex = i
+                break
+            }
+        }
+        swapButtonsCheck.checked = config.get_swap_buttons()
+        middleButtonCheck.checked = config.get_simulate_middle_button()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: mouseSettingsDialog.config
+        function onConfig_changed() {
+            if (mouseSettingsDialog.visible)
+                mouseSettingsDialog.loadSettings()
+        }
+    }
+
     // Apply settings
     function applySettings() {
-        // Mouse settings are defined in common/src/config.rs (MouseConfig)
-        // but not yet exposed via ConfigManager. Settings are visual only for now.
+        config.set_mouse_protocol_value(protocolCombo.model.get(protocolCombo.currentIndex).value)
+        config.set_swap_buttons_value(swapButtonsCheck.checked)
+        config.set_simulate_middle_button_value(middleButtonCheck.checked)
         config.save()
         settingsApplied()
     }
@@ -16,20 +16,83 @@ Dialog {
     // Reference to config manager
     required property var config
 
+    // Reference to network controller (MAC vendor lookup and validation)
+    required property var network
+
+    // Translated message for NetworkController.mac_warning
+    function macWarningText(code) {
+        switch (code) {
+        case "invalid": return qsTr("Enter six hexadecimal byte pairs.")
+        case "multicast": return qsTr("Multicast address: the first byte must be even.")
+        case "broadcast": return qsTr("The broadcast address cannot be used by a network card.")
+        case "zero": return qsTr("All-zero address; choose Auto-generate instead.")
+        default: return ""
+        }
+    }
+
     signal settingsApplied()
 
-    // Load current values when dialog opens
-    onOpened: {
+    // Fill the interface list ("name - kind" entries) and select `current`
+    function loadInterfaces(current) {
+        interfaceModel.clear()
+        interfaceModel.append({ text: "Auto-detect", value: "" })
+        let listed = network.get_available_interfaces()
+        let entries = listed === "" ? [] : listed.split(";")
+        interfaceCombo.currentIndex = 0
+        for (let i = 0; i < entries.length; i++) {
+            let name = entries[i].split(" - ")[0]
+            interfaceModel.append({ text: entries[i], value: name })
+            if (name === current)
+                interfaceCombo.currentIndex = interfaceModel.count - 1
+        }
+    }
+
+    // Load current values, when the dialog opens or the file is edited
+    function loadSettings() {
         enableNetworkCheck.checked = config.get_network_enabled()
+        natRadio.checked = config.get_network_mode() === "nat"
+        bridgedRadio.checked = !natRadio.checked
         macAddressField.text = config.get_mac_address()
+        loadInterfaces(config.get_network_interface())
+        autoTapCheck.checked = config.get_network_auto_tap()
+        tapNameField.text = config.get_network_tap_name()
+        bridgeField.text = config.get_network_bridge()
+        createBridgeCheck.checked = config.get_network_create_bridge()
+        advertiseCheck.checked = config.get_remote_advertise()
+        profileNameField.text = config.get_remote_profile_name()
+        controlPortSpin.value = config.get_remote_control_port()
+        controlAddressField.text = config.get_remote_control_address()
+        controlTokenField.text = config.get_remote_control_token()
+    }
+
+    onOpened: loadSettings()
+
+    // Show edits made to the configuration file while open
+    Connections {
+        target: networkSettingsDialog.config
+        function onConfig_changed() {
+            if (networkSettingsDialog.visible)
+                networkSettingsDialog.loadSettings()
+        }
     }
 
     // Apply settings
     function applySettings() {
         config.set_network_enabled_value(enableNetworkCheck.checked)
-        if (customMacRadio.checked) {
+        config.set_network_mode_value(natRadio.checked ? "nat" : "bridged")
+        config.set_network_interface_value(interfaceModel.get(interfaceCombo.currentIndex).value)
+        if (customMacRadio.checked && network.check_mac(macAddressField.text)) {
             config.set_mac_address_value(macAddressField.text)
         }
+        config.set_network_auto_tap_value(autoTapCheck.checked)
+        config.set_network_tap_name_value(tapNameField.text)
+        config.set_network_bridge_value(bridgeField.text)
+        config.set_network_create_bridge_value(createBridgeCheck.checked)
+        config.set_remote_advertise_value(advertiseCheck.checked)
+        config.set_remote_profile_name_value(profileNameField.text)
+        config.set_remote_control_port_value(controlPortSpin.value)
+        config.set_remote_control_address_value(controlAddressField.text)
+        config.set_remote_control_token_value(controlTokenField.text)
         config.save()
         settingsApplied()
     }
@@ -58,7 +121,7 @@ Dialog {
                     }
 
                     Text {
-                        text: "The guest will see an Ethernet adapter that bridges to the host network."
+                        text: "The guest will see an Ethernet adapter connected to the host network."
                         font.pixelSize: 11
                         color: palette.text
                         opacity: 0.6
@@ -68,11 +131,45 @@ Dialog {
                 }
             }
 
+            // Attachment mode
+            GroupBox {
+                title: "Connection"
+                Layout.fillWidth: true
+                enabled: enableNetworkCheck.checked
+
+                ColumnLayout {
+                    anchors.fill: parent
+                    spacing: 8
+
+                    RadioButton {
+                        id: bridgedRadio
+                        text: "Bridged (TAP interface)"
+                        checked: true
+                    }
+
+                    RadioButton {
+                        id: natRadio
+                        text: "NAT (no host setup required)"
+                    }
+
+                    Text {
+                        text: natRadio.checked
+                              ? "The guest gets 10.0.2.15 by DHCP and reaches the network through the host.\n" +
+                                "Outbound TCP/UDP only; 10.0.2.2 is the host itself. No root needed."
+                              : "The guest appears on the host network with its own address.\n" +
+                                "Requires a TAP interface on a bridge."
+                        font.pixelSize: 11
+                        color: palette.text
+                        opacity: 0.6
+                    }
+                }
+            }
+
             // Host interface selection
             GroupBox {
                 title: "Host Network Interface"
                 Layout.fillWidth: true
-                enabled: enableNetworkCheck.checked
+                enabled: enableNetworkCheck.checked && bridgedRadio.checked
 
                 ColumnLayout {
                     anchors.fill: parent
@@ -81,13 +178,9 @@ Dialog {
                     ComboBox {
                         id: interfaceCombo
                         Layout.fillWidth: true
-                        // This would be populated dynamically from system interfaces
+                        // Listed by the network controller when opened
                         model: ListModel {
-                            ListElement { text: "Auto-detect"; value: "auto" }
-                            ListElement { text: "eth0 - Ethernet"; value: "eth0" }
-                            ListElement { text: "enp0s3 - Ethernet"; value: "enp0s3" }
-                            ListElement { text: "wlan0 - Wireless"; value: "wlan0" }
-                            ListElement { text: "br0 - Bridge"; value: "br0" }
+                            id: interfaceModel
                         }
                         textRole: "text"
                         currentIndex: 0
@@ -103,6 +196,59 @@ Dialog {
                 }
             }
 
+            // Host TAP interface
+            GroupBox {
+                title: "TAP Interface"
+                Layout.fillWidth: true
+                enabled: enableNetworkCheck.checked && bridgedRadio.checked
+
+                ColumnLayout {
+                    anchors.fill: parent
+                    spacing: 8
+
+                    CheckBox {
+                        id: autoTapCheck
+                        text: "Create TAP interface automatically"
+                    }
+
+                    GridLayout {
+                        columns: 2
+                        columnSpacing: 8
+                        enabled: autoTapCheck.checked
+
+                        Label { text: "TAP name:" }
+                        TextField {
+                            id: tapNameField
+                            placeholderText: "sunpci0"
+                            maximumLength: 15
+                            Layout.preferredWidth: 140
+                        }
+
+                        Label { text: "Bridge:" }
+                        TextField {
+                            id: bridgeField
+                            placeholderText: "none"
+                            maximumLength: 15
+                            Layout.preferredWidth: 140
+                        }
+                    }
+
+                    CheckBox {
+                        id: createBridgeCheck
+                        text: "Create bridge if it does not exist"
+                        enabled: autoTapCheck.checked && bridgeField.text !== ""
+                    }
+
+                    Text {
+                        text: "The TAP is created when the session starts and removed on exit.\n" +
+                              "Requires permission to manage network interfaces (CAP_NET_ADMIN)."
+                        font.pixelSize: 11
+                        color: palette.text
+                        opacity: 0.6
+                    }
+                }
+            }
+
             // MAC address
             GroupBox {
                 title: "MAC Address"
@@ -134,9 +280,26 @@ Dialog {
                             inputMask: "HH:HH:HH:HH:HH:HH;_"
                             font.family: "monospace"
                             Layout.preferredWidth: 140
+                            onTextChanged: network.check_mac(text)
                         }
                     }
 
+                    // Vendor of the entered address, or why it is unusable
+                    Text {
+                        visible: customMacRadio.checked
+                        text: network.mac_warning !== ""
+                              ? macWarningText(network.mac_warning)
+                              : network.mac_vendor !== ""
+                                ? qsTr("Vendor: %1").arg(network.mac_vendor)
+                                : network.mac_local
+                                  ? qsTr("Locally administered address")
+                                  : qsTr("Unknown vendor")
+                        font.pixelSize: 11
+                        color: network.mac_warning !== "" ? "#aa3333" : palette.text
+                        wrapMode: Text.WordWrap
+                        Layout.fillWidth: true
+                    }
+
                     Text {
                         text: "MAC address identifies the guest on the network.\n" +
                               "Auto-generated addresses use the 00:00:00 prefix."
@@ -147,6 +310,61 @@ Dialog {
                 }
             }
 
+            // Live throughput over the last minute
+            GroupBox {
+                title: "Throughput"
+                Layout.fillWidth: true
+                visible: network.network_connected
+
+                ColumnLayout {
+                    anchors.fill: parent
+                    spacing: 4
+
+                    Canvas {
+                        id: throughputGraph
+                        Layout.fillWidth: true
+                        Layout.preferredHeight: 80
+
+                        onPaint: {
+                            let ctx = getContext("2d")
+                            ctx.reset()
+                            let rx = JSON.parse(network.get_rx_history())
+                            let tx = JSON.parse(network.get_tx_history())
+                            let peak = Math.max(1, Math.max.apply(null, rx.concat(tx)))
+                            // 60 one-second samples across the full width
+                            let step = width / 59
+
+                            function plot(series, color) {
+                                ctx.strokeStyle = color
+                                ctx.lineWidth = 1.5
+                                ctx.beginPath()
+                                let x0 = width - (series.length - 1) * step
+                                for (let i = 0; i < series.length; i++) {
+                                    let y = height - series[i] / peak * (height - 2)
+                                    if (i === 0) ctx.moveTo(x0, y)
+                                    else ctx.lineTo(x0 + i * step, y)
+                                }
+                                ctx.stroke()
+                            }
+                            plot(rx, "#3a8ee6")
+                            plot(tx, "#e6a23a")
+                        }
+
+                        Connections {
+                            target: network
+                            function onThroughput_updated() { throughputGraph.requestPaint() }
+                        }
+                    }
+
+                    Text {
+                        text: "Receive " + network.format_bytes(network.rx_rate) + "/s" +
+                              "    Transmit " + network.format_bytes(network.tx_rate) + "/s"
+                        font.pixelSize: 11
+                        color: palette.text
+                    }
+                }
+            }
+
             // Guest driver info
             GroupBox {
                 title: "Guest Driver Information"
@@ -174,6 +392,96 @@ Dialog {
                 }
             }
 
+            // mDNS advertisement and control API
+            GroupBox {
+                title: "Remote Access"
+                Layout.fillWidth: true
+
+                ColumnLayout {
+                    anchors.fill: parent
+                    spacing: 8
+
+                    CheckBox {
+                        id: advertiseCheck
+                        text: "Advertise running sessions on the local network (mDNS)"
+                    }
+
+                    RowLayout {
+                        Layout.fillWidth: true
+                        spacing: 8
+                        enabled: advertiseCheck.checked
+
+                        Label { text: "Profile name:" }
+
+                        TextField {
+                            id: profileNameField
+                            Layout.fillWidth: true
+                            placeholderText: "Host name"
+                        }
+                    }
+
+                    RowLayout {
+                        Layout.fillWidth: true
+                        spacing: 8
+
+                        Label { text: "Control API port:" }
+
+                        SpinBox {
+                            id: controlPortSpin
+                            from: 0
+                            to: 65535
+                            editable: true
+                            textFromValue: (value) => value === 0 ? "Off" : value.toString()
+                        }
+
+                        Item { Layout.fillWidth: true }
+                    }
+
+                    RowLayout {
+                        Layout.fillWidth: true
+                        spacing: 8
+                        enabled: controlPortSpin.value !== 0
+
+                        Label { text: "Listen on:" }
+
+                        TextField {
+                            id: controlAddressField
+                            Layout.fillWidth: true
+                            placeholderText: "127.0.0.1 (this computer only)"
+                        }
+                    }
+
+                    RowLayout {
+                        Layout.fillWidth: true
+                        spacing: 8
+                        enabled: controlPortSpin.value !== 0
+
+                        Label { text: "Control token:" }
+
+                        TextField {
+                            id: controlTokenField
+                            Layout.fillWidth: true
+                            echoMode: TextInput.PasswordEchoOnEdit
+                            placeholderText: "Required to listen beyond this computer"
+                        }
+                    }
+
+                    Text {
+                        Layout.fillWidth: true
+                        text: "Other instances and the CLI find the session as _risingsun._tcp; " +
+                              "rising-sun-cli --host controls it through the control port. " +
+                              "The port only accepts connections from other computers when it " +
+                              "listens on another address (0.0.0.0 for all) and a token is set, " +
+                              "which clients give with --token. The token is sent unencrypted, " +
+                              "so use on trusted networks only. Takes effect after a restart."
+                        font.pixelSize: 11
+                        color: palette.text
+                        opacity: 0.6
+                        wrapMode: Text.WordWrap
+                    }
+                }
+            }
+
             // IRQ selection
             GroupBox {
                 title: "Advanced"
@@ -0,0 +1,91 @@
+import QtQuick 2.15
+import QtQuick.Controls 2.15
+import QtQuick.Layouts 1.15
+
+// List of recently used files from a RecentFilesModel, with pinned files
+// first. Clicking a file selects it; each row can be pinned or removed,
+// and Clear forgets all files that are not pinned.
+ColumnLayout {
+    id: recentFilesList
+    spacing: 4
+
+    // RecentFilesModel of the category to show
+    required property var recentModel
+
+    signal fileSelected(string path)
+
+    RowLayout {
+        Layout.fillWidth: true
+
+        Text {
+            text: "Recent images:"
+            font.bold: true
+            color: palette.text
+            Layout.fillWidth: true
+        }
+
+        Button {
+            text: "Clear"
+            flat: true
+            enabled: recentFilesList.recentModel.count > 0
+            onClicked: recentFilesList.recentModel.clear()
+        }
+    }
+
+    ListView {
+        id: recentListView
+        Layout.fillWidth: true
+        Layout.preferredHeight: 100
+        clip: true
+        model: recentFilesList.recentModel
+
+        delegate: ItemDelegate {
+            width: recentListView.width
+            height: 28
+            onClicked: recentFilesList.fileSelected(model.path)
+            ToolTip.text: model.path
+            ToolTip.visible: hovered
+            ToolTip.delay: 500
+
+            contentItem: RowLayout {
+                spacing: 4
+
+                Text {
+                    text: model.name
+                    font.pixelSize: 11
+                    font.bold: model.pinned
+                    elide: Text.ElideMiddle
+                    color: palette.text
+                    Layout.fillWidth: true
+                }
+
+                ToolButton {
+                    text: model.pinned ? "★" : "☆"
+                    implicitWidth: 24
+                    implicitHeight: 24
+                    ToolTip.text: model.pinned ? "Unpin" : "Pin to the top"
+                    ToolTip.visible: hovered
+                    onClicked: recentFilesList.recentModel.pin(index, !model.pinned)
+                }
+
+                ToolButton {
+                    text: "✕"
+                    implicitWidth: 24
+                    implicitHeight: 24
+                    ToolTip.text: "Remove from the list"
+                    ToolTip.visible: hovered
+                    onClicked: recentFilesList.recentModel.remove(index)
+                }
+            }
+        }
+
+        Text {
+            anchors.centerIn: parent
+            visible: recentListView.count === 0
+            text: "No recent images"
+            font.pixelSize: 11
+            color: palette.text
+            opacity: 0.6
+        }
+    }
+}
@@ -7,44 +7,615 @@ import "dialogs"
 
 ApplicationWindow {
     id: window
-    visible: true
+    // Hidden for the whole run with --headless
+    visible: !mainWindow.headless
     width: 800
     height: 600
     minimumWidth: 640
     minimumHeight: 480
-    title: "Rising Sun"
+    // Name the card when this machine has more than one
+    title: cardMenu.cardCount > 1 ? "Rising Sun - " + sessionController.card_name : "Rising Sun"
+
+    // Close once the session has ended, shut down or saved (see onClosing)
+    property bool closeAfterShutdown: false
+    // The user chose how to end the running session; close without asking
+    property bool closeConfirmed: false
+    // The first frame ended the startup profile
+    property bool startupReported: false
+
+    onFrameSwapped: {
+        if (!startupReported) {
+            startupReported = true
+            mainWindow.startup_finished()
+        }
+    }
 
     MainWindow {
         id: mainWindow
+        Component.onCompleted: {
+            load_window_config()
+            window.restoreGeometry()
+            if (start_fullscreen) {
+                enter_fullscreen()
+            }
+        }
+
+        onFullscreenChanged: {
+            if (fullscreen) {
+                if (window.visibility !== Window.FullScreen) {
+                    window.moveToFullscreenScreen()
+                    window.showFullScreen()
+                }
+            } else if (window.visibility === Window.FullScreen) {
+                if (window_maximized) {
+                    window.showMaximized()
+                } else {
+                    window.showNormal()
+                }
+            }
+        }
+    }
+
+    // Screens as MainWindow.place_window reads them, primary first
+    function screenList() {
+        var screens = []
+        for (var i = 0; i < Qt.application.screens.length; i++) {
+            var screen = Qt.application.screens[i]
+            screens.push({
+                name: screen.name,
+                x: screen.virtualX,
+                y: screen.virtualY,
+                width: screen.width,
+                height: screen.height,
+                device_pixel_ratio: screen.devicePixelRatio
+            })
+        }
+        return screens
+    }
+
+    // Put the window back on the screen and at the geometry it had
+    function restoreGeometry() {
+        if (!mainWindow.place_window(JSON.stringify(screenList()), width, height)) {
+            return
+        }
+        window.screen = Qt.application.screens[mainWindow.place_screen]
+        window.x = mainWindow.place_x
+        window.y = mainWindow.place_y
+        window.width = mainWindow.place_width
+        window.height = mainWindow.place_height
+    }
+
+    // Move to the configured fullscreen screen, if there is one
+    function moveToFullscreenScreen() {
+        var screens = Qt.application.screens
+        var current = 0
+        for (var i = 0; i < screens.length; i++) {
+            if (screens[i].name === window.screen.name) {
+                current = i
+            }
+        }
+        var target = mainWindow.fullscreen_screen_index(screens.length, current)
+        if (target !== current) {
+            window.screen = screens[target]
+        }
+    }
+
+    // Follow fullscreen changes made by the window manager
+    onVisibilityChanged: (visibility) => {
+        if (visibility === Window.FullScreen) {
+            mainWindow.sync_fullscreen(true)
+        } else if (visibility === Window.Windowed || visibility === Window.Maximized) {
+            mainWindow.window_maximized = visibility === Window.Maximized
+            mainWindow.sync_fullscreen(false)
+        }
     }
 
     // Session controller for driver communication
     SessionController {
         id: sessionController
-        Component.onCompleted: check_driver()
+        // The driver is checked off the UI thread so the window shows
+        // at once; driverProbeTimer picks up the result
+        Component.onCompleted: {
+            probe_driver()
+            start_control()
+            if (auto_start()) {
+                start_session()
+                // Nothing to show a failure in without a window
+                if (mainWindow.headless && !session_running) {
+                    console.log("Cannot start session:", error_message)
+                    Qt.quit()
+                }
+            }
+        }
+
+        // Events come from the card's own device
+        onCardChanged: {
+            eventController.stop_events()
+            if (driver_loaded) {
+                eventController.start_events()
+            }
+        }
+
+        onSession_runningChanged: {
+            // Headless runs last as long as their session
+            if (!session_running && mainWindow.headless) {
+                Qt.quit()
+                return
+            }
+            if (!session_running && window.closeAfterShutdown) {
+                window.closeConfirmed = true
+                window.close()
+            }
+        }
+
+        // CLI commands for the disk manager (rising-sun-cli --host)
+        onControl_request: (id, command, argument) => {
+            switch (command) {
+            case "mount-iso":
+                if (diskManager.mount_iso(argument)) {
+                    mountIsoDialog.selectedIsoPath = argument
+                    mountIsoDialog.isMounted = true
+                    control_reply(id, true, "Mounted " + argument)
+                } else {
+                    control_reply(id, false, "Cannot mount " + argument)
+                }
+                break
+            case "eject":
+                diskManager.eject_cdrom()
+                mountIsoDialog.isMounted = false
+                mountIsoDialog.selectedIsoPath = ""
+                control_reply(id, true, "CD-ROM ejected")
+                break
+            case "release": {
+                // Another frontend is taking the session over
+                let cdrom = diskManager.cdrom_mounted ? diskManager.cdrom_path : ""
+                let served = diskManager.cdrom_passthrough
+                    || cdrom.toLowerCase().endsWith(".cue")
+                let state = release_session(cdrom, served)
+                if (state !== "") {
+                    diskManager.release_cdrom()
+                    mountIsoDialog.isMounted = false
+                    mountIsoDialog.selectedIsoPath = ""
+                }
+                control_reply(id, state !== "", state !== "" ? state : error_message)
+                break
+            }
+            case "show":
+                // The frontend was started again for this card
+                if (mainWindow.headless) {
+                    control_reply(id, true, "Running without a window")
+                    break
+                }
+                window.show()
+                window.raise()
+                window.requestActivate()
+                if (argument === "fullscreen")
+                    mainWindow.enter_fullscreen()
+                control_reply(id, true, "Shown")
+                break
+            default:
+                control_reply(id, false, "Unsupported command " + command)
+            }
+        }
+
+        onState_saved: (success, message) => {
+            if (success)
+                return
+            let closing = window.closeAfterShutdown
+            window.closeAfterShutdown = false
+            toast.show(qsTr("Could not save the guest state: ") + message)
+            if (closing)
+                closeSessionDialog.open()
+        }
+
+        // The saved state is gone either way; boot the guest instead
+        onState_restored: (success, message) => {
+            if (success)
+                return
+            toast.show(qsTr("Could not resume the saved guest; starting it afresh: ") + message)
+            start_session()
+        }
+
+        // Pick up the CD-ROM the previous frontend was using
+        // The screen is saved before anything else happens to the session
+        onGuest_incident: (incident, action) => {
+            let what = incident === "hung" ? qsTr("The guest stopped responding")
+                                           : qsTr("The guest crashed")
+            let path = hotkeyController.screenshot_path()
+            displayImage.grabToImage(function(result) {
+                let saved = result.saveToFile(path)
+                if (!saved)
+                    console.log("Failed to save crash screenshot:", path)
+                if (action === "run_command" && saved
+                        && !sessionController.run_crash_command(incident, path))
+                    toast.show(what + qsTr("; the crash command could not be run"))
+            })
+
+            if (action === "restart")
+                toast.show(what + qsTr("; restarting the session"))
+            else if (action === "give_up")
+                toast.show(what + qsTr(" again; not restarting after repeated failures"))
+            else
+                toast.show(what + qsTr("; screenshot saved to ") + path)
+        }
+
+        onSession_adopted: (cdrom, served) => {
+            if (cdrom === "")
+                return
+            let mounted = true
+            if (served) {
+                mounted = cdrom.startsWith("/dev/")
+                    ? diskManager.mount_host_cdrom(cdrom)
+                    : diskManager.mount_iso(cdrom)
+            } else {
+                diskManager.cdrom_path = cdrom
+                diskManager.cdrom_mounted = true
+            }
+            mountIsoDialog.isMounted = mounted
+            mountIsoDialog.selectedIsoPath = mounted ? cdrom : ""
+        }
+    }
+
+    // Falls back to a hard stop if the guest ignores a shutdown request
+    Timer {
+        id: shutdownTimer
+        interval: 1000
+        repeat: true
+        running: sessionController.shutting_down
+        onTriggered: sessionController.check_shutdown()
+    }
+
+    // Progress of saving or restoring the guest state
+    Timer {
+        id: stateTaskTimer
+        interval: 200
+        repeat: true
+        running: sessionController.saving_state || sessionController.restoring_state
+        onTriggered: sessionController.poll_state_task()
+    }
+
+    // Crash and hang detection
+    Timer {
+        id: watchdogTimer
+        interval: 2000
+        repeat: true
+        running: sessionController.session_running
+        onTriggered: sessionController.check_watchdog()
+    }
+
+    // Short notice at the bottom of the window
+    Popup {
+        id: toast
+        x: (window.width - width) / 2
+        y: window.height - height - 48
+        padding: 10
+        closePolicy: Popup.CloseOnPressOutside
+
+        function show(message) {
+            toastLabel.text = message
+            open()
+            toastTimer.restart()
+        }
+
+        Label {
+            id: toastLabel
+        }
+
+        Timer {
+            id: toastTimer
+            interval: 5000
+            onTriggered: toast.close()
+        }
+    }
+
+    // Driver probe polling timer (startup)
+    Timer {
+        id: driverProbeTimer
+        interval: 20
+        repeat: true
+        running: sessionController.driver_probing
+        onTriggered: {
+            if (sessionController.poll_driver_probe() && sessionController.driver_loaded) {
+                eventController.start_events()
+            }
+        }
+    }
+
+    // Control API polling timer
+    Timer {
+        id: controlPollTimer
+        interval: 100
+        repeat: true
+        running: sessionController.control_listening
+        onTriggered: sessionController.poll_control()
     }
 
     // Disk manager for disk image operations
     DiskManager {
         id: diskManager
+
+        // Most recent background task, shown in the status bar
+        property int currentTaskId: -1
+        property string currentTaskName: ""
+        property string currentTaskStep: ""
+        property int currentTaskPercent: 0
+
+        onTask_progress: (taskId, name, step, percent) => {
+            currentTaskId = taskId
+            currentTaskName = name
+            currentTaskStep = step
+            currentTaskPercent = percent
+        }
+
+        onTask_finished: (taskId, success, errorCode, message) => {
+            console.log("Disk task", taskId, success ? "completed" : "failed:", message)
+            if (taskId === currentTaskId) {
+                currentTaskId = -1
+                currentTaskStep = ""
+            }
+        }
+
+        // The guest ejected a disc, or another frontend or the CLI changed it
+        onCdrom_changed: (path, mounted) => {
+            mountIsoDialog.isMounted = mounted
+            mountIsoDialog.selectedIsoPath = path
+            configManager.set_cdrom_iso(path)
+            configManager.save()
+        }
+
+        onFloppy_changed: (drive, path, mounted) => {
+            if (mountFloppyDialog.driveNumber === drive) {
+                mountFloppyDialog.isMounted = mounted
+                mountFloppyDialog.selectedFloppyPath = path
+            }
+        }
+
+        onDisk_created: (taskId, path) => {
+            console.log("Disk image created:", path)
+        }
+
+        // Running out of space has its own dialog, and a cancel needs no reply
+        onDisk_creation_failed: (taskId, path, errorCode, message) => {
+            if (errorCode !== 1)  // error_code::FAILED
+                return
+            diskCreateFailedDialog.path = path
+            diskCreateFailedDialog.message = message
+            diskCreateFailedDialog.open()
+        }
+
+        onInsufficient_space: (path, requiredMb, availableMb) => {
+            diskSpaceDialog.path = path
+            diskSpaceDialog.requiredMb = requiredMb
+            diskSpaceDialog.availableMb = availableMb
+            diskSpaceDialog.open()
+        }
+    }
+
+    // Background disk task polling timer
+    Timer {
+        id: diskTaskTimer
+        interval: 100
+        repeat: true
+        running: diskManager.active_tasks > 0
+        onTriggered: diskManager.poll_tasks()
+        // Deliver the final task_finished signal after the last task stops
+        onRunningChanged: if (!running) diskManager.poll_tasks()
+    }
+
+    // Asynchronous driver events, routed to the controller each concerns
+    EventController {
+        id: eventController
+
+        onEvents_lost: {
+            sessionController.poll_display()
+            clipboardController.poll_guest_clipboard()
+        }
+        onEject_requested: (drive) => diskManager.apply_eject_request(drive)
+        onMedia_changed: (drive, path, present) => diskManager.apply_media_change(drive, path, present)
+        onDisplay_changed: sessionController.poll_display()
+        onSession_changed: (state) => sessionController.apply_session_state(state)
+        onClipboard_changed: (format) => clipboardController.poll_guest_clipboard()
+        onPalette_changed: (generation) => sessionController.palette_changed(generation)
+        onGuest_powered_off: sessionController.guest_powered_off()
+    }
+
+    // Hands events collected by the listener thread to the UI thread
+    Timer {
+        id: eventPollTimer
+        interval: 50
+        repeat: true
+        running: eventController.listening
+        onTriggered: eventController.poll_events()
     }
 
     // Configuration manager for persistent settings
     ConfigManager {
         id: configManager
-        Component.onCompleted: load()
+        Component.onCompleted: {
+            load()
+            displayView.loadScaling()
+            displayView.load_crt_config()
+            // Report unusable media now rather than when the guest mounts it
+            if (media_issue_count() > 0) {
+                missingMediaDialog.open()
+            }
+        }
+
+        // The file was edited elsewhere (by hand, the CLI or another
+        // frontend): take on the settings that apply without a restart;
+        // open dialogs show the new values themselves
+        onConfig_changed: {
+            window.presentationMode = get_presentation_mode()
+            displayView.loadScaling()
+            displayView.load_crt_config()
+            inputController.sync_caps_lock = get_sync_caps_lock()
+            inputController.sync_num_lock = get_sync_num_lock()
+            inputController.sync_scroll_lock = get_sync_scroll_lock()
+            inputController.absolute_mode = get_mouse_protocol() === "absolute"
+            clipboardController.code_page = get_code_page()
+            clipboardController.share_images = get_clipboard_share_images()
+            clipboardController.share_rich_text = get_clipboard_share_rich_text()
+            driveMappingController.auto_reapply = get_reapply_drive_mappings()
+            removableMediaController.auto_map = get_auto_map_removable_media()
+            driveMappingModel.load()
+        }
+    }
+
+    // Look for edits made to the configuration file elsewhere
+    Timer {
+        interval: 2000
+        running: true
+        repeat: true
+        onTriggered: configManager.poll_changes()
+    }
+
+    // Host hotkeys from the [hotkeys] config section
+    HotkeyController {
+        id: hotkeyController
+        Component.onCompleted: load_bindings()
+
+        // Hotkeys run through the action registry, like the command palette
+        onAction_triggered: (action) => actionController.trigger(action)
+    }
+
+    // Registered actions (see actions.rs) that QML carries out
+    ActionController {
+        id: actionController
+
+        onAction_triggered: (action) => {
+            switch (action) {
+            case "release_capture":
+                if (inputController.keyboard_captured || inputController.mouse_captured) {
+                    inputController.release_capture()
+                } else if (sessionController.session_running) {
+                    inputController.toggle_keyboard_capture()
+                }
+                break
+            case "toggle_fullscreen":
+                window.toggleFullscreen()
+                break
+            case "send_ctrl_alt_del":
+                if (sessionController.session_running) {
+                    inputController.send_ctrl_alt_del()
+                }
+                break
+            case "eject_cdrom":
+                diskManager.eject_cdrom()
+                mountIsoDialog.isMounted = false
+                mountIsoDialog.selectedIsoPath = ""
+                break
+            case "screenshot":
+                if (sessionController.session_running) {
+                    var path = hotkeyController.screenshot_path()
+                    displayImage.grabToImage(function(result) {
+                        if (result.saveToFile(path)) {
+                            console.log("Screenshot saved:", path)
+                        } else {
+                            console.log("Failed to save screenshot:", path)
+                        }
+                    })
+                }
+                break
+            case "quit":
+                quitAction.trigger()
+                break
+            case "start_session":
+                startAction.trigger()
+                break
+            case "reset_session":
+                resetAction.trigger()
+                break
+            case "shut_down":
+                shutDownAction.trigger()
+                break
+            case "save_state":
+                saveStateAction.trigger()
+                break
+            case "stop_session":
+                stopAction.trigger()
+                break
+            case "create_disk":
+                createDiskDialog.open()
+                break
+            case "mount_iso":
+                mountIsoDialog.open()
+                break
+            case "mount_floppy":
+                mountFloppyDialog.driveNumber = 0
+                mountFloppyDialog.open()
+                break
+            case "shared_folders":
+                driveMappingDialog.open()
+                break
+            case "session_history":
+                historyDialog.open()
+                break
+            case "log_console":
+                logConsoleDialog.open()
+                break
+            case "bios_settings":
+                biosDialog.open()
+                break
+            case "display_settings":
+                displaySettingsDialog.open()
+                break
+            case "keyboard_settings":
+                keyboardSettingsDialog.open()
+                break
+            case "mouse_settings":
+                mouseSettingsDialog.open()
+                break
+            case "network_settings":
+                networkSettingsDialog.open()
+                break
+            case "clipboard_settings":
+                clipboardSettingsDialog.open()
+                break
+            }
+        }
+    }
+
+    function toggleFullscreen() {
+        mainWindow.toggle_fullscreen()
     }
 
     // Input controller for keyboard and mouse handling
+    // Paces the display refresh by guest drawing activity
+    DisplayView {
+        id: displayView
+        source_width: sessionController.display_width
+        source_height: sessionController.display_height
+        onSource_widthChanged: place()
+        onSource_heightChanged: place()
+
+        // Take the scaling settings from the configuration and re-place
+        function loadScaling() {
+            scaling_mode = configManager.get_scaling_mode()
+            scale_factor = configManager.get_scale_factor()
+            maintain_aspect = configManager.get_maintain_aspect_ratio()
+            integer_scaling = configManager.get_integer_scaling()
+            place()
+        }
+
+        function place() {
+            compute_target_rect(displayOutput.width, displayOutput.height)
+        }
+    }
+
     InputController {
         id: inputController
         guest_width: sessionController.display_width
         guest_height: sessionController.display_height
+        device_pixel_ratio: window.screen ? window.screen.devicePixelRatio : 1.0
+        sync_caps_lock: configManager.get_sync_caps_lock()
+        sync_num_lock: configManager.get_sync_num_lock()
+        sync_scroll_lock: configManager.get_sync_scroll_lock()
+        absolute_mode: configManager.get_mouse_protocol() === "absolute"
         
         // Connect to driver when session starts
         Component.onCompleted: {
             if (sessionController.session_running) {
-                driver_fd = sessionController.get_driver_fd()
+                set_driver(sessionController.get_driver_fd())
             }
         }
         
@@ -57,27 +628,123 @@ ApplicationWindow {
             // Cursor shape is handled by displayMouseArea's cursorShape binding
         }
     }
+
+    // Records keys sent to the guest, and plays saved macros back
+    MacroController {
+        id: macroController
+    }
+
+    Timer {
+        interval: 200
+        repeat: true
+        running: macroController.playing
+        onTriggered: macroController.poll_status()
+    }
+
+    // Types the profile's boot keystroke macro once the guest is starting
+    Timer {
+        id: bootMacroTimer
+        interval: sessionController.boot_macro_delay
+        onTriggered: {
+            if (!inputController.type_macro(sessionController.boot_macro))
+                toast.show(qsTr("The boot keystroke macro is not valid; see the log"))
+        }
+    }
+
+    // Notices when text or a keystroke macro has all been typed into the guest
+    Timer {
+        interval: 200
+        repeat: true
+        running: inputController.typing
+        onTriggered: inputController.poll_typing()
+    }
+
+    // QML has no clipboard API; pasting into a hidden editor reads it
+    TextEdit {
+        id: hostClipboardReader
+        visible: false
+        textFormat: TextEdit.PlainText
+
+        function read() {
+            text = ""
+            paste()
+            let result = text
+            text = ""
+            return result
+        }
+    }
     
     // Update input controller when session state changes
     Connections {
         target: sessionController
         function onSession_runningChanged() {
             if (sessionController.session_running) {
-                inputController.driver_fd = sessionController.get_driver_fd()
+                inputController.set_driver(sessionController.get_driver_fd())
                 audioController.init_audio(sessionController.get_driver_fd())
                 if (audioController.audio_available && audioController.audio_enabled) {
                     audioController.start_playback()
                 }
+                midiController.start(sessionController.get_driver_fd())
+                gameportController.start(sessionController.get_driver_fd())
                 clipboardController.init_clipboard(sessionController.get_driver_fd())
                 networkController.init_network(sessionController.get_driver_fd())
                 if (networkController.network_enabled) {
                     networkController.apply_config()
                 }
                 driveMappingController.init_mappings(sessionController.get_driver_fd())
-                driveMappingController.apply_mappings()
+                let problems = sessionController.startup_problems === ""
+                    ? [] : sessionController.startup_problems.split("\n")
+                if (!driveMappingController.apply_mappings())
+                    problems.push(qsTr("Some shared folders could not be mapped"))
+                driveMappingController.refresh_usage(true)
+                statsController.init_stats(sessionController.get_driver_fd())
+                displayView.load_refresh_config()
+                historyController.start_recording()
+                // The boot CD was mounted by the session
+                if (sessionController.boot_cdrom !== "") {
+                    diskManager.cdrom_path = sessionController.boot_cdrom
+                    diskManager.cdrom_mounted = true
+                    mountIsoDialog.selectedIsoPath = sessionController.boot_cdrom
+                    mountIsoDialog.isMounted = true
+                }
+                // Media mounted by auto_mount; a CUE sheet is served from here
+                let cdrom = sessionController.startup_cdrom
+                if (cdrom !== "") {
+                    let mounted = true
+                    if (cdrom.toLowerCase().endsWith(".cue")) {
+                        mounted = diskManager.mount_iso(cdrom)
+                        if (!mounted)
+                            problems.push(qsTr("Cannot serve ") + cdrom)
+                    } else {
+                        diskManager.cdrom_path = cdrom
+                        diskManager.cdrom_mounted = true
+                    }
+                    mountIsoDialog.selectedIsoPath = mounted ? cdrom : ""
+                    mountIsoDialog.isMounted = mounted
+                }
+                if (sessionController.startup_floppy_a !== "") {
+                    diskManager.floppy_a_path = sessionController.startup_floppy_a
+                    diskManager.floppy_a_mounted = true
+                }
+                if (sessionController.startup_floppy_b !== "") {
+                    diskManager.floppy_b_path = sessionController.startup_floppy_b
+                    diskManager.floppy_b_mounted = true
+                }
+                if (problems.length > 0)
+                    toast.show(qsTr("Session started with problems: ") + problems.join("; "))
+                if (sessionController.boot_macro !== "")
+                    bootMacroTimer.restart()
             } else {
                 inputController.release_capture()
+                inputController.cancel_typing()
+                bootMacroTimer.stop()
+                macroController.stop_playing()
                 audioController.stop_playback()
+                midiController.stop()
+                gameportController.stop()
+                networkController.session_stopped()
+                statsController.reset_stats()
+                historyController.stop_recording()
             }
         }
     }
@@ -103,10 +770,30 @@ ApplicationWindow {
         onTriggered: audioController.poll_status()
     }
 
+    // MIDI controller for the guest's MPU-401 music
+    MidiController {
+        id: midiController
+    }
+
+    // Game port controller for host gamepad passthrough
+    GameportController {
+        id: gameportController
+    }
+
+    Timer {
+        interval: 500
+        repeat: true
+        running: sessionController.session_running && gameportController.gameport_enabled
+        onTriggered: gameportController.poll_status()
+    }
+
     // Clipboard controller for host/guest clipboard sync
     ClipboardController {
         id: clipboardController
         clipboard_enabled: configManager.get_clipboard_enabled()
+        share_images: configManager.get_clipboard_share_images()
+        share_rich_text: configManager.get_clipboard_share_rich_text()
+        code_page: configManager.get_code_page()
         host_to_guest: true
         guest_to_host: true
         
@@ -116,32 +803,18 @@ ApplicationWindow {
             }
         }
         
-        // When guest clipboard changes, update host clipboard
-        onGuest_clipboard_changed: (text) => {
-            // Use Qt.callLater to avoid reentrancy
-            Qt.callLater(function() {
-                if (text.length > 0) {
-                    // Note: In Qt5 QML, we need to use Clipboard API
-                    // This signal is emitted when guest has new content
-                    console.log("Guest clipboard:", text.substring(0, 50) + (text.length > 50 ? "..." : ""))
-                }
-            })
-        }
+        onTransfer_failed: (message) => toast.show(message)
     }
     
-    // Clipboard polling timer (check guest clipboard periodically)
-    Timer {
-        id: clipboardPollTimer
-        interval: 500  // 2 Hz - check for guest clipboard changes
-        repeat: true
-        running: sessionController.session_running && clipboardController.clipboard_enabled && clipboardController.guest_to_host
-        onTriggered: clipboardController.poll_guest_clipboard()
-    }
-
     // Network controller for virtual NIC management
     NetworkController {
         id: networkController
         network_enabled: configManager.get_network_enabled()
+        auto_tap: configManager.get_network_auto_tap()
+        tap_name: configManager.get_network_tap_name()
+        bridge_name: configManager.get_network_bridge()
+        create_bridge: configManager.get_network_create_bridge()
+        network_mode: configManager.get_network_mode()
         
         Component.onCompleted: {
             if (sessionController.session_running) {
@@ -158,20 +831,118 @@ ApplicationWindow {
         }
     }
 
+    // Recently used media, for the mount dialogs' lists
+    RecentFilesModel {
+        id: recentIsoFiles
+        category: "iso"
+    }
+
+    RecentFilesModel {
+        id: recentFloppyFiles
+        category: "floppy"
+    }
+
+    // Drive mappings as configured, for DriveMappingDialog's list
+    DriveMappingModel {
+        id: driveMappingModel
+    }
+
     // Drive mapping controller for host filesystem redirection
     DriveMappingController {
         id: driveMappingController
         
         Component.onCompleted: {
-            // Load default mappings
-            load_mappings_json(get_default_mappings_json())
+            auto_reapply = configManager.get_reapply_drive_mappings()
+
+            // The configured mappings (the model is filled here so that it
+            // is before the controller reads it)
+            driveMappingModel.load()
+            load_mappings_json(driveMappingModel.to_json())
             
             if (sessionController.session_running) {
                 init_mappings(sessionController.get_driver_fd())
             }
         }
+
+        onMapping_availability_changed: (driveLetter, hostPath, available) => {
+            console.log("Drive", driveLetter, hostPath, available ? "is available" : "is unavailable")
+        }
     }
     
+    // USB sticks and other removable drives, mapped to free drive letters
+    RemovableMediaController {
+        id: removableMediaController
+
+        Component.onCompleted: auto_map = configManager.get_auto_map_removable_media()
+
+        // Map a volume to the next free letter
+        function mapVolume(mountPoint) {
+            let letter = driveMappingController.get_available_letters().split(",")[0]
+            if (!letter) {
+                console.warn("No free drive letter for", mountPoint)
+                return
+            }
+            driveMappingController.add_mapping(letter, mountPoint, false)
+            if (sessionController.session_running)
+                driveMappingController.apply_mappings()
+        }
+
+        onMedia_inserted: (device, mountPoint, label) => {
+            if (driveMappingController.find_mapping_for_path(mountPoint) !== "")
+                return
+            if (auto_map) {
+                mapVolume(mountPoint)
+            } else {
+                removableMediaDialog.mountPoint = mountPoint
+                removableMediaDialog.label = label
+                removableMediaDialog.open()
+            }
+        }
+
+        onMedia_removed: (device, mountPoint) => {
+            if (removableMediaDialog.visible && removableMediaDialog.mountPoint === mountPoint)
+                removableMediaDialog.close()
+            let letter = driveMappingController.find_mapping_for_path(mountPoint)
+            if (letter !== "")
+                driveMappingController.remove_mapping(letter)
+        }
+    }
+
+    Timer {
+        id: removableMediaTimer
+        interval: 1000
+        repeat: true
+        running: removableMediaController.available
+        onTriggered: removableMediaController.poll()
+    }
+
+    // Notice mapped host directories coming and going (inotify, so cheap)
+    Timer {
+        id: drivePathTimer
+        interval: 1000
+        repeat: true
+        running: driveMappingController.mapping_count > 0
+        onTriggered: driveMappingController.poll_paths()
+    }
+
+    // Follow host daylight saving changes on mapped drives' file times
+    Timer {
+        id: driveTimestampTimer
+        interval: 60000
+        repeat: true
+        running: sessionController.session_running && driveMappingController.mapping_count > 0
+        onTriggered: driveMappingController.refresh_timestamps()
+    }
+
+    // Re-measure size-limited drives, catching files deleted since
+    Timer {
+        id: driveUsageTimer
+        interval: 300000
+        repeat: true
+        running: sessionController.session_running && driveMappingController.mapping_count > 0
+        onTriggered: driveMappingController.refresh_usage(true)
+    }
+
     // Network status polling (slow - just for stats)
     Timer {
         id: networkStatusTimer
@@ -181,22 +952,135 @@ ApplicationWindow {
         onTriggered: networkController.poll_status()
     }
 
-    // Display refresh timer (60 FPS when running)
+    // Session statistics for the status bar
+    StatsController {
+        id: statsController
+
+        Component.onCompleted: {
+            if (sessionController.session_running) {
+                init_stats(sessionController.get_driver_fd())
+            }
+        }
+    }
+
+    // Stats polling timer (uptime and disk activity)
+    Timer {
+        id: statsPollTimer
+        interval: 500  // 2 Hz - fast enough for disk activity blinks
+        repeat: true
+        running: sessionController.session_running
+        onTriggered: statsController.poll_stats()
+    }
+
+    // Searchable record of the guest screen ([history] config section)
+    HistoryController {
+        id: historyController
+    }
+
+    // Driver messages and the frontend's log, collected while the console
+    // is closed too so it opens with recent history
+    LogController {
+        id: logController
+    }
+
+    Timer {
+        id: logPollTimer
+        interval: logConsoleDialog.opened ? 250 : 1000
+        repeat: true
+        running: true
+        onTriggered: logController.poll()
+    }
+
+    Timer {
+        id: historyTextTimer
+        interval: historyController.text_interval
+        repeat: true
+        running: historyController.recording && sessionController.text_mode
+        onTriggered: historyController.capture_text()
+    }
+
+    Timer {
+        id: historyThumbnailTimer
+        interval: historyController.thumbnail_interval
+        repeat: true
+        running: historyController.recording
+        onTriggered: {
+            let path = historyController.thumbnail_path()
+            if (path === "")
+                return
+            displayImage.grabToImage(function(result) {
+                if (result.saveToFile(path)) {
+                    historyController.add_thumbnail(path)
+                }
+            }, Qt.size(320, 240))
+        }
+    }
+
+    // Display presentation mode ("immediate" or "vsync")
+    property string presentationMode: configManager.get_presentation_mode()
+
+    // Follow display mode changes and upload the next frame
+    function refreshDisplay() {
+        sessionController.poll_display()
+        displayImage.update()
+    }
+
+    // Display refresh timer: the active rate while the guest draws, the
+    // idle rate once the screen has been still. With vsync it only runs
+    // while idle, to notice the guest drawing again.
     Timer {
         id: displayRefreshTimer
-        interval: 16  // ~60 FPS
+        interval: displayView.refresh_interval
         repeat: true
         running: sessionController.session_running
+                 && (window.presentationMode === "immediate" || displayView.display_idle)
         onTriggered: {
-            sessionController.poll_display()
-            displayImage.source = ""  // Force reload
-            displayImage.source = "image://framebuffer/frame?" + Date.now()
+            displayView.pace_frame()
+            refreshDisplay()
         }
     }
 
+    // Vsync-aligned refresh: upload once per presented frame. Updating the
+    // item schedules the next frame, so this keeps pace with the render loop
+    // and a frame is never swapped in halfway through scan-out. Once the
+    // screen is idle the loop is left to stop and the timer above takes over.
+    Connections {
+        target: window
+        enabled: sessionController.session_running && window.presentationMode === "vsync"
+        function onFrameSwapped() {
+            Qt.callLater(vsyncRefresh)
+        }
+    }
+
+    function vsyncRefresh() {
+        displayView.pace_frame()
+        if (!displayView.display_idle)
+            refreshDisplay()
+    }
+
     // Save config when window closes
     onClosing: (close) => {
+        // general.save_state_on_exit: the guest is suspended, not lost
+        if (sessionController.session_running && !window.closeConfirmed
+                && configManager.get_save_state_on_exit() && !sessionController.ephemeral
+                && !sessionController.restoring_state) {
+            close.accepted = false
+            window.closeAfterShutdown = true
+            sessionController.save_state_and_stop()
+            return
+        }
+        if (sessionController.session_running && !window.closeConfirmed
+                && configManager.get_confirm_on_close()) {
+            close.accepted = false
+            closeSessionDialog.open()
+            return
+        }
         configManager.save()
+        if (window.visibility === Window.Windowed) {
+            mainWindow.save_window_geometry(window.screen.name, window.x, window.y,
+                window.width, window.height)
+        }
+        networkController.release_tap()
     }
 
     // Global keyboard shortcuts
@@ -215,7 +1099,56 @@ ApplicationWindow {
         onActivated: resetAction.trigger()
     }
 
+    Shortcut {
+        sequence: "Ctrl+K"
+        onActivated: commandPalette.open()
+    }
+
+    // F11 has to work while fullscreen hides the menu bar
+    Shortcut {
+        sequence: "F11"
+        onActivated: window.toggleFullscreen()
+    }
+
+    header: ToolBar {
+        visible: mainWindow.menu_visible
+
+        RowLayout {
+            anchors.fill: parent
+            spacing: 4
+
+            ToolButton {
+                text: sessionController.session_running ? qsTr("Stop") : qsTr("Start")
+                enabled: sessionController.session_running ? stopAction.enabled : startAction.enabled
+                onClicked: sessionController.session_running ? stopAction.trigger() : startAction.trigger()
+            }
+
+            ToolSeparator {}
+
+            // Writes go to throwaway overlays, deleted when the session stops
+            ToolButton {
+                id: ephemeralButton
+                text: qsTr("Don't Save Changes")
+                checkable: true
+                checked: sessionController.ephemeral
+                enabled: !sessionController.session_running && !sessionController.session_starting
+                onToggled: sessionController.ephemeral = checked
+                ToolTip.visible: hovered
+                ToolTip.text: qsTr("Discard everything written to the disks and floppies when the session stops")
+            }
+
+            Label {
+                visible: sessionController.session_running && sessionController.ephemeral
+                text: qsTr("Changes will be discarded")
+                color: "#b06000"
+            }
+
+            Item { Layout.fillWidth: true }
+        }
+    }
+
     menuBar: MenuBar {
+        visible: mainWindow.menu_visible
         // Custom delegate for menu bar items to add padding (Qt5 fix)
         delegate: MenuBarItem {
             id: menuBarItem
@@ -242,7 +1175,8 @@ ApplicationWindow {
             Action {
                 id: quitAction
                 text: qsTr("&Quit") + "\t" + "Ctrl+Q"
-                onTriggered: Qt.quit()
+                // Through onClosing, so a running session is confirmed
+                onTriggered: window.close()
             }
         }
 
@@ -251,11 +1185,19 @@ ApplicationWindow {
             Action {
                 id: startAction
                 text: qsTr("&Start") + "\t" + "Ctrl+R"
-                enabled: !sessionController.session_running && !sessionController.session_starting && sessionController.driver_loaded
+                enabled: !sessionController.session_running && !sessionController.session_starting
+                    && !sessionController.session_foreign && sessionController.driver_loaded
                 onTriggered: {
                     sessionController.start_session()
                 }
             }
+            Action {
+                text: qsTr("Take &Over Session")
+                enabled: sessionController.session_foreign
+                onTriggered: {
+                    sessionController.adopt_session()
+                }
+            }
             Action {
                 id: resetAction
                 text: qsTr("&Reset") + "\t" + "Ctrl+Shift+R"
@@ -266,12 +1208,121 @@ ApplicationWindow {
             }
             MenuSeparator {}
             Action {
+                id: saveStateAction
+                text: sessionController.saving_state ? qsTr("Saving State...") : qsTr("Save State and S&top")
+                enabled: sessionController.session_running && !sessionController.ephemeral
+                    && !sessionController.saving_state && !sessionController.restoring_state
+                onTriggered: {
+                    sessionController.save_state_and_stop()
+                }
+            }
+            Action {
+                id: shutDownAction
+                text: sessionController.shutting_down ? qsTr("Shutting Down...") : qsTr("S&hut Down")
+                enabled: sessionController.session_running && !sessionController.shutting_down
+                onTriggered: {
+                    sessionController.request_guest_shutdown()
+                }
+            }
+            Action {
+                id: stopAction
                 text: qsTr("S&top")
                 enabled: sessionController.session_running
                 onTriggered: {
                     sessionController.stop_session()
                 }
             }
+            MenuSeparator {}
+            Menu {
+                id: bootMenu
+                title: qsTr("&Boot From")
+
+                // Takes effect at the next start
+                property string bootDevice: configManager.get_boot_device()
+                property string cdProblem: ""
+
+                onAboutToShow: cdProblem = sessionController.get_boot_cd_problem()
+
+                function select(device) {
+                    configManager.set_boot_device_value(device)
+                    configManager.save()
+                    bootDevice = device
+                }
+
+                ActionGroup { id: bootGroup }
+                Action {
+                    text: qsTr("&Default (C:, then CD-ROM)")
+                    checkable: true
+                    checked: bootMenu.bootDevice === "default"
+                    ActionGroup.group: bootGroup
+                    onTriggered: bootMenu.select("default")
+                }
+                Action {
+                    text: qsTr("&Hard Disk")
+                    checkable: true
+                    checked: bootMenu.bootDevice === "hard_disk"
+                    ActionGroup.group: bootGroup
+                    onTriggered: bootMenu.select("hard_disk")
+                }
+                Action {
+                    text: bootMenu.cdProblem === "" ? qsTr("&CD-ROM") : qsTr("&CD-ROM (not bootable)")
+                    checkable: true
+                    checked: bootMenu.bootDevice === "cdrom"
+                    ActionGroup.group: bootGroup
+                    onTriggered: {
+                        bootMenu.select("cdrom")
+                        bootMenu.cdProblem = sessionController.get_boot_cd_problem()
+                    }
+                }
+                Action {
+                    text: qsTr("&Floppy")
+                    checkable: true
+                    checked: bootMenu.bootDevice === "floppy"
+                    ActionGroup.group: bootGroup
+                    onTriggered: bootMenu.select("floppy")
+                }
+            }
+            Menu {
+                id: cardMenu
+                title: qsTr("&Card")
+                // Only machines with more than one card need to choose
+                enabled: cardCount > 1 && !sessionController.session_running
+                    && !sessionController.session_starting
+
+                property int cardCount: 0
+
+                function refresh() {
+                    cardsModel.clear()
+                    let cards = JSON.parse(sessionController.list_cards())
+                    for (let i = 0; i < cards.length; i++) {
+                        cardsModel.append(cards[i])
+                    }
+                    cardCount = cards.length
+                }
+
+                Component.onCompleted: refresh()
+                onAboutToShow: refresh()
+
+                ListModel { id: cardsModel }
+                ActionGroup { id: cardGroup }
+
+                Instantiator {
+                    model: cardsModel
+                    delegate: Action {
+                        text: model.name + "  (" + model.path + ")"
+                        checkable: true
+                        checked: sessionController.card === model.index
+                        ActionGroup.group: cardGroup
+                        onTriggered: sessionController.select_card(model.index)
+                    }
+                    onObjectAdded: (index, object) => cardMenu.insertAction(index, object)
+                    onObjectRemoved: (index, object) => cardMenu.removeAction(object)
+                }
+            }
+            Action {
+                text: qsTr("B&IOS Image...")
+                onTriggered: biosDialog.open()
+            }
         }
 
         Menu {
@@ -334,6 +1385,21 @@ ApplicationWindow {
                     text: qsTr("&Mount ISO Image...")
                     onTriggered: mountIsoDialog.open()
                 }
+                Action {
+                    text: qsTr("Mount &Guest Tools")
+                    onTriggered: {
+                        var result = JSON.parse(diskManager.mount_guest_tools())
+                        if (result.error !== undefined) {
+                            toast.show(result.error)
+                        } else {
+                            mountIsoDialog.isMounted = true
+                            mountIsoDialog.selectedIsoPath = result.path
+                            toast.show(result.missing.length > 0
+                                       ? qsTr("Guest tools mounted; not found: %1").arg(result.missing.join(", "))
+                                       : qsTr("Guest tools mounted"))
+                        }
+                    }
+                }
                 Action {
                     text: qsTr("&Eject")
                     enabled: diskManager.cdrom_mounted
@@ -357,6 +1423,134 @@ ApplicationWindow {
                     checkable: true
                     checked: true
                 }
+                MenuSeparator {}
+                Action {
+                    text: qsTr("Send &Wake-on-LAN...")
+                    onTriggered: wakeOnLanDialog.open()
+                }
+            }
+            Menu {
+                title: qsTr("Audio &Latency")
+                ActionGroup { id: latencyGroup }
+                Action {
+                    text: qsTr("&Low (50 ms)")
+                    checkable: true
+                    checked: audioController.target_latency_ms === 50
+                    ActionGroup.group: latencyGroup
+                    onTriggered: audioController.set_latency_ms(50)
+                }
+                Action {
+                    text: qsTr("&Normal (100 ms)")
+                    checkable: true
+                    checked: audioController.target_latency_ms === 100
+                    ActionGroup.group: latencyGroup
+                    onTriggered: audioController.set_latency_ms(100)
+                }
+                Action {
+                    text: qsTr("&Safe (250 ms)")
+                    checkable: true
+                    checked: audioController.target_latency_ms === 250
+                    ActionGroup.group: latencyGroup
+                    onTriggered: audioController.set_latency_ms(250)
+                }
+                Action {
+                    text: qsTr("&Very Safe (500 ms)")
+                    checkable: true
+                    checked: audioController.target_latency_ms === 500
+                    ActionGroup.group: latencyGroup
+                    onTriggered: audioController.set_latency_ms(500)
+                }
+            }
+            Menu {
+                id: midiMenu
+                title: qsTr("&MIDI Output")
+                property var ports: []
+                onAboutToShow: ports = JSON.parse(midiController.sequencer_ports())
+
+                ActionGroup { id: midiGroup }
+                Action {
+                    text: qsTr("&Off")
+                    checkable: true
+                    checked: midiController.midi_output === "off"
+                    ActionGroup.group: midiGroup
+                    onTriggered: midiController.set_output("off", "")
+                }
+                Action {
+                    text: qsTr("&Built-in Synthesizer")
+                    checkable: true
+                    checked: midiController.midi_output === "softsynth"
+                    ActionGroup.group: midiGroup
+                    onTriggered: midiController.set_output("softsynth", "")
+                }
+                Action {
+                    text: qsTr("&First Sequencer Synthesizer")
+                    checkable: true
+                    checked: midiController.midi_output === "sequencer" && midiController.midi_port === ""
+                    ActionGroup.group: midiGroup
+                    onTriggered: midiController.set_output("sequencer", "")
+                }
+                MenuSeparator { visible: midiMenu.ports.length > 0 }
+                Instantiator {
+                    model: midiMenu.ports
+                    delegate: Action {
+                        text: modelData.name + "  (" + modelData.address + ")"
+                        checkable: true
+                        checked: midiController.midi_output === "sequencer" &&
+                                 midiController.midi_port === modelData.address
+                        ActionGroup.group: midiGroup
+                        onTriggered: midiController.set_output("sequencer", modelData.address)
+                    }
+                    // After the three outputs and the separator
+                    onObjectAdded: (index, object) => midiMenu.insertAction(index + 4, object)
+                    onObjectRemoved: (index, object) => midiMenu.removeAction(object)
+                }
+            }
+            Menu {
+                id: gameportMenu
+                title: qsTr("&Game Port")
+                property var gamepads: []
+                onAboutToShow: gamepads = JSON.parse(gameportController.list_gamepads())
+
+                Action {
+                    text: gameportController.gamepad_connected
+                          ? qsTr("&Enabled (%1)").arg(gameportController.device_name)
+                          : qsTr("&Enabled")
+                    checkable: true
+                    checked: gameportController.gameport_enabled
+                    onTriggered: gameportController.set_enabled(checked)
+                }
+                MenuSeparator {}
+                ActionGroup { id: gamepadGroup }
+                Action {
+                    text: qsTr("&Any Gamepad")
+                    checkable: true
+                    checked: gameportController.device === ""
+                    ActionGroup.group: gamepadGroup
+                    onTriggered: gameportController.set_gamepad("")
+                }
+                Instantiator {
+                    model: gameportMenu.gamepads
+                    delegate: Action {
+                        text: modelData.name
+                        checkable: true
+                        checked: gameportController.device === modelData.name
+                        ActionGroup.group: gamepadGroup
+                        onTriggered: gameportController.set_gamepad(modelData.name)
+                    }
+                    // After the toggle, the separator and "Any Gamepad"
+                    onObjectAdded: (index, object) => gameportMenu.insertAction(index + 3, object)
+                    onObjectRemoved: (index, object) => gameportMenu.removeAction(object)
+                }
+                MenuSeparator {}
+                Action {
+                    text: qsTr("&Calibrate Center")
+                    enabled: gameportController.gamepad_connected
+                    onTriggered: gameportController.calibrate_center()
+                }
+                Action {
+                    text: qsTr("&Reset Calibration")
+                    onTriggered: gameportController.reset_calibration()
+                }
             }
             Action {
                 text: qsTr("&Shared Folders...")
@@ -375,14 +1569,8 @@ ApplicationWindow {
                 id: fullscreenMenuItem
                 text: qsTr("&Fullscreen")
                 checkable: true
-                checked: window.visibility === 5  // Qt.WindowFullScreen = 5
-                onTriggered: {
-                    if (window.visibility === 5) {
-                        window.showNormal()
-                    } else {
-                        window.showFullScreen()
-                    }
-                }
+                checked: mainWindow.fullscreen
+                onTriggered: window.toggleFullscreen()
                 // Show checkbox indicator for Qt5 compatibility
                 indicator: Rectangle {
                     implicitWidth: 16
@@ -405,60 +1593,65 @@ ApplicationWindow {
                 }
                 leftPadding: 32
             }
-            Shortcut {
-                sequence: "F11"
-                onActivated: {
-                    if (window.visibility === 5) {
-                        window.showNormal()
-                    } else {
-                        window.showFullScreen()
-                    }
-                }
-            }
             MenuSeparator {}
             Menu {
                 id: scalingMenu
                 title: qsTr("&Scaling")
                 
-                // Scaling mode: 0 = None (1:1), 1 = Fit to Window, 2 = Integer Scaling
-                property int scalingMode: {
-                    if (configManager.get_integer_scaling()) return 2
-                    if (configManager.get_maintain_aspect_ratio()) return 1
-                    return 0
+                // "none", "fit", "stretch" or "fixed"; integer scaling is fit in whole steps
+                property string scalingMode: displayView.scaling_mode === "fit" && displayView.integer_scaling
+                                             ? "integer" : displayView.scaling_mode
+
+                function apply(mode, integer, aspect) {
+                    configManager.set_scaling_mode_value(mode)
+                    configManager.set_integer_scaling_value(integer)
+                    configManager.set_maintain_aspect_ratio_value(aspect)
+                    displayView.loadScaling()
                 }
-                
+
                 Action {
                     text: qsTr("&None (1:1)")
                     checkable: true
-                    checked: scalingMenu.scalingMode === 0
-                    onTriggered: {
-                        configManager.set_integer_scaling_value(false)
-                        configManager.set_maintain_aspect_ratio_value(false)
-                    }
+                    checked: scalingMenu.scalingMode === "none"
+                    onTriggered: scalingMenu.apply("none", false, true)
                 }
                 Action {
                     text: qsTr("&Fit to Window")
                     checkable: true
-                    checked: scalingMenu.scalingMode === 1
-                    onTriggered: {
-                        configManager.set_integer_scaling_value(false)
-                        configManager.set_maintain_aspect_ratio_value(true)
-                    }
+                    checked: scalingMenu.scalingMode === "fit"
+                    onTriggered: scalingMenu.apply("fit", false, true)
                 }
                 Action {
                     text: qsTr("&Integer Scaling")
                     checkable: true
-                    checked: scalingMenu.scalingMode === 2
-                    onTriggered: {
-                        configManager.set_integer_scaling_value(true)
-                        configManager.set_maintain_aspect_ratio_value(false)
-                    }
+                    checked: scalingMenu.scalingMode === "integer"
+                    onTriggered: scalingMenu.apply("fit", true, true)
+                }
+                Action {
+                    text: qsTr("&Stretch")
+                    checkable: true
+                    checked: scalingMenu.scalingMode === "stretch"
+                    onTriggered: scalingMenu.apply("stretch", false, false)
+                }
+                Action {
+                    text: qsTr("Fi&xed Scale (%1x)").arg(configManager.get_scale_factor())
+                    checkable: true
+                    checked: scalingMenu.scalingMode === "fixed"
+                    onTriggered: scalingMenu.apply("fixed", false, true)
                 }
             }
             Action {
                 text: qsTr("&Display Settings...")
                 onTriggered: displaySettingsDialog.open()
             }
+            Action {
+                text: qsTr("Session &History...")
+                onTriggered: historyDialog.open()
+            }
+            Action {
+                text: qsTr("&Log Console...")
+                onTriggered: logConsoleDialog.open()
+            }
             MenuSeparator {}
             Action {
                 text: qsTr("&Status Bar")
@@ -497,10 +1690,6 @@ ApplicationWindow {
                 }
                 leftPadding: 32
             }
-            Shortcut {
-                sequence: "Right Ctrl"
-                onActivated: inputController.toggle_keyboard_capture()
-            }
             MenuItem {
                 text: qsTr("&Mouse Capture")
                 checkable: true
@@ -536,11 +1725,83 @@ ApplicationWindow {
                     inputController.send_ctrl_alt_del()
                 }
             }
-            Action {
-                text: qsTr("Send Ctrl+Alt+&Backspace")
-                enabled: sessionController.session_running
-                onTriggered: {
-                    inputController.send_ctrl_alt_backspace()
+            Action {
+                text: qsTr("Send Ctrl+Alt+&Backspace")
+                enabled: sessionController.session_running
+                onTriggered: {
+                    inputController.send_ctrl_alt_backspace()
+                }
+            }
+            Action {
+                text: inputController.typing ? qsTr("Stop &Typing Clipboard")
+                                             : qsTr("&Type Clipboard as Keystrokes")
+                enabled: sessionController.session_running
+                onTriggered: {
+                    if (inputController.typing) {
+                        inputController.cancel_typing()
+                        return
+                    }
+                    let skipped = inputController.type_text(hostClipboardReader.read(), 0)
+                    if (skipped > 0)
+                        toast.show(qsTr("Left out %1 characters the guest keyboard cannot type").arg(skipped))
+                }
+            }
+            MenuSeparator {}
+            Action {
+                text: macroController.recording
+                      ? qsTr("Stop &Recording Macro (%1 keys)...").arg(macroController.recorded_keys)
+                      : qsTr("&Record Macro")
+                enabled: sessionController.session_running || macroController.recording
+                onTriggered: {
+                    if (macroController.recording) {
+                        macroNameDialog.open()
+                        return
+                    }
+                    macroController.start_recording()
+                    toast.show(qsTr("Recording the keys typed into the guest while it has the keyboard"))
+                }
+            }
+            Menu {
+                id: playMacroMenu
+                title: qsTr("&Play Macro")
+                enabled: sessionController.session_running
+                property var macros: []
+                onAboutToShow: macros = JSON.parse(macroController.list_macros())
+
+                Action {
+                    text: qsTr("&Stop Playing %1").arg(macroController.playing_name)
+                    enabled: macroController.playing
+                    onTriggered: macroController.stop_playing()
+                }
+                MenuSeparator {}
+                Instantiator {
+                    model: playMacroMenu.macros
+                    delegate: Action {
+                        text: modelData.name + "  (" + modelData.seconds.toFixed(1) + " s)"
+                        onTriggered: {
+                            if (!macroController.play(modelData.name))
+                                toast.show(qsTr("Could not play ") + modelData.name)
+                        }
+                    }
+                    // After the stop action and the separator
+                    onObjectAdded: (index, object) => playMacroMenu.insertAction(index + 2, object)
+                    onObjectRemoved: (index, object) => playMacroMenu.removeAction(object)
+                }
+            }
+            Menu {
+                id: deleteMacroMenu
+                title: qsTr("D&elete Macro")
+                property var macros: []
+                onAboutToShow: macros = JSON.parse(macroController.list_macros())
+
+                Instantiator {
+                    model: deleteMacroMenu.macros
+                    delegate: Action {
+                        text: modelData.name
+                        onTriggered: macroController.delete_macro(modelData.name)
+                    }
+                    onObjectAdded: (index, object) => deleteMacroMenu.insertAction(index, object)
+                    onObjectRemoved: (index, object) => deleteMacroMenu.removeAction(object)
                 }
             }
             MenuSeparator {}
@@ -555,6 +1816,250 @@ ApplicationWindow {
         }
     }
 
+    // Shown when a disk operation runs out of host disk space
+    Dialog {
+        id: diskSpaceDialog
+        title: "Not Enough Disk Space"
+        anchors.centerIn: parent
+        modal: true
+        standardButtons: Dialog.Ok
+
+        property string path: ""
+        property int requiredMb: 0
+        property int availableMb: 0
+
+        ColumnLayout {
+            spacing: 8
+
+            Text {
+                text: "There is not enough free space to write\n" + diskSpaceDialog.path
+                font.pixelSize: 12
+                color: palette.text
+            }
+
+            Text {
+                text: "Required: " + diskSpaceDialog.requiredMb + " MB, available: "
+                      + diskSpaceDialog.availableMb + " MB.\n"
+                      + "Free at least " + Math.max(0, diskSpaceDialog.requiredMb - diskSpaceDialog.availableMb)
+                      + " MB or choose another location."
+                font.pixelSize: 12
+                color: palette.text
+                opacity: 0.8
+            }
+        }
+    }
+
+    // Offers a removable drive the host mounted to the guest
+    Dialog {
+        id: removableMediaDialog
+        title: "Removable Drive"
+        anchors.centerIn: parent
+        modal: true
+        standardButtons: Dialog.Yes | Dialog.No
+
+        property string mountPoint: ""
+        property string label: ""
+
+        ColumnLayout {
+            spacing: 8
+
+            Text {
+                text: "Map " + removableMediaDialog.label + " to the next free drive letter?\n"
+                      + removableMediaDialog.mountPoint
+                font.pixelSize: 12
+                color: palette.text
+            }
+
+            CheckBox {
+                id: autoMapCheck
+                text: "Always map removable drives without asking"
+                checked: false
+            }
+        }
+
+        onAccepted: {
+            if (autoMapCheck.checked) {
+                removableMediaController.auto_map = true
+                configManager.set_auto_map_removable_media_value(true)
+                configManager.save()
+            }
+            removableMediaController.mapVolume(mountPoint)
+        }
+    }
+
+    // Shown when a disk image could not be created
+    Dialog {
+        id: diskCreateFailedDialog
+        title: "Cannot Create Disk Image"
+        anchors.centerIn: parent
+        modal: true
+        standardButtons: Dialog.Ok
+
+        property string path: ""
+        property string message: ""
+
+        ColumnLayout {
+            spacing: 8
+
+            Text {
+                text: "Could not create\n" + diskCreateFailedDialog.path
+                font.pixelSize: 12
+                color: palette.text
+            }
+
+            Text {
+                text: diskCreateFailedDialog.message
+                font.pixelSize: 12
+                color: palette.text
+                opacity: 0.8
+                wrapMode: Text.WordWrap
+                Layout.maximumWidth: 400
+            }
+        }
+    }
+
+    // Saving or restoring the guest state (general.save_state_on_exit)
+    Dialog {
+        id: stateTaskDialog
+        title: sessionController.saving_state ? "Saving Guest State" : "Resuming Guest"
+        anchors.centerIn: parent
+        modal: true
+        closePolicy: Popup.NoAutoClose
+        visible: sessionController.saving_state || sessionController.restoring_state
+
+        ColumnLayout {
+            spacing: 8
+
+            Label {
+                text: sessionController.state_step
+            }
+            ProgressBar {
+                Layout.preferredWidth: 300
+                from: 0
+                to: 100
+                value: sessionController.state_percent
+            }
+        }
+
+        footer: DialogButtonBox {
+            Button {
+                text: "Cancel"
+                DialogButtonBox.buttonRole: DialogButtonBox.RejectRole
+                onClicked: sessionController.cancel_state_task()
+            }
+        }
+    }
+
+    // Closing the window while the session runs (general.confirm_on_close)
+    Dialog {
+        id: closeSessionDialog
+        title: "Session Running"
+        anchors.centerIn: parent
+        modal: true
+
+        Text {
+            text: "The session is still running. Shut the guest down first, or stop\n"
+                  + "it now and lose anything it has not saved?"
+            font.pixelSize: 12
+            color: palette.text
+        }
+
+        footer: DialogButtonBox {
+            Button {
+                text: "Shut Down"
+                DialogButtonBox.buttonRole: DialogButtonBox.AcceptRole
+                onClicked: {
+                    window.closeAfterShutdown = true
+                    sessionController.request_guest_shutdown()
+                    closeSessionDialog.close()
+                }
+            }
+            Button {
+                text: "Stop Now"
+                DialogButtonBox.buttonRole: DialogButtonBox.DestructiveRole
+                onClicked: {
+                    closeSessionDialog.close()
+                    sessionController.stop_session()
+                    window.closeConfirmed = true
+                    window.close()
+                }
+            }
+            Button {
+                text: "Cancel"
+                DialogButtonBox.buttonRole: DialogButtonBox.RejectRole
+                onClicked: closeSessionDialog.close()
+            }
+        }
+    }
+
+    // Wake another machine on the guest's LAN
+    Dialog {
+        id: wakeOnLanDialog
+        title: "Send Wake-on-LAN"
+        anchors.centerIn: parent
+        modal: true
+        standardButtons: Dialog.Ok | Dialog.Cancel
+
+        onOpened: wolMacField.forceActiveFocus()
+        onAccepted: networkController.send_wol(wolMacField.text)
+
+        ColumnLayout {
+            spacing: 8
+
+            Text {
+                text: "MAC address of the machine to wake:"
+                font.pixelSize: 12
+                color: palette.text
+            }
+
+            TextField {
+                id: wolMacField
+                inputMask: "HH:HH:HH:HH:HH:HH;_"
+                font.family: "monospace"
+                Layout.preferredWidth: 160
+            }
+        }
+    }
+
+    // Names a keystroke macro when its recording stops
+    Dialog {
+        id: macroNameDialog
+        title: "Save Keystroke Macro"
+        anchors.centerIn: parent
+        modal: true
+        standardButtons: Dialog.Save | Dialog.Discard
+
+        onOpened: {
+            macroNameField.text = ""
+            macroNameField.forceActiveFocus()
+        }
+        onAccepted: {
+            if (!macroController.stop_recording(macroNameField.text))
+                toast.show(qsTr("Nothing was recorded, or the macro has no name"))
+        }
+        onDiscarded: {
+            macroController.cancel_recording()
+            close()
+        }
+        onRejected: macroController.cancel_recording()
+
+        ColumnLayout {
+            spacing: 8
+
+            Text {
+                text: "Name for the " + macroController.recorded_keys + " keys recorded:"
+                font.pixelSize: 12
+                color: palette.text
+            }
+
+            TextField {
+                id: macroNameField
+                Layout.preferredWidth: 240
+                onAccepted: macroNameDialog.accept()
+            }
+        }
+    }
+
     // About dialog
     Dialog {
         id: aboutDialog
@@ -611,34 +2116,30 @@ ApplicationWindow {
             Layout.fillWidth: true
             Layout.fillHeight: true
             color: "black"
+            clip: true
+            onWidthChanged: displayView.place()
+            onHeightChanged: displayView.place()
 
-            // Framebuffer display image
-            Image {
+            // Framebuffer display, uploaded straight from the driver mapping
+            FramebufferItem {
                 id: displayImage
-                anchors.centerIn: parent
-                width: sessionController.display_width * displayScale
-                height: sessionController.display_height * displayScale
-                source: sessionController.session_running ? "image://framebuffer/frame" : ""
-                cache: false
-                smooth: !configManager.get_integer_scaling()
+                x: displayView.target_x
+                y: displayView.target_y
+                width: displayView.target_width
+                height: displayView.target_height
+                smooth: !displayView.integer_scaling && displayView.scaling_mode !== "fixed"
                 visible: sessionController.session_running
 
-                // Calculate scale based on settings
-                property real displayScale: {
-                    if (configManager.get_integer_scaling()) {
-                        // Integer scaling - find largest integer that fits
-                        var xScale = Math.floor(displayOutput.width / sessionController.display_width)
-                        var yScale = Math.floor(displayOutput.height / sessionController.display_height)
-                        return Math.max(1, Math.min(xScale, yScale))
-                    } else if (configManager.get_maintain_aspect_ratio()) {
-                        // Fit to window maintaining aspect ratio
-                        var xRatio = displayOutput.width / sessionController.display_width
-                        var yRatio = displayOutput.height / sessionController.display_height
-                        return Math.min(xRatio, yRatio)
-                    } else {
-                        // Stretch to fill (handled by anchors.fill instead)
-                        return 1.0
-                    }
+                // CRT post-processing, drawn through the shader in shaders/
+                layer.enabled: displayView.crt_effect !== 0
+                layer.effect: ShaderEffect {
+                    property size sourceSize: Qt.size(displayView.source_width, displayView.source_height)
+                    property size targetSize: Qt.size(displayView.target_width, displayView.target_height)
+                    property int effect: displayView.crt_effect
+                    property real scanlineStrength: displayView.scanline_strength
+                    property real maskIntensity: displayView.mask_intensity
+                    property real curvature: displayView.curvature
+                    fragmentShader: displayView.crt_shader
                 }
             }
 
@@ -654,15 +2155,15 @@ ApplicationWindow {
                     width: 80
                     height: 80
                     radius: 40
-                    color: sessionController.driver_loaded ? "#2a4a2a" : "#4a2a2a"
-                    border.color: sessionController.driver_loaded ? "#4a8a4a" : "#8a4a4a"
+                    color: sessionController.driver_loaded || sessionController.driver_probing ? "#2a4a2a" : "#4a2a2a"
+                    border.color: sessionController.driver_loaded || sessionController.driver_probing ? "#4a8a4a" : "#8a4a4a"
                     border.width: 2
 
                     Text {
                         anchors.centerIn: parent
-                        text: sessionController.driver_loaded ? "✓" : "✗"
+                        text: sessionController.driver_probing ? "…" : sessionController.driver_loaded ? "✓" : "✗"
                         font.pixelSize: 40
-                        color: sessionController.driver_loaded ? "#6aba6a" : "#ba6a6a"
+                        color: sessionController.driver_loaded || sessionController.driver_probing ? "#6aba6a" : "#ba6a6a"
                     }
                 }
 
@@ -672,13 +2173,15 @@ ApplicationWindow {
                     text: {
                         if (sessionController.session_starting) {
                             return "Starting session..."
+                        } else if (sessionController.driver_probing) {
+                            return "Checking for SunPCi Driver..."
                         } else if (!sessionController.driver_loaded) {
                             return "SunPCi Driver Not Loaded"
                         } else {
                             return "Ready to Start"
                         }
                     }
-                    color: sessionController.driver_loaded ? "#888888" : "#aa6666"
+                    color: sessionController.driver_loaded || sessionController.driver_probing ? "#888888" : "#aa6666"
                     font.pixelSize: 18
                     font.bold: true
                 }
@@ -687,7 +2190,9 @@ ApplicationWindow {
                 Text {
                     anchors.horizontalCenter: parent.horizontalCenter
                     text: {
-                        if (!sessionController.driver_loaded) {
+                        if (sessionController.driver_probing) {
+                            return ""
+                        } else if (!sessionController.driver_loaded) {
                             return "The sunpci kernel module is not loaded.\nRun: sudo insmod driver/sunpci.ko\nor: sudo modprobe sunpci"
                         } else {
                             return "Press Ctrl+R or use Machine → Start"
@@ -703,9 +2208,19 @@ ApplicationWindow {
                 Button {
                     anchors.horizontalCenter: parent.horizontalCenter
                     text: "Check Again"
-                    visible: !sessionController.driver_loaded
+                    visible: !sessionController.driver_loaded && !sessionController.driver_probing
                     onClicked: sessionController.check_driver()
                 }
+
+                // Guided fix when the card cannot be opened
+                Button {
+                    anchors.horizontalCenter: parent.horizontalCenter
+                    text: "How to Fix..."
+                    visible: !sessionController.driver_probing
+                             && (!sessionController.driver_loaded
+                                 || sessionController.error_message.startsWith("Failed to open driver"))
+                    onClicked: driverDiagnosticsDialog.open()
+                }
             }
 
             // Error display
@@ -721,22 +2236,34 @@ ApplicationWindow {
             // Focus handling for keyboard input
             focus: true
             Keys.onPressed: (event) => {
+                if (hotkeyController.handle_key_press(event.key, event.modifiers, event.nativeScanCode)) {
+                    event.accepted = true
+                    return
+                }
                 if (sessionController.session_running) {
                     var handled = inputController.handle_key_press(
                         event.key,
                         event.modifiers,
                         event.nativeScanCode
                     )
+                    if (handled && macroController.recording)
+                        macroController.record_key(event.key, event.nativeScanCode, true)
                     event.accepted = handled || inputController.keyboard_captured
                 }
             }
             Keys.onReleased: (event) => {
+                if (hotkeyController.handle_key_release(event.key, event.modifiers, event.nativeScanCode)) {
+                    event.accepted = true
+                    return
+                }
                 if (sessionController.session_running) {
                     var handled = inputController.handle_key_release(
                         event.key,
                         event.modifiers,
                         event.nativeScanCode
                     )
+                    if (handled && macroController.recording)
+                        macroController.record_key(event.key, event.nativeScanCode, false)
                     event.accepted = handled || inputController.keyboard_captured
                 }
             }
@@ -749,16 +2276,30 @@ ApplicationWindow {
                 visible: sessionController.session_running
                 hoverEnabled: true
                 acceptedButtons: Qt.LeftButton | Qt.RightButton | Qt.MiddleButton
-                cursorShape: inputController.mouse_captured ? Qt.BlankCursor : Qt.ArrowCursor
+                cursorShape: (inputController.mouse_captured || inputController.absolute_mode)
+                    ? Qt.BlankCursor : Qt.ArrowCursor
                 
                 property real lastX: 0
                 property real lastY: 0
 
+                // Forward the pointer position in tablet mode (no capture needed)
+                function sendAbsolute(mouse) {
+                    var pos = displayImage.mapFromItem(displayMouseArea, mouse.x, mouse.y)
+                    inputController.handle_mouse_move_absolute(pos.x, pos.y,
+                        displayImage.width, displayImage.height)
+                }
+
                 onPressed: (mouse) => {
                     parent.forceActiveFocus()
                     
-                    // If not captured, first click captures
-                    if (!inputController.mouse_captured) {
+                    if (inputController.absolute_mode) {
+                        // Tablet mode: capture keyboard only, pointer maps 1:1
+                        if (!inputController.keyboard_captured) {
+                            inputController.toggle_keyboard_capture()
+                        }
+                        sendAbsolute(mouse)
+                    } else if (!inputController.mouse_captured) {
+                        // If not captured, first click captures
                         inputController.toggle_mouse_capture()
                         inputController.toggle_keyboard_capture()
                         lastX = mouse.x
@@ -774,7 +2315,7 @@ ApplicationWindow {
                     inputController.handle_mouse_press(button)
                 }
                 onReleased: (mouse) => {
-                    if (!inputController.mouse_captured) return
+                    if (!inputController.mouse_captured && !inputController.absolute_mode) return
                     
                     var button = 0
                     if (mouse.button === Qt.LeftButton) button = 1
@@ -783,6 +2324,10 @@ ApplicationWindow {
                     inputController.handle_mouse_release(button)
                 }
                 onPositionChanged: (mouse) => {
+                    if (inputController.absolute_mode) {
+                        sendAbsolute(mouse)
+                        return
+                    }
                     if (!inputController.mouse_captured) {
                         lastX = mouse.x
                         lastY = mouse.y
@@ -800,7 +2345,7 @@ ApplicationWindow {
                     }
                 }
                 onWheel: (wheel) => {
-                    if (!inputController.mouse_captured) return
+                    if (!inputController.mouse_captured && !inputController.absolute_mode) return
                     inputController.handle_mouse_wheel(wheel.angleDelta.y)
                 }
             }
@@ -823,7 +2368,7 @@ ApplicationWindow {
                 StatusIndicator {
                     icon: "HDD"
                     tooltipText: "Hard Disk"
-                    active: sessionController.session_running
+                    active: sessionController.session_running && statsController.disk_activity !== 0
                 }
 
                 // Floppy indicator
@@ -833,11 +2378,36 @@ ApplicationWindow {
                     active: false
                 }
 
+                // Network indicator: tells a guest with its adapter down
+                // apart from an unplugged host cable
+                StatusIndicator {
+                    icon: "NET"
+                    visible: networkController.network_enabled
+                    tooltipText: {
+                        switch (networkController.link_state) {
+                        case "guest_offline":
+                            return qsTr("Network: guest adapter offline")
+                        case "host_unplugged":
+                            return qsTr("Network: host cable unplugged")
+                        case "connected":
+                            if (networkController.host_link_speed > 0) {
+                                return qsTr("Network: connected\nHost link %1 Mb/s %2")
+                                    .arg(networkController.host_link_speed)
+                                    .arg(networkController.host_link_duplex)
+                            }
+                            return qsTr("Network: connected")
+                        default:
+                            return qsTr("Network: disabled")
+                        }
+                    }
+                    active: networkController.link_state === "connected"
+                }
+
                 // Keyboard capture indicator
                 StatusIndicator {
                     icon: "KBD"
                     tooltipText: inputController.keyboard_captured 
-                        ? "Keyboard Capture: On\nPress Right Ctrl to release"
+                        ? "Keyboard Capture: On\nPress " + hotkeyController.get_binding("release_capture") + " to release"
                         : "Keyboard Capture: Off\nClick in display to capture"
                     active: inputController.keyboard_captured
                 }
@@ -846,7 +2416,7 @@ ApplicationWindow {
                 StatusIndicator {
                     icon: "MSE"
                     tooltipText: inputController.mouse_captured
-                        ? "Mouse Capture: On\nPress Right Ctrl to release"
+                        ? "Mouse Capture: On\nPress " + hotkeyController.get_binding("release_capture") + " to release"
                         : "Mouse Capture: Off\nClick in display to capture"
                     active: inputController.mouse_captured
                 }
@@ -860,9 +2430,16 @@ ApplicationWindow {
                         } else if (audioController.audio_muted) {
                             return "Audio: Muted\nClick to unmute"
                         } else {
-                            return "Audio: " + audioController.get_volume_percent() + "%\n" +
+                            var text = "Audio: " + audioController.get_volume_percent() + "%\n" +
                                    audioController.sample_rate + " Hz, " +
-                                   audioController.channels + " ch"
+                                   audioController.channels + " ch\n" +
+                                   "Latency: " + audioController.target_latency_ms + " ms" +
+                                   " (buffer " + audioController.buffer_fill + "% full)"
+                            if (audioController.underruns > 0 || audioController.guest_underruns > 0) {
+                                text += "\nDropouts: " + audioController.underruns + " host, " +
+                                        audioController.guest_underruns + " guest"
+                            }
+                            return text
                         }
                     }
                     active: audioController.audio_playing && !audioController.audio_muted
@@ -873,9 +2450,71 @@ ApplicationWindow {
                     }
                 }
 
+                // Microphone indicator
+                StatusIndicator {
+                    visible: audioController.audio_available
+                    icon: "🎤"
+                    tooltipText: {
+                        if (!audioController.capture_enabled) {
+                            return "Microphone: Off\nClick to pass it to the guest when it records"
+                        } else if (audioController.capture_active) {
+                            return "Microphone: Recording\nClick to turn off"
+                        } else {
+                            return "Microphone: On when the guest records\nClick to turn off"
+                        }
+                    }
+                    active: audioController.capture_active
+
+                    MouseArea {
+                        anchors.fill: parent
+                        onClicked: audioController.capture_enabled = !audioController.capture_enabled
+                    }
+                }
+
                 // Spacer
                 Item { Layout.fillWidth: true }
 
+                // Background disk task progress
+                RowLayout {
+                    visible: diskManager.active_tasks > 0
+                    spacing: 6
+
+                    Text {
+                        text: diskManager.currentTaskStep !== ""
+                              ? diskManager.currentTaskName + ": " + diskManager.currentTaskStep
+                              : diskManager.currentTaskName
+                        color: "#888888"
+                        font.pixelSize: 11
+                        elide: Text.ElideMiddle
+                        Layout.maximumWidth: 200
+                    }
+
+                    ProgressBar {
+                        from: 0
+                        to: 100
+                        value: diskManager.currentTaskPercent
+                        Layout.preferredWidth: 80
+                    }
+
+                    Button {
+                        text: qsTr("Cancel")
+                        flat: true
+                        font.pixelSize: 11
+                        Layout.preferredHeight: 20
+                        enabled: diskManager.currentTaskId >= 0
+                        onClicked: diskManager.cancel_task(diskManager.currentTaskId)
+                    }
+                }
+
+                // Uptime display
+                Text {
+                    visible: sessionController.session_running
+                    text: statsController.uptime_text
+                    color: "#888888"
+                    font.pixelSize: 11
+                    font.family: "monospace"
+                }
+
                 // Resolution display
                 Text {
                     text: sessionController.display_width + "×" + sessionController.display_height
@@ -983,130 +2622,173 @@ ApplicationWindow {
     // ==========================================================================
 
     // Create Disk Dialog - for creating new virtual disk images
-    CreateDiskDialog {
+    LazyDialog {
         id: createDiskDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-
-        onDiskCreated: (path, sizeMb, revision) => {
-            console.log("Creating disk:", path, sizeMb, "MB, revision", revision)
-            if (diskManager.create_disk(path, sizeMb, revision)) {
-                console.log("Disk created successfully!")
-            } else {
-                console.log("Failed to create disk")
+        sourceComponent: CreateDiskDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+
+            onDiskCreated: (path, sizeMb, revision, allocation) => {
+                console.log("Creating disk:", path, sizeMb, "MB, revision", revision, allocation)
+                diskManager.currentTaskId = diskManager.create_disk_async(path, sizeMb, revision, allocation)
             }
         }
     }
 
     // Disk Properties Dialog - for viewing/editing disk settings
-    DiskPropertiesDialog {
+    LazyDialog {
         id: diskPropertiesDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-
-        // Properties will be populated when opening for a specific disk
-        diskPath: ""
-        diskSizeMb: 0
-        revision: 2
-        cylinders: 0
-        heads: 0
-        sectorsPerTrack: 0
-        isBootable: false
+        sourceComponent: DiskPropertiesDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            disks: diskManager
+
+            // Properties will be populated when opening for a specific disk
+            diskPath: ""
+            diskSizeMb: 0
+            revision: 2
+            cylinders: 0
+            heads: 0
+            sectorsPerTrack: 0
+            isBootable: false
+        }
     }
 
     // Display Settings Dialog
     // Note: Resolution/color depth are controlled by guest OS, not here
-    DisplaySettingsDialog {
+    LazyDialog {
         id: displaySettingsDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-        config: configManager
-
-        onSettingsApplied: {
-            console.log("Display presentation settings applied")
-            // Settings saved to config - QML Image handles scaling via config values
+        sourceComponent: DisplaySettingsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+
+            onSettingsApplied: {
+                window.presentationMode = configManager.get_presentation_mode()
+                displayView.loadScaling()
+                displayView.load_crt_config()
+                mainWindow.load_window_config()
+                sessionController.reload_display_settings()
+                console.log("Display presentation settings applied")
+            }
         }
     }
 
     // Keyboard Settings Dialog
-    KeyboardSettingsDialog {
+    LazyDialog {
         id: keyboardSettingsDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-        config: configManager
-
-        onSettingsApplied: {
-            console.log("Keyboard settings applied")
-            // Settings saved to config, applied on next session start
+        sourceComponent: KeyboardSettingsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+
+            onSettingsApplied: {
+                console.log("Keyboard settings applied")
+                inputController.sync_caps_lock = configManager.get_sync_caps_lock()
+                inputController.sync_num_lock = configManager.get_sync_num_lock()
+                inputController.sync_scroll_lock = configManager.get_sync_scroll_lock()
+                clipboardController.code_page = configManager.get_code_page()
+            }
         }
     }
 
     // Mouse Settings Dialog
-    MouseSettingsDialog {
+    LazyDialog {
         id: mouseSettingsDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-        config: configManager
-
-        onSettingsApplied: {
-            console.log("Mouse settings applied")
-            // Settings saved to config, applied on next session start
+        sourceComponent: MouseSettingsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+
+            onSettingsApplied: {
+                console.log("Mouse settings applied")
+                inputController.absolute_mode = configManager.get_mouse_protocol() === "absolute"
+                inputController.load_mouse_config()
+                if (inputController.absolute_mode && inputController.mouse_captured) {
+                    inputController.toggle_mouse_capture()
+                }
+            }
         }
     }
 
     // Drive Mapping Dialog - for host filesystem redirection
-    DriveMappingDialog {
+    LazyDialog {
         id: driveMappingDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-
-        onMappingsApplied: (mappings) => {
-            console.log("Drive mappings:", JSON.stringify(mappings))
-            // Load the mappings into the controller
-            driveMappingController.load_mappings_json(JSON.stringify(mappings))
-            // Apply if session is running
-            if (sessionController.session_running) {
-                driveMappingController.apply_mappings()
+        sourceComponent: DriveMappingDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+
+            controller: driveMappingController
+            mappingModel: driveMappingModel
+            autoReapply: configManager.get_reapply_drive_mappings()
+            autoMapRemovable: configManager.get_auto_map_removable_media()
+
+            onMappingsApplied: (mappings, autoReapply, autoMapRemovable) => {
+                console.log("Drive mappings:", JSON.stringify(mappings))
+                driveMappingController.auto_reapply = autoReapply
+                removableMediaController.auto_map = autoMapRemovable
+                configManager.set_reapply_drive_mappings_value(autoReapply)
+                configManager.set_auto_map_removable_media_value(autoMapRemovable)
+                configManager.save()
+                // Load the mappings into the controller
+                driveMappingController.load_mappings_json(JSON.stringify(mappings))
+                // Apply if session is running
+                if (sessionController.session_running) {
+                    driveMappingController.apply_mappings()
+                    driveMappingController.refresh_usage(true)
+                }
             }
         }
     }
 
     // Clipboard Settings Dialog
-    ClipboardSettingsDialog {
+    LazyDialog {
         id: clipboardSettingsDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-        config: configManager
-
-        onSettingsApplied: (enabled, direction) => {
-            console.log("Clipboard settings applied: enabled=" + enabled + ", direction=" + direction)
-            clipboardController.set_enabled(enabled)
-            clipboardController.set_direction(direction)
+        sourceComponent: ClipboardSettingsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+
+            onSettingsApplied: (enabled, direction) => {
+                console.log("Clipboard settings applied: enabled=" + enabled + ", direction=" + direction)
+                clipboardController.set_enabled(enabled)
+                clipboardController.set_direction(direction)
+                clipboardController.share_images = configManager.get_clipboard_share_images()
+                clipboardController.share_rich_text = configManager.get_clipboard_share_rich_text()
+            }
         }
     }
 
     // Network Settings Dialog
-    NetworkSettingsDialog {
+    LazyDialog {
         id: networkSettingsDialog
-        parent: Overlay.overlay
-        x: Math.round((window.width - width) / 2)
-        y: Math.round((window.height - height) / 2)
-        config: configManager
-
-        onSettingsApplied: {
-            console.log("Network settings applied")
-            networkController.set_enabled(configManager.get_network_enabled())
-            // Interface would be read from the dialog's combo box
-            // MAC would be read from the dialog's text field
-            if (sessionController.session_running && networkController.network_enabled) {
-                networkController.apply_config()
+        sourceComponent: NetworkSettingsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+            network: networkController
+
+            onSettingsApplied: {
+                console.log("Network settings applied")
+                networkController.set_enabled(configManager.get_network_enabled())
+                networkController.auto_tap = configManager.get_network_auto_tap()
+                networkController.tap_name = configManager.get_network_tap_name()
+                networkController.bridge_name = configManager.get_network_bridge()
+                networkController.create_bridge = configManager.get_network_create_bridge()
+                networkController.network_mode = configManager.get_network_mode()
+                // Interface would be read from the dialog's combo box
+                // MAC would be read from the dialog's text field
+                if (sessionController.session_running && networkController.network_enabled) {
+                    networkController.apply_config()
+                }
             }
         }
     }
@@ -1117,17 +2799,38 @@ ApplicationWindow {
         parent: Overlay.overlay
         x: Math.round((window.width - width) / 2)
         y: Math.round((window.height - height) / 2)
+        disks: diskManager
+        recentFiles: recentIsoFiles
 
         onIsoMounted: (path) => {
             console.log("ISO mounted:", path)
             if (!diskManager.mount_iso(path)) {
                 console.log("Failed to mount ISO")
+                return
             }
+            // Remembered as the disc to boot from
+            configManager.set_cdrom_iso(path)
+            if (mountIsoDialog.bootFromCd) {
+                configManager.set_boot_device_value("cdrom")
+                bootMenu.bootDevice = "cdrom"
+            }
+            configManager.save()
         }
 
         onIsoEjected: {
             console.log("ISO ejected")
             diskManager.eject_cdrom()
+            configManager.set_cdrom_iso("")
+            configManager.save()
+        }
+
+        onHostDriveSelected: (device) => {
+            console.log("Host drive selected:", device)
+            if (!diskManager.mount_host_cdrom(device)) {
+                console.log("Failed to use host drive")
+                mountIsoDialog.isMounted = false
+                mountIsoDialog.selectedIsoPath = ""
+            }
         }
     }
 
@@ -1137,12 +2840,15 @@ ApplicationWindow {
         parent: Overlay.overlay
         x: Math.round((window.width - width) / 2)
         y: Math.round((window.height - height) / 2)
+        recentFiles: recentFloppyFiles
 
         onFloppyMounted: (path, drive) => {
             console.log("Floppy mounted:", path, "on drive", drive)
             if (!diskManager.mount_floppy(path, drive)) {
                 console.log("Failed to mount floppy")
+                return
             }
+            recentFloppyFiles.add(path)
         }
 
         onFloppyEjected: (drive) => {
@@ -1150,4 +2856,76 @@ ApplicationWindow {
             diskManager.eject_floppy(drive)
         }
     }
+
+    // Driver Diagnostics Dialog - why the card cannot be opened, and the fix
+    LazyDialog {
+        id: driverDiagnosticsDialog
+        sourceComponent: DriverDiagnosticsDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            session: sessionController
+
+            // Pick up a fix made while it was open
+            onClosed: {
+                if (!sessionController.session_running)
+                    sessionController.check_driver()
+            }
+        }
+    }
+
+    // Missing Media Dialog - problems found with the profile's media on load
+    LazyDialog {
+        id: missingMediaDialog
+        sourceComponent: MissingMediaDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+
+            onMediaChanged: console.log("Profile media updated")
+        }
+    }
+
+    // Session History Dialog - search what the guest screen showed
+    LazyDialog {
+        id: commandPalette
+        sourceComponent: CommandPalette {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: 48
+            actions: actionController
+        }
+    }
+
+    LazyDialog {
+        id: historyDialog
+        sourceComponent: HistoryDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            history: historyController
+        }
+    }
+
+    LazyDialog {
+        id: biosDialog
+        sourceComponent: BiosDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            config: configManager
+        }
+    }
+
+    LazyDialog {
+        id: logConsoleDialog
+        sourceComponent: LogConsoleDialog {
+            parent: Overlay.overlay
+            x: Math.round((window.width - width) / 2)
+            y: Math.round((window.height - height) / 2)
+            log: logController
+            config: configManager
+        }
+    }
 }
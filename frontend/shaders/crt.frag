@@ -0,0 +1,58 @@
+#version 440
+// CRT post-processing for the display (Qt 6, compiled with qsb)
+//
+// Keep in step with crt_gl2.frag, the Qt 5 version.
+
+layout(location = 0) in vec2 qt_TexCoord0;
+layout(location = 0) out vec4 fragColor;
+
+layout(std140, binding = 0) uniform buf {
+    mat4 qt_Matrix;
+    float qt_Opacity;
+    // Guest display and on-screen size, in pixels
+    vec2 sourceSize;
+    vec2 targetSize;
+    // 0 none, 1 scanlines, 2 aperture grille, 3 curvature
+    int effect;
+    float scanlineStrength;
+    float maskIntensity;
+    float curvature;
+};
+
+layout(binding = 1) uniform sampler2D source;
+
+void main()
+{
+    vec2 uv = qt_TexCoord0;
+    if (effect == 3) {
+        // Barrel distortion; outside the bent screen is the bezel
+        vec2 centered = uv * 2.0 - 1.0;
+        centered *= 1.0 + curvature * centered.yx * centered.yx;
+        uv = centered * 0.5 + 0.5;
+        if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+            fragColor = vec4(0.0, 0.0, 0.0, qt_Opacity);
+            return;
+        }
+    }
+
+    vec4 color = texture(source, uv);
+    if (effect != 0) {
+        // Brightest at the middle of each guest line, dark between them
+        float beam = sin(fract(uv.y * sourceSize.y) * 3.14159265);
+        color.rgb *= 1.0 - scanlineStrength * (1.0 - beam);
+    }
+    if (effect == 2) {
+        // One host column each of red, green and blue phosphor
+        float column = mod(floor(uv.x * targetSize.x), 3.0);
+        vec3 mask = vec3(1.0 - maskIntensity);
+        if (column < 0.5) {
+            mask.r = 1.0;
+        } else if (column < 1.5) {
+            mask.g = 1.0;
+        } else {
+            mask.b = 1.0;
+        }
+        color.rgb *= mask;
+    }
+    fragColor = color * qt_Opacity;
+}
@@ -0,0 +1,52 @@
+// CRT post-processing for the display (Qt 5, OpenGL)
+//
+// Keep in step with crt.frag, the Qt 6 version.
+
+varying highp vec2 qt_TexCoord0;
+
+uniform lowp float qt_Opacity;
+uniform sampler2D source;
+// Guest display and on-screen size, in pixels
+uniform highp vec2 sourceSize;
+uniform highp vec2 targetSize;
+// 0 none, 1 scanlines, 2 aperture grille, 3 curvature
+uniform int effect;
+uniform lowp float scanlineStrength;
+uniform lowp float maskIntensity;
+uniform highp float curvature;
+
+void main()
+{
+    highp vec2 uv = qt_TexCoord0;
+    if (effect == 3) {
+        // Barrel distortion; outside the bent screen is the bezel
+        highp vec2 centered = uv * 2.0 - 1.0;
+        centered *= 1.0 + curvature * centered.yx * centered.yx;
+        uv = centered * 0.5 + 0.5;
+        if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+            gl_FragColor = vec4(0.0, 0.0, 0.0, qt_Opacity);
+            return;
+        }
+    }
+
+    lowp vec4 color = texture2D(source, uv);
+    if (effect != 0) {
+        // Brightest at the middle of each guest line, dark between them
+        highp float beam = sin(fract(uv.y * sourceSize.y) * 3.14159265);
+        color.rgb *= 1.0 - scanlineStrength * (1.0 - beam);
+    }
+    if (effect == 2) {
+        // One host column each of red, green and blue phosphor
+        highp float column = mod(floor(uv.x * targetSize.x), 3.0);
+        lowp vec3 mask = vec3(1.0 - maskIntensity);
+        if (column < 0.5) {
+            mask.r = 1.0;
+        } else if (column < 1.5) {
+            mask.g = 1.0;
+        } else {
+            mask.b = 1.0;
+        }
+        color.rgb *= mask;
+    }
+    gl_FragColor = color * qt_Opacity;
+}
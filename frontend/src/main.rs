@@ -3,10 +3,188 @@
 mod bridge;
 mod ui;
 
+use std::path::PathBuf;
+
 use anyhow::Result;
+use clap::{Arg, ArgAction, ArgMatches, Command, value_parser};
 use cxx_qt_lib::{QGuiApplication, QQmlApplicationEngine, QString, QUrl};
+use rising_sun_common::AppConfig;
+use rising_sun_common::control::{ControlClient, ControlRequest, command};
+use rising_sun_common::instance::{self, Instance};
+
+use ui::run_options::{self, RunOptions};
+
+fn cli() -> Command {
+    Command::new("rising-sun")
+        .about("Run a SunPCi session")
+        .version(env!("CARGO_PKG_VERSION"))
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .action(ArgAction::Count)
+                .help("Log more than the configuration asks for (twice for everything)"),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_parser(value_parser!(PathBuf))
+                .conflicts_with("profile")
+                .help("Configuration file to use instead of the default one"),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_parser(parse_profile)
+                .help("Use the named profile's configuration file"),
+        )
+        .arg(
+            Arg::new("card")
+                .long("card")
+                .value_parser(value_parser!(u32))
+                .help("SunPCi card to drive (N in /dev/sunpciN)"),
+        )
+        .arg(
+            Arg::new("ephemeral")
+                .long("ephemeral")
+                .action(ArgAction::SetTrue)
+                .help("Discard the session's changes to its media"),
+        )
+        .arg(
+            Arg::new("auto-start")
+                .long("auto-start")
+                .action(ArgAction::SetTrue)
+                .help("Start the session as soon as the window is up"),
+        )
+        .arg(
+            Arg::new("fullscreen")
+                .long("fullscreen")
+                .action(ArgAction::SetTrue)
+                .help("Start in fullscreen"),
+        )
+        .arg(
+            Arg::new("headless")
+                .long("headless")
+                .action(ArgAction::SetTrue)
+                .help("Start the session without showing a window; quit when it stops"),
+        )
+        .arg(
+            Arg::new("primary-disk")
+                .long("primary-disk")
+                .value_parser(value_parser!(PathBuf))
+                .help("Hard disk image to boot instead of the configured one"),
+        )
+        .arg(
+            Arg::new("iso")
+                .long("iso")
+                .value_parser(value_parser!(PathBuf))
+                .help("ISO image to put in the CD-ROM drive"),
+        )
+        .arg(
+            Arg::new("take-over")
+                .long("take-over")
+                .action(ArgAction::SetTrue)
+                .help("Start beside the frontend already running for the card, to take its session over"),
+        )
+}
+
+/// A profile name, which becomes a file name
+fn parse_profile(name: &str) -> Result<String, String> {
+    if name.is_empty() || name.starts_with('.') || name.contains('/') {
+        return Err(format!("{:?} is not a profile name", name));
+    }
+    Ok(name.to_string())
+}
+
+/// The overrides given for this run
+fn run_options(matches: &ArgMatches) -> RunOptions {
+    RunOptions {
+        auto_start: matches.get_flag("auto-start"),
+        fullscreen: matches.get_flag("fullscreen"),
+        headless: matches.get_flag("headless"),
+        primary_disk: matches.get_one::<PathBuf>("primary-disk").cloned(),
+        iso: matches.get_one::<PathBuf>("iso").cloned(),
+    }
+}
+
+/// Hand the command line to the frontend already running for the card
+fn forward(mut client: ControlClient, matches: &ArgMatches) -> Result<()> {
+    for ignored in ["config", "profile", "primary-disk", "ephemeral", "headless"] {
+        if matches.value_source(ignored) == Some(clap::parser::ValueSource::CommandLine) {
+            eprintln!("rising-sun: --{} ignored, the running frontend keeps its own", ignored);
+        }
+    }
+
+    let mut requests = Vec::new();
+    if let Some(iso) = matches.get_one::<PathBuf>("iso") {
+        // The running frontend has a working directory of its own
+        let iso = std::path::absolute(iso)?;
+        requests.push(ControlRequest::new(command::MOUNT_ISO, &[&iso.to_string_lossy()]));
+    }
+    if matches.get_flag("auto-start") {
+        requests.push(ControlRequest::new(command::START, &[]));
+    }
+    let show: &[&str] = if matches.get_flag("fullscreen") { &["fullscreen"] } else { &[] };
+    requests.push(ControlRequest::new(command::SHOW, show));
+
+    let mut failed = false;
+    for request in &requests {
+        let response = client.call(request)?;
+        if !response.ok {
+            eprintln!("rising-sun: {}", response.message);
+            failed = true;
+        }
+    }
+    if failed {
+        anyhow::bail!("the running frontend refused part of the command line");
+    }
+    Ok(())
+}
 
 fn main() -> Result<()> {
+    ui::startup::begin();
+    let matches = cli().get_matches();
+
+    // Another configuration file for this run; the overrides are never
+    // written to it
+    if let Some(path) = matches.get_one::<PathBuf>("config") {
+        AppConfig::use_config_file(path.clone());
+    } else if let Some(name) = matches.get_one::<String>("profile") {
+        AppConfig::use_config_file(AppConfig::profile_file(name));
+    }
+    run_options::set(run_options(&matches));
+
+    // Log at the configured level, more with --verbose (-v), and to
+    // rotating files when the configuration asks for it
+    let verbose = matches.get_count("verbose").min(2);
+    let log = rising_sun_common::load_config().unwrap_or_default().log;
+    ui::logging::init(&log, verbose);
+
+    // Discard the session's changes to its media (also a toolbar toggle)
+    if matches.get_flag("ephemeral") {
+        ui::ephemeral::set_enabled(true);
+    }
+
+    // Drive another card than /dev/sunpci0
+    if let Some(&card) = matches.get_one::<u32>("card") {
+        rising_sun_common::select_card(card);
+    }
+
+    // The card is driven by one frontend at a time; a second one started
+    // for it passes its command line on and exits
+    if !matches.get_flag("take-over") {
+        match instance::claim(rising_sun_common::selected_card()) {
+            Ok(Instance::First) => {}
+            Ok(Instance::Running(client)) => {
+                tracing::info!("Frontend already running for this card, handing the command line to it");
+                return forward(client, &matches);
+            }
+            Err(e) => tracing::warn!("Cannot check for a frontend already running: {}", e),
+        }
+    }
+
+    ui::startup::mark("setup");
+
     // Initialize Qt application
     let mut app = QGuiApplication::new();
     
@@ -19,13 +197,18 @@ fn main() -> Result<()> {
         app_ref.as_mut().set_organization_domain(&org_domain);
         app_ref.as_mut().set_application_name(&app_name);
     }
-    
+
+    ui::startup::mark("qt");
+
     let mut engine = QQmlApplicationEngine::new();
 
     // Load the main QML file
     if let Some(engine) = engine.as_mut() {
         engine.load(&QUrl::from("qrc:/qt/qml/com/risingsun/qml/main.qml"));
     }
+    // Controllers created and QML Component.onCompleted handlers run;
+    // the window reports its first frame with MainWindow.startup_finished
+    ui::startup::mark("qml");
 
     // Run the application
     if let Some(app) = app.as_mut() {
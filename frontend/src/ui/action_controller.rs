@@ -0,0 +1,72 @@
+//! Action controller Qt bridge for the action registry.
+//!
+//! The command palette lists the registered actions with `search` and
+//! runs the chosen one with `trigger`; hotkeys are triggered through it as
+//! well. Actions carried out by QML come back as action_triggered, which
+//! main.qml routes to the controllers and dialogs concerned.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        type ActionController = super::ActionControllerRust;
+
+        /// Search the registered actions. Returns a JSON array of
+        /// {name, title, category, shortcut, enabled}, best match first.
+        #[qinvokable]
+        fn search(self: &ActionController, query: QString) -> QString;
+
+        /// Run an action; false if it is unknown or disabled
+        #[qinvokable]
+        fn trigger(self: Pin<&mut ActionController>, name: QString) -> bool;
+
+        /// Get the shortcut of an action (empty if none)
+        #[qinvokable]
+        fn get_shortcut(self: &ActionController, name: QString) -> QString;
+
+        /// An action QML carries out was triggered
+        #[qsignal]
+        fn action_triggered(self: Pin<&mut ActionController>, name: QString);
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
+use super::actions;
+
+/// Rust implementation of the ActionController
+#[derive(Default)]
+pub struct ActionControllerRust {}
+
+impl qobject::ActionController {
+    /// Search the registered actions
+    pub fn search(&self, query: QString) -> QString {
+        let found = actions::search(&query.to_string());
+        QString::from(&serde_json::to_string(&found).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Run an action, handing those QML carries out back to it
+    pub fn trigger(mut self: Pin<&mut Self>, name: QString) -> bool {
+        let name = name.to_string();
+        if !actions::trigger(&name) {
+            tracing::debug!("Action {} is unavailable", name);
+            return false;
+        }
+        for posted in actions::take_posted() {
+            self.as_mut().action_triggered(QString::from(&posted));
+        }
+        true
+    }
+
+    /// Get the shortcut of an action
+    pub fn get_shortcut(&self, name: QString) -> QString {
+        QString::from(&actions::shortcut_of(&name.to_string()))
+    }
+}
@@ -0,0 +1,312 @@
+//! Registry of the actions the user can trigger.
+//!
+//! Controllers register the actions they offer here when they are created:
+//! a name, a title and category for display, the shortcut that triggers
+//! it, whether it can run right now, and what running it does. The command
+//! palette (Ctrl+K) lists and filters the registry through ActionController
+//! and hotkeys trigger actions through it, so every place that offers a
+//! command agrees on its name, shortcut and enabled state.
+//!
+//! Most actions are carried out by QML, which owns the dialogs and wires
+//! the controllers together. Their invoke closure is the default one,
+//! which queues the action's name; ActionController hands queued names to
+//! QML with action_triggered.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+type Predicate = Arc<dyn Fn() -> bool + Send + Sync>;
+type Invoke = Arc<dyn Fn() + Send + Sync>;
+
+/// A command the user can trigger
+#[derive(Clone)]
+pub struct Action {
+    name: String,
+    title: String,
+    category: String,
+    shortcut: String,
+    enabled: Predicate,
+    invoke: Invoke,
+}
+
+impl Action {
+    /// An always-enabled action carried out by QML
+    pub fn new(name: &str, title: &str, category: &str) -> Self {
+        let posted = name.to_string();
+        Self {
+            name: name.to_string(),
+            title: title.to_string(),
+            category: category.to_string(),
+            shortcut: String::new(),
+            enabled: Arc::new(|| true),
+            invoke: Arc::new(move || post(&posted)),
+        }
+    }
+
+    /// Shortcut shown next to the action, e.g. "Ctrl+R"
+    pub fn shortcut(mut self, shortcut: &str) -> Self {
+        self.shortcut = shortcut.to_string();
+        self
+    }
+
+    /// Only offer the action while `enabled` returns true
+    pub fn enabled_when(mut self, enabled: impl Fn() -> bool + Send + Sync + 'static) -> Self {
+        self.enabled = Arc::new(enabled);
+        self
+    }
+
+    /// Run `invoke` instead of handing the action to QML
+    pub fn on_invoke(mut self, invoke: impl Fn() + Send + Sync + 'static) -> Self {
+        self.invoke = Arc::new(invoke);
+        self
+    }
+}
+
+/// What the command palette shows of an action
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ActionInfo {
+    pub name: String,
+    pub title: String,
+    pub category: String,
+    pub shortcut: String,
+    pub enabled: bool,
+}
+
+/// Registered actions
+#[derive(Default)]
+pub struct Registry {
+    actions: Vec<Action>,
+    /// Shortcuts set by name, e.g. from the [hotkeys] config section; they
+    /// win over the registered ones and may be set before registration
+    shortcuts: HashMap<String, String>,
+}
+
+impl Registry {
+    /// Add an action, replacing one registered under the same name
+    pub fn register(&mut self, action: Action) {
+        match self.actions.iter_mut().find(|a| a.name == action.name) {
+            Some(existing) => *existing = action,
+            None => self.actions.push(action),
+        }
+    }
+
+    /// Bind `name` to `shortcut` (empty to fall back to the registered one)
+    pub fn set_shortcut(&mut self, name: &str, shortcut: &str) {
+        if shortcut.is_empty() {
+            self.shortcuts.remove(name);
+        } else {
+            self.shortcuts.insert(name.to_string(), shortcut.to_string());
+        }
+    }
+
+    /// Shortcut of an action (empty if none or unknown)
+    pub fn shortcut_of(&self, name: &str) -> String {
+        match self.shortcuts.get(name) {
+            Some(shortcut) => shortcut.clone(),
+            None => self
+                .actions
+                .iter()
+                .find(|a| a.name == name)
+                .map(|a| a.shortcut.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Actions matching `query`, best match first
+    ///
+    /// The query matches "category title" by its characters in order, so
+    /// "mnt iso" finds "Devices Mount ISO Image...". Enabled actions come
+    /// before disabled ones; an empty query lists everything by category.
+    pub fn search(&self, query: &str) -> Vec<ActionInfo> {
+        let mut found: Vec<(u32, ActionInfo)> = self
+            .actions
+            .iter()
+            .filter_map(|action| {
+                let score = match_score(query, &action.title, &action.category)?;
+                let info = ActionInfo {
+                    name: action.name.clone(),
+                    title: action.title.clone(),
+                    category: action.category.clone(),
+                    shortcut: self.shortcut_of(&action.name),
+                    enabled: (action.enabled)(),
+                };
+                Some((score, info))
+            })
+            .collect();
+        found.sort_by(|(a_score, a), (b_score, b)| {
+            b.enabled
+                .cmp(&a.enabled)
+                .then(b_score.cmp(a_score))
+                .then_with(|| a.category.cmp(&b.category))
+                .then_with(|| a.title.cmp(&b.title))
+        });
+        found.into_iter().map(|(_, info)| info).collect()
+    }
+
+    /// The invoke closure of an enabled action
+    fn invoker(&self, name: &str) -> Option<Invoke> {
+        self.actions
+            .iter()
+            .find(|a| a.name == name && (a.enabled)())
+            .map(|a| Arc::clone(&a.invoke))
+    }
+}
+
+/// How well `query` matches an action; None if it does not
+///
+/// Titles starting with the query rank first, then titles containing it,
+/// then anything containing its characters in order.
+fn match_score(query: &str, title: &str, category: &str) -> Option<u32> {
+    let query: String = query.to_lowercase().split_whitespace().collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+    let title_key: String = title.to_lowercase().split_whitespace().collect();
+    if title_key.starts_with(&query) {
+        return Some(3);
+    }
+    if title_key.contains(&query) {
+        return Some(2);
+    }
+    let haystack = format!("{}{}", category, title).to_lowercase();
+    let mut chars = haystack.chars();
+    query
+        .chars()
+        .all(|q| chars.any(|c| c == q))
+        .then_some(1)
+}
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+/// Names of actions queued for QML
+static POSTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    let mut registry = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    f(registry.get_or_insert_with(Registry::default))
+}
+
+/// Add an action to the registry
+pub fn register(action: Action) {
+    with_registry(|r| r.register(action));
+}
+
+/// Bind an action to a shortcut (see `Registry::set_shortcut`)
+pub fn set_shortcut(name: &str, shortcut: &str) {
+    with_registry(|r| r.set_shortcut(name, shortcut));
+}
+
+/// Shortcut of an action
+pub fn shortcut_of(name: &str) -> String {
+    with_registry(|r| r.shortcut_of(name))
+}
+
+/// Actions matching `query`, best match first
+pub fn search(query: &str) -> Vec<ActionInfo> {
+    with_registry(|r| r.search(query))
+}
+
+/// Run an action; false if it is unknown or disabled
+pub fn trigger(name: &str) -> bool {
+    // Not under the lock: the closure may use the registry itself
+    let Some(invoke) = with_registry(|r| r.invoker(name)) else {
+        return false;
+    };
+    invoke();
+    true
+}
+
+/// Queue an action for QML to carry out
+pub fn post(name: &str) {
+    POSTED.lock().unwrap_or_else(|e| e.into_inner()).push(name.to_string());
+}
+
+/// Take the actions queued for QML, oldest first
+pub fn take_posted() -> Vec<String> {
+    std::mem::take(&mut *POSTED.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+    fn names(found: &[ActionInfo]) -> Vec<&str> {
+        found.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let mut registry = Registry::default();
+        registry.register(Action::new("mount_iso", "Mount ISO Image...", "Devices"));
+        registry.register(Action::new("eject_cdrom", "Eject CD-ROM", "Devices"));
+        registry.register(Action::new("start_session", "Start", "Machine").shortcut("Ctrl+R"));
+        registry.register(
+            Action::new("reset_session", "Reset", "Machine").enabled_when(|| false),
+        );
+
+        // Everything, enabled first, by category
+        assert_eq!(
+            names(&registry.search("")),
+            ["eject_cdrom", "mount_iso", "start_session", "reset_session"]
+        );
+        // Prefix beats substring beats characters in order
+        assert_eq!(names(&registry.search("mount")), ["mount_iso"]);
+        assert_eq!(registry.search("iso")[0].name, "mount_iso");
+        assert_eq!(names(&registry.search("mnt iso")), ["mount_iso"]);
+        assert_eq!(names(&registry.search("dev ej")), ["eject_cdrom"]);
+        assert!(registry.search("xyz").is_empty());
+
+        let reset = &registry.search("reset")[0];
+        assert!(!reset.enabled);
+        assert_eq!(registry.search("start")[0].shortcut, "Ctrl+R");
+    }
+
+    #[test]
+    fn test_register_replaces() {
+        let mut registry = Registry::default();
+        registry.register(Action::new("screenshot", "Screenshot", "View"));
+        registry.register(Action::new("screenshot", "Save Screenshot", "View"));
+        let found = registry.search("");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].title, "Save Screenshot");
+    }
+
+    #[test]
+    fn test_shortcut_override() {
+        let mut registry = Registry::default();
+        // Hotkeys may load before the action is registered
+        registry.set_shortcut("toggle_fullscreen", "RightCtrl+F");
+        registry.register(Action::new("toggle_fullscreen", "Fullscreen", "View").shortcut("F11"));
+        assert_eq!(registry.shortcut_of("toggle_fullscreen"), "RightCtrl+F");
+        registry.set_shortcut("toggle_fullscreen", "");
+        assert_eq!(registry.shortcut_of("toggle_fullscreen"), "F11");
+        assert_eq!(registry.shortcut_of("unknown"), "");
+    }
+
+    #[test]
+    fn test_trigger() {
+        static RUNS: AtomicU32 = AtomicU32::new(0);
+        static ENABLED: AtomicBool = AtomicBool::new(true);
+        register(
+            Action::new("test_trigger_action", "Test", "Test")
+                .enabled_when(|| ENABLED.load(Ordering::SeqCst))
+                .on_invoke(|| {
+                    RUNS.fetch_add(1, Ordering::SeqCst);
+                }),
+        );
+
+        assert!(trigger("test_trigger_action"));
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+        ENABLED.store(false, Ordering::SeqCst);
+        assert!(!trigger("test_trigger_action"));
+        assert_eq!(RUNS.load(Ordering::SeqCst), 1);
+        assert!(!trigger("test_trigger_unknown"));
+
+        // The default closure queues the action for QML
+        register(Action::new("test_trigger_posted", "Test", "Test"));
+        assert!(trigger("test_trigger_posted"));
+        assert!(take_posted().contains(&"test_trigger_posted".to_string()));
+    }
+}
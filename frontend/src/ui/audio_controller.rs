@@ -2,14 +2,30 @@
 //!
 //! This module handles:
 //! - Reading PCM audio from the driver
-//! - Playing audio via the system audio API (ALSA/PipeWire)
-//! - Volume control and mute state
+//! - Playing audio via the system audio API (ALSA/PipeWire), resampled
+//!   when the device does not take the guest's rate, and reopened when the
+//!   guest changes rate or sample layout
+//! - Volume control and mute state (volume is the driver's; mute is applied
+//!   to the output here, so it holds across reopened streams)
+//! - Ducking other host applications while the guest plays
+//!   (`[audio] duck_host_audio`)
+//! - Passing the host microphone to the guest's Sound Blaster input while
+//!   the guest records (`[audio] microphone`, or `capture_enabled`); the
+//!   input device is only opened for as long as the guest records
+//! - Buffer statistics: how much audio is buffered ahead of the device
+//!   (`[audio] latency_ms`, changed with `set_latency_ms`), and how often
+//!   playback ran dry on the host (`underruns`) or in the guest
+//!   (`guest_underruns`)
 
 use std::cell::RefCell;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use std::sync::Arc;
 
+use rising_sun_common::ducking::HostDucker;
 use rising_sun_common::ioctl::{AudioFormat, AudioStatus, AudioVolume, audio_status_flags};
+use rising_sun_common::{AudioConfig, load_config, save_config};
+use rising_sun_common::resample::Resampler;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -33,6 +49,14 @@ mod qobject {
         #[qproperty(i32, bits_per_sample)]
         #[qproperty(i32, driver_fd)]
         #[qproperty(QString, status_text)]
+        #[qproperty(bool, host_ducked)]
+        #[qproperty(bool, capture_enabled)]
+        #[qproperty(bool, capture_active)]
+        #[qproperty(i32, target_latency_ms)]
+        #[qproperty(i32, buffer_size)]
+        #[qproperty(i32, buffer_fill)]
+        #[qproperty(i32, underruns)]
+        #[qproperty(i32, guest_underruns)]
         type AudioController = super::AudioControllerRust;
 
         /// Initialize audio with driver file descriptor
@@ -63,6 +87,11 @@ mod qobject {
         #[qinvokable]
         fn poll_status(self: Pin<&mut AudioController>);
 
+        /// Set how much audio is buffered ahead of the device, in
+        /// milliseconds, and keep it in the configuration
+        #[qinvokable]
+        fn set_latency_ms(self: Pin<&mut AudioController>, latency_ms: i32);
+
         /// Get volume as percentage (0-100)
         #[qinvokable]
         fn get_volume_percent(self: &AudioController) -> i32;
@@ -75,6 +104,7 @@ mod qobject {
 
 use std::pin::Pin;
 use cxx_qt_lib::QString;
+use super::session_gate;
 
 /// Audio playback state
 struct PlaybackState {
@@ -82,6 +112,12 @@ struct PlaybackState {
     running: Arc<AtomicBool>,
     /// Audio thread handle (if using threaded approach)
     thread_handle: Option<std::thread::JoinHandle<()>>,
+    /// Whether the output plays silence
+    muted: Arc<AtomicBool>,
+    /// Audio to buffer ahead of the device, in milliseconds
+    latency_ms: Arc<AtomicU32>,
+    /// Counters kept by the playback thread
+    stats: Arc<PlaybackStats>,
 }
 
 impl Default for PlaybackState {
@@ -89,10 +125,38 @@ impl Default for PlaybackState {
         Self {
             running: Arc::new(AtomicBool::new(false)),
             thread_handle: None,
+            muted: Arc::new(AtomicBool::new(false)),
+            latency_ms: Arc::new(AtomicU32::new(AudioConfig::default().latency())),
+            stats: Arc::new(PlaybackStats::default()),
         }
     }
 }
 
+/// Counters the playback thread keeps for the controller
+#[derive(Default)]
+struct PlaybackStats {
+    /// Size of the open stream's ring buffer, in samples
+    buffer_size: AtomicUsize,
+    /// Samples waiting in the ring buffer
+    buffered: AtomicUsize,
+    /// Times the device ran dry in the middle of the guest's audio
+    underruns: AtomicU32,
+    /// Set by the device callback when it runs out of samples
+    ran_dry: AtomicBool,
+}
+
+/// A gap in the guest's audio longer than this is silence, not a dropout
+const SILENCE_GAP: Duration = Duration::from_secs(1);
+
+/// Microphone capture state
+#[derive(Default)]
+struct CaptureState {
+    /// Whether capture is running
+    running: Arc<AtomicBool>,
+    /// Capture thread handle
+    thread_handle: Option<std::thread::JoinHandle<()>>,
+}
+
 /// Rust implementation of the AudioController
 pub struct AudioControllerRust {
     /// Whether audio hardware is available
@@ -123,6 +187,26 @@ pub struct AudioControllerRust {
     playback: RefCell<PlaybackState>,
     /// Cached audio format
     format: RefCell<Option<AudioFormat>>,
+    /// Whether other host applications are turned down
+    host_ducked: bool,
+    /// Ducks other host applications, if configured
+    ducker: RefCell<Option<HostDucker>>,
+    /// Whether the host microphone goes to the guest while it records
+    capture_enabled: bool,
+    /// Whether the microphone is being recorded
+    capture_active: bool,
+    /// Microphone capture state
+    capture: RefCell<CaptureState>,
+    /// Audio buffered ahead of the device, in milliseconds
+    target_latency_ms: i32,
+    /// Ring buffer size of the open stream, in samples
+    buffer_size: i32,
+    /// How full the ring buffer is, in percent
+    buffer_fill: i32,
+    /// Times the host's output ran dry this session
+    underruns: i32,
+    /// Times the guest's sound card ran dry, as the driver counts them
+    guest_underruns: i32,
 }
 
 impl Default for AudioControllerRust {
@@ -142,6 +226,16 @@ impl Default for AudioControllerRust {
             status_text: QString::from("Not initialized"),
             playback: RefCell::new(PlaybackState::default()),
             format: RefCell::new(None),
+            host_ducked: false,
+            ducker: RefCell::new(None),
+            capture_enabled: false,
+            capture_active: false,
+            capture: RefCell::new(CaptureState::default()),
+            target_latency_ms: AudioConfig::default().latency() as i32,
+            buffer_size: 0,
+            buffer_fill: 0,
+            underruns: 0,
+            guest_underruns: 0,
         }
     }
 }
@@ -151,6 +245,14 @@ impl qobject::AudioController {
     pub fn init_audio(mut self: Pin<&mut Self>, fd: i32) -> bool {
         self.as_mut().set_driver_fd(fd);
 
+        let config = load_config().unwrap_or_default().audio;
+        *self.ducker.borrow_mut() = config
+            .duck_host_audio
+            .then(|| HostDucker::new(config.duck_level));
+        self.as_mut().set_capture_enabled(config.microphone);
+        self.as_mut().set_target_latency_ms(config.latency() as i32);
+        self.playback.borrow().latency_ms.store(config.latency(), Ordering::SeqCst);
+
         if fd < 0 {
             self.as_mut().set_audio_available(false);
             self.set_status_text(QString::from("No driver connection"));
@@ -178,6 +280,7 @@ impl qobject::AudioController {
                         self.as_mut().set_volume_right(volume.right as i32);
                         self.as_mut().set_volume_master((volume.left as i32 + volume.right as i32) / 2);
                         self.as_mut().set_audio_muted(volume.muted != 0);
+                        self.playback.borrow().muted.store(volume.muted != 0, Ordering::SeqCst);
                     }
                     
                     self.set_status_text(QString::from("Audio ready"));
@@ -225,13 +328,14 @@ impl qobject::AudioController {
         };
 
         // Start audio playback thread
-        let running = self.playback.borrow().running.clone();
-        let sample_rate = format.sample_rate;
-        let channels = format.channels;
-        let bits = format.bits_per_sample;
-
+        let (running, muted, latency_ms, stats) = {
+            let playback = self.playback.borrow();
+            (playback.running.clone(), playback.muted.clone(), playback.latency_ms.clone(), playback.stats.clone())
+        };
+        stats.underruns.store(0, Ordering::Relaxed);
+        self.as_mut().set_underruns(0);
         let handle = std::thread::spawn(move || {
-            audio_playback_thread(fd, running, sample_rate, channels, bits);
+            audio_playback_thread(fd, running, muted, latency_ms, stats, format);
         });
 
         self.playback.borrow_mut().thread_handle = Some(handle);
@@ -253,6 +357,9 @@ impl qobject::AudioController {
         }
         
         self.as_mut().set_audio_playing(false);
+        self.as_mut().update_ducking(false);
+        self.as_mut().update_capture(false);
+        self.as_mut().set_buffer_fill(0);
         self.set_status_text(QString::from("Stopped"));
     }
 
@@ -260,6 +367,7 @@ impl qobject::AudioController {
     pub fn toggle_mute(mut self: Pin<&mut Self>) {
         let muted = !*self.as_ref().audio_muted();
         self.as_mut().set_audio_muted(muted);
+        self.playback.borrow().muted.store(muted, Ordering::SeqCst);
         
         let fd = *self.as_ref().driver_fd();
         if fd >= 0 {
@@ -299,17 +407,47 @@ impl qobject::AudioController {
         }
     }
 
+    /// Set how much audio is buffered ahead of the device; an open stream
+    /// is reopened with a ring buffer of the new size
+    pub fn set_latency_ms(mut self: Pin<&mut Self>, latency_ms: i32) {
+        let mut config = load_config().unwrap_or_default();
+        config.audio.latency_ms = latency_ms.max(0) as u32;
+        let latency = config.audio.latency();
+        self.as_mut().set_target_latency_ms(latency as i32);
+        self.playback.borrow().latency_ms.store(latency, Ordering::SeqCst);
+        if let Err(e) = save_config(&config) {
+            tracing::warn!("Failed to save audio latency: {}", e);
+        }
+    }
+
     /// Poll for status updates
     pub fn poll_status(mut self: Pin<&mut Self>) {
         let fd = *self.as_ref().driver_fd();
-        if fd < 0 {
+        if fd < 0 || !session_gate::is_open() {
             return;
         }
 
+        let (size, buffered, underruns) = {
+            let playback = self.playback.borrow();
+            let stats = &playback.stats;
+            (
+                stats.buffer_size.load(Ordering::Relaxed),
+                stats.buffered.load(Ordering::Relaxed),
+                stats.underruns.load(Ordering::Relaxed),
+            )
+        };
+        self.as_mut().set_buffer_size(size as i32);
+        self.as_mut().set_buffer_fill((buffered * 100).checked_div(size).unwrap_or(0) as i32);
+        self.as_mut().set_underruns(underruns as i32);
+
         if let Ok(status) = self.query_audio_status(fd) {
             let playing = status.flags & audio_status_flags::PLAYING != 0;
             self.as_mut().set_audio_playing(playing);
+            self.as_mut().update_ducking(playing);
+            let recording = status.is_recording() && *self.as_ref().capture_enabled();
+            self.as_mut().update_capture(recording);
             self.as_mut().set_sample_rate(status.sample_rate as i32);
+            self.as_mut().set_guest_underruns(status.underruns as i32);
             
             // Update format if changed
             if let Ok(format) = self.query_audio_format(fd) {
@@ -337,6 +475,53 @@ impl qobject::AudioController {
     // Private helper methods
     // =========================================================================
 
+    /// Internal: duck other host applications while the guest plays
+    ///
+    /// Acts on changes only, so a missing `pactl` is reported once per
+    /// change rather than on every poll.
+    fn update_ducking(mut self: Pin<&mut Self>, playing: bool) {
+        if *self.as_ref().host_ducked() == playing {
+            return;
+        }
+        {
+            let mut ducker = self.ducker.borrow_mut();
+            let Some(ducker) = ducker.as_mut() else {
+                return;
+            };
+            let result = if playing { ducker.duck() } else { ducker.restore() };
+            match result {
+                Ok(count) if playing => tracing::debug!("Ducked {} host audio streams", count),
+                Ok(count) => tracing::debug!("Restored {} host audio streams", count),
+                Err(e) => tracing::warn!("Cannot adjust host audio volumes: {}", e),
+            }
+        }
+        self.as_mut().set_host_ducked(playing);
+    }
+
+    /// Internal: record the microphone for the guest while it records
+    fn update_capture(mut self: Pin<&mut Self>, recording: bool) {
+        if *self.as_ref().capture_active() == recording {
+            return;
+        }
+        {
+            let mut capture = self.capture.borrow_mut();
+            if recording {
+                let fd = *self.as_ref().driver_fd();
+                let running = capture.running.clone();
+                running.store(true, Ordering::SeqCst);
+                capture.thread_handle = Some(std::thread::spawn(move || audio_capture_thread(fd, running)));
+                tracing::info!("Guest is recording, passing the microphone to it");
+            } else {
+                capture.running.store(false, Ordering::SeqCst);
+                if let Some(handle) = capture.thread_handle.take() {
+                    let _ = handle.join();
+                }
+                tracing::info!("Microphone capture stopped");
+            }
+        }
+        self.as_mut().set_capture_active(recording);
+    }
+
     fn query_audio_status(&self, fd: i32) -> Result<AudioStatus, String> {
         let mut status = AudioStatus::default();
         unsafe {
@@ -461,20 +646,69 @@ impl AudioRingBuffer {
     }
 }
 
+/// How often the playback thread checks whether the guest changed format
+const FORMAT_CHECK: std::time::Duration = std::time::Duration::from_millis(250);
+
 /// Audio playback thread
-/// 
-/// Reads audio samples from the driver and plays them through the system audio.
-/// Uses cpal for cross-platform audio output (ALSA/PipeWire/PulseAudio on Linux).
+///
+/// Plays the guest's audio until stopped, opening the output stream again
+/// whenever the guest changes format (e.g. from 22050 to 44100 Hz, or from
+/// 8-bit mono to 16-bit stereo) or the latency is changed. `muted`,
+/// `latency_ms` and `stats` are shared with the controller and every
+/// stream opened.
 fn audio_playback_thread(
     fd: i32,
     running: Arc<AtomicBool>,
-    sample_rate: u32,
-    channels: u32,
-    bits_per_sample: u32,
+    muted: Arc<AtomicBool>,
+    latency_ms: Arc<AtomicU32>,
+    stats: Arc<PlaybackStats>,
+    mut format: AudioFormat,
 ) {
+    while let Some(next) = play_format(fd, &running, &muted, &latency_ms, &stats, &format) {
+        format = next;
+    }
+    tracing::info!("Audio thread stopped");
+}
+
+/// Rate to open the device at: the guest's if the device takes it,
+/// otherwise the device's own, resampling to it
+fn output_rate(device: &cpal::Device, channels: u16, sample_rate: u32) -> u32 {
+    use cpal::traits::DeviceTrait;
+
+    let supported = device.supported_output_configs().is_ok_and(|mut configs| {
+        configs.any(|c| {
+            c.channels() == channels
+                && c.min_sample_rate().0 <= sample_rate
+                && sample_rate <= c.max_sample_rate().0
+        })
+    });
+    if supported {
+        return sample_rate;
+    }
+    match device.default_output_config() {
+        Ok(config) => config.sample_rate().0,
+        Err(_) => 48000,
+    }
+}
+
+/// Play the guest's audio in one format
+///
+/// Reads audio samples from the driver and plays them through the system audio.
+/// Uses cpal for cross-platform audio output (ALSA/PipeWire/PulseAudio on Linux).
+/// Returns the format to play next when the guest's format or the latency
+/// changes, None when stopped or the stream cannot be opened.
+fn play_format(
+    fd: i32,
+    running: &AtomicBool,
+    muted: &Arc<AtomicBool>,
+    latency_ms: &AtomicU32,
+    stats: &Arc<PlaybackStats>,
+    format: &AudioFormat,
+) -> Option<AudioFormat> {
     use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-    use rising_sun_common::ioctl::{AudioBuffer, sunpci_read_audio};
+    use rising_sun_common::ioctl::{AudioBuffer, sunpci_get_audio_format, sunpci_get_audio_status, sunpci_read_audio};
 
+    let (sample_rate, channels, bits_per_sample) = (format.sample_rate, format.channels, format.bits_per_sample);
     tracing::info!(
         "Audio thread starting: {}Hz, {} channels, {}-bit",
         sample_rate, channels, bits_per_sample
@@ -487,24 +721,35 @@ fn audio_playback_thread(
         Some(d) => d,
         None => {
             tracing::error!("No audio output device found");
-            return;
+            return None;
         }
     };
 
     tracing::info!("Using audio device: {}", device.name().unwrap_or_default());
 
-    // Build stream config matching the guest audio format
+    // Build stream config matching the guest audio format, at a rate the
+    // device takes
+    let output_rate = output_rate(&device, channels as u16, sample_rate);
+    let mut resampler = Resampler::new(sample_rate, output_rate, channels);
+    if !resampler.is_passthrough() {
+        tracing::info!("Device does not take {}Hz, resampling to {}Hz", sample_rate, output_rate);
+    }
     let config = cpal::StreamConfig {
         channels: channels as u16,
-        sample_rate: cpal::SampleRate(sample_rate),
+        sample_rate: cpal::SampleRate(output_rate),
         buffer_size: cpal::BufferSize::Default,
     };
 
-    // Create ring buffer - sized for ~200ms of audio (good balance of latency vs. underrun protection)
-    // At 44100Hz stereo, that's 44100 * 2 * 0.2 = 17640 samples
-    let ring_buffer_size = (sample_rate as usize * channels as usize / 4).max(8192);
+    // Create ring buffer - sized for the configured latency; at 44100Hz
+    // stereo and 250ms that's 44100 * 2 * 0.25 = 22050 samples
+    let latency = latency_ms.load(Ordering::SeqCst);
+    let ring_buffer_size = (output_rate as usize * channels as usize * latency as usize / 1000).max(1024);
     let ring_buffer = Arc::new(AudioRingBuffer::new(ring_buffer_size));
     let ring_buffer_callback = Arc::clone(&ring_buffer);
+    let muted_callback = Arc::clone(muted);
+    let stats_callback = Arc::clone(stats);
+    stats.buffer_size.store(ring_buffer_size, Ordering::Relaxed);
+    stats.ran_dry.store(false, Ordering::Relaxed);
 
     // Error callback
     let err_fn = |err| tracing::error!("Audio stream error: {}", err);
@@ -514,8 +759,13 @@ fn audio_playback_thread(
         &config,
         move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
             let read = ring_buffer_callback.read(data);
-            // Zero-fill any remaining space (underrun)
-            for sample in data[read..].iter_mut() {
+            if read < data.len() {
+                stats_callback.ran_dry.store(true, Ordering::Relaxed);
+            }
+            // Zero-fill any remaining space (underrun), or all of it while
+            // muted so the buffer keeps draining
+            let silent = if muted_callback.load(Ordering::Relaxed) { 0 } else { read };
+            for sample in data[silent..].iter_mut() {
                 *sample = 0;
             }
         },
@@ -527,32 +777,64 @@ fn audio_playback_thread(
         Ok(s) => s,
         Err(e) => {
             tracing::error!("Failed to build audio stream: {}", e);
-            return;
+            return None;
         }
     };
 
     // Start the audio stream
     if let Err(e) = stream.play() {
         tracing::error!("Failed to start audio stream: {}", e);
-        return;
+        return None;
     }
 
-    tracing::info!("Audio stream started (ring buffer: {} samples)", ring_buffer_size);
+    tracing::info!("Audio stream started (ring buffer: {} samples, {}ms)", ring_buffer_size, latency);
 
-    // Pre-allocate conversion buffer to avoid heap allocations in the loop
+    // Pre-allocate conversion buffers to avoid heap allocations in the loop
     let max_samples = 16384 / 2; // AudioBuffer is 16KB, max 8K i16 samples
     let mut sample_buffer: Vec<i16> = Vec::with_capacity(max_samples);
+    let mut resampled: Vec<i16> = Vec::with_capacity(max_samples * 2);
 
     // Buffer for reading from driver
     let mut buffer = AudioBuffer::default();
     
     // Calculate timing based on ring buffer fill level
-    let samples_per_ms = (sample_rate * channels) / 1000;
+    let samples_per_ms = (output_rate * channels) / 1000;
+    let frame_bytes = format.bytes_per_sample().max(1) as usize;
+    let mut format_checked = std::time::Instant::now();
+    let mut last_fed: Option<Instant> = None;
     
     // Main loop: read from driver and feed to ring buffer
     while running.load(Ordering::SeqCst) {
+        // Nothing to read until the session runs
+        if !session_gate::is_open() {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            continue;
+        }
+
+        // A new format needs a new stream; the status tells of one
+        if format_checked.elapsed() >= FORMAT_CHECK {
+            format_checked = std::time::Instant::now();
+            let mut status = AudioStatus::default();
+            if unsafe { sunpci_get_audio_status(fd, &mut status) }.is_ok() && format.differs_from(&status) {
+                let mut current = AudioFormat::default();
+                if unsafe { sunpci_get_audio_format(fd, &mut current) }.is_ok() && current.sample_rate != 0 {
+                    tracing::info!(
+                        "Guest audio format changed to {}Hz, {} channels, {}-bit",
+                        current.sample_rate, current.channels, current.bits_per_sample
+                    );
+                    return Some(current);
+                }
+            }
+            // So does a new latency, for a ring buffer of the new size
+            if latency_ms.load(Ordering::SeqCst) != latency {
+                tracing::info!("Audio latency changed to {}ms", latency_ms.load(Ordering::SeqCst));
+                return Some(*format);
+            }
+        }
+
         // Check ring buffer fill level
         let available = ring_buffer.available();
+        stats.buffered.store(available, Ordering::Relaxed);
         let fill_percent = (available * 100) / ring_buffer_size;
         
         // If buffer is >75% full, sleep a bit to let it drain
@@ -563,33 +845,49 @@ fn audio_playback_thread(
             continue;
         }
 
-        // Read from driver
-        buffer.size = buffer.data.len() as u32;
+        // Read from driver, no more than the ring buffer has room for once
+        // converted to the device's rate
+        let room = ring_buffer.free_space() / channels as usize * sample_rate as usize / output_rate as usize;
+        let wanted = (room * frame_bytes).min(buffer.data.len());
+        if wanted < frame_bytes {
+            std::thread::sleep(std::time::Duration::from_millis(2));
+            continue;
+        }
+        buffer.size = (wanted - wanted % frame_bytes) as u32;
         let result = unsafe { sunpci_read_audio(fd, &mut buffer) };
         
         match result {
             Ok(_) => {
                 let bytes_read = buffer.size as usize;
                 if bytes_read > 0 {
+                    // The device running out between two pieces of the
+                    // guest's audio is a dropout; after a pause it is not
+                    let now = Instant::now();
+                    if stats.ran_dry.swap(false, Ordering::Relaxed)
+                        && last_fed.is_some_and(|t| now.duration_since(t) < SILENCE_GAP)
+                    {
+                        stats.underruns.fetch_add(1, Ordering::Relaxed);
+                    }
+                    last_fed = Some(now);
+
                     // Convert to i16 samples
                     sample_buffer.clear();
-                    if bits_per_sample == 16 {
-                        // 16-bit signed - reinterpret bytes as i16
-                        for chunk in buffer.data[..bytes_read].chunks_exact(2) {
-                            sample_buffer.push(i16::from_le_bytes([chunk[0], chunk[1]]));
-                        }
+                    format.decode(&buffer.data[..bytes_read], &mut sample_buffer);
+
+                    // Convert to the device's rate
+                    let samples = if resampler.is_passthrough() {
+                        &sample_buffer
                     } else {
-                        // 8-bit unsigned - convert to 16-bit signed
-                        for &b in buffer.data[..bytes_read].iter() {
-                            sample_buffer.push((b as i16 - 128) * 256);
-                        }
-                    }
+                        resampled.clear();
+                        resampler.process(&sample_buffer, &mut resampled);
+                        &resampled
+                    };
 
                     // Write to ring buffer
-                    let written = ring_buffer.write(&sample_buffer);
-                    if written < sample_buffer.len() {
+                    let written = ring_buffer.write(samples);
+                    if written < samples.len() {
                         tracing::trace!("Ring buffer overflow, dropped {} samples", 
-                            sample_buffer.len() - written);
+                            samples.len() - written);
                     }
                 } else {
                     // No data from driver, brief sleep
@@ -605,5 +903,124 @@ fn audio_playback_thread(
 
     // Stop the stream
     drop(stream);
-    tracing::info!("Audio thread stopped");
+    None
+}
+
+/// Audio capture thread
+///
+/// Records the host's default input device and writes it to the guest's
+/// input as 16-bit mono at the driver's capture rate. Audio the guest has
+/// no room for is dropped: recording is live, not queued.
+fn audio_capture_thread(fd: i32, running: Arc<AtomicBool>) {
+    use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+    use rising_sun_common::ioctl::{
+        AudioBuffer, SUNPCI_AUDIO_CAPTURE_RATE, SUNPCI_AUDIO_MAX_BUFFER, sunpci_write_audio,
+    };
+
+    let host = cpal::default_host();
+    let device = match host.default_input_device() {
+        Some(d) => d,
+        None => {
+            tracing::error!("No audio input device found");
+            return;
+        }
+    };
+    let config = match device.default_input_config() {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!("Cannot query audio input device: {}", e);
+            return;
+        }
+    };
+    let channels = config.channels().max(1) as usize;
+    let input_rate = config.sample_rate().0;
+    tracing::info!(
+        "Recording from {}: {}Hz, {} channels",
+        device.name().unwrap_or_default(), input_rate, channels
+    );
+
+    // About half a second of input between the callback and this thread
+    let ring_buffer = Arc::new(AudioRingBuffer::new((input_rate as usize * channels / 2).max(8192)));
+    let ring_buffer_callback = Arc::clone(&ring_buffer);
+    let err_fn = |err| tracing::error!("Audio input error: {}", err);
+
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config.config(),
+            move |data: &[i16], _: &cpal::InputCallbackInfo| {
+                ring_buffer_callback.write(data);
+            },
+            err_fn,
+            None,
+        ),
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config.config(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let mut converted = [0i16; 256];
+                for chunk in data.chunks(converted.len()) {
+                    for (out, &sample) in converted.iter_mut().zip(chunk) {
+                        *out = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                    }
+                    ring_buffer_callback.write(&converted[..chunk.len()]);
+                }
+            },
+            err_fn,
+            None,
+        ),
+        other => {
+            tracing::error!("Audio input sample format {:?} is not supported", other);
+            return;
+        }
+    };
+    let stream = match stream {
+        Ok(s) => s,
+        Err(e) => {
+            tracing::error!("Failed to build audio input stream: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = stream.play() {
+        tracing::error!("Failed to start audio input stream: {}", e);
+        return;
+    }
+
+    let mut resampler = Resampler::new(input_rate, SUNPCI_AUDIO_CAPTURE_RATE, 1);
+    let mut recorded = vec![0i16; 4096 * channels];
+    let mut mono: Vec<i16> = Vec::with_capacity(4096);
+    let mut converted: Vec<i16> = Vec::with_capacity(4096);
+    let mut bytes: Vec<u8> = Vec::with_capacity(SUNPCI_AUDIO_MAX_BUFFER);
+    let mut buffer = AudioBuffer::default();
+
+    while running.load(Ordering::SeqCst) {
+        // Whole frames only
+        let wanted = ring_buffer.available().min(recorded.len());
+        let read = ring_buffer.read(&mut recorded[..wanted - wanted % channels]);
+        if read == 0 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            continue;
+        }
+
+        // Down to mono at the capture rate
+        mono.clear();
+        mono.extend(recorded[..read].chunks_exact(channels).map(|frame| {
+            (frame.iter().map(|&s| s as i32).sum::<i32>() / channels as i32) as i16
+        }));
+        converted.clear();
+        resampler.process(&mono, &mut converted);
+        bytes.clear();
+        bytes.extend(converted.iter().flat_map(|s| s.to_le_bytes()));
+
+        for chunk in bytes.chunks(SUNPCI_AUDIO_MAX_BUFFER) {
+            buffer.size = chunk.len() as u32;
+            buffer.data[..chunk.len()].copy_from_slice(chunk);
+            if let Err(e) = unsafe { sunpci_write_audio(fd, &mut buffer) } {
+                tracing::warn!("Audio write error: {}", e);
+                std::thread::sleep(std::time::Duration::from_millis(20));
+                break;
+            }
+        }
+    }
+
+    drop(stream);
+    tracing::info!("Audio capture thread stopped");
 }
@@ -4,21 +4,36 @@
 //! - Monitoring host clipboard changes → sending to guest
 //! - Polling guest clipboard → updating host clipboard
 //! - Bidirectional clipboard sync with direction control
+//! - Bitmap transfer (CF_DIB) using the chunked clipboard ioctls
+//! - Rich text: host HTML/RTF ↔ guest CF_RTF, also chunked
+//! - Text larger than one ioctl buffer, also sent/received in chunks
+//! - 8-bit guest text in the configured (or guessed) OEM code page
+//! - Line ending and trailing NUL cleanup per direction (`[clipboard]` config)
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use rising_sun_common::ioctl::{Clipboard, SUNPCI_MAX_CLIPBOARD, clipboard_format};
+use rising_sun_common::ioctl::{
+    Clipboard, ClipboardChunk, SUNPCI_MAX_CLIPBOARD, SUNPCI_MAX_CLIPBOARD_TRANSFER,
+    clipboard_format,
+};
+
+use rising_sun_common::clipboard_bitmap::{dib_to_rgba, rgba_to_dib};
+use rising_sun_common::clipboard_rtf::{html_to_rtf, rtf_to_html};
+use rising_sun_common::clipboard_text::{
+    decode_text, encode_utf16le, normalize,
+};
+use rising_sun_common::codepage::CodePage;
+use rising_sun_common::{load_config, ClipboardConfig, TextNormalization};
 
 #[cxx_qt::bridge]
 mod qobject {
     unsafe extern "C++Qt" {
         include!("cxx-qt-lib/qstring.h");
         type QString = cxx_qt_lib::QString;
-        
-        include!(<QtGui/QGuiApplication>);
-        include!(<QtGui/QClipboard>);
+        include!("cxx-qt-lib/qimage.h");
+        type QImage = cxx_qt_lib::QImage;
     }
 
     unsafe extern "RustQt" {
@@ -27,6 +42,9 @@ mod qobject {
         #[qproperty(bool, clipboard_enabled)]
         #[qproperty(bool, host_to_guest)]
         #[qproperty(bool, guest_to_host)]
+        #[qproperty(bool, share_images)]
+        #[qproperty(bool, share_rich_text)]
+        #[qproperty(QString, code_page)]
         #[qproperty(i32, driver_fd)]
         #[qproperty(QString, last_host_text)]
         #[qproperty(QString, last_guest_text)]
@@ -51,6 +69,18 @@ mod qobject {
         #[qinvokable]
         fn on_host_clipboard_changed(self: Pin<&mut ClipboardController>, text: QString);
 
+        /// Called when host clipboard holds an image
+        #[qinvokable]
+        fn on_host_image_changed(self: Pin<&mut ClipboardController>, image: &QImage);
+
+        /// Called when host clipboard holds formatted text (HTML and/or RTF)
+        #[qinvokable]
+        fn on_host_rich_text_changed(
+            self: Pin<&mut ClipboardController>,
+            html: QString,
+            rtf: QString,
+        );
+
         /// Poll guest clipboard and update host if changed
         #[qinvokable]
         fn poll_guest_clipboard(self: Pin<&mut ClipboardController>);
@@ -67,19 +97,52 @@ mod qobject {
         #[qinvokable]
         fn get_stats(self: &ClipboardController) -> QString;
 
-        /// Signal emitted when guest clipboard has new content for host
+        /// Signal emitted when host clipboard content could not be sent to
+        /// the guest, with a message for the user
         #[qsignal]
-        fn guest_clipboard_changed(self: Pin<&mut ClipboardController>, text: QString);
+        fn transfer_failed(self: Pin<&mut ClipboardController>, message: QString);
 
         /// Signal emitted when clipboard sync status changes
         #[qsignal]
         fn status_changed(self: Pin<&mut ClipboardController>, status: QString);
     }
+
+    unsafe extern "C++" {
+        include!("host_clipboard.h");
+
+        /// Call the controller's `on_host_*` handlers on host clipboard
+        /// changes
+        #[namespace = "rising_sun"]
+        #[cxx_name = "watchHostClipboard"]
+        fn watch_host_clipboard(controller: Pin<&mut ClipboardController>);
+
+        /// Put text on the host clipboard
+        #[namespace = "rising_sun"]
+        #[cxx_name = "setHostClipboardText"]
+        fn set_host_clipboard_text(text: &QString);
+
+        /// Put an image on the host clipboard
+        #[namespace = "rising_sun"]
+        #[cxx_name = "setHostClipboardImage"]
+        fn set_host_clipboard_image(image: &QImage);
+
+        /// Put formatted text on the host clipboard as HTML and RTF, with
+        /// a plain text version
+        #[namespace = "rising_sun"]
+        #[cxx_name = "setHostClipboardRichText"]
+        fn set_host_clipboard_rich_text(html: &QString, rtf: &QString, text: &QString);
+    }
+
+    impl cxx_qt::Initialize for ClipboardController {}
 }
 
 use std::pin::Pin;
-use cxx_qt_lib::QString;
-use rising_sun_common::ioctl::{sunpci_get_clipboard, sunpci_set_clipboard};
+use cxx_qt_lib::{QImage, QImageFormat, QPoint, QString};
+use super::session_gate;
+use rising_sun_common::ioctl::{
+    sunpci_get_clipboard, sunpci_get_clipboard_chunk, sunpci_set_clipboard,
+    sunpci_set_clipboard_chunk,
+};
 
 /// Rust implementation of the ClipboardController
 pub struct ClipboardControllerRust {
@@ -89,6 +152,12 @@ pub struct ClipboardControllerRust {
     host_to_guest: bool,
     /// Whether guest→host transfer is enabled
     guest_to_host: bool,
+    /// Whether images are shared (in addition to text)
+    share_images: bool,
+    /// Whether formatted text is shared (in addition to plain text)
+    share_rich_text: bool,
+    /// Guest OEM code page for 8-bit text ("437", "850", ... or "auto")
+    code_page: QString,
     /// Driver file descriptor
     driver_fd: i32,
     /// Last text sent from host (to avoid loops)
@@ -105,6 +174,18 @@ pub struct ClipboardControllerRust {
     last_host_hash: RefCell<u64>,
     /// Internal: last guest clipboard hash (to detect changes)
     last_guest_hash: RefCell<u64>,
+    /// Internal: last host image hash
+    last_host_image_hash: RefCell<u64>,
+    /// Internal: last guest image hash
+    last_guest_image_hash: RefCell<u64>,
+    /// Internal: last host RTF hash
+    last_host_rtf_hash: RefCell<u64>,
+    /// Internal: last guest RTF hash
+    last_guest_rtf_hash: RefCell<u64>,
+    /// Internal: cleanup of text copied in the guest
+    guest_to_host_text: Cell<TextNormalization>,
+    /// Internal: cleanup of text copied on the host
+    host_to_guest_text: Cell<TextNormalization>,
     /// Internal: whether we're currently updating clipboard (to prevent recursion)
     updating: Arc<AtomicBool>,
 }
@@ -115,6 +196,9 @@ impl Default for ClipboardControllerRust {
             clipboard_enabled: true,
             host_to_guest: true,
             guest_to_host: true,
+            share_images: true,
+            share_rich_text: true,
+            code_page: QString::from("437"),
             driver_fd: -1,
             last_host_text: QString::from(""),
             last_guest_text: QString::from(""),
@@ -123,20 +207,48 @@ impl Default for ClipboardControllerRust {
             status_text: QString::from("Clipboard disabled"),
             last_host_hash: RefCell::new(0),
             last_guest_hash: RefCell::new(0),
+            last_host_image_hash: RefCell::new(0),
+            last_guest_image_hash: RefCell::new(0),
+            last_host_rtf_hash: RefCell::new(0),
+            last_guest_rtf_hash: RefCell::new(0),
+            guest_to_host_text: Cell::new(ClipboardConfig::default().guest_to_host_text),
+            host_to_guest_text: Cell::new(ClipboardConfig::default().host_to_guest_text),
             updating: Arc::new(AtomicBool::new(false)),
         }
     }
 }
 
+/// First 100 characters of clipboard text, for display
+fn preview(text: &str) -> String {
+    text.chars().take(100).collect()
+}
+
 /// Simple hash for clipboard text comparison
 fn hash_text(text: &str) -> u64 {
+    hash_bytes(text.as_bytes())
+}
+
+/// Message for clipboard content too large for the driver to transfer
+fn too_large_message(len: usize) -> String {
+    format!("Clipboard too large for the guest ({} KiB, the limit is {} KiB)",
+        len / 1024, SUNPCI_MAX_CLIPBOARD_TRANSFER / 1024)
+}
+
+/// Simple hash for clipboard data comparison
+fn hash_bytes(data: &[u8]) -> u64 {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
     let mut hasher = DefaultHasher::new();
-    text.hash(&mut hasher);
+    data.hash(&mut hasher);
     hasher.finish()
 }
 
+impl cxx_qt::Initialize for qobject::ClipboardController {
+    fn initialize(self: Pin<&mut Self>) {
+        qobject::watch_host_clipboard(self);
+    }
+}
+
 impl qobject::ClipboardController {
     /// Initialize clipboard controller with driver file descriptor
     pub fn init_clipboard(mut self: Pin<&mut Self>, fd: i32) -> bool {
@@ -147,6 +259,10 @@ impl qobject::ClipboardController {
         }
 
         self.as_mut().set_driver_fd(fd);
+
+        let config = load_config().unwrap_or_default().clipboard;
+        self.guest_to_host_text.set(config.guest_to_host_text);
+        self.host_to_guest_text.set(config.host_to_guest_text);
         
         if self.clipboard_enabled {
             self.as_mut().set_status_text(QString::from("Clipboard ready"));
@@ -227,11 +343,86 @@ impl qobject::ClipboardController {
         *self.last_host_hash.borrow_mut() = new_hash;
 
         // Send to guest
-        if self.send_to_guest_internal(&text_str) {
-            self.as_mut().set_last_host_text(QString::from(&text_str[..text_str.len().min(100)]));
-            let count = self.host_to_guest_count + 1;
-            self.as_mut().set_host_to_guest_count(count);
-            tracing::debug!("Sent clipboard to guest ({} bytes)", text_str.len());
+        match self.send_to_guest_internal(&text_str) {
+            Ok(()) => {
+                self.as_mut().set_last_host_text(QString::from(&preview(&text_str)));
+                let count = self.host_to_guest_count + 1;
+                self.as_mut().set_host_to_guest_count(count);
+                tracing::debug!("Sent clipboard to guest ({} bytes)", text_str.len());
+            }
+            Err(message) => self.as_mut().transfer_failed(QString::from(&message)),
+        }
+    }
+
+    /// Called when host clipboard holds an image
+    pub fn on_host_image_changed(mut self: Pin<&mut Self>, image: &QImage) {
+        if !self.clipboard_enabled || !self.host_to_guest || !self.share_images {
+            return;
+        }
+        if self.updating.load(Ordering::SeqCst) || image.is_null() {
+            return;
+        }
+
+        let (width, height, rgba) = qimage_to_rgba(image);
+        let Some(dib) = rgba_to_dib(width, height, &rgba) else {
+            return;
+        };
+
+        let new_hash = hash_bytes(&dib);
+        if new_hash == *self.last_host_image_hash.borrow() {
+            return;
+        }
+        *self.last_host_image_hash.borrow_mut() = new_hash;
+
+        match self.send_chunked(clipboard_format::BITMAP, &dib) {
+            Ok(()) => {
+                let count = self.host_to_guest_count + 1;
+                self.as_mut().set_host_to_guest_count(count);
+                tracing::debug!("Sent clipboard image to guest ({}x{}, {} bytes)",
+                    width, height, dib.len());
+            }
+            Err(message) => self.as_mut().transfer_failed(QString::from(&message)),
+        }
+    }
+
+    /// Called when host clipboard holds formatted text
+    ///
+    /// RTF is forwarded as-is; otherwise the HTML is converted.
+    pub fn on_host_rich_text_changed(mut self: Pin<&mut Self>, html: QString, rtf: QString) {
+        if !self.clipboard_enabled || !self.host_to_guest || !self.share_rich_text {
+            return;
+        }
+        if self.updating.load(Ordering::SeqCst) {
+            return;
+        }
+
+        let rtf = rtf.to_string();
+        let rtf = if rtf.starts_with("{\\rtf") {
+            rtf
+        } else {
+            let html = html.to_string();
+            if html.is_empty() {
+                return;
+            }
+            html_to_rtf(&html)
+        };
+
+        let new_hash = hash_text(&rtf);
+        if new_hash == *self.last_host_rtf_hash.borrow() {
+            return;
+        }
+        *self.last_host_rtf_hash.borrow_mut() = new_hash;
+
+        // CF_RTF is a null-terminated 8-bit string
+        let mut data = rtf.into_bytes();
+        data.push(0);
+        match self.send_chunked(clipboard_format::RTF, &data) {
+            Ok(()) => {
+                let count = self.host_to_guest_count + 1;
+                self.as_mut().set_host_to_guest_count(count);
+                tracing::debug!("Sent rich text to guest ({} bytes)", data.len());
+            }
+            Err(message) => self.as_mut().transfer_failed(QString::from(&message)),
         }
     }
 
@@ -242,7 +433,7 @@ impl qobject::ClipboardController {
             return;
         }
 
-        if self.driver_fd < 0 {
+        if self.driver_fd < 0 || !session_gate::is_open() {
             return;
         }
 
@@ -254,8 +445,30 @@ impl qobject::ClipboardController {
         // Get clipboard from guest
         let result = self.get_from_guest_internal();
         
+        let image = if self.share_images {
+            self.get_chunked(clipboard_format::BITMAP)
+        } else {
+            None
+        };
+
+        let rich_text = if self.share_rich_text {
+            self.get_chunked(clipboard_format::RTF)
+        } else {
+            None
+        };
+
         self.updating.store(false, Ordering::SeqCst);
 
+        if let Some(dib) = image {
+            self.as_mut().handle_guest_image(&dib);
+        }
+
+        // Formatted text goes to the host with the plain text alongside
+        let rich_text_sent = match rich_text {
+            Some(rtf) => self.as_mut().handle_guest_rich_text(&rtf, result.as_deref()),
+            None => false,
+        };
+
         if let Some(text) = result {
             if text.is_empty() {
                 return;
@@ -279,72 +492,62 @@ impl qobject::ClipboardController {
 
             tracing::debug!("Guest clipboard changed: {} bytes", text.len());
             
-            let preview = text[..text.len().min(100)].to_string();
-            self.as_mut().set_last_guest_text(QString::from(&preview));
+            self.as_mut().set_last_guest_text(QString::from(&preview(&text)));
             
             let count = self.guest_to_host_count + 1;
             self.as_mut().set_guest_to_host_count(count);
 
-            // Emit signal for QML to update host clipboard
-            let text_qstring = QString::from(&text);
-            self.as_mut().guest_clipboard_changed(text_qstring);
+            // Don't send it back when the host clipboard reports the change
+            *self.last_host_hash.borrow_mut() = new_hash;
+            if !rich_text_sent {
+                self.updating.store(true, Ordering::SeqCst);
+                qobject::set_host_clipboard_text(&QString::from(&text));
+                self.updating.store(false, Ordering::SeqCst);
+            }
         }
     }
 
     /// Send text to guest clipboard (callable from QML)
-    pub fn send_to_guest(self: Pin<&mut Self>, text: QString) -> bool {
+    pub fn send_to_guest(mut self: Pin<&mut Self>, text: QString) -> bool {
         if self.driver_fd < 0 {
             return false;
         }
-        self.send_to_guest_internal(&text.to_string())
+        match self.send_to_guest_internal(&text.to_string()) {
+            Ok(()) => true,
+            Err(message) => {
+                self.as_mut().transfer_failed(QString::from(&message));
+                false
+            }
+        }
     }
 
-    /// Internal: send text to guest
-    fn send_to_guest_internal(&self, text: &str) -> bool {
+    /// Internal: send text to guest, or a message for the user saying why not
+    fn send_to_guest_internal(&self, text: &str) -> Result<(), String> {
         if self.driver_fd < 0 {
-            return false;
+            return Err("No driver connection".to_string());
         }
 
         let mut clipboard = Clipboard::default();
+        let text = &normalize(text, &self.host_to_guest_text.get());
         
-        // Convert to UTF-16LE for Windows guest
-        let utf16: Vec<u16> = text.encode_utf16().collect();
-        let bytes: Vec<u8> = utf16.iter()
-            .flat_map(|&c| c.to_le_bytes())
-            .collect();
-
-        // Check size limit
-        if bytes.len() > SUNPCI_MAX_CLIPBOARD - 2 {
-            tracing::warn!("Clipboard text too large: {} bytes (max {})", 
-                bytes.len(), SUNPCI_MAX_CLIPBOARD - 2);
-            // Truncate to fit
-            let max_chars = (SUNPCI_MAX_CLIPBOARD - 2) / 2;
-            let truncated: Vec<u8> = text.encode_utf16()
-                .take(max_chars)
-                .flat_map(|c| c.to_le_bytes())
-                .collect();
-            clipboard.data[..truncated.len()].copy_from_slice(&truncated);
-            clipboard.length = truncated.len() as u32 + 2; // +2 for null terminator
-            // Add null terminator
-            clipboard.data[truncated.len()] = 0;
-            clipboard.data[truncated.len() + 1] = 0;
-        } else {
-            clipboard.data[..bytes.len()].copy_from_slice(&bytes);
-            clipboard.length = bytes.len() as u32 + 2; // +2 for null terminator
-            // Add null terminator
-            clipboard.data[bytes.len()] = 0;
-            clipboard.data[bytes.len() + 1] = 0;
-        }
+        // Convert to null-terminated UTF-16LE for Windows guest
+        let bytes = encode_utf16le(text);
 
+        // Text that doesn't fit one ioctl goes through the chunked protocol
+        if bytes.len() > SUNPCI_MAX_CLIPBOARD {
+            return self.send_chunked(clipboard_format::UNICODE, &bytes);
+        }
+        clipboard.data[..bytes.len()].copy_from_slice(&bytes);
+        clipboard.length = bytes.len() as u32;
         clipboard.format = clipboard_format::UNICODE;
 
         let result = unsafe { sunpci_set_clipboard(self.driver_fd, &clipboard) };
         
         match result {
-            Ok(_) => true,
+            Ok(_) => Ok(()),
             Err(e) => {
                 tracing::error!("Failed to set guest clipboard: {}", e);
-                false
+                Err(format!("Could not send the clipboard to the guest: {e}"))
             }
         }
     }
@@ -379,17 +582,17 @@ impl qobject::ClipboardController {
                     return None;
                 }
 
-                let text = if clipboard.format == clipboard_format::UNICODE {
-                    // UTF-16LE from Windows
-                    decode_utf16le(&clipboard.data[..len])
+                // A full buffer may be truncated; fetch the whole text in chunks
+                let chunked = if len == SUNPCI_MAX_CLIPBOARD {
+                    self.get_chunked(clipboard.format)
                 } else {
-                    // Plain text (assume ASCII/Latin-1)
-                    String::from_utf8_lossy(&clipboard.data[..len])
-                        .trim_end_matches('\0')
-                        .to_string()
+                    None
                 };
+                let data = chunked.as_deref().unwrap_or(&clipboard.data[..len]);
 
-                Some(text)
+                let code_page = CodePage::from_name(&self.code_page.to_string());
+                let text = decode_text(clipboard.format, data, code_page);
+                Some(normalize(&text, &self.guest_to_host_text.get()))
             }
             Err(e) => {
                 // Don't log every poll failure - EAGAIN is normal when no clipboard data
@@ -399,6 +602,155 @@ impl qobject::ClipboardController {
         }
     }
 
+    /// Internal: put a new guest bitmap on the host clipboard
+    fn handle_guest_image(mut self: Pin<&mut Self>, dib: &[u8]) {
+        let new_hash = hash_bytes(dib);
+        if new_hash == *self.last_guest_image_hash.borrow()
+            || new_hash == *self.last_host_image_hash.borrow()
+        {
+            return;
+        }
+        *self.last_guest_image_hash.borrow_mut() = new_hash;
+
+        let Some((width, height, rgba)) = dib_to_rgba(dib) else {
+            tracing::warn!("Unsupported bitmap from guest clipboard ({} bytes)", dib.len());
+            return;
+        };
+
+        tracing::debug!("Guest clipboard image changed: {}x{}", width, height);
+
+        let count = self.guest_to_host_count + 1;
+        self.as_mut().set_guest_to_host_count(count);
+
+        // The host clipboard hands the image back as it would a copied one;
+        // remember that bitmap so it isn't sent back
+        if let Some(echo) = rgba_to_dib(width, height, &rgba) {
+            *self.last_host_image_hash.borrow_mut() = hash_bytes(&echo);
+        }
+
+        // Safety: the image owns the pixels, which are width * height RGBA
+        let image = unsafe {
+            QImage::from_raw_bytes(rgba, width as i32, height as i32, QImageFormat::Format_RGBA8888)
+        };
+        self.updating.store(true, Ordering::SeqCst);
+        qobject::set_host_clipboard_image(&image);
+        self.updating.store(false, Ordering::SeqCst);
+    }
+
+    /// Internal: put new guest RTF on the host clipboard, with an HTML
+    /// rendition and the guest's plain text. Returns whether it did.
+    fn handle_guest_rich_text(self: Pin<&mut Self>, data: &[u8], text: Option<&str>) -> bool {
+        let rtf = String::from_utf8_lossy(data).trim_end_matches('\0').to_string();
+        if !rtf.starts_with("{\\rtf") {
+            tracing::warn!("Ignoring malformed RTF from guest clipboard ({} bytes)", data.len());
+            return false;
+        }
+
+        let new_hash = hash_text(&rtf);
+        if new_hash == *self.last_guest_rtf_hash.borrow()
+            || new_hash == *self.last_host_rtf_hash.borrow()
+        {
+            return false;
+        }
+        *self.last_guest_rtf_hash.borrow_mut() = new_hash;
+        // The host clipboard hands the RTF back unchanged; don't send it back
+        *self.last_host_rtf_hash.borrow_mut() = new_hash;
+
+        tracing::debug!("Guest clipboard rich text changed: {} bytes", rtf.len());
+
+        let html = rtf_to_html(&rtf);
+        self.updating.store(true, Ordering::SeqCst);
+        qobject::set_host_clipboard_rich_text(
+            &QString::from(&html),
+            &QString::from(&rtf),
+            &QString::from(text.unwrap_or_default()),
+        );
+        self.updating.store(false, Ordering::SeqCst);
+        true
+    }
+
+    /// Internal: send data larger than one ioctl as a chunked transfer, or
+    /// a message for the user saying why not
+    fn send_chunked(&self, format: u32, data: &[u8]) -> Result<(), String> {
+        if self.driver_fd < 0 {
+            return Err("No driver connection".to_string());
+        }
+        if data.is_empty() {
+            return Ok(());
+        }
+        if data.len() > SUNPCI_MAX_CLIPBOARD_TRANSFER {
+            tracing::warn!("Clipboard data too large: {} bytes (max {})",
+                data.len(), SUNPCI_MAX_CLIPBOARD_TRANSFER);
+            return Err(too_large_message(data.len()));
+        }
+
+        let mut chunk = ClipboardChunk {
+            format,
+            total_length: data.len() as u32,
+            ..Default::default()
+        };
+
+        for (i, part) in data.chunks(SUNPCI_MAX_CLIPBOARD).enumerate() {
+            chunk.offset = (i * SUNPCI_MAX_CLIPBOARD) as u32;
+            chunk.length = part.len() as u32;
+            chunk.data[..part.len()].copy_from_slice(part);
+
+            if let Err(e) = unsafe { sunpci_set_clipboard_chunk(self.driver_fd, &chunk) } {
+                tracing::error!("Failed to send clipboard chunk at {}: {}", chunk.offset, e);
+                let too_large = std::io::Error::from(e).kind() == std::io::ErrorKind::ArgumentListTooLong;
+                return Err(if too_large {
+                    too_large_message(data.len())
+                } else {
+                    format!("Could not send the clipboard to the guest: {e}")
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Internal: read a complete chunked transfer from the guest
+    fn get_chunked(&self, format: u32) -> Option<Vec<u8>> {
+        if self.driver_fd < 0 {
+            return None;
+        }
+
+        let mut chunk = ClipboardChunk {
+            format,
+            ..Default::default()
+        };
+        let mut data = Vec::new();
+
+        loop {
+            chunk.offset = data.len() as u32;
+            if let Err(e) = unsafe { sunpci_get_clipboard_chunk(self.driver_fd, &mut chunk) } {
+                // ENODATA (no data in this format) is normal while polling
+                tracing::trace!("Failed to get clipboard chunk: {}", e);
+                return None;
+            }
+
+            let total = chunk.total_length as usize;
+            let len = chunk.length as usize;
+            if total == 0 {
+                return None;
+            }
+            if total > SUNPCI_MAX_CLIPBOARD_TRANSFER
+                || len == 0
+                || len > SUNPCI_MAX_CLIPBOARD
+                || data.len() + len > total
+            {
+                tracing::warn!("Invalid clipboard chunk: offset={} length={} total={}",
+                    data.len(), len, total);
+                return None;
+            }
+
+            data.extend_from_slice(&chunk.data[..len]);
+            if data.len() == total {
+                return Some(data);
+            }
+        }
+    }
+
     /// Get clipboard statistics
     pub fn get_stats(&self) -> QString {
         QString::from(&format!(
@@ -408,20 +760,25 @@ impl qobject::ClipboardController {
     }
 }
 
-/// Decode UTF-16LE bytes to String
-fn decode_utf16le(bytes: &[u8]) -> String {
-    if bytes.len() < 2 {
-        return String::new();
+/// Extract RGBA pixels from a QImage
+fn qimage_to_rgba(image: &QImage) -> (u32, u32, Vec<u8>) {
+    let width = image.width().max(0);
+    let height = image.height().max(0);
+    let mut rgba = Vec::with_capacity(width as usize * height as usize * 4);
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = image.pixel_color(QPoint::new(x, y));
+            rgba.extend_from_slice(&[
+                color.red() as u8,
+                color.green() as u8,
+                color.blue() as u8,
+                color.alpha() as u8,
+            ]);
+        }
     }
 
-    // Convert bytes to u16 values
-    let u16_values: Vec<u16> = bytes
-        .chunks_exact(2)
-        .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
-        .take_while(|&c| c != 0) // Stop at null terminator
-        .collect();
-
-    String::from_utf16_lossy(&u16_values)
+    (width as u32, height as u32, rgba)
 }
 
 #[cfg(test)]
@@ -429,17 +786,10 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_decode_utf16le() {
-        // "Hello" in UTF-16LE
-        let bytes = [0x48, 0x00, 0x65, 0x00, 0x6C, 0x00, 0x6C, 0x00, 0x6F, 0x00, 0x00, 0x00];
-        assert_eq!(decode_utf16le(&bytes), "Hello");
-    }
-
-    #[test]
-    fn test_decode_utf16le_unicode() {
-        // "日本" in UTF-16LE
-        let bytes = [0xE5, 0x65, 0x2C, 0x67, 0x00, 0x00];
-        assert_eq!(decode_utf16le(&bytes), "日本");
+    fn test_preview_multibyte() {
+        // Must not split a multi-byte character
+        let text = "é".repeat(150);
+        assert_eq!(preview(&text).chars().count(), 100);
     }
 
     #[test]
@@ -1,8 +1,23 @@
 //! Configuration manager Qt bridge for accessing persistent settings from QML.
 
-use rising_sun_common::{AppConfig, load_config, save_config, DiskConfig, DriveMapping};
-use std::path::PathBuf;
+use rising_sun_common::{
+    AppConfig, load_config, save_config, BootDevice, KnownBios, DeinterlaceMode, DiskConfig, MouseProtocol,
+    NetworkMode, PresentationMode, ScalingMode, RecentCategory, config_backups, restore_config_backup,
+};
+use rising_sun_common::bios;
+use rising_sun_common::config_watch::ConfigWatcher;
+use rising_sun_common::crt;
+use rising_sun_common::image_ref;
+use rising_sun_common::media_check::{self, FixAction, MediaIssue, MediaReport, MediaSlot};
+use rising_sun_common::settings_bundle;
+use std::path::{Path, PathBuf};
 use std::cell::RefCell;
+use std::pin::Pin;
+use std::time::UNIX_EPOCH;
+
+use super::logging;
+use super::recent_files_model;
+use super::startup;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -42,9 +57,57 @@ mod qobject {
         #[qinvokable]
         fn set_integer_scaling_value(self: &ConfigManager, value: bool);
         #[qinvokable]
+        fn get_scaling_mode(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_scaling_mode_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_scale_factor(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn set_scale_factor_value(self: &ConfigManager, value: i32);
+        #[qinvokable]
         fn get_scanline_effect(self: &ConfigManager) -> bool;
         #[qinvokable]
         fn set_scanline_effect_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_start_fullscreen(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_start_fullscreen_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_fullscreen_hide_menu(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_fullscreen_hide_menu_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_fullscreen_screen(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn set_fullscreen_screen_value(self: &ConfigManager, value: i32);
+        #[qinvokable]
+        fn get_crt_effect(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_crt_effect_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_scanline_intensity(self: &ConfigManager) -> f64;
+        #[qinvokable]
+        fn set_scanline_intensity_value(self: &ConfigManager, value: f64);
+        #[qinvokable]
+        fn get_mask_intensity(self: &ConfigManager) -> f64;
+        #[qinvokable]
+        fn set_mask_intensity_value(self: &ConfigManager, value: f64);
+        #[qinvokable]
+        fn get_curvature(self: &ConfigManager) -> f64;
+        #[qinvokable]
+        fn set_curvature_value(self: &ConfigManager, value: f64);
+        #[qinvokable]
+        fn get_palette_sync(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_palette_sync_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_presentation_mode(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_presentation_mode_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_deinterlace_mode(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_deinterlace_mode_value(self: &ConfigManager, value: QString);
 
         // Keyboard settings
         #[qinvokable]
@@ -55,6 +118,32 @@ mod qobject {
         fn get_code_page(self: &ConfigManager) -> QString;
         #[qinvokable]
         fn set_code_page_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_sync_caps_lock(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_sync_caps_lock_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_sync_num_lock(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_sync_num_lock_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_sync_scroll_lock(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_sync_scroll_lock_value(self: &ConfigManager, value: bool);
+
+        // Mouse settings
+        #[qinvokable]
+        fn get_mouse_protocol(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_mouse_protocol_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_swap_buttons(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_swap_buttons_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_simulate_middle_button(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_simulate_middle_button_value(self: &ConfigManager, value: bool);
 
         // Storage paths
         #[qinvokable]
@@ -77,6 +166,11 @@ mod qobject {
         fn get_floppy_b_path(self: &ConfigManager) -> QString;
         #[qinvokable]
         fn set_floppy_b(self: &ConfigManager, path: QString);
+        /// Boot drive: "default", "hard_disk", "cdrom" or "floppy"
+        #[qinvokable]
+        fn get_boot_device(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_boot_device_value(self: &ConfigManager, value: QString);
 
         // Network settings
         #[qinvokable]
@@ -91,50 +185,179 @@ mod qobject {
         fn get_mac_address(self: &ConfigManager) -> QString;
         #[qinvokable]
         fn set_mac_address_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_network_mode(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_network_mode_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_network_auto_tap(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_network_auto_tap_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_network_tap_name(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_network_tap_name_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_network_bridge(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_network_bridge_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_network_create_bridge(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_network_create_bridge_value(self: &ConfigManager, value: bool);
+
+        // Remote access settings
+        #[qinvokable]
+        fn get_remote_advertise(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_remote_advertise_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_remote_profile_name(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_remote_profile_name_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_remote_control_port(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn set_remote_control_port_value(self: &ConfigManager, value: i32);
+        #[qinvokable]
+        fn get_remote_control_address(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_remote_control_address_value(self: &ConfigManager, value: QString);
+        #[qinvokable]
+        fn get_remote_control_token(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_remote_control_token_value(self: &ConfigManager, value: QString);
 
         // Clipboard settings
         #[qinvokable]
         fn get_clipboard_enabled(self: &ConfigManager) -> bool;
         #[qinvokable]
         fn set_clipboard_enabled_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_clipboard_share_images(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_clipboard_share_images_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_clipboard_share_rich_text(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_clipboard_share_rich_text_value(self: &ConfigManager, value: bool);
+
+        // Drive mappings (the mappings themselves are in DriveMappingModel)
+        #[qinvokable]
+        fn get_reapply_drive_mappings(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_reapply_drive_mappings_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_auto_map_removable_media(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_auto_map_removable_media_value(self: &ConfigManager, value: bool);
 
-        // Drive mappings
+        // Referenced media check (runs on load)
+        #[qinvokable]
+        fn media_issue_count(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn get_media_issue_slot(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn drive_mapping_count(self: &ConfigManager) -> i32;
+        fn get_media_issue_label(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn get_drive_mapping_letter(self: &ConfigManager, index: i32) -> QString;
+        fn get_media_issue_path(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn get_drive_mapping_path(self: &ConfigManager, index: i32) -> QString;
+        fn get_media_issue_problem(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn get_drive_mapping_description(self: &ConfigManager, index: i32) -> QString;
+        fn get_media_issue_actions(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn get_drive_mapping_enabled(self: &ConfigManager, index: i32) -> bool;
+        fn get_media_issue_download_url(self: &ConfigManager, index: i32) -> QString;
         #[qinvokable]
-        fn add_drive_mapping(self: &ConfigManager, letter: QString, path: QString, description: QString);
+        fn recheck_media(self: &ConfigManager) -> i32;
         #[qinvokable]
-        fn remove_drive_mapping(self: &ConfigManager, letter: QString);
+        fn fix_media_remove(self: &ConfigManager, slot: QString) -> i32;
         #[qinvokable]
-        fn set_drive_mapping_enabled(self: &ConfigManager, letter: QString, enabled: bool);
+        fn fix_media_browse(self: &ConfigManager, slot: QString, path: QString) -> i32;
 
-        // Recent files
+        // Logging
+        /// Level logged at: "error", "warn", "info", "debug" or "trace"
+        #[qinvokable]
+        fn get_log_level(self: &ConfigManager) -> QString;
+        #[qinvokable]
+        fn set_log_level_value(self: &ConfigManager, value: QString);
+        /// Log at `level` from now on and keep it in the configuration;
+        /// false if it is not a level
+        #[qinvokable]
+        fn set_log_level(self: &ConfigManager, level: QString) -> bool;
+        /// Write rotating log files (from the next start)
+        #[qinvokable]
+        fn get_log_file_enabled(self: &ConfigManager) -> bool;
+        #[qinvokable]
+        fn set_log_file_enabled_value(self: &ConfigManager, value: bool);
+        #[qinvokable]
+        fn get_log_max_file_kb(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn set_log_max_file_kb_value(self: &ConfigManager, value: i32);
+        #[qinvokable]
+        fn get_log_max_files(self: &ConfigManager) -> i32;
+        #[qinvokable]
+        fn set_log_max_files_value(self: &ConfigManager, value: i32);
+        /// Directory the log files are written to
+        #[qinvokable]
+        fn get_log_dir(self: &ConfigManager) -> QString;
+
+        // BIOS image
+        /// Image loaded instead of the card's BIOS; empty for the card's own
+        #[qinvokable]
+        fn get_bios_path(self: &ConfigManager) -> QString;
+        /// Use the image at `path` (empty for the card's own BIOS), recording
+        /// its checksum; returns what is wrong with it, or an empty string
         #[qinvokable]
-        fn recent_disk_count(self: &ConfigManager) -> i32;
+        fn choose_bios(self: &ConfigManager, path: QString) -> QString;
+        /// Refuse the image if it changed since it was chosen
         #[qinvokable]
-        fn get_recent_disk_path(self: &ConfigManager, index: i32) -> QString;
+        fn get_bios_verify(self: &ConfigManager) -> bool;
         #[qinvokable]
-        fn recent_iso_count(self: &ConfigManager) -> i32;
+        fn set_bios_verify_value(self: &ConfigManager, value: bool);
+        /// BIOS date of the image, empty if it has none or cannot be read
         #[qinvokable]
-        fn get_recent_iso_path(self: &ConfigManager, index: i32) -> QString;
+        fn get_bios_version(self: &ConfigManager) -> QString;
+        /// "default", "known_good", "unknown" or "problem"
         #[qinvokable]
-        fn recent_floppy_count(self: &ConfigManager) -> i32;
+        fn get_bios_status(self: &ConfigManager) -> QString;
+        /// What is wrong with the image, or an empty string
         #[qinvokable]
-        fn get_recent_floppy_path(self: &ConfigManager, index: i32) -> QString;
+        fn check_bios(self: &ConfigManager) -> QString;
+        /// Add the image to the known-good list with `note`
+        #[qinvokable]
+        fn mark_bios_known_good(self: &ConfigManager, note: QString) -> bool;
 
         // Load and save
         #[qinvokable]
         fn load(self: &ConfigManager);
         #[qinvokable]
         fn save(self: &ConfigManager);
+        /// Earlier versions of the configuration file kept, newest first,
+        /// as a JSON array of {index, replaced} (seconds since the epoch)
+        #[qinvokable]
+        fn get_config_backups_json(self: &ConfigManager) -> QString;
+        /// Put back backup `index` and load it; the models that read the
+        /// file themselves (drive mappings, recent files) need loading again
+        #[qinvokable]
+        fn restore_config_backup(self: &ConfigManager, index: i32) -> bool;
+        /// Write the settings, drive mappings and recent files included, to
+        /// a bundle file; `strip_paths` cuts host paths down to file names
+        /// for attaching to bug reports
+        #[qinvokable]
+        fn export_bundle(self: &ConfigManager, path: QString, strip_paths: bool) -> bool;
+        /// Replace the settings with those of a bundle file (the current
+        /// ones are kept as a backup) and emit config_changed
+        #[qinvokable]
+        fn import_bundle(self: Pin<&mut ConfigManager>, path: QString) -> bool;
+        /// Check whether the configuration file was edited elsewhere, and
+        /// load it if so
+        #[qinvokable]
+        fn poll_changes(self: Pin<&mut ConfigManager>);
+
+        /// Signal emitted when the configuration was loaded again after
+        /// the file was edited elsewhere, or a bundle was imported
+        #[qsignal]
+        fn config_changed(self: Pin<&mut ConfigManager>);
     }
 }
 
@@ -143,13 +366,22 @@ use cxx_qt_lib::QString;
 /// Rust implementation of the ConfigManager
 pub struct ConfigManagerRust {
     config: RefCell<AppConfig>,
+    /// Problems found with the profile's media at the last check
+    media_report: RefCell<MediaReport>,
+    /// None if the configuration file cannot be watched
+    watcher: RefCell<Option<ConfigWatcher>>,
 }
 
 impl Default for ConfigManagerRust {
     fn default() -> Self {
+        let watcher = ConfigWatcher::new(&AppConfig::config_file())
+            .inspect_err(|e| tracing::warn!("Cannot watch the configuration file for edits: {}", e))
+            .ok();
         // Start with default config - load() should be called from QML
         Self {
             config: RefCell::new(AppConfig::default()),
+            media_report: RefCell::new(MediaReport::default()),
+            watcher: RefCell::new(watcher),
         }
     }
 }
@@ -188,12 +420,122 @@ impl qobject::ConfigManager {
     fn set_integer_scaling_value(&self, value: bool) {
         self.config.borrow_mut().display.integer_scaling = value;
     }
+    fn get_scaling_mode(&self) -> QString {
+        let mode = match self.config.borrow().display.scaling_mode {
+            ScalingMode::None => "none",
+            ScalingMode::Fit => "fit",
+            ScalingMode::Stretch => "stretch",
+            ScalingMode::Fixed(_) => "fixed",
+        };
+        QString::from(mode)
+    }
+    /// "fixed" keeps the current factor (2 if there is none)
+    fn set_scaling_mode_value(&self, value: QString) {
+        let mode = match value.to_string().as_str() {
+            "none" => ScalingMode::None,
+            "stretch" => ScalingMode::Stretch,
+            "fixed" => ScalingMode::Fixed(self.get_scale_factor() as u32),
+            _ => ScalingMode::Fit,
+        };
+        self.config.borrow_mut().display.scaling_mode = mode;
+    }
+    /// Factor of the fixed scaling mode, 2 in other modes
+    fn get_scale_factor(&self) -> i32 {
+        match self.config.borrow().display.scaling_mode {
+            ScalingMode::Fixed(factor) => factor.max(1) as i32,
+            _ => 2,
+        }
+    }
+    /// Selects the fixed scaling mode with this factor
+    fn set_scale_factor_value(&self, value: i32) {
+        self.config.borrow_mut().display.scaling_mode = ScalingMode::Fixed(value.max(1) as u32);
+    }
     fn get_scanline_effect(&self) -> bool {
         self.config.borrow().display.scanline_effect
     }
     fn set_scanline_effect_value(&self, value: bool) {
         self.config.borrow_mut().display.scanline_effect = value;
     }
+    fn get_start_fullscreen(&self) -> bool {
+        self.config.borrow().display.start_fullscreen
+    }
+    fn set_start_fullscreen_value(&self, value: bool) {
+        self.config.borrow_mut().display.start_fullscreen = value;
+    }
+    fn get_fullscreen_hide_menu(&self) -> bool {
+        self.config.borrow().display.fullscreen_hide_menu
+    }
+    fn set_fullscreen_hide_menu_value(&self, value: bool) {
+        self.config.borrow_mut().display.fullscreen_hide_menu = value;
+    }
+    /// Screen index, or -1 for the window's screen
+    fn get_fullscreen_screen(&self) -> i32 {
+        self.config.borrow().display.fullscreen_screen.map_or(-1, |n| n as i32)
+    }
+    fn set_fullscreen_screen_value(&self, value: i32) {
+        self.config.borrow_mut().display.fullscreen_screen = (value >= 0).then_some(value as u32);
+    }
+    /// "scanlines", "aperture" or "curvature"
+    fn get_crt_effect(&self) -> QString {
+        QString::from(crt::effect_name(self.config.borrow().display.crt_effect))
+    }
+    fn set_crt_effect_value(&self, value: QString) {
+        if let Some(effect) = crt::effect_from_name(&value.to_string()) {
+            self.config.borrow_mut().display.crt_effect = effect;
+        }
+    }
+    fn get_scanline_intensity(&self) -> f64 {
+        self.config.borrow().display.scanline_intensity.into()
+    }
+    fn set_scanline_intensity_value(&self, value: f64) {
+        self.config.borrow_mut().display.scanline_intensity = value.clamp(0.0, 1.0) as f32;
+    }
+    fn get_mask_intensity(&self) -> f64 {
+        self.config.borrow().display.mask_intensity.into()
+    }
+    fn set_mask_intensity_value(&self, value: f64) {
+        self.config.borrow_mut().display.mask_intensity = value.clamp(0.0, 1.0) as f32;
+    }
+    fn get_curvature(&self) -> f64 {
+        self.config.borrow().display.curvature.into()
+    }
+    fn set_curvature_value(&self, value: f64) {
+        self.config.borrow_mut().display.curvature = value.clamp(0.0, 0.5) as f32;
+    }
+    fn get_palette_sync(&self) -> bool {
+        self.config.borrow().display.palette_sync
+    }
+    fn set_palette_sync_value(&self, value: bool) {
+        self.config.borrow_mut().display.palette_sync = value;
+    }
+    fn get_presentation_mode(&self) -> QString {
+        let mode = match self.config.borrow().display.presentation_mode {
+            PresentationMode::Immediate => "immediate",
+            PresentationMode::Vsync => "vsync",
+        };
+        QString::from(mode)
+    }
+    fn set_presentation_mode_value(&self, value: QString) {
+        let mode = match value.to_string().as_str() {
+            "immediate" => PresentationMode::Immediate,
+            _ => PresentationMode::Vsync,
+        };
+        self.config.borrow_mut().display.presentation_mode = mode;
+    }
+    fn get_deinterlace_mode(&self) -> QString {
+        let mode = match self.config.borrow().display.deinterlace {
+            DeinterlaceMode::Bob => "bob",
+            DeinterlaceMode::Weave => "weave",
+        };
+        QString::from(mode)
+    }
+    fn set_deinterlace_mode_value(&self, value: QString) {
+        let mode = match value.to_string().as_str() {
+            "weave" => DeinterlaceMode::Weave,
+            _ => DeinterlaceMode::Bob,
+        };
+        self.config.borrow_mut().display.deinterlace = mode;
+    }
 
     // Keyboard settings
     fn get_keyboard_layout(&self) -> QString {
@@ -208,6 +550,54 @@ impl qobject::ConfigManager {
     fn set_code_page_value(&self, value: QString) {
         self.config.borrow_mut().keyboard.code_page = value.to_string();
     }
+    fn get_sync_caps_lock(&self) -> bool {
+        self.config.borrow().keyboard.sync_caps_lock
+    }
+    fn set_sync_caps_lock_value(&self, value: bool) {
+        self.config.borrow_mut().keyboard.sync_caps_lock = value;
+    }
+    fn get_sync_num_lock(&self) -> bool {
+        self.config.borrow().keyboard.sync_num_lock
+    }
+    fn set_sync_num_lock_value(&self, value: bool) {
+        self.config.borrow_mut().keyboard.sync_num_lock = value;
+    }
+    fn get_sync_scroll_lock(&self) -> bool {
+        self.config.borrow().keyboard.sync_scroll_lock
+    }
+    fn set_sync_scroll_lock_value(&self, value: bool) {
+        self.config.borrow_mut().keyboard.sync_scroll_lock = value;
+    }
+
+    // Mouse settings
+    fn get_mouse_protocol(&self) -> QString {
+        let protocol = match self.config.borrow().mouse.protocol {
+            MouseProtocol::Ps2 => "ps2",
+            MouseProtocol::Serial => "serial",
+            MouseProtocol::Absolute => "absolute",
+        };
+        QString::from(protocol)
+    }
+    fn set_mouse_protocol_value(&self, value: QString) {
+        let protocol = match value.to_string().as_str() {
+            "serial" => MouseProtocol::Serial,
+            "absolute" => MouseProtocol::Absolute,
+            _ => MouseProtocol::Ps2,
+        };
+        self.config.borrow_mut().mouse.protocol = protocol;
+    }
+    fn get_swap_buttons(&self) -> bool {
+        self.config.borrow().mouse.swap_buttons
+    }
+    fn set_swap_buttons_value(&self, value: bool) {
+        self.config.borrow_mut().mouse.swap_buttons = value;
+    }
+    fn get_simulate_middle_button(&self) -> bool {
+        self.config.borrow().mouse.simulate_middle_button
+    }
+    fn set_simulate_middle_button_value(&self, value: bool) {
+        self.config.borrow_mut().mouse.simulate_middle_button = value;
+    }
 
     // Storage paths
     fn get_primary_disk_path(&self) -> QString {
@@ -225,14 +615,19 @@ impl qobject::ConfigManager {
         if path_str.is_empty() {
             config.storage.primary_disk = None;
         } else {
+            let overlay = config
+                .storage
+                .primary_disk
+                .take()
+                .filter(|d| d.path == Path::new(&path_str))
+                .and_then(|d| d.overlay);
             config.storage.primary_disk = Some(DiskConfig {
                 path: PathBuf::from(&path_str),
                 bootable: true,
+                content_id: image_ref::content_id(Path::new(&path_str)).ok(),
+                overlay,
             });
-            // Add to recent files
-            config.recent.disk_images.retain(|p| p.to_string_lossy() != path_str);
-            config.recent.disk_images.insert(0, PathBuf::from(&path_str));
-            config.recent.disk_images.truncate(10);
+            recent_files_model::remember(RecentCategory::DiskImage, PathBuf::from(&path_str));
         }
     }
 
@@ -251,9 +646,18 @@ impl qobject::ConfigManager {
         if path_str.is_empty() {
             config.storage.secondary_disk = None;
         } else {
+            // The same template keeps its overlay child
+            let overlay = config
+                .storage
+                .secondary_disk
+                .take()
+                .filter(|d| d.path == Path::new(&path_str))
+                .and_then(|d| d.overlay);
             config.storage.secondary_disk = Some(DiskConfig {
                 path: PathBuf::from(&path_str),
                 bootable: false,
+                content_id: image_ref::content_id(Path::new(&path_str)).ok(),
+                overlay,
             });
         }
     }
@@ -275,10 +679,7 @@ impl qobject::ConfigManager {
             config.storage.cdrom.mounted_iso = None;
         } else {
             config.storage.cdrom.mounted_iso = Some(PathBuf::from(&path_str));
-            // Add to recent files
-            config.recent.iso_files.retain(|p| p.to_string_lossy() != path_str);
-            config.recent.iso_files.insert(0, PathBuf::from(&path_str));
-            config.recent.iso_files.truncate(10);
+            recent_files_model::remember(RecentCategory::Iso, PathBuf::from(&path_str));
         }
     }
 
@@ -299,10 +700,7 @@ impl qobject::ConfigManager {
             config.storage.floppy_a.mounted_image = None;
         } else {
             config.storage.floppy_a.mounted_image = Some(PathBuf::from(&path_str));
-            // Add to recent files
-            config.recent.floppy_images.retain(|p| p.to_string_lossy() != path_str);
-            config.recent.floppy_images.insert(0, PathBuf::from(&path_str));
-            config.recent.floppy_images.truncate(10);
+            recent_files_model::remember(RecentCategory::Floppy, PathBuf::from(&path_str));
         }
     }
 
@@ -323,9 +721,32 @@ impl qobject::ConfigManager {
             config.storage.floppy_b.mounted_image = None;
         } else {
             config.storage.floppy_b.mounted_image = Some(PathBuf::from(&path_str));
+            recent_files_model::remember(RecentCategory::Floppy, PathBuf::from(&path_str));
         }
     }
 
+    fn get_boot_device(&self) -> QString {
+        let device = match self.config.borrow().storage.boot_order() {
+            BootDevice::Default => "default",
+            BootDevice::HardDisk => "hard_disk",
+            BootDevice::Cdrom => "cdrom",
+            BootDevice::Floppy => "floppy",
+        };
+        QString::from(device)
+    }
+    fn set_boot_device_value(&self, value: QString) {
+        let device = match value.to_string().as_str() {
+            "hard_disk" => BootDevice::HardDisk,
+            "cdrom" => BootDevice::Cdrom,
+            "floppy" => BootDevice::Floppy,
+            _ => BootDevice::Default,
+        };
+        let mut config = self.config.borrow_mut();
+        config.storage.boot_device = device;
+        // Replaced by boot_device
+        config.storage.cdrom.boot_from_cd = false;
+    }
+
     // Network settings
     fn get_network_enabled(&self) -> bool {
         self.config.borrow().network.enabled
@@ -345,6 +766,79 @@ impl qobject::ConfigManager {
     fn set_mac_address_value(&self, value: QString) {
         self.config.borrow_mut().network.mac_address = value.to_string();
     }
+    fn get_network_mode(&self) -> QString {
+        let mode = match self.config.borrow().network.mode {
+            NetworkMode::Bridged => "bridged",
+            NetworkMode::Nat => "nat",
+        };
+        QString::from(mode)
+    }
+    fn set_network_mode_value(&self, value: QString) {
+        let mode = match value.to_string().as_str() {
+            "nat" => NetworkMode::Nat,
+            _ => NetworkMode::Bridged,
+        };
+        self.config.borrow_mut().network.mode = mode;
+    }
+    fn get_network_auto_tap(&self) -> bool {
+        self.config.borrow().network.auto_tap
+    }
+    fn set_network_auto_tap_value(&self, value: bool) {
+        self.config.borrow_mut().network.auto_tap = value;
+    }
+    fn get_network_tap_name(&self) -> QString {
+        QString::from(&self.config.borrow().network.tap_name)
+    }
+    fn set_network_tap_name_value(&self, value: QString) {
+        self.config.borrow_mut().network.tap_name = value.to_string();
+    }
+    fn get_network_bridge(&self) -> QString {
+        QString::from(&self.config.borrow().network.bridge)
+    }
+    fn set_network_bridge_value(&self, value: QString) {
+        self.config.borrow_mut().network.bridge = value.to_string();
+    }
+    fn get_network_create_bridge(&self) -> bool {
+        self.config.borrow().network.create_bridge
+    }
+    fn set_network_create_bridge_value(&self, value: bool) {
+        self.config.borrow_mut().network.create_bridge = value;
+    }
+
+    // Remote access settings
+    fn get_remote_advertise(&self) -> bool {
+        self.config.borrow().remote.advertise
+    }
+    fn set_remote_advertise_value(&self, value: bool) {
+        self.config.borrow_mut().remote.advertise = value;
+    }
+    fn get_remote_profile_name(&self) -> QString {
+        QString::from(&self.config.borrow().remote.profile_name)
+    }
+    fn set_remote_profile_name_value(&self, value: QString) {
+        self.config.borrow_mut().remote.profile_name = value.to_string();
+    }
+    fn get_remote_control_port(&self) -> i32 {
+        self.config.borrow().remote.control_port.map_or(0, i32::from)
+    }
+    fn set_remote_control_port_value(&self, value: i32) {
+        // 0 turns the control API off
+        self.config.borrow_mut().remote.control_port = u16::try_from(value).ok().filter(|&p| p != 0);
+    }
+    fn get_remote_control_address(&self) -> QString {
+        let address = self.config.borrow().remote.control_address;
+        address.map_or_else(QString::default, |ip| QString::from(&ip.to_string()))
+    }
+    fn set_remote_control_address_value(&self, value: QString) {
+        // Empty or unparseable keeps the control API on loopback
+        self.config.borrow_mut().remote.control_address = value.to_string().trim().parse().ok();
+    }
+    fn get_remote_control_token(&self) -> QString {
+        QString::from(&self.config.borrow().remote.control_token)
+    }
+    fn set_remote_control_token_value(&self, value: QString) {
+        self.config.borrow_mut().remote.control_token = value.to_string().trim().to_string();
+    }
 
     // Clipboard settings
     fn get_clipboard_enabled(&self) -> bool {
@@ -353,110 +847,212 @@ impl qobject::ConfigManager {
     fn set_clipboard_enabled_value(&self, value: bool) {
         self.config.borrow_mut().clipboard.enabled = value;
     }
+    fn get_clipboard_share_images(&self) -> bool {
+        self.config.borrow().clipboard.share_images
+    }
+    fn set_clipboard_share_images_value(&self, value: bool) {
+        self.config.borrow_mut().clipboard.share_images = value;
+    }
+    fn get_clipboard_share_rich_text(&self) -> bool {
+        self.config.borrow().clipboard.share_rich_text
+    }
+    fn set_clipboard_share_rich_text_value(&self, value: bool) {
+        self.config.borrow_mut().clipboard.share_rich_text = value;
+    }
 
     // Drive mappings
-    fn drive_mapping_count(&self) -> i32 {
-        self.config.borrow().drive_mappings.len() as i32
+    fn get_reapply_drive_mappings(&self) -> bool {
+        self.config.borrow().general.reapply_drive_mappings
     }
-    fn get_drive_mapping_letter(&self, index: i32) -> QString {
-        self.config
-            .borrow()
-            .drive_mappings
-            .get(index as usize)
-            .map(|m| QString::from(&m.drive_letter))
-            .unwrap_or_default()
+    fn set_reapply_drive_mappings_value(&self, value: bool) {
+        self.config.borrow_mut().general.reapply_drive_mappings = value;
     }
-    fn get_drive_mapping_path(&self, index: i32) -> QString {
-        self.config
-            .borrow()
-            .drive_mappings
-            .get(index as usize)
-            .map(|m| QString::from(m.host_path.to_string_lossy().as_ref()))
-            .unwrap_or_default()
+    fn get_auto_map_removable_media(&self) -> bool {
+        self.config.borrow().general.auto_map_removable_media
     }
-    fn get_drive_mapping_description(&self, index: i32) -> QString {
-        self.config
-            .borrow()
-            .drive_mappings
-            .get(index as usize)
-            .map(|m| QString::from(&m.description))
-            .unwrap_or_default()
+    fn set_auto_map_removable_media_value(&self, value: bool) {
+        self.config.borrow_mut().general.auto_map_removable_media = value;
     }
-    fn get_drive_mapping_enabled(&self, index: i32) -> bool {
-        self.config
-            .borrow()
-            .drive_mappings
-            .get(index as usize)
-            .map(|m| m.enabled)
-            .unwrap_or(false)
-    }
-    fn add_drive_mapping(&self, letter: QString, path: QString, description: QString) {
-        let mapping = DriveMapping {
-            drive_letter: letter.to_string(),
-            host_path: PathBuf::from(path.to_string()),
-            description: description.to_string(),
-            enabled: true,
-        };
-        let letter_str = letter.to_string();
-        let mut config = self.config.borrow_mut();
-        config.drive_mappings.retain(|m| m.drive_letter != letter_str);
-        config.drive_mappings.push(mapping);
+
+    // Referenced media check
+    fn media_issue_count(&self) -> i32 {
+        self.media_report.borrow().issues.len() as i32
     }
-    fn remove_drive_mapping(&self, letter: QString) {
-        let letter_str = letter.to_string();
-        self.config.borrow_mut().drive_mappings.retain(|m| m.drive_letter != letter_str);
+    fn get_media_issue_slot(&self, index: i32) -> QString {
+        self.media_issue(index, |i| i.slot.id().to_string())
     }
-    fn set_drive_mapping_enabled(&self, letter: QString, enabled: bool) {
-        let letter_str = letter.to_string();
-        let mut config = self.config.borrow_mut();
-        if let Some(mapping) = config.drive_mappings.iter_mut().find(|m| m.drive_letter == letter_str) {
-            mapping.enabled = enabled;
+    fn get_media_issue_label(&self, index: i32) -> QString {
+        self.media_issue(index, |i| i.slot.label().to_string())
+    }
+    fn get_media_issue_path(&self, index: i32) -> QString {
+        self.media_issue(index, |i| i.path.to_string_lossy().into_owned())
+    }
+    fn get_media_issue_problem(&self, index: i32) -> QString {
+        self.media_issue(index, |i| i.problem.to_string())
+    }
+    /// Offered fix-ups, semicolon-separated ("browse;remove;download")
+    fn get_media_issue_actions(&self, index: i32) -> QString {
+        self.media_issue(index, |i| {
+            i.actions.iter().map(FixAction::id).collect::<Vec<_>>().join(";")
+        })
+    }
+    fn get_media_issue_download_url(&self, index: i32) -> QString {
+        self.media_issue(index, |i| {
+            i.actions
+                .iter()
+                .find_map(|a| match a {
+                    FixAction::Download(url) => Some(url.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        })
+    }
+    fn recheck_media(&self) -> i32 {
+        let report = media_check::check_media(&self.config.borrow().storage);
+        for issue in &report.issues {
+            tracing::warn!("Media problem: {}", issue);
         }
+        *self.media_report.borrow_mut() = report;
+        self.media_issue_count()
     }
-
-    // Recent files
-    fn recent_disk_count(&self) -> i32 {
-        self.config.borrow().recent.disk_images.len() as i32
+    /// Detach a slot's image; returns the remaining issue count
+    fn fix_media_remove(&self, slot: QString) -> i32 {
+        if let Some(slot) = MediaSlot::from_id(&slot.to_string()) {
+            slot.remove(&mut self.config.borrow_mut().storage);
+            tracing::info!("Removed {} from the profile", slot.label());
+        }
+        self.recheck_media()
     }
-    fn get_recent_disk_path(&self, index: i32) -> QString {
-        self.config
+    /// Point a slot at another image; returns the remaining issue count
+    fn fix_media_browse(&self, slot: QString, path: QString) -> i32 {
+        if let Some(slot) = MediaSlot::from_id(&slot.to_string()) {
+            let path = PathBuf::from(path.to_string());
+            let mut config = self.config.borrow_mut();
+            slot.replace(&mut config.storage, path);
+            config.relocate_images();
+        }
+        self.recheck_media()
+    }
+
+    /// Internal: a field of the issue at `index`, or an empty string
+    fn media_issue(&self, index: i32, field: impl Fn(&MediaIssue) -> String) -> QString {
+        self.media_report
             .borrow()
-            .recent
-            .disk_images
+            .issues
             .get(index as usize)
-            .map(|p| QString::from(p.to_string_lossy().as_ref()))
+            .map(|i| QString::from(&field(i)))
             .unwrap_or_default()
     }
-    fn recent_iso_count(&self) -> i32 {
-        self.config.borrow().recent.iso_files.len() as i32
+
+    // Logging
+    fn get_log_level(&self) -> QString {
+        QString::from(&self.config.borrow().log.level)
     }
-    fn get_recent_iso_path(&self, index: i32) -> QString {
-        self.config
-            .borrow()
-            .recent
-            .iso_files
-            .get(index as usize)
-            .map(|p| QString::from(p.to_string_lossy().as_ref()))
-            .unwrap_or_default()
+    fn set_log_level_value(&self, value: QString) {
+        self.config.borrow_mut().log.level = value.to_string();
+    }
+    fn set_log_level(&self, level: QString) -> bool {
+        let level = level.to_string();
+        if !logging::set_level(&level) {
+            return false;
+        }
+        self.config.borrow_mut().log.level = level;
+        true
     }
-    fn recent_floppy_count(&self) -> i32 {
-        self.config.borrow().recent.floppy_images.len() as i32
+    fn get_log_file_enabled(&self) -> bool {
+        self.config.borrow().log.file
     }
-    fn get_recent_floppy_path(&self, index: i32) -> QString {
+    fn set_log_file_enabled_value(&self, value: bool) {
+        self.config.borrow_mut().log.file = value;
+    }
+    fn get_log_max_file_kb(&self) -> i32 {
+        self.config.borrow().log.max_file_kb as i32
+    }
+    fn set_log_max_file_kb_value(&self, value: i32) {
+        self.config.borrow_mut().log.max_file_kb = value.max(1) as u32;
+    }
+    fn get_log_max_files(&self) -> i32 {
+        self.config.borrow().log.max_files as i32
+    }
+    fn set_log_max_files_value(&self, value: i32) {
+        self.config.borrow_mut().log.max_files = value.max(0) as u32;
+    }
+    fn get_log_dir(&self) -> QString {
+        QString::from(AppConfig::log_dir().to_string_lossy().as_ref())
+    }
+
+    // BIOS image
+    fn get_bios_path(&self) -> QString {
         self.config
             .borrow()
-            .recent
-            .floppy_images
-            .get(index as usize)
+            .bios
+            .path
+            .as_ref()
             .map(|p| QString::from(p.to_string_lossy().as_ref()))
             .unwrap_or_default()
     }
+    fn choose_bios(&self, path: QString) -> QString {
+        let path = path.to_string();
+        let mut config = self.config.borrow_mut();
+        if path.is_empty() {
+            config.bios.path = None;
+            config.bios.sha256 = None;
+            return QString::default();
+        }
+        match bios::choose(&mut config.bios, Path::new(&path)) {
+            Ok(_) => QString::default(),
+            Err(problem) => QString::from(&problem.to_string()),
+        }
+    }
+    fn get_bios_verify(&self) -> bool {
+        self.config.borrow().bios.verify_checksum
+    }
+    fn set_bios_verify_value(&self, value: bool) {
+        self.config.borrow_mut().bios.verify_checksum = value;
+    }
+    fn get_bios_version(&self) -> QString {
+        match bios::check(&self.config.borrow().bios) {
+            Ok(Some(image)) => QString::from(image.version.as_deref().unwrap_or_default()),
+            _ => QString::default(),
+        }
+    }
+    fn get_bios_status(&self) -> QString {
+        let config = self.config.borrow();
+        let status = match bios::check(&config.bios) {
+            Ok(None) => "default",
+            Ok(Some(image)) if config.bios.known(&image.sha256).is_some() => "known_good",
+            Ok(Some(_)) => "unknown",
+            Err(_) => "problem",
+        };
+        QString::from(status)
+    }
+    fn check_bios(&self) -> QString {
+        match bios::check(&self.config.borrow().bios) {
+            Ok(_) => QString::default(),
+            Err(problem) => QString::from(&problem.to_string()),
+        }
+    }
+    fn mark_bios_known_good(&self, note: QString) -> bool {
+        let mut config = self.config.borrow_mut();
+        let Ok(Some(image)) = bios::check(&config.bios) else {
+            return false;
+        };
+        if config.bios.known(&image.sha256).is_none() {
+            config.bios.known_good.push(KnownBios {
+                sha256: image.sha256,
+                version: image.version.unwrap_or_default(),
+                note: note.to_string(),
+            });
+        }
+        true
+    }
 
     // Load and save
     fn load(&self) {
+        let _timing = startup::span("config load");
         match load_config() {
             Ok(config) => {
-                *self.config.borrow_mut() = config;
+                self.use_config(config);
                 tracing::info!("Configuration loaded from {:?}", AppConfig::config_file());
             }
             Err(e) => {
@@ -465,11 +1061,111 @@ impl qobject::ConfigManager {
         }
     }
 
+    /// Settings loaded from the file, with moved images found again
+    fn use_config(&self, mut config: AppConfig) {
+        for (old, new) in config.relocate_images() {
+            tracing::info!("Disk image {:?} moved, now using {:?}", old, new);
+        }
+        *self.config.borrow_mut() = config;
+        self.recheck_media();
+    }
+
     fn save(&self) {
+        self.take_saved_sections();
         if let Err(e) = save_config(&self.config.borrow()) {
             tracing::error!("Failed to save configuration: {}", e);
         } else {
             tracing::info!("Configuration saved to {:?}", AppConfig::config_file());
         }
     }
+
+    /// DriveMappingModel saves the drive mappings and RecentFilesModel the
+    /// recent files; take them as they did
+    fn take_saved_sections(&self) {
+        if let Ok(saved) = load_config() {
+            let mut config = self.config.borrow_mut();
+            config.drive_mappings = saved.drive_mappings;
+            config.recent = saved.recent;
+        }
+    }
+
+    fn export_bundle(&self, path: QString, strip_paths: bool) -> bool {
+        self.take_saved_sections();
+        let path = PathBuf::from(path.to_string());
+        match settings_bundle::export_bundle(&self.config.borrow(), &path, strip_paths) {
+            Ok(_) => {
+                tracing::info!("Settings exported to {:?}", path);
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to export settings: {:#}", e);
+                false
+            }
+        }
+    }
+
+    fn import_bundle(self: Pin<&mut Self>, path: QString) -> bool {
+        let path = PathBuf::from(path.to_string());
+        let result = settings_bundle::import_bundle(&path)
+            .and_then(|(info, config)| save_config(&config).map(|()| (info, config)).map_err(Into::into));
+        match result {
+            Ok((info, config)) => {
+                tracing::info!(
+                    "Settings imported from {:?} (made by {} on {}{})",
+                    path,
+                    info.app_version,
+                    if info.host.is_empty() { "an unnamed host" } else { &info.host },
+                    if info.paths_stripped { ", paths stripped" } else { "" }
+                );
+                self.use_config(config);
+                self.config_changed();
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to import settings: {:#}", e);
+                false
+            }
+        }
+    }
+
+    fn get_config_backups_json(&self) -> QString {
+        let backups: Vec<serde_json::Value> = config_backups(&AppConfig::config_file())
+            .into_iter()
+            .map(|(index, replaced)| {
+                let replaced = replaced.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+                serde_json::json!({ "index": index, "replaced": replaced })
+            })
+            .collect();
+        QString::from(&serde_json::Value::Array(backups).to_string())
+    }
+
+    fn poll_changes(self: Pin<&mut Self>) {
+        let result = match self.watcher.borrow_mut().as_mut() {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+        match result {
+            Ok(Some(config)) => {
+                tracing::info!("Configuration file edited, loaded again");
+                self.use_config(config);
+                self.config_changed();
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Configuration file edited but cannot be read: {}", e),
+        }
+    }
+
+    fn restore_config_backup(&self, index: i32) -> bool {
+        match restore_config_backup(&AppConfig::config_file(), index.max(0) as usize) {
+            Ok(_) => {
+                tracing::info!("Configuration restored from backup {}", index);
+                self.load();
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to restore configuration backup {}: {}", index, e);
+                false
+            }
+        }
+    }
 }
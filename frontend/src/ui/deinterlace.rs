@@ -0,0 +1,101 @@
+//! Deinterlacing for interlaced guest video modes.
+//!
+//! In interlaced modes (e.g. 1024x768i) the driver exposes only the most
+//! recent field, holding every other line of the frame. This reconstructs a
+//! full-height RGBA frame from those fields:
+//! - Bob: line-double the current field
+//! - Weave: interleave the current field with the previous one
+
+use rising_sun_common::DeinterlaceMode;
+
+/// Deinterlacer state carried between fields
+#[derive(Default)]
+pub struct Deinterlacer {
+    /// Selected deinterlacing method
+    pub mode: DeinterlaceMode,
+    /// Previous field (RGBA) for weaving
+    prev_field: Vec<u8>,
+    /// Parity of the previous field
+    prev_odd: bool,
+}
+
+impl Deinterlacer {
+    /// Create a deinterlacer using the given method
+    pub fn new(mode: DeinterlaceMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    /// Forget the previous field (e.g. after a mode change)
+    pub fn reset(&mut self) {
+        self.prev_field.clear();
+    }
+
+    /// Build a full frame of `field_rows * 2` lines from one RGBA field
+    pub fn process(&mut self, field: &[u8], width: u32, field_rows: u32, odd: bool) -> Vec<u8> {
+        let row_len = width as usize * 4;
+        let rows = field_rows as usize;
+        let mut frame = vec![0u8; row_len * rows * 2];
+
+        // Weave only works with a complementary field of the same geometry
+        let can_weave = self.mode == DeinterlaceMode::Weave
+            && self.prev_field.len() == field.len()
+            && self.prev_odd != odd;
+
+        for y in 0..rows {
+            let src = &field[y * row_len..(y + 1) * row_len];
+            let own = y * 2 + odd as usize;
+            let other = y * 2 + !odd as usize;
+            frame[own * row_len..(own + 1) * row_len].copy_from_slice(src);
+
+            let fill = if can_weave {
+                &self.prev_field[y * row_len..(y + 1) * row_len]
+            } else {
+                src
+            };
+            frame[other * row_len..(other + 1) * row_len].copy_from_slice(fill);
+        }
+
+        if self.mode == DeinterlaceMode::Weave {
+            self.prev_field.clear();
+            self.prev_field.extend_from_slice(field);
+            self.prev_odd = odd;
+        }
+
+        frame
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One-pixel-wide field whose rows are filled with the given values
+    fn field(values: &[u8]) -> Vec<u8> {
+        values.iter().flat_map(|&v| [v; 4]).collect()
+    }
+
+    /// First channel of each row of a one-pixel-wide frame
+    fn rows(frame: &[u8]) -> Vec<u8> {
+        frame.chunks(4).map(|px| px[0]).collect()
+    }
+
+    #[test]
+    fn test_bob_line_doubles() {
+        let mut d = Deinterlacer::new(DeinterlaceMode::Bob);
+        let out = d.process(&field(&[1, 2]), 1, 2, false);
+        assert_eq!(rows(&out), vec![1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_weave_interleaves_fields() {
+        let mut d = Deinterlacer::new(DeinterlaceMode::Weave);
+        // First field has nothing to weave with, so it is line-doubled
+        let out = d.process(&field(&[1, 3]), 1, 2, false);
+        assert_eq!(rows(&out), vec![1, 1, 3, 3]);
+        let out = d.process(&field(&[2, 4]), 1, 2, true);
+        assert_eq!(rows(&out), vec![1, 2, 3, 4]);
+    }
+}
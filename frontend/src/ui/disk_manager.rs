@@ -1,10 +1,38 @@
 //! Disk manager Qt bridge for handling virtual disk operations.
 
 use std::fs::File;
-use std::io::{Read, Write, Seek, SeekFrom};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use rising_sun_common::{DriverHandle, is_driver_loaded};
+use rising_sun_common::{DriverHandle, is_driver_loaded, load_config, save_config};
+use rising_sun_common::appliance;
+use rising_sun_common::disk_check;
+use rising_sun_common::disk_compact;
+use rising_sun_common::disk_convert::{self, DiskFormat};
+use rising_sun_common::disk_image::{self, Allocation, SECTOR_SIZE};
+use rising_sun_common::disk_library;
+use rising_sun_common::disk_resize;
+use rising_sun_common::diskspace::{self, DiskSpaceError};
+use rising_sun_common::el_torito::{self, BootCatalog};
+use rising_sun_common::guest_os;
+use rising_sun_common::guest_tools;
+use rising_sun_common::host_cdrom::{self, HostCdrom};
+use rising_sun_common::ioctl::{SCSI_DATA_MAX_LEN, ScsiRequest, ScsiResponse, event_drive};
+use rising_sun_common::progress::ProgressReporter;
+use rising_sun_common::scsi::{CD_FRAMES_PER_SECOND, SECTOR_SIZE_CDROM};
+use rising_sun_common::tasks::{TaskId, TaskManager, TaskStatus};
+use rising_sun_common::ui_json::{self, DiskInfoJson};
+use rising_sun_common::virtual_cd::{self, VirtualCd};
+
+use super::actions::{self, Action};
+use super::ephemeral;
+use super::startup;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -21,11 +49,99 @@ mod qobject {
         #[qproperty(bool, floppy_a_mounted)]
         #[qproperty(bool, floppy_b_mounted)]
         #[qproperty(bool, cdrom_mounted)]
+        #[qproperty(bool, cdrom_passthrough)]
+        #[qproperty(i32, active_tasks)]
         type DiskManager = super::DiskManagerRust;
 
-        /// Create a new disk image
+        /// Create a new disk image, blocking until it is written
+        #[qinvokable]
+        fn create_disk(
+            self: &DiskManager,
+            path: QString,
+            size_mb: i32,
+            revision: i32,
+            allocation: QString,
+        ) -> bool;
+
+        /// Create a new disk image on a background worker
+        /// Returns the task id (progress is reported via task_progress, the
+        /// outcome via disk_created or disk_creation_failed)
+        #[qinvokable]
+        fn create_disk_async(
+            self: Pin<&mut DiskManager>,
+            path: QString,
+            size_mb: i32,
+            revision: i32,
+            allocation: QString,
+        ) -> i32;
+
+        /// Copy a disk image on a background worker, keeping it sparse
+        /// Returns the task id
+        #[qinvokable]
+        fn copy_disk_async(self: Pin<&mut DiskManager>, source: QString, destination: QString) -> i32;
+
+        /// Move a disk image on a background worker
+        /// Returns the task id
+        #[qinvokable]
+        fn move_disk_async(self: Pin<&mut DiskManager>, source: QString, destination: QString) -> i32;
+
+        /// Grow a disk image to size_mb on a background worker, and its FAT
+        /// volume with it if grow_filesystem is set
+        /// Returns the task id
+        #[qinvokable]
+        fn resize_disk_async(self: Pin<&mut DiskManager>, path: QString, size_mb: i32, grow_filesystem: bool) -> i32;
+
+        /// Convert a disk image to format ("raw", "sunpci" or "vhd") on a
+        /// background worker, writing it to destination
+        /// Returns the task id
+        #[qinvokable]
+        fn convert_disk_async(self: Pin<&mut DiskManager>, source: QString, destination: QString, format: QString) -> i32;
+
+        /// Give back the host space of the free, zeroed clusters of a disk
+        /// image on a background worker
+        /// Returns the task id
+        #[qinvokable]
+        fn compact_disk_async(self: Pin<&mut DiskManager>, path: QString) -> i32;
+
+        /// Rename a disk image within its directory
+        /// Returns the new path, or an empty string on failure
+        #[qinvokable]
+        fn rename_disk(self: &DiskManager, path: QString, new_name: QString) -> QString;
+
+        /// Copy a disk image into the templates directory, read-only
+        /// Returns the task id
+        #[qinvokable]
+        fn duplicate_as_template_async(self: Pin<&mut DiskManager>, source: QString, name: QString) -> i32;
+
+        /// Move a disk image into the templates directory, read-only
+        /// Profiles using it get a copy-on-write overlay when they start
+        /// Returns the task id
+        #[qinvokable]
+        fn mark_as_template_async(self: Pin<&mut DiskManager>, path: QString) -> i32;
+
+        /// List the template disks as a JSON array of paths
+        #[qinvokable]
+        fn list_templates(self: &DiskManager) -> QString;
+
+        /// Bundle the saved profile and its media into the directory
+        /// `path`, with a SHA-256 manifest
+        /// Returns the task id
+        #[qinvokable]
+        fn export_appliance_async(self: Pin<&mut DiskManager>, path: QString) -> i32;
+
+        /// Verify and import an appliance bundle, replacing the saved
+        /// profile with it (reload the ConfigManager when the task finishes)
+        /// Returns the task id
         #[qinvokable]
-        fn create_disk(self: &DiskManager, path: QString, size_mb: i32, revision: i32) -> bool;
+        fn import_appliance_async(self: Pin<&mut DiskManager>, path: QString) -> i32;
+
+        /// Request cancellation of a background task
+        #[qinvokable]
+        fn cancel_task(self: &DiskManager, task_id: i32) -> bool;
+
+        /// Poll background tasks and emit progress/finished signals
+        #[qinvokable]
+        fn poll_tasks(self: Pin<&mut DiskManager>);
 
         /// Mount a disk image to primary (slot 0) or secondary (slot 1)
         #[qinvokable]
@@ -47,18 +163,62 @@ mod qobject {
         #[qinvokable]
         fn eject_floppy(self: Pin<&mut DiskManager>, drive_number: i32);
 
-        /// Mount an ISO image
+        /// Mount an ISO image or a CUE/BIN image
         #[qinvokable]
         fn mount_iso(self: Pin<&mut DiskManager>, path: QString) -> bool;
 
+        /// Build the guest tools CD for the loaded driver and mount it;
+        /// returns JSON {"path", "files", "missing"} or {"error"}
+        #[qinvokable]
+        fn mount_guest_tools(self: Pin<&mut DiskManager>) -> QString;
+
         /// Eject the CD-ROM
         #[qinvokable]
         fn eject_cdrom(self: Pin<&mut DiskManager>);
 
+        /// Stop serving the CD-ROM without ejecting it, for a session handoff
+        #[qinvokable]
+        fn release_cdrom(self: Pin<&mut DiskManager>);
+
+        /// Carry out a guest request to eject a drive (BIOS drive number)
+        #[qinvokable]
+        fn apply_eject_request(self: Pin<&mut DiskManager>, drive: i32);
+
+        /// Track a medium mounted or ejected outside this frontend's own
+        /// calls (BIOS drive number)
+        #[qinvokable]
+        fn apply_media_change(self: Pin<&mut DiskManager>, drive: i32, path: QString, present: bool);
+
+        /// List host optical drives as "device - model" entries separated by ';'
+        #[qinvokable]
+        fn get_host_cdroms(self: &DiskManager) -> QString;
+
+        /// Pass the guest CD-ROM through to a host drive such as /dev/sr0
+        #[qinvokable]
+        fn mount_host_cdrom(self: Pin<&mut DiskManager>, device: QString) -> bool;
+
+        /// Describe a CD image (ISO or CUE sheet) as a JSON string
+        #[qinvokable]
+        fn get_disc_info(self: &DiskManager, path: QString) -> QString;
+
         /// Get disk information as JSON string
         #[qinvokable]
         fn get_disk_info(self: &DiskManager, path: QString) -> QString;
 
+        /// Check a disk image's header, partition table and FAT volume,
+        /// fixing what can be fixed if repair is set; returns a JSON report
+        #[qinvokable]
+        fn check_disk(self: &DiskManager, path: QString, repair: bool) -> QString;
+
+        /// Guess the operating system on a disk image; returns JSON
+        #[qinvokable]
+        fn detect_guest_os(self: &DiskManager, path: QString) -> QString;
+
+        /// Set the mouse protocol and clipboard to suit the operating system
+        /// on a disk image; false if it is not known
+        #[qinvokable]
+        fn apply_guest_os_tweaks(self: &DiskManager, path: QString) -> bool;
+
         /// Check if the disk at path is a valid SunPCi disk image
         #[qinvokable]
         fn is_valid_disk(self: &DiskManager, path: QString) -> bool;
@@ -66,6 +226,58 @@ mod qobject {
         /// Get the size of a disk image in MB
         #[qinvokable]
         fn get_disk_size_mb(self: &DiskManager, path: QString) -> i32;
+
+        /// Signal emitted while a background task is running
+        #[qsignal]
+        fn task_progress(
+            self: Pin<&mut DiskManager>,
+            task_id: i32,
+            name: QString,
+            step: QString,
+            percent: i32,
+        );
+
+        /// Signal emitted when a background task completes, fails or is cancelled
+        #[qsignal]
+        fn task_finished(
+            self: Pin<&mut DiskManager>,
+            task_id: i32,
+            success: bool,
+            error_code: i32,
+            message: QString,
+        );
+
+        /// Signal emitted when a background task has created a disk image
+        #[qsignal]
+        fn disk_created(self: Pin<&mut DiskManager>, task_id: i32, path: QString);
+
+        /// Signal emitted when creating a disk image failed or was cancelled;
+        /// the partial image has been removed
+        #[qsignal]
+        fn disk_creation_failed(
+            self: Pin<&mut DiskManager>,
+            task_id: i32,
+            path: QString,
+            error_code: i32,
+            message: QString,
+        );
+
+        /// Signal emitted when a driver event changed the CD-ROM
+        #[qsignal]
+        fn cdrom_changed(self: Pin<&mut DiskManager>, path: QString, mounted: bool);
+
+        /// Signal emitted when a driver event changed a floppy drive
+        #[qsignal]
+        fn floppy_changed(self: Pin<&mut DiskManager>, drive_number: i32, path: QString, mounted: bool);
+
+        /// Signal emitted when an operation fails for lack of free space
+        #[qsignal]
+        fn insufficient_space(
+            self: Pin<&mut DiskManager>,
+            path: QString,
+            required_mb: i32,
+            available_mb: i32,
+        );
     }
 
     unsafe extern "C++Qt" {
@@ -77,6 +289,18 @@ mod qobject {
 use std::pin::Pin;
 use cxx_qt_lib::QString;
 
+/// Error codes reported with task_finished
+pub mod error_code {
+    /// Operation succeeded
+    pub const NONE: i32 = 0;
+    /// Operation failed (see the message)
+    pub const FAILED: i32 = 1;
+    /// Operation was cancelled
+    pub const CANCELLED: i32 = 2;
+    /// Not enough free space on the host filesystem
+    pub const NO_SPACE: i32 = 3;
+}
+
 /// Rust implementation of the DiskManager
 pub struct DiskManagerRust {
     primary_disk_path: QString,
@@ -89,10 +313,26 @@ pub struct DiskManagerRust {
     floppy_a_mounted: bool,
     floppy_b_mounted: bool,
     cdrom_mounted: bool,
+    /// CD-ROM is a host drive rather than an ISO
+    cdrom_passthrough: bool,
+    /// Number of queued or running background tasks
+    active_tasks: i32,
+    /// Worker pool for long-running disk operations
+    tasks: TaskManager,
+    /// Paths of the disk images being created, by task
+    creating: RefCell<HashMap<TaskId, String>>,
+    /// Thread serving guest CD-ROM commands from a host drive
+    passthrough: RefCell<Option<PassthroughWorker>>,
 }
 
 impl Default for DiskManagerRust {
     fn default() -> Self {
+        let _timing = startup::span("DiskManager");
+        actions::register(Action::new("create_disk", "Create Disk Image...", "Devices"));
+        actions::register(Action::new("mount_iso", "Mount ISO Image...", "Devices"));
+        actions::register(Action::new("eject_cdrom", "Eject CD-ROM", "Devices"));
+        actions::register(Action::new("mount_floppy", "Mount Floppy Image...", "Devices"));
+        actions::register(Action::new("shared_folders", "Shared Folders...", "Devices"));
         Self {
             primary_disk_path: QString::default(),
             secondary_disk_path: QString::default(),
@@ -104,6 +344,11 @@ impl Default for DiskManagerRust {
             floppy_a_mounted: false,
             floppy_b_mounted: false,
             cdrom_mounted: false,
+            cdrom_passthrough: false,
+            active_tasks: 0,
+            tasks: TaskManager::new(2),
+            creating: RefCell::new(HashMap::new()),
+            passthrough: RefCell::new(None),
         }
     }
 }
@@ -115,16 +360,22 @@ impl qobject::DiskManager {
     /// - Magic "SPCI" at offset 12
     /// - MBR partition table
     /// - FAT16 filesystem (for sizes > 32MB) or FAT12 (smaller)
-    pub fn create_disk(&self, path: QString, size_mb: i32, revision: i32) -> bool {
+    ///
+    /// `allocation` is "sparse", "preallocate" or "zero-fill".
+    pub fn create_disk(&self, path: QString, size_mb: i32, revision: i32, allocation: QString) -> bool {
         let path_str = path.to_string();
+        let Some(allocation) = parse_allocation(&allocation) else {
+            return false;
+        };
         tracing::info!(
-            "Creating disk: path={}, size={}MB, revision={}",
+            "Creating disk: path={}, size={}MB, revision={}, allocation={}",
             path_str,
             size_mb,
-            revision
+            revision,
+            allocation.name()
         );
 
-        match create_disk_image(&path_str, size_mb as u32, revision as u8) {
+        match create_disk_image(&path_str, size_mb as u32, revision as u8, allocation, &ProgressReporter::new()) {
             Ok(()) => {
                 tracing::info!("Disk created successfully: {}", path_str);
                 true
@@ -136,6 +387,359 @@ impl qobject::DiskManager {
         }
     }
 
+    /// Create a new disk image on a background worker
+    pub fn create_disk_async(
+        mut self: Pin<&mut Self>,
+        path: QString,
+        size_mb: i32,
+        revision: i32,
+        allocation: QString,
+    ) -> i32 {
+        let path_str = path.to_string();
+        let Some(allocation) = parse_allocation(&allocation) else {
+            return -1;
+        };
+        tracing::info!(
+            "Queueing disk creation: path={}, size={}MB, revision={}, allocation={}",
+            path_str,
+            size_mb,
+            revision,
+            allocation.name()
+        );
+
+        let name = format!("Creating {}", path_str);
+        let job_path = path_str.clone();
+        let task = self.tasks.spawn(&name, move |progress| {
+            create_disk_image(&job_path, size_mb as u32, revision as u8, allocation, progress)
+        });
+        self.creating.borrow_mut().insert(task.id(), path_str);
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Copy a disk image on a background worker, keeping it sparse
+    pub fn copy_disk_async(mut self: Pin<&mut Self>, source: QString, destination: QString) -> i32 {
+        let source = expand_path(&source.to_string());
+        let destination = expand_path(&destination.to_string());
+        tracing::info!("Queueing disk copy: {} -> {}", source.display(), destination.display());
+
+        let name = format!("Copying {}", source.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            let method = disk_library::copy_image(&source, &destination, progress)?;
+            tracing::info!("Copied {} to {} ({:?})", source.display(), destination.display(), method);
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Move a disk image on a background worker
+    pub fn move_disk_async(mut self: Pin<&mut Self>, source: QString, destination: QString) -> i32 {
+        let source = expand_path(&source.to_string());
+        let destination = expand_path(&destination.to_string());
+        tracing::info!("Queueing disk move: {} -> {}", source.display(), destination.display());
+
+        let name = format!("Moving {}", source.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            disk_library::move_image(&source, &destination, progress)
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Grow a disk image on a background worker
+    pub fn resize_disk_async(mut self: Pin<&mut Self>, path: QString, size_mb: i32, grow_filesystem: bool) -> i32 {
+        let path_str = path.to_string();
+        let path = expand_path(&path_str);
+        let mounted = [self.as_ref().primary_disk_path(), self.as_ref().secondary_disk_path()]
+            .into_iter()
+            .any(|mounted| !mounted.is_empty() && expand_path(&mounted.to_string()) == path);
+        tracing::info!(
+            "Queueing disk resize: path={}, size={}MB, grow_filesystem={}",
+            path.display(),
+            size_mb,
+            grow_filesystem
+        );
+
+        let name = format!("Resizing {}", path.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            if mounted {
+                anyhow::bail!("{} is mounted; unmount it before resizing", path.display());
+            }
+            let resized = disk_resize::resize_disk_image(&path, size_mb.max(0) as u32, grow_filesystem, progress)?;
+            match resized.clusters {
+                Some((old, new)) => tracing::info!(
+                    "Resized {} to {} sectors, volume from {} to {} clusters",
+                    path.display(),
+                    resized.new_sectors,
+                    old,
+                    new
+                ),
+                None => tracing::info!("Resized {} to {} sectors", path.display(), resized.new_sectors),
+            }
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Convert a disk image on a background worker
+    pub fn convert_disk_async(mut self: Pin<&mut Self>, source: QString, destination: QString, format: QString) -> i32 {
+        let source = expand_path(&source.to_string());
+        let destination = expand_path(&destination.to_string());
+        let format_name = format.to_string();
+        tracing::info!(
+            "Queueing disk conversion: {} -> {} ({})",
+            source.display(),
+            destination.display(),
+            format_name
+        );
+
+        let name = format!("Converting {}", source.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            let format = match format_name.as_str() {
+                "" => DiskFormat::for_path(&destination),
+                name => DiskFormat::parse(name).ok_or_else(|| anyhow::anyhow!("unknown disk format {}", name))?,
+            };
+            let converted = disk_convert::convert_disk(&source, &destination, format, progress)?;
+            tracing::info!(
+                "Converted {} ({}) to {} ({})",
+                source.display(),
+                converted.from.name(),
+                destination.display(),
+                converted.to.name()
+            );
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Compact a sparse disk image on a background worker
+    pub fn compact_disk_async(mut self: Pin<&mut Self>, path: QString) -> i32 {
+        let path = expand_path(&path.to_string());
+        tracing::info!("Queueing disk compaction of {}", path.display());
+
+        let name = format!("Compacting {}", path.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            progress.set_step("Scanning free clusters");
+            let compacted = disk_compact::compact_disk_image(&path, progress)?;
+            tracing::info!(
+                "Compacted {}: {} free clusters, {} MB given back",
+                path.display(),
+                compacted.clusters,
+                compacted.reclaimed() / (1024 * 1024)
+            );
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Rename a disk image within its directory
+    pub fn rename_disk(&self, path: QString, new_name: QString) -> QString {
+        let path = expand_path(&path.to_string());
+        match disk_library::rename_image(&path, &new_name.to_string()) {
+            Ok(renamed) => {
+                tracing::info!("Renamed {} to {}", path.display(), renamed.display());
+                QString::from(&renamed.to_string_lossy().into_owned())
+            }
+            Err(e) => {
+                tracing::error!("Failed to rename {}: {:#}", path.display(), e);
+                QString::default()
+            }
+        }
+    }
+
+    /// Copy a disk image into the templates directory, read-only
+    pub fn duplicate_as_template_async(mut self: Pin<&mut Self>, source: QString, name: QString) -> i32 {
+        let source = expand_path(&source.to_string());
+        let template_name = name.to_string();
+        tracing::info!("Queueing template from {}: {}", source.display(), template_name);
+
+        let name = format!("Saving template {}", template_name);
+        let task = self.tasks.spawn(&name, move |progress| {
+            let template = disk_library::duplicate_as_template(
+                &source,
+                &disk_library::templates_dir(),
+                &template_name,
+                progress,
+            )?;
+            tracing::info!("Saved template {}", template.display());
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Move a disk image into the templates directory, read-only
+    pub fn mark_as_template_async(mut self: Pin<&mut Self>, path: QString) -> i32 {
+        let path = expand_path(&path.to_string());
+        tracing::info!("Queueing template marking of {}", path.display());
+
+        let name = format!("Making {} a template", path.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            let template = disk_library::mark_template(&path, &disk_library::templates_dir(), progress)?;
+            tracing::info!("{} is now template {}", path.display(), template.display());
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// List the template disks as a JSON array of paths
+    pub fn list_templates(&self) -> QString {
+        let templates: Vec<String> = disk_library::list_templates(&disk_library::templates_dir())
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+        QString::from(&serde_json::to_string(&templates).unwrap_or_else(|_| "[]".into()))
+    }
+
+    /// Bundle the saved profile and its media into the directory `path`
+    pub fn export_appliance_async(mut self: Pin<&mut Self>, path: QString) -> i32 {
+        let path = expand_path(&path.to_string());
+        tracing::info!("Queueing appliance export to {}", path.display());
+
+        let name = format!("Exporting {}", path.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            let config = load_config()?;
+            appliance::export_appliance(&config, &path, progress)?;
+            tracing::info!("Exported appliance {}", path.display());
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Verify and import an appliance bundle as the saved profile
+    pub fn import_appliance_async(mut self: Pin<&mut Self>, path: QString) -> i32 {
+        let path = expand_path(&path.to_string());
+        tracing::info!("Queueing appliance import from {}", path.display());
+
+        let name = format!("Importing {}", path.display());
+        let task = self.tasks.spawn(&name, move |progress| {
+            let bundle_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let dir = appliance::appliances_dir();
+            let mut dest = dir.join(&bundle_name);
+            let mut n = 1;
+            while dest.exists() {
+                dest = dir.join(format!("{}-{}", bundle_name, n));
+                n += 1;
+            }
+
+            let imported = appliance::import_appliance(&path, &dest, &disk_library::templates_dir(), progress)?;
+            let config = appliance::with_host_settings(imported, &load_config()?);
+            save_config(&config)?;
+            tracing::info!("Imported appliance {} into {}", path.display(), dest.display());
+            Ok(())
+        });
+
+        let active = self.tasks.active_count() as i32;
+        self.as_mut().set_active_tasks(active);
+        task.id() as i32
+    }
+
+    /// Request cancellation of a background task
+    pub fn cancel_task(&self, task_id: i32) -> bool {
+        tracing::info!("Cancelling task {}", task_id);
+        self.tasks.cancel(task_id as u64)
+    }
+
+    /// Poll background tasks and emit progress/finished signals
+    pub fn poll_tasks(mut self: Pin<&mut Self>) {
+        for task in self.tasks.tasks() {
+            if task.status() == TaskStatus::Running {
+                let progress = task.progress();
+                self.as_mut().task_progress(
+                    task.id() as i32,
+                    QString::from(task.name()),
+                    QString::from(&progress.step),
+                    progress.percent() as i32,
+                );
+            }
+        }
+
+        for task in self.tasks.take_finished() {
+            let (code, message) = match task.status() {
+                TaskStatus::Completed => (error_code::NONE, "Completed".to_string()),
+                TaskStatus::Cancelled => (error_code::CANCELLED, "Cancelled".to_string()),
+                TaskStatus::Failed(e) => (error_code::FAILED, e),
+                _ => continue,
+            };
+
+            let error = task.error();
+            let space = error
+                .as_deref()
+                .and_then(|e| e.chain().find_map(|c| c.downcast_ref::<DiskSpaceError>()));
+            let code = if space.is_some() { error_code::NO_SPACE } else { code };
+
+            if code == error_code::NONE {
+                tracing::info!("{}: done", task.name());
+            } else {
+                tracing::warn!("{}: {}", task.name(), message);
+            }
+            if let Some(space) = space {
+                self.as_mut().report_insufficient_space(space);
+            }
+            self.as_mut().task_finished(
+                task.id() as i32,
+                code == error_code::NONE,
+                code,
+                QString::from(&message),
+            );
+
+            let created = self.creating.borrow_mut().remove(&task.id());
+            if let Some(path) = created {
+                if code == error_code::NONE {
+                    self.as_mut().disk_created(task.id() as i32, QString::from(&path));
+                } else {
+                    self.as_mut().disk_creation_failed(
+                        task.id() as i32,
+                        QString::from(&path),
+                        code,
+                        QString::from(&message),
+                    );
+                }
+            }
+        }
+
+        let active = self.tasks.active_count() as i32;
+        if *self.as_ref().active_tasks() != active {
+            self.as_mut().set_active_tasks(active);
+        }
+    }
+
+    /// Emit insufficient_space with the sizes rounded up to whole MB
+    fn report_insufficient_space(self: Pin<&mut Self>, err: &DiskSpaceError) {
+        const MB: u64 = 1024 * 1024;
+        let required_mb = err.required().div_ceil(MB).min(i32::MAX as u64) as i32;
+        let available_mb = (err.available() / MB).min(i32::MAX as u64) as i32;
+        self.insufficient_space(
+            QString::from(&err.path().display().to_string()),
+            required_mb,
+            available_mb,
+        );
+    }
+
     /// Mount a disk image to a slot (0 = primary/C:, 1 = secondary/D:)
     pub fn mount_disk(mut self: Pin<&mut Self>, path: QString, slot: i32) -> bool {
         let path_str = path.to_string();
@@ -148,9 +752,15 @@ impl qobject::DiskManager {
             return false;
         }
 
-        // Expand path
+        // Expand path; writes go to a throwaway overlay when changes are not kept
         let expanded_path = expand_path(&path_str);
-        let expanded_str = expanded_path.to_string_lossy().to_string();
+        let expanded_str = match ephemeral::writable_image(&expanded_path) {
+            Ok(image) => image.to_string_lossy().to_string(),
+            Err(e) => {
+                tracing::error!("Cannot create an overlay of {}: {:#}", path_str, e);
+                return false;
+            }
+        };
 
         // Try to mount via driver - do this in separate scope to avoid borrow issues
         let mount_result = {
@@ -236,7 +846,6 @@ impl qobject::DiskManager {
 
         // Expand path
         let expanded_path = expand_path(&path_str);
-        let expanded_str = expanded_path.to_string_lossy().to_string();
 
         // Check file exists and has reasonable size for floppy
         match std::fs::metadata(&expanded_path) {
@@ -254,6 +863,15 @@ impl qobject::DiskManager {
             }
         }
 
+        // Writes go to a throwaway overlay when changes are not kept
+        let expanded_str = match ephemeral::writable_image(&expanded_path) {
+            Ok(image) => image.to_string_lossy().to_string(),
+            Err(e) => {
+                tracing::error!("Cannot create an overlay of {}: {:#}", path_str, e);
+                return false;
+            }
+        };
+
         // Mount via driver
         let mount_result = {
             if !is_driver_loaded() {
@@ -297,44 +915,18 @@ impl qobject::DiskManager {
         let path_str = path.to_string();
         tracing::info!("Creating floppy image: {} ({} bytes)", path_str, size_bytes);
 
-        if size_bytes <= 0 || size_bytes > 3 * 1024 * 1024 {
+        if size_bytes <= 0 {
             tracing::error!("Invalid floppy size: {} bytes", size_bytes);
             return false;
         }
 
-        // Expand path
-        let expanded_path = expand_path(&path_str);
-
-        // Check if file already exists
-        if expanded_path.exists() {
-            tracing::error!("File already exists: {}", path_str);
-            return false;
-        }
-
-        // Create parent directory if needed
-        if let Some(parent) = expanded_path.parent() {
-            if !parent.exists() {
-                if let Err(e) = std::fs::create_dir_all(parent) {
-                    tracing::error!("Failed to create directory: {}", e);
-                    return false;
-                }
-            }
-        }
-
-        // Create the file filled with zeros
-        match std::fs::File::create(&expanded_path) {
-            Ok(file) => {
-                // Set the file size (sparse file)
-                if let Err(e) = file.set_len(size_bytes as u64) {
-                    tracing::error!("Failed to set file size: {}", e);
-                    let _ = std::fs::remove_file(&expanded_path);
-                    return false;
-                }
+        match disk_image::create_floppy_image(&expand_path(&path_str), size_bytes as u64) {
+            Ok(()) => {
                 tracing::info!("Floppy image created: {}", path_str);
                 true
             }
             Err(e) => {
-                tracing::error!("Failed to create floppy image: {}", e);
+                tracing::error!("Failed to create floppy image {}: {}", path_str, e);
                 false
             }
         }
@@ -378,6 +970,9 @@ impl qobject::DiskManager {
     }
 
     /// Mount an ISO image as CD-ROM
+    ///
+    /// A CUE sheet is served by the frontend through the pass-through
+    /// interface, since the driver only understands single-track ISOs.
     pub fn mount_iso(mut self: Pin<&mut Self>, path: QString) -> bool {
         let path_str = path.to_string();
         tracing::info!("Mounting ISO: {}", path_str);
@@ -392,6 +987,26 @@ impl qobject::DiskManager {
             return false;
         }
 
+        if is_cue_sheet(&expanded_path) {
+            let image = match VirtualCd::open(&expanded_path) {
+                Ok(image) => image,
+                Err(e) => {
+                    tracing::error!("Cannot use CUE sheet {}: {}", path_str, e);
+                    return false;
+                }
+            };
+            tracing::info!("CUE sheet has {} tracks", image.toc().tracks.len());
+            if !self.as_mut().start_passthrough(CdSource::Image(image), &expanded_str) {
+                return false;
+            }
+            self.as_mut().set_cdrom_path(path);
+            self.as_mut().set_cdrom_mounted(true);
+            return true;
+        }
+
+        // The driver ignores the ISO while a host drive is passed through
+        self.as_mut().stop_passthrough();
+
         // Mount via driver
         let mount_result = {
             if !is_driver_loaded() {
@@ -421,9 +1036,46 @@ impl qobject::DiskManager {
         }
     }
 
+    /// Build the guest tools CD and mount it
+    pub fn mount_guest_tools(self: Pin<&mut Self>) -> QString {
+        let error = |message: String| QString::from(&serde_json::json!({ "error": message }).to_string());
+        let version = match DriverHandle::open().and_then(|handle| handle.get_version()) {
+            Ok(version) => version,
+            Err(e) => return error(format!("Cannot read the driver version: {}", e)),
+        };
+        let configured = load_config().unwrap_or_default().guest_tools.source;
+        let Some(source) = guest_tools::find_source(configured.as_deref()) else {
+            return error("No SunPCi software found; set [guest_tools] source to a copy of it".to_string());
+        };
+
+        let built = match guest_tools::build_iso(&source, &version, &guest_tools::iso_path(&version)) {
+            Ok(built) => built,
+            Err(e) => {
+                tracing::warn!("Failed to build the guest tools CD: {:#}", e);
+                return error(format!("{:#}", e));
+            }
+        };
+        tracing::info!(
+            "Built {} with {} tools, {} missing",
+            built.path.display(),
+            built.files.len(),
+            built.missing.len()
+        );
+        if !self.mount_iso(QString::from(&built.path.to_string_lossy().to_string())) {
+            return error(format!("Cannot mount {}", built.path.display()));
+        }
+        let json = serde_json::json!({
+            "path": built.path.display().to_string(),
+            "files": built.files,
+            "missing": built.missing,
+        });
+        QString::from(&json.to_string())
+    }
+
     /// Eject the CD-ROM
     pub fn eject_cdrom(mut self: Pin<&mut Self>) {
         tracing::info!("Ejecting CD-ROM");
+        self.as_mut().stop_passthrough();
 
         let eject_result = {
             if !is_driver_loaded() {
@@ -451,6 +1103,202 @@ impl qobject::DiskManager {
         }
     }
 
+    /// Stop serving the CD-ROM without ejecting it
+    ///
+    /// Used when another frontend adopts the session: the driver keeps an
+    /// ISO mounted, while a pass-through source is served again by the
+    /// adopter.
+    pub fn release_cdrom(mut self: Pin<&mut Self>) {
+        self.as_mut().stop_passthrough();
+        self.as_mut().set_cdrom_path(QString::default());
+        self.as_mut().set_cdrom_mounted(false);
+    }
+
+    /// Carry out a guest request to eject a drive
+    pub fn apply_eject_request(mut self: Pin<&mut Self>, drive: i32) {
+        match drive as u32 {
+            event_drive::CDROM => {
+                tracing::info!("Guest requested CD-ROM eject");
+                self.as_mut().eject_cdrom();
+                if !*self.as_ref().cdrom_mounted() {
+                    self.as_mut().cdrom_changed(QString::default(), false);
+                }
+            }
+            event_drive::FLOPPY_A | event_drive::FLOPPY_B => {
+                tracing::info!("Guest requested floppy eject from drive {}", drive);
+                self.as_mut().eject_floppy(drive);
+                let mounted = if drive as u32 == event_drive::FLOPPY_A {
+                    *self.as_ref().floppy_a_mounted()
+                } else {
+                    *self.as_ref().floppy_b_mounted()
+                };
+                if !mounted {
+                    self.as_mut().floppy_changed(drive, QString::default(), false);
+                }
+            }
+            _ => tracing::debug!("Ignoring eject request for drive {:#x}", drive),
+        }
+    }
+
+    /// Track a medium mounted or ejected through the driver
+    ///
+    /// Changes this frontend made itself are reported back too; they match
+    /// the properties already, and events arrive in order, so only changes
+    /// made elsewhere (the CLI, another frontend) emit a signal.
+    pub fn apply_media_change(mut self: Pin<&mut Self>, drive: i32, path: QString, present: bool) {
+        let path_str = path.to_string();
+        let description = if present { path_str.as_str() } else { "ejected" };
+        match drive as u32 {
+            event_drive::CDROM => {
+                // A served CD is not the driver's; its events are stale
+                if self.passthrough.borrow().is_some() {
+                    return;
+                }
+                if *self.as_ref().cdrom_mounted() == present
+                    && expand_path(&self.as_ref().cdrom_path().to_string()) == Path::new(&path_str)
+                {
+                    return;
+                }
+                tracing::info!("CD-ROM changed: {}", description);
+                self.as_mut().set_cdrom_path(path.clone());
+                self.as_mut().set_cdrom_mounted(present);
+                self.as_mut().cdrom_changed(path, present);
+            }
+            event_drive::FLOPPY_A | event_drive::FLOPPY_B => {
+                let drive_a = drive as u32 == event_drive::FLOPPY_A;
+                let (current, mounted) = if drive_a {
+                    (self.as_ref().floppy_a_path().to_string(), *self.as_ref().floppy_a_mounted())
+                } else {
+                    (self.as_ref().floppy_b_path().to_string(), *self.as_ref().floppy_b_mounted())
+                };
+                if mounted == present && expand_path(&current) == Path::new(&path_str) {
+                    return;
+                }
+                tracing::info!("Floppy drive {} changed: {}", drive, description);
+                if drive_a {
+                    self.as_mut().set_floppy_a_path(path.clone());
+                    self.as_mut().set_floppy_a_mounted(present);
+                } else {
+                    self.as_mut().set_floppy_b_path(path.clone());
+                    self.as_mut().set_floppy_b_mounted(present);
+                }
+                self.as_mut().floppy_changed(drive, path, present);
+            }
+            _ => {}
+        }
+    }
+
+    /// List host optical drives
+    ///
+    /// Entries are "/dev/sr0 - Vendor Model" (or just the device when the
+    /// kernel reports no model), separated by ';'.
+    pub fn get_host_cdroms(&self) -> QString {
+        let drives: Vec<String> = host_cdrom::list_host_cdroms()
+            .iter()
+            .map(|path| match host_cdrom::drive_model(path) {
+                Some(model) => format!("{} - {}", path.display(), model),
+                None => path.display().to_string(),
+            })
+            .collect();
+        QString::from(&drives.join(";"))
+    }
+
+    /// Pass the guest CD-ROM through to a host drive
+    ///
+    /// Every guest SCSI command is sent to the drive unchanged, so audio
+    /// CDs, mixed-mode and copy-protected discs behave as on real hardware.
+    /// Replaces any mounted ISO.
+    pub fn mount_host_cdrom(mut self: Pin<&mut Self>, device: QString) -> bool {
+        let device_str = device.to_string();
+        tracing::info!("Passing CD-ROM through to host drive: {}", device_str);
+
+        if !is_driver_loaded() {
+            tracing::error!("Failed to use host drive: Driver not loaded");
+            return false;
+        }
+
+        // Open the drive here so a permission problem is reported up front
+        let drive = match HostCdrom::open(Path::new(&device_str)) {
+            Ok(drive) => drive,
+            Err(e) => {
+                tracing::error!("Cannot open {}: {}", device_str, e);
+                return false;
+            }
+        };
+
+        if !self.as_mut().start_passthrough(CdSource::Host(drive), &device_str) {
+            return false;
+        }
+        self.as_mut().set_cdrom_path(device);
+        self.as_mut().set_cdrom_mounted(true);
+        self.as_mut().set_cdrom_passthrough(true);
+        true
+    }
+
+    /// Internal: take guest CD-ROM commands from the driver and serve them from `source`
+    fn start_passthrough(mut self: Pin<&mut Self>, source: CdSource, label: &str) -> bool {
+        self.as_mut().stop_passthrough();
+
+        let handle = match DriverHandle::open() {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::error!("Failed to open driver for CD-ROM pass-through: {}", e);
+                return false;
+            }
+        };
+        if let Err(e) = handle.set_cdrom_passthrough(Some(label)) {
+            tracing::error!("Failed to enable CD-ROM pass-through: {}", e);
+            return false;
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let thread = std::thread::spawn(move || passthrough_thread(handle, source, thread_running));
+        *self.passthrough.borrow_mut() = Some(PassthroughWorker { running, handle: Some(thread) });
+        true
+    }
+
+    /// Internal: stop serving a host drive or CUE image and hand the CD-ROM back to the driver
+    fn stop_passthrough(mut self: Pin<&mut Self>) {
+        // Dropping the worker joins the thread
+        if self.passthrough.borrow_mut().take().is_none() {
+            return;
+        }
+
+        let result = DriverHandle::open().and_then(|handle| handle.set_cdrom_passthrough(None));
+        if let Err(e) = result {
+            tracing::error!("Failed to disable CD-ROM pass-through: {}", e);
+        }
+        self.as_mut().set_cdrom_path(QString::default());
+        self.as_mut().set_cdrom_mounted(false);
+        self.as_mut().set_cdrom_passthrough(false);
+    }
+
+    /// Describe a CD image as JSON
+    ///
+    /// Returns JSON with fields:
+    /// - valid: bool - whether the image can be mounted
+    /// - format: string - "ISO 9660" or "CUE/BIN"
+    /// - tracks: array - {number, type ("data"/"audio"), mode, start, sectors}
+    /// - total_sectors: number - sectors up to the lead-out
+    /// - volume_label: string - ISO 9660 label of the first data track, or ""
+    /// - bootable: bool - whether the guest can boot from the image
+    /// - boot: string - El Torito boot image description, or ""
+    /// - error: string - why the image is not valid
+    pub fn get_disc_info(&self, path: QString) -> QString {
+        let path = expand_path(&path.to_string());
+        let info = if is_cue_sheet(&path) {
+            cue_disc_info(&path)
+        } else {
+            iso_disc_info(&path)
+        };
+        let info = info.unwrap_or_else(|e| {
+            tracing::warn!("Failed to read disc info for {}: {}", path.display(), e);
+            serde_json::json!({ "valid": false, "error": e })
+        });
+        QString::from(&info.to_string())
+    }
+
     /// Get disk information as JSON
     /// 
     /// Returns JSON with fields:
@@ -463,27 +1311,107 @@ impl qobject::DiskManager {
     /// - total_sectors: number - total sector count
     /// - bootable: bool - whether partition is marked bootable
     /// - partition_type: string - partition type description
+    /// - allocation: string - "sparse", "preallocate" or "zero-fill"
     pub fn get_disk_info(&self, path: QString) -> QString {
         let path_str = path.to_string();
         tracing::debug!("Getting disk info for: {}", path_str);
         
-        match read_disk_header(&path_str) {
-            Ok(info) => {
-                QString::from(&format!(
-                    r#"{{"valid": true, "size_mb": {}, "revision": {}, "cylinders": {}, "heads": {}, "sectors": {}, "total_sectors": {}, "bootable": {}, "partition_type": "{}"}}"#,
-                    info.size_mb,
-                    info.revision,
-                    info.cylinders,
-                    info.heads,
-                    info.sectors_per_track,
-                    info.total_sectors,
-                    info.bootable,
-                    info.partition_type
-                ))
-            }
+        let info = match disk_image::read_disk_header(&expand_path(&path_str)) {
+            Ok(info) => DiskInfoJson::valid(&info),
             Err(e) => {
                 tracing::warn!("Failed to read disk info for {}: {}", path_str, e);
-                QString::from(r#"{"valid": false, "error": "Failed to read disk header"}"#)
+                DiskInfoJson::invalid("Failed to read disk header")
+            }
+        };
+        QString::from(&ui_json::to_string(&info))
+    }
+
+    /// Check (and optionally repair) a disk image
+    pub fn check_disk(&self, path: QString, repair: bool) -> QString {
+        let path = expand_path(&path.to_string());
+        let mounted = [self.primary_disk_path(), self.secondary_disk_path()]
+            .into_iter()
+            .any(|mounted| !mounted.is_empty() && expand_path(&mounted.to_string()) == path);
+        if repair && mounted {
+            let message = format!("{} is mounted; unmount it before repairing", path.display());
+            return QString::from(&serde_json::json!({ "error": message }).to_string());
+        }
+
+        match disk_check::check_disk(&path, repair) {
+            Ok(report) => {
+                let remaining = report.remaining();
+                let repaired = report.issues.len() - remaining;
+                if repaired > 0 {
+                    tracing::info!("{}: repaired {} problems, {} left", path.display(), repaired, remaining);
+                } else if remaining > 0 {
+                    tracing::warn!("{}: {} problems found", path.display(), remaining);
+                }
+                let mut json = serde_json::to_value(&report).unwrap_or_default();
+                json["clean"] = report.is_clean().into();
+                QString::from(&json.to_string())
+            }
+            Err(e) => {
+                tracing::warn!("Failed to check {}: {}", path.display(), e);
+                let message = if e.kind() == io::ErrorKind::WouldBlock {
+                    format!("{} is in use by a running session", path.display())
+                } else {
+                    format!("Cannot check {}: {}", path.display(), e)
+                };
+                QString::from(&serde_json::json!({ "error": message }).to_string())
+            }
+        }
+    }
+
+    /// Guess the operating system on a disk image
+    ///
+    /// Returns JSON with fields:
+    /// - os: string - "unknown", "dos", "windows31", "windows9x" or "windows_nt"
+    /// - name: string - name of the system to show
+    /// - version: string or null - release, when the files tell
+    /// - volume_label: string - label of the volume
+    /// - evidence: array - files and partitions the guess rests on
+    /// - tweaks: object or null - {mouse_protocol, clipboard, drivers}
+    /// - error: string - why the image could not be read
+    pub fn detect_guest_os(&self, path: QString) -> QString {
+        let path = expand_path(&path.to_string());
+        let json = match guest_os::detect(&path) {
+            Ok(detection) => {
+                let mut json = serde_json::to_value(&detection).unwrap_or_default();
+                json["name"] = detection.os.name().into();
+                json["tweaks"] = serde_json::to_value(detection.os.tweaks()).unwrap_or_default();
+                json
+            }
+            Err(e) => {
+                tracing::warn!("Failed to detect the guest OS of {}: {}", path.display(), e);
+                serde_json::json!({ "error": format!("Cannot read {}: {}", path.display(), e) })
+            }
+        };
+        QString::from(&json.to_string())
+    }
+
+    /// Set the mouse protocol and clipboard to suit a disk's operating system
+    pub fn apply_guest_os_tweaks(&self, path: QString) -> bool {
+        let path = expand_path(&path.to_string());
+        let os = match guest_os::detect(&path) {
+            Ok(detection) => detection.os,
+            Err(e) => {
+                tracing::warn!("Failed to detect the guest OS of {}: {}", path.display(), e);
+                return false;
+            }
+        };
+        let Some(tweaks) = os.tweaks() else {
+            return false;
+        };
+        let mut config = load_config().unwrap_or_default();
+        tweaks.apply(&mut config);
+        match save_config(&config) {
+            Ok(()) => {
+                tracing::info!("Settings adjusted for {} on {}", os.name(), path.display());
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Failed to save settings for {}: {}", os.name(), e);
+                false
             }
         }
     }
@@ -491,7 +1419,7 @@ impl qobject::DiskManager {
     /// Check if the disk at path is a valid SunPCi disk image
     pub fn is_valid_disk(&self, path: QString) -> bool {
         let path_str = path.to_string();
-        match read_disk_header(&path_str) {
+        match disk_image::read_disk_header(&expand_path(&path_str)) {
             Ok(info) => info.is_sunpci,
             Err(_) => false,
         }
@@ -500,225 +1428,234 @@ impl qobject::DiskManager {
     /// Get the size of a disk image in MB
     pub fn get_disk_size_mb(&self, path: QString) -> i32 {
         let path_str = path.to_string();
-        match read_disk_header(&path_str) {
+        match disk_image::read_disk_header(&expand_path(&path_str)) {
             Ok(info) => info.size_mb as i32,
             Err(_) => 0,
         }
     }
 }
 
-/// SunPCi disk magic number: "SPCI" = 0x53504349
-const SUNPCI_MAGIC: u32 = 0x53504349;
-
-/// Sector size in bytes
-const SECTOR_SIZE: u32 = 512;
-
-/// Calculate disk geometry for a given size
-/// Returns (cylinders, heads, sectors_per_track)
-fn calculate_geometry(size_mb: u32) -> (u16, u8, u8) {
-    let total_sectors = (size_mb as u64 * 1024 * 1024) / SECTOR_SIZE as u64;
-    
-    // Standard sectors per track
-    let sectors_per_track: u8 = 63;
-    
-    // Choose heads based on disk size to stay within 1024 cylinder limit
-    let heads: u8 = if size_mb <= 504 {
-        16
-    } else if size_mb <= 1008 {
-        32
-    } else if size_mb <= 2016 {
-        64
-    } else if size_mb <= 4032 {
-        128
-    } else {
-        255
-    };
-    
-    let cylinders = (total_sectors / (heads as u64 * sectors_per_track as u64)) as u16;
-    let cylinders = cylinders.min(1024); // CHS limit
-    
-    (cylinders, heads, sectors_per_track)
+/// Whether a CD image path names a CUE sheet rather than an ISO
+fn is_cue_sheet(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cue"))
 }
 
-/// Create a SunPCi-compatible disk image
-fn create_disk_image(path: &str, size_mb: u32, revision: u8) -> std::io::Result<()> {
-    // Expand ~ to home directory
-    let expanded_path = if path.starts_with("~/") {
-        if let Some(home) = std::env::var_os("HOME") {
-            Path::new(&home).join(&path[2..])
-        } else {
-            Path::new(path).to_path_buf()
-        }
-    } else {
-        Path::new(path).to_path_buf()
-    };
-    
-    // Create parent directories if needed
-    if let Some(parent) = expanded_path.parent() {
-        std::fs::create_dir_all(parent)?;
-    }
-    
-    let (cylinders, heads, sectors_per_track) = calculate_geometry(size_mb);
+/// `get_disc_info` for a CUE sheet
+fn cue_disc_info(path: &Path) -> Result<serde_json::Value, String> {
+    let image = VirtualCd::open(path).map_err(|e| e.to_string())?;
+    let toc = image.toc();
+    let tracks: Vec<serde_json::Value> = image
+        .sheet()
+        .tracks
+        .iter()
+        .zip(&toc.tracks)
+        .map(|(cue, track)| {
+            serde_json::json!({
+                "number": track.number,
+                "type": if track.audio { "audio" } else { "data" },
+                "mode": cue.mode.name(),
+                "start": track.start_lba,
+                "sectors": toc.track_end(track) - track.start_lba,
+            })
+        })
+        .collect();
+    Ok(serde_json::json!({
+        "valid": true,
+        "format": "CUE/BIN",
+        "tracks": tracks,
+        "total_sectors": toc.lead_out,
+        "volume_label": image.volume_label().unwrap_or_default(),
+        // The BIOS only boots ISOs the driver mounts itself
+        "bootable": false,
+        "boot": boot_description(el_torito::read_catalog(|lba, buf| {
+            image
+                .read_user_data(lba, buf)
+                .map_err(|_| io::Error::from(io::ErrorKind::InvalidData))
+        })),
+    }))
+}
+
+/// `get_disc_info` for an ISO image
+fn iso_disc_info(path: &Path) -> Result<serde_json::Value, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    if size == 0 || size % u64::from(SECTOR_SIZE_CDROM) != 0 {
+        return Err("not a whole number of 2048-byte sectors".to_string());
+    }
+    let sectors = size / u64::from(SECTOR_SIZE_CDROM);
+
+    let mut pvd = vec![0u8; SECTOR_SIZE_CDROM as usize];
+    file.seek(SeekFrom::Start(u64::from(virtual_cd::ISO9660_PVD_SECTOR * SECTOR_SIZE_CDROM)))
+        .and_then(|_| file.read_exact(&mut pvd))
+        .map_err(|_| "too small to be an ISO 9660 image".to_string())?;
+    let label = virtual_cd::iso9660_label(&pvd).ok_or("no ISO 9660 volume descriptor")?;
+
+    Ok(serde_json::json!({
+        "valid": true,
+        "format": "ISO 9660",
+        "tracks": [{ "number": 1, "type": "data", "mode": "MODE1/2048", "start": 0, "sectors": sectors }],
+        "total_sectors": sectors,
+        "volume_label": label,
+        "bootable": el_torito::check_bootable(path).is_ok(),
+        "boot": boot_description(el_torito::read_iso(path)),
+    }))
+}
+
+/// Describe an El Torito boot image for `get_disc_info`
+fn boot_description(catalog: io::Result<Option<BootCatalog>>) -> String {
+    match catalog {
+        Ok(Some(catalog)) => {
+            let mut description = format!("{}, {}", catalog.platform.name(), catalog.initial.media.name());
+            if !catalog.initial.bootable {
+                description.push_str(" (not marked bootable)");
+            }
+            description
+        }
+        Ok(None) => String::new(),
+        Err(e) => format!("unreadable boot catalog: {}", e),
+    }
+}
+
+/// Where guest CD-ROM commands are served from in pass-through mode
+enum CdSource {
+    /// A host optical drive, via SG_IO
+    Host(HostCdrom),
+    /// A CUE/BIN image
+    Image(VirtualCd),
+}
+
+impl CdSource {
+    fn execute(&mut self, request: &ScsiRequest, data: &mut [u8]) -> ScsiResponse {
+        match self {
+            Self::Host(drive) => drive.execute(request, data).unwrap_or_else(|e| {
+                tracing::warn!("{}", e);
+                // LOGICAL UNIT COMMUNICATION FAILURE
+                ScsiResponse::check_condition(SENSE_HARDWARE_ERROR, 0x08, 0x00)
+            }),
+            Self::Image(image) => image.execute(request, data),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Host(drive) => drive.path().display().to_string(),
+            Self::Image(image) => image
+                .sheet()
+                .files
+                .first()
+                .map_or_else(String::new, |f| f.display().to_string()),
+        }
+    }
+}
+
+/// Thread answering guest CD-ROM commands from a host drive or CUE image
+struct PassthroughWorker {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for PassthroughWorker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Idle time between polls when the guest is not using the CD-ROM
+const PASSTHROUGH_IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// Back-off after the driver refuses a poll
+const PASSTHROUGH_ERROR_SLEEP: Duration = Duration::from_millis(100);
+
+/// SCSI sense key for a failure the guest cannot retry around
+const SENSE_HARDWARE_ERROR: u8 = 0x04;
+
+/// Serve guest CD-ROM commands from `source` until stopped
+///
+/// For an image this also moves CD audio play along at 75 sectors a
+/// second, so the guest sees the position advance and play complete. The
+/// samples themselves are not mixed into the host audio output.
+fn passthrough_thread(driver: DriverHandle, mut source: CdSource, running: Arc<AtomicBool>) {
+    let mut data = vec![0u8; SCSI_DATA_MAX_LEN];
+    let mut audio_clock = Instant::now();
+
+    while running.load(Ordering::SeqCst) {
+        if let CdSource::Image(image) = &mut source {
+            let sectors = audio_clock.elapsed().as_millis() as u64 * u64::from(CD_FRAMES_PER_SECOND) / 1000;
+            if sectors > 0 {
+                image.advance_audio(sectors as u32);
+                audio_clock += Duration::from_secs(sectors) / CD_FRAMES_PER_SECOND;
+            }
+        }
+
+        let (tag, request) = match driver.next_scsi_request(&mut data) {
+            Ok(Some(next)) => next,
+            Ok(None) => {
+                std::thread::sleep(PASSTHROUGH_IDLE_SLEEP);
+                continue;
+            }
+            Err(e) => {
+                tracing::debug!("CD-ROM pass-through poll failed: {}", e);
+                std::thread::sleep(PASSTHROUGH_ERROR_SLEEP);
+                continue;
+            }
+        };
+
+        let response = source.execute(&request, &mut data);
+        if let Err(e) = driver.complete_scsi_request(tag, &response, &data) {
+            tracing::warn!("Failed to complete CD-ROM command {}: {}", tag, e);
+        }
+    }
+
+    tracing::info!("CD-ROM pass-through to {} stopped", source.name());
+}
+
+/// Parse an allocation mode from QML, logging an unknown one
+fn parse_allocation(name: &QString) -> Option<Allocation> {
+    let name = name.to_string();
+    let allocation = Allocation::parse(&name);
+    if allocation.is_none() {
+        tracing::error!("Unknown disk allocation mode: {}", name);
+    }
+    allocation
+}
+
+/// Create a disk image, logging the geometry and a short-space warning
+fn create_disk_image(
+    path: &str,
+    size_mb: u32,
+    revision: u8,
+    allocation: Allocation,
+    progress: &ProgressReporter,
+) -> anyhow::Result<()> {
+    let expanded_path = expand_path(path);
+    let (cylinders, heads, sectors_per_track) = disk_image::calculate_geometry(size_mb);
     let total_sectors = cylinders as u64 * heads as u64 * sectors_per_track as u64;
     let total_bytes = total_sectors * SECTOR_SIZE as u64;
-    
     tracing::debug!(
         "Disk geometry: {} cylinders, {} heads, {} sectors/track = {} sectors ({} bytes)",
         cylinders, heads, sectors_per_track, total_sectors, total_bytes
     );
-    
-    let mut file = File::create(&expanded_path)?;
-    
-    // Create the MBR (sector 0)
-    let mut mbr = [0u8; 512];
-    
-    // Add SunPCi magic at offset 12
-    mbr[12..16].copy_from_slice(&SUNPCI_MAGIC.to_le_bytes());
-    
-    // Add revision info at offset 16
-    mbr[16] = revision;  // Major version
-    mbr[17] = 0;         // Minor version
-    
-    // Store geometry in header (offsets 18-23)
-    mbr[18..20].copy_from_slice(&cylinders.to_le_bytes());
-    mbr[20] = heads;
-    mbr[21] = sectors_per_track;
-    mbr[22..26].copy_from_slice(&(total_sectors as u32).to_le_bytes());
-    
-    // Create partition table entry at offset 0x1BE (446)
-    // Partition 1: Primary, active, FAT16
-    let partition_start: u32 = sectors_per_track as u32;  // Start after first track
-    let partition_sectors: u32 = total_sectors as u32 - partition_start;
-    
-    // Partition entry 1
-    let part_entry = &mut mbr[0x1BE..0x1CE];
-    part_entry[0] = 0x80;  // Active/bootable
-    
-    // CHS start (head 0, sector 1, cylinder 0) - after MBR
-    part_entry[1] = 1;     // Start head
-    part_entry[2] = 1;     // Start sector (bits 0-5) | cylinder high (bits 6-7)
-    part_entry[3] = 0;     // Start cylinder low
-    
-    // Partition type: FAT16 for larger disks, FAT12 for small
-    part_entry[4] = if size_mb > 32 { 0x06 } else { 0x01 };  // 0x06 = FAT16, 0x01 = FAT12
-    
-    // CHS end
-    let end_cyl = (cylinders - 1).min(1023);
-    let end_head = heads - 1;
-    let end_sector = sectors_per_track;
-    part_entry[5] = end_head;
-    part_entry[6] = (end_sector & 0x3F) | (((end_cyl >> 8) & 0x03) << 6) as u8;
-    part_entry[7] = (end_cyl & 0xFF) as u8;
-    
-    // LBA start and size
-    part_entry[8..12].copy_from_slice(&partition_start.to_le_bytes());
-    part_entry[12..16].copy_from_slice(&partition_sectors.to_le_bytes());
-    
-    // MBR signature
-    mbr[510] = 0x55;
-    mbr[511] = 0xAA;
-    
-    // Write MBR
-    file.write_all(&mbr)?;
-    
-    // Write FAT boot sector at partition start
-    let mut boot_sector = [0u8; 512];
-    
-    // Jump instruction
-    boot_sector[0] = 0xEB;
-    boot_sector[1] = 0x3C;
-    boot_sector[2] = 0x90;
-    
-    // OEM name
-    boot_sector[3..11].copy_from_slice(b"SUNPCI  ");
-    
-    // BIOS Parameter Block (BPB)
-    boot_sector[11..13].copy_from_slice(&512u16.to_le_bytes());  // Bytes per sector
-    boot_sector[13] = if size_mb > 256 { 8 } else { 4 };         // Sectors per cluster
-    boot_sector[14..16].copy_from_slice(&1u16.to_le_bytes());    // Reserved sectors
-    boot_sector[16] = 2;                                          // Number of FATs
-    boot_sector[17..19].copy_from_slice(&512u16.to_le_bytes());  // Root entries
-    
-    // Total sectors (16-bit if <= 65535, else in 32-bit field)
-    if partition_sectors <= 65535 {
-        boot_sector[19..21].copy_from_slice(&(partition_sectors as u16).to_le_bytes());
-    } else {
-        boot_sector[19..21].copy_from_slice(&0u16.to_le_bytes());
-        boot_sector[32..36].copy_from_slice(&partition_sectors.to_le_bytes());
-    }
-    
-    boot_sector[21] = 0xF8;  // Media descriptor (fixed disk)
-    
-    // Sectors per FAT (estimate)
-    let sectors_per_fat = ((partition_sectors / boot_sector[13] as u32) * 2 / 512 + 1) as u16;
-    boot_sector[22..24].copy_from_slice(&sectors_per_fat.to_le_bytes());
-    
-    boot_sector[24..26].copy_from_slice(&(sectors_per_track as u16).to_le_bytes());
-    boot_sector[26..28].copy_from_slice(&(heads as u16).to_le_bytes());
-    boot_sector[28..32].copy_from_slice(&partition_start.to_le_bytes());  // Hidden sectors
-    
-    // Extended boot record
-    boot_sector[36] = 0x80;  // Drive number
-    boot_sector[38] = 0x29;  // Extended boot signature
-    boot_sector[39..43].copy_from_slice(&0x12345678u32.to_le_bytes());  // Volume serial
-    boot_sector[43..54].copy_from_slice(b"NO NAME    ");  // Volume label
-    boot_sector[54..62].copy_from_slice(b"FAT16   ");     // FS type
-    
-    // Boot signature
-    boot_sector[510] = 0x55;
-    boot_sector[511] = 0xAA;
-    
-    // Seek to partition start and write boot sector
-    file.seek(SeekFrom::Start(partition_start as u64 * SECTOR_SIZE as u64))?;
-    file.write_all(&boot_sector)?;
-    
-    // Initialize first FAT
-    let mut fat = vec![0u8; sectors_per_fat as usize * SECTOR_SIZE as usize];
-    fat[0] = 0xF8;  // Media descriptor
-    fat[1] = 0xFF;
-    fat[2] = 0xFF;
-    fat[3] = 0xFF;
-    
-    // Write FAT1
-    file.write_all(&fat)?;
-    
-    // Write FAT2
-    file.write_all(&fat)?;
-    
-    // Write empty root directory (512 entries * 32 bytes = 16384 bytes = 32 sectors)
-    let root_dir = vec![0u8; 512 * 32];
-    file.write_all(&root_dir)?;
-    
-    // Extend file to full size
-    file.seek(SeekFrom::Start(total_bytes - 1))?;
-    file.write_all(&[0])?;
-    
+
+    // A preallocated image is refused outright by the space check
+    if let Ok(available) = diskspace::available_space(&expanded_path)
+        && allocation.is_sparse()
+        && available < total_bytes
+    {
+        tracing::warn!(
+            "Only {} MB free for a {} MB disk image; the guest will run out of space before the disk is full",
+            available / (1024 * 1024),
+            size_mb
+        );
+    }
+
+    disk_image::create_disk_image(&expanded_path, size_mb, revision, allocation, progress)?;
     tracing::info!("Created disk image: {} ({} MB)", expanded_path.display(), size_mb);
     Ok(())
 }
 
-/// Disk information parsed from header
-struct DiskInfo {
-    /// Whether this appears to be a SunPCi disk image
-    is_sunpci: bool,
-    /// Size in megabytes
-    size_mb: u32,
-    /// SunPCi format revision
-    revision: u8,
-    /// CHS cylinders
-    cylinders: u16,
-    /// CHS heads
-    heads: u8,
-    /// CHS sectors per track
-    sectors_per_track: u8,
-    /// Total sectors
-    total_sectors: u64,
-    /// Whether partition is bootable
-    bootable: bool,
-    /// Partition type description
-    partition_type: String,
-}
-
 /// Expand ~ to home directory in paths
 fn expand_path(path: &str) -> std::path::PathBuf {
     if path.starts_with("~/") {
@@ -728,79 +1665,3 @@ fn expand_path(path: &str) -> std::path::PathBuf {
     }
     Path::new(path).to_path_buf()
 }
-
-/// Read and parse a disk image header
-fn read_disk_header(path: &str) -> std::io::Result<DiskInfo> {
-    let expanded_path = expand_path(path);
-    
-    let mut file = File::open(&expanded_path)?;
-    let file_size = file.metadata()?.len();
-    
-    // Read MBR (first 512 bytes)
-    let mut mbr = [0u8; 512];
-    file.read_exact(&mut mbr)?;
-    
-    // Check for MBR signature
-    if mbr[510] != 0x55 || mbr[511] != 0xAA {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Invalid MBR signature"
-        ));
-    }
-    
-    // Check for SunPCi magic at offset 12
-    let magic = u32::from_le_bytes([mbr[12], mbr[13], mbr[14], mbr[15]]);
-    let is_sunpci = magic == SUNPCI_MAGIC;
-    
-    // Read SunPCi-specific fields if present
-    let (revision, cylinders, heads, sectors_per_track, stored_sectors) = if is_sunpci {
-        let rev = mbr[16];
-        let cyls = u16::from_le_bytes([mbr[18], mbr[19]]);
-        let heads = mbr[20];
-        let spt = mbr[21];
-        let sectors = u32::from_le_bytes([mbr[22], mbr[23], mbr[24], mbr[25]]);
-        (rev, cyls, heads, spt, sectors as u64)
-    } else {
-        // Calculate geometry from file size
-        let size_mb = (file_size / (1024 * 1024)) as u32;
-        let (cyls, heads, spt) = calculate_geometry(size_mb);
-        let sectors = file_size / SECTOR_SIZE as u64;
-        (0, cyls, heads, spt, sectors)
-    };
-    
-    // Parse partition table entry 1 (offset 0x1BE)
-    let part_entry = &mbr[0x1BE..0x1CE];
-    let bootable = part_entry[0] == 0x80;
-    let partition_type_byte = part_entry[4];
-    
-    let partition_type = match partition_type_byte {
-        0x00 => "Empty",
-        0x01 => "FAT12",
-        0x04 => "FAT16 (<32MB)",
-        0x05 => "Extended",
-        0x06 => "FAT16",
-        0x07 => "NTFS/HPFS",
-        0x0B => "FAT32",
-        0x0C => "FAT32 (LBA)",
-        0x0E => "FAT16 (LBA)",
-        0x0F => "Extended (LBA)",
-        0x82 => "Linux Swap",
-        0x83 => "Linux",
-        _ => "Unknown",
-    }.to_string();
-    
-    let size_mb = (file_size / (1024 * 1024)) as u32;
-    let total_sectors = if stored_sectors > 0 { stored_sectors } else { file_size / SECTOR_SIZE as u64 };
-    
-    Ok(DiskInfo {
-        is_sunpci,
-        size_mb,
-        revision,
-        cylinders,
-        heads,
-        sectors_per_track,
-        total_sectors,
-        bootable,
-        partition_type,
-    })
-}
@@ -1,13 +1,36 @@
 //! Display view Qt component for rendering the SunPCi framebuffer.
 //!
 //! This provides a QObject that manages framebuffer mmap and updates.
-//! The actual rendering is done via QML Image + ImageProvider.
+//! The actual rendering is done by FramebufferItem (see `framebuffer_item`).
+//!
+//! It also paces the refresh timer: `pace_frame` samples the framebuffer
+//! before each refresh and sets `refresh_interval` from guest drawing
+//! activity (see `rising_sun_common::frame_pacing`); with vsync
+//! presentation, `display_idle` pauses the per-frame uploads instead.
+//! `compute_target_rect` places the display in the window according to
+//! the scaling settings; the display item binds to the `target_*` properties, and its CRT shader
+//! to the `crt_*` ones (see `rising_sun_common::crt`).
 
 use std::cell::RefCell;
-use std::ptr;
+use std::time::{Duration, Instant};
+
+use rising_sun_common::crt::CrtParams;
+use rising_sun_common::frame_pacing::FramePacer;
+use rising_sun_common::ioctl::region_id;
+use rising_sun_common::scaling::target_rect;
+use rising_sun_common::{load_config, DisplayConfig, ScalingMode};
+
+use super::framebuffer_provider::frame_checksum;
+use super::mapped_region::MappedRegion;
+use super::startup;
 
 #[cxx_qt::bridge]
 mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
     unsafe extern "RustQt" {
         #[qobject]
         #[qml_element]
@@ -22,6 +45,23 @@ mod qobject {
         #[qproperty(bool, maintain_aspect)]
         #[qproperty(bool, integer_scaling)]
         #[qproperty(bool, framebuffer_ready)]
+        #[qproperty(bool, adaptive_refresh)]
+        #[qproperty(i32, active_refresh_hz)]
+        #[qproperty(i32, idle_refresh_hz)]
+        #[qproperty(i32, idle_after_ms)]
+        #[qproperty(i32, refresh_interval)]
+        #[qproperty(bool, display_idle)]
+        #[qproperty(QString, scaling_mode)]
+        #[qproperty(i32, scale_factor)]
+        #[qproperty(i32, target_x)]
+        #[qproperty(i32, target_y)]
+        #[qproperty(i32, target_width)]
+        #[qproperty(i32, target_height)]
+        #[qproperty(i32, crt_effect)]
+        #[qproperty(f32, scanline_strength)]
+        #[qproperty(f32, mask_intensity)]
+        #[qproperty(f32, curvature)]
+        #[qproperty(QString, crt_shader)]
         type DisplayView = super::DisplayViewRust;
 
         /// Initialize the mmap for the framebuffer
@@ -35,29 +75,29 @@ mod qobject {
         /// Check if framebuffer is mapped
         #[qinvokable]
         fn is_mapped(self: &DisplayView) -> bool;
-    }
-}
 
-use std::pin::Pin;
+        /// Load the refresh pacing settings from the configuration
+        #[qinvokable]
+        fn load_refresh_config(self: Pin<&mut DisplayView>);
 
-/// Framebuffer mapping information
-struct FramebufferMapping {
-    /// Pointer to mapped memory
-    ptr: *mut u8,
-    /// Size of mapping
-    size: usize,
-}
+        /// Sample the framebuffer and update refresh_interval and display_idle
+        #[qinvokable]
+        fn pace_frame(self: Pin<&mut DisplayView>);
 
-impl Drop for FramebufferMapping {
-    fn drop(&mut self) {
-        if !self.ptr.is_null() && self.size > 0 {
-            unsafe {
-                libc::munmap(self.ptr as *mut libc::c_void, self.size);
-            }
-        }
+        /// Place the display in a window of this size, updating the
+        /// target_* properties
+        #[qinvokable]
+        fn compute_target_rect(self: Pin<&mut DisplayView>, window_w: i32, window_h: i32);
+
+        /// Load the CRT effect settings from the configuration
+        #[qinvokable]
+        fn load_crt_config(self: Pin<&mut DisplayView>);
     }
 }
 
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
 /// Rust implementation of the DisplayView
 pub struct DisplayViewRust {
     /// Source framebuffer width
@@ -83,11 +123,54 @@ pub struct DisplayViewRust {
     /// Whether framebuffer is ready
     framebuffer_ready: bool,
     /// Framebuffer mapping
-    mapping: RefCell<Option<FramebufferMapping>>,
+    mapping: RefCell<Option<MappedRegion>>,
+    /// Drop to the idle refresh rate while the screen is unchanged
+    adaptive_refresh: bool,
+    /// Refresh rate while the guest is drawing
+    active_refresh_hz: i32,
+    /// Refresh rate while the screen is unchanged
+    idle_refresh_hz: i32,
+    /// How long the screen stays unchanged before the idle rate applies
+    idle_after_ms: i32,
+    /// Milliseconds until the next refresh
+    refresh_interval: i32,
+    /// Whether the screen has been unchanged for idle_after_ms
+    display_idle: bool,
+    /// Guest drawing activity
+    pacer: RefCell<FramePacer>,
+    /// Scaling mode ("none", "fit", "stretch" or "fixed")
+    scaling_mode: QString,
+    /// Scale factor of the fixed scaling mode
+    scale_factor: i32,
+    /// Display position and size in the window
+    target_x: i32,
+    target_y: i32,
+    target_width: i32,
+    target_height: i32,
+    /// CRT effect number, 0 for none (see `rising_sun_common::crt`)
+    crt_effect: i32,
+    /// Scanline darkness at the current scale
+    scanline_strength: f32,
+    /// Aperture grille stripe strength
+    mask_intensity: f32,
+    /// Barrel distortion of the curvature effect
+    curvature: f32,
+    /// URL of the CRT fragment shader
+    crt_shader: QString,
+    /// CRT settings as loaded
+    crt: RefCell<CrtParams>,
 }
 
 impl Default for DisplayViewRust {
     fn default() -> Self {
+        let _timing = startup::span("DisplayView");
+        let defaults = DisplayConfig::default();
+        let pacer = FramePacer::new(
+            defaults.active_refresh_hz,
+            defaults.idle_refresh_hz,
+            Duration::from_millis(defaults.idle_after_ms.into()),
+        );
+        let crt = CrtParams::from_config(&defaults);
         Self {
             source_width: 640,
             source_height: 480,
@@ -101,6 +184,25 @@ impl Default for DisplayViewRust {
             integer_scaling: false,
             framebuffer_ready: false,
             mapping: RefCell::new(None),
+            adaptive_refresh: defaults.adaptive_refresh,
+            active_refresh_hz: defaults.active_refresh_hz as i32,
+            idle_refresh_hz: defaults.idle_refresh_hz as i32,
+            idle_after_ms: defaults.idle_after_ms as i32,
+            refresh_interval: pacer.active_interval.as_millis() as i32,
+            display_idle: false,
+            pacer: RefCell::new(pacer),
+            scaling_mode: QString::from("fit"),
+            scale_factor: 2,
+            target_x: 0,
+            target_y: 0,
+            target_width: 640,
+            target_height: 480,
+            crt_effect: crt.effect,
+            scanline_strength: 0.0,
+            mask_intensity: crt.mask_intensity,
+            curvature: crt.curvature,
+            crt_shader: QString::from(env!("RISING_SUN_CRT_SHADER")),
+            crt: RefCell::new(crt),
         }
     }
 }
@@ -118,27 +220,16 @@ impl qobject::DisplayView {
         // Release any existing mapping
         *self.mapping.borrow_mut() = None;
 
-        // mmap the framebuffer
-        // The kernel driver exposes the framebuffer at offset 0 for mmap
-        let ptr = unsafe {
-            libc::mmap(
-                ptr::null_mut(),
-                size,
-                libc::PROT_READ,
-                libc::MAP_SHARED,
-                fd,
-                0, // offset 0 = framebuffer
-            )
+        // The framebuffer's mmap offset comes from the driver's region table
+        let mapping = match MappedRegion::map_region(fd, region_id::FRAMEBUFFER, size) {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                tracing::warn!("Failed to map framebuffer: {}", e);
+                return false;
+            }
         };
 
-        if ptr == libc::MAP_FAILED {
-            return false;
-        }
-
-        *self.mapping.borrow_mut() = Some(FramebufferMapping {
-            ptr: ptr as *mut u8,
-            size,
-        });
+        *self.mapping.borrow_mut() = Some(mapping);
 
         self.set_framebuffer_ready(true);
         true
@@ -154,4 +245,87 @@ impl qobject::DisplayView {
     pub fn is_mapped(&self) -> bool {
         self.mapping.borrow().is_some()
     }
+
+    /// Load the refresh pacing settings from the configuration
+    pub fn load_refresh_config(mut self: Pin<&mut Self>) {
+        let display = load_config().unwrap_or_default().display;
+        self.as_mut().set_adaptive_refresh(display.adaptive_refresh);
+        self.as_mut().set_active_refresh_hz(display.active_refresh_hz as i32);
+        self.as_mut().set_idle_refresh_hz(display.idle_refresh_hz as i32);
+        self.as_mut().set_idle_after_ms(display.idle_after_ms as i32);
+        self.pacer.borrow_mut().reset();
+    }
+
+    /// Sample the framebuffer and pick the interval until the next refresh
+    ///
+    /// Without adaptive refresh the interval stays at the active rate.
+    pub fn pace_frame(mut self: Pin<&mut Self>) {
+        let active_hz = (*self.as_ref().active_refresh_hz()).max(1) as u32;
+        let idle_hz = (*self.as_ref().idle_refresh_hz()).max(1) as u32;
+        let idle_after = Duration::from_millis((*self.as_ref().idle_after_ms()).max(0) as u64);
+        let adaptive = *self.as_ref().adaptive_refresh();
+
+        let (interval, idle) = {
+            let mut pacer = self.pacer.borrow_mut();
+            pacer.set_rates(active_hz, idle_hz);
+            pacer.idle_after = idle_after;
+            if adaptive {
+                let now = Instant::now();
+                let interval = pacer.sample(now, frame_checksum());
+                (interval, pacer.is_idle(now))
+            } else {
+                (pacer.active_interval, false)
+            }
+        };
+
+        self.as_mut().set_refresh_interval(interval.as_millis() as i32);
+        self.as_mut().set_display_idle(idle);
+    }
+
+    /// Place the display in a window of this size
+    pub fn compute_target_rect(mut self: Pin<&mut Self>, window_w: i32, window_h: i32) {
+        let scaling_mode = match self.as_ref().scaling_mode().to_string().as_str() {
+            "none" => ScalingMode::None,
+            "stretch" => ScalingMode::Stretch,
+            "fixed" => ScalingMode::Fixed((*self.as_ref().scale_factor()).max(1) as u32),
+            _ => ScalingMode::Fit,
+        };
+        let display = DisplayConfig {
+            scaling_mode,
+            maintain_aspect_ratio: *self.as_ref().maintain_aspect(),
+            integer_scaling: *self.as_ref().integer_scaling(),
+            ..Default::default()
+        };
+        let rect = target_rect(
+            &display,
+            (*self.as_ref().source_width()).max(0) as u32,
+            (*self.as_ref().source_height()).max(0) as u32,
+            window_w.max(0) as u32,
+            window_h.max(0) as u32,
+        );
+        self.as_mut().set_target_x(rect.x);
+        self.as_mut().set_target_y(rect.y);
+        self.as_mut().set_target_width(rect.width);
+        self.as_mut().set_target_height(rect.height);
+        self.update_scanline_strength();
+    }
+
+    /// Load the CRT effect settings from the configuration
+    pub fn load_crt_config(mut self: Pin<&mut Self>) {
+        let crt = CrtParams::from_config(&load_config().unwrap_or_default().display);
+        *self.crt.borrow_mut() = crt;
+        self.as_mut().set_crt_effect(crt.effect);
+        self.as_mut().set_mask_intensity(crt.mask_intensity);
+        self.as_mut().set_curvature(crt.curvature);
+        self.update_scanline_strength();
+    }
+
+    /// Fade the scanlines with the vertical scale of the display
+    fn update_scanline_strength(self: Pin<&mut Self>) {
+        let strength = self.crt.borrow().scanline_strength(
+            (*self.as_ref().source_height()).max(0) as u32,
+            (*self.as_ref().target_height()).max(0) as u32,
+        );
+        self.set_scanline_strength(strength);
+    }
 }
@@ -2,9 +2,35 @@
 //!
 //! Maps host directories to guest drive letters (E: through Z:).
 //! Uses the kernel driver's FSD (Filesystem Redirection) subsystem.
+//!
+//! Each mapping has a timestamp policy; the driver converts file times
+//! with the UTC offset it gives (see `rising_sun_common::fat_time`), and
+//! name options saying how long host names are shortened to 8.3 names
+//! (see `rising_sun_common::short_name`).
+//!
+//! Host paths are watched (see `rising_sun_common::path_watch`), so a
+//! mapping is marked unavailable while its directory is missing, such as
+//! an unplugged USB stick, and can be given to the driver again when it
+//! comes back.
+//!
+//! A mapping can have a size limit, so the guest cannot fill the host disk
+//! through it. The driver counts what the guest writes from a starting
+//! figure measured here (see `rising_sun_common::drive_usage`) on a
+//! background thread, which `refresh_usage` starts and `poll_paths` collects.
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rising_sun_common::{Mangling, NameCase, SunPciError, TimestampPolicy};
+use rising_sun_common::drive_usage;
+use rising_sun_common::fat_time;
+use rising_sun_common::short_name;
+use rising_sun_common::path_watch::PathWatcher;
+use rising_sun_common::progress::ProgressReporter;
+use rising_sun_common::ui_json::{self, MappingJson};
 
 use rising_sun_common::ioctl::{DriveMapping as IoctlDriveMapping, DriveLetter, drive_flags};
 use rising_sun_common::ioctl::{sunpci_add_drive_map, sunpci_remove_drive_map, SUNPCI_MAX_PATH};
@@ -16,6 +42,7 @@ mod qobject {
         #[qml_element]
         #[qproperty(i32, driver_fd)]
         #[qproperty(i32, mapping_count)]
+        #[qproperty(bool, auto_reapply)]
         type DriveMappingController = super::DriveMappingControllerRust;
 
         /// Initialize with driver file descriptor
@@ -26,6 +53,49 @@ mod qobject {
         #[qinvokable]
         fn add_mapping(self: Pin<&mut DriveMappingController>, drive_letter: QString, host_path: QString, readonly: bool) -> bool;
 
+        /// Set the time zone a mapping shows file times in: "utc",
+        /// "local" or an offset such as "+05:30"
+        #[qinvokable]
+        fn set_mapping_timestamps(self: Pin<&mut DriveMappingController>, drive_letter: QString, policy: QString) -> bool;
+
+        /// Re-apply mappings whose UTC offset changed, e.g. when
+        /// daylight saving time starts or ends
+        #[qinvokable]
+        fn refresh_timestamps(self: Pin<&mut DriveMappingController>) -> bool;
+
+        /// Set how a mapping shortens long host names: mangling "tail",
+        /// "hash" or "truncate", case "upper", "lower" or "preserve", and
+        /// whether names starting with '.' are left out
+        #[qinvokable]
+        fn set_mapping_names(
+            self: Pin<&mut DriveMappingController>,
+            drive_letter: QString,
+            mangling: QString,
+            name_case: QString,
+            hide_dotfiles: bool,
+        ) -> bool;
+
+        /// The 8.3 name the guest sees for a host name, with the given
+        /// mangling and case
+        #[qinvokable]
+        fn preview_short_name(self: &DriveMappingController, name: QString, mangling: QString, name_case: QString) -> QString;
+
+        /// Set the most the guest may store on a mapping, in MB (0 = no limit)
+        #[qinvokable]
+        fn set_mapping_max_size(self: Pin<&mut DriveMappingController>, drive_letter: QString, max_size_mb: i32) -> bool;
+
+        /// Measure the space used under the mappings' host paths in the
+        /// background, only those with a size limit if `limited_only`;
+        /// false if a measurement is running already
+        #[qinvokable]
+        fn refresh_usage(self: &DriveMappingController, limited_only: bool) -> bool;
+
+        /// Space used under each mapping's host path as a JSON array of
+        /// {driveLetter, hostPath, usedBytes, maxSizeMb}; usedBytes is null
+        /// until measured
+        #[qinvokable]
+        fn get_usage_json(self: &DriveMappingController) -> QString;
+
         /// Remove a drive mapping
         #[qinvokable]
         fn remove_mapping(self: Pin<&mut DriveMappingController>, drive_letter: QString) -> bool;
@@ -57,6 +127,29 @@ mod qobject {
         /// Get list of available (unmapped) drive letters
         #[qinvokable]
         fn get_available_letters(self: &DriveMappingController) -> QString;
+
+        /// Drive letter ("F:") mapped to a host path, empty if none
+        #[qinvokable]
+        fn find_mapping_for_path(self: &DriveMappingController, host_path: QString) -> QString;
+
+        /// Check for host paths that appeared or disappeared, re-applying
+        /// mappings that came back if auto_reapply is set
+        #[qinvokable]
+        fn poll_paths(self: Pin<&mut DriveMappingController>);
+
+        /// Signal emitted when the space used under a mapping's host path
+        /// has been measured
+        #[qsignal]
+        fn usage_measured(self: Pin<&mut DriveMappingController>, drive_letter: QString);
+
+        /// Signal emitted when a mapping's host path appears or disappears
+        #[qsignal]
+        fn mapping_availability_changed(
+            self: Pin<&mut DriveMappingController>,
+            drive_letter: QString,
+            host_path: QString,
+            available: bool,
+        );
     }
 
     unsafe extern "C++Qt" {
@@ -75,6 +168,36 @@ pub struct DriveMapping {
     pub host_path: String,
     pub readonly: bool,
     pub enabled: bool,
+    pub timestamps: TimestampPolicy,
+    pub mangling: Mangling,
+    pub name_case: NameCase,
+    pub hide_dotfiles: bool,
+    /// Most the guest may store, in MB (0 = no limit)
+    pub max_size_mb: u32,
+    /// Bytes stored under the host path when last measured
+    pub used: Option<u64>,
+    /// UTC offset last given to the driver, in minutes
+    pub applied_offset: Option<i32>,
+    /// Whether the host path was an existing directory when last checked
+    pub available: bool,
+}
+
+impl From<&DriveMapping> for MappingJson {
+    fn from(m: &DriveMapping) -> Self {
+        Self {
+            drive_letter: format!("{}:", m.letter),
+            host_path: m.host_path.clone(),
+            description: String::new(),
+            readonly: m.readonly,
+            enabled: m.enabled,
+            timestamps: m.timestamps,
+            mangling: m.mangling,
+            name_case: m.name_case,
+            hide_dotfiles: m.hide_dotfiles,
+            max_size_mb: m.max_size_mb,
+            available: m.available,
+        }
+    }
 }
 
 /// Rust implementation of the DriveMappingController
@@ -82,16 +205,28 @@ pub struct DriveMapping {
 pub struct DriveMappingControllerRust {
     driver_fd: i32,
     mapping_count: i32,
+    /// Re-apply mappings whose host path comes back
+    auto_reapply: bool,
     /// Current drive mappings
     mappings: RefCell<HashMap<char, DriveMapping>>,
+    /// Watches the mappings' host paths; None if inotify is unavailable
+    watcher: RefCell<Option<PathWatcher>>,
+    /// Results of the usage measurement running, if any
+    measuring: RefCell<Option<Receiver<(char, Option<u64>)>>>,
 }
 
 impl Default for DriveMappingControllerRust {
     fn default() -> Self {
+        let watcher = PathWatcher::new()
+            .inspect_err(|e| tracing::warn!("Cannot watch drive mapping paths: {}", e))
+            .ok();
         Self {
             driver_fd: -1,
             mapping_count: 0,
+            auto_reapply: false,
             mappings: RefCell::new(HashMap::new()),
+            watcher: RefCell::new(watcher),
+            measuring: RefCell::new(None),
         }
     }
 }
@@ -137,20 +272,24 @@ impl qobject::DriveMappingController {
             path
         };
 
-        // Verify path exists
-        if !std::path::Path::new(&expanded_path).exists() {
-            tracing::warn!("Host path does not exist: {}", expanded_path);
-            // Still allow adding - might be created later
-        }
-
+        // A missing path is still added; it is watched for (see watch_paths)
         let mapping = DriveMapping {
             letter,
             host_path: expanded_path,
             readonly,
             enabled: true,
+            timestamps: TimestampPolicy::default(),
+            mangling: Mangling::default(),
+            name_case: NameCase::default(),
+            hide_dotfiles: false,
+            max_size_mb: 0,
+            used: None,
+            applied_offset: None,
+            available: false,
         };
 
         self.mappings.borrow_mut().insert(letter, mapping);
+        self.watch_paths();
         let count = self.mappings.borrow().len() as i32;
         self.as_mut().set_mapping_count(count);
 
@@ -173,6 +312,7 @@ impl qobject::DriveMappingController {
         let removed = self.mappings.borrow_mut().remove(&letter).is_some();
         
         if removed {
+            self.watch_paths();
             let count = self.mappings.borrow().len() as i32;
             self.as_mut().set_mapping_count(count);
 
@@ -196,6 +336,126 @@ impl qobject::DriveMappingController {
         removed
     }
 
+    /// Set the time zone a mapping shows file times in
+    pub fn set_mapping_timestamps(self: Pin<&mut Self>, drive_letter: QString, policy: QString) -> bool {
+        let Some(letter) = parse_drive_letter(&drive_letter.to_string()) else {
+            tracing::warn!("Invalid drive letter: {}", drive_letter);
+            return false;
+        };
+        let Some(timestamps) = fat_time::parse_policy(&policy.to_string()) else {
+            tracing::warn!("Invalid timestamp policy: {}", policy);
+            return false;
+        };
+        match self.mappings.borrow_mut().get_mut(&letter) {
+            Some(mapping) => {
+                mapping.timestamps = timestamps;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set how a mapping shortens long host names
+    pub fn set_mapping_names(
+        self: Pin<&mut Self>,
+        drive_letter: QString,
+        mangling: QString,
+        name_case: QString,
+        hide_dotfiles: bool,
+    ) -> bool {
+        let Some(letter) = parse_drive_letter(&drive_letter.to_string()) else {
+            tracing::warn!("Invalid drive letter: {}", drive_letter);
+            return false;
+        };
+        let (Some(mangling), Some(name_case)) = (
+            short_name::parse_mangling(&mangling.to_string()),
+            short_name::parse_case(&name_case.to_string()),
+        ) else {
+            tracing::warn!("Invalid name options: {} {}", mangling, name_case);
+            return false;
+        };
+        match self.mappings.borrow_mut().get_mut(&letter) {
+            Some(mapping) => {
+                mapping.mangling = mangling;
+                mapping.name_case = name_case;
+                mapping.hide_dotfiles = hide_dotfiles;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The 8.3 name the guest sees for a host name
+    pub fn preview_short_name(&self, name: QString, mangling: QString, name_case: QString) -> QString {
+        let mangling = short_name::parse_mangling(&mangling.to_string()).unwrap_or_default();
+        let name_case = short_name::parse_case(&name_case.to_string()).unwrap_or_default();
+        QString::from(&short_name::short_name(&name.to_string(), mangling, name_case, 1))
+    }
+
+    /// Set the most the guest may store on a mapping
+    pub fn set_mapping_max_size(self: Pin<&mut Self>, drive_letter: QString, max_size_mb: i32) -> bool {
+        let Some(letter) = parse_drive_letter(&drive_letter.to_string()) else {
+            tracing::warn!("Invalid drive letter: {}", drive_letter);
+            return false;
+        };
+        match self.mappings.borrow_mut().get_mut(&letter) {
+            Some(mapping) => {
+                mapping.max_size_mb = max_size_mb.max(0) as u32;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Measure the space used under the mappings' host paths
+    pub fn refresh_usage(&self, limited_only: bool) -> bool {
+        if self.measuring.borrow().is_some() {
+            return false;
+        }
+        let dirs: Vec<(char, PathBuf)> = self
+            .mappings
+            .borrow()
+            .values()
+            .filter(|m| m.enabled && m.available && (!limited_only || m.max_size_mb > 0))
+            .map(|m| (m.letter, host_dir(&m.host_path)))
+            .collect();
+        if dirs.is_empty() {
+            return true;
+        }
+
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let progress = ProgressReporter::new();
+            for (letter, dir) in dirs {
+                let used = drive_usage::measure(&dir, &progress)
+                    .inspect_err(|e| tracing::warn!("Cannot measure {}: {}", dir.display(), e))
+                    .ok();
+                if tx.send((letter, used)).is_err() {
+                    break;
+                }
+            }
+        });
+        *self.measuring.borrow_mut() = Some(rx);
+        true
+    }
+
+    /// Space used under each mapping's host path as JSON
+    pub fn get_usage_json(&self) -> QString {
+        let mappings = self.mappings.borrow();
+        let usage: Vec<serde_json::Value> = mappings
+            .values()
+            .map(|m| {
+                serde_json::json!({
+                    "driveLetter": format!("{}:", m.letter),
+                    "hostPath": m.host_path,
+                    "usedBytes": m.used,
+                    "maxSizeMb": m.max_size_mb,
+                })
+            })
+            .collect();
+        QString::from(&serde_json::Value::Array(usage).to_string())
+    }
+
     /// Apply all drive mappings to the driver
     pub fn apply_mappings(self: Pin<&mut Self>) -> bool {
         if self.driver_fd < 0 {
@@ -203,31 +463,20 @@ impl qobject::DriveMappingController {
             return false;
         }
 
-        let mappings = self.mappings.borrow();
+        let now = unix_now();
+        let mut mappings = self.mappings.borrow_mut();
         let mut success = true;
 
-        for mapping in mappings.values() {
+        for mapping in mappings.values_mut() {
             if !mapping.enabled {
                 continue;
             }
 
-            let mut ioctl_mapping = IoctlDriveMapping::default();
-            ioctl_mapping.letter = mapping.letter as u8;
-            ioctl_mapping.flags = if mapping.readonly { drive_flags::READONLY } else { 0 };
-            
-            // Copy path
-            let path_bytes = mapping.host_path.as_bytes();
-            let len = path_bytes.len().min(SUNPCI_MAX_PATH - 1);
-            ioctl_mapping.path[..len].copy_from_slice(&path_bytes[..len]);
-            ioctl_mapping.path[len] = 0;
-
-            let result = unsafe {
-                sunpci_add_drive_map(self.driver_fd, &ioctl_mapping)
-            };
-
-            match result {
-                Ok(_) => {
-                    tracing::info!("Applied mapping {}:", mapping.letter);
+            let offset = fat_time::offset_minutes(mapping.timestamps, now);
+            match apply_mapping(self.driver_fd, mapping, offset) {
+                Ok(()) => {
+                    mapping.applied_offset = Some(offset);
+                    tracing::info!("Applied mapping {}: (UTC offset {} min)", mapping.letter, offset);
                 }
                 Err(e) => {
                     tracing::error!("Failed to apply mapping {}:: {}", mapping.letter, e);
@@ -239,11 +488,42 @@ impl qobject::DriveMappingController {
         success
     }
 
+    /// Re-apply mappings whose UTC offset changed since they were applied
+    pub fn refresh_timestamps(self: Pin<&mut Self>) -> bool {
+        if self.driver_fd < 0 {
+            return false;
+        }
+
+        let now = unix_now();
+        let mut mappings = self.mappings.borrow_mut();
+        let mut success = true;
+
+        for mapping in mappings.values_mut() {
+            let offset = fat_time::offset_minutes(mapping.timestamps, now);
+            if !mapping.enabled || mapping.applied_offset.is_none_or(|applied| applied == offset) {
+                continue;
+            }
+            match apply_mapping(self.driver_fd, mapping, offset) {
+                Ok(()) => {
+                    mapping.applied_offset = Some(offset);
+                    tracing::info!("Mapping {}: now at UTC offset {} min", mapping.letter, offset);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to update mapping {}:: {}", mapping.letter, e);
+                    success = false;
+                }
+            }
+        }
+
+        success
+    }
+
     /// Clear all mappings from the driver
     pub fn clear_mappings(mut self: Pin<&mut Self>) -> bool {
         if self.driver_fd < 0 {
             // Just clear local state
             self.mappings.borrow_mut().clear();
+            self.watch_paths();
             self.as_mut().set_mapping_count(0);
             return true;
         }
@@ -259,6 +539,7 @@ impl qobject::DriveMappingController {
         }
 
         self.mappings.borrow_mut().clear();
+        self.watch_paths();
         self.as_mut().set_mapping_count(0);
         tracing::info!("Cleared all drive mappings");
         true
@@ -266,59 +547,49 @@ impl qobject::DriveMappingController {
 
     /// Get current mappings as JSON
     pub fn get_mappings_json(&self) -> QString {
-        let mappings = self.mappings.borrow();
-        
-        let json_array: Vec<String> = mappings.values().map(|m| {
-            format!(
-                r#"{{"driveLetter":"{}:","hostPath":"{}","readonly":{},"enabled":{}}}"#,
-                m.letter,
-                m.host_path.replace('\\', "\\\\").replace('"', "\\\""),
-                m.readonly,
-                m.enabled
-            )
-        }).collect();
-
-        QString::from(&format!("[{}]", json_array.join(",")))
+        let mappings: Vec<MappingJson> = self.mappings.borrow().values().map(MappingJson::from).collect();
+        QString::from(&ui_json::to_string(&mappings))
     }
 
-    /// Load mappings from JSON
+    /// Load mappings from JSON, as `get_mappings_json` gives them
     pub fn load_mappings_json(mut self: Pin<&mut Self>, json: QString) -> bool {
-        let json_str = json.to_string();
-        
-        // Simple JSON parsing (for array of mapping objects)
-        // Expected format: [{"driveLetter":"F:","hostPath":"/path","readonly":false,"enabled":true},...]
-        
-        self.mappings.borrow_mut().clear();
+        let entries = match ui_json::parse_mappings(&json.to_string()) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("Invalid drive mappings JSON: {}", e);
+                return false;
+            }
+        };
 
-        // Very simple parsing - look for driveLetter and hostPath patterns
-        for entry in json_str.split('}') {
-            if let (Some(letter_start), Some(path_start)) = (
-                entry.find("\"driveLetter\""),
-                entry.find("\"hostPath\""),
-            ) {
-                // Extract drive letter
-                let letter_part = &entry[letter_start..];
-                if let Some(letter) = extract_json_string(letter_part, "driveLetter") {
-                    if let Some(l) = parse_drive_letter(&letter) {
-                        // Extract host path
-                        let path_part = &entry[path_start..];
-                        if let Some(path) = extract_json_string(path_part, "hostPath") {
-                            let readonly = entry.contains("\"readonly\":true");
-                            let enabled = !entry.contains("\"enabled\":false");
-
-                            let mapping = DriveMapping {
-                                letter: l,
-                                host_path: path,
-                                readonly,
-                                enabled,
-                            };
-                            self.mappings.borrow_mut().insert(l, mapping);
-                        }
-                    }
-                }
+        {
+            let mut mappings = self.mappings.borrow_mut();
+            mappings.clear();
+            for entry in entries {
+                let Some(letter) = parse_drive_letter(&entry.drive_letter) else {
+                    tracing::warn!("Invalid drive letter: {}", entry.drive_letter);
+                    continue;
+                };
+                mappings.insert(
+                    letter,
+                    DriveMapping {
+                        letter,
+                        host_path: entry.host_path,
+                        readonly: entry.readonly,
+                        enabled: entry.enabled,
+                        timestamps: entry.timestamps,
+                        mangling: entry.mangling,
+                        name_case: entry.name_case,
+                        hide_dotfiles: entry.hide_dotfiles,
+                        max_size_mb: entry.max_size_mb,
+                        used: None,
+                        applied_offset: None,
+                        available: false,
+                    },
+                );
             }
         }
 
+        self.watch_paths();
         let count = self.mappings.borrow().len() as i32;
         self.as_mut().set_mapping_count(count);
         tracing::debug!("Loaded {} mappings from JSON", count);
@@ -350,6 +621,180 @@ impl qobject::DriveMappingController {
 
         QString::from(&available.join(","))
     }
+
+    /// Drive letter mapped to a host path
+    pub fn find_mapping_for_path(&self, host_path: QString) -> QString {
+        let path = host_dir(&host_path.to_string());
+        self.mappings
+            .borrow()
+            .values()
+            .find(|m| host_dir(&m.host_path) == path)
+            .map(|m| QString::from(&format!("{}:", m.letter)))
+            .unwrap_or_default()
+    }
+
+    /// Check for host paths that appeared or disappeared
+    pub fn poll_paths(mut self: Pin<&mut Self>) {
+        self.as_mut().collect_usage();
+
+        let changed = match self.watcher.borrow_mut().as_mut().map(PathWatcher::poll) {
+            Some(Ok(changed)) => changed,
+            Some(Err(e)) => {
+                tracing::warn!("Failed to watch drive mapping paths: {}", e);
+                return;
+            }
+            None => return,
+        };
+
+        for (path, available) in changed {
+            let letters: Vec<char> = self
+                .mappings
+                .borrow()
+                .values()
+                .filter(|m| host_dir(&m.host_path) == path)
+                .map(|m| m.letter)
+                .collect();
+            for letter in letters {
+                self.as_mut().set_availability(letter, available);
+            }
+        }
+    }
+
+    /// Take the usage measured since the last call, giving the driver the
+    /// new starting figure of mappings with a size limit
+    fn collect_usage(mut self: Pin<&mut Self>) {
+        let mut measured = Vec::new();
+        let finished = match self.measuring.borrow().as_ref() {
+            Some(rx) => loop {
+                match rx.try_recv() {
+                    Ok(result) => measured.push(result),
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            },
+            None => return,
+        };
+        if finished {
+            *self.measuring.borrow_mut() = None;
+        }
+
+        for (letter, used) in measured {
+            {
+                let mut mappings = self.mappings.borrow_mut();
+                let Some(mapping) = mappings.get_mut(&letter) else {
+                    continue;
+                };
+                mapping.used = used;
+                // Only mappings applied already; the rest get it when applied
+                if mapping.max_size_mb > 0
+                    && self.driver_fd >= 0
+                    && let Some(offset) = mapping.applied_offset
+                    && let Err(e) = apply_mapping(self.driver_fd, mapping, offset)
+                {
+                    tracing::warn!("Failed to update mapping {}:: {}", letter, e);
+                }
+            }
+            self.as_mut().usage_measured(QString::from(&format!("{}:", letter)));
+        }
+    }
+
+    /// Record that a mapping's host path appeared or disappeared
+    fn set_availability(mut self: Pin<&mut Self>, letter: char, available: bool) {
+        let reapply = available && *self.as_ref().auto_reapply() && self.driver_fd >= 0;
+        let host_path = {
+            let mut mappings = self.mappings.borrow_mut();
+            let Some(mapping) = mappings.get_mut(&letter) else {
+                return;
+            };
+            mapping.available = available;
+            if available {
+                tracing::info!("Mapping {}: host path {} is available", letter, mapping.host_path);
+            } else {
+                tracing::warn!("Mapping {}: host path {} has gone", letter, mapping.host_path);
+            }
+
+            if reapply && mapping.enabled {
+                let offset = fat_time::offset_minutes(mapping.timestamps, unix_now());
+                match apply_mapping(self.driver_fd, mapping, offset) {
+                    Ok(()) => {
+                        mapping.applied_offset = Some(offset);
+                        tracing::info!("Re-applied mapping {}:", letter);
+                    }
+                    Err(e) => tracing::warn!("Failed to re-apply mapping {}:: {}", letter, e),
+                }
+            }
+            mapping.host_path.clone()
+        };
+
+        self.as_mut().mapping_availability_changed(
+            QString::from(&format!("{}:", letter)),
+            QString::from(&host_path),
+            available,
+        );
+    }
+
+    /// Watch the host paths of the current mappings
+    fn watch_paths(&self) {
+        let mut mappings = self.mappings.borrow_mut();
+        let paths: Vec<PathBuf> = mappings.values().map(|m| host_dir(&m.host_path)).collect();
+        let mut watcher = self.watcher.borrow_mut();
+        if let Some(watcher) = watcher.as_mut()
+            && let Err(e) = watcher.set_paths(paths)
+        {
+            tracing::warn!("Failed to watch drive mapping paths: {}", e);
+        }
+
+        for mapping in mappings.values_mut() {
+            let path = host_dir(&mapping.host_path);
+            mapping.available = match watcher.as_ref() {
+                Some(watcher) => watcher.is_available(&path).unwrap_or(false),
+                None => path.is_dir(),
+            };
+            if !mapping.available {
+                tracing::warn!("Mapping {}: host path {} does not exist", mapping.letter, mapping.host_path);
+            }
+        }
+    }
+}
+
+/// Give a mapping to the driver, with file times `offset` minutes east of UTC
+fn apply_mapping(fd: i32, mapping: &DriveMapping, offset: i32) -> Result<(), SunPciError> {
+    let mut ioctl_mapping = IoctlDriveMapping::default();
+    ioctl_mapping.letter = mapping.letter as u8;
+    ioctl_mapping.flags = drive_flags::TZ_OFFSET
+        | short_name::flags(mapping.mangling, mapping.name_case, mapping.hide_dotfiles);
+    if mapping.readonly {
+        ioctl_mapping.flags |= drive_flags::READONLY;
+    }
+    ioctl_mapping.tz_offset = offset as i16;
+    ioctl_mapping.max_size_mb = mapping.max_size_mb;
+    ioctl_mapping.used.set(mapping.used.unwrap_or(0));
+
+    // Copy path
+    let path_bytes = mapping.host_path.as_bytes();
+    let len = path_bytes.len().min(SUNPCI_MAX_PATH - 1);
+    ioctl_mapping.path[..len].copy_from_slice(&path_bytes[..len]);
+    ioctl_mapping.path[len] = 0;
+
+    unsafe { sunpci_add_drive_map(fd, &ioctl_mapping) }
+        .map(|_| ())
+        .map_err(SunPciError::from)
+}
+
+/// The directory a mapping's host path names, with ~ expanded
+fn host_dir(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), std::env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest.trim_start_matches('/')),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 /// Parse a drive letter string (e.g., "F:", "F", "f:") to a char
@@ -366,39 +811,6 @@ fn parse_drive_letter(s: &str) -> Option<char> {
     }
 }
 
-/// Extract a JSON string value (very simple parser)
-fn extract_json_string(s: &str, key: &str) -> Option<String> {
-    let pattern = format!("\"{}\"", key);
-    let start = s.find(&pattern)?;
-    let after_key = &s[start + pattern.len()..];
-    
-    // Find the colon and opening quote
-    let colon = after_key.find(':')?;
-    let after_colon = &after_key[colon + 1..];
-    let quote_start = after_colon.find('"')?;
-    let after_quote = &after_colon[quote_start + 1..];
-    
-    // Find the closing quote (handle escaped quotes)
-    let mut end = 0;
-    let mut escaped = false;
-    for (i, c) in after_quote.chars().enumerate() {
-        if escaped {
-            escaped = false;
-            continue;
-        }
-        if c == '\\' {
-            escaped = true;
-            continue;
-        }
-        if c == '"' {
-            end = i;
-            break;
-        }
-    }
-    
-    Some(after_quote[..end].to_string())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,11 +832,4 @@ mod tests {
         assert_eq!(parse_drive_letter("1:"), None);
         assert_eq!(parse_drive_letter(""), None);
     }
-
-    #[test]
-    fn test_extract_json_string() {
-        let json = r#"{"driveLetter":"F:","hostPath":"/opt/SUNWspci"}"#;
-        assert_eq!(extract_json_string(json, "driveLetter"), Some("F:".to_string()));
-        assert_eq!(extract_json_string(json, "hostPath"), Some("/opt/SUNWspci".to_string()));
-    }
 }
@@ -0,0 +1,310 @@
+//! Drive mapping list model Qt bridge.
+//!
+//! The drive mappings kept in the configuration, as a QAbstractListModel
+//! QML views bind to directly; rows update in place as they are edited.
+//! The model owns `drive_mappings` in the configuration file: `load` reads
+//! them and `save` writes them back without touching other sections (and
+//! ConfigManager leaves them as saved). `to_json` gives the mappings as
+//! DriveMappingController's `load_mappings_json` takes them.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++" {
+        include!(<QtCore/QAbstractListModel>);
+
+        include!("cxx-qt-lib/qbytearray.h");
+        type QByteArray = cxx_qt_lib::QByteArray;
+        include!("cxx-qt-lib/qhash.h");
+        type QHash_i32_QByteArray = cxx_qt_lib::QHash<cxx_qt_lib::QHashPair_i32_QByteArray>;
+        include!("cxx-qt-lib/qmodelindex.h");
+        type QModelIndex = cxx_qt_lib::QModelIndex;
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+        include!("cxx-qt-lib/qvariant.h");
+        type QVariant = cxx_qt_lib::QVariant;
+        include!("cxx-qt-lib/qvector.h");
+        type QVector_i32 = cxx_qt_lib::QVector<i32>;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[base = "QAbstractListModel"]
+        #[qproperty(i32, count)]
+        type DriveMappingModel = super::DriveMappingModelRust;
+
+        /// Read the mappings from the configuration file
+        #[qinvokable]
+        fn load(self: Pin<&mut DriveMappingModel>);
+
+        /// Write the mappings to the configuration file
+        #[qinvokable]
+        fn save(self: &DriveMappingModel) -> bool;
+
+        /// A row as JSON {driveLetter, hostPath, description, enabled,
+        /// readonly, timestamps, mangling, nameCase, hideDotfiles, maxSizeMb}
+        #[qinvokable]
+        fn get(self: &DriveMappingModel, row: i32) -> QString;
+
+        /// Replace a row from JSON as `get` gives it, or append one if row
+        /// is -1; the row replaced or added, -1 if the JSON is invalid
+        #[qinvokable]
+        fn set_mapping(self: Pin<&mut DriveMappingModel>, row: i32, json: QString) -> i32;
+
+        /// Enable or disable a row's mapping
+        #[qinvokable]
+        fn set_enabled(self: Pin<&mut DriveMappingModel>, row: i32, enabled: bool);
+
+        /// Remove a row
+        #[qinvokable]
+        fn remove_mapping(self: Pin<&mut DriveMappingModel>, row: i32);
+
+        /// Replace the rows with the suggested mappings
+        #[qinvokable]
+        fn restore_defaults(self: Pin<&mut DriveMappingModel>);
+
+        /// All rows as a JSON array for DriveMappingController
+        #[qinvokable]
+        fn to_json(self: &DriveMappingModel) -> QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[inherit]
+        #[cxx_name = "beginInsertRows"]
+        unsafe fn begin_insert_rows(self: Pin<&mut DriveMappingModel>, parent: &QModelIndex, first: i32, last: i32);
+        #[inherit]
+        #[cxx_name = "endInsertRows"]
+        unsafe fn end_insert_rows(self: Pin<&mut DriveMappingModel>);
+        #[inherit]
+        #[cxx_name = "beginRemoveRows"]
+        unsafe fn begin_remove_rows(self: Pin<&mut DriveMappingModel>, parent: &QModelIndex, first: i32, last: i32);
+        #[inherit]
+        #[cxx_name = "endRemoveRows"]
+        unsafe fn end_remove_rows(self: Pin<&mut DriveMappingModel>);
+        #[inherit]
+        #[cxx_name = "beginResetModel"]
+        unsafe fn begin_reset_model(self: Pin<&mut DriveMappingModel>);
+        #[inherit]
+        #[cxx_name = "endResetModel"]
+        unsafe fn end_reset_model(self: Pin<&mut DriveMappingModel>);
+        #[inherit]
+        fn index(self: &DriveMappingModel, row: i32, column: i32, parent: &QModelIndex) -> QModelIndex;
+
+        #[inherit]
+        #[qsignal]
+        #[cxx_name = "dataChanged"]
+        fn data_changed(
+            self: Pin<&mut DriveMappingModel>,
+            top_left: &QModelIndex,
+            bottom_right: &QModelIndex,
+            roles: &QVector_i32,
+        );
+    }
+
+    unsafe extern "RustQt" {
+        #[qinvokable]
+        #[cxx_override]
+        fn data(self: &DriveMappingModel, index: &QModelIndex, role: i32) -> QVariant;
+
+        #[qinvokable]
+        #[cxx_override]
+        #[cxx_name = "roleNames"]
+        fn role_names(self: &DriveMappingModel) -> QHash_i32_QByteArray;
+
+        #[qinvokable]
+        #[cxx_override]
+        #[cxx_name = "rowCount"]
+        fn row_count(self: &DriveMappingModel, parent: &QModelIndex) -> i32;
+    }
+}
+
+use std::cell::RefCell;
+use std::pin::Pin;
+
+use cxx_qt_lib::{QByteArray, QHash, QHashPair_i32_QByteArray, QModelIndex, QString, QVariant, QVector};
+use rising_sun_common::ui_json::{self, MappingJson};
+use rising_sun_common::{AppConfig, DriveMapping, fat_time, load_config, save_config, short_name};
+
+/// First role number free for models (Qt::UserRole)
+const USER_ROLE: i32 = 0x0100;
+
+/// Role names as QML sees them, from USER_ROLE on
+const ROLES: [&str; 10] = [
+    "driveLetter",
+    "hostPath",
+    "description",
+    "enabled",
+    "readonly",
+    "timestamps",
+    "mangling",
+    "nameCase",
+    "hideDotfiles",
+    "maxSizeMb",
+];
+
+/// Role of the enabled flag
+const ENABLED_ROLE: i32 = USER_ROLE + 3;
+
+/// Rust implementation of the DriveMappingModel
+#[derive(Default)]
+pub struct DriveMappingModelRust {
+    count: i32,
+    mappings: RefCell<Vec<DriveMapping>>,
+}
+
+impl qobject::DriveMappingModel {
+    /// Read the mappings from the configuration file
+    pub fn load(mut self: Pin<&mut Self>) {
+        let mappings = match load_config() {
+            Ok(config) => config.drive_mappings,
+            Err(e) => {
+                tracing::error!("Failed to load drive mappings: {}", e);
+                return;
+            }
+        };
+        self.as_mut().reset(mappings);
+    }
+
+    /// Write the mappings to the configuration file
+    pub fn save(&self) -> bool {
+        let result = load_config().and_then(|mut config| {
+            config.drive_mappings = self.mappings.borrow().clone();
+            save_config(&config)
+        });
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::error!("Failed to save drive mappings: {}", e);
+                false
+            }
+        }
+    }
+
+    /// A row as JSON
+    pub fn get(&self, row: i32) -> QString {
+        match self.mappings.borrow().get(row as usize) {
+            Some(mapping) => QString::from(&ui_json::to_string(&MappingJson::from(mapping))),
+            None => QString::default(),
+        }
+    }
+
+    /// Replace or append a row from JSON
+    pub fn set_mapping(mut self: Pin<&mut Self>, row: i32, json: QString) -> i32 {
+        let mapping = match serde_json::from_str::<MappingJson>(&json.to_string()) {
+            Ok(entry) => entry.to_config(),
+            Err(e) => {
+                tracing::warn!("Invalid drive mapping JSON: {}", e);
+                return -1;
+            }
+        };
+
+        let len = self.mappings.borrow().len() as i32;
+        if (0..len).contains(&row) {
+            self.mappings.borrow_mut()[row as usize] = mapping;
+            self.as_mut().row_changed(row, &[]);
+            return row;
+        }
+
+        let parent = QModelIndex::default();
+        // SAFETY: begin/end pair around the row inserted at the end
+        unsafe { self.as_mut().begin_insert_rows(&parent, len, len) };
+        self.mappings.borrow_mut().push(mapping);
+        unsafe { self.as_mut().end_insert_rows() };
+        self.as_mut().set_count(len + 1);
+        len
+    }
+
+    /// Enable or disable a row's mapping
+    pub fn set_enabled(mut self: Pin<&mut Self>, row: i32, enabled: bool) {
+        let changed = match self.mappings.borrow_mut().get_mut(row as usize) {
+            Some(mapping) if mapping.enabled != enabled => {
+                mapping.enabled = enabled;
+                true
+            }
+            _ => false,
+        };
+        if changed {
+            self.as_mut().row_changed(row, &[ENABLED_ROLE]);
+        }
+    }
+
+    /// Remove a row
+    pub fn remove_mapping(mut self: Pin<&mut Self>, row: i32) {
+        let len = self.mappings.borrow().len() as i32;
+        if !(0..len).contains(&row) {
+            return;
+        }
+        let parent = QModelIndex::default();
+        // SAFETY: begin/end pair around the row removed
+        unsafe { self.as_mut().begin_remove_rows(&parent, row, row) };
+        self.mappings.borrow_mut().remove(row as usize);
+        unsafe { self.as_mut().end_remove_rows() };
+        self.as_mut().set_count(len - 1);
+    }
+
+    /// Replace the rows with the suggested mappings
+    pub fn restore_defaults(self: Pin<&mut Self>) {
+        self.reset(AppConfig::suggested_drive_mappings());
+    }
+
+    /// All rows as JSON
+    pub fn to_json(&self) -> QString {
+        let mappings: Vec<MappingJson> = self.mappings.borrow().iter().map(MappingJson::from).collect();
+        QString::from(&ui_json::to_string(&mappings))
+    }
+
+    /// Value of a role for a row
+    pub fn data(&self, index: &QModelIndex, role: i32) -> QVariant {
+        let mappings = self.mappings.borrow();
+        let Some(m) = mappings.get(index.row() as usize) else {
+            return QVariant::default();
+        };
+        match ROLES.get((role - USER_ROLE) as usize).copied() {
+            Some("driveLetter") => QVariant::from(&QString::from(&m.drive_letter)),
+            Some("hostPath") => QVariant::from(&QString::from(m.host_path.to_string_lossy().as_ref())),
+            Some("description") => QVariant::from(&QString::from(&m.description)),
+            Some("enabled") => QVariant::from(&m.enabled),
+            Some("readonly") => QVariant::from(&m.readonly),
+            Some("timestamps") => QVariant::from(&QString::from(&fat_time::policy_name(m.timestamps))),
+            Some("mangling") => QVariant::from(&QString::from(short_name::mangling_name(m.mangling))),
+            Some("nameCase") => QVariant::from(&QString::from(short_name::case_name(m.name_case))),
+            Some("hideDotfiles") => QVariant::from(&m.hide_dotfiles),
+            Some("maxSizeMb") => QVariant::from(&(m.max_size_mb.min(i32::MAX as u32) as i32)),
+            _ => QVariant::default(),
+        }
+    }
+
+    /// Role numbers and the names QML uses for them
+    pub fn role_names(&self) -> QHash<QHashPair_i32_QByteArray> {
+        let mut roles = QHash::<QHashPair_i32_QByteArray>::default();
+        for (i, name) in ROLES.iter().enumerate() {
+            roles.insert(USER_ROLE + i as i32, QByteArray::from(*name));
+        }
+        roles
+    }
+
+    /// Number of rows
+    pub fn row_count(&self, _parent: &QModelIndex) -> i32 {
+        self.mappings.borrow().len() as i32
+    }
+
+    /// Replace all rows
+    fn reset(mut self: Pin<&mut Self>, mappings: Vec<DriveMapping>) {
+        let count = mappings.len() as i32;
+        // SAFETY: begin/end pair around the rows replaced
+        unsafe { self.as_mut().begin_reset_model() };
+        *self.mappings.borrow_mut() = mappings;
+        unsafe { self.as_mut().end_reset_model() };
+        self.as_mut().set_count(count);
+    }
+
+    /// Tell views a row changed, in the given roles or all of them
+    fn row_changed(mut self: Pin<&mut Self>, row: i32, roles: &[i32]) {
+        let index = self.index(row, 0, &QModelIndex::default());
+        let mut changed = QVector::<i32>::default();
+        for &role in roles {
+            changed.append(role);
+        }
+        self.as_mut().data_changed(&index, &index, &changed);
+    }
+}
@@ -0,0 +1,53 @@
+//! "Don't save changes this run".
+//!
+//! While enabled, writable media are mounted through throwaway overlays
+//! (see `overlay::throwaway`) instead of the images themselves: the
+//! session's hard disks when it starts, and floppies as they are inserted.
+//! SessionController deletes them all when the session stops. Enabled by
+//! the `--ephemeral` command line flag or the toolbar toggle, and only
+//! changed while no session is running.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use rising_sun_common::overlay;
+
+/// Whether writes go to throwaway overlays
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Overlays to delete when the session stops
+static OVERLAYS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Turn throwaway overlays on or off for the next session
+pub fn set_enabled(enabled: bool) {
+    if ENABLED.swap(enabled, Ordering::SeqCst) != enabled {
+        tracing::info!("Changes to media will {}", if enabled { "be discarded" } else { "be saved" });
+    }
+}
+
+/// Whether writes go to throwaway overlays
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Image to mount for the writable image at `path`: a new throwaway
+/// overlay when enabled, otherwise `path` itself
+pub fn writable_image(path: &Path) -> anyhow::Result<PathBuf> {
+    if !is_enabled() {
+        return Ok(path.to_path_buf());
+    }
+    let temporary = overlay::throwaway(path, &overlay::throwaway_dir())?;
+    tracing::info!("Writes to {} go to {}", path.display(), temporary.display());
+    OVERLAYS.lock().unwrap().push(temporary.clone());
+    Ok(temporary)
+}
+
+/// Delete the throwaway overlays of the session that stopped
+pub fn discard() {
+    for path in OVERLAYS.lock().unwrap().drain(..) {
+        match std::fs::remove_file(&path) {
+            Ok(()) => tracing::info!("Discarded {}", path.display()),
+            Err(e) => tracing::warn!("Failed to remove {}: {}", path.display(), e),
+        }
+    }
+}
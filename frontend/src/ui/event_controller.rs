@@ -0,0 +1,154 @@
+//! Event controller Qt bridge dispatching asynchronous driver events.
+//!
+//! A background thread waits on the device with epoll (see
+//! `rising_sun_common::driver::EventListener`); `poll_events` hands what it
+//! collected to QML as signals, which route each event to the controller
+//! that cares. This replaces polling the driver for guest clipboard data,
+//! media changes and session state on separate timers.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, listening)]
+        type EventController = super::EventControllerRust;
+
+        /// Start listening for driver events
+        #[qinvokable]
+        fn start_events(self: Pin<&mut EventController>) -> bool;
+
+        /// Stop listening for driver events
+        #[qinvokable]
+        fn stop_events(self: Pin<&mut EventController>);
+
+        /// Emit a signal for each event received since the last poll
+        #[qinvokable]
+        fn poll_events(self: Pin<&mut EventController>);
+
+        /// Signal emitted when events were lost; state should be re-read
+        #[qsignal]
+        fn events_lost(self: Pin<&mut EventController>);
+
+        /// Signal emitted when the guest asks to eject a medium
+        /// (drive uses BIOS numbering: 0x00/0x01 floppy, 0xE0 CD-ROM)
+        #[qsignal]
+        fn eject_requested(self: Pin<&mut EventController>, drive: i32);
+
+        /// Signal emitted when a medium was mounted or ejected
+        #[qsignal]
+        fn media_changed(self: Pin<&mut EventController>, drive: i32, path: QString, present: bool);
+
+        /// Signal emitted when the guest sets a display mode
+        #[qsignal]
+        fn display_changed(self: Pin<&mut EventController>);
+
+        /// Signal emitted when the session is started, stopped or reset
+        #[qsignal]
+        fn session_changed(self: Pin<&mut EventController>, state: i32);
+
+        /// Signal emitted when the guest clipboard has new data
+        #[qsignal]
+        fn clipboard_changed(self: Pin<&mut EventController>, format: i32);
+
+        /// Signal emitted when the guest changes the 8-bit palette
+        #[qsignal]
+        fn palette_changed(self: Pin<&mut EventController>, generation: i32);
+
+        /// Signal emitted when the guest has powered itself off
+        #[qsignal]
+        fn guest_powered_off(self: Pin<&mut EventController>);
+    }
+}
+
+use std::cell::RefCell;
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+use rising_sun_common::driver::{Event, EventListener};
+
+/// Rust implementation of the EventController
+pub struct EventControllerRust {
+    /// Whether driver events are being received
+    listening: bool,
+    /// Thread waiting for driver events
+    listener: RefCell<Option<EventListener>>,
+}
+
+impl Default for EventControllerRust {
+    fn default() -> Self {
+        Self {
+            listening: false,
+            listener: RefCell::new(None),
+        }
+    }
+}
+
+impl qobject::EventController {
+    /// Start listening for driver events
+    pub fn start_events(mut self: Pin<&mut Self>) -> bool {
+        if self.listener.borrow().is_some() {
+            return true;
+        }
+        match EventListener::start() {
+            Ok(listener) => {
+                tracing::info!("Listening for driver events");
+                *self.listener.borrow_mut() = Some(listener);
+                self.as_mut().set_listening(true);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Cannot listen for driver events: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Stop listening for driver events
+    pub fn stop_events(mut self: Pin<&mut Self>) {
+        self.listener.borrow_mut().take();
+        self.as_mut().set_listening(false);
+    }
+
+    /// Emit a signal for each event received since the last poll
+    pub fn poll_events(mut self: Pin<&mut Self>) {
+        loop {
+            let (event, listening) = match self.listener.borrow().as_ref() {
+                Some(listener) => (listener.try_next(), listener.is_listening()),
+                None => return,
+            };
+            match event {
+                Some(event) => self.as_mut().dispatch(event),
+                None => {
+                    if !listening {
+                        tracing::warn!("Driver event thread stopped");
+                        self.as_mut().stop_events();
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
+    fn dispatch(mut self: Pin<&mut Self>, event: Event) {
+        tracing::debug!("Driver event: {:?}", event);
+        match event {
+            Event::Overflow => self.as_mut().events_lost(),
+            Event::EjectRequest { drive } => self.as_mut().eject_requested(drive as i32),
+            Event::MediaChanged { drive, path } => {
+                let present = path.is_some();
+                let path = QString::from(&path.unwrap_or_default());
+                self.as_mut().media_changed(drive as i32, path, present);
+            }
+            Event::DisplayChanged => self.as_mut().display_changed(),
+            Event::SessionChanged { state } => self.as_mut().session_changed(state as i32),
+            Event::Clipboard { format } => self.as_mut().clipboard_changed(format as i32),
+            Event::PaletteChanged { generation } => self.as_mut().palette_changed(generation as i32),
+            Event::PowerOff => self.as_mut().guest_powered_off(),
+        }
+    }
+}
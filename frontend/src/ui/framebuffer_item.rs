@@ -0,0 +1,102 @@
+//! Scene graph item showing the SunPCi framebuffer.
+//!
+//! Each frame is uploaded from `updatePaintNode` into a texture node (see
+//! `cpp/framebuffer_node.cpp`). Direct color modes go to the GPU straight
+//! from the driver mapping; text, Indexed8 and interlaced frames are
+//! converted to RGBA first. QML calls `update()` to request a frame.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++" {
+        include!("framebuffer_node.h");
+        type QSGNode;
+        #[namespace = "rising_sun"]
+        type UpdatePaintNodeData;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[base = "QQuickItem"]
+        type FramebufferItem = super::FramebufferItemRust;
+
+        /// Upload the current frame into the item's texture node
+        #[qinvokable]
+        #[cxx_override]
+        #[cxx_name = "updatePaintNode"]
+        unsafe fn update_paint_node(
+            self: Pin<&mut FramebufferItem>,
+            old: *mut QSGNode,
+            data: *mut UpdatePaintNodeData,
+        ) -> *mut QSGNode;
+    }
+
+    unsafe extern "C++" {
+        /// Mark the item as drawing its own content
+        #[namespace = "rising_sun"]
+        #[cxx_name = "initFramebufferItem"]
+        fn init_framebuffer_item(item: Pin<&mut FramebufferItem>);
+
+        /// Upload a frame into the item's node, creating it if needed
+        /// (null pixels drop the node)
+        #[namespace = "rising_sun"]
+        #[cxx_name = "updateFramebufferNode"]
+        unsafe fn update_framebuffer_node(
+            item: Pin<&mut FramebufferItem>,
+            old: *mut QSGNode,
+            pixels: *const u8,
+            width: i32,
+            height: i32,
+            stride: i32,
+            layout: i32,
+        ) -> *mut QSGNode;
+    }
+
+    impl cxx_qt::Initialize for FramebufferItem {}
+}
+
+use std::pin::Pin;
+
+use super::framebuffer_provider::with_frame;
+
+/// Rust implementation of the FramebufferItem
+///
+/// Frames come from the shared framebuffer state, so the item keeps none
+/// of its own.
+#[derive(Default)]
+pub struct FramebufferItemRust;
+
+impl cxx_qt::Initialize for qobject::FramebufferItem {
+    fn initialize(self: Pin<&mut Self>) {
+        qobject::init_framebuffer_item(self);
+    }
+}
+
+impl qobject::FramebufferItem {
+    /// Upload the current frame into the item's texture node
+    ///
+    /// Runs on the render thread while the GUI thread is blocked, so the
+    /// mapping is read in place under the framebuffer state lock.
+    pub unsafe fn update_paint_node(
+        self: Pin<&mut Self>,
+        old: *mut qobject::QSGNode,
+        _data: *mut qobject::UpdatePaintNodeData,
+    ) -> *mut qobject::QSGNode {
+        with_frame(|frame| {
+            let (pixels, width, height, stride, layout) = match frame {
+                Some(frame) => (
+                    frame.pixels,
+                    frame.width as i32,
+                    frame.height as i32,
+                    frame.stride as i32,
+                    frame.layout as i32,
+                ),
+                None => (std::ptr::null(), 0, 0, 0, 0),
+            };
+            // The pixels outlive the call, which uploads them before returning
+            unsafe {
+                qobject::update_framebuffer_node(self, old, pixels, width, height, stride, layout)
+            }
+        })
+    }
+}
@@ -1,15 +1,28 @@
 //! Framebuffer image provider for QML.
 //!
 //! This provides framebuffer access for rendering in QML.
-//! The framebuffer data comes from the kernel driver via mmap.
+//! The framebuffer data comes from the kernel driver via mmap; in text
+//! mode it holds character cells, which are rendered with the VGA font
+//! (see `rising_sun_common::text_render`).
 //!
-//! Note: These functions are prepared for future ImageProvider integration.
+//! The display item (`framebuffer_item`) reads frames through `with_frame`.
 
 #![allow(dead_code)]
 
 use std::os::unix::io::RawFd;
-use std::ptr;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use rising_sun_common::DeinterlaceMode;
+use rising_sun_common::frame_pacing;
+use rising_sun_common::ioctl::{
+    region_id, sunpci_get_palette, sunpci_get_text_cursor, Palette, TextCursor,
+};
+use rising_sun_common::text_render::{self, VGA_COLORS};
+use rising_sun_common::text_screen::{TextScreen, CELL_BYTES};
+
+use super::deinterlace::Deinterlacer;
+use super::mapped_region::MappedRegion;
 
 /// Shared state for the framebuffer provider
 pub struct FramebufferProviderState {
@@ -25,8 +38,26 @@ pub struct FramebufferProviderState {
     pub format: u32,
     /// Buffer size
     pub size: usize,
-    /// Mapped pointer (managed externally)
-    pub mapped_ptr: Option<*const u8>,
+    /// Framebuffer mapping
+    pub mapping: Option<MappedRegion>,
+    /// Whether the framebuffer holds interlaced fields (height/2 lines each)
+    pub interlaced: bool,
+    /// Whether the current field is the odd field
+    pub odd_field: bool,
+    /// Deinterlacer for interlaced modes
+    pub deinterlacer: Deinterlacer,
+    /// Text screen size in characters (0 x 0 in graphics modes)
+    pub text_cols: u32,
+    pub text_rows: u32,
+    /// Clock for the text mode cursor and character blink
+    pub blink_epoch: Instant,
+    /// Palette for Indexed8 frames, fetched from the driver when needed
+    pub palette: Option<Palette>,
+    /// The palette has not been fetched since the guest last changed it
+    pub palette_stale: bool,
+    /// Fetch a changed palette when the next frame is converted rather
+    /// than as soon as the driver reports it
+    pub palette_sync: bool,
 }
 
 impl Default for FramebufferProviderState {
@@ -38,16 +69,20 @@ impl Default for FramebufferProviderState {
             stride: 640,
             format: 0,
             size: 0,
-            mapped_ptr: None,
+            mapping: None,
+            interlaced: false,
+            odd_field: false,
+            deinterlacer: Deinterlacer::default(),
+            text_cols: 0,
+            text_rows: 0,
+            blink_epoch: Instant::now(),
+            palette: None,
+            palette_stale: true,
+            palette_sync: true,
         }
     }
 }
 
-// Safety: The mapped_ptr is only accessed from the main thread
-// and the Qt event loop serializes access
-unsafe impl Send for FramebufferProviderState {}
-unsafe impl Sync for FramebufferProviderState {}
-
 /// Global state shared between SessionController and the image provider
 pub static FRAMEBUFFER_STATE: std::sync::LazyLock<Arc<Mutex<FramebufferProviderState>>> =
     std::sync::LazyLock::new(|| Arc::new(Mutex::new(FramebufferProviderState::default())));
@@ -62,15 +97,9 @@ pub fn update_framebuffer_state(
     size: usize,
 ) {
     if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
-        // Unmap old if fd changed
-        if state.driver_fd != fd {
-            if let Some(ptr) = state.mapped_ptr.take() {
-                if state.size > 0 {
-                    unsafe {
-                        libc::munmap(ptr as *mut libc::c_void, state.size);
-                    }
-                }
-            }
+        // Unmap old if fd or size changed
+        if state.driver_fd != fd || state.size != size {
+            state.mapping = None;
         }
 
         state.driver_fd = fd;
@@ -81,132 +110,435 @@ pub fn update_framebuffer_state(
         state.size = size;
 
         // Map new framebuffer if needed
-        if fd >= 0 && size > 0 && state.mapped_ptr.is_none() {
-            let ptr = unsafe {
-                libc::mmap(
-                    ptr::null_mut(),
-                    size,
-                    libc::PROT_READ,
-                    libc::MAP_SHARED,
-                    fd,
-                    0,
-                )
-            };
-
-            if ptr != libc::MAP_FAILED {
-                state.mapped_ptr = Some(ptr as *const u8);
+        if fd >= 0 && size > 0 && state.mapping.is_none() {
+            match MappedRegion::map_region(fd, region_id::FRAMEBUFFER, size) {
+                Ok(mapping) => state.mapping = Some(mapping),
+                Err(e) => tracing::warn!("Failed to map framebuffer: {}", e),
             }
         }
     }
 }
 
+/// Update field information for interlaced modes (called from SessionController)
+pub fn set_field_state(interlaced: bool, odd_field: bool) {
+    if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
+        if state.interlaced != interlaced {
+            state.deinterlacer.reset();
+        }
+        state.interlaced = interlaced;
+        state.odd_field = odd_field;
+    }
+}
+
+/// Update the text screen size, 0 x 0 outside text mode (called from SessionController)
+pub fn set_text_layout(cols: u32, rows: u32) {
+    if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
+        state.text_cols = cols;
+        state.text_rows = rows;
+    }
+}
+
+/// Select the deinterlacing method
+pub fn set_deinterlace_mode(mode: DeinterlaceMode) {
+    if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
+        state.deinterlacer = Deinterlacer::new(mode);
+    }
+}
+
+/// Choose whether palette changes wait for the next frame (DisplayConfig::palette_sync)
+pub fn set_palette_sync(enabled: bool) {
+    if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
+        state.palette_sync = enabled;
+    }
+}
+
+/// Note that the driver reported a new palette generation
+///
+/// With palette sync the palette is fetched when the next frame is
+/// converted, so a frame never mixes two palettes; otherwise right away.
+pub fn invalidate_palette(generation: u32) {
+    if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
+        if state.palette.is_some_and(|p| p.generation == generation) {
+            return;
+        }
+        state.palette_stale = true;
+        if !state.palette_sync {
+            refresh_palette(&mut state);
+        }
+    }
+}
+
+/// Fetch the palette from the driver, keeping the old one on failure
+fn refresh_palette(state: &mut FramebufferProviderState) {
+    if state.driver_fd < 0 {
+        return;
+    }
+    match read_palette(state.driver_fd) {
+        Ok(palette) => state.palette = Some(palette),
+        Err(e) => tracing::warn!("Failed to read palette: {}", e),
+    }
+    // Not retried until the next change, so a failure isn't logged every frame
+    state.palette_stale = false;
+}
+
+/// Read the current palette with SUNPCI_IOC_GET_PALETTE
+fn read_palette(fd: RawFd) -> std::io::Result<Palette> {
+    let mut palette = Palette::default();
+    unsafe { sunpci_get_palette(fd, &mut palette)? };
+    Ok(palette)
+}
+
 /// Clear the framebuffer state (called when session stops)
 pub fn clear_framebuffer_state() {
     if let Ok(mut state) = FRAMEBUFFER_STATE.lock() {
-        if let Some(ptr) = state.mapped_ptr.take() {
-            if state.size > 0 {
-                unsafe {
-                    libc::munmap(ptr as *mut libc::c_void, state.size);
-                }
-            }
-        }
+        let mode = state.deinterlacer.mode;
+        let palette_sync = state.palette_sync;
         *state = FramebufferProviderState::default();
+        state.deinterlacer = Deinterlacer::new(mode);
+        state.palette_sync = palette_sync;
     }
 }
 
 /// Get a snapshot of the current framebuffer as RGBA pixels
 /// 
+/// In text mode the character cells are rendered with the VGA font.
 /// Returns (width, height, rgba_data) or None if not available
 pub fn get_framebuffer_rgba() -> Option<(u32, u32, Vec<u8>)> {
-    let state = FRAMEBUFFER_STATE.lock().ok()?;
-    let ptr = state.mapped_ptr?;
-
-    if state.width == 0 || state.height == 0 || state.size == 0 {
+    let mut guard = FRAMEBUFFER_STATE.lock().ok()?;
+    let state = &mut *guard;
+    if state.text_cols > 0 && state.text_rows > 0 {
+        return render_text_screen(state);
+    }
+    if state.mapping.is_none() || state.width == 0 || state.height == 0 {
         return None;
     }
 
+    if state.format == 0 && state.palette_stale {
+        refresh_palette(state);
+    }
+    let mapping = state.mapping.as_ref()?;
+    let palette = state.palette.as_ref();
+
     let width = state.width;
-    let height = state.height;
     let stride = state.stride as usize;
-    let format = state.format;
+
+    if state.interlaced {
+        // Each field holds every other line of the frame
+        let field_rows = state.height / 2;
+        let src = mapping.read(0, stride * field_rows as usize)?;
+        let field = convert_to_rgba(&src, width, field_rows, stride, state.format, palette);
+        let frame = state.deinterlacer.process(&field, width, field_rows, state.odd_field);
+        return Some((width, field_rows * 2, frame));
+    }
+
+    let height = state.height;
+    let src = mapping.read(0, stride * height as usize)?;
+    let rgba = convert_to_rgba(&src, width, height, stride, state.format, palette);
+    Some((width, height, rgba))
+}
+
+/// Pixel layout of a frame handed to the display item
+///
+/// Must match `FramebufferLayout` in `cpp/framebuffer_node.h`.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameLayout {
+    Rgba8888 = 0,
+    Rgb565 = 1,
+    /// RGB888 in B, G, R byte order
+    Bgr888 = 2,
+    /// XRGB8888, little-endian
+    Xrgb8888 = 3,
+}
+
+/// A frame ready for display
+pub struct FrameView {
+    /// First pixel, valid until the `with_frame` callback returns; the
+    /// guest may still be writing to it, so it is only ever copied
+    pub pixels: *const u8,
+    pub width: u32,
+    pub height: u32,
+    /// Bytes per row
+    pub stride: u32,
+    pub layout: FrameLayout,
+}
+
+/// Run `f` on the current frame, or on None if there is none
+///
+/// Direct color modes point straight into the driver mapping, with the
+/// framebuffer state locked until `f` returns. Text, Indexed8 and
+/// interlaced frames are converted to RGBA first.
+pub fn with_frame<R>(f: impl FnOnce(Option<FrameView>) -> R) -> R {
+    let Ok(guard) = FRAMEBUFFER_STATE.lock() else {
+        return f(None);
+    };
+    let layout = match guard.format {
+        1 => Some(FrameLayout::Rgb565),
+        2 => Some(FrameLayout::Bgr888),
+        3 => Some(FrameLayout::Xrgb8888),
+        _ => None,
+    };
+    let text_mode = guard.text_cols > 0 && guard.text_rows > 0;
+    if let Some(layout) = layout.filter(|_| !guard.interlaced && !text_mode) {
+        let len = guard.stride as usize * guard.height as usize;
+        let view = guard
+            .mapping
+            .as_ref()
+            .and_then(|mapping| mapping.ptr_at(0, len))
+            .filter(|_| guard.width > 0 && guard.height > 0)
+            .map(|pixels| FrameView {
+                pixels,
+                width: guard.width,
+                height: guard.height,
+                stride: guard.stride,
+                layout,
+            });
+        return f(view);
+    }
+    drop(guard);
+
+    let frame = get_framebuffer_rgba();
+    f(frame.as_ref().map(|(width, height, rgba)| FrameView {
+        pixels: rgba.as_ptr(),
+        width: *width,
+        height: *height,
+        stride: width * 4,
+        layout: FrameLayout::Rgba8888,
+    }))
+}
+
+/// Render the character cells of a text mode screen
+fn render_text_screen(state: &mut FramebufferProviderState) -> Option<(u32, u32, Vec<u8>)> {
+    if state.palette_stale {
+        refresh_palette(state);
+    }
+    let mapping = state.mapping.as_ref()?;
+    let len = (state.text_cols * state.text_rows) as usize * CELL_BYTES;
+    let screen = TextScreen::from_cells(&mapping.read(0, len)?, state.text_cols, state.text_rows)?;
+
+    // Text colors are the first 16 palette entries
+    let colors = match &state.palette {
+        Some(palette) if palette.count >= 16 => std::array::from_fn(|i| palette.rgb(i as u8)),
+        _ => VGA_COLORS,
+    };
+    let mut cursor = TextCursor::default();
+    let cursor = unsafe { sunpci_get_text_cursor(state.driver_fd, &mut cursor) }
+        .ok()
+        .map(|_| cursor);
+    let elapsed_ms = state.blink_epoch.elapsed().as_millis() as u64;
+
+    Some(text_render::render_rgba(&screen, &colors, cursor.as_ref(), elapsed_ms))
+}
+
+/// Checksum of the current frame for refresh pacing, or None without one
+///
+/// A palette change counts as a change of frame, so palette cycling keeps
+/// the display at its active rate.
+pub fn frame_checksum() -> Option<u64> {
+    let state = FRAMEBUFFER_STATE.lock().ok()?;
+    let len = if state.text_cols > 0 && state.text_rows > 0 {
+        (state.text_cols * state.text_rows) as usize * CELL_BYTES
+    } else {
+        state.stride as usize * state.height as usize
+    };
+    let data = state.mapping.as_ref()?.read(0, len)?;
+    let palette = state.palette.map_or(0, |p| u64::from(p.generation));
+    Some(frame_pacing::checksum(&data) ^ palette.rotate_left(32) ^ u64::from(state.palette_stale))
+}
+
+/// Get the guest's text screen, if it is in text mode
+pub fn get_text_screen() -> Option<TextScreen> {
+    let state = FRAMEBUFFER_STATE.lock().ok()?;
+    let mapping = state.mapping.as_ref()?;
+    let len = (state.text_cols * state.text_rows) as usize * CELL_BYTES;
+    let cells = mapping.read(0, len)?;
+    TextScreen::from_cells(&cells, state.text_cols, state.text_rows)
+}
+
+/// Convert `height` rows of raw framebuffer data to RGBA pixels
+///
+/// Rows that don't fit in `src` are left black. Indexed8 pixels without
+/// a palette are shown as grayscale.
+fn convert_to_rgba(
+    src: &[u8],
+    width: u32,
+    height: u32,
+    stride: usize,
+    format: u32,
+    palette: Option<&Palette>,
+) -> Vec<u8> {
+    let width = width as usize;
 
     // Allocate RGBA output buffer
-    let mut rgba = vec![0u8; (width * height * 4) as usize];
+    let mut rgba = vec![0u8; width * height as usize * 4];
+
+    let bytes_per_pixel = match format {
+        0 => 1,
+        1 => 2,
+        2 => 3,
+        3 => 4,
+        _ => {
+            // Unknown format - fill with magenta
+            for pixel in rgba.chunks_mut(4) {
+                pixel.copy_from_slice(&[255, 0, 255, 255]);
+            }
+            return rgba;
+        }
+    };
+
+    for (y, dst_row) in rgba.chunks_exact_mut(width * 4).enumerate() {
+        let start = y * stride;
+        let Some(src_row) = src.get(start..start + width * bytes_per_pixel) else {
+            break;
+        };
+        let pixels = src_row.chunks_exact(bytes_per_pixel).zip(dst_row.chunks_exact_mut(4));
 
-    unsafe {
         match format {
             0 => {
                 // Indexed8 - 256-color paletted mode
-                // The VGA palette is managed by the driver (vga.c) but not yet exposed via ioctl.
-                // For now, treat palette indices as grayscale which works for most cases.
-                // Future: Add SUNPCI_IOC_GET_PALETTE ioctl to retrieve the current VGA palette.
-                for y in 0..height as usize {
-                    let src_row = ptr.add(y * stride);
-                    let dst_row = &mut rgba[y * width as usize * 4..];
-                    for x in 0..width as usize {
-                        let pixel = *src_row.add(x);
-                        dst_row[x * 4] = pixel;     // R
-                        dst_row[x * 4 + 1] = pixel; // G
-                        dst_row[x * 4 + 2] = pixel; // B
-                        dst_row[x * 4 + 3] = 255;   // A
-                    }
+                for (src, dst) in pixels {
+                    let [r, g, b] = match palette {
+                        Some(palette) => palette.rgb(src[0]),
+                        None => [src[0]; 3],
+                    };
+                    dst.copy_from_slice(&[r, g, b, 255]);
                 }
             }
             1 => {
                 // RGB565
-                for y in 0..height as usize {
-                    let src_row = ptr.add(y * stride) as *const u16;
-                    let dst_row = &mut rgba[y * width as usize * 4..];
-                    for x in 0..width as usize {
-                        let pixel = *src_row.add(x);
-                        let r = ((pixel >> 11) & 0x1F) as u8;
-                        let g = ((pixel >> 5) & 0x3F) as u8;
-                        let b = (pixel & 0x1F) as u8;
-                        dst_row[x * 4] = (r << 3) | (r >> 2);     // R
-                        dst_row[x * 4 + 1] = (g << 2) | (g >> 4); // G
-                        dst_row[x * 4 + 2] = (b << 3) | (b >> 2); // B
-                        dst_row[x * 4 + 3] = 255;                 // A
-                    }
+                for (src, dst) in pixels {
+                    let pixel = u16::from_le_bytes([src[0], src[1]]);
+                    let r = ((pixel >> 11) & 0x1F) as u8;
+                    let g = ((pixel >> 5) & 0x3F) as u8;
+                    let b = (pixel & 0x1F) as u8;
+                    dst[0] = (r << 3) | (r >> 2);     // R
+                    dst[1] = (g << 2) | (g >> 4);     // G
+                    dst[2] = (b << 3) | (b >> 2);     // B
+                    dst[3] = 255;                     // A
                 }
             }
             2 => {
-                // RGB888
-                for y in 0..height as usize {
-                    let src_row = ptr.add(y * stride);
-                    let dst_row = &mut rgba[y * width as usize * 4..];
-                    for x in 0..width as usize {
-                        dst_row[x * 4] = *src_row.add(x * 3 + 2);     // R (BGR order)
-                        dst_row[x * 4 + 1] = *src_row.add(x * 3 + 1); // G
-                        dst_row[x * 4 + 2] = *src_row.add(x * 3);     // B
-                        dst_row[x * 4 + 3] = 255;                     // A
-                    }
-                }
-            }
-            3 => {
-                // XRGB8888
-                for y in 0..height as usize {
-                    let src_row = ptr.add(y * stride) as *const u32;
-                    let dst_row = &mut rgba[y * width as usize * 4..];
-                    for x in 0..width as usize {
-                        let pixel = *src_row.add(x);
-                        dst_row[x * 4] = ((pixel >> 16) & 0xFF) as u8;     // R
-                        dst_row[x * 4 + 1] = ((pixel >> 8) & 0xFF) as u8;  // G
-                        dst_row[x * 4 + 2] = (pixel & 0xFF) as u8;         // B
-                        dst_row[x * 4 + 3] = 255;                          // A
-                    }
+                // RGB888 (BGR order)
+                for (src, dst) in pixels {
+                    dst.copy_from_slice(&[src[2], src[1], src[0], 255]);
                 }
             }
             _ => {
-                // Unknown format - fill with magenta
-                for pixel in rgba.chunks_mut(4) {
-                    pixel[0] = 255;
-                    pixel[1] = 0;
-                    pixel[2] = 255;
-                    pixel[3] = 255;
+                // XRGB8888 (little-endian: B, G, R, X)
+                for (src, dst) in pixels {
+                    dst.copy_from_slice(&[src[2], src[1], src[0], 255]);
                 }
             }
         }
     }
 
-    Some((width, height, rgba))
+    rgba
+}
+
+/// Metadata describing a raw frame dump
+#[derive(Debug, Clone, Copy)]
+pub struct FrameDumpInfo {
+    /// Visible width in pixels
+    pub width: u32,
+    /// Visible height in pixels
+    pub height: u32,
+    /// Bytes per row in the raw data
+    pub stride: u32,
+    /// Pixel format (0=indexed8, 1=rgb565, 2=rgb888, 3=xrgb8888)
+    pub format: u32,
+}
+
+/// Render frame dump metadata as `key=value` lines
+///
+/// `palette` lists the colors of an indexed frame as comma-separated
+/// RRGGBB hex values, or is `none`.
+pub fn frame_dump_metadata(info: &FrameDumpInfo, index: u32, palette: Option<&Palette>) -> String {
+    let format_name = match info.format {
+        0 => "indexed8",
+        1 => "rgb565",
+        2 => "rgb888",
+        3 => "xrgb8888",
+        _ => "unknown",
+    };
+    let palette = match palette {
+        Some(palette) if info.format == 0 => palette.colors()
+            .iter()
+            .map(|color| format!("{:06x}", color & 0xFF_FFFF))
+            .collect::<Vec<_>>()
+            .join(","),
+        _ => "none".to_string(),
+    };
+    format!(
+        "frame={}\nwidth={}\nheight={}\nstride={}\nformat={}\nformat_name={}\npalette={}\n",
+        index, info.width, info.height, info.stride, info.format, format_name, palette
+    )
+}
+
+/// Dump the current framebuffer contents to `dir` as frame_NNNNN.raw plus
+/// a frame_NNNNN.txt metadata file
+///
+/// Maps the framebuffer read-only for the duration of the copy so this works
+/// whether or not the image provider currently holds a mapping.
+pub fn dump_raw_frame(
+    fd: RawFd,
+    info: &FrameDumpInfo,
+    map_size: usize,
+    dir: &std::path::Path,
+    index: u32,
+) -> std::io::Result<()> {
+    let frame_len = info.stride as usize * info.height as usize;
+    if fd < 0 || frame_len == 0 || frame_len > map_size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "framebuffer not available or geometry exceeds mapping",
+        ));
+    }
+
+    let data = {
+        let mapping = MappedRegion::map_region(fd, region_id::FRAMEBUFFER, map_size)?;
+        mapping.read(0, frame_len).expect("frame_len checked against map_size")
+    };
+
+    let palette = if info.format == 0 { read_palette(fd).ok() } else { None };
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(dir.join(format!("frame_{:05}.raw", index)), &data)?;
+    std::fs::write(
+        dir.join(format!("frame_{:05}.txt", index)),
+        frame_dump_metadata(info, index, palette.as_ref()),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_rgb565() {
+        // One red and one blue pixel with a padded stride
+        let src = [0x00, 0xF8, 0x1F, 0x00, 0xAA, 0xAA];
+        let rgba = convert_to_rgba(&src, 2, 1, 6, 1, None);
+        assert_eq!(rgba, vec![255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_convert_short_source() {
+        // Second row is missing from the source and stays black
+        let src = [0x10, 0x20, 0x30, 0x00];
+        let rgba = convert_to_rgba(&src, 1, 2, 4, 3, None);
+        assert_eq!(rgba, vec![0x30, 0x20, 0x10, 255, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_convert_indexed_palette() {
+        let mut palette = Palette { count: 2, ..Palette::default() };
+        palette.entries[1] = 0x00FF8000;
+        // Index 5 is past the palette's colors
+        let src = [1, 5];
+        let rgba = convert_to_rgba(&src, 2, 1, 2, 0, Some(&palette));
+        assert_eq!(rgba, vec![255, 128, 0, 255, 0, 0, 0, 255]);
+        let gray = convert_to_rgba(&src, 2, 1, 2, 0, None);
+        assert_eq!(gray, vec![1, 1, 1, 255, 5, 5, 5, 255]);
+    }
 }
@@ -0,0 +1,317 @@
+//! Game port controller Qt bridge for joystick passthrough.
+//!
+//! This module handles:
+//! - Finding the host gamepad `[gameport] device` names, and waiting for it
+//!   to be plugged in
+//! - Reading it through evdev and sending the guest's game port state as it
+//!   changes (see `rising_sun_common::gameport`)
+//! - Calibrating the centres of the mapped axes, kept in `[gameport]`
+
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use rising_sun_common::gameport::{Gamepad, find_gamepad, list_gamepads};
+use rising_sun_common::ioctl::{GameportEvent, sunpci_gameport_event};
+use rising_sun_common::{GameportConfig, load_config, save_config};
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, gameport_enabled)]
+        #[qproperty(QString, device)]
+        #[qproperty(QString, device_name)]
+        #[qproperty(bool, gamepad_connected)]
+        #[qproperty(QString, status_text)]
+        type GameportController = super::GameportControllerRust;
+
+        /// Start passing the configured gamepad to the guest
+        #[qinvokable]
+        fn start(self: Pin<&mut GameportController>, fd: i32) -> bool;
+
+        /// Stop passing the gamepad to the guest
+        #[qinvokable]
+        fn stop(self: Pin<&mut GameportController>);
+
+        /// Turn passthrough on or off, and keep it in the configuration
+        #[qinvokable]
+        fn set_enabled(self: Pin<&mut GameportController>, enabled: bool);
+
+        /// Choose the gamepad (a path, part of a name, or empty for the
+        /// first one) and keep it in the configuration
+        #[qinvokable]
+        fn set_gamepad(self: Pin<&mut GameportController>, device: QString);
+
+        /// Gamepads on the host, as a JSON array of {"path", "name"}
+        #[qinvokable]
+        fn list_gamepads(self: &GameportController) -> QString;
+
+        /// Take the sticks' current positions as their centres
+        #[qinvokable]
+        fn calibrate_center(self: &GameportController);
+
+        /// Forget the calibration, using the gamepad's own ranges
+        #[qinvokable]
+        fn reset_calibration(self: &GameportController);
+
+        /// Poll for connection updates
+        #[qinvokable]
+        fn poll_status(self: Pin<&mut GameportController>);
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
+/// What the controller and the passthrough thread share
+#[derive(Default)]
+struct Shared {
+    /// Whether the thread is running
+    running: AtomicBool,
+    /// Set to have the thread calibrate the centres from the sticks
+    calibrate: AtomicBool,
+    /// Mapping in use; changes apply at once
+    config: Mutex<GameportConfig>,
+    /// Name of the gamepad being read, empty while none is
+    connected: Mutex<String>,
+}
+
+/// Rust implementation of the GameportController
+pub struct GameportControllerRust {
+    /// Whether a host gamepad is passed to the guest
+    gameport_enabled: bool,
+    /// Configured gamepad (path or part of its name)
+    device: QString,
+    /// Name of the gamepad being read
+    device_name: QString,
+    /// Whether a gamepad is being read
+    gamepad_connected: bool,
+    /// Status text for UI
+    status_text: QString,
+    /// Driver file descriptor of the running session
+    driver_fd: Cell<i32>,
+    /// State shared with the thread
+    shared: Arc<Shared>,
+    /// Thread handle
+    thread_handle: RefCell<Option<std::thread::JoinHandle<()>>>,
+}
+
+impl Default for GameportControllerRust {
+    fn default() -> Self {
+        let config = load_config().unwrap_or_default().gameport;
+        Self {
+            gameport_enabled: config.enabled,
+            device: QString::from(&config.device),
+            device_name: QString::default(),
+            gamepad_connected: false,
+            status_text: QString::from("Stopped"),
+            driver_fd: Cell::new(-1),
+            shared: Arc::new(Shared::default()),
+            thread_handle: RefCell::new(None),
+        }
+    }
+}
+
+impl qobject::GameportController {
+    /// Start passing the configured gamepad to the guest
+    pub fn start(mut self: Pin<&mut Self>, fd: i32) -> bool {
+        self.as_mut().stop();
+        self.driver_fd.set(fd);
+
+        let config = load_config().unwrap_or_default().gameport;
+        self.as_mut().set_gameport_enabled(config.enabled);
+        self.as_mut().set_device(QString::from(&config.device));
+        if fd < 0 || !config.enabled {
+            self.set_status_text(QString::from("Off"));
+            return false;
+        }
+
+        *self.shared.config.lock().unwrap_or_else(|e| e.into_inner()) = config;
+        self.shared.running.store(true, Ordering::SeqCst);
+        let shared = Arc::clone(&self.shared);
+        *self.thread_handle.borrow_mut() = Some(std::thread::spawn(move || gameport_thread(fd, shared)));
+        self.set_status_text(QString::from("Waiting for a gamepad"));
+        true
+    }
+
+    /// Stop passing the gamepad to the guest
+    pub fn stop(mut self: Pin<&mut Self>) {
+        self.shared.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.thread_handle.borrow_mut().take() {
+            let _ = handle.join();
+        }
+        self.driver_fd.set(-1);
+        self.as_mut().set_gamepad_connected(false);
+        self.as_mut().set_device_name(QString::default());
+        self.set_status_text(QString::from("Stopped"));
+    }
+
+    /// Turn passthrough on or off
+    pub fn set_enabled(mut self: Pin<&mut Self>, enabled: bool) {
+        update_config(|config| config.enabled = enabled);
+        self.as_mut().set_gameport_enabled(enabled);
+        self.restart();
+    }
+
+    /// Choose the gamepad
+    pub fn set_gamepad(mut self: Pin<&mut Self>, device: QString) {
+        let spec = device.to_string();
+        update_config(|config| config.device = spec);
+        self.as_mut().set_device(device);
+        self.restart();
+    }
+
+    /// Gamepads on the host
+    pub fn list_gamepads(&self) -> QString {
+        #[derive(serde::Serialize)]
+        struct Entry {
+            path: String,
+            name: String,
+        }
+
+        let gamepads: Vec<Entry> = list_gamepads()
+            .into_iter()
+            .map(|g| Entry {
+                path: g.path.display().to_string(),
+                name: g.name,
+            })
+            .collect();
+        QString::from(&serde_json::to_string(&gamepads).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Take the sticks' current positions as their centres
+    pub fn calibrate_center(&self) {
+        self.shared.calibrate.store(true, Ordering::SeqCst);
+    }
+
+    /// Forget the calibration
+    pub fn reset_calibration(&self) {
+        let mut config = load_config().unwrap_or_default();
+        for axis in &mut config.gameport.axes {
+            axis.min = None;
+            axis.center = None;
+            axis.max = None;
+        }
+        self.shared.config.lock().unwrap_or_else(|e| e.into_inner()).axes = config.gameport.axes.clone();
+        if let Err(e) = save_config(&config) {
+            tracing::warn!("Failed to save game port calibration: {}", e);
+        }
+    }
+
+    /// Poll for connection updates
+    pub fn poll_status(mut self: Pin<&mut Self>) {
+        if self.thread_handle.borrow().is_none() {
+            return;
+        }
+        let name = self.shared.connected.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let connected = !name.is_empty();
+        if connected != *self.as_ref().gamepad_connected() {
+            self.as_mut().set_gamepad_connected(connected);
+            self.as_mut().set_device_name(QString::from(&name));
+            self.set_status_text(QString::from(if connected { "Connected" } else { "Waiting for a gamepad" }));
+        }
+    }
+
+    /// Internal: pick up new settings if a session is running
+    fn restart(self: Pin<&mut Self>) {
+        let fd = self.driver_fd.get();
+        if fd >= 0 {
+            self.start(fd);
+        }
+    }
+}
+
+/// Change `[gameport]` and save it
+fn update_config(change: impl FnOnce(&mut GameportConfig)) {
+    let mut config = load_config().unwrap_or_default();
+    change(&mut config.gameport);
+    if let Err(e) = save_config(&config) {
+        tracing::warn!("Failed to save game port settings: {}", e);
+    }
+}
+
+/// How often to look for the gamepad while none is plugged in
+const RESCAN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest wait for gamepad input, so a stop is noticed
+const POLL_TIMEOUT_MS: i32 = 50;
+
+/// Game port passthrough thread
+///
+/// Waits for the gamepad to be there, then sends the guest's game port
+/// state every time it changes. An unplugged gamepad leaves the guest's
+/// sticks unplugged until it is back.
+fn gameport_thread(fd: i32, shared: Arc<Shared>) {
+    let send = |event: &GameportEvent| {
+        if let Err(e) = unsafe { sunpci_gameport_event(fd, event) } {
+            tracing::warn!("Game port event failed: {}", e);
+        }
+    };
+
+    while shared.running.load(Ordering::SeqCst) {
+        let spec = shared.config.lock().unwrap_or_else(|e| e.into_inner()).device.clone();
+        let Some(mut gamepad) = find_gamepad(&spec).and_then(|info| Gamepad::open(&info.path).ok()) else {
+            sleep_while_running(&shared, RESCAN_INTERVAL);
+            continue;
+        };
+        tracing::info!("Passing {} to the game port", gamepad.name());
+        *shared.connected.lock().unwrap_or_else(|e| e.into_inner()) = gamepad.name().to_string();
+
+        let mut last: Option<GameportEvent> = None;
+        while shared.running.load(Ordering::SeqCst) {
+            if shared.calibrate.swap(false, Ordering::SeqCst) {
+                calibrate(&shared, &gamepad);
+            }
+
+            let event = gamepad.state().to_event(&shared.config.lock().unwrap_or_else(|e| e.into_inner()));
+            if last != Some(event) {
+                send(&event);
+                last = Some(event);
+            }
+
+            let mut pollfd = libc::pollfd {
+                fd: std::os::unix::io::AsRawFd::as_raw_fd(&gamepad),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            unsafe { libc::poll(&mut pollfd, 1, POLL_TIMEOUT_MS) };
+            if let Err(e) = gamepad.read_events() {
+                tracing::info!("{} went away: {}", gamepad.name(), e);
+                break;
+            }
+        }
+
+        // Sticks out of the port
+        send(&GameportEvent::default());
+        shared.connected.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+    tracing::info!("Game port thread stopped");
+}
+
+/// Take the sticks' current positions as their centres, and save them
+fn calibrate(shared: &Shared, gamepad: &Gamepad) {
+    let mut config = load_config().unwrap_or_default();
+    gamepad.state().calibrate_center(&mut config.gameport);
+    shared.config.lock().unwrap_or_else(|e| e.into_inner()).axes = config.gameport.axes.clone();
+    match save_config(&config) {
+        Ok(()) => tracing::info!("Game port centres calibrated"),
+        Err(e) => tracing::warn!("Failed to save game port calibration: {}", e),
+    }
+}
+
+fn sleep_while_running(shared: &Shared, duration: Duration) {
+    let step = Duration::from_millis(100);
+    let mut slept = Duration::ZERO;
+    while slept < duration && shared.running.load(Ordering::SeqCst) {
+        std::thread::sleep(step);
+        slept += step;
+    }
+}
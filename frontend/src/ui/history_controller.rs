@@ -0,0 +1,199 @@
+//! History controller Qt bridge for the searchable session history.
+//!
+//! While a session runs, QML timers call `capture_text` and save display
+//! thumbnails to `thumbnail_path`; both end up in the session's history
+//! (see `rising_sun_common::history`). The history dialog queries it with
+//! `search`, which answers with JSON for the timeline view.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, recording)]
+        #[qproperty(i32, text_interval)]
+        #[qproperty(i32, thumbnail_interval)]
+        type HistoryController = super::HistoryControllerRust;
+
+        /// Start recording the running session if `[history] enabled` is set
+        #[qinvokable]
+        fn start_recording(self: Pin<&mut HistoryController>) -> bool;
+
+        /// Stop recording (the session stopped)
+        #[qinvokable]
+        fn stop_recording(self: Pin<&mut HistoryController>);
+
+        /// Record the screen text if the guest is in text mode
+        #[qinvokable]
+        fn capture_text(self: &HistoryController);
+
+        /// Get a path to save a new thumbnail to, or "" when not recording
+        #[qinvokable]
+        fn thumbnail_path(self: &HistoryController) -> QString;
+
+        /// Record a thumbnail saved to a path from thumbnail_path
+        #[qinvokable]
+        fn add_thumbnail(self: &HistoryController, path: QString);
+
+        /// Search the screen text of all recorded sessions. Returns a JSON
+        /// array of {session, time_ms, line, thumbnail}, newest first.
+        #[qinvokable]
+        fn search(self: &HistoryController, query: QString, limit: i32) -> QString;
+
+        /// Get the recorded sessions as a JSON array of
+        /// {id, started_ms, snapshots}, newest first
+        #[qinvokable]
+        fn get_sessions_json(self: &HistoryController) -> QString;
+    }
+}
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+use rising_sun_common::history::{self, SessionRecorder, Snapshot};
+use rising_sun_common::load_config;
+
+use super::actions::{self, Action};
+use super::framebuffer_provider::get_text_screen;
+use super::startup;
+
+/// Rust implementation of the HistoryController
+pub struct HistoryControllerRust {
+    /// Whether the running session is being recorded
+    recording: bool,
+    /// Milliseconds between screen text captures
+    text_interval: i32,
+    /// Milliseconds between thumbnails
+    thumbnail_interval: i32,
+    /// Recorder of the running session
+    recorder: RefCell<Option<SessionRecorder>>,
+}
+
+impl Default for HistoryControllerRust {
+    fn default() -> Self {
+        let _timing = startup::span("HistoryController");
+        actions::register(Action::new("session_history", "Session History...", "View"));
+        Self {
+            recording: false,
+            text_interval: 5000,
+            thumbnail_interval: 60000,
+            recorder: RefCell::new(None),
+        }
+    }
+}
+
+impl qobject::HistoryController {
+    /// Start recording the running session
+    pub fn start_recording(mut self: Pin<&mut Self>) -> bool {
+        if self.recorder.borrow().is_some() {
+            return true;
+        }
+        let config = load_config().unwrap_or_default().history;
+        if !config.enabled {
+            return false;
+        }
+
+        // Make room for the new session
+        let root = history::history_dir();
+        let keep = (config.max_sessions as usize).saturating_sub(1);
+        match history::prune(&root, keep) {
+            Ok(0) => {}
+            Ok(removed) => tracing::info!("Deleted history of {} old sessions", removed),
+            Err(e) => tracing::warn!("Cannot delete old session history: {}", e),
+        }
+
+        match SessionRecorder::start(&root, history::now_ms()) {
+            Ok(recorder) => {
+                tracing::info!("Recording session history {}", recorder.id());
+                *self.recorder.borrow_mut() = Some(recorder);
+                let text_interval = config.text_interval_secs.max(1).saturating_mul(1000);
+                let thumbnail_interval = config.thumbnail_interval_secs.max(1).saturating_mul(1000);
+                self.as_mut().set_text_interval(text_interval.min(i32::MAX as u32) as i32);
+                self.as_mut().set_thumbnail_interval(thumbnail_interval.min(i32::MAX as u32) as i32);
+                self.as_mut().set_recording(true);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Cannot record session history: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Stop recording
+    pub fn stop_recording(mut self: Pin<&mut Self>) {
+        self.recorder.borrow_mut().take();
+        self.as_mut().set_recording(false);
+    }
+
+    /// Record the screen text if the guest is in text mode
+    pub fn capture_text(&self) {
+        let Some(text) = screen_text() else {
+            return;
+        };
+        self.record(Snapshot {
+            time_ms: history::now_ms(),
+            text,
+            thumbnail: None,
+        });
+    }
+
+    /// Get a path to save a new thumbnail to
+    pub fn thumbnail_path(&self) -> QString {
+        self.recorder
+            .borrow()
+            .as_ref()
+            .map(|r| QString::from(r.thumbnail_path(history::now_ms()).to_string_lossy().as_ref()))
+            .unwrap_or_default()
+    }
+
+    /// Record a saved thumbnail, with the screen text of the moment
+    pub fn add_thumbnail(&self, path: QString) {
+        let path = path.to_string();
+        let Some(name) = Path::new(&path).file_name() else {
+            return;
+        };
+        self.record(Snapshot {
+            time_ms: history::now_ms(),
+            text: screen_text().unwrap_or_default(),
+            thumbnail: Some(name.to_string_lossy().into_owned()),
+        });
+    }
+
+    /// Search the screen text of all recorded sessions
+    pub fn search(&self, query: QString, limit: i32) -> QString {
+        let matches = history::search(&history::history_dir(), &query.to_string(), limit.max(0) as usize);
+        QString::from(&serde_json::to_string(&matches).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Get the recorded sessions
+    pub fn get_sessions_json(&self) -> QString {
+        let sessions = history::sessions(&history::history_dir());
+        QString::from(&serde_json::to_string(&sessions).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Internal: append a snapshot, giving up on the history if it fails
+    fn record(&self, snapshot: Snapshot) {
+        let mut recorder = self.recorder.borrow_mut();
+        let Some(r) = recorder.as_mut() else {
+            return;
+        };
+        if let Err(e) = r.record(&snapshot) {
+            tracing::warn!("Cannot write session history, no longer recording: {}", e);
+            *recorder = None;
+        }
+    }
+}
+
+/// Lines on the guest's text screen, or None outside text mode
+fn screen_text() -> Option<Vec<String>> {
+    get_text_screen()
+        .filter(|screen| !screen.is_blank())
+        .map(|screen| screen.lines())
+}
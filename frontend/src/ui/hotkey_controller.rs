@@ -0,0 +1,387 @@
+//! Hotkey controller Qt bridge for host-side keyboard shortcuts.
+//!
+//! This module handles:
+//! - Parsing key combinations from the [hotkeys] config section
+//! - Matching key events against the bindings before they reach the guest
+//! - Tracking the host key (Right Ctrl), which is a modifier in its own right
+//!
+//! A binding consisting of just the host key fires when it is released
+//! without any other key having been pressed in between, so host key
+//! combinations don't also trigger the bare host key action.
+//!
+//! Hotkeys name actions in the action registry, which shows the bound
+//! combination as the action's shortcut; QML triggers them through it.
+
+use std::cell::{Cell, RefCell};
+
+use rising_sun_common::{AppConfig, HotkeyConfig, load_config};
+
+use super::actions::{self, Action};
+use super::session_gate;
+use super::startup;
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, host_key_down)]
+        type HotkeyController = super::HotkeyControllerRust;
+
+        /// Load the hotkey bindings from the config file
+        #[qinvokable]
+        fn load_bindings(self: Pin<&mut HotkeyController>);
+
+        /// Handle a Qt key press event
+        /// Returns true if the event was consumed by a hotkey
+        #[qinvokable]
+        fn handle_key_press(self: Pin<&mut HotkeyController>, qt_key: i32, modifiers: i32, native_scancode: i32) -> bool;
+
+        /// Handle a Qt key release event
+        /// Returns true if the event was consumed by a hotkey
+        #[qinvokable]
+        fn handle_key_release(self: Pin<&mut HotkeyController>, qt_key: i32, modifiers: i32, native_scancode: i32) -> bool;
+
+        /// Get the key combination bound to an action (empty if unbound)
+        #[qinvokable]
+        fn get_binding(self: &HotkeyController, action: QString) -> QString;
+
+        /// Get a path for a new screenshot file (creates the directory)
+        #[qinvokable]
+        fn screenshot_path(self: &HotkeyController) -> QString;
+
+        /// Signal emitted when a hotkey fires
+        #[qsignal]
+        fn action_triggered(self: Pin<&mut HotkeyController>, action: QString);
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
+/// Qt::Key_Control
+const QT_KEY_CONTROL: i32 = 0x01000021;
+
+/// X11/evdev keycode of Right Ctrl (evdev KEY_RIGHTCTRL + 8)
+const NATIVE_RIGHT_CTRL: i32 = 105;
+
+/// Qt keyboard modifier flags
+mod qt_modifiers {
+    pub const SHIFT: u32 = 0x0200_0000;
+    pub const CONTROL: u32 = 0x0400_0000;
+    pub const ALT: u32 = 0x0800_0000;
+    pub const META: u32 = 0x1000_0000;
+    pub const MASK: u32 = SHIFT | CONTROL | ALT | META;
+}
+
+/// A parsed key combination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hotkey {
+    /// Requires the host key (Right Ctrl) to be held
+    host: bool,
+    /// Required Qt modifier flags
+    modifiers: u32,
+    /// Qt key code (None for a bare host key binding)
+    key: Option<i32>,
+}
+
+/// Rust implementation of the HotkeyController
+pub struct HotkeyControllerRust {
+    /// Whether the host key is currently held
+    host_key_down: bool,
+    /// Parsed bindings as (action name, hotkey)
+    bindings: RefCell<Vec<(&'static str, Hotkey)>>,
+    /// Raw binding strings from the config
+    config: RefCell<HotkeyConfig>,
+    /// Whether another key was pressed while the host key was held
+    host_used: Cell<bool>,
+}
+
+impl Default for HotkeyControllerRust {
+    fn default() -> Self {
+        let _timing = startup::span("HotkeyController");
+        let config = HotkeyConfig::default();
+        actions::register(
+            Action::new("screenshot", "Save Screenshot", "View")
+                .enabled_when(session_gate::is_running),
+        );
+        publish_shortcuts(&config);
+        Self {
+            host_key_down: false,
+            bindings: RefCell::new(parse_bindings(&config)),
+            config: RefCell::new(config),
+            host_used: Cell::new(false),
+        }
+    }
+}
+
+impl qobject::HotkeyController {
+    /// Load the hotkey bindings from the config file
+    pub fn load_bindings(self: Pin<&mut Self>) {
+        let config = load_config().unwrap_or_default().hotkeys;
+        *self.bindings.borrow_mut() = parse_bindings(&config);
+        publish_shortcuts(&config);
+        *self.config.borrow_mut() = config;
+        tracing::info!("Loaded {} hotkey bindings", self.bindings.borrow().len());
+    }
+
+    /// Handle a key press, returning true if it triggered a hotkey
+    pub fn handle_key_press(
+        mut self: Pin<&mut Self>,
+        qt_key: i32,
+        modifiers: i32,
+        native_scancode: i32,
+    ) -> bool {
+        if qt_key == QT_KEY_CONTROL && native_scancode == NATIVE_RIGHT_CTRL {
+            self.host_used.set(false);
+            self.as_mut().set_host_key_down(true);
+            // Swallow the host key only when it is bound to something
+            return self.bindings.borrow().iter().any(|(_, h)| h.host);
+        }
+
+        let host_down = *self.as_ref().host_key_down();
+        if host_down {
+            self.host_used.set(true);
+        }
+
+        let action = self
+            .bindings
+            .borrow()
+            .iter()
+            .find(|(_, h)| matches_press(h, qt_key, modifiers as u32, host_down))
+            .map(|(name, _)| *name);
+
+        match action {
+            Some(name) => {
+                tracing::debug!("Hotkey triggered: {}", name);
+                self.as_mut().action_triggered(QString::from(name));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Handle a key release, returning true if it triggered a hotkey
+    pub fn handle_key_release(
+        mut self: Pin<&mut Self>,
+        qt_key: i32,
+        _modifiers: i32,
+        native_scancode: i32,
+    ) -> bool {
+        if qt_key != QT_KEY_CONTROL || native_scancode != NATIVE_RIGHT_CTRL {
+            return false;
+        }
+
+        self.as_mut().set_host_key_down(false);
+        if self.host_used.get() {
+            return true;
+        }
+
+        let action = self
+            .bindings
+            .borrow()
+            .iter()
+            .find(|(_, h)| h.host && h.key.is_none() && h.modifiers == 0)
+            .map(|(name, _)| *name);
+
+        match action {
+            Some(name) => {
+                tracing::debug!("Hotkey triggered: {}", name);
+                self.as_mut().action_triggered(QString::from(name));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the key combination bound to an action
+    pub fn get_binding(&self, action: QString) -> QString {
+        let config = self.config.borrow();
+        let binding = match action.to_string().as_str() {
+            "release_capture" => &config.release_capture,
+            "toggle_fullscreen" => &config.toggle_fullscreen,
+            "send_ctrl_alt_del" => &config.send_ctrl_alt_del,
+            "eject_cdrom" => &config.eject_cdrom,
+            "screenshot" => &config.screenshot,
+            _ => return QString::default(),
+        };
+        QString::from(binding)
+    }
+
+    /// Get a path for a new screenshot
+    pub fn screenshot_path(&self) -> QString {
+        let dir = AppConfig::data_dir().join("screenshots");
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::error!("Failed to create screenshot directory: {}", e);
+        }
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = dir.join(format!("screenshot-{}.png", stamp));
+        QString::from(path.to_string_lossy().as_ref())
+    }
+}
+
+/// Configured bindings as (action name, key combination)
+fn binding_entries(config: &HotkeyConfig) -> [(&'static str, &str); 5] {
+    [
+        ("release_capture", &config.release_capture),
+        ("toggle_fullscreen", &config.toggle_fullscreen),
+        ("send_ctrl_alt_del", &config.send_ctrl_alt_del),
+        ("eject_cdrom", &config.eject_cdrom),
+        ("screenshot", &config.screenshot),
+    ]
+}
+
+/// Show the valid bindings as the shortcuts of their actions
+fn publish_shortcuts(config: &HotkeyConfig) {
+    for (name, combo) in binding_entries(config) {
+        let valid = !combo.trim().is_empty() && parse_hotkey(combo).is_some();
+        actions::set_shortcut(name, if valid { combo.trim() } else { "" });
+    }
+}
+
+/// Parse all configured bindings, skipping empty or invalid ones
+fn parse_bindings(config: &HotkeyConfig) -> Vec<(&'static str, Hotkey)> {
+    binding_entries(config)
+        .into_iter()
+        .filter(|(_, combo)| !combo.trim().is_empty())
+        .filter_map(|(name, combo)| match parse_hotkey(combo) {
+            Some(hotkey) => Some((name, hotkey)),
+            None => {
+                tracing::warn!("Invalid hotkey for {}: {:?}", name, combo);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Parse a key combination such as "Ctrl+Alt+F" or "RightCtrl+Del"
+fn parse_hotkey(combo: &str) -> Option<Hotkey> {
+    let mut hotkey = Hotkey { host: false, modifiers: 0, key: None };
+
+    for part in combo.split('+').map(str::trim) {
+        match part.to_ascii_lowercase().as_str() {
+            "rightctrl" | "host" => hotkey.host = true,
+            "ctrl" | "control" => hotkey.modifiers |= qt_modifiers::CONTROL,
+            "alt" => hotkey.modifiers |= qt_modifiers::ALT,
+            "shift" => hotkey.modifiers |= qt_modifiers::SHIFT,
+            "meta" | "super" => hotkey.modifiers |= qt_modifiers::META,
+            name => {
+                // Only one non-modifier key per combination
+                if hotkey.key.is_some() {
+                    return None;
+                }
+                hotkey.key = Some(key_name_to_qt(name)?);
+            }
+        }
+    }
+
+    // A combination needs a key, or must be the bare host key
+    if hotkey.key.is_none() && !(hotkey.host && hotkey.modifiers == 0) {
+        return None;
+    }
+    Some(hotkey)
+}
+
+/// Map a lowercase key name to a Qt key code
+fn key_name_to_qt(name: &str) -> Option<i32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_alphanumeric() {
+            return Some(c.to_ascii_uppercase() as i32);
+        }
+    }
+
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse::<i32>().ok()) {
+        if (1..=12).contains(&n) {
+            return Some(0x01000030 + n - 1);
+        }
+    }
+
+    let key = match name {
+        "esc" | "escape" => 0x01000000,
+        "tab" => 0x01000001,
+        "backspace" => 0x01000003,
+        "enter" | "return" => 0x01000004,
+        "ins" | "insert" => 0x01000006,
+        "del" | "delete" => 0x01000007,
+        "pause" => 0x01000008,
+        "print" | "printscreen" => 0x01000009,
+        "home" => 0x01000010,
+        "end" => 0x01000011,
+        "left" => 0x01000012,
+        "up" => 0x01000013,
+        "right" => 0x01000014,
+        "down" => 0x01000015,
+        "pgup" | "pageup" => 0x01000016,
+        "pgdn" | "pagedown" => 0x01000017,
+        "space" => 0x20,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Check whether a key press matches a hotkey
+fn matches_press(hotkey: &Hotkey, qt_key: i32, modifiers: u32, host_down: bool) -> bool {
+    if hotkey.key != Some(qt_key) || hotkey.host != host_down {
+        return false;
+    }
+    let mut active = modifiers & qt_modifiers::MASK;
+    if host_down {
+        // The host key itself reports as a Control modifier
+        active &= !qt_modifiers::CONTROL;
+    }
+    active == hotkey.modifiers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hotkey() {
+        assert_eq!(
+            parse_hotkey("Ctrl+Alt+F"),
+            Some(Hotkey {
+                host: false,
+                modifiers: qt_modifiers::CONTROL | qt_modifiers::ALT,
+                key: Some('F' as i32),
+            })
+        );
+        assert_eq!(
+            parse_hotkey("RightCtrl+Del"),
+            Some(Hotkey { host: true, modifiers: 0, key: Some(0x01000007) })
+        );
+        assert_eq!(
+            parse_hotkey("RightCtrl"),
+            Some(Hotkey { host: true, modifiers: 0, key: None })
+        );
+        assert_eq!(parse_hotkey("Ctrl+F11").and_then(|h| h.key), Some(0x0100003a));
+    }
+
+    #[test]
+    fn test_parse_hotkey_invalid() {
+        assert_eq!(parse_hotkey("Ctrl+Alt"), None);
+        assert_eq!(parse_hotkey("A+B"), None);
+        assert_eq!(parse_hotkey("Ctrl+Bogus"), None);
+    }
+
+    #[test]
+    fn test_matches_press() {
+        let host_f = parse_hotkey("RightCtrl+F").unwrap();
+        assert!(matches_press(&host_f, 'F' as i32, qt_modifiers::CONTROL, true));
+        assert!(!matches_press(&host_f, 'F' as i32, qt_modifiers::CONTROL, false));
+
+        let ctrl_alt_f = parse_hotkey("Ctrl+Alt+F").unwrap();
+        let mods = qt_modifiers::CONTROL | qt_modifiers::ALT;
+        assert!(matches_press(&ctrl_alt_f, 'F' as i32, mods, false));
+        assert!(!matches_press(&ctrl_alt_f, 'F' as i32, qt_modifiers::CONTROL, false));
+    }
+}
@@ -1,14 +1,30 @@
 //! Input controller Qt bridge for keyboard and mouse event handling.
 //!
 //! This module handles:
-//! - Converting Qt key codes to PC XT scancodes
+//! - Converting Qt key codes to PC XT scancodes (see common::scancode)
 //! - Mouse movement and button tracking
 //! - Input capture state management
+//! - Keyboard LED (lock key) synchronization with the guest
+//! - Typing text (the host clipboard) and keystroke macros into the guest
 
 use std::cell::RefCell;
 use std::collections::HashSet;
-
-use rising_sun_common::ioctl::{KeyEvent, MouseEvent, key_flags, mouse_buttons};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rising_sun_common::keymacro;
+use rising_sun_common::placement::MotionScaler;
+use rising_sun_common::scancode::{has_layout, layout_keystrokes, macro_keystrokes, qt_key_to_scancode};
+use rising_sun_common::ioctl::{
+    KeyEvent, MouseAbsEvent, MouseEvent, key_flags, keyboard_leds, mouse_buttons,
+};
+use rising_sun_common::{load_config, DriverHandle, MacroKey};
+
+use super::actions::{self, Action};
+use super::session_gate;
+use super::startup;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -25,12 +41,23 @@ mod qobject {
         #[qproperty(i32, guest_width)]
         #[qproperty(i32, guest_height)]
         #[qproperty(i32, driver_fd)]
+        #[qproperty(bool, sync_caps_lock)]
+        #[qproperty(bool, sync_num_lock)]
+        #[qproperty(bool, sync_scroll_lock)]
+        #[qproperty(i32, guest_leds)]
+        #[qproperty(bool, absolute_mode)]
+        #[qproperty(bool, typing)]
+        #[qproperty(f64, device_pixel_ratio)]
         type InputController = super::InputControllerRust;
 
         /// Set the driver file descriptor
         #[qinvokable]
         fn set_driver(self: Pin<&mut InputController>, fd: i32);
 
+        /// (Re)load button swap / middle-button emulation settings from config
+        #[qinvokable]
+        fn load_mouse_config(self: Pin<&mut InputController>);
+
         /// Toggle keyboard capture mode
         #[qinvokable]
         fn toggle_keyboard_capture(self: Pin<&mut InputController>);
@@ -63,8 +90,20 @@ mod qobject {
         fn handle_mouse_release(self: Pin<&mut InputController>, button: i32);
 
         /// Handle mouse movement (relative mode)
+        /// dx, dy are in logical pixels, scaled by device_pixel_ratio
+        #[qinvokable]
+        fn handle_mouse_move(self: Pin<&mut InputController>, dx: f64, dy: f64);
+
+        /// Handle mouse movement (absolute/tablet mode)
+        /// x, y are relative to the displayed image of view_width x view_height
         #[qinvokable]
-        fn handle_mouse_move(self: Pin<&mut InputController>, dx: i32, dy: i32);
+        fn handle_mouse_move_absolute(
+            self: Pin<&mut InputController>,
+            x: f64,
+            y: f64,
+            view_width: f64,
+            view_height: f64,
+        );
 
         /// Handle mouse wheel
         #[qinvokable]
@@ -81,10 +120,36 @@ mod qobject {
         /// Send Ctrl+Alt+Backspace to guest
         #[qinvokable]
         fn send_ctrl_alt_backspace(self: Pin<&mut InputController>);
+
+        /// Push the host's lock key LED state to the guest
+        #[qinvokable]
+        fn sync_leds_to_guest(self: Pin<&mut InputController>);
+
+        /// Type text into the guest as key strokes in its keyboard layout,
+        /// `cps` characters per second (0 for `[keyboard]
+        /// type_chars_per_sec`). Returns the number of characters left out
+        /// because no key types them, or -1 if nothing can be typed.
+        #[qinvokable]
+        fn type_text(self: Pin<&mut InputController>, text: QString, cps: i32) -> i32;
+
+        /// Type a keystroke macro (see scancode::macro_keystrokes) into the
+        /// guest. Returns false, typing nothing, if it does not parse.
+        #[qinvokable]
+        fn type_macro(self: Pin<&mut InputController>, keys: QString) -> bool;
+
+        /// Stop typing into the guest
+        #[qinvokable]
+        fn cancel_typing(self: Pin<&mut InputController>);
+
+        /// Notice everything has been typed (called by a timer while
+        /// typing)
+        #[qinvokable]
+        fn poll_typing(self: Pin<&mut InputController>);
     }
 }
 
 use std::pin::Pin;
+use cxx_qt_lib::QString;
 
 /// Rust implementation of the InputController
 pub struct InputControllerRust {
@@ -98,24 +163,63 @@ pub struct InputControllerRust {
     guest_height: i32,
     /// Driver file descriptor
     driver_fd: i32,
+    /// Synchronize Caps Lock with the host
+    sync_caps_lock: bool,
+    /// Synchronize Num Lock with the host
+    sync_num_lock: bool,
+    /// Synchronize Scroll Lock with the host
+    sync_scroll_lock: bool,
+    /// Last known guest LED state (keyboard_leds::* bitmap)
+    guest_leds: i32,
+    /// Send absolute pointer positions instead of relative deltas
+    absolute_mode: bool,
+    /// Whether text or a keystroke macro is being typed into the guest
+    typing: bool,
+    /// Physical pixels per logical pixel on the window's screen
+    device_pixel_ratio: f64,
+    /// Set to stop typing
+    typing_stop: Arc<AtomicBool>,
+    /// Thread typing into the guest
+    typing_thread: RefCell<Option<std::thread::JoinHandle<()>>>,
     /// Currently pressed keys (for tracking modifier state)
     pressed_keys: RefCell<HashSet<u32>>,
-    /// Current mouse button state
-    button_state: RefCell<u32>,
+    /// Mouse button state (with swap / middle-button emulation applied)
+    buttons: RefCell<ButtonMapper>,
+    /// Last absolute pointer position in guest pixels
+    abs_position: RefCell<(u32, u32)>,
+    /// Fractions of a device pixel not yet sent to the guest
+    motion: RefCell<MotionScaler>,
     /// Driver handle (created from fd)
-    handle: RefCell<Option<std::os::unix::io::RawFd>>,
+    handle: RefCell<Option<RawFd>>,
 }
 
 impl Default for InputControllerRust {
     fn default() -> Self {
+        let _timing = startup::span("InputController");
+        actions::register(Action::new("release_capture", "Capture or Release Input", "Input"));
+        actions::register(
+            Action::new("send_ctrl_alt_del", "Send Ctrl+Alt+Del", "Input")
+                .enabled_when(session_gate::is_running),
+        );
         Self {
             keyboard_captured: false,
             mouse_captured: false,
             guest_width: 640,
             guest_height: 480,
             driver_fd: -1,
+            sync_caps_lock: true,
+            sync_num_lock: true,
+            sync_scroll_lock: true,
+            guest_leds: 0,
+            absolute_mode: false,
+            typing: false,
+            device_pixel_ratio: 1.0,
+            typing_stop: Arc::new(AtomicBool::new(false)),
+            typing_thread: RefCell::new(None),
             pressed_keys: RefCell::new(HashSet::new()),
-            button_state: RefCell::new(0),
+            buttons: RefCell::new(ButtonMapper::default()),
+            abs_position: RefCell::new((0, 0)),
+            motion: RefCell::new(MotionScaler::default()),
             handle: RefCell::new(None),
         }
     }
@@ -130,22 +234,47 @@ impl qobject::InputController {
         } else {
             *self.handle.borrow_mut() = None;
         }
+        self.load_mouse_config();
+    }
+
+    /// Reload button mapping settings from the config file
+    pub fn load_mouse_config(self: Pin<&mut Self>) {
+        let mouse = load_config().unwrap_or_default().mouse;
+        let mut buttons = self.buttons.borrow_mut();
+        buttons.swap = mouse.swap_buttons;
+        buttons.emulate_middle = mouse.simulate_middle_button;
+        buttons.window = Duration::from_millis(mouse.middle_button_window_ms as u64);
+        tracing::debug!(
+            "Mouse buttons: swap={} emulate_middle={} window={}ms",
+            mouse.swap_buttons,
+            mouse.simulate_middle_button,
+            mouse.middle_button_window_ms
+        );
     }
 
     /// Toggle keyboard capture
-    pub fn toggle_keyboard_capture(self: Pin<&mut Self>) {
+    pub fn toggle_keyboard_capture(mut self: Pin<&mut Self>) {
         let current = *self.as_ref().keyboard_captured();
+        if current {
+            self.as_mut().read_guest_leds();
+        } else {
+            self.as_mut().sync_leds_to_guest();
+        }
         self.set_keyboard_captured(!current);
     }
 
     /// Toggle mouse capture
     pub fn toggle_mouse_capture(self: Pin<&mut Self>) {
         let current = *self.as_ref().mouse_captured();
+        self.motion.borrow_mut().reset();
         self.set_mouse_captured(!current);
     }
 
     /// Release all capture
     pub fn release_capture(mut self: Pin<&mut Self>) {
+        if *self.as_ref().keyboard_captured() {
+            self.as_mut().read_guest_leds();
+        }
         self.as_mut().set_keyboard_captured(false);
         self.set_mouse_captured(false);
     }
@@ -154,15 +283,9 @@ impl qobject::InputController {
     pub fn handle_key_press(
         mut self: Pin<&mut Self>,
         qt_key: i32,
-        modifiers: i32,
+        _modifiers: i32,
         native_scancode: i32,
     ) -> bool {
-        // Check for release combo (Right Ctrl alone, or Ctrl+Alt)
-        if self.check_release_combo(qt_key, modifiers) {
-            self.as_mut().release_capture();
-            return true;
-        }
-
         // Only process if captured
         if !*self.as_ref().keyboard_captured() {
             return false;
@@ -208,50 +331,78 @@ impl qobject::InputController {
 
     /// Handle mouse button press
     pub fn handle_mouse_press(self: Pin<&mut Self>, button: i32) {
-        if !*self.as_ref().mouse_captured() {
+        if !self.mouse_active() {
             return;
         }
 
-        let mut state = self.button_state.borrow_mut();
-        match button {
-            1 => *state |= mouse_buttons::LEFT,   // Left
-            2 => *state |= mouse_buttons::RIGHT,  // Right
-            4 => *state |= mouse_buttons::MIDDLE, // Middle
-            _ => {}
-        }
+        let changed = {
+            let mut buttons = self.buttons.borrow_mut();
+            let before = buttons.state();
+            buttons.press(button as u32, Instant::now());
+            buttons.state() != before
+        };
 
-        self.send_mouse_event(0, 0, 0);
+        if changed {
+            self.send_mouse_event(0, 0, 0);
+        }
     }
 
     /// Handle mouse button release
     pub fn handle_mouse_release(self: Pin<&mut Self>, button: i32) {
-        if !*self.as_ref().mouse_captured() {
+        if !self.mouse_active() {
             return;
         }
 
-        let mut state = self.button_state.borrow_mut();
-        match button {
-            1 => *state &= !mouse_buttons::LEFT,
-            2 => *state &= !mouse_buttons::RIGHT,
-            4 => *state &= !mouse_buttons::MIDDLE,
-            _ => {}
-        }
+        let changed = {
+            let mut buttons = self.buttons.borrow_mut();
+            let before = buttons.state();
+            buttons.release(button as u32);
+            buttons.state() != before
+        };
 
-        self.send_mouse_event(0, 0, 0);
+        if changed {
+            self.send_mouse_event(0, 0, 0);
+        }
     }
 
     /// Handle mouse movement
-    pub fn handle_mouse_move(self: Pin<&mut Self>, dx: i32, dy: i32) {
+    pub fn handle_mouse_move(self: Pin<&mut Self>, dx: f64, dy: f64) {
         if !*self.as_ref().mouse_captured() {
             return;
         }
 
-        self.send_mouse_event(dx, dy, 0);
+        let ratio = *self.as_ref().device_pixel_ratio();
+        let (dx, dy) = self.motion.borrow_mut().scale(dx, dy, ratio);
+        if dx != 0 || dy != 0 {
+            self.send_mouse_event(dx, dy, 0);
+        }
+    }
+
+    /// Handle absolute mouse movement
+    pub fn handle_mouse_move_absolute(
+        self: Pin<&mut Self>,
+        x: f64,
+        y: f64,
+        view_width: f64,
+        view_height: f64,
+    ) {
+        if !self.absolute_mode {
+            return;
+        }
+
+        let gx = scale_to_guest(x, view_width, self.guest_width);
+        let gy = scale_to_guest(y, view_height, self.guest_height);
+        if *self.abs_position.borrow() == (gx, gy) {
+            return;
+        }
+
+        *self.abs_position.borrow_mut() = (gx, gy);
+        self.send_mouse_event(0, 0, 0);
     }
 
     /// Handle mouse wheel
     pub fn handle_mouse_wheel(self: Pin<&mut Self>, delta: i32) {
-        if !*self.as_ref().mouse_captured() {
+        if !self.mouse_active() {
             return;
         }
 
@@ -299,22 +450,135 @@ impl qobject::InputController {
         self.send_key_event(0x1D, false, false);
     }
 
+    /// Type text into the guest in its keyboard layout
+    pub fn type_text(mut self: Pin<&mut Self>, text: QString, cps: i32) -> i32 {
+        let Some(fd) = *self.handle.borrow() else {
+            return -1;
+        };
+        self.as_mut().cancel_typing();
+
+        let keyboard = load_config().unwrap_or_default().keyboard;
+        if !has_layout(&keyboard.layout) {
+            tracing::warn!("No typing table for keyboard layout {:?}; typing as US", keyboard.layout);
+        }
+        let caps_lock = self.guest_caps_lock();
+        let (groups, skipped) = layout_keystrokes(&text.to_string(), &keyboard.layout, caps_lock);
+        if skipped > 0 {
+            tracing::warn!("{} characters have no key on the guest keyboard and will not be typed", skipped);
+        }
+        if groups.is_empty() {
+            return skipped as i32;
+        }
+
+        let rate = if cps > 0 { cps as u32 } else { keyboard.type_chars_per_sec }.clamp(1, MAX_TYPE_RATE);
+        tracing::info!("Typing {} characters into the guest at {}/s ({} layout)", groups.len(), rate, keyboard.layout);
+        let step = Duration::from_millis((1000 / rate).into());
+        self.start_typing(fd, keymacro::from_keystrokes(&groups, step));
+        skipped as i32
+    }
+
+    /// Type a keystroke macro into the guest in its keyboard layout
+    pub fn type_macro(mut self: Pin<&mut Self>, keys: QString) -> bool {
+        let Some(fd) = *self.handle.borrow() else {
+            return false;
+        };
+        self.as_mut().cancel_typing();
+
+        let keyboard = load_config().unwrap_or_default().keyboard;
+        let rate = keyboard.type_chars_per_sec.clamp(1, MAX_TYPE_RATE);
+        let step = Duration::from_millis((1000 / rate).into());
+        let caps_lock = self.guest_caps_lock();
+        let steps = match macro_keystrokes(&keys.to_string(), &keyboard.layout, caps_lock) {
+            Ok(steps) => steps,
+            Err(e) => {
+                tracing::warn!("Not typing keystroke macro: {}", e);
+                return false;
+            }
+        };
+
+        let strokes = keymacro::from_macro_steps(&steps, step);
+        tracing::info!(
+            "Typing a keystroke macro of {} key strokes into the guest over {:.1} s",
+            strokes.len(),
+            keymacro::duration(&strokes).as_secs_f32()
+        );
+        self.start_typing(fd, strokes);
+        true
+    }
+
+    /// Stop typing into the guest
+    pub fn cancel_typing(self: Pin<&mut Self>) {
+        self.typing_stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.typing_thread.borrow_mut().take() {
+            let _ = thread.join();
+        }
+        self.set_typing(false);
+    }
+
+    /// Notice everything has been typed
+    pub fn poll_typing(self: Pin<&mut Self>) {
+        let finished = self.typing_thread.borrow().as_ref().is_some_and(|thread| thread.is_finished());
+        if finished {
+            self.cancel_typing();
+        }
+    }
+
+    /// Push the host's lock key LED state to the guest
+    pub fn sync_leds_to_guest(mut self: Pin<&mut Self>) {
+        let mask = self.led_sync_mask();
+        if mask == 0 {
+            return;
+        }
+        let Some(driver) = self.driver() else {
+            return;
+        };
+
+        let Some(host) = read_host_leds() else {
+            tracing::debug!("Host LED state unavailable, skipping LED sync");
+            return;
+        };
+
+        let current = driver.get_keyboard_leds().unwrap_or_else(|e| {
+            tracing::debug!("Failed to read guest LEDs: {}", e);
+            0
+        });
+        let leds = merge_leds(current, host, mask);
+        if leds == current {
+            self.as_mut().set_guest_leds(leds as i32);
+            return;
+        }
+
+        match driver.set_keyboard_leds(leds) {
+            Ok(()) => {
+                tracing::debug!("Guest LEDs set to {:#x}", leds);
+                self.as_mut().set_guest_leds(leds as i32);
+            }
+            Err(e) => tracing::warn!("Failed to set guest LEDs: {}", e),
+        }
+    }
+
     // =========================================================================
     // Private helper methods
     // =========================================================================
 
-    /// Check if this key event is the release combo (Right Ctrl)
-    fn check_release_combo(&self, qt_key: i32, _modifiers: i32) -> bool {
-        // Qt::Key_Control is 0x01000021
-        // We check for Right Ctrl specifically
-        // Native scancode for Right Ctrl is 0xE01D (extended 0x1D)
-        if qt_key == 0x01000021 {
-            // For now, any Ctrl release while Ctrl+Alt held releases capture
-            if self.is_release_combo_pressed() {
-                return true;
-            }
+    /// Whether mouse events should be forwarded (captured, or tablet mode)
+    fn mouse_active(&self) -> bool {
+        self.mouse_captured || self.absolute_mode
+    }
+
+    /// Bitmap of LEDs that should be synchronized (keyboard_leds::*)
+    fn led_sync_mask(&self) -> u32 {
+        let mut mask = 0;
+        if self.sync_caps_lock {
+            mask |= keyboard_leds::CAPS_LOCK;
+        }
+        if self.sync_num_lock {
+            mask |= keyboard_leds::NUM_LOCK;
         }
-        false
+        if self.sync_scroll_lock {
+            mask |= keyboard_leds::SCROLL_LOCK;
+        }
+        mask
     }
 
     /// Send a keyboard event to the driver
@@ -340,6 +604,63 @@ impl qobject::InputController {
         }
     }
 
+    /// Driver handle for the LED ioctls, while a session's driver is set
+    fn driver(&self) -> Option<DriverHandle> {
+        if self.driver_fd < 0 {
+            return None;
+        }
+        DriverHandle::open()
+            .map_err(|e| tracing::debug!("Cannot open the driver for keyboard LEDs: {}", e))
+            .ok()
+    }
+
+    /// Record the guest's lock key LED state in guest_leds
+    fn read_guest_leds(self: Pin<&mut Self>) {
+        let Some(driver) = self.driver() else {
+            return;
+        };
+        match driver.get_keyboard_leds() {
+            Ok(leds) => self.set_guest_leds(leds as i32),
+            Err(e) => tracing::debug!("Failed to read guest LEDs: {}", e),
+        }
+    }
+
+    /// Whether Caps Lock is on in the guest, which typing has to undo
+    fn guest_caps_lock(&self) -> bool {
+        let leds = self
+            .driver()
+            .and_then(|driver| driver.get_keyboard_leds().ok())
+            .unwrap_or(*self.guest_leds() as u32);
+        leds & keyboard_leds::CAPS_LOCK != 0
+    }
+
+    /// Play keys into the guest on a thread, so a slow typing rate or a
+    /// macro's pauses don't hold up the UI
+    fn start_typing(mut self: Pin<&mut Self>, fd: RawFd, keys: Vec<MacroKey>) {
+        self.typing_stop.store(false, Ordering::SeqCst);
+        let stop = Arc::clone(&self.typing_stop);
+        *self.typing_thread.borrow_mut() = Some(std::thread::spawn(move || {
+            let send = |key: &MacroKey| -> anyhow::Result<()> {
+                let mut flags = if key.pressed { key_flags::PRESSED } else { 0 };
+                if key.extended {
+                    flags |= key_flags::EXTENDED;
+                }
+                let event = KeyEvent { scancode: key.scancode, flags };
+                unsafe {
+                    use rising_sun_common::ioctl::sunpci_keyboard_event;
+                    sunpci_keyboard_event(fd, &event)?;
+                }
+                Ok(())
+            };
+            match keymacro::play(&keys, &stop, send) {
+                Ok(true) => tracing::info!("Finished typing into the guest"),
+                Ok(false) => tracing::info!("Stopped typing into the guest"),
+                Err(e) => tracing::warn!("Typing into the guest failed: {}", e),
+            }
+        }));
+        self.as_mut().set_typing(true);
+    }
+
     /// Send a mouse event to the driver
     fn send_mouse_event(&self, dx: i32, dy: i32, dz: i32) {
         let fd = match *self.handle.borrow() {
@@ -347,7 +668,18 @@ impl qobject::InputController {
             None => return,
         };
 
-        let buttons = *self.button_state.borrow();
+        let buttons = self.buttons.borrow().state();
+
+        if self.absolute_mode {
+            let (x, y) = *self.abs_position.borrow();
+            let event = MouseAbsEvent { x, y, dz, buttons };
+            unsafe {
+                use rising_sun_common::ioctl::sunpci_mouse_abs_event;
+                let _ = sunpci_mouse_abs_event(fd, &event);
+            }
+            return;
+        }
+
         let event = MouseEvent { dx, dy, dz, buttons };
 
         unsafe {
@@ -358,155 +690,186 @@ impl qobject::InputController {
 }
 
 // =============================================================================
-// Qt Key to XT Scancode Conversion
+// Mouse Button Mapping
+// =============================================================================
+
+/// Translates host button presses into guest button state, applying
+/// left/right swapping and left+right chord to middle-button emulation.
+///
+/// A chord is recognized when the second of left/right is pressed within
+/// `window` of the first; the middle button is then held until both are up.
+struct ButtonMapper {
+    /// Swap left and right buttons
+    swap: bool,
+    /// Synthesize middle clicks from left+right chords
+    emulate_middle: bool,
+    /// Maximum delay between the two presses of a chord
+    window: Duration,
+    /// Buttons physically held (after swapping)
+    physical: u32,
+    /// First half of a potential chord and when it was pressed
+    pending: Option<(u32, Instant)>,
+    /// Whether a chord is currently being reported as middle
+    chord: bool,
+}
+
+impl Default for ButtonMapper {
+    fn default() -> Self {
+        Self {
+            swap: false,
+            emulate_middle: false,
+            window: Duration::from_millis(50),
+            physical: 0,
+            pending: None,
+            chord: false,
+        }
+    }
+}
+
+impl ButtonMapper {
+    /// Apply button swapping (button is a mouse_buttons::* flag)
+    fn map(&self, button: u32) -> u32 {
+        match button {
+            mouse_buttons::LEFT if self.swap => mouse_buttons::RIGHT,
+            mouse_buttons::RIGHT if self.swap => mouse_buttons::LEFT,
+            other => other & (mouse_buttons::LEFT | mouse_buttons::RIGHT | mouse_buttons::MIDDLE),
+        }
+    }
+
+    /// Record a button press
+    fn press(&mut self, button: u32, now: Instant) {
+        let b = self.map(button);
+        self.physical |= b;
+
+        let left_right = mouse_buttons::LEFT | mouse_buttons::RIGHT;
+        if self.emulate_middle && b & left_right != 0 && !self.chord {
+            let other = b ^ left_right;
+            match self.pending.take() {
+                Some((first, at))
+                    if first == other
+                        && self.physical & other != 0
+                        && now.duration_since(at) <= self.window =>
+                {
+                    self.chord = true;
+                }
+                _ => self.pending = Some((b, now)),
+            }
+        }
+    }
+
+    /// Record a button release
+    fn release(&mut self, button: u32) {
+        let b = self.map(button);
+        self.physical &= !b;
+
+        if matches!(self.pending, Some((first, _)) if first == b) {
+            self.pending = None;
+        }
+        if self.chord && self.physical & (mouse_buttons::LEFT | mouse_buttons::RIGHT) == 0 {
+            self.chord = false;
+        }
+    }
+
+    /// Button bitmap to report to the guest
+    fn state(&self) -> u32 {
+        if self.chord {
+            (self.physical & mouse_buttons::MIDDLE) | mouse_buttons::MIDDLE
+        } else {
+            self.physical
+        }
+    }
+}
+
+/// Scale a view coordinate to a guest pixel coordinate, clamped to the screen
+fn scale_to_guest(pos: f64, view_size: f64, guest_size: i32) -> u32 {
+    if view_size <= 0.0 || guest_size <= 0 {
+        return 0;
+    }
+    let max = (guest_size - 1) as f64;
+    (pos * guest_size as f64 / view_size).floor().clamp(0.0, max) as u32
+}
+
+// =============================================================================
+// Host LED Access
 // =============================================================================
 
-/// Convert a Qt key code to an XT scancode.
-/// Returns (scancode, is_extended).
-/// Returns (0, false) if the key is not mappable.
-fn qt_key_to_scancode(qt_key: i32, native_scancode: i32) -> (u32, bool) {
-    // If we have a valid native scancode from the system, prefer it
-    // Linux evdev scancodes are offset by 8 from XT scancodes
-    if native_scancode > 8 {
-        let xt = (native_scancode - 8) as u32;
-        // Extended keys have high bit set in certain ranges
-        let extended = xt > 0x7F || is_extended_key(qt_key);
-        return (xt & 0x7F, extended);
-    }
-
-    // Fall back to Qt key code mapping
-    qt_key_to_xt_scancode(qt_key)
+/// Fastest text is typed into the guest, in characters per second
+const MAX_TYPE_RATE: u32 = 1000;
+
+/// sysfs LED name suffixes and their keyboard_leds bit
+const HOST_LEDS: [(&str, u32); 3] = [
+    ("::capslock", keyboard_leds::CAPS_LOCK),
+    ("::numlock", keyboard_leds::NUM_LOCK),
+    ("::scrolllock", keyboard_leds::SCROLL_LOCK),
+];
+
+/// Replace the bits in `mask` of `current` with those from `desired`
+fn merge_leds(current: u32, desired: u32, mask: u32) -> u32 {
+    (current & !mask) | (desired & mask)
 }
 
-/// Check if a Qt key is an extended key
-fn is_extended_key(qt_key: i32) -> bool {
-    matches!(
-        qt_key,
-        0x01000010  // Key_Home
-        | 0x01000011  // Key_End
-        | 0x01000012  // Key_Left
-        | 0x01000013  // Key_Up
-        | 0x01000014  // Key_Right
-        | 0x01000015  // Key_Down
-        | 0x01000016  // Key_PageUp
-        | 0x01000017  // Key_PageDown
-        | 0x01000006  // Key_Insert
-        | 0x01000007  // Key_Delete
-        | 0x01000025  // Key_Print
-        | 0x01000026  // Key_ScrollLock (sometimes)
-        | 0x01000027  // Key_Pause
-    )
+/// Read the host keyboard LED state from /sys/class/leds
+///
+/// An LED counts as lit if any keyboard exposing it has it lit.
+/// Returns None if no keyboard LEDs are exposed.
+fn read_host_leds() -> Option<u32> {
+    let entries = std::fs::read_dir("/sys/class/leds").ok()?;
+    let mut found = false;
+    let mut leds = 0u32;
+
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        for (suffix, bit) in HOST_LEDS {
+            if name.ends_with(suffix) {
+                found = true;
+                let lit = std::fs::read_to_string(entry.path().join("brightness"))
+                    .map(|s| s.trim() != "0")
+                    .unwrap_or(false);
+                if lit {
+                    leds |= bit;
+                }
+            }
+        }
+    }
+
+    found.then_some(leds)
 }
 
-/// Map Qt key constants to XT scancodes
-/// This is a fallback when native scancodes aren't available
-fn qt_key_to_xt_scancode(qt_key: i32) -> (u32, bool) {
-    // Qt key constants (from Qt::Key enum)
-    match qt_key {
-        // Escape
-        0x01000000 => (0x01, false), // Key_Escape
-        
-        // Function keys
-        0x01000030 => (0x3B, false), // Key_F1
-        0x01000031 => (0x3C, false), // Key_F2
-        0x01000032 => (0x3D, false), // Key_F3
-        0x01000033 => (0x3E, false), // Key_F4
-        0x01000034 => (0x3F, false), // Key_F5
-        0x01000035 => (0x40, false), // Key_F6
-        0x01000036 => (0x41, false), // Key_F7
-        0x01000037 => (0x42, false), // Key_F8
-        0x01000038 => (0x43, false), // Key_F9
-        0x01000039 => (0x44, false), // Key_F10
-        0x0100003a => (0x57, false), // Key_F11
-        0x0100003b => (0x58, false), // Key_F12
-
-        // Number row
-        0x31 => (0x02, false), // 1
-        0x32 => (0x03, false), // 2
-        0x33 => (0x04, false), // 3
-        0x34 => (0x05, false), // 4
-        0x35 => (0x06, false), // 5
-        0x36 => (0x07, false), // 6
-        0x37 => (0x08, false), // 7
-        0x38 => (0x09, false), // 8
-        0x39 => (0x0A, false), // 9
-        0x30 => (0x0B, false), // 0
-        0x2D => (0x0C, false), // -
-        0x3D => (0x0D, false), // =
-
-        // Backspace, Tab, Enter
-        0x01000003 => (0x0E, false), // Key_Backspace
-        0x01000001 => (0x0F, false), // Key_Tab
-        0x01000004 | 0x01000005 => (0x1C, false), // Key_Return / Key_Enter
-
-        // Letter keys (uppercase ASCII)
-        0x51 => (0x10, false), // Q
-        0x57 => (0x11, false), // W
-        0x45 => (0x12, false), // E
-        0x52 => (0x13, false), // R
-        0x54 => (0x14, false), // T
-        0x59 => (0x15, false), // Y
-        0x55 => (0x16, false), // U
-        0x49 => (0x17, false), // I
-        0x4F => (0x18, false), // O
-        0x50 => (0x19, false), // P
-        0x5B => (0x1A, false), // [
-        0x5D => (0x1B, false), // ]
-
-        0x41 => (0x1E, false), // A
-        0x53 => (0x1F, false), // S
-        0x44 => (0x20, false), // D
-        0x46 => (0x21, false), // F
-        0x47 => (0x22, false), // G
-        0x48 => (0x23, false), // H
-        0x4A => (0x24, false), // J
-        0x4B => (0x25, false), // K
-        0x4C => (0x26, false), // L
-        0x3B => (0x27, false), // ;
-        0x27 => (0x28, false), // '
-        0x60 => (0x29, false), // ` (backtick)
-        0x5C => (0x2B, false), // \
-
-        0x5A => (0x2C, false), // Z
-        0x58 => (0x2D, false), // X
-        0x43 => (0x2E, false), // C
-        0x56 => (0x2F, false), // V
-        0x42 => (0x30, false), // B
-        0x4E => (0x31, false), // N
-        0x4D => (0x32, false), // M
-        0x2C => (0x33, false), // ,
-        0x2E => (0x34, false), // .
-        0x2F => (0x35, false), // /
-
-        // Modifier keys
-        0x01000020 => (0x2A, false), // Key_Shift (left)
-        0x01000021 => (0x1D, false), // Key_Control (left)
-        0x01000023 => (0x38, false), // Key_Alt (left)
-        0x01000022 => (0x3A, false), // Key_CapsLock
-        
-        // Space
-        0x20 => (0x39, false), // Space
-
-        // Navigation keys (extended)
-        0x01000010 => (0x47, true), // Key_Home
-        0x01000011 => (0x4F, true), // Key_End
-        0x01000016 => (0x49, true), // Key_PageUp
-        0x01000017 => (0x51, true), // Key_PageDown
-        0x01000012 => (0x4B, true), // Key_Left
-        0x01000014 => (0x4D, true), // Key_Right
-        0x01000013 => (0x48, true), // Key_Up
-        0x01000015 => (0x50, true), // Key_Down
-        0x01000006 => (0x52, true), // Key_Insert
-        0x01000007 => (0x53, true), // Key_Delete
-
-        // Numpad
-        0x01000024 => (0x45, false), // Key_NumLock
-        
-        // Print Screen, Scroll Lock, Pause
-        0x01000025 => (0x37, true),  // Key_Print (SysRq)
-        0x01000026 => (0x46, false), // Key_ScrollLock
-        0x01000027 => (0x45, true),  // Key_Pause
-
-        _ => (0, false), // Unknown key
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_button_swap() {
+        let mut mapper = ButtonMapper { swap: true, ..Default::default() };
+        mapper.press(mouse_buttons::LEFT, Instant::now());
+        assert_eq!(mapper.state(), mouse_buttons::RIGHT);
+        mapper.release(mouse_buttons::LEFT);
+        assert_eq!(mapper.state(), 0);
+    }
+
+    #[test]
+    fn test_middle_button_chord() {
+        let mut mapper = ButtonMapper { emulate_middle: true, ..Default::default() };
+        let now = Instant::now();
+        mapper.press(mouse_buttons::LEFT, now);
+        assert_eq!(mapper.state(), mouse_buttons::LEFT);
+        mapper.press(mouse_buttons::RIGHT, now + Duration::from_millis(10));
+        assert_eq!(mapper.state(), mouse_buttons::MIDDLE);
+
+        // Middle stays held until both buttons are released
+        mapper.release(mouse_buttons::LEFT);
+        assert_eq!(mapper.state(), mouse_buttons::MIDDLE);
+        mapper.release(mouse_buttons::RIGHT);
+        assert_eq!(mapper.state(), 0);
+    }
+
+    #[test]
+    fn test_middle_button_chord_outside_window() {
+        let mut mapper = ButtonMapper { emulate_middle: true, ..Default::default() };
+        let now = Instant::now();
+        mapper.press(mouse_buttons::LEFT, now);
+        mapper.press(mouse_buttons::RIGHT, now + Duration::from_millis(200));
+        assert_eq!(mapper.state(), mouse_buttons::LEFT | mouse_buttons::RIGHT);
     }
 }
@@ -0,0 +1,79 @@
+//! Capture of the frontend's tracing output for the log console.
+//!
+//! main installs `CaptureLayer` next to the stderr output. It queues each
+//! event as a session log line; LogController collects them with `take`
+//! and merges them with the driver's messages. Until it does, the queue
+//! keeps only the most recent lines.
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::Mutex;
+
+use rising_sun_common::history::now_ms;
+use rising_sun_common::session_log::{Level, LogLine, Source};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+/// Lines queued before the oldest are dropped
+const MAX_QUEUED: usize = 2000;
+
+static QUEUE: Mutex<VecDeque<LogLine>> = Mutex::new(VecDeque::new());
+
+/// Tracing layer queueing events for the log console
+pub struct CaptureLayer;
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = MessageVisitor::default();
+        event.record(&mut message);
+
+        let metadata = event.metadata();
+        let level = match *metadata.level() {
+            tracing::Level::ERROR => Level::Error,
+            tracing::Level::WARN => Level::Warn,
+            tracing::Level::INFO => Level::Info,
+            tracing::Level::DEBUG => Level::Debug,
+            tracing::Level::TRACE => Level::Trace,
+        };
+        // "rising_sun_frontend::ui::session_controller" -> "session_controller"
+        let target = metadata.target().rsplit("::").next().unwrap_or_default();
+        let line = LogLine::new(now_ms(), level, Source::Frontend, target, &message.text);
+
+        let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+        if queue.len() == MAX_QUEUED {
+            queue.pop_front();
+        }
+        queue.push_back(line);
+    }
+}
+
+/// Take the lines captured since the last call, oldest first
+pub fn take() -> Vec<LogLine> {
+    QUEUE.lock().unwrap_or_else(|e| e.into_inner()).drain(..).collect()
+}
+
+/// The message of an event, followed by its other fields as key=value
+#[derive(Default)]
+struct MessageVisitor {
+    text: String,
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let fields = std::mem::take(&mut self.text);
+            let _ = write!(self.text, "{:?}{}", value, fields);
+        } else {
+            let _ = write!(self.text, " {}={:?}", field.name(), value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.text.insert_str(0, value);
+        } else {
+            let _ = write!(self.text, " {}={}", field.name(), value);
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! Log controller Qt bridge for the session log console.
+//!
+//! A QML timer calls `poll`, which collects the frontend's captured
+//! tracing output (see log_capture) and the messages the selected card's
+//! driver logged since the last poll into one session log (see
+//! `rising_sun_common::session_log`). The console shows the lines the
+//! filter lets through: `apply_filter` returns all of them, and
+//! lines_added reports the ones added by each poll.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, driver_available)]
+        type LogController = super::LogControllerRust;
+
+        /// Collect new frontend and driver lines
+        #[qinvokable]
+        fn poll(self: Pin<&mut LogController>);
+
+        /// Show lines at `level` ("error", "warn", "info", "debug" or
+        /// "trace") or more severe, from `source` ("all", "driver" or
+        /// "frontend"), containing `text`. Returns the lines shown as a
+        /// JSON array of {id, time_ms, level, source, target, message}.
+        #[qinvokable]
+        fn apply_filter(self: &LogController, level: QString, source: QString, text: QString) -> QString;
+
+        /// Drop every line collected so far
+        #[qinvokable]
+        fn clear(self: &LogController);
+
+        /// Write the lines shown to a text file
+        #[qinvokable]
+        fn export_log(self: &LogController, path: QString) -> bool;
+
+        /// Lines the filter shows were added (JSON as from apply_filter)
+        #[qsignal]
+        fn lines_added(self: Pin<&mut LogController>, lines: QString);
+    }
+}
+
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+use rising_sun_common::driver;
+use rising_sun_common::session_log::{DriverLogTail, Level, LogFilter, SessionLog, Source};
+use rising_sun_common::{is_driver_loaded, DriverHandle};
+
+use super::actions::{self, Action};
+use super::log_capture;
+use super::startup;
+
+/// Rust implementation of the LogController
+pub struct LogControllerRust {
+    /// Whether driver messages are being collected
+    driver_available: bool,
+    /// Lines of both sources
+    log: RefCell<SessionLog>,
+    /// Which lines the console shows
+    filter: RefCell<LogFilter>,
+    /// Id of the newest line the console has been given
+    shown_through: Cell<u64>,
+    /// Handle to the card whose log is followed, with its index
+    handle: RefCell<Option<(u32, DriverHandle)>>,
+    /// Position in the driver's log
+    tail: RefCell<DriverLogTail>,
+    /// Card whose driver cannot be read (too old for READ_LOG)
+    unsupported_card: Cell<Option<u32>>,
+}
+
+impl Default for LogControllerRust {
+    fn default() -> Self {
+        let _timing = startup::span("LogController");
+        actions::register(Action::new("log_console", "Log Console...", "View"));
+        Self {
+            driver_available: false,
+            log: RefCell::new(SessionLog::default()),
+            filter: RefCell::new(LogFilter::default()),
+            shown_through: Cell::new(0),
+            handle: RefCell::new(None),
+            tail: RefCell::new(DriverLogTail::new()),
+            unsupported_card: Cell::new(None),
+        }
+    }
+}
+
+impl qobject::LogController {
+    /// Collect new frontend and driver lines
+    pub fn poll(mut self: Pin<&mut Self>) {
+        {
+            let mut log = self.log.borrow_mut();
+            for line in log_capture::take() {
+                log.push(line);
+            }
+        }
+        let available = self.poll_driver();
+        if *self.as_ref().driver_available() != available {
+            self.as_mut().set_driver_available(available);
+        }
+
+        let (json, last_id) = {
+            let log = self.log.borrow();
+            let filter = self.filter.borrow();
+            let added: Vec<_> = log.filtered_after(self.shown_through.get(), &filter).collect();
+            let json = (!added.is_empty())
+                .then(|| serde_json::to_string(&added).unwrap_or_else(|_| "[]".to_string()));
+            (json, log.last_id())
+        };
+        self.shown_through.set(last_id);
+        if let Some(json) = json {
+            self.as_mut().lines_added(QString::from(&json));
+        }
+    }
+
+    /// Read the selected card's new driver messages into the log; false
+    /// if there is no driver log to read
+    fn poll_driver(&self) -> bool {
+        let card = driver::selected_card();
+        if !is_driver_loaded() || self.unsupported_card.get() == Some(card) {
+            self.handle.borrow_mut().take();
+            return false;
+        }
+
+        // Follow the card the session controller switched to
+        let mut handle = self.handle.borrow_mut();
+        if handle.as_ref().is_none_or(|(index, _)| *index != card) {
+            match DriverHandle::open() {
+                Ok(opened) => {
+                    *handle = Some((card, opened));
+                    *self.tail.borrow_mut() = DriverLogTail::new();
+                }
+                Err(e) => {
+                    tracing::debug!("Cannot open the driver for its log: {}", e);
+                    *handle = None;
+                    return false;
+                }
+            }
+        }
+        let Some((_, opened)) = handle.as_ref() else {
+            return false;
+        };
+
+        match self.tail.borrow_mut().poll(opened) {
+            Ok(lines) => {
+                let mut log = self.log.borrow_mut();
+                for line in lines {
+                    log.push(line);
+                }
+                true
+            }
+            Err(e) => {
+                tracing::info!("Driver log not available: {}", e);
+                self.unsupported_card.set(Some(card));
+                *handle = None;
+                false
+            }
+        }
+    }
+
+    /// Change the filter, returning every line it shows
+    pub fn apply_filter(&self, level: QString, source: QString, text: QString) -> QString {
+        let filter = LogFilter {
+            level: Level::parse(&level.to_string()).unwrap_or(Level::Info),
+            source: match source.to_string().as_str() {
+                "driver" => Some(Source::Driver),
+                "frontend" => Some(Source::Frontend),
+                _ => None,
+            },
+            text: text.to_string(),
+        };
+        let log = self.log.borrow();
+        let shown: Vec<_> = log.filtered(&filter).collect();
+        let json = serde_json::to_string(&shown).unwrap_or_else(|_| "[]".to_string());
+        self.shown_through.set(log.last_id());
+        *self.filter.borrow_mut() = filter;
+        QString::from(&json)
+    }
+
+    /// Drop every line collected so far
+    pub fn clear(&self) {
+        self.log.borrow_mut().clear();
+    }
+
+    /// Write the lines shown to a text file
+    pub fn export_log(&self, path: QString) -> bool {
+        let path = path.to_string();
+        match self.log.borrow().export(Path::new(&path), &self.filter.borrow()) {
+            Ok(count) => {
+                tracing::info!("Exported {} log lines to {}", count, path);
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Cannot export the log to {}: {}", path, e);
+                false
+            }
+        }
+    }
+}
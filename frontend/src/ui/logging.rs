@@ -0,0 +1,114 @@
+//! The frontend's tracing subscriber.
+//!
+//! Events go to stderr and, with `[log] file` set, to a rotating log file
+//! in the log directory (see `log_file`), both at the configured level;
+//! `--verbose` raises it to debug for one run, twice to trace. The log
+//! console's capture layer (see `log_capture`) always keeps debug events.
+//!
+//! The level can be changed while running with `set_level`, which
+//! ConfigManager.set_log_level exposes to the UI. Whether a log file is
+//! written only changes at the next start.
+
+use std::sync::Mutex;
+
+use rising_sun_common::log_file::RotatingFile;
+use rising_sun_common::session_log::Level;
+use rising_sun_common::{AppConfig, LogConfig};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+use super::log_capture;
+
+/// Changes the level of the stderr and file output
+static LEVEL: Mutex<Option<reload::Handle<LevelFilter, Registry>>> = Mutex::new(None);
+
+/// Filter for a level name, None if it is not one
+fn level_filter(name: &str) -> Option<LevelFilter> {
+    Some(match Level::parse(name)? {
+        Level::Error => LevelFilter::ERROR,
+        Level::Warn => LevelFilter::WARN,
+        Level::Info => LevelFilter::INFO,
+        Level::Debug => LevelFilter::DEBUG,
+        Level::Trace => LevelFilter::TRACE,
+    })
+}
+
+/// Install the subscriber; `verbose` counts the --verbose flags given
+pub fn init(config: &LogConfig, verbose: u8) {
+    let configured = level_filter(&config.level);
+    let level = match verbose {
+        0 => configured.unwrap_or(LevelFilter::INFO),
+        1 => LevelFilter::DEBUG.max(configured.unwrap_or(LevelFilter::INFO)),
+        _ => LevelFilter::TRACE,
+    };
+
+    let mut file_error = None;
+    let file = if config.file {
+        match RotatingFile::from_config(&AppConfig::log_dir(), config) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                file_error = Some(e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let file_path = file.as_ref().map(|f| f.path().to_path_buf());
+    // Structured for reading back: targets and thread names, no colors
+    let file_layer = file.map(|file| {
+        fmt::layer()
+            .with_ansi(false)
+            .with_thread_names(true)
+            .with_writer(Mutex::new(file))
+    });
+
+    let (filter, handle) = reload::Layer::new(level);
+    tracing_subscriber::registry()
+        .with(
+            fmt::layer()
+                .with_writer(std::io::stderr)
+                .and_then(file_layer)
+                .with_filter(filter),
+        )
+        .with(log_capture::CaptureLayer.with_filter(LevelFilter::DEBUG))
+        .init();
+    *LEVEL.lock().unwrap_or_else(|e| e.into_inner()) = Some(handle);
+
+    if configured.is_none() {
+        tracing::warn!("Unknown log level {:?} in the configuration", config.level);
+    }
+    if let Some(path) = file_path {
+        tracing::info!("Logging to {}", path.display());
+    }
+    if let Some(e) = file_error {
+        tracing::warn!("Cannot write the log file: {:#}", e);
+    }
+}
+
+/// Log at `level` ("error" to "trace") from now on; false if it is not one
+pub fn set_level(level: &str) -> bool {
+    let Some(filter) = level_filter(level) else {
+        return false;
+    };
+    let handle = LEVEL.lock().unwrap_or_else(|e| e.into_inner());
+    match handle.as_ref().map(|h| h.reload(filter)) {
+        Some(Ok(())) => {
+            tracing::info!("Log level set to {}", filter);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// The level logged at now ("info" if the subscriber is not installed)
+pub fn level() -> String {
+    LEVEL
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .and_then(|h| h.clone_current())
+        .map(|filter| filter.to_string().to_lowercase())
+        .unwrap_or_else(|| "info".to_string())
+}
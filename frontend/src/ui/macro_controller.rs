@@ -0,0 +1,278 @@
+//! Macro controller Qt bridge for recorded keystroke macros.
+//!
+//! This module handles:
+//! - Recording the keys sent to the guest while the keyboard is captured,
+//!   with their timing (see `rising_sun_common::keymacro`)
+//! - Keeping recordings by name in the configuration (`[[macros]]`)
+//! - Playing a macro back into the guest on a thread of its own
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use rising_sun_common::ioctl::{key_flags, KeyEvent};
+use rising_sun_common::keymacro::{self, MacroRecorder};
+use rising_sun_common::scancode::qt_key_to_scancode;
+use rising_sun_common::{load_config, save_config, DriverHandle, KeyMacro, MacroKey};
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, recording)]
+        #[qproperty(i32, recorded_keys)]
+        #[qproperty(bool, playing)]
+        #[qproperty(QString, playing_name)]
+        type MacroController = super::MacroControllerRust;
+
+        /// Start recording the keys sent to the guest
+        #[qinvokable]
+        fn start_recording(self: Pin<&mut MacroController>);
+
+        /// Note a key sent to the guest (called from the key handlers while
+        /// recording)
+        #[qinvokable]
+        fn record_key(self: Pin<&mut MacroController>, qt_key: i32, native_scancode: i32, pressed: bool);
+
+        /// Stop recording and keep the macro under `name`, replacing one of
+        /// that name; false (keeping nothing) if it is empty
+        #[qinvokable]
+        fn stop_recording(self: Pin<&mut MacroController>, name: QString) -> bool;
+
+        /// Stop recording and throw the recording away
+        #[qinvokable]
+        fn cancel_recording(self: Pin<&mut MacroController>);
+
+        /// Saved macros, as a JSON array of {"name", "keys", "seconds"}
+        #[qinvokable]
+        fn list_macros(self: &MacroController) -> QString;
+
+        /// Type the macro called `name` into the guest
+        #[qinvokable]
+        fn play(self: Pin<&mut MacroController>, name: QString) -> bool;
+
+        /// Stop the macro being played
+        #[qinvokable]
+        fn stop_playing(self: Pin<&mut MacroController>);
+
+        /// Delete the macro called `name`
+        #[qinvokable]
+        fn delete_macro(self: &MacroController, name: QString) -> bool;
+
+        /// Notice a macro that has finished playing (called by a timer
+        /// while playing)
+        #[qinvokable]
+        fn poll_status(self: Pin<&mut MacroController>);
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
+/// Rust implementation of the MacroController
+pub struct MacroControllerRust {
+    /// Whether keys are being recorded
+    recording: bool,
+    /// Key presses and releases recorded so far
+    recorded_keys: i32,
+    /// Whether a macro is being played
+    playing: bool,
+    /// Name of the macro being played
+    playing_name: QString,
+    /// Recording in progress
+    recorder: RefCell<Option<MacroRecorder>>,
+    /// Set to stop the macro being played
+    stop: Arc<AtomicBool>,
+    /// Thread playing the macro
+    player: RefCell<Option<std::thread::JoinHandle<()>>>,
+}
+
+impl Default for MacroControllerRust {
+    fn default() -> Self {
+        Self {
+            recording: false,
+            recorded_keys: 0,
+            playing: false,
+            playing_name: QString::default(),
+            recorder: RefCell::new(None),
+            stop: Arc::new(AtomicBool::new(false)),
+            player: RefCell::new(None),
+        }
+    }
+}
+
+impl qobject::MacroController {
+    /// Start recording the keys sent to the guest
+    pub fn start_recording(mut self: Pin<&mut Self>) {
+        tracing::info!("Recording a keystroke macro");
+        *self.recorder.borrow_mut() = Some(MacroRecorder::new());
+        self.as_mut().set_recorded_keys(0);
+        self.set_recording(true);
+    }
+
+    /// Note a key sent to the guest
+    pub fn record_key(self: Pin<&mut Self>, qt_key: i32, native_scancode: i32, pressed: bool) {
+        let (scancode, extended) = qt_key_to_scancode(qt_key, native_scancode);
+        if scancode == 0 {
+            return;
+        }
+        let count = {
+            let mut recorder = self.recorder.borrow_mut();
+            let Some(recorder) = recorder.as_mut() else {
+                return;
+            };
+            recorder.record(scancode, extended, pressed, Instant::now());
+            recorder.len()
+        };
+        self.set_recorded_keys(count as i32);
+    }
+
+    /// Stop recording and keep the macro
+    pub fn stop_recording(mut self: Pin<&mut Self>, name: QString) -> bool {
+        let recorder = self.recorder.borrow_mut().take();
+        self.as_mut().set_recording(false);
+        let name = name.to_string().trim().to_string();
+        let Some(recorder) = recorder.filter(|r| !r.is_empty() && !name.is_empty()) else {
+            tracing::info!("Keystroke macro recording discarded");
+            return false;
+        };
+
+        let keys = recorder.finish();
+        let mut config = load_config().unwrap_or_default();
+        config.macros.retain(|m| m.name != name);
+        tracing::info!(
+            "Saving keystroke macro \"{}\": {} keys, {:.1} s",
+            name,
+            keys.len(),
+            keymacro::duration(&keys).as_secs_f32()
+        );
+        config.macros.push(KeyMacro { name, keys });
+        match save_config(&config) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to save keystroke macro: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Stop recording and throw the recording away
+    pub fn cancel_recording(mut self: Pin<&mut Self>) {
+        self.recorder.borrow_mut().take();
+        self.as_mut().set_recorded_keys(0);
+        self.set_recording(false);
+    }
+
+    /// Saved macros
+    pub fn list_macros(&self) -> QString {
+        #[derive(serde::Serialize)]
+        struct Entry {
+            name: String,
+            keys: usize,
+            seconds: f32,
+        }
+
+        let macros: Vec<Entry> = load_config()
+            .unwrap_or_default()
+            .macros
+            .into_iter()
+            .map(|m| Entry {
+                seconds: keymacro::duration(&m.keys).as_secs_f32(),
+                keys: m.keys.len(),
+                name: m.name,
+            })
+            .collect();
+        QString::from(&serde_json::to_string(&macros).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Type a macro into the guest
+    pub fn play(mut self: Pin<&mut Self>, name: QString) -> bool {
+        let name = name.to_string();
+        let Some(keys) = load_config()
+            .unwrap_or_default()
+            .macros
+            .into_iter()
+            .find(|m| m.name == name)
+            .map(|m| m.keys)
+        else {
+            tracing::warn!("No keystroke macro called \"{}\"", name);
+            return false;
+        };
+
+        self.as_mut().stop_playing();
+        let handle = match DriverHandle::open() {
+            Ok(handle) => handle,
+            Err(e) => {
+                tracing::warn!("Cannot play keystroke macro: {}", e);
+                return false;
+            }
+        };
+
+        tracing::info!("Playing keystroke macro \"{}\"", name);
+        self.stop.store(false, Ordering::SeqCst);
+        let stop = Arc::clone(&self.stop);
+        *self.player.borrow_mut() = Some(std::thread::spawn(move || {
+            let send = |key: &MacroKey| {
+                let mut flags = 0;
+                if key.pressed {
+                    flags |= key_flags::PRESSED;
+                }
+                if key.extended {
+                    flags |= key_flags::EXTENDED;
+                }
+                handle.send_key_event(&KeyEvent { scancode: key.scancode, flags })
+            };
+            match keymacro::play(&keys, &stop, send) {
+                Ok(true) => tracing::info!("Keystroke macro played"),
+                Ok(false) => tracing::info!("Keystroke macro stopped"),
+                Err(e) => tracing::warn!("Keystroke macro failed: {}", e),
+            }
+        }));
+        self.as_mut().set_playing_name(QString::from(&name));
+        self.set_playing(true);
+        true
+    }
+
+    /// Stop the macro being played
+    pub fn stop_playing(mut self: Pin<&mut Self>) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(player) = self.player.borrow_mut().take() {
+            let _ = player.join();
+        }
+        self.as_mut().set_playing_name(QString::default());
+        self.set_playing(false);
+    }
+
+    /// Delete a macro
+    pub fn delete_macro(&self, name: QString) -> bool {
+        let name = name.to_string();
+        let mut config = load_config().unwrap_or_default();
+        let before = config.macros.len();
+        config.macros.retain(|m| m.name != name);
+        if config.macros.len() == before {
+            return false;
+        }
+        match save_config(&config) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!("Failed to delete keystroke macro: {}", e);
+                false
+            }
+        }
+    }
+
+    /// Notice a macro that has finished playing
+    pub fn poll_status(self: Pin<&mut Self>) {
+        let finished = self.player.borrow().as_ref().is_some_and(|player| player.is_finished());
+        if finished {
+            self.stop_playing();
+        }
+    }
+}
@@ -1,4 +1,22 @@
 //! Main window Qt object bridge.
+//!
+//! Keeps the window's fullscreen state. QML shows the window fullscreen
+//! or normal when `fullscreen` changes, and reports changes made by the
+//! window manager back through `sync_fullscreen`. `menu_visible` hides the
+//! menu bar in fullscreen when the configuration asks for it.
+//!
+//! At startup `place_window` picks the screen and geometry the window is
+//! restored to (see `placement`), and `fullscreen_screen` the screen it
+//! goes fullscreen on. QML passes the screens in as JSON.
+//!
+//! The window's first frame ends the startup profile (`startup_finished`).
+
+use rising_sun_common::placement::{self, ScreenInfo};
+use rising_sun_common::{load_config, save_config};
+
+use super::actions::{self, Action};
+use super::run_options;
+use super::startup;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -6,12 +24,218 @@ mod qobject {
         #[qobject]
         #[qml_element]
         #[qproperty(bool, session_running)]
+        #[qproperty(bool, fullscreen)]
+        #[qproperty(bool, start_fullscreen)]
+        #[qproperty(bool, headless)]
+        #[qproperty(bool, hide_menu_in_fullscreen)]
+        #[qproperty(bool, menu_visible)]
+        #[qproperty(bool, window_maximized)]
+        #[qproperty(i32, fullscreen_screen)]
+        #[qproperty(i32, place_screen)]
+        #[qproperty(i32, place_x)]
+        #[qproperty(i32, place_y)]
+        #[qproperty(i32, place_width)]
+        #[qproperty(i32, place_height)]
         type MainWindow = super::MainWindowRust;
+
+        /// Load the fullscreen settings from the configuration
+        #[qinvokable]
+        fn load_window_config(self: Pin<&mut MainWindow>);
+
+        /// Switch to fullscreen
+        #[qinvokable]
+        fn enter_fullscreen(self: Pin<&mut MainWindow>);
+
+        /// Leave fullscreen
+        #[qinvokable]
+        fn exit_fullscreen(self: Pin<&mut MainWindow>);
+
+        /// Enter or leave fullscreen
+        #[qinvokable]
+        fn toggle_fullscreen(self: Pin<&mut MainWindow>);
+
+        /// Record a fullscreen change made outside the application,
+        /// e.g. by the window manager
+        #[qinvokable]
+        fn sync_fullscreen(self: Pin<&mut MainWindow>, fullscreen: bool);
+
+        /// Work out where the window goes from the saved geometry
+        /// `screens` is a JSON array of screens, primary first
+        /// Returns false if the window should be left where Qt put it
+        #[qinvokable]
+        fn place_window(self: Pin<&mut MainWindow>, screens: QString, width: i32, height: i32) -> bool;
+
+        /// Save the window geometry and the name of its screen
+        #[qinvokable]
+        fn save_window_geometry(self: &MainWindow, screen: QString, x: i32, y: i32, width: i32, height: i32);
+
+        /// Index of the screen to go fullscreen on
+        #[qinvokable]
+        fn fullscreen_screen_index(self: &MainWindow, screen_count: i32, current: i32) -> i32;
+
+        /// The window showed its first frame; logs the startup timings
+        #[qinvokable]
+        fn startup_finished(self: &MainWindow);
     }
 }
 
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
 /// Rust implementation of the MainWindow
-#[derive(Default)]
 pub struct MainWindowRust {
     session_running: bool,
+    /// Whether the window is fullscreen
+    fullscreen: bool,
+    /// Go fullscreen at startup
+    start_fullscreen: bool,
+    /// Never show the window (--headless)
+    headless: bool,
+    /// Hide the menu bar while fullscreen
+    hide_menu_in_fullscreen: bool,
+    /// Whether the menu bar is shown
+    menu_visible: bool,
+    /// Whether the window was maximized before going fullscreen
+    window_maximized: bool,
+    /// Configured fullscreen screen (-1 = the window's screen)
+    fullscreen_screen: i32,
+    /// Screen the window is placed on
+    place_screen: i32,
+    /// Placed window geometry
+    place_x: i32,
+    place_y: i32,
+    place_width: i32,
+    place_height: i32,
+}
+
+impl Default for MainWindowRust {
+    fn default() -> Self {
+        let _timing = startup::span("MainWindow");
+        register_actions();
+        Self {
+            session_running: false,
+            fullscreen: false,
+            start_fullscreen: false,
+            headless: run_options::get().headless,
+            hide_menu_in_fullscreen: true,
+            menu_visible: true,
+            window_maximized: false,
+            fullscreen_screen: -1,
+            place_screen: 0,
+            place_x: 0,
+            place_y: 0,
+            place_width: 800,
+            place_height: 600,
+        }
+    }
+}
+
+impl qobject::MainWindow {
+    /// Load the fullscreen settings from the configuration
+    pub fn load_window_config(mut self: Pin<&mut Self>) {
+        let display = load_config().unwrap_or_default().display;
+        self.as_mut()
+            .set_start_fullscreen(display.start_fullscreen || run_options::get().fullscreen);
+        self.as_mut().set_hide_menu_in_fullscreen(display.fullscreen_hide_menu);
+        self.as_mut()
+            .set_fullscreen_screen(display.fullscreen_screen.map_or(-1, |n| n as i32));
+        self.update_menu_visible();
+    }
+
+    /// Switch to fullscreen
+    pub fn enter_fullscreen(self: Pin<&mut Self>) {
+        self.sync_fullscreen(true);
+    }
+
+    /// Leave fullscreen
+    pub fn exit_fullscreen(self: Pin<&mut Self>) {
+        self.sync_fullscreen(false);
+    }
+
+    /// Enter or leave fullscreen
+    pub fn toggle_fullscreen(self: Pin<&mut Self>) {
+        let fullscreen = !*self.as_ref().fullscreen();
+        self.sync_fullscreen(fullscreen);
+    }
+
+    /// Record the fullscreen state
+    pub fn sync_fullscreen(mut self: Pin<&mut Self>, fullscreen: bool) {
+        self.as_mut().set_fullscreen(fullscreen);
+        self.update_menu_visible();
+    }
+
+    /// Show the menu bar unless fullscreen hides it
+    fn update_menu_visible(self: Pin<&mut Self>) {
+        let hidden = *self.as_ref().fullscreen() && *self.as_ref().hide_menu_in_fullscreen();
+        self.set_menu_visible(!hidden);
+    }
+
+    /// Work out where the window goes from the saved geometry
+    pub fn place_window(mut self: Pin<&mut Self>, screens: QString, width: i32, height: i32) -> bool {
+        let screens: Vec<ScreenInfo> = match serde_json::from_str(&screens.to_string()) {
+            Ok(screens) => screens,
+            Err(e) => {
+                tracing::warn!("Invalid screen list: {}", e);
+                return false;
+            }
+        };
+        let general = load_config().unwrap_or_default().general;
+        let Some(placed) = placement::place_window(&general, &screens, width, height) else {
+            return false;
+        };
+        tracing::debug!(
+            "Placing window on {} at {},{} {}x{}",
+            screens[placed.screen].name,
+            placed.x,
+            placed.y,
+            placed.width,
+            placed.height
+        );
+
+        self.as_mut().set_place_screen(placed.screen as i32);
+        self.as_mut().set_place_x(placed.x);
+        self.as_mut().set_place_y(placed.y);
+        self.as_mut().set_place_width(placed.width);
+        self.as_mut().set_place_height(placed.height);
+        true
+    }
+
+    /// Save the window geometry and the name of its screen
+    pub fn save_window_geometry(&self, screen: QString, x: i32, y: i32, width: i32, height: i32) {
+        let mut config = load_config().unwrap_or_default();
+        if !config.general.remember_window_geometry {
+            return;
+        }
+        config.general.window_screen = Some(screen.to_string());
+        config.general.window_x = Some(x);
+        config.general.window_y = Some(y);
+        config.general.window_width = Some(width.max(0) as u32);
+        config.general.window_height = Some(height.max(0) as u32);
+        if let Err(e) = save_config(&config) {
+            tracing::warn!("Failed to save window geometry: {}", e);
+        }
+    }
+
+    /// Index of the screen to go fullscreen on
+    pub fn fullscreen_screen_index(&self, screen_count: i32, current: i32) -> i32 {
+        let display = load_config().unwrap_or_default().display;
+        placement::fullscreen_screen(&display, screen_count.max(0) as usize, current.max(0) as usize) as i32
+    }
+
+    /// End the startup profile at the first frame
+    pub fn startup_finished(&self) {
+        startup::finish();
+    }
+}
+
+/// Register the window and settings actions (see actions)
+fn register_actions() {
+    actions::register(Action::new("quit", "Quit", "File").shortcut("Ctrl+Q"));
+    actions::register(Action::new("toggle_fullscreen", "Fullscreen", "View").shortcut("F11"));
+    actions::register(Action::new("bios_settings", "BIOS Image...", "Machine"));
+    actions::register(Action::new("display_settings", "Display Settings...", "View"));
+    actions::register(Action::new("keyboard_settings", "Keyboard Settings...", "Input"));
+    actions::register(Action::new("mouse_settings", "Mouse Settings...", "Input"));
+    actions::register(Action::new("network_settings", "Network Settings...", "Devices"));
+    actions::register(Action::new("clipboard_settings", "Clipboard Settings...", "Devices"));
 }
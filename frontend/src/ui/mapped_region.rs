@@ -0,0 +1,131 @@
+//! Safe wrapper for memory-mapped driver regions.
+//!
+//! The driver exposes the framebuffer and other shared areas through mmap at
+//! offsets published in its region table (SUNPCI_IOC_GET_MMAP_REGIONS).
+//! `MappedRegion` owns one such mapping and unmaps it on drop.
+//!
+//! The guest can write to the mapped memory at any time, so it is never
+//! handed out as a `&[u8]`: Rust assumes memory behind a shared reference
+//! does not change, and the compiler may rely on that. Instead `read`
+//! copies a bounds-checked range out with `ptr::copy_nonoverlapping`, and
+//! the conversion code works on the copy. Code that hands the memory to
+//! C++ to copy (the texture upload) takes a raw pointer from `ptr_at`.
+//! Either way a copy may catch a frame mid-update, which only affects
+//! what gets displayed.
+
+use std::io;
+use std::os::unix::io::RawFd;
+use std::ptr::{self, NonNull};
+
+use rising_sun_common::ioctl::{MmapRegions, region_id, sunpci_get_mmap_regions};
+
+/// Find the mmap offset of a region (see ioctl::region_id)
+///
+/// Drivers without the region table ioctl only expose the framebuffer at
+/// offset 0, so that is the fallback. Returns None if the region is absent.
+pub fn region_mmap_offset(fd: RawFd, id: u32) -> Option<libc::off_t> {
+    let mut regions = MmapRegions::default();
+    match unsafe { sunpci_get_mmap_regions(fd, &mut regions) } {
+        Ok(_) => regions.find(id).map(|r| r.offset() as libc::off_t),
+        Err(_) if id == region_id::FRAMEBUFFER => Some(0),
+        Err(_) => None,
+    }
+}
+
+/// A read-only shared mapping of a driver region
+pub struct MappedRegion {
+    /// Start of the mapping
+    ptr: NonNull<u8>,
+    /// Length of the mapping in bytes
+    len: usize,
+}
+
+// Safety: the mapping is read-only and owned exclusively by this value
+unsafe impl Send for MappedRegion {}
+unsafe impl Sync for MappedRegion {}
+
+impl MappedRegion {
+    /// Map `len` bytes of the given region (see ioctl::region_id)
+    pub fn map_region(fd: RawFd, id: u32, len: usize) -> io::Result<Self> {
+        let offset = region_mmap_offset(fd, id).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no mmap region {}", id))
+        })?;
+        Self::map(fd, offset, len)
+    }
+
+    /// Map `len` bytes at the given mmap offset
+    pub fn map(fd: RawFd, offset: libc::off_t, len: usize) -> io::Result<Self> {
+        if fd < 0 || len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid fd or empty mapping",
+            ));
+        }
+
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let ptr = NonNull::new(ptr as *mut u8)
+            .ok_or_else(|| io::Error::other("mmap returned null"))?;
+        Ok(Self { ptr, len })
+    }
+
+    /// Length of the mapping in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the mapping is empty (never true for a successful map)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Pointer to `len` bytes starting at `offset`, or None if out of bounds
+    ///
+    /// The pointer is valid while `self` lives; the bytes behind it may
+    /// change at any time, so it must not be turned into a slice.
+    pub fn ptr_at(&self, offset: usize, len: usize) -> Option<*const u8> {
+        let end = offset.checked_add(len)?;
+        if end > self.len {
+            return None;
+        }
+        // Safety: offset is within the mapping
+        Some(unsafe { self.ptr.as_ptr().add(offset) }.cast_const())
+    }
+
+    /// Copy the bytes starting at `offset` into `dst`; false if out of bounds
+    pub fn read_into(&self, offset: usize, dst: &mut [u8]) -> bool {
+        let Some(src) = self.ptr_at(offset, dst.len()) else {
+            return false;
+        };
+        // Safety: src is valid for dst.len() bytes and cannot overlap a
+        // Rust-owned buffer
+        unsafe { ptr::copy_nonoverlapping(src, dst.as_mut_ptr(), dst.len()) };
+        true
+    }
+
+    /// Copy of `len` bytes starting at `offset`, or None if out of bounds
+    pub fn read(&self, offset: usize, len: usize) -> Option<Vec<u8>> {
+        let mut data = vec![0; len];
+        self.read_into(offset, &mut data).then_some(data)
+    }
+}
+
+impl Drop for MappedRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+        }
+    }
+}
@@ -0,0 +1,443 @@
+//! MIDI controller Qt bridge for the guest's MPU-401 output.
+//!
+//! This module handles:
+//! - Reading the bytes the guest writes to the MPU-401 from the driver
+//! - Playing them on the built-in synthesizer (`rising_sun_common::softsynth`)
+//!   through the audio output, or sending them to a port on the host's ALSA
+//!   sequencer, as `[midi] output` says
+//! - Listing the sequencer ports a synthesizer can be reached on
+
+use std::cell::{Cell, RefCell};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use alsa::seq::{Addr, ClientIter, MidiEvent, PortCap, PortInfo, PortIter, PortSubscribe, PortType, Seq};
+use rising_sun_common::ioctl::{MidiBuffer, sunpci_read_midi};
+use rising_sun_common::midi::{MidiMessage, MidiParser};
+use rising_sun_common::softsynth::SoftSynth;
+use rising_sun_common::{MidiOutput, load_config, save_config};
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(QString, midi_output)]
+        #[qproperty(QString, midi_port)]
+        #[qproperty(QString, connected_port)]
+        #[qproperty(bool, midi_active)]
+        #[qproperty(QString, status_text)]
+        type MidiController = super::MidiControllerRust;
+
+        /// Start passing the guest's MIDI output on, as configured
+        #[qinvokable]
+        fn start(self: Pin<&mut MidiController>, fd: i32) -> bool;
+
+        /// Stop passing MIDI output on
+        #[qinvokable]
+        fn stop(self: Pin<&mut MidiController>);
+
+        /// Choose where MIDI goes ("off", "softsynth" or "sequencer", and a
+        /// sequencer port) and keep it in the configuration
+        #[qinvokable]
+        fn set_output(self: Pin<&mut MidiController>, output: QString, port: QString);
+
+        /// Sequencer ports that take MIDI, as a JSON array of
+        /// {"address": "client:port", "name": ...}
+        #[qinvokable]
+        fn sequencer_ports(self: &MidiController) -> QString;
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+
+/// MIDI thread state
+#[derive(Default)]
+struct MidiState {
+    /// Whether the thread is running
+    running: Arc<AtomicBool>,
+    /// Thread handle
+    thread_handle: Option<std::thread::JoinHandle<()>>,
+}
+
+/// Rust implementation of the MidiController
+pub struct MidiControllerRust {
+    /// Configured output: "off", "softsynth" or "sequencer"
+    midi_output: QString,
+    /// Configured sequencer port
+    midi_port: QString,
+    /// What MIDI is being played on
+    connected_port: QString,
+    /// Whether MIDI is being passed on
+    midi_active: bool,
+    /// Status text for UI
+    status_text: QString,
+    /// Driver file descriptor of the running session
+    driver_fd: Cell<i32>,
+    /// Thread state
+    state: RefCell<MidiState>,
+}
+
+impl Default for MidiControllerRust {
+    fn default() -> Self {
+        let config = load_config().unwrap_or_default().midi;
+        Self {
+            midi_output: QString::from(output_name(config.output)),
+            midi_port: QString::from(&config.port),
+            connected_port: QString::default(),
+            midi_active: false,
+            status_text: QString::from("Stopped"),
+            driver_fd: Cell::new(-1),
+            state: RefCell::new(MidiState::default()),
+        }
+    }
+}
+
+fn output_name(output: MidiOutput) -> &'static str {
+    match output {
+        MidiOutput::Off => "off",
+        MidiOutput::SoftSynth => "softsynth",
+        MidiOutput::Sequencer => "sequencer",
+    }
+}
+
+fn parse_output(name: &str) -> Option<MidiOutput> {
+    match name {
+        "off" => Some(MidiOutput::Off),
+        "softsynth" => Some(MidiOutput::SoftSynth),
+        "sequencer" => Some(MidiOutput::Sequencer),
+        _ => None,
+    }
+}
+
+impl qobject::MidiController {
+    /// Start passing the guest's MIDI output on
+    pub fn start(mut self: Pin<&mut Self>, fd: i32) -> bool {
+        self.as_mut().stop();
+        self.driver_fd.set(fd);
+
+        let config = load_config().unwrap_or_default().midi;
+        self.as_mut().set_midi_output(QString::from(output_name(config.output)));
+        self.as_mut().set_midi_port(QString::from(&config.port));
+        if fd < 0 || config.output == MidiOutput::Off {
+            self.set_status_text(QString::from("Off"));
+            return false;
+        }
+
+        // The sink is opened on the thread (an audio stream stays on the
+        // thread that made it); wait to hear how that went
+        let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+        let running = self.state.borrow().running.clone();
+        running.store(true, Ordering::SeqCst);
+        let handle = std::thread::spawn(move || midi_thread(fd, running, config.output, config.port, ready_tx));
+        self.state.borrow_mut().thread_handle = Some(handle);
+
+        match ready_rx.recv().unwrap_or_else(|_| Err("MIDI thread exited".to_string())) {
+            Ok(name) => {
+                tracing::info!("Playing guest MIDI on {}", name);
+                self.as_mut().set_connected_port(QString::from(&name));
+                self.as_mut().set_midi_active(true);
+                self.set_status_text(QString::from("Playing"));
+                true
+            }
+            Err(e) => {
+                tracing::warn!("MIDI output unavailable: {}", e);
+                self.as_mut().stop();
+                self.set_status_text(QString::from(&format!("Error: {}", e)));
+                false
+            }
+        }
+    }
+
+    /// Stop passing MIDI output on
+    pub fn stop(mut self: Pin<&mut Self>) {
+        {
+            let mut state = self.state.borrow_mut();
+            state.running.store(false, Ordering::SeqCst);
+            if let Some(handle) = state.thread_handle.take() {
+                let _ = handle.join();
+            }
+        }
+        self.driver_fd.set(-1);
+        self.as_mut().set_midi_active(false);
+        self.as_mut().set_connected_port(QString::default());
+        self.set_status_text(QString::from("Stopped"));
+    }
+
+    /// Choose where MIDI goes, and restart on it if running
+    pub fn set_output(mut self: Pin<&mut Self>, output: QString, port: QString) {
+        let Some(output) = parse_output(&output.to_string()) else {
+            tracing::warn!("Unknown MIDI output '{}'", output);
+            return;
+        };
+        let mut config = load_config().unwrap_or_default();
+        config.midi.output = output;
+        config.midi.port = port.to_string();
+        if let Err(e) = save_config(&config) {
+            tracing::warn!("Failed to save MIDI output: {}", e);
+        }
+        self.as_mut().set_midi_output(QString::from(output_name(output)));
+        self.as_mut().set_midi_port(port);
+
+        let fd = self.driver_fd.get();
+        if *self.as_ref().midi_active() || (fd >= 0 && output != MidiOutput::Off) {
+            self.start(fd);
+        }
+    }
+
+    /// Sequencer ports that take MIDI
+    pub fn sequencer_ports(&self) -> QString {
+        #[derive(serde::Serialize)]
+        struct Port {
+            address: String,
+            name: String,
+        }
+
+        let ports: Vec<Port> = match Seq::open(None, None, false) {
+            Ok(seq) => writable_ports(&seq)
+                .into_iter()
+                .map(|(addr, name)| Port {
+                    address: format!("{}:{}", addr.client, addr.port),
+                    name,
+                })
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Cannot open the ALSA sequencer: {}", e);
+                Vec::new()
+            }
+        };
+        QString::from(&serde_json::to_string(&ports).unwrap_or_else(|_| "[]".to_string()))
+    }
+}
+
+/// Where parsed MIDI messages are played
+trait MidiSink {
+    fn send(&mut self, message: &MidiMessage);
+}
+
+/// The built-in synthesizer, playing through the default audio output
+struct SoftSynthSink {
+    synth: Arc<Mutex<SoftSynth>>,
+    _stream: cpal::Stream,
+}
+
+impl SoftSynthSink {
+    fn open() -> Result<(Self, String), String> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| "no audio output device".to_string())?;
+        let rate = device
+            .default_output_config()
+            .map(|c| c.sample_rate().0)
+            .unwrap_or(48000);
+        let config = cpal::StreamConfig {
+            channels: 2,
+            sample_rate: cpal::SampleRate(rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+
+        let synth = Arc::new(Mutex::new(SoftSynth::new(rate)));
+        let synth_callback = Arc::clone(&synth);
+        let stream = device
+            .build_output_stream(
+                &config,
+                move |data: &mut [i16], _: &cpal::OutputCallbackInfo| {
+                    synth_callback.lock().unwrap_or_else(|e| e.into_inner()).render(data);
+                },
+                |err| tracing::error!("MIDI audio stream error: {}", err),
+                None,
+            )
+            .map_err(|e| e.to_string())?;
+        stream.play().map_err(|e| e.to_string())?;
+
+        let name = format!("built-in synthesizer ({})", device.name().unwrap_or_default());
+        Ok((Self { synth, _stream: stream }, name))
+    }
+}
+
+impl MidiSink for SoftSynthSink {
+    fn send(&mut self, message: &MidiMessage) {
+        self.synth.lock().unwrap_or_else(|e| e.into_inner()).handle(message);
+    }
+}
+
+/// A port on the host's ALSA sequencer
+struct SequencerSink {
+    seq: Seq,
+    port: i32,
+    encoder: MidiEvent,
+}
+
+impl SequencerSink {
+    fn open(spec: &str) -> Result<(Self, String), String> {
+        let seq = Seq::open(None, Some(alsa::Direction::Playback), false).map_err(|e| e.to_string())?;
+        let client_name = CString::new("Rising Sun").unwrap_or_default();
+        seq.set_client_name(&client_name).map_err(|e| e.to_string())?;
+
+        let (dest, name) = find_port(&seq, spec)
+            .ok_or_else(|| if spec.is_empty() { "no synthesizer on the sequencer".to_string() } else { format!("no sequencer port '{}'", spec) })?;
+
+        let port_name = CString::new("MPU-401").unwrap_or_default();
+        let port = seq
+            .create_simple_port(&port_name, PortCap::READ | PortCap::SUBS_READ, PortType::MIDI_GENERIC | PortType::APPLICATION)
+            .map_err(|e| e.to_string())?;
+        let subscription = PortSubscribe::empty().map_err(|e| e.to_string())?;
+        subscription.set_sender(Addr {
+            client: seq.client_id().map_err(|e| e.to_string())?,
+            port,
+        });
+        subscription.set_dest(dest);
+        seq.subscribe_port(&subscription).map_err(|e| e.to_string())?;
+
+        let encoder = MidiEvent::new(256).map_err(|e| e.to_string())?;
+        Ok((Self { seq, port, encoder }, name))
+    }
+}
+
+impl MidiSink for SequencerSink {
+    fn send(&mut self, message: &MidiMessage) {
+        self.encoder.reset_encode();
+        let bytes = message.to_bytes();
+        let (_, event) = match self.encoder.encode(&bytes) {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::trace!("Cannot encode MIDI message {:?}: {}", message, e);
+                return;
+            }
+        };
+        if let Some(mut event) = event {
+            event.set_source(self.port);
+            event.set_subs();
+            event.set_direct();
+            if let Err(e) = self.seq.event_output_direct(&mut event) {
+                tracing::trace!("MIDI send error: {}", e);
+            }
+        }
+    }
+}
+
+impl Drop for SequencerSink {
+    fn drop(&mut self) {
+        // Notes left on would ring on in the synthesizer
+        for channel in 0..16 {
+            self.send(&MidiMessage::ControlChange { channel, controller: 123, value: 0 });
+        }
+    }
+}
+
+/// Ports other clients can send MIDI to, with their names
+fn writable_ports(seq: &Seq) -> Vec<(Addr, String)> {
+    let own = seq.client_id().unwrap_or(-1);
+    let mut ports = Vec::new();
+    for client in ClientIter::new(seq) {
+        // The system client announces and times; it plays nothing
+        if client.get_client() == 0 || client.get_client() == own {
+            continue;
+        }
+        for port in PortIter::new(seq, client.get_client()) {
+            if port.get_capability().contains(PortCap::WRITE | PortCap::SUBS_WRITE) {
+                let name = format!("{}: {}", client.get_name().unwrap_or(""), port.get_name().unwrap_or(""));
+                ports.push((port.addr(), name));
+            }
+        }
+    }
+    ports
+}
+
+/// The port `spec` names ("client:port", or a client or port name), or
+/// the first synthesizer when it is empty
+fn find_port(seq: &Seq, spec: &str) -> Option<(Addr, String)> {
+    let ports = writable_ports(seq);
+    if spec.is_empty() {
+        let is_synth = |addr: &Addr| {
+            seq.get_any_port_info(*addr)
+                .is_ok_and(|info: PortInfo| info.get_type().intersects(PortType::SYNTHESIZER | PortType::MIDI_GM))
+        };
+        // Failing that, anything but the kernel's loopback
+        return ports
+            .iter()
+            .find(|(addr, _)| is_synth(addr))
+            .or_else(|| ports.iter().find(|(_, name)| !name.starts_with("Midi Through")))
+            .cloned();
+    }
+    if let Some((client, port)) = spec.split_once(':')
+        && let (Ok(client), Ok(port)) = (client.trim().parse(), port.trim().parse())
+    {
+        let addr = Addr { client, port };
+        return ports.into_iter().find(|(a, _)| *a == addr);
+    }
+    ports.into_iter().find(|(_, name)| {
+        let (client, port) = name.split_once(": ").unwrap_or((name, ""));
+        client.eq_ignore_ascii_case(spec) || port.eq_ignore_ascii_case(spec) || name.eq_ignore_ascii_case(spec)
+    })
+}
+
+/// MIDI thread
+///
+/// Opens the sink, says how that went on `ready`, then passes the guest's
+/// MPU-401 output to it until stopped.
+fn midi_thread(
+    fd: i32,
+    running: Arc<AtomicBool>,
+    output: MidiOutput,
+    port: String,
+    ready: mpsc::SyncSender<Result<String, String>>,
+) {
+    let opened = match output {
+        MidiOutput::SoftSynth => SoftSynthSink::open().map(|(sink, name)| (Box::new(sink) as Box<dyn MidiSink>, name)),
+        MidiOutput::Sequencer => SequencerSink::open(&port).map(|(sink, name)| (Box::new(sink) as Box<dyn MidiSink>, name)),
+        MidiOutput::Off => Err("MIDI output is off".to_string()),
+    };
+    let mut sink = match opened {
+        Ok((sink, name)) => {
+            let _ = ready.send(Ok(name));
+            sink
+        }
+        Err(e) => {
+            let _ = ready.send(Err(e));
+            return;
+        }
+    };
+
+    let mut parser = MidiParser::new();
+    let mut messages = Vec::new();
+    let mut buffer = MidiBuffer::default();
+    let mut last_data = Instant::now();
+
+    while running.load(Ordering::SeqCst) {
+        buffer.size = buffer.data.len() as u32;
+        match unsafe { sunpci_read_midi(fd, &mut buffer) } {
+            Ok(_) if buffer.size > 0 => {
+                last_data = Instant::now();
+                messages.clear();
+                parser.feed(&buffer.data[..buffer.size as usize], &mut messages);
+                for message in &messages {
+                    sink.send(message);
+                }
+            }
+            Ok(_) => {
+                // Poll closely while music plays, and less when it does not
+                let idle = last_data.elapsed() > Duration::from_secs(1);
+                std::thread::sleep(Duration::from_millis(if idle { 10 } else { 1 }));
+            }
+            Err(e) => {
+                tracing::warn!("MIDI read error: {}", e);
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        }
+    }
+
+    drop(sink);
+    tracing::info!("MIDI thread stopped");
+}
@@ -1,15 +1,38 @@
 //! UI components and Qt bridge types.
 
+mod action_controller;
+mod actions;
 mod audio_controller;
 mod clipboard_controller;
 mod config_manager;
+mod deinterlace;
 mod disk_manager;
 mod display_view;
 mod drive_mapping_controller;
+mod drive_mapping_model;
+pub mod ephemeral;
+mod event_controller;
+mod framebuffer_item;
 mod framebuffer_provider;
+mod gameport_controller;
+mod history_controller;
+mod hotkey_controller;
 mod input_controller;
+pub mod log_capture;
+mod log_controller;
+pub mod logging;
+mod macro_controller;
 mod main_window;
+mod mapped_region;
+mod midi_controller;
 mod network_controller;
+mod oui;
+mod recent_files_model;
+mod removable_media_controller;
+pub mod run_options;
 mod session_controller;
+mod session_gate;
 mod settings_controller;
+pub mod startup;
+mod stats_controller;
 
@@ -3,12 +3,30 @@
 //! This module handles:
 //! - Enabling/disabling the virtual network adapter
 //! - Configuring host interface bridging
-//! - MAC address configuration
-//! - Network statistics display
+//! - MAC address configuration, vendor (OUI) display and validation
+//! - Network statistics display and throughput history for graphs
+//! - Host link state (carrier/speed/duplex) of the bridged interface
+//! - Creating the host TAP interface for the session (common::netsetup)
+//! - Running the user-space NAT in NAT mode (common::nat)
+//! - Sending Wake-on-LAN magic packets to other machines on the LAN
+//!
+//! The host's interfaces are listed on a thread of their own, starting when
+//! the controller is created, so the network settings dialog opens without
+//! waiting on sysfs.
 
 use std::cell::RefCell;
-
-use rising_sun_common::ioctl::{NetworkConfig, NetworkStatus, net_flags};
+use std::collections::VecDeque;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::fd::AsRawFd;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use rising_sun_common::ioctl::{NetFrame, NetworkConfig, NetworkStatus, net_flags};
+use rising_sun_common::nat::{self, NatStack};
+use rising_sun_common::netsetup::{self, LinkInfo, TapDevice, TapOptions};
+use rising_sun_common::startup::Deferred;
 
 #[cxx_qt::bridge]
 mod qobject {
@@ -25,11 +43,25 @@ mod qobject {
         #[qproperty(i32, driver_fd)]
         #[qproperty(QString, interface_name)]
         #[qproperty(QString, mac_address)]
+        #[qproperty(QString, mac_vendor)]
+        #[qproperty(QString, mac_warning)]
+        #[qproperty(bool, mac_local)]
         #[qproperty(i64, rx_packets)]
         #[qproperty(i64, tx_packets)]
         #[qproperty(i64, rx_bytes)]
         #[qproperty(i64, tx_bytes)]
+        #[qproperty(i64, rx_rate)]
+        #[qproperty(i64, tx_rate)]
         #[qproperty(QString, status_text)]
+        #[qproperty(bool, auto_tap)]
+        #[qproperty(QString, tap_name)]
+        #[qproperty(QString, bridge_name)]
+        #[qproperty(bool, create_bridge)]
+        #[qproperty(QString, network_mode)]
+        #[qproperty(bool, host_carrier)]
+        #[qproperty(i32, host_link_speed)]
+        #[qproperty(QString, host_link_duplex)]
+        #[qproperty(QString, link_state)]
         type NetworkController = super::NetworkControllerRust;
 
         /// Initialize network controller with driver file descriptor
@@ -48,6 +80,14 @@ mod qobject {
         #[qinvokable]
         fn set_mac(self: Pin<&mut NetworkController>, mac: QString) -> bool;
 
+        /// Look up the vendor of a MAC address being entered and validate it
+        #[qinvokable]
+        fn check_mac(self: Pin<&mut NetworkController>, mac: QString) -> bool;
+
+        /// Send a Wake-on-LAN magic packet for `mac` on the bridged interface
+        #[qinvokable]
+        fn send_wol(self: Pin<&mut NetworkController>, mac: QString) -> bool;
+
         /// Apply all pending configuration changes
         #[qinvokable]
         fn apply_config(self: Pin<&mut NetworkController>) -> bool;
@@ -56,6 +96,14 @@ mod qobject {
         #[qinvokable]
         fn poll_status(self: Pin<&mut NetworkController>);
 
+        /// Take the session's TAP interface down (session stopped)
+        #[qinvokable]
+        fn session_stopped(self: Pin<&mut NetworkController>);
+
+        /// Remove the TAP interface and bridge created for the session
+        #[qinvokable]
+        fn release_tap(self: Pin<&mut NetworkController>);
+
         /// Get list of available host network interfaces (semicolon-separated)
         #[qinvokable]
         fn get_available_interfaces(self: &NetworkController) -> QString;
@@ -68,6 +116,20 @@ mod qobject {
         #[qinvokable]
         fn format_bytes(self: &NetworkController, bytes: i64) -> QString;
 
+        /// Received bytes per second over the last minute, oldest first
+        /// (JSON array)
+        #[qinvokable]
+        fn get_rx_history(self: &NetworkController) -> QString;
+
+        /// Transmitted bytes per second over the last minute, oldest first
+        /// (JSON array)
+        #[qinvokable]
+        fn get_tx_history(self: &NetworkController) -> QString;
+
+        /// Signal emitted when a throughput sample was added
+        #[qsignal]
+        fn throughput_updated(self: Pin<&mut NetworkController>);
+
         /// Signal emitted when network status changes
         #[qsignal]
         fn status_changed(self: Pin<&mut NetworkController>);
@@ -80,6 +142,9 @@ mod qobject {
 
 use std::pin::Pin;
 use cxx_qt_lib::QString;
+use super::oui;
+use super::session_gate;
+use super::startup;
 use rising_sun_common::ioctl::{sunpci_set_network, sunpci_get_network};
 
 /// Rust implementation of the NetworkController
@@ -94,6 +159,13 @@ pub struct NetworkControllerRust {
     interface_name: QString,
     /// MAC address string (XX:XX:XX:XX:XX:XX)
     mac_address: QString,
+    /// Vendor of the last checked MAC address (empty if unknown)
+    mac_vendor: QString,
+    /// Problem with the last checked MAC address: "", "invalid",
+    /// "multicast", "broadcast" or "zero" (translated by the UI)
+    mac_warning: QString,
+    /// Whether the last checked MAC address is locally administered
+    mac_local: bool,
     /// Received packets
     rx_packets: i64,
     /// Transmitted packets
@@ -102,29 +174,79 @@ pub struct NetworkControllerRust {
     rx_bytes: i64,
     /// Transmitted bytes
     tx_bytes: i64,
+    /// Receive rate over the last poll interval (bytes/s)
+    rx_rate: i64,
+    /// Transmit rate over the last poll interval (bytes/s)
+    tx_rate: i64,
     /// Current status text
     status_text: QString,
+    /// Whether to create the TAP interface instead of using an existing one
+    auto_tap: bool,
+    /// TAP interface to create
+    tap_name: QString,
+    /// Bridge to attach the TAP to (empty = none)
+    bridge_name: QString,
+    /// Whether to create the bridge if missing
+    create_bridge: bool,
+    /// Attachment mode: "bridged" (TAP) or "nat" (user-space NAT)
+    network_mode: QString,
+    /// Whether the host interface has carrier (cable plugged in)
+    host_carrier: bool,
+    /// Host interface speed in Mb/s (0 = unknown)
+    host_link_speed: i32,
+    /// Host interface duplex: "full", "half" or "" if unknown
+    host_link_duplex: QString,
+    /// Overall state for the status UI: "disabled", "guest_offline",
+    /// "host_unplugged" or "connected"
+    link_state: QString,
+    /// Per-second rx/tx rates for the throughput graph
+    history: RefCell<ThroughputHistory>,
+    /// NAT thread, while running in NAT mode
+    nat: RefCell<Option<NatWorker>>,
+    /// TAP interface created for the session (removed on drop)
+    tap: RefCell<Option<TapDevice>>,
     /// Pending configuration (not yet applied)
     pending_config: RefCell<NetworkConfig>,
     /// Last applied configuration
     last_config: RefCell<NetworkConfig>,
+    /// Host interfaces being listed for get_available_interfaces
+    interfaces: RefCell<Deferred<Vec<String>>>,
 }
 
 impl Default for NetworkControllerRust {
     fn default() -> Self {
+        let _timing = startup::span("NetworkController");
         Self {
             network_enabled: false,
             network_connected: false,
             driver_fd: -1,
             interface_name: QString::from(""),
             mac_address: QString::from(""),
+            mac_vendor: QString::default(),
+            mac_warning: QString::default(),
+            mac_local: false,
             rx_packets: 0,
             tx_packets: 0,
             rx_bytes: 0,
             tx_bytes: 0,
+            rx_rate: 0,
+            tx_rate: 0,
             status_text: QString::from("Network disabled"),
+            auto_tap: false,
+            tap_name: QString::from("sunpci0"),
+            bridge_name: QString::from(""),
+            create_bridge: false,
+            network_mode: QString::from("bridged"),
+            host_carrier: false,
+            host_link_speed: 0,
+            host_link_duplex: QString::default(),
+            link_state: QString::from("disabled"),
+            history: RefCell::new(ThroughputHistory::default()),
+            nat: RefCell::new(None),
+            tap: RefCell::new(None),
             pending_config: RefCell::new(NetworkConfig::default()),
             last_config: RefCell::new(NetworkConfig::default()),
+            interfaces: RefCell::new(list_interfaces()),
         }
     }
 }
@@ -173,14 +295,7 @@ impl qobject::NetworkController {
     /// Configure the host interface to bridge to
     pub fn set_interface(mut self: Pin<&mut Self>, interface: QString) -> bool {
         let iface = interface.to_string();
-        
-        {
-            let mut config = self.pending_config.borrow_mut();
-            let bytes = iface.as_bytes();
-            let len = bytes.len().min(config.interface.len() - 1);
-            config.interface[..len].copy_from_slice(&bytes[..len]);
-            config.interface[len] = 0;
-        }
+        set_interface_name(&mut self.pending_config.borrow_mut(), &iface);
 
         self.as_mut().set_interface_name(interface);
         tracing::info!("Network interface set to: {}", iface);
@@ -190,28 +305,70 @@ impl qobject::NetworkController {
     /// Set the MAC address
     pub fn set_mac(mut self: Pin<&mut Self>, mac: QString) -> bool {
         let mac_str = mac.to_string();
-        
-        // Parse MAC address (XX:XX:XX:XX:XX:XX)
-        let bytes = parse_mac_address(&mac_str);
-        
-        {
-            let mut config = self.pending_config.borrow_mut();
-            if let Some(mac_bytes) = bytes {
-                config.mac_address.copy_from_slice(&mac_bytes);
-            } else if mac_str.is_empty() {
-                // Empty = auto-generate (driver will fill in)
-                config.mac_address = [0; 6];
-            } else {
-                tracing::warn!("Invalid MAC address format: {}", mac_str);
-                return false;
-            }
+
+        if !self.as_mut().check_mac(mac.clone()) {
+            tracing::warn!("Rejected MAC address {}: {}", mac_str, self.mac_warning);
+            return false;
         }
+        // Empty = auto-generate (driver will fill in)
+        let bytes = parse_mac_address(&mac_str).unwrap_or([0; 6]);
+        self.pending_config.borrow_mut().mac_address.copy_from_slice(&bytes);
 
         self.as_mut().set_mac_address(mac);
         tracing::info!("MAC address set to: {}", if mac_str.is_empty() { "auto" } else { &mac_str });
         true
     }
 
+    /// Look up the vendor of a MAC address and validate it
+    ///
+    /// Updates `mac_vendor`, `mac_warning` and `mac_local`; returns false if
+    /// the address cannot be given to the guest. An empty address (auto)
+    /// is valid.
+    pub fn check_mac(mut self: Pin<&mut Self>, mac: QString) -> bool {
+        let (vendor, warning, local) = match check_mac_address(&mac.to_string()) {
+            Ok(Some(bytes)) => (oui::vendor(&bytes).unwrap_or(""), None, bytes[0] & 0x02 != 0),
+            Ok(None) => ("", None, false),
+            Err(warning) => ("", Some(warning), false),
+        };
+        self.as_mut().set_mac_vendor(QString::from(vendor));
+        self.as_mut().set_mac_warning(QString::from(warning.map_or("", MacWarning::code)));
+        self.as_mut().set_mac_local(local);
+        warning.is_none()
+    }
+
+    /// Send a Wake-on-LAN magic packet
+    ///
+    /// The packet is a UDP broadcast to the discard port, which every
+    /// WoL-capable NIC recognizes regardless of the payload's transport.
+    /// In bridged mode it goes out of the host interface the guest is
+    /// bridged to; otherwise the host's default route decides.
+    pub fn send_wol(mut self: Pin<&mut Self>, mac: QString) -> bool {
+        let mac_str = mac.to_string();
+        let Some(target) = parse_mac_address(&mac_str) else {
+            self.as_mut()
+                .config_error(QString::from(&format!("Invalid MAC address: {}", mac_str)));
+            return false;
+        };
+
+        let uplink = if self.network_mode.to_string() == "nat" {
+            None
+        } else {
+            self.uplink_name()
+        };
+        match send_magic_packet(&target, uplink.as_deref()) {
+            Ok(()) => {
+                tracing::info!("Sent Wake-on-LAN packet to {}", mac_str);
+                true
+            }
+            Err(e) => {
+                let msg = format!("Failed to send Wake-on-LAN packet: {}", e);
+                tracing::warn!("{}", msg);
+                self.as_mut().config_error(QString::from(&msg));
+                false
+            }
+        }
+    }
+
     /// Apply all pending configuration changes
     pub fn apply_config(mut self: Pin<&mut Self>) -> bool {
         if self.driver_fd < 0 {
@@ -219,20 +376,46 @@ impl qobject::NetworkController {
             return false;
         }
 
-        let config = self.pending_config.borrow().clone();
-        
+        let mut config = *self.pending_config.borrow();
+        let enabled = config.flags & net_flags::ENABLED != 0;
+        let nat_mode = self.network_mode.to_string() == "nat";
+
+        if nat_mode {
+            // No TAP: the driver hands frames to the NAT thread instead
+            config.flags |= net_flags::USER_MODE;
+            self.as_mut().release_tap();
+        } else if enabled && self.auto_tap {
+            // Create the TAP before the driver tries to attach to it
+            match self.ensure_tap() {
+                Ok(name) => set_interface_name(&mut config, &name),
+                Err(msg) => {
+                    tracing::error!("{}", msg);
+                    self.as_mut().set_status_text(QString::from(&msg));
+                    self.as_mut().config_error(QString::from(&msg));
+                    return false;
+                }
+            }
+        }
+
         let result = unsafe { sunpci_set_network(self.driver_fd, &config) };
 
         match result {
             Ok(_) => {
                 *self.last_config.borrow_mut() = config;
                 
-                if config.flags & net_flags::ENABLED != 0 {
+                if enabled && nat_mode {
+                    self.start_nat();
+                    self.as_mut().set_status_text(QString::from("Network active (NAT)"));
+                    self.as_mut().set_network_connected(true);
+                } else if enabled {
+                    self.stop_nat();
                     self.as_mut().set_status_text(QString::from("Network active"));
                     self.as_mut().set_network_connected(true);
                 } else {
+                    self.stop_nat();
                     self.as_mut().set_status_text(QString::from("Network disabled"));
                     self.as_mut().set_network_connected(false);
+                    self.as_mut().release_tap();
                 }
                 
                 self.as_mut().status_changed();
@@ -252,7 +435,7 @@ impl qobject::NetworkController {
 
     /// Poll for network status updates
     pub fn poll_status(mut self: Pin<&mut Self>) {
-        if self.driver_fd < 0 {
+        if self.driver_fd < 0 || !session_gate::is_open() {
             return;
         }
 
@@ -268,20 +451,178 @@ impl qobject::NetworkController {
                 self.as_mut().set_tx_packets(status.tx_packets as i64);
                 self.as_mut().set_rx_bytes(status.rx_bytes as i64);
                 self.as_mut().set_tx_bytes(status.tx_bytes as i64);
+
+                let sample = self.history.borrow_mut().record(
+                    Instant::now(),
+                    status.rx_bytes,
+                    status.tx_bytes,
+                );
+                if let Some((rx, tx)) = sample {
+                    self.as_mut().set_rx_rate(rx as i64);
+                    self.as_mut().set_tx_rate(tx as i64);
+                    self.as_mut().throughput_updated();
+                }
             }
             Err(e) => {
                 tracing::trace!("Failed to poll network status: {}", e);
             }
         }
+
+        self.as_mut().poll_host_link();
+    }
+
+    /// Internal: refresh the host interface link properties and link_state
+    ///
+    /// NAT mode goes through the host's routing, so only a bridged
+    /// session depends on the carrier of one particular interface.
+    fn poll_host_link(mut self: Pin<&mut Self>) {
+        let link = if self.network_mode.to_string() == "nat" {
+            None
+        } else {
+            self.uplink_name().and_then(|name| netsetup::link_info(&name))
+        };
+        let info = link.clone().unwrap_or_default();
+        self.as_mut().set_host_carrier(info.carrier);
+        let speed = info.speed_mbps.map_or(0, |s| i32::try_from(s).unwrap_or(i32::MAX));
+        self.as_mut().set_host_link_speed(speed);
+        let duplex = match info.full_duplex {
+            Some(true) => "full",
+            Some(false) => "half",
+            None => "",
+        };
+        self.as_mut().set_host_link_duplex(QString::from(duplex));
+
+        let state = link_state(self.network_enabled, self.network_connected, link.as_ref());
+        if self.link_state.to_string() != state {
+            let text = match state {
+                "disabled" => "Network disabled",
+                "guest_offline" => "Guest adapter offline",
+                "host_unplugged" => "Host network cable unplugged",
+                _ => "Connected",
+            };
+            tracing::info!("Network link state: {}", state);
+            self.as_mut().set_link_state(QString::from(state));
+            self.as_mut().set_status_text(QString::from(text));
+            self.as_mut().status_changed();
+        }
+    }
+
+    /// Internal: host interface carrying the guest's bridged traffic
+    ///
+    /// The configured host interface, else the bridge the TAP joins.
+    fn uplink_name(&self) -> Option<String> {
+        [self.interface_name.to_string(), self.bridge_name.to_string()]
+            .into_iter()
+            .find(|name| !name.is_empty())
+    }
+
+    /// Take the session's TAP interface down (session stopped)
+    ///
+    /// The interface itself is kept so the next session can reuse it.
+    pub fn session_stopped(self: Pin<&mut Self>) {
+        self.stop_nat();
+        if let Some(tap) = self.tap.borrow_mut().as_mut()
+            && let Err(e) = tap.set_up(false)
+        {
+            tracing::warn!("Failed to take {} down: {}", tap.name(), e);
+        }
+    }
+
+    /// Remove the TAP interface and bridge created for the session
+    pub fn release_tap(self: Pin<&mut Self>) {
+        let Some(tap) = self.tap.borrow_mut().take() else {
+            return;
+        };
+        let name = tap.name().to_string();
+        match tap.teardown() {
+            Ok(()) => tracing::info!("Released TAP interface {}", name),
+            Err(e) => tracing::warn!("Failed to clean up TAP interface {}: {}", name, e),
+        }
+    }
+
+    /// Internal: start the NAT thread if it is not running
+    fn start_nat(&self) {
+        let mut nat = self.nat.borrow_mut();
+        if nat.as_ref().and_then(|w| w.handle.as_ref()).is_some_and(|h| !h.is_finished()) {
+            return;
+        }
+
+        let fd = self.driver_fd;
+        let running = Arc::new(AtomicBool::new(true));
+        let thread_running = running.clone();
+        let handle = std::thread::spawn(move || nat_thread(fd, thread_running));
+        *nat = Some(NatWorker { running, handle: Some(handle) });
+    }
+
+    /// Internal: stop the NAT thread, closing its connections
+    fn stop_nat(&self) {
+        // Dropping the worker joins the thread
+        self.nat.borrow_mut().take();
+    }
+
+    /// Internal: create the session's TAP (or reuse it) and bring it up
+    ///
+    /// Returns the interface name for the driver, or an error message.
+    fn ensure_tap(&self) -> Result<String, String> {
+        let options = TapOptions {
+            tap_name: self.tap_name.to_string(),
+            bridge: self.bridge_name.to_string(),
+            create_bridge: self.create_bridge,
+            owner: None,
+        };
+
+        let mut tap = self.tap.borrow_mut();
+        // Settings changed since the TAP was created: start over
+        let stale = tap.as_ref().is_some_and(|t| {
+            t.name() != options.tap_name || t.bridge().unwrap_or("") != options.bridge
+        });
+        if stale {
+            tap.take();
+        }
+
+        if tap.is_none() {
+            let device = TapDevice::create(&options)
+                .map_err(|e| format!("Failed to set up TAP interface: {}", e))?;
+            tracing::info!(
+                "TAP interface {} ready{}",
+                device.name(),
+                device.bridge().map(|b| format!(" on bridge {}", b)).unwrap_or_default()
+            );
+            *tap = Some(device);
+        }
+
+        let device = tap.as_mut().expect("TAP created above");
+        device
+            .set_up(true)
+            .map_err(|e| format!("Failed to bring up {}: {}", device.name(), e))?;
+        Ok(device.name().to_string())
     }
 
     /// Get list of available host network interfaces as semicolon-separated string
     /// QML can split this with: interfaces.split(";")
+    ///
+    /// Returns the list made in the background, usually already done, and
+    /// starts the next one so interfaces added since show up next time.
     pub fn get_available_interfaces(&self) -> QString {
-        let interfaces = enumerate_network_interfaces();
+        let mut listing = self.interfaces.borrow_mut();
+        let interfaces = listing.wait().unwrap_or_else(|e| {
+            tracing::warn!("Cannot list host interfaces: {}", e);
+            Vec::new()
+        });
+        *listing = list_interfaces();
         QString::from(&interfaces.join(";"))
     }
 
+    /// Received bytes per second, oldest first
+    pub fn get_rx_history(&self) -> QString {
+        QString::from(&json_series(self.history.borrow().samples.iter().map(|s| s.0)))
+    }
+
+    /// Transmitted bytes per second, oldest first
+    pub fn get_tx_history(&self) -> QString {
+        QString::from(&json_series(self.history.borrow().samples.iter().map(|s| s.1)))
+    }
+
     /// Get formatted statistics string
     pub fn get_stats_text(&self) -> QString {
         QString::from(&format!(
@@ -299,6 +640,129 @@ impl qobject::NetworkController {
     }
 }
 
+/// Seconds of throughput kept for the graph
+const HISTORY_SECONDS: usize = 60;
+
+/// Ring buffer of rx/tx rates derived from the driver's byte counters
+#[derive(Debug, Default)]
+struct ThroughputHistory {
+    /// (rx, tx) bytes per second, oldest first
+    samples: VecDeque<(u64, u64)>,
+    /// Counters at the previous poll
+    last: Option<(Instant, u64, u64)>,
+}
+
+impl ThroughputHistory {
+    /// Add a sample from the current byte counters
+    ///
+    /// Rates are scaled to the time since the previous poll, so a late
+    /// timer tick does not show up as a spike. Returns the new rates, or
+    /// None for the first poll (nothing to compare against).
+    fn record(&mut self, now: Instant, rx_total: u64, tx_total: u64) -> Option<(u64, u64)> {
+        let (then, last_rx, last_tx) = self.last.replace((now, rx_total, tx_total))?;
+        let elapsed = now.duration_since(then).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        // Counters restart from zero with each session
+        let rate = |total: u64, last: u64| (total.saturating_sub(last) as f64 / elapsed) as u64;
+        let sample = (rate(rx_total, last_rx), rate(tx_total, last_tx));
+
+        if self.samples.len() == HISTORY_SECONDS {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        Some(sample)
+    }
+}
+
+/// Format numbers as a JSON array for QML (JSON.parse)
+fn json_series(values: impl Iterator<Item = u64>) -> String {
+    let items: Vec<String> = values.map(|v| v.to_string()).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Background thread running the user-space NAT
+struct NatWorker {
+    running: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for NatWorker {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Idle time between polls when no frames are moving
+const NAT_IDLE_SLEEP: Duration = Duration::from_millis(2);
+/// Idle time between checks while the session is not running
+const NAT_SUSPENDED_SLEEP: Duration = Duration::from_millis(100);
+
+/// Shuttle frames between the driver and the NAT stack until stopped
+fn nat_thread(fd: i32, running: Arc<AtomicBool>) {
+    use rising_sun_common::ioctl::{sunpci_net_recv_frame, sunpci_net_send_frame};
+
+    let dns = nat::host_nameserver();
+    tracing::info!(
+        "NAT started (guest {}, gateway {}, DNS via {})",
+        nat::GUEST_IP,
+        nat::GATEWAY_IP,
+        dns.map(|d| d.to_string()).unwrap_or_else(|| "none".to_string())
+    );
+    let mut stack = NatStack::new(dns);
+
+    while running.load(Ordering::SeqCst) {
+        if !session_gate::is_open() {
+            std::thread::sleep(NAT_SUSPENDED_SLEEP);
+            continue;
+        }
+        let mut busy = false;
+
+        // Frames from the guest
+        loop {
+            let mut frame = NetFrame::default();
+            match unsafe { sunpci_net_recv_frame(fd, &mut frame) } {
+                Ok(_) => {
+                    stack.handle_frame(frame.as_bytes());
+                    busy = true;
+                }
+                Err(e) if e as i32 == libc::EAGAIN => break,
+                Err(e) => {
+                    tracing::error!("NAT stopped: failed to read guest frame: {}", e);
+                    return;
+                }
+            }
+        }
+
+        // Frames for the guest
+        stack.poll();
+        while let Some(frame) = stack.next_frame() {
+            busy = true;
+            if let Err(e) = unsafe { sunpci_net_send_frame(fd, &NetFrame::new(&frame)) } {
+                tracing::trace!("NAT: dropped frame for guest: {}", e);
+            }
+        }
+
+        if !busy {
+            std::thread::sleep(NAT_IDLE_SLEEP);
+        }
+    }
+
+    tracing::info!("NAT stopped ({} connections closed)", stack.connection_count());
+}
+
+/// Store a null-terminated interface name in a driver network config
+fn set_interface_name(config: &mut NetworkConfig, name: &str) {
+    let bytes = name.as_bytes();
+    let len = bytes.len().min(config.interface.len() - 1);
+    config.interface[..len].copy_from_slice(&bytes[..len]);
+    config.interface[len..].fill(0);
+}
+
 /// Parse MAC address string (XX:XX:XX:XX:XX:XX) to bytes
 fn parse_mac_address(mac: &str) -> Option<[u8; 6]> {
     let parts: Vec<&str> = mac.split(':').collect();
@@ -316,6 +780,108 @@ fn parse_mac_address(mac: &str) -> Option<[u8; 6]> {
     Some(bytes)
 }
 
+/// UDP port for Wake-on-LAN packets (discard)
+const WOL_PORT: u16 = 9;
+
+/// Wake-on-LAN magic packet: 6 bytes of 0xFF, then the MAC 16 times
+fn magic_packet(mac: &[u8; 6]) -> [u8; 102] {
+    let mut packet = [0xFF; 102];
+    for chunk in packet[6..].chunks_exact_mut(6) {
+        chunk.copy_from_slice(mac);
+    }
+    packet
+}
+
+/// Broadcast a magic packet, out of `interface` if given
+fn send_magic_packet(mac: &[u8; 6], interface: Option<&str>) -> std::io::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_broadcast(true)?;
+
+    if let Some(name) = interface {
+        // SO_BINDTODEVICE needs CAP_NET_RAW; without it the default
+        // route's interface is used, which is usually the same LAN
+        let ret = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                name.as_ptr() as *const libc::c_void,
+                name.len() as libc::socklen_t,
+            )
+        };
+        if ret < 0 {
+            tracing::debug!(
+                "Cannot bind Wake-on-LAN socket to {}: {}",
+                name,
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    socket.send_to(&magic_packet(mac), (Ipv4Addr::BROADCAST, WOL_PORT))?;
+    Ok(())
+}
+
+/// Overall network state from the guest adapter and the host uplink
+///
+/// `host` is None when no uplink is involved (NAT) or it is unknown, in
+/// which case the host side is assumed to be fine.
+fn link_state(enabled: bool, guest_up: bool, host: Option<&LinkInfo>) -> &'static str {
+    if !enabled {
+        "disabled"
+    } else if !guest_up {
+        "guest_offline"
+    } else if host.is_some_and(|h| !h.carrier) {
+        "host_unplugged"
+    } else {
+        "connected"
+    }
+}
+
+/// Why a MAC address cannot be used for the guest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MacWarning {
+    /// Not six colon-separated hex bytes
+    Invalid,
+    /// FF:FF:FF:FF:FF:FF
+    Broadcast,
+    /// Group bit set; no NIC may send from a multicast address
+    Multicast,
+    /// 00:00:00:00:00:00 (means auto-generate to the driver)
+    Zero,
+}
+
+impl MacWarning {
+    /// Identifier passed to QML, which shows a translated message
+    fn code(self) -> &'static str {
+        match self {
+            Self::Invalid => "invalid",
+            Self::Broadcast => "broadcast",
+            Self::Multicast => "multicast",
+            Self::Zero => "zero",
+        }
+    }
+}
+
+/// Validate a MAC address as entered; None for an empty field (auto)
+///
+/// Mask placeholders from the input field ("__:__:...") count as empty.
+fn check_mac_address(mac: &str) -> Result<Option<[u8; 6]>, MacWarning> {
+    if mac.chars().all(|c| matches!(c, ':' | '_' | ' ')) {
+        return Ok(None);
+    }
+    let bytes = parse_mac_address(mac).ok_or(MacWarning::Invalid)?;
+    if bytes == [0xFF; 6] {
+        Err(MacWarning::Broadcast)
+    } else if bytes[0] & 0x01 != 0 {
+        Err(MacWarning::Multicast)
+    } else if bytes == [0; 6] {
+        Err(MacWarning::Zero)
+    } else {
+        Ok(Some(bytes))
+    }
+}
+
 /// Format MAC address bytes to string (used in tests)
 #[cfg(test)]
 fn format_mac_address(mac: &[u8; 6]) -> String {
@@ -342,93 +908,23 @@ fn format_byte_size(bytes: u64) -> String {
     }
 }
 
-/// Enumerate available network interfaces on the system
-fn enumerate_network_interfaces() -> Vec<String> {
-    let mut interfaces = Vec::new();
-
-    // Read from /sys/class/net/
-    if let Ok(entries) = std::fs::read_dir("/sys/class/net") {
-        for entry in entries.flatten() {
-            if let Ok(name) = entry.file_name().into_string() {
-                // Skip loopback
-                if name == "lo" {
-                    continue;
-                }
-                
-                // Try to determine interface type
-                let operstate_path = format!("/sys/class/net/{}/operstate", name);
-                let iface_type = get_interface_type(&name);
-                
-                // Check if interface is up
-                let state = std::fs::read_to_string(&operstate_path)
-                    .map(|s| s.trim().to_string())
-                    .unwrap_or_else(|_| "unknown".to_string());
-                
-                let display = if state == "up" {
-                    format!("{} - {} (up)", name, iface_type)
-                } else {
-                    format!("{} - {}", name, iface_type)
-                };
-                
-                interfaces.push(display);
-            }
-        }
-    }
-
-    // Sort: physical interfaces first, then virtual
-    interfaces.sort_by(|a, b| {
-        let a_is_physical = !a.contains("Virtual") && !a.contains("Bridge") && !a.contains("TAP");
-        let b_is_physical = !b.contains("Virtual") && !b.contains("Bridge") && !b.contains("TAP");
-        b_is_physical.cmp(&a_is_physical).then(a.cmp(b))
-    });
-
-    interfaces
+/// List the host network interfaces in the background
+fn list_interfaces() -> Deferred<Vec<String>> {
+    Deferred::spawn("list-interfaces", enumerate_network_interfaces)
 }
 
-/// Determine interface type from name and sysfs
-fn get_interface_type(name: &str) -> &'static str {
-    // Check for wireless
-    let wireless_path = format!("/sys/class/net/{}/wireless", name);
-    if std::path::Path::new(&wireless_path).exists() {
-        return "Wireless";
-    }
-
-    // Check device type
-    let type_path = format!("/sys/class/net/{}/type", name);
-    if let Ok(type_str) = std::fs::read_to_string(&type_path) {
-        if let Ok(type_num) = type_str.trim().parse::<u32>() {
-            match type_num {
-                1 => {
-                    // Ethernet - check if it's a bridge or virtual
-                    if name.starts_with("br") || name.starts_with("virbr") {
-                        return "Bridge";
-                    }
-                    if name.starts_with("veth") || name.starts_with("docker") {
-                        return "Virtual";
-                    }
-                    if name.starts_with("tap") || name.starts_with("tun") {
-                        return "TAP/TUN";
-                    }
-                    return "Ethernet";
-                }
-                772 => return "Loopback",
-                _ => {}
+/// Host network interfaces as "name - kind" entries, "(up)" when up
+fn enumerate_network_interfaces() -> Vec<String> {
+    netsetup::host_interfaces()
+        .iter()
+        .map(|iface| {
+            if iface.up {
+                format!("{} - {} (up)", iface.name, iface.kind.name())
+            } else {
+                format!("{} - {}", iface.name, iface.kind.name())
             }
-        }
-    }
-
-    // Check naming convention
-    if name.starts_with("en") || name.starts_with("eth") {
-        "Ethernet"
-    } else if name.starts_with("wl") || name.starts_with("wlan") {
-        "Wireless"
-    } else if name.starts_with("br") {
-        "Bridge"
-    } else if name.starts_with("docker") || name.starts_with("veth") {
-        "Virtual"
-    } else {
-        "Unknown"
-    }
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -449,6 +945,59 @@ mod tests {
         assert_eq!(parse_mac_address("00:11:22"), None);
     }
 
+    #[test]
+    fn test_check_mac_address() {
+        assert_eq!(check_mac_address(""), Ok(None));
+        assert_eq!(check_mac_address(":::::"), Ok(None));
+        assert_eq!(
+            check_mac_address("08:00:20:12:34:56"),
+            Ok(Some([0x08, 0x00, 0x20, 0x12, 0x34, 0x56]))
+        );
+        assert_eq!(check_mac_address("08:00:2"), Err(MacWarning::Invalid));
+        assert_eq!(check_mac_address("01:00:5E:00:00:01"), Err(MacWarning::Multicast));
+        assert_eq!(check_mac_address("FF:FF:FF:FF:FF:FF"), Err(MacWarning::Broadcast));
+        assert_eq!(check_mac_address("00:00:00:00:00:00"), Err(MacWarning::Zero));
+    }
+
+    #[test]
+    fn test_link_state() {
+        let plugged = LinkInfo { carrier: true, ..Default::default() };
+        let unplugged = LinkInfo::default();
+        assert_eq!(link_state(false, false, Some(&plugged)), "disabled");
+        assert_eq!(link_state(true, false, Some(&unplugged)), "guest_offline");
+        assert_eq!(link_state(true, true, Some(&unplugged)), "host_unplugged");
+        assert_eq!(link_state(true, true, Some(&plugged)), "connected");
+        assert_eq!(link_state(true, true, None), "connected");
+    }
+
+    #[test]
+    fn test_throughput_history() {
+        let start = Instant::now();
+        let mut history = ThroughputHistory::default();
+        assert_eq!(history.record(start, 1000, 500), None);
+        assert_eq!(
+            history.record(start + Duration::from_secs(2), 5000, 500),
+            Some((2000, 0))
+        );
+        // Counter reset at a new session
+        assert_eq!(history.record(start + Duration::from_secs(3), 100, 0), Some((0, 0)));
+
+        for i in 0..HISTORY_SECONDS as u64 {
+            history.record(start + Duration::from_secs(4 + i), 100 + i * 10, 0);
+        }
+        assert_eq!(history.samples.len(), HISTORY_SECONDS);
+        assert_eq!(history.samples.back(), Some(&(10, 0)));
+        assert_eq!(json_series([1u64, 2, 3].into_iter()), "[1,2,3]");
+    }
+
+    #[test]
+    fn test_magic_packet() {
+        let mac = [0x08, 0x00, 0x20, 0x12, 0x34, 0x56];
+        let packet = magic_packet(&mac);
+        assert_eq!(&packet[..6], &[0xFF; 6]);
+        assert!(packet[6..].chunks(6).all(|c| c == mac));
+    }
+
     #[test]
     fn test_format_mac_address() {
         assert_eq!(
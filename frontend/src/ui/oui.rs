@@ -0,0 +1,68 @@
+//! MAC address vendor lookup.
+//!
+//! The first three bytes of a globally administered MAC address are the
+//! IEEE Organizationally Unique Identifier (OUI) of the vendor. A short
+//! table of vendors likely to show up next to a SunPCi guest (Sun itself,
+//! common NIC makers and virtualization products) is compiled in when the
+//! `oui-table` feature is enabled; without it every lookup misses and the
+//! network settings only show the address type.
+
+/// Known OUIs, sorted by prefix for binary search
+#[cfg(feature = "oui-table")]
+const OUI_TABLE: &[([u8; 3], &str)] = &[
+    ([0x00, 0x00, 0x0C], "Cisco Systems"),
+    ([0x00, 0x00, 0x5E], "IANA"),
+    ([0x00, 0x00, 0xC0], "Western Digital / SMC"),
+    ([0x00, 0x03, 0x93], "Apple"),
+    ([0x00, 0x03, 0xBA], "Sun Microsystems"),
+    ([0x00, 0x04, 0xAC], "IBM"),
+    ([0x00, 0x05, 0x5D], "D-Link"),
+    ([0x00, 0x05, 0x69], "VMware"),
+    ([0x00, 0x0A, 0x95], "Apple"),
+    ([0x00, 0x0C, 0x29], "VMware"),
+    ([0x00, 0x10, 0x4B], "3Com"),
+    ([0x00, 0x10, 0x5A], "3Com"),
+    ([0x00, 0x14, 0x4F], "Sun Microsystems"),
+    ([0x00, 0x15, 0x5D], "Microsoft (Hyper-V)"),
+    ([0x00, 0x16, 0x3E], "Xen"),
+    ([0x00, 0x1B, 0x21], "Intel"),
+    ([0x00, 0x1C, 0x42], "Parallels"),
+    ([0x00, 0x20, 0xAF], "3Com"),
+    ([0x00, 0x50, 0x56], "VMware"),
+    ([0x00, 0x60, 0x97], "3Com"),
+    ([0x00, 0x80, 0xC8], "D-Link"),
+    ([0x00, 0x90, 0x27], "Intel"),
+    ([0x00, 0xA0, 0xC9], "Intel"),
+    ([0x00, 0xAA, 0x00], "Intel"),
+    ([0x00, 0xE0, 0x4C], "Realtek"),
+    ([0x02, 0x60, 0x8C], "3Com"),
+    ([0x08, 0x00, 0x20], "Sun Microsystems"),
+    ([0x08, 0x00, 0x27], "VirtualBox"),
+    ([0x08, 0x00, 0x2B], "Digital Equipment"),
+    ([0x52, 0x54, 0x00], "QEMU/KVM"),
+];
+
+#[cfg(not(feature = "oui-table"))]
+const OUI_TABLE: &[([u8; 3], &str)] = &[];
+
+/// Vendor name for a MAC address, if its OUI is in the table
+pub fn vendor(mac: &[u8; 6]) -> Option<&'static str> {
+    let oui = [mac[0], mac[1], mac[2]];
+    OUI_TABLE
+        .binary_search_by(|(prefix, _)| prefix.cmp(&oui))
+        .ok()
+        .map(|i| OUI_TABLE[i].1)
+}
+
+#[cfg(all(test, feature = "oui-table"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vendor_lookup() {
+        assert!(OUI_TABLE.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(vendor(&[0x08, 0x00, 0x20, 0x12, 0x34, 0x56]), Some("Sun Microsystems"));
+        assert_eq!(vendor(&[0x52, 0x54, 0x00, 0x00, 0x00, 0x01]), Some("QEMU/KVM"));
+        assert_eq!(vendor(&[0x12, 0x34, 0x56, 0x00, 0x00, 0x01]), None);
+    }
+}
@@ -0,0 +1,235 @@
+//! Recent files list model Qt bridge.
+//!
+//! One category of the recently used files kept in the configuration
+//! ("disk", "iso" or "floppy", set through `category`), as a
+//! QAbstractListModel for the media dialogs' lists. Files can be pinned to
+//! the top of the list, removed one by one or cleared; each change is
+//! written to the configuration file straight away. `load` also drops files
+//! that no longer exist.
+//!
+//! ConfigManager adds the files it is given through `remember`, and leaves
+//! the recent files as they were saved.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++" {
+        include!(<QtCore/QAbstractListModel>);
+
+        include!("cxx-qt-lib/qbytearray.h");
+        type QByteArray = cxx_qt_lib::QByteArray;
+        include!("cxx-qt-lib/qhash.h");
+        type QHash_i32_QByteArray = cxx_qt_lib::QHash<cxx_qt_lib::QHashPair_i32_QByteArray>;
+        include!("cxx-qt-lib/qmodelindex.h");
+        type QModelIndex = cxx_qt_lib::QModelIndex;
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+        include!("cxx-qt-lib/qvariant.h");
+        type QVariant = cxx_qt_lib::QVariant;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[base = "QAbstractListModel"]
+        #[qproperty(QString, category)]
+        #[qproperty(i32, count)]
+        type RecentFilesModel = super::RecentFilesModelRust;
+
+        /// Read the category's files from the configuration file, dropping
+        /// those that no longer exist
+        #[qinvokable]
+        fn load(self: Pin<&mut RecentFilesModel>);
+
+        /// Put a file at the top of the list, after the pinned ones
+        #[qinvokable]
+        fn add(self: Pin<&mut RecentFilesModel>, path: QString);
+
+        /// Pin a row's file to the top of the list, or unpin it
+        #[qinvokable]
+        fn pin(self: Pin<&mut RecentFilesModel>, row: i32, pinned: bool);
+
+        /// Forget a row's file
+        #[qinvokable]
+        fn remove(self: Pin<&mut RecentFilesModel>, row: i32);
+
+        /// Forget all files that are not pinned
+        #[qinvokable]
+        fn clear(self: Pin<&mut RecentFilesModel>);
+    }
+
+    unsafe extern "RustQt" {
+        #[inherit]
+        #[cxx_name = "beginResetModel"]
+        unsafe fn begin_reset_model(self: Pin<&mut RecentFilesModel>);
+        #[inherit]
+        #[cxx_name = "endResetModel"]
+        unsafe fn end_reset_model(self: Pin<&mut RecentFilesModel>);
+    }
+
+    unsafe extern "RustQt" {
+        #[qinvokable]
+        #[cxx_override]
+        fn data(self: &RecentFilesModel, index: &QModelIndex, role: i32) -> QVariant;
+
+        #[qinvokable]
+        #[cxx_override]
+        #[cxx_name = "roleNames"]
+        fn role_names(self: &RecentFilesModel) -> QHash_i32_QByteArray;
+
+        #[qinvokable]
+        #[cxx_override]
+        #[cxx_name = "rowCount"]
+        fn row_count(self: &RecentFilesModel, parent: &QModelIndex) -> i32;
+    }
+}
+
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use cxx_qt_lib::{QByteArray, QHash, QHashPair_i32_QByteArray, QModelIndex, QString, QVariant};
+use rising_sun_common::{RecentCategory, RecentFiles, load_config, save_config};
+
+/// First role number free for models (Qt::UserRole)
+const USER_ROLE: i32 = 0x0100;
+
+/// Role names as QML sees them, from USER_ROLE on: the full path, the
+/// file name, and whether it is pinned
+const ROLES: [&str; 3] = ["path", "name", "pinned"];
+
+/// Add a file to the recent files of a category in the configuration file
+pub fn remember(category: RecentCategory, path: PathBuf) {
+    if let Err(e) = update_recent(|recent| recent.add(category, path)) {
+        tracing::warn!("Failed to remember recent file: {}", e);
+    }
+}
+
+/// Change the recent files in the configuration file
+fn update_recent(change: impl FnOnce(&mut RecentFiles)) -> anyhow::Result<RecentFiles> {
+    let mut config = load_config()?;
+    change(&mut config.recent);
+    save_config(&config)?;
+    Ok(config.recent)
+}
+
+/// Rust implementation of the RecentFilesModel
+#[derive(Default)]
+pub struct RecentFilesModelRust {
+    category: QString,
+    count: i32,
+    /// Files and whether each is pinned
+    files: RefCell<Vec<(PathBuf, bool)>>,
+}
+
+impl qobject::RecentFilesModel {
+    /// Read the category's files, dropping missing ones
+    pub fn load(self: Pin<&mut Self>) {
+        let result = load_config().map_err(anyhow::Error::from).and_then(|mut config| {
+            if config.recent.prune() {
+                save_config(&config)?;
+            }
+            Ok(config.recent)
+        });
+        self.show(result);
+    }
+
+    /// Put a file at the top of the list
+    pub fn add(self: Pin<&mut Self>, path: QString) {
+        let Some(category) = self.recent_category() else { return };
+        let path = PathBuf::from(path.to_string());
+        let result = update_recent(|recent| recent.add(category, path));
+        self.show(result);
+    }
+
+    /// Pin or unpin a row's file
+    pub fn pin(self: Pin<&mut Self>, row: i32, pinned: bool) {
+        let (Some(category), Some(path)) = (self.recent_category(), self.path(row)) else {
+            return;
+        };
+        let result = update_recent(|recent| recent.set_pinned(category, &path, pinned));
+        self.show(result);
+    }
+
+    /// Forget a row's file
+    pub fn remove(self: Pin<&mut Self>, row: i32) {
+        let (Some(category), Some(path)) = (self.recent_category(), self.path(row)) else {
+            return;
+        };
+        let result = update_recent(|recent| recent.remove(category, &path));
+        self.show(result);
+    }
+
+    /// Forget the files that are not pinned
+    pub fn clear(self: Pin<&mut Self>) {
+        let Some(category) = self.recent_category() else { return };
+        let result = update_recent(|recent| recent.clear(category));
+        self.show(result);
+    }
+
+    /// Value of a role for a row
+    pub fn data(&self, index: &QModelIndex, role: i32) -> QVariant {
+        let files = self.files.borrow();
+        let Some((path, pinned)) = files.get(index.row() as usize) else {
+            return QVariant::default();
+        };
+        match ROLES.get((role - USER_ROLE) as usize).copied() {
+            Some("path") => QVariant::from(&QString::from(path.to_string_lossy().as_ref())),
+            Some("name") => QVariant::from(&QString::from(
+                path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().as_ref(),
+            )),
+            Some("pinned") => QVariant::from(pinned),
+            _ => QVariant::default(),
+        }
+    }
+
+    /// Role numbers and the names QML uses for them
+    pub fn role_names(&self) -> QHash<QHashPair_i32_QByteArray> {
+        let mut roles = QHash::<QHashPair_i32_QByteArray>::default();
+        for (i, name) in ROLES.iter().enumerate() {
+            roles.insert(USER_ROLE + i as i32, QByteArray::from(*name));
+        }
+        roles
+    }
+
+    /// Number of rows
+    pub fn row_count(&self, _parent: &QModelIndex) -> i32 {
+        self.files.borrow().len() as i32
+    }
+
+    /// The category set through `category`
+    fn recent_category(&self) -> Option<RecentCategory> {
+        let category = RecentCategory::parse(&self.category.to_string());
+        if category.is_none() {
+            tracing::warn!("Unknown recent files category {:?}", self.category.to_string());
+        }
+        category
+    }
+
+    /// A row's file
+    fn path(&self, row: i32) -> Option<PathBuf> {
+        self.files.borrow().get(row as usize).map(|(path, _)| path.clone())
+    }
+
+    /// Show the category's files as the configuration has them now
+    fn show(mut self: Pin<&mut Self>, recent: anyhow::Result<RecentFiles>) {
+        let recent = match recent {
+            Ok(recent) => recent,
+            Err(e) => {
+                tracing::error!("Failed to update recent files: {}", e);
+                return;
+            }
+        };
+        let Some(category) = self.recent_category() else { return };
+        let files: Vec<(PathBuf, bool)> = recent
+            .list(category)
+            .iter()
+            .map(|path| (path.clone(), recent.is_pinned(path)))
+            .collect();
+        let count = files.len() as i32;
+        // SAFETY: begin/end pair around the rows replaced
+        unsafe { self.as_mut().begin_reset_model() };
+        *self.files.borrow_mut() = files;
+        unsafe { self.as_mut().end_reset_model() };
+        self.as_mut().set_count(count);
+    }
+}
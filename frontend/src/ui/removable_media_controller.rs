@@ -0,0 +1,134 @@
+//! Removable media controller Qt bridge.
+//!
+//! Follows USB sticks and other removable drives the host mounts (see
+//! `rising_sun_common::removable`). A QML timer calls `poll`, and each
+//! volume inserted or removed is signalled; main.qml maps it to the next
+//! free drive letter through the DriveMappingController, right away if
+//! `auto_map` is set or after asking otherwise, and unmaps it on removal.
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(bool, available)]
+        #[qproperty(bool, auto_map)]
+        type RemovableMediaController = super::RemovableMediaControllerRust;
+
+        /// Check for removable volumes inserted or removed
+        #[qinvokable]
+        fn poll(self: Pin<&mut RemovableMediaController>);
+
+        /// Removable volumes mounted now, as a JSON array of
+        /// {device, mountPoint, label}
+        #[qinvokable]
+        fn get_volumes_json(self: &RemovableMediaController) -> QString;
+
+        /// Signal emitted when the host mounts a removable volume
+        #[qsignal]
+        fn media_inserted(
+            self: Pin<&mut RemovableMediaController>,
+            device: QString,
+            mount_point: QString,
+            label: QString,
+        );
+
+        /// Signal emitted when a removable volume is unmounted or pulled out
+        #[qsignal]
+        fn media_removed(self: Pin<&mut RemovableMediaController>, device: QString, mount_point: QString);
+    }
+}
+
+use std::cell::RefCell;
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+use rising_sun_common::removable::{MediaEvent, MediaMonitor};
+
+use super::startup;
+
+/// Rust implementation of the RemovableMediaController
+pub struct RemovableMediaControllerRust {
+    /// Whether removable media are being followed
+    available: bool,
+    /// Map inserted volumes without asking
+    auto_map: bool,
+    /// None if uevents or the mount table cannot be read
+    monitor: RefCell<Option<MediaMonitor>>,
+}
+
+impl Default for RemovableMediaControllerRust {
+    fn default() -> Self {
+        let _timing = startup::span("RemovableMediaController");
+        let monitor = MediaMonitor::new()
+            .inspect_err(|e| tracing::warn!("Cannot follow removable media: {}", e))
+            .ok();
+        if let Some(monitor) = &monitor {
+            tracing::debug!("{} removable volumes mounted", monitor.volumes().len());
+        }
+        Self {
+            available: monitor.is_some(),
+            auto_map: false,
+            monitor: RefCell::new(monitor),
+        }
+    }
+}
+
+impl qobject::RemovableMediaController {
+    /// Check for removable volumes inserted or removed
+    pub fn poll(mut self: Pin<&mut Self>) {
+        let events = match self.monitor.borrow_mut().as_mut().map(MediaMonitor::poll) {
+            Some(Ok(events)) => events,
+            Some(Err(e)) => {
+                tracing::warn!("Failed to check for removable media: {}", e);
+                return;
+            }
+            None => return,
+        };
+
+        for event in events {
+            match event {
+                MediaEvent::Inserted(volume) => {
+                    tracing::info!(
+                        "Removable volume {} mounted at {}",
+                        volume.device.display(),
+                        volume.mount_point.display()
+                    );
+                    self.as_mut().media_inserted(
+                        QString::from(volume.device.to_string_lossy().as_ref()),
+                        QString::from(volume.mount_point.to_string_lossy().as_ref()),
+                        QString::from(&volume.label),
+                    );
+                }
+                MediaEvent::Removed(volume) => {
+                    tracing::info!("Removable volume {} removed", volume.device.display());
+                    self.as_mut().media_removed(
+                        QString::from(volume.device.to_string_lossy().as_ref()),
+                        QString::from(volume.mount_point.to_string_lossy().as_ref()),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Removable volumes mounted now as JSON
+    pub fn get_volumes_json(&self) -> QString {
+        let monitor = self.monitor.borrow();
+        let volumes: Vec<serde_json::Value> = monitor
+            .iter()
+            .flat_map(|m| m.volumes())
+            .map(|v| {
+                serde_json::json!({
+                    "device": v.device.to_string_lossy(),
+                    "mountPoint": v.mount_point.to_string_lossy(),
+                    "label": v.label,
+                })
+            })
+            .collect();
+        QString::from(&serde_json::Value::Array(volumes).to_string())
+    }
+}
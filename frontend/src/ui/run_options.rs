@@ -0,0 +1,67 @@
+//! Settings given on the command line for this run.
+//!
+//! `main` parses them before Qt starts. They are laid over the
+//! configuration where the window and the session read it, and never
+//! written back to the configuration file: `--primary-disk` and `--iso`
+//! replace the media of the session started, `--fullscreen` and
+//! `--auto-start` add to the configuration's own switches, and
+//! `--headless` keeps the window hidden and starts the session at once, to
+//! be driven through the control API (`rising-sun-cli --host`) or VNC.
+//! A frontend started for a card that already has one hands them to that
+//! one instead (see `rising_sun_common::instance`).
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use rising_sun_common::{DiskConfig, GeneralConfig, StorageConfig};
+
+/// Overrides from the command line
+#[derive(Debug, Default)]
+pub struct RunOptions {
+    /// Start a session as soon as the window is up
+    pub auto_start: bool,
+    /// Start in fullscreen
+    pub fullscreen: bool,
+    /// Never show the window; implies `auto_start`
+    pub headless: bool,
+    /// Hard disk to boot instead of the configured primary disk
+    pub primary_disk: Option<PathBuf>,
+    /// ISO image to put in the CD-ROM drive when the session starts
+    pub iso: Option<PathBuf>,
+}
+
+static OPTIONS: OnceLock<RunOptions> = OnceLock::new();
+
+/// Take the command line's overrides; only the first call counts
+pub fn set(options: RunOptions) {
+    let _ = OPTIONS.set(options);
+}
+
+/// The command line's overrides (none if `set` was not called)
+pub fn get() -> &'static RunOptions {
+    OPTIONS.get_or_init(RunOptions::default)
+}
+
+/// Whether to start a session as soon as the window is up
+pub fn auto_start(general: &GeneralConfig) -> bool {
+    let options = get();
+    general.auto_start || options.auto_start || options.headless
+}
+
+/// Lay the media given on the command line over `storage`
+pub fn apply_storage(storage: &mut StorageConfig) {
+    let options = get();
+    if let Some(path) = &options.primary_disk {
+        if storage.primary_disk.as_ref().is_none_or(|disk| disk.path != *path) {
+            storage.primary_disk = Some(DiskConfig {
+                path: path.clone(),
+                bootable: true,
+                content_id: None,
+                overlay: None,
+            });
+        }
+    }
+    if let Some(path) = &options.iso {
+        storage.cdrom.mounted_iso = Some(path.clone());
+    }
+}
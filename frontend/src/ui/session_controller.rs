@@ -3,11 +3,42 @@
 //! This wraps the DriverHandle from common to provide Qt/QML integration
 //! for starting, stopping, and monitoring sessions.
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
 
 use rising_sun_common::{
-    is_driver_loaded, DriverHandle, load_config, ClipboardDirection,
-    ioctl::{IoctlSessionConfig, FramebufferInfo, flags},
+    check_compatibility, driver, is_driver_loaded, AppConfig, BootDevice, DriverHandle, load_card_config, load_config,
+    save_config,
+    ClipboardDirection, StorageConfig,
+    ioctl::{IoctlSessionConfig, FramebufferInfo, SessionOwner, SessionState, boot_device, flags},
+};
+use rising_sun_common::bios;
+use rising_sun_common::el_torito;
+use rising_sun_common::control::{
+    command, ControlClient, ControlRequest, ControlResponse, ControlServer, PendingRequest,
+};
+use rising_sun_common::handoff::{self, HandoffError, HandoffState};
+use rising_sun_common::instance;
+use rising_sun_common::mdns::{Advertiser, ServiceInfo};
+use rising_sun_common::overlay;
+use rising_sun_common::saved_state;
+use rising_sun_common::startup::Deferred;
+use rising_sun_common::tasks::{TaskHandle, TaskManager, TaskStatus};
+use rising_sun_common::watchdog::{self, Incident, Watchdog};
+use rising_sun_common::media_check::{self, MediaLock, MediaSlot};
+
+use super::actions::{self, Action};
+use super::ephemeral;
+use super::run_options;
+use super::session_gate;
+use super::startup;
+use super::framebuffer_provider::{
+    clear_framebuffer_state, dump_raw_frame, invalidate_palette, set_deinterlace_mode,
+    set_field_state, set_palette_sync, set_text_layout, update_framebuffer_state, FrameDumpInfo,
 };
 
 #[cxx_qt::bridge]
@@ -21,6 +52,7 @@ mod qobject {
         #[qobject]
         #[qml_element]
         #[qproperty(bool, driver_loaded)]
+        #[qproperty(bool, driver_probing)]
         #[qproperty(bool, session_running)]
         #[qproperty(bool, session_starting)]
         #[qproperty(bool, session_error)]
@@ -30,16 +62,64 @@ mod qobject {
         #[qproperty(i32, color_depth)]
         #[qproperty(bool, text_mode)]
         #[qproperty(QString, driver_version)]
+        #[qproperty(bool, control_listening)]
+        #[qproperty(bool, session_foreign)]
+        #[qproperty(QString, boot_cdrom)]
+        #[qproperty(QString, startup_cdrom)]
+        #[qproperty(QString, startup_floppy_a)]
+        #[qproperty(QString, startup_floppy_b)]
+        #[qproperty(QString, startup_problems)]
+        #[qproperty(QString, boot_macro)]
+        #[qproperty(i32, boot_macro_delay)]
+        #[qproperty(bool, ephemeral)]
+        #[qproperty(bool, shutting_down)]
+        #[qproperty(bool, saving_state)]
+        #[qproperty(bool, restoring_state)]
+        #[qproperty(QString, state_step)]
+        #[qproperty(i32, state_percent)]
+        #[qproperty(i32, card)]
+        #[qproperty(QString, card_name)]
         type SessionController = super::SessionControllerRust;
 
         /// Check if the SunPCi driver is loaded and accessible
         #[qinvokable]
         fn check_driver(self: Pin<&mut SessionController>);
 
+        /// Check the driver on a thread of its own, so a slow card does not
+        /// hold up the window; poll_driver_probe picks up the result
+        #[qinvokable]
+        fn probe_driver(self: Pin<&mut SessionController>);
+
+        /// Apply the result of probe_driver if it is ready; true once it
+        /// was (called by a timer while driver_probing is set)
+        #[qinvokable]
+        fn poll_driver_probe(self: Pin<&mut SessionController>) -> bool;
+
+        /// List the SunPCi cards the driver found. Returns a JSON array of
+        /// {index, name, path}.
+        #[qinvokable]
+        fn list_cards(self: &SessionController) -> QString;
+
+        /// Find out why the card cannot be opened: module, device node,
+        /// permissions, groups and security modules. Returns JSON with
+        /// {device, ok, checks: [{name, status, detail, fix}], udev_rule}.
+        #[qinvokable]
+        fn diagnose_driver(self: &SessionController) -> QString;
+
+        /// Drive card `index` instead of the current one; refused while
+        /// this frontend runs a session
+        #[qinvokable]
+        fn select_card(self: Pin<&mut SessionController>, index: i32) -> bool;
+
         /// Start a session using the current configuration
         #[qinvokable]
         fn start_session(self: Pin<&mut SessionController>);
 
+        /// Whether to start a session as soon as the window is up: the
+        /// configuration's auto_start, --auto-start or --headless
+        #[qinvokable]
+        fn auto_start(self: &SessionController) -> bool;
+
         /// Stop the running session
         #[qinvokable]
         fn stop_session(self: Pin<&mut SessionController>);
@@ -48,6 +128,25 @@ mod qobject {
         #[qinvokable]
         fn reset_session(self: Pin<&mut SessionController>);
 
+        /// Ask the guest OS to shut down cleanly; the session is stopped
+        /// when it has, or after `shutdown_timeout_secs` if it does not
+        #[qinvokable]
+        fn request_guest_shutdown(self: Pin<&mut SessionController>);
+
+        /// Stop the session if the guest has not shut down in time
+        /// (called by a timer while shutting_down is set)
+        #[qinvokable]
+        fn check_shutdown(self: Pin<&mut SessionController>);
+
+        /// The guest powered itself off; stop the session
+        #[qinvokable]
+        fn guest_powered_off(self: Pin<&mut SessionController>);
+
+        /// Follow a session started or stopped outside this frontend
+        /// (state is a SessionState value from a driver event)
+        #[qinvokable]
+        fn apply_session_state(self: Pin<&mut SessionController>, state: i32);
+
         /// Get the file descriptor for the driver (for mmap in display view)
         #[qinvokable]
         fn get_driver_fd(self: &SessionController) -> i32;
@@ -56,6 +155,15 @@ mod qobject {
         #[qinvokable]
         fn poll_display(self: Pin<&mut SessionController>);
 
+        /// Pick up a palette change reported by the driver
+        #[qinvokable]
+        fn palette_changed(self: &SessionController, generation: i32);
+
+        /// Pick up changed display settings (deinterlacing, palette sync)
+        /// in a running session
+        #[qinvokable]
+        fn reload_display_settings(self: &SessionController);
+
         /// Get framebuffer stride (bytes per row)
         #[qinvokable]
         fn get_framebuffer_stride(self: &SessionController) -> i32;
@@ -67,6 +175,86 @@ mod qobject {
         /// Get framebuffer pixel format (0=indexed8, 1=rgb565, 2=rgb888, 3=xrgb8888)
         #[qinvokable]
         fn get_framebuffer_format(self: &SessionController) -> i32;
+
+        /// Dump the next `count` frames (raw data + metadata) to a directory.
+        /// An empty directory uses a timestamped folder under the data dir.
+        /// Returns the directory being written to, or an empty string on error.
+        #[qinvokable]
+        fn dump_frames(self: Pin<&mut SessionController>, count: i32, directory: QString) -> QString;
+
+        /// Listen for CLI commands if `[remote] control_port` is set, and
+        /// for frontends started later for the card
+        #[qinvokable]
+        fn start_control(self: Pin<&mut SessionController>);
+
+        /// Handle waiting CLI commands; ones for other controllers are
+        /// forwarded with control_request
+        #[qinvokable]
+        fn poll_control(self: Pin<&mut SessionController>);
+
+        /// Answer a command forwarded with control_request
+        #[qinvokable]
+        fn control_reply(self: &SessionController, id: i32, ok: bool, message: QString);
+
+        /// A CLI command for QML to carry out (mount-iso, eject, release)
+        #[qsignal]
+        fn control_request(self: Pin<&mut SessionController>, id: i32, command: QString, argument: QString);
+
+        /// Why the configured boot CD cannot be booted, or "" if it can (or
+        /// the session does not boot from CD)
+        #[qinvokable]
+        fn get_boot_cd_problem(self: &SessionController) -> QString;
+
+        /// Take over a session left running or driven by another frontend
+        #[qinvokable]
+        fn adopt_session(self: Pin<&mut SessionController>) -> bool;
+
+        /// Stop driving the session without stopping the guest, for another
+        /// frontend to adopt. Returns the handoff state as JSON, or an empty
+        /// string on error.
+        #[qinvokable]
+        fn release_session(self: Pin<&mut SessionController>, cdrom: QString, cdrom_served: bool) -> QString;
+
+        /// A session was adopted; QML serves the CD-ROM again if needed
+        #[qsignal]
+        fn session_adopted(self: Pin<&mut SessionController>, cdrom: QString, cdrom_served: bool);
+
+        /// Check the running session for a crashed or hung guest
+        /// (called by a timer while the session runs)
+        #[qinvokable]
+        fn check_watchdog(self: Pin<&mut SessionController>);
+
+        /// The guest crashed or hung ("crashed"/"hung"); action is "notify",
+        /// "restart", "give_up" (too many restarts) or "run_command"
+        #[qsignal]
+        fn guest_incident(self: Pin<&mut SessionController>, incident: QString, action: QString);
+
+        /// Run the profile's crash command on the screenshot saved for an
+        /// incident; returns false if there is none or it cannot start
+        #[qinvokable]
+        fn run_crash_command(self: &SessionController, incident: QString, screenshot: QString) -> bool;
+
+        /// Suspend the guest to the saved state file, then stop the session
+        #[qinvokable]
+        fn save_state_and_stop(self: Pin<&mut SessionController>);
+
+        /// Stop saving or restoring the guest state
+        #[qinvokable]
+        fn cancel_state_task(self: &SessionController);
+
+        /// Update state_step/state_percent and finish a save or restore
+        #[qinvokable]
+        fn poll_state_task(self: Pin<&mut SessionController>);
+
+        /// A save started with save_state_and_stop finished; on success the
+        /// session has been stopped
+        #[qsignal]
+        fn state_saved(self: Pin<&mut SessionController>, success: bool, message: QString);
+
+        /// Restoring the saved state at session start finished; on failure
+        /// the session has been stopped and the saved state discarded
+        #[qsignal]
+        fn state_restored(self: Pin<&mut SessionController>, success: bool, message: QString);
     }
 }
 
@@ -77,6 +265,10 @@ use cxx_qt_lib::QString;
 pub struct SessionControllerRust {
     /// Whether the driver is loaded and accessible
     driver_loaded: bool,
+    /// Whether probe_driver is still running
+    driver_probing: bool,
+    /// Probe started by probe_driver
+    probe: RefCell<Option<Deferred<DriverProbe>>>,
     /// Whether a session is currently running
     session_running: bool,
     /// Whether session is in the process of starting
@@ -99,12 +291,81 @@ pub struct SessionControllerRust {
     handle: RefCell<Option<DriverHandle>>,
     /// Cached framebuffer info
     framebuffer: RefCell<Option<FramebufferInfo>>,
+    /// Directory receiving frame dumps (None when no dump is armed)
+    dump_dir: RefCell<Option<PathBuf>>,
+    /// Frames still to be dumped
+    dump_remaining: Cell<u32>,
+    /// Index of the next dumped frame
+    dump_index: Cell<u32>,
+    /// Locks on the session's media, held while it runs
+    media_locks: RefCell<Vec<MediaLock>>,
+    /// Crash and hang detection for the running session
+    watchdog: RefCell<Option<Watchdog>>,
+    /// mDNS advertisement of the running session, if enabled
+    advertiser: RefCell<Option<Advertiser>>,
+    /// Whether the control API is listening
+    control_listening: bool,
+    /// Control API server, if a control port is configured
+    control: RefCell<Option<ControlServer>>,
+    /// Control API on the card's local socket, for frontends started later
+    local_control: RefCell<Option<ControlServer>>,
+    /// Commands forwarded to QML, by id, waiting for control_reply
+    control_pending: RefCell<HashMap<i32, PendingRequest>>,
+    /// Id of the next forwarded command
+    next_control_id: Cell<i32>,
+    /// Whether a session is running that another frontend drives (or that
+    /// has not been adopted)
+    session_foreign: bool,
+    /// ISO mounted at session start to boot from, empty if none
+    boot_cdrom: QString,
+    /// Other ISO mounted at session start, empty if none; a CUE sheet is
+    /// left for QML to serve
+    startup_cdrom: QString,
+    /// Floppy images mounted at session start, empty if none
+    startup_floppy_a: QString,
+    startup_floppy_b: QString,
+    /// What went wrong preparing the session's media, one per line
+    startup_problems: QString,
+    /// Keystroke macro to type once the guest has started, empty if none
+    boot_macro: QString,
+    /// Time from the start of the session to typing boot_macro, in ms
+    boot_macro_delay: i32,
+    /// Mount writable media through throwaway overlays (see ephemeral)
+    ephemeral: bool,
+    /// Whether the guest has been asked to shut down
+    shutting_down: bool,
+    /// When the session is stopped if the guest has not shut down
+    shutdown_deadline: Cell<Option<Instant>>,
+    /// Flags the running session was started with
+    session_flags: Cell<Option<u32>>,
+    /// Media of the running session, handed on when released
+    session_storage: RefCell<Option<StorageConfig>>,
+    /// Whether the guest is being suspended to the saved state file
+    saving_state: bool,
+    /// Whether the guest is being restored from the saved state file
+    restoring_state: bool,
+    /// Step of the state save or restore
+    state_step: QString,
+    /// Progress of the state save or restore
+    state_percent: i32,
+    /// Worker moving the guest state between the card and the file
+    tasks: TaskManager,
+    /// Save or restore in progress
+    state_task: RefCell<Option<TaskHandle>>,
+    /// Card this frontend drives (N in /dev/sunpciN)
+    card: i32,
+    /// Name of the card from its [[cards]] section
+    card_name: QString,
 }
 
 impl Default for SessionControllerRust {
     fn default() -> Self {
+        let _timing = startup::span("SessionController");
+        register_actions();
         Self {
             driver_loaded: false,
+            driver_probing: false,
+            probe: RefCell::new(None),
             session_running: false,
             session_starting: false,
             session_error: false,
@@ -116,6 +377,40 @@ impl Default for SessionControllerRust {
             driver_version: QString::from("Unknown"),
             handle: RefCell::new(None),
             framebuffer: RefCell::new(None),
+            dump_dir: RefCell::new(None),
+            dump_remaining: Cell::new(0),
+            dump_index: Cell::new(0),
+            media_locks: RefCell::new(Vec::new()),
+            watchdog: RefCell::new(None),
+            advertiser: RefCell::new(None),
+            control_listening: false,
+            control: RefCell::new(None),
+            local_control: RefCell::new(None),
+            control_pending: RefCell::new(HashMap::new()),
+            next_control_id: Cell::new(1),
+            session_foreign: false,
+            boot_cdrom: QString::default(),
+            startup_cdrom: QString::default(),
+            startup_floppy_a: QString::default(),
+            startup_floppy_b: QString::default(),
+            startup_problems: QString::default(),
+            boot_macro: QString::default(),
+            boot_macro_delay: 0,
+            ephemeral: ephemeral::is_enabled(),
+            shutting_down: false,
+            shutdown_deadline: Cell::new(None),
+            session_flags: Cell::new(None),
+            session_storage: RefCell::new(None),
+            saving_state: false,
+            restoring_state: false,
+            state_step: QString::default(),
+            state_percent: 0,
+            tasks: TaskManager::new(1),
+            state_task: RefCell::new(None),
+            card: driver::selected_card() as i32,
+            card_name: QString::from(
+                &load_config().unwrap_or_default().card_name(driver::selected_card()),
+            ),
         }
     }
 }
@@ -123,34 +418,156 @@ impl Default for SessionControllerRust {
 impl qobject::SessionController {
     /// Check if the driver is loaded and try to open it
     pub fn check_driver(mut self: Pin<&mut Self>) {
-        let loaded = is_driver_loaded();
-        self.as_mut().set_driver_loaded(loaded);
+        // Supersedes a probe still running
+        self.probe.borrow_mut().take();
+        self.as_mut().set_driver_probing(false);
+        self.apply_driver_probe(DriverProbe::run());
+    }
 
-        if loaded {
-            // Try to open the driver
-            match DriverHandle::open() {
-                Ok(handle) => {
-                    // Get driver version
-                    if let Ok(version) = handle.get_version() {
-                        let version_str = format!("{}.{}.{}", version.major, version.minor, version.patch);
-                        self.as_mut().set_driver_version(QString::from(&version_str));
-                    }
-                    
-                    // Check current status
-                    if let Ok(status) = handle.get_status() {
-                        let running = status.state == 2; // Running state
-                        self.as_mut().set_session_running(running);
-                    }
-                    *self.handle.borrow_mut() = Some(handle);
+    /// Check the driver in the background
+    pub fn probe_driver(mut self: Pin<&mut Self>) {
+        if self.probe.borrow().is_some() {
+            return;
+        }
+        *self.probe.borrow_mut() = Some(Deferred::spawn("driver-probe", DriverProbe::run));
+        self.as_mut().set_driver_probing(true);
+    }
+
+    /// Apply the background driver check once it has finished
+    pub fn poll_driver_probe(mut self: Pin<&mut Self>) -> bool {
+        let (result, started, took) = {
+            let mut probe = self.probe.borrow_mut();
+            let Some(deferred) = probe.as_mut() else {
+                return false;
+            };
+            let Some(result) = deferred.try_take() else {
+                return false;
+            };
+            (result, deferred.started(), deferred.took())
+        };
+        self.probe.borrow_mut().take();
+        self.as_mut().set_driver_probing(false);
+        if let Some(took) = took {
+            startup::record("driver probe", started, took);
+        }
+        match result {
+            Ok(probe) => self.apply_driver_probe(probe),
+            Err(e) => {
+                self.as_mut().set_session_error(true);
+                self.set_error_message(QString::from(&format!("Failed to open driver: {}", e)));
+            }
+        }
+        true
+    }
+
+    /// Take over the card a probe opened and follow its session
+    fn apply_driver_probe(mut self: Pin<&mut Self>, probe: DriverProbe) {
+        self.as_mut().set_driver_loaded(!matches!(probe, DriverProbe::NotLoaded));
+
+        match probe {
+            DriverProbe::Opened { handle, version, running, owner } => {
+                if let Some(version) = version {
+                    self.as_mut().set_driver_version(QString::from(&version));
                 }
-                Err(e) => {
-                    self.as_mut().set_session_error(true);
-                    self.set_error_message(QString::from(&format!("Failed to open driver: {}", e)));
+                *self.handle.borrow_mut() = Some(handle);
+
+                if running {
+                    match owner {
+                        // Another frontend drives it; adopting is the user's call
+                        Ok(owner) if owner.is_owned() => {
+                            tracing::info!("Session is driven by another frontend (pid {})", owner.pid);
+                            self.as_mut().set_session_foreign(true);
+                        }
+                        // Left behind by a frontend that exited
+                        Ok(_) => {
+                            tracing::info!("Adopting session left running");
+                            self.as_mut().adopt_session();
+                        }
+                        // Driver without ownership tracking
+                        Err(_) => {
+                            self.as_mut().set_session_running(true);
+                            session_gate::set_running(true);
+                        }
+                    }
                 }
             }
-        } else {
-            self.as_mut().set_driver_version(QString::from("Not loaded"));
+            DriverProbe::Failed(e) => {
+                self.as_mut().set_session_error(true);
+                self.set_error_message(QString::from(&format!("Failed to open driver: {}", e)));
+            }
+            DriverProbe::NotLoaded => {
+                self.as_mut().set_driver_version(QString::from("Not loaded"));
+            }
+        }
+    }
+
+    /// List the cards the driver found
+    pub fn list_cards(&self) -> QString {
+        let config = load_config().unwrap_or_default();
+        let cards: Vec<CardInfo> = driver::list_devices()
+            .into_iter()
+            .map(|index| CardInfo {
+                index,
+                name: config.card_name(index),
+                path: driver::device_path(index).to_string_lossy().into_owned(),
+            })
+            .collect();
+        QString::from(&serde_json::to_string(&cards).unwrap_or_else(|_| "[]".to_string()))
+    }
+
+    /// Find out why the card cannot be opened
+    pub fn diagnose_driver(&self) -> QString {
+        let diagnosis = driver::diagnose();
+        for check in diagnosis.checks.iter().filter(|c| c.status != driver::CheckStatus::Ok) {
+            tracing::warn!("Driver check {}: {}", check.name, check.detail);
         }
+        let mut json = serde_json::to_value(&diagnosis).unwrap_or_default();
+        json["ok"] = diagnosis.is_ok().into();
+        QString::from(&json.to_string())
+    }
+
+    /// Switch to another card
+    ///
+    /// The handle to the current card is closed; a session left running
+    /// there keeps running and can be taken over again by selecting it.
+    pub fn select_card(mut self: Pin<&mut Self>, index: i32) -> bool {
+        let Ok(card) = u32::try_from(index) else {
+            return false;
+        };
+        if card == driver::selected_card() {
+            return true;
+        }
+        if *self.as_ref().session_running()
+            || *self.as_ref().session_starting()
+            || self.state_task.borrow().is_some()
+        {
+            self.as_mut().set_error_message(QString::from("Stop the session before switching cards"));
+            return false;
+        }
+        if !driver::device_path(card).exists() {
+            self.as_mut().set_error_message(QString::from(&format!("Card {} not found", card)));
+            return false;
+        }
+
+        tracing::info!("Switching to card {}", card);
+        self.handle.borrow_mut().take();
+        *self.framebuffer.borrow_mut() = None;
+        clear_framebuffer_state();
+        driver::select_card(card);
+
+        let name = load_config().unwrap_or_default().card_name(card);
+        self.as_mut().set_card_name(QString::from(&name));
+        self.as_mut().set_session_foreign(false);
+        self.as_mut().set_session_error(false);
+        self.as_mut().set_error_message(QString::default());
+        self.as_mut().set_card(index);
+        self.check_driver();
+        true
+    }
+
+    /// Whether to start a session as soon as the window is up
+    pub fn auto_start(&self) -> bool {
+        run_options::auto_start(&load_config().unwrap_or_default().general)
     }
 
     /// Start a session with the current configuration
@@ -174,8 +591,109 @@ impl qobject::SessionController {
             }
         }
 
-        // Load configuration
-        let config = load_config().unwrap_or_default();
+        // A driver from another release would misread the ioctls below, so
+        // refuse it with a way out rather than fail on one of them
+        let version = self.handle.borrow().as_ref().expect("handle opened above").get_version();
+        let version = match version {
+            Ok(version) => version,
+            Err(e) => {
+                self.as_mut().set_session_error(true);
+                self.as_mut().set_error_message(QString::from(&format!("Failed to read the driver version: {}", e)));
+                self.set_session_starting(false);
+                return;
+            }
+        };
+        if let Some(message) = check_compatibility(&version).message(&version) {
+            tracing::error!("{}", message);
+            self.as_mut().set_session_error(true);
+            self.as_mut().set_error_message(QString::from(&message));
+            self.set_session_starting(false);
+            return;
+        }
+
+        // Load configuration with the command line's media over it,
+        // following disk images that moved
+        let mut config = load_card_config().unwrap_or_default();
+        run_options::apply_storage(&mut config.storage);
+        for (old, new) in config.relocate_images() {
+            tracing::info!("Disk image {:?} not found, using {:?}", old, new);
+        }
+        set_deinterlace_mode(config.display.deinterlace);
+        session_gate::set_suspend(config.general.suspend_polling_when_stopped);
+        set_palette_sync(config.display.palette_sync);
+
+        // Template disks are mounted through their copy-on-write child
+        if let Err(e) = instantiate_templates(&mut config.storage) {
+            let message = format!("Cannot start session: {}", e);
+            self.as_mut().set_session_error(true);
+            self.as_mut().set_error_message(QString::from(&message));
+            self.set_session_starting(false);
+            return;
+        }
+
+        // A bad BIOS image leaves the guest dead at power-on, so refuse it here
+        let bios_image = match bios::check(&config.bios) {
+            Ok(image) => image,
+            Err(problem) => {
+                let message = format!("Cannot start session: {}", problem);
+                self.as_mut().set_session_error(true);
+                self.as_mut().set_error_message(QString::from(&message));
+                self.set_session_starting(false);
+                return;
+            }
+        };
+        if let Some(ref image) = bios_image {
+            let version = image.version.as_deref().unwrap_or("undated");
+            if config.bios.known(&image.sha256).is_none() {
+                tracing::warn!("BIOS image {} ({}) is not a known-good image", image.path.display(), version);
+            } else {
+                tracing::info!("Using BIOS image {} ({})", image.path.display(), version);
+            }
+        }
+
+        // Fail now on unusable disks rather than when the guest touches them;
+        // removable media problems only detach that drive
+        let report = media_check::check_media(&config.storage);
+        let mut problems = Vec::new();
+        for issue in &report.issues {
+            tracing::warn!("Media problem: {}", issue);
+            problems.push(issue.to_string());
+        }
+        let disk_issue = [MediaSlot::PrimaryDisk, MediaSlot::SecondaryDisk]
+            .into_iter()
+            .find_map(|slot| report.issue(slot));
+        if let Some(issue) = disk_issue {
+            let message = format!("Cannot start session: {}", issue);
+            self.as_mut().set_session_error(true);
+            self.as_mut().set_error_message(QString::from(&message));
+            self.set_session_starting(false);
+            return;
+        }
+        for issue in &report.issues {
+            issue.slot.remove(&mut config.storage);
+        }
+        match media_check::lock_media(&config.storage) {
+            Ok(locks) => *self.media_locks.borrow_mut() = locks,
+            Err(issue) => {
+                let message = format!("Cannot start session: {}", issue);
+                self.as_mut().set_session_error(true);
+                self.as_mut().set_error_message(QString::from(&message));
+                self.set_session_starting(false);
+                return;
+            }
+        }
+
+        // Throwaway overlays take the writes when changes are not kept
+        ephemeral::set_enabled(*self.as_ref().ephemeral());
+        if let Err(e) = ephemeral_disks(&mut config.storage) {
+            ephemeral::discard();
+            self.media_locks.borrow_mut().clear();
+            let message = format!("Cannot start session: {:#}", e);
+            self.as_mut().set_session_error(true);
+            self.as_mut().set_error_message(QString::from(&message));
+            self.set_session_starting(false);
+            return;
+        }
 
         // Build ioctl config (memory is physical on SunPCi card, not configurable)
         let mut ioctl_config = IoctlSessionConfig::default();
@@ -200,6 +718,11 @@ impl qobject::SessionController {
                 }
             }
         }
+        // Pick the guest up where it was suspended if its disks are unchanged
+        let resume = !*self.as_ref().ephemeral() && resumable(&config.storage);
+        if resume {
+            session_flags |= flags::RESUME;
+        }
         ioctl_config.flags = session_flags;
 
         // Set disk paths
@@ -212,9 +735,43 @@ impl qobject::SessionController {
                 &secondary.path.to_string_lossy());
         }
 
+        if let Some(ref image) = bios_image {
+            IoctlSessionConfig::set_path(&mut ioctl_config.bios_path,
+                &image.path.to_string_lossy());
+        }
+
+        // Boot order; a boot CD has to be in the drive before the BIOS looks
+        let mut boot_cd = None;
+        ioctl_config.boot_device = match config.storage.boot_order() {
+            BootDevice::Default => boot_device::DEFAULT,
+            BootDevice::HardDisk => boot_device::HARD_DISK,
+            BootDevice::Floppy => boot_device::FLOPPY,
+            BootDevice::Cdrom => match boot_cd_path(&config.storage) {
+                Ok(path) => {
+                    boot_cd = Some(path);
+                    boot_device::CDROM
+                }
+                Err(problem) => {
+                    tracing::warn!("Not booting from CD-ROM: {}", problem);
+                    boot_device::DEFAULT
+                }
+            },
+        };
+
         // Start the session
         let handle_ref = self.handle.borrow();
         if let Some(handle) = handle_ref.as_ref() {
+            if let Some(path) = &boot_cd {
+                if let Err(e) = handle.mount_cdrom(&path.to_string_lossy()) {
+                    tracing::warn!("Not booting from CD-ROM: {}", e);
+                    ioctl_config.boot_device = boot_device::DEFAULT;
+                    boot_cd = None;
+                }
+            }
+            // Media set to mount at start, and an ISO given with --iso, are
+            // in the drives from the start
+            let media = mount_startup_media(handle, &config.storage, boot_cd.is_none());
+            problems.extend(media.problems.iter().cloned());
             match handle.start_session(&ioctl_config) {
                 Ok(()) => {
                     // Get initial framebuffer info
@@ -224,11 +781,35 @@ impl qobject::SessionController {
                     } else {
                         drop(handle_ref);
                     }
+                    self.claim();
+                    let boot_cdrom = boot_cd.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default();
+                    self.as_mut().set_boot_cdrom(QString::from(&boot_cdrom));
+                    let [floppy_a, floppy_b] = media.floppies.map(|path| QString::from(&display_path(path.as_deref())));
+                    self.as_mut().set_startup_cdrom(QString::from(&display_path(media.cdrom.as_deref())));
+                    self.as_mut().set_startup_floppy_a(floppy_a);
+                    self.as_mut().set_startup_floppy_b(floppy_b);
+                    self.as_mut().set_startup_problems(QString::from(&problems.join("\n")));
+                    // A resumed guest is past its boot menus
+                    let boot_macro = if resume { String::new() } else { config.keyboard.boot_macro.clone() };
+                    self.as_mut().set_boot_macro(QString::from(&boot_macro));
+                    self.as_mut().set_boot_macro_delay(i32::try_from(config.keyboard.boot_macro_delay_ms).unwrap_or(i32::MAX));
+                    self.session_flags.set(Some(session_flags));
+                    *self.session_storage.borrow_mut() = Some(config.storage.clone());
+                    *self.watchdog.borrow_mut() = Some(Watchdog::new(&config.general));
+                    if config.remote.advertise {
+                        self.advertise(&config);
+                    }
                     self.as_mut().set_session_running(true);
-                    self.set_session_starting(false);
+                    session_gate::set_running(true);
+                    self.as_mut().set_session_starting(false);
+                    if resume {
+                        self.start_restore();
+                    }
                 }
                 Err(e) => {
                     drop(handle_ref);
+                    self.media_locks.borrow_mut().clear();
+                    ephemeral::discard();
                     self.as_mut().set_session_error(true);
                     self.as_mut().set_error_message(QString::from(&format!("Failed to start session: {}", e)));
                     self.set_session_starting(false);
@@ -236,6 +817,8 @@ impl qobject::SessionController {
             }
         } else {
             drop(handle_ref);
+            self.media_locks.borrow_mut().clear();
+            ephemeral::discard();
             self.as_mut().set_session_error(true);
             self.as_mut().set_error_message(QString::from("Driver handle not available"));
             self.set_session_starting(false);
@@ -249,8 +832,7 @@ impl qobject::SessionController {
             match handle.stop_session() {
                 Ok(()) => {
                     drop(handle_ref);
-                    self.as_mut().set_session_running(false);
-                    *self.framebuffer.borrow_mut() = None;
+                    self.as_mut().clear_session();
                 }
                 Err(e) => {
                     drop(handle_ref);
@@ -261,6 +843,490 @@ impl qobject::SessionController {
         }
     }
 
+    /// Ask the guest OS to shut down cleanly
+    ///
+    /// A guest that cannot be asked (no driver support) is stopped at once.
+    pub fn request_guest_shutdown(mut self: Pin<&mut Self>) {
+        if !*self.as_ref().session_running() || *self.as_ref().shutting_down() {
+            return;
+        }
+        let result = match self.handle.borrow().as_ref() {
+            Some(handle) => handle.request_shutdown(),
+            None => return,
+        };
+        if let Err(e) = result {
+            tracing::warn!("Cannot ask the guest to shut down ({}); stopping the session", e);
+            self.stop_session();
+            return;
+        }
+
+        let timeout = load_config().unwrap_or_default().general.shutdown_timeout_secs;
+        tracing::info!("Asked the guest to shut down (waiting {} s)", timeout);
+        self.shutdown_deadline
+            .set(Some(Instant::now() + Duration::from_secs(u64::from(timeout))));
+        self.set_shutting_down(true);
+    }
+
+    /// Stop the session if the guest has not shut down in time
+    pub fn check_shutdown(self: Pin<&mut Self>) {
+        let Some(deadline) = self.shutdown_deadline.get() else {
+            return;
+        };
+        if Instant::now() >= deadline {
+            tracing::warn!("Guest did not shut down in time; stopping the session");
+            self.stop_session();
+        }
+    }
+
+    /// The guest powered itself off, asked to or not
+    pub fn guest_powered_off(self: Pin<&mut Self>) {
+        if !*self.as_ref().session_running() {
+            return;
+        }
+        tracing::info!("Guest powered off; stopping the session");
+        self.stop_session();
+    }
+
+    /// Suspend the guest to the saved state file, then stop the session
+    pub fn save_state_and_stop(mut self: Pin<&mut Self>) {
+        if !*self.as_ref().session_running() || self.state_task.borrow().is_some() {
+            return;
+        }
+        let disks = self
+            .session_storage
+            .borrow()
+            .as_ref()
+            .map(saved_state::session_disks)
+            .unwrap_or_default();
+        let task = self.tasks.spawn("Save guest state", move |progress| {
+            let handle = DriverHandle::open()?;
+            saved_state::save_state(&handle, &disks, &saved_state::state_file(), progress)
+        });
+        tracing::info!("Saving the guest state");
+        *self.state_task.borrow_mut() = Some(task);
+        self.as_mut().set_state_percent(0);
+        self.set_saving_state(true);
+    }
+
+    /// Write the saved state to a session started to resume it
+    fn start_restore(mut self: Pin<&mut Self>) {
+        let task = self.tasks.spawn("Restore guest state", |progress| {
+            let handle = DriverHandle::open()?;
+            let path = saved_state::state_file();
+            let result = saved_state::restore_state(&handle, &path, progress);
+            // Resumed or not, the saved state is used up
+            let _ = std::fs::remove_file(&path);
+            result
+        });
+        tracing::info!("Resuming the saved guest state");
+        *self.state_task.borrow_mut() = Some(task);
+        self.as_mut().set_state_percent(0);
+        self.set_restoring_state(true);
+    }
+
+    /// Stop saving or restoring the guest state
+    pub fn cancel_state_task(&self) {
+        if let Some(task) = self.state_task.borrow().as_ref() {
+            task.cancel();
+        }
+    }
+
+    /// Update state_step/state_percent and finish a save or restore
+    pub fn poll_state_task(mut self: Pin<&mut Self>) {
+        let Some(task) = self.state_task.borrow().clone() else {
+            return;
+        };
+        let progress = task.progress();
+        self.as_mut().set_state_step(QString::from(&progress.step));
+        self.as_mut().set_state_percent(progress.percent() as i32);
+
+        let status = task.status();
+        if !status.is_finished() {
+            return;
+        }
+        self.state_task.borrow_mut().take();
+        self.tasks.take_finished();
+        let message = match status {
+            TaskStatus::Completed => String::new(),
+            TaskStatus::Failed(e) => e,
+            _ => "Cancelled".to_string(),
+        };
+        let success = message.is_empty();
+        let running = *self.as_ref().session_running();
+
+        if *self.as_ref().saving_state() {
+            self.as_mut().set_saving_state(false);
+            if success {
+                tracing::info!("Guest state saved; stopping the session");
+                self.as_mut().stop_session();
+            } else {
+                tracing::warn!("Failed to save the guest state: {}", message);
+            }
+            self.state_saved(success, QString::from(&message));
+        } else {
+            self.as_mut().set_restoring_state(false);
+            if success {
+                tracing::info!("Guest resumed from the saved state");
+            } else {
+                tracing::warn!("Failed to resume the saved guest state: {}", message);
+                if running {
+                    self.as_mut().stop_session();
+                }
+            }
+            self.state_restored(success, QString::from(&message));
+        }
+    }
+
+    /// Forget the state of a session that has stopped
+    fn clear_session(mut self: Pin<&mut Self>) {
+        self.as_mut().set_session_running(false);
+        session_gate::set_running(false);
+        *self.framebuffer.borrow_mut() = None;
+        self.media_locks.borrow_mut().clear();
+        ephemeral::discard();
+        self.watchdog.borrow_mut().take();
+        self.session_flags.set(None);
+        *self.session_storage.borrow_mut() = None;
+        self.as_mut().set_boot_cdrom(QString::default());
+        self.as_mut().set_startup_cdrom(QString::default());
+        self.as_mut().set_startup_floppy_a(QString::default());
+        self.as_mut().set_startup_floppy_b(QString::default());
+        self.as_mut().set_startup_problems(QString::default());
+        self.as_mut().set_boot_macro(QString::default());
+        self.shutdown_deadline.set(None);
+        self.as_mut().set_shutting_down(false);
+        // Dropping the advertiser sends the goodbye
+        self.advertiser.borrow_mut().take();
+    }
+
+    /// Follow a session started or stopped outside this frontend
+    ///
+    /// The frontend's own start and stop are reported back as well; by then
+    /// the properties already agree, so they change nothing.
+    pub fn apply_session_state(mut self: Pin<&mut Self>, state: i32) {
+        let running = *self.as_ref().session_running();
+        let foreign = *self.as_ref().session_foreign();
+        if state == SessionState::Stopped as i32 {
+            if running {
+                tracing::warn!("Session was stopped outside this frontend");
+                self.as_mut().clear_session();
+            }
+            if foreign {
+                self.as_mut().set_session_foreign(false);
+            }
+        } else if state == SessionState::Running as i32 && !running && !foreign {
+            tracing::info!("Session was started by another frontend");
+            self.as_mut().set_session_foreign(true);
+        }
+        // Polling resumes only once our own session is back to Running,
+        // e.g. after a reset
+        session_gate::set_running(
+            *self.as_ref().session_running() && state == SessionState::Running as i32,
+        );
+    }
+
+    /// Start the control API server if a port is configured, and take
+    /// the local one of this card's frontend
+    pub fn start_control(mut self: Pin<&mut Self>) {
+        if let Some(server) = instance::take_server() {
+            *self.local_control.borrow_mut() = Some(server);
+            self.as_mut().set_control_listening(true);
+        }
+        if self.control.borrow().is_some() {
+            return;
+        }
+        let config = load_config().unwrap_or_default();
+        let Some(addr) = config.remote.control_addr() else {
+            return;
+        };
+        match ControlServer::start(addr, config.remote.token()) {
+            Ok(server) => {
+                tracing::info!("Control API listening on {}:{}", addr.ip(), server.port());
+                *self.control.borrow_mut() = Some(server);
+                self.as_mut().set_control_listening(true);
+            }
+            Err(e) => tracing::warn!("Cannot listen for control commands on {}: {}", addr, e),
+        }
+    }
+
+    /// Handle CLI commands received since the last poll
+    pub fn poll_control(mut self: Pin<&mut Self>) {
+        loop {
+            let next = [&self.local_control, &self.control]
+                .into_iter()
+                .find_map(|server| server.borrow().as_ref().and_then(|c| c.try_next()));
+            let Some(pending) = next else {
+                break;
+            };
+            let request = pending.request.clone();
+            tracing::info!("Control command: {} {}", request.command, request.args.join(" "));
+
+            let response = match request.command.as_str() {
+                command::STATUS => Some(self.control_status()),
+                command::START => {
+                    if self.session_running {
+                        Some(ControlResponse::error("session is already running"))
+                    } else {
+                        self.as_mut().start_session();
+                        Some(self.session_result("Session started"))
+                    }
+                }
+                command::STOP => {
+                    self.as_mut().set_session_error(false);
+                    self.as_mut().stop_session();
+                    Some(self.session_result("Session stopped"))
+                }
+                command::RESET => {
+                    self.as_mut().set_session_error(false);
+                    self.as_mut().reset_session();
+                    Some(self.session_result("Guest reset"))
+                }
+                command::MOUNT_ISO | command::EJECT | command::RELEASE | command::SHOW => None,
+                other => Some(ControlResponse::error(format!("unknown command {}", other))),
+            };
+
+            match response {
+                Some(response) => pending.reply(response),
+                None => {
+                    // Disk commands belong to DiskManager; QML answers with control_reply
+                    let id = self.next_control_id.get();
+                    self.next_control_id.set(id.wrapping_add(1));
+                    self.control_pending.borrow_mut().insert(id, pending);
+                    self.as_mut().control_request(
+                        id,
+                        QString::from(&request.command),
+                        QString::from(request.arg()),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Answer a command forwarded with control_request
+    pub fn control_reply(&self, id: i32, ok: bool, message: QString) {
+        let Some(pending) = self.control_pending.borrow_mut().remove(&id) else {
+            return;
+        };
+        let message = message.to_string();
+        pending.reply(if ok { ControlResponse::ok(message) } else { ControlResponse::error(message) });
+    }
+
+    /// Internal: session state for the status command
+    fn control_status(&self) -> ControlResponse {
+        let state = if self.session_running {
+            format!(
+                "Session running, {}x{} {}",
+                self.display_width,
+                self.display_height,
+                if self.text_mode { "text mode" } else { "graphics mode" }
+            )
+        } else {
+            "Session stopped".to_string()
+        };
+        ControlResponse::ok(state)
+    }
+
+    /// Internal: result of a session command from the error properties
+    fn session_result(&self, success: &str) -> ControlResponse {
+        if self.session_error {
+            ControlResponse::error(self.error_message.to_string())
+        } else {
+            ControlResponse::ok(success)
+        }
+    }
+
+    /// Internal: announce the session on the LAN
+    ///
+    /// Discovery is a convenience, so failing to bind the mDNS port only
+    /// logs a warning.
+    fn advertise(&self, config: &AppConfig) {
+        let info = ServiceInfo::for_session(&config.remote);
+        let profile = info.profile().to_string();
+        match Advertiser::start(info) {
+            Ok(advertiser) => {
+                tracing::info!("Advertising session {:?} with mDNS", profile);
+                *self.advertiser.borrow_mut() = Some(advertiser);
+            }
+            Err(e) => tracing::warn!("Cannot advertise session with mDNS: {}", e),
+        }
+    }
+
+    /// Why the configured boot CD cannot be booted
+    pub fn get_boot_cd_problem(&self) -> QString {
+        let storage = load_card_config().unwrap_or_default().storage;
+        if storage.boot_order() != BootDevice::Cdrom {
+            return QString::default();
+        }
+        match boot_cd_path(&storage) {
+            Ok(_) => QString::default(),
+            Err(problem) => QString::from(&problem),
+        }
+    }
+
+    /// Take over a session left running or driven by another frontend
+    ///
+    /// A live owner is asked to release the session through its control
+    /// port; see `rising_sun_common::handoff`.
+    pub fn adopt_session(mut self: Pin<&mut Self>) -> bool {
+        self.as_mut().set_session_error(false);
+        self.as_mut().set_error_message(QString::default());
+
+        match self.try_adopt() {
+            Ok(state) => {
+                tracing::info!("Adopted session from frontend {}", state.frontend_version);
+                self.as_mut().set_session_foreign(false);
+                self.as_mut().set_session_running(true);
+                session_gate::set_running(true);
+                self.as_mut().session_adopted(
+                    QString::from(state.cdrom.as_deref().unwrap_or("")),
+                    state.cdrom_served,
+                );
+                true
+            }
+            Err(e) => {
+                tracing::warn!("Cannot adopt session: {}", e);
+                if matches!(e, HandoffError::NotRunning) {
+                    self.as_mut().set_session_foreign(false);
+                }
+                self.as_mut().set_session_error(true);
+                self.set_error_message(QString::from(&format!("Cannot take over session: {}", e)));
+                false
+            }
+        }
+    }
+
+    /// Internal: everything in adopting a session short of updating properties
+    fn try_adopt(&self) -> Result<HandoffState, HandoffError> {
+        let driver_error = |e: anyhow::Error| HandoffError::Driver(e.to_string());
+        if self.handle.borrow().is_none() {
+            let handle = DriverHandle::open().map_err(driver_error)?;
+            *self.handle.borrow_mut() = Some(handle);
+        }
+        let handle_ref = self.handle.borrow();
+        let handle = handle_ref.as_ref().expect("handle opened above");
+
+        let status = handle.get_status().map_err(driver_error)?;
+        if status.state != SessionState::Running as u32 {
+            return Err(HandoffError::NotRunning);
+        }
+        let version = handle.get_version().map_err(driver_error)?;
+        let owner = handle.get_owner().map_err(driver_error)?;
+        let config = load_card_config().unwrap_or_default();
+        session_gate::set_suspend(config.general.suspend_polling_when_stopped);
+
+        let state = if owner.is_owned() {
+            if owner.control_port == 0 {
+                return Err(HandoffError::NoControlPort(owner.pid));
+            }
+            // The owner shares this host's remote settings
+            let remote = load_config().unwrap_or_default().remote;
+            let host = match remote.control_address {
+                Some(ip) if !ip.is_unspecified() => ip.to_string(),
+                _ => "127.0.0.1".to_string(),
+            };
+            let mut client = ControlClient::connect(&host, Some(owner.control_port))
+                .map_err(|e| HandoffError::Refused(e.to_string()))?
+                .with_token(remote.token());
+            let response = client
+                .call(&ControlRequest::new(command::RELEASE, &[]))
+                .map_err(|e| HandoffError::Refused(e.to_string()))?;
+            if !response.ok {
+                return Err(HandoffError::Refused(response.message));
+            }
+            HandoffState::from_json(&response.message)?
+        } else {
+            HandoffState::orphaned(&version, &config)
+        };
+
+        for warning in handoff::check_adoption(&state, &version, &config)? {
+            tracing::warn!("Adopting session: {}", warning);
+        }
+
+        // Removable media that cannot be reached from here is dropped
+        let mut storage = state.storage.clone();
+        for issue in media_check::check_media(&storage).issues {
+            issue.slot.remove(&mut storage);
+        }
+        let locks = media_check::lock_media(&storage).map_err(|issue| HandoffError::Media(issue.to_string()))?;
+        let port = self.control.borrow().as_ref().map_or(0, |c| c.port());
+        handle.claim_session(port, false).map_err(driver_error)?;
+
+        // The framebuffer belonged to the old owner's mapping; map it here
+        if let (Ok(display), Ok(fb)) = (handle.get_display(), handle.get_framebuffer()) {
+            update_framebuffer_state(
+                handle.as_raw_fd(),
+                display.width,
+                display.height,
+                fb.stride,
+                fb.format,
+                fb.size() as usize,
+            );
+            *self.framebuffer.borrow_mut() = Some(fb);
+        }
+        drop(handle_ref);
+
+        *self.media_locks.borrow_mut() = locks;
+        self.session_flags.set(state.session_flags);
+        *self.session_storage.borrow_mut() = Some(storage);
+        if config.remote.advertise {
+            self.advertise(&config);
+        }
+        Ok(state)
+    }
+
+    /// Stop driving the session so another frontend can adopt it
+    ///
+    /// The guest keeps running. QML passes what is in the CD-ROM drive and
+    /// stops serving it once released, for the adopter to serve instead.
+    pub fn release_session(mut self: Pin<&mut Self>, cdrom: QString, cdrom_served: bool) -> QString {
+        if !self.session_running {
+            self.as_mut().set_error_message(QString::from("No session to release"));
+            return QString::default();
+        }
+        let handle_ref = self.handle.borrow();
+        let Some(handle) = handle_ref.as_ref() else {
+            return QString::default();
+        };
+        let version = handle.get_version().unwrap_or_default();
+        if let Err(e) = handle.release_session() {
+            drop(handle_ref);
+            let message = format!("Failed to release session: {}", e);
+            tracing::warn!("{}", message);
+            self.as_mut().set_error_message(QString::from(&message));
+            return QString::default();
+        }
+        drop(handle_ref);
+
+        let config = load_card_config().unwrap_or_default();
+        let storage = self.session_storage.borrow_mut().take().unwrap_or(config.storage);
+        let mut state = HandoffState::new(&version, self.session_flags.take(), storage);
+        let cdrom = cdrom.to_string();
+        state.cdrom = (!cdrom.is_empty()).then_some(cdrom);
+        state.cdrom_served = cdrom_served;
+        state.profile_name = config.remote.profile_name;
+
+        // Unlock the media and stop advertising before the adopter looks
+        self.media_locks.borrow_mut().clear();
+        self.advertiser.borrow_mut().take();
+        *self.framebuffer.borrow_mut() = None;
+        clear_framebuffer_state();
+        self.as_mut().set_session_running(false);
+        session_gate::set_running(false);
+        self.as_mut().set_session_foreign(true);
+        tracing::info!("Released session for another frontend");
+        QString::from(&state.to_json())
+    }
+
+    /// Internal: become the session's owner, publishing the control port
+    fn claim(&self) {
+        let port = self.control.borrow().as_ref().map_or(0, |c| c.port());
+        if let Some(handle) = self.handle.borrow().as_ref() {
+            if let Err(e) = handle.claim_session(port, false) {
+                tracing::warn!("Cannot claim session: {}", e);
+            }
+        }
+    }
+
     /// Reset the session (warm reboot)
     pub fn reset_session(mut self: Pin<&mut Self>) {
         let handle_ref = self.handle.borrow();
@@ -273,6 +1339,69 @@ impl qobject::SessionController {
         }
     }
 
+    /// Check the running session for a crashed or hung guest
+    pub fn check_watchdog(mut self: Pin<&mut Self>) {
+        let status = match self.handle.borrow().as_ref().map(|h| h.get_status()) {
+            Some(Ok(status)) => status,
+            _ => return,
+        };
+        let verdict = match self.watchdog.borrow_mut().as_mut() {
+            Some(watchdog) => watchdog.check(&status, Instant::now()),
+            None => return,
+        };
+        let Some((incident, action)) = verdict else {
+            return;
+        };
+
+        tracing::warn!("Guest {} ({})", incident.name(), action.name());
+        self.as_mut()
+            .guest_incident(QString::from(incident.name()), QString::from(action.name()));
+
+        if action != watchdog::Action::Restart {
+            return;
+        }
+        // A crashed session cannot be reset, only started again; the
+        // watchdog (and its restart count) carries over
+        let watchdog = self.watchdog.borrow_mut().take();
+        if status.state == SessionState::Error as u32 {
+            self.as_mut().stop_session();
+            self.as_mut().start_session();
+        } else {
+            self.as_mut().reset_session();
+        }
+        if *self.as_ref().session_running()
+            && let Some(mut watchdog) = watchdog
+        {
+            watchdog.clear();
+            *self.watchdog.borrow_mut() = Some(watchdog);
+        }
+    }
+
+    /// Run the crash command on an incident's screenshot
+    pub fn run_crash_command(&self, incident: QString, screenshot: QString) -> bool {
+        let command = load_config().unwrap_or_default().general.crash_command;
+        if command.trim().is_empty() {
+            tracing::warn!("No crash command is configured");
+            return false;
+        }
+        let incident = if incident.to_string() == Incident::Hung.name() {
+            Incident::Hung
+        } else {
+            Incident::Crashed
+        };
+        let screenshot = screenshot.to_string();
+        match watchdog::run_hook(&command, incident, Path::new(&screenshot)) {
+            Ok(()) => {
+                tracing::info!("Ran crash command for {}", screenshot);
+                true
+            }
+            Err(e) => {
+                tracing::error!("Failed to run crash command {:?}: {}", command, e);
+                false
+            }
+        }
+    }
+
     /// Get the driver file descriptor for mmap operations
     pub fn get_driver_fd(&self) -> i32 {
         self.handle
@@ -290,7 +1419,13 @@ impl qobject::SessionController {
                 let width = info.width as i32;
                 let height = info.height as i32;
                 let depth = info.color_depth as i32;
-                let text = info.mode == 0;
+                let text = info.is_text();
+                set_field_state(info.is_interlaced(), info.is_odd_field());
+                if text {
+                    set_text_layout(info.text_cols, info.text_rows);
+                } else {
+                    set_text_layout(0, 0);
+                }
                 
                 // Update framebuffer info
                 if let Ok(fb) = handle.get_framebuffer() {
@@ -300,6 +1435,10 @@ impl qobject::SessionController {
                     drop(handle_ref);
                 }
                 
+                if self.dump_remaining.get() > 0 {
+                    self.dump_next_frame(width as u32, height as u32);
+                }
+
                 self.as_mut().set_display_width(width);
                 self.as_mut().set_display_height(height);
                 self.as_mut().set_color_depth(depth);
@@ -308,6 +1447,81 @@ impl qobject::SessionController {
         }
     }
 
+    /// Pick up a palette change reported by the driver
+    pub fn palette_changed(&self, generation: i32) {
+        invalidate_palette(generation as u32);
+    }
+
+    /// Pick up changed display settings in a running session
+    pub fn reload_display_settings(&self) {
+        let display = load_card_config().unwrap_or_default().display;
+        set_deinterlace_mode(display.deinterlace);
+        set_palette_sync(display.palette_sync);
+    }
+
+    /// Arm a dump of the next `count` frames
+    pub fn dump_frames(self: Pin<&mut Self>, count: i32, directory: QString) -> QString {
+        if count <= 0 {
+            self.dump_remaining.set(0);
+            *self.dump_dir.borrow_mut() = None;
+            return QString::default();
+        }
+
+        let dir = if directory.is_empty() {
+            let stamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            AppConfig::data_dir().join("frame-dumps").join(stamp.to_string())
+        } else {
+            PathBuf::from(directory.to_string())
+        };
+
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            tracing::error!("Failed to create frame dump directory {}: {}", dir.display(), e);
+            return QString::default();
+        }
+
+        tracing::info!("Dumping next {} frames to {}", count, dir.display());
+        let dir_str = dir.to_string_lossy().to_string();
+        *self.dump_dir.borrow_mut() = Some(dir);
+        self.dump_remaining.set(count as u32);
+        self.dump_index.set(0);
+        QString::from(&dir_str)
+    }
+
+    /// Write one frame of an armed dump
+    fn dump_next_frame(&self, width: u32, height: u32) {
+        let Some(fb) = *self.framebuffer.borrow() else {
+            return;
+        };
+        let Some(dir) = self.dump_dir.borrow().clone() else {
+            return;
+        };
+
+        let info = FrameDumpInfo {
+            width,
+            height,
+            stride: fb.stride,
+            format: fb.format,
+        };
+        let index = self.dump_index.get();
+
+        if let Err(e) = dump_raw_frame(self.get_driver_fd(), &info, fb.size() as usize, &dir, index) {
+            tracing::error!("Frame dump failed at frame {}: {}", index, e);
+            self.dump_remaining.set(0);
+            return;
+        }
+
+        self.dump_index.set(index + 1);
+        let remaining = self.dump_remaining.get() - 1;
+        self.dump_remaining.set(remaining);
+        if remaining == 0 {
+            tracing::info!("Frame dump complete: {} frames in {}", index + 1, dir.display());
+            *self.dump_dir.borrow_mut() = None;
+        }
+    }
+
     /// Get framebuffer stride
     pub fn get_framebuffer_stride(&self) -> i32 {
         self.framebuffer
@@ -332,3 +1546,210 @@ impl qobject::SessionController {
             .unwrap_or(0)
     }
 }
+
+/// A card as list_cards describes it
+#[derive(Serialize)]
+struct CardInfo {
+    index: u32,
+    name: String,
+    path: String,
+}
+
+/// What opening the selected card found
+enum DriverProbe {
+    /// No device node for the card
+    NotLoaded,
+    /// The device node is there but could not be opened
+    Failed(anyhow::Error),
+    Opened {
+        handle: DriverHandle,
+        /// Driver version, e.g. "1.0.0"
+        version: Option<String>,
+        /// Whether a session is running; it may outlive the frontend
+        /// that started it
+        running: bool,
+        owner: anyhow::Result<SessionOwner>,
+    },
+}
+
+impl DriverProbe {
+    /// Open the selected card and ask it about its session
+    ///
+    /// Can take a while on a card whose firmware is still busy, so at
+    /// startup this runs off the UI thread (see probe_driver).
+    fn run() -> Self {
+        if !is_driver_loaded() {
+            return Self::NotLoaded;
+        }
+        let handle = match DriverHandle::open() {
+            Ok(handle) => handle,
+            Err(e) => return Self::Failed(e),
+        };
+        let version = handle
+            .get_version()
+            .ok()
+            .map(|v| format!("{}.{}.{}", v.major, v.minor, v.patch));
+        let running = handle
+            .get_status()
+            .is_ok_and(|status| status.state == SessionState::Running as u32);
+        let owner = handle.get_owner();
+        Self::Opened { handle, version, running, owner }
+    }
+}
+
+/// Point template disks at their overlay child, creating it the first
+/// time; the child is remembered in the saved configuration
+fn instantiate_templates(storage: &mut StorageConfig) -> Result<(), String> {
+    let mut saved = load_config().unwrap_or_default();
+    let saved_storage = saved.card_storage_mut(driver::selected_card());
+    let mut changed = false;
+    let slots = [
+        (&mut storage.primary_disk, &mut saved_storage.primary_disk),
+        (&mut storage.secondary_disk, &mut saved_storage.secondary_disk),
+    ];
+    for (disk, saved_disk) in slots {
+        let Some(disk) = disk.as_mut() else {
+            continue;
+        };
+        if !overlay::is_template(&disk.path) {
+            continue;
+        }
+        let child = overlay::instantiate(&disk.path, disk.overlay.as_deref(), &overlay::overlays_dir())
+            .map_err(|e| format!("cannot create an overlay of template {}: {}", disk.path.display(), e))?;
+        if disk.overlay.as_ref() != Some(&child) {
+            tracing::info!("Created overlay {} of template {}", child.display(), disk.path.display());
+            if let Some(saved_disk) = saved_disk.as_mut().filter(|d| d.path == disk.path) {
+                saved_disk.overlay = Some(child.clone());
+                changed = true;
+            }
+        }
+        disk.overlay = Some(child.clone());
+        disk.path = child;
+    }
+    if changed && let Err(e) = save_config(&saved) {
+        tracing::warn!("Failed to remember the overlay: {}", e);
+    }
+    Ok(())
+}
+
+/// Register the session actions (see actions)
+fn register_actions() {
+    let running = session_gate::is_running;
+    actions::register(
+        Action::new("start_session", "Start", "Machine")
+            .shortcut("Ctrl+R")
+            .enabled_when(move || !running()),
+    );
+    actions::register(
+        Action::new("reset_session", "Reset", "Machine")
+            .shortcut("Ctrl+Shift+R")
+            .enabled_when(running),
+    );
+    actions::register(Action::new("shut_down", "Shut Down", "Machine").enabled_when(running));
+    actions::register(Action::new("save_state", "Save State and Stop", "Machine").enabled_when(running));
+    actions::register(Action::new("stop_session", "Stop", "Machine").enabled_when(running));
+}
+
+/// Whether the saved state can be resumed with the disks in `storage`;
+/// a saved state that cannot is deleted
+fn resumable(storage: &StorageConfig) -> bool {
+    let path = saved_state::state_file();
+    if !path.exists() {
+        return false;
+    }
+    let problem = match saved_state::read_header(&path) {
+        Ok(state) if state.matches(&saved_state::session_disks(storage)) => return true,
+        Ok(_) => "the disks changed since it was saved".to_string(),
+        Err(e) => e.to_string(),
+    };
+    tracing::warn!("Discarding the saved guest state: {}", problem);
+    let _ = std::fs::remove_file(&path);
+    false
+}
+
+/// Mount the hard disks through throwaway overlays if changes this run
+/// are not kept
+fn ephemeral_disks(storage: &mut StorageConfig) -> anyhow::Result<()> {
+    for disk in [&mut storage.primary_disk, &mut storage.secondary_disk].into_iter().flatten() {
+        disk.path = ephemeral::writable_image(&disk.path)?;
+    }
+    Ok(())
+}
+
+/// The remembered ISO to boot from, if the BIOS can boot it
+///
+/// Only ISOs the driver mounts itself can be booted; the BIOS cannot reach
+/// a CUE image or host drive served through pass-through.
+/// Removable media mounted at session start
+#[derive(Debug, Default)]
+struct StartupMedia {
+    /// ISO in the CD-ROM drive; a CUE sheet is not mounted, as it has to be
+    /// served by the disk manager
+    cdrom: Option<PathBuf>,
+    /// Images in floppy drives A: and B:
+    floppies: [Option<PathBuf>; 2],
+    /// What could not be mounted
+    problems: Vec<String>,
+}
+
+/// Mount the media `storage` has set to auto_mount (and an ISO given with
+/// --iso), before the session starts; the CD-ROM only if `cdrom` is free
+fn mount_startup_media(handle: &DriverHandle, storage: &StorageConfig, cdrom: bool) -> StartupMedia {
+    let mut media = StartupMedia::default();
+
+    let iso = storage
+        .cdrom
+        .mounted_iso
+        .as_ref()
+        .filter(|_| cdrom && (storage.cdrom.auto_mount || run_options::get().iso.is_some()));
+    if let Some(path) = iso {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cue")) {
+            media.cdrom = Some(path.clone());
+        } else {
+            match handle.mount_cdrom(&path.to_string_lossy()) {
+                Ok(()) => {
+                    tracing::info!("Mounted {} at session start", path.display());
+                    media.cdrom = Some(path.clone());
+                }
+                Err(e) => media.problems.push(format!("Cannot mount {}: {}", path.display(), e)),
+            }
+        }
+    }
+
+    for (drive, floppy) in [&storage.floppy_a, &storage.floppy_b].into_iter().enumerate() {
+        let Some(path) = floppy.mounted_image.as_ref().filter(|_| floppy.auto_mount) else {
+            continue;
+        };
+        let letter = if drive == 0 { "A:" } else { "B:" };
+        // Writes go to a throwaway overlay when changes are not kept
+        let result = ephemeral::writable_image(path)
+            .map_err(|e| format!("{:#}", e))
+            .and_then(|image| handle.mount_floppy(drive as u32, &image.to_string_lossy()).map_err(|e| e.to_string()));
+        match result {
+            Ok(()) => {
+                tracing::info!("Mounted {} as {} at session start", path.display(), letter);
+                media.floppies[drive] = Some(path.clone());
+            }
+            Err(e) => media.problems.push(format!("Cannot mount {} as {}: {}", path.display(), letter, e)),
+        }
+    }
+    media
+}
+
+/// A path for QML, empty for none
+fn display_path(path: Option<&Path>) -> String {
+    path.map(|p| p.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+fn boot_cd_path(storage: &StorageConfig) -> Result<PathBuf, String> {
+    let path = storage
+        .cdrom
+        .mounted_iso
+        .clone()
+        .ok_or("no CD image is mounted")?;
+    if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("cue")) {
+        return Err(format!("{} is a CUE sheet, which the BIOS cannot boot", path.display()));
+    }
+    el_torito::check_bootable(&path)?;
+    Ok(path)
+}
@@ -0,0 +1,37 @@
+//! Gate suspending background polling while no session is running.
+//!
+//! The audio, network and clipboard controllers poll their ioctls on
+//! timers and worker threads. With no session those calls only fail or
+//! return nothing, so they check the gate first. SessionController opens
+//! it when the driver reports the session Running and closes it on any
+//! other state.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether the session this frontend drives is running
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+/// Whether polling is suspended while the gate is closed
+/// (`general.suspend_polling_when_stopped`)
+static SUSPEND: AtomicBool = AtomicBool::new(true);
+
+/// Open the gate while the session this frontend drives is Running
+pub fn set_running(active: bool) {
+    if ACTIVE.swap(active, Ordering::SeqCst) != active {
+        tracing::debug!("Polling {}", if active { "resumed" } else { "suspended" });
+    }
+}
+
+/// Choose whether polling stops while the session is not running
+pub fn set_suspend(suspend: bool) {
+    SUSPEND.store(suspend, Ordering::SeqCst);
+}
+
+/// Whether the session this frontend drives is Running
+pub fn is_running() -> bool {
+    ACTIVE.load(Ordering::SeqCst)
+}
+
+/// Whether controllers should poll the driver
+pub fn is_open() -> bool {
+    ACTIVE.load(Ordering::SeqCst) || !SUSPEND.load(Ordering::SeqCst)
+}
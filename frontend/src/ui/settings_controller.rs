@@ -70,7 +70,7 @@ impl qobject::SettingsController {
 
     /// Apply display presentation settings
     /// Note: Resolution/color depth are set by guest OS (via INT 10h or Windows drivers).
-    /// Scaling and smoothing are handled by QML Image element properties.
+    /// Placement and smoothing come from DisplayView.compute_target_rect.
     pub fn apply_display_settings(&self, scaling_mode: QString, scale_factor: i32, smooth: bool) {
         tracing::info!(
             "Applying display settings: mode={}, scale={}, smooth={}",
@@ -0,0 +1,86 @@
+//! The frontend's startup profile.
+//!
+//! main() marks the phases of the start and the controllers time their
+//! creation with `span`; when the window shows its first frame MainWindow
+//! calls `finish`, which logs a one-line summary and, at debug level (so
+//! it shows in the log console), the per-phase report. Deferred probes
+//! that complete later are added with `record` and logged on their own.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use rising_sun_common::startup::{format_ms, StartupProfile};
+
+static PROFILE: Mutex<Option<StartupProfile>> = Mutex::new(None);
+
+fn with_profile<T>(f: impl FnOnce(&mut StartupProfile) -> T) -> T {
+    let mut profile = PROFILE.lock().unwrap_or_else(|e| e.into_inner());
+    f(profile.get_or_insert_with(StartupProfile::new))
+}
+
+/// Start timing (first thing in main)
+pub fn begin() {
+    with_profile(|_| ());
+}
+
+/// End the current phase, naming it
+pub fn mark(name: &str) {
+    with_profile(|p| p.mark(name));
+}
+
+/// Record work that started at `start` and took `duration`
+pub fn record(name: &str, start: Instant, duration: Duration) {
+    let late = with_profile(|p| {
+        p.record(name, start, duration);
+        p.is_finished()
+    });
+    if late {
+        tracing::debug!("{} took {} (after startup)", name, format_ms(duration));
+    }
+}
+
+/// Times its scope; see `span`
+pub struct SpanGuard {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        record(self.name, self.start, self.start.elapsed());
+    }
+}
+
+/// Time until the returned guard is dropped, e.g. a controller's creation
+pub fn span(name: &'static str) -> SpanGuard {
+    SpanGuard {
+        name,
+        start: Instant::now(),
+    }
+}
+
+/// The first frame is on screen: end the last phase and log the timings
+pub fn finish() {
+    let profile = with_profile(|p| {
+        if p.is_finished() {
+            return None;
+        }
+        p.mark("first frame");
+        p.finish();
+        Some(p.clone())
+    });
+    let Some(profile) = profile else {
+        return;
+    };
+    if profile.over_budget() {
+        let slowest: Vec<String> = profile
+            .slowest(3)
+            .iter()
+            .map(|s| format!("{} {}", s.name, format_ms(s.duration)))
+            .collect();
+        tracing::warn!("{}; slowest: {}", profile.summary(), slowest.join(", "));
+    } else {
+        tracing::info!("{}", profile.summary());
+    }
+    tracing::debug!("{}", profile.report());
+}
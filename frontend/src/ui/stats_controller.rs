@@ -0,0 +1,155 @@
+//! Stats controller Qt bridge for the status bar's live session statistics.
+//!
+//! This module handles:
+//! - Polling SessionStatus from the driver
+//! - Formatting uptime for display
+//! - Exposing the disk activity bitmap to QML
+//!
+//! The driver's SessionStatus no longer carries CPU or memory figures (those
+//! fields are reserved - the guest runs on the card's own CPU and RAM, which
+//! the host cannot observe). `cpu_percent` and `memory_mb` are kept so the
+//! status bar has a stable binding, but they report -1 ("unavailable").
+
+#[cxx_qt::bridge]
+mod qobject {
+    unsafe extern "C++Qt" {
+        include!("cxx-qt-lib/qstring.h");
+        type QString = cxx_qt_lib::QString;
+    }
+
+    unsafe extern "RustQt" {
+        #[qobject]
+        #[qml_element]
+        #[qproperty(i32, driver_fd)]
+        #[qproperty(f64, cpu_percent)]
+        #[qproperty(i32, memory_mb)]
+        #[qproperty(QString, uptime_text)]
+        #[qproperty(i32, disk_activity)]
+        type StatsController = super::StatsControllerRust;
+
+        /// Initialize stats controller with driver file descriptor
+        #[qinvokable]
+        fn init_stats(self: Pin<&mut StatsController>, fd: i32) -> bool;
+
+        /// Poll the driver for updated session statistics
+        #[qinvokable]
+        fn poll_stats(self: Pin<&mut StatsController>);
+
+        /// Reset all statistics (e.g. when the session stops)
+        #[qinvokable]
+        fn reset_stats(self: Pin<&mut StatsController>);
+
+        /// Check whether a drive (bit 0=C, bit 1=D, ...) is currently active
+        #[qinvokable]
+        fn is_drive_active(self: &StatsController, drive: i32) -> bool;
+
+        /// Signal emitted after each successful poll
+        #[qsignal]
+        fn stats_updated(self: Pin<&mut StatsController>);
+    }
+}
+
+use std::pin::Pin;
+use cxx_qt_lib::QString;
+use rising_sun_common::ioctl::{SessionStatus, sunpci_get_status};
+
+/// Rust implementation of the StatsController
+pub struct StatsControllerRust {
+    /// Driver file descriptor
+    driver_fd: i32,
+    /// Guest CPU usage in percent (-1 = unavailable)
+    cpu_percent: f64,
+    /// Guest memory in use in MB (-1 = unavailable)
+    memory_mb: i32,
+    /// Formatted session uptime (e.g. "1:02:03")
+    uptime_text: QString,
+    /// Bitmap of active drives (bit 0=C, bit 1=D, etc.)
+    disk_activity: i32,
+}
+
+impl Default for StatsControllerRust {
+    fn default() -> Self {
+        Self {
+            driver_fd: -1,
+            cpu_percent: -1.0,
+            memory_mb: -1,
+            uptime_text: QString::from("0:00:00"),
+            disk_activity: 0,
+        }
+    }
+}
+
+impl qobject::StatsController {
+    /// Initialize stats controller with driver file descriptor
+    pub fn init_stats(mut self: Pin<&mut Self>, fd: i32) -> bool {
+        if fd < 0 {
+            tracing::warn!("StatsController: invalid driver fd");
+            return false;
+        }
+
+        self.as_mut().set_driver_fd(fd);
+        self.as_mut().poll_stats();
+
+        tracing::info!("StatsController initialized with fd={}", fd);
+        true
+    }
+
+    /// Poll the driver for updated session statistics
+    pub fn poll_stats(mut self: Pin<&mut Self>) {
+        if self.driver_fd < 0 {
+            return;
+        }
+
+        let mut status = SessionStatus::default();
+        let result = unsafe { sunpci_get_status(self.driver_fd, &mut status) };
+
+        match result {
+            Ok(_) => {
+                let uptime = format_uptime(status.uptime_ns());
+                if self.uptime_text.to_string() != uptime {
+                    self.as_mut().set_uptime_text(QString::from(&uptime));
+                }
+                self.as_mut().set_disk_activity(status.disk_activity as i32);
+                self.as_mut().stats_updated();
+            }
+            Err(e) => {
+                tracing::trace!("Failed to poll session stats: {}", e);
+            }
+        }
+    }
+
+    /// Reset all statistics
+    pub fn reset_stats(mut self: Pin<&mut Self>) {
+        self.as_mut().set_uptime_text(QString::from("0:00:00"));
+        self.as_mut().set_disk_activity(0);
+        self.as_mut().stats_updated();
+    }
+
+    /// Check whether a drive is currently active
+    pub fn is_drive_active(&self, drive: i32) -> bool {
+        (0..32).contains(&drive) && (self.disk_activity as u32) & (1 << drive) != 0
+    }
+}
+
+/// Format an uptime in nanoseconds as H:MM:SS (days are folded into hours)
+fn format_uptime(uptime_ns: u64) -> String {
+    let total_secs = uptime_ns / 1_000_000_000;
+    let hours = total_secs / 3600;
+    let minutes = (total_secs % 3600) / 60;
+    let seconds = total_secs % 60;
+    format!("{}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(0), "0:00:00");
+        assert_eq!(format_uptime(999_999_999), "0:00:00");
+        assert_eq!(format_uptime(61 * 1_000_000_000), "0:01:01");
+        assert_eq!(format_uptime(3723 * 1_000_000_000), "1:02:03");
+        assert_eq!(format_uptime(100 * 3600 * 1_000_000_000), "100:00:00");
+    }
+}
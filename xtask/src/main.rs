@@ -0,0 +1,40 @@
+//! Development tasks, run as `cargo xtask <task> [args]`.
+//!
+//! - `abi-check`: compare the ioctl structs in `rising_sun_common::ioctl`
+//!   with the ones bindgen generates from driver/include/uapi/sunpci_ioctl.h,
+//!   field by field. Needs libclang. Further arguments go to `cargo test`,
+//!   so `cargo xtask abi-check --target i686-unknown-linux-gnu` checks the
+//!   32-bit layout.
+
+use std::process::{Command, ExitCode};
+
+use anyhow::{Context, Result, bail};
+
+const USAGE: &str = "Usage: cargo xtask <task> [args]
+
+Tasks:
+  abi-check    Check the ioctl structs against the driver's uapi header";
+
+fn main() -> Result<ExitCode> {
+    let mut args = std::env::args().skip(1);
+    match args.next().as_deref() {
+        Some("abi-check") => abi_check(args.collect()),
+        Some("-h" | "--help") | None => {
+            println!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        Some(task) => bail!("Unknown task `{task}`\n\n{USAGE}"),
+    }
+}
+
+/// Run the ioctl layout test with bindgen's structs built in
+fn abi_check(extra: Vec<String>) -> Result<ExitCode> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(&cargo)
+        .args(["test", "--package", "rising-sun-common", "--features", "abi-check", "--lib"])
+        .args(extra)
+        .args(["--", "ioctl::abi_check"])
+        .status()
+        .with_context(|| format!("Cannot run {cargo}"))?;
+    Ok(if status.success() { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}